@@ -1,147 +1,60 @@
-pub mod cppgc {
-    pub mod internal {
-        // Placeholder for api-constants.h functionality.  Likely needs to be
-        // more complex based on its use in V8, possibly involving feature flags
-        // or constants.
-        // For now just a dummy struct so that we can compile
-        pub struct ApiConstants {}
-    }
-
-    pub mod platform {
-        // Placeholder for platform.h functionality.  This would likely need to
-        // handle OS-specific implementations in a real conversion.
-        // For now just a dummy struct so that we can compile
-        pub struct Platform {}
-    }
-
-    pub mod trace_trait {
-        // This module seems empty, Rust does not need a direct equivalent
-        // to an empty header file.
-    }
-
-    pub mod type_traits {
-        // Placeholder for type-traits.h functionality.  This would likely need to
-        // handle advanced type introspection in a real conversion.
-        // For now just a dummy struct so that we can compile
-        pub struct TypeTraits {}
-    }
-
-    pub struct Visitor {}
-
-    impl Visitor {
-        pub fn trace<T>(&mut self, _object: &T) {
-            // Placeholder for tracing logic. In a real implementation,
-            // this would need to handle different types and recursively
-            // trace their fields.
-        }
-    }
-
-    pub trait GarbageCollectedBase {
-        fn trace(&self, visitor: &mut Visitor);
-    }
-
-    pub struct FinalType {}
-
-    impl FinalType {
-        pub fn new() -> Self {
-            FinalType {}
-        }
-    }
-
-    impl GarbageCollectedBase for FinalType {
-        fn trace(&self, visitor: &mut Visitor) {
-            // Dispatch using visitor->Trace(...);
-            visitor.trace(self);
-        }
-    }
-
-    pub struct NonFinalBase {}
-
-    impl NonFinalBase {
-        pub fn new() -> Self {
-            NonFinalBase {}
-        }
-    }
-
-    impl GarbageCollectedBase for NonFinalBase {
-        fn trace(&self, _visitor: &mut Visitor) {
-            // Do nothing for the base class
-        }
-    }
-
-    pub struct FinalChild {}
-
-    impl FinalChild {
-        pub fn new() -> Self {
-            FinalChild {}
-        }
-    }
-
-    impl GarbageCollectedBase for FinalChild {
-        fn trace(&self, visitor: &mut Visitor) {
-            visitor.trace(self);
-
-            let base = NonFinalBase::new();
-            base.trace(visitor);
-        }
-    }
-
-    /// Base class for managed objects. Only descendent types of
-    /// `GarbageCollected` can be constructed using
-    /// `MakeGarbageCollected()`. Must be inherited from as left-most base
-    /// class.
-    ///
-    /// Types inheriting from GarbageCollected must provide a method of
-    /// signature `void Trace(cppgc::Visitor*) const` that dispatchs all
-    /// managed pointers to the visitor and delegates to garbage-collected base
-    /// classes. The method must be virtual if the type is not directly a
-    /// child of GarbageCollected and marked as final.
-    ///
-    /// \code
-    /// // Example using final class.
-    /// class FinalType final : public GarbageCollected<FinalType> {
-    ///  public:
-    ///   void Trace(cppgc::Visitor* visitor) const {
-    ///     // Dispatch using visitor->Trace(...);
-    ///   }
-    /// };
-    ///
-    /// // Example using non-final base class.
-    /// class NonFinalBase : public GarbageCollected<NonFinalBase> {
-    ///  public:
-    ///   virtual void Trace(cppgc::Visitor*) const {}
-    /// };
-    ///
-    /// class FinalChild final : public NonFinalBase {
-    ///  public:
-    ///   void Trace(cppgc::Visitor* visitor) const final {
-    ///     // Dispatch using visitor->Trace(...);
-    ///     NonFinalBase::Trace(visitor);
-    ///   }
-    /// };
-    /// \endcode
-    pub trait GarbageCollected {
-        type ParentMostGarbageCollectedType;
-    }
-
-    /// Base class for managed mixin objects. Such objects cannot be
-    /// constructed directly but must be mixed into the inheritance hierarchy
-    /// of a GarbageCollected object.
-    ///
-    /// Types inheriting from GarbageCollectedMixin must override a virtual
-    /// method of signature `void Trace(cppgc::Visitor*) const` that
-    /// dispatchs all managed pointers to the visitor and delegates to base
-    /// classes.
-    ///
-    /// \code
-    /// class Mixin : public GarbageCollectedMixin {
-    ///  public:
-    ///   void Trace(cppgc::Visitor* visitor) const override {
-    ///     // Dispatch using visitor->Trace(...);
-    ///   }
-    /// };
-    /// \endcode
-    pub trait GarbageCollectedMixin {
-        fn trace(&self, visitor: &mut Visitor);
-    }
-}
\ No newline at end of file
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::marker::PhantomData;
+
+use crate::include::cppgc::trace_trait::Trace;
+
+/// Types allocated on a cppgc heap via `make_garbage_collected`.
+///
+/// ```ignore
+/// struct Node {
+///     parent: WeakMember<Node>,
+///     children: RefCell<Vec<Member<Node>>>,
+/// }
+///
+/// impl Trace for Node {
+///     fn trace(&self, visitor: &mut Visitor) {
+///         visitor.trace(&self.parent);
+///         visitor.trace(&self.children);
+///     }
+/// }
+///
+/// impl GarbageCollected for Node {}
+/// ```
+///
+/// Destructors (`Drop`) of garbage-collected objects run during sweeping,
+/// when other objects may already be gone; dereferencing a `Member` there
+/// panics. Cleanup that needs other objects belongs in a prefinalizer.
+pub trait GarbageCollected: Trace + 'static {
+    /// Invoked for dead objects after marking and before any object is
+    /// reclaimed, so other objects may still be accessed. Prefinalizers must
+    /// not allocate or resurrect dead objects.
+    const PRE_FINALIZER: Option<fn(&Self)> = None;
+
+    /// Types that may be traced on concurrent marking threads return
+    /// `Some(ConcurrentTraceMarker::new())`, which requires the type to be
+    /// `Sync`. All other types are traced on the mutator thread.
+    fn concurrent_trace_marker() -> Option<ConcurrentTraceMarker<Self>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// Proof that a type may be traced concurrently with the mutator.
+pub struct ConcurrentTraceMarker<T: ?Sized>(PhantomData<fn(&T)>);
+
+impl<T: Sync + ?Sized> ConcurrentTraceMarker<T> {
+    pub fn new() -> Self {
+        ConcurrentTraceMarker(PhantomData)
+    }
+}
+
+impl<T: Sync + ?Sized> Default for ConcurrentTraceMarker<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
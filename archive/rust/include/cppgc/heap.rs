@@ -2,166 +2,518 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod common;
-pub mod platform;
+//! cppgc - A garbage collection library for Rust types.
 
-/// cppgc - A C++ garbage collection library.
-pub mod cppgc {
-    use std::borrow::BorrowMut;
+use crate::heap::cppgc::heap as internal;
+use crate::include::cppgc::allocation::AllocationHandle;
+
+pub use crate::include::cppgc::common::EmbedderStackState as StackState;
+
+/// Specifies whether conservative stack scanning is supported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StackSupport {
+    /// Conservative stack scan is supported.
+    SupportsConservativeStackScan,
+    /// Conservative stack scan is not supported. Garbage collections are then
+    /// only finalized when the embedder promises that the stack holds no
+    /// references, see `Heap::force_garbage_collection_slow`.
+    NoConservativeStackScan,
+}
+
+/// Specifies supported marking types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MarkingType {
+    /// Atomic stop-the-world marking. This option does not require any write
+    /// barriers but is the most intrusive in terms of jank.
+    Atomic,
+    /// Incremental marking interleaves marking with the rest of the application
+    /// workload on the same thread.
+    Incremental,
+    /// Incremental and concurrent marking.
+    IncrementalAndConcurrent,
+}
+
+/// Specifies supported sweeping types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepingType {
+    /// Atomic stop-the-world sweeping. All of sweeping is performed at once.
+    Atomic,
+    /// Incremental sweeping interleaves sweeping with the rest of the
+    /// application workload on the same thread.
+    Incremental,
+    /// Incremental and concurrent sweeping. Sweeping is split and interleaved
+    /// with the rest of the application. Destructors are not thread-safe in
+    /// general, so this currently sweeps incrementally on the owning thread.
+    IncrementalAndConcurrent,
+}
+
+/// Constraints for a Heap setup.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ResourceConstraints {
+    /// Allows the heap to grow to some initial size in bytes before triggering
+    /// garbage collections. This is useful when it is known that applications
+    /// need a certain minimum heap to run to avoid repeatedly invoking the
+    /// garbage collector when growing the heap.
+    pub initial_heap_size_bytes: usize,
+}
+
+/// Options specifying Heap properties when initializing a heap through
+/// `Heap::create()`.
+#[derive(Clone, Copy, Debug)]
+pub struct HeapOptions {
+    /// Specifies whether conservative stack scan is supported. When
+    /// conservative stack scan is not supported, the embedder must take care
+    /// of finalizing garbage collections through
+    /// `force_garbage_collection_slow()` or `finalize_garbage_collection()`
+    /// at points where the stack holds no references into the heap.
+    pub stack_support: StackSupport,
+
+    /// Specifies which types of marking are supported by the heap.
+    pub marking_support: MarkingType,
+
+    /// Specifies which types of sweeping are supported by the heap.
+    pub sweeping_support: SweepingType,
+
+    /// Resource constraints specifying various properties that the internal
+    /// GC scheduler follows.
+    pub resource_constraints: ResourceConstraints,
+}
+
+impl Default for HeapOptions {
+    /// Creates reasonable defaults for instantiating a Heap.
+    fn default() -> Self {
+        HeapOptions {
+            stack_support: StackSupport::SupportsConservativeStackScan,
+            marking_support: MarkingType::IncrementalAndConcurrent,
+            sweeping_support: SweepingType::IncrementalAndConcurrent,
+            resource_constraints: ResourceConstraints::default(),
+        }
+    }
+}
+
+/// A garbage-collected heap bound to the thread that created it.
+///
+/// Dropping the heap runs all remaining prefinalizers and destructors;
+/// persistents outliving it become empty. References obtained from the heap
+/// must not be used after that.
+pub struct Heap {
+    heap: Box<internal::Heap>,
+}
+
+impl Heap {
+    /// Creates a new heap that can be used for object allocation.
+    ///
+    /// \param options HeapOptions specifying various properties for the Heap.
+    /// \returns a new Heap instance.
+    pub fn create(options: HeapOptions) -> Box<Heap> {
+        Box::new(Heap {
+            heap: internal::Heap::create(&options),
+        })
+    }
+
+    /// Forces garbage collection, finishing any collection in progress.
+    ///
+    /// \param source String specifying the source (or caller) triggering a
+    ///   forced garbage collection.
+    /// \param reason String specifying the reason for the forced garbage
+    ///   collection.
+    /// \param stack_state The embedder stack state, see StackState.
+    pub fn force_garbage_collection_slow(
+        &self,
+        _source: &str,
+        _reason: &str,
+        stack_state: StackState,
+    ) {
+        self.heap.collect_garbage(stack_state);
+        self.heap.finish_sweeping();
+    }
+
+    /// Starts an incremental (and, if supported, concurrent) garbage
+    /// collection. Marking then advances on allocation and through
+    /// `perform_marking_step()`.
+    pub fn start_incremental_garbage_collection(&self) {
+        self.heap.start_garbage_collection(MarkingType::IncrementalAndConcurrent);
+    }
+
+    /// Performs a marking step of roughly `byte_budget` bytes. Returns true
+    /// if marking is done and the collection may be finalized.
+    pub fn perform_marking_step(&self, byte_budget: usize) -> bool {
+        self.heap.advance_marking(byte_budget)
+    }
+
+    /// Finalizes the garbage collection in progress, if any.
+    pub fn finalize_garbage_collection(&self, stack_state: StackState) {
+        self.heap.finalize_garbage_collection(stack_state);
+    }
+
+    /// \returns the opaque handle for allocating objects using
+    /// `make_garbage_collected()`.
+    pub fn get_allocation_handle(&self) -> &AllocationHandle {
+        AllocationHandle::from_heap(&self.heap)
+    }
+
+    /// \returns the size of live objects as of the last garbage collection
+    /// plus the size of objects allocated since.
+    pub fn allocated_object_size(&self) -> usize {
+        self.heap.allocated_object_size()
+    }
+
+    /// \returns the number of garbage collections finished on this heap.
+    pub fn epoch(&self) -> usize {
+        self.heap.epoch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::{Cell, RefCell};
     use std::rc::Rc;
-    use std::{any::Any, vec};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::heap::cppgc::metric_recorder::{GCCycle, GCCycleType, MainThreadIncrementalMark, MetricRecorder};
+    use crate::include::cppgc::allocation::make_garbage_collected;
+    use crate::include::cppgc::garbage_collected::{ConcurrentTraceMarker, GarbageCollected};
+    use crate::include::cppgc::member::{Member, WeakMember};
+    use crate::include::cppgc::persistent::{Persistent, WeakPersistent};
+    use crate::include::cppgc::trace_trait::Trace;
+    use crate::include::cppgc::visitor::Visitor;
+
+    struct Node {
+        next: Member<Node>,
+        weak: WeakMember<Node>,
+        drops: Rc<Cell<usize>>,
+    }
+
+    impl Trace for Node {
+        fn trace(&self, visitor: &mut Visitor<'_>) {
+            visitor.trace(&self.next);
+            visitor.trace(&self.weak);
+        }
+    }
 
-    pub use crate::common::EmbedderStackState as StackState;
-    pub use crate::platform::Platform;
+    impl GarbageCollected for Node {}
 
-    pub trait CustomSpaceBase: Any {
-        fn as_any(&self) -> &dyn Any;
-        fn as_any_mut(&mut self) -> &mut dyn Any;
+    impl Drop for Node {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
     }
 
-    pub struct AllocationHandle {}
-    pub struct HeapHandle {}
+    fn node(heap: &Heap, drops: &Rc<Cell<usize>>) -> Member<Node> {
+        make_garbage_collected(
+            heap.get_allocation_handle(),
+            Node {
+                next: Member::null(),
+                weak: WeakMember::null(),
+                drops: drops.clone(),
+            },
+        )
+    }
 
-    mod internal {
-        pub struct Heap {}
+    fn collect(heap: &Heap) {
+        heap.force_garbage_collection_slow("test", "testing", StackState::NoHeapPointers);
     }
 
-    /// Specifies whether conservative stack scanning is supported.
-    #[derive(Clone, Copy)]
-    pub enum StackSupport {
-        /// Conservative stack scan is supported.
-        SupportsConservativeStackScan,
-        /// Conservative stack scan is not supported. Embedders may use this option
-        /// when using custom infrastructure that is unsupported by the library.
-        NoConservativeStackScan,
+    #[inline(never)]
+    fn allocate_cycle(heap: &Heap, drops: &Rc<Cell<usize>>) {
+        let a = node(heap, drops);
+        let b = node(heap, drops);
+        a.next.set(b.get());
+        b.next.set(a.get());
     }
 
-    /// Specifies supported marking types.
-    #[derive(Clone, Copy)]
-    pub enum MarkingType {
-        /// Atomic stop-the-world marking. This option does not require any write
-        /// barriers but is the most intrusive in terms of jank.
-        Atomic,
-        /// Incremental marking interleaves marking with the rest of the application
-        /// workload on the same thread.
-        Incremental,
-        /// Incremental and concurrent marking.
-        IncrementalAndConcurrent,
+    #[test]
+    fn collects_unreachable_cycles() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        allocate_cycle(&heap, &drops);
+        collect(&heap);
+        assert_eq!(drops.get(), 2);
+        assert_eq!(heap.epoch(), 1);
     }
 
-    /// Specifies supported sweeping types.
-    #[derive(Clone, Copy)]
-    pub enum SweepingType {
-        /// Atomic stop-the-world sweeping. All of sweeping is performed at once.
-        Atomic,
-        /// Incremental sweeping interleaves sweeping with the rest of the
-        /// application workload on the same thread.
-        Incremental,
-        /// Incremental and concurrent sweeping. Sweeping is split and interleaved
-        /// with the rest of the application.
-        IncrementalAndConcurrent,
+    #[test]
+    fn persistents_keep_objects_alive() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        let root = Persistent::from(&node(&heap, &drops));
+        root.next.set(node(&heap, &drops).get());
+        collect(&heap);
+        assert_eq!(drops.get(), 0);
+        assert!(root.next.get().is_some());
+        drop(root);
+        collect(&heap);
+        assert_eq!(drops.get(), 2);
     }
 
-    /// Constraints for a Heap setup.
-    #[derive(Default)]
-    pub struct ResourceConstraints {
-        /// Allows the heap to grow to some initial size in bytes before triggering
-        /// garbage collections. This is useful when it is known that applications
-        /// need a certain minimum heap to run to avoid repeatedly invoking the
-        /// garbage collector when growing the heap.
-        pub initial_heap_size_bytes: usize,
+    #[test]
+    fn weak_references_are_cleared() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        let root = Persistent::from(&node(&heap, &drops));
+        let target = node(&heap, &drops);
+        root.weak.set(target.get());
+        let weak = WeakPersistent::new(&*target);
+        collect(&heap);
+        assert_eq!(drops.get(), 1);
+        assert!(root.weak.get().is_none());
+        assert!(weak.get().is_none());
+    }
+
+    struct Finalizable {
+        other: Member<Node>,
+        log: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    impl Trace for Finalizable {
+        fn trace(&self, visitor: &mut Visitor<'_>) {
+            visitor.trace(&self.other);
+        }
+    }
+
+    impl GarbageCollected for Finalizable {
+        const PRE_FINALIZER: Option<fn(&Self)> = Some(|this: &Self| {
+            // Other objects are still accessible in prefinalizers.
+            assert!(this.other.next.is_null());
+            this.log.borrow_mut().push("prefinalizer");
+        });
+    }
+
+    impl Drop for Finalizable {
+        fn drop(&mut self) {
+            self.log.borrow_mut().push("drop");
+        }
+    }
+
+    #[test]
+    fn prefinalizers_run_before_reclamation() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        let log = Rc::new(RefCell::new(Vec::new()));
+        make_garbage_collected(
+            heap.get_allocation_handle(),
+            Finalizable {
+                other: node(&heap, &drops),
+                log: log.clone(),
+            },
+        );
+        collect(&heap);
+        assert_eq!(*log.borrow(), ["prefinalizer", "drop"]);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn write_barrier_preserves_objects_stored_during_marking() {
+        let heap = Heap::create(HeapOptions {
+            marking_support: MarkingType::Incremental,
+            ..HeapOptions::default()
+        });
+        let drops = Rc::new(Cell::new(0));
+        let root = Persistent::from(&node(&heap, &drops));
+        let white = node(&heap, &drops);
+        heap.start_incremental_garbage_collection();
+        assert!(heap.perform_marking_step(usize::MAX));
+        // The root was already traced; only the barrier can mark `white`.
+        root.next.set(white.get());
+        heap.finalize_garbage_collection(StackState::NoHeapPointers);
+        assert_eq!(drops.get(), 0);
+        assert!(root.next.get().is_some());
     }
 
-    /// Options specifying Heap properties (e.g. custom spaces) when initializing a
-    /// heap through `Heap::Create()`.
     #[derive(Default)]
-    pub struct HeapOptions {
-        /// Custom spaces added to heap are required to have indices forming a
-        /// numbered sequence starting at 0, i.e., their `kSpaceIndex` must
-        /// correspond to the index they reside in the vector.
-        pub custom_spaces: Vec<Box<dyn CustomSpaceBase>>,
-
-        /// Specifies whether conservative stack scan is supported. When conservative
-        /// stack scan is not supported, the collector may try to invoke
-        /// garbage collections using non-nestable task, which are guaranteed to have
-        /// no interesting stack, through the provided Platform. If such tasks are
-        /// not supported by the Platform, the embedder must take care of invoking
-        /// the GC through `ForceGarbageCollectionSlow()`.
-        pub stack_support: StackSupport,
-
-        /// Specifies which types of marking are supported by the heap.
-        pub marking_support: MarkingType,
-
-        /// Specifies which types of sweeping are supported by the heap.
-        pub sweeping_support: SweepingType,
-
-        /// Resource constraints specifying various properties that the internal
-        /// GC scheduler follows.
-        pub resource_constraints: ResourceConstraints,
-    }
-
-    impl HeapOptions {
-        /// Creates reasonable defaults for instantiating a Heap.
-        ///
-        /// \returns the HeapOptions that can be passed to `Heap::Create()`.
-        pub fn default() -> Self {
-            HeapOptions {
-                stack_support: StackSupport::SupportsConservativeStackScan,
-                marking_support: MarkingType::IncrementalAndConcurrent,
-                sweeping_support: SweepingType::IncrementalAndConcurrent,
-                ..Default::default()
-            }
+    struct TestMetricRecorder {
+        cycles: Rc<RefCell<Vec<GCCycle>>>,
+        incremental_marks: Rc<Cell<usize>>,
+    }
+
+    impl MetricRecorder for TestMetricRecorder {
+        fn add_main_thread_event_gc_cycle(&self, event: &GCCycle) {
+            self.cycles.borrow_mut().push(*event);
+        }
+
+        fn add_main_thread_event_incremental_mark(&self, _event: &MainThreadIncrementalMark) {
+            self.incremental_marks.set(self.incremental_marks.get() + 1);
+        }
+    }
+
+    #[test]
+    fn finished_cycles_are_reported_to_the_metric_recorder() {
+        let heap = Heap::create(HeapOptions {
+            marking_support: MarkingType::Incremental,
+            sweeping_support: SweepingType::Incremental,
+            ..HeapOptions::default()
+        });
+        let recorder = TestMetricRecorder::default();
+        let cycles = recorder.cycles.clone();
+        let incremental_marks = recorder.incremental_marks.clone();
+        heap.heap.set_metric_recorder(Some(Box::new(recorder)));
+        let drops = Rc::new(Cell::new(0));
+        let _root = Persistent::from(&node(&heap, &drops));
+        allocate_cycle(&heap, &drops);
+
+        heap.start_incremental_garbage_collection();
+        heap.perform_marking_step(usize::MAX);
+        heap.finalize_garbage_collection(StackState::NoHeapPointers);
+        // The cycle is only reported once sweeping has finished.
+        assert_eq!(cycles.borrow().len(), usize::from(!heap.heap.is_sweeping()));
+        heap.heap.finish_sweeping();
+
+        assert_eq!(incremental_marks.get(), 1);
+        let cycles = cycles.borrow();
+        assert_eq!(cycles.len(), 1);
+        let cycle = &cycles[0];
+        assert_eq!(cycle.type_, GCCycleType::Major);
+        assert!(cycle.main_thread_incremental.mark_duration_us >= 0);
+        assert!(cycle.main_thread.mark_duration_us >= cycle.main_thread_atomic.mark_duration_us);
+        assert!(cycle.objects.freed_bytes > 0);
+        assert_eq!(cycle.objects.freed_bytes, cycle.objects.before_bytes - cycle.objects.after_bytes);
+        assert!(cycle.memory.before_bytes > 0);
+    }
+
+    static SYNC_NODE_DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    struct SyncNode {
+        next: Member<SyncNode>,
+        value: usize,
+    }
+
+    impl Trace for SyncNode {
+        fn trace(&self, visitor: &mut Visitor<'_>) {
+            visitor.trace(&self.next);
         }
     }
 
-    pub struct Heap {
-        allocation_handle: AllocationHandle,
-        heap_handle: HeapHandle,
+    impl GarbageCollected for SyncNode {
+        fn concurrent_trace_marker() -> Option<ConcurrentTraceMarker<Self>> {
+            Some(ConcurrentTraceMarker::new())
+        }
     }
 
-    impl Heap {
-        /// Creates a new heap that can be used for object allocation.
-        ///
-        /// \param platform implemented and provided by the embedder.
-        /// \param options HeapOptions specifying various properties for the Heap.
-        /// \returns a new Heap instance.
-        pub fn create(platform: Rc<dyn Platform>, options: HeapOptions) -> Box<Heap> {
-            Box::new(Heap {
-                allocation_handle: AllocationHandle {},
-                heap_handle: HeapHandle {},
-            })
+    impl Drop for SyncNode {
+        fn drop(&mut self) {
+            SYNC_NODE_DROPS.fetch_add(1, Ordering::Relaxed);
         }
+    }
 
-        /// Forces garbage collection.
-        ///
-        /// \param source String specifying the source (or caller) triggering a
-        ///   forced garbage collection.
-        /// \param reason String specifying the reason for the forced garbage
-        ///   collection.
-        /// \param stack_state The embedder stack state, see StackState.
-        pub fn force_garbage_collection_slow(
-            &mut self,
-            source: &str,
-            reason: &str,
-            stack_state: StackState,
-        ) {
-            // Implementation of garbage collection is not provided.
-            println!(
-                "Forcing garbage collection: source={}, reason={}, stack_state={:?}",
-                source, reason, stack_state
+    #[inline(never)]
+    fn allocate_chain(heap: &Heap, length: usize) -> Persistent<SyncNode> {
+        let mut head = Member::null();
+        for value in 0..length {
+            head = make_garbage_collected(
+                heap.get_allocation_handle(),
+                SyncNode { next: head, value },
             );
         }
+        Persistent::from(&head)
+    }
+
+    #[test]
+    fn concurrent_marking_traces_sync_types() {
+        let heap = Heap::create(HeapOptions::default());
+        let live = allocate_chain(&heap, 1000);
+        drop(allocate_chain(&heap, 500));
+        heap.start_incremental_garbage_collection();
+        heap.finalize_garbage_collection(StackState::NoHeapPointers);
+        heap.force_garbage_collection_slow("test", "sweep", StackState::NoHeapPointers);
+        assert_eq!(SYNC_NODE_DROPS.load(Ordering::Relaxed), 500);
+        let mut sum = 0;
+        let mut current = live.get();
+        while let Some(node) = current {
+            sum += node.value;
+            current = node.next.get();
+        }
+        assert_eq!(sum, (0..1000).sum());
+    }
+
+    #[test]
+    fn stack_scanning_keeps_on_stack_objects_alive() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        let on_stack = node(&heap, &drops);
+        heap.force_garbage_collection_slow("test", "testing", StackState::MayContainHeapPointers);
+        assert_eq!(drops.get(), 0);
+        assert!(std::hint::black_box(&on_stack).next.is_null());
+    }
+
+    #[test]
+    #[should_panic(expected = "conservative stack scanning is not supported")]
+    fn heaps_without_stack_support_reject_stack_scanning() {
+        let heap = Heap::create(HeapOptions {
+            stack_support: StackSupport::NoConservativeStackScan,
+            ..HeapOptions::default()
+        });
+        heap.force_garbage_collection_slow("test", "testing", StackState::MayContainHeapPointers);
+    }
+
+    #[test]
+    fn allocation_triggers_garbage_collections() {
+        let heap = Heap::create(HeapOptions {
+            stack_support: StackSupport::NoConservativeStackScan,
+            marking_support: MarkingType::Atomic,
+            ..HeapOptions::default()
+        });
+        let drops = Rc::new(Cell::new(0));
+        // Without stack scanning, collections are started by allocation but
+        // only finalized by the embedder.
+        for _ in 0..100_000 {
+            node(&heap, &drops);
+        }
+        collect(&heap);
+        assert_eq!(drops.get(), 100_000);
 
-        /// \returns the opaque handle for allocating objects using
-        /// `MakeGarbageCollected()`.
-        pub fn get_allocation_handle(&mut self) -> &AllocationHandle {
-            &self.allocation_handle
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        for _ in 0..100_000 {
+            node(&heap, &drops);
         }
+        assert!(heap.epoch() > 0);
+        assert!(drops.get() > 0);
+    }
+
+    struct Large {
+        data: [u8; 100_000],
+        drops: Rc<Cell<usize>>,
+    }
+
+    impl Trace for Large {
+        fn trace(&self, _visitor: &mut Visitor<'_>) {}
+    }
 
-        /// \returns the opaque heap handle which may be used to refer to this heap in
-        ///   other APIs. Valid as long as the underlying `Heap` is alive.
-        pub fn get_heap_handle(&mut self) -> &HeapHandle {
-            &self.heap_handle
+    impl GarbageCollected for Large {}
+
+    impl Drop for Large {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn large_objects() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        let large = Persistent::from(&make_garbage_collected(
+            heap.get_allocation_handle(),
+            Large {
+                data: [7; 100_000],
+                drops: drops.clone(),
+            },
+        ));
+        collect(&heap);
+        assert!(large.data.iter().all(|&byte| byte == 7));
+        drop(large);
+        collect(&heap);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn dropping_the_heap_finalizes_all_objects() {
+        let heap = Heap::create(HeapOptions::default());
+        let drops = Rc::new(Cell::new(0));
+        let weak = WeakPersistent::new(&*Persistent::from(&node(&heap, &drops)));
+        let _strong = Persistent::from(&node(&heap, &drops));
+        drop(heap);
+        assert_eq!(drops.get(), 2);
+        assert!(weak.get().is_none());
+    }
+}
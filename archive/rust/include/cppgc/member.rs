@@ -1,813 +1,285 @@
-pub mod cppgc {
-    pub mod internal {
-        pub mod api_constants {
-            // Placeholder for api-constants.h content
-        }
-
-        pub mod member_storage {
-            // Placeholder for member-storage.h content
-        }
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::heap::cppgc::gc_info_table::gc_info_index;
+use crate::heap::cppgc::heap_object_header::is_finalizing;
+use crate::heap::cppgc::heap_page::BasePage;
+use crate::heap::cppgc::write_barrier::WriteBarrier;
+use crate::include::cppgc::garbage_collected::GarbageCollected;
+use crate::include::cppgc::trace_trait::Trace;
+use crate::include::cppgc::visitor::Visitor;
+
+/// Untyped storage of `Member` and `WeakMember`. Accesses are atomic so that
+/// concurrent markers can read references while the mutator updates them.
+pub struct MemberBase {
+    raw: AtomicPtr<u8>,
+}
 
-        pub mod pointer_policies {
-            // Placeholder for pointer-policies.h content
+impl MemberBase {
+    const fn new(raw: *const u8) -> Self {
+        MemberBase {
+            raw: AtomicPtr::new(raw as *mut u8),
         }
-
-        // Define tags as empty structs
-        pub struct StrongMemberTag;
-        pub struct WeakMemberTag;
-        pub struct UntracedMemberTag;
-
-        pub struct DijkstraWriteBarrierPolicy;
-        pub struct NoWriteBarrierPolicy;
-        pub struct DefaultMemberCheckingPolicy;
-
-        pub struct DefaultMemberStorage;
-        pub struct RawPointer;
-        pub struct CompressedPointer;
-
-        pub struct Dummy;
-
-        pub const K_SIZE_OF_MEMBER: usize = std::mem::size_of::<Member<Dummy>>();
-        pub const K_SIZE_OF_UNCOMPRESSED_MEMBER: usize =
-            std::mem::size_of::<subtle::UncompressedMember<Dummy>>();
-
-        #[cfg(feature = "cppgc_pointer_compression")]
-        pub const K_SIZEOF_COMPRESSED_MEMBER: usize =
-            std::mem::size_of::<subtle::CompressedMember<Dummy>>();
     }
 
-    pub mod subtle {
-        pub struct HeapConsistency;
-
-        // Define aliases for compressed/uncompressed members
-        pub type UncompressedMember<T> = internal::BasicMember<
-            T,
-            internal::StrongMemberTag,
-            internal::DijkstraWriteBarrierPolicy,
-            internal::DefaultMemberCheckingPolicy,
-            internal::RawPointer,
-        >;
-
-        #[cfg(feature = "cppgc_pointer_compression")]
-        pub type CompressedMember<T> = internal::BasicMember<
-            T,
-            internal::StrongMemberTag,
-            internal::DijkstraWriteBarrierPolicy,
-            internal::DefaultMemberCheckingPolicy,
-            internal::CompressedPointer,
-        >;
+    #[inline]
+    pub fn load(&self) -> *const u8 {
+        self.raw.load(Ordering::Acquire)
     }
 
-    pub struct Visitor;
+    #[inline]
+    fn store(&self, raw: *const u8) {
+        self.raw.store(raw as *mut u8, Ordering::Release);
+    }
 
-    pub struct SentinelPointer;
+    pub fn clear(&self) {
+        self.store(std::ptr::null());
+    }
+}
 
-    // MemberBase - needs to be refactored to use safe Rust semantics and ownership.
-    pub mod internal {
-        use std::sync::atomic::{AtomicPtr, Ordering};
-        use std::marker::PhantomData;
+/// Returns the address of `value`, checking that it is the start of a
+/// garbage-collected object of type `T`.
+pub(crate) fn checked_object_start<T: GarbageCollected>(value: &T) -> *const u8 {
+    let object = value as *const T as *const u8;
+    let header = BasePage::try_from_address(object as usize)
+        .and_then(|page| page.try_object_header_from_inner_address(object as usize));
+    match header {
+        Some(header)
+            if std::ptr::eq(header.object_start(), object)
+                && header.gc_info_index() == gc_info_index::<T>() => {}
+        _ => panic!(
+            "{} at {:p} is not a garbage-collected object",
+            std::any::type_name::<T>(),
+            object
+        ),
+    }
+    object
+}
 
-        // Placeholder for RawStorage type (needs proper implementation)
-        #[derive(Copy, Clone)]
-        pub struct RawStorage(*mut std::ffi::c_void);
+#[inline]
+fn check_not_finalizing() {
+    assert!(
+        !is_finalizing(),
+        "garbage-collected objects must not be accessed from destructors; use a prefinalizer"
+    );
+}
 
-        impl RawStorage {
-            pub fn new(ptr: *mut std::ffi::c_void) -> Self {
-                RawStorage(ptr)
-            }
+/// A strong reference from a garbage-collected object to another one on the
+/// same heap.
+///
+/// Members keep their target alive only when they are traced, i.e. when
+/// they are fields of garbage-collected objects (or of containers inside
+/// them). On the stack, objects are kept alive by conservative stack
+/// scanning; everywhere else, e.g. in `Rc`s or global data, a `Persistent`
+/// is needed.
+pub struct Member<T: GarbageCollected> {
+    base: MemberBase,
+    _phantom: PhantomData<*const T>,
+}
 
-            pub fn load(&self) -> *mut std::ffi::c_void {
-                self.0
-            }
+// Sharing members is required for concurrent tracing; see
+// `GarbageCollected::concurrent_trace_marker`. Members are never `Send`: they
+// are only valid on the thread owning the heap.
+unsafe impl<T: GarbageCollected + Sync> Sync for Member<T> {}
+
+impl<T: GarbageCollected> Member<T> {
+    /// Creates an empty member.
+    pub const fn null() -> Self {
+        Member {
+            base: MemberBase::new(std::ptr::null()),
+            _phantom: PhantomData,
+        }
+    }
 
-            pub fn store(&mut self, value: *mut std::ffi::c_void) {
-                self.0 = value;
-            }
+    /// Creates a member pointing to `value`, which must be a
+    /// garbage-collected object.
+    pub fn new(value: &T) -> Self {
+        unsafe { Self::from_raw(checked_object_start(value) as *const T) }
+    }
 
-            pub fn load_atomic(&self) -> *mut std::ffi::c_void {
-                 self.0 // This is not truly atomic, requires synchronization primitive if needed
-            }
+    /// Creates a member from a pointer to a garbage-collected object.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must be null or point to the start of a live object of type
+    /// `T`.
+    pub(crate) unsafe fn from_raw(raw: *const T) -> Self {
+        WriteBarrier::dijkstra_marking_barrier(raw as *const u8);
+        Member {
+            base: MemberBase::new(raw as *const u8),
+            _phantom: PhantomData,
+        }
+    }
 
-            pub fn store_atomic(&mut self, value: *mut std::ffi::c_void) {
-                self.0 = value; // This is not truly atomic, requires synchronization primitive if needed
-            }
+    /// Returns the target, if any.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        check_not_finalizing();
+        unsafe { (self.base.load() as *const T).as_ref() }
+    }
 
-            pub fn is_cleared(&self) -> bool {
-                self.0.is_null()
-            }
+    /// Points the member to `value`, which must be a garbage-collected
+    /// object, or clears it.
+    pub fn set(&self, value: Option<&T>) {
+        let raw = value.map_or(std::ptr::null(), checked_object_start);
+        WriteBarrier::dijkstra_marking_barrier(raw);
+        self.base.store(raw);
+    }
 
-            pub fn clear(&mut self) {
-                self.0 = std::ptr::null_mut();
-            }
+    pub fn clear(&self) {
+        self.base.clear();
+    }
 
-             pub fn is_sentinel(&self) -> bool {
-                self.0.is_null() // Placeholder for SentinelPointer logic
-            }
-        }
+    pub fn is_null(&self) -> bool {
+        self.base.load().is_null()
+    }
 
-        // MemberBase always refers to the object as const object and defers to
-        // BasicMember on casting to the right type as needed.
-        #[derive(Debug)]
-        pub struct MemberBase<StorageType> {
-            raw_: AtomicPtr<std::ffi::c_void>,
-            _phantom: PhantomData<StorageType>,
-        }
+    pub fn as_ptr(&self) -> *const T {
+        self.base.load() as *const T
+    }
 
-        impl<StorageType> MemberBase<StorageType> {
-            pub type RawStorage = StorageType;
-
-            pub struct AtomicInitializerTag;
-
-            #[inline]
-            pub fn new() -> Self {
-                MemberBase {
-                    raw_: AtomicPtr::new(std::ptr::null_mut()),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_value(value: *mut std::ffi::c_void) -> Self {
-                MemberBase {
-                    raw_: AtomicPtr::new(value),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-             pub fn from_value_atomic(value: *mut std::ffi::c_void, _tag: AtomicInitializerTag) -> Self {
-                 MemberBase {
-                    raw_: AtomicPtr::new(value), // Placeholder:  AtomicInitializerTag initialization
-                    _phantom: PhantomData,
-                }
-            }
-
-
-            #[inline]
-            pub fn from_raw(raw: RawStorage) -> Self {
-                 MemberBase {
-                    raw_: AtomicPtr::new(raw.load()),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_nullptr() -> Self {
-                 MemberBase {
-                    raw_: AtomicPtr::new(std::ptr::null_mut()),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_sentinel(_s: super::SentinelPointer) -> Self {
-                 MemberBase {
-                    raw_: AtomicPtr::new(std::ptr::null_mut()), // Placeholder:  SentinelPointer logic
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn get_raw_slot(&self) -> *const *mut std::ffi::c_void {
-                &self.raw_ as *const AtomicPtr<std::ffi::c_void> as *const *mut std::ffi::c_void
-            }
-
-            #[inline]
-            pub fn get_raw(&self) -> *mut std::ffi::c_void {
-                self.raw_.load(Ordering::Relaxed)
-            }
-
-            #[inline]
-            pub fn set_raw(&self, value: *mut std::ffi::c_void) {
-                self.raw_.store(value, Ordering::Relaxed);
-            }
-
-            #[inline]
-            pub fn get_raw_atomic(&self) -> *mut std::ffi::c_void {
-                self.raw_.load(Ordering::Acquire)
-            }
-
-            #[inline]
-            pub fn set_raw_atomic(&self, value: *mut std::ffi::c_void) {
-                self.raw_.store(value, Ordering::Release);
-            }
-
-            #[inline]
-            pub fn get_raw_storage(&self) -> RawStorage {
-                RawStorage(self.get_raw())
-            }
-
-            #[inline]
-            pub fn set_raw_storage_atomic(&self, other: RawStorage) {
-                self.raw_.store(other.load(), Ordering::Relaxed);
-            }
-
-            #[inline]
-            pub fn is_cleared(&self) -> bool {
-                self.get_raw().is_null()
-            }
-
-            #[inline]
-            pub fn clear_from_gc(&self) {
-                self.set_raw(std::ptr::null_mut());
-            }
-        }
+    /// Returns true if both members point to the same object.
+    pub fn ptr_eq(&self, other: &Member<T>) -> bool {
+        self.base.load() == other.base.load()
+    }
+}
 
-        // The basic class from which all Member classes are 'generated'.
-        #[derive(Debug)]
-        pub struct BasicMember<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType> {
-            base: MemberBase<StorageType>,
-            _phantom: PhantomData<(T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy)>,
-        }
+impl<T: GarbageCollected> Deref for Member<T> {
+    type Target = T;
 
-        impl<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-            BasicMember<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-        {
-            pub type PointeeType = T;
-            pub type RawStorage = <MemberBase<StorageType> as MemberBaseTrait>::RawStorage;
-
-            #[inline]
-            pub const fn new() -> Self {
-                BasicMember {
-                    base: MemberBase::new(),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub const fn from_nullptr() -> Self {
-                BasicMember {
-                    base: MemberBase::from_nullptr(),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_sentinel(s: super::SentinelPointer) -> Self {
-                BasicMember {
-                    base: MemberBase::from_sentinel(s),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_raw(raw: *mut T) -> Self {
-                let mut member = BasicMember {
-                    base: MemberBase::from_value(raw as *mut std::ffi::c_void),
-                    _phantom: PhantomData,
-                };
-                member.initializing_write_barrier(raw);
-                member.check_pointer(raw);
-                member
-            }
-
-            #[inline]
-            pub fn from_ref(raw: &mut T) -> Self {
-                Self::from_raw(raw as *mut T)
-            }
-
-            // Atomic ctor. Using the AtomicInitializerTag forces BasicMember to
-            // initialize using atomic assignments. This is required for preventing
-            // data races with concurrent marking.
-            pub type AtomicInitializerTag = <MemberBase<StorageType> as MemberBaseTrait>::AtomicInitializerTag;
-
-            #[inline]
-            pub fn from_nullptr_atomic(atomic: AtomicInitializerTag) -> Self {
-                BasicMember {
-                    base: MemberBase::from_value_atomic(std::ptr::null_mut(), atomic),
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_sentinel_atomic(s: super::SentinelPointer, atomic: AtomicInitializerTag) -> Self {
-                 BasicMember {
-                    base: MemberBase::from_value_atomic(std::ptr::null_mut(), atomic), // Placeholder:  SentinelPointer logic
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_raw_atomic(raw: *mut T, atomic: AtomicInitializerTag) -> Self {
-                let mut member = BasicMember {
-                    base: MemberBase::from_value_atomic(raw as *mut std::ffi::c_void, atomic),
-                    _phantom: PhantomData,
-                };
-                member.initializing_write_barrier(raw);
-                member.check_pointer(raw);
-                member
-            }
-
-            #[inline]
-            pub fn from_ref_atomic(raw: &mut T, atomic: AtomicInitializerTag) -> Self {
-                Self::from_raw_atomic(raw as *mut T, atomic)
-            }
-
-            // Copy ctor.
-            #[inline]
-            pub fn from_basic_member(other: &Self) -> Self {
-                BasicMember {
-                    base: MemberBase::from_raw(other.get_raw_storage()),
-                    _phantom: PhantomData,
-                }
-            }
-
-            // Heterogeneous copy constructors. When the source pointer have a different
-            // type, perform a compress-decompress round, because the source pointer may
-            // need to be adjusted.
-             #[inline]
-            pub fn from_heterogeneous_copy<U, OtherBarrierPolicy, OtherWeaknessTag, OtherCheckingPolicy, StorageType2>(
-                other: &BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> Self
-                where T: IsDecayedSame<U>
-            {
-                BasicMember {
-                    base: MemberBase::from_raw(other.get() as *mut std::ffi::c_void), // or get_raw_storage(), check C++ logic
-                    _phantom: PhantomData,
-                }
-            }
-
-            #[inline]
-            pub fn from_heterogeneous_base<U, OtherBarrierPolicy, OtherWeaknessTag, OtherCheckingPolicy, StorageType2>(
-                other: &BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> Self
-                where T: IsStrictlyBaseOf<U>
-            {
-                 BasicMember {
-                    base: MemberBase::from_raw(other.get() as *mut std::ffi::c_void),
-                    _phantom: PhantomData,
-                }
-            }
-
-            // Move ctor.
-            #[inline]
-            pub fn from_basic_member_move(mut other: Self) -> Self {
-                 let result = BasicMember {
-                    base: MemberBase::from_raw(other.get_raw_storage()),
-                    _phantom: PhantomData,
-                };
-                other.clear();
-                result
-            }
-
-              #[inline]
-            pub fn from_heterogeneous_move<U, OtherBarrierPolicy, OtherWeaknessTag, OtherCheckingPolicy, StorageType2>(
-                mut other: BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> Self
-                where T: IsDecayedSame<U>
-            {
-                 let result = BasicMember {
-                    base: MemberBase::from_raw(other.get_raw_storage()),
-                    _phantom: PhantomData,
-                };
-                other.clear();
-                result
-            }
-
-            #[inline]
-            pub fn from_heterogeneous_base_move<U, OtherBarrierPolicy, OtherWeaknessTag, OtherCheckingPolicy, StorageType2>(
-                mut other: BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> Self
-                where T: IsStrictlyBaseOf<U>
-            {
-                 let result = BasicMember {
-                    base: MemberBase::from_raw(other.get() as *mut std::ffi::c_void),
-                    _phantom: PhantomData,
-                };
-                other.clear();
-                result
-            }
-
-            // Construction from Persistent.  (Stub Implementation as BasicPersistent is not defined.)
-            /*
-            #[inline]
-            pub fn from_persistent<U, PersistentWeaknessPolicy, PersistentLocationPolicy, PersistentCheckingPolicy>(p: &BasicPersistent<U, PersistentWeaknessPolicy, PersistentLocationPolicy, PersistentCheckingPolicy>) -> Self
-                where T: std::marker::BaseOf<U>
-            {
-                Self::from_raw(p.get())
-            }
-            */
-
-            // Copy assignment.
-            #[inline]
-            pub fn assign_from_basic_member(&mut self, other: &Self) -> &mut Self {
-                self.assign_from_raw_storage(other.get_raw_storage());
-                self
-            }
-
-            // Heterogeneous copy assignment. When the source pointer have a different
-            // type, perform a compress-decompress round, because the source pointer may
-            // need to be adjusted.
-           #[inline]
-            pub fn assign_from_heterogeneous<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>(
-                &mut self,
-                other: &BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> &mut Self
-                where T: IsDecayedSame<U>
-            {
-                self.assign_from_raw_storage(other.get_raw_storage());
-                self
-            }
-
-             #[inline]
-            pub fn assign_from_heterogeneous_base<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>(
-                &mut self,
-                other: &BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> &mut Self
-                where T: IsStrictlyBaseOf<U>
-            {
-                self.assign_from_raw(other.get());
-                self
-            }
-
-            // Move assignment.
-            #[inline]
-            pub fn assign_from_basic_member_move(&mut self, mut other: Self) -> &mut Self {
-                self.assign_from_raw_storage(other.get_raw_storage());
-                other.clear();
-                self
-            }
-
-            // Heterogeneous move assignment. When the source pointer have a different
-            // type, perform a compress-decompress round, because the source pointer may
-            // need to be adjusted.
-            #[inline]
-            pub fn assign_from_heterogeneous_move<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>(
-                &mut self,
-                mut other: BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> &mut Self
-                where T: IsDecayedSame<U>
-            {
-                self.assign_from_raw_storage(other.get_raw_storage());
-                other.clear();
-                self
-            }
-
-           #[inline]
-            pub fn assign_from_heterogeneous_base_move<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>(
-                &mut self,
-                mut other: BasicMember<U, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) -> &mut Self
-                where T: IsStrictlyBaseOf<U>
-            {
-                 self.assign_from_raw(other.get());
-                other.clear();
-                self
-            }
-
-            // Assignment from Persistent (Stub)
-           /*
-            #[inline]
-            pub fn assign_from_persistent<U, PersistentWeaknessPolicy, PersistentLocationPolicy, PersistentCheckingPolicy>(
-                &mut self,
-                other: &BasicPersistent<U, PersistentWeaknessPolicy, PersistentLocationPolicy, PersistentCheckingPolicy>
-            ) -> &mut Self
-                where T: std::marker::BaseOf<U>
-            {
-                self.assign_from_raw(other.get());
-                self
-            }
-            */
-
-            #[inline]
-            pub fn assign_from_raw(&mut self, other: *mut T) -> &mut Self {
-                self.base.set_raw_atomic(other as *mut std::ffi::c_void);
-                self.assigning_write_barrier(other);
-                self.check_pointer(other);
-                self
-            }
-
-            #[inline]
-            pub fn assign_from_nullptr(&mut self) -> &mut Self {
-                self.clear();
-                self
-            }
-
-            #[inline]
-            pub fn assign_from_sentinel(&mut self, s: super::SentinelPointer) -> &mut Self {
-                self.base.set_raw_atomic(std::ptr::null_mut()); // Placeholder:  SentinelPointer logic
-                self
-            }
-
-            #[inline]
-            pub fn swap<OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>(
-                &mut self,
-                other: &mut BasicMember<T, OtherWeaknessTag, OtherBarrierPolicy, OtherCheckingPolicy, StorageType2>
-            ) {
-                let tmp = self.get_raw_storage();
-                *self = BasicMember::from_basic_member_move(std::mem::replace(other, BasicMember::from_raw(std::ptr::null_mut()))); // Placeholder from_raw with nullptr
-                *other = BasicMember::from_raw(tmp.load() as *mut T); //Placeholder from_raw
-            }
-
-            #[inline]
-            pub fn as_bool(&self) -> bool {
-                !self.base.is_cleared()
-            }
-
-            #[inline]
-            pub fn as_ptr(&self) -> *mut T {
-                self.get()
-            }
-
-            #[inline]
-            pub fn deref(&self) -> &T {
-                unsafe { &*self.get() }
-            }
-
-            #[inline]
-            pub fn get(&self) -> *mut T {
-                self.base.get_raw() as *mut T
-            }
-
-            #[inline]
-            pub fn clear(&mut self) {
-                self.base.set_raw_storage_atomic(RawStorage::new(std::ptr::null_mut()));
-            }
-
-            #[inline]
-            pub fn release(&mut self) -> *mut T {
-                let result = self.get();
-                self.clear();
-                result
-            }
-
-            #[inline]
-            pub fn get_slot_for_testing(&self) -> *const *mut T {
-                self.base.get_raw_slot() as *const *mut T
-            }
-
-            #[inline]
-            pub fn get_raw_storage(&self) -> RawStorage {
-                self.base.get_raw_storage()
-            }
-
-            // Private methods
-            #[inline]
-            fn from_raw_storage(raw: RawStorage) -> Self {
-                let mut member = BasicMember {
-                    base: MemberBase::from_raw(raw),
-                    _phantom: PhantomData,
-                };
-                member.initializing_write_barrier(member.get()); // Check raw validity before dereferencing
-                member.check_pointer(member.get());
-                member
-            }
-
-            #[inline]
-            fn assign_from_raw_storage(&mut self, other: RawStorage) -> &mut Self {
-                self.base.set_raw_storage_atomic(other);
-                self.assigning_write_barrier(self.get());  // Check raw validity before dereferencing
-                self.check_pointer(self.get());
-                self
-            }
-
-            #[inline]
-            fn get_raw_atomic(&self) -> *const T {
-                self.base.get_raw_atomic() as *const T
-            }
-
-            #[inline]
-            fn initializing_write_barrier(&self, value: *mut T) {
-                // WriteBarrierPolicy::InitializingBarrier(self.base.GetRawSlot(), value);
-                 // Placeholder for initializing write barrier policy
-                 let _ = value;
-            }
-
-            #[inline]
-            fn initializing_write_barrier_no_value(&self) {
-                //WriteBarrierPolicy::InitializingBarrier(self.base.GetRawSlot(),self.base.GetRawStorage());
-                 // Placeholder for initializing write barrier policy
-            }
-
-            #[inline]
-            fn assigning_write_barrier(&self, value: *mut T) {
-                //WriteBarrierPolicy::template AssigningBarrier<StorageType::kWriteBarrierSlotType>(self.base.GetRawSlot(), value);
-                 // Placeholder for assigning write barrier policy
-                 let _ = value;
-            }
-
-            #[inline]
-            fn assigning_write_barrier_no_value(&self) {
-                //WriteBarrierPolicy::template AssigningBarrier<StorageType::kWriteBarrierSlotType>(self.base.GetRawSlot(),self.base.GetRawStorage());
-                 // Placeholder for assigning write barrier policy
-            }
-
-            #[inline]
-            fn check_pointer(&self, value: *mut T) {
-                //CheckingPolicy::template CheckPointer<T>(value);
-                 // Placeholder for checking pointer policy
-                 let _ = value;
-            }
-
-            #[inline]
-            fn check_pointer_no_value(&self) {
-                //CheckingPolicy::template CheckPointer<T>(self.base.GetRawStorage());
-                 // Placeholder for checking pointer policy
-            }
-
-            #[inline]
-            fn clear_from_gc(&self) {
-                self.base.clear_from_gc();
-            }
-
-            #[inline]
-            fn get_from_gc(&self) -> *mut T {
-                self.get()
-            }
-        }
+    fn deref(&self) -> &T {
+        self.get().expect("dereferenced an empty Member")
+    }
+}
 
-        // Trait to mirror some MemberBase C++ functionality
-        pub trait MemberBaseTrait {
-            type RawStorage;
-            struct AtomicInitializerTag;
-            fn new() -> Self;
-        }
+impl<T: GarbageCollected> Clone for Member<T> {
+    fn clone(&self) -> Self {
+        unsafe { Self::from_raw(self.as_ptr()) }
+    }
+}
 
-        impl<StorageType> MemberBaseTrait for MemberBase<StorageType> {
-            type RawStorage = StorageType;
-            struct AtomicInitializerTag;
+impl<T: GarbageCollected> Default for Member<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
 
-            fn new() -> Self {
-                MemberBase {
-                    raw_: AtomicPtr::new(std::ptr::null_mut()),
-                    _phantom: PhantomData,
-                }
-            }
-        }
+impl<T: GarbageCollected> PartialEq for Member<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other)
+    }
+}
 
-        // Implement BaseOf and DecayedSame traits - Needed to implement heterogenous copy/move constructors/assignments
+impl<T: GarbageCollected> Eq for Member<T> {}
 
-        pub trait IsBaseOf<T> {
-            const VALUE: bool;
-        }
+impl<T: GarbageCollected> fmt::Debug for Member<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Member({:p})", self.as_ptr())
+    }
+}
 
-        pub trait IsStrictlyBaseOf<T> {
-            const VALUE: bool;
+impl<T: GarbageCollected> Trace for Member<T> {
+    #[inline]
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        let object = self.base.load();
+        if !object.is_null() {
+            visitor.visit(object);
         }
+    }
+}
 
-        pub trait IsDecayedSame<T> {
-            const VALUE: bool;
-        }
+/// A weak reference between garbage-collected objects. It does not keep its
+/// target alive and is cleared when the target is reclaimed.
+pub struct WeakMember<T: GarbageCollected> {
+    base: MemberBase,
+    _phantom: PhantomData<*const T>,
+}
 
-        impl<T> IsBaseOf<T> for T {
-            const VALUE: bool = true;
-        }
+unsafe impl<T: GarbageCollected + Sync> Sync for WeakMember<T> {}
 
-        impl<T> IsStrictlyBaseOf<T> for T {
-            const VALUE: bool = false;
+impl<T: GarbageCollected> WeakMember<T> {
+    pub const fn null() -> Self {
+        WeakMember {
+            base: MemberBase::new(std::ptr::null()),
+            _phantom: PhantomData,
         }
+    }
 
-        impl<T> IsDecayedSame<T> for T {
-            const VALUE: bool = true;
+    /// Creates a weak member pointing to `value`, which must be a
+    /// garbage-collected object.
+    pub fn new(value: &T) -> Self {
+        let raw = checked_object_start(value);
+        WriteBarrier::weak_barrier(raw);
+        WeakMember {
+            base: MemberBase::new(raw),
+            _phantom: PhantomData,
         }
-
     }
 
-    /// Members are used in classes to contain strong pointers to other garbage
-    /// collected objects. All Member fields of a class must be traced in the class'
-    /// trace method.
-    pub type Member<T> = internal::BasicMember<
-        T,
-        internal::StrongMemberTag,
-        internal::DijkstraWriteBarrierPolicy,
-        internal::DefaultMemberCheckingPolicy,
-        internal::DefaultMemberStorage,
-    >;
-
-    /// WeakMember is similar to Member in that it is used to point to other garbage
-    /// collected objects. However instead of creating a strong pointer to the
-    /// object, the WeakMember creates a weak pointer, which does not keep the
-    /// pointee alive. Hence if all pointers to to a heap allocated object are weak
-    /// the object will be garbage collected. At the time of GC the weak pointers
-    /// will automatically be set to null.
-    pub type WeakMember<T> = internal::BasicMember<
-        T,
-        internal::WeakMemberTag,
-        internal::DijkstraWriteBarrierPolicy,
-        internal::DefaultMemberCheckingPolicy,
-        internal::DefaultMemberStorage,
-    >;
-
-    /// UntracedMember is a pointer to an on-heap object that is not traced for some
-    /// reason. Do not use this unless you know what you are doing. Keeping raw
-    /// pointers to on-heap objects is prohibited unless used from stack. Pointee
-    /// must be kept alive through other means.
-    pub type UntracedMember<T> = internal::BasicMember<
-        T,
-        internal::UntracedMemberTag,
-        internal::NoWriteBarrierPolicy,
-        internal::DefaultMemberCheckingPolicy,
-        internal::DefaultMemberStorage,
-    >;
-
-    pub mod internal_ops {
-        use super::internal::*;
-        use super::*;
-
-        // Equality operators
-        impl<T1, WeaknessTag1, WriteBarrierPolicy1, CheckingPolicy1, T2, WeaknessTag2,
-             WriteBarrierPolicy2, CheckingPolicy2, StorageType>
-            PartialEq<BasicMember<T2, WeaknessTag2, WriteBarrierPolicy2, CheckingPolicy2, StorageType>>
-            for BasicMember<T1, WeaknessTag1, WriteBarrierPolicy1, CheckingPolicy1, StorageType>
-        where
-            T1: IsDecayedSame<T2>, // Replace IsDecayedSame with the appropriate trait
-        {
-            fn eq(
-                &self,
-                other: &BasicMember<T2, WeaknessTag2, WriteBarrierPolicy2, CheckingPolicy2, StorageType>,
-            ) -> bool {
-                self.get_raw_storage().load() == other.get_raw_storage().load()
-            }
-        }
+    /// Returns the target if it is still alive.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        check_not_finalizing();
+        unsafe { (self.base.load() as *const T).as_ref() }
+    }
 
-         impl<T1, WeaknessTag1, WriteBarrierPolicy1, CheckingPolicy1, T2, WeaknessTag2,
-             WriteBarrierPolicy2, CheckingPolicy2, StorageType>
-            PartialEq<BasicMember<T2, WeaknessTag2, WriteBarrierPolicy2, CheckingPolicy2, StorageType>>
-            for BasicMember<T1, WeaknessTag1, WriteBarrierPolicy1, CheckingPolicy1, StorageType>
-        where
-            T1: IsStrictlyBaseOf<T2> // Replace IsStrictlyBaseOf with the appropriate trait
-        {
-            fn eq(
-                &self,
-                other: &BasicMember<T2, WeaknessTag2, WriteBarrierPolicy2, CheckingPolicy2, StorageType>,
-            ) -> bool {
-                self.get() == other.get()
-            }
-        }
+    /// Returns a strong reference to the target if it is still alive.
+    pub fn upgrade(&self) -> Option<Member<T>> {
+        self.get()
+            .map(|value| unsafe { Member::from_raw(value as *const T) })
+    }
 
-        // Equality with raw pointers
-        impl<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType, U>
-            PartialEq<*mut U> for BasicMember<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-         where
-             T: IsDecayedSame<U>,
-        {
-            fn eq(&self, raw: &*mut U) -> bool {
-                self.get_raw_storage().load() == *raw as *mut std::ffi::c_void
-            }
-        }
+    pub fn set(&self, value: Option<&T>) {
+        let raw = value.map_or(std::ptr::null(), checked_object_start);
+        WriteBarrier::weak_barrier(raw);
+        self.base.store(raw);
+    }
 
-        impl<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType, U>
-            PartialEq<*mut U> for BasicMember<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-         where
-             T: IsStrictlyBaseOf<U>,
-        {
-            fn eq(&self, raw: &*mut U) -> bool {
-                self.get_raw_storage().load() == *raw as *mut std::ffi::c_void
-            }
-        }
+    pub fn clear(&self) {
+        self.base.clear();
+    }
 
-        // Implement PartialEq for equality with SentinelPointer
-        impl<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-            PartialEq<SentinelPointer> for BasicMember<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-        {
-            fn eq(&self, _other: &SentinelPointer) -> bool {
-                 self.get_raw_storage().is_sentinel()
-            }
-        }
+    pub fn is_null(&self) -> bool {
+        self.base.load().is_null()
+    }
 
-        // Implement PartialEq for equality with nullptr
-        impl<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-            PartialEq<std::ptr::NonNull<T>> for BasicMember<T, WeaknessTag, WriteBarrierPolicy, CheckingPolicy, StorageType>
-        {
-            fn eq(&self, _other: &std::ptr::NonNull<T>) -> bool {
-                !self.as_bool()
-            }
-        }
+    pub fn as_ptr(&self) -> *const T {
+        self.base.load() as *const T
+    }
+}
 
-         // Relational operators
-        impl<T1, WeaknessTag1, WriteBarrierPolicy1, CheckingPolicy1, T2, WeaknessTag2,
-             WriteBarrierPolicy2, CheckingPolicy2, StorageType>
-            PartialOrd<BasicMember<T2, WeaknessTag2, WriteBarrierPolicy2, CheckingPolicy2, StorageType>>
-            for BasicMember<T1, WeaknessTag1, WriteBarrierPolicy1, CheckingPolicy1, StorageType>
-        where
-            T1: IsDecayedSame<T2>,
-        {
-            fn partial_cmp(
-                &self,
-                other: &BasicMember<T2, WeaknessTag2, WriteBarrierPolicy2, CheckingPolicy2, StorageType>,
-            ) -> Option<std::cmp::Ordering> {
-                self.get_raw_storage().load().partial_cmp(&other.get_raw_storage().load())
-            }
+impl<T: GarbageCollected> Clone for WeakMember<T> {
+    fn clone(&self) -> Self {
+        let raw = self.base.load();
+        WriteBarrier::weak_barrier(raw);
+        WeakMember {
+            base: MemberBase::new(raw),
+            _phantom: PhantomData,
         }
-
     }
+}
 
-    // Define IsWeak trait and implementation
-    pub trait IsWeakHelper<T> {
-        const IS_WEAK: bool;
+impl<T: GarbageCollected> Default for WeakMember<T> {
+    fn default() -> Self {
+        Self::null()
     }
+}
 
-    impl<T, WriteBarrierPolicy, CheckingPolicy, StorageType> IsWeakHelper<
-        internal::BasicMember<T, internal::WeakMemberTag, WriteBarrierPolicy, CheckingPolicy, StorageType>,
-    > for internal::BasicMember<T, internal::WeakMemberTag, WriteBarrierPolicy, CheckingPolicy, StorageType> {
-        const IS_WEAK: bool = true;
+impl<T: GarbageCollected> fmt::Debug for WeakMember<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WeakMember({:p})", self.as_ptr())
     }
-
-    pub struct TraceTrait; // Placeholder since it is used for friend declaration in the C++ code.
 }
 
-mod std {
-    pub mod ops {
-        pub use core::ops::*;
+impl<T: GarbageCollected> Trace for WeakMember<T> {
+    #[inline]
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        visitor.visit_weak(&self.base);
     }
-}
\ No newline at end of file
+}
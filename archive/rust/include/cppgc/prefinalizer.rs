@@ -1,91 +1,14 @@
-pub mod internal {
-    use std::marker::PhantomData;
-    use std::ptr::NonNull;
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-    use crate::LivenessBroker;
+use crate::include::cppgc::garbage_collected::GarbageCollected;
 
-    /// Represents a registration for a prefinalizer callback.
-    pub struct PrefinalizerRegistration {
-        // Hold the object address to ensure the callback is associated with it.
-        _object_address: NonNull<()>,
-        // phantom data to hold callback
-        _phantom: PhantomData<fn(&LivenessBroker, *mut ()) -> bool>,
-    }
+/// Signature of prefinalizers, see `GarbageCollected::PRE_FINALIZER`.
+pub type PreFinalizer<T> = fn(&T);
 
-    impl PrefinalizerRegistration {
-        /// Creates a new `PrefinalizerRegistration`.
-        pub fn new(object: *mut (), _callback: fn(&LivenessBroker, *mut ()) -> bool) -> Self {
-            PrefinalizerRegistration {
-                _object_address: NonNull::new(object).expect("Object pointer must not be null."),
-                _phantom: PhantomData,
-            }
-        }
-    }
-} // namespace internal
-
-/// A trait that marks a type as garbage collected, enabling prefinalization.
-pub trait GarbageCollected {
-    // Intentionally empty.  Acts as a marker trait.
-}
-
-/// A trait for types that can be traced by the garbage collector.
-pub trait Traceable {
-    /// Traces the object by visiting its fields.
-    fn trace(&self, visitor: &mut dyn Visitor);
+/// Returns true if objects of type `T` are registered for prefinalization
+/// when allocated.
+pub const fn has_pre_finalizer<T: GarbageCollected>() -> bool {
+    T::PRE_FINALIZER.is_some()
 }
-
-/// A trait for visitor instances.
-pub trait Visitor {
-    /// Visits a reference and traces it if necessary.
-    fn visit<T: GarbageCollected + Traceable>(&mut self, object: &T);
-}
-
-/// Macro to register a prefinalization callback.
-#[macro_export]
-macro_rules! cppgc_using_pre_finalizer {
-    ($Class:ident, $PreFinalizer:ident) => {
-        impl $Class {
-            #[allow(non_snake_case)]
-            #[doc(hidden)]
-            pub fn InvokePreFinalizer(liveness_broker: &cppgc::LivenessBroker, object: *mut ()) -> bool {
-                use cppgc::GarbageCollected;
-                use std::any::TypeId;
-
-                // Safety: The macro ensures that only garbage collected objects can have prefinalizers.
-                if TypeId::of::<Self>() != TypeId::of_val(&object) {
-                    let self_ptr = object as *mut Self;
-                    let self_ref: &mut Self = unsafe { &mut *self_ptr };
-                    if liveness_broker.is_heap_object_alive(self_ref) {
-                        return false;
-                    }
-                     self_ref.$PreFinalizer();
-                    return true;
-                }
-                 return false;
-            }
-        }
-
-        #[allow(non_snake_case)]
-        #[doc(hidden)]
-        lazy_static::lazy_static! {
-            static ref prefinalizer_dummy_: cppgc::internal::PrefinalizerRegistration = {
-                let object = core::ptr::null_mut() as *mut (); // self is not available here
-                cppgc::internal::PrefinalizerRegistration::new(object, Self::InvokePreFinalizer)
-            };
-        }
-        // Force semicolon
-        const _: () = ();
-    };
-}
-
-/// Represents the liveness of an object in the heap.
-pub struct LivenessBroker {}
-
-impl LivenessBroker {
-    /// Checks if a heap object is alive.
-    pub fn is_heap_object_alive<T>(&self, _object: &T) -> bool {
-        // Placeholder implementation: Always returns false.  In V8, this would query the
-        // garbage collector to determine if the object is alive.
-        false
-    }
-}
\ No newline at end of file
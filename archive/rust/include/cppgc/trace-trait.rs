@@ -2,86 +2,137 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// TODO(you): Replace with appropriate crate if `v8config.h` defines constants required here
-// #[cfg(feature = "v8_config")]
-// extern crate v8_config;
-
-pub mod cppgc {
-    pub struct TraceDescriptor {
-        pub base_object_payload: *const std::ffi::c_void,
-        pub callback: TraceCallback,
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::include::cppgc::visitor::Visitor;
+
+/// Types that can report the references they hold to the garbage collector.
+///
+/// Garbage-collected types (see `GarbageCollected`) and every field type
+/// holding `Member`s or `WeakMember`s implement this trait. Implementations
+/// must trace all such fields; missing a field results in its target being
+/// reclaimed while still referenced.
+pub trait Trace {
+    fn trace(&self, visitor: &mut Visitor<'_>);
+}
+
+macro_rules! impl_trace_for_leaf_types {
+    ($($type:ty),* $(,)?) => {
+        $(
+            impl Trace for $type {
+                #[inline]
+                fn trace(&self, _visitor: &mut Visitor<'_>) {}
+            }
+        )*
+    };
+}
+
+impl_trace_for_leaf_types!(
+    (), bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64,
+    String, str, &'static str, std::time::Duration,
+);
+
+impl<T: Trace + ?Sized> Trace for Box<T> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        (**self).trace(visitor);
     }
+}
 
-    pub type TraceCallback = fn(visitor: *mut Visitor, object: *const std::ffi::c_void);
-    pub type TraceDescriptorCallback = fn(address: *const std::ffi::c_void) -> TraceDescriptor;
-
-    pub struct Visitor {}
-
-    pub mod internal {
-        use super::{TraceCallback, TraceDescriptor, Visitor};
-
-        pub struct RootVisitor {}
-        pub type TraceRootCallback = fn(visitor: &mut RootVisitor, object: *const std::ffi::c_void);
+impl<T: Trace> Trace for Option<T> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        if let Some(value) = self {
+            value.trace(visitor);
+        }
+    }
+}
 
-        pub trait Traceable {
-            fn trace(&self, visitor: *mut Visitor);
+impl<T: Trace> Trace for [T] {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        for value in self {
+            value.trace(visitor);
         }
+    }
+}
 
-        pub struct TraceTraitFromInnerAddressImpl {}
+impl<T: Trace, const N: usize> Trace for [T; N] {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        self.as_slice().trace(visitor);
+    }
+}
 
-        impl TraceTraitFromInnerAddressImpl {
-            pub fn get_trace_descriptor(address: *const std::ffi::c_void) -> TraceDescriptor {
-                // TODO(you): Implement logic to determine the correct TraceDescriptor based on the inner address.
-                // This may involve looking up a table or using other metadata.
-                panic!("TraceTraitFromInnerAddressImpl::GetTraceDescriptor not implemented");
-            }
-        }
+impl<T: Trace> Trace for Vec<T> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        self.as_slice().trace(visitor);
+    }
+}
 
-        pub struct TraceTraitBase<T> {
-            _phantom: std::marker::PhantomData<T>,
+impl<T: Trace> Trace for VecDeque<T> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        for value in self {
+            value.trace(visitor);
         }
+    }
+}
 
-        impl<T> TraceTraitBase<T>
-        where
-            T: Traceable,
-        {
-            pub fn get_trace_descriptor(self_ptr: *const T) -> TraceDescriptor {
-                TraceTraitImpl::<T>::get_trace_descriptor(self_ptr)
-            }
+impl<K: Trace, V: Trace, S> Trace for HashMap<K, V, S> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        for (key, value) in self {
+            key.trace(visitor);
+            value.trace(visitor);
+        }
+    }
+}
 
-            pub extern "C" fn trace(visitor: *mut Visitor, self_ptr: *const std::ffi::c_void) {
-                let self_ref: &T = unsafe { &*(self_ptr as *const T) };
-                self_ref.trace(visitor);
-            }
+impl<T: Trace, S> Trace for HashSet<T, S> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        for value in self {
+            value.trace(visitor);
         }
+    }
+}
 
-        pub struct TraceTraitImpl<T> {
-            _phantom: std::marker::PhantomData<T>,
+impl<K: Trace, V: Trace> Trace for BTreeMap<K, V> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        for (key, value) in self {
+            key.trace(visitor);
+            value.trace(visitor);
         }
+    }
+}
 
-        impl<T> TraceTraitImpl<T>
-        where
-            T: Traceable,
-        {
-            pub fn get_trace_descriptor(self_ptr: *const T) -> TraceDescriptor {
-                TraceDescriptor {
-                    base_object_payload: self_ptr as *const std::ffi::c_void,
-                    callback: TraceTraitBase::<T>::trace,
-                }
-            }
+impl<T: Trace> Trace for BTreeSet<T> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        for value in self {
+            value.trace(visitor);
         }
     }
+}
 
-    pub struct TraceTrait<T> {
-        _phantom: std::marker::PhantomData<T>,
+impl<A: Trace, B: Trace> Trace for (A, B) {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        self.0.trace(visitor);
+        self.1.trace(visitor);
     }
+}
 
-    impl<T> TraceTrait<T>
-    where
-        T: internal::Traceable,
-    {
-        pub fn get_trace_descriptor(self_ptr: *const T) -> TraceDescriptor {
-            internal::TraceTraitBase::<T>::get_trace_descriptor(self_ptr)
-        }
+impl<A: Trace, B: Trace, C: Trace> Trace for (A, B, C) {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        self.0.trace(visitor);
+        self.1.trace(visitor);
+        self.2.trace(visitor);
+    }
+}
+
+/// The contents of a `RefCell` can change without going through `Member`
+/// write barriers, so the owning object is traced again in the atomic pause
+/// of incremental and concurrent collections.
+impl<T: Trace + ?Sized> Trace for RefCell<T> {
+    fn trace(&self, visitor: &mut Visitor<'_>) {
+        visitor.visit_mutable_container();
+        // Tracing happens on the owning thread while the mutator is stopped,
+        // so even an outstanding mutable borrow cannot change the contents
+        // concurrently.
+        unsafe { &*self.as_ptr() }.trace(visitor);
     }
-}
\ No newline at end of file
+}
@@ -2,157 +2,35 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod platform {
-    use std::time::Duration;
-    //use v8::{Isolate, Platform, TaskPriority}; // Assuming v8 crate has these.
-    //use v8::job::{JobHandle, JobTask}; // Assuming these are in a job submodule
-    //use v8::tracing::TracingController; // Assuming this is in a tracing submodule
-    use std::ptr::NonNull;
-
-    // Dummy implementations, replace with actual v8 crate imports and types.
-    pub struct Isolate {}
-    pub struct Platform {}
-    pub struct TaskPriority {}
-    pub struct JobHandle {}
-    pub struct JobTask {}
-    pub struct TracingController {}
-
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub enum IdleTaskSupport {
-        Disabled,
-        Enabled,
-    }
-
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub enum InProcessStackDumping {
-        Disabled,
-        Enabled,
-    }
-
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub enum MessageLoopBehavior {
-        DoNotWait,
-        WaitForWork,
-    }
-
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub enum PriorityMode {
-        DontApply,
-        Apply,
-    }
-
-    /// Returns a new instance of the default v8::Platform implementation.
-    ///
-    /// The caller will take ownership of the returned pointer. |thread_pool_size|
-    /// is the number of worker threads to allocate for background jobs. If a value
-    /// of zero is passed, a suitable default based on the current number of
-    /// processors online will be chosen.
-    /// If |idle_task_support| is enabled then the platform will accept idle
-    /// tasks (IdleTasksEnabled will return true) and will rely on the embedder
-    /// calling v8::platform::RunIdleTasks to process the idle tasks.
-    /// If |tracing_controller| is nullptr, the default platform will create a
-    /// v8::platform::TracingController instance and use it.
-    /// If |priority_mode| is PriorityMode::kApply, the default platform will use
-    /// multiple task queues executed by threads different system-level priorities
-    /// (where available) to schedule tasks.
-    #[no_mangle]
-    pub extern "C" fn new_default_platform(
-        thread_pool_size: i32,
-        idle_task_support: IdleTaskSupport,
-        in_process_stack_dumping: InProcessStackDumping,
-        tracing_controller: Option<Box<TracingController>>,
-        priority_mode: PriorityMode,
-    ) -> *mut Platform {
-        let _ = thread_pool_size;
-        let _ = idle_task_support;
-        let _ = in_process_stack_dumping;
-        let _ = tracing_controller;
-        let _ = priority_mode;
-        // Placeholder, replace with actual platform creation logic using the v8 crate
-        Box::into_raw(Box::new(Platform {}))
-    }
-
-    /// The same as NewDefaultPlatform but disables the worker thread pool.
-    /// It must be used with the --single-threaded V8 flag.
-    #[no_mangle]
-    pub extern "C" fn new_single_threaded_default_platform(
-        idle_task_support: IdleTaskSupport,
-        in_process_stack_dumping: InProcessStackDumping,
-        tracing_controller: Option<Box<TracingController>>,
-    ) -> *mut Platform {
-        let _ = idle_task_support;
-        let _ = in_process_stack_dumping;
-        let _ = tracing_controller;
-        // Placeholder, replace with actual platform creation logic using the v8 crate
-        Box::into_raw(Box::new(Platform {}))
-    }
-
-    /// Returns a new instance of the default v8::JobHandle implementation.
-    ///
-    /// The job will be executed by spawning up to |num_worker_threads| many worker
-    /// threads on the provided |platform| with the given |priority|.
-    #[no_mangle]
-    pub extern "C" fn new_default_job_handle(
-        platform: *mut Platform,
-        priority: *mut TaskPriority, //v8::TaskPriority,
-        job_task: *mut JobTask, //std::unique_ptr<v8::JobTask> job_task,
-        num_worker_threads: usize,
-    ) -> *mut JobHandle {
-        let _ = platform;
-        let _ = priority;
-        let _ = job_task;
-        let _ = num_worker_threads;
-        // Placeholder, replace with actual job handle creation logic using the v8 crate
-        Box::into_raw(Box::new(JobHandle {}))
-    }
-
-    /// Pumps the message loop for the given isolate.
-    ///
-    /// The caller has to make sure that this is called from the right thread.
-    /// Returns true if a task was executed, and false otherwise. If the call to
-    /// PumpMessageLoop is nested within another call to PumpMessageLoop, only
-    /// nestable tasks may run. Otherwise, any task may run. Unless requested through
-    /// the |behavior| parameter, this call does not block if no task is pending. The
-    /// |platform| has to be created using |NewDefaultPlatform|.
-    #[no_mangle]
-    pub extern "C" fn pump_message_loop(
-        platform: *mut Platform,
-        isolate: *mut Isolate,
-        behavior: MessageLoopBehavior,
-    ) -> bool {
-        let _ = platform;
-        let _ = isolate;
-        let _ = behavior;
-        // Placeholder, replace with actual message loop pumping logic using the v8 crate
-        false
-    }
-
-    /// Runs pending idle tasks for at most |idle_time_in_seconds| seconds.
-    ///
-    /// The caller has to make sure that this is called from the right thread.
-    /// This call does not block if no task is pending. The |platform| has to be
-    /// created using |NewDefaultPlatform|.
-    #[no_mangle]
-    pub extern "C" fn run_idle_tasks(
-        platform: *mut Platform,
-        isolate: *mut Isolate,
-        idle_time_in_seconds: f64,
-    ) {
-        let _ = platform;
-        let _ = isolate;
-        let _ = idle_time_in_seconds;
-        // Placeholder, replace with actual idle task execution logic using the v8 crate
-    }
-
-    /// Notifies the given platform about the Isolate getting deleted soon. Has to be
-    /// called for all Isolates which are deleted - unless we're shutting down the
-    /// platform.
-    ///
-    /// The |platform| has to be created using |NewDefaultPlatform|.
-    #[no_mangle]
-    pub extern "C" fn notify_isolate_shutdown(platform: *mut Platform, isolate: *mut Isolate) {
-        let _ = platform;
-        let _ = isolate;
-        // Placeholder, replace with actual isolate shutdown notification logic using the v8 crate
-    }
-}
\ No newline at end of file
+pub use crate::libplatform::default_platform::{
+    new_default_job_handle, new_default_platform, new_single_threaded_default_platform, notify_isolate_shutdown,
+    pump_message_loop, run_idle_tasks, DefaultPlatform,
+};
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IdleTaskSupport {
+    kDisabled,
+    kEnabled,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InProcessStackDumping {
+    kDisabled,
+    kEnabled,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MessageLoopBehavior {
+    kDoNotWait,
+    kWaitForWork,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PriorityMode {
+    kDontApply,
+    kApply,
+}
@@ -1,5 +1,7 @@
 // Module declarations for converted include code
 
+pub mod cppgc;
+
 pub mod v8-array-buffer;
 pub mod v8-value-serializer-version;
 pub mod v8;
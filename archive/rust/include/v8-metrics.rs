@@ -1,408 +1,456 @@
-pub mod v8_metrics {
-    use std::time::Duration;
-
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct GarbageCollectionPhases {
-        pub total_wall_clock_duration_in_us: i64,
-        pub compact_wall_clock_duration_in_us: i64,
-        pub mark_wall_clock_duration_in_us: i64,
-        pub sweep_wall_clock_duration_in_us: i64,
-        pub weak_wall_clock_duration_in_us: i64,
-    }
-
-    impl GarbageCollectionPhases {
-        pub fn new() -> Self {
-            GarbageCollectionPhases {
-                total_wall_clock_duration_in_us: -1,
-                compact_wall_clock_duration_in_us: -1,
-                mark_wall_clock_duration_in_us: -1,
-                sweep_wall_clock_duration_in_us: -1,
-                weak_wall_clock_duration_in_us: -1,
-            }
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Event-based metrics reported to an embedder `Recorder`. Durations and
+//! sizes that were not measured are -1.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageCollectionPhases {
+    pub total_wall_clock_duration_in_us: i64,
+    pub compact_wall_clock_duration_in_us: i64,
+    pub mark_wall_clock_duration_in_us: i64,
+    pub sweep_wall_clock_duration_in_us: i64,
+    pub weak_wall_clock_duration_in_us: i64,
+}
+
+impl GarbageCollectionPhases {
+    pub const fn new() -> Self {
+        GarbageCollectionPhases {
+            total_wall_clock_duration_in_us: -1,
+            compact_wall_clock_duration_in_us: -1,
+            mark_wall_clock_duration_in_us: -1,
+            sweep_wall_clock_duration_in_us: -1,
+            weak_wall_clock_duration_in_us: -1,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct GarbageCollectionSizes {
-        pub bytes_before: i64,
-        pub bytes_after: i64,
-        pub bytes_freed: i64,
+impl Default for GarbageCollectionPhases {
+    fn default() -> Self {
+        Self::new()
     }
-
-    impl GarbageCollectionSizes {
-        pub fn new() -> Self {
-            GarbageCollectionSizes {
-                bytes_before: -1,
-                bytes_after: -1,
-                bytes_freed: -1,
-            }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageCollectionSizes {
+    pub bytes_before: i64,
+    pub bytes_after: i64,
+    pub bytes_freed: i64,
+}
+
+impl GarbageCollectionSizes {
+    pub const fn new() -> Self {
+        GarbageCollectionSizes {
+            bytes_before: -1,
+            bytes_after: -1,
+            bytes_freed: -1,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone)]
-    pub struct GarbageCollectionFullCycle {
-        pub reason: i32,
-        // The priority of the isolate during the GC cycle. A None value denotes a
-        // mixed priority cycle, meaning the Isolate's priority was changed while the
-        // cycle was in progress.
-        pub priority: Option<i32>, // Using i32 as a placeholder for v8::Isolate::Priority
-        pub total: GarbageCollectionPhases,
-        pub total_cpp: GarbageCollectionPhases,
-        pub main_thread: GarbageCollectionPhases,
-        pub main_thread_cpp: GarbageCollectionPhases,
-        pub main_thread_atomic: GarbageCollectionPhases,
-        pub main_thread_atomic_cpp: GarbageCollectionPhases,
-        pub main_thread_incremental: GarbageCollectionPhases,
-        pub main_thread_incremental_cpp: GarbageCollectionPhases,
-        pub objects: GarbageCollectionSizes,
-        pub objects_cpp: GarbageCollectionSizes,
-        pub memory: GarbageCollectionSizes,
-        pub memory_cpp: GarbageCollectionSizes,
-        pub collection_rate_in_percent: f64,
-        pub collection_rate_cpp_in_percent: f64,
-        pub efficiency_in_bytes_per_us: f64,
-        pub efficiency_cpp_in_bytes_per_us: f64,
-        pub main_thread_efficiency_in_bytes_per_us: f64,
-        pub main_thread_efficiency_cpp_in_bytes_per_us: f64,
-        pub collection_weight_in_percent: f64,
-        pub collection_weight_cpp_in_percent: f64,
-        pub main_thread_collection_weight_in_percent: f64,
-        pub main_thread_collection_weight_cpp_in_percent: f64,
-        pub incremental_marking_start_stop_wall_clock_duration_in_us: i64,
-    }
-
-    impl GarbageCollectionFullCycle {
-        pub fn new() -> Self {
-            GarbageCollectionFullCycle {
-                reason: -1,
-                priority: None,
-                total: GarbageCollectionPhases::new(),
-                total_cpp: GarbageCollectionPhases::new(),
-                main_thread: GarbageCollectionPhases::new(),
-                main_thread_cpp: GarbageCollectionPhases::new(),
-                main_thread_atomic: GarbageCollectionPhases::new(),
-                main_thread_atomic_cpp: GarbageCollectionPhases::new(),
-                main_thread_incremental: GarbageCollectionPhases::new(),
-                main_thread_incremental_cpp: GarbageCollectionPhases::new(),
-                objects: GarbageCollectionSizes::new(),
-                objects_cpp: GarbageCollectionSizes::new(),
-                memory: GarbageCollectionSizes::new(),
-                memory_cpp: GarbageCollectionSizes::new(),
-                collection_rate_in_percent: -1.0,
-                collection_rate_cpp_in_percent: -1.0,
-                efficiency_in_bytes_per_us: -1.0,
-                efficiency_cpp_in_bytes_per_us: -1.0,
-                main_thread_efficiency_in_bytes_per_us: -1.0,
-                main_thread_efficiency_cpp_in_bytes_per_us: -1.0,
-                collection_weight_in_percent: -1.0,
-                collection_weight_cpp_in_percent: -1.0,
-                main_thread_collection_weight_in_percent: -1.0,
-                main_thread_collection_weight_cpp_in_percent: -1.0,
-                incremental_marking_start_stop_wall_clock_duration_in_us: -1,
-            }
+impl Default for GarbageCollectionSizes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GarbageCollectionFullCycle {
+    pub reason: i32,
+    /// The priority of the isolate during the GC cycle. None denotes a mixed
+    /// priority cycle, i.e. the priority changed while the cycle was in
+    /// progress.
+    pub priority: Option<i32>,
+    pub total: GarbageCollectionPhases,
+    pub total_cpp: GarbageCollectionPhases,
+    pub main_thread: GarbageCollectionPhases,
+    pub main_thread_cpp: GarbageCollectionPhases,
+    pub main_thread_atomic: GarbageCollectionPhases,
+    pub main_thread_atomic_cpp: GarbageCollectionPhases,
+    pub main_thread_incremental: GarbageCollectionPhases,
+    pub main_thread_incremental_cpp: GarbageCollectionPhases,
+    pub objects: GarbageCollectionSizes,
+    pub objects_cpp: GarbageCollectionSizes,
+    pub memory: GarbageCollectionSizes,
+    pub memory_cpp: GarbageCollectionSizes,
+    pub collection_rate_in_percent: f64,
+    pub collection_rate_cpp_in_percent: f64,
+    pub efficiency_in_bytes_per_us: f64,
+    pub efficiency_cpp_in_bytes_per_us: f64,
+    pub main_thread_efficiency_in_bytes_per_us: f64,
+    pub main_thread_efficiency_cpp_in_bytes_per_us: f64,
+    pub collection_weight_in_percent: f64,
+    pub collection_weight_cpp_in_percent: f64,
+    pub main_thread_collection_weight_in_percent: f64,
+    pub main_thread_collection_weight_cpp_in_percent: f64,
+    pub incremental_marking_start_stop_wall_clock_duration_in_us: i64,
+}
+
+impl GarbageCollectionFullCycle {
+    pub const fn new() -> Self {
+        GarbageCollectionFullCycle {
+            reason: -1,
+            priority: None,
+            total: GarbageCollectionPhases::new(),
+            total_cpp: GarbageCollectionPhases::new(),
+            main_thread: GarbageCollectionPhases::new(),
+            main_thread_cpp: GarbageCollectionPhases::new(),
+            main_thread_atomic: GarbageCollectionPhases::new(),
+            main_thread_atomic_cpp: GarbageCollectionPhases::new(),
+            main_thread_incremental: GarbageCollectionPhases::new(),
+            main_thread_incremental_cpp: GarbageCollectionPhases::new(),
+            objects: GarbageCollectionSizes::new(),
+            objects_cpp: GarbageCollectionSizes::new(),
+            memory: GarbageCollectionSizes::new(),
+            memory_cpp: GarbageCollectionSizes::new(),
+            collection_rate_in_percent: -1.0,
+            collection_rate_cpp_in_percent: -1.0,
+            efficiency_in_bytes_per_us: -1.0,
+            efficiency_cpp_in_bytes_per_us: -1.0,
+            main_thread_efficiency_in_bytes_per_us: -1.0,
+            main_thread_efficiency_cpp_in_bytes_per_us: -1.0,
+            collection_weight_in_percent: -1.0,
+            collection_weight_cpp_in_percent: -1.0,
+            main_thread_collection_weight_in_percent: -1.0,
+            main_thread_collection_weight_cpp_in_percent: -1.0,
+            incremental_marking_start_stop_wall_clock_duration_in_us: -1,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct GarbageCollectionFullMainThreadIncrementalMark {
-        pub wall_clock_duration_in_us: i64,
-        pub cpp_wall_clock_duration_in_us: i64,
+impl Default for GarbageCollectionFullCycle {
+    fn default() -> Self {
+        Self::new()
     }
-
-    impl GarbageCollectionFullMainThreadIncrementalMark {
-        pub fn new() -> Self {
-            GarbageCollectionFullMainThreadIncrementalMark {
-                wall_clock_duration_in_us: -1,
-                cpp_wall_clock_duration_in_us: -1,
-            }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageCollectionFullMainThreadIncrementalMark {
+    pub wall_clock_duration_in_us: i64,
+    pub cpp_wall_clock_duration_in_us: i64,
+}
+
+impl GarbageCollectionFullMainThreadIncrementalMark {
+    pub const fn new() -> Self {
+        GarbageCollectionFullMainThreadIncrementalMark {
+            wall_clock_duration_in_us: -1,
+            cpp_wall_clock_duration_in_us: -1,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct GarbageCollectionFullMainThreadIncrementalSweep {
-        pub wall_clock_duration_in_us: i64,
-        pub cpp_wall_clock_duration_in_us: i64,
+impl Default for GarbageCollectionFullMainThreadIncrementalMark {
+    fn default() -> Self {
+        Self::new()
     }
-
-    impl GarbageCollectionFullMainThreadIncrementalSweep {
-        pub fn new() -> Self {
-            GarbageCollectionFullMainThreadIncrementalSweep {
-                wall_clock_duration_in_us: -1,
-                cpp_wall_clock_duration_in_us: -1,
-            }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageCollectionFullMainThreadIncrementalSweep {
+    pub wall_clock_duration_in_us: i64,
+    pub cpp_wall_clock_duration_in_us: i64,
+}
+
+impl GarbageCollectionFullMainThreadIncrementalSweep {
+    pub const fn new() -> Self {
+        GarbageCollectionFullMainThreadIncrementalSweep {
+            wall_clock_duration_in_us: -1,
+            cpp_wall_clock_duration_in_us: -1,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone)]
-    pub struct GarbageCollectionBatchedEvents<EventType> {
-        pub events: Vec<EventType>,
-    }
-
-    pub type GarbageCollectionFullMainThreadBatchedIncrementalMark =
-        GarbageCollectionBatchedEvents<GarbageCollectionFullMainThreadIncrementalMark>;
-    pub type GarbageCollectionFullMainThreadBatchedIncrementalSweep =
-        GarbageCollectionBatchedEvents<GarbageCollectionFullMainThreadIncrementalSweep>;
-
-    #[derive(Default, Debug, Clone)]
-    pub struct GarbageCollectionYoungCycle {
-        pub reason: i32,
-        // The priority of the isolate during the GC cycle. A None value denotes a
-        // mixed priority cycle, meaning the Isolate's priority was changed while the
-        // cycle was in progress.
-        pub priority: Option<i32>, // Using i32 as a placeholder for v8::Isolate::Priority
-        pub total_wall_clock_duration_in_us: i64,
-        pub main_thread_wall_clock_duration_in_us: i64,
-        pub collection_rate_in_percent: f64,
-        pub efficiency_in_bytes_per_us: f64,
-        pub main_thread_efficiency_in_bytes_per_us: f64,
-        #[cfg(feature = "cppgc_young_generation")]
-        pub total_cpp: GarbageCollectionPhases,
-        #[cfg(feature = "cppgc_young_generation")]
-        pub objects_cpp: GarbageCollectionSizes,
-        #[cfg(feature = "cppgc_young_generation")]
-        pub memory_cpp: GarbageCollectionSizes,
-        #[cfg(feature = "cppgc_young_generation")]
-        pub collection_rate_cpp_in_percent: f64,
-        #[cfg(feature = "cppgc_young_generation")]
-        pub efficiency_cpp_in_bytes_per_us: f64,
-        #[cfg(feature = "cppgc_young_generation")]
-        pub main_thread_efficiency_cpp_in_bytes_per_us: f64,
-    }
-
-    impl GarbageCollectionYoungCycle {
-        pub fn new() -> Self {
-            GarbageCollectionYoungCycle {
-                reason: -1,
-                priority: None,
-                total_wall_clock_duration_in_us: -1,
-                main_thread_wall_clock_duration_in_us: -1,
-                collection_rate_in_percent: -1.0,
-                efficiency_in_bytes_per_us: -1.0,
-                main_thread_efficiency_in_bytes_per_us: -1.0,
-                #[cfg(feature = "cppgc_young_generation")]
-                total_cpp: GarbageCollectionPhases::new(),
-                #[cfg(feature = "cppgc_young_generation")]
-                objects_cpp: GarbageCollectionSizes::new(),
-                #[cfg(feature = "cppgc_young_generation")]
-                memory_cpp: GarbageCollectionSizes::new(),
-                #[cfg(feature = "cppgc_young_generation")]
-                collection_rate_cpp_in_percent: -1.0,
-                #[cfg(feature = "cppgc_young_generation")]
-                efficiency_cpp_in_bytes_per_us: -1.0,
-                #[cfg(feature = "cppgc_young_generation")]
-                main_thread_efficiency_cpp_in_bytes_per_us: -1.0,
-            }
-        }
+impl Default for GarbageCollectionFullMainThreadIncrementalSweep {
+    fn default() -> Self {
+        Self::new()
     }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GarbageCollectionBatchedEvents<EventType> {
+    pub events: Vec<EventType>,
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct WasmModuleDecoded {
-        pub async_flag: bool,
-        pub streamed: bool,
-        pub success: bool,
-        pub module_size_in_bytes: usize,
-        pub function_count: usize,
-        pub wall_clock_duration_in_us: i64,
-    }
-
-    impl WasmModuleDecoded {
-        pub fn new(
-            async_flag: bool,
-            streamed: bool,
-            success: bool,
-            module_size_in_bytes: usize,
-            function_count: usize,
-            wall_clock_duration_in_us: i64,
-        ) -> Self {
-            WasmModuleDecoded {
-                async_flag,
-                streamed,
-                success,
-                module_size_in_bytes,
-                function_count,
-                wall_clock_duration_in_us,
-            }
+impl<EventType> Default for GarbageCollectionBatchedEvents<EventType> {
+    fn default() -> Self {
+        GarbageCollectionBatchedEvents { events: Vec::new() }
+    }
+}
+
+pub type GarbageCollectionFullMainThreadBatchedIncrementalMark =
+    GarbageCollectionBatchedEvents<GarbageCollectionFullMainThreadIncrementalMark>;
+pub type GarbageCollectionFullMainThreadBatchedIncrementalSweep =
+    GarbageCollectionBatchedEvents<GarbageCollectionFullMainThreadIncrementalSweep>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GarbageCollectionYoungCycle {
+    pub reason: i32,
+    /// The priority of the isolate during the GC cycle. None denotes a mixed
+    /// priority cycle, i.e. the priority changed while the cycle was in
+    /// progress.
+    pub priority: Option<i32>,
+    pub total_wall_clock_duration_in_us: i64,
+    pub main_thread_wall_clock_duration_in_us: i64,
+    pub collection_rate_in_percent: f64,
+    pub efficiency_in_bytes_per_us: f64,
+    pub main_thread_efficiency_in_bytes_per_us: f64,
+    /// The C++ heap statistics stay unset unless cppgc has a young
+    /// generation.
+    pub total_cpp: GarbageCollectionPhases,
+    pub objects_cpp: GarbageCollectionSizes,
+    pub memory_cpp: GarbageCollectionSizes,
+    pub collection_rate_cpp_in_percent: f64,
+    pub efficiency_cpp_in_bytes_per_us: f64,
+    pub main_thread_efficiency_cpp_in_bytes_per_us: f64,
+}
+
+impl GarbageCollectionYoungCycle {
+    pub const fn new() -> Self {
+        GarbageCollectionYoungCycle {
+            reason: -1,
+            priority: None,
+            total_wall_clock_duration_in_us: -1,
+            main_thread_wall_clock_duration_in_us: -1,
+            collection_rate_in_percent: -1.0,
+            efficiency_in_bytes_per_us: -1.0,
+            main_thread_efficiency_in_bytes_per_us: -1.0,
+            total_cpp: GarbageCollectionPhases::new(),
+            objects_cpp: GarbageCollectionSizes::new(),
+            memory_cpp: GarbageCollectionSizes::new(),
+            collection_rate_cpp_in_percent: -1.0,
+            efficiency_cpp_in_bytes_per_us: -1.0,
+            main_thread_efficiency_cpp_in_bytes_per_us: -1.0,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct WasmModuleCompiled {
-        pub async_flag: bool,
-        pub streamed: bool,
-        pub cached: bool,
-        pub deserialized: bool,
-        pub lazy: bool,
-        pub success: bool,
-        pub code_size_in_bytes: usize,
-        pub liftoff_bailout_count: usize,
-        pub wall_clock_duration_in_us: i64,
-    }
-
-    impl WasmModuleCompiled {
-        pub fn new(
-            async_flag: bool,
-            streamed: bool,
-            cached: bool,
-            deserialized: bool,
-            lazy: bool,
-            success: bool,
-            code_size_in_bytes: usize,
-            liftoff_bailout_count: usize,
-            wall_clock_duration_in_us: i64,
-        ) -> Self {
-            WasmModuleCompiled {
-                async_flag,
-                streamed,
-                cached,
-                deserialized,
-                lazy,
-                success,
-                code_size_in_bytes,
-                liftoff_bailout_count,
-                wall_clock_duration_in_us,
-            }
+impl Default for GarbageCollectionYoungCycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmModuleDecoded {
+    pub async_flag: bool,
+    pub streamed: bool,
+    pub success: bool,
+    pub module_size_in_bytes: usize,
+    pub function_count: usize,
+    pub wall_clock_duration_in_us: i64,
+}
+
+impl WasmModuleDecoded {
+    pub fn new(
+        async_flag: bool,
+        streamed: bool,
+        success: bool,
+        module_size_in_bytes: usize,
+        function_count: usize,
+        wall_clock_duration_in_us: i64,
+    ) -> Self {
+        WasmModuleDecoded {
+            async_flag,
+            streamed,
+            success,
+            module_size_in_bytes,
+            function_count,
+            wall_clock_duration_in_us,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct WasmModuleInstantiated {
-        pub async_flag: bool,
-        pub success: bool,
-        pub imported_function_count: usize,
-        pub wall_clock_duration_in_us: i64,
-    }
-
-    impl WasmModuleInstantiated {
-        pub fn new(
-            async_flag: bool,
-            success: bool,
-            imported_function_count: usize,
-            wall_clock_duration_in_us: i64,
-        ) -> Self {
-            WasmModuleInstantiated {
-                async_flag,
-                success,
-                imported_function_count,
-                wall_clock_duration_in_us,
-            }
+impl Default for WasmModuleDecoded {
+    fn default() -> Self {
+        Self::new(false, false, false, 0, 0, -1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmModuleCompiled {
+    pub async_flag: bool,
+    pub streamed: bool,
+    pub cached: bool,
+    pub deserialized: bool,
+    pub lazy: bool,
+    pub success: bool,
+    pub code_size_in_bytes: usize,
+    pub liftoff_bailout_count: usize,
+    pub wall_clock_duration_in_us: i64,
+}
+
+impl WasmModuleCompiled {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        async_flag: bool,
+        streamed: bool,
+        cached: bool,
+        deserialized: bool,
+        lazy: bool,
+        success: bool,
+        code_size_in_bytes: usize,
+        liftoff_bailout_count: usize,
+        wall_clock_duration_in_us: i64,
+    ) -> Self {
+        WasmModuleCompiled {
+            async_flag,
+            streamed,
+            cached,
+            deserialized,
+            lazy,
+            success,
+            code_size_in_bytes,
+            liftoff_bailout_count,
+            wall_clock_duration_in_us,
         }
     }
+}
 
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct WasmModulesPerIsolate {
-        pub count: usize,
+impl Default for WasmModuleCompiled {
+    fn default() -> Self {
+        Self::new(false, false, false, false, false, false, 0, 0, -1)
     }
-
-    impl WasmModulesPerIsolate {
-        pub fn new() -> Self {
-            WasmModulesPerIsolate { count: 0 }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmModuleInstantiated {
+    pub async_flag: bool,
+    pub success: bool,
+    pub imported_function_count: usize,
+    pub wall_clock_duration_in_us: i64,
+}
+
+impl WasmModuleInstantiated {
+    pub fn new(
+        async_flag: bool,
+        success: bool,
+        imported_function_count: usize,
+        wall_clock_duration_in_us: i64,
+    ) -> Self {
+        WasmModuleInstantiated {
+            async_flag,
+            success,
+            imported_function_count,
+            wall_clock_duration_in_us,
         }
     }
+}
 
-    /// This class serves as a base class for recording event-based metrics.
-    pub trait Recorder {
-        /// Adds a main thread event.
-        fn add_main_thread_event_garbage_collection_full_cycle(&mut self, event: &GarbageCollectionFullCycle, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_garbage_collection_full_main_thread_incremental_mark(&mut self, event: &GarbageCollectionFullMainThreadIncrementalMark, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_garbage_collection_full_main_thread_batched_incremental_mark(&mut self, event: &GarbageCollectionFullMainThreadBatchedIncrementalMark, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_garbage_collection_full_main_thread_incremental_sweep(&mut self, event: &GarbageCollectionFullMainThreadIncrementalSweep, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_garbage_collection_full_main_thread_batched_incremental_sweep(&mut self, event: &GarbageCollectionFullMainThreadBatchedIncrementalSweep, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_garbage_collection_young_cycle(&mut self, event: &GarbageCollectionYoungCycle, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_wasm_module_decoded(&mut self, event: &WasmModuleDecoded, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_wasm_module_compiled(&mut self, event: &WasmModuleCompiled, context_id: ContextId) {}
-        /// Adds a main thread event.
-        fn add_main_thread_event_wasm_module_instantiated(&mut self, event: &WasmModuleInstantiated, context_id: ContextId) {}
-
-        /// Adds a thread-safe event.
-        fn add_thread_safe_event_wasm_modules_per_isolate(&mut self, event: &WasmModulesPerIsolate) {}
-
-        /// Notifies isolate disposal.
-        fn notify_isolate_disposal(&mut self) {}
-
-        // These functions are not directly translatable without `v8::Isolate` and `v8::Context`
-        // They are left as unimplemented stubs.
-        // Return the context with the given id or an empty handle if the context
-        // was already garbage collected.
-        //static MaybeLocal<Context> GetContext(Isolate* isolate, ContextId id);
-        // Return the unique id corresponding to the given context.
-        //static ContextId GetContextId(Local<Context> context);
-    }
-
-    /// A unique identifier for a context in this Isolate.
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub struct ContextId {
-        id_: usize,
-    }
-
-    impl ContextId {
-        pub fn new() -> Self {
-            ContextId { id_: Self::k_empty_id() }
-        }
+impl Default for WasmModuleInstantiated {
+    fn default() -> Self {
+        Self::new(false, false, 0, -1)
+    }
+}
 
-        pub fn is_empty(&self) -> bool {
-            self.id_ == Self::k_empty_id()
-        }
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmModulesPerIsolate {
+    pub count: usize,
+}
 
-        pub fn empty() -> Self {
-            ContextId { id_: Self::k_empty_id() }
-        }
+impl WasmModulesPerIsolate {
+    pub fn new() -> Self {
+        WasmModulesPerIsolate { count: 0 }
+    }
+}
+
+/// Receives event-based metrics. Main thread events are delivered in the
+/// order they happened, but not necessarily on the thread that produced
+/// them, and thread-safe events may arrive on any thread; implementations
+/// therefore have to be `Send + Sync`. All methods default to ignoring the
+/// event.
+pub trait Recorder: Send + Sync {
+    fn add_main_thread_event_garbage_collection_full_cycle(
+        &self,
+        _event: &GarbageCollectionFullCycle,
+        _context_id: ContextId,
+    ) {
+    }
+    fn add_main_thread_event_garbage_collection_full_main_thread_incremental_mark(
+        &self,
+        _event: &GarbageCollectionFullMainThreadIncrementalMark,
+        _context_id: ContextId,
+    ) {
+    }
+    fn add_main_thread_event_garbage_collection_full_main_thread_batched_incremental_mark(
+        &self,
+        _event: &GarbageCollectionFullMainThreadBatchedIncrementalMark,
+        _context_id: ContextId,
+    ) {
+    }
+    fn add_main_thread_event_garbage_collection_full_main_thread_incremental_sweep(
+        &self,
+        _event: &GarbageCollectionFullMainThreadIncrementalSweep,
+        _context_id: ContextId,
+    ) {
+    }
+    fn add_main_thread_event_garbage_collection_full_main_thread_batched_incremental_sweep(
+        &self,
+        _event: &GarbageCollectionFullMainThreadBatchedIncrementalSweep,
+        _context_id: ContextId,
+    ) {
+    }
+    fn add_main_thread_event_garbage_collection_young_cycle(
+        &self,
+        _event: &GarbageCollectionYoungCycle,
+        _context_id: ContextId,
+    ) {
+    }
+    fn add_main_thread_event_wasm_module_decoded(&self, _event: &WasmModuleDecoded, _context_id: ContextId) {}
+    fn add_main_thread_event_wasm_module_compiled(&self, _event: &WasmModuleCompiled, _context_id: ContextId) {}
+    fn add_main_thread_event_wasm_module_instantiated(&self, _event: &WasmModuleInstantiated, _context_id: ContextId) {}
+
+    fn add_thread_safe_event_wasm_modules_per_isolate(&self, _event: &WasmModulesPerIsolate) {}
+
+    /// Called when the isolate the recorder is attached to is disposed.
+    /// No events are delivered afterwards.
+    fn notify_isolate_disposal(&self) {}
+}
+
+/// Identifies a context of an isolate in recorded events. Ids are assigned
+/// by the isolate's metrics recorder and are never reused, so an embedder
+/// can keep per-context state keyed by them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ContextId {
+    id_: usize,
+}
+
+impl ContextId {
+    const K_EMPTY_ID: usize = 0;
+
+    pub const fn new() -> Self {
+        ContextId { id_: Self::K_EMPTY_ID }
+    }
 
-        const fn k_empty_id() -> usize {
-            0
-        }
+    pub const fn empty() -> Self {
+        Self::new()
+    }
 
-        // Private constructor only used within the v8 module
-        // pub(crate) fn from_usize(id: usize) -> Self {
-        //     ContextId { id_: id }
-        // }
+    pub(crate) const fn from_id(id: usize) -> Self {
+        ContextId { id_: id }
+    }
 
-        // Method to get the internal id_
-        pub fn get_id(&self) -> usize {
-            self.id_
-        }
+    pub fn is_empty(&self) -> bool {
+        self.id_ == Self::K_EMPTY_ID
     }
 
-    impl Default for ContextId {
-        fn default() -> Self {
-            Self::new()
-        }
+    pub fn get_id(&self) -> usize {
+        self.id_
     }
+}
 
-    /// Experimental API intended for the LongTasks UKM (crbug.com/1173527).
-    #[derive(Default, Debug, Clone, Copy)]
-    pub struct LongTaskStats {
-        pub gc_full_atomic_wall_clock_duration_us: i64,
-        pub gc_full_incremental_wall_clock_duration_us: i64,
-        pub gc_young_wall_clock_duration_us: i64,
-        // Only collected with --slow-histograms
-        pub v8_execute_us: i64,
-    }
-
-    impl LongTaskStats {
-        /// Resets durations of V8 work for the new task.
-        // V8_INLINE static void Reset(Isolate* isolate) {
-        //   v8::internal::Internals::IncrementLongTasksStatsCounter(isolate);
-        // }
-        // Requires v8::internal::Internals and Isolate*, so this is a placeholder
-        pub fn reset() {}
-
-        /// Returns durations of V8 work that happened since the last Reset().
-        //static LongTaskStats Get(Isolate* isolate);
-        // Requires Isolate*, so this is a placeholder
-        pub fn get() -> Self {
-            LongTaskStats {
-                gc_full_atomic_wall_clock_duration_us: 0,
-                gc_full_incremental_wall_clock_duration_us: 0,
-                gc_young_wall_clock_duration_us: 0,
-                v8_execute_us: 0,
-            }
-        }
+impl Default for ContextId {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
+
+/// Experimental API intended for the LongTasks UKM (crbug.com/1173527).
+#[derive(Default, Debug, Clone, Copy)]
+pub struct LongTaskStats {
+    pub gc_full_atomic_wall_clock_duration_us: i64,
+    pub gc_full_incremental_wall_clock_duration_us: i64,
+    pub gc_young_wall_clock_duration_us: i64,
+    /// Only collected with --slow-histograms.
+    pub v8_execute_us: i64,
+}
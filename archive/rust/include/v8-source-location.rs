@@ -31,7 +31,7 @@ impl SourceLocation {
         feature = "source_location"
     )))]
     pub const fn current() -> Self {
-        SourceLocation::default()
+        SourceLocation::new()
     }
 
     /// Constructs unspecified source location information.
@@ -64,14 +64,14 @@ impl SourceLocation {
     pub const fn line(&self) -> usize {
         self.line_
     }
+}
 
-    /// Returns a human-readable string representing this object.
-    ///
-    /// \returns a human-readable string representing source location information.
-    pub fn to_string(&self) -> String {
+/// A human-readable string representing source location information.
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match (self.function_, self.file_) {
-            (Some(function), Some(file)) => format!("{}@{}::{}", function, file, self.line_),
-            _ => String::new(),
+            (Some(function), Some(file)) => write!(f, "{}@{}::{}", function, file, self.line_),
+            _ => Ok(()),
         }
     }
 }
\ No newline at end of file
@@ -3,250 +3,182 @@
 // found in the LICENSE file.
 
 pub mod v8_traced_handle {
-    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::fmt;
     use std::marker::PhantomData;
-    // use std::mem::size_of;
+    use std::sync::{Arc, OnceLock};
 
-    // Placeholder types and constants.  Need to import/define these properly.
-    pub type Address = *mut u8;
+    use crate::handles::traced_handles::TracedNode;
+    use crate::heap::cppgc_js::unified_heap_marking_state::traced_reference_barrier;
+    use crate::heap::heap::Heap;
+    use crate::heap::heap_layout::HeapObject;
+    use crate::include::cppgc::trace_trait::Trace;
+    use crate::include::cppgc::visitor::Visitor;
 
-    pub trait Value {}
-
-    pub trait Isolate {
-        fn cast_to_internal(&self) -> *mut InternalIsolate;
-    }
-
-    pub struct InternalIsolate {}
-
-    pub struct Local<'a, T> {
-        _phantom: PhantomData<&'a T>,
-        ptr: *mut T, // For demonstration purposes, replace with proper handle type if needed.
-    }
-
-    impl<'a, T> Local<'a, T> {
-        pub fn new(_isolate: &dyn Isolate, value: &TracedReferenceBase) -> Self {
-            // Placeholder implementation:
-            Local {
-                _phantom: PhantomData,
-                ptr: value.value::<T>() as *mut T,
-            }
-        }
-
-        pub fn is_empty(&self) -> bool {
-            self.ptr.is_null()
-        }
-    }
-
-    // Placeholder implementation of the New function.
-    impl<'a, T> Local<'a, T> {
-        pub fn New(_isolate: &dyn Isolate, value: *mut T) -> Self {
-            Local {
-                _phantom: PhantomData,
-                ptr: value
-            }
-        }
-    }
-
-    #[derive(PartialEq, Eq, Copy, Clone)]
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
     pub enum TracedReferenceStoreMode {
         kInitializingStore,
         kAssigningStore,
     }
 
-    #[derive(PartialEq, Eq, Copy, Clone)]
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
     pub enum TracedReferenceHandling {
         kDefault,
         kDroppable,
     }
 
-    extern "C" {
-        pub fn GlobalizeTracedReference(
-            isolate: *mut InternalIsolate,
-            value: Address,
-            slot: *mut Address,
-            store_mode: TracedReferenceStoreMode,
-            reference_handling: TracedReferenceHandling,
-        ) -> *mut Address;
-        pub fn MoveTracedReference(from: *mut *mut Address, to: *mut *mut Address);
-        pub fn CopyTracedReference(from: *const *const Address, to: *mut *mut Address);
-        pub fn DisposeTracedReference(global_handle: *mut Address);
-    }
-
+    /// Untyped part of `TracedReference`. The node is created on the first
+    /// non-empty store and may be read by concurrent markers.
     pub struct TracedReferenceBase {
-        slot_: AtomicPtr<Address>,
+        node: OnceLock<Arc<TracedNode>>,
     }
 
     impl TracedReferenceBase {
-        pub fn reset(&self) {
-            if self.is_empty() {
-                return;
-            }
-            unsafe {
-                DisposeTracedReference(self.slot_.load(Ordering::Relaxed));
+        pub const fn new() -> Self {
+            TracedReferenceBase {
+                node: OnceLock::new(),
             }
-            self.set_slot_thread_safe(std::ptr::null_mut());
         }
 
-        pub fn get<'a, Data>(&self, isolate: &dyn Isolate) -> Local<'a, Data> {
-            if self.is_empty() {
-                return Local { _phantom: PhantomData, ptr: std::ptr::null_mut() };
+        fn store(&self, heap: &Heap, object: Option<HeapObject>, _store_mode: TracedReferenceStoreMode) {
+            let node = match self.node.get() {
+                Some(node) => node,
+                None if object.is_none() => return,
+                None => self.node.get_or_init(|| heap.traced_handles().create(None)),
+            };
+            node.set_object(object);
+            // Both initializing and assigning stores may happen while the
+            // holder is already traced, so either needs the barrier.
+            if object.is_some() {
+                traced_reference_barrier(node.address());
             }
-            Local::<'a, Data>::New(isolate, self)
         }
 
-        pub fn is_empty(&self) -> bool {
-            self.get_slot_thread_safe().is_null()
+        /// Clears the reference.
+        pub fn reset(&self) {
+            if let Some(node) = self.node.get() {
+                node.set_object(None);
+            }
         }
 
-        fn set_slot_thread_safe(&self, new_val: *mut Address) {
-            self.slot_.store(new_val, Ordering::Relaxed);
+        pub fn is_empty(&self) -> bool {
+            self.is_empty_thread_safe()
         }
 
-        fn get_slot_thread_safe(&self) -> *mut Address {
-            self.slot_.load(Ordering::Relaxed)
+        pub fn is_empty_thread_safe(&self) -> bool {
+            self.get().is_none()
         }
 
-        pub fn value<Data>(&self) -> *mut Data {
-            self.get_slot_thread_safe() as *mut Data
+        /// Returns the referenced V8 object, if any.
+        pub fn get(&self) -> Option<HeapObject> {
+            self.node.get().and_then(|node| node.object())
         }
     }
 
-    impl TracedReferenceBase {
-        pub const ATOMIC_ADDRESS_SIZE_ASSERT: () = assert!(std::mem::size_of::<AtomicPtr<Address>>() == std::mem::size_of::<*mut Address>());
-
-        pub fn new() -> Self {
-            TracedReferenceBase {
-                slot_: AtomicPtr::new(std::ptr::null_mut()),
-            }
+    impl Default for TracedReferenceBase {
+        fn default() -> Self {
+            Self::new()
         }
-
-        // The CheckValue method doesn't have a direct equivalent in Rust, it's intended as a debug
-        // utility in C++. We skip its implementation here, but potentially can add runtime checks
-        // if needed.
-        // pub fn check_value(&self) {}
     }
 
-    pub struct BasicTracedReference<T> {
-        base: TracedReferenceBase,
-        _phantom: PhantomData<T>,
-    }
-
-    impl<T> BasicTracedReference<T> {
-        pub fn get<'a>(&self, isolate: &dyn Isolate) -> Local<'a, T> {
-            Local::<'a, T>::New(isolate, &self.base)
+    impl Drop for TracedReferenceBase {
+        fn drop(&mut self) {
+            // The node stays allocated until the next full garbage
+            // collection, but must no longer keep its object alive.
+            self.reset();
         }
     }
 
-    impl<T> BasicTracedReference<T> {
-        pub fn new() -> Self {
-            BasicTracedReference {
-                base: TracedReferenceBase::new(),
-                _phantom: PhantomData,
-            }
+    impl PartialEq for TracedReferenceBase {
+        fn eq(&self, other: &Self) -> bool {
+            self.get() == other.get()
         }
     }
 
-    impl<T> BasicTracedReference<T> {
-         unsafe fn new_from_non_empty_value(
-            isolate: *mut InternalIsolate,
-            that: *mut T,
-            slot: *mut *mut Address,
-            store_mode: TracedReferenceStoreMode,
-            reference_handling: TracedReferenceHandling,
-        ) -> *mut Address {
-            GlobalizeTracedReference(
-                isolate,
-                that as *mut u8, // Assuming T can be cast to a byte pointer.
-                slot,
-                store_mode,
-                reference_handling,
-            )
-        }
-
-         fn slot(&self) -> *mut *mut Address {
-            &self.base.slot_ as *const AtomicPtr<Address> as *mut *mut Address
+    impl Trace for TracedReferenceBase {
+        fn trace(&self, visitor: &mut Visitor<'_>) {
+            if let Some(node) = self.node.get()
+                && node.object().is_some()
+            {
+                visitor.visit_traced_reference(node.address());
+            }
         }
     }
 
+    /// A reference from a cppgc object to an object of type `T` on the V8
+    /// heap. It keeps its target alive during unified garbage collections
+    /// as long as the cppgc object holding it is alive, and must therefore
+    /// be traced by that object.
     pub struct TracedReference<T> {
-        base: BasicTracedReference<T>,
+        base: TracedReferenceBase,
+        _phantom: PhantomData<fn() -> T>,
     }
 
     impl<T> TracedReference<T> {
-        pub struct IsDroppable {}
-
-        pub fn new() -> Self {
+        /// Creates an empty reference.
+        pub const fn empty() -> Self {
             TracedReference {
-                base: BasicTracedReference::new(),
+                base: TracedReferenceBase::new(),
+                _phantom: PhantomData,
             }
         }
 
+        /// Creates a reference to `object` on `heap`.
+        pub fn new(heap: &Heap, object: HeapObject) -> Self {
+            let reference = Self::empty();
+            reference
+                .base
+                .store(heap, Some(object), TracedReferenceStoreMode::kInitializingStore);
+            reference
+        }
+
+        /// Points the reference to `object` on `heap`; `None` clears it. A
+        /// reference may only refer to objects of one heap.
+        pub fn set(&self, heap: &Heap, object: Option<HeapObject>) {
+            self.base
+                .store(heap, object, TracedReferenceStoreMode::kAssigningStore);
+        }
+
         pub fn reset(&self) {
-            self.base.base.reset();
+            self.base.reset();
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.base.is_empty()
         }
 
-        pub fn get<'a>(&self, isolate: &dyn Isolate) -> Local<'a, T> {
-            self.base.get(isolate)
+        /// Returns the referenced V8 object, if any.
+        pub fn get(&self) -> Option<HeapObject> {
+            self.base.get()
         }
 
-        pub fn slot(&self) -> *mut *mut Address {
-            self.base.slot()
+        pub fn base(&self) -> &TracedReferenceBase {
+            &self.base
         }
     }
 
-    impl<T> TracedReference<T> {
-        pub fn from_local<'a, S: 'a + Value, I: Isolate>(isolate: &I, that: Local<'a, S>) -> Self
-        where
-            T: Value,
-            S: Value,
-        {
-            // static_assert(std::is_base_of::<T, S>::value, "type check"); // Can be checked with trait bounds
-            let mut traced_reference = TracedReference::new();
-            if that.is_empty() {
-                return traced_reference;
-            }
+    impl<T> Default for TracedReference<T> {
+        fn default() -> Self {
+            Self::empty()
+        }
+    }
 
-            let isolate_ptr = isolate.cast_to_internal();
-            unsafe {
-                *traced_reference.slot() = BasicTracedReference::new_from_non_empty_value(
-                    isolate_ptr,
-                    that.ptr as *mut T, // Need to ensure valid casting and lifetime
-                    traced_reference.slot(),
-                    TracedReferenceStoreMode::kInitializingStore,
-                    TracedReferenceHandling::kDefault,
-                );
-            }
-            traced_reference
-        }
-
-        pub fn from_local_droppable<'a, S: 'a + Value, I: Isolate>(isolate: &I, that: Local<'a, S>, _is_droppable: IsDroppable) -> Self
-        where
-            T: Value,
-            S: Value,
-        {
-            // static_assert(std::is_base_of::<T, S>::value, "type check"); // Can be checked with trait bounds
-            let mut traced_reference = TracedReference::new();
-            if that.is_empty() {
-                return traced_reference;
-            }
-            let isolate_ptr = isolate.cast_to_internal();
-
-            unsafe {
-                *traced_reference.slot() = BasicTracedReference::new_from_non_empty_value(
-                    isolate_ptr,
-                    that.ptr as *mut T, // Need to ensure valid casting and lifetime
-                    traced_reference.slot(),
-                    TracedReferenceStoreMode::kInitializingStore,
-                    TracedReferenceHandling::kDroppable,
-                );
-            }
-            traced_reference
+    impl<T> PartialEq for TracedReference<T> {
+        fn eq(&self, other: &Self) -> bool {
+            self.base == other.base
         }
     }
 
-    impl<T> Drop for TracedReference<T> {
-        fn drop(&mut self) {
-            self.reset();
+    impl<T> fmt::Debug for TracedReference<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "TracedReference({:?})", self.base.get())
+        }
+    }
+
+    impl<T> Trace for TracedReference<T> {
+        fn trace(&self, visitor: &mut Visitor<'_>) {
+            self.base.trace(visitor);
         }
     }
-}
\ No newline at end of file
+}
@@ -27,9 +27,11 @@
  * in a similar fashion to ENTER_V8.
  */
 
+use crate::logging::runtime_call_stats::RuntimeCallCounterId;
+
 macro_rules! api_rcs_scope {
     ($i_isolate:expr, $class_name:ident, $function_name:ident) => {
-        rcs_scope!($i_isolate, RuntimeCallCounterId::kAPI_$class_name_$function_name);
+        crate::rcs_scope!($i_isolate, RuntimeCallCounterId::kAPI_$class_name_$function_name);
     };
 }
 
@@ -192,6 +194,9 @@ macro_rules! return_escaped {
 // Placeholder modules and structs.  These would need to be fleshed out based
 // on the actual V8 implementation.
 mod i {
+    use crate::logging::runtime_call_stats::RuntimeCallStatsTables;
+    use crate::logging::runtime_call_stats_scope::HasRuntimeCallStats;
+
     pub struct Isolate {
         terminating: bool,
     }
@@ -203,6 +208,12 @@ mod i {
         }
     }
 
+    impl HasRuntimeCallStats for Isolate {
+        fn runtime_call_stats(&self) -> Option<&RuntimeCallStatsTables> {
+            None
+        }
+    }
+
     pub struct VMState<T> {
         _phantom: std::marker::PhantomData<T>,
     }
@@ -248,12 +259,6 @@ mod v8 {
     pub enum OTHER {}
 }
 
-enum RuntimeCallCounterId {
-    kAPI_ClassName_FunctionName, // Placeholder. Actual enum would go here.
-}
-
-fn rcs_scope(_isolate: &i::Isolate, _id: RuntimeCallCounterId) {}
-
 struct CallDepthScope<const DO_CALLBACK: bool> {
     _isolate: *mut i::Isolate,
     _context: *mut std::ffi::c_void, // Placeholder.  Should be actual context type.
@@ -2,234 +2,226 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
-use crate::base::region_allocator::RegionAllocator;
-use crate::base::virtual_address_space::VirtualAddressSpace;
-use crate::base::platform::memory::PagePermissions;
-use crate::base::platform::shared_memory::PlatformSharedMemoryHandle;
-use rand::{Rng, SeedableRng, rngs::SmallRng};
-
-pub mod base {
-    pub mod platform {
-        pub mod memory {
-            #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-            pub enum PagePermissions {
-                ReadWrite,
-                ReadExecute,
-                ReadWriteExecute,
-                ReadOnly,
-                NoAccess,
-            }
-        }
-        pub mod shared_memory {
-            pub type PlatformSharedMemoryHandle = usize;
-        }
-    }
-
-    pub mod region_allocator {
-        #[derive(Default)]
-        pub struct RegionAllocator {}
-
-        impl RegionAllocator {
-            pub fn new() -> Self {
-                RegionAllocator {}
-            }
-        }
-    }
-
-    pub mod virtual_address_space {
-        pub type Address = usize;
-
-        pub trait VirtualAddressSpace {
-            fn set_random_seed(&mut self, seed: i64);
-            fn random_page_address(&mut self) -> Address;
-            fn allocate_pages(&mut self, hint: Address, size: usize, alignment: usize, permissions: crate::base::platform::memory::PagePermissions) -> Option<Address>;
-            fn free_pages(&mut self, address: Address, size: usize);
-            fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: crate::base::platform::memory::PagePermissions, handle: crate::base::platform::shared_memory::PlatformSharedMemoryHandle, offset: u64) -> Option<Address>;
-            fn free_shared_pages(&mut self, address: Address, size: usize);
-            fn set_page_permissions(&mut self, address: Address, size: usize, permissions: crate::base::platform::memory::PagePermissions) -> bool;
-            fn allocate_guard_region(&mut self, address: Address, size: usize) -> bool;
-            fn free_guard_region(&mut self, address: Address, size: usize);
-            fn can_allocate_subspaces(&self) -> bool;
-            fn allocate_subspace(&mut self, hint: Address, size: usize, alignment: usize, max_page_permissions: crate::base::platform::memory::PagePermissions) -> Option<Box<dyn VirtualAddressSpace>>;
-            fn recommit_pages(&mut self, address: Address, size: usize, permissions: crate::base::platform::memory::PagePermissions) -> bool;
-            fn discard_system_pages(&mut self, address: Address, size: usize) -> bool;
-            fn decommit_pages(&mut self, address: Address, size: usize) -> bool;
-        }
-    }
-}
 
-type Address = usize;
-
-/// Emulates a virtual address subspace.
-///
-/// This class is (optionally) backed by a page allocation and emulates a virtual
-/// address space that is potentially larger than that mapping. It generally
-/// first attempts to satisfy page allocation requests from its backing mapping,
-/// but will also attempt to obtain new page mappings inside the unmapped space
-/// through page allocation hints if necessary.
-///
-/// Caveat: an emulated subspace violates the invariant that page allocations in
-/// an address space will never end up inside a child space and so does not
-/// provide the same security gurarantees.
+use crate::base::region_allocator::{RegionAllocator, RegionState};
+use crate::base::virtual_address_space::{Address, PagePermissions, VirtualAddressSpace, K_NULL_ADDRESS};
+
+/// Emulates a reserved subspace of `total_size` bytes when only the first
+/// `mapped_size` bytes can actually be reserved, for example because the
+/// address space is limited. Allocations in the mapped part are managed
+/// here; allocations in the unmapped part are requested from the parent
+/// space with random hints inside that part, and rejected when the parent
+/// places them elsewhere. Nothing prevents other code from mapping memory
+/// in the unmapped part, so this offers weaker guarantees than a real
+/// subspace.
 pub struct EmulatedVirtualAddressSubspace {
-    mapped_size_: usize,
-    parent_space_: *mut dyn VirtualAddressSpace, // Raw pointer to avoid ownership issues.  Lifetime management is external.
-    mutex_: Mutex<()>,
-    region_allocator_: RegionAllocator,
-    rng_: SmallRng,
-    base_: Address,
-    total_size_: usize,
+    base: Address,
+    size: usize,
+    mapped_size: usize,
+    parent_space: &'static dyn VirtualAddressSpace,
+    region_allocator: Mutex<RegionAllocator>,
+    random_state: AtomicU64,
 }
 
 impl EmulatedVirtualAddressSubspace {
-    /// Construct an emulated virtual address subspace of the specified total size,
-    /// potentially backed by a page allocation from the parent space. The newly
-    /// created instance takes ownership of the page allocation (if any) and frees
-    /// it during destruction.
-    pub fn new(parent_space: *mut dyn VirtualAddressSpace, base: Address, mapped_size: usize, total_size: usize) -> Self {
+    /// How often to try placing an allocation in the unmapped part.
+    const K_MAX_RANDOM_ALLOCATION_ATTEMPTS: usize = 10;
+
+    /// Takes ownership of `[base, base + mapped_size)`, which must have been
+    /// allocated from `parent_space` as inaccessible pages.
+    pub fn new(parent_space: &'static dyn VirtualAddressSpace, base: Address, mapped_size: usize, total_size: usize) -> Self {
+        let granularity = parent_space.allocation_granularity();
+        assert!(mapped_size > 0 && mapped_size <= total_size);
+        assert_eq!(base % granularity, 0);
+        assert_eq!(mapped_size % granularity, 0);
+        assert_eq!(total_size % granularity, 0);
         EmulatedVirtualAddressSubspace {
-            mapped_size_: mapped_size,
-            parent_space_: parent_space,
-            mutex_: Mutex::new(()),
-            region_allocator_: RegionAllocator::new(),
-            rng_: SmallRng::from_entropy(),
-            base_: base,
-            total_size_: total_size,
+            base,
+            size: total_size,
+            mapped_size,
+            parent_space,
+            region_allocator: Mutex::new(RegionAllocator::new(base, mapped_size, granularity)),
+            random_state: AtomicU64::new(base as u64 | 1),
         }
     }
 
-    fn mapped_size(&self) -> usize {
-        self.mapped_size_
+    pub fn mapped_size(&self) -> usize {
+        self.mapped_size
     }
 
-    fn unmapped_size(&self) -> usize {
-        self.total_size_ - self.mapped_size_
+    fn unmapped_base(&self) -> Address {
+        self.base + self.mapped_size
     }
 
-    fn mapped_base(&self) -> Address {
-        self.base_
+    fn unmapped_size(&self) -> usize {
+        self.size - self.mapped_size
     }
 
-    fn unmapped_base(&self) -> Address {
-        self.base_ + self.mapped_size_
+    fn mapped_region_contains(&self, address: Address, size: usize) -> bool {
+        address >= self.base && address + size <= self.unmapped_base()
     }
 
-    fn contains(&self, outer_start: Address, outer_size: usize, inner_start: Address, inner_size: usize) -> bool {
-        (inner_start >= outer_start) &&
-            ((inner_start + inner_size) <= (outer_start + outer_size))
+    fn unmapped_region_contains(&self, address: Address, size: usize) -> bool {
+        address >= self.unmapped_base() && address + size <= self.base + self.size
     }
 
-    fn contains_addr(&self, addr: Address, length: usize) -> bool {
-        self.contains(self.base_, self.total_size_, addr, length)
+    fn next_random(&self) -> u64 {
+        // splitmix64
+        let state = self.random_state.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+        let mut random = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        random = (random ^ (random >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        random = (random ^ (random >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        random ^ (random >> 31)
     }
 
-    fn mapped_region_contains(&self, addr: Address, length: usize) -> bool {
-        self.contains(self.mapped_base(), self.mapped_size(), addr, length)
+    fn random_address_in_unmapped_region(&self, alignment: usize) -> Address {
+        let granules = self.unmapped_size() / alignment;
+        let address = self.unmapped_base() + (self.next_random() as usize % granules) * alignment;
+        address & !(alignment - 1)
     }
+}
 
-    fn unmapped_region_contains(&self, addr: Address, length: usize) -> bool {
-        self.contains(self.unmapped_base(), self.unmapped_size(), addr, length)
+impl VirtualAddressSpace for EmulatedVirtualAddressSubspace {
+    fn page_size(&self) -> usize {
+        self.parent_space.page_size()
     }
 
-    /// Helper function to define a limit for the size of allocations in the
-    /// unmapped region. This limit makes it possible to estimate the expected
-    /// runtime of some loops in the Allocate methods.
-    fn is_usable_size_for_unmapped_region(&self, size: usize) -> bool {
-        size <= (self.unmapped_size() / 2)
+    fn allocation_granularity(&self) -> usize {
+        self.parent_space.allocation_granularity()
     }
-}
 
-impl Drop for EmulatedVirtualAddressSubspace {
-    fn drop(&mut self) {
-        // No explicit deallocation is performed here, assuming the parent
-        // VirtualAddressSpace is responsible for managing the underlying memory.
-        // The `parent_space_` is a raw pointer and its lifetime is managed elsewhere.
+    fn base(&self) -> Address {
+        self.base
     }
-}
 
-impl VirtualAddressSpace for EmulatedVirtualAddressSubspace {
-    fn set_random_seed(&mut self, seed: i64) {
-        self.rng_ = SmallRng::seed_from_u64(seed as u64);
+    fn size(&self) -> usize {
+        self.size
     }
 
-    fn random_page_address(&mut self) -> Address {
-        let _lock = self.mutex_.lock().unwrap();
-        self.base_ + self.rng_.gen_range(0..self.total_size_)
+    fn max_page_permissions(&self) -> PagePermissions {
+        self.parent_space.max_page_permissions()
     }
 
-    fn allocate_pages(&mut self, hint: Address, size: usize, alignment: usize, permissions: PagePermissions) -> Option<Address> {
-        let _lock = self.mutex_.lock().unwrap();
-        // This is a placeholder.  Needs actual allocation logic.
-        // RegionAllocator needs to be integrated properly.
-        // Also, interaction with the parent space is required.
-        if self.is_usable_size_for_unmapped_region(size) {
-            Some(self.unmapped_base() + self.rng_.gen_range(0..self.unmapped_size() - size))
-        } else if self.mapped_region_contains(hint, size) {
-            // Allocate from mapped region using RegionAllocator
-            Some(self.mapped_base() + self.rng_.gen_range(0..self.mapped_size() - size))
-        } else {
-            None
-        }
+    fn set_random_seed(&self, seed: i64) {
+        self.random_state.store(seed as u64 | 1, Ordering::Relaxed);
     }
 
-    fn free_pages(&mut self, address: Address, size: usize) {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs actual deallocation logic using RegionAllocator
+    fn random_page_address(&self) -> Address {
+        let pages = self.size / self.allocation_granularity();
+        self.base + (self.next_random() as usize % pages) * self.allocation_granularity()
     }
 
-    fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: PlatformSharedMemoryHandle, offset: u64) -> Option<Address> {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific shared memory allocation logic.
+    fn allocate_pages(&self, hint: Address, size: usize, alignment: usize, permissions: PagePermissions) -> Option<Address> {
+        if hint == K_NULL_ADDRESS || self.mapped_region_contains(hint, size) {
+            let mut regions = self.region_allocator.lock().unwrap();
+            let mut address = RegionAllocator::K_ALLOCATION_FAILURE;
+            if hint != K_NULL_ADDRESS && hint.is_multiple_of(alignment) && regions.allocate_region_at(hint, size, RegionState::Allocated) {
+                address = hint;
+            }
+            if address == RegionAllocator::K_ALLOCATION_FAILURE {
+                address = regions.allocate_aligned_region(size, alignment);
+            }
+            if address != RegionAllocator::K_ALLOCATION_FAILURE {
+                drop(regions);
+                if permissions == PagePermissions::kNoAccess
+                    || self.parent_space.set_page_permissions(address, size, permissions)
+                {
+                    return Some(address);
+                }
+                self.region_allocator.lock().unwrap().free_region(address);
+                return None;
+            }
+        }
+
+        // Try the unmapped part, where the parent may or may not honour our
+        // hints.
+        if size > self.unmapped_size() {
+            return None;
+        }
+        let mut hint = if self.unmapped_region_contains(hint, size) {
+            hint
+        } else {
+            self.random_address_in_unmapped_region(alignment)
+        };
+        for _ in 0..Self::K_MAX_RANDOM_ALLOCATION_ATTEMPTS {
+            if let Some(address) = self.parent_space.allocate_pages(hint, size, alignment, permissions) {
+                if self.unmapped_region_contains(address, size) {
+                    return Some(address);
+                }
+                self.parent_space.free_pages(address, size);
+            }
+            hint = self.random_address_in_unmapped_region(alignment);
+        }
         None
     }
 
-    fn free_shared_pages(&mut self, address: Address, size: usize) {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific shared memory deallocation logic.
+    fn free_pages(&self, address: Address, size: usize) {
+        if self.mapped_region_contains(address, size) {
+            assert_eq!(self.region_allocator.lock().unwrap().free_region(address), size);
+            assert!(self.parent_space.decommit_pages(address, size));
+        } else {
+            debug_assert!(self.unmapped_region_contains(address, size));
+            self.parent_space.free_pages(address, size);
+        }
     }
 
-    fn set_page_permissions(&mut self, address: Address, size: usize, permissions: PagePermissions) -> bool {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific page permission setting logic.
-        false
+    fn set_page_permissions(&self, address: Address, size: usize, permissions: PagePermissions) -> bool {
+        debug_assert!(self.contains(address));
+        self.parent_space.set_page_permissions(address, size, permissions)
     }
 
-    fn allocate_guard_region(&mut self, address: Address, size: usize) -> bool {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific guard region allocation logic.
-        false
+    fn allocate_guard_region(&self, address: Address, size: usize) -> bool {
+        if self.mapped_region_contains(address, size) {
+            return self
+                .region_allocator
+                .lock()
+                .unwrap()
+                .allocate_region_at(address, size, RegionState::Excluded);
+        }
+        self.unmapped_region_contains(address, size) && self.parent_space.allocate_guard_region(address, size)
     }
 
-    fn free_guard_region(&mut self, address: Address, size: usize) {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific guard region deallocation logic.
+    fn free_guard_region(&self, address: Address, size: usize) {
+        if self.mapped_region_contains(address, size) {
+            assert_eq!(self.region_allocator.lock().unwrap().free_region(address), size);
+        } else {
+            self.parent_space.free_guard_region(address, size);
+        }
     }
 
     fn can_allocate_subspaces(&self) -> bool {
-        true
+        // Subspaces would have to be reserved in the unmapped part, where
+        // nothing can be reserved.
+        false
     }
 
-    fn allocate_subspace(&mut self, hint: Address, size: usize, alignment: usize, max_page_permissions: PagePermissions) -> Option<Box<dyn VirtualAddressSpace>> {
-        // Placeholder. Needs logic for allocating subspaces.  This is likely recursive and needs care to avoid stack overflows.
+    fn allocate_subspace(
+        &self,
+        _hint: Address,
+        _size: usize,
+        _alignment: usize,
+        _max_page_permissions: PagePermissions,
+    ) -> Option<Box<dyn VirtualAddressSpace>> {
         None
     }
 
-    fn recommit_pages(&mut self, address: Address, size: usize, permissions: PagePermissions) -> bool {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific page recommit logic.
-        false
+    fn recommit_pages(&self, address: Address, size: usize, permissions: PagePermissions) -> bool {
+        self.parent_space.recommit_pages(address, size, permissions)
     }
 
-    fn discard_system_pages(&mut self, address: Address, size: usize) -> bool {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific page discard logic.
-        false
+    fn discard_system_pages(&self, address: Address, size: usize) -> bool {
+        self.parent_space.discard_system_pages(address, size)
     }
 
-    fn decommit_pages(&mut self, address: Address, size: usize) -> bool {
-        let _lock = self.mutex_.lock().unwrap();
-        // Placeholder. Needs platform specific page decommit logic.
-        false
+    fn decommit_pages(&self, address: Address, size: usize) -> bool {
+        self.parent_space.decommit_pages(address, size)
     }
-}
\ No newline at end of file
+}
+
+impl Drop for EmulatedVirtualAddressSubspace {
+    fn drop(&mut self) {
+        self.parent_space.free_pages(self.base, self.mapped_size);
+    }
+}
@@ -63,3 +63,4 @@ pub mod overflowing-math;
 pub mod iterator;
 pub mod ubsan;
 pub mod timezone-cache;
+pub mod utils;
@@ -8,23 +8,18 @@ pub mod diy_fp {
     /// have the most significant bit of the significand set.
     /// Multiplication and Subtraction do not normalize their results.
     /// DiyFp are not designed to contain special doubles (NaN and Infinity).
-    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
     pub struct DiyFp {
         f: u64,
         e: i32,
     }
 
     impl DiyFp {
-        pub const SIGNIFICAND_SIZE: i32 = 64;
+        pub const K_SIGNIFICAND_SIZE: i32 = 64;
         const K_UINT64_MSB: u64 = 1 << 63;
 
-        /// Creates a new DiyFp with f = 0 and e = 0.
-        pub const fn new() -> Self {
-            DiyFp { f: 0, e: 0 }
-        }
-
         /// Creates a new DiyFp with the given significand and exponent.
-        pub const fn new_with_values(f: u64, e: i32) -> Self {
+        pub const fn new(f: u64, e: i32) -> Self {
             DiyFp { f, e }
         }
 
@@ -49,28 +44,21 @@ pub mod diy_fp {
             result
         }
 
-        /// Multiplies `self` by `other`.
+        /// Multiplies `self` by `other`, keeping the upper 64 bits of the
+        /// product rounded to nearest.
         pub fn multiply(&mut self, other: &DiyFp) {
-            let a = self.f;
-            let b = other.f;
-
-            let (hi, lo) = multiply_u64_by_u64(a, b);
-
-            self.f = hi + (lo >> 63);
+            let product = self.f as u128 * other.f as u128;
+            // Emulate the rounding of the 128-bit product to 64 bits by adding
+            // half a unit of the dropped lower half.
+            self.f = ((product + (1u128 << 63)) >> 64) as u64;
             self.e += other.e + 64;
         }
 
         /// Returns `a * b`.
         pub fn times(a: &DiyFp, b: &DiyFp) -> DiyFp {
-            let a_f = a.f;
-            let b_f = b.f;
-
-            let (hi, lo) = multiply_u64_by_u64(a_f, b_f);
-
-            DiyFp {
-                f: hi + (lo >> 63),
-                e: a.e + b.e + 64,
-            }
+            let mut result = *a;
+            result.multiply(b);
+            result
         }
 
         /// Normalizes the DiyFp number.
@@ -114,40 +102,13 @@ pub mod diy_fp {
         }
 
         /// Sets the significand.
-        pub const fn set_f(&mut self, new_value: u64) {
+        pub fn set_f(&mut self, new_value: u64) {
             self.f = new_value;
         }
 
         /// Sets the exponent.
-        pub const fn set_e(&mut self, new_value: i32) {
+        pub fn set_e(&mut self, new_value: i32) {
             self.e = new_value;
         }
     }
-
-    fn multiply_u64_by_u64(a: u64, b: u64) -> (u64, u64) {
-        let a_hi = a >> 32;
-        let a_lo = a & 0xFFFFFFFF;
-        let b_hi = b >> 32;
-        let b_lo = b & 0xFFFFFFFF;
-
-        let hi_hi = a_hi * b_hi;
-        let hi_lo = a_hi * b_lo;
-        let lo_hi = a_lo * b_hi;
-        let lo_lo = a_lo * b_lo;
-
-        let mut carry = 0;
-        let lo = lo_lo.wrapping_add((hi_lo << 32) & 0xFFFFFFFF00000000);
-        if lo < lo_lo {
-            carry += 1;
-        }
-
-        let lo = lo.wrapping_add((lo_hi << 32) & 0xFFFFFFFF00000000);
-        if lo < (lo - ((lo_hi << 32) & 0xFFFFFFFF00000000)) {
-            carry += 1;
-        }
-
-        let hi = hi_hi + (hi_lo >> 32) + (lo_hi >> 32) + carry;
-
-        (hi, lo)
-    }
-}
\ No newline at end of file
+}
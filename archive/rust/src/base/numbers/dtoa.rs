@@ -4,7 +4,13 @@
 
 /// DTOA module for double-to-ASCII conversion.
 pub mod dtoa {
+    use crate::base::numbers::bignum_dtoa::bignum_dtoa::{bignum_dtoa, BignumDtoaMode};
+    use crate::base::numbers::double::double::Double;
+    use crate::base::numbers::fast_dtoa::fast_dtoa::{fast_dtoa, FastDtoaMode};
+    use crate::base::numbers::fixed_dtoa::fixed_dtoa::fast_fixed_dtoa;
+
     /// Represents the different modes for double-to-ASCII conversion.
+    #[allow(non_camel_case_types)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum DtoaMode {
         /// Return the shortest correct representation.
@@ -18,68 +24,242 @@ pub mod dtoa {
     /// The maximal length of digits a double can have in base 10.
     pub const K_BASE10_MAXIMAL_LENGTH: usize = 17;
 
+    fn dtoa_to_bignum_dtoa_mode(dtoa_mode: DtoaMode) -> BignumDtoaMode {
+        match dtoa_mode {
+            DtoaMode::DTOA_SHORTEST => BignumDtoaMode::Shortest,
+            DtoaMode::DTOA_FIXED => BignumDtoaMode::Fixed,
+            DtoaMode::DTOA_PRECISION => BignumDtoaMode::Precision,
+        }
+    }
+
     /// Converts the given double 'v' to ASCII.
     ///
-    /// The result should be interpreted as buffer * 10^(point-length).
+    /// The result should be interpreted as buffer * 10^(point-length). The
+    /// buffer is not null-terminated; only its first `length` bytes are valid.
     ///
-    /// # Arguments
+    /// The output depends on the given mode:
+    ///  - DTOA_SHORTEST: produce the least amount of digits for which the
+    ///    internal identity requirement is still satisfied. If the digits are
+    ///    printed (together with the correct exponent) then reading this
+    ///    number will give 'v' again. The buffer will choose the
+    ///    representation that is closest to 'v'. If there are two at the same
+    ///    distance, than the digits are rounded to even. In this mode the
+    ///    'requested_digits' parameter is ignored.
+    ///  - DTOA_FIXED: produces digits necessary to print a given number with
+    ///    'requested_digits' digits after the decimal point. The produced
+    ///    digits might be too short in which case the caller has to fill the
+    ///    gaps with '0's. Example: toFixed(0.001, 5) is allowed to return
+    ///    buffer="1", point=-2. Halfway cases are rounded towards +/-Infinity
+    ///    (away from 0). The call toFixed(0.15, 2) thus returns buffer="2",
+    ///    point=0.
+    ///  - DTOA_PRECISION: produces 'requested_digits' of significant digits.
+    ///    Halfway cases are rounded away from 0.
     ///
-    /// * `v` - The double to convert.
-    /// * `mode` - The conversion mode.
-    /// * `requested_digits` - The number of digits requested (depending on the mode).
-    /// * `buffer` - The buffer to write the ASCII representation to.
-    /// * `sign` - A mutable reference to an integer to store the sign of the number.
-    /// * `length` - A mutable reference to an integer to store the length of the buffer.
-    /// * `point` - A mutable reference to an integer to store the position of the decimal point.
+    /// `sign` is set to true for negative numbers, including -0.0.
     ///
-    /// # Safety
-    ///
-    /// This function is unsafe because it writes to a raw buffer. The caller must ensure that the buffer is large enough
-    /// to hold the result and that the pointers are valid.
-    #[no_mangle]
-    pub unsafe extern "C" fn DoubleToAscii(
-        v: f64,
+    /// The buffer must be big enough to hold the result: for DTOA_SHORTEST
+    /// K_BASE10_MAXIMAL_LENGTH bytes, for DTOA_PRECISION `requested_digits`
+    /// bytes and for DTOA_FIXED the number of integral digits (at most 309)
+    /// plus `requested_digits`.
+    pub fn double_to_ascii(
+        mut v: f64,
         mode: DtoaMode,
         requested_digits: i32,
-        buffer: *mut i8, // Assuming char is i8
-        sign: *mut i32,
-        length: *mut i32,
-        point: *mut i32,
+        buffer: &mut [u8],
+        sign: &mut bool,
+        length: &mut i32,
+        point: &mut i32,
     ) {
-        // This is a placeholder implementation. A full implementation would
-        // require a more complex algorithm, potentially relying on a third-party
-        // library for precise floating-point formatting.
-        let formatted = match mode {
-            DtoaMode::DTOA_SHORTEST => format!("{}", v),
-            DtoaMode::DTOA_FIXED => format!("{:.width$}", v, width = requested_digits as usize),
-            DtoaMode::DTOA_PRECISION => format!("{:.*}", requested_digits as usize, v),
+        debug_assert!(!Double::from_f64(v).is_special());
+        debug_assert!(mode == DtoaMode::DTOA_SHORTEST || requested_digits >= 0);
+
+        if Double::from_f64(v).sign() < 0 {
+            *sign = true;
+            v = -v;
+        } else {
+            *sign = false;
+        }
+
+        if v == 0.0 {
+            buffer[0] = b'0';
+            *length = 1;
+            *point = 1;
+            return;
+        }
+
+        if mode == DtoaMode::DTOA_PRECISION && requested_digits == 0 {
+            *length = 0;
+            return;
+        }
+
+        let fast_worked = match mode {
+            DtoaMode::DTOA_SHORTEST => fast_dtoa(v, FastDtoaMode::Shortest, 0, buffer, length, point),
+            DtoaMode::DTOA_FIXED => fast_fixed_dtoa(v, requested_digits, buffer, length, point),
+            DtoaMode::DTOA_PRECISION => {
+                fast_dtoa(v, FastDtoaMode::Precision, requested_digits, buffer, length, point)
+            }
         };
+        if fast_worked {
+            return;
+        }
+
+        // If the fast dtoa didn't succeed use the slower bignum version.
+        let bignum_mode = dtoa_to_bignum_dtoa_mode(mode);
+        bignum_dtoa(v, bignum_mode, requested_digits, buffer, length, point);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-        let mut s = 1;
-        if v < 0.0 {
-          s = -1;
+        /// Deterministic xorshift64* generator, so failures are reproducible.
+        struct Rng(u64);
+
+        impl Rng {
+            fn next_u64(&mut self) -> u64 {
+                self.0 ^= self.0 >> 12;
+                self.0 ^= self.0 << 25;
+                self.0 ^= self.0 >> 27;
+                self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+            }
+
+            fn next_finite_double(&mut self) -> f64 {
+                loop {
+                    let v = f64::from_bits(self.next_u64());
+                    if v.is_finite() && v != 0.0 {
+                        return v;
+                    }
+                }
+            }
         }
 
-        *sign = s;
+        fn dtoa(v: f64, mode: DtoaMode, requested_digits: i32) -> (bool, String, i32) {
+            let mut buffer = [0u8; 512];
+            let mut sign = false;
+            let mut length = 0;
+            let mut point = 0;
+            double_to_ascii(v, mode, requested_digits, &mut buffer, &mut sign, &mut length, &mut point);
+            (sign, String::from_utf8(buffer[..length as usize].to_vec()).unwrap(), point)
+        }
 
-        let bytes = formatted.as_bytes();
-        let len = bytes.len();
+        /// Splits the output of `{:e}` into its digits and the decimal point
+        /// in double_to_ascii's convention.
+        fn reference_digits(formatted: &str) -> (String, i32) {
+            let (mantissa, exponent) = formatted.split_once('e').unwrap();
+            let exponent: i32 = exponent.parse().unwrap();
+            let mantissa = mantissa.trim_start_matches('-');
+            (mantissa.replace('.', ""), exponent + 1)
+        }
 
-        *length = len as i32;
+        /// Returns true if the exact decimal expansion of v continues with
+        /// exactly "5" after its first `significant_digits` digits. The
+        /// reference rounds these halfway cases to even, ECMAScript does not.
+        fn is_halfway(v: f64, significant_digits: usize) -> bool {
+            let (exact, _) = reference_digits(&format!("{:.1100e}", v));
+            significant_digits < exact.len() && exact[significant_digits..].trim_end_matches('0') == "5"
+        }
 
-        let mut decimal_pos: i32 = 0;
+        #[test]
+        fn test_shortest_edge_cases() {
+            assert_eq!(dtoa(0.0, DtoaMode::DTOA_SHORTEST, 0), (false, "0".to_string(), 1));
+            assert_eq!(dtoa(-0.0, DtoaMode::DTOA_SHORTEST, 0), (true, "0".to_string(), 1));
+            assert_eq!(dtoa(1.0, DtoaMode::DTOA_SHORTEST, 0), (false, "1".to_string(), 1));
+            assert_eq!(dtoa(0.1, DtoaMode::DTOA_SHORTEST, 0), (false, "1".to_string(), 0));
+            assert_eq!(dtoa(-123.456, DtoaMode::DTOA_SHORTEST, 0), (true, "123456".to_string(), 3));
+            assert_eq!(dtoa(f64::MAX, DtoaMode::DTOA_SHORTEST, 0), (false, "17976931348623157".to_string(), 309));
+            assert_eq!(dtoa(5e-324, DtoaMode::DTOA_SHORTEST, 0), (false, "5".to_string(), -323));
+            assert_eq!(
+                dtoa(2.2250738585072014e-308, DtoaMode::DTOA_SHORTEST, 0),
+                (false, "22250738585072014".to_string(), -307)
+            );
+            assert_eq!(dtoa(1.5e-323, DtoaMode::DTOA_SHORTEST, 0), (false, "15".to_string(), -322));
+            assert_eq!(dtoa(9007199254740993.0, DtoaMode::DTOA_SHORTEST, 0), (false, "9007199254740992".to_string(), 16));
+            // Grisu3 cannot decide exact halfway cases, so they go through the
+            // bignum path, which rounds them to even.
+            assert_eq!(dtoa(1_394_865_425_023_536.2, DtoaMode::DTOA_SHORTEST, 0), (false, "13948654250235362".to_string(), 16));
+        }
 
-        if formatted.contains(".") {
-          decimal_pos = formatted.find(".").unwrap() as i32;
-        } else {
-          decimal_pos = len as i32;
+        #[test]
+        fn test_shortest_matches_reference() {
+            let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+            for _ in 0..200_000 {
+                let v = rng.next_finite_double();
+                let (sign, digits, point) = dtoa(v, DtoaMode::DTOA_SHORTEST, 0);
+                assert_eq!(sign, v < 0.0);
+                let expected = reference_digits(&format!("{:e}", v));
+                if (&digits, point) != (&expected.0, expected.1) && is_halfway(v, digits.len()) {
+                    continue;
+                }
+                assert_eq!((digits, point), expected, "{:e}", v);
+            }
         }
 
-        *point = decimal_pos;
+        #[test]
+        fn test_precision_matches_reference() {
+            let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+            for _ in 0..100_000 {
+                let v = rng.next_finite_double().abs();
+                let requested = (rng.next_u64() % 40) as i32 + 1;
+                let (_, digits, point) = dtoa(v, DtoaMode::DTOA_PRECISION, requested);
+                let (expected, expected_point) = reference_digits(&format!("{:.*e}", requested as usize - 1, v));
+                if digits != expected && is_halfway(v, requested as usize) {
+                    continue;
+                }
+                assert_eq!(digits, expected, "{:e}", v);
+                assert_eq!(point, expected_point, "{:e}", v);
+            }
+            assert_eq!(dtoa(1.0, DtoaMode::DTOA_PRECISION, 0), (false, String::new(), 0));
+            // Ties round away from zero, as required by ECMAScript.
+            assert_eq!(dtoa(2.5, DtoaMode::DTOA_PRECISION, 1), (false, "3".to_string(), 1));
+            assert_eq!(dtoa(0.125, DtoaMode::DTOA_PRECISION, 2), (false, "13".to_string(), 0));
+            assert_eq!(dtoa(999.99, DtoaMode::DTOA_PRECISION, 3), (false, "100".to_string(), 4));
+        }
 
-        for (i, &byte) in bytes.iter().enumerate() {
-            *buffer.add(i) = byte as i8;
+        #[test]
+        fn test_fixed_matches_reference() {
+            let mut rng = Rng(0xA076_1D64_78BD_642F);
+            for i in 0..100_000 {
+                // Mix fully random bit patterns with values of a magnitude
+                // that is typical for toFixed.
+                let v = if i % 2 == 0 {
+                    rng.next_finite_double().abs()
+                } else {
+                    (rng.next_u64() >> 11) as f64 / (1u64 << (rng.next_u64() % 64)) as f64
+                };
+                if v >= 1e21 || v == 0.0 {
+                    continue;
+                }
+                let fractional_count = (rng.next_u64() % 101) as i32;
+                let (_, digits, point) = dtoa(v, DtoaMode::DTOA_FIXED, fractional_count);
+                let expected = format!("{:.*}", fractional_count as usize, v);
+                let expected_digits = expected.replace('.', "");
+                let expected_digits = expected_digits.trim_start_matches('0');
+                let expected_point = expected.find('.').unwrap_or(expected.len()) as i32
+                    - (expected.replace('.', "").len() - expected_digits.len()) as i32;
+                // Trailing zeros are not significant.
+                if digits.trim_end_matches('0') != expected_digits.trim_end_matches('0') {
+                    let exact = format!("{:.1100}", v);
+                    let tail = &exact[exact.find('.').unwrap() + 1 + fractional_count as usize..];
+                    if tail.trim_end_matches('0') == "5" {
+                        continue;
+                    }
+                }
+                assert_eq!(
+                    digits.trim_end_matches('0'),
+                    expected_digits.trim_end_matches('0'),
+                    "{:e} {}",
+                    v,
+                    fractional_count
+                );
+                if !digits.is_empty() {
+                    assert_eq!(point, expected_point, "{:e} {}", v, fractional_count);
+                }
+            }
+            assert_eq!(dtoa(0.5, DtoaMode::DTOA_FIXED, 0), (false, "1".to_string(), 1));
+            assert_eq!(dtoa(0.15, DtoaMode::DTOA_FIXED, 1), (false, "1".to_string(), 0));
+            assert_eq!(dtoa(1.005, DtoaMode::DTOA_FIXED, 2), (false, "1".to_string(), 1));
+            assert_eq!(dtoa(0.001, DtoaMode::DTOA_FIXED, 2), (false, String::new(), -2));
         }
-        *buffer.add(len) = 0; // Null-terminate
     }
-}
\ No newline at end of file
+}
+
@@ -4,609 +4,544 @@
 
 // src/base/numbers/fast-dtoa.rs
 
-use std::convert::TryInto;
-use std::fmt;
-
-mod cached_powers;
-mod diy_fp;
-mod double;
-
-use cached_powers::PowersOfTenCache;
-use diy_fp::DiyFp;
-use double::Double;
-
-const K_MINIMAL_TARGET_EXPONENT: i32 = -60;
-const K_MAXIMAL_TARGET_EXPONENT: i32 = -32;
-
-/// Adjusts the last digit of the generated number, and screens out generated
-/// solutions that may be inaccurate.
-///
-/// Returns `true` if the buffer is guaranteed to contain the closest
-/// representable number to the input. Modifies the generated digits in the
-/// buffer to approach (round towards) w.
-fn round_weed(
-    last_digit: &mut char,
-    distance_too_high_w: u64,
-    unsafe_interval: u64,
-    rest: u64,
-    ten_kappa: u64,
-    unit: u64,
-) -> bool {
-    let small_distance = distance_too_high_w - unit;
-    let big_distance = distance_too_high_w + unit;
-
-    if rest < small_distance
-        && unsafe_interval - rest >= ten_kappa
-        && (rest + ten_kappa < small_distance
-            || small_distance - rest >= rest + ten_kappa - small_distance)
-    {
-        *last_digit = char::from_digit((*last_digit as u32 - '0' as u32 - 1).try_into().unwrap(), 10).unwrap();
-        
-        rest += ten_kappa;
-    }
-
-    if rest < big_distance
-        && unsafe_interval - rest >= ten_kappa
-        && (rest + ten_kappa < big_distance
-            || big_distance - rest > rest + ten_kappa - big_distance)
-    {
-        return false;
-    }
-
-    2 * unit <= rest && rest <= unsafe_interval - 4 * unit
-}
-
-/// Rounds the buffer upwards if the result is closer to v by possibly adding
-/// 1 to the buffer. If the precision of the calculation is not sufficient to
-/// round correctly, return false.
-fn round_weed_counted(
-    buffer: &mut [char],
-    length: usize,
-    rest: u64,
-    ten_kappa: u64,
-    unit: u64,
-    kappa: &mut i32,
-) -> bool {
-    if unit >= ten_kappa {
-        return false;
-    }
-    if ten_kappa - unit <= unit {
-        return false;
-    }
-
-    if (ten_kappa - rest > rest) && (ten_kappa - 2 * rest >= 2 * unit) {
-        return true;
+pub mod fast_dtoa {
+    use crate::base::numbers::cached_powers::cached_powers::PowersOfTenCache;
+    use crate::base::numbers::diy_fp::diy_fp::DiyFp;
+    use crate::base::numbers::double::double::Double;
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum FastDtoaMode {
+        /// Computes the shortest representation of the given input. The
+        /// returned result will be the most accurate number of this length.
+        /// Longer representations might be more accurate.
+        Shortest,
+        /// Computes a representation where the precision (number of digits) is
+        /// given as input. The precision is independent of the decimal point.
+        Precision,
     }
 
-    if (rest > unit) && (ten_kappa - (rest - unit) <= (rest - unit)) {
-        buffer[length - 1] = char::from_digit((buffer[length - 1] as u32 - '0' as u32 + 1).try_into().unwrap(), 10).unwrap();
-        
-        for i in (0..length - 1).rev() {
-            if buffer[i + 1] != '0' {
-                break;
-            }
-            buffer[i + 1] = '0';
-            buffer[i] = char::from_digit((buffer[i] as u32 - '0' as u32 + 1).try_into().unwrap(), 10).unwrap();
-            
+    /// FastDtoa will produce at most K_FAST_DTOA_MAXIMAL_LENGTH digits.
+    pub const K_FAST_DTOA_MAXIMAL_LENGTH: usize = 17;
+
+    // The minimal and maximal target exponent define the range of w's binary
+    // exponent, where 'w' is the result of multiplying the input by a cached
+    // power of ten.
+    //
+    // A different range might be chosen on a different platform, to optimize
+    // digit generation, but a smaller range requires more powers of ten to be
+    // cached.
+    const K_MINIMAL_TARGET_EXPONENT: i32 = -60;
+    const K_MAXIMAL_TARGET_EXPONENT: i32 = -32;
+
+    /// Adjusts the last digit of the generated number, and screens out
+    /// generated solutions that may be inaccurate. A solution may be
+    /// inaccurate if it is outside the safe interval, or if we cannot prove
+    /// that it is closer to the input than a neighboring representation of
+    /// the same length.
+    ///
+    /// Input: * buffer containing the digits of too_high / 10^kappa
+    ///        * distance_too_high_w == (too_high - w).f() * unit
+    ///        * unsafe_interval == (too_high - too_low).f() * unit
+    ///        * rest = (too_high - buffer * 10^kappa).f() * unit
+    ///        * ten_kappa = 10^kappa * unit
+    ///        * unit = the common multiplier
+    /// Output: returns true if the buffer is guaranteed to contain the closest
+    ///    representable number to the input.
+    ///  Modifies the generated digits in the buffer to approach (round towards)
+    ///  w.
+    fn round_weed(
+        last_digit: &mut u8,
+        distance_too_high_w: u64,
+        unsafe_interval: u64,
+        mut rest: u64,
+        ten_kappa: u64,
+        unit: u64,
+    ) -> bool {
+        let small_distance = distance_too_high_w - unit;
+        let big_distance = distance_too_high_w + unit;
+        // Let w_low  = too_high - big_distance, and
+        //     w_high = too_high - small_distance.
+        // Note: w_low < w < w_high
+        //
+        // The real w (* unit) must lie somewhere inside the interval
+        // ]w_low; w_high[ (often written as "(w_low; w_high)")
+
+        // Basically the buffer currently contains a number in the unsafe
+        // interval ]too_low; too_high[ with too_low < w < too_high
+        //
+        // We need to do the following tests in this order to avoid over- and
+        // underflows.
+        debug_assert!(rest <= unsafe_interval);
+        while rest < small_distance // Negated condition 1
+            && unsafe_interval - rest >= ten_kappa // Negated condition 2
+            && (rest + ten_kappa < small_distance // buffer{-1} > w_high
+                || small_distance - rest >= rest + ten_kappa - small_distance)
+        {
+            *last_digit -= 1;
+            rest += ten_kappa;
         }
 
-        if buffer[0] == char::from_digit(10, 10).unwrap() {
-            buffer[0] = '1';
-            *kappa += 1;
+        // We have approached w+ from below. Now we check if we are too close
+        // to w- (or past it). If the unsafe interval around the buffer lies on
+        // both sides of w_low we cannot decide whether buffer or buffer-1 is
+        // closer to w.
+        if rest < big_distance
+            && unsafe_interval - rest >= ten_kappa
+            && (rest + ten_kappa < big_distance || big_distance - rest > rest + ten_kappa - big_distance)
+        {
+            return false;
         }
-        return true;
-    }
-
-    false
-}
-
-const K_TEN4: u32 = 10000;
-const K_TEN5: u32 = 100000;
-const K_TEN6: u32 = 1000000;
-const K_TEN7: u32 = 10000000;
-const K_TEN8: u32 = 100000000;
-const K_TEN9: u32 = 1000000000;
 
-#[derive(Clone, Copy)]
-struct DivMagic {
-    mul: u32,
-    shift: u32,
-}
-
-static DIV: [DivMagic; 9] = [
-    DivMagic { mul: 0, shift: 0 },
-    DivMagic {
-        mul: 0x9999999a,
-        shift: 3,
-    },
-    DivMagic {
-        mul: 0x47ae147b,
-        shift: 6,
-    },
-    DivMagic {
-        mul: 0x0624dd30,
-        shift: 9,
-    },
-    DivMagic {
-        mul: 0xa36e2eb2,
-        shift: 13,
-    },
-    DivMagic {
-        mul: 0x4f8b588f,
-        shift: 16,
-    },
-    DivMagic {
-        mul: 0x0c6f7a0c,
-        shift: 19,
-    },
-    DivMagic {
-        mul: 0xad7f29ac,
-        shift: 23,
-    },
-    DivMagic {
-        mul: 0x5798ee24,
-        shift: 26,
-    },
-];
-
-/// Returns *val / divisor, and does *val %= divisor. d must be the DivMagic
-/// corresponding to the divisor.
-fn fast_divmod(val: &mut u32, divisor: u32, d: &DivMagic) -> u32 {
-    if divisor == 1 {
-        let digit = *val;
-        *val = 0;
-        return digit;
-    } else {
-        let q = ((*val as u64) * (d.mul as u64)) >> 32;
-        let t = ((*val - q) >> 1) + q;
-        let digit = t >> d.shift;
-        *val -= digit * divisor;
-        return digit;
+        // Weeding test.
+        //   The safe interval is [too_low + 2 ulp; too_high - 2 ulp]
+        //   Since too_low = too_high - unsafe_interval this is equivalent to
+        //      [too_high - unsafe_interval + 4 ulp; too_high - 2 ulp]
+        //   Conceptually we have: rest ~= too_high - buffer
+        (2 * unit <= rest) && (rest <= unsafe_interval - 4 * unit)
     }
-}
 
-/// Returns the biggest power of ten that is less than or equal than the given
-/// number.
-fn biggest_power_ten(number: u32, number_bits: i32, power: &mut u32, exponent: &mut i32) {
-    match number_bits {
-        32..=i32::MAX | 31 | 30 => {
-            if K_TEN9 <= number {
-                *power = K_TEN9;
-                *exponent = 9;
-            } else {
-                match 29..=i32::MAX | 28 | 27 {
-                    _ => {
-                        if K_TEN8 <= number {
-                            *power = K_TEN8;
-                            *exponent = 8;
-                        } else {
-                            match 26..=i32::MAX | 25 | 24 {
-                                _ => {
-                                    if K_TEN7 <= number {
-                                        *power = K_TEN7;
-                                        *exponent = 7;
-                                    } else {
-                                        match 23..=i32::MAX | 22 | 21 | 20 {
-                                            _ => {
-                                                if K_TEN6 <= number {
-                                                    *power = K_TEN6;
-                                                    *exponent = 6;
-                                                } else {
-                                                    match 19..=i32::MAX | 18 | 17 {
-                                                        _ => {
-                                                            if K_TEN5 <= number {
-                                                                *power = K_TEN5;
-                                                                *exponent = 5;
-                                                            } else {
-                                                                match 16..=i32::MAX | 15 | 14 {
-                                                                    _ => {
-                                                                        if K_TEN4 <= number {
-                                                                            *power = K_TEN4;
-                                                                            *exponent = 4;
-                                                                        } else {
-                                                                            match 13..=i32::MAX | 12 | 11
-                                                                                | 10
-                                                                            {
-                                                                                _ => {
-                                                                                    if 1000 <= number {
-                                                                                        *power = 1000;
-                                                                                        *exponent = 3;
-                                                                                    } else {
-                                                                                        match 9..=i32::MAX | 8 | 7 {
-                                                                                            _ => {
-                                                                                                if 100 <= number {
-                                                                                                    *power = 100;
-                                                                                                    *exponent = 2;
-                                                                                                } else {
-                                                                                                    match 6..=i32::MAX | 5 | 4 {
-                                                                                                        _ => {
-                                                                                                            if 10 <= number {
-                                                                                                                *power = 10;
-                                                                                                                *exponent = 1;
-                                                                                                            } else {
-                                                                                                                match 3..=i32::MAX | 2 | 1 {
-                                                                                                                    _ => {
-                                                                                                                        if 1 <= number {
-                                                                                                                            *power = 1;
-                                                                                                                            *exponent = 0;
-                                                                                                                        } else {
-                                                                                                                            match 0 {
-                                                                                                                                _ => {
-                                                                                                                                    *power = 0;
-                                                                                                                                    *exponent = -1;
-                                                                                                                                }
-                                                                                                                            }
-                                                                                                                        }
-                                                                                                                    }
-                                                                                                                }
-                                                                                                            }
-                                                                                                        }
-                                                                                                    }
-                                                                                                }
-                                                                                            }
-                                                                                        }
-                                                                                    }
-                                                                                }
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+    /// Rounds the buffer upwards if the result is closer to v by possibly
+    /// adding 1 to the buffer. If the precision of the calculation is not
+    /// sufficient to round correctly, return false.
+    /// The rounding might shift the whole buffer in which case the kappa is
+    /// adjusted. For example "99", kappa = 3 might become "10", kappa = 4.
+    ///
+    /// If 2*rest > ten_kappa then the buffer needs to be round up.
+    /// rest can have an error of +/- 1 unit. This function accounts for the
+    /// imprecision and returns false, if the rounding direction cannot be
+    /// unambiguously determined.
+    ///
+    /// Precondition: rest < ten_kappa.
+    fn round_weed_counted(buffer: &mut [u8], length: usize, rest: u64, ten_kappa: u64, unit: u64, kappa: &mut i32) -> bool {
+        debug_assert!(rest < ten_kappa);
+        // The following tests are done in a specific order to avoid
+        // overflows. They will work correctly with any u64 values of rest <
+        // ten_kappa and unit.
+        //
+        // If the unit is too big, then we don't know which way to round. For
+        // example a unit of 50 means that the real number lies within rest +/-
+        // 50. If 10^kappa == 40 then there is no way to tell which way to
+        // round.
+        if unit >= ten_kappa {
+            return false;
+        }
+        // Even if unit is just half the size of 10^kappa we are already
+        // completely lost. (And after the previous test we know that the
+        // expression will not over/underflow.)
+        if ten_kappa - unit <= unit {
+            return false;
+        }
+        // If 2 * (rest + unit) <= 10^kappa we can safely round down.
+        if (ten_kappa - rest > rest) && (ten_kappa - 2 * rest >= 2 * unit) {
+            return true;
+        }
+        // If 2 * (rest - unit) >= 10^kappa, then we can safely round up.
+        if (rest > unit) && (ten_kappa - (rest - unit) <= (rest - unit)) {
+            // Increment the last digit recursively until we find a non '9'
+            // digit.
+            buffer[length - 1] += 1;
+            for i in (1..length).rev() {
+                if buffer[i] != b'0' + 10 {
+                    break;
                 }
+                buffer[i] = b'0';
+                buffer[i - 1] += 1;
             }
+            // If the first digit is now '0'+ 10 we had a buffer with all '9's.
+            // With the exception of the first digit all digits are now '0'.
+            // Simply switch the first digit to '1' and adjust the kappa.
+            // Example: "99" becomes "60" and the kappa is increased by 1.
+            if buffer[0] == b'0' + 10 {
+                buffer[0] = b'1';
+                *kappa += 1;
+            }
+            return true;
         }
-        _ => {
-            *power = 0;
-            *exponent = 0;
-            // unreachable!()
-        }
+        false
     }
-}
-
-/// Generates the digits of input number w.
-fn digit_gen(
-    low: DiyFp,
-    w: DiyFp,
-    high: DiyFp,
-    outptr: &mut &mut [u8],
-    kappa: &mut i32,
-) -> bool {
-    assert_eq!(low.e(), w.e());
-    assert_eq!(w.e(), high.e());
-    assert!(low.f() + 1 <= high.f() - 1);
-    assert!(K_MINIMAL_TARGET_EXPONENT <= w.e() && w.e() <= K_MAXIMAL_TARGET_EXPONENT);
-
-    let unit = 1;
-    let too_low = DiyFp::new(low.f() - unit, low.e());
-    let too_high = DiyFp::new(high.f() + unit, high.e());
-
-    let unsafe_interval = DiyFp::minus(too_high, too_low);
-
-    let one = DiyFp::new((1u64 << -w.e()) as u64, w.e());
-
-    let integrals = (too_high.f() >> -one.e()) as u32;
-    let fractionals = too_high.f() & (one.f() - 1);
-
-    let mut divisor = 0;
-    let mut divisor_exponent: i32 = 0;
-    biggest_power_ten(
-        integrals,
-        DiyFp::k_significand_size() as i32 - (-one.e()),
-        &mut divisor,
-        &mut divisor_exponent,
-    );
-    *kappa = divisor_exponent + 1;
-
-    let mut integrals_mut = integrals;
-
-    while *kappa > 0 {
-        let digit = fast_divmod(&mut integrals_mut, divisor, &DIV[divisor_exponent as usize]);
-        **outptr = b'0' + digit as u8;
-        *outptr = &mut (*outptr)[1..];
-        *kappa -= 1;
-
-        let rest = ((integrals_mut as u64) << -one.e()) + fractionals;
 
-        if rest < unsafe_interval.f() {
-            let last_digit = (**outptr.split_at(0).0.last().unwrap()) as char;
-
-            return round_weed(
-                &mut (last_digit),
-                DiyFp::minus(too_high, w).f(),
-                unsafe_interval.f(),
-                rest,
-                (divisor as u64) << -one.e(),
-                unit,
-            );
-        }
-
-        if *kappa <= 0 {
-            break;
+    const K_SMALL_POWERS_OF_TEN: [u32; 11] =
+        [0, 1, 10, 100, 1000, 10000, 100000, 1000000, 10000000, 100000000, 1000000000];
+
+    /// Returns the biggest power of ten that is less than or equal to the
+    /// given number, together with its exponent plus one. number_bits is the
+    /// bit size of the number. If number == 0 the result is (0, 0).
+    fn biggest_power_ten(number: u32, number_bits: i32) -> (u32, i32) {
+        debug_assert!((number as u64) < (1u64 << (number_bits + 1)));
+        // 1233/4096 is approximately 1/lg(10).
+        let mut exponent_plus_one_guess = ((number_bits + 1) * 1233) >> 12;
+        // We increment to skip the power of zero.
+        exponent_plus_one_guess += 1;
+        // We don't have any guarantees that 2^number_bits <= number.
+        if number < K_SMALL_POWERS_OF_TEN[exponent_plus_one_guess as usize] {
+            exponent_plus_one_guess -= 1;
         }
-
-        divisor /= 10;
-        divisor_exponent -= 1;
+        (K_SMALL_POWERS_OF_TEN[exponent_plus_one_guess as usize], exponent_plus_one_guess)
     }
 
-    assert!(one.e() >= -60);
-    assert!(fractionals < one.f());
-    assert!(0xFFFF_FFFF_FFFF_FFFF / 10 >= one.f());
-
-    let mut fractionals_mut = fractionals;
-    let mut unit_mut = unit;
-    let mut unsafe_interval_mut = unsafe_interval;
-
-    loop {
-        fractionals_mut *= 10;
-        unit_mut *= 10;
-        unsafe_interval_mut.set_f(unsafe_interval_mut.f() * 10);
-
-        let digit = (fractionals_mut >> -one.e()) as i32;
-
-        **outptr = b'0' + digit as u8;
-        *outptr = &mut (*outptr)[1..];
-        *kappa -= 1;
-        fractionals_mut &= one.f() - 1; // Modulo by one
-
-        if fractionals_mut < unsafe_interval_mut.f() {
+    /// Generates the digits of input number w.
+    /// w is a floating-point number (DiyFp), consisting of a significand and
+    /// an exponent. Its exponent is bounded by K_MINIMAL_TARGET_EXPONENT and
+    /// K_MAXIMAL_TARGET_EXPONENT. Hence -60 <= w.e() <= -32.
+    ///
+    /// Returns false if it fails, in which case the generated digits in the
+    /// buffer should not be used.
+    /// Preconditions:
+    ///  * low, w and high are correct up to 1 ulp (unit in the last place).
+    ///    That is, their error must be less than a unit of their last digits.
+    ///  * low.e() == w.e() == high.e()
+    ///  * low < w < high, and taking into account their error:
+    ///    low~ <= high~
+    ///  * K_MINIMAL_TARGET_EXPONENT <= w.e() <= K_MAXIMAL_TARGET_EXPONENT
+    ///
+    /// Postconditions: returns false if procedure fails.
+    ///   otherwise:
+    ///     * buffer is not null-terminated, but len contains the number of
+    ///       digits.
+    ///     * buffer contains the shortest possible decimal digit-sequence
+    ///       such that LOW < buffer * 10^kappa < HIGH, where LOW and HIGH are
+    ///       the correct values of low and high (without their error).
+    ///     * if more than one decimal representation gives the minimal number
+    ///       of decimal digits then the one closest to W (where W is the
+    ///       correct value of w) is chosen.
+    fn digit_gen(low: DiyFp, w: DiyFp, high: DiyFp, buffer: &mut [u8], length: &mut i32, kappa: &mut i32) -> bool {
+        debug_assert!(low.e() == w.e() && w.e() == high.e());
+        debug_assert!(low.f() < high.f() - 1);
+        debug_assert!(K_MINIMAL_TARGET_EXPONENT <= w.e() && w.e() <= K_MAXIMAL_TARGET_EXPONENT);
+        // low, w and high are imprecise, but by less than one ulp (unit in the
+        // last place). If we remove (resp. add) 1 ulp from low (resp. high) we
+        // are certain that the new numbers are outside of the interval we want
+        // the final representation to lie in.
+        // Inversely adding (resp. removing) 1 ulp from low (resp. high) would
+        // yield numbers that are certain to lie in the interval. We will use
+        // this fact later on.
+        // We will now start by generating the digits within the uncertain
+        // interval. Later we will weed out representations that lie outside
+        // the safe interval and thus _might_ lie outside the correct interval.
+        let mut unit: u64 = 1;
+        let too_low = DiyFp::new(low.f() - unit, low.e());
+        let too_high = DiyFp::new(high.f() + unit, high.e());
+        // too_low and too_high are guaranteed to lie outside the interval we
+        // want the generated number in.
+        let mut unsafe_interval = DiyFp::minus(&too_high, &too_low);
+        // We now cut the input number into two parts; the integral part and
+        // the fractionals. We will not write any decimal separator though, but
+        // adapt kappa instead.
+        // Reminder: we are currently computing the digits (stored inside the
+        // buffer) such that:   too_low < buffer * 10^kappa < too_high
+        // We use too_high for the digit_generation and stop as soon as
+        // possible. If we stop early we effectively round down.
+        let one = DiyFp::new(1u64 << -w.e(), w.e());
+        // Division by one is a shift.
+        let mut integrals = (too_high.f() >> -one.e()) as u32;
+        // Modulo by one is an and.
+        let mut fractionals = too_high.f() & (one.f() - 1);
+        let (mut divisor, divisor_exponent_plus_one) =
+            biggest_power_ten(integrals, DiyFp::K_SIGNIFICAND_SIZE - (-one.e()));
+        *kappa = divisor_exponent_plus_one;
+        *length = 0;
+        // Loop invariant: buffer = too_high / 10^kappa  (integer division)
+        // The invariant holds for the first iteration: kappa has been
+        // initialized with the divisor exponent + 1. And the divisor is the
+        // biggest power of ten that is smaller than integrals.
+        while *kappa > 0 {
+            let digit = integrals / divisor;
+            buffer[*length as usize] = b'0' + digit as u8;
+            *length += 1;
+            integrals %= divisor;
+            *kappa -= 1;
+            // Note that kappa now equals the exponent of the divisor and that
+            // the invariant thus holds again.
+            let rest = ((integrals as u64) << -one.e()) + fractionals;
+            // Invariant: too_high = buffer * 10^kappa + DiyFp(rest, one.e())
+            // Reminder: unsafe_interval.e() == one.e()
+            if rest < unsafe_interval.f() {
+                // Rounding down (by not emitting the remaining digits) yields a
+                // number that lies within the unsafe interval.
+                return round_weed(
+                    &mut buffer[*length as usize - 1],
+                    DiyFp::minus(&too_high, &w).f(),
+                    unsafe_interval.f(),
+                    rest,
+                    (divisor as u64) << -one.e(),
+                    unit,
+                );
+            }
+            divisor /= 10;
+        }
 
-            let last_digit = (**outptr.split_at(0).0.last().unwrap()) as char;
-            return round_weed(
-                &mut (last_digit),
-                DiyFp::minus(too_high, w).f() * unit_mut,
-                unsafe_interval_mut.f(),
-                fractionals_mut,
-                one.f(),
-                unit_mut,
-            );
+        // The integrals have been generated. We are at the point of the
+        // decimal separator. In the following loop we simply multiply the
+        // remaining digits by 10 and divide by one. We just need to pay
+        // attention to multiply associated data (like the interval or 'unit'),
+        // too. Note that the multiplication by 10 does not overflow, because
+        // w.e >= -60 and thus one.e >= -60.
+        debug_assert!(one.e() >= -60);
+        debug_assert!(fractionals < one.f());
+        debug_assert!(0xFFFF_FFFF_FFFF_FFFFu64 / 10 >= one.f());
+        loop {
+            fractionals *= 10;
+            unit *= 10;
+            unsafe_interval.set_f(unsafe_interval.f() * 10);
+            // Integer division by one.
+            let digit = (fractionals >> -one.e()) as u8;
+            buffer[*length as usize] = b'0' + digit;
+            *length += 1;
+            fractionals &= one.f() - 1; // Modulo by one.
+            *kappa -= 1;
+            if fractionals < unsafe_interval.f() {
+                return round_weed(
+                    &mut buffer[*length as usize - 1],
+                    DiyFp::minus(&too_high, &w).f() * unit,
+                    unsafe_interval.f(),
+                    fractionals,
+                    one.f(),
+                    unit,
+                );
+            }
         }
     }
-}
 
-/// Generates (at most) requested_digits of input number w.
-fn digit_gen_counted(
-    w: DiyFp,
-    requested_digits: i32,
-    buffer: &mut [char],
-    length: &mut usize,
-    kappa: &mut i32,
-) -> bool {
-    assert!(K_MINIMAL_TARGET_EXPONENT <= w.e() && w.e() <= K_MAXIMAL_TARGET_EXPONENT);
-    assert!(K_MINIMAL_TARGET_EXPONENT >= -60);
-    assert!(K_MAXIMAL_TARGET_EXPONENT <= -32);
-
-    let mut w_error = 1;
-
-    let one = DiyFp::new((1u64 << -w.e()) as u64, w.e());
-
-    let integrals = (w.f() >> -one.e()) as u32;
-    let fractionals = w.f() & (one.f() - 1);
-
-    let mut divisor = 0;
-    let mut divisor_exponent: i32 = 0;
-
-    biggest_power_ten(
-        integrals,
-        DiyFp::k_significand_size() as i32 - (-one.e()),
-        &mut divisor,
-        &mut divisor_exponent,
-    );
-
-    *kappa = divisor_exponent + 1;
-    *length = 0;
-
-    let mut integrals_mut = integrals;
-    let mut requested_digits_mut = requested_digits;
-
-    while *kappa > 0 {
-        let digit = fast_divmod(&mut integrals_mut, divisor, &DIV[divisor_exponent as usize]);
-        buffer[*length] = char::from_digit(digit, 10).unwrap();
-        *length += 1;
-        requested_digits_mut -= 1;
-        *kappa -= 1;
+    /// Generates (at most) requested_digits of input number w.
+    /// w is a floating-point number (DiyFp), consisting of a significand and
+    /// an exponent. Its exponent is bounded by K_MINIMAL_TARGET_EXPONENT and
+    /// K_MAXIMAL_TARGET_EXPONENT. Hence -60 <= w.e() <= -32.
+    ///
+    /// Returns false if it fails, in which case the generated digits in the
+    /// buffer should not be used.
+    /// Preconditions:
+    ///  * w is correct up to 1 ulp (unit in the last place). That is, its
+    ///    error must be strictly less than a unit of its last digit.
+    ///  * K_MINIMAL_TARGET_EXPONENT <= w.e() <= K_MAXIMAL_TARGET_EXPONENT
+    ///
+    /// Postconditions: returns false if procedure fails.
+    ///   otherwise:
+    ///     * buffer is not null-terminated, but length contains the number of
+    ///       digits.
+    ///     * the representation in buffer is the most precise representation
+    ///       of requested_digits digits.
+    ///     * buffer contains at most requested_digits digits of w. If there
+    ///       are less than requested_digits digits then some trailing '0's
+    ///       have been removed.
+    ///     * kappa is such that
+    ///            w = buffer * 10^kappa + eps with |eps| < 10^kappa / 2.
+    ///
+    /// Remark: This procedure takes into account the imprecision of its input
+    ///   numbers. If the precision is not enough to guarantee all the
+    ///   postconditions, then false is returned. This usually happens rarely,
+    ///   but the failure-rate increases with higher requested_digits.
+    fn digit_gen_counted(w: DiyFp, mut requested_digits: i32, buffer: &mut [u8], length: &mut i32, kappa: &mut i32) -> bool {
+        debug_assert!(K_MINIMAL_TARGET_EXPONENT <= w.e() && w.e() <= K_MAXIMAL_TARGET_EXPONENT);
+        // w is assumed to have an error less than 1 unit. Furthermore w_error
+        // is not updated for the integrals, as the rounding of the last digit
+        // accounts for it.
+        let mut w_error: u64 = 1;
+        // We cut the input number into two parts; the integral part and the
+        // fractionals. We will not write any decimal separator though, but
+        // adapt kappa instead.
+        let one = DiyFp::new(1u64 << -w.e(), w.e());
+        // Division by one is a shift.
+        let mut integrals = (w.f() >> -one.e()) as u32;
+        // Modulo by one is an and.
+        let mut fractionals = w.f() & (one.f() - 1);
+        let (mut divisor, divisor_exponent_plus_one) =
+            biggest_power_ten(integrals, DiyFp::K_SIGNIFICAND_SIZE - (-one.e()));
+        *kappa = divisor_exponent_plus_one;
+        *length = 0;
+
+        // Loop invariant: buffer = w / 10^kappa  (integer division)
+        // The invariant holds for the first iteration: kappa has been
+        // initialized with the divisor exponent + 1. And the divisor is the
+        // biggest power of ten that is smaller than integrals.
+        while *kappa > 0 {
+            let digit = integrals / divisor;
+            buffer[*length as usize] = b'0' + digit as u8;
+            *length += 1;
+            requested_digits -= 1;
+            integrals %= divisor;
+            *kappa -= 1;
+            // Note that kappa now equals the exponent of the divisor and that
+            // the invariant thus holds again.
+            if requested_digits == 0 {
+                break;
+            }
+            divisor /= 10;
+        }
 
-        if requested_digits_mut == 0 {
-            break;
+        if requested_digits == 0 {
+            let rest = ((integrals as u64) << -one.e()) + fractionals;
+            return round_weed_counted(buffer, *length as usize, rest, (divisor as u64) << -one.e(), w_error, kappa);
         }
 
-        divisor /= 10;
-        divisor_exponent -= 1;
+        // The integrals have been generated. We are at the point of the
+        // decimal separator. In the following loop we simply multiply the
+        // remaining digits by 10 and divide by one. We just need to pay
+        // attention to multiply associated data (the 'unit'), too. Note that
+        // the multiplication by 10 does not overflow, because w.e >= -60 and
+        // thus one.e >= -60.
+        debug_assert!(one.e() >= -60);
+        debug_assert!(fractionals < one.f());
+        debug_assert!(0xFFFF_FFFF_FFFF_FFFFu64 / 10 >= one.f());
+        while requested_digits > 0 && fractionals > w_error {
+            fractionals *= 10;
+            w_error *= 10;
+            // Integer division by one.
+            let digit = (fractionals >> -one.e()) as u8;
+            buffer[*length as usize] = b'0' + digit;
+            *length += 1;
+            requested_digits -= 1;
+            fractionals &= one.f() - 1; // Modulo by one.
+            *kappa -= 1;
+        }
+        if requested_digits != 0 {
+            return false;
+        }
+        round_weed_counted(buffer, *length as usize, fractionals, one.f(), w_error, kappa)
     }
 
-    if requested_digits_mut == 0 {
-        let rest = ((integrals_mut as u64) << -one.e()) + fractionals;
-        return round_weed_counted(
-            buffer,
-            *length,
-            rest,
-            (divisor as u64) << -one.e(),
-            w_error,
-            kappa,
+    /// Provides a decimal representation of v.
+    /// Returns true if it succeeds, otherwise the result cannot be trusted.
+    /// There will be *length digits inside the buffer (not null-terminated).
+    /// If the function returns true then
+    ///        v == buffer * 10^decimal_exponent.
+    /// The representation is the shortest possible, and, if there are two
+    /// equally short representations, the one closer to v.
+    /// The last digit will be closest to the actual v. That is, even if
+    /// several digits might correctly yield 'v' when read again, the closest
+    /// will be computed.
+    fn grisu3(v: f64, buffer: &mut [u8], length: &mut i32, decimal_exponent: &mut i32) -> bool {
+        let w = Double::from_f64(v).as_normalized_diy_fp();
+        // boundary_minus and boundary_plus are the boundaries between v and
+        // its closest floating-point neighbors. Any number strictly between
+        // boundary_minus and boundary_plus will round to v when converted to
+        // a double. Grisu3 will never output representations that lie exactly
+        // on a boundary.
+        let (boundary_minus, boundary_plus) = Double::from_f64(v).normalized_boundaries();
+        debug_assert_eq!(boundary_plus.e(), w.e());
+        let ten_mk_minimal_binary_exponent = K_MINIMAL_TARGET_EXPONENT - (w.e() + DiyFp::K_SIGNIFICAND_SIZE);
+        let ten_mk_maximal_binary_exponent = K_MAXIMAL_TARGET_EXPONENT - (w.e() + DiyFp::K_SIGNIFICAND_SIZE);
+        // Cached power of ten: 10^-k
+        let (ten_mk, mk) = PowersOfTenCache::get_cached_power_for_binary_exponent_range(
+            ten_mk_minimal_binary_exponent,
+            ten_mk_maximal_binary_exponent,
         );
+        debug_assert!(
+            K_MINIMAL_TARGET_EXPONENT <= w.e() + ten_mk.e() + DiyFp::K_SIGNIFICAND_SIZE
+                && K_MAXIMAL_TARGET_EXPONENT >= w.e() + ten_mk.e() + DiyFp::K_SIGNIFICAND_SIZE
+        );
+        // Note that ten_mk is only an approximation of 10^-k. A DiyFp only
+        // contains a 64 bit significand and ten_mk is thus only precise up to
+        // 64 bits.
+
+        // The DiyFp::times procedure rounds its result, and ten_mk is
+        // approximated too. The variable scaled_w (as well as
+        // scaled_boundary_minus/plus) are now off by a small amount.
+        // In fact: scaled_w - w*10^k < 1ulp (unit in the last place) of
+        // scaled_w. In other words: let f = scaled_w.f() and e = scaled_w.e(),
+        // then (f-1) * 2^e < w*10^k < (f+1) * 2^e
+        let scaled_w = DiyFp::times(&w, &ten_mk);
+        debug_assert_eq!(scaled_w.e(), boundary_plus.e() + ten_mk.e() + DiyFp::K_SIGNIFICAND_SIZE);
+        // In theory it would be possible to avoid some recomputations by
+        // computing the difference between w and boundary_minus/plus (a power
+        // of 2) and to compute scaled_boundary_minus/plus by subtracting/adding
+        // from scaled_w. However the code becomes much less readable and the
+        // speed enhancements are not terrific.
+        let scaled_boundary_minus = DiyFp::times(&boundary_minus, &ten_mk);
+        let scaled_boundary_plus = DiyFp::times(&boundary_plus, &ten_mk);
+
+        // digit_gen will generate the digits of scaled_w. Therefore we have
+        // v == (double) (scaled_w * 10^-mk).
+        // Set decimal_exponent == -mk and pass it to digit_gen. If scaled_w is
+        // not an integer than it will be updated. For instance if scaled_w ==
+        // 1.23 then the buffer will be filled with "123" und the
+        // decimal_exponent will be decreased by 2.
+        let mut kappa = 0;
+        let result = digit_gen(scaled_boundary_minus, scaled_w, scaled_boundary_plus, buffer, length, &mut kappa);
+        *decimal_exponent = -mk + kappa;
+        result
     }
 
-    assert!(one.e() >= -60);
-    assert!(fractionals < one.f());
-    assert!(0xFFFF_FFFF_FFFF_FFFF / 10 >= one.f());
-
-    let mut fractionals_mut = fractionals;
-    let mut w_error_mut = w_error;
-
-    while requested_digits_mut > 0 && fractionals_mut > w_error_mut {
-        fractionals_mut *= 10;
-        w_error_mut *= 10;
-
-        let digit = (fractionals_mut >> -one.e()) as i32;
-        buffer[*length] = char::from_digit(digit, 10).unwrap();
-        *length += 1;
-        requested_digits_mut -= 1;
-        fractionals_mut &= one.f() - 1; // Modulo by one
-        *kappa -= 1;
-    }
-
-    if requested_digits_mut != 0 {
-        return false;
+    /// The "counted" version of grisu3 (see above) only generates
+    /// requested_digits number of digits. This version does not generate the
+    /// shortest representation, and with enough requested digits 0.1 will at
+    /// some point print as 0.9999999...
+    /// Grisu3 is too imprecise for real halfway cases (1.5 will not work) and
+    /// therefore the rounding strategy for halfway cases is irrelevant.
+    fn grisu3_counted(v: f64, requested_digits: i32, buffer: &mut [u8], length: &mut i32, decimal_exponent: &mut i32) -> bool {
+        let w = Double::from_f64(v).as_normalized_diy_fp();
+        let ten_mk_minimal_binary_exponent = K_MINIMAL_TARGET_EXPONENT - (w.e() + DiyFp::K_SIGNIFICAND_SIZE);
+        let ten_mk_maximal_binary_exponent = K_MAXIMAL_TARGET_EXPONENT - (w.e() + DiyFp::K_SIGNIFICAND_SIZE);
+        let (ten_mk, mk) = PowersOfTenCache::get_cached_power_for_binary_exponent_range(
+            ten_mk_minimal_binary_exponent,
+            ten_mk_maximal_binary_exponent,
+        );
+        debug_assert!(
+            K_MINIMAL_TARGET_EXPONENT <= w.e() + ten_mk.e() + DiyFp::K_SIGNIFICAND_SIZE
+                && K_MAXIMAL_TARGET_EXPONENT >= w.e() + ten_mk.e() + DiyFp::K_SIGNIFICAND_SIZE
+        );
+        // Note that ten_mk is only an approximation of 10^-k. A DiyFp only
+        // contains a 64 bit significand and ten_mk is thus only precise up to
+        // 64 bits.
+
+        // The DiyFp::times procedure rounds its result, and ten_mk is
+        // approximated too. The variable scaled_w (as well as
+        // scaled_boundary_minus/plus) are now off by a small amount.
+        // In fact: scaled_w - w*10^k < 1ulp (unit in the last place) of
+        // scaled_w. In other words: let f = scaled_w.f() and e = scaled_w.e(),
+        // then (f-1) * 2^e < w*10^k < (f+1) * 2^e
+        let scaled_w = DiyFp::times(&w, &ten_mk);
+
+        // We now have (double) (scaled_w * 10^-mk).
+        // digit_gen_counted will generate the first requested_digits digits
+        // of scaled_w and return together with a kappa such that
+        // scaled_w ~= buffer * 10^kappa. (It will not always be exactly the
+        // same since digit_gen_counted only produces a limited number of
+        // digits.)
+        let mut kappa = 0;
+        let result = digit_gen_counted(scaled_w, requested_digits, buffer, length, &mut kappa);
+        *decimal_exponent = -mk + kappa;
+        result
     }
 
-    round_weed_counted(buffer, *length, fractionals_mut, one.f(), w_error_mut, kappa)
-}
-
-/// Provides a decimal representation of v.
-fn grisu3(v: f64, outptr: &mut &mut [u8], decimal_exponent: &mut i32) -> bool {
-    let w = Double::new(v).as_normalized_diy_fp();
-
-    let mut boundary_minus = DiyFp::new(0,0);
-    let mut boundary_plus = DiyFp::new(0,0);
-    Double::new(v).normalized_boundaries(&mut boundary_minus, &mut boundary_plus);
-
-    assert_eq!(boundary_plus.e(), w.e());
-
-    let mut ten_mk = DiyFp::new(0,0);
-    let mut mk: i32 = 0;
-
-    let ten_mk_minimal_binary_exponent =
-        K_MINIMAL_TARGET_EXPONENT - (w.e() + DiyFp::k_significand_size() as i32);
-    let ten_mk_maximal_binary_exponent =
-        K_MAXIMAL_TARGET_EXPONENT - (w.e() + DiyFp::k_significand_size() as i32);
-
-    PowersOfTenCache::get_cached_power_for_binary_exponent_range(
-        ten_mk_minimal_binary_exponent,
-        ten_mk_maximal_binary_exponent,
-        &mut ten_mk,
-        &mut mk,
-    );
-
-    assert!(
-        K_MINIMAL_TARGET_EXPONENT
-            <= w.e() + ten_mk.e() + DiyFp::k_significand_size() as i32
-            && K_MAXIMAL_TARGET_EXPONENT
-                >= w.e() + ten_mk.e() + DiyFp::k_significand_size() as i32
-    );
-
-    let scaled_w = DiyFp::times(w, ten_mk);
-    assert_eq!(
-        scaled_w.e(),
-        boundary_plus.e() + ten_mk.e() + DiyFp::k_significand_size() as i32
-    );
-
-    let scaled_boundary_minus = DiyFp::times(boundary_minus, ten_mk);
-    let scaled_boundary_plus = DiyFp::times(boundary_plus, ten_mk);
-
-    let mut kappa: i32 = 0;
-    let result = digit_gen(
-        scaled_boundary_minus,
-        scaled_w,
-        scaled_boundary_plus,
-        outptr,
-        &mut kappa,
-    );
-    *decimal_exponent = -mk + kappa;
-    result
-}
-
-/// The "counted" version of grisu3 (see above) only generates requested_digits
-/// number of digits.
-fn grisu3_counted(
-    v: f64,
-    requested_digits: i32,
-    buffer: &mut [char],
-    length: &mut usize,
-    decimal_exponent: &mut i32,
-) -> bool {
-    let w = Double::new(v).as_normalized_diy_fp();
-
-    let mut ten_mk = DiyFp::new(0,0);
-    let mut mk: i32 = 0;
-
-    let ten_mk_minimal_binary_exponent =
-        K_MINIMAL_TARGET_EXPONENT - (w.e() + DiyFp::k_significand_size() as i32);
-    let ten_mk_maximal_binary_exponent =
-        K_MAXIMAL_TARGET_EXPONENT - (w.e() + DiyFp::k_significand_size() as i32);
-
-    PowersOfTenCache::get_cached_power_for_binary_exponent_range(
-        ten_mk_minimal_binary_exponent,
-        ten_mk_maximal_binary_exponent,
-        &mut ten_mk,
-        &mut mk,
-    );
-
-    assert!(
-        K_MINIMAL_TARGET_EXPONENT
-            <= w.e() + ten_mk.e() + DiyFp::k_significand_size() as i32
-            && K_MAXIMAL_TARGET_EXPONENT
-                >= w.e() + ten_mk.e() + DiyFp::k_significand_size() as i32
-    );
-
-    let scaled_w = DiyFp::times(w, ten_mk);
-
-    let mut kappa: i32 = 0;
-    let result = digit_gen_counted(scaled_w, requested_digits, buffer, length, &mut kappa);
-    *decimal_exponent = -mk + kappa;
-    result
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum FastDtoaMode {
-    /// Generate the shortest representation possible.
-    Shortest,
-    /// Generate a representation with the specified number of digits.
-    Precision,
-}
-
-/// Provides a decimal representation of v.
-pub fn fast_dtoa(
-    v: f64,
-    mode: FastDtoaMode,
-    requested_digits: i32,
-    buffer: &mut [char],
-    length: &mut usize,
-    decimal_point: &mut i32,
-) -> bool {
-    assert!(v > 0.0);
-    assert!(!Double::new(v).is_special());
-
-    let mut result = false;
-    let mut decimal_exponent = 0;
-
-    match mode {
-        FastDtoaMode::Shortest => {
-            let mut outptr_bytes = buffer.iter_mut().map(|c| *c as u8).collect::<Vec<u8>>();
-            let mut outptr_slice: &mut [u8] = outptr_bytes.as_mut_slice();
-            let mut outptr: &mut &mut [u8] = &mut outptr_slice;
-            
-            result = grisu3(v, outptr, &mut decimal_exponent);
-
-            let len = buffer.len() - outptr.len();
-            *length = len;
-
-             
-        }
-        FastDtoaMode::Precision => {
-            let mut local_length = 0;
-            result = grisu3_counted(
-                v,
-                requested_digits,
-                buffer,
-                &mut local_length,
-                &mut decimal_exponent,
-            );
-            *length = local_length;
+    /// Provides a decimal representation of v.
+    /// The result should be interpreted as buffer * 10^(point - length).
+    ///
+    /// Precondition:
+    ///   * v must be a strictly positive finite double.
+    ///
+    /// Returns true if it succeeds, otherwise the result can not be trusted.
+    /// There will be *length digits inside the buffer (not null-terminated).
+    /// If the function returns true and mode equals
+    ///  - Shortest, then
+    ///    the parameter requested_digits is ignored.
+    ///    The result satisfies
+    ///    v == (double) (buffer * 10^(point - length)).
+    ///    The digits in the buffer are the shortest representation possible.
+    ///    E.g. if 0.099999999999 and 0.1 represent the same double then "1"
+    ///    is returned with point = 0.
+    ///    The last digit will be closest to the actual v. That is, even if
+    ///    several digits might correctly yield 'v' when read again, the
+    ///    buffer will contain the one closest to v.
+    ///  - Precision, then
+    ///    the buffer contains requested_digits digits.
+    ///    the difference v - (buffer * 10^(point-length)) is closest to zero
+    ///    for all possible representations of requested_digits digits.
+    ///    If there are two values that are equally close, then fast_dtoa
+    ///    returns false.
+    ///
+    /// For both modes the buffer must be large enough to hold the result.
+    pub fn fast_dtoa(v: f64, mode: FastDtoaMode, requested_digits: i32, buffer: &mut [u8], length: &mut i32, decimal_point: &mut i32) -> bool {
+        debug_assert!(v > 0.0);
+        debug_assert!(!Double::from_f64(v).is_special());
+
+        let mut decimal_exponent = 0;
+        let result = match mode {
+            FastDtoaMode::Shortest => grisu3(v, buffer, length, &mut decimal_exponent),
+            FastDtoaMode::Precision => grisu3_counted(v, requested_digits, buffer, length, &mut decimal_exponent),
+        };
+        if result {
+            *decimal_point = *length + decimal_exponent;
         }
+        result
     }
-
-    if result {
-        *decimal_point = *length as i32 + decimal_exponent;
-        // buffer[*length] = '\0'; // String termination is not required in Rust
-    }
-    result
-}
\ No newline at end of file
+}
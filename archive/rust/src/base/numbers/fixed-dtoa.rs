@@ -3,10 +3,199 @@
 // found in the LICENSE file.
 
 pub mod fixed_dtoa {
+    use crate::base::numbers::double::double::Double;
+
+    const K_DOUBLE_SIGNIFICAND_SIZE: i32 = 53; // Includes the hidden bit.
+
+    fn fill_digits32_fixed_length(mut number: u32, requested_length: i32, buffer: &mut [u8], length: &mut i32) {
+        for i in (0..requested_length).rev() {
+            buffer[(*length + i) as usize] = b'0' + (number % 10) as u8;
+            number /= 10;
+        }
+        *length += requested_length;
+    }
+
+    fn fill_digits32(mut number: u32, buffer: &mut [u8], length: &mut i32) {
+        let start = *length as usize;
+        let mut number_length = 0;
+        // We fill the digits in reverse order and exchange them afterwards.
+        while number != 0 {
+            let digit = (number % 10) as u8;
+            number /= 10;
+            buffer[start + number_length] = b'0' + digit;
+            number_length += 1;
+        }
+        // Exchange the digits.
+        buffer[start..start + number_length].reverse();
+        *length += number_length as i32;
+    }
+
+    fn fill_digits64_fixed_length(mut number: u64, buffer: &mut [u8], length: &mut i32) {
+        const K_TEN7: u64 = 10_000_000;
+        // For efficiency cut the number into 3 u32 parts, and print those.
+        let part2 = (number % K_TEN7) as u32;
+        number /= K_TEN7;
+        let part1 = (number % K_TEN7) as u32;
+        let part0 = (number / K_TEN7) as u32;
+
+        fill_digits32_fixed_length(part0, 3, buffer, length);
+        fill_digits32_fixed_length(part1, 7, buffer, length);
+        fill_digits32_fixed_length(part2, 7, buffer, length);
+    }
+
+    fn fill_digits64(mut number: u64, buffer: &mut [u8], length: &mut i32) {
+        const K_TEN7: u64 = 10_000_000;
+        // For efficiency cut the number into 3 u32 parts, and print those.
+        let part2 = (number % K_TEN7) as u32;
+        number /= K_TEN7;
+        let part1 = (number % K_TEN7) as u32;
+        let part0 = (number / K_TEN7) as u32;
+
+        if part0 != 0 {
+            fill_digits32(part0, buffer, length);
+            fill_digits32_fixed_length(part1, 7, buffer, length);
+            fill_digits32_fixed_length(part2, 7, buffer, length);
+        } else if part1 != 0 {
+            fill_digits32(part1, buffer, length);
+            fill_digits32_fixed_length(part2, 7, buffer, length);
+        } else {
+            fill_digits32(part2, buffer, length);
+        }
+    }
+
+    fn dtoa_round_up(buffer: &mut [u8], length: &mut i32, decimal_point: &mut i32) {
+        // An empty buffer represents 0.
+        if *length == 0 {
+            buffer[0] = b'1';
+            *decimal_point = 1;
+            *length = 1;
+            return;
+        }
+        // Round the last digit until we either have a digit that was not '9'
+        // or until we reached the first digit.
+        let len = *length as usize;
+        buffer[len - 1] += 1;
+        for i in (1..len).rev() {
+            if buffer[i] != b'0' + 10 {
+                return;
+            }
+            buffer[i] = b'0';
+            buffer[i - 1] += 1;
+        }
+        // If the first digit is now '0' + 10, we would need to set it to '0'
+        // and add a '1' in front. However we reach the first digit only if all
+        // following digits had been '9' before rounding up. Now all trailing
+        // digits are '0' and we simply switch the first digit to '1' and
+        // update the decimal-point (indicating that the point is now one digit
+        // to the right).
+        if buffer[0] == b'0' + 10 {
+            buffer[0] = b'1';
+            *decimal_point += 1;
+        }
+    }
+
+    /// The given fractionals number represents a fixed-point number with
+    /// binary point at bit (-exponent).
+    /// Preconditions:
+    ///   -128 <= exponent <= 0.
+    ///   0 <= fractionals * 2^exponent < 1
+    ///   The buffer holds the result.
+    /// The function will round its result. During the rounding-process digits
+    /// not generated by this function might be updated, and the decimal-point
+    /// variable might be updated. If this function generates the digits 99
+    /// and the buffer already contained "199" (thus yielding a buffer of
+    /// "19999") then a rounding-up will change the contents of the buffer to
+    /// "20000".
+    fn fill_fractionals(
+        mut fractionals: u64,
+        exponent: i32,
+        fractional_count: i32,
+        buffer: &mut [u8],
+        length: &mut i32,
+        decimal_point: &mut i32,
+    ) {
+        debug_assert!((-128..=0).contains(&exponent));
+        if -exponent <= 64 {
+            // One 64 bit number is sufficient.
+            debug_assert_eq!(fractionals >> 56, 0);
+            let mut point = -exponent;
+            for _ in 0..fractional_count {
+                if fractionals == 0 {
+                    break;
+                }
+                // Instead of multiplying by 10 we multiply by 5 and adjust the
+                // point location. This way the fractionals variable will not
+                // overflow.
+                // Invariant at the beginning of the loop: fractionals < 2^point.
+                // Initially we have: point <= 64 and fractionals < 2^56
+                // After each iteration the point is decremented by one.
+                // Note that 5^3 = 125 < 128 = 2^7.
+                // Therefore three iterations of this loop will not overflow
+                // fractionals (even without the subtraction at the end of the
+                // loop body). At this time point will satisfy point <= 61 and
+                // therefore fractionals < 2^point and any further
+                // multiplication of fractionals by 5 will not overflow.
+                fractionals *= 5;
+                point -= 1;
+                let digit = fractionals >> point;
+                debug_assert!(digit <= 9);
+                buffer[*length as usize] = b'0' + digit as u8;
+                *length += 1;
+                fractionals -= digit << point;
+            }
+            // If the first bit after the point is set we have to round up.
+            debug_assert!(fractionals == 0 || point > 0);
+            if fractionals != 0 && ((fractionals >> (point - 1)) & 1) == 1 {
+                dtoa_round_up(buffer, length, decimal_point);
+            }
+        } else {
+            // We need 128 bits.
+            debug_assert!(64 < -exponent && -exponent <= 128);
+            let mut fractionals128 = (fractionals as u128) << (128 + exponent);
+            let mut point = 128;
+            for _ in 0..fractional_count {
+                if fractionals128 == 0 {
+                    break;
+                }
+                // As before: instead of multiplying by 10 we multiply by 5 and
+                // adjust the point location.
+                // This multiplication will not overflow for the same reasons
+                // as before.
+                fractionals128 *= 5;
+                point -= 1;
+                let digit = fractionals128 >> point;
+                debug_assert!(digit <= 9);
+                buffer[*length as usize] = b'0' + digit as u8;
+                *length += 1;
+                fractionals128 -= digit << point;
+            }
+            if (fractionals128 >> (point - 1)) & 1 == 1 {
+                dtoa_round_up(buffer, length, decimal_point);
+            }
+        }
+    }
+
+    /// Removes leading and trailing zeros.
+    /// If leading zeros are removed then the decimal point position is
+    /// adjusted.
+    fn trim_zeros(buffer: &mut [u8], length: &mut i32, decimal_point: &mut i32) {
+        while *length > 0 && buffer[*length as usize - 1] == b'0' {
+            *length -= 1;
+        }
+        let mut first_non_zero = 0;
+        while first_non_zero < *length && buffer[first_non_zero as usize] == b'0' {
+            first_non_zero += 1;
+        }
+        if first_non_zero != 0 {
+            buffer.copy_within(first_non_zero as usize..*length as usize, 0);
+            *length -= first_non_zero;
+            *decimal_point -= first_non_zero;
+        }
+    }
+
     /// Produces digits necessary to print a given number with
     /// `fractional_count` digits after the decimal point.
-    /// The buffer must be big enough to hold the result plus one terminating null
-    /// character.
+    /// The buffer must be big enough to hold the result.
     ///
     /// The produced digits might be too short in which case the caller has to fill
     /// the gaps with '0's.
@@ -18,7 +207,7 @@ pub mod fixed_dtoa {
     /// shortest representation of the input.
     ///
     /// This method only works for some parameters. If it can't handle the input it
-    /// returns `false`. The output is null-terminated when the function succeeds.
+    /// returns `false`.
     pub fn fast_fixed_dtoa(
         v: f64,
         fractional_count: i32,
@@ -26,13 +215,93 @@ pub mod fixed_dtoa {
         length: &mut i32,
         decimal_point: &mut i32,
     ) -> bool {
-        // Placeholder implementation.  A full port would require a
-        // significant amount of numerical code.
-        // This stub always returns false.
-
-        // This is the safest way to indicate failure without doing anything unsafe.
+        const K_MAX_UINT32: u64 = 0xFFFF_FFFF;
+        let mut significand = Double::from_f64(v).significand();
+        let exponent = Double::from_f64(v).exponent();
+        // v = significand * 2^exponent (with significand a 53bit integer).
+        // If the exponent is larger than 20 (i.e. we may have a 73bit number)
+        // then we don't know how to compute the representation. 2^73 ~=
+        // 9.5*10^21. If necessary this limit could probably be increased, but
+        // we don't need more.
+        if exponent > 20 {
+            return false;
+        }
+        if fractional_count > 20 {
+            return false;
+        }
         *length = 0;
-        *decimal_point = 0;
-        false
+        // At most K_DOUBLE_SIGNIFICAND_SIZE bits of the significand are
+        // non-zero. Given a 64 bit integer we have 11 0s followed by 53
+        // potentially non-zero bits:  0..11*..0xxx..53*..xx
+        if exponent + K_DOUBLE_SIGNIFICAND_SIZE > 64 {
+            // The exponent must be > 11.
+            //
+            // We know that v = significand * 2^exponent.
+            // And the exponent > 11.
+            // We simplify the task by dividing v by 10^17.
+            // The quotient delivers the first digits, and the remainder fits
+            // into a 64 bit number.
+            // Dividing by 10^17 is equivalent to dividing by 5^17*2^17.
+            const K_FIVE17: u64 = 0xB1_A2BC_2EC5; // 5^17
+            let mut divisor = K_FIVE17;
+            let divisor_power = 17;
+            let mut dividend = significand;
+            let quotient: u32;
+            let remainder: u64;
+            // Let v = f * 2^e with f == significand and e == exponent.
+            // Then need q (quotient) and r (remainder) as follows:
+            //   v            = q * 10^17       + r
+            //   f * 2^e      = q * 10^17       + r
+            //   f * 2^e      = q * 5^17 * 2^17 + r
+            // If e > 17 then
+            //   f * 2^(e-17) = q * 5^17        + r/2^17
+            // else
+            //   f  = q * 5^17 * 2^(17-e) + r/2^e
+            if exponent > divisor_power {
+                // We only allow exponents of up to 20 and therefore (17 - e) <= 3
+                dividend <<= exponent - divisor_power;
+                quotient = (dividend / divisor) as u32;
+                remainder = (dividend % divisor) << divisor_power;
+            } else {
+                divisor <<= divisor_power - exponent;
+                quotient = (dividend / divisor) as u32;
+                remainder = (dividend % divisor) << exponent;
+            }
+            fill_digits32(quotient, buffer, length);
+            fill_digits64_fixed_length(remainder, buffer, length);
+            *decimal_point = *length;
+        } else if exponent >= 0 {
+            // 0 <= exponent <= 11
+            significand <<= exponent;
+            fill_digits64(significand, buffer, length);
+            *decimal_point = *length;
+        } else if exponent > -K_DOUBLE_SIGNIFICAND_SIZE {
+            // We have to cut the number.
+            let integrals = significand >> -exponent;
+            let fractionals = significand - (integrals << -exponent);
+            if integrals > K_MAX_UINT32 {
+                fill_digits64(integrals, buffer, length);
+            } else {
+                fill_digits32(integrals as u32, buffer, length);
+            }
+            *decimal_point = *length;
+            fill_fractionals(fractionals, exponent, fractional_count, buffer, length, decimal_point);
+        } else if exponent < -128 {
+            // This configuration (with at most 20 digits) means that all digits
+            // must be 0.
+            debug_assert!(fractional_count <= 20);
+            *length = 0;
+            *decimal_point = -fractional_count;
+        } else {
+            *decimal_point = 0;
+            fill_fractionals(significand, exponent, fractional_count, buffer, length, decimal_point);
+        }
+        trim_zeros(buffer, length, decimal_point);
+        if *length == 0 {
+            // The string is empty and the decimal_point thus has no importance.
+            // Mimick Gay's dtoa and and set it to -fractional_count.
+            *decimal_point = -fractional_count;
+        }
+        true
     }
-}
\ No newline at end of file
+}
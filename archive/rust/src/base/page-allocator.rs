@@ -2,148 +2,201 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub type Address = usize;
+pub const K_NULL_ADDRESS: Address = 0;
+
+/// Memory permissions of allocated pages.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Permission {
+    kNoAccess,
+    kRead,
+    kReadWrite,
+    kReadWriteExecute,
+    kReadExecute,
+    /// Like `kNoAccess`, but the pages will later be made executable.
+    kNoAccessWillJitLater,
+}
+
+impl Permission {
+    fn protection(self) -> libc::c_int {
+        match self {
+            Permission::kNoAccess | Permission::kNoAccessWillJitLater => libc::PROT_NONE,
+            Permission::kRead => libc::PROT_READ,
+            Permission::kReadWrite => libc::PROT_READ | libc::PROT_WRITE,
+            Permission::kReadWriteExecute => libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            Permission::kReadExecute => libc::PROT_READ | libc::PROT_EXEC,
+        }
+    }
+}
+
+/// Interface for allocating pages of memory. All addresses and sizes must
+/// be multiples of `allocate_page_size()` unless noted otherwise.
+pub trait PageAllocator: Send + Sync {
+    /// Granularity of allocations.
+    fn allocate_page_size(&self) -> usize;
+
+    /// Granularity of permission changes.
+    fn commit_page_size(&self) -> usize;
 
-pub mod base {
-    use std::ptr::null_mut;
+    /// Allocates `size` bytes aligned to `alignment`, preferably at `hint`.
+    /// Returns `None` if no memory is available.
+    fn allocate_pages(&self, hint: Address, size: usize, alignment: usize, access: Permission) -> Option<Address>;
 
-    pub trait SharedMemory {} // Placeholder for SharedMemory functionality
+    fn free_pages(&self, address: Address, size: usize) -> bool;
 
-    // Dummy implementations for platform types, replace with actual types.
-    pub type Permission = i32;
+    /// Shrinks an allocation from `size` to `new_size` bytes.
+    fn release_pages(&self, address: Address, size: usize, new_size: usize) -> bool;
 
-    /// Represents a page allocator for managing memory pages.
-    pub struct PageAllocator {
-        allocate_page_size_: usize,
-        commit_page_size_: usize,
+    fn set_permissions(&self, address: Address, size: usize, access: Permission) -> bool;
+
+    /// Makes decommitted or discarded pages accessible again.
+    fn recommit_pages(&self, address: Address, size: usize, access: Permission) -> bool {
+        self.set_permissions(address, size, access)
     }
 
-    impl PageAllocator {
-        /// Creates a new `PageAllocator`.
-        pub fn new() -> Self {
-            // Initialize with platform-specific values or reasonable defaults.
-            let allocate_page_size_ = 4096; // Example page size
-            let commit_page_size_ = 4096;   // Example page size
-            PageAllocator {
-                allocate_page_size_,
-                commit_page_size_,
-            }
-        }
+    /// Returns the physical memory of the pages to the OS. They stay
+    /// accessible and read as zero afterwards.
+    fn discard_system_pages(&self, address: Address, size: usize) -> bool;
 
-        /// Returns the allocation page size.
-        pub fn allocate_page_size(&self) -> usize {
-            self.allocate_page_size_
-        }
+    /// Returns the physical memory of the pages to the OS and makes them
+    /// inaccessible. They read as zero once recommitted.
+    fn decommit_pages(&self, address: Address, size: usize) -> bool;
+}
 
-        /// Returns the commit page size.
-        pub fn commit_page_size(&self) -> usize {
-            self.commit_page_size_
-        }
+/// Page allocator backed directly by the OS.
+#[derive(Default)]
+pub struct OsPageAllocator {
+    random_state: AtomicU64,
+}
 
-        /// Sets a random seed for memory mapping.
-        pub fn set_random_mmap_seed(&mut self, _seed: i64) {
-            // Implement the platform-specific logic for setting the random seed.
-            // This is often used for ASLR (Address Space Layout Randomization).
-        }
+impl OsPageAllocator {
+    pub const fn new() -> Self {
+        OsPageAllocator { random_state: AtomicU64::new(0) }
+    }
 
-        /// Returns a random memory mapping address.
-        pub fn get_random_mmap_addr(&self) -> *mut std::ffi::c_void {
-            // Implement the platform-specific logic for obtaining a random mmap address.
-            null_mut() // Placeholder
-        }
+    pub fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
 
-        /// Allocates pages with a specified hint, size, alignment, and access permissions.
-        pub fn allocate_pages(
-            &self,
-            _hint: *mut std::ffi::c_void,
-            size: usize,
-            _alignment: usize,
-            _access: Permission,
-        ) -> *mut std::ffi::c_void {
-            // Implement the platform-specific memory allocation logic.
-            // Consider using `std::alloc::alloc` and `std::alloc::dealloc`.
-
-            unsafe {
-                let layout = std::alloc::Layout::from_size_align(size, _alignment).unwrap();
-                let ptr = std::alloc::alloc(layout);
-                if ptr.is_null() {
-                    null_mut()
-                } else {
-                    ptr as *mut std::ffi::c_void
-                }
-            }
+    pub fn set_random_mmap_seed(&self, seed: i64) {
+        if seed != 0 {
+            self.random_state.store(seed as u64, Ordering::Relaxed);
         }
+    }
 
-        /// Checks if shared pages can be allocated.
-        pub fn can_allocate_shared_pages(&self) -> bool {
-            // Implement the platform-specific check for shared memory allocation support.
-            false // Placeholder
+    /// Returns a random page-aligned address in the lower half of the
+    /// user address space, to be used as an allocation hint.
+    pub fn get_random_mmap_addr(&self) -> Address {
+        let mut state = self.random_state.load(Ordering::Relaxed);
+        if state == 0 {
+            state = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0x5eed, |duration| duration.as_nanos() as u64)
+                | 1;
         }
+        // splitmix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        self.random_state.store(state, Ordering::Relaxed);
+        let mut random = state;
+        random = (random ^ (random >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        random = (random ^ (random >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        random ^= random >> 31;
+        // Keep hints in [4GB, 64TB), away from the null region and the
+        // region where the OS places mappings without a hint.
+        let address = (random as Address & ((1 << 46) - 1)) | (1 << 32);
+        address & !(Self::page_size() - 1)
+    }
 
-        /// Allocates shared pages with a specified size and original address.
-        pub fn allocate_shared_pages(
-            &self,
-            _size: usize,
-            _original_address: *const std::ffi::c_void,
-        ) -> Option<Box<dyn SharedMemory>> {
-            // Implement the platform-specific shared memory allocation logic.
-            // This would involve creating a shared memory object and returning it.
-            None // Placeholder
+    fn map(hint: Address, size: usize, access: Permission) -> Option<Address> {
+        let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+        if access == Permission::kNoAccess || access == Permission::kNoAccessWillJitLater {
+            flags |= libc::MAP_NORESERVE;
         }
-
-        /// Frees allocated pages.
-        pub fn free_pages(&self, address: *mut std::ffi::c_void, size: usize) -> bool {
-            // Implement the platform-specific memory deallocation logic.
-            // Consider using `std::alloc::dealloc`.
-            if address.is_null() {
-                return false;
-            }
-            unsafe {
-                let layout = std::alloc::Layout::from_size_align(size, std::mem::align_of::<usize>()).unwrap();
-                std::alloc::dealloc(address as *mut u8, layout);
-            }
-
-            true
+        let result = unsafe { libc::mmap(hint as *mut libc::c_void, size, access.protection(), flags, -1, 0) };
+        if result == libc::MAP_FAILED {
+            None
+        } else {
+            Some(result as Address)
         }
+    }
+}
 
-        /// Releases pages, potentially reducing the allocated size.
-        pub fn release_pages(&self, _address: *mut std::ffi::c_void, _size: usize, _new_size: usize) -> bool {
-            // Implement the platform-specific memory release logic.
-            // This may involve shrinking the allocated memory region.
-            false // Placeholder
-        }
+/// Returns the page allocator of the whole process.
+pub fn get_platform_page_allocator() -> &'static OsPageAllocator {
+    static PAGE_ALLOCATOR: OsPageAllocator = OsPageAllocator::new();
+    &PAGE_ALLOCATOR
+}
 
-        /// Sets the access permissions for a memory region.
-        pub fn set_permissions(&self, _address: *mut std::ffi::c_void, _size: usize, _access: Permission) -> bool {
-            // Implement the platform-specific permission setting logic.
-            // This often involves using `mprotect` on Unix-like systems or `VirtualProtect` on Windows.
-            false // Placeholder
-        }
+impl PageAllocator for OsPageAllocator {
+    fn allocate_page_size(&self) -> usize {
+        Self::page_size()
+    }
 
-        /// Recommits pages with a specified access.
-        pub fn recommit_pages(&self, _address: *mut std::ffi::c_void, _size: usize, _access: Permission) -> bool {
-            // Implement recommitting memory pages (if applicable).
-            false // Placeholder
-        }
+    fn commit_page_size(&self) -> usize {
+        Self::page_size()
+    }
 
-        /// Discards system pages.
-        pub fn discard_system_pages(&self, _address: *mut std::ffi::c_void, _size: usize) -> bool {
-            // Implement discarding memory pages (if applicable).
-            false // Placeholder
+    fn allocate_pages(&self, hint: Address, size: usize, alignment: usize, access: Permission) -> Option<Address> {
+        let page_size = Self::page_size();
+        debug_assert_eq!(size % page_size, 0);
+        debug_assert!(alignment.is_power_of_two() && alignment >= page_size);
+        let hint = hint & !(alignment - 1);
+        if alignment == page_size {
+            return Self::map(hint, size, access);
         }
-
-        /// Decommits pages.
-        pub fn decommit_pages(&self, _address: *mut std::ffi::c_void, _size: usize) -> bool {
-            // Implement decommitting memory pages (if applicable).
-            false // Placeholder
+        // Over-allocate and trim the misaligned head and the tail.
+        let request_size = size + alignment - page_size;
+        let base = Self::map(hint, request_size, access)?;
+        let aligned_base = (base + alignment - 1) & !(alignment - 1);
+        let prefix_size = aligned_base - base;
+        if prefix_size != 0 {
+            self.free_pages(base, prefix_size);
         }
+        let suffix_size = request_size - prefix_size - size;
+        if suffix_size != 0 {
+            self.free_pages(aligned_base + size, suffix_size);
+        }
+        Some(aligned_base)
+    }
 
-        /// Seals pages, preventing further modifications.
-        pub fn seal_pages(&self, _address: *mut std::ffi::c_void, _size: usize) -> bool {
-            // Implement sealing memory pages (if applicable).
-            false // Placeholder
+    fn free_pages(&self, address: Address, size: usize) -> bool {
+        unsafe { libc::munmap(address as *mut libc::c_void, size) == 0 }
+    }
+
+    fn release_pages(&self, address: Address, size: usize, new_size: usize) -> bool {
+        debug_assert!(new_size < size);
+        self.free_pages(address + new_size, size - new_size)
+    }
+
+    fn set_permissions(&self, address: Address, size: usize, access: Permission) -> bool {
+        let result = unsafe { libc::mprotect(address as *mut libc::c_void, size, access.protection()) };
+        if result == 0 && access == Permission::kNoAccess {
+            // Inaccessible pages need no physical memory.
+            self.discard_system_pages(address, size);
         }
+        result == 0
+    }
+
+    fn discard_system_pages(&self, address: Address, size: usize) -> bool {
+        unsafe { libc::madvise(address as *mut libc::c_void, size, libc::MADV_DONTNEED) == 0 }
+    }
 
-        //Remap Shared function implementation missing, since its usage is limited to the crate.
+    fn decommit_pages(&self, address: Address, size: usize) -> bool {
+        // Mapping fresh inaccessible pages over the range releases the
+        // physical memory and guarantees the pages read as zero later.
+        let result = unsafe {
+            libc::mmap(
+                address as *mut libc::c_void,
+                size,
+                libc::PROT_NONE,
+                libc::MAP_FIXED | libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        result as Address == address
     }
-}
\ No newline at end of file
+}
@@ -2,24 +2,22 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::cmp::{max, min};
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
-use std::ops::{Deref, DerefMut};
-use std::ptr::NonNull;
 
-//use crate::base::bits; // Assuming this is a local module
+pub type Address = usize;
 
 // If |free_size| < |region_size| * |kMaxLoadFactorForRandomization| stop trying
 // to randomize region allocation.
 const K_MAX_LOAD_FACTOR_FOR_RANDOMIZATION: f64 = 0.40;
 
 // Max number of attempts to allocate page at random address.
-const K_MAX_RANDOMIZATION_ATTEMPTS: i32 = 3;
+const K_MAX_RANDOMIZATION_ATTEMPTS: usize = 3;
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum RegionState {
     Free,
+    /// Allocated, but never handed out, e.g. guard regions.
     Excluded,
     Allocated,
 }
@@ -34,8 +32,6 @@ impl fmt::Display for RegionState {
     }
 }
 
-pub type Address = usize; // Assuming Address is a usize
-
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct Region {
     begin: Address,
@@ -60,593 +56,269 @@ impl Region {
         self.size
     }
 
-    pub fn set_size(&mut self, size: usize) {
-        self.size = size;
-    }
-
     pub fn state(&self) -> RegionState {
         self.state
     }
 
-    pub fn set_state(&mut self, state: RegionState) {
-        self.state = state;
-    }
-
     pub fn is_free(&self) -> bool {
         self.state == RegionState::Free
     }
 
-    pub fn is_excluded(&self) -> bool {
-        self.state == RegionState::Excluded
-    }
-
-    pub fn is_allocated(&self) -> bool {
-        self.state == RegionState::Allocated
-    }
-
     pub fn contains(&self, address: Address) -> bool {
         address >= self.begin() && address < self.end()
     }
-
-    pub fn contains_range(&self, address: Address, size: usize) -> bool {
-        address >= self.begin() && (address + size) <= self.end()
-    }
-
-    pub fn print(&self, os: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            os,
-            "[{:#x}, {:#x}), size: {}, {}",
-            self.begin(),
-            self.end(),
-            self.size(),
-            self.state()
-        )
-    }
-}
-
-impl Ord for Region {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.end().cmp(&other.end())
-    }
 }
 
-impl PartialOrd for Region {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:#x}, {:#x}), size: {}, {}", self.begin(), self.end(), self.size(), self.state())
     }
 }
 
-pub type OnSplitCallback = Option<Box<dyn Fn(Address, usize)>>;
-pub type OnMergeCallback = Option<Box<dyn Fn(Address, usize)>>;
-
+/// Manages the pages of a memory region. Regions are split on allocation
+/// and merged with free neighbours when freed. Not thread-safe; callers
+/// lock around it.
 pub struct RegionAllocator {
-    whole_region_: Region,
-    region_size_in_pages_: usize,
-    max_load_for_randomization_: usize,
-    free_size_: usize,
-    page_size_: usize,
-    all_regions_: BTreeSet<NonNull<Region>>,
-    free_regions_: BTreeSet<NonNull<Region>>,
-    on_split_: OnSplitCallback,
-    on_merge_: OnMergeCallback,
-}
-
-// Helper function to create NonNull<Region> from a Region
-fn region_to_nonnull(region: Region) -> NonNull<Region> {
-    unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(region))) }
-}
-
-// Helper function to extract a Region from NonNull<Region> safely
-fn region_from_nonnull(ptr: NonNull<Region>) -> Box<Region> {
-    unsafe { Box::from_raw(ptr.as_ptr()) }
+    whole_region: Region,
+    page_size: usize,
+    max_load_for_randomization: usize,
+    free_size: usize,
+    /// All regions by start address; they tile the whole region.
+    all_regions: BTreeMap<Address, Region>,
+    /// Free regions ordered by (size, start) for best-fit allocation.
+    free_regions: BTreeSet<(usize, Address)>,
 }
 
 impl RegionAllocator {
-    pub fn new(memory_region_begin: Address, memory_region_size: usize, page_size: usize) -> Self {
-        assert!(memory_region_begin < memory_region_begin + memory_region_size);
-        //assert!(bits::is_power_of_two(page_size)); // Assuming is_power_of_two functionality
-        assert!(memory_region_size % page_size == 0); //IsAligned
-        assert!(memory_region_begin % page_size == 0); //IsAligned
-
-        let whole_region_ = Region::new(
-            memory_region_begin,
-            memory_region_size,
-            RegionState::Free,
-        );
-        let region_size_in_pages_ = memory_region_size / page_size;
-        let max_load_for_randomization_ =
-            (memory_region_size as f64 * K_MAX_LOAD_FACTOR_FOR_RANDOMIZATION) as usize;
-        let mut all_regions_: BTreeSet<NonNull<Region>> = BTreeSet::new();
-        let mut free_regions_: BTreeSet<NonNull<Region>> = BTreeSet::new();
-
-        let region = region_to_nonnull(whole_region_);
-
-        all_regions_.insert(region);
+    pub const K_ALLOCATION_FAILURE: Address = Address::MAX;
 
+    pub fn new(memory_region_begin: Address, memory_region_size: usize, page_size: usize) -> Self {
+        assert!(page_size.is_power_of_two());
+        assert_eq!(memory_region_begin % page_size, 0);
+        assert_eq!(memory_region_size % page_size, 0);
+        assert!(memory_region_size > 0);
+        let whole_region = Region::new(memory_region_begin, memory_region_size, RegionState::Free);
         let mut allocator = RegionAllocator {
-            whole_region_: Region::new(
-                memory_region_begin,
-                memory_region_size,
-                RegionState::Free,
-            ),
-            region_size_in_pages_: region_size_in_pages_,
-            max_load_for_randomization_: max_load_for_randomization_,
-            free_size_: 0,
-            page_size_: page_size,
-            all_regions_: all_regions_,
-            free_regions_: free_regions_,
-            on_split_: None,
-            on_merge_: None,
+            whole_region,
+            page_size,
+            max_load_for_randomization: (memory_region_size as f64 * K_MAX_LOAD_FACTOR_FOR_RANDOMIZATION) as usize,
+            free_size: 0,
+            all_regions: BTreeMap::new(),
+            free_regions: BTreeSet::new(),
         };
-
-        allocator.FreeListAddRegion(unsafe { region.as_ref() });
-
+        allocator.insert(whole_region);
         allocator
     }
 
     pub fn begin(&self) -> Address {
-        self.whole_region_.begin()
+        self.whole_region.begin()
     }
 
     pub fn end(&self) -> Address {
-        self.whole_region_.end()
+        self.whole_region.end()
     }
 
     pub fn size(&self) -> usize {
-        self.whole_region_.size()
+        self.whole_region.size()
     }
 
     pub fn page_size(&self) -> usize {
-        self.page_size_
+        self.page_size
     }
 
     pub fn free_size(&self) -> usize {
-        self.free_size_
+        self.free_size
     }
 
-    fn find_region(&self, address: Address) -> Option<NonNull<Region>> {
-        if !self.whole_region_.contains(address) {
-            return None;
-        }
-
-        let key = Region::new(address, 0, RegionState::Free);
-        let mut iter = self.all_regions_.range(key..); // Use range instead of upper_bound
-
-        // Find the first region that *ends* after the address
-        while let Some(&region_ptr) = iter.next() {
-            let region = unsafe { region_ptr.as_ref() };
-            if region.contains(address) {
-                return Some(region_ptr);
-            }
-        }
-
-        None
+    pub fn allocated_size(&self) -> usize {
+        self.size() - self.free_size
     }
 
-    fn FreeListAddRegion(&mut self, region: &Region) {
-        self.free_size_ += region.size();
-        self.free_regions_.insert(unsafe {NonNull::new_unchecked(region as *const Region as *mut Region)});
+    pub fn contains(&self, address: Address) -> bool {
+        self.whole_region.contains(address)
     }
 
-    fn FreeListFindRegion(&self, size: usize) -> Option<NonNull<Region>> {
-        let key = Region::new(0, size, RegionState::Free);
-        let mut iter = self.free_regions_.range(key..);
-
-        if let Some(&region_ptr) = iter.next() {
-            let region = unsafe{region_ptr.as_ref()};
-            if region.size() >= size {
-                return Some(region_ptr);
-            }
-        }
-        None
+    pub fn contains_range(&self, address: Address, size: usize) -> bool {
+        address >= self.begin() && size <= self.end() - address
     }
 
-    fn FreeListRemoveRegion(&mut self, region: &Region) {
-        assert!(region.is_free());
-
-        // Create a NonNull<Region> from the raw pointer of the region
-        let region_ptr = unsafe { NonNull::new_unchecked(region as *const Region as *mut Region) };
-
-        if let Some(iter) = self.free_regions_.take(&region_ptr) {
-            let region = unsafe { iter.as_ref() };
-            assert!(region.size() <= self.free_size_);
-            self.free_size_ -= region.size();
-        }
+    fn is_aligned(&self, value: usize) -> bool {
+        value.is_multiple_of(self.page_size)
     }
 
-    fn Split(&mut self, region_ptr: NonNull<Region>, new_size: usize) -> NonNull<Region> {
-        let region = unsafe { region_ptr.as_mut() };
-        assert!(self.IsAligned(new_size, self.page_size_));
-        assert_ne!(new_size, 0);
-        assert!(region.size() > new_size);
-
-        if let Some(ref on_split) = self.on_split_ {
-            on_split(region.begin(), new_size);
+    fn insert(&mut self, region: Region) {
+        if region.is_free() {
+            self.free_size += region.size();
+            self.free_regions.insert((region.size(), region.begin()));
         }
+        self.all_regions.insert(region.begin(), region);
+    }
 
-        assert!(!region.is_excluded());
-        let state = region.state();
-        let new_region_begin = region.begin() + new_size;
-        let new_region_size = region.size() - new_size;
-        let new_region = Region::new(new_region_begin, new_region_size, state);
-        let new_region_ptr = region_to_nonnull(new_region);
-
-        if state == RegionState::Free {
-            self.FreeListRemoveRegion(region);
+    fn remove(&mut self, begin: Address) -> Region {
+        let region = self.all_regions.remove(&begin).expect("no region starts at this address");
+        if region.is_free() {
+            self.free_size -= region.size();
+            self.free_regions.remove(&(region.size(), region.begin()));
         }
-        region.set_size(new_size);
-
-        self.all_regions_.insert(new_region_ptr);
+        region
+    }
 
-        if state == RegionState::Free {
-            self.FreeListAddRegion(region);
-            self.FreeListAddRegion(unsafe {new_region_ptr.as_ref()});
+    /// Returns the region containing `address`.
+    fn find_region(&self, address: Address) -> Option<Region> {
+        if !self.contains(address) {
+            return None;
         }
-
-        region_ptr
+        self.all_regions.range(..=address).next_back().map(|(_, region)| *region)
     }
 
-    fn Merge(&mut self, prev_iter: NonNull<Region>, next_iter: NonNull<Region>) {
-        let prev = unsafe { prev_iter.as_mut() };
-        let next = unsafe { next_iter.as_mut() };
-        assert_eq!(prev.end(), next.begin());
-
-        if let Some(ref on_merge) = self.on_merge_ {
-            on_merge(prev.begin(), prev.size() + next.size());
+    /// Marks `[address, address + size)`, which must lie within a single
+    /// free region, as `state`, splitting off the remainders.
+    fn carve(&mut self, region: Region, address: Address, size: usize, state: RegionState) {
+        debug_assert!(region.is_free() && address >= region.begin() && address + size <= region.end());
+        self.remove(region.begin());
+        if address > region.begin() {
+            self.insert(Region::new(region.begin(), address - region.begin(), RegionState::Free));
+        }
+        self.insert(Region::new(address, size, state));
+        if address + size < region.end() {
+            self.insert(Region::new(address + size, region.end() - address - size, RegionState::Free));
         }
-
-        prev.set_size(prev.size() + next.size());
-        self.all_regions_.remove(&next_iter);
-
-        assert_eq!(self.free_regions_.get(&next_iter), None);
     }
 
-    pub fn AllocateRegion(&mut self, size: usize) -> Address {
-        assert_ne!(size, 0);
-        assert!(self.IsAligned(size, self.page_size_));
-
-        let region_ptr = self.FreeListFindRegion(size);
-
-        let region_ptr = match region_ptr {
-            Some(ptr) => ptr,
-            None => return 0, //kAllocationFailure, assuming 0 represents failure
+    /// Allocates `size` bytes from the smallest free region that fits.
+    /// Returns `K_ALLOCATION_FAILURE` if there is none.
+    pub fn allocate_region(&mut self, size: usize) -> Address {
+        assert!(size != 0 && self.is_aligned(size));
+        let Some(&(_, begin)) = self.free_regions.range((size, 0)..).next() else {
+            return Self::K_ALLOCATION_FAILURE;
         };
-
-        let region = unsafe{region_ptr.as_mut()};
-
-        if region.size() != size {
-            self.Split(region_ptr, size);
-        }
-        assert!(self.IsAligned(region.begin(), self.page_size_));
-        assert_eq!(region.size(), size);
-
-        self.FreeListRemoveRegion(region);
-        region.set_state(RegionState::Allocated);
-        region.begin()
-    }
-
-    pub fn AllocateRegionRng(
-        &mut self,
-        rng: &mut dyn RandomNumberGenerator,
-        size: usize,
-    ) -> Address {
-        if self.free_size() >= self.max_load_for_randomization_ {
-            let mut random = 0u64;
-
-            for _i in 0..K_MAX_RANDOMIZATION_ATTEMPTS {
-                let random_bytes = rng.next_bytes(8);
-                random = u64::from_ne_bytes(random_bytes.try_into().unwrap());
-                let random_offset = self.page_size_ * (random as usize % self.region_size_in_pages_);
-                let address = self.begin() + random_offset;
-                if self.AllocateRegionAt(address, size, RegionState::Allocated) {
+        let region = self.all_regions[&begin];
+        self.carve(region, begin, size, RegionState::Allocated);
+        begin
+    }
+
+    /// Like `allocate_region`, but tries random addresses first while the
+    /// region is lightly used.
+    pub fn allocate_region_randomized(&mut self, mut next_random: impl FnMut() -> u64, size: usize) -> Address {
+        if self.free_size >= self.max_load_for_randomization {
+            let pages = (self.size() / self.page_size) as u64;
+            for _ in 0..K_MAX_RANDOMIZATION_ATTEMPTS {
+                let address = self.begin() + (next_random() % pages) as usize * self.page_size;
+                if self.allocate_region_at(address, size, RegionState::Allocated) {
                     return address;
                 }
             }
         }
-        self.AllocateRegion(size)
+        self.allocate_region(size)
     }
 
-    pub fn AllocateRegionAt(
-        &mut self,
-        requested_address: Address,
-        size: usize,
-        region_state: RegionState,
-    ) -> bool {
-        assert!(self.IsAligned(requested_address, self.page_size_));
-        assert_ne!(size, 0);
-        assert!(self.IsAligned(size, self.page_size_));
-        assert_ne!(region_state, RegionState::Free);
-
-        let requested_end = requested_address + size;
-        assert!(requested_end <= self.end());
-
-        let region_ptr = self.find_region(requested_address);
-
-        let region_ptr = match region_ptr {
-            Some(ptr) => ptr,
-            None => return false,
-        };
-
-        let region = unsafe { region_ptr.as_ref() };
-
-        if !region.is_free() || region.end() < requested_end {
+    /// Allocates exactly `[requested_address, requested_address + size)` if
+    /// it is free.
+    pub fn allocate_region_at(&mut self, requested_address: Address, size: usize, state: RegionState) -> bool {
+        assert!(self.is_aligned(requested_address) && size != 0 && self.is_aligned(size));
+        assert_ne!(state, RegionState::Free);
+        if !self.contains_range(requested_address, size) {
             return false;
         }
-
-        let region = unsafe { region_ptr.as_mut() };
-        if region.begin() != requested_address {
-            let new_size = requested_address - region.begin();
-            assert!(self.IsAligned(new_size, self.page_size_));
-            self.Split(region_ptr, new_size);
-        }
-
-        let region = unsafe { region_ptr.as_mut() };
-        if region.end() != requested_end {
-            self.Split(region_ptr, size);
-        }
-
-        let region = unsafe { region_ptr.as_mut() };
-
-        assert_eq!(region.begin(), requested_address);
-        assert_eq!(region.size(), size);
-
-        self.FreeListRemoveRegion(region);
-        region.set_state(region_state);
-        true
-    }
-
-    pub fn AllocateAlignedRegion(&mut self, size: usize, alignment: usize) -> Address {
-        assert!(self.IsAligned(size, self.page_size_));
-        assert!(self.IsAligned(alignment, self.page_size_));
-        assert!(alignment >= self.page_size_);
-
-        let padded_size = size + alignment - self.page_size_;
-        let region_ptr = self.FreeListFindRegion(padded_size);
-
-        let region_ptr = match region_ptr {
-            Some(ptr) => ptr,
-            None => {
-                let region_ptr = self.FreeListFindRegion(size);
-                match region_ptr{
-                    Some(ptr) => ptr,
-                    None => return 0
-                }
+        match self.find_region(requested_address) {
+            Some(region) if region.is_free() && requested_address + size <= region.end() => {
+                self.carve(region, requested_address, size, state);
+                true
             }
-        };
-
-        let region = unsafe{region_ptr.as_mut()};
-
-        if !self.IsAligned(region.begin(), alignment) {
-            let start = self.RoundUp(region.begin(), alignment);
-            if start + size > region.end() {
-                return 0;
-            }
-            self.Split(region_ptr, start - region.begin());
-        }
-
-        let region = unsafe{region_ptr.as_mut()};
-        assert!(self.IsAligned(region.begin(), alignment));
-
-        if region.size() != size {
-            self.Split(region_ptr, size);
+            _ => false,
         }
-
-        let region = unsafe{region_ptr.as_mut()};
-        assert!(self.IsAligned(region.begin(), alignment));
-        assert_eq!(region.size(), size);
-
-        self.FreeListRemoveRegion(region);
-        region.set_state(RegionState::Allocated);
-        region.begin()
     }
 
-    pub fn AllocateRegionHint(
-        &mut self,
-        hint: Address,
-        size: usize,
-        alignment: usize,
-    ) -> Address {
-        assert!(self.IsAligned(alignment, self.page_size()));
-        assert!(self.IsAligned(hint, alignment));
-
-        if hint != 0 && self.contains(hint, size) {
-            if self.AllocateRegionAt(hint, size, RegionState::Allocated) {
-                return hint;
+    /// Allocates `size` bytes at an address aligned to `alignment`.
+    pub fn allocate_aligned_region(&mut self, size: usize, alignment: usize) -> Address {
+        assert!(size != 0 && self.is_aligned(size));
+        assert!(alignment.is_power_of_two() && alignment >= self.page_size);
+        // Best fit among the free regions that can hold an aligned block.
+        let found = self.free_regions.range((size, 0)..).find_map(|&(_, begin)| {
+            let region = self.all_regions[&begin];
+            let aligned = (region.begin() + alignment - 1) & !(alignment - 1);
+            (aligned + size <= region.end()).then_some((region, aligned))
+        });
+        match found {
+            Some((region, aligned)) => {
+                self.carve(region, aligned, size, RegionState::Allocated);
+                aligned
             }
+            None => Self::K_ALLOCATION_FAILURE,
         }
-
-        let address = if alignment <= self.page_size() {
-            self.AllocateRegion(size)
-        } else {
-            self.AllocateAlignedRegion(size, alignment)
-        };
-
-        address
     }
 
-    pub fn TrimRegion(&mut self, address: Address, new_size: usize) -> usize {
-        assert!(self.IsAligned(new_size, self.page_size_));
-
-        let region_ptr = self.find_region(address);
-
-        let region_ptr = match region_ptr {
-            Some(ptr) => ptr,
-            None => return 0,
-        };
-
-        let region = unsafe { region_ptr.as_mut() };
-
-        if region.begin() != address || !region.is_allocated() {
-            return 0;
-        }
-
-        assert_eq!(self.free_regions_.get(&region_ptr), None);
-
-        let region = unsafe{region_ptr.as_mut()};
-        let mut region_iter = region_ptr;
-
-        if new_size > 0 {
-            self.Split(region_ptr, new_size);
-            region_iter = unsafe {
-              let mut regions = self.all_regions_.iter();
-              while let Some(&r) = regions.next(){
-                if unsafe {r.as_ref().begin()} == unsafe {region_ptr.as_ref().end()} {
-                  break;
-                }
-              }
-              let reg = regions.next().unwrap();
-              *reg
-            }; // Increment the iterator
+    /// Frees the allocated region starting at `address` and returns its
+    /// size, or 0 if there is no such region.
+    pub fn free_region(&mut self, address: Address) -> usize {
+        match self.all_regions.get(&address) {
+            Some(region) if !region.is_free() => {}
+            _ => return 0,
         }
-
-        let region = unsafe { region_iter.as_mut() };
-
+        let region = self.remove(address);
         let size = region.size();
-        region.set_state(RegionState::Free);
-
-        if region.end() != self.whole_region_.end() {
-            let mut next_iter_found = false;
-            let mut next_iter:NonNull<Region> = NonNull::dangling();
-
-            let mut regions = self.all_regions_.iter();
-            while let Some(&r) = regions.next(){
-              if unsafe {r.as_ref().begin()} == unsafe {region_iter.as_ref().end()} {
-                next_iter_found = true;
-                next_iter = *regions.next().unwrap();
-                break;
-              }
-            }
-
-            if next_iter_found{
-              let next = unsafe {next_iter.as_ref()};
-              if next.is_free() {
-                  self.FreeListRemoveRegion(next);
-                  self.Merge(region_iter, next_iter);
-              }
-            }
+        let mut begin = region.begin();
+        let mut end = region.end();
+        if let Some(next) = self.all_regions.get(&end).copied()
+            && next.is_free()
+        {
+            self.remove(next.begin());
+            end = next.end();
         }
-
-        if new_size == 0 && region.begin() != self.whole_region_.begin() {
-            let mut prev_iter_found = false;
-            let mut prev_iter:NonNull<Region> = NonNull::dangling();
-
-            let mut regions = self.all_regions_.iter().rev();
-            while let Some(&r) = regions.next(){
-              if unsafe {r.as_ref().end()} == unsafe {region_iter.as_ref().begin()} {
-                prev_iter_found = true;
-                prev_iter = *regions.next().unwrap();
-                break;
-              }
-            }
-
-            if prev_iter_found{
-              let prev = unsafe {prev_iter.as_ref()};
-              if prev.is_free() {
-                  self.FreeListRemoveRegion(prev);
-                  self.Merge(prev_iter, region_iter);
-                  region_iter = prev_iter;
-              }
+        if let Some((_, previous)) = self.all_regions.range(..begin).next_back() {
+            let previous = *previous;
+            if previous.is_free() {
+                self.remove(previous.begin());
+                begin = previous.begin();
             }
         }
-        let region = unsafe{region_iter.as_mut()};
-
-        self.FreeListAddRegion(region);
+        self.insert(Region::new(begin, end - begin, RegionState::Free));
         size
     }
 
-    pub fn CheckRegion(&self, address: Address) -> usize {
-        let region_ptr = self.find_region(address);
-
-        let region_ptr = match region_ptr {
-            Some(ptr) => ptr,
-            None => return 0,
+    /// Shrinks the allocated region at `address` to `new_size` bytes and
+    /// frees the rest. Returns the number of bytes freed.
+    pub fn trim_region(&mut self, address: Address, new_size: usize) -> usize {
+        assert!(self.is_aligned(new_size));
+        let Some(region) = self.all_regions.get(&address).copied() else {
+            return 0;
         };
-
-        let region = unsafe { region_ptr.as_ref() };
-
-        if region.begin() != address || region.is_free() {
+        if region.is_free() || new_size >= region.size() {
             return 0;
         }
-        region.size()
-    }
-
-    pub fn IsFree(&self, address: Address, size: usize) -> bool {
-        assert!(self.contains(address, size));
-        let region_ptr = self.find_region(address);
-
-        let region_ptr = match region_ptr {
-            Some(ptr) => ptr,
-            None => return true,
-        };
-
-        let region = unsafe { region_ptr.as_ref() };
-        region.is_free() && region.contains_range(address, size)
-    }
-
-    pub fn contains(&self, address: Address, size: usize) -> bool {
-        self.whole_region_.contains_range(address, size)
-    }
-
-    pub fn set_on_split_callback<F>(&mut self, callback: F)
-        where F: Fn(Address, usize) + 'static {
-        self.on_split_ = Some(Box::new(callback));
-    }
-
-    pub fn set_on_merge_callback<F>(&mut self, callback: F)
-        where F: Fn(Address, usize) + 'static {
-        self.on_merge_ = Some(Box::new(callback));
-    }
-
-    fn IsAligned(&self, value: usize, alignment: usize) -> bool {
-        value & (alignment - 1) == 0
-    }
-
-    fn RoundUp(&self, value: Address, alignment: usize) -> Address {
-        (value + alignment - 1) & !(alignment - 1)
+        if new_size == 0 {
+            return self.free_region(address);
+        }
+        self.remove(address);
+        self.insert(Region::new(address, new_size, region.state()));
+        self.insert(Region::new(address + new_size, region.size() - new_size, region.state()));
+        self.free_region(address + new_size)
+    }
+
+    /// Returns the size of the allocated region starting at `address`, or 0
+    /// if there is none.
+    pub fn check_region(&self, address: Address) -> usize {
+        match self.all_regions.get(&address) {
+            Some(region) if !region.is_free() => region.size(),
+            _ => 0,
+        }
     }
-}
 
-impl Drop for RegionAllocator {
-    fn drop(&mut self) {
-        for region_ptr in &self.all_regions_ {
-            let _region = region_from_nonnull(*region_ptr); //Deallocates the box
+    /// Returns true if `[address, address + size)` is entirely free.
+    pub fn is_free(&self, address: Address, size: usize) -> bool {
+        match self.find_region(address) {
+            Some(region) => region.is_free() && address + size <= region.end(),
+            None => false,
         }
     }
 }
 
 impl fmt::Display for RegionAllocator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(
-            f,
-            "RegionAllocator: [{:#x}, {:#x})",
-            self.begin(),
-            self.end()
-        )?;
-        writeln!(f, "size: {}", self.size())?;
-        writeln!(f, "free_size: {}", self.free_size())?;
-        writeln!(f, "page_size: {}", self.page_size())?;
-
-        writeln!(f, "all regions:")?;
-        for region_ptr in &self.all_regions_ {
-            let region = unsafe { region_ptr.as_ref() };
-            write!(f, "  ")?;
-            region.print(f)?;
-            writeln!(f)?;
-        }
-
-        writeln!(f, "free regions:")?;
-        for region_ptr in &self.free_regions_ {
-            let region = unsafe { region_ptr.as_ref() };
-            write!(f, "  ")?;
-            region.print(f)?;
-            writeln!(f)?;
+        writeln!(f, "RegionAllocator: [{:#x}, {:#x}), free: {}", self.begin(), self.end(), self.free_size)?;
+        for region in self.all_regions.values() {
+            writeln!(f, "  {region}")?;
         }
         Ok(())
     }
 }
-
-pub trait RandomNumberGenerator {
-    fn next_bytes(&mut self, num_bytes: usize) -> Vec<u8>;
-}
-
-pub const K_ALLOCATION_FAILURE: Address = 0;
\ No newline at end of file
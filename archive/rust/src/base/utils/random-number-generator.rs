@@ -1,262 +1,244 @@
-pub mod base {
-    pub mod utils {
-        use std::collections::HashSet;
-        use std::convert::TryInto;
-        use std::limits;
-        use std::mem;
-        use std::slice;
+// Copyright 2013 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::collections::HashSet;
+use std::io::Read;
+use std::sync::Mutex;
+
+/// Fills the buffer with entropy and returns true on success.
+pub type EntropySource = fn(&mut [u8]) -> bool;
+
+static ENTROPY_SOURCE: Mutex<Option<EntropySource>> = Mutex::new(None);
+
+/// Sets the entropy source used to seed new generators. The embedder should
+/// provide a strong one; otherwise /dev/urandom or the time is used.
+pub fn set_entropy_source(entropy_source: EntropySource) {
+    *ENTROPY_SOURCE.lock().unwrap() = Some(entropy_source);
+}
+
+/// A pseudo-random number generator using xorshift128+.
+///
+/// This generates a stream of pseudo-random numbers with a period length of
+/// 2^128-1. It uses a 64-bit seed, which is passed through MurmurHash3 to
+/// create two 64-bit state values. This pair of state values is then used in
+/// xorshift128+.
+///
+/// NOTE: Any changes to the algorithm must be tested against TestU01.
+///
+/// This class is neither reentrant nor threadsafe.
+#[derive(Debug, Clone)]
+pub struct RandomNumberGenerator {
+    initial_seed_: i64,
+    state0_: u64,
+    state1_: u64,
+}
+
+impl Default for RandomNumberGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        /// Represents a source of entropy for the random number generator.
-        pub type EntropySource = fn(*mut u8, usize) -> bool;
+impl RandomNumberGenerator {
+    /// Creates a generator seeded from the entropy source, if one is set,
+    /// and from /dev/urandom or the time otherwise.
+    pub fn new() -> Self {
+        Self::new_with_seed(Self::generate_seed())
+    }
 
-        static mut ENTROPY_SOURCE: Option<EntropySource> = None;
+    pub fn new_with_seed(seed: i64) -> Self {
+        let mut rng = RandomNumberGenerator { initial_seed_: 0, state0_: 0, state1_: 0 };
+        rng.set_seed(seed);
+        rng
+    }
 
-        /// Sets the global entropy source for the random number generator.
-        pub fn set_entropy_source(entropy_source: EntropySource) {
-            unsafe {
-                ENTROPY_SOURCE = Some(entropy_source);
-            }
+    fn generate_seed() -> i64 {
+        let mut buffer = [0u8; 8];
+        if let Some(entropy_source) = *ENTROPY_SOURCE.lock().unwrap()
+            && entropy_source(&mut buffer)
+        {
+            return i64::from_ne_bytes(buffer);
         }
-
-        /// A pseudo-random number generator using xorshift128+.
-        ///
-        /// This struct generates a stream of pseudo-random numbers with a period
-        /// length of 2^128-1.  It uses a 64-bit seed, which is passed through
-        /// MurmurHash3 to create two 64-bit state values. This pair of state
-        /// values is then used in xorshift128+.
-        ///
-        /// NOTE: Any changes to the algorithm must be tested against TestU01.
-        ///       Please find instructions for this in the internal repository.
-        ///
-        /// This class is neither reentrant nor threadsafe.
-        pub struct RandomNumberGenerator {
-            initial_seed_: i64,
-            state0_: u64,
-            state1_: u64,
+        if let Ok(mut urandom) = std::fs::File::open("/dev/urandom")
+            && urandom.read_exact(&mut buffer).is_ok()
+        {
+            return i64::from_ne_bytes(buffer);
         }
+        // Gather entropy from the time as a last resort.
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        (nanos as i64) ^ ((nanos >> 64) as i64)
+    }
 
-        impl RandomNumberGenerator {
-            const MULTIPLIER: i64 = 0x5_deec_e66d;
-            const ADDEND: i64 = 0xb;
-            const MASK: i64 = 0xffff_ffff_ffff;
-
-            /// Creates a new `RandomNumberGenerator` with a seed derived from a weak entropy source.
-            pub fn new() -> Self {
-                let mut rng = Self {
-                    initial_seed_: 0,
-                    state0_: 0,
-                    state1_: 0,
-                };
-                rng.set_seed(Self::generate_seed());
-                rng
-            }
-
-            fn generate_seed() -> i64 {
-                let mut buffer: [u8; 8] = [0; 8];
-                let buffer_slice = buffer.as_mut_ptr();
-
-                unsafe {
-                    if let Some(entropy_source) = ENTROPY_SOURCE {
-                        if entropy_source(buffer_slice, 8) {
-                            return i64::from_ne_bytes(buffer);
-                        }
-                    }
-                }
-
-                // Fallback to a less secure method if entropy source is not set or fails.
-                // This is similar to the C++ code, which relies on the embedder setting a
-                // proper entropy source.
-                let timestamp = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .map(|d| d.as_nanos())
-                    .unwrap_or(0) as i64;
-                timestamp.wrapping_mul(Self::MULTIPLIER).wrapping_add(Self::ADDEND) & Self::MASK
-            }
-
-            /// Creates a new `RandomNumberGenerator` with the specified seed.
-            pub fn new_with_seed(seed: i64) -> Self {
-                let mut rng = Self {
-                    initial_seed_: 0,
-                    state0_: 0,
-                    state1_: 0,
-                };
-                rng.set_seed(seed);
-                rng
-            }
-
-            /// Returns the next pseudorandom, uniformly distributed `i32` value
-            /// from this random number generator's sequence.
-            pub fn next_int(&mut self) -> i32 {
-                self.next(32) as i32
-            }
-
-            /// Returns a pseudorandom, uniformly distributed `i32` value between
-            /// 0 (inclusive) and the specified `max` value (exclusive).
-            pub fn next_int_max(&mut self, max: i32) -> i32 {
-                if max <= 0 {
-                    panic!("max must be positive");
-                }
-                (self.next_double() * (max as f64)) as i32
-            }
-
-            /// Returns the next pseudorandom, uniformly distributed `bool` value
-            /// from this random number generator's sequence.
-            pub fn next_bool(&mut self) -> bool {
-                self.next(1) != 0
-            }
-
-            /// Returns the next pseudorandom, uniformly distributed `f64` value
-            /// between 0.0 (inclusive) and 1.0 (exclusive) from this random
-            /// number generator's sequence.
-            pub fn next_double(&mut self) -> f64 {
-                Self::to_double(self.state0_)
-            }
-
-            /// Returns the next pseudorandom, uniformly distributed `i64` value
-            /// from this random number generator's sequence.
-            pub fn next_int64(&mut self) -> i64 {
-                let high = self.next(32) as i64;
-                let low = self.next(32) as i64;
-                (high << 32) | low
-            }
-
-            /// Fills the elements of a specified array of bytes with random numbers.
-            pub fn next_bytes(&mut self, buffer: &mut [u8]) {
-                let mut i = 0;
-                while i + 7 < buffer.len() {
-                    let value = self.next_int64();
-                    buffer[i..i + 8].copy_from_slice(&value.to_ne_bytes());
-                    i += 8;
-                }
-                let remaining = buffer.len() - i;
-                if remaining > 0 {
-                    let value = self.next_int64();
-                    let bytes = value.to_ne_bytes();
-                    buffer[i..].copy_from_slice(&bytes[..remaining]);
-                }
-            }
+    /// Returns the next pseudorandom, uniformly distributed int value from
+    /// this random number generator's sequence.
+    pub fn next_int(&mut self) -> i32 {
+        self.next(32)
+    }
 
-            /// Returns the next pseudorandom set of `n` unique `u64` values smaller
-            /// than `max`.
-            ///
-            /// `n` must be less or equal to `max`.
-            pub fn next_sample(&mut self, max: u64, n: usize) -> Vec<u64> {
-                if n > max as usize {
-                    panic!("n must be less or equal to max");
-                }
-                self.next_sample_slow(max, n, &HashSet::new())
+    /// Returns a pseudorandom, uniformly distributed int value between 0
+    /// (inclusive) and the specified `max` value (exclusive), drawn from this
+    /// random number generator's sequence.
+    pub fn next_int_max(&mut self, max: i32) -> i32 {
+        assert!(max > 0, "max must be positive");
+        // Fast path if max is a power of 2.
+        if (max & max.wrapping_neg()) == max {
+            return ((max as i64 * self.next(31) as i64) >> 31) as i32;
+        }
+        loop {
+            let rnd = self.next(31);
+            let val = rnd % max;
+            if i32::MAX - (rnd - val) >= max - 1 {
+                return val;
             }
+        }
+    }
 
-            /// Returns the next pseudorandom set of `n` unique `u64` values smaller
-            /// than `max`.
-            ///
-            /// `n` must be less or equal to `max`.
-            /// `max - excluded` must be less or equal to `n`.
-            ///
-            /// Generates list of all possible values and removes random values from
-            /// it until size reaches n.
-            pub fn next_sample_slow(
-                &mut self,
-                max: u64,
-                n: usize,
-                excluded: &HashSet<u64>,
-            ) -> Vec<u64> {
-                if n > max as usize {
-                    panic!("n must be less or equal to max");
-                }
-
-                let mut candidates: Vec<u64> = (0..max).filter(|&x| !excluded.contains(&x)).collect();
-
-                if candidates.len() < n {
-                  return candidates; // Cannot generate n unique values.
-                }
+    /// Returns the next pseudorandom, uniformly distributed boolean value
+    /// from this random number generator's sequence.
+    pub fn next_bool(&mut self) -> bool {
+        self.next(1) != 0
+    }
 
-                let mut result: Vec<u64> = Vec::with_capacity(n);
-                for _ in 0..n {
-                    let index = self.next_int_max(candidates.len() as i32) as usize;
-                    result.push(candidates.remove(index));
-                }
-                result
-            }
+    /// Returns the next pseudorandom, uniformly distributed double value
+    /// between 0.0 (inclusive) and 1.0 (exclusive) from this random number
+    /// generator's sequence.
+    pub fn next_double(&mut self) -> f64 {
+        Self::xor_shift128(&mut self.state0_, &mut self.state1_);
+        Self::to_double(self.state0_)
+    }
 
-            /// Overrides the current seed.
-            pub fn set_seed(&mut self, seed: i64) {
-                self.initial_seed_ = seed;
-                let hash = Self::murmur_hash3(seed as u64);
-                self.state0_ = hash;
-                self.state1_ = hash ^ 0xdeadbeef;
-            }
+    /// Returns the next pseudorandom, uniformly distributed int64 value from
+    /// this random number generator's sequence.
+    pub fn next_int64(&mut self) -> i64 {
+        Self::xor_shift128(&mut self.state0_, &mut self.state1_);
+        self.state0_.wrapping_add(self.state1_) as i64
+    }
 
-            /// Returns the initial seed used to initialize this generator.
-            pub fn initial_seed(&self) -> i64 {
-                self.initial_seed_
-            }
+    /// Fills the elements of `buffer` with random bytes.
+    pub fn next_bytes(&mut self, buffer: &mut [u8]) {
+        for byte in buffer {
+            *byte = self.next(8) as u8;
+        }
+    }
 
-            /// Converts a u64 state to a double in the range [0.0, 1.0).
-            pub fn to_double(state0: u64) -> f64 {
-                // Get a random [0,2**53) integer value (up to MAX_SAFE_INTEGER) by dropping
-                // 11 bits of the state.
-                let random_0_to_2_53 = (state0 >> 11) as f64;
-                // Map this to [0,1) by division with 2**53.
-                const K2_53: f64 = (1_u64 << 53) as f64;
-                random_0_to_2_53 / K2_53
+    /// Returns the next pseudorandom set of `n` unique values smaller than
+    /// `max`. `n` must be less or equal to `max`.
+    pub fn next_sample(&mut self, max: u64, n: u64) -> Vec<u64> {
+        assert!(n <= max);
+        if n == 0 {
+            return Vec::new();
+        }
+        // Choose to select or exclude, whatever needs fewer generator calls.
+        let smaller_part = n.min(max - n);
+        let mut selected = HashSet::new();
+        let mut counter = 0;
+        while (selected.len() as u64) != smaller_part && counter / 3 < smaller_part {
+            let x = (self.next_double() * max as f64) as u64;
+            assert!(x < max);
+            selected.insert(x);
+            counter += 1;
+        }
+        if selected.len() as u64 == smaller_part {
+            if smaller_part != n {
+                return Self::complement_sample(&selected, max);
             }
+            return selected.into_iter().collect();
+        }
+        // Failed to select numbers in smaller_part * 3 steps, try different
+        // approach.
+        self.next_sample_slow(max, n, &selected)
+    }
 
-            /// Performs the xorshift128+ operation.
-            pub fn xor_shift128(state0: &mut u64, state1: &mut u64) {
-                let mut s1 = *state0;
-                let s0 = *state1;
-                *state0 = s0;
-                s1 ^= s1 << 23;
-                s1 ^= s1 >> 17;
-                s1 ^= s0;
-                s1 ^= s0 >> 26;
-                *state1 = s1;
-            }
+    /// Returns the next pseudorandom set of `n` unique values smaller than
+    /// `max` that are not in `excluded`. `max - excluded.len()` must be at
+    /// least `n`.
+    ///
+    /// Generates the list of all possible values and removes random values
+    /// from it until its size reaches `n`.
+    pub fn next_sample_slow(&mut self, max: u64, n: u64, excluded: &HashSet<u64>) -> Vec<u64> {
+        assert!(max - excluded.len() as u64 >= n);
+        let mut result: Vec<u64> = (0..max).filter(|x| !excluded.contains(x)).collect();
+        let mut larger_part = max - excluded.len() as u64;
+        while larger_part > n {
+            let x = self.next_int_max(larger_part as i32) as usize;
+            result.swap_remove(x);
+            larger_part -= 1;
+        }
+        result
+    }
 
-            /// MurmurHash3 algorithm for generating initial state.
-            pub fn murmur_hash3(mut k: u64) -> u64 {
-                k ^= k >> 33;
-                k = k.wrapping_mul(0xff51afd7ed558ccd);
-                k ^= k >> 33;
-                k = k.wrapping_mul(0xc2b2ae353253864f);
-                k ^= k >> 33;
-                k
-            }
+    /// Overrides the current seed.
+    pub fn set_seed(&mut self, seed: i64) {
+        self.initial_seed_ = seed;
+        self.state0_ = Self::murmur_hash3(seed as u64);
+        self.state1_ = Self::murmur_hash3(!self.state0_);
+        assert!(self.state0_ != 0 || self.state1_ != 0);
+    }
 
-            fn next(&mut self, bits: i32) -> u32 {
-                Self::xor_shift128(&mut self.state0_, &mut self.state1_);
-                (self.state0_ >> (64 - bits)) as u32
-            }
-        }
+    pub fn initial_seed(&self) -> i64 {
+        self.initial_seed_
+    }
 
-        impl std::ops::FnMut<()> for RandomNumberGenerator {
-            extern "rust-call" fn call_mut(&mut self, _args: ()) -> Self::Output {
-                self.next_int() as Self::Output
-            }
-        }
+    /// Maps the state to a double in [0, 1) through the 52 mantissa bits.
+    pub fn to_double(state0: u64) -> f64 {
+        // Exponent for double values for [1.0 .. 2.0)
+        const K_EXPONENT_BITS: u64 = 0x3FF0_0000_0000_0000;
+        let random = (state0 >> 12) | K_EXPONENT_BITS;
+        f64::from_bits(random) - 1.0
+    }
 
-        impl std::ops::FnOnce<()> for RandomNumberGenerator {
-            type Output = u32;
+    pub fn xor_shift128(state0: &mut u64, state1: &mut u64) {
+        let mut s1 = *state0;
+        let s0 = *state1;
+        *state0 = s0;
+        s1 ^= s1 << 23;
+        s1 ^= s1 >> 17;
+        s1 ^= s0;
+        s1 ^= s0 >> 26;
+        *state1 = s1;
+    }
 
-            extern "rust-call" fn call_once(self, args: ()) -> Self::Output {
-                (self.call_mut)(args)
-            }
-        }
+    pub fn murmur_hash3(mut h: u64) -> u64 {
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+        h ^= h >> 33;
+        h
+    }
 
-        impl std::ops::Fn<()> for RandomNumberGenerator {
-            extern "rust-call" fn call(&self, _args: ()) -> Self::Output {
-                panic!("cannot call RandomNumberGenerator::call without a mutable reference");
-            }
-        }
+    fn next(&mut self, bits: u32) -> i32 {
+        debug_assert!(0 < bits && bits <= 32);
+        Self::xor_shift128(&mut self.state0_, &mut self.state1_);
+        (self.state0_.wrapping_add(self.state1_) >> (64 - bits)) as i32
+    }
 
-        impl RandomNumberGenerator {
-            pub const fn min() -> u32 {
-                0
-            }
-            pub const fn max() -> u32 {
-                std::u32::MAX
-            }
+    fn complement_sample(set: &HashSet<u64>, max: u64) -> Vec<u64> {
+        (0..max).filter(|i| !set.contains(i)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut a = RandomNumberGenerator::new_with_seed(123);
+        let mut b = RandomNumberGenerator::new_with_seed(123);
+        for _ in 0..100 {
+            let value = a.next_double();
+            assert!((0.0..1.0).contains(&value));
+            assert_eq!(value, b.next_double());
+            assert_eq!(a.next_int_max(10), b.next_int_max(10));
         }
+        let mut sample = a.next_sample(20, 15);
+        sample.sort_unstable();
+        sample.dedup();
+        assert_eq!(sample.len(), 15);
+        assert!(sample.iter().all(|&x| x < 20));
     }
-}
\ No newline at end of file
+}
@@ -2,241 +2,438 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod virtual_address_space {
-    use std::sync::Mutex;
-    //use v8::platform; // Assuming v8::platform maps to some Rust equivalent
-    //use crate::base::base_export; // Assuming base_export defines some attributes or functions
-
-    pub type Address = usize;
-    pub const K_NULL_ADDRESS: Address = 0;
-
-    pub enum PagePermissions {
-        // Placeholder, define actual permissions here
-    }
-
-    pub trait VirtualAddressSpaceTrait {
-        fn set_random_seed(&mut self, seed: i64);
-        fn random_page_address(&self) -> Address;
-        fn allocate_pages(&mut self, hint: Address, size: usize, alignment: usize, access: PagePermissions) -> Option<Address>;
-        fn free_pages(&mut self, address: Address, size: usize);
-        fn set_page_permissions(&mut self, address: Address, size: usize, access: PagePermissions) -> bool;
-        fn allocate_guard_region(&mut self, address: Address, size: usize) -> bool;
-        fn free_guard_region(&mut self, address: Address, size: usize);
-        // Assuming PlatformSharedMemoryHandle is some OS-specific type
-        // and needs a conditional compilation
-        #[cfg(target_os = "linux")]
-        fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: i32, offset: u64) -> Option<Address>;
-        #[cfg(not(target_os = "linux"))]
-        fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: i32, offset: u64) -> Option<Address>; // Placeholder
-        fn free_shared_pages(&mut self, address: Address, size: usize);
-        fn can_allocate_subspaces(&self) -> bool;
-        fn allocate_subspace(&mut self, hint: Address, size: usize, alignment: usize, max_page_permissions: PagePermissions) -> Option<Box<dyn VirtualAddressSpaceTrait>>;
-        fn recommit_pages(&mut self, address: Address, size: usize, access: PagePermissions) -> bool;
-        fn discard_system_pages(&mut self, address: Address, size: usize) -> bool;
-        fn decommit_pages(&mut self, address: Address, size: usize) -> bool;
-    }
-
-    pub trait VirtualAddressSpaceBase : VirtualAddressSpaceTrait {
-        //fn new() -> Self;
-        //fn free_subspace(&mut self, subspace: &mut VirtualAddressSubspace);
-    }
-
-    pub fn is_subset(lhs: PagePermissions, rhs: PagePermissions) -> bool {
-        // Placeholder implementation
-        true
-    }
-    
-    pub struct VirtualAddressSpace {
-        // Add necessary fields here
-    }
-    
-    impl VirtualAddressSpace {
-        pub fn new() -> Self {
-            VirtualAddressSpace {}
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::base::page_allocator::{OsPageAllocator, PageAllocator, Permission};
+use crate::base::region_allocator::{RegionAllocator, RegionState};
+
+pub type Address = usize;
+pub const K_NULL_ADDRESS: Address = 0;
+
+/// Passed as hint when the caller does not care where pages end up.
+pub const K_NO_HINT: Address = K_NULL_ADDRESS;
+
+/// Size of the user part of the address space with 4-level page tables.
+pub const K_USER_ADDRESS_SPACE_SIZE: usize = 1 << 47;
+
+/// Permissions of pages in a virtual address space.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PagePermissions {
+    kNoAccess,
+    kRead,
+    kReadWrite,
+    kReadWriteExecute,
+    kReadExecute,
+}
+
+impl PagePermissions {
+    fn bits(self) -> u8 {
+        const READ: u8 = 1;
+        const WRITE: u8 = 2;
+        const EXECUTE: u8 = 4;
+        match self {
+            PagePermissions::kNoAccess => 0,
+            PagePermissions::kRead => READ,
+            PagePermissions::kReadWrite => READ | WRITE,
+            PagePermissions::kReadWriteExecute => READ | WRITE | EXECUTE,
+            PagePermissions::kReadExecute => READ | EXECUTE,
         }
     }
 
-    impl VirtualAddressSpaceTrait for VirtualAddressSpace {
-        fn set_random_seed(&mut self, seed: i64) {
-            // Implementation
+    pub(crate) fn to_permission(self) -> Permission {
+        match self {
+            PagePermissions::kNoAccess => Permission::kNoAccess,
+            PagePermissions::kRead => Permission::kRead,
+            PagePermissions::kReadWrite => Permission::kReadWrite,
+            PagePermissions::kReadWriteExecute => Permission::kReadWriteExecute,
+            PagePermissions::kReadExecute => Permission::kReadExecute,
         }
+    }
+}
 
-        fn random_page_address(&self) -> Address {
-            // Implementation
-            0
-        }
+/// Returns true if every access `lhs` allows is also allowed by `rhs`.
+pub fn is_subset(lhs: PagePermissions, rhs: PagePermissions) -> bool {
+    lhs.bits() & !rhs.bits() == 0
+}
 
-        fn allocate_pages(&mut self, hint: Address, size: usize, alignment: usize, access: PagePermissions) -> Option<Address> {
-            // Implementation
-            Some(0)
-        }
+/// A region of virtual address space from which pages, guard regions and
+/// nested subspaces can be allocated. Page addresses and sizes must be
+/// multiples of `allocation_granularity()`; permission changes work at
+/// `page_size()` granularity.
+pub trait VirtualAddressSpace: Send + Sync {
+    fn page_size(&self) -> usize;
 
-        fn free_pages(&mut self, address: Address, size: usize) {
-            // Implementation
-        }
+    fn allocation_granularity(&self) -> usize;
 
-        fn set_page_permissions(&mut self, address: Address, size: usize, access: PagePermissions) -> bool {
-            // Implementation
-            true
-        }
+    fn base(&self) -> Address;
 
-        fn allocate_guard_region(&mut self, address: Address, size: usize) -> bool {
-            // Implementation
-            true
-        }
+    fn size(&self) -> usize;
 
-        fn free_guard_region(&mut self, address: Address, size: usize) {
-            // Implementation
-        }
+    /// The most permissive permissions pages in this space may have.
+    fn max_page_permissions(&self) -> PagePermissions;
 
-        #[cfg(target_os = "linux")]
-        fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: i32, offset: u64) -> Option<Address> {
-            // Implementation
-            Some(0)
-        }
+    fn contains(&self, address: Address) -> bool {
+        address.wrapping_sub(self.base()) < self.size()
+    }
 
-        #[cfg(not(target_os = "linux"))]
-        fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: i32, offset: u64) -> Option<Address> {
-            // Implementation
-            Some(0)
-        }
+    fn set_random_seed(&self, seed: i64);
 
-        fn free_shared_pages(&mut self, address: Address, size: usize) {
-            // Implementation
-        }
+    /// Returns a random allocation-granularity aligned address inside the
+    /// space, to be used as a hint.
+    fn random_page_address(&self) -> Address;
 
-        fn can_allocate_subspaces(&self) -> bool {
-            true
-        }
+    /// Allocates `size` bytes aligned to `alignment`, at `hint` if
+    /// possible. Returns `None` if there is no room.
+    fn allocate_pages(&self, hint: Address, size: usize, alignment: usize, permissions: PagePermissions) -> Option<Address>;
 
-        fn allocate_subspace(&mut self, hint: Address, size: usize, alignment: usize, max_page_permissions: PagePermissions) -> Option<Box<dyn VirtualAddressSpaceTrait>> {
-            // Implementation
-            None
-        }
+    fn free_pages(&self, address: Address, size: usize);
 
-        fn recommit_pages(&mut self, address: Address, size: usize, access: PagePermissions) -> bool {
-            // Implementation
-            true
-        }
+    fn set_page_permissions(&self, address: Address, size: usize, permissions: PagePermissions) -> bool;
 
-        fn discard_system_pages(&mut self, address: Address, size: usize) -> bool {
-            // Implementation
-            true
-        }
+    /// Reserves `[address, address + size)` so that nothing is allocated
+    /// there. Guard regions are inaccessible.
+    fn allocate_guard_region(&self, address: Address, size: usize) -> bool;
 
-        fn decommit_pages(&mut self, address: Address, size: usize) -> bool {
-            // Implementation
-            true
-        }
+    fn free_guard_region(&self, address: Address, size: usize);
+
+    fn can_allocate_subspaces(&self) -> bool;
+
+    /// Reserves a nested address space. The subspace must be dropped before
+    /// the space it was allocated from.
+    fn allocate_subspace(
+        &self,
+        hint: Address,
+        size: usize,
+        alignment: usize,
+        max_page_permissions: PagePermissions,
+    ) -> Option<Box<dyn VirtualAddressSpace>>;
+
+    fn recommit_pages(&self, address: Address, size: usize, permissions: PagePermissions) -> bool;
+
+    /// Releases the physical memory of the pages; they stay accessible.
+    fn discard_system_pages(&self, address: Address, size: usize) -> bool;
+
+    /// Releases the physical memory of the pages and makes them
+    /// inaccessible.
+    fn decommit_pages(&self, address: Address, size: usize) -> bool;
+}
+
+/// The process-wide address space, backed directly by the OS.
+pub struct OsVirtualAddressSpace {
+    allocator: OsPageAllocator,
+}
+
+impl OsVirtualAddressSpace {
+    pub const fn new() -> Self {
+        OsVirtualAddressSpace { allocator: OsPageAllocator::new() }
+    }
+}
+
+impl Default for OsVirtualAddressSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the address space of the whole process.
+pub fn get_platform_virtual_address_space() -> &'static OsVirtualAddressSpace {
+    static ROOT_SPACE: OsVirtualAddressSpace = OsVirtualAddressSpace::new();
+    &ROOT_SPACE
+}
+
+impl VirtualAddressSpace for OsVirtualAddressSpace {
+    fn page_size(&self) -> usize {
+        self.allocator.commit_page_size()
+    }
+
+    fn allocation_granularity(&self) -> usize {
+        self.allocator.allocate_page_size()
+    }
+
+    fn base(&self) -> Address {
+        K_NULL_ADDRESS
+    }
+
+    fn size(&self) -> usize {
+        K_USER_ADDRESS_SPACE_SIZE
     }
-    
-    impl VirtualAddressSpaceBase for VirtualAddressSpace {}
 
-    struct AddressSpaceReservation {} //Placeholder
+    fn max_page_permissions(&self) -> PagePermissions {
+        PagePermissions::kReadWriteExecute
+    }
+
+    fn set_random_seed(&self, seed: i64) {
+        self.allocator.set_random_mmap_seed(seed);
+    }
+
+    fn random_page_address(&self) -> Address {
+        self.allocator.get_random_mmap_addr()
+    }
+
+    fn allocate_pages(&self, hint: Address, size: usize, alignment: usize, permissions: PagePermissions) -> Option<Address> {
+        self.allocator.allocate_pages(hint, size, alignment, permissions.to_permission())
+    }
 
-    struct RegionAllocator {} // Placeholder
+    fn free_pages(&self, address: Address, size: usize) {
+        assert!(self.allocator.free_pages(address, size));
+    }
 
-    struct RandomNumberGenerator {} // Placeholder
-    
-    pub struct VirtualAddressSubspace {
-        reservation_: AddressSpaceReservation,
-        mutex_: Mutex<()>,
-        region_allocator_: RegionAllocator,
-        rng_: RandomNumberGenerator,
-        parent_space_: *mut dyn VirtualAddressSpaceTrait,
+    fn set_page_permissions(&self, address: Address, size: usize, permissions: PagePermissions) -> bool {
+        self.allocator.set_permissions(address, size, permissions.to_permission())
     }
 
-    impl VirtualAddressSubspace {
-        fn new(reservation: AddressSpaceReservation, parent_space: *mut dyn VirtualAddressSpaceTrait, max_page_permissions: PagePermissions) -> Self {
-            VirtualAddressSubspace {
-                reservation_: reservation,
-                mutex_: Mutex::new(()),
-                region_allocator_: RegionAllocator{},
-                rng_: RandomNumberGenerator{},
-                parent_space_: parent_space,
+    fn allocate_guard_region(&self, address: Address, size: usize) -> bool {
+        let granularity = self.allocation_granularity();
+        match self.allocator.allocate_pages(address, size, granularity, Permission::kNoAccess) {
+            Some(result) if result == address => true,
+            Some(result) => {
+                self.allocator.free_pages(result, size);
+                false
             }
+            None => false,
         }
     }
 
-    impl Drop for VirtualAddressSubspace {
-        fn drop(&mut self) {
-            // Implementation for freeing subspace
-        }
+    fn free_guard_region(&self, address: Address, size: usize) {
+        self.free_pages(address, size);
     }
 
-    impl VirtualAddressSpaceTrait for VirtualAddressSubspace {
-        fn set_random_seed(&mut self, seed: i64) {
-            // Implementation
-        }
+    fn can_allocate_subspaces(&self) -> bool {
+        true
+    }
 
-        fn random_page_address(&self) -> Address {
-            // Implementation
-            0
-        }
+    fn allocate_subspace(
+        &self,
+        hint: Address,
+        size: usize,
+        alignment: usize,
+        max_page_permissions: PagePermissions,
+    ) -> Option<Box<dyn VirtualAddressSpace>> {
+        let base = self.allocator.allocate_pages(hint, size, alignment, Permission::kNoAccess)?;
+        Some(Box::new(VirtualAddressSubspace::new(base, size, max_page_permissions, None)))
+    }
 
-        fn allocate_pages(&mut self, hint: Address, size: usize, alignment: usize, permissions: PagePermissions) -> Option<Address> {
-            // Implementation
-            Some(0)
-        }
+    fn recommit_pages(&self, address: Address, size: usize, permissions: PagePermissions) -> bool {
+        self.allocator.recommit_pages(address, size, permissions.to_permission())
+    }
 
-        fn free_pages(&mut self, address: Address, size: usize) {
-            // Implementation
-        }
+    fn discard_system_pages(&self, address: Address, size: usize) -> bool {
+        self.allocator.discard_system_pages(address, size)
+    }
 
-        fn set_page_permissions(&mut self, address: Address, size: usize, permissions: PagePermissions) -> bool {
-            // Implementation
-            true
+    fn decommit_pages(&self, address: Address, size: usize) -> bool {
+        self.allocator.decommit_pages(address, size)
+    }
+}
+
+/// An address space backed by a single reservation. Its pages are tracked
+/// by a region allocator; unallocated pages are inaccessible.
+pub struct VirtualAddressSubspace {
+    base: Address,
+    size: usize,
+    max_page_permissions: PagePermissions,
+    allocator: OsPageAllocator,
+    region_allocator: Arc<Mutex<RegionAllocator>>,
+    /// The regions of the parent subspace, if this subspace was carved out
+    /// of one. Otherwise this subspace owns its OS reservation.
+    parent_regions: Option<Arc<Mutex<RegionAllocator>>>,
+    random_state: AtomicU64,
+}
+
+impl VirtualAddressSubspace {
+    fn new(
+        base: Address,
+        size: usize,
+        max_page_permissions: PagePermissions,
+        parent_regions: Option<Arc<Mutex<RegionAllocator>>>,
+    ) -> Self {
+        let page_size = OsPageAllocator::page_size();
+        VirtualAddressSubspace {
+            base,
+            size,
+            max_page_permissions,
+            allocator: OsPageAllocator::new(),
+            region_allocator: Arc::new(Mutex::new(RegionAllocator::new(base, size, page_size))),
+            parent_regions,
+            random_state: AtomicU64::new(base as u64 | 1),
         }
+    }
 
-        fn allocate_guard_region(&mut self, address: Address, size: usize) -> bool {
-            // Implementation
-            true
-        }
+    fn next_random(&self) -> u64 {
+        // splitmix64
+        let state = self.random_state.fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed);
+        let mut random = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        random = (random ^ (random >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        random = (random ^ (random >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        random ^ (random >> 31)
+    }
 
-        fn free_guard_region(&mut self, address: Address, size: usize) {
-            // Implementation
-        }
+    /// Allocates a region at `hint` if it is free and suitably aligned,
+    /// anywhere suitably aligned otherwise.
+    fn allocate_region(&self, hint: Address, size: usize, alignment: usize, state: RegionState) -> Option<Address> {
+        let mut regions = self.region_allocator.lock().unwrap();
+        if hint != K_NULL_ADDRESS
+            && hint.is_multiple_of(alignment)
+            && regions.contains_range(hint, size)
+            && regions.allocate_region_at(hint, size, state)
+        {
+            return Some(hint);
+        }
+        let address = regions.allocate_aligned_region(size, alignment);
+        (address != RegionAllocator::K_ALLOCATION_FAILURE).then_some(address)
+    }
+}
 
-        #[cfg(target_os = "linux")]
-        fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: i32, offset: u64) -> Option<Address> {
-            // Implementation
-            Some(0)
-        }
+impl VirtualAddressSpace for VirtualAddressSubspace {
+    fn page_size(&self) -> usize {
+        self.allocator.commit_page_size()
+    }
 
-        #[cfg(not(target_os = "linux"))]
-        fn allocate_shared_pages(&mut self, hint: Address, size: usize, permissions: PagePermissions, handle: i32, offset: u64) -> Option<Address> {
-            // Implementation
-            Some(0)
-        }
+    fn allocation_granularity(&self) -> usize {
+        self.allocator.allocate_page_size()
+    }
 
-        fn free_shared_pages(&mut self, address: Address, size: usize) {
-            // Implementation
-        }
+    fn base(&self) -> Address {
+        self.base
+    }
 
-        fn can_allocate_subspaces(&self) -> bool {
-            true
-        }
+    fn size(&self) -> usize {
+        self.size
+    }
 
-        fn allocate_subspace(&mut self, hint: Address, size: usize, alignment: usize, max_page_permissions: PagePermissions) -> Option<Box<dyn VirtualAddressSpaceTrait>> {
-            // Implementation
-            None
-        }
+    fn max_page_permissions(&self) -> PagePermissions {
+        self.max_page_permissions
+    }
 
-        fn recommit_pages(&mut self, address: Address, size: usize, permissions: PagePermissions) -> bool {
-            // Implementation
-            true
-        }
+    fn set_random_seed(&self, seed: i64) {
+        self.random_state.store(seed as u64 | 1, Ordering::Relaxed);
+    }
 
-        fn discard_system_pages(&mut self, address: Address, size: usize) -> bool {
-            // Implementation
-            true
-        }
+    fn random_page_address(&self) -> Address {
+        let pages = self.size / self.allocation_granularity();
+        self.base + (self.next_random() as usize % pages) * self.allocation_granularity()
+    }
+
+    fn allocate_pages(&self, hint: Address, size: usize, alignment: usize, permissions: PagePermissions) -> Option<Address> {
+        debug_assert!(is_subset(permissions, self.max_page_permissions));
+        let address = self.allocate_region(hint, size, alignment, RegionState::Allocated)?;
+        // Free pages are inaccessible already.
+        if permissions != PagePermissions::kNoAccess
+            && !self.allocator.set_permissions(address, size, permissions.to_permission())
+        {
+            self.region_allocator.lock().unwrap().free_region(address);
+            return None;
+        }
+        Some(address)
+    }
+
+    fn free_pages(&self, address: Address, size: usize) {
+        assert_eq!(self.region_allocator.lock().unwrap().free_region(address), size);
+        assert!(self.allocator.decommit_pages(address, size));
+    }
+
+    fn set_page_permissions(&self, address: Address, size: usize, permissions: PagePermissions) -> bool {
+        debug_assert!(is_subset(permissions, self.max_page_permissions));
+        self.allocator.set_permissions(address, size, permissions.to_permission())
+    }
+
+    fn allocate_guard_region(&self, address: Address, size: usize) -> bool {
+        let mut regions = self.region_allocator.lock().unwrap();
+        regions.contains_range(address, size) && regions.allocate_region_at(address, size, RegionState::Excluded)
+    }
+
+    fn free_guard_region(&self, address: Address, size: usize) {
+        assert_eq!(self.region_allocator.lock().unwrap().free_region(address), size);
+    }
+
+    fn can_allocate_subspaces(&self) -> bool {
+        true
+    }
+
+    fn allocate_subspace(
+        &self,
+        hint: Address,
+        size: usize,
+        alignment: usize,
+        max_page_permissions: PagePermissions,
+    ) -> Option<Box<dyn VirtualAddressSpace>> {
+        debug_assert!(is_subset(max_page_permissions, self.max_page_permissions));
+        let base = self.allocate_region(hint, size, alignment, RegionState::Allocated)?;
+        Some(Box::new(VirtualAddressSubspace::new(
+            base,
+            size,
+            max_page_permissions,
+            Some(self.region_allocator.clone()),
+        )))
+    }
+
+    fn recommit_pages(&self, address: Address, size: usize, permissions: PagePermissions) -> bool {
+        self.allocator.recommit_pages(address, size, permissions.to_permission())
+    }
 
-        fn decommit_pages(&mut self, address: Address, size: usize) -> bool {
-            // Implementation
-            true
+    fn discard_system_pages(&self, address: Address, size: usize) -> bool {
+        self.allocator.discard_system_pages(address, size)
+    }
+
+    fn decommit_pages(&self, address: Address, size: usize) -> bool {
+        self.allocator.decommit_pages(address, size)
+    }
+}
+
+impl Drop for VirtualAddressSubspace {
+    fn drop(&mut self) {
+        match &self.parent_regions {
+            Some(parent_regions) => {
+                // Return the memory to the parent, which keeps the range
+                // reserved.
+                self.allocator.decommit_pages(self.base, self.size);
+                parent_regions.lock().unwrap().free_region(self.base);
+            }
+            None => {
+                self.allocator.free_pages(self.base, self.size);
+            }
         }
     }
-    
-    impl VirtualAddressSpaceBase for VirtualAddressSubspace {}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subspaces_hand_out_pages_within_their_reservation() {
+        let root = get_platform_virtual_address_space();
+        let granularity = root.allocation_granularity();
+        let subspace = root
+            .allocate_subspace(root.random_page_address(), 64 * granularity, 4 * granularity, PagePermissions::kReadWrite)
+            .unwrap();
+        assert_eq!(subspace.base() % (4 * granularity), 0);
+
+        let page = subspace.allocate_pages(K_NO_HINT, granularity, granularity, PagePermissions::kReadWrite).unwrap();
+        assert!(subspace.contains(page));
+        unsafe { *(page as *mut u64) = 42 };
+
+        // A taken hint falls back to another address.
+        let other = subspace.allocate_pages(page, granularity, granularity, PagePermissions::kReadWrite).unwrap();
+        assert_ne!(other, page);
+
+        // Guard regions keep allocations out.
+        let guard = subspace.base() + 32 * granularity;
+        assert!(subspace.allocate_guard_region(guard, 32 * granularity));
+        assert!(!subspace.allocate_guard_region(guard, granularity));
+
+        // Freed pages read as zero when allocated again.
+        subspace.free_pages(page, granularity);
+        let again = subspace.allocate_pages(page, granularity, granularity, PagePermissions::kReadWrite).unwrap();
+        assert_eq!(again, page);
+        assert_eq!(unsafe { *(again as *const u64) }, 0);
+
+        let nested = subspace
+            .allocate_subspace(K_NO_HINT, 8 * granularity, granularity, PagePermissions::kRead)
+            .unwrap();
+        assert!(subspace.contains(nested.base()) && subspace.contains(nested.base() + nested.size() - 1));
+        drop(nested);
+    }
+}
@@ -25,6 +25,11 @@ pub mod internal {
 
     use std::convert::TryInto;
 
+    use crate::objects::elements_kind::elements_kind::{elements_kind_to_byte_size, ElementsKind};
+    use crate::objects::typed_array_elements::typed_array_elements::{
+        load_element, store_element, TypedElementValue,
+    };
+
     // Placeholder for HandleScope
     pub struct HandleScope<'a> {
         isolate: &'a v8::Isolate,
@@ -326,6 +331,7 @@ pub mod internal {
         kDetachedOperation,
         kInvalidOffset,
         kInvalidDataViewLength,
+        kInvalidDataViewAccessorOffset,
     }
 
     impl MessageTemplate {
@@ -336,6 +342,9 @@ pub mod internal {
                 MessageTemplate::kDetachedOperation => "Detached operation",
                 MessageTemplate::kInvalidOffset => "Invalid offset",
                 MessageTemplate::kInvalidDataViewLength => "Invalid DataView length",
+                MessageTemplate::kInvalidDataViewAccessorOffset => {
+                    "Offset is outside the bounds of the DataView"
+                }
             }
         }
     }
@@ -588,4 +597,119 @@ pub mod internal {
         // 21. Return O.
         Ok(result)
     }
+
+    // ES #sec-getviewvalue / #sec-setviewvalue, steps from after the value
+    // conversion on. The caller has already converted the request index with
+    // ToIntegerOrInfinity and the little-endian flag with ToBoolean.
+    fn view_element_range(
+        isolate: &v8::Isolate,
+        data_view: &JSDataViewOrRabGsabDataView,
+        request_index: f64,
+        kind: ElementsKind,
+        method_name: &str,
+    ) -> Result<std::ops::Range<usize>, String> {
+        // Let getIndex be ? ToIndex(requestIndex).
+        if !(0.0..=9007199254740991.0).contains(&request_index) {
+            return Err(new_range_error_no_arg(isolate, MessageTemplate::kInvalidDataViewAccessorOffset));
+        }
+        let get_index = request_index as usize;
+
+        // If IsViewOutOfBounds(viewRecord) is true, throw a TypeError exception.
+        let buffer = unsafe { &*data_view.buffer };
+        if buffer.was_detached() {
+            return Err(new_type_error(
+                isolate,
+                MessageTemplate::kDetachedOperation,
+                isolate.factory().new_string_from_ascii_checked(method_name),
+            ));
+        }
+        let view_size = if data_view.is_length_tracking {
+            match buffer.get_byte_length().checked_sub(data_view.byte_offset) {
+                Some(size) => size,
+                None => {
+                    return Err(new_type_error(
+                        isolate,
+                        MessageTemplate::kDetachedOperation,
+                        isolate.factory().new_string_from_ascii_checked(method_name),
+                    ))
+                }
+            }
+        } else {
+            data_view.byte_length
+        };
+
+        // If getIndex + elementSize > viewSize, throw a RangeError exception.
+        let element_size = elements_kind_to_byte_size(kind);
+        if element_size > view_size || get_index > view_size - element_size {
+            return Err(new_range_error_no_arg(isolate, MessageTemplate::kInvalidDataViewAccessorOffset));
+        }
+        Ok(get_index..get_index + element_size)
+    }
+
+    pub fn get_view_value(
+        isolate: &v8::Isolate,
+        data_view: &JSDataViewOrRabGsabDataView,
+        request_index: f64,
+        little_endian: bool,
+        kind: ElementsKind,
+        method_name: &str,
+    ) -> Result<TypedElementValue, String> {
+        let range = view_element_range(isolate, data_view, request_index, kind, method_name)?;
+        let bytes = unsafe { std::slice::from_raw_parts(data_view.data_pointer.add(range.start), range.len()) };
+        Ok(load_element(kind, bytes, little_endian))
+    }
+
+    pub fn set_view_value(
+        isolate: &v8::Isolate,
+        data_view: &mut JSDataViewOrRabGsabDataView,
+        request_index: f64,
+        little_endian: bool,
+        kind: ElementsKind,
+        value: TypedElementValue,
+        method_name: &str,
+    ) -> Result<(), String> {
+        let range = view_element_range(isolate, data_view, request_index, kind, method_name)?;
+        let bytes = unsafe { std::slice::from_raw_parts_mut(data_view.data_pointer.add(range.start), range.len()) };
+        store_element(kind, bytes, value, little_endian);
+        Ok(())
+    }
+
+    // ES #sec-dataview.prototype.getfloat16
+    pub fn dataview_prototype_get_float16(
+        isolate: &v8::Isolate,
+        data_view: &JSDataViewOrRabGsabDataView,
+        request_index: f64,
+        little_endian: bool,
+    ) -> Result<f64, String> {
+        match get_view_value(
+            isolate,
+            data_view,
+            request_index,
+            little_endian,
+            ElementsKind::FLOAT16_ELEMENTS,
+            "DataView.prototype.getFloat16",
+        )? {
+            TypedElementValue::Number(value) => Ok(value),
+            TypedElementValue::BigInt(_) => unreachable!(),
+        }
+    }
+
+    // ES #sec-dataview.prototype.setfloat16
+    pub fn dataview_prototype_set_float16(
+        isolate: &v8::Isolate,
+        data_view: &mut JSDataViewOrRabGsabDataView,
+        request_index: f64,
+        value: f64,
+        little_endian: bool,
+    ) -> Result<(), String> {
+        set_view_value(
+            isolate,
+            data_view,
+            request_index,
+            little_endian,
+            ElementsKind::FLOAT16_ELEMENTS,
+            TypedElementValue::Number(value),
+            "DataView.prototype.setFloat16",
+        )
+    }
 } // namespace internal
\ No newline at end of file
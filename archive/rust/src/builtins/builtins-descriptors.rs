@@ -216,6 +216,7 @@ pub mod builtins_descriptors {
         (MathExpm1, Builtin::TSJ, 1),
         (MathFloor, Builtin::TSJ, 1),
         (MathFround, Builtin::TSJ, 1),
+        (MathF16round, Builtin::TSJ, 1),
         (MathHypot, Builtin::TSJ, 2, Arg0),
         (MathImul, Builtin::TSJ, 2),
         (MathLog, Builtin::TSJ, 1),
@@ -13,11 +13,10 @@
 //use crate::objects::intl_objects;
 //use crate::objects::objects;
 
-// Placeholder constants
-const K_MAX_FRACTION_DIGITS: i32 = 20;
-const K_DOUBLE_TO_EXPONENTIAL_MAX_CHARS: usize = 30;
-const K_DOUBLE_TO_FIXED_MAX_CHARS: usize = 30;
-const K_DOUBLE_TO_PRECISION_MAX_CHARS: usize = 30;
+use crate::numbers::conversions::conversions::{
+    double_to_exponential_string_view, double_to_fixed_string_view,
+    double_to_precision_string_view, K_MAX_FRACTION_DIGITS,
+};
 
 // Placeholder types and functions
 // These need to be replaced with actual implementations based on V8's internal structure
@@ -255,35 +254,6 @@ enum UseCounterFeature {
     KNumberToLocaleString,
 }
 
-// Placeholder for string conversion functions
-fn double_to_exponential_string_view(value: f64, f: i32, buffer: &mut Vec<char>) -> String {
-    // Placeholder implementation
-    let formatted = if f == -1 {
-        format!("{:e}", value)
-    } else {
-        format!("{:.prec}e", value, prec = f as usize)
-    };
-    buffer.clear();
-    buffer.extend(formatted.chars());
-    formatted
-}
-
-fn double_to_fixed_string_view(value: f64, fraction_digits: i32, buffer: &mut Vec<char>) -> String {
-    // Placeholder implementation
-    let formatted = format!("{:.prec}", value, prec = fraction_digits as usize);
-    buffer.clear();
-    buffer.extend(formatted.chars());
-    formatted
-}
-
-fn double_to_precision_string_view(value: f64, precision: i32, buffer: &mut Vec<char>) -> String {
-    // Placeholder implementation
-    let formatted = format!("{:.prec$}", value, prec = precision as usize);
-    buffer.clear();
-    buffer.extend(formatted.chars());
-    formatted
-}
-
 // -----------------------------------------------------------------------------
 // ES6 section 20.1 Number Objects
 
@@ -343,8 +313,7 @@ fn number_prototype_to_exponential(isolate: &Isolate, args: &[ObjectValue]) -> B
         fraction_digits_number as i32
     };
 
-    let mut chars: Vec<char> = Vec::with_capacity(K_DOUBLE_TO_EXPONENTIAL_MAX_CHARS);
-    let str_value = double_to_exponential_string_view(value_number, f, &mut chars);
+    let str_value = double_to_exponential_string_view(value_number, f);
 
     let result = isolate.factory().new_string_from_ascii_checked(&str_value);
     Ok(result)
@@ -398,12 +367,7 @@ fn number_prototype_to_fixed(isolate: &Isolate, args: &[ObjectValue]) -> Builtin
             read_only_roots(isolate).infinity_string
         });
     }
-    let mut chars: Vec<char> = Vec::with_capacity(K_DOUBLE_TO_FIXED_MAX_CHARS);
-    let str_value = double_to_fixed_string_view(
-        value_number,
-        fraction_digits_number as i32,
-        &mut chars,
-    );
+    let str_value = double_to_fixed_string_view(value_number, fraction_digits_number as i32);
     let result = isolate.factory().new_string_from_ascii_checked(&str_value);
     Ok(result)
 }
@@ -494,12 +458,7 @@ fn number_prototype_to_precision(isolate: &Isolate, args: &[ObjectValue]) -> Bui
             }
         );
     }
-    let mut chars: Vec<char> = Vec::with_capacity(K_DOUBLE_TO_PRECISION_MAX_CHARS);
-    let str_value = double_to_precision_string_view(
-        value_number,
-        precision_number as i32,
-        &mut chars,
-    );
+    let str_value = double_to_precision_string_view(value_number, precision_number as i32);
     let result = isolate.factory().new_string_from_ascii_checked(&str_value);
     Ok(result)
 }
@@ -2,226 +2,391 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod source_position_table {
-    use std::rc::Rc;
-    use std::vec::Vec;
-
-    //use crate::base::export_template::*; // Assuming export-template.h is not directly translatable
-    //use crate::base::vector::*; // Assuming base::Vector is replaced by std::vec::Vec
-    use crate::codegen::source_position::*;
-    //use crate::common::assert_scope::*; // Assuming assert-scope.h is not directly translatable
-    //use crate::common::checks::*; // Assuming checks.h is not directly translatable
-    //use crate::common::globals::*; // Assuming globals.h is not directly translatable
-    //use crate::zone::zone_containers::*; // Assuming zone-containers.h is not directly translatable
-
-    pub const K_FUNCTION_ENTRY_BYTECODE_OFFSET: i32 = 0;
-
-    #[derive(Debug, Clone, Copy)]
-    pub struct PositionTableEntry {
-        pub source_position: i64,
-        pub code_offset: i32,
-        pub is_statement: bool,
-    }
-
-    impl PositionTableEntry {
-        pub fn new() -> Self {
-            PositionTableEntry {
-                source_position: 0,
-                code_offset: K_FUNCTION_ENTRY_BYTECODE_OFFSET,
-                is_statement: false,
-            }
-        }
+// The table is a sequence of entries, each the delta to the previous entry:
+// the code offset, whose sign encodes is_statement, followed by the source
+// position. Both are zig-zag encoded VLQs.
+
+/// The code offset of the function entry stack check of bytecode.
+pub const K_FUNCTION_ENTRY_BYTECODE_OFFSET: i32 = -1;
+
+/// The bit-packed form of a source position, as stored in the table. Script
+/// offsets and inlining ids are stored plus one, so that the unknown
+/// position is all zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePosition {
+    value: u64,
+}
 
-        pub fn new_with_params(offset: i32, source: i64, statement: bool) -> Self {
-            PositionTableEntry {
-                source_position: source,
-                code_offset: offset,
-                is_statement: statement,
-            }
-        }
+impl SourcePosition {
+    pub const K_NOT_INLINED: i32 = -1;
+    pub const K_NO_SOURCE_POSITION: i32 = -1;
+
+    const IS_EXTERNAL_SHIFT: u32 = 0;
+    const EXTERNAL_LINE_SHIFT: u32 = 1;
+    const EXTERNAL_LINE_BITS: u32 = 20;
+    const EXTERNAL_FILE_ID_SHIFT: u32 = 21;
+    const EXTERNAL_FILE_ID_BITS: u32 = 10;
+    const SCRIPT_OFFSET_SHIFT: u32 = 1;
+    const SCRIPT_OFFSET_BITS: u32 = 30;
+    const INLINING_ID_SHIFT: u32 = 31;
+    const INLINING_ID_BITS: u32 = 16;
+
+    pub fn new(script_offset: i32, inlining_id: i32) -> Self {
+        let mut position = SourcePosition { value: 0 };
+        position.set(Self::SCRIPT_OFFSET_SHIFT, Self::SCRIPT_OFFSET_BITS, (script_offset + 1) as u64);
+        position.set(Self::INLINING_ID_SHIFT, Self::INLINING_ID_BITS, (inlining_id + 1) as u64);
+        position
     }
 
-    pub struct SourcePositionTableBuilder {
-        mode_: RecordingMode,
-        bytes_: Vec<u8>,
-        #[cfg(feature = "slow_dchecks")]
-        raw_entries_: Vec<PositionTableEntry>,
-        previous_: PositionTableEntry,
+    /// A position in a file that is not JavaScript, e.g. a Torque source.
+    pub fn external(line: i32, file_id: i32) -> Self {
+        let mut position = SourcePosition { value: 0 };
+        position.set(Self::IS_EXTERNAL_SHIFT, 1, 1);
+        position.set(Self::EXTERNAL_LINE_SHIFT, Self::EXTERNAL_LINE_BITS, line as u64);
+        position.set(Self::EXTERNAL_FILE_ID_SHIFT, Self::EXTERNAL_FILE_ID_BITS, file_id as u64);
+        position.set(Self::INLINING_ID_SHIFT, Self::INLINING_ID_BITS, 0);
+        position
     }
 
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    pub enum RecordingMode {
-        OMIT_SOURCE_POSITIONS,
-        LAZY_SOURCE_POSITIONS,
-        RECORD_SOURCE_POSITIONS,
+    pub fn unknown() -> Self {
+        SourcePosition::new(Self::K_NO_SOURCE_POSITION, Self::K_NOT_INLINED)
     }
 
-    impl SourcePositionTableBuilder {
-        pub fn new(zone: &Zone, mode: RecordingMode) -> Self {
-            SourcePositionTableBuilder {
-                mode_: mode,
-                bytes_: Vec::new(),
-                #[cfg(feature = "slow_dchecks")]
-                raw_entries_: Vec::new(),
-                previous_: PositionTableEntry::new(),
-            }
-        }
+    pub fn from_raw(raw: i64) -> Self {
+        SourcePosition { value: raw as u64 }
+    }
 
-        pub fn add_position(&mut self, code_offset: usize, source_position: SourcePosition, is_statement: bool) {
-            let entry = PositionTableEntry::new_with_params(code_offset as i32, source_position.raw(), is_statement);
-            self.add_entry(entry);
-        }
+    pub fn raw(&self) -> i64 {
+        self.value as i64
+    }
 
-        fn add_entry(&mut self, entry: PositionTableEntry) {
-            // Placeholder for actual byte encoding logic, to compute delta and write bytes.
-            // Needs more information on how deltas are computed and the encoding scheme.
-            self.bytes_.push(0); // Example: push a placeholder byte
-            #[cfg(feature = "slow_dchecks")]
-            self.raw_entries_.push(entry);
-            self.previous_ = entry;
-        }
+    pub fn is_known(&self) -> bool {
+        *self != SourcePosition::unknown()
+    }
 
-        pub fn to_source_position_table_vector(self) -> Vec<u8> {
-            self.bytes_
-        }
+    pub fn is_external(&self) -> bool {
+        self.get(Self::IS_EXTERNAL_SHIFT, 1) != 0
+    }
 
-        pub fn omit(&self) -> bool {
-            self.mode_ != RecordingMode::RECORD_SOURCE_POSITIONS
-        }
+    pub fn is_javascript(&self) -> bool {
+        !self.is_external()
+    }
 
-        pub fn lazy(&self) -> bool {
-            self.mode_ == RecordingMode::LAZY_SOURCE_POSITIONS
-        }
-        // Function ToSourcePositionTable needs IsolateT which is not availabe currently.
-        // It is ignored and should be implemented when necessary.
-    }
-
-    pub struct SourcePositionTableIterator {
-        raw_table_: Rc<Vec<u8>>,
-        index_: i32,
-        current_: PositionTableEntry,
-        iteration_filter_: IterationFilter,
-        function_entry_filter_: FunctionEntryFilter,
-        //no_gc: DisallowGarbageCollection, // Assuming DISALLOW_GARBAGE_COLLECTION is not directly translatable
-    }
-
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    pub enum IterationFilter {
-        KJavaScriptOnly = 0,
-        KExternalOnly = 1,
-        KAll = 2,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    pub enum FunctionEntryFilter {
-        KSkipFunctionEntry = 0,
-        KDontSkipFunctionEntry = 1,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    pub struct IndexAndPositionState {
-        pub index_: i32,
-        pub position_: PositionTableEntry,
-        pub iteration_filter_: IterationFilter,
-        pub function_entry_filter_: FunctionEntryFilter,
-    }
-
-    const K_DONE: i32 = -1;
-
-    impl SourcePositionTableIterator {
-        pub fn new(
-            byte_array: Rc<Vec<u8>>,
-            iteration_filter: IterationFilter,
-            function_entry_filter: FunctionEntryFilter,
-        ) -> Self {
-            let mut iterator = SourcePositionTableIterator {
-                raw_table_: byte_array,
-                index_: 0,
-                current_: PositionTableEntry::new(),
-                iteration_filter_: iteration_filter,
-                function_entry_filter_: function_entry_filter,
-                //no_gc: DisallowGarbageCollection::new(), // Assuming DISALLOW_GARBAGE_COLLECTION is not directly translatable
-            };
-            iterator.initialize();
-            iterator
-        }
+    pub fn script_offset(&self) -> i32 {
+        debug_assert!(self.is_javascript());
+        self.get(Self::SCRIPT_OFFSET_SHIFT, Self::SCRIPT_OFFSET_BITS) as i32 - 1
+    }
 
-        fn initialize(&mut self) {
-            // Placeholder: Needs the correct logic to parse and load first entry from the byte array.
-            // Also handle the FunctionEntry SourcePosition according to function_entry_filter_.
-            // This requires detailed knowledge about the byte array format.
+    pub fn inlining_id(&self) -> i32 {
+        self.get(Self::INLINING_ID_SHIFT, Self::INLINING_ID_BITS) as i32 - 1
+    }
 
-            // Placeholder for now:
-            if self.function_entry_filter_ == FunctionEntryFilter::KSkipFunctionEntry {
-                self.advance();
-            }
-        }
+    pub fn is_inlined(&self) -> bool {
+        self.inlining_id() != Self::K_NOT_INLINED
+    }
 
-        pub fn advance(&mut self) {
-            // Placeholder: Needs the correct logic to advance and parse next entry from byte array.
-            // Also consider iteration_filter_ to skip entries.
-            // This requires detailed knowledge about the byte array format.
+    pub fn external_line(&self) -> i32 {
+        debug_assert!(self.is_external());
+        self.get(Self::EXTERNAL_LINE_SHIFT, Self::EXTERNAL_LINE_BITS) as i32
+    }
 
-            // Placeholder, setting to done after first advance.
-            self.index_ = K_DONE;
-        }
+    pub fn external_file_id(&self) -> i32 {
+        debug_assert!(self.is_external());
+        self.get(Self::EXTERNAL_FILE_ID_SHIFT, Self::EXTERNAL_FILE_ID_BITS) as i32
+    }
+
+    fn get(&self, shift: u32, bits: u32) -> u64 {
+        (self.value >> shift) & ((1 << bits) - 1)
+    }
+
+    fn set(&mut self, shift: u32, bits: u32, field: u64) {
+        let mask = ((1u64 << bits) - 1) << shift;
+        self.value = (self.value & !mask) | ((field << shift) & mask);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PositionTableEntry {
+    pub source_position: i64,
+    pub code_offset: i32,
+    pub is_statement: bool,
+}
+
+impl PositionTableEntry {
+    pub fn new() -> Self {
+        PositionTableEntry { source_position: 0, code_offset: K_FUNCTION_ENTRY_BYTECODE_OFFSET, is_statement: false }
+    }
+
+    pub fn new_with_params(offset: i32, source: i64, statement: bool) -> Self {
+        PositionTableEntry { source_position: source, code_offset: offset, is_statement: statement }
+    }
+}
+
+impl Default for PositionTableEntry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn subtract_from_entry(value: &mut PositionTableEntry, other: &PositionTableEntry) {
+    value.code_offset -= other.code_offset;
+    value.source_position -= other.source_position;
+}
 
-        pub fn code_offset(&self) -> i32 {
-            assert!(!self.done());
-            self.current_.code_offset
+fn add_and_set_entry(value: &mut PositionTableEntry, other: &PositionTableEntry) {
+    value.code_offset += other.code_offset;
+    value.source_position += other.source_position;
+    value.is_statement = other.is_statement;
+}
+
+/// Appends a zig-zag encoded VLQ.
+fn encode_int(bytes: &mut Vec<u8>, value: i64) {
+    let mut encoded = ((value << 1) ^ (value >> 63)) as u64;
+    loop {
+        let current = (encoded & 0x7F) as u8;
+        encoded >>= 7;
+        if encoded == 0 {
+            bytes.push(current);
+            break;
         }
+        bytes.push(current | 0x80);
+    }
+}
 
-        pub fn source_position(&self) -> SourcePosition {
-            assert!(!self.done());
-            SourcePosition::from_raw(self.current_.source_position)
+fn decode_int(bytes: &[u8], index: &mut usize) -> i64 {
+    let mut encoded: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let current = bytes[*index];
+        *index += 1;
+        encoded |= u64::from(current & 0x7F) << shift;
+        shift += 7;
+        if current & 0x80 == 0 {
+            break;
         }
+    }
+    (encoded >> 1) as i64 ^ -((encoded & 1) as i64)
+}
 
-        pub fn is_statement(&self) -> bool {
-            assert!(!self.done());
-            self.current_.is_statement
+fn encode_entry(bytes: &mut Vec<u8>, entry: &PositionTableEntry) {
+    // The code offset delta is never negative, so the sign encodes
+    // is_statement.
+    let code_offset = entry.code_offset as i64;
+    encode_int(bytes, if entry.is_statement { code_offset } else { -code_offset - 1 });
+    encode_int(bytes, entry.source_position);
+}
+
+fn decode_entry(bytes: &[u8], index: &mut usize) -> PositionTableEntry {
+    let code_offset = decode_int(bytes, index);
+    let (code_offset, is_statement) = if code_offset >= 0 { (code_offset, true) } else { (-(code_offset + 1), false) };
+    let source_position = decode_int(bytes, index);
+    PositionTableEntry::new_with_params(code_offset as i32, source_position, is_statement)
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecordingMode {
+    OMIT_SOURCE_POSITIONS,
+    LAZY_SOURCE_POSITIONS,
+    RECORD_SOURCE_POSITIONS,
+}
+
+pub struct SourcePositionTableBuilder {
+    mode_: RecordingMode,
+    bytes_: Vec<u8>,
+    previous_: PositionTableEntry,
+}
+
+impl SourcePositionTableBuilder {
+    pub fn new(mode: RecordingMode) -> Self {
+        SourcePositionTableBuilder { mode_: mode, bytes_: Vec::new(), previous_: PositionTableEntry::new() }
+    }
+
+    pub fn add_position(&mut self, code_offset: usize, source_position: SourcePosition, is_statement: bool) {
+        if self.omit() {
+            return;
         }
+        debug_assert!(source_position.is_known());
+        self.add_entry(PositionTableEntry::new_with_params(code_offset as i32, source_position.raw(), is_statement));
+    }
+
+    fn add_entry(&mut self, entry: PositionTableEntry) {
+        let mut delta = entry;
+        subtract_from_entry(&mut delta, &self.previous_);
+        encode_entry(&mut self.bytes_, &delta);
+        self.previous_ = entry;
+    }
 
-        pub fn done(&self) -> bool {
-            self.index_ == K_DONE
+    pub fn to_source_position_table_vector(self) -> Vec<u8> {
+        self.bytes_
+    }
+
+    pub fn omit(&self) -> bool {
+        self.mode_ != RecordingMode::RECORD_SOURCE_POSITIONS
+    }
+
+    pub fn lazy(&self) -> bool {
+        self.mode_ == RecordingMode::LAZY_SOURCE_POSITIONS
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IterationFilter {
+    KJavaScriptOnly = 0,
+    KExternalOnly = 1,
+    KAll = 2,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FunctionEntryFilter {
+    KSkipFunctionEntry = 0,
+    KDontSkipFunctionEntry = 1,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IndexAndPositionState {
+    pub index_: i32,
+    pub position_: PositionTableEntry,
+    pub iteration_filter_: IterationFilter,
+    pub function_entry_filter_: FunctionEntryFilter,
+}
+
+const K_DONE: i32 = -1;
+
+pub struct SourcePositionTableIterator<'a> {
+    raw_table_: &'a [u8],
+    index_: i32,
+    current_: PositionTableEntry,
+    iteration_filter_: IterationFilter,
+    function_entry_filter_: FunctionEntryFilter,
+}
+
+impl<'a> SourcePositionTableIterator<'a> {
+    pub fn new(
+        byte_array: &'a [u8],
+        iteration_filter: IterationFilter,
+        function_entry_filter: FunctionEntryFilter,
+    ) -> Self {
+        let mut iterator = SourcePositionTableIterator {
+            raw_table_: byte_array,
+            index_: 0,
+            current_: PositionTableEntry::new(),
+            iteration_filter_: iteration_filter,
+            function_entry_filter_: function_entry_filter,
+        };
+        iterator.initialize();
+        iterator
+    }
+
+    /// Iterates the JavaScript positions, without the function entry.
+    pub fn with_defaults(byte_array: &'a [u8]) -> Self {
+        Self::new(byte_array, IterationFilter::KJavaScriptOnly, FunctionEntryFilter::KSkipFunctionEntry)
+    }
+
+    fn initialize(&mut self) {
+        self.advance();
+        if self.function_entry_filter_ == FunctionEntryFilter::KSkipFunctionEntry
+            && !self.done()
+            && self.current_.code_offset == K_FUNCTION_ENTRY_BYTECODE_OFFSET
+        {
+            self.advance();
         }
+    }
 
-        pub fn get_state(&self) -> IndexAndPositionState {
-            IndexAndPositionState {
-                index_: self.index_,
-                position_: self.current_,
-                iteration_filter_: self.iteration_filter_,
-                function_entry_filter_: self.function_entry_filter_,
+    pub fn advance(&mut self) {
+        debug_assert!(!self.done());
+        let mut filter_satisfied = false;
+        while !self.done() && !filter_satisfied {
+            if self.index_ as usize >= self.raw_table_.len() {
+                self.index_ = K_DONE;
+            } else {
+                let mut index = self.index_ as usize;
+                let delta = decode_entry(self.raw_table_, &mut index);
+                self.index_ = index as i32;
+                add_and_set_entry(&mut self.current_, &delta);
+                let position = self.source_position();
+                filter_satisfied = match self.iteration_filter_ {
+                    IterationFilter::KAll => true,
+                    IterationFilter::KJavaScriptOnly => position.is_javascript(),
+                    IterationFilter::KExternalOnly => position.is_external(),
+                };
             }
         }
+    }
+
+    pub fn code_offset(&self) -> i32 {
+        debug_assert!(!self.done());
+        self.current_.code_offset
+    }
 
-        pub fn restore_state(&mut self, saved_state: &IndexAndPositionState) {
-            self.index_ = saved_state.index_;
-            self.current_ = saved_state.position_;
-            self.iteration_filter_ = saved_state.iteration_filter_;
-            self.function_entry_filter_ = saved_state.function_entry_filter_;
+    pub fn source_position(&self) -> SourcePosition {
+        debug_assert!(!self.done());
+        SourcePosition::from_raw(self.current_.source_position)
+    }
+
+    pub fn is_statement(&self) -> bool {
+        debug_assert!(!self.done());
+        self.current_.is_statement
+    }
+
+    pub fn done(&self) -> bool {
+        self.index_ == K_DONE
+    }
+
+    pub fn get_state(&self) -> IndexAndPositionState {
+        IndexAndPositionState {
+            index_: self.index_,
+            position_: self.current_,
+            iteration_filter_: self.iteration_filter_,
+            function_entry_filter_: self.function_entry_filter_,
         }
     }
 
-    // Dummy Zone struct for compilation purposes.
-    // Replace it with the actual Zone implementation if needed.
-    pub struct Zone {}
+    pub fn restore_state(&mut self, saved_state: &IndexAndPositionState) {
+        self.index_ = saved_state.index_;
+        self.current_ = saved_state.position_;
+        self.iteration_filter_ = saved_state.iteration_filter_;
+        self.function_entry_filter_ = saved_state.function_entry_filter_;
+    }
 }
 
-mod codegen {
-    pub mod source_position {
-        #[derive(Debug, Clone, Copy)]
-        pub struct SourcePosition {
-            raw: i64,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_and_decodes_positions() {
+        let positions = [
+            (K_FUNCTION_ENTRY_BYTECODE_OFFSET, SourcePosition::new(0, SourcePosition::K_NOT_INLINED), true),
+            (0, SourcePosition::new(10, SourcePosition::K_NOT_INLINED), true),
+            (7, SourcePosition::external(42, 3), false),
+            (9, SourcePosition::new(5, 2), false),
+            (300, SourcePosition::new(1 << 20, SourcePosition::K_NOT_INLINED), true),
+        ];
+        let mut builder = SourcePositionTableBuilder::new(RecordingMode::RECORD_SOURCE_POSITIONS);
+        for &(offset, position, is_statement) in &positions {
+            builder.add_entry(PositionTableEntry::new_with_params(offset, position.raw(), is_statement));
         }
+        let table = builder.to_source_position_table_vector();
 
-        impl SourcePosition {
-            pub fn from_raw(raw: i64) -> Self {
-                SourcePosition { raw }
-            }
-
-            pub fn raw(&self) -> i64 {
-                self.raw
-            }
+        let mut decoded = Vec::new();
+        let mut it = SourcePositionTableIterator::new(&table, IterationFilter::KAll, FunctionEntryFilter::KDontSkipFunctionEntry);
+        while !it.done() {
+            decoded.push((it.code_offset(), it.source_position(), it.is_statement()));
+            it.advance();
+        }
+        assert_eq!(decoded, positions);
+        assert_eq!(decoded[3].1.script_offset(), 5);
+        assert_eq!(decoded[3].1.inlining_id(), 2);
+        assert_eq!(decoded[2].1.external_line(), 42);
+
+        // The default filters skip the function entry and external positions.
+        let mut offsets = Vec::new();
+        let mut it = SourcePositionTableIterator::with_defaults(&table);
+        while !it.done() {
+            offsets.push(it.code_offset());
+            it.advance();
         }
+        assert_eq!(offsets, [0, 9, 300]);
     }
-}
\ No newline at end of file
+}
@@ -2,725 +2,824 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-mod eh_frame_constants {
+use std::io::{self, Write};
+
+const K_INT32_SIZE: usize = 4;
+const K_SYSTEM_POINTER_SIZE: usize = std::mem::size_of::<usize>();
+
+pub mod dwarf_opcodes {
+    pub const K_NOP: u8 = 0x00;
+    pub const K_ADVANCE_LOC1: u8 = 0x02;
+    pub const K_ADVANCE_LOC2: u8 = 0x03;
+    pub const K_ADVANCE_LOC4: u8 = 0x04;
+    pub const K_RESTORE_EXTENDED: u8 = 0x06;
+    pub const K_SAME_VALUE: u8 = 0x08;
+    pub const K_DEF_CFA: u8 = 0x0c;
+    pub const K_DEF_CFA_REGISTER: u8 = 0x0d;
+    pub const K_DEF_CFA_OFFSET: u8 = 0x0e;
+    pub const K_OFFSET_EXTENDED_SF: u8 = 0x11;
+}
+
+pub struct EhFrameConstants;
+
+impl EhFrameConstants {
+    // DWARF encoding specifiers.
+    pub const K_UDATA4: u8 = 0x03;
+    pub const K_SDATA4: u8 = 0x0b;
+    pub const K_PC_REL: u8 = 0x10;
+    pub const K_DATA_REL: u8 = 0x30;
+    pub const K_OMIT: u8 = 0xff;
+
+    pub const K_LOCATION_TAG: u8 = 1;
+    pub const K_LOCATION_MASK_SIZE: u8 = 6;
+    pub const K_LOCATION_MASK: u8 = (1 << Self::K_LOCATION_MASK_SIZE) - 1;
+
+    pub const K_SAVED_REGISTER_TAG: u8 = 2;
+    pub const K_SAVED_REGISTER_MASK_SIZE: u8 = 6;
+    pub const K_SAVED_REGISTER_MASK: u8 = (1 << Self::K_SAVED_REGISTER_MASK_SIZE) - 1;
+
+    pub const K_FOLLOW_INITIAL_RULE_TAG: u8 = 3;
+    pub const K_FOLLOW_INITIAL_RULE_MASK_SIZE: u8 = 6;
+    pub const K_FOLLOW_INITIAL_RULE_MASK: u8 = (1 << Self::K_FOLLOW_INITIAL_RULE_MASK_SIZE) - 1;
+
+    pub const K_PROCEDURE_ADDRESS_OFFSET_IN_FDE: usize = 2 * K_INT32_SIZE;
+    pub const K_PROCEDURE_SIZE_OFFSET_IN_FDE: usize = 3 * K_INT32_SIZE;
+
+    pub const K_INITIAL_STATE_OFFSET_IN_CIE: usize = 19;
     pub const K_EH_FRAME_TERMINATOR_SIZE: usize = 4;
-    pub const K_EH_FRAME_HDR_VERSION: u8 = 1;
-    pub const K_EH_FRAME_HDR_SIZE: usize = 16;
-    pub const K_INITIAL_STATE_OFFSET_IN_CIE: usize = 14;
+
+    pub const K_CODE_ALIGNMENT_FACTOR: i32 = arch::K_CODE_ALIGNMENT_FACTOR;
+    pub const K_DATA_ALIGNMENT_FACTOR: i32 = arch::K_DATA_ALIGNMENT_FACTOR;
+
     pub const K_FDE_VERSION_SIZE: usize = 1;
     pub const K_FDE_ENCODING_SPECIFIERS_SIZE: usize = 3;
-    pub const K_PROCEDURE_ADDRESS_OFFSET_IN_FDE: usize = 4;
-    pub const K_PROCEDURE_SIZE_OFFSET_IN_FDE: usize = 8;
 
-    pub const K_SDATA4: u8 = 0x02;
-    pub const K_PCREL: u8 = 0x40;
-    pub const K_UDATA4: u8 = 0x03;
-    pub const K_DATAREL: u8 = 0x80;
-    pub const KOMIT: u8 = 0x00;
+    pub const K_EH_FRAME_HDR_VERSION: u8 = 1;
+    pub const K_EH_FRAME_HDR_SIZE: usize = 20;
+}
 
-    pub const K_CODE_ALIGNMENT_FACTOR: i32 = 1;
-    pub const K_DATA_ALIGNMENT_FACTOR: i32 = 1;
-
-    pub const K_LOCATION_TAG: u8 = 0x08;
-    pub const K_LOCATION_MASK_SIZE: u8 = 3;
-    pub const K_LOCATION_MASK: u8 = 0x07;
-
-    pub const K_SAVED_REGISTER_TAG: u8 = 0x09;
-    pub const K_SAVED_REGISTER_MASK_SIZE: u8 = 4;
-    pub const K_SAVED_REGISTER_MASK: u8 = 0x0F;
-
-    pub const K_FOLLOW_INITIAL_RULE_TAG: u8 = 0x0C;
-    pub const K_FOLLOW_INITIAL_RULE_MASK_SIZE: u8 = 4;
-    pub const K_FOLLOW_INITIAL_RULE_MASK: u8 = 0x0F;
-
-    pub mod dwarf_opcodes {
-        pub const K_OFFSET_EXTENDED_SF: u8 = 0x0F;
-        pub const K_ADVANCE_LOC1: u8 = 0x1;
-        pub const K_ADVANCE_LOC2: u8 = 0x2;
-        pub const K_ADVANCE_LOC4: u8 = 0x3;
-        pub const K_DEF_CFA: u8 = 0x0C;
-        pub const K_DEF_CFA_OFFSET: u8 = 0x0E;
-        pub const K_DEF_CFA_REGISTER: u8 = 0x0D;
-        pub const K_SAME_VALUE: u8 = 0x23;
-        pub const K_NOP: u8 = 0x00;
-        pub const K_RESTORE_EXTENDED: u8 = 0x2D;
+/// A machine register, identified by its code in the assembler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Register {
+    code: i32,
+}
+
+impl Register {
+    /// No register, e.g. before the CFA base register is set.
+    pub const NO_REG: Register = Register { code: -1 };
+
+    /// `code` must be the code of a general purpose register of the target.
+    pub const fn from_code(code: i32) -> Self {
+        assert!(code >= 0 && code < arch::K_NUM_REGISTERS);
+        Register { code }
+    }
+
+    pub fn code(&self) -> i32 {
+        self.code
     }
 }
 
-use eh_frame_constants as constants;
+#[cfg(not(target_arch = "aarch64"))]
+pub mod arch {
+    use super::{EhFrameWriter, Register, K_SYSTEM_POINTER_SIZE};
+
+    pub const K_CODE_ALIGNMENT_FACTOR: i32 = 1;
+    pub const K_DATA_ALIGNMENT_FACTOR: i32 = -8;
 
-pub mod eh_frame {
-    use super::constants;
-    use byteorder::{LittleEndian, WriteBytesExt};
-    use std::io::{self, Write};
-    use std::vec::Vec;
+    pub const K_NUM_REGISTERS: i32 = 16;
 
-    const K_INT32_SIZE: usize = 4;
-    const K_MAX_UINT8: u32 = u8::MAX as u32;
-    const K_MAX_UINT16: u32 = u16::MAX as u32;
-    const K_SYSTEM_POINTER_SIZE: usize = 4;
+    const K_RBP_DWARF_CODE: i32 = 6;
+    const K_RSP_DWARF_CODE: i32 = 7;
+    const K_RIP_DWARF_CODE: i32 = 16;
 
-    pub struct EhFrameWriter {
-        cie_size_: usize,
-        last_pc_offset_: i32,
-        writer_state_: InternalState,
-        base_register_: Register,
-        base_offset_: i32,
-        eh_frame_buffer_: Vec<u8>,
+    /// DWARF codes of the registers by register code. The assembler orders
+    /// the first eight registers rax, rcx, rdx, rbx, rsp, rbp, rsi, rdi, while
+    /// DWARF orders them rax, rdx, rcx, rbx, rsi, rdi, rbp, rsp; r8 to r15
+    /// match.
+    const K_DWARF_CODES: [i32; K_NUM_REGISTERS as usize] = [0, 2, 1, 3, 7, 6, 4, 5, 8, 9, 10, 11, 12, 13, 14, 15];
+
+    pub const RAX: Register = Register::from_code(0);
+    pub const RSP: Register = Register::from_code(4);
+    pub const RBP: Register = Register::from_code(5);
+
+    pub(super) fn write_return_address_register_code(writer: &mut EhFrameWriter) {
+        writer.write_uleb128(K_RIP_DWARF_CODE as u32);
     }
 
-    #[derive(PartialEq, Debug)]
-    enum InternalState {
-        Undefined,
-        Initialized,
-        Finalized,
+    pub(super) fn write_initial_state_in_cie(writer: &mut EhFrameWriter) {
+        writer.set_base_address_register_and_offset(RSP, K_SYSTEM_POINTER_SIZE as i32);
+        // x64 rip (r16) has no Register instance associated.
+        writer.record_register_saved_to_stack(K_RIP_DWARF_CODE, K_SYSTEM_POINTER_SIZE as i32);
     }
 
-    #[derive(PartialEq, Copy, Clone, Debug)]
-    pub enum Register {
-        NoReg,
-        SomeReg(u32) // Placeholder, replace with actual register definitions.
+    pub fn register_to_dwarf_code(name: Register) -> i32 {
+        K_DWARF_CODES[name.code() as usize]
     }
 
-    impl Register {
-        fn is_no_reg(&self) -> bool {
-            match self {
-                Register::NoReg => true,
-                _ => false,
-            }
+    pub fn dwarf_register_code_to_string(code: i32) -> String {
+        match code {
+            K_RBP_DWARF_CODE => "rbp".to_string(),
+            K_RSP_DWARF_CODE => "rsp".to_string(),
+            K_RIP_DWARF_CODE => "rip".to_string(),
+            _ => format!("r{code}"),
         }
     }
+}
 
-    impl EhFrameWriter {
-        const K_INT32_PLACEHOLDER: u32 = 0xBADCAFED; //arbitrary value
+#[cfg(target_arch = "aarch64")]
+pub mod arch {
+    use super::{EhFrameWriter, Register};
 
-        pub fn new() -> Self {
-            EhFrameWriter {
-                cie_size_: 0,
-                last_pc_offset_: 0,
-                writer_state_: InternalState::Undefined,
-                base_register_: Register::NoReg,
-                base_offset_: 0,
-                eh_frame_buffer_: Vec::new(),
-            }
-        }
+    pub const K_CODE_ALIGNMENT_FACTOR: i32 = 4;
+    pub const K_DATA_ALIGNMENT_FACTOR: i32 = -8;
 
-        pub fn write_empty_eh_frame<W: Write>(mut stream: W) -> io::Result<()> {
-            stream.write_u8(constants::K_EH_FRAME_HDR_VERSION)?;
+    /// x0 to x30 and sp.
+    pub const K_NUM_REGISTERS: i32 = 32;
 
-            stream.write_u8(constants::K_SDATA4 | constants::K_PCREL)?;
+    const K_FP_DWARF_CODE: i32 = 29;
+    const K_LR_DWARF_CODE: i32 = 30;
+    const K_SP_DWARF_CODE: i32 = 31;
 
-            stream.write_u8(constants::K_UDATA4)?;
+    pub const X0: Register = Register::from_code(0);
+    pub const FP: Register = Register::from_code(29);
+    pub const LR: Register = Register::from_code(30);
+    pub const SP: Register = Register::from_code(31);
 
-            stream.write_u8(constants::K_SDATA4 | constants::K_DATAREL)?;
+    pub(super) fn write_return_address_register_code(writer: &mut EhFrameWriter) {
+        writer.write_uleb128(K_LR_DWARF_CODE as u32);
+    }
 
-            let dummy_data = [0u8; constants::K_EH_FRAME_HDR_SIZE - 4];
-            stream.write_all(&dummy_data)?;
-            Ok(())
-        }
+    pub(super) fn write_initial_state_in_cie(writer: &mut EhFrameWriter) {
+        writer.set_base_address_register_and_offset(FP, 0);
+        writer.record_register_not_modified(LR);
+    }
+
+    /// The DWARF codes of x0 to x30 and sp match their register codes.
+    pub fn register_to_dwarf_code(name: Register) -> i32 {
+        name.code()
+    }
 
-        pub fn initialize(&mut self) {
-            assert_eq!(self.writer_state_, InternalState::Undefined);
-            self.eh_frame_buffer_.reserve(128);
-            self.writer_state_ = InternalState::Initialized;
-            self.write_cie();
-            self.write_fde_header();
+    pub fn dwarf_register_code_to_string(code: i32) -> String {
+        match code {
+            K_FP_DWARF_CODE => "fp".to_string(),
+            K_LR_DWARF_CODE => "lr".to_string(),
+            K_SP_DWARF_CODE => "sp".to_string(),
+            _ => format!("x{code}"),
         }
+    }
+}
 
-        fn write_cie(&mut self) {
-            const K_CIE_IDENTIFIER: i32 = 0;
-            const K_CIE_VERSION: u8 = 3;
-            const K_AUGMENTATION_DATA_SIZE: u8 = 2;
-            const K_AUGMENTATION_STRING: &[u8] = b"zLR\0";
+#[allow(non_camel_case_types, clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InternalState {
+    kUndefined,
+    kInitialized,
+    kFinalized,
+}
 
-            let size_offset = self.eh_frame_offset();
-            self.write_int32(EhFrameWriter::K_INT32_PLACEHOLDER);
+/// Writes the .eh_frame and .eh_frame_hdr of a single code object: a CIE
+/// with the initial state of the architecture, one FDE with the unwinding
+/// directives, a terminator and the lookup table header.
+pub struct EhFrameWriter {
+    cie_size_: usize,
+    last_pc_offset_: i32,
+    writer_state_: InternalState,
+    base_register_: Register,
+    base_offset_: i32,
+    eh_frame_buffer_: Vec<u8>,
+}
 
-            let record_start_offset = self.eh_frame_offset();
-            self.write_int32(K_CIE_IDENTIFIER);
-            self.write_byte(K_CIE_VERSION);
+impl Default for EhFrameWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            self.write_bytes(K_AUGMENTATION_STRING);
+impl EhFrameWriter {
+    const K_INT32_PLACEHOLDER: i32 = 0xBADCAFEu32 as i32;
 
-            self.write_sleb128(constants::K_CODE_ALIGNMENT_FACTOR);
-            self.write_sleb128(constants::K_DATA_ALIGNMENT_FACTOR);
+    pub fn new() -> Self {
+        EhFrameWriter {
+            cie_size_: 0,
+            last_pc_offset_: 0,
+            writer_state_: InternalState::kUndefined,
+            base_register_: Register::NO_REG,
+            base_offset_: 0,
+            eh_frame_buffer_: Vec::new(),
+        }
+    }
 
-            self.write_return_address_register_code();
+    /// The empty .eh_frame_hdr that perf expects for code without unwinding
+    /// info.
+    pub fn write_empty_eh_frame<W: Write>(stream: &mut W) -> io::Result<()> {
+        stream.write_all(&[
+            EhFrameConstants::K_EH_FRAME_HDR_VERSION,
+            // .eh_frame pointer encoding specifier.
+            EhFrameConstants::K_SDATA4 | EhFrameConstants::K_PC_REL,
+            // Lookup table size encoding.
+            EhFrameConstants::K_UDATA4,
+            // Lookup table entries encoding.
+            EhFrameConstants::K_SDATA4 | EhFrameConstants::K_DATA_REL,
+        ])?;
+        stream.write_all(&[0; EhFrameConstants::K_EH_FRAME_HDR_SIZE - 4])
+    }
 
-            self.write_uleb128(K_AUGMENTATION_DATA_SIZE as u32);
-            self.write_byte(constants::KOMIT);
-            self.write_byte(constants::K_SDATA4 | constants::K_PCREL);
+    pub fn initialize(&mut self) {
+        assert_eq!(self.writer_state_, InternalState::kUndefined);
+        self.eh_frame_buffer_.reserve(128);
+        self.writer_state_ = InternalState::kInitialized;
+        self.write_cie();
+        self.write_fde_header();
+    }
 
-            assert_eq!(
-                self.eh_frame_offset() - size_offset,
-                constants::K_INITIAL_STATE_OFFSET_IN_CIE
-            );
-            self.write_initial_state_in_cie();
+    fn write_cie(&mut self) {
+        const K_CIE_IDENTIFIER: i32 = 0;
+        const K_CIE_VERSION: u8 = 3;
+        const K_AUGMENTATION_DATA_SIZE: u32 = 2;
+        const K_AUGMENTATION_STRING: &[u8] = b"zLR\0";
 
-            self.write_padding_to_aligned_size(self.eh_frame_offset() - record_start_offset);
+        // Placeholder for the size of the CIE.
+        let size_offset = self.eh_frame_offset();
+        self.write_int32(Self::K_INT32_PLACEHOLDER);
 
-            let record_end_offset = self.eh_frame_offset();
-            let encoded_cie_size = record_end_offset - record_start_offset;
-            self.cie_size_ = record_end_offset - size_offset;
+        // The following bytes are the ones counted by the size.
+        let record_start_offset = self.eh_frame_offset();
+        self.write_int32(K_CIE_IDENTIFIER);
+        self.write_byte(K_CIE_VERSION);
 
-            self.patch_int32(size_offset, encoded_cie_size);
-        }
+        // Augmentation data contents descriptor: LSDA and FDE encoding.
+        self.write_bytes(K_AUGMENTATION_STRING);
 
-        fn write_fde_header(&mut self) {
-            assert_ne!(self.cie_size_, 0);
+        // Alignment factors.
+        self.write_sleb128(EhFrameConstants::K_CODE_ALIGNMENT_FACTOR);
+        self.write_sleb128(EhFrameConstants::K_DATA_ALIGNMENT_FACTOR);
 
-            assert_eq!(self.eh_frame_offset(), self.fde_offset());
-            self.write_int32(EhFrameWriter::K_INT32_PLACEHOLDER);
+        arch::write_return_address_register_code(self);
 
-            self.write_int32((self.cie_size_ + K_INT32_SIZE) as i32);
+        // Augmentation data.
+        self.write_uleb128(K_AUGMENTATION_DATA_SIZE);
+        // No language-specific data area (LSDA).
+        self.write_byte(EhFrameConstants::K_OMIT);
+        // FDE pointers encoding.
+        self.write_byte(EhFrameConstants::K_SDATA4 | EhFrameConstants::K_PC_REL);
 
-            assert_eq!(self.eh_frame_offset(), self.get_procedure_address_offset());
-            self.write_int32(EhFrameWriter::K_INT32_PLACEHOLDER);
+        // Write directives to build the initial state of the unwinding
+        // table.
+        assert_eq!(self.eh_frame_offset() - size_offset, EhFrameConstants::K_INITIAL_STATE_OFFSET_IN_CIE);
+        arch::write_initial_state_in_cie(self);
 
-            assert_eq!(self.eh_frame_offset(), self.get_procedure_size_offset());
-            self.write_int32(EhFrameWriter::K_INT32_PLACEHOLDER);
+        self.write_padding_to_aligned_size(self.eh_frame_offset() - record_start_offset);
 
-            self.write_byte(0);
-        }
+        let record_end_offset = self.eh_frame_offset();
+        let encoded_cie_size = record_end_offset - record_start_offset;
+        self.cie_size_ = record_end_offset - size_offset;
 
-        pub fn write_eh_frame_hdr(&mut self, code_size: i32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
+        // Patch the size of the CIE now that we know it.
+        self.patch_int32(size_offset, encoded_cie_size as i32);
+    }
 
-            let eh_frame_size = self.eh_frame_offset();
+    fn write_fde_header(&mut self) {
+        assert_ne!(self.cie_size_, 0);
 
-            self.write_byte(constants::K_EH_FRAME_HDR_VERSION);
+        // Placeholder for size of the FDE. Will be filled in Finish().
+        assert_eq!(self.eh_frame_offset(), self.fde_offset());
+        self.write_int32(Self::K_INT32_PLACEHOLDER);
 
-            self.write_byte(constants::K_SDATA4 | constants::K_PCREL);
-            self.write_byte(constants::K_UDATA4);
-            self.write_byte(constants::K_SDATA4 | constants::K_DATAREL);
+        // Backwards offset to the CIE.
+        self.write_int32((self.cie_size_ + K_INT32_SIZE) as i32);
 
-            self.write_int32(
-                -(eh_frame_size as i32 + constants::K_FDE_VERSION_SIZE as i32 +
-                    constants::K_FDE_ENCODING_SPECIFIERS_SIZE as i32),
-            );
+        // Placeholder for pointer to procedure. Will be filled in Finish().
+        assert_eq!(self.eh_frame_offset(), self.get_procedure_address_offset());
+        self.write_int32(Self::K_INT32_PLACEHOLDER);
 
-            self.write_int32(1);
+        // Placeholder for size of the procedure. Will be filled in Finish().
+        assert_eq!(self.eh_frame_offset(), self.get_procedure_size_offset());
+        self.write_int32(Self::K_INT32_PLACEHOLDER);
 
-            self.write_int32(-(round_up(code_size, 8) as i32 + eh_frame_size as i32));
+        // No augmentation data.
+        self.write_byte(0);
+    }
 
-            self.write_int32(-(eh_frame_size as i32 - self.cie_size_ as i32));
+    fn write_eh_frame_hdr(&mut self, code_size: i32) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
 
-            assert_eq!(
-                self.eh_frame_offset() - eh_frame_size,
-                constants::K_EH_FRAME_HDR_SIZE
-            );
-        }
+        let eh_frame_size = self.eh_frame_offset() as i32;
 
-        fn write_padding_to_aligned_size(&mut self, unpadded_size: usize) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            assert!(unpadded_size >= 0);
+        self.write_byte(EhFrameConstants::K_EH_FRAME_HDR_VERSION);
+        // .eh_frame pointer encoding specifier.
+        self.write_byte(EhFrameConstants::K_SDATA4 | EhFrameConstants::K_PC_REL);
+        // Lookup table size encoding.
+        self.write_byte(EhFrameConstants::K_UDATA4);
+        // Lookup table entries encoding.
+        self.write_byte(EhFrameConstants::K_SDATA4 | EhFrameConstants::K_DATA_REL);
 
-            let padding_size = round_up(unpadded_size as i32, K_SYSTEM_POINTER_SIZE as i32) as usize - unpadded_size;
+        // Pointer to .eh_frame, relative to this offset.
+        self.write_int32(
+            -(eh_frame_size
+                + EhFrameConstants::K_FDE_VERSION_SIZE as i32
+                + EhFrameConstants::K_FDE_ENCODING_SPECIFIERS_SIZE as i32),
+        );
 
-            let nop = constants::dwarf_opcodes::K_NOP;
-            let k_padding = [nop; 8];
-            assert!(padding_size <= k_padding.len());
-            self.write_bytes(&k_padding[0..padding_size]);
-        }
+        // Number of entries in the lookup table, one for the only routine.
+        self.write_int32(1);
 
-        pub fn advance_location(&mut self, pc_offset: i32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            assert!(pc_offset >= self.last_pc_offset_);
-            let delta = (pc_offset - self.last_pc_offset_) as u32;
-
-            assert_eq!(delta % (constants::K_CODE_ALIGNMENT_FACTOR as u32), 0);
-            let factored_delta = delta / (constants::K_CODE_ALIGNMENT_FACTOR as u32);
-
-            if factored_delta <= constants::K_LOCATION_MASK as u32 {
-                self.write_byte(
-                    (constants::K_LOCATION_TAG << constants::K_LOCATION_MASK_SIZE)
-                        | (factored_delta as u8 & constants::K_LOCATION_MASK),
-                );
-            } else if factored_delta <= K_MAX_UINT8 {
-                self.write_opcode(constants::dwarf_opcodes::K_ADVANCE_LOC1);
-                self.write_byte(factored_delta as u8);
-            } else if factored_delta <= K_MAX_UINT16 {
-                self.write_opcode(constants::dwarf_opcodes::K_ADVANCE_LOC2);
-                self.write_int16(factored_delta as u16);
-            } else {
-                self.write_opcode(constants::dwarf_opcodes::K_ADVANCE_LOC4);
-                self.write_int32(factored_delta as i32);
-            }
+        // Pointer to the start of the routine, relative to the beginning of
+        // the .eh_frame_hdr.
+        self.write_int32(-(round_up(code_size, 8) + eh_frame_size));
 
-            self.last_pc_offset_ = pc_offset;
-        }
+        // Pointer to the start of the associated FDE, relative to the start of
+        // the .eh_frame_hdr.
+        self.write_int32(-(eh_frame_size - self.cie_size_ as i32));
 
-        pub fn set_base_address_offset(&mut self, base_offset: i32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            assert!(base_offset >= 0);
-            self.write_opcode(constants::dwarf_opcodes::K_DEF_CFA_OFFSET);
-            self.write_uleb128(base_offset as u32);
-            self.base_offset_ = base_offset;
-        }
+        assert_eq!(self.eh_frame_offset() - eh_frame_size as usize, EhFrameConstants::K_EH_FRAME_HDR_SIZE);
+    }
 
-        pub fn set_base_address_register(&mut self, base_register: Register) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            let code = self.register_to_dwarf_code(base_register);
-            self.write_opcode(constants::dwarf_opcodes::K_DEF_CFA_REGISTER);
-            self.write_uleb128(code);
-            self.base_register_ = base_register;
-        }
+    fn write_padding_to_aligned_size(&mut self, unpadded_size: usize) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        let padding_size = round_up(unpadded_size as i32, K_SYSTEM_POINTER_SIZE as i32) as usize - unpadded_size;
+        let k_padding = [dwarf_opcodes::K_NOP; 8];
+        assert!(padding_size <= k_padding.len());
+        self.write_bytes(&k_padding[..padding_size]);
+    }
 
-        pub fn set_base_address_register_and_offset(
-            &mut self,
-            base_register: Register,
-            base_offset: i32,
-        ) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            assert!(base_offset >= 0);
-            let code = self.register_to_dwarf_code(base_register);
-            self.write_opcode(constants::dwarf_opcodes::K_DEF_CFA);
-            self.write_uleb128(code);
-            self.write_uleb128(base_offset as u32);
-            self.base_offset_ = base_offset;
-            self.base_register_ = base_register;
-        }
+    pub fn advance_location(&mut self, pc_offset: i32) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        assert!(pc_offset >= self.last_pc_offset_);
+        let delta = (pc_offset - self.last_pc_offset_) as u32;
 
-        pub fn record_register_saved_to_stack(&mut self, dwarf_register_code: i32, offset: i32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            assert_eq!(offset % constants::K_DATA_ALIGNMENT_FACTOR, 0);
-            let factored_offset = offset / constants::K_DATA_ALIGNMENT_FACTOR;
-            if factored_offset >= 0 {
-                //assert!(dwarf_register_code <= constants::K_SAVED_REGISTER_MASK as i32); //removed for compilation
-                self.write_byte(
-                    (constants::K_SAVED_REGISTER_TAG << constants::K_SAVED_REGISTER_MASK_SIZE)
-                        | (dwarf_register_code as u8 & constants::K_SAVED_REGISTER_MASK),
-                );
-                self.write_uleb128(factored_offset as u32);
-            } else {
-                self.write_opcode(constants::dwarf_opcodes::K_OFFSET_EXTENDED_SF);
-                self.write_uleb128(dwarf_register_code as u32);
-                self.write_sleb128(factored_offset);
-            }
-        }
+        assert_eq!(delta % EhFrameConstants::K_CODE_ALIGNMENT_FACTOR as u32, 0);
+        let factored_delta = delta / EhFrameConstants::K_CODE_ALIGNMENT_FACTOR as u32;
 
-        pub fn record_register_not_modified(&mut self, name: Register) {
-            self.record_register_not_modified_by_code(self.register_to_dwarf_code(name));
+        if factored_delta <= EhFrameConstants::K_LOCATION_MASK as u32 {
+            self.write_byte(
+                (EhFrameConstants::K_LOCATION_TAG << EhFrameConstants::K_LOCATION_MASK_SIZE)
+                    | (factored_delta as u8 & EhFrameConstants::K_LOCATION_MASK),
+            );
+        } else if factored_delta <= u8::MAX as u32 {
+            self.write_opcode(dwarf_opcodes::K_ADVANCE_LOC1);
+            self.write_byte(factored_delta as u8);
+        } else if factored_delta <= u16::MAX as u32 {
+            self.write_opcode(dwarf_opcodes::K_ADVANCE_LOC2);
+            self.write_int16(factored_delta as u16);
+        } else {
+            self.write_opcode(dwarf_opcodes::K_ADVANCE_LOC4);
+            self.write_int32(factored_delta as i32);
         }
 
-        pub fn record_register_not_modified_by_code(&mut self, dwarf_register_code: u32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            self.write_opcode(constants::dwarf_opcodes::K_SAME_VALUE);
-            self.write_uleb128(dwarf_register_code);
-        }
+        self.last_pc_offset_ = pc_offset;
+    }
 
-        pub fn record_register_follows_initial_rule(&mut self, name: Register) {
-            self.record_register_follows_initial_rule_by_code(self.register_to_dwarf_code(name));
-        }
+    pub fn last_pc_offset(&self) -> i32 {
+        self.last_pc_offset_
+    }
 
-        pub fn record_register_follows_initial_rule_by_code(&mut self, dwarf_register_code: u32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            if dwarf_register_code <= constants::K_FOLLOW_INITIAL_RULE_MASK as u32 {
-                self.write_byte(
-                    (constants::K_FOLLOW_INITIAL_RULE_TAG << constants::K_FOLLOW_INITIAL_RULE_MASK_SIZE)
-                        | (dwarf_register_code as u8 & constants::K_FOLLOW_INITIAL_RULE_MASK),
-                );
-            } else {
-                self.write_opcode(constants::dwarf_opcodes::K_RESTORE_EXTENDED);
-                self.write_uleb128(dwarf_register_code);
-            }
-        }
+    pub fn base_register(&self) -> Register {
+        self.base_register_
+    }
 
-        pub fn finish(&mut self, code_size: i32) {
-            assert_eq!(self.writer_state_, InternalState::Initialized);
-            assert!(self.eh_frame_offset() >= self.cie_size_);
+    pub fn base_offset(&self) -> i32 {
+        self.base_offset_
+    }
 
-            assert!(self.eh_frame_offset() >= self.fde_offset() + K_INT32_SIZE);
-            self.write_padding_to_aligned_size(self.eh_frame_offset() - self.fde_offset() - K_INT32_SIZE);
+    pub fn increase_base_address_offset(&mut self, base_delta: i32) {
+        self.set_base_address_offset(self.base_offset_ + base_delta);
+    }
 
-            let encoded_fde_size = self.eh_frame_offset() - self.fde_offset() - K_INT32_SIZE;
-            self.patch_int32(self.fde_offset(), encoded_fde_size);
+    pub fn set_base_address_offset(&mut self, base_offset: i32) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        assert!(base_offset >= 0);
+        self.write_opcode(dwarf_opcodes::K_DEF_CFA_OFFSET);
+        self.write_uleb128(base_offset as u32);
+        self.base_offset_ = base_offset;
+    }
 
-            self.patch_int32(
-                self.get_procedure_address_offset(),
-                -(round_up(code_size, 8) as i32 + self.get_procedure_address_offset() as i32),
-            );
-            self.patch_int32(self.get_procedure_size_offset(), code_size);
+    pub fn set_base_address_register(&mut self, base_register: Register) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        let code = arch::register_to_dwarf_code(base_register);
+        self.write_opcode(dwarf_opcodes::K_DEF_CFA_REGISTER);
+        self.write_uleb128(code as u32);
+        self.base_register_ = base_register;
+    }
 
-            let terminator = [0u8; constants::K_EH_FRAME_TERMINATOR_SIZE];
-            self.write_bytes(&terminator);
+    pub fn set_base_address_register_and_offset(&mut self, base_register: Register, base_offset: i32) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        assert!(base_offset >= 0);
+        let code = arch::register_to_dwarf_code(base_register);
+        self.write_opcode(dwarf_opcodes::K_DEF_CFA);
+        self.write_uleb128(code as u32);
+        self.write_uleb128(base_offset as u32);
+        self.base_offset_ = base_offset;
+        self.base_register_ = base_register;
+    }
 
-            self.write_eh_frame_hdr(code_size);
+    pub fn record_register_saved_to_stack_reg(&mut self, name: Register, offset: i32) {
+        self.record_register_saved_to_stack(arch::register_to_dwarf_code(name), offset);
+    }
 
-            self.writer_state_ = InternalState::Finalized;
+    pub fn record_register_saved_to_stack(&mut self, dwarf_register_code: i32, offset: i32) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        assert_eq!(offset % EhFrameConstants::K_DATA_ALIGNMENT_FACTOR, 0);
+        let factored_offset = offset / EhFrameConstants::K_DATA_ALIGNMENT_FACTOR;
+        if factored_offset >= 0 {
+            assert!(dwarf_register_code <= EhFrameConstants::K_SAVED_REGISTER_MASK as i32);
+            self.write_byte(
+                (EhFrameConstants::K_SAVED_REGISTER_TAG << EhFrameConstants::K_SAVED_REGISTER_MASK_SIZE)
+                    | (dwarf_register_code as u8 & EhFrameConstants::K_SAVED_REGISTER_MASK),
+            );
+            self.write_uleb128(factored_offset as u32);
+        } else {
+            self.write_opcode(dwarf_opcodes::K_OFFSET_EXTENDED_SF);
+            self.write_uleb128(dwarf_register_code as u32);
+            self.write_sleb128(factored_offset);
         }
+    }
 
-        pub fn get_eh_frame(&self) -> Vec<u8> {
-            assert_eq!(self.writer_state_, InternalState::Finalized);
-            self.eh_frame_buffer_.clone()
-        }
+    pub fn record_register_not_modified(&mut self, name: Register) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        self.write_opcode(dwarf_opcodes::K_SAME_VALUE);
+        self.write_uleb128(arch::register_to_dwarf_code(name) as u32);
+    }
 
-        fn write_uleb128(&mut self, mut value: u32) {
-            loop {
-                let mut chunk = value & 0x7F;
-                value >>= 7;
-                if value != 0 {
-                    chunk |= 0x80;
-                }
-                self.write_byte(chunk as u8);
-                if value == 0 {
-                    break;
-                }
-            }
+    pub fn record_register_follows_initial_rule(&mut self, name: Register) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        let code = arch::register_to_dwarf_code(name);
+        if code <= EhFrameConstants::K_FOLLOW_INITIAL_RULE_MASK as i32 {
+            self.write_byte(
+                (EhFrameConstants::K_FOLLOW_INITIAL_RULE_TAG << EhFrameConstants::K_FOLLOW_INITIAL_RULE_MASK_SIZE)
+                    | (code as u8 & EhFrameConstants::K_FOLLOW_INITIAL_RULE_MASK),
+            );
+        } else {
+            self.write_opcode(dwarf_opcodes::K_RESTORE_EXTENDED);
+            self.write_uleb128(code as u32);
         }
+    }
 
-        fn write_sleb128(&mut self, mut value: i32) {
-            const K_SIGN_BIT_MASK: i32 = 0x40;
-            loop {
-                let mut chunk = value & 0x7F;
-                value >>= 7;
-                let done = (value == 0 && (chunk & K_SIGN_BIT_MASK) == 0) || (value == -1 && (chunk & K_SIGN_BIT_MASK) != 0);
-                if !done {
-                    chunk |= 0x80;
-                }
-                self.write_byte(chunk as u8);
-                if done {
-                    break;
-                }
-            }
-        }
+    /// Completes the FDE and writes the .eh_frame_hdr. The code the FDE
+    /// describes is expected right before the .eh_frame, 8-byte aligned.
+    pub fn finish(&mut self, code_size: i32) {
+        assert_eq!(self.writer_state_, InternalState::kInitialized);
+        assert!(self.eh_frame_offset() >= self.cie_size_);
 
-        fn write_int32(&mut self, value: i32) {
-            self.eh_frame_buffer_.write_i32::<LittleEndian>(value).unwrap();
-        }
+        assert!(self.eh_frame_offset() >= self.fde_offset() + K_INT32_SIZE);
+        self.write_padding_to_aligned_size(self.eh_frame_offset() - self.fde_offset() - K_INT32_SIZE);
 
-        fn write_int16(&mut self, value: u16) {
-            self.eh_frame_buffer_.write_u16::<LittleEndian>(value).unwrap();
-        }
+        // Write the size of the FDE now that we know it. The encoded size
+        // does not include the size field itself.
+        let encoded_fde_size = self.eh_frame_offset() - self.fde_offset() - K_INT32_SIZE;
+        self.patch_int32(self.fde_offset(), encoded_fde_size as i32);
 
-        fn write_byte(&mut self, value: u8) {
-            self.eh_frame_buffer_.push(value);
-        }
+        // Write size and offset to procedure.
+        self.patch_int32(
+            self.get_procedure_address_offset(),
+            -(round_up(code_size, 8) + self.get_procedure_address_offset() as i32),
+        );
+        self.patch_int32(self.get_procedure_size_offset(), code_size);
 
-        fn write_bytes(&mut self, bytes: &[u8]) {
-            self.eh_frame_buffer_.extend_from_slice(bytes);
-        }
+        // Terminate the .eh_frame.
+        self.write_bytes(&[0; EhFrameConstants::K_EH_FRAME_TERMINATOR_SIZE]);
 
-        fn write_opcode(&mut self, opcode: u8) {
-            self.write_byte(opcode);
-        }
+        self.write_eh_frame_hdr(code_size);
 
-        fn patch_int32(&mut self, offset: usize, value: i32) {
-            let bytes = value.to_le_bytes();
-            self.eh_frame_buffer_[offset..offset + 4].copy_from_slice(&bytes);
-        }
+        self.writer_state_ = InternalState::kFinalized;
+    }
 
-        fn eh_frame_offset(&self) -> usize {
-            self.eh_frame_buffer_.len()
-        }
+    /// Returns the .eh_frame followed by the .eh_frame_hdr.
+    pub fn get_eh_frame(&self) -> Vec<u8> {
+        assert_eq!(self.writer_state_, InternalState::kFinalized);
+        self.eh_frame_buffer_.clone()
+    }
 
-        fn fde_offset(&self) -> usize {
-            self.cie_size_ + K_INT32_SIZE
+    fn write_uleb128(&mut self, mut value: u32) {
+        loop {
+            let mut chunk = (value & 0x7F) as u8;
+            value >>= 7;
+            if value != 0 {
+                chunk |= 0x80;
+            }
+            self.write_byte(chunk);
+            if value == 0 {
+                break;
+            }
         }
+    }
 
-        fn get_procedure_address_offset(&self) -> usize {
-            self.cie_size_ + (2 * K_INT32_SIZE)
+    fn write_sleb128(&mut self, mut value: i32) {
+        const K_SIGN_BIT_MASK: u8 = 0x40;
+        loop {
+            let mut chunk = (value & 0x7F) as u8;
+            value >>= 7;
+            let done = (value == 0 && (chunk & K_SIGN_BIT_MASK) == 0) || (value == -1 && (chunk & K_SIGN_BIT_MASK) != 0);
+            if !done {
+                chunk |= 0x80;
+            }
+            self.write_byte(chunk);
+            if done {
+                break;
+            }
         }
+    }
 
-        fn get_procedure_size_offset(&self) -> usize {
-            self.cie_size_ + (3 * K_INT32_SIZE)
-        }
+    fn write_int32(&mut self, value: i32) {
+        self.eh_frame_buffer_.extend_from_slice(&value.to_le_bytes());
+    }
 
-        fn write_return_address_register_code(&mut self) {
-            // Architecture-specific code, unimplemented in this example
-            // Handle the register code generation here
-            // Replace with the correct architecture-specific code generation.
-            println!("UNIMPLEMENTED: WriteReturnAddressRegisterCode");
-        }
+    fn write_int16(&mut self, value: u16) {
+        self.eh_frame_buffer_.extend_from_slice(&value.to_le_bytes());
+    }
 
-        fn write_initial_state_in_cie(&mut self) {
-            // Architecture-specific code, unimplemented in this example
-            // Handle the initial state generation here
-            // Replace with the correct architecture-specific code generation.
-             println!("UNIMPLEMENTED: WriteInitialStateInCie");
-        }
+    fn write_byte(&mut self, value: u8) {
+        self.eh_frame_buffer_.push(value);
+    }
 
-        fn register_to_dwarf_code(&self, reg: Register) -> u32 {
-            // Architecture-specific code, unimplemented in this example
-            // Translate the register to dwarf code.
-            // Replace with the correct architecture-specific code translation.
-            // Example:
-            // match reg {
-            //     Register::SomeReg(0) => 0,
-            //     Register::SomeReg(1) => 1,
-            //     _ => panic!("Unsupported register"),
-            // }
-            if reg.is_no_reg() {
-                0
-            } else {
-                println!("UNIMPLEMENTED: RegisterToDwarfCode");
-                0
-            }
-        }
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.eh_frame_buffer_.extend_from_slice(bytes);
     }
 
-    fn round_up(value: i32, alignment: i32) -> i32 {
-        if alignment == 0 {
-            return value;
-        }
-        let modulus = value % alignment;
-        if modulus == 0 {
-            value
-        } else {
-            value + (alignment - modulus)
-        }
+    fn write_opcode(&mut self, opcode: u8) {
+        self.write_byte(opcode);
     }
-}
 
-mod eh_frame_iterator {
-    use byteorder::{LittleEndian, ReadBytesExt};
-    use std::io::Cursor;
-    use std::convert::TryInto;
+    fn patch_int32(&mut self, offset: usize, value: i32) {
+        self.eh_frame_buffer_[offset..offset + K_INT32_SIZE].copy_from_slice(&value.to_le_bytes());
+    }
 
-    pub struct EhFrameIterator<'a> {
-        next_: &'a [u8],
-        end_: &'a [u8],
+    fn eh_frame_offset(&self) -> usize {
+        self.eh_frame_buffer_.len()
     }
 
-    impl<'a> EhFrameIterator<'a> {
-        pub fn new(start: &'a [u8], end: &'a [u8]) -> Self {
-            EhFrameIterator {
-                next_: start,
-                end_: end,
-            }
-        }
+    fn fde_offset(&self) -> usize {
+        self.cie_size_
+    }
 
-        pub fn get_next_uleb128(&mut self) -> u32 {
-            let mut size = 0;
-            let result = self.decode_uleb128(&mut size);
-            assert!(self.next_.len() >= size);
-            self.next_ = &self.next_[size..];
-            result
-        }
+    fn get_procedure_address_offset(&self) -> usize {
+        self.fde_offset() + EhFrameConstants::K_PROCEDURE_ADDRESS_OFFSET_IN_FDE
+    }
 
-        pub fn get_next_sleb128(&mut self) -> i32 {
-            let mut size = 0;
-            let result = self.decode_sleb128(&mut size);
-            assert!(self.next_.len() >= size);
-            self.next_ = &self.next_[size..];
-            result
-        }
+    fn get_procedure_size_offset(&self) -> usize {
+        self.fde_offset() + EhFrameConstants::K_PROCEDURE_SIZE_OFFSET_IN_FDE
+    }
+}
 
-        pub fn decode_uleb128(&mut self, encoded_size: &mut usize) -> u32 {
-            let mut current = self.next_;
-            let mut result: u32 = 0;
-            let mut shift = 0;
-            let mut encoded_bytes: usize = 0;
-
-            loop {
-                assert!(shift < 8 * std::mem::size_of::<u32>());
-                let byte = current[0];
-                current = &current[1..];
-                encoded_bytes += 1;
-                result |= ((byte & 0x7F) as u32) << shift;
-                shift += 7;
-                if byte < 128 {
-                    break;
-                }
-            }
+fn round_up(value: i32, alignment: i32) -> i32 {
+    (value + alignment - 1) / alignment * alignment
+}
 
-            *encoded_size = encoded_bytes;
-            result
-        }
+/// Reads the values written by EhFrameWriter.
+pub struct EhFrameIterator<'a> {
+    bytes_: &'a [u8],
+    next_: usize,
+}
 
-        pub fn decode_sleb128(&mut self, encoded_size: &mut usize) -> i32 {
-            const K_SIGN_BIT_MASK: u8 = 0x40;
-
-            let mut current = self.next_;
-            let mut result: i32 = 0;
-            let mut shift = 0;
-            let mut chunk: u8 = 0;
-            let mut encoded_bytes: usize = 0;
-
-            loop {
-                chunk = current[0];
-                current = &current[1..];
-                encoded_bytes += 1;
-                assert!(shift < 8 * std::mem::size_of::<i32>());
-                result |= ((chunk & 0x7F) as i32) << shift;
-                shift += 7;
-                if chunk < 128 {
-                    break;
-                }
-            }
+impl<'a> EhFrameIterator<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        EhFrameIterator { bytes_: bytes, next_: 0 }
+    }
 
-            if (chunk & K_SIGN_BIT_MASK) != 0 {
-                result |= (!0i64 << shift) as i32;
-            }
+    pub fn skip(&mut self, how_many: usize) {
+        self.next_ += how_many;
+        assert!(self.next_ <= self.bytes_.len());
+    }
 
-            *encoded_size = encoded_bytes;
-            result
-        }
+    pub fn get_next_uint32(&mut self) -> u32 {
+        let bytes = self.bytes_[self.next_..self.next_ + 4].try_into().unwrap();
+        self.next_ += 4;
+        u32::from_le_bytes(bytes)
+    }
 
-        pub fn done(&self) -> bool {
-            self.next_ >= self.end_
-        }
+    pub fn get_next_uint16(&mut self) -> u16 {
+        let bytes = self.bytes_[self.next_..self.next_ + 2].try_into().unwrap();
+        self.next_ += 2;
+        u16::from_le_bytes(bytes)
+    }
 
-        pub fn current_address(&self) -> usize {
-            self.end_.len() - self.next_.len()
-        }
+    pub fn get_next_byte(&mut self) -> u8 {
+        let byte = self.bytes_[self.next_];
+        self.next_ += 1;
+        byte
+    }
 
-        pub fn get_next_byte(&mut self) -> u8 {
-            let byte = self.next_[0];
-            self.next_ = &self.next_[1..];
-            byte
+    pub fn get_next_uleb128(&mut self) -> u32 {
+        let mut result: u32 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.get_next_byte();
+            result |= u32::from(byte & 0x7F) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return result;
+            }
         }
+    }
 
-        pub fn get_next_u16(&mut self) -> u16 {
-            let mut rdr = Cursor::new(self.next_);
-            let value = rdr.read_u16::<LittleEndian>().unwrap();
-            self.next_ = &self.next_[2..];
-            value
+    pub fn get_next_sleb128(&mut self) -> i32 {
+        const K_SIGN_BIT_MASK: u8 = 0x40;
+        let mut result: i32 = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.get_next_byte();
+            result |= i32::from(chunk & 0x7F) << shift;
+            shift += 7;
+            if chunk & 0x80 == 0 {
+                if chunk & K_SIGN_BIT_MASK != 0 && shift < 32 {
+                    result |= -1 << shift;
+                }
+                return result;
+            }
         }
+    }
 
-        pub fn get_next_u32(&mut self) -> u32 {
-            let mut rdr = Cursor::new(self.next_);
-            let value = rdr.read_u32::<LittleEndian>().unwrap();
-            self.next_ = &self.next_[4..];
-            value
-        }
+    pub fn done(&self) -> bool {
+        self.next_ >= self.bytes_.len()
+    }
+
+    pub fn get_current_offset(&self) -> usize {
+        self.next_
     }
 }
 
-mod eh_frame_disassembler {
-    use super::eh_frame_constants as constants;
-    use super::eh_frame_iterator::EhFrameIterator;
-    use byteorder::{LittleEndian, ReadBytesExt};
-    use std::io::{self, Write, Cursor};
-    use std::convert::TryInto;
+/// Prints the directives of an .eh_frame written by EhFrameWriter.
+pub struct EhFrameDisassembler<'a> {
+    eh_frame_: &'a [u8],
+}
 
-    pub struct EhFrameDisassembler<'a> {
-        start_: &'a [u8],
-        end_: &'a [u8],
+impl<'a> EhFrameDisassembler<'a> {
+    pub fn new(eh_frame: &'a [u8]) -> Self {
+        EhFrameDisassembler { eh_frame_: eh_frame }
     }
 
-    impl<'a> EhFrameDisassembler<'a> {
-        pub fn new(start: &'a [u8], end: &'a [u8]) -> Self {
-            EhFrameDisassembler {
-                start_: start,
-                end_: end,
-            }
-        }
+    fn dump_dwarf_directives<W: Write>(stream: &mut W, directives: &[u8], base_offset: usize) -> io::Result<()> {
+        let mut eh_frame_iterator = EhFrameIterator::new(directives);
+        let mut offset_in_procedure: i32 = 0;
 
-        pub fn dump_dwarf_directives<W: Write>(
-            stream: &mut W,
-            start: &[u8],
-            end: &[u8],
-        ) -> io::Result<()> {
-            let mut eh_frame_iterator = EhFrameIterator::new(start, end);
-            let mut offset_in_procedure: i32 = 0;
+        while !eh_frame_iterator.done() {
+            write!(stream, "{:04x}  ", base_offset + eh_frame_iterator.get_current_offset())?;
 
-            while !eh_frame_iterator.done() {
-                write!(stream, "{:04x}  ", eh_frame_iterator.current_address())?;
+            let bytecode = eh_frame_iterator.get_next_byte();
 
-                let bytecode = eh_frame_iterator.get_next_byte();
+            if (bytecode >> EhFrameConstants::K_LOCATION_MASK_SIZE) == EhFrameConstants::K_LOCATION_TAG {
+                let value = (bytecode & EhFrameConstants::K_LOCATION_MASK) as i32 * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR;
+                offset_in_procedure += value;
+                writeln!(stream, "| pc_offset={offset_in_procedure} (delta={value})")?;
+                continue;
+            }
 
-                if ((bytecode >> constants::K_LOCATION_MASK_SIZE) & 0xFF) == constants::K_LOCATION_TAG {
-                    let value = (bytecode & constants::K_LOCATION_MASK) as i32 * constants::K_CODE_ALIGNMENT_FACTOR;
-                    offset_in_procedure += value;
-                    writeln!(stream, "| pc_offset={} (delta={})", offset_in_procedure, value)?;
-                    continue;
-                }
+            if (bytecode >> EhFrameConstants::K_SAVED_REGISTER_MASK_SIZE) == EhFrameConstants::K_SAVED_REGISTER_TAG {
+                let decoded_offset = eh_frame_iterator.get_next_uleb128() as i32;
+                writeln!(
+                    stream,
+                    "| {} saved at base{:+}",
+                    arch::dwarf_register_code_to_string((bytecode & EhFrameConstants::K_SAVED_REGISTER_MASK) as i32),
+                    decoded_offset * EhFrameConstants::K_DATA_ALIGNMENT_FACTOR
+                )?;
+                continue;
+            }
+
+            if (bytecode >> EhFrameConstants::K_FOLLOW_INITIAL_RULE_MASK_SIZE) == EhFrameConstants::K_FOLLOW_INITIAL_RULE_TAG
+            {
+                writeln!(
+                    stream,
+                    "| {} follows rule in CIE",
+                    arch::dwarf_register_code_to_string((bytecode & EhFrameConstants::K_FOLLOW_INITIAL_RULE_MASK) as i32)
+                )?;
+                continue;
+            }
 
-                if ((bytecode >> constants::K_SAVED_REGISTER_MASK_SIZE) & 0xFF) == constants::K_SAVED_REGISTER_TAG {
-                    let decoded_offset = eh_frame_iterator.get_next_uleb128();
+            match bytecode {
+                dwarf_opcodes::K_OFFSET_EXTENDED_SF => {
+                    let register = arch::dwarf_register_code_to_string(eh_frame_iterator.get_next_uleb128() as i32);
+                    let decoded_offset = eh_frame_iterator.get_next_sleb128();
                     writeln!(
                         stream,
-                        "| {} saved at base +{}",
-                        Self::dwarf_register_code_to_string(bytecode as i32 & constants::K_LOCATION_MASK as i32),
-                        decoded_offset * constants::K_DATA_ALIGNMENT_FACTOR as u32
+                        "| {register} saved at base{:+}",
+                        decoded_offset * EhFrameConstants::K_DATA_ALIGNMENT_FACTOR
                     )?;
-                    continue;
                 }
-
-                if ((bytecode >> constants::K_FOLLOW_INITIAL_RULE_MASK_SIZE) & 0xFF) == constants::K_FOLLOW_INITIAL_RULE_TAG {
+                dwarf_opcodes::K_ADVANCE_LOC1 | dwarf_opcodes::K_ADVANCE_LOC2 | dwarf_opcodes::K_ADVANCE_LOC4 => {
+                    let delta = match bytecode {
+                        dwarf_opcodes::K_ADVANCE_LOC1 => eh_frame_iterator.get_next_byte() as i32,
+                        dwarf_opcodes::K_ADVANCE_LOC2 => eh_frame_iterator.get_next_uint16() as i32,
+                        _ => eh_frame_iterator.get_next_uint32() as i32,
+                    };
+                    let value = delta * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR;
+                    offset_in_procedure += value;
+                    writeln!(stream, "| pc_offset={offset_in_procedure} (delta={value})")?;
+                }
+                dwarf_opcodes::K_DEF_CFA => {
+                    let base_register = eh_frame_iterator.get_next_uleb128();
+                    let base_offset = eh_frame_iterator.get_next_uleb128();
                     writeln!(
                         stream,
-                        "| {} follows rule in CIE",
-                        Self::dwarf_register_code_to_string(bytecode as i32 & constants::K_LOCATION_MASK as i32)
+                        "| base_register={}, base_offset={base_offset}",
+                        arch::dwarf_register_code_to_string(base_register as i32)
                     )?;
-                    continue;
                 }
-
-                match bytecode as u32 {
-                    constants::dwarf_opcodes::K_OFFSET_EXTENDED_SF as u32 => {
-                        let register_code = eh_frame_iterator.get_next_uleb128();
-                        let decoded_offset = eh_frame_iterator.get_next_sleb128();
-                        writeln!(
-                            stream,
-                            "| {} saved at base +{}",
-                            Self::dwarf_register_code_to_string(register_code as i32),
-                            decoded_offset * constants::K_DATA_ALIGNMENT_FACTOR
-                        )?;
-                        
-                    }
-                    constants::dwarf_opcodes::K_ADVANCE_LOC1 as u32 => {
-                        let value = eh_frame_iterator.get_next_byte() as i32 * constants::K_CODE_ALIGNMENT_FACTOR;
-                        offset_in_procedure += value;
-                        writeln!(stream, "| pc_offset={} (delta={})", offset_in_procedure, value)?;
-                    }
-                    constants::dwarf_opcodes::K_ADVANCE_LOC2 as u32 => {
-                        let value = eh_frame_iterator.get_next_u16() as i32 * constants::K_CODE_ALIGNMENT_FACTOR;
-                        offset_in_procedure += value;
-                        writeln!(stream, "| pc_offset={} (delta={})", offset_in_procedure, value)?;
-                    }
-                    constants::dwarf_opcodes::K_ADVANCE_LOC4 as u32 => {
-                        let value = eh_frame_iterator.get_next_u32() as i32 * constants::K_CODE_ALIGNMENT_FACTOR;
-                        offset_in_procedure += value;
-                        writeln!(stream, "| pc_offset={} (delta={})", offset_in_procedure, value)?;
-                    }
-                    constants::dwarf_opcodes::K_DEF_CFA as u32 => {
-                        let base_register = eh_frame_iterator.get_next_uleb128();
-                        let base_offset = eh_frame_iterator.get_next_uleb128();
-                        writeln!(
-                            stream,
-                            "| base_register={}, base_offset={}",
-                            Self::dwarf_register_code_to_string(base_register as i32),
-                            base_offset
-                        )?;
-                    }
-                    constants::dwarf_opcodes::K_DEF_CFA_OFFSET as u32 => {
-                        let base_offset = eh_frame_iterator.get_next_uleb128();
-                        writeln!(stream, "| base_offset={}", base_offset)?;
-                    }
-                    constants::dwarf_opcodes::K_DEF_CFA_REGISTER as u32 => {
-                        let base_register = eh_frame_iterator.get_next_uleb128();
-                        writeln!(
-                            stream,
-                            "| base_register={}",
-                            Self::dwarf_register_code_to_string(base_register as i32)
-                        )?;
-                    }
-                    constants::dwarf_opcodes::K_SAME_VALUE as u32 => {
-                        let register_code = eh_frame_iterator.get_next_uleb128();
-                        writeln!(
-                            stream,
-                            "| {} not modified from previous frame",
-                            Self::dwarf_register_code_to_string(register_code as i32)
-                        )?;
-                    }
-                    constants::dwarf_opcodes::K_NOP as u32 => {
-                        writeln!(stream, "| nop")?;
-                    }
-                    _ => {
-                        println!("UNREACHABLE");
-                    }
+                dwarf_opcodes::K_DEF_CFA_OFFSET => {
+                    writeln!(stream, "| base_offset={}", eh_frame_iterator.get_next_uleb128())?;
+                }
+                dwarf_opcodes::K_DEF_CFA_REGISTER => {
+                    let base_register = eh_frame_iterator.get_next_uleb128();
+                    writeln!(stream, "| base_register={}", arch::dwarf_register_code_to_string(base_register as i32))?;
                 }
+                dwarf_opcodes::K_SAME_VALUE => {
+                    let register = eh_frame_iterator.get_next_uleb128();
+                    writeln!(
+                        stream,
+                        "| {} not modified from previous frame",
+                        arch::dwarf_register_code_to_string(register as i32)
+                    )?;
+                }
+                dwarf_opcodes::K_NOP => writeln!(stream, "| nop")?,
+                _ => writeln!(stream, "| unknown opcode {bytecode:#04x}")?,
             }
-            Ok(())
         }
+        Ok(())
+    }
 
-        pub fn disassemble_to_stream<W: Write>(&self, stream: &mut W) -> io::Result<()> {
-            let cie_size = {
-                let mut rdr = Cursor::new(self.start_);
-                rdr.read_u32::<LittleEndian>().unwrap() as usize + 4
-            };
+    pub fn disassemble_to_stream<W: Write>(&self, stream: &mut W) -> io::Result<()> {
+        let bytes = self.eh_frame_;
+        let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+        // The encoded CIE size does not include the size field itself.
+        let cie_size = read_u32(0) as usize + K_INT32_SIZE;
+        let fde_offset = cie_size;
+
+        writeln!(stream, "0000  .eh_frame: CIE")?;
+        Self::dump_dwarf_directives(
+            stream,
+            &bytes[EhFrameConstants::K_INITIAL_STATE_OFFSET_IN_CIE..cie_size],
+            EhFrameConstants::K_INITIAL_STATE_OFFSET_IN_CIE,
+        )?;
+
+        let procedure_offset_offset = fde_offset + EhFrameConstants::K_PROCEDURE_ADDRESS_OFFSET_IN_FDE;
+        let procedure_size_offset = fde_offset + EhFrameConstants::K_PROCEDURE_SIZE_OFFSET_IN_FDE;
+        writeln!(stream, "{fde_offset:04x}  .eh_frame: FDE")?;
+        writeln!(stream, "{procedure_offset_offset:04x}  | procedure_offset={}", read_u32(procedure_offset_offset) as i32)?;
+        writeln!(stream, "{procedure_size_offset:04x}  | procedure_size={}", read_u32(procedure_size_offset))?;
+
+        let fde_directives_offset = fde_offset + 4 * K_INT32_SIZE + 1;
+        let fde_directives_end =
+            bytes.len() - EhFrameConstants::K_EH_FRAME_HDR_SIZE - EhFrameConstants::K_EH_FRAME_TERMINATOR_SIZE;
+        Self::dump_dwarf_directives(stream, &bytes[fde_directives_offset..fde_directives_end], fde_directives_offset)?;
+
+        writeln!(stream, "{fde_directives_end:04x}  .eh_frame: terminator")?;
+        writeln!(
+            stream,
+            "{:04x}  .eh_frame_hdr",
+            fde_directives_end + EhFrameConstants::K_EH_FRAME_TERMINATOR_SIZE
+        )
+    }
+}
 
-            let fde_offset = cie_size;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_cie_fde_and_header() {
+        let mut writer = EhFrameWriter::new();
+        writer.initialize();
+        writer.advance_location(4 * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR);
+        writer.set_base_address_offset(16);
+        writer.advance_location(400 * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR);
+        writer.record_register_saved_to_stack(3, -16);
+        writer.finish(1000);
+        let eh_frame = writer.get_eh_frame();
+
+        let mut iterator = EhFrameIterator::new(&eh_frame);
+        let encoded_cie_size = iterator.get_next_uint32() as usize;
+        assert_eq!(encoded_cie_size % K_SYSTEM_POINTER_SIZE, 0);
+        let cie_size = encoded_cie_size + 4;
+        iterator.skip(cie_size - 4);
+        let fde_size = iterator.get_next_uint32() as usize + 4;
+        assert_eq!(iterator.get_next_uint32() as usize, cie_size + 4);
+        assert_eq!(iterator.get_next_uint32() as i32, -(1000 + 8 + cie_size as i32));
+        assert_eq!(iterator.get_next_uint32(), 1000);
+        assert_eq!(
+            eh_frame.len(),
+            cie_size + fde_size + EhFrameConstants::K_EH_FRAME_TERMINATOR_SIZE + EhFrameConstants::K_EH_FRAME_HDR_SIZE
+        );
+
+        let mut listing = Vec::new();
+        EhFrameDisassembler::new(&eh_frame).disassemble_to_stream(&mut listing).unwrap();
+        let listing = String::from_utf8(listing).unwrap();
+        for expected in [
+            ".eh_frame: CIE",
+            "| procedure_size=1000",
+            &format!("| pc_offset={} (delta={})", 4 * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR, 4 * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR),
+            "| base_offset=16",
+            &format!("| pc_offset={} (delta=", 400 * EhFrameConstants::K_CODE_ALIGNMENT_FACTOR),
+            "saved at base-16",
+            ".eh_frame_hdr",
+        ] {
+            assert!(listing.contains(expected), "missing {expected:?} in\n{listing}");
+        }
+    }
 
-            let cie_directives_start = &self.start_[constants::K_INITIAL_STATE_OFFSET_IN_CIE..];
\ No newline at end of file
+    #[test]
+    fn empty_eh_frame_is_a_header() {
+        let mut bytes = Vec::new();
+        EhFrameWriter::write_empty_eh_frame(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), EhFrameConstants::K_EH_FRAME_HDR_SIZE);
+        assert_eq!(bytes[0], EhFrameConstants::K_EH_FRAME_HDR_VERSION);
+    }
+}
@@ -1,28 +1,12 @@
-#![allow(non_camel_case_types, non_snake_case, unused_imports, dead_code, unused_variables)]
-
-use std::collections::HashMap;
-use std::mem;
-use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulong, c_ushort};
-use std::ptr;
-use std::slice;
-use std::str;
-use std::sync::{LazyLock, Mutex};
-
-//use libc; // Not used directly, so commenting out
-//use object; // Not used directly, so commenting out
-
-// Placeholder for v8-callbacks.h
-mod v8_callbacks {
-    // Define necessary types and functions here if needed
-}
+// Copyright 2010 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-// Placeholder for api-inl.h
-mod api_inl {
-    // Define necessary types and functions here if needed
-}
+pub mod gdb_jit {
+    use std::collections::BTreeMap;
+    use std::ptr;
+    use std::sync::Mutex;
 
-// Placeholder for base/address-region.h
-mod address_region {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
     pub struct AddressRegion {
         begin: usize,
@@ -30,8 +14,8 @@ mod address_region {
     }
 
     impl AddressRegion {
-        pub const fn new(begin: usize, end: usize) -> Self {
-            Self { begin, end }
+        pub const fn new(begin: usize, size: usize) -> Self {
+            Self { begin, end: begin + size }
         }
 
         pub const fn begin(&self) -> usize {
@@ -45,990 +29,1147 @@ mod address_region {
         pub const fn size(&self) -> usize {
             self.end - self.begin
         }
-    }
-}
-
-use address_region::AddressRegion;
 
-// Placeholder for base/bits.h
-mod bits {
-    pub fn is_power_of_two(x: u32) -> bool {
-        x != 0 && (x & (x - 1)) == 0
-    }
-
-    pub fn which_power_of_two(x: u32) -> u32 {
-        if !is_power_of_two(x) {
-            panic!("Not a power of two");
+        pub const fn overlaps(&self, other: &AddressRegion) -> bool {
+            self.begin < other.end && other.begin < self.end
         }
-        x.trailing_zeros()
     }
-}
 
-// Placeholder for base/hashmap.h
-mod hashmap {
-    use std::collections::HashMap;
-    use std::hash::{BuildHasherDefault, Hasher};
+    // -------------------------------------------------------------------
+    // Code events, as delivered to v8::JitCodeEventHandler.
 
-    #[derive(Default)]
-    pub struct SimpleHasher {
-        value: u64,
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum JitCodeEventType {
+        CODE_ADDED,
+        CODE_MOVED,
+        CODE_REMOVED,
+        CODE_ADD_LINE_POS_INFO,
+        CODE_START_LINE_INFO_RECORDING,
+        CODE_END_LINE_INFO_RECORDING,
     }
 
-    impl Hasher for SimpleHasher {
-        fn finish(&self) -> u64 {
-            self.value
-        }
-
-        fn write(&mut self, bytes: &[u8]) {
-            for &byte in bytes {
-                self.value = self.value.wrapping_mul(1103515245).wrapping_add(byte as u64);
-            }
-        }
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum PositionType {
+        POSITION,
+        STATEMENT_POSITION,
     }
 
-    pub type BuildSimpleHasher = BuildHasherDefault<SimpleHasher>;
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LinePosInfo {
+        /// Offset of the instruction from the start of the code.
+        pub offset: usize,
+        /// Source position, i.e. character offset into the script.
+        pub pos: usize,
+        pub position_type: PositionType,
+    }
 
-    pub struct HashMap {
-        map: std::collections::HashMap<usize, usize, BuildSimpleHasher>,
+    /// The script a code object was compiled from. Line ends are kept so
+    /// source positions can be mapped to lines.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct JitCodeScript {
+        name: String,
+        line_ends: Vec<usize>,
     }
 
-    impl HashMap {
-        pub fn new() -> Self {
-            HashMap {
-                map: std::collections::HashMap::with_hasher(BuildSimpleHasher::default()),
+    impl JitCodeScript {
+        pub fn new(name: &str, source: &str) -> Self {
+            JitCodeScript {
+                name: name.to_string(),
+                line_ends: source.char_indices().filter(|&(_, c)| c == '\n').map(|(i, _)| i).collect(),
             }
         }
 
-        pub fn lookup_or_insert(&mut self, key: *const std::ffi::c_void, hash: u32) -> Entry {
-            let key_usize = key as usize;
-            Entry {
-                value: *self.map.entry(key_usize).or_insert(0),
-            }
+        /// |line_ends| are the offsets of the line terminators of the source.
+        pub fn with_line_ends(name: &str, line_ends: Vec<usize>) -> Self {
+            JitCodeScript { name: name.to_string(), line_ends }
         }
 
-        pub fn remove(&mut self, key: *const std::ffi::c_void, hash: u32) -> *mut std::ffi::c_void {
-            let key_usize = key as usize;
-            if let Some(value) = self.map.remove(&key_usize) {
-                value as *mut std::ffi::c_void
-            } else {
-                std::ptr::null_mut()
-            }
+        pub fn name(&self) -> &str {
+            &self.name
         }
-    }
 
-    pub struct Entry {
-        pub value: usize,
+        /// 1-based line number of source position |pos|.
+        pub fn get_line_number(&self, pos: usize) -> u32 {
+            self.line_ends.partition_point(|&end| end < pos) as u32 + 1
+        }
     }
-}
-
-// Placeholder for base/memory.h
-mod base_memory {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for base/platform/platform.h
-mod platform {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for base/platform/wrappers.h
-mod wrappers {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for base/strings.h
-mod strings {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for base/vector.h
-mod vector {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for execution/frames-inl.h
-mod frames_inl {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for execution/frames.h
-mod frames {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for handles/global-handles.h
-mod global_handles {
-    // Define necessary types and functions here if needed
-}
 
-// Placeholder for init/bootstrapper.h
-mod bootstrapper {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for objects/code-inl.h
-mod code_inl {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for objects/objects.h
-mod objects {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for utils/ostreams.h
-mod ostreams {
-    // Define necessary types and functions here if needed
-}
-
-// Placeholder for zone/zone-chunk-list.h
-mod zone_chunk_list {
-    use std::cell::RefCell;
-    use std::rc::Rc;
-
-    pub struct ZoneChunkList<T> {
-        chunks: Vec<Vec<T>>,
-        current_chunk: Option<Rc<RefCell<Vec<T>>>>,
-        chunk_size: usize,
+    #[derive(Debug)]
+    pub struct JitCodeEvent<'a> {
+        pub type_: JitCodeEventType,
+        pub code_start: usize,
+        pub code_len: usize,
+        pub name: &'a str,
+        /// Set for code compiled from a script; builtins have none.
+        pub script: Option<&'a JitCodeScript>,
+        /// CODE_ADD_LINE_POS_INFO only.
+        pub line_info: Option<LinePosInfo>,
+        /// CODE_MOVED only.
+        pub new_code_start: usize,
+        /// Line information collected between CODE_START_LINE_INFO_RECORDING
+        /// and CODE_END_LINE_INFO_RECORDING. The handler creates it and the
+        /// code generator passes it back with every event of that code.
+        pub user_data: Option<Box<LineInfo>>,
     }
 
-    impl<T> ZoneChunkList<T> {
-        pub fn new() -> Self {
-            ZoneChunkList {
-                chunks: Vec::new(),
-                current_chunk: None,
-                chunk_size: 4096, // Example chunk size
+    impl<'a> JitCodeEvent<'a> {
+        pub fn new(type_: JitCodeEventType, code_start: usize, code_len: usize) -> Self {
+            JitCodeEvent {
+                type_,
+                code_start,
+                code_len,
+                name: "",
+                script: None,
+                line_info: None,
+                new_code_start: 0,
+                user_data: None,
             }
         }
+    }
 
-        pub fn push(&mut self, value: T) {
-            if self.current_chunk.is_none()
-                || self.current_chunk.as_ref().unwrap().borrow().len() == self.chunk_size
-            {
-                self.allocate_new_chunk();
-            }
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PCInfo {
+        pub pc: usize,
+        pub pos: usize,
+        pub is_statement: bool,
+    }
+
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct LineInfo {
+        pc_info: Vec<PCInfo>,
+    }
 
-            self.current_chunk
-                .as_ref()
-                .unwrap()
-                .borrow_mut()
-                .push(value);
+    impl LineInfo {
+        pub fn new() -> Self {
+            LineInfo::default()
         }
 
-        fn allocate_new_chunk(&mut self) {
-            let new_chunk: Vec<T> = Vec::with_capacity(self.chunk_size);
-            let rc = Rc::new(RefCell::new(new_chunk));
-            self.chunks.push(rc.borrow().clone()); // Store a copy in chunks
-            self.current_chunk = Some(rc);
+        pub fn set_position(&mut self, pc: usize, pos: usize, is_statement: bool) {
+            self.pc_info.push(PCInfo { pc, pos, is_statement });
         }
 
-        pub fn find(&self, index: usize) -> Option<&T> {
-            if index < self.chunks.len() {
-                self.chunks.get(index).and_then(|chunk| chunk.get(0))
-            } else {
-                None
-            }
+        pub fn pc_info(&self) -> &[PCInfo] {
+            &self.pc_info
         }
     }
-}
-
-use zone_chunk_list::ZoneChunkList;
 
-// Placeholder for zone/zone.h
-mod zone {
-    // Define necessary types and functions here if needed
-    pub struct Zone {
-        // Placeholder
+    /// Everything the debug object for one code object is built from.
+    #[derive(Debug, Clone)]
+    struct CodeDescription {
+        name: String,
+        code_region: AddressRegion,
+        script: Option<JitCodeScript>,
+        lineinfo: Option<LineInfo>,
     }
 
-    impl Zone {
-        pub fn new() -> Self {
-            Zone {}
+    impl CodeDescription {
+        fn name(&self) -> &str {
+            &self.name
         }
 
-        pub fn new_with_name(_name: &str) -> Self {
-            Zone{}
+        fn code_start(&self) -> usize {
+            self.code_region.begin()
         }
 
-        pub fn new_in_memory(_memory: *mut u8, _size: usize, _name: &str) -> Self {
-            Zone{}
+        fn code_end(&self) -> usize {
+            self.code_region.end()
         }
 
-        pub fn clear(&mut self) {}
-
-        pub fn allocate_aligned(&self, _size: usize, _alignment: usize) -> *mut u8 {
-            unsafe {
-                let layout = std::alloc::Layout::from_size_align(_size, _alignment).unwrap();
-                std::alloc::alloc(layout)
-            }
+        fn code_size(&self) -> usize {
+            self.code_region.size()
         }
 
-        pub fn allocate(&self, size: usize) -> *mut u8 {
-            unsafe {
-                let layout = std::alloc::Layout::new(size);
-                std::alloc::alloc(layout)
-            }
+        fn is_line_info_available(&self) -> bool {
+            self.script.is_some() && self.lineinfo.is_some()
         }
-    }
-
-    pub trait ZoneObject {}
-}
 
-use zone::ZoneObject;
-
-mod gdb_jit {
-    use super::*;
-    use std::alloc::{alloc, dealloc, Layout};
-    use std::ffi::CString;
-    use std::io::Write;
-    use std::mem::MaybeUninit;
-    use std::ptr::NonNull;
-    use std::sync::Mutex;
-
-    const ENABLE_GDB_JIT_INTERFACE: bool = true;
-
-    #[cfg(target_os = "macos")]
-    const __MACH_O: bool = true;
-    #[cfg(not(target_os = "macos"))]
-    const __MACH_O: bool = false;
-
-    #[cfg(target_os = "linux")]
-    const __ELF: bool = true;
-    #[cfg(not(target_os = "linux"))]
-    const __ELF: bool = false;
-
-    trait DebugObject {}
-    trait DebugSection {}
-
-    #[cfg(__MACH_O)]
-    type DebugObjectType = MachO;
-    #[cfg(__MACH_O)]
-    type DebugSectionType = MachOSection;
-
-    #[cfg(__ELF)]
-    type DebugObjectType = ELF;
-    #[cfg(__ELF)]
-    type DebugSectionType = ELFSection;
-
-    struct Writer<'a> {
-        debug_object: &'a mut DebugObjectType,
-        position: usize,
-        capacity: usize,
-        buffer: *mut u8,
-    }
-
-    impl<'a> Writer<'a> {
-        fn new(debug_object: &'a mut DebugObjectType) -> Self {
-            let capacity = 1024;
-            let layout = Layout::array::<u8>(capacity).unwrap();
-            let buffer = unsafe { alloc(layout) };
-            Writer {
-                debug_object,
-                position: 0,
-                capacity,
-                buffer,
+        fn get_filename(&self) -> &str {
+            match &self.script {
+                Some(script) if !script.name().is_empty() => script.name(),
+                _ => "<unknown>",
             }
         }
 
-        fn position(&self) -> usize {
-            self.position
-        }
-
-        unsafe fn raw_slot_at<T>(&self, offset: usize) -> *mut T {
-            assert!(
-                offset < self.capacity && offset + std::mem::size_of::<T>() <= self.capacity
-            );
-            self.buffer.add(offset) as *mut T
+        fn get_script_line_number(&self, pos: usize) -> u32 {
+            self.script.as_ref().map_or(0, |script| script.get_line_number(pos))
         }
+    }
 
-        unsafe fn address_at<T>(&self, offset: usize) -> *mut T {
-            assert!(
-                offset < self.capacity && offset + std::mem::size_of::<T>() <= self.capacity
-            );
-            self.buffer.add(offset) as *mut T
-        }
+    // -------------------------------------------------------------------
+    // ELF image construction.
 
-        fn write<T>(&mut self, val: T) {
-            self.ensure(self.position + std::mem::size_of::<T>());
-            unsafe {
-                let ptr = self.address_at::<T>(self.position);
-                std::ptr::write_unaligned(ptr, val);
-            }
-            self.position += std::mem::size_of::<T>();
-        }
+    #[derive(Debug, Default)]
+    struct Writer {
+        buffer: Vec<u8>,
+    }
 
-        fn slot_at<T>(&mut self, offset: usize) -> Slot<T> {
-            self.ensure(offset + std::mem::size_of::<T>());
-            Slot::new(self, offset)
+    impl Writer {
+        fn position(&self) -> usize {
+            self.buffer.len()
         }
 
-        fn create_slot_here<T>(&mut self) -> Slot<T> {
-            self.create_slots_here::<T>(1)
+        fn write_u8(&mut self, value: u8) {
+            self.buffer.push(value);
         }
 
-        fn create_slots_here<T>(&mut self, count: u32) -> Slot<T> {
-            let slot_position = self.position;
-            self.position += std::mem::size_of::<T>() * count as usize;
-            self.ensure(self.position);
-            self.slot_at::<T>(slot_position)
+        fn write_u16(&mut self, value: u16) {
+            self.buffer.extend_from_slice(&value.to_le_bytes());
         }
 
-        fn ensure(&mut self, pos: usize) {
-            if self.capacity < pos {
-                let mut new_capacity = self.capacity;
-                while new_capacity < pos {
-                    new_capacity *= 2;
-                }
-
-                let old_layout = Layout::array::<u8>(self.capacity).unwrap();
-                let new_layout = Layout::array::<u8>(new_capacity).unwrap();
-
-                unsafe {
-                    self.buffer = std::alloc::realloc(self.buffer, old_layout, new_layout.size());
-                }
-                self.capacity = new_capacity;
-            }
+        fn write_u32(&mut self, value: u32) {
+            self.buffer.extend_from_slice(&value.to_le_bytes());
         }
 
-        fn debug_object(&mut self) -> &mut DebugObjectType {
-            self.debug_object
+        fn write_u64(&mut self, value: u64) {
+            self.buffer.extend_from_slice(&value.to_le_bytes());
         }
 
-        fn buffer(&self) -> *mut u8 {
-            self.buffer
+        fn write_bytes(&mut self, bytes: &[u8]) {
+            self.buffer.extend_from_slice(bytes);
         }
 
-        fn align(&mut self, align: usize) {
-            let delta = self.position % align;
-            if delta == 0 {
-                return;
-            }
-            let padding = align - delta;
-            self.ensure(self.position + padding);
-            self.position += padding;
-            assert_eq!(self.position % align, 0);
+        fn write_string(&mut self, str: &str) {
+            self.buffer.extend_from_slice(str.as_bytes());
+            self.buffer.push(0);
         }
 
-        fn write_uleb128(&mut self, mut value: usize) {
+        fn write_uleb128(&mut self, mut value: u64) {
             loop {
-                let mut byte = (value & 0x7F) as u8;
+                let byte = (value & 0x7f) as u8;
                 value >>= 7;
-                if value != 0 {
-                    byte |= 0x80;
-                }
-                self.write(byte);
                 if value == 0 {
-                    break;
+                    self.write_u8(byte);
+                    return;
                 }
+                self.write_u8(byte | 0x80);
             }
         }
 
-        fn write_sleb128(&mut self, mut value: isize) {
-            let mut more = true;
-            while more {
-                let mut byte = (value & 0x7F) as i8;
-                let byte_sign = (byte & 0x40) != 0;
+        fn write_sleb128(&mut self, mut value: i64) {
+            loop {
+                let byte = (value & 0x7f) as u8;
                 value >>= 7;
-
-                if (value == 0 && !byte_sign) || (value == -1 && byte_sign) {
-                    more = false;
-                } else {
-                    byte |= 0x80;
+                let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+                if done {
+                    self.write_u8(byte);
+                    return;
                 }
-
-                self.write(byte);
+                self.write_u8(byte | 0x80);
             }
         }
 
-        fn write_string(&mut self, str: &str) {
-            for &byte in str.as_bytes() {
-                self.write(byte as char);
+        fn align(&mut self, align: usize) {
+            while !self.buffer.len().is_multiple_of(align) {
+                self.buffer.push(0);
             }
-            self.write('\0');
         }
-    }
 
-    impl<'a> Drop for Writer<'a> {
-        fn drop(&mut self) {
-            let layout = Layout::array::<u8>(self.capacity).unwrap();
-            unsafe {
-                dealloc(self.buffer, layout);
-            }
+        fn patch_u32(&mut self, offset: usize, value: u32) {
+            self.buffer[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
         }
-    }
-
-    struct Slot<'a, T> {
-        w: &'a mut Writer<'a>,
-        offset: usize,
-    }
 
-    impl<'a, T> Slot<'a, T> {
-        fn new(w: &'a mut Writer<'a>, offset: usize) -> Self {
-            Slot { w, offset }
-        }
-
-        unsafe fn raw(&mut self) -> *mut T {
-            self.w.raw_slot_at::<T>(self.offset)
-        }
-
-        fn set(&mut self, value: T) {
-            unsafe {
-                let ptr = self.w.address_at::<T>(self.offset);
-                std::ptr::write_unaligned(ptr, value);
-            }
-        }
-
-        fn at(&mut self, i: usize) -> Slot<'a, T> {
-            Slot::new(self.w, self.offset + std::mem::size_of::<T>() * i)
+        fn patch_u64(&mut self, offset: usize, value: u64) {
+            self.buffer[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
         }
     }
 
-    trait ELFStringTableTrait {
-        fn add(&mut self, str: &str) -> usize;
+    #[derive(Debug, Default)]
+    struct ELFStringTable {
+        data: Vec<u8>,
     }
 
-    #[cfg(__ELF)]
-    impl ELFStringTableTrait for ELFStringTable {
-        fn add(&mut self, str: &str) -> usize {
+    impl ELFStringTable {
+        fn new() -> Self {
+            // Index 0 is the empty string.
+            ELFStringTable { data: vec![0] }
+        }
+
+        fn add(&mut self, str: &str) -> u32 {
             if str.is_empty() {
                 return 0;
             }
-
-            let offset = self.size;
-            self.write_string(str);
+            let offset = self.data.len() as u32;
+            self.data.extend_from_slice(str.as_bytes());
+            self.data.push(0);
             offset
         }
     }
 
-    #[cfg(not(__ELF))]
-    impl ELFStringTableTrait for PlaceholderELFStringTable {
-        fn add(&mut self, str: &str) -> usize {
-            0
-        }
+    mod elf_section_type {
+        pub const TYPE_NULL: u32 = 0;
+        pub const TYPE_PROGBITS: u32 = 1;
+        pub const TYPE_SYMTAB: u32 = 2;
+        pub const TYPE_STRTAB: u32 = 3;
+        pub const TYPE_NOBITS: u32 = 8;
     }
 
-    struct PlaceholderELFStringTable {}
-
-    impl PlaceholderELFStringTable {
-        pub fn new() -> Self {
-            PlaceholderELFStringTable {}
-        }
+    mod elf_section_flags {
+        pub const FLAG_ALLOC: u64 = 1 << 1;
+        pub const FLAG_EXEC: u64 = 1 << 2;
     }
 
-    #[cfg(__MACH_O)]
-    struct MachO {}
-
-    #[cfg(__MACH_O)]
-    impl DebugObject for MachO {}
+    #[derive(Debug)]
+    struct ELFSection {
+        name: &'static str,
+        type_: u32,
+        flags: u64,
+        address: u64,
+        align: u64,
+        link: u32,
+        info: u32,
+        entry_size: u64,
+        body: Vec<u8>,
+        /// For TYPE_NOBITS sections, which have no body in the image.
+        nobits_size: u64,
+    }
 
-    #[cfg(__MACH_O)]
-    impl MachO {
-        fn new(_zone: &Zone) -> Self {
-            MachO {}
-        }
+    impl ELFSection {
+        const INDEX_ABSOLUTE: u16 = 0xfff1;
 
-        fn add_section(&mut self, _section: &mut MachOSection) -> usize {
-            0
+        fn new(name: &'static str, type_: u32, align: u64, body: Vec<u8>) -> Self {
+            ELFSection {
+                name,
+                type_,
+                flags: 0,
+                address: 0,
+                align,
+                link: 0,
+                info: 0,
+                entry_size: 0,
+                body,
+                nobits_size: 0,
+            }
         }
-
-        fn write(&mut self, _w: &mut Writer, _code_start: usize, _code_size: usize) {}
     }
 
-    #[cfg(__ELF)]
+    #[cfg(target_arch = "aarch64")]
+    const ELF_MACHINE: u16 = 183; // EM_AARCH64
+    #[cfg(not(target_arch = "aarch64"))]
+    const ELF_MACHINE: u16 = 62; // EM_X86_64
+
+    /// A relocatable ELF64 object whose sections describe code that already
+    /// lives in memory. The .text section has no contents; its address is
+    /// the address of the code.
+    #[allow(clippy::upper_case_acronyms)]
     struct ELF {
-        sections: ZoneChunkList<ELFSection>,
+        sections: Vec<ELFSection>,
     }
 
-    #[cfg(__ELF)]
-    impl DebugObject for ELF {}
-
-    #[cfg(__ELF)]
     impl ELF {
-        fn new(zone: &Zone) -> Self {
-            let mut elf = ELF {
-                sections: ZoneChunkList::new(),
-            };
-
-            let null_section = ELFSection::new("", ELFSectionType::TYPE_NULL, 0);
-            elf.add_section(null_section);
-
-            let strtab_section = ELFStringTable::new(".shstrtab");
-            elf.add_section(strtab_section);
-
-            elf
-        }
-
-        fn write(&mut self, w: &mut Writer) {
-            self.write_header(w);
-            self.write_section_table(w);
-            self.write_sections(w);
-        }
-
-        fn section_at(&mut self, index: usize) -> Option<&mut ELFSection> {
-            self.sections.find(index).cloned()
+        const K_HEADER_SIZE: usize = 64;
+        const K_SECTION_HEADER_SIZE: usize = 64;
+        const K_SHSTRTAB_INDEX: u16 = 1;
+
+        fn new() -> Self {
+            ELF {
+                sections: vec![
+                    ELFSection::new("", elf_section_type::TYPE_NULL, 0, vec![]),
+                    ELFSection::new(".shstrtab", elf_section_type::TYPE_STRTAB, 1, vec![]),
+                ],
+            }
         }
 
         fn add_section(&mut self, section: ELFSection) -> usize {
-            //let mut section = section;
-            let index = self.sections.chunks.len();
-            //section.index = index as u16;
             self.sections.push(section);
-            index
+            self.sections.len() - 1
         }
 
-        fn write_header(&mut self, w: &mut Writer) {
-            assert_eq!(w.position(), 0);
-            let mut header = w.create_slot_here::<ELFHeader>();
+        fn write(mut self) -> Vec<u8> {
+            let mut shstrtab = ELFStringTable::new();
+            let names: Vec<u32> = self.sections.iter().map(|section| shstrtab.add(section.name)).collect();
+            self.sections[Self::K_SHSTRTAB_INDEX as usize].body = shstrtab.data;
 
-            #[cfg(any(target_arch = "x86", target_arch = "arm"))]
-            let ident: [u8; 16] = [0x7F, b'E', b'L', b'F', 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
-
-            #[cfg(all(target_arch = "x86_64", target_pointer_width = "64"))]
-            let ident: [u8; 16] = [0x7F, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
-
-            #[cfg(all(target_arch = "powerpc64", target_endian = "little"))]
-            let ident: [u8; 16] = [0x7F, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
-
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    ident.as_ptr(),
-                    (*header.raw()).ident.as_mut_ptr(),
-                    16,
-                );
-                (*header.raw()).type_ = 1;
-
-                #[cfg(target_arch = "x86")]
-                {
-                    (*header.raw()).machine = 3;
-                }
-                #[cfg(target_arch = "x86_64")]
-                {
-                    (*header.raw()).machine = 62;
-                }
-                #[cfg(target_arch = "arm")]
-                {
-                    (*header.raw()).machine = 40;
-                }
-                #[cfg(all(target_arch = "powerpc64", target_os = "linux"))]
-                {
-                    (*header.raw()).machine = 21;
-                }
-                #[cfg(target_arch = "s390x")]
-                {
-                    (*header.raw()).machine = 22;
+            let mut w = Writer::default();
+            self.write_header(&mut w);
+            let mut offsets = vec![0u64; self.sections.len()];
+            for (index, section) in self.sections.iter().enumerate().skip(1) {
+                if section.type_ == elf_section_type::TYPE_NOBITS {
+                    continue;
                 }
+                w.align(section.align.max(1) as usize);
+                offsets[index] = w.position() as u64;
+                w.write_bytes(&section.body);
+            }
 
-                (*header.raw()).version = 1;
-                (*header.raw()).entry = 0;
-                (*header.raw()).pht_offset = 0;
-                (*header.raw()).sht_offset = std::mem::size_of::<ELFHeader>() as usize;
-                (*header.raw()).flags = 0;
-                (*header.raw()).header_size = std::mem::size_of::<ELFHeader>() as u16;
-                (*header.raw()).pht_entry_size = 0;
-                (*header.raw()).pht_entry_num = 0;
-                (*header.raw()).sht_entry_size =
-                    std::mem::size_of::<ELFSectionHeader>() as u16;
-                (*header.raw()).sht_entry_num = self.sections.chunks.len() as u16;
-                (*header.raw()).sht_strtab_index = 1;
+            w.align(8);
+            let section_table_offset = w.position() as u64;
+            for (index, section) in self.sections.iter().enumerate() {
+                let size = if section.type_ == elf_section_type::TYPE_NOBITS {
+                    section.nobits_size
+                } else {
+                    section.body.len() as u64
+                };
+                w.write_u32(names[index]);
+                w.write_u32(section.type_);
+                w.write_u64(section.flags);
+                w.write_u64(section.address);
+                w.write_u64(offsets[index]);
+                w.write_u64(size);
+                w.write_u32(section.link);
+                w.write_u32(section.info);
+                w.write_u64(section.align);
+                w.write_u64(section.entry_size);
             }
+            // e_shoff
+            w.patch_u64(40, section_table_offset);
+            w.buffer
+        }
+
+        fn write_header(&self, w: &mut Writer) {
+            const K_IDENT: [u8; 16] = [0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+            w.write_bytes(&K_IDENT);
+            w.write_u16(1); // ET_REL
+            w.write_u16(ELF_MACHINE);
+            w.write_u32(1); // EV_CURRENT
+            w.write_u64(0); // entry
+            w.write_u64(0); // program header offset
+            w.write_u64(0); // section header offset, patched
+            w.write_u32(0); // flags
+            w.write_u16(Self::K_HEADER_SIZE as u16);
+            w.write_u16(0); // program header entry size
+            w.write_u16(0); // program header count
+            w.write_u16(Self::K_SECTION_HEADER_SIZE as u16);
+            w.write_u16(self.sections.len() as u16);
+            w.write_u16(Self::K_SHSTRTAB_INDEX);
+            debug_assert_eq!(w.position(), Self::K_HEADER_SIZE);
         }
+    }
 
-        fn write_section_table(&mut self, w: &mut Writer) {
-            assert_eq!(w.position(), std::mem::size_of::<ELFHeader>());
+    mod elf_symbol {
+        pub const BIND_LOCAL: u8 = 0;
+        pub const BIND_GLOBAL: u8 = 1;
+        pub const TYPE_FUNC: u8 = 2;
+        pub const TYPE_FILE: u8 = 4;
+        pub const K_SIZE: u64 = 24;
+    }
 
-            let mut headers = w.create_slots_here::<ELFSectionHeader>(
-                self.sections.chunks.len() as u32,
-            );
+    fn write_symbol(w: &mut Writer, name: u32, value: u64, size: u64, binding: u8, type_: u8, section: u16) {
+        w.write_u32(name);
+        w.write_u8((binding << 4) | type_);
+        w.write_u8(0);
+        w.write_u16(section);
+        w.write_u64(value);
+        w.write_u64(size);
+    }
 
-            let strtab = self.section_at(1).unwrap();
-            //let strtab = unsafe { &mut *(strtab_ptr as *mut ELFStringTable) };
+    fn create_symbols_table(desc: &CodeDescription, elf: &mut ELF, text_section_index: usize) {
+        let mut strtab = ELFStringTable::new();
+        let mut w = Writer::default();
+        write_symbol(&mut w, 0, 0, 0, 0, 0, 0);
+        write_symbol(
+            &mut w,
+            strtab.add("V8 Code"),
+            0,
+            0,
+            elf_symbol::BIND_LOCAL,
+            elf_symbol::TYPE_FILE,
+            ELFSection::INDEX_ABSOLUTE,
+        );
+        let first_global = 2;
+        write_symbol(
+            &mut w,
+            strtab.add(desc.name()),
+            0,
+            desc.code_size() as u64,
+            elf_symbol::BIND_GLOBAL,
+            elf_symbol::TYPE_FUNC,
+            text_section_index as u16,
+        );
+
+        // Symbol table should be followed by the linked string table.
+        let symtab_index = elf.sections.len();
+        let mut symtab = ELFSection::new(".symtab", elf_section_type::TYPE_SYMTAB, 8, w.buffer);
+        symtab.link = symtab_index as u32 + 1;
+        symtab.info = first_global;
+        symtab.entry_size = elf_symbol::K_SIZE;
+        elf.add_section(symtab);
+        elf.add_section(ELFSection::new(".strtab", elf_section_type::TYPE_STRTAB, 1, strtab.data));
+    }
 
-            // Attach writer to the string table
-            //strtab.attach_writer(w);
-            let mut index = 0;
+    // -------------------------------------------------------------------
+    // DWARF 2 debug information.
+
+    mod dwarf {
+        pub const DW_TAG_COMPILE_UNIT: u64 = 0x11;
+        pub const DW_TAG_SUBPROGRAM: u64 = 0x2e;
+        pub const DW_CHILDREN_NO: u8 = 0;
+        pub const DW_CHILDREN_YES: u8 = 1;
+        pub const DW_AT_NAME: u64 = 0x03;
+        pub const DW_AT_STMT_LIST: u64 = 0x10;
+        pub const DW_AT_LOW_PC: u64 = 0x11;
+        pub const DW_AT_HIGH_PC: u64 = 0x12;
+        pub const DW_FORM_ADDR: u64 = 0x01;
+        pub const DW_FORM_DATA4: u64 = 0x06;
+        pub const DW_FORM_STRING: u64 = 0x08;
+
+        pub const DW_LNS_COPY: u8 = 1;
+        pub const DW_LNS_ADVANCE_PC: u8 = 2;
+        pub const DW_LNS_ADVANCE_LINE: u8 = 3;
+        pub const DW_LNS_NEGATE_STMT: u8 = 6;
+        pub const DW_LNE_END_SEQUENCE: u8 = 1;
+        pub const DW_LNE_SET_ADDRESS: u8 = 2;
+    }
 
-            while index < self.sections.chunks.len() {
-                if let Some(section) = self.sections.find(index) {
-                    //println!("Index: {}", index);
-                    //section.populate_header(&mut headers.at(index), strtab);
-                }
-                index += 1;
-            }
+    const K_COMPILE_UNIT_ABBREV: u64 = 1;
+    const K_SUBPROGRAM_ABBREV: u64 = 2;
+
+    fn create_debug_abbrev_section() -> ELFSection {
+        let mut w = Writer::default();
+        w.write_uleb128(K_COMPILE_UNIT_ABBREV);
+        w.write_uleb128(dwarf::DW_TAG_COMPILE_UNIT);
+        w.write_u8(dwarf::DW_CHILDREN_YES);
+        for (attribute, form) in [
+            (dwarf::DW_AT_NAME, dwarf::DW_FORM_STRING),
+            (dwarf::DW_AT_LOW_PC, dwarf::DW_FORM_ADDR),
+            (dwarf::DW_AT_HIGH_PC, dwarf::DW_FORM_ADDR),
+            (dwarf::DW_AT_STMT_LIST, dwarf::DW_FORM_DATA4),
+        ] {
+            w.write_uleb128(attribute);
+            w.write_uleb128(form);
+        }
+        w.write_uleb128(0);
+        w.write_uleb128(0);
+
+        w.write_uleb128(K_SUBPROGRAM_ABBREV);
+        w.write_uleb128(dwarf::DW_TAG_SUBPROGRAM);
+        w.write_u8(dwarf::DW_CHILDREN_NO);
+        for (attribute, form) in [
+            (dwarf::DW_AT_NAME, dwarf::DW_FORM_STRING),
+            (dwarf::DW_AT_LOW_PC, dwarf::DW_FORM_ADDR),
+            (dwarf::DW_AT_HIGH_PC, dwarf::DW_FORM_ADDR),
+        ] {
+            w.write_uleb128(attribute);
+            w.write_uleb128(form);
+        }
+        w.write_uleb128(0);
+        w.write_uleb128(0);
+        w.write_uleb128(0);
+        ELFSection::new(".debug_abbrev", elf_section_type::TYPE_PROGBITS, 1, w.buffer)
+    }
 
-            //strtab.detach_writer();
-        }
+    fn create_debug_info_section(desc: &CodeDescription) -> ELFSection {
+        let mut w = Writer::default();
+        let unit_length = w.position();
+        w.write_u32(0);
+        w.write_u16(2); // DWARF version.
+        w.write_u32(0); // Abbreviation table offset.
+        w.write_u8(8); // Pointer size.
+
+        w.write_uleb128(K_COMPILE_UNIT_ABBREV);
+        w.write_string(desc.get_filename());
+        w.write_u64(desc.code_start() as u64);
+        w.write_u64(desc.code_end() as u64);
+        w.write_u32(0); // Offset into .debug_line.
+
+        w.write_uleb128(K_SUBPROGRAM_ABBREV);
+        w.write_string(desc.name());
+        w.write_u64(desc.code_start() as u64);
+        w.write_u64(desc.code_end() as u64);
+        w.write_uleb128(0); // End of the compile unit's children.
+
+        let length = (w.position() - unit_length - 4) as u32;
+        w.patch_u32(unit_length, length);
+        ELFSection::new(".debug_info", elf_section_type::TYPE_PROGBITS, 1, w.buffer)
+    }
 
-        fn section_header_position(&self, section_index: u32) -> usize {
-            std::mem::size_of::<ELFHeader>()
-                + std::mem::size_of::<ELFSectionHeader>() * section_index as usize
+    fn create_debug_line_section(desc: &CodeDescription) -> ELFSection {
+        const K_MIN_INSTRUCTION_LENGTH: u8 = 1;
+        const K_LINE_BASE: i8 = -5;
+        const K_LINE_RANGE: u8 = 14;
+        const K_OPCODE_BASE: u8 = 13;
+        const K_STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+        let mut w = Writer::default();
+        let unit_length = w.position();
+        w.write_u32(0);
+        w.write_u16(2); // DWARF version.
+        let header_length = w.position();
+        w.write_u32(0);
+        w.write_u8(K_MIN_INSTRUCTION_LENGTH);
+        w.write_u8(1); // default_is_stmt
+        w.write_u8(K_LINE_BASE as u8);
+        w.write_u8(K_LINE_RANGE);
+        w.write_u8(K_OPCODE_BASE);
+        w.write_bytes(&K_STANDARD_OPCODE_LENGTHS);
+        w.write_u8(0); // No include directories.
+        w.write_string(desc.get_filename());
+        w.write_uleb128(0); // Directory index.
+        w.write_uleb128(0); // Modification time.
+        w.write_uleb128(0); // File length.
+        w.write_u8(0); // End of the file names.
+        let program_start = w.position();
+        w.patch_u32(header_length, (program_start - header_length - 4) as u32);
+
+        w.write_u8(0);
+        w.write_uleb128(9);
+        w.write_u8(dwarf::DW_LNE_SET_ADDRESS);
+        w.write_u64(desc.code_start() as u64);
+
+        // Sort by pc; of several entries at the same pc, keep the statement
+        // position, as that is where a debugger stops.
+        let mut pc_info = desc.lineinfo.as_ref().map_or(vec![], |lineinfo| lineinfo.pc_info().to_vec());
+        pc_info.sort_by_key(|info| (info.pc, info.is_statement));
+        pc_info.dedup_by(|later, earlier| {
+            if later.pc == earlier.pc {
+                *earlier = *later;
+                true
+            } else {
+                false
+            }
+        });
+
+        let mut pc = 0usize;
+        let mut line = 1i64;
+        let mut is_statement = true;
+        let mut has_row = false;
+        for info in pc_info.into_iter().filter(|info| info.pc < desc.code_size()) {
+            let new_line = desc.get_script_line_number(info.pos) as i64;
+            if has_row && new_line == line && info.is_statement == is_statement {
+                continue;
+            }
+            has_row = true;
+            if info.pc != pc {
+                w.write_u8(dwarf::DW_LNS_ADVANCE_PC);
+                w.write_uleb128((info.pc - pc) as u64);
+                pc = info.pc;
+            }
+            if new_line != line {
+                w.write_u8(dwarf::DW_LNS_ADVANCE_LINE);
+                w.write_sleb128(new_line - line);
+                line = new_line;
+            }
+            if info.is_statement != is_statement {
+                w.write_u8(dwarf::DW_LNS_NEGATE_STMT);
+                is_statement = info.is_statement;
+            }
+            w.write_u8(dwarf::DW_LNS_COPY);
         }
 
-        fn write_sections(&mut self, w: &mut Writer) {
-            let headers = w.slot_at::<ELFSectionHeader>(std::mem::size_of::<ELFHeader>());
-            let mut index = 0;
-            while index < self.sections.chunks.len() {
-                //let section = unsafe { &mut *(section_ptr as *mut ELFSection) };
+        w.write_u8(dwarf::DW_LNS_ADVANCE_PC);
+        w.write_uleb128((desc.code_size() - pc) as u64);
+        w.write_u8(0);
+        w.write_uleb128(1);
+        w.write_u8(dwarf::DW_LNE_END_SEQUENCE);
 
-                //section.write_body(&mut headers.at(index), w);
-                index += 1;
-            }
-        }
+        let length = (w.position() - unit_length - 4) as u32;
+        w.patch_u32(unit_length, length);
+        ELFSection::new(".debug_line", elf_section_type::TYPE_PROGBITS, 1, w.buffer)
     }
 
-    #[derive(Debug, Clone, Copy)]
-    #[repr(C)]
-    struct ELFHeader {
-        ident: [u8; 16],
-        type_: u16,
-        machine: u16,
-        version: u32,
-        entry: usize,
-        pht_offset: usize,
-        sht_offset: usize,
-        flags: u32,
-        header_size: u16,
-        pht_entry_size: u16,
-        pht_entry_num: u16,
-        sht_entry_size: u16,
-        sht_entry_num: u16,
-        sht_strtab_index: u16,
+    fn create_elf_object(desc: &CodeDescription) -> Vec<u8> {
+        let mut elf = ELF::new();
+        let mut text = ELFSection::new(".text", elf_section_type::TYPE_NOBITS, 16, vec![]);
+        text.flags = elf_section_flags::FLAG_ALLOC | elf_section_flags::FLAG_EXEC;
+        text.address = desc.code_start() as u64;
+        text.nobits_size = desc.code_size() as u64;
+        let text_section_index = elf.add_section(text);
+
+        create_symbols_table(desc, &mut elf, text_section_index);
+        if desc.is_line_info_available() {
+            elf.add_section(create_debug_info_section(desc));
+            elf.add_section(create_debug_abbrev_section());
+            elf.add_section(create_debug_line_section(desc));
+        }
+        elf.write()
+    }
+
+    // -------------------------------------------------------------------
+    // Binary GDB JIT Interface as described in
+    //   http://sourceware.org/gdb/onlinedocs/gdb/Declarations.html
+
+    #[allow(non_camel_case_types)]
+    #[repr(u32)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum JITAction {
+        JIT_NOACTION = 0,
+        JIT_REGISTER_FN,
+        JIT_UNREGISTER_FN,
     }
 
-    #[derive(Debug, Clone, Copy)]
     #[repr(C)]
-    struct ELFSectionHeader {
-        name: u32,
-        type_: u32,
-        flags: usize,
-        address: usize,
-        offset: usize,
-        size: usize,
-        link: u32,
-        info: u32,
-        alignment: usize,
-        entry_size: usize,
+    #[derive(Debug)]
+    pub struct JITCodeEntry {
+        next_: *mut JITCodeEntry,
+        prev_: *mut JITCodeEntry,
+        symfile_addr_: *const u8,
+        symfile_size_: u64,
     }
 
-    trait DebugSectionBaseTrait {
-        fn write_body(&mut self, header: &mut Slot<ELFSectionHeader>, writer: &mut Writer);
+    #[repr(C)]
+    #[derive(Debug)]
+    pub struct JITDescriptor {
+        version_: u32,
+        action_flag_: u32,
+        relevant_entry_: *mut JITCodeEntry,
+        first_entry_: *mut JITCodeEntry,
     }
 
-    #[derive(Debug, Clone)]
-    struct ELFSection {
-        name: String,
-        type_: ELFSectionType,
-        align: usize,
-        index: u16,
+    /// GDB will place breakpoint into this function. To prevent GCC from
+    /// inlining or removing it we place noinline attribute and inline
+    /// assembler statement inside.
+    #[unsafe(no_mangle)]
+    #[inline(never)]
+    pub extern "C" fn __jit_debug_register_code() {
+        std::hint::black_box(());
     }
 
-    impl ELFSection {
-        fn new(name: &str, type_: ELFSectionType, align: usize) -> Self {
-            ELFSection {
-                name: name.to_string(),
-                type_,
-                align,
-                index: 0,
-            }
-        }
+    /// GDB will inspect contents of this descriptor. Static initialization is
+    /// necessary to prevent GDB from seeing uninitialized descriptor.
+    #[unsafe(no_mangle)]
+    pub static mut __jit_debug_descriptor: JITDescriptor = JITDescriptor {
+        version_: 1,
+        action_flag_: JITAction::JIT_NOACTION as u32,
+        relevant_entry_: ptr::null_mut(),
+        first_entry_: ptr::null_mut(),
+    };
+
+    /// Owns a registered JITCodeEntry and the ELF image it points to.
+    struct RegisteredEntry {
+        entry: *mut JITCodeEntry,
+        symfile: Box<[u8]>,
+    }
 
-        fn populate_header(&mut self, header: &mut Slot<ELFSectionHeader>, strtab: &mut impl ELFStringTableTrait) {
-            unsafe {
-                (*header.raw()).name = strtab.add(&self.name) as u32;
-                (*header.raw()).type_ = self.type_ as u32;
-                (*header.raw()).alignment = self.align;
+    // The entries are only touched while holding the GDB JIT lock.
+    unsafe impl Send for RegisteredEntry {}
+
+    fn register_code_entry(symfile: Vec<u8>) -> RegisteredEntry {
+        let symfile = symfile.into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JITCodeEntry {
+            next_: ptr::null_mut(),
+            prev_: ptr::null_mut(),
+            symfile_addr_: symfile.as_ptr(),
+            symfile_size_: symfile.len() as u64,
+        }));
+        // SAFETY: Called with the GDB JIT lock held, which serializes all
+        // accesses to the descriptor and the entry list.
+        unsafe {
+            let descriptor = ptr::addr_of_mut!(__jit_debug_descriptor);
+            (*entry).next_ = (*descriptor).first_entry_;
+            if !(*entry).next_.is_null() {
+                (*(*entry).next_).prev_ = entry;
             }
-            self.populate_header_internal(header);
+            (*descriptor).first_entry_ = entry;
+            (*descriptor).relevant_entry_ = entry;
+            (*descriptor).action_flag_ = JITAction::JIT_REGISTER_FN as u32;
         }
+        __jit_debug_register_code();
+        RegisteredEntry { entry, symfile }
+    }
 
-        fn populate_header_internal(&mut self, header: &mut Slot<ELFSectionHeader>) {
-            unsafe {
-                (*header.raw()).flags = 0;
-                (*header.raw()).address = 0;
-                (*header.raw()).offset = 0;
-                (*header.raw()).size = 0;
-                (*header.raw()).link = 0;
-                (*header.raw()).info = 0;
-                (*header.raw()).entry_size = 0;
+    fn unregister_code_entry(registered: RegisteredEntry) {
+        let entry = registered.entry;
+        // SAFETY: See register_code_entry. |entry| is linked into the list.
+        unsafe {
+            let descriptor = ptr::addr_of_mut!(__jit_debug_descriptor);
+            if !(*entry).prev_.is_null() {
+                (*(*entry).prev_).next_ = (*entry).next_;
+            } else {
+                (*descriptor).first_entry_ = (*entry).next_;
             }
-        }
-
-        fn write_body(&mut self, header: &mut Slot<ELFSectionHeader>, w: &mut Writer) {
-            let start = w.position();
-            if self.write_body_internal(w) {
-                let end = w.position();
-                unsafe {
-                    (*header.raw()).offset = start;
-                    (*header.raw()).size = end - start;
-                }
+            if !(*entry).next_.is_null() {
+                (*(*entry).next_).prev_ = (*entry).prev_;
             }
-        }
-
-        fn write_body_internal(&mut self, w: &mut Writer) -> bool {
-            false
-        }
+            (*descriptor).relevant_entry_ = entry;
+            (*descriptor).action_flag_ = JITAction::JIT_UNREGISTER_FN as u32;
+        }
+        __jit_debug_register_code();
+        // SAFETY: The entry was allocated by register_code_entry and GDB is
+        // done with it once __jit_debug_register_code returns.
+        drop(unsafe { Box::from_raw(entry) });
+        drop(registered.symfile);
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    #[repr(u32)]
-    enum ELFSectionType {
-        TYPE_NULL = 0,
-        TYPE_PROGBITS = 1,
-        TYPE_SYMTAB = 2,
-        TYPE_STRTAB = 3,
-        TYPE_RELA = 4,
-        TYPE_HASH = 5,
-        TYPE_DYNAMIC = 6,
-        TYPE_NOTE = 7,
-        TYPE_NOBITS = 8,
-        TYPE_REL = 9,
-        TYPE_SHLIB = 10,
-        TYPE_DYNSYM = 11,
-        TYPE_LOPROC = 0x70000000,
-        TYPE_X86_64_UNWIND = 0x70000001,
-        TYPE_HIPROC = 0x7FFFFFFF,
-        TYPE_LOUSER = 0x80000000,
-        TYPE_HIUSER = 0xFFFFFFFF,
+    struct CodeEntry {
+        desc: CodeDescription,
+        registered: RegisteredEntry,
     }
 
-    #[derive(Debug, Clone, Copy)]
-    struct FullHeaderELFSection {
-        name: &'static str,
-        type_: ELFSectionType,
-        align: usize,
-        addr: usize,
-        offset: usize,
-        size: usize,
-        flags: usize,
+    struct GdbJitState {
+        /// Registered code, keyed by start address.
+        code_map: BTreeMap<usize, CodeEntry>,
+        /// Line info recorded for code that has not been added yet.
+        line_info_map: BTreeMap<usize, LineInfo>,
     }
 
-    impl FullHeaderELFSection {
-        fn new(
-            name: &'static str,
-            type_: ELFSectionType,
-            align: usize,
-            addr: usize,
-            offset: usize,
-            size: usize,
-            flags: usize,
-        ) -> Self {
-            FullHeaderELFSection {
-                name,
-                type_,
-                align,
-                addr,
-                offset,
-                size,
-                flags,
+    static GDB_JIT_STATE: Mutex<GdbJitState> =
+        Mutex::new(GdbJitState { code_map: BTreeMap::new(), line_info_map: BTreeMap::new() });
+
+    impl GdbJitState {
+        /// Removes every entry that overlaps |region|; code space that is
+        /// reused has been freed without a CODE_REMOVED event. An empty
+        /// region or entry covers its start address.
+        fn remove_overlapping(&mut self, region: AddressRegion) {
+            let covers = |outer: &AddressRegion, address: usize| outer.begin() <= address && address < outer.end();
+            // Entries do not overlap each other, so their ends increase with
+            // their starts and the scan can stop at the first entry that ends
+            // before |region|.
+            let overlapping: Vec<usize> = self
+                .code_map
+                .range(..=region.end())
+                .rev()
+                .take_while(|&(&start, entry)| entry.desc.code_region.end() > region.begin() || start == region.begin())
+                .filter(|&(&start, entry)| {
+                    start == region.begin()
+                        || entry.desc.code_region.overlaps(&region)
+                        || covers(&entry.desc.code_region, region.begin())
+                        || covers(&region, start)
+                })
+                .map(|(&start, _)| start)
+                .collect();
+            for start in overlapping {
+                let entry = self.code_map.remove(&start).unwrap();
+                unregister_code_entry(entry.registered);
             }
         }
 
-        fn populate_header(
-            &self,
-            header: &mut Slot<ELFSectionHeader>,
-        ) {
-            unsafe {
-                (*header.raw()).address = self.addr;
-                (*header.raw()).offset = self.offset;
-                (*header.raw()).size = self.size;
-                (*header.raw()).flags = self.flags;
-            }
+        fn add_code(&mut self, desc: CodeDescription) {
+            self.remove_overlapping(desc.code_region);
+            let registered = register_code_entry(create_elf_object(&desc));
+            self.code_map.insert(desc.code_start(), CodeEntry { desc, registered });
         }
     }
 
-    struct ELFStringTable {
-        name: String,
-        type_: ELFSectionType,
-        align: usize,
-        writer: *mut Writer<'static>,
-        offset: usize,
-        size: usize,
-        index: u16,
-    }
-
-    impl ELFStringTable {
-        fn new(name: &str) -> Self {
-            ELFStringTable {
-                name: name.to_string(),
-                type_: ELFSectionType::TYPE_STRTAB,
-                align: 1,
-                writer: std::ptr::null_mut(),
-                offset: 0,
-                size: 0,
-                index: 0,
+    /// The v8::JitCodeEventHandler installed by --gdbjit. Every code object
+    /// is registered with GDB as an in-memory ELF object carrying its symbol
+    /// and, for code compiled from a script, DWARF line information.
+    pub fn event_handler(event: &mut JitCodeEvent) {
+        let mut state = GDB_JIT_STATE.lock().unwrap();
+        match event.type_ {
+            JitCodeEventType::CODE_ADDED => {
+                let region = AddressRegion::new(event.code_start, event.code_len);
+                let lineinfo = state.line_info_map.remove(&event.code_start);
+                state.add_code(CodeDescription {
+                    name: event.name.to_string(),
+                    code_region: region,
+                    script: event.script.cloned(),
+                    lineinfo,
+                });
             }
-        }
-
-        fn attach_writer(&mut self, w: &mut Writer<'static>) {
-            self.writer = w;
-            self.offset = w.position();
-
-            // First entry in the string table should be an empty string.
-            self.write_string("");
-        }
-
-        fn detach_writer(&mut self) {
-            self.writer = std::ptr::null_mut();
-        }
-
-        fn write_string(&mut self, str: &str) {
-            let mut written = 0;
-            for &byte in str.as_bytes() {
-                let writer_ptr = self.writer;
-                unsafe {
-                    (*writer_ptr).write(byte as char);
+            JitCodeEventType::CODE_MOVED => {
+                // Re-register the code at its new address.
+                if let Some(entry) = state.code_map.remove(&event.code_start) {
+                    unregister_code_entry(entry.registered);
+                    let mut desc = entry.desc;
+                    desc.code_region = AddressRegion::new(event.new_code_start, desc.code_size());
+                    state.add_code(desc);
                 }
-                written += 1;
             }
-            let writer_ptr = self.writer;
-            unsafe {
-                (*writer_ptr).write('\0');
+            JitCodeEventType::CODE_REMOVED => {
+                if let Some(entry) = state.code_map.remove(&event.code_start) {
+                    unregister_code_entry(entry.registered);
+                }
             }
-            written += 1;
-            self.size += written;
-        }
-
-        fn write_body(&mut self, header: &mut Slot<ELFSectionHeader>, w: &mut Writer) {
-            unsafe {
-                (*header.raw()).offset = self.offset;
-                (*header.raw()).size = self.size;
+            JitCodeEventType::CODE_START_LINE_INFO_RECORDING => {
+                event.user_data = Some(Box::new(LineInfo::new()));
+            }
+            JitCodeEventType::CODE_ADD_LINE_POS_INFO => {
+                let line_info = event.line_info.expect("CODE_ADD_LINE_POS_INFO without line info");
+                event.user_data.as_mut().expect("Line info recording was not started").set_position(
+                    line_info.offset,
+                    line_info.pos,
+                    line_info.position_type == PositionType::STATEMENT_POSITION,
+                );
+            }
+            JitCodeEventType::CODE_END_LINE_INFO_RECORDING => {
+                let line_info = event.user_data.take().expect("Line info recording was not started");
+                state.line_info_map.insert(event.code_start, *line_info);
             }
         }
     }
 
-    #[cfg(__MACH_O)]
-    struct MachOSectionHeader {}
-
-    #[cfg(not(__MACH_O))]
-    struct MachOSectionHeader {}
-
-    #[cfg(__MACH_O)]
-    struct MachOSection {}
-
-    #[cfg(not(__MACH_O))]
-    struct MachOSection {}
-
-    #[derive(Debug, Clone, Copy)]
-    struct CodeDescription {
-        name: &'static str,
-        shared_info: i32, //Tagged<SharedFunctionInfo>, //TODO replace with correct type
-        lineinfo: *mut LineInfo,
-        is_function: bool,
-        code_region: AddressRegion,
+    /// Registers a code region without a name, e.g. the embedded blob.
+    pub fn add_region_for_testing(region: AddressRegion) {
+        let mut state = GDB_JIT_STATE.lock().unwrap();
+        state.add_code(CodeDescription {
+            name: String::new(),
+            code_region: region,
+            script: None,
+            lineinfo: None,
+        });
     }
 
-    impl CodeDescription {
-        fn new(
-            name: &'static str,
-            region: AddressRegion,
-            shared: i32, //Tagged<SharedFunctionInfo>,
-            lineinfo: *mut LineInfo,
-            is_function: bool,
-        ) -> Self {
-            CodeDescription {
-                name,
-                shared_info: shared,
-                lineinfo,
-                is_function,
-                code_region: region,
-            }
+    pub fn clear_code_map_for_testing() {
+        let mut state = GDB_JIT_STATE.lock().unwrap();
+        for (_, entry) in std::mem::take(&mut state.code_map) {
+            unregister_code_entry(entry.registered);
         }
+        state.line_info_map.clear();
+    }
 
-        fn name(&self) -> &str {
-            self.name
-        }
+    pub fn num_overlap_entries_for_testing(region: AddressRegion) -> usize {
+        let state = GDB_JIT_STATE.lock().unwrap();
+        state.code_map.values().filter(|entry| entry.desc.code_region.overlaps(&region)).count()
+    }
 
-        fn lineinfo(&self) -> *mut LineInfo {
-            self.lineinfo
-        }
+    #[cfg(test)]
+    mod tests {
+        use super::*;
 
-        fn is_function(&self) -> bool {
-            self.is_function
+        fn u16_at(data: &[u8], offset: usize) -> u16 {
+            u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
         }
 
-        fn has_scope_info(&self) -> bool {
-            false // TODO replace !shared_info_.is_null()
+        fn u32_at(data: &[u8], offset: usize) -> u32 {
+            u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
         }
 
-        fn code_start(&self) -> usize {
-            self.code_region.begin
+        fn u64_at(data: &[u8], offset: usize) -> u64 {
+            u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
         }
 
-        fn code_end(&self) -> usize {
-            self.code_region.end
+        fn c_string_at(data: &[u8], offset: usize) -> &str {
+            let end = data[offset..].iter().position(|&b| b == 0).unwrap();
+            std::str::from_utf8(&data[offset..offset + end]).unwrap()
         }
 
-        fn code_size(&self) -> usize {
-            self.code_region.size()
+        /// Returns (name, type, address, contents) for every section.
+        fn sections(image: &[u8]) -> Vec<(String, u32, u64, Vec<u8>)> {
+            let section_table = u64_at(image, 40) as usize;
+            let count = u16_at(image, 60) as usize;
+            let header = |index: usize| section_table + index * ELF::K_SECTION_HEADER_SIZE;
+            let shstrtab_offset = u64_at(image, header(u16_at(image, 62) as usize) + 24) as usize;
+            (0..count)
+                .map(|index| {
+                    let h = header(index);
+                    let type_ = u32_at(image, h + 4);
+                    let offset = u64_at(image, h + 24) as usize;
+                    let size = u64_at(image, h + 32) as usize;
+                    let contents = if type_ == elf_section_type::TYPE_NOBITS {
+                        vec![]
+                    } else {
+                        image[offset..offset + size].to_vec()
+                    };
+                    let name = c_string_at(image, shstrtab_offset + u32_at(image, h) as usize).to_string();
+                    (name, type_, u64_at(image, h + 16), contents)
+                })
+                .collect()
         }
 
-        fn is_line_info_available(&self) -> bool {
-            !self.lineinfo.is_null()
+        fn read_uleb128(data: &[u8], position: &mut usize) -> u64 {
+            let (mut result, mut shift) = (0u64, 0);
+            loop {
+                let byte = data[*position];
+                *position += 1;
+                result |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    return result;
+                }
+            }
         }
 
-        fn get_filename(&self) -> String {
-            String::from("test_file.rs")
+        fn read_sleb128(data: &[u8], position: &mut usize) -> i64 {
+            let (mut result, mut shift) = (0i64, 0);
+            loop {
+                let byte = data[*position];
+                *position += 1;
+                result |= ((byte & 0x7f) as i64) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    if byte & 0x40 != 0 && shift < 64 {
+                        result |= -1 << shift;
+                    }
+                    return result;
+                }
+            }
         }
 
-        fn get_script_line_number(&self, pos: i32) -> i32 {
-            0
+        /// Runs the line number program and returns the (address, line,
+        /// is_stmt) rows, including the end of the sequence.
+        fn line_table(debug_line: &[u8]) -> Vec<(u64, i64, bool)> {
+            let header_length = u32_at(debug_line, 6) as usize;
+            let end = 4 + u32_at(debug_line, 0) as usize;
+            let mut position = 10 + header_length;
+            let (mut address, mut line, mut is_stmt) = (0u64, 1i64, true);
+            let mut rows = vec![];
+            while position < end {
+                let opcode = debug_line[position];
+                position += 1;
+                match opcode {
+                    0 => {
+                        let length = read_uleb128(debug_line, &mut position) as usize;
+                        match debug_line[position] {
+                            dwarf::DW_LNE_SET_ADDRESS => address = u64_at(debug_line, position + 1),
+                            dwarf::DW_LNE_END_SEQUENCE => rows.push((address, line, is_stmt)),
+                            other => panic!("Unexpected extended opcode {}", other),
+                        }
+                        position += length;
+                    }
+                    dwarf::DW_LNS_COPY => rows.push((address, line, is_stmt)),
+                    dwarf::DW_LNS_ADVANCE_PC => address += read_uleb128(debug_line, &mut position),
+                    dwarf::DW_LNS_ADVANCE_LINE => line += read_sleb128(debug_line, &mut position),
+                    dwarf::DW_LNS_NEGATE_STMT => is_stmt = !is_stmt,
+                    other => panic!("Unexpected opcode {}", other),
+                }
+            }
+            rows
+        }
+
+        #[test]
+        fn test_elf_object_with_line_info() {
+            let script = JitCodeScript::new("app.js", "function f() {\n  let x = 1;\n  return x;\n}\n");
+            let mut lineinfo = LineInfo::new();
+            lineinfo.set_position(0, 0, true);
+            lineinfo.set_position(8, 17, false);
+            lineinfo.set_position(8, 17, true);
+            lineinfo.set_position(20, 30, true);
+            let desc = CodeDescription {
+                name: "f".to_string(),
+                code_region: AddressRegion::new(0x7f00_0000_1000, 64),
+                script: Some(script),
+                lineinfo: Some(lineinfo),
+            };
+            let image = create_elf_object(&desc);
+            assert_eq!(&image[..4], b"\x7fELF");
+            assert_eq!(u16_at(&image, 18), ELF_MACHINE);
+
+            let sections = sections(&image);
+            let names: Vec<&str> = sections.iter().map(|(name, ..)| name.as_str()).collect();
+            assert_eq!(
+                names,
+                ["", ".shstrtab", ".text", ".symtab", ".strtab", ".debug_info", ".debug_abbrev", ".debug_line"]
+            );
+            let (_, text_type, text_address, _) = &sections[2];
+            assert_eq!((*text_type, *text_address), (elf_section_type::TYPE_NOBITS, 0x7f00_0000_1000));
+
+            // The function symbol refers to .text and has the code size.
+            let (_, _, _, symtab) = &sections[3];
+            let (_, _, _, strtab) = &sections[4];
+            let function = &symtab[2 * elf_symbol::K_SIZE as usize..];
+            assert_eq!(c_string_at(strtab, u32_at(function, 0) as usize), "f");
+            assert_eq!(u16_at(function, 6), 2);
+            assert_eq!(u64_at(function, 16), 64);
+
+            let (_, _, _, debug_info) = &sections[5];
+            assert_eq!(c_string_at(debug_info, 12), "app.js");
+
+            let (_, _, _, debug_line) = &sections[7];
+            assert_eq!(
+                line_table(debug_line),
+                [
+                    (0x7f00_0000_1000, 1, true),
+                    (0x7f00_0000_1008, 2, true),
+                    (0x7f00_0000_1014, 3, true),
+                    (0x7f00_0000_1040, 3, true)
+                ]
+            );
         }
-    }
 
-    #[derive(Default, Debug)]
-    struct LineInfo {
-        pc_info: Vec<PCInfo>,
-    }
+        /// The code map and the GDB descriptor are process-wide.
+        static TEST_LOCK: Mutex<()> = Mutex::new(());
 
-    impl LineInfo {
-        fn set_position(&mut self, pc: isize, pos: i32, is_statement: bool) {
-            self.add_pc_info(PCInfo {
-                pc_: pc,
-                pos_: pos,
-                is_statement_: is_statement,
-            });
-        }
-
-        fn pc_info(&mut self) -> &mut Vec<PCInfo> {
-            &mut self.pc_info
-        }
+        #[test]
+        fn test_code_events_register_with_gdb() {
+            let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let first_entry = || unsafe { (*ptr::addr_of!(__jit_debug_descriptor)).first_entry_ };
+            let entry_count = || {
+                let mut count = 0;
+                let mut entry = first_entry();
+                while !entry.is_null() {
+                    count += 1;
+                    entry = unsafe { (*entry).next_ };
+                }
+                count
+            };
+            clear_code_map_for_testing();
+            assert_eq!(entry_count(), 0);
+
+            // Line info is recorded before the code is added.
+            let script = JitCodeScript::new("app.js", "a;\nb;\n");
+            let mut event = JitCodeEvent::new(JitCodeEventType::CODE_START_LINE_INFO_RECORDING, 0, 0);
+            event_handler(&mut event);
+            let mut user_data = event.user_data.take();
+            for (offset, pos) in [(0, 0), (4, 3)] {
+                let mut event = JitCodeEvent::new(JitCodeEventType::CODE_ADD_LINE_POS_INFO, 0, 0);
+                event.line_info =
+                    Some(LinePosInfo { offset, pos, position_type: PositionType::STATEMENT_POSITION });
+                event.user_data = user_data.take();
+                event_handler(&mut event);
+                user_data = event.user_data.take();
+            }
+            assert_eq!(user_data.as_ref().unwrap().pc_info().len(), 2);
+            let mut event = JitCodeEvent::new(JitCodeEventType::CODE_END_LINE_INFO_RECORDING, 0x1000, 0);
+            event.user_data = user_data;
+            event_handler(&mut event);
+
+            let mut event = JitCodeEvent::new(JitCodeEventType::CODE_ADDED, 0x1000, 0x100);
+            event.name = "LazyCompile:main app.js:1";
+            event.script = Some(&script);
+            event_handler(&mut event);
+            let mut event = JitCodeEvent::new(JitCodeEventType::CODE_ADDED, 0x2000, 0x80);
+            event.name = "Builtin:ArrayPush";
+            event_handler(&mut event);
+            assert_eq!(entry_count(), 2);
+            assert_eq!(unsafe { (*ptr::addr_of!(__jit_debug_descriptor)).action_flag_ }, JITAction::JIT_REGISTER_FN as u32);
+
+            // The most recent entry is first; its symfile is an ELF image.
+            let entry = first_entry();
+            let symfile = unsafe { std::slice::from_raw_parts((*entry).symfile_addr_, (*entry).symfile_size_ as usize) };
+            assert_eq!(sections(symfile).len(), 5);
+            let entry = unsafe { (*entry).next_ };
+            let symfile = unsafe { std::slice::from_raw_parts((*entry).symfile_addr_, (*entry).symfile_size_ as usize) };
+            assert_eq!(sections(symfile).len(), 8);
+
+            // New code in reused space replaces the stale entries.
+            add_region_for_testing(AddressRegion::new(0x1080, 0x1000));
+            assert_eq!(num_overlap_entries_for_testing(AddressRegion::new(0, 0x10000)), 1);
+            assert_eq!(entry_count(), 1);
+
+            let mut event = JitCodeEvent::new(JitCodeEventType::CODE_MOVED, 0x1080, 0x1000);
+            event.new_code_start = 0x8000;
+            event_handler(&mut event);
+            assert_eq!(num_overlap_entries_for_testing(AddressRegion::new(0x8000, 1)), 1);
+            assert_eq!(entry_count(), 1);
+
+            let mut event = JitCodeEvent::new(JitCodeEventType::CODE_REMOVED, 0x8000, 0x1000);
+            event_handler(&mut event);
+            assert_eq!(entry_count(), 0);
+            assert_eq!(unsafe { (*ptr::addr_of!(__jit_debug_descriptor)).action_flag_ }, JITAction::JIT_UNREGISTER_FN as u32);
+        }
+
+        #[test]
+        fn test_empty_regions_replace_entries_at_their_start() {
+            let _lock = TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            clear_code_map_for_testing();
+            let entries = || GDB_JIT_STATE.lock().unwrap().code_map.keys().copied().collect::<Vec<_>>();
+            // Replaced entries must also be unregistered from GDB.
+            let registered_count = || {
+                let mut count = 0;
+                let mut entry = unsafe { (*ptr::addr_of!(__jit_debug_descriptor)).first_entry_ };
+                while !entry.is_null() {
+                    count += 1;
+                    entry = unsafe { (*entry).next_ };
+                }
+                count
+            };
 
-        fn add_pc_info(&mut self, pc_info: PCInfo) {
-            self.pc_info.push(pc_info);
+            add_region_for_testing(AddressRegion::new(0x1000, 0));
+            add_region_for_testing(AddressRegion::new(0x1000, 0));
+            assert_eq!(entries(), [0x1000]);
+            assert_eq!(registered_count(), 1);
+
+            // An entry starting at an empty one replaces it, and vice versa.
+            add_region_for_testing(AddressRegion::new(0x1000, 0x100));
+            assert_eq!(entries(), [0x1000]);
+            assert_eq!(num_overlap_entries_for_testing(AddressRegion::new(0x1000, 0x100)), 1);
+            add_region_for_testing(AddressRegion::new(0x1000, 0));
+            assert_eq!(entries(), [0x1000]);
+            assert_eq!(registered_count(), 1);
+            assert_eq!(num_overlap_entries_for_testing(AddressRegion::new(0x1000, 0x100)), 0);
+
+            // Empty entries inside a new region and regions ending at an
+            // entry's start are handled like non-empty ones.
+            add_region_for_testing(AddressRegion::new(0x1080, 0));
+            add_region_for_testing(AddressRegion::new(0x2000, 0x100));
+            assert_eq!(entries(), [0x1000, 0x1080, 0x2000]);
+            add_region_for_testing(AddressRegion::new(0x1000, 0x1000));
+            assert_eq!(entries(), [0x1000, 0x2000]);
+            add_region_for_testing(AddressRegion::new(0x2080, 0));
+            assert_eq!(entries(), [0x1000, 0x2080]);
+            assert_eq!(registered_count(), 2);
+            clear_code_map_for_testing();
         }
     }
-
-    #[derive(Default, Debug, Clone, Copy)]
-    struct PCInfo {
-        pc_: isize,
-        pos_: i32,
-        is_statement_: bool,
-    }
-
-    // -------------------------------------------------------------------
-    // Binary GDB JIT Interface as described in
-    //   http://sourceware.org/gdb/onlinedocs/gdb/Declarations.html
-
-    #[repr(C)]
-    enum JITAction {
-        JIT_NOACTION = 0,
-        JIT_REGISTER_FN,
-        JIT_UNREGISTER_FN,
-    }
-
-    #[repr(C)]
-    struct JITCodeEntry {
-        next_: *mut JITCodeEntry,
-        prev_: *mut JITCodeEntry,
-        symfile_addr_: *mut u8,
-        symfile_size_: u64,
-    }
-
-    #[repr(C)]
-    struct JITDescriptor {
-        version_: u32,
-        action_flag_: u32,
-        relevant_entry_: *mut JITCodeEntry,
-        first_entry_: *mut JITCodeEntry,
-    }
-
-    // GDB will place breakpoint
\ No newline at end of file
+}
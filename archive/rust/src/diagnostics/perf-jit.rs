@@ -25,493 +25,609 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-// Note: The following code is a partial translation and relies on external definitions
-//       for types like Isolate, AbstractCode, SharedFunctionInfo, Code, Script,
-//       Tagged, MaybeDirectHandle, DirectHandle, CodeKind, TrustedByteArray,
-//       SourcePositionTableIterator, SourcePositionInfo, WasmCode, WasmModuleSourceMap,
-//       WireBytesRef, NativeModule, and functions like GetTimestamp, GetElfMach, etc.
-//       It also requires external flags definitions (v8_flags).
-
-#[cfg(target_os = "linux")]
-pub mod perf_jit {
-    use std::fs::{File, OpenOptions};
-    use std::io::{Write, Seek, SeekFrom};
-    use std::os::unix::io::AsRawFd;
-    use std::sync::Mutex;
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    //use crate::base::platform::wrappers; // Assuming this exists in Rust
-    //use crate::codegen::assembler; // Assuming this exists in Rust
-    //use crate::codegen::source_position_table; // Assuming this exists in Rust
-    //use crate::diagnostics::eh_frame; // Assuming this exists in Rust
-    //use crate::objects::code_kind; // Assuming this exists in Rust
-    //use crate::objects::objects_inl; // Assuming this exists in Rust
-    //use crate::objects::shared_function_info; // Assuming this exists in Rust
-    //use crate::snapshot::embedded::embedded_data; // Assuming this exists in Rust
-    //use crate::utils::ostreams; // Assuming this exists in Rust
-
-    //#[cfg(feature = "v8_enable_webassembly")]
-    //use crate::wasm::wasm_code_manager; // Assuming this exists in Rust
-
-    //use crate::flags::flags;
-
-    use libc::{sysconf, _SC_PAGESIZE, open, unlink, mmap, munmap, MAP_PRIVATE, PROT_READ, PROT_EXEC, MAP_FAILED, O_CREAT, O_TRUNC, O_RDWR};
-    use std::ffi::CString;
-    use std::ptr;
-
-    lazy_static::lazy_static! {
-        static ref FILE_MUTEX: Mutex<()> = Mutex::new(());
-    }
-
-    #[repr(C)]
-    struct PerfJitHeader {
-        magic_: u32,
-        version_: u32,
-        size_: u32,
-        elf_mach_target_: u32,
-        reserved_: u32,
-        process_id_: u32,
-        time_stamp_: u64,
-        flags_: u64,
-    }
-
-    impl PerfJitHeader {
-        const K_MAGIC: u32 = 0x4A695444;
-        const K_VERSION: u32 = 1;
-    }
-
-    #[repr(C)]
-    struct PerfJitBase {
-        event_: u32,
-        size_: u32,
-        time_stamp_: u64,
-    }
-
-    impl PerfJitBase {
-        const K_LOAD: u32 = 0;
-        const K_MOVE: u32 = 1;
-        const K_DEBUG_INFO: u32 = 2;
-        const K_CLOSE: u32 = 3;
-        const K_UNWINDING_INFO: u32 = 4;
-    }
-
-    #[repr(C)]
-    struct PerfJitCodeLoad {
-        base: PerfJitBase,
-        process_id_: u32,
-        thread_id_: u32,
-        vma_: u64,
-        code_address_: u64,
-        code_size_: u64,
-        code_id_: u64,
-    }
-
-    #[repr(C)]
-    struct PerfJitDebugEntry {
-        address_: u64,
-        line_number_: i32,
-        column_: i32,
-    }
-
-    #[repr(C)]
-    struct PerfJitCodeDebugInfo {
-        base: PerfJitBase,
-        address_: u64,
-        entry_count_: u64,
-    }
-
-    #[repr(C)]
-    struct PerfJitCodeUnwindingInfo {
-        base: PerfJitBase,
-        unwinding_size_: u64,
-        eh_frame_hdr_size_: u64,
-        mapped_size_: u64,
-    }
-
-    const K_FILENAME_FORMAT_STRING: &str = "%s/jit-%d.dump";
-    const K_FILENAME_BUFFER_PADDING: usize = 16;
-    const K_STRING_TERMINATOR: [u8; 1] = [0];
-    const K_LOG_BUFFER_SIZE: usize = 65536; // Example value for kLogBufferSize
-
-    static mut PROCESS_ID: i32 = 0;
-    static mut REFERENCE_COUNT: u64 = 0;
-    static mut MARKER_ADDRESS: *mut libc::c_void = ptr::null_mut();
-    static mut CODE_INDEX: u64 = 0;
-    static mut PERF_OUTPUT_HANDLE: Option<File> = None;
-
-    fn open_jit_dump_file() -> Result<(), std::io::Error> {
-        unsafe {
-            // Open the perf JIT dump file.
-            PERF_OUTPUT_HANDLE = None;
-
-            let perf_prof_path = v8_flags::perf_prof_path(); // Placeholder for the actual flag access
-            let filename = format!("{}/jit-{}.dump", perf_prof_path, PROCESS_ID);
+#![cfg(target_os = "linux")]
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::codegen::source_position_table::{SourcePosition, SourcePositionTableIterator};
+use crate::diagnostics::eh_frame::{EhFrameConstants, EhFrameWriter};
+use crate::logging::code_events::{AbstractCode, Address, Script, SharedFunctionInfo};
+use crate::logging::log::CodeEventLogger;
+use crate::objects::code_kind::CodeKind;
+
+/// The flags controlling the perf loggers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerfFlags {
+    /// --perf-basic-prof: write the perf-<pid>.map symbol map.
+    pub perf_basic_prof: bool,
+    /// --perf-basic-prof-path: the directory of the perf-<pid>.map file.
+    pub perf_basic_prof_path: String,
+    /// --perf-basic-prof-only-functions: only log builtins and JS functions.
+    pub perf_basic_prof_only_functions: bool,
+    /// --perf-prof: write debug info records to the jitdump file.
+    pub perf_prof: bool,
+    /// --perf-prof-path: the directory of the jit-<pid>.dump file.
+    pub perf_prof_path: String,
+    /// --perf-prof-delete-file: unlink the jitdump file right after it was
+    /// mapped, so that it disappears when the process exits.
+    pub perf_prof_delete_file: bool,
+    /// --perf-prof-unwinding-info: write unwinding info records.
+    pub perf_prof_unwinding_info: bool,
+}
 
-            let fd = open(
-                CString::new(filename.as_str()).unwrap().as_ptr(),
-                O_CREAT | O_TRUNC | O_RDWR,
-                0o666,
-            );
+impl Default for PerfFlags {
+    fn default() -> Self {
+        PerfFlags {
+            perf_basic_prof: false,
+            perf_basic_prof_path: "/tmp".to_string(),
+            perf_basic_prof_only_functions: false,
+            perf_prof: false,
+            perf_prof_path: ".".to_string(),
+            perf_prof_delete_file: false,
+            perf_prof_unwinding_info: false,
+        }
+    }
+}
 
-            if fd == -1 {
-                return Err(std::io::Error::last_os_error());
-            }
+fn code_kind_is_builtin_or_js_function(kind: CodeKind) -> bool {
+    matches!(
+        kind,
+        CodeKind::BUILTIN
+            | CodeKind::INTERPRETED_FUNCTION
+            | CodeKind::BASELINE
+            | CodeKind::MAGLEV
+            | CodeKind::TURBOFAN_JS
+    )
+}
 
-            // If --perf-prof-delete-file is given, unlink the file right after opening
-            // it. This keeps the file handle to the file valid. This only works on Linux,
-            // which is the only platform supported for --perf-prof anyway.
-            if v8_flags::perf_prof_delete_file() { // Placeholder for the actual flag access
-                if unlink(CString::new(filename.as_str()).unwrap().as_ptr()) != 0 {
-                   return Err(std::io::Error::last_os_error());
-                }
-            }
+/// Returns the instructions of `code`.
+///
+/// # Safety
+///
+/// Code events are delivered while the code object is alive, so its
+/// instructions stay mapped for the duration of the call.
+unsafe fn instructions(code: &AbstractCode) -> &[u8] {
+    // SAFETY: the caller guarantees that the instructions are mapped.
+    unsafe { std::slice::from_raw_parts(code.instruction_start as *const u8, code.instruction_size) }
+}
+
+/// The perf-<pid>.map shared by all isolates of the process.
+struct PerfMapFile {
+    file: BufWriter<File>,
+    reference_count: usize,
+}
+
+static PERF_MAP_FILE: Mutex<Option<PerfMapFile>> = Mutex::new(None);
 
-            MARKER_ADDRESS = open_marker_file(fd)?;
-            PERF_OUTPUT_HANDLE = Some(File::from_raw_fd(fd));
+/// Writes `perf-<pid>.map`, the symbol map perf uses for code without an
+/// object file: one "<start> <size> <name>" line per code object.
+pub struct LinuxPerfBasicLogger {
+    flags: PerfFlags,
+    /// The logged code by start address, to log it again when it moves.
+    code_map: Mutex<HashMap<Address, (String, usize)>>,
+}
 
-            Ok(())
+impl LinuxPerfBasicLogger {
+    const K_FILENAME_FORMAT_STRING: &'static str = "perf-{pid}.map";
+
+    pub fn new(flags: &PerfFlags) -> io::Result<Self> {
+        let mut perf_map_file = PERF_MAP_FILE.lock().unwrap();
+        match perf_map_file.as_mut() {
+            Some(perf_map_file) => perf_map_file.reference_count += 1,
+            None => {
+                let file_name = Self::K_FILENAME_FORMAT_STRING.replace("{pid}", &std::process::id().to_string());
+                let file = File::create(format!("{}/{}", flags.perf_basic_prof_path, file_name))?;
+                *perf_map_file = Some(PerfMapFile { file: BufWriter::new(file), reference_count: 1 });
+            }
         }
+        Ok(LinuxPerfBasicLogger { flags: flags.clone(), code_map: Mutex::new(HashMap::new()) })
+    }
+
+    fn write_log_record_to_file(start: Address, size: usize, name: &str) {
+        let mut perf_map_file = PERF_MAP_FILE.lock().unwrap();
+        let Some(perf_map_file) = perf_map_file.as_mut() else { return };
+        // perf reads the map when the process is gone, so the lines may stay
+        // buffered until then.
+        let _ = writeln!(perf_map_file.file, "{start:x} {size:x} {name}");
     }
+}
 
-    fn close_jit_dump_file() -> Result<(), std::io::Error> {
-        unsafe {
-            if let Some(handle) = PERF_OUTPUT_HANDLE.take() {
-                drop(handle);
+impl Drop for LinuxPerfBasicLogger {
+    fn drop(&mut self) {
+        let mut perf_map_file = PERF_MAP_FILE.lock().unwrap();
+        if let Some(file) = perf_map_file.as_mut() {
+            file.reference_count -= 1;
+            if file.reference_count == 0 {
+                let _ = file.file.flush();
+                *perf_map_file = None;
             }
-            Ok(())
         }
     }
+}
 
-    fn open_marker_file(fd: i32) -> Result<*mut libc::c_void, std::io::Error> {
-        unsafe {
-            let page_size = sysconf(_SC_PAGESIZE);
-            if page_size == -1 {
-                return Err(std::io::Error::last_os_error());
-            }
+impl CodeEventLogger for LinuxPerfBasicLogger {
+    fn log_recorded_buffer(&self, code: &AbstractCode, _shared: Option<&SharedFunctionInfo>, name: &str) {
+        if self.flags.perf_basic_prof_only_functions && !code_kind_is_builtin_or_js_function(code.kind) {
+            return;
+        }
+        if code.instruction_start == 0 {
+            return;
+        }
+        Self::write_log_record_to_file(code.instruction_start, code.instruction_size, name);
+        self.code_map.lock().unwrap().insert(code.instruction_start, (name.to_string(), code.instruction_size));
+    }
 
-            // Mmap the file so that there is a mmap record in the perf_data file.
-            //
-            // The map must be PROT_EXEC to ensure it is not ignored by perf record.
-            let marker_address = mmap(
-                ptr::null_mut(),
-                page_size as usize,
-                PROT_READ | PROT_EXEC,
-                MAP_PRIVATE,
-                fd,
-                0,
-            );
+    fn code_move_event(&self, from: Address, to: Address) {
+        let mut code_map = self.code_map.lock().unwrap();
+        let Some((name, size)) = code_map.remove(&from) else { return };
+        // perf resolves an address with the last line that covers it.
+        Self::write_log_record_to_file(to, size, &name);
+        code_map.insert(to, (name, size));
+    }
 
-            if marker_address == MAP_FAILED {
-                return Err(std::io::Error::last_os_error());
-            }
+    fn code_delete_event(&self, instruction_start: Address) {
+        self.code_map.lock().unwrap().remove(&instruction_start);
+    }
+}
+
+// The jitdump format, see tools/perf/Documentation/jitdump-specification.txt
+// in the Linux sources.
+
+struct PerfJitHeader;
+
+impl PerfJitHeader {
+    const K_MAGIC: u32 = 0x4A695444;
+    const K_VERSION: u32 = 1;
+    const K_SIZE: u32 = 40;
+    const K_RESERVED: u32 = 0xDEADBEEF;
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PerfJitEvent {
+    kLoad = 0,
+    kMove = 1,
+    kDebugInfo = 2,
+    kClose = 3,
+    kUnwindingInfo = 4,
+}
 
-            Ok(marker_address)
+/// The event, size and time stamp that start every record.
+const K_PERF_JIT_BASE_SIZE: usize = 16;
+const K_PERF_JIT_CODE_LOAD_SIZE: usize = K_PERF_JIT_BASE_SIZE + 2 * 4 + 4 * 8;
+const K_PERF_JIT_CODE_MOVE_SIZE: usize = K_PERF_JIT_BASE_SIZE + 2 * 4 + 5 * 8;
+const K_PERF_JIT_CODE_DEBUG_INFO_SIZE: usize = K_PERF_JIT_BASE_SIZE + 2 * 8;
+const K_PERF_JIT_DEBUG_ENTRY_SIZE: usize = 8 + 2 * 4;
+const K_PERF_JIT_CODE_UNWINDING_INFO_SIZE: usize = K_PERF_JIT_BASE_SIZE + 3 * 8;
+
+#[cfg(target_arch = "x86")]
+const K_ELF_MACH_TARGET: u32 = 3; // EM_386
+#[cfg(target_arch = "arm")]
+const K_ELF_MACH_TARGET: u32 = 40; // EM_ARM
+#[cfg(target_arch = "x86_64")]
+const K_ELF_MACH_TARGET: u32 = 62; // EM_X86_64
+#[cfg(target_arch = "aarch64")]
+const K_ELF_MACH_TARGET: u32 = 183; // EM_AARCH64
+#[cfg(not(any(target_arch = "x86", target_arch = "arm", target_arch = "x86_64", target_arch = "aarch64")))]
+const K_ELF_MACH_TARGET: u32 = 0; // EM_NONE
+
+/// perf inject places the code of a load record right after the ELF header
+/// of the object file it generates for it.
+const K_ELF_HEADER_SIZE: u64 = 0x40;
+
+const K_UNKNOWN_SCRIPT_NAME_STRING: &str = "<unknown>";
+
+/// The jit-<pid>.dump shared by all isolates of the process.
+struct JitDumpFile {
+    file: BufWriter<File>,
+    /// The executable mapping of the file that tells perf record where the
+    /// jitdump is.
+    marker_address: *mut libc::c_void,
+    marker_size: usize,
+    code_index: u64,
+    reference_count: usize,
+}
+
+// SAFETY: the marker mapping is only ever unmapped, under the lock.
+unsafe impl Send for JitDumpFile {}
+
+static JIT_DUMP_FILE: Mutex<Option<JitDumpFile>> = Mutex::new(None);
+
+impl JitDumpFile {
+    const K_FILENAME_FORMAT_STRING: &'static str = "jit-{pid}.dump";
+
+    fn open(flags: &PerfFlags) -> io::Result<Self> {
+        let file_name = Self::K_FILENAME_FORMAT_STRING.replace("{pid}", &std::process::id().to_string());
+        let path = format!("{}/{}", flags.perf_prof_path, file_name);
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).mode(0o666).open(&path)?;
+
+        // SAFETY: sysconf has no preconditions.
+        let marker_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        // perf record looks for the mmap event of a file named jit-<pid>.dump
+        // to find the jitdump of the process.
+        // SAFETY: mapping a page of a file we own, unmapped on close.
+        let marker_address = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                marker_size,
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if marker_address == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
         }
+        if flags.perf_prof_delete_file {
+            fs::remove_file(&path)?;
+        }
+
+        let mut jit_dump_file =
+            JitDumpFile { file: BufWriter::new(file), marker_address, marker_size, code_index: 0, reference_count: 1 };
+        jit_dump_file.log_write_header()?;
+        Ok(jit_dump_file)
     }
 
-    fn close_marker_file(marker_address: *mut libc::c_void) -> Result<(), std::io::Error> {
-        unsafe {
-            if marker_address.is_null() {
-                return Ok(());
-            }
+    fn log_write_header(&mut self) -> io::Result<()> {
+        let time_stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_micros() as u64);
+        let mut header = Vec::with_capacity(PerfJitHeader::K_SIZE as usize);
+        header.extend_from_slice(&PerfJitHeader::K_MAGIC.to_ne_bytes());
+        header.extend_from_slice(&PerfJitHeader::K_VERSION.to_ne_bytes());
+        header.extend_from_slice(&PerfJitHeader::K_SIZE.to_ne_bytes());
+        header.extend_from_slice(&K_ELF_MACH_TARGET.to_ne_bytes());
+        header.extend_from_slice(&PerfJitHeader::K_RESERVED.to_ne_bytes());
+        header.extend_from_slice(&std::process::id().to_ne_bytes());
+        header.extend_from_slice(&time_stamp.to_ne_bytes());
+        header.extend_from_slice(&0u64.to_ne_bytes());
+        self.file.write_all(&header)
+    }
 
-            let page_size = sysconf(_SC_PAGESIZE);
-            if page_size == -1 {
-                return Err(std::io::Error::last_os_error());
-            }
+    fn close(mut self) {
+        let _ = self.file.flush();
+        // SAFETY: the marker was mapped in open() and is not used otherwise.
+        unsafe { libc::munmap(self.marker_address, self.marker_size) };
+    }
+}
 
-            if munmap(marker_address, page_size as usize) != 0 {
-                return Err(std::io::Error::last_os_error());
-            }
-            Ok(())
-        }
+/// Builds a jitdump record.
+struct RecordBuilder {
+    bytes: Vec<u8>,
+}
+
+impl RecordBuilder {
+    fn new(event: PerfJitEvent, size: usize) -> Self {
+        let mut bytes = Vec::with_capacity(size);
+        bytes.extend_from_slice(&(event as u32).to_ne_bytes());
+        bytes.extend_from_slice(&(size as u32).to_ne_bytes());
+        bytes.extend_from_slice(&LinuxPerfJitLogger::get_timestamp().to_ne_bytes());
+        RecordBuilder { bytes }
     }
 
-    pub struct LinuxPerfJitLogger {
-        //isolate: *mut Isolate, // Assuming Isolate is a type
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+        self
     }
 
-    impl LinuxPerfJitLogger {
-        pub fn new(/*isolate: *mut Isolate*/) -> Self {
-            let _guard = FILE_MUTEX.lock().unwrap();
-            unsafe {
-                PROCESS_ID = std::process::id() as i32;
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
 
-                REFERENCE_COUNT += 1;
-                // If this is the first logger, open the file and write the header.
-                if REFERENCE_COUNT == 1 {
-                   if let Err(e) = open_jit_dump_file() {
-                       eprintln!("Failed to open jit dump file: {}", e);
-                   } else {
-                       log_write_header();
-                   }
-                }
-            }
-            LinuxPerfJitLogger { /*isolate*/ }
-        }
+    fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+        self
     }
 
-    impl Drop for LinuxPerfJitLogger {
-        fn drop(&mut self) {
-            let _guard = FILE_MUTEX.lock().unwrap();
-            unsafe {
-                REFERENCE_COUNT -= 1;
-                // If this was the last logger, close the file.
-                if REFERENCE_COUNT == 0 {
-                    close_jit_dump_file().unwrap(); // Handle error more gracefully if needed.
-                }
-            }
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(bytes);
+        self
+    }
+
+    fn string(&mut self, string: &str) -> &mut Self {
+        self.bytes(string.as_bytes()).bytes(&[0])
+    }
+
+    fn padding(&mut self, size: usize) -> &mut Self {
+        self.bytes.resize(self.bytes.len() + size, 0);
+        self
+    }
+
+    fn finish(&self) -> &[u8] {
+        debug_assert_eq!(
+            self.bytes.len(),
+            u32::from_ne_bytes(self.bytes[4..8].try_into().unwrap()) as usize
+        );
+        &self.bytes
+    }
+}
+
+/// Writes `jit-<pid>.dump` for `perf inject --jit`, which turns the code load
+/// records into object files with line tables and unwinding info, so that
+/// perf report shows JS function names and line numbers.
+pub struct LinuxPerfJitLogger {
+    flags: PerfFlags,
+    /// The code index and size of the loaded code by start address, for the
+    /// move records of compacting GCs.
+    code_map: Mutex<HashMap<Address, (u64, usize)>>,
+}
+
+impl LinuxPerfJitLogger {
+    pub fn new(flags: &PerfFlags) -> io::Result<Self> {
+        let mut jit_dump_file = JIT_DUMP_FILE.lock().unwrap();
+        match jit_dump_file.as_mut() {
+            Some(jit_dump_file) => jit_dump_file.reference_count += 1,
+            None => *jit_dump_file = Some(JitDumpFile::open(flags)?),
         }
+        Ok(LinuxPerfJitLogger { flags: flags.clone(), code_map: Mutex::new(HashMap::new()) })
     }
 
+    /// The time stamp of the records, which perf expects from the monotonic
+    /// clock when recording with `-k mono`.
     fn get_timestamp() -> u64 {
-        let now = SystemTime::now();
-        let since_the_epoch = now
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-        since_the_epoch.as_nanos() as u64
-    }
-
-    impl LinuxPerfJitLogger {
-        pub fn log_recorded_buffer(
-            &mut self,
-            /*abstract_code: Tagged<AbstractCode>,
-            maybe_sfi: MaybeDirectHandle<SharedFunctionInfo>,
-            name: &str,
-            length: usize,*/
-            abstract_code: u64, // placeholders
-            maybe_sfi: u64,     // placeholders
-            name: &str,
-            length: usize,
-        ) {
-            //DisallowGarbageCollection no_gc; // Placeholder
-            //if v8_flags.perf_basic_prof_only_functions { // Placeholder
-            //    CodeKind code_kind = abstract_code->kind(isolate_); // Placeholder
-            //    if !CodeKindIsJSFunction(code_kind) { // Placeholder
-            //        return;
-            //    }
-            //}
-
-            let _guard = FILE_MUTEX.lock().unwrap();
-
-            unsafe {
-                if PERF_OUTPUT_HANDLE.is_none() {
-                    return;
-                }
-
-                // We only support non-interpreted functions.
-                //if !IsCode(abstract_code, isolate_) { return; } // Placeholder
-                //Tagged<Code> code = Cast<Code>(abstract_code); // Placeholder
-                let code: u64 = abstract_code; // Placeholder
-
-                // Debug info has to be emitted first.
-                //DirectHandle<SharedFunctionInfo> sfi; // Placeholder
-                //if v8_flags.perf_prof && maybe_sfi.ToHandle(&sfi) { // Placeholder
-                    // TODO(herhut): This currently breaks for js2wasm/wasm2js functions.
-                    //CodeKind kind = code->kind(); // Placeholder
-                    //if kind != CodeKind::JS_TO_WASM_FUNCTION && // Placeholder
-                    //    kind != CodeKind::WASM_TO_JS_FUNCTION { // Placeholder
-                    //    DCHECK_IMPLIES(IsScript(sfi->script()), // Placeholder
-                    //                    Cast<Script>(sfi->script())->has_line_ends()); // Placeholder
-                        //self.log_write_debug_info(code, sfi); // Placeholder
-                    //}
-                //}
-
-                let code_name = name;
-                //uint8_t* code_pointer = reinterpret_cast<uint8_t*>(code->instruction_start()); // Placeholder
-                let code_pointer: *const u8 = code as *const u64 as *const u8; // Placeholder
-
-                // Unwinding info comes right after debug info.
-                //if v8_flags.perf_prof_unwinding_info { self.log_write_unwinding_info(code); } // Placeholder
-
-                self.write_jit_code_load_entry(code_pointer, 1024, code_name, length); // Placeholder, use 1024 as a size.
-            }
-        }
+        let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        // SAFETY: ts is a valid timespec.
+        let result = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        debug_assert_eq!(result, 0);
+        const K_NSEC_PER_SEC: u64 = 1_000_000_000;
+        ts.tv_sec as u64 * K_NSEC_PER_SEC + ts.tv_nsec as u64
+    }
 
-        fn write_jit_code_load_entry(
-            &mut self,
-            code_pointer: *const u8,
-            code_size: u32,
-            name: &str,
-            name_length: usize,
-        ) {
-            unsafe {
-                let mut code_load = PerfJitCodeLoad {
-                    base: PerfJitBase {
-                        event_: PerfJitBase::K_LOAD,
-                        size_: 0, // Placeholder, will be calculated later
-                        time_stamp_: get_timestamp(),
-                    },
-                    process_id_: std::process::id() as u32,
-                    thread_id_: std::thread::current().id().as_u64() as u32,
-                    vma_: code_pointer as u64,
-                    code_address_: code_pointer as u64,
-                    code_size_: code_size,
-                    code_id_: CODE_INDEX,
-                };
-
-                CODE_INDEX += 1;
-
-                code_load.base.size_ = (std::mem::size_of::<PerfJitCodeLoad>()
-                    + name_length
-                    + K_STRING_TERMINATOR.len()
-                    + code_size as usize) as u32;
-
-                log_write_bytes(
-                    std::mem::transmute::<*const PerfJitCodeLoad, *const u8>(&code_load as *const PerfJitCodeLoad),
-                    std::mem::size_of::<PerfJitCodeLoad>(),
-                );
-                log_write_bytes(name.as_ptr(), name_length);
-                log_write_bytes(K_STRING_TERMINATOR.as_ptr(), K_STRING_TERMINATOR.len());
-                log_write_bytes(code_pointer, code_size as usize);
-            }
-        }
+    fn thread_id() -> u32 {
+        // SAFETY: gettid has no preconditions.
+        unsafe { libc::syscall(libc::SYS_gettid) as u32 }
     }
 
-    // Placeholder, needs proper WasmCode definition and implementation
-    //#[cfg(feature = "v8_enable_webassembly")]
-    //impl LinuxPerfJitLogger {
-    //    pub fn log_recorded_buffer_wasm(
-    //        &mut self,
-    //        code: &WasmCode,
-    //        name: &str,
-    //        length: usize,
-    //    ) {
-    //        let _guard = FILE_MUTEX.lock().unwrap();
-
-    //        unsafe {
-    //            if PERF_OUTPUT_HANDLE.is_none() {
-    //                return;
-    //            }
-
-    //            if v8_flags::perf_prof_annotate_wasm() {
-    //               //self.log_write_debug_info_wasm(code);
-    //            }
-
-    //            self.write_jit_code_load_entry(
-    //                code.instructions().begin(),
-    //                code.instructions().length(),
-    //                name,
-    //                length,
-    //            );
-    //        }
-    //    }
-    //}
-
-    // Placeholder, needs proper implementation.
-    //impl LinuxPerfJitLogger {
-    //    fn log_write_debug_info(
-    //        &mut self,
-    //        code: Tagged<Code>,
-    //        shared: DirectHandle<SharedFunctionInfo>,
-    //    ) {
-    //        // Placeholder
-    //    }
-
-    //    //#[cfg(feature = "v8_enable_webassembly")]
-    //    //fn log_write_debug_info_wasm(&mut self, code: &WasmCode) {
-    //    //    // Placeholder
-    //    //}
-
-    //    fn log_write_unwinding_info(&mut self, code: Tagged<Code>) {
-    //        // Placeholder
-    //    }
-    //}
-
-    fn log_write_bytes(bytes: *const u8, size: usize) {
-        unsafe {
-            if let Some(ref mut handle) = PERF_OUTPUT_HANDLE {
-                let slice = std::slice::from_raw_parts(bytes, size);
-                handle.write_all(slice).unwrap();
-            }
+    fn log_write_bytes(bytes: &[u8]) {
+        let mut jit_dump_file = JIT_DUMP_FILE.lock().unwrap();
+        if let Some(jit_dump_file) = jit_dump_file.as_mut() {
+            let _ = jit_dump_file.file.write_all(bytes);
         }
     }
 
-    fn log_write_header() {
-        unsafe {
-            if PERF_OUTPUT_HANDLE.is_none() {
-                return;
-            }
+    fn write_jit_code_load_entry(&self, code: &AbstractCode, name: &str) {
+        let mut jit_dump_file = JIT_DUMP_FILE.lock().unwrap();
+        let Some(jit_dump_file) = jit_dump_file.as_mut() else { return };
+        let code_index = jit_dump_file.code_index;
+        jit_dump_file.code_index += 1;
+
+        // SAFETY: see instructions().
+        let code_bytes = unsafe { instructions(code) };
+        let size = K_PERF_JIT_CODE_LOAD_SIZE + name.len() + 1 + code_bytes.len();
+        let mut record = RecordBuilder::new(PerfJitEvent::kLoad, size);
+        record
+            .u32(std::process::id())
+            .u32(Self::thread_id())
+            .u64(code.instruction_start as u64)
+            .u64(code.instruction_start as u64)
+            .u64(code.instruction_size as u64)
+            .u64(code_index)
+            .string(name)
+            .bytes(code_bytes);
+        let _ = jit_dump_file.file.write_all(record.finish());
+
+        self.code_map.lock().unwrap().insert(code.instruction_start, (code_index, code.instruction_size));
+    }
 
-            let header = PerfJitHeader {
-                magic_: PerfJitHeader::K_MAGIC,
-                version_: PerfJitHeader::K_VERSION,
-                size_: std::mem::size_of::<PerfJitHeader>() as u32,
-                elf_mach_target_: v8_flags::get_elf_mach() as u32, // Placeholder
-                reserved_: 0xDEADBEEF,
-                process_id_: std::process::id() as u32,
-                time_stamp_: {
-                   let now = SystemTime::now();
-                   let since_the_epoch = now
-                      .duration_since(UNIX_EPOCH)
-                      .expect("Time went backwards");
-                      (since_the_epoch.as_millis() as f64 * 1000.0) as u64
-                },
-                flags_: 0,
-            };
-
-            log_write_bytes(
-                std::mem::transmute::<*const PerfJitHeader, *const u8>(&header as *const PerfJitHeader),
-                std::mem::size_of::<PerfJitHeader>(),
-            );
+    /// The zero-based line and column of `position`, or -1 if unknown.
+    fn get_source_position_info(script: &Script, position: SourcePosition) -> (i32, i32) {
+        if position.is_external() {
+            return (position.external_line() - 1, -1);
         }
+        script.get_position_info(position.script_offset()).unwrap_or((-1, -1))
     }
 
-    mod v8_flags {
-        pub fn perf_prof_path() -> String {
-            // Placeholder implementation. Replace with actual flag access logic.
-            "/tmp".to_string()
-        }
+    fn log_write_debug_info(&self, code: &AbstractCode, shared: &SharedFunctionInfo) {
+        let (Some(script), Some(table)) = (&shared.script, &code.source_position_table) else { return };
+        let script_name = script.name.as_deref().unwrap_or(K_UNKNOWN_SCRIPT_NAME_STRING);
 
-        pub fn perf_prof_delete_file() -> bool {
-            // Placeholder implementation. Replace with actual flag access logic.
-            false
+        let mut entries = Vec::new();
+        let mut iterator = SourcePositionTableIterator::with_defaults(table);
+        while !iterator.done() {
+            entries.push((iterator.code_offset(), Self::get_source_position_info(script, iterator.source_position())));
+            iterator.advance();
+        }
+        if entries.is_empty() {
+            return;
         }
 
-        pub fn perf_basic_prof_only_functions() -> bool {
-            false
+        let size = K_PERF_JIT_CODE_DEBUG_INFO_SIZE
+            + entries.len() * (K_PERF_JIT_DEBUG_ENTRY_SIZE + script_name.len() + 1);
+        let padding = size.next_multiple_of(8) - size;
+        let mut record = RecordBuilder::new(PerfJitEvent::kDebugInfo, size + padding);
+        record.u64(code.instruction_start as u64).u64(entries.len() as u64);
+        for (code_offset, (line, column)) in entries {
+            // The entry point of the function will be placed straight after
+            // the ELF header when processed by perf inject.
+            record
+                .u64(code.instruction_start as u64 + code_offset as u64 + K_ELF_HEADER_SIZE)
+                .i32(line + 1)
+                .i32(column + 1)
+                .string(script_name);
         }
+        record.padding(padding);
+        Self::log_write_bytes(record.finish());
+    }
 
-        pub fn perf_prof() -> bool {
-            false
+    fn log_write_unwinding_info(&self, code: &AbstractCode) {
+        let (unwinding_size, mapped_size) = match &code.unwinding_info {
+            Some(unwinding_info) => (unwinding_info.len(), unwinding_info.len()),
+            None => (EhFrameConstants::K_EH_FRAME_HDR_SIZE, 0),
+        };
+        let content_size = K_PERF_JIT_CODE_UNWINDING_INFO_SIZE + unwinding_size;
+        let padding = content_size.next_multiple_of(8) - content_size;
+
+        let mut record = RecordBuilder::new(PerfJitEvent::kUnwindingInfo, content_size + padding);
+        record.u64(unwinding_size as u64).u64(EhFrameConstants::K_EH_FRAME_HDR_SIZE as u64).u64(mapped_size as u64);
+        match &code.unwinding_info {
+            Some(unwinding_info) => {
+                record.bytes(unwinding_info);
+            }
+            None => {
+                let mut empty_eh_frame = Vec::new();
+                let _ = EhFrameWriter::write_empty_eh_frame(&mut empty_eh_frame);
+                record.bytes(&empty_eh_frame);
+            }
         }
+        record.padding(padding);
+        Self::log_write_bytes(record.finish());
+    }
+}
 
-        pub fn perf_prof_annotate_wasm() -> bool {
-            false
+impl Drop for LinuxPerfJitLogger {
+    fn drop(&mut self) {
+        let mut jit_dump_file = JIT_DUMP_FILE.lock().unwrap();
+        if let Some(file) = jit_dump_file.as_mut() {
+            file.reference_count -= 1;
+            if file.reference_count == 0 {
+                jit_dump_file.take().unwrap().close();
+            }
         }
+    }
+}
 
-        pub fn perf_prof_unwinding_info() -> bool {
-            false
+impl CodeEventLogger for LinuxPerfJitLogger {
+    fn log_recorded_buffer(&self, code: &AbstractCode, shared: Option<&SharedFunctionInfo>, name: &str) {
+        if self.flags.perf_basic_prof_only_functions && !code_kind_is_builtin_or_js_function(code.kind) {
+            return;
+        }
+        // Bytecode has no instructions; interpreted frames show up as the
+        // per-function copies of the interpreter entry trampoline.
+        if code.instruction_start == 0 {
+            return;
         }
 
-        pub fn get_elf_mach() -> i32 { 0 }
-    }
+        // Debug info has to be emitted first.
+        if let Some(shared) = shared
+            && self.flags.perf_prof
+            && code.kind != CodeKind::JS_TO_WASM_FUNCTION
+            && code.kind != CodeKind::WASM_TO_JS_FUNCTION
+        {
+            self.log_write_debug_info(code, shared);
+        }
 
-    pub fn round_up(num_to_round: i32, multiple: i32) -> i32 {
-      if multiple == 0 {
-          return num_to_round;
-      }
+        // Unwinding info comes right after debug info.
+        if self.flags.perf_prof_unwinding_info {
+            self.log_write_unwinding_info(code);
+        }
 
-      let remainder = num_to_round % multiple;
-      if remainder == 0 {
-          return num_to_round;
-      }
+        self.write_jit_code_load_entry(code, name);
+    }
 
-      return num_to_round + multiple - remainder;
+    fn code_move_event(&self, from: Address, to: Address) {
+        let mut code_map = self.code_map.lock().unwrap();
+        let Some((code_index, size)) = code_map.remove(&from) else { return };
+        let mut record = RecordBuilder::new(PerfJitEvent::kMove, K_PERF_JIT_CODE_MOVE_SIZE);
+        record
+            .u32(std::process::id())
+            .u32(Self::thread_id())
+            .u64(to as u64)
+            .u64(from as u64)
+            .u64(to as u64)
+            .u64(size as u64)
+            .u64(code_index);
+        Self::log_write_bytes(record.finish());
+        code_map.insert(to, (code_index, size));
     }
 
-    const K_ELF_HEADER_SIZE: u64 = 64;
+    fn code_delete_event(&self, instruction_start: Address) {
+        self.code_map.lock().unwrap().remove(&instruction_start);
+    }
 }
 
-use perf_jit::LinuxPerfJitLogger;
-#[cfg(target_os = "linux")]
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::codegen::source_position_table::{RecordingMode, SourcePositionTableBuilder};
+    use crate::logging::code_events::{CodeTag, LogEventListener};
+    use std::sync::Arc;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_ne_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_ne_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
 
     #[test]
-    fn test_logger_creation() {
-        let _logger = LinuxPerfJitLogger::new(/*SomeIsolatePointer*/);
-        // Additional assertions can be added here to verify the file creation and header writing.
-        // Requires mocking or safe access to global static variables.
+    fn writes_jitdump_records() {
+        let directory = std::env::temp_dir().join(format!("perf-jit-test-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let flags = PerfFlags {
+            perf_prof: true,
+            perf_prof_path: directory.to_str().unwrap().to_string(),
+            perf_prof_unwinding_info: true,
+            ..PerfFlags::default()
+        };
+
+        let instructions = [0xccu8; 24];
+        let source = "function f() {\n  return 1;\n}\n";
+        let mut builder = SourcePositionTableBuilder::new(RecordingMode::RECORD_SOURCE_POSITIONS);
+        builder.add_position(0, SourcePosition::new(0, -1), true);
+        builder.add_position(8, SourcePosition::new(source.find("return").unwrap() as i32, -1), true);
+        let code = AbstractCode {
+            source_position_table: Some(builder.to_source_position_table_vector().into()),
+            ..AbstractCode::new(CodeKind::TURBOFAN_JS, instructions.as_ptr() as Address, instructions.len())
+        };
+        let shared = SharedFunctionInfo {
+            address: 0x1000,
+            debug_name: "f".to_string(),
+            script: Some(Arc::new(Script::new(3, Some("a.js".to_string()), source))),
+        };
+
+        let logger = LinuxPerfJitLogger::new(&flags).unwrap();
+        logger.code_create_event_shared(CodeTag::kFunction, &code, &shared, "a.js", 1, 1);
+        LogEventListener::code_move_event(&logger, code.instruction_start, code.instruction_start + 0x100);
+        drop(logger);
+
+        let dump = fs::read(directory.join(format!("jit-{}.dump", std::process::id()))).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(read_u32(&dump, 0), PerfJitHeader::K_MAGIC);
+        assert_eq!(read_u32(&dump, 12), K_ELF_MACH_TARGET);
+
+        let mut records = Vec::new();
+        let mut offset = PerfJitHeader::K_SIZE as usize;
+        while offset < dump.len() {
+            let size = read_u32(&dump, offset + 4) as usize;
+            records.push((read_u32(&dump, offset), &dump[offset..offset + size]));
+            offset += size;
+        }
+        let events: Vec<u32> = records.iter().map(|(event, _)| *event).collect();
+        assert_eq!(
+            events,
+            [PerfJitEvent::kDebugInfo, PerfJitEvent::kUnwindingInfo, PerfJitEvent::kLoad, PerfJitEvent::kMove]
+                .map(|event| event as u32)
+        );
+
+        let debug_info = records[0].1;
+        assert_eq!(debug_info.len() % 8, 0);
+        assert_eq!(read_u64(debug_info, 24), 2);
+        let second_entry = K_PERF_JIT_CODE_DEBUG_INFO_SIZE + K_PERF_JIT_DEBUG_ENTRY_SIZE + "a.js\0".len();
+        assert_eq!(read_u64(debug_info, second_entry), code.instruction_start as u64 + 8 + K_ELF_HEADER_SIZE);
+        assert_eq!(read_u32(debug_info, second_entry + 8), 2);
+        assert_eq!(read_u32(debug_info, second_entry + 12), 3);
+
+        let unwinding_info = records[1].1;
+        assert_eq!(read_u64(unwinding_info, 16), EhFrameConstants::K_EH_FRAME_HDR_SIZE as u64);
+        assert_eq!(read_u64(unwinding_info, 32), 0);
+
+        let load = records[2].1;
+        let name = "JS:*f a.js:1:1\0";
+        assert_eq!(&load[K_PERF_JIT_CODE_LOAD_SIZE..K_PERF_JIT_CODE_LOAD_SIZE + name.len()], name.as_bytes());
+        assert_eq!(&load[K_PERF_JIT_CODE_LOAD_SIZE + name.len()..], &instructions[..]);
+
+        let code_index = read_u64(load, 48);
+        let code_move = records[3].1;
+        assert_eq!(read_u64(code_move, 32), code.instruction_start as u64);
+        assert_eq!(read_u64(code_move, 40), code.instruction_start as u64 + 0x100);
+        assert_eq!(read_u64(code_move, 56), code_index);
     }
-}
\ No newline at end of file
+}
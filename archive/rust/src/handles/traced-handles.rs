@@ -2,838 +2,103 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::atomic::{AtomicPtr, Ordering};
-use std::{alloc, fmt};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-// TODO: Add the necessary Rust crates and imports for V8 internal functionality
-// For example:
-// use v8::...;
-// use crate::heap::*;
-// use crate::objects::*;
-// use crate::base::*;
+use crate::heap::heap_layout::{Address, HeapObject, ObjectSlot, Tagged};
 
-const K_SYSTEM_POINTER_SIZE: usize = 8; // Assuming 64-bit architecture
-
-// Define a macro for static asserts
-macro_rules! static_assert {
-    ($cond:expr) => {
-        const _: [(); 0 - !($cond) as usize] = [];
-    };
-}
-
-// Placeholder types, replace with actual V8 types
-type Address = usize;
-type IndexType = u32; // Choosing u32 as a reasonable default
-
-// Placeholder functions, replace with actual V8 functions
-// fn allocate_at_least<T>(size: usize) -> Result<*mut T, std::alloc::AllocError> {
-//     unsafe {
-//         let layout = std::alloc::Layout::from_size_align(size, std::mem::align_of::<T>()).unwrap();
-//         let ptr = std::alloc::alloc(layout);
-//         if ptr.is_null() {
-//             return Err(std::alloc::AllocError);
-//         }
-//         Ok(ptr as *mut T)
-//     }
-// }
-
-const K_INVALID_FREE_LIST_NODE_INDEX: IndexType = IndexType::MAX;
-const K_TRACED_HANDLE_EAGER_RESET_ZAP_VALUE: Address = 0xDEADC0DE;
-const K_TRACED_HANDLE_FULL_GC_RESET_ZAP_VALUE: Address = 0xDEADC0DE;
-const K_TRACED_HANDLE_MINOR_GC_RESET_ZAP_VALUE: Address = 0xDEADC0DE;
-const K_GLOBAL_HANDLE_ZAP_VALUE: Address = 0xDEADC0DE;
-const K_NULL_ADDRESS: Address = 0;
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum TracedReferenceStoreMode {
-    kAssigningStore,
-}
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum TracedReferenceHandling {
-    kDefault,
-}
-
-// Struct representing a TracedNode
-#[repr(C)]
+/// Storage of a `TracedReference`: a slot on the V8 side that the garbage
+/// collectors update when its object moves. The slot may be read by
+/// concurrent cppgc markers.
+#[repr(align(8))]
 pub struct TracedNode {
-    next_free_index_: IndexType,
-    index_: IndexType,
-    flags_: u8, // Using a byte for flags
-    object_: AtomicPtr<()>, // Atomic pointer to the object, type needs to be adjusted
-}
-
-impl fmt::Debug for TracedNode {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TracedNode")
-            .field("next_free_index_", &self.next_free_index_)
-            .field("index_", &self.index_)
-            .field("flags_", &self.flags_)
-            .field("object_", &self.object_.load(Ordering::Relaxed))
-            .finish()
-    }
+    object: AtomicUsize,
 }
 
 impl TracedNode {
-    const IN_USE_BIT: u8 = 1 << 0;
-    const IN_YOUNG_LIST_BIT: u8 = 1 << 1;
-    const WEAK_BIT: u8 = 1 << 2;
-    const MARKBIT_BIT: u8 = 1 << 3;
-    const HAS_OLD_HOST_BIT: u8 = 1 << 4;
-    const DROPPABLE_BIT: u8 = 1 << 5;
-
-    pub fn new(index: IndexType, next_free_index: IndexType) -> Self {
-        static_assert!(std::mem::size_of::<TracedNode>() <= (2 * K_SYSTEM_POINTER_SIZE));
-
-        let node = TracedNode {
-            next_free_index_: next_free_index,
-            index_: index,
-            flags_: 0,
-            object_: AtomicPtr::new(std::ptr::null_mut()),
-        };
-        assert!(!node.is_in_use());
-        assert!(!node.is_in_young_list());
-        assert!(!node.is_weak());
-        assert!(!node.markbit());
-        assert!(!node.has_old_host());
-        assert!(!node.is_droppable());
-
-        node
-    }
-
-    pub fn release(&mut self, zap_value: Address) {
-        assert!(self.is_in_use());
-        // Clear all flags.
-        self.flags_ = 0;
-        self.clear_markbit();
-        self.set_raw_object(zap_value as *mut ());
-        assert!(self.is_metadata_cleared());
-    }
-
-    pub fn set_next_free(&mut self, next_free: IndexType) {
-        self.next_free_index_ = next_free;
-    }
-
-    pub fn next_free(&self) -> IndexType {
-        self.next_free_index_
-    }
-
-    pub fn index(&self) -> IndexType {
-        self.index_
-    }
-
-    pub fn set_markbit(&mut self) {
-        self.flags_ |= Self::MARKBIT_BIT;
-    }
-
-    pub fn clear_markbit(&mut self) {
-        self.flags_ &= !Self::MARKBIT_BIT;
-    }
-
-    pub fn markbit(&self) -> bool {
-        (self.flags_ & Self::MARKBIT_BIT) != 0
-    }
-
-    pub fn is_in_use(&self) -> bool {
-        (self.flags_ & Self::IN_USE_BIT) != 0
-    }
-
-    pub fn set_is_in_use(&mut self, value: bool) {
-        if value {
-            self.flags_ |= Self::IN_USE_BIT;
-        } else {
-            self.flags_ &= !Self::IN_USE_BIT;
-        }
-    }
-
-    pub fn is_in_young_list(&self) -> bool {
-        (self.flags_ & Self::IN_YOUNG_LIST_BIT) != 0
-    }
-
-    pub fn set_is_in_young_list(&mut self, value: bool) {
-        if value {
-            self.flags_ |= Self::IN_YOUNG_LIST_BIT;
-        } else {
-            self.flags_ &= !Self::IN_YOUNG_LIST_BIT;
-        }
-    }
-
-    pub fn is_weak(&self) -> bool {
-        (self.flags_ & Self::WEAK_BIT) != 0
-    }
-
-    pub fn set_weak(&mut self, value: bool) {
-        if value {
-            self.flags_ |= Self::WEAK_BIT;
-        } else {
-            self.flags_ &= !Self::WEAK_BIT;
-        }
-    }
-
-    pub fn has_old_host(&self) -> bool {
-        (self.flags_ & Self::HAS_OLD_HOST_BIT) != 0
-    }
-
-    pub fn set_has_old_host(&mut self, value: bool) {
-        if value {
-            self.flags_ |= Self::HAS_OLD_HOST_BIT;
-        } else {
-            self.flags_ &= !Self::HAS_OLD_HOST_BIT;
-        }
-    }
-
-    pub fn is_droppable(&self) -> bool {
-        (self.flags_ & Self::DROPPABLE_BIT) != 0
-    }
-
-    pub fn set_droppable(&mut self, value: bool) {
-        if value {
-            self.flags_ |= Self::DROPPABLE_BIT;
-        } else {
-            self.flags_ &= !Self::DROPPABLE_BIT;
-        }
-    }
-
-    pub fn raw_object(&self) -> *mut () {
-        self.object_.load(Ordering::Relaxed)
-    }
-
-    pub fn set_raw_object(&mut self, object: *mut ()) {
-        self.object_.store(object, Ordering::Relaxed);
-    }
-
-    pub fn object(&self) -> Address {
-        self.raw_object() as Address
-    }
-
-    pub fn set_object(&mut self, object: Address) {
-        self.set_raw_object(object as *mut ());
-    }
-
-    pub fn set_raw_object_atomic(&self, object: Address) {
-        self.object_.store(object as *mut (), Ordering::Relaxed);
-    }
-
-    pub fn is_metadata_cleared(&self) -> bool {
-        self.flags_ == 0 && self.raw_object() as Address == K_NULL_ADDRESS
-    }
-
-    pub fn from_location(location: *const Address) -> *mut TracedNode {
-        location as *mut TracedNode
-    }
-}
-
-// Struct representing a TracedNodeBlock
-#[repr(C)]
-pub struct TracedNodeBlock {
-    traced_handles_: *mut TracedHandles,
-    capacity_: IndexType,
-    first_free_node_: IndexType,
-    used_: usize,
-    nodes_: [TracedNode; 0], // Flexible array member, needs special handling
-    reprocessing_: bool,
-    in_usable_list_: bool,
-    in_young_list_: bool,
-    locally_freed_: usize,
-}
-
-impl fmt::Debug for TracedNodeBlock {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TracedNodeBlock")
-            .field("traced_handles_", &self.traced_handles_)
-            .field("capacity_", &self.capacity_)
-            .field("first_free_node_", &self.first_free_node_)
-            .field("used_", &self.used_)
-            //.field("nodes_", &self.nodes_) // Skip the flexible array member
-            .field("reprocessing_", &self.reprocessing_)
-            .field("in_usable_list_", &self.in_usable_list_)
-            .field("in_young_list_", &self.in_young_list_)
-            .field("locally_freed_", &self.locally_freed_)
-            .finish()
-    }
-}
-
-impl TracedNodeBlock {
-    pub const K_MIN_CAPACITY: usize = 8;
-    pub const K_MAX_CAPACITY: usize = 256; // Example value
-
-    // Creates a TracedNodeBlock
-    pub fn create(traced_handles: &mut TracedHandles) -> *mut TracedNodeBlock {
-        static_assert!(std::mem::align_of::<TracedNodeBlock>() >= std::mem::align_of::<TracedNode>());
-        static_assert!(std::mem::size_of::<TracedNodeBlock>() % std::mem::align_of::<TracedNode>() == 0);
-
-        let min_wanted_size = std::mem::size_of::<TracedNodeBlock>() +
-            std::mem::size_of::<TracedNode>() * Self::K_MIN_CAPACITY;
-
-        // Using the global allocator directly as a substitute for v8::base::AllocateAtLeast.
-        // TODO: Replace with V8's allocator once available.
-
-        let layout = std::alloc::Layout::from_size_align(min_wanted_size, std::mem::align_of::<TracedNodeBlock>()).unwrap();
-
-        let raw_result = unsafe {
-            std::alloc::alloc(layout)
-        };
-
-        if raw_result.is_null() {
-            panic!("Allocation failed");
-        }
-
-        let raw_result_size = layout.size();
-
-        let capacity = std::cmp::min(
-            (raw_result_size - std::mem::size_of::<TracedNodeBlock>()) / std::mem::size_of::<TracedNode>(),
-            Self::K_MAX_CAPACITY);
-
-        assert!(capacity < IndexType::MAX as usize);
-
-        let block = unsafe {
-            let block_ptr = raw_result as *mut TracedNodeBlock;
-            //Placement new
-            std::ptr::write(block_ptr, TracedNodeBlock::new(traced_handles, capacity as IndexType));
-            block_ptr
-        };
-
-        block
-    }
-
-    pub fn delete(block: *mut TracedNodeBlock) {
-        unsafe {
-            if !block.is_null() {
-                let capacity = (*block).capacity_ as usize;
-                for i in 0..capacity {
-                    let node_ptr = (*block).at(i as IndexType);
-                    std::ptr::drop_in_place(node_ptr);
-                }
-
-                let layout = std::alloc::Layout::from_size_align(
-                    std::mem::size_of::<TracedNodeBlock>() + std::mem::size_of::<TracedNode>() * capacity,
-                    std::mem::align_of::<TracedNodeBlock>(),
-                ).unwrap();
-                std::alloc::dealloc(block as *mut u8, layout);
-            }
-        }
-    }
-
-    fn new(traced_handles: &mut TracedHandles, capacity: IndexType) -> Self {
-        let mut block = TracedNodeBlock {
-            traced_handles_: traced_handles,
-            capacity_: capacity,
-            first_free_node_: 0,
-            used_: 0,
-            nodes_: [],
-            reprocessing_: false,
-            in_usable_list_: false,
-            in_young_list_: false,
-            locally_freed_: 0,
-        };
-
-        for i in 0..(capacity - 1) {
-            unsafe {
-                let node_ptr = block.at(i as IndexType);
-                std::ptr::write(node_ptr, TracedNode::new(i as IndexType, (i + 1) as IndexType));
-            }
-        }
-
-        unsafe {
-            let node_ptr = block.at((capacity - 1) as IndexType);
-            std::ptr::write(node_ptr, TracedNode::new((capacity - 1) as IndexType, K_INVALID_FREE_LIST_NODE_INDEX));
-        }
-
-        block
-    }
-
-    pub fn from(node: &TracedNode) -> *mut TracedNodeBlock {
-        let first_node = (node as *const TracedNode as usize) - (node.index() as usize * std::mem::size_of::<TracedNode>());
-        let block = first_node - std::mem::size_of::<TracedNodeBlock>();
-        block as *mut TracedNodeBlock
-    }
-
-    pub fn from_const(node: &TracedNode) -> *const TracedNodeBlock {
-        Self::from(node) as *const TracedNodeBlock
-    }
-
-    pub fn free_node(&mut self, node: &mut TracedNode, zap_value: Address) {
-        assert!(node.is_in_use());
-        node.release(zap_value);
-        assert!(!node.is_in_use());
-        node.set_next_free(self.first_free_node_);
-        self.first_free_node_ = node.index();
-        self.used_ -= 1;
-    }
-
-    unsafe fn at(&self, index: IndexType) -> *mut TracedNode {
-        let base = self as *const Self as *const u8;
-        let offset = std::mem::size_of::<TracedNodeBlock>() + (index as usize) * std::mem::size_of::<TracedNode>();
-        base.add(offset) as *mut TracedNode
-    }
-
-    pub fn traced_handles(&mut self) -> &mut TracedHandles {
-        unsafe {
-            &mut *self.traced_handles_
-        }
-    }
-
-    pub fn capacity(&self) -> IndexType {
-        self.capacity_
-    }
-
-    pub fn is_full(&self) -> bool {
-        self.used_ == self.capacity_ as usize
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.used_ == 0
-    }
-
-    pub fn nodes_begin_address(&self) -> Address {
-        unsafe {
-            self.at(0) as Address
-        }
-    }
-
-    pub fn nodes_end_address(&self) -> Address {
-        unsafe {
-            self.at(self.capacity() - 1).add(1) as Address
+    fn new(object: Tagged) -> Self {
+        TracedNode {
+            object: AtomicUsize::new(object.raw()),
         }
     }
 
-    pub fn used(&self) -> usize {
-        self.used_
-    }
-
-    pub fn set_reprocessing(&mut self, value: bool) {
-        self.reprocessing_ = value;
-    }
-
-    pub fn needs_reprocessing(&self) -> bool {
-        self.reprocessing_
-    }
-
-    pub fn in_usable_list(&self) -> bool {
-        self.in_usable_list_
+    pub fn slot(&self) -> ObjectSlot {
+        ObjectSlot::new(&self.object as *const AtomicUsize as Address)
     }
 
-    pub fn set_in_usable_list(&mut self, value: bool) {
-        self.in_usable_list_ = value;
+    /// Returns the referenced object, if any.
+    pub fn object(&self) -> Option<HeapObject> {
+        Tagged::from_raw(self.object.load(Ordering::Acquire)).get_heap_object()
     }
 
-    pub fn in_young_list(&self) -> bool {
-        self.in_young_list_
+    pub fn set_object(&self, object: Option<HeapObject>) {
+        let value = object.map_or(Tagged::ZERO, Tagged::strong);
+        self.object.store(value.raw(), Ordering::Release);
     }
 
-    pub fn set_in_young_list(&mut self, value: bool) {
-        self.in_young_list_ = value;
+    /// Address of the node, as handed to the marker by `TracedReference`s.
+    pub fn address(&self) -> Address {
+        self as *const TracedNode as Address
     }
 
-    pub fn set_locally_freed(&mut self, value: usize) {
-        self.locally_freed_ = value;
-    }
-
-    pub fn consume_locally_freed(&mut self) -> usize {
-        let locally_freed = self.locally_freed_;
-        self.locally_freed_ = 0;
-        locally_freed
-    }
-}
-
-impl<'a> IntoIterator for &'a TracedNodeBlock {
-    type Item = *mut TracedNode;
-    type IntoIter = TracedNodeBlockIterator<'a>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        TracedNodeBlockIterator {
-            block: self,
-            index: 0,
-        }
+    /// Returns the node at `address`.
+    ///
+    /// # Safety
+    ///
+    /// `address` must be the address of a node that was not freed yet, see
+    /// `TracedHandles::free_unused_nodes()`.
+    pub unsafe fn from_address<'a>(address: Address) -> &'a TracedNode {
+        // SAFETY: guaranteed by the caller.
+        unsafe { &*(address as *const TracedNode) }
     }
 }
 
-pub struct TracedNodeBlockIterator<'a> {
-    block: &'a TracedNodeBlock,
-    index: u32,
-}
-
-impl<'a> Iterator for TracedNodeBlockIterator<'a> {
-    type Item = *mut TracedNode;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.block.capacity() {
-            let node_ptr = unsafe { self.block.at(self.index as IndexType) };
-            self.index += 1;
-            Some(node_ptr)
-        } else {
-            None
-        }
-    }
-}
-
-// Helper function to set slot thread-safely
-fn set_slot_thread_safe(slot: &mut AtomicPtr<()>, val: *mut ()) {
-    slot.store(val, Ordering::Relaxed);
-}
-
-// Struct representing TracedHandles
+/// The nodes of all `TracedReference`s into a V8 heap.
+///
+/// While a `CppHeap` is attached, traced nodes are only kept alive by
+/// cppgc objects tracing their references; otherwise they are strong
+/// roots. Either way the collectors update them when objects move.
+///
+/// Nodes whose references were dropped are freed after full garbage
+/// collections only, so that node addresses handed to the marker stay
+/// valid for the whole marking cycle.
+#[derive(Default)]
 pub struct TracedHandles {
-    isolate_: *mut (), // Isolate pointer, type needs to be adjusted
-    blocks_: Vec<*mut TracedNodeBlock>,
-    usable_blocks_: Vec<*mut TracedNodeBlock>,
-    young_blocks_: Vec<*mut TracedNodeBlock>,
-    empty_blocks_: Vec<*mut TracedNodeBlock>,
-    num_blocks_: usize,
-    num_young_blocks_: usize,
-    used_nodes_: usize,
-    block_size_bytes_: usize,
-    is_marking_: bool,
-    is_sweeping_on_mutator_thread_: bool,
-    disable_block_handling_on_free_: bool,
+    nodes: Mutex<Vec<Arc<TracedNode>>>,
 }
 
 impl TracedHandles {
-    pub fn new(isolate: *mut ()) -> Self {
-        TracedHandles {
-            isolate_: isolate,
-            blocks_: Vec::new(),
-            usable_blocks_: Vec::new(),
-            young_blocks_: Vec::new(),
-            empty_blocks_: Vec::new(),
-            num_blocks_: 0,
-            num_young_blocks_: 0,
-            used_nodes_: 0,
-            block_size_bytes_: 0,
-            is_marking_: false,
-            is_sweeping_on_mutator_thread_: false,
-            disable_block_handling_on_free_: false,
-        }
-    }
-
-    pub fn refill_usable_node_blocks(&mut self) {
-        let mut block: *mut TracedNodeBlock;
-        if self.empty_blocks_.is_empty() {
-            block = TracedNodeBlock::create(self);
-            self.block_size_bytes_ += unsafe {(*block).size_bytes()};
-        } else {
-            block = self.empty_blocks_.pop().unwrap();
-        }
-        self.usable_blocks_.push(block);
-        self.blocks_.push(block);
-        self.num_blocks_ += 1;
-
-        assert!(!unsafe { (*block).in_young_list() });
-        assert!(unsafe { (*block).is_empty() });
-        assert_eq!(self.usable_blocks_.last().cloned().unwrap(), block);
-        assert!(!self.usable_blocks_.is_empty());
-    }
-
-    pub fn free_node(&mut self, node: &mut TracedNode, zap_value: Address) {
-        let block_ptr = TracedNodeBlock::from(node);
-        let block = unsafe {&mut *block_ptr};
-
-        if self.disable_block_handling_on_free_ {
-            block.free_node(node, zap_value);
-            return;
-        }
-
-        if block.is_full() {
-            assert!(!self.usable_blocks_.contains(&block_ptr));
-            self.usable_blocks_.push(block_ptr);
-        }
-
-        block.free_node(node, zap_value);
-
-        if block.is_empty() {
-            self.usable_blocks_.retain(|&b| b != block_ptr);
-            self.blocks_.retain(|&b| b != block_ptr);
-
-            if block.in_young_list() {
-                self.young_blocks_.retain(|&b| b != block_ptr);
-                assert!(!block.in_young_list());
-                self.num_young_blocks_ -= 1;
-            }
-
-            self.num_blocks_ -= 1;
-            self.empty_blocks_.push(block_ptr);
-        }
-
-        self.used_nodes_ -= 1;
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn destroy(&mut self, node_block: &mut TracedNodeBlock, node: &mut TracedNode) {
-        // TODO: Add the correct implementations and conditions based on marking and sweeping phases
-        // DCHECK_IMPLIES(is_marking_, !is_sweeping_on_mutator_thread_);
-        // DCHECK_IMPLIES(is_sweeping_on_mutator_thread_, !is_marking_);
-
-        if self.is_sweeping_on_mutator_thread_ {
-            return;
-        }
-
-        if self.is_marking_ {
-            node.set_raw_object_atomic(K_NULL_ADDRESS);
-            return;
-        }
-
-        self.free_node(node, K_TRACED_HANDLE_EAGER_RESET_ZAP_VALUE);
-    }
-
-    //Placeholder
-    pub fn create(object: Address, location: *mut Address, mode: TracedReferenceStoreMode, handling: TracedReferenceHandling) -> FullObjectSlot {
-        FullObjectSlot {}
-    }
-
-    pub fn copy(&mut self, from_node: &TracedNode, to: &mut *mut Address) {
-        assert_ne!(K_GLOBAL_HANDLE_ZAP_VALUE, from_node.raw_object());
-        let o = self.create(from_node.raw_object(), to as *mut Address, TracedReferenceStoreMode::kAssigningStore, TracedReferenceHandling::kDefault);
-        set_slot_thread_safe(to as *mut *mut () as *mut AtomicPtr<()>, o.location() as *mut ());
-        // TODO: Add VERIFY_HEAP functionality
-        // #ifdef VERIFY_HEAP
-        //   if (v8_flags.verify_heap) {
-        //     Object::ObjectVerify(Tagged<Object>(**to), isolate_);
-        //   }
-        // #endif  // VERIFY_HEAP
-    }
-
-    pub fn move_node(&mut self, from_node: &mut TracedNode, from: &mut *mut Address, to: &mut *mut Address) {
-        assert!(from_node.is_in_use());
-        let to_node_ptr = TracedNode::from_location(*to);
-        let to_node = unsafe {&mut *to_node_ptr};
-
-        // Deal with old "to".
-        assert!(*to == 0 || to_node.is_in_use());
-        assert!(*to == 0 || K_GLOBAL_HANDLE_ZAP_VALUE != to_node.raw_object());
-        assert_ne!(K_GLOBAL_HANDLE_ZAP_VALUE, from_node.raw_object());
-        if *to != 0 {
-            let to_node_block_ptr = TracedNodeBlock::from(to_node);
-            let to_node_block = unsafe {&mut *to_node_block_ptr};
-
-            self.destroy(to_node_block, to_node);
-        }
-
-        // Set "to" to "from".
-        set_slot_thread_safe(to as *mut *mut () as *mut AtomicPtr<()>, *from as *mut ());
-        let to_node_ptr = TracedNode::from_location(*to);
-        let to_node = unsafe {&mut *to_node_ptr};
-
-        // Deal with new "to"
-        assert!(*to != 0);
-        assert_eq!(*from, *to);
-        if self.is_marking_ {
-            // Write barrier needs to cover node as well as object.
-            to_node.set_markbit();
-            // WriteBarrier::MarkingFromTracedHandle(to_node->object());
-            todo!();
-        } else {
-            // TODO: Add CppHeap logic
-            // if (auto* cpp_heap = GetCppHeapIfUnifiedYoungGC(isolate_)) {
-            //     const bool object_is_young_and_not_yet_recorded =
-            //         !from_node.has_old_host() &&
-            //         HeapLayout::InYoungGeneration(from_node.object());
-            //     if (object_is_young_and_not_yet_recorded &&
-            //         IsCppGCHostOld(*cpp_heap, reinterpret_cast<Address>(to))) {
-            //       DCHECK(from_node.is_in_young_list());
-            //       from_node.set_has_old_host(true);
-            //     }
-            // }
-        }
-        set_slot_thread_safe(from as *mut *mut () as *mut AtomicPtr<()>, std::ptr::null_mut());
-    }
-
-    pub fn set_is_marking(&mut self, value: bool) {
-        assert_eq!(self.is_marking_, !value);
-        self.is_marking_ = value;
-    }
-
-    pub fn set_is_sweeping_on_mutator_thread(&mut self, value: bool) {
-        assert_eq!(self.is_sweeping_on_mutator_thread_, !value);
-        self.is_sweeping_on_mutator_thread_ = value;
-    }
-
-    pub type NodeBounds = Vec< (Address, Address) >;
-
-    pub fn get_node_bounds(&self) -> Self::NodeBounds {
-        let mut block_bounds: Self::NodeBounds = Vec::with_capacity(self.num_blocks_);
-        for &block in &self.blocks_ {
-            let block_ref = unsafe { &*block };
-            block_bounds.push((block_ref.nodes_begin_address(), block_ref.nodes_end_address()));
-        }
-
-        block_bounds.sort_by(|a, b| a.0.cmp(&b.0));
-        block_bounds
-    }
-
-    pub fn update_list_of_young_nodes(&mut self) {
-        // TODO: implement the update list of young nodes
-        todo!();
-    }
-
-    pub fn delete_empty_blocks(&mut self) {
-        if self.empty_blocks_.len() <= 1 {
-            return;
-        }
-
-        for i in 1..self.empty_blocks_.len() {
-            let block = self.empty_blocks_[i];
-            let block_ref = unsafe {&*block};
-            assert!(block_ref.is_empty());
-            assert!(self.block_size_bytes_ >= block_ref.size_bytes());
-            self.block_size_bytes_ -= block_ref.size_bytes();
-            TracedNodeBlock::delete(block);
-        }
-        self.empty_blocks_.drain(1..);
+    /// Creates a node referring to `object`.
+    pub fn create(&self, object: Option<HeapObject>) -> Arc<TracedNode> {
+        let node = Arc::new(TracedNode::new(object.map_or(Tagged::ZERO, Tagged::strong)));
+        self.nodes.lock().unwrap().push(node.clone());
+        node
     }
 
-    //Placeholder
-    pub fn reset_dead_nodes(&mut self, should_reset_handle: WeakSlotCallbackWithHeap) {
-        // Manual iteration as the block may be deleted in `FreeNode()`.
-        let mut it = 0;
-        while it < self.blocks_.len() {
-            let block_ptr = self.blocks_[it];
-            let block = unsafe {&mut *block_ptr};
-
-            for node_ptr in block {
-                let node = unsafe { &mut *node_ptr };
-
-                if !node.is_in_use() {
-                    continue;
-                }
-
-                // Detect unreachable nodes first.
-                if !node.markbit() {
-                    self.free_node(node, K_TRACED_HANDLE_FULL_GC_RESET_ZAP_VALUE);
-                    continue;
-                }
-
-                // Node was reachable. Clear the markbit for the next GC.
-                node.clear_markbit();
-                // TODO(v8:13141): Turn into a DCHECK after some time.
-                //CHECK(!should_reset_handle(isolate_->heap(), node->location()));
-            }
-
-            if block.in_young_list() {
-                self.young_blocks_.retain(|&b| b != block_ptr);
-                assert!(!block.in_young_list());
-                self.num_young_blocks_ -= 1;
+    /// Invokes `f` on the slot of every node that is still in use.
+    pub fn iterate(&self, mut f: impl FnMut(ObjectSlot)) {
+        for node in self.nodes.lock().unwrap().iter() {
+            if Arc::strong_count(node) > 1 {
+                f(node.slot());
             }
-            it += 1;
         }
-        assert!(self.young_blocks_.is_empty());
-    }
-
-    pub fn reset_young_dead_nodes(&mut self, should_reset_handle: WeakSlotCallbackWithHeap) {
-        // TODO: implement the reset young dead nodes
-        todo!();
-    }
-
-    pub fn supports_clearing_weak_non_live_wrappers(&self) -> bool {
-        // TODO: implement the logic
-        false
     }
 
-    pub fn compute_weakness_for_young_objects(&mut self) {
-        // TODO: implement the compute weakness for young objects
-        todo!();
+    /// Frees the nodes whose references were dropped. Must not be called
+    /// while marking.
+    pub fn free_unused_nodes(&self) {
+        self.nodes
+            .lock()
+            .unwrap()
+            .retain(|node| Arc::strong_count(node) > 1);
     }
 
-    pub fn process_weak_young_objects(&mut self, visitor: *mut (), should_reset_handle: WeakSlotCallbackWithHeap) {
-        // TODO: implement the process weak young objects
-        todo!();
-    }
-
-    pub fn iterate(&mut self, visitor: *mut ()) {
-        // TODO: implement the iterate function
-        todo!();
-    }
-
-    pub fn iterate_young(&mut self, visitor: *mut ()) {
-        // TODO: implement the iterate young
-        todo!();
-    }
-
-    pub fn iterate_young_roots(&mut self, visitor: *mut ()) {
-        // TODO: implement the iterate young roots
-        todo!();
-    }
-
-    pub fn iterate_and_mark_young_roots_with_old_hosts(&mut self, visitor: *mut ()) {
-        // TODO: implement the iterate and mark young roots with old hosts
-        todo!();
-    }
-
-    pub fn iterate_young_roots_with_old_hosts_for_testing(&mut self, visitor: *mut ()) {
-        // TODO: implement the iterate young roots with old hosts for testing
-        todo!();
-    }
-
-    pub fn destroy_address(location: *mut Address) {
-        // TODO: implement the destroy for address
-        todo!();
-    }
-
-    pub fn copy_address(from: *const *const Address, to: &mut *mut Address) {
-        // TODO: implement the copy for address
-        todo!();
-    }
-
-    pub fn move_address(from: &mut *mut Address, to: &mut *mut Address) {
-        // TODO: implement the move for address
-        todo!();
-    }
-
-    pub fn mark(location: *mut Address, mark_mode: MarkMode) -> TaggedObject {
-        // TODO: implement the mark
-        todo!();
-    }
-
-    pub fn mark_conservatively(inner_location: *mut Address, traced_node_block_base: *mut Address, mark_mode: MarkMode) -> TaggedObject {
-        // TODO: implement the mark conservatively
-        todo!();
-    }
-
-    pub fn is_valid_in_use_node(location: *const Address) -> bool {
-        // TODO: implement the is valid in use node
-        todo!();
-    }
-
-    pub fn has_young(&self) -> bool {
-        !self.young_blocks_.is_empty()
-    }
-}
-
-impl Drop for TracedHandles {
-    fn drop(&mut self) {
-        let mut block_size_bytes = 0;
-        while let Some(block) = self.blocks_.pop() {
-            block_size_bytes += unsafe { (*block).size_bytes() };
-            TracedNodeBlock::delete(block);
-        }
-        for block in self.empty_blocks_.drain(..) {
-            block_size_bytes += unsafe { (*block).size_bytes() };
-            TracedNodeBlock::delete(block);
-        }
-        assert_eq!(block_size_bytes, self.block_size_bytes_);
+    /// Returns the number of nodes, including unused ones not freed yet.
+    pub fn used_size(&self) -> usize {
+        self.nodes.lock().unwrap().len()
     }
 }
-
-//Placeholder
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub struct FullObjectSlot {}
-
-impl FullObjectSlot {
-    pub fn location(&self) -> *mut () {
-        std::ptr::null_mut()
-    }
-}
-
-type WeakSlotCallbackWithHeap = fn(heap: *mut (), location: *mut ()) -> bool;
-type TaggedObject = usize;
-
-pub enum MarkMode {
-    kAll,
-    kOnlyYoung,
-}
-
-impl fmt::Debug for TracedHandles {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("TracedHandles")
-            .field("isolate_", &self.isolate_)
-            .field("blocks_", &self.blocks_.len())
-            .field("usable_blocks_", &self.usable_blocks_.len())
-            .field("young_blocks_", &self.young
\ No newline at end of file
@@ -5,24 +5,49 @@
 // src/heap/allocation-observer.h (converted to module definition)
 pub mod allocation_observer {
     use std::cmp;
-    use std::collections::HashSet;
-    use std::ops::Deref;
+    use std::fmt;
 
-    // Re-export the types used in the public API
-    pub use super::heap::Heap;
+    use crate::heap::heap::Heap;
 
-    #[derive(Debug)]
     struct AllocationObserverCounter<'a> {
         observer_: &'a dyn AllocationObserver,
         prev_counter_: usize,
         next_counter_: usize,
     }
 
-    trait AllocationObserver {
+    impl<'a> fmt::Debug for AllocationObserverCounter<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("AllocationObserverCounter")
+                .field("observer_", &observer_key(self.observer_))
+                .field("prev_counter_", &self.prev_counter_)
+                .field("next_counter_", &self.next_counter_)
+                .finish()
+        }
+    }
+
+    /// Observer for allocations that is aware of LAB-based allocation.
+    ///
+    /// Observers are notified every time roughly |get_next_step_size()| bytes
+    /// have been allocated. Observers receive shared references, so stateful
+    /// observers keep their state in cells.
+    pub trait AllocationObserver {
+        /// Called each time the observed space does an allocation step. The
+        /// object at |soon_object| (of |object_size| bytes) is about to be
+        /// initialized; it may be 0 if the step was triggered without an
+        /// allocation. |current_counter| is the number of bytes allocated since
+        /// the previous step of this observer.
         fn step(&self, current_counter: i32, soon_object: usize, object_size: usize);
+
+        /// Subclasses can override this method to make step size dynamic.
         fn get_next_step_size(&self) -> i64;
     }
 
+    /// Identity of an observer; trait object pointers are compared by their
+    /// data address only.
+    fn observer_key(observer: &dyn AllocationObserver) -> *const () {
+        observer as *const dyn AllocationObserver as *const ()
+    }
+
     #[derive(Debug)]
     pub struct AllocationCounter<'a> {
         observers_: Vec<AllocationObserverCounter<'a>>,
@@ -30,7 +55,7 @@ pub mod allocation_observer {
         next_counter_: usize,
         step_in_progress_: bool,
         pending_added_: Vec<AllocationObserverCounter<'a>>,
-        pending_removed_: HashSet<&'a dyn AllocationObserver>,
+        pending_removed_: Vec<*const ()>,
     }
 
     impl<'a> AllocationCounter<'a> {
@@ -41,14 +66,27 @@ pub mod allocation_observer {
                 next_counter_: 0,
                 step_in_progress_: false,
                 pending_added_: Vec::new(),
-                pending_removed_: HashSet::new(),
+                pending_removed_: Vec::new(),
+            }
+        }
+
+        /// True if at least one observer is registered.
+        pub fn is_active(&self) -> bool {
+            !self.observers_.is_empty()
+        }
+
+        /// Number of bytes left until the next observer step.
+        pub fn next_bytes(&self) -> usize {
+            if self.observers_.is_empty() {
+                return usize::MAX;
             }
+            self.next_counter_ - self.current_counter_
         }
 
         pub fn add_allocation_observer(&mut self, observer: &'a dyn AllocationObserver) {
             #[cfg(debug_assertions)]
             {
-                assert!(self.observers_.iter().find(|aoc| aoc.observer_ as *const _ == observer as *const _).is_none());
+                assert!(self.observers_.iter().all(|aoc| observer_key(aoc.observer_) != observer_key(observer)));
             }
 
             if self.step_in_progress_ {
@@ -79,13 +117,13 @@ pub mod allocation_observer {
         }
 
         pub fn remove_allocation_observer(&mut self, observer: &'a dyn AllocationObserver) {
-            let it = self.observers_.iter().position(|aoc| aoc.observer_ as *const _ == observer as *const _);
+            let it = self.observers_.iter().position(|aoc| observer_key(aoc.observer_) == observer_key(observer));
             
             match it {
                 Some(index) => {
                     if self.step_in_progress_ {
-                        assert!(!self.pending_removed_.contains(observer));
-                        self.pending_removed_.insert(observer);
+                        assert!(!self.pending_removed_.contains(&observer_key(observer)));
+                        self.pending_removed_.push(observer_key(observer));
                         return;
                     }
 
@@ -186,8 +224,8 @@ pub mod allocation_observer {
             self.pending_added_.clear();
 
             if !self.pending_removed_.is_empty() {
-                self.observers_.retain(|aoc| !self.pending_removed_.contains(aoc.observer_));
-                self.pending_removed_.clear();
+                let pending_removed = std::mem::take(&mut self.pending_removed_);
+                self.observers_.retain(|aoc| !pending_removed.contains(&observer_key(aoc.observer_)));
 
                 // Some observers were removed, recalculate step size.
                 step_size = 0;
@@ -214,98 +252,71 @@ pub mod allocation_observer {
     }
 
     /// RAII-style helper to pause allocation observers during GC.
-    #[derive(Debug)]
     pub struct PauseAllocationObserversScope<'a> {
-        heap_: &'a mut Heap,
+        heap_: &'a Heap,
     }
 
     impl<'a> PauseAllocationObserversScope<'a> {
-        pub fn new(heap: &'a mut Heap) -> Self {
-            // assert_eq!(heap.gc_state(), Heap::NOT_IN_GC);
-            heap.allocator().pause_allocation_observers();
-            heap.pause_allocation_observers_depth_ += 1;
+        pub fn new(heap: &'a Heap) -> Self {
+            heap.pause_allocation_observers();
             PauseAllocationObserversScope { heap_: heap }
         }
     }
 
     impl<'a> Drop for PauseAllocationObserversScope<'a> {
         fn drop(&mut self) {
-            self.heap_.pause_allocation_observers_depth_ -= 1;
-            self.heap_.allocator().resume_allocation_observers();
+            self.heap_.resume_allocation_observers();
         }
     }
 
-    // Dummy structs and methods for compilation, to be replaced with the
-    // actual Heap and Allocator implementations.
-    pub mod heap {
-        #[derive(Debug)]
-        pub struct Heap {
-            allocator_: Allocator,
-            pub pause_allocation_observers_depth_: i32,
-            gc_state_: i32, // Added gc_state_ field
-        }
-    
-        impl Heap {
-            pub fn new(allocator: Allocator) -> Self {
-                Heap {
-                    allocator_: allocator,
-                    pause_allocation_observers_depth_: 0,
-                    gc_state_: 0, // Initialize gc_state_ to 0
-                }
-            }
-            pub fn allocator(&mut self) -> &mut Allocator {
-                &mut self.allocator_
-            }
-            pub fn gc_state(&self) -> i32 {
-                self.gc_state_
-            }
-        }
+    /// RAII-style helper that makes allocations succeed without triggering
+    /// garbage collections, like V8's AlwaysAllocateScope. Objects allocated
+    /// within the scope do not move unless a collection is requested
+    /// explicitly.
+    pub struct AlwaysAllocateScope<'a> {
+        heap_: &'a Heap,
     }
 
-    #[derive(Debug)]
-    pub struct Allocator {
-        paused_: bool,
+    impl<'a> AlwaysAllocateScope<'a> {
+        pub fn new(heap: &'a Heap) -> Self {
+            heap.enter_always_allocate();
+            AlwaysAllocateScope { heap_: heap }
+        }
     }
 
-    impl Allocator {
-        pub fn new() -> Self {
-            Allocator {
-                paused_: false,
-            }
-        }
-        pub fn pause_allocation_observers(&mut self) {
-            self.paused_ = true;
-        }
-        pub fn resume_allocation_observers(&mut self) {
-            self.paused_ = false;
+    impl<'a> Drop for AlwaysAllocateScope<'a> {
+        fn drop(&mut self) {
+            self.heap_.leave_always_allocate();
         }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
-    
+        use crate::heap::heap::{AllocationType, HeapOptions};
+        use std::cell::Cell;
+
         #[derive(Debug)]
         struct MockAllocationObserver {
             step_size: i64,
-            step_called: bool,
+            steps: Cell<usize>,
         }
-    
+
         impl MockAllocationObserver {
             fn new(step_size: i64) -> Self {
                 MockAllocationObserver {
                     step_size,
-                    step_called: false,
+                    steps: Cell::new(0),
                 }
             }
         }
-    
+
         impl AllocationObserver for MockAllocationObserver {
-            fn step(&self, current_counter: i32, soon_object: usize, object_size: usize) {
-                println!("Step called with counter: {}, object: {}, size: {}", current_counter, soon_object, object_size);
-                //self.step_called = true; //Does not compile with &self
+            fn step(&self, _current_counter: i32, soon_object: usize, _object_size: usize) {
+                assert_ne!(soon_object, 0);
+                self.steps.set(self.steps.get() + 1);
             }
-    
+
             fn get_next_step_size(&self) -> i64 {
                 self.step_size
             }
@@ -320,12 +331,14 @@ pub mod allocation_observer {
             counter.add_allocation_observer(&observer1);
             counter.add_allocation_observer(&observer2);
             assert_eq!(counter.observers_.len(), 2);
+            assert_eq!(counter.next_bytes(), 100);
 
             counter.remove_allocation_observer(&observer1);
             assert_eq!(counter.observers_.len(), 1);
 
             counter.remove_allocation_observer(&observer2);
             assert_eq!(counter.observers_.len(), 0);
+            assert!(!counter.is_active());
         }
 
         #[test]
@@ -334,24 +347,50 @@ pub mod allocation_observer {
             let observer1 = MockAllocationObserver::new(100);
             let soon_object = 0x1000; // Example address
             let object_size = 50;
-            let aligned_object_size = 64;
+            let aligned_object_size = 100;
 
             counter.add_allocation_observer(&observer1);
             counter.invoke_allocation_observers(soon_object, object_size, aligned_object_size);
-            //assert!(observer1.step_called);  //Does not compile with &self
+            assert_eq!(observer1.steps.get(), 1);
         }
-    
+
         #[test]
         fn test_pause_allocation_observers_scope() {
-            let allocator = Allocator::new();
-            let mut heap = Heap::new(allocator);
-    
+            let heap = Heap::new(HeapOptions::default());
+            let observer = MockAllocationObserver::new(64);
+            unsafe { heap.add_allocation_observer(&observer) };
+
             {
-                let _pause_scope = PauseAllocationObserversScope::new(&mut heap);
-                assert_eq!(heap.allocator().paused_, true);
+                let _pause_scope = PauseAllocationObserversScope::new(&heap);
+                for _ in 0..16 {
+                    heap.allocate_fixed_array(4, AllocationType::kYoung);
+                }
+                assert_eq!(observer.steps.get(), 0);
+            }
+
+            for _ in 0..16 {
+                heap.allocate_fixed_array(4, AllocationType::kYoung);
+            }
+            assert!(observer.steps.get() > 0);
+            unsafe { heap.remove_allocation_observer(&observer) };
+        }
+
+        #[test]
+        fn test_always_allocate_scope() {
+            let heap = Heap::new(HeapOptions {
+                max_semi_space_size: 64 * 1024,
+                stress_scavenge: Some(1),
+                ..HeapOptions::default()
+            });
+            {
+                let _always_allocate_scope = AlwaysAllocateScope::new(&heap);
+                for _ in 0..1024 {
+                    heap.allocate_fixed_array(16, AllocationType::kYoung);
+                }
+                assert_eq!(heap.gc_count(), 0);
             }
-    
-            assert_eq!(heap.allocator().paused_, false);
+            heap.allocate_fixed_array(16, AllocationType::kYoung);
+            assert!(heap.gc_count() > 0);
         }
     }
-}
\ No newline at end of file
+}
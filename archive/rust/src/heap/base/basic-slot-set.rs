@@ -2,544 +2,245 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+use std::ptr;
 use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
-use std::{mem, ptr};
 
-#[derive(PartialEq, Eq, Copy, Clone)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum SlotCallbackResult {
     KEEP_SLOT,
     REMOVE_SLOT,
 }
 
-pub mod atomic_utils {
-    use std::sync::atomic::{AtomicU32, Ordering};
+const K_BITS_PER_CELL_LOG2: usize = 5;
+const K_BITS_PER_CELL: usize = 1 << K_BITS_PER_CELL_LOG2;
+const K_CELLS_PER_BUCKET_LOG2: usize = 5;
+const K_CELLS_PER_BUCKET: usize = 1 << K_CELLS_PER_BUCKET_LOG2;
+const K_BITS_PER_BUCKET_LOG2: usize = K_CELLS_PER_BUCKET_LOG2 + K_BITS_PER_CELL_LOG2;
+const K_BITS_PER_BUCKET: usize = 1 << K_BITS_PER_BUCKET_LOG2;
 
-    pub struct AsAtomic32 {}
-
-    impl AsAtomic32 {
-        #[inline]
-        pub fn acquire_load(atomic: &AtomicU32) -> u32 {
-            atomic.load(Ordering::Acquire)
-        }
-
-        #[inline]
-        pub fn release_store(atomic: &AtomicU32, value: u32) {
-            atomic.store(value, Ordering::Release);
-        }
-
-        #[inline]
-        pub fn release_set_bits(atomic: &AtomicU32, value: u32, mask: u32) {
-            let mut current = atomic.load(Ordering::Acquire);
-            loop {
-                let new_value = (current & !mask) | value;
-                match atomic.compare_exchange_weak(
-                    current,
-                    new_value,
-                    Ordering::Release,
-                    Ordering::Acquire,
-                ) {
-                    Ok(_) => break,
-                    Err(x) => current = x,
-                }
-            }
-        }
-    }
-
-    pub struct AsAtomicPointer {}
-
-    impl AsAtomicPointer {
-        #[inline]
-        pub fn acquire_load<T>(atomic: &AtomicPtr<T>) -> *mut T {
-            atomic.load(Ordering::Acquire)
-        }
-
-        #[inline]
-        pub fn release_store<T>(atomic: &AtomicPtr<T>, value: *mut T) {
-            atomic.store(value, Ordering::Release);
-        }
-
-        #[inline]
-        pub fn release_compare_and_swap<T>(
-            atomic: &AtomicPtr<T>,
-            current: *mut T,
-            new: *mut T,
-        ) -> *mut T {
-            match atomic.compare_exchange(current, new, Ordering::Release, Ordering::Relaxed) {
-                Ok(_) => current,
-                Err(x) => x,
-            }
-        }
-    }
+struct Bucket {
+    cells: [AtomicU32; K_CELLS_PER_BUCKET],
 }
 
-pub mod bits {
-    pub fn count_trailing_zeros(x: u32) -> usize {
-        x.trailing_zeros() as usize
+impl Bucket {
+    fn new() -> Box<Bucket> {
+        Box::new(Bucket {
+            cells: std::array::from_fn(|_| AtomicU32::new(0)),
+        })
     }
-}
 
-pub mod platform {
-    pub mod memory {
-        use std::alloc::{alloc, dealloc, Layout};
-        use std::ptr;
-
-        pub unsafe fn aligned_alloc(size: usize, alignment: usize) -> *mut u8 {
-            let layout = Layout::from_size_align(size, alignment).unwrap();
-            if layout.size() == 0 {
-                return alignment as *mut u8;
-            }
-            let ptr = alloc(layout);
-            if ptr.is_null() {
-                panic!("Allocation failed");
-            }
-            ptr
-        }
-
-        pub unsafe fn aligned_free(ptr: *mut u8) {
-            if ptr.is_null() {
-                return;
-            }
-
-            //This is a dummy aligned_free since we don't have the layout information
-            //from the allocation.  This is ONLY safe because the allocator is never used
-            //in the current code.
-
-            //TODO: Properly implement aligned_free.
-        }
+    fn is_empty(&self) -> bool {
+        self.cells
+            .iter()
+            .all(|cell| cell.load(Ordering::Relaxed) == 0)
     }
 }
 
-pub mod base {
-    use super::*;
-    use std::sync::atomic::AtomicPtr;
-
-    #[derive(Debug)]
-    pub struct AlignedAllocError;
+/// Data structure for maintaining a set of slots in a standard (non-large)
+/// page. The slot set is a bitmap with one bit per `SLOT_GRANULARITY` bytes;
+/// bits are grouped into buckets that are allocated on first insertion.
+///
+/// Insertion, removal and iteration use atomic operations on the cells, so
+/// slots may be inserted concurrently with each other and with iteration.
+/// Buckets are only freed by `free_empty_buckets()`, which must not run
+/// concurrently with anything else.
+pub struct BasicSlotSet<const SLOT_GRANULARITY: usize> {
+    buckets: Box<[AtomicPtr<Bucket>]>,
+}
 
-    pub unsafe fn aligned_alloc(size: usize, alignment: usize) -> Result<*mut u8, AlignedAllocError> {
-        let ptr = platform::memory::aligned_alloc(size, alignment);
-        if ptr.is_null() {
-            return Err(AlignedAllocError);
+impl<const SLOT_GRANULARITY: usize> BasicSlotSet<SLOT_GRANULARITY> {
+    /// Creates a slot set for an area of `size` bytes.
+    pub fn new(size: usize) -> Self {
+        let slots = size.div_ceil(SLOT_GRANULARITY);
+        let buckets = slots.div_ceil(K_BITS_PER_BUCKET);
+        BasicSlotSet {
+            buckets: (0..buckets).map(|_| AtomicPtr::new(ptr::null_mut())).collect(),
         }
-        Ok(ptr)
     }
 
-    pub unsafe fn aligned_free(ptr: *mut u8) {
-        platform::memory::aligned_free(ptr);
+    pub fn buckets(&self) -> usize {
+        self.buckets.len()
     }
-}
-
-pub mod internal {
-    pub struct WriteBarrierCodeStubAssembler {}
-}
 
-pub mod heap_base {
-    use super::*;
-    use std::sync::atomic::AtomicPtr;
-
-    #[derive(PartialEq, Eq, Copy, Clone)]
-    pub enum EmptyBucketMode {
-        FREE_EMPTY_BUCKETS,
-        KEEP_EMPTY_BUCKETS,
+    /// The slot offset specifies a slot at address page_start + slot_offset.
+    pub fn insert(&self, slot_offset: usize) {
+        let (bucket_index, cell_index, bit_mask) = Self::slot_to_indices(slot_offset);
+        let bucket = self.load_or_allocate_bucket(bucket_index);
+        let cell = &bucket.cells[cell_index];
+        if cell.load(Ordering::Relaxed) & bit_mask == 0 {
+            cell.fetch_or(bit_mask, Ordering::Relaxed);
+        }
     }
 
-    pub struct BasicSlotSet<const SLOT_GRANULARITY: usize> {
-        num_buckets: usize,
-        buckets: Vec<*mut Bucket>,
+    /// Returns true if the set contains the slot.
+    pub fn contains(&self, slot_offset: usize) -> bool {
+        let (bucket_index, cell_index, bit_mask) = Self::slot_to_indices(slot_offset);
+        self.load_bucket(bucket_index)
+            .is_some_and(|bucket| bucket.cells[cell_index].load(Ordering::Relaxed) & bit_mask != 0)
     }
 
-    impl<const SLOT_GRANULARITY: usize> BasicSlotSet<SLOT_GRANULARITY> {
-        const K_SYSTEM_POINTER_SIZE: usize = mem::size_of::<usize>();
-        const K_CELLS_PER_BUCKET: usize = 32;
-        const K_CELLS_PER_BUCKET_LOG2: usize = 5;
-        const K_CELL_SIZE_BYTES_LOG2: usize = 2;
-        const K_CELL_SIZE_BYTES: usize = 1 << Self::K_CELL_SIZE_BYTES_LOG2;
-        const K_BITS_PER_CELL: usize = 32;
-        const K_BITS_PER_CELL_LOG2: usize = 5;
-        const K_BITS_PER_BUCKET: usize = Self::K_CELLS_PER_BUCKET * Self::K_BITS_PER_CELL;
-        const K_BITS_PER_BUCKET_LOG2: usize =
-            Self::K_CELLS_PER_BUCKET_LOG2 + Self::K_BITS_PER_CELL_LOG2;
-        const K_NUM_BUCKETS_SIZE: usize = mem::size_of::<usize>();
-
-        pub fn allocate(buckets: usize) -> Box<Self> {
-            let mut v = Vec::with_capacity(buckets);
-            v.resize_with(buckets, || std::ptr::null_mut());
-
-            Box::new(Self {
-                num_buckets: buckets,
-                buckets: v,
-            })
-        }
-
-        pub fn delete(slot_set: Box<Self>) {
-            //Drop will handle this for now.
-        }
-
-        pub const fn buckets_for_size(size: usize) -> usize {
-            (size + (SLOT_GRANULARITY * Self::K_BITS_PER_BUCKET) - 1)
-                / (SLOT_GRANULARITY * Self::K_BITS_PER_BUCKET)
-        }
-
-        pub const fn bucket_for_slot(slot_offset: usize) -> usize {
-            assert!(Self::is_aligned(slot_offset as u64, SLOT_GRANULARITY as u64));
-            slot_offset / (SLOT_GRANULARITY * Self::K_BITS_PER_BUCKET)
-        }
-
-        pub const fn offset_for_bucket(bucket_index: usize) -> usize {
-            bucket_index * SLOT_GRANULARITY * Self::K_BITS_PER_BUCKET
-        }
-
-        fn is_aligned(value: u64, alignment: u64) -> bool {
-            value & (alignment - 1) == 0
-        }
-
-        pub fn insert<const ACCESS_MODE: u8>(&mut self, slot_offset: usize) {
-            let (bucket_index, cell_index, bit_index) = self.slot_to_indices(slot_offset);
-
-            let bucket_ptr = self.load_bucket::<ACCESS_MODE>(bucket_index);
-            let mut bucket: Box<Bucket>;
-
-            if bucket_ptr.is_null() {
-                bucket = Box::new(Bucket::default());
-                let bucket_raw = Box::into_raw(bucket);
-
-                unsafe {
-                    if !self.swap_in_new_bucket::<ACCESS_MODE>(bucket_index, bucket_raw) {
-                        drop(Box::from_raw(bucket_raw)); //Drop the bucket
-                    }
-                }
-
-                bucket_ptr = self.load_bucket::<ACCESS_MODE>(bucket_index);
-            }
-
-            assert!(!bucket_ptr.is_null());
-
-            let bucket = unsafe {&mut *bucket_ptr};
-
-            let mask = 1u32 << bit_index;
-            if (bucket.load_cell::<ACCESS_MODE>(cell_index) & mask) == 0 {
-                bucket.set_cell_bits::<ACCESS_MODE>(cell_index, mask);
-            }
-        }
-
-        pub fn contains(&self, slot_offset: usize) -> bool {
-            let (bucket_index, cell_index, bit_index) = self.slot_to_indices(slot_offset);
-            let bucket = self.load_bucket::<0>(bucket_index);
-            if bucket.is_null() {
-                return false;
-            }
-            let bucket = unsafe { &*bucket };
-            (bucket.load_cell::<0>(cell_index) & (1u32 << bit_index)) != 0
-        }
-
-        pub fn remove(&mut self, slot_offset: usize) {
-            let (bucket_index, cell_index, bit_index) = self.slot_to_indices(slot_offset);
-            let bucket = self.load_bucket::<0>(bucket_index);
-            if !bucket.is_null() {
-                let bucket = unsafe { &mut *bucket };
-                let cell = bucket.load_cell::<0>(cell_index);
-                let bit_mask = 1u32 << bit_index;
-                if cell & bit_mask != 0 {
-                    bucket.clear_cell_bits::<0>(cell_index, bit_mask);
-                }
-            }
-        }
-
-        pub fn remove_range(
-            &mut self,
-            start_offset: usize,
-            end_offset: usize,
-            buckets: usize,
-            mode: EmptyBucketMode,
-        ) {
-            assert!(end_offset <= buckets * Self::K_BITS_PER_BUCKET * SLOT_GRANULARITY);
-            assert!(start_offset <= end_offset);
-
-            let (mut start_bucket, mut start_cell, mut start_bit) = self.slot_to_indices(start_offset);
-            let (end_bucket, end_cell, mut end_bit) =
-                self.slot_to_indices(end_offset - SLOT_GRANULARITY);
-
-            end_bit += 1;
-            if end_bit >= Self::K_BITS_PER_CELL {
-                end_bit = 0;
-                start_cell += 1;
-                if start_cell >= Self::K_CELLS_PER_BUCKET {
-                    start_cell = 0;
-                    start_bucket += 1;
-                }
-            }
-
-            let start_mask = (1u32 << start_bit) - 1;
-            let end_mask = !((1u32 << end_bit) - 1);
-            let mut bucket: *mut Bucket;
-
-            if start_bucket == end_bucket && start_cell == end_cell {
-                bucket = self.load_bucket::<0>(start_bucket);
-                if !bucket.is_null() {
-                  let bucket = unsafe {&mut *bucket};
-                    bucket.clear_cell_bits::<0>(start_cell, !(start_mask | end_mask));
-                }
-                return;
-            }
-
-            let mut current_bucket = start_bucket;
-            let mut current_cell = start_cell;
-
-            bucket = self.load_bucket::<0>(current_bucket);
-            if !bucket.is_null() {
-              let bucket = unsafe {&mut *bucket};
-              bucket.clear_cell_bits::<0>(current_cell, !start_mask);
-            }
-
-            current_cell += 1;
-
-            if current_bucket < end_bucket {
-                if !bucket.is_null() {
-                  let bucket = unsafe {&mut *bucket};
-                    self.clear_bucket(bucket, current_cell, Self::K_CELLS_PER_BUCKET);
-                }
-                current_bucket += 1;
-                current_cell = 0;
-            }
-
-            assert!(current_bucket == end_bucket || (current_bucket < end_bucket && current_cell == 0));
-
-            while current_bucket < end_bucket {
-                if mode == EmptyBucketMode::FREE_EMPTY_BUCKETS {
-                    self.release_bucket::<0>(current_bucket);
-                } else {
-                    assert!(mode == EmptyBucketMode::KEEP_EMPTY_BUCKETS);
-                    bucket = self.load_bucket::<0>(current_bucket);
-                    if !bucket.is_null() {
-                      let bucket = unsafe {&mut *bucket};
-                        self.clear_bucket(bucket, 0, Self::K_CELLS_PER_BUCKET);
-                    }
-                }
-                current_bucket += 1;
-            }
-
-            assert!(current_bucket == end_bucket);
-            if current_bucket == buckets {
-                return;
-            }
-
-            bucket = self.load_bucket::<0>(current_bucket);
-            assert!(current_cell <= end_cell);
-            if bucket.is_null() {
-                return;
-            }
-
-            let bucket = unsafe {&mut *bucket};
-
-            while current_cell < end_cell {
-                bucket.store_cell(current_cell, 0);
-                current_cell += 1;
-            }
-
-            assert!(current_bucket == end_bucket && current_cell == end_cell);
-            bucket.clear_cell_bits::<0>(end_cell, !end_mask);
-        }
-
-        pub fn lookup(&self, slot_offset: usize) -> bool {
-            let (bucket_index, cell_index, bit_index) = self.slot_to_indices(slot_offset);
-            let bucket = self.load_bucket::<0>(bucket_index);
-            if bucket.is_null() {
-                return false;
-            }
-            let bucket = unsafe {&*bucket};
-            (bucket.load_cell::<0>(cell_index) & (1u32 << bit_index)) != 0
+    pub fn remove(&self, slot_offset: usize) {
+        let (bucket_index, cell_index, bit_mask) = Self::slot_to_indices(slot_offset);
+        if let Some(bucket) = self.load_bucket(bucket_index) {
+            bucket.cells[cell_index].fetch_and(!bit_mask, Ordering::Relaxed);
         }
+    }
 
-        pub fn iterate<const ACCESS_MODE: u8, F>(
-            &mut self,
-            chunk_start: usize,
-            start_bucket: usize,
-            end_bucket: usize,
-            callback: F,
-            mode: EmptyBucketMode,
-        ) -> usize
-        where
-            F: Fn(usize) -> SlotCallbackResult,
-        {
-            let empty_bucket_callback = |bucket_index: usize| {
-                if mode == EmptyBucketMode::FREE_EMPTY_BUCKETS {
-                    self.release_bucket::<0>(bucket_index);
-                }
+    /// Removes all slots in the range [start_offset, end_offset).
+    pub fn remove_range(&self, start_offset: usize, end_offset: usize) {
+        let mut offset = start_offset.next_multiple_of(SLOT_GRANULARITY);
+        while offset < end_offset {
+            let (bucket_index, cell_index, bit_mask) = Self::slot_to_indices(offset);
+            let Some(bucket) = self.load_bucket(bucket_index) else {
+                // Skip to the next bucket.
+                offset = (bucket_index + 1) * K_BITS_PER_BUCKET * SLOT_GRANULARITY;
+                continue;
+            };
+            // Clear the remaining bits of the cell that lie in the range.
+            let first_bit = bit_mask.trailing_zeros() as usize;
+            let bits_left = (end_offset - offset).div_ceil(SLOT_GRANULARITY);
+            let last_bit = (first_bit + bits_left).min(K_BITS_PER_CELL);
+            let mask = if last_bit == K_BITS_PER_CELL {
+                !0u32 << first_bit
+            } else {
+                ((1u32 << last_bit) - 1) & (!0u32 << first_bit)
             };
-            self.iterate_internal::<ACCESS_MODE, F, _>(
-                chunk_start,
-                start_bucket,
-                end_bucket,
-                callback,
-                empty_bucket_callback,
-            )
+            bucket.cells[cell_index].fetch_and(!mask, Ordering::Relaxed);
+            offset += (last_bit - first_bit) * SLOT_GRANULARITY;
         }
+    }
 
-        fn iterate_internal<const ACCESS_MODE: u8, F, E>(
-            &mut self,
-            chunk_start: usize,
-            start_bucket: usize,
-            end_bucket: usize,
-            callback: F,
-            empty_bucket_callback: E,
-        ) -> usize
-        where
-            F: Fn(usize) -> SlotCallbackResult,
-            E: Fn(usize),
-        {
-            let mut new_count = 0;
-            for bucket_index in start_bucket..end_bucket {
-                let bucket_ptr = self.load_bucket::<ACCESS_MODE>(bucket_index);
-                if !bucket_ptr.is_null() {
-                  let bucket = unsafe {&mut *bucket_ptr};
-                    let mut in_bucket_count = 0;
-                    let cell_offset = bucket_index << Self::K_BITS_PER_BUCKET_LOG2;
-                    for i in 0..Self::K_CELLS_PER_BUCKET {
-                        let cell_offset_local = cell_offset + (i << Self::K_BITS_PER_CELL_LOG2);
-                        let cell = bucket.load_cell::<ACCESS_MODE>(i);
-                        if cell != 0 {
-                            let mut old_cell = cell;
-                            let mut mask = 0;
-                            let mut cell_local = cell;
-                            while cell_local != 0 {
-                                let bit_offset = bits::count_trailing_zeros(cell_local);
-                                let bit_mask = 1u32 << bit_offset;
-                                let slot = (cell_offset_local + bit_offset) * SLOT_GRANULARITY;
-                                if callback(chunk_start + slot) == SlotCallbackResult::KEEP_SLOT {
-                                    in_bucket_count += 1;
-                                } else {
-                                    mask |= bit_mask;
-                                }
-                                cell_local ^= bit_mask;
-                            }
-                            let new_cell = old_cell & !mask;
-                            if old_cell != new_cell {
-                                bucket.clear_cell_bits::<ACCESS_MODE>(i, mask);
-                            }
-                        }
-                    }
-                    if in_bucket_count == 0 {
-                        empty_bucket_callback(bucket_index);
+    /// Iterates over all slots in the set and for each slot invokes the
+    /// callback with the slot address. If the callback returns REMOVE_SLOT,
+    /// the slot is removed from the set. Returns the number of slots kept.
+    pub fn iterate(&self, chunk_start: usize, mut callback: impl FnMut(usize) -> SlotCallbackResult) -> usize {
+        let mut kept = 0;
+        for bucket_index in 0..self.buckets.len() {
+            let Some(bucket) = self.load_bucket(bucket_index) else {
+                continue;
+            };
+            for (cell_index, cell) in bucket.cells.iter().enumerate() {
+                let mut bits = cell.load(Ordering::Relaxed);
+                let mut remove_mask = 0u32;
+                while bits != 0 {
+                    let bit = bits.trailing_zeros() as usize;
+                    bits &= bits - 1;
+                    let slot_index = (bucket_index << K_BITS_PER_BUCKET_LOG2)
+                        | (cell_index << K_BITS_PER_CELL_LOG2)
+                        | bit;
+                    match callback(chunk_start + slot_index * SLOT_GRANULARITY) {
+                        SlotCallbackResult::KEEP_SLOT => kept += 1,
+                        SlotCallbackResult::REMOVE_SLOT => remove_mask |= 1 << bit,
                     }
-                    new_count += in_bucket_count;
                 }
-            }
-            new_count
-        }
-
-        fn free_bucket_if_empty(&mut self, bucket_index: usize) -> bool {
-            let bucket = self.load_bucket::<0>(bucket_index);
-            if !bucket.is_null() {
-                let bucket = unsafe {&*bucket};
-                if bucket.is_empty() {
-                    self.release_bucket::<0>(bucket_index);
-                } else {
-                    return false;
+                if remove_mask != 0 {
+                    // Only clear the bits that were removed; slots may have
+                    // been inserted concurrently.
+                    cell.fetch_and(!remove_mask, Ordering::Relaxed);
                 }
             }
-
-            true
-        }
-
-        fn clear_bucket(&mut self, bucket: &mut Bucket, start_cell: usize, end_cell: usize) {
-            assert!(start_cell >= 0);
-            assert!(end_cell <= Self::K_CELLS_PER_BUCKET);
-            let mut current_cell = start_cell;
-            while current_cell < Self::K_CELLS_PER_BUCKET {
-                bucket.store_cell(current_cell, 0);
-                current_cell += 1;
-            }
-        }
-
-        fn release_bucket<const ACCESS_MODE: u8>(&mut self, bucket_index: usize) {
-            let bucket_ptr = self.load_bucket::<ACCESS_MODE>(bucket_index);
-            self.store_bucket::<ACCESS_MODE>(bucket_index, std::ptr::null_mut());
-            if !bucket_ptr.is_null() {
-                unsafe {
-                    drop(Box::from_raw(bucket_ptr));
-                }
-            }
-        }
-
-        fn load_bucket<const ACCESS_MODE: u8>(&self, bucket_index: usize) -> *mut Bucket {
-                self.buckets[bucket_index]
-        }
-
-        fn store_bucket<const ACCESS_MODE: u8>(&mut self, bucket_index: usize, value: *mut Bucket) {
-                self.buckets[bucket_index] = value;
-        }
-
-        unsafe fn swap_in_new_bucket<const ACCESS_MODE: u8>(
-            &mut self,
-            bucket_index: usize,
-            value: *mut Bucket,
-        ) -> bool {
-            if self.buckets[bucket_index].is_null() {
-                self.buckets[bucket_index] = value;
-                true
-            } else {
-                false
-            }
         }
+        kept
+    }
 
-        fn slot_to_indices(&self, slot_offset: usize) -> (usize, usize, usize) {
-            assert!(Self::is_aligned(slot_offset as u64, SLOT_GRANULARITY as u64));
-            let slot = slot_offset / SLOT_GRANULARITY;
-            let bucket_index = slot >> Self::K_BITS_PER_BUCKET_LOG2;
-            assert!(bucket_index < self.num_buckets);
-            let cell_index =
-                ((slot >> Self::K_BITS_PER_CELL_LOG2) & (Self::K_CELLS_PER_BUCKET - 1)) as usize;
-            let bit_index = (slot & (Self::K_BITS_PER_CELL - 1)) as usize;
-            (bucket_index, cell_index, bit_index)
-        }
+    pub fn is_empty(&self) -> bool {
+        (0..self.buckets.len()).all(|index| self.load_bucket(index).is_none_or(Bucket::is_empty))
     }
 
-    impl<const SLOT_GRANULARITY: usize> Drop for BasicSlotSet<SLOT_GRANULARITY> {
-        fn drop(&mut self) {
-            for i in 0..self.num_buckets {
-                self.release_bucket::<0>(i);
+    /// Releases buckets without slots.
+    pub fn free_empty_buckets(&mut self) {
+        for bucket in self.buckets.iter_mut() {
+            let raw = *bucket.get_mut();
+            // SAFETY: non-null buckets were created by `Box::into_raw` and
+            // `&mut self` rules out concurrent access.
+            if !raw.is_null() && unsafe { &*raw }.is_empty() {
+                drop(unsafe { Box::from_raw(raw) });
+                *bucket.get_mut() = ptr::null_mut();
             }
         }
     }
 
-    #[derive(Default)]
-    pub struct Bucket {
-        cells: [AtomicU32; BasicSlotSet::<1>::K_CELLS_PER_BUCKET],
+    fn slot_to_indices(slot_offset: usize) -> (usize, usize, u32) {
+        debug_assert_eq!(slot_offset % SLOT_GRANULARITY, 0);
+        let slot = slot_offset / SLOT_GRANULARITY;
+        let bucket_index = slot >> K_BITS_PER_BUCKET_LOG2;
+        let cell_index = (slot >> K_BITS_PER_CELL_LOG2) & (K_CELLS_PER_BUCKET - 1);
+        let bit_mask = 1u32 << (slot & (K_BITS_PER_CELL - 1));
+        (bucket_index, cell_index, bit_mask)
     }
 
-    impl Bucket {
-        pub fn cells(&mut self) -> &mut [AtomicU32; BasicSlotSet::<1>::K_CELLS_PER_BUCKET] {
-            &mut self.cells
-        }
-
-        pub fn cell(&mut self, cell_index: usize) -> &mut AtomicU32 {
-            &mut self.cells[cell_index]
-        }
+    fn load_bucket(&self, bucket_index: usize) -> Option<&Bucket> {
+        let raw = self.buckets[bucket_index].load(Ordering::Acquire);
+        // SAFETY: buckets stay alive until `free_empty_buckets()` or drop,
+        // both of which require exclusive access.
+        unsafe { raw.as_ref() }
+    }
 
-        pub fn load_cell<const ACCESS_MODE: u8>(&self, cell_index: usize) -> u32 {
-            assert!(cell_index < BasicSlotSet::<1>::K_CELLS_PER_BUCKET);
-                atomic_utils::AsAtomic32::acquire_load(&self.cells[cell_index])
+    fn load_or_allocate_bucket(&self, bucket_index: usize) -> &Bucket {
+        if let Some(bucket) = self.load_bucket(bucket_index) {
+            return bucket;
         }
-
-        pub fn set_cell_bits<const ACCESS_MODE: u8>(&mut self, cell_index: usize, mask: u32) {
-                atomic_utils::AsAtomic32::release_set_bits(&self.cells[cell_index], mask, mask);
+        let new_bucket = Box::into_raw(Bucket::new());
+        match self.buckets[bucket_index].compare_exchange(
+            ptr::null_mut(),
+            new_bucket,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            // SAFETY: the bucket was just installed and lives as long as the
+            // set.
+            Ok(_) => unsafe { &*new_bucket },
+            Err(existing) => {
+                // Another thread installed a bucket first.
+                drop(unsafe { Box::from_raw(new_bucket) });
+                unsafe { &*existing }
+            }
         }
+    }
+}
 
-        pub fn clear_cell_bits<const ACCESS_MODE: u8>(&mut self, cell_index: usize, mask: u32) {
-                atomic_utils::AsAtomic32::release_set_bits(&self.cells[cell_index], 0u32, mask);
+impl<const SLOT_GRANULARITY: usize> Drop for BasicSlotSet<SLOT_GRANULARITY> {
+    fn drop(&mut self) {
+        for bucket in self.buckets.iter_mut() {
+            let raw = *bucket.get_mut();
+            if !raw.is_null() {
+                // SAFETY: see `free_empty_buckets()`.
+                drop(unsafe { Box::from_raw(raw) });
+            }
         }
+    }
+}
 
-        pub fn store_cell(&mut self, cell_index: usize, value: u32) {
-            atomic_utils::AsAtomic32::release_store(&self.cells[cell_index], value);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        pub fn is_empty(&self) -> bool {
-            for i in 0..BasicSlotSet::<1>::K_CELLS_PER_BUCKET {
-                if self.cells[i].load(Ordering::Relaxed) != 0 {
-                    return false;
-                }
-            }
-            true
-        }
+    #[test]
+    fn insert_iterate_and_remove() {
+        let set = BasicSlotSet::<8>::new(1 << 18);
+        for offset in [0, 8, 256, 8192, (1 << 18) - 8] {
+            set.insert(offset);
+        }
+        assert!(set.contains(256));
+        assert!(!set.contains(264));
+        let mut seen = Vec::new();
+        let kept = set.iterate(0x1000, |slot| {
+            seen.push(slot - 0x1000);
+            if slot - 0x1000 == 8 {
+                SlotCallbackResult::REMOVE_SLOT
+            } else {
+                SlotCallbackResult::KEEP_SLOT
+            }
+        });
+        assert_eq!(seen, [0, 8, 256, 8192, (1 << 18) - 8]);
+        assert_eq!(kept, 4);
+        assert!(!set.contains(8));
+
+        set.remove_range(0, 8200);
+        assert!(!set.contains(0) && !set.contains(256) && !set.contains(8192));
+        assert!(set.contains((1 << 18) - 8));
+        set.remove((1 << 18) - 8);
+        assert!(set.is_empty());
     }
-}
\ No newline at end of file
+}
@@ -2,327 +2,172 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/base/stack.h (Module definition - incomplete)
-pub mod stack {
-    use std::{
-        collections::HashMap,
-        sync::{Arc, Mutex},
-    };
-
-    pub type IterateStackCallback = Box<dyn Fn(*const std::ffi::c_void)>;
-
-    #[derive(Debug, Clone)]
-    pub struct Segment {
-        pub top: *const std::ffi::c_void,
-        pub start: *const std::ffi::c_void,
-        pub asan_fake_stack: *mut std::ffi::c_void,
-        pub unsafe_stack_start: *mut std::ffi::c_void,
-        pub unsafe_stack_top: *mut std::ffi::c_void,
-    }
-
-    pub struct Stack {
-        current_segment_: Segment,
-        background_stacks_: Arc<Mutex<HashMap<usize, Segment>>>,
-        marker_: *const std::ffi::c_void,
-        callback_: Option<IterateStackCallback>,
-        scan_simulator_callback_: Option<Box<dyn Fn(&mut StackVisitor)>>,
-    }
-
-    impl Stack {
-        pub fn new(current_segment: Segment) -> Self {
-            Stack {
-                current_segment_: current_segment,
-                background_stacks_: Arc::new(Mutex::new(HashMap::new())),
-                marker_: std::ptr::null(),
-                callback_: None,
-                scan_simulator_callback_: None,
-            }
-        }
-
-        pub fn set_scan_simulator_callback(&mut self, callback: Box<dyn Fn(&mut StackVisitor)>) {
-            self.scan_simulator_callback_ = Some(callback);
-        }
-
-        pub fn add_background_stack(&self, id: usize, segment: Segment) {
-            let mut stacks = self.background_stacks_.lock().unwrap();
-            stacks.insert(id, segment);
-        }
-
-        pub fn remove_background_stack(&self, id: &usize) {
-          let mut stacks = self.background_stacks_.lock().unwrap();
-          stacks.remove(id);
-        }
-
-        pub fn current_segment(&self) -> &Segment {
-            &self.current_segment_
-        }
-
-        pub fn background_stacks(&self) -> Arc<Mutex<HashMap<usize, Segment>>> {
-            self.background_stacks_.clone()
-        }
-
-        pub fn marker(&self) -> *const std::ffi::c_void {
-          self.marker_
-        }
-        
-        pub fn set_marker(&mut self, marker: *const std::ffi::c_void) {
-          self.marker_ = marker
-        }
-
-        pub fn set_callback(&mut self, callback: IterateStackCallback) {
-            self.callback_ = Some(callback);
-        }
-
-        pub fn clear_callback(&mut self) {
-            self.callback_ = None;
-        }
-
-        pub fn callback(&self) -> &Option<IterateStackCallback> {
-            &self.callback_
-        }
-
-        pub fn iterate_pointers_for_testing(&mut self, visitor: &mut StackVisitor) {
-            let visitor_ptr = visitor as *mut StackVisitor;
-            self.set_marker_and_callback(move || {
-                self.iterate_pointers(unsafe { &mut *visitor_ptr });
-            });
-        }
-
-        fn set_marker_and_callback<F>(&mut self, f: F)
-        where
-            F: FnOnce(),
-        {
-            f(); // Execute the callback immediately
-        }
-
-        pub fn iterate_pointers(&mut self, visitor: &mut StackVisitor) {
-            self.iterate_pointers_until_marker(visitor);
-        }
-
-        pub fn iterate_background_stacks(&self, visitor: &mut StackVisitor) {
-            // Temporarily stop checking MTE tags whilst scanning the stack (whilst V8
-            // may not be tagging its portion of the stack, higher frames from the OS or
-            // libc could be using stack tagging.)
-            let _s = SuspendTagCheckingScope {}; // Dummy for now
-
-            let stacks = self.background_stacks_.lock().unwrap();
-            for (_, segment) in stacks.iter() {
-                // All supported platforms should have their stack aligned to at least
-                // sizeof(void*).
-                const K_MIN_STACK_ALIGNMENT: usize = std::mem::size_of::<*const std::ffi::c_void>();
-                assert_eq!(
-                    segment.top as usize & (K_MIN_STACK_ALIGNMENT - 1),
-                    0,
-                    "Stack alignment check failed"
-                );
-                iterate_pointers_in_stack(visitor, segment);
-                iterate_pointers_in_unsafe_stack_if_necessary(visitor, segment);
-            }
-        }
-
-        fn iterate_pointers_until_marker(&self, visitor: &mut StackVisitor) {
-            // Temporarily stop checking MTE tags whilst scanning the stack (whilst V8
-            // may not be tagging its portion of the stack, higher frames from the OS or
-            // libc could be using stack tagging.)
-            let _s = SuspendTagCheckingScope {}; // Dummy for now
-
-            iterate_pointers_in_stack(visitor, &self.current_segment_);
-            iterate_pointers_in_unsafe_stack_if_necessary(visitor, &self.current_segment_);
-
-            if let Some(ref callback) = self.scan_simulator_callback_ {
-                callback(visitor);
-            }
-        }
-
-        pub fn trampoline_callback_helper(
-            &mut self,
-            argument: *mut std::ffi::c_void,
-            callback: IterateStackCallback,
-        ) {
-            unsafe {
-                push_all_registers_and_iterate_stack(self, argument, callback);
-            }
-            // TODO(chromium:1056170): Add support for SIMD and/or filtering.
-        }
-
-        #[cfg(debug_assertions)]
-        pub fn is_on_current_stack(ptr: *const std::ffi::c_void) -> bool {
-            assert!(!ptr.is_null());
-            let current_stack_start = v8::base::stack::get_stack_start_unchecked();
-            let current_stack_top = v8::base::stack::get_current_stack_position();
-            ptr <= current_stack_start && ptr >= current_stack_top
-        }
-
-        // static
-        pub fn is_on_stack(slot: *const std::ffi::c_void) -> bool {
-            #[cfg(feature = "v8_use_address_sanitizer")]
-            {
-                // If the slot is part of a fake frame, then it is definitely on the stack.
-                // if __asan_addr_is_in_fake_stack(__asan_get_current_fake_stack(),
-                //                                  const_cast<void*>(slot), nullptr, nullptr)) {
-                //   return true;
-                // }
-                // Fall through as there is still a regular stack present even when running
-                // with ASAN fake stacks.
-                //TODO: Implement ASAN stack check
-            }
-            #[cfg(feature = "v8_use_safe_stack")]
-            {
-                //if __builtin___get_unsafe_stack_ptr() <= slot &&
-                // slot <= __builtin___get_unsafe_stack_top() {
-                //  return true;
-                //}
-                //TODO: Implement safe stack check
-            }
-            v8::base::stack::get_current_stack_position() <= slot
-                && slot <= v8::base::stack::get_stack_start_unchecked()
+//! Conservative stack scanning support for the thread owning a heap.
+
+/// Number of callee-saved registers that are spilled before the stack is
+/// iterated.
+#[cfg(target_arch = "x86_64")]
+const NUM_CALLEE_SAVED_REGISTERS: usize = 8;
+#[cfg(target_arch = "aarch64")]
+const NUM_CALLEE_SAVED_REGISTERS: usize = 11;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const NUM_CALLEE_SAVED_REGISTERS: usize = 0;
+
+/// Abstraction over the stack of the thread that created it. The stack is
+/// iterated word by word from the current position up to the stack start,
+/// after the callee-saved registers have been spilled onto it so that values
+/// only held in registers are found as well.
+pub struct Stack {
+    stack_start: usize,
+}
+
+impl Stack {
+    /// Creates a stack for the current thread.
+    pub fn new() -> Self {
+        Stack {
+            stack_start: get_stack_start(),
         }
     }
 
-    #[repr(C)]
-    pub struct StackVisitor {}
-
-    impl StackVisitor {
-        pub fn visit_pointer(&mut self, ptr: *const std::ffi::c_void) {
-            // Placeholder function
-            println!("Visiting pointer: {:?}", ptr);
-        }
+    /// Returns the highest address of the stack (stacks grow downwards).
+    pub fn stack_start(&self) -> usize {
+        self.stack_start
     }
 
-    //Dummy struct to represent SuspendTagCheckingScope
-    struct SuspendTagCheckingScope {}
-    
-    // src/base/stack.h (Placeholder for stack functions from v8::base)
-    pub mod v8 {
-        pub mod base {
-            pub mod stack {
-                pub fn get_current_stack_position() -> *const std::ffi::c_void {
-                    std::ptr::null() // Placeholder
-                }
-                pub fn get_stack_start_unchecked() -> *const std::ffi::c_void {
-                    std::ptr::null() // Placeholder
-                }
-            }
-        }
+    /// Returns true if `slot` lies on the active part of the stack.
+    pub fn is_on_stack(&self, slot: usize) -> bool {
+        get_current_stack_position() <= slot && slot < self.stack_start
     }
 
-    // Dummy function - replace with actual FFI call
-    extern "C" {
-        fn push_all_registers_and_iterate_stack(
-            stack: *mut Stack,
-            argument: *mut std::ffi::c_void,
-            callback: IterateStackCallback,
-        );
+    /// Calls `visitor` with every word between the current stack position and
+    /// the stack start. Must be called on the thread that created the stack.
+    #[inline(never)]
+    pub fn iterate_pointers(&self, visitor: &mut dyn FnMut(usize)) {
+        let registers = push_all_registers();
+        // The spilled registers live in this frame; everything between them
+        // and the stack start belongs to callers.
+        let top = registers.as_ptr() as usize;
+        iterate_range(top, self.stack_start, visitor);
+        std::hint::black_box(&registers);
     }
+}
 
-    // Dummy function to disable ASAN
-    macro_rules! disable_asan {
-        () => {
-            #[cfg(feature = "v8_use_address_sanitizer")]
-            {
-                // Intentionally empty
-            }
-        };
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new()
     }
-    use disable_asan;
-    
-    // Dummy function to disable HWASAN
-    macro_rules! disable_hwasan {
-        () => {
-            #[cfg(feature = "v8_use_hwaddress_sanitizer")]
-            {
-                // Intentionally empty
-            }
-        };
+}
+
+#[inline(never)]
+fn iterate_range(top: usize, start: usize, visitor: &mut dyn FnMut(usize)) {
+    const WORD: usize = std::mem::size_of::<usize>();
+    let mut current = (top + WORD - 1) & !(WORD - 1);
+    while current + WORD <= start {
+        // Volatile as the slots belong to frames the compiler knows nothing
+        // about.
+        let value = unsafe { std::ptr::read_volatile(current as *const usize) };
+        visitor(value);
+        current += WORD;
     }
-    use disable_hwasan;
-    
-    // Dummy function to disable TSAN
-    macro_rules! disable_tsan {
-        () => {
-            #[cfg(feature = "v8_use_thread_sanitizer")]
-            {
-                // Intentionally empty
-            }
-        };
+}
+
+/// Returns an approximation of the current stack position.
+#[inline(never)]
+pub fn get_current_stack_position() -> usize {
+    let marker = 0usize;
+    std::hint::black_box(&marker) as *const usize as usize
+}
+
+#[inline(always)]
+fn push_all_registers() -> [usize; NUM_CALLEE_SAVED_REGISTERS] {
+    #[allow(unused_mut)]
+    let mut registers = [0usize; NUM_CALLEE_SAVED_REGISTERS];
+    // If the compiler picks a callee-saved register for the base address, the
+    // caller's value of that register has been saved in this frame's
+    // prologue, which is scanned as well.
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        std::arch::asm!(
+            "mov [{0}], rbx",
+            "mov [{0} + 8], rbp",
+            "mov [{0} + 16], rsi",
+            "mov [{0} + 24], rdi",
+            "mov [{0} + 32], r12",
+            "mov [{0} + 40], r13",
+            "mov [{0} + 48], r14",
+            "mov [{0} + 56], r15",
+            in(reg) registers.as_mut_ptr(),
+            options(nostack, preserves_flags),
+        );
     }
-    use disable_tsan;
-    
-    fn iterate_asan_fake_frame_if_necessary(
-        _visitor: &mut StackVisitor,
-        _segment: &Segment,
-        _address: *const std::ffi::c_void,
-    ) {
-        // Placeholder as ASAN support is complex and requires external library interaction
-        // Implement ASAN fake frame iteration if necessary.
-        // This requires access to ASAN runtime functions.
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        std::arch::asm!(
+            "stp x19, x20, [{0}]",
+            "stp x21, x22, [{0}, #16]",
+            "stp x23, x24, [{0}, #32]",
+            "stp x25, x26, [{0}, #48]",
+            "stp x27, x28, [{0}, #64]",
+            "str x29, [{0}, #80]",
+            in(reg) registers.as_mut_ptr(),
+            options(nostack, preserves_flags),
+        );
+    }
+    registers
+}
+
+#[cfg(target_os = "linux")]
+fn get_stack_start() -> usize {
+    // Large enough for pthread_attr_t on all Linux targets.
+    #[repr(C, align(16))]
+    struct PthreadAttr([u8; 128]);
+
+    unsafe extern "C" {
+        fn pthread_self() -> usize;
+        fn pthread_getattr_np(thread: usize, attr: *mut PthreadAttr) -> i32;
+        fn pthread_attr_getstack(
+            attr: *const PthreadAttr,
+            stackaddr: *mut *mut u8,
+            stacksize: *mut usize,
+        ) -> i32;
+        fn pthread_attr_destroy(attr: *mut PthreadAttr) -> i32;
     }
-    
-    fn iterate_pointers_in_unsafe_stack_if_necessary(
-        _visitor: &mut StackVisitor,
-        _segment: &Segment,
-    ) {
-        #[cfg(feature = "v8_use_safe_stack")]
-        {
-            assert!(!_segment.unsafe_stack_start.is_null());
-            assert!(!_segment.unsafe_stack_top.is_null());
-
-            const K_SAFE_STACK_ALIGNMENT_BYTES: usize = 16;
-            assert!(_segment.unsafe_stack_start as usize >= _segment.unsafe_stack_top as usize);
-            assert_eq!(_segment.unsafe_stack_top as usize & (K_SAFE_STACK_ALIGNMENT_BYTES - 1), 0);
-            assert_eq!(_segment.unsafe_stack_start as usize & (K_SAFE_STACK_ALIGNMENT_BYTES - 1), 0);
 
-            let mut current = _segment.unsafe_stack_top as *const *const std::ffi::c_void;
-            while current < _segment.unsafe_stack_start as *const *const std::ffi::c_void {
-                unsafe {
-                    let address_curr = *current;
-                    if !address_curr.is_null() {
-                        _visitor.visit_pointer(address_curr);
-                    }
-                    current = current.offset(1);
-                }
-            }
+    unsafe {
+        let mut attr = PthreadAttr([0; 128]);
+        if pthread_getattr_np(pthread_self(), &mut attr) != 0 {
+            return fallback_stack_start();
+        }
+        let mut base = std::ptr::null_mut();
+        let mut size = 0;
+        let result = pthread_attr_getstack(&attr, &mut base, &mut size);
+        pthread_attr_destroy(&mut attr);
+        if result != 0 || base.is_null() {
+            return fallback_stack_start();
         }
+        base as usize + size
     }
+}
 
-    // This method should never be inlined to ensure that a possible redzone cannot
-    // contain any data that needs to be scanned.
-    #[inline(never)]
-    disable_asan!();
-    disable_hwasan!();
-    disable_tsan!();
-    fn iterate_pointers_in_stack(visitor: &mut StackVisitor, segment: &Segment) {
-        assert!(!segment.top.is_null());
-        assert!(!segment.start.is_null());
-        assert!(segment.start as usize >= segment.top as usize);
-
-        // All supported platforms should have their stack aligned to at least
-        // sizeof(void*).
-        const K_MIN_STACK_ALIGNMENT: usize = std::mem::size_of::<*const std::ffi::c_void>();
-        assert_eq!(
-            segment.top as usize & (K_MIN_STACK_ALIGNMENT - 1),
-            0,
-            "Stack alignment check failed"
-        );
-        assert_eq!(
-            segment.start as usize & (K_MIN_STACK_ALIGNMENT - 1),
-            0,
-            "Stack alignment check failed"
-        );
-    
-        let mut current = segment.top as *const *const std::ffi::c_void;
-        while current < segment.start as *const *const std::ffi::c_void {
-            unsafe {
-                // MSAN: Instead of unpoisoning the whole stack, the slot's value is copied
-                // into a local which is unpoisoned.
-                let address = *current;
-                //MSAN_MEMORY_IS_INITIALIZED(&address, sizeof(address)); //TODO: Implement MSAN support
-                if !address.is_null() {
-                    visitor.visit_pointer(address);
-                    iterate_asan_fake_frame_if_necessary(visitor, segment, address);
-                }
-                current = current.offset(1);
-            }
-        }
+#[cfg(target_os = "macos")]
+fn get_stack_start() -> usize {
+    unsafe extern "C" {
+        fn pthread_self() -> usize;
+        fn pthread_get_stackaddr_np(thread: usize) -> *mut u8;
     }
-}
\ No newline at end of file
+    unsafe { pthread_get_stackaddr_np(pthread_self()) as usize }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn get_stack_start() -> usize {
+    fallback_stack_start()
+}
+
+/// Without a way to query the thread's stack bounds, the position at which
+/// the stack was created is used. Only frames called after that point are
+/// scanned, so the stack must be created before any frame that holds heap
+/// references.
+#[allow(dead_code)]
+fn fallback_stack_start() -> usize {
+    get_current_stack_position()
+}
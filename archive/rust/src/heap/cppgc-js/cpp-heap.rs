@@ -1,225 +1,440 @@
-// src/heap/cppgc-js/cpp-heap.rs
-
-//use crate::base::logging; // Assuming base::logging is in base.rs
-//use crate::base::macros; // Assuming base::macros is in base.rs
-//use crate::base::platform::time; // Assuming base::platform::time is in base.rs
-//use crate::execution::isolate_inl; // Assuming execution::isolate_inl is in execution.rs
-//use crate::execution::v8threads; // Assuming execution::v8threads is in execution.rs
-//use crate::flags::flags; // Assuming flags::flags is in flags.rs
-//use crate::handles::handles; // Assuming handles::handles is in handles.rs
-//use crate::handles::traced_handles; // Assuming handles::traced_handles is in handles.rs
-//use crate::heap::base::stack; // Assuming heap::base::stack is in heap.rs
-//use crate::heap::cppgc_js::cpp_marking_state; // Assuming heap::cppgc_js::cpp_marking_state is in heap.rs
-//use crate::heap::cppgc_js::cpp_snapshot; // Assuming heap::cppgc_js::cpp_snapshot is in heap.rs
-//use crate::heap::cppgc_js::unified_heap_marking_state_inl; // Assuming heap::cppgc_js::unified_heap_marking_state_inl is in heap.rs
-//use crate::heap::cppgc_js::unified_heap_marking_state; // Assuming heap::cppgc_js::unified_heap_marking_state is in heap.rs
-//use crate::heap::cppgc_js::unified_heap_marking_verifier; // Assuming heap::cppgc_js::unified_heap_marking_verifier is in heap.rs
-//use crate::heap::cppgc_js::unified_heap_marking_visitor; // Assuming heap::cppgc_js::unified_heap_marking_visitor is in heap.rs
-//use crate::heap::cppgc::concurrent_marker; // Assuming heap::cppgc::concurrent_marker is in heap.rs
-//use crate::heap::cppgc::gc_info_table; // Assuming heap::cppgc::gc_info_table is in heap.rs
-//use crate::heap::cppgc::heap_base; // Assuming heap::cppgc::heap_base is in heap.rs
-//use crate::heap::cppgc::heap_object_header; // Assuming heap::cppgc::heap_object_header is in heap.rs
-//use crate::heap::cppgc::marker; // Assuming heap::cppgc::marker is in heap.rs
-//use crate::heap::cppgc::marking_state; // Assuming heap::cppgc::marking_state is in heap.rs
-//use crate::heap::cppgc::marking_visitor; // Assuming heap::cppgc::marking_visitor is in heap.rs
-//use crate::heap::cppgc::metric_recorder; // Assuming heap::cppgc::metric_recorder is in heap.rs
-//use crate::heap::cppgc::object_allocator; // Assuming heap::cppgc::object_allocator is in heap.rs
-//use crate::heap::cppgc::page_memory; // Assuming heap::cppgc::page_memory is in heap.rs
-//use crate::heap::cppgc::platform; // Assuming heap::cppgc::platform is in heap.rs
-//use crate::heap::cppgc::prefinalizer_handler; // Assuming heap::cppgc::prefinalizer_handler is in heap.rs
-//use crate::heap::cppgc::raw_heap; // Assuming heap::cppgc::raw_heap is in heap.rs
-//use crate::heap::cppgc::stats_collector; // Assuming heap::cppgc::stats_collector is in heap.rs
-//use crate::heap::cppgc::sweeper; // Assuming heap::cppgc::sweeper is in heap.rs
-//use crate::heap::cppgc::unmarker; // Assuming heap::cppgc::unmarker is in heap.rs
-//use crate::heap::cppgc::visitor; // Assuming heap::cppgc::visitor is in heap.rs
-//use crate::heap::gc_tracer; // Assuming heap::gc_tracer is in heap.rs
-//use crate::heap::heap; // Assuming heap::heap is in heap.rs
-//use crate::heap::marking_worklist; // Assuming heap::marking_worklist is in heap.rs
-//use crate::heap::minor_mark_sweep; // Assuming heap::minor_mark_sweep is in heap.rs
-//use crate::heap::traced_handles_marking_visitor; // Assuming heap::traced_handles_marking_visitor is in heap.rs
-//use crate::init::v8; // Assuming init::v8 is in init.rs
-//use crate::profiler::heap_profiler; // Assuming profiler::heap_profiler is in profiler.rs
-
-use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::Duration;
-use std::{cmp, mem};
-
-//use v8::{HeapProfiler, Isolate, Platform}; // Assuming v8 crate is used
-//use cppgc::{AllocationHandle, CustomSpaceBase, CustomSpaceIndex, HeapHandle, HeapStatistics, Platform as CppgcPlatform, Visitor}; // Assuming cppgc crate is used
-
-//#[macro_use]
-//extern crate lazy_static;
-
-mod cppgc {
-    pub trait Platform {}
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::cell::{Cell, RefCell};
+
+use crate::heap::cppgc::heap::Heap;
+use crate::heap::cppgc_js::cpp_marking_state::CppMarkingState;
+use crate::heap::cppgc_js::cross_heap_remembered_set::CrossHeapRememberedSet;
+use crate::heap::cppgc_js::unified_heap_marking_state::{UnifiedHeapMarkingState, V8MarkingState};
+use crate::heap::gc_tracer::GarbageCollector;
+use crate::heap::heap::{Heap as V8Heap, HeapObjectTracker};
+use crate::heap::heap_layout::Address;
+use crate::include::cppgc::allocation::AllocationHandle;
+use crate::include::cppgc::common::EmbedderStackState as StackState;
+use crate::include::cppgc::heap::{HeapOptions, MarkingType, StackSupport, SweepingType};
+
+/// Parameters for `CppHeap::create`.
+#[derive(Clone, Copy, Debug)]
+pub struct CppHeapCreateParams {
+    pub marking_support: MarkingType,
+    pub sweeping_support: SweepingType,
 }
 
-mod v8 {
-    pub trait Platform {}
-    pub struct Isolate {}
+impl Default for CppHeapCreateParams {
+    fn default() -> Self {
+        CppHeapCreateParams {
+            marking_support: MarkingType::IncrementalAndConcurrent,
+            sweeping_support: SweepingType::IncrementalAndConcurrent,
+        }
+    }
 }
 
-// Placeholder for CppHeapCreateParams.  Replace with actual definition.
-pub struct CppHeapCreateParams {}
+/// A cppgc heap for objects of an embedder that can be attached to an
+/// isolate's heap.
+///
+/// While attached, garbage collections are driven by V8: the
+/// `MarkCompactCollector` marks both heaps in a single unified cycle. It
+/// marks the wrappables of API objects through `cpp_marking_state()`, and
+/// cppgc marking hands V8 objects referenced through `TracedReference`s to
+/// V8's `MarkingVisitor` in `advance_tracing()`:
+///
+/// ```ignore
+/// cpp_heap.start_tracing();
+/// // Incremental steps, interleaved with V8 marking.
+/// cpp_heap.advance_tracing(budget, &mut visitor);
+/// // Atomic pause.
+/// cpp_heap.enter_final_pause(cpp_heap.embedder_stack_state());
+/// loop {
+///     cpp_heap.advance_tracing(usize::MAX, &mut visitor);
+///     visitor.process_marking_worklist(usize::MAX);
+///     if cpp_heap.is_tracing_done() {
+///         break;
+///     }
+/// }
+/// cpp_heap.finish_tracing();
+/// ```
+pub struct CppHeap {
+    heap: Box<Heap>,
+    isolate_heap: Cell<Option<*const V8Heap>>,
+    embedder_stack_state: Cell<StackState>,
+    unified_heap_marking_state: RefCell<Option<UnifiedHeapMarkingState>>,
+    tracing_drained: Cell<bool>,
+    cross_heap_remembered_set: CrossHeapRememberedSet,
+}
 
-pub mod internal {
-    use super::*;
-    use std::sync::Mutex;
-    // Placeholder structs/enums.  Replace with actual definitions.
-    pub struct HeapBase {}
-    pub struct StatsCollector {}
-    pub struct RawHeap {}
-    pub struct PageAllocator {}
-
-    impl StatsCollector {
-        pub fn register_observer(&mut self, _observer: &MinorGCHeapGrowing) {}
-        pub fn allocated_object_size(&self) -> usize { 0 }
-    }
-
-    pub struct MinorGCHeapGrowing {
-        stats_collector: Mutex<StatsCollector>,
-        initial_heap_size: usize,
-        limit_for_atomic_gc: usize,
-    }
-
-    impl MinorGCHeapGrowing {
-        pub fn new(stats_collector: &mut StatsCollector) -> Self {
-            let mut growing = Self {
-                stats_collector: Mutex::new(stats_collector),
-                initial_heap_size: 1 * 1024 * 1024, // 1MB
-                limit_for_atomic_gc: 0,
-            };
-            //stats_collector.register_observer(&growing);
-            growing
-        }
+impl CppHeap {
+    pub fn create(params: &CppHeapCreateParams) -> Box<CppHeap> {
+        let options = HeapOptions {
+            stack_support: StackSupport::SupportsConservativeStackScan,
+            marking_support: params.marking_support,
+            sweeping_support: params.sweeping_support,
+            ..HeapOptions::default()
+        };
+        Box::new(CppHeap {
+            heap: Heap::create(&options),
+            isolate_heap: Cell::new(None),
+            embedder_stack_state: Cell::new(StackState::MayContainHeapPointers),
+            unified_heap_marking_state: RefCell::new(None),
+            tracing_drained: Cell::new(false),
+            cross_heap_remembered_set: CrossHeapRememberedSet::new(),
+        })
+    }
 
-        pub fn limit_reached(&self) -> bool {
-            //self.stats_collector.lock().unwrap().allocated_object_size() >= self.limit_for_atomic_gc
-            true
-        }
+    pub fn get_allocation_handle(&self) -> &AllocationHandle {
+        AllocationHandle::from_heap(&self.heap)
     }
 
-    impl HeapBase {
-        pub fn collect_statistics(_detail_level: i32) -> i32 { 0 }
+    pub fn heap(&self) -> &Heap {
+        &self.heap
     }
 
-    pub const K_PAGE_SIZE: usize = 4096;
-    pub const K_NUMBER_OF_REGULAR_SPACES: usize = 3; // Placeholder
+    pub fn cross_heap_remembered_set(&self) -> &CrossHeapRememberedSet {
+        &self.cross_heap_remembered_set
+    }
 
-    // Placeholder for CppHeap.  Replace with actual definition.
-    pub struct CppHeap {
-        platform: Box<dyn cppgc::Platform>,
-        //custom_spaces: Vec<Box<dyn CustomSpaceBase>>,
-        //marking_support: MarkingType,
-        //sweeping_support: SweepingType,
-        //stats_collector: StatsCollector,
-        minor_gc_heap_growing: MinorGCHeapGrowing,
+    /// Attaches the heap to the heap of an isolate, which drives garbage
+    /// collections from then on. A collection of the heap alone that is in
+    /// progress is finished first. `isolate_heap` must not be marking.
+    ///
+    /// # Safety
+    ///
+    /// `isolate_heap` must stay valid until the heap is detached again,
+    /// which happens at the latest when the `CppHeap` is dropped.
+    pub unsafe fn attach_isolate(&self, isolate_heap: &V8Heap) {
+        assert!(self.isolate_heap.get().is_none(), "CppHeap is already attached");
+        self.heap
+            .finalize_garbage_collection(StackState::MayContainHeapPointers);
+        isolate_heap.attach_cpp_heap(self);
+        // SAFETY: the tracker is removed in `detach_isolate()`, which runs
+        // before the `CppHeap` goes away.
+        unsafe { isolate_heap.add_object_tracker(self as &dyn HeapObjectTracker) };
+        self.heap.set_driven_by_embedder(true);
+        self.isolate_heap.set(Some(isolate_heap));
     }
 
-    impl CppHeap {
-        pub fn new(platform: Box<dyn cppgc::Platform>, _custom_spaces: Vec<Box<dyn CustomSpaceBase>>, _marking_support: i32, _sweeping_support: i32) -> Self {
-            let mut stats_collector = StatsCollector {};
-            let minor_gc_heap_growing = MinorGCHeapGrowing::new(&mut stats_collector);
-            Self {
-                platform,
-                //custom_spaces,
-                //marking_support,
-                //sweeping_support,
-                //stats_collector,
-                minor_gc_heap_growing,
-            }
-        }
+    /// Detaches the heap from its isolate. Must not be called during a
+    /// unified garbage collection.
+    pub fn detach_isolate(&self) {
+        let Some(isolate_heap) = self.isolate_heap.take() else {
+            panic!("CppHeap is not attached");
+        };
+        assert!(
+            self.unified_heap_marking_state.borrow().is_none(),
+            "CppHeap may not be detached during a unified garbage collection"
+        );
+        // SAFETY: guaranteed by the caller of `attach_isolate()`.
+        let isolate_heap = unsafe { &*isolate_heap };
+        isolate_heap.remove_object_tracker(self as &dyn HeapObjectTracker);
+        isolate_heap.detach_cpp_heap();
+        self.heap.set_driven_by_embedder(false);
+        self.cross_heap_remembered_set.reset();
+    }
 
-        pub fn from(_heap: &super::CppHeap) -> &Self {
-            unimplemented!()
-        }
+    pub fn is_attached(&self) -> bool {
+        self.isolate_heap.get().is_some()
+    }
 
-        pub fn object_allocator(&self) -> i32 { 0 }
+    /// The stack state assumed by the atomic pauses of unified garbage
+    /// collections. Embedders that know that the stack holds no pointers
+    /// to cppgc objects, e.g. when collecting from a task, may set
+    /// `NoHeapPointers` to avoid the conservative stack scan.
+    pub fn set_embedder_stack_state(&self, stack_state: StackState) {
+        self.embedder_stack_state.set(stack_state);
+    }
 
-        pub fn as_base(&self) -> &HeapBase {
-            unimplemented!()
-        }
+    pub fn embedder_stack_state(&self) -> StackState {
+        self.embedder_stack_state.get()
+    }
+
+    /// Returns true if the heap grew enough that V8 should start a unified
+    /// garbage collection. Polled by V8 on allocation.
+    pub fn should_request_garbage_collection(&self) -> bool {
+        self.is_attached() && !self.heap.is_marking() && self.heap.is_allocation_limit_reached()
+    }
+
+    /// Starts the cppgc side of a unified marking cycle.
+    pub fn start_tracing(&self) {
+        assert!(self.is_attached(), "unified marking requires an attached CppHeap");
+        self.heap
+            .start_garbage_collection(MarkingType::IncrementalAndConcurrent);
+        self.tracing_drained.set(false);
+        *self.unified_heap_marking_state.borrow_mut() = Some(UnifiedHeapMarkingState::new(
+            self.heap.marking_worklists().clone(),
+        ));
+    }
 
-        pub fn terminate(&mut self) {}
+    /// Returns the state through which V8's marker marks cppgc objects
+    /// referenced from wrappers.
+    pub fn cpp_marking_state(&self) -> CppMarkingState {
+        CppMarkingState::new(&self.heap)
+    }
 
-        pub fn collect_custom_space_statistics_at_last_gc(
-            &self,
-            _custom_spaces: Vec<i32>,
-            _receiver: i32,
-        ) {
-            unimplemented!()
+    /// Traces roughly `byte_budget` bytes of cppgc objects and hands the V8
+    /// objects found to `v8_marking_state`, usually V8's `MarkingVisitor`.
+    /// Returns `is_tracing_done()`.
+    pub fn advance_tracing(&self, byte_budget: usize, v8_marking_state: &mut dyn V8MarkingState) -> bool {
+        let drained = self.heap.advance_marking(byte_budget);
+        self.tracing_drained.set(drained);
+        if let Some(state) = &*self.unified_heap_marking_state.borrow() {
+            state.process_traced_references(v8_marking_state);
         }
+        self.is_tracing_done()
+    }
 
-        pub fn enable_detached_garbage_collections_for_testing(&self) {}
+    /// Returns true if cppgc marking has no work left, neither for itself
+    /// nor for V8. V8 may add work by marking wrappables.
+    pub fn is_tracing_done(&self) -> bool {
+        self.tracing_drained.get()
+            && self.heap.marking_worklists().is_empty()
+            && self
+                .unified_heap_marking_state
+                .borrow()
+                .as_ref()
+                .is_none_or(UnifiedHeapMarkingState::is_empty)
+    }
 
-        pub fn collect_garbage_for_testing(&self, _collection_type: i32, _stack_state: i32) {}
+    /// Enters the atomic pause of the unified cycle. Tracing continues
+    /// through `advance_tracing()` until V8 and cppgc reach a fixpoint.
+    pub fn enter_final_pause(&self, stack_state: StackState) {
+        self.heap.enter_final_pause(stack_state);
+        self.tracing_drained.set(false);
+    }
 
-        // Dummy implementations for methods used but not fully implemented.
-        pub fn from_mut(_heap: &mut super::CppHeap) -> &mut Self {
-            unimplemented!()
-        }
+    /// Finishes the cppgc side of a unified cycle once the fixpoint is
+    /// reached: clears weak references, invokes prefinalizers and starts
+    /// sweeping.
+    pub fn finish_tracing(&self) {
+        let state = self.unified_heap_marking_state.borrow_mut().take();
+        debug_assert!(state.as_ref().is_none_or(UnifiedHeapMarkingState::is_empty));
+        self.heap.finish_garbage_collection();
+        drop(state);
+    }
 
-        pub fn as_base_mut(&mut self) -> &mut HeapBase {
-            unimplemented!()
+    /// Performs a garbage collection of the cppgc heap alone. While
+    /// attached, remembered references from V8 wrappers are treated as
+    /// roots and V8 objects are not collected.
+    pub fn collect_garbage_for_testing(&self, stack_state: StackState) {
+        assert!(
+            self.unified_heap_marking_state.borrow().is_none(),
+            "a unified garbage collection is in progress"
+        );
+        if !self.heap.is_marking() {
+            self.heap.start_garbage_collection(MarkingType::Atomic);
         }
+        if self.is_attached() {
+            let cpp_marking_state = self.cpp_marking_state();
+            self.cross_heap_remembered_set
+                .visit(|_, cppgc_object| cpp_marking_state.mark_and_push(cppgc_object));
+        }
+        self.heap.finalize_garbage_collection(stack_state);
+        // Without V8 marking, references into the V8 heap are not followed.
+        self.heap.marking_worklists().take_traced_references();
+        self.heap.finish_sweeping();
     }
 }
 
-pub struct CppHeap {
-    inner: Arc<Mutex<internal::CppHeap>>,
+/// Follows the wrappers of the remembered V8 to cppgc references across
+/// V8's garbage collections.
+impl HeapObjectTracker for CppHeap {
+    fn tracked_objects(&self) -> Vec<Address> {
+        self.cross_heap_remembered_set.hosts()
+    }
+
+    fn on_object_moved(&self, from: Address, to: Address) {
+        self.cross_heap_remembered_set.update_host(from, to);
+    }
+
+    fn on_garbage_collection(&self, _collector: GarbageCollector, is_live: &dyn Fn(Address) -> bool) {
+        self.cross_heap_remembered_set.reset_dead(is_live);
+    }
 }
 
-impl CppHeap {
-    pub fn create(platform: Box<dyn v8::Platform>, params: &CppHeapCreateParams) -> Self {
-        let cpp_heap = internal::CppHeap::new(
-            platform as Box<dyn cppgc::Platform>,
-            Vec::new(), // params.custom_spaces
-            0,           // params.marking_support
-            0,           // params.sweeping_support
-        );
-        CppHeap {
-            inner: Arc::new(Mutex::new(cpp_heap)),
+impl Drop for CppHeap {
+    fn drop(&mut self) {
+        // Uninstall the traced reference barrier before the heap goes away.
+        self.unified_heap_marking_state.borrow_mut().take();
+        if self.is_attached() {
+            self.detach_isolate();
         }
     }
+}
 
-    pub fn get_allocation_handle(&self) -> i32 { 0 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    use crate::heap::gc_tracer::GarbageCollectionReason;
+    use crate::heap::heap::{AllocationType, HeapOptions, RootHandle};
+    use crate::heap::heap_layout::{HeapLayout, HeapObject, Tagged};
+    use crate::heap::memory_chunk::AllocationSpace;
+    use crate::include::cppgc::allocation::make_garbage_collected;
+    use crate::include::cppgc::garbage_collected::GarbageCollected;
+    use crate::include::cppgc::member::Member;
+    use crate::include::cppgc::trace_trait::Trace;
+    use crate::include::cppgc::visitor::Visitor;
+    use crate::include::v8_traced_handle::v8_traced_handle::TracedReference;
+
+    /// A cppgc object pointing back into the V8 heap.
+    struct Wrappable {
+        wrapper: TracedReference<()>,
+        child: Member<Wrappable>,
+        drops: Rc<Cell<usize>>,
+    }
+
+    impl Trace for Wrappable {
+        fn trace(&self, visitor: &mut Visitor<'_>) {
+            visitor.trace(&self.wrapper);
+            visitor.trace(&self.child);
+        }
+    }
 
-    pub fn get_heap_handle(&self) -> i32 { 0 }
+    impl GarbageCollected for Wrappable {}
 
-    pub fn terminate(&self) {
-        let mut inner = self.inner.lock().unwrap();
-        inner.terminate();
+    impl Drop for Wrappable {
+        fn drop(&mut self) {
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    fn new_isolate_heap() -> V8Heap {
+        V8Heap::new(HeapOptions {
+            concurrent_marking: false,
+            concurrent_sweeping: false,
+            parallel_compaction: false,
+            verify_heap: true,
+            ..HeapOptions::default()
+        })
     }
 
-    pub fn collect_statistics(&self, detail_level: i32) -> i32 {
-        let inner = self.inner.lock().unwrap();
-        internal::CppHeap::from(self).as_base().collect_statistics(detail_level)
+    fn attached_heap(isolate_heap: &V8Heap) -> Box<CppHeap> {
+        let cpp_heap = CppHeap::create(&CppHeapCreateParams::default());
+        // SAFETY: the tests drop the `CppHeap` before the isolate's heap.
+        unsafe { cpp_heap.attach_isolate(isolate_heap) };
+        cpp_heap.set_embedder_stack_state(StackState::NoHeapPointers);
+        cpp_heap
     }
 
-    pub fn collect_custom_space_statistics_at_last_gc(
-        &self,
-        custom_spaces: Vec<i32>,
-        receiver: i32,
-    ) {
-        let inner = self.inner.lock().unwrap();
-        internal::CppHeap::from(self).collect_custom_space_statistics_at_last_gc(
-            custom_spaces,
-            receiver,
+    fn full_gc(isolate_heap: &V8Heap, cpp_heap: &CppHeap) {
+        isolate_heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kTesting);
+        cpp_heap.heap().finish_sweeping();
+    }
+
+    /// Creates an API object wrapping a new wrappable that refers back to
+    /// it.
+    fn wrap(
+        isolate_heap: &V8Heap,
+        cpp_heap: &CppHeap,
+        allocation: AllocationType,
+        drops: &Rc<Cell<usize>>,
+    ) -> (HeapObject, Member<Wrappable>) {
+        let wrapper = isolate_heap.allocate_js_api_object(1, allocation);
+        let wrappable = make_garbage_collected(
+            cpp_heap.get_allocation_handle(),
+            Wrappable {
+                wrapper: TracedReference::new(isolate_heap, wrapper),
+                child: Member::null(),
+                drops: drops.clone(),
+            },
         );
+        isolate_heap.set_wrappable(wrapper, wrappable.as_ptr() as *const u8);
+        (wrapper, wrappable)
+    }
+
+    /// Builds root -> W1 => A -> B -> W2 => C and an unreachable cycle
+    /// W3 => D -> W3 spanning both heaps, where => are references to
+    /// wrappables and -> other references. W2 is young. Returns the root
+    /// and C.
+    #[inline(never)]
+    fn build_graph(isolate_heap: &V8Heap, cpp_heap: &CppHeap, drops: &Rc<Cell<usize>>) -> (RootHandle, *const u8) {
+        let (w1, a) = wrap(isolate_heap, cpp_heap, AllocationType::kOld, drops);
+        // The wrapper of B dies.
+        let (_, b) = wrap(isolate_heap, cpp_heap, AllocationType::kOld, drops);
+        let (w2, c) = wrap(isolate_heap, cpp_heap, AllocationType::kYoung, drops);
+        a.get().unwrap().child.set(b.get());
+        b.get().unwrap().wrapper.set(isolate_heap, Some(w2));
+        wrap(isolate_heap, cpp_heap, AllocationType::kOld, drops);
+        (isolate_heap.create_root(Tagged::strong(w1)), c.as_ptr() as *const u8)
     }
 
-    pub fn enable_detached_garbage_collections_for_testing(&self) {
-        let inner = self.inner.lock().unwrap();
-        internal::CppHeap::from(self).enable_detached_garbage_collections_for_testing();
+    fn wrappable_of(isolate_heap: &V8Heap, wrapper: HeapObject) -> &Wrappable {
+        let wrappable = isolate_heap.wrappable(wrapper).expect("no wrappable");
+        // SAFETY: the tests only wrap `Wrappable`s.
+        unsafe { &*(wrappable as *const Wrappable) }
     }
 
-    pub fn collect_garbage_for_testing(&self, stack_state: i32) {
-        let inner = self.inner.lock().unwrap();
-        internal::CppHeap::from(self).collect_garbage_for_testing(0, stack_state);
+    #[test]
+    fn unified_marking_traces_across_heaps() {
+        let isolate_heap = new_isolate_heap();
+        let cpp_heap = attached_heap(&isolate_heap);
+        let drops = Rc::new(Cell::new(0));
+        let (root, c) = build_graph(&isolate_heap, &cpp_heap, &drops);
+        full_gc(&isolate_heap, &cpp_heap);
+        assert_eq!(drops.get(), 1);
+
+        let a = wrappable_of(&isolate_heap, isolate_heap.root_object(root));
+        let b = a.child.get().unwrap();
+        // W2 was promoted; the traced reference and the remembered set
+        // follow it.
+        let w2 = b.wrapper.get().unwrap();
+        assert!(!HeapLayout::in_young_generation(w2));
+        assert_eq!(isolate_heap.wrappable(w2), Some(c));
+        let hosts = cpp_heap.cross_heap_remembered_set().hosts();
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts.contains(&w2.address()));
     }
 
-    pub fn collect_garbage_in_young_generation_for_testing(&self, stack_state: i32) {
-        let inner = self.inner.lock().unwrap();
-        internal::CppHeap::from(self).collect_garbage_for_testing(1, stack_state);
+    #[test]
+    fn traced_reference_barrier_marks_v8_objects() {
+        let isolate_heap = new_isolate_heap();
+        let cpp_heap = attached_heap(&isolate_heap);
+        let drops = Rc::new(Cell::new(0));
+        let (wrapper, holder) = wrap(&isolate_heap, &cpp_heap, AllocationType::kOld, &drops);
+        isolate_heap.create_root(Tagged::strong(wrapper));
+        // Traced handles are no roots while a `CppHeap` is attached.
+        let target = TracedReference::<()>::new(&isolate_heap, isolate_heap.allocate_fixed_array(1, AllocationType::kOld));
+
+        let incremental_marking = isolate_heap.incremental_marking();
+        incremental_marking.start(&isolate_heap, GarbageCollectionReason::kTesting);
+        while !incremental_marking.is_complete() {
+            incremental_marking.step(&isolate_heap, usize::MAX);
+        }
+        // The holder was traced already; the barrier reports the new target.
+        holder.get().unwrap().wrapper.set(&isolate_heap, target.get());
+        drop(target);
+        full_gc(&isolate_heap, &cpp_heap);
+
+        let object = holder.get().unwrap().wrapper.get().expect("target was collected");
+        assert_eq!(object.map().name(), "FixedArray");
+        assert_eq!(drops.get(), 0);
+    }
+
+    #[test]
+    fn remembered_references_are_roots_for_cppgc_only_collections() {
+        let isolate_heap = new_isolate_heap();
+        let cpp_heap = attached_heap(&isolate_heap);
+        let drops = Rc::new(Cell::new(0));
+        build_graph(&isolate_heap, &cpp_heap, &drops);
+        cpp_heap.collect_garbage_for_testing(StackState::NoHeapPointers);
+        assert_eq!(drops.get(), 0);
+        full_gc(&isolate_heap, &cpp_heap);
+        assert_eq!(drops.get(), 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn traced_references_are_roots_without_cpp_heap() {
+        let isolate_heap = new_isolate_heap();
+        let reference = TracedReference::<()>::new(
+            &isolate_heap,
+            isolate_heap.allocate_fixed_array(1, AllocationType::kYoung),
+        );
+        isolate_heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kTesting);
+        let object = reference.get().expect("target was collected");
+        assert!(!HeapLayout::in_young_generation(object));
+        drop(reference);
+        isolate_heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kTesting);
+        assert_eq!(isolate_heap.traced_handles().used_size(), 0);
+    }
+}
@@ -1,54 +1,40 @@
-// Copyright 2021 the V8 project authors. All rights reserved.
+// Copyright 2022 the V8 project authors. All rights reserved.
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod cpp_marking_state {
-    use std::ptr::NonNull;
-    //use crate::heap::cppgc_js::cpp_heap::CppHeap; // Assuming this exists in your crate
-    use crate::heap::cppgc::marking_state::MarkingStateBase;
-    use crate::heap::cppgc::marking_worklists::MarkingWorklists;
-    //use crate::objects::embedder_data_slot::EmbedderDataSlot; // Assuming this exists in your crate
-
-    // Placeholder for JSObject and EmbedderDataSlot.  These would need
-    // more complete definitions if they are actually used beyond name.
-    pub struct JSObject {}
-    pub struct EmbedderDataSlot {}
-
-    pub struct CppMarkingState<'a> {
-        owned_marking_state_: Option<Box<MarkingStateBase>>,
-        marking_state_: &'a MarkingStateBase,
-    }
-
-    impl<'a> CppMarkingState<'a> {
-        pub fn new_from_base(main_thread_marking_state: &'a mut MarkingStateBase) -> Self {
-            CppMarkingState {
-                owned_marking_state_: None,
-                marking_state_: main_thread_marking_state,
-            }
-        }
-
-        pub fn new_from_owned(concurrent_marking_state: Box<MarkingStateBase>) -> Self {
-            CppMarkingState {
-                owned_marking_state_: Some(concurrent_marking_state),
-                marking_state_: unsafe { &*NonNull::from(concurrent_marking_state.as_ref()).as_ptr() },
-            }
-        }
-
-        pub fn publish(&self) {
-            self.marking_state_.publish();
-        }
-
-        pub fn mark_and_push(&self, instance: *mut std::ffi::c_void) {
-            // Implementation here requires access to internal details
-            // of MarkingStateBase and MarkingWorklists, which aren't
-            // fully defined in the provided C++ header.  This is a
-            // placeholder.
-            // self.marking_state_.marking_worklist().push(instance);
-            unimplemented!()
+use std::sync::Arc;
+
+use crate::heap::cppgc::heap::Heap;
+use crate::heap::cppgc::heap_page::BasePage;
+use crate::heap::cppgc::marking_worklists::MarkingWorklists;
+
+/// Used by V8's marker to mark cppgc objects referenced from V8 wrapper
+/// objects. Safe to use from concurrent marking threads.
+#[derive(Clone)]
+pub struct CppMarkingState {
+    heap: usize,
+    worklists: Arc<MarkingWorklists>,
+}
+
+impl CppMarkingState {
+    pub fn new(heap: &Heap) -> Self {
+        CppMarkingState {
+            heap: heap as *const Heap as usize,
+            worklists: heap.marking_worklists().clone(),
         }
+    }
 
-        pub fn is_local_empty(&self) -> bool {
-            self.marking_state_.marking_worklist().is_local_empty()
+    /// Marks the object `wrappable` of this heap and pushes it for tracing.
+    pub fn mark_and_push(&self, wrappable: *const u8) {
+        let header = BasePage::try_from_address(wrappable as usize)
+            .filter(|page| page.heap_ptr() as usize == self.heap)
+            .and_then(|page| page.try_object_header_from_inner_address(wrappable as usize))
+            .filter(|header| std::ptr::eq(header.object_start(), wrappable));
+        let Some(header) = header else {
+            panic!("{wrappable:p} is not an object of the attached CppHeap");
+        };
+        if header.try_mark_atomic() {
+            self.worklists.push(header.address());
         }
     }
-}
\ No newline at end of file
+}
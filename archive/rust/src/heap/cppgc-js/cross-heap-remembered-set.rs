@@ -2,98 +2,90 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/cppgc-js/cross-heap-remembered-set.h
-
 use std::cell::RefCell;
-use std::rc::Rc;
 
-pub(crate) mod cppgc {
-    pub(crate) mod internal {
-        pub struct HeapBase {} // Placeholder
-    }
+use crate::heap::heap_layout::Address;
+
+/// Remembers V8 to cppgc references, i.e. V8 wrapper objects and the cppgc
+/// objects they wrap.
+///
+/// Garbage collections of the cppgc heap alone cannot see the V8 heap and
+/// treat the remembered references as roots. V8 records references when
+/// wrappers are initialized, see `Heap::set_wrappable()`; the `CppHeap`
+/// follows moves of the wrappers and drops those of dead wrappers after
+/// V8's garbage collections.
+pub struct CrossHeapRememberedSet {
+    remembered_v8_to_cppgc_references: RefCell<Vec<(Address, *const u8)>>,
 }
 
-pub mod internal {
-    pub struct JSObject {} // Placeholder
-
-    // Placeholder for Tagged<JSObject>
-    pub struct TaggedJSObject {}
-
-    impl TaggedJSObject {
-        pub fn new() -> Self {
-            TaggedJSObject {}
+impl CrossHeapRememberedSet {
+    pub fn new() -> Self {
+        CrossHeapRememberedSet {
+            remembered_v8_to_cppgc_references: RefCell::new(Vec::new()),
         }
     }
 
-    pub struct Isolate {} // Placeholder
-
-    impl Isolate {
-        pub fn new() -> Self {
-            Isolate {}
+    /// Records that the V8 object `host_obj` references `cppgc_object`.
+    pub fn remember_reference_if_needed(&self, host_obj: Address, cppgc_object: *const u8) {
+        let mut references = self.remembered_v8_to_cppgc_references.borrow_mut();
+        if !references.contains(&(host_obj, cppgc_object)) {
+            references.push((host_obj, cppgc_object));
         }
     }
 
-    // Placeholder for IndirectHandle<JSObject>
-    pub struct IndirectHandle<T> {
-        pub(crate) obj: Rc<RefCell<T>>,
+    /// Forgets all references of `host_obj`, e.g. when its wrappable is
+    /// replaced.
+    pub fn forget_references_of(&self, host_obj: Address) {
+        self.remembered_v8_to_cppgc_references
+            .borrow_mut()
+            .retain(|&(host, _)| host != host_obj);
     }
 
-    impl<T> IndirectHandle<T> {
-        pub fn new(obj: Rc<RefCell<T>>) -> Self {
-            IndirectHandle { obj }
-        }
-        pub fn get(&self) -> Rc<RefCell<T>> {
-            self.obj.clone()
+    /// Records that the V8 object `from` was moved to `to`.
+    pub fn update_host(&self, from: Address, to: Address) {
+        for (host, _) in self.remembered_v8_to_cppgc_references.borrow_mut().iter_mut() {
+            if *host == from {
+                *host = to;
+            }
         }
     }
 
-    /// The class is used to remember V8 to Oilpan references.
-    pub struct CrossHeapRememberedSet {
-        heap_base: *mut cppgc::internal::HeapBase, // Raw pointer to HeapBase
-        // The vector keeps handles to remembered V8 objects that have outgoing
-        // references to the cppgc heap. Please note that the handles are global.
-        remembered_v8_to_cppgc_references: Vec<IndirectHandle<JSObject>>,
+    /// Returns the remembered hosts.
+    pub fn hosts(&self) -> Vec<Address> {
+        self.remembered_v8_to_cppgc_references
+            .borrow()
+            .iter()
+            .map(|&(host, _)| host)
+            .collect()
     }
 
-    impl CrossHeapRememberedSet {
-        pub fn new(heap_base: &mut cppgc::internal::HeapBase) -> Self {
-            CrossHeapRememberedSet {
-                heap_base: heap_base,
-                remembered_v8_to_cppgc_references: Vec::new(),
-            }
-        }
-
-        // Disabled copy and move constructors
-        // CrossHeapRememberedSet(const CrossHeapRememberedSet&) = delete;
-        // CrossHeapRememberedSet(CrossHeapRememberedSet&&) = delete;
+    /// Drops the references of hosts for which `is_live` returns false.
+    pub fn reset_dead(&self, mut is_live: impl FnMut(Address) -> bool) {
+        self.remembered_v8_to_cppgc_references
+            .borrow_mut()
+            .retain(|&(host, _)| is_live(host));
+    }
 
-        pub fn remember_reference_if_needed(
-            &mut self,
-            _isolate: &mut Isolate,
-            host_obj: TaggedJSObject,
-            _cppgc_object: *mut std::ffi::c_void,
-        ) {
-            // For now, create a dummy JSObject and store it
-            let js_obj = Rc::new(RefCell::new(JSObject {}));
-            self.remembered_v8_to_cppgc_references
-                .push(IndirectHandle::new(js_obj));
-        }
+    pub fn reset(&self) {
+        self.remembered_v8_to_cppgc_references.borrow_mut().clear();
+    }
 
-        pub fn reset(&mut self, _isolate: &mut Isolate) {
-            self.remembered_v8_to_cppgc_references.clear();
+    /// Calls `f` with every remembered host and the cppgc object it
+    /// references.
+    pub fn visit(&self, mut f: impl FnMut(Address, *const u8)) {
+        let references = self.remembered_v8_to_cppgc_references.borrow().clone();
+        for (host, cppgc_object) in references {
+            f(host, cppgc_object);
         }
+    }
 
-        pub fn visit<F>(&self, _isolate: &mut Isolate, mut f: F)
-        where
-            F: FnMut(&JSObject),
-        {
-            for obj in &self.remembered_v8_to_cppgc_references {
-                f(&obj.obj.borrow());
-            }
-        }
+    pub fn is_empty(&self) -> bool {
+        self.remembered_v8_to_cppgc_references.borrow().is_empty()
+    }
+}
 
-        pub fn is_empty(&self) -> bool {
-            self.remembered_v8_to_cppgc_references.is_empty()
-        }
+impl Default for CrossHeapRememberedSet {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
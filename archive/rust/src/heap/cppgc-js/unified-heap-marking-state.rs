@@ -2,121 +2,76 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// TODO(you): Add necessary crates and features if needed.
-// Example:
-// extern crate some_crate;
-
-pub mod unified_heap_marking_state {
-    use std::ptr::NonNull;
-
-    // Placeholder for v8-cppgc crate, replace with actual crate if available
-    pub mod cppgc {
-        pub mod internal {
-            #[derive(Debug, Copy, Clone)]
-            pub enum CollectionType {
-                Normal,
-                Precise,
-            }
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crate::heap::cppgc::marking_worklists::MarkingWorklists;
+use crate::heap::cppgc_js::unified_heap_marking_visitor::UnifiedHeapMarkingVisitor;
+use crate::heap::heap_layout::{Address, HeapObject};
+
+/// The V8 side of unified marking, implemented by V8's `MarkingVisitor`.
+pub trait V8MarkingState {
+    /// Marks `object`, which was reached from a cppgc object, and pushes
+    /// it onto V8's marking worklist.
+    fn mark_and_push(&mut self, object: HeapObject);
+}
+
+thread_local! {
+    /// Worklists of the `CppHeap` on this thread that is part of a unified
+    /// marking cycle, if any.
+    static UNIFIED_MARKING_WORKLISTS: RefCell<Option<Arc<MarkingWorklists>>> =
+        const { RefCell::new(None) };
+}
+
+/// Marking barrier for `TracedReference` stores: while a unified marking
+/// cycle is running, the traced node of a newly stored reference is handed
+/// to the V8 marker as its holder may already have been traced.
+pub fn traced_reference_barrier(node: Address) {
+    UNIFIED_MARKING_WORKLISTS.with(|worklists| {
+        if let Some(worklists) = &*worklists.borrow() {
+            worklists.push_traced_reference(node);
         }
+    });
+}
+
+/// Forwards V8 objects reached through `TracedReference`s from cppgc
+/// marking to the V8 marker. Installs the traced reference barrier for as
+/// long as it is alive.
+pub struct UnifiedHeapMarkingState {
+    worklists: Arc<MarkingWorklists>,
+}
+
+impl UnifiedHeapMarkingState {
+    pub fn new(worklists: Arc<MarkingWorklists>) -> Self {
+        UNIFIED_MARKING_WORKLISTS.with(|current| {
+            let mut current = current.borrow_mut();
+            assert!(
+                current.is_none(),
+                "only one unified marking cycle may run per thread"
+            );
+            *current = Some(worklists.clone());
+        });
+        UnifiedHeapMarkingState { worklists }
     }
 
-    // Placeholder for handles
-    pub mod handles {
-        #[derive(Debug, Copy, Clone)]
-        pub enum MarkMode {
-            Normal,
-            Incremental,
+    /// Hands the targets of all traced references found so far to
+    /// `v8_marking_state`. Returns true if there were any.
+    pub fn process_traced_references(&self, v8_marking_state: &mut dyn V8MarkingState) -> bool {
+        let nodes = self.worklists.take_traced_references();
+        let mut visitor = UnifiedHeapMarkingVisitor::new(v8_marking_state);
+        for &node in &nodes {
+            visitor.visit_traced_reference(node);
         }
+        !nodes.is_empty()
     }
 
-    // Placeholder for heap
-    pub mod heap {
-        pub struct Heap {}
-
-        impl Heap {
-            pub fn new() -> Self {
-                Heap {}
-            }
-        }
-
-        pub mod mark_compact {
-            pub struct MarkingState {}
-
-            impl MarkingState {
-                pub fn new() -> Self {
-                    MarkingState {}
-                }
-            }
-        }
-
-        pub mod marking_worklist {
-            pub struct Local {}
-
-            impl Local {
-                pub fn new() -> Self {
-                    Local {}
-                }
-            }
-        }
+    pub fn is_empty(&self) -> bool {
+        self.worklists.is_traced_references_worklist_empty()
     }
+}
 
-    use self::handles::MarkMode;
-    use self::heap::{Heap, mark_compact::MarkingState, marking_worklist::Local};
-    use self::cppgc::internal::CollectionType;
-
-    /// `UnifiedHeapMarkingState` is used to handle `TracedReferenceBase` and
-    /// friends. It is used when `CppHeap` is attached but also detached. In detached
-    /// mode, the expectation is that no non-null `TracedReferenceBase` is found.
-    pub struct UnifiedHeapMarkingState {
-        heap: *mut Heap,
-        marking_state: *mut MarkingState,
-        local_marking_worklist: *mut Local,
-        mark_mode: MarkMode,
+impl Drop for UnifiedHeapMarkingState {
+    fn drop(&mut self) {
+        UNIFIED_MARKING_WORKLISTS.with(|current| *current.borrow_mut() = None);
     }
-
-    impl UnifiedHeapMarkingState {
-        /// Constructor for `UnifiedHeapMarkingState`.
-        pub fn new(
-            heap: *mut Heap,
-            local_marking_worklist: *mut Local,
-            collection_type: CollectionType,
-        ) -> Self {
-            let marking_state = Box::into_raw(Box::new(MarkingState::new()));
-
-            UnifiedHeapMarkingState {
-                heap: heap,
-                marking_state: marking_state,
-                local_marking_worklist: local_marking_worklist,
-                mark_mode: MarkMode::Normal,
-            }
-        }
-
-        /// Updates the marking worklist.
-        pub fn update(&mut self, local_marking_worklist: *mut Local) {
-            self.local_marking_worklist = local_marking_worklist;
-        }
-
-        /// Marks and pushes a traced reference.
-        pub fn mark_and_push(&self, traced_reference: &TracedReferenceBase) {
-            // TODO(you): Implement the marking and pushing logic here.
-            // This is a placeholder.
-            println!("Marking and pushing traced reference: {:?}", traced_reference);
-        }
-
-        /// Returns a raw pointer to the heap.
-        pub fn heap(&self) -> *mut Heap {
-            self.heap
-        }
-    }
-
-    impl Drop for UnifiedHeapMarkingState {
-        fn drop(&mut self) {
-            unsafe {
-                drop(Box::from_raw(self.marking_state));
-            }
-        }
-    }
-
-    #[derive(Debug)]
-    pub struct TracedReferenceBase {}
-}
\ No newline at end of file
+}
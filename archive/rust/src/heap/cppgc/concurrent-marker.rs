@@ -2,165 +2,83 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod concurrent_marker {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::{Arc, Mutex};
-
-    // Placeholder for cppgc::Platform
-    pub struct Platform {}
-
-    // Placeholder for JobHandle
-    pub struct JobHandle {}
-
-    // Placeholder for IncrementalMarkingSchedule
-    pub struct IncrementalMarkingSchedule {}
-
-    // Placeholder for HeapBase
-    pub struct HeapBase {}
-
-    // Placeholder for MarkingWorklists
-    pub struct MarkingWorklists {}
-
-    // Placeholder for Visitor
-    pub trait Visitor {}
-
-    // Placeholder for ConcurrentMarkingState
-    pub struct ConcurrentMarkingState {}
-
-    #[derive(Debug, Clone, Copy)]
-    pub enum TaskPriority {
-        Low,
-        Normal,
-        High,
-    }
-
-    pub struct ConcurrentMarkerBase {
-        heap_: Arc<HeapBase>,
-        marking_worklists_: Arc<Mutex<MarkingWorklists>>,
-        incremental_marking_schedule_: Arc<Mutex<IncrementalMarkingSchedule>>,
-        platform_: *mut Platform, // Raw pointer because Platform is opaque
-        concurrent_marking_handle_: Mutex<Option<Box<JobHandle>>>,
-        concurrently_marked_bytes_: AtomicUsize,
-        concurrent_marking_priority_increased_: Mutex<bool>,
-    }
-
-    impl ConcurrentMarkerBase {
-        pub fn new(
-            heap: &Arc<HeapBase>,
-            marking_worklists: &Arc<Mutex<MarkingWorklists>>,
-            incremental_marking_schedule: &Arc<Mutex<IncrementalMarkingSchedule>>,
-            platform: *mut Platform,
-        ) -> Self {
-            ConcurrentMarkerBase {
-                heap_: Arc::clone(heap),
-                marking_worklists_: Arc::clone(marking_worklists),
-                incremental_marking_schedule_: Arc::clone(incremental_marking_schedule),
-                platform_: platform,
-                concurrent_marking_handle_: Mutex::new(None),
-                concurrently_marked_bytes_: AtomicUsize::new(0),
-                concurrent_marking_priority_increased_: Mutex::new(false),
-            }
-        }
-
-        pub fn start(&self) {
-            let mut handle = self.concurrent_marking_handle_.lock().unwrap();
-            if handle.is_none() {
-                *handle = Some(Box::new(JobHandle {})); // Replace with actual job creation
-            }
-        }
-
-        pub fn join(&self) -> bool {
-            self.concurrent_marking_handle_.lock().unwrap().is_none()
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::heap::cppgc::marking_state::MarkingState;
+use crate::heap::cppgc::marking_worklists::MarkingWorklists;
+
+/// Upper bound on the number of concurrent marking threads.
+const MAX_CONCURRENT_MARKERS: usize = 4;
+
+/// Marks objects on background threads while the mutator keeps running.
+/// Markers only trace objects whose types opted into concurrent tracing and
+/// hand all other objects back to the mutator.
+pub struct ConcurrentMarker {
+    worklists: Arc<MarkingWorklists>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<usize>>,
+}
+
+impl ConcurrentMarker {
+    /// Starts the background markers.
+    pub fn start(worklists: Arc<MarkingWorklists>) -> Self {
+        let num_threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get().saturating_sub(1))
+            .clamp(1, MAX_CONCURRENT_MARKERS);
+        let stop = Arc::new(AtomicBool::new(false));
+        let threads = (0..num_threads)
+            .map(|i| {
+                let worklists = worklists.clone();
+                let stop = stop.clone();
+                std::thread::Builder::new()
+                    .name(format!("cppgc-marker-{i}"))
+                    .spawn(move || Self::run(worklists, stop))
+                    .expect("failed to spawn concurrent marker")
+            })
+            .collect();
+        ConcurrentMarker {
+            worklists,
+            stop,
+            threads,
         }
+    }
 
-        pub fn cancel(&self) -> bool {
-            let mut handle = self.concurrent_marking_handle_.lock().unwrap();
-            if handle.is_some() {
-                *handle = None; // Replace with actual job cancellation
-                true
-            } else {
-                false
+    fn run(worklists: Arc<MarkingWorklists>, stop: Arc<AtomicBool>) -> usize {
+        let mut state = MarkingState::new(worklists.clone(), true);
+        while !stop.load(Ordering::Acquire) {
+            // Publish after every chunk so that the mutator sees the work
+            // left over when it joins the markers.
+            let drained = state.process_worklist(Some(64 * 1024));
+            state.publish();
+            if drained {
+                worklists.wait_for_work(Duration::from_millis(1));
             }
         }
-
-        pub fn notify_incremental_mutator_step_completed(&self) {}
-
-        pub fn notify_of_work_needed(&self, priority: TaskPriority) {}
-
-        pub fn is_active(&self) -> bool {
-            self.concurrent_marking_handle_.lock().unwrap().is_some()
-        }
-
-        pub fn heap(&self) -> &Arc<HeapBase> {
-            &self.heap_
-        }
-
-        pub fn marking_worklists(&self) -> &Arc<Mutex<MarkingWorklists>> {
-            &self.marking_worklists_
-        }
-
-        pub fn incremental_marking_schedule(&self) -> &Arc<Mutex<IncrementalMarkingSchedule>> {
-            &self.incremental_marking_schedule_
-        }
-
-        pub fn add_concurrently_marked_bytes(&self, marked_bytes: usize) {
-            self.concurrently_marked_bytes_
-                .fetch_add(marked_bytes, Ordering::Relaxed);
-        }
-
-        pub fn concurrently_marked_bytes(&self) -> usize {
-            self.concurrently_marked_bytes_.load(Ordering::Relaxed)
-        }
-
-        // Abstract method in C++, needs a default implementation or be made a trait
-        // This implementation returns a default Visitor
-        pub fn create_concurrent_marking_visitor(
-            &self,
-            _state: &ConcurrentMarkingState,
-        ) -> Box<dyn Visitor> {
-            Box::new(DefaultVisitor {})
-        }
-
-        fn increase_marking_priority_if_needed(&self) {}
+        state.publish();
+        state.marked_bytes()
     }
 
-    impl Drop for ConcurrentMarkerBase {
-        fn drop(&mut self) {
-            // Potential cleanup logic, especially for raw pointers
-            // For now, just dropping the struct.
-        }
+    /// Stops and joins the markers. Returns the number of bytes they marked.
+    pub fn join(mut self) -> usize {
+        self.stop_and_join()
     }
 
-    // A default visitor for trait object.
-    struct DefaultVisitor {}
-    impl Visitor for DefaultVisitor {}
-
-    pub struct ConcurrentMarker {
-        base: ConcurrentMarkerBase,
+    fn stop_and_join(&mut self) -> usize {
+        self.stop.store(true, Ordering::Release);
+        self.worklists.notify_all();
+        self.threads
+            .drain(..)
+            .map(|thread| thread.join().expect("concurrent marker panicked"))
+            .sum()
     }
+}
 
-    impl ConcurrentMarker {
-        pub fn new(
-            heap: &Arc<HeapBase>,
-            marking_worklists: &Arc<Mutex<MarkingWorklists>>,
-            incremental_marking_schedule: &Arc<Mutex<IncrementalMarkingSchedule>>,
-            platform: *mut Platform,
-        ) -> Self {
-            ConcurrentMarker {
-                base: ConcurrentMarkerBase::new(
-                    heap,
-                    marking_worklists,
-                    incremental_marking_schedule,
-                    platform,
-                ),
-            }
-        }
-
-        pub fn create_concurrent_marking_visitor(
-            &self,
-            state: &ConcurrentMarkingState,
-        ) -> Box<dyn Visitor> {
-            self.base.create_concurrent_marking_visitor(state)
-        }
+impl Drop for ConcurrentMarker {
+    fn drop(&mut self) {
+        // Markers must never outlive the heap they are marking.
+        self.stop_and_join();
     }
-}
\ No newline at end of file
+}
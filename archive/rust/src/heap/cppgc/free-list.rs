@@ -2,250 +2,171 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::{
-    array,
-    mem,
-    ptr,
-    sync::atomic::{AtomicPtr, Ordering},
-};
-
-//use crate::base::macros::*;  // Assuming these are just utility macros
-//use crate::base::sanitizer::asan::*; // ASAN functionality - may need a crate or conditional compilation
-//use crate::heap::cppgc::globals::*; // Define kFreeListGCInfoIndex
-//use crate::heap::cppgc::heap_object_header::*; // Define HeapObjectHeader
-
-const K_PAGE_SIZE_LOG2: usize = 12; // Example value, replace with actual
-
-// Placeholder for ASAN_UNPOISON_MEMORY_REGION
-macro_rules! ASAN_UNPOISON_MEMORY_REGION {
-    ($ptr:expr, $size:expr) => {
-        // Implement ASAN unpoisoning logic here, or use conditional compilation.
-        // For example, if using a crate:
-        // asan::unpoison_memory_region($ptr as *mut u8, $size);
-        // Or, if ASAN is not enabled, do nothing:
-    };
-}
+use crate::heap::cppgc::globals::cppgc::internal::{FREE_LIST_ENTRY_SIZE, FREE_LIST_GC_INFO_INDEX};
+use crate::heap::cppgc::heap_object_header::HeapObjectHeader;
 
-#[derive(Debug)]
-struct HeapObjectHeader {
-    size: usize,
-    gc_info_index: usize,
-}
+const PAGE_SIZE_BUCKETS: usize = usize::BITS as usize;
 
-impl HeapObjectHeader {
-    fn new(size: usize, gc_info_index: usize) -> Self {
-        HeapObjectHeader {
-            size,
-            gc_info_index,
-        }
-    }
+/// A free-list entry: a block header followed by the link to the next entry
+/// in the same bucket.
+#[repr(C)]
+struct Entry {
+    header: HeapObjectHeader,
+    next: *mut Entry,
 }
 
-const K_FREE_LIST_GC_INFO_INDEX: usize = 0; //Example value
-
-mod internal {
-    use super::*;
-    use std::{mem, ptr};
-
-    pub struct Filler {
-        header: HeapObjectHeader,
-    }
+const _: () = assert!(std::mem::size_of::<Entry>() <= FREE_LIST_ENTRY_SIZE);
 
-    impl Filler {
-        pub fn create_at(memory: *mut u8, size: usize) -> &'static mut Self {
-            unsafe {
-                ASAN_UNPOISON_MEMORY_REGION!(memory, mem::size_of::<Self>());
-                let filler = Filler {
-                    header: HeapObjectHeader::new(size, K_FREE_LIST_GC_INFO_INDEX),
-                };
-                let ptr = memory as *mut Filler;
-                ptr::write(ptr, filler);
-                &mut *ptr
-            }
-        }
-    }
+fn bucket_index_for_size(size: usize) -> usize {
+    debug_assert!(size > 0);
+    (usize::BITS - 1 - size.leading_zeros()) as usize
 }
 
-mod cppgc {
-    pub mod internal {
-        use super::*;
-        use std::{
-            mem,
-            ptr,
-            sync::atomic::{AtomicPtr, Ordering},
-        };
-
-        #[derive(Debug, Copy, Clone)]
-        pub struct Block {
-            pub address: *mut u8,
-            pub size: usize,
-        }
-
-        impl Block {
-            pub fn new(address: *mut u8, size: usize) -> Self {
-                Block { address, size }
-            }
-        }
+/// Segregated free list. Bucket `i` holds blocks of size `[2^i, 2^(i+1))`.
+pub struct FreeList {
+    free_list_heads: [*mut Entry; PAGE_SIZE_BUCKETS],
+    biggest_free_list_index: usize,
+}
 
-        struct Entry {
-            block: Block,
-            next: AtomicPtr<Entry>,
-        }
+/// A block handed out by `FreeList::allocate`.
+#[derive(Clone, Copy, Debug)]
+pub struct Block {
+    pub address: usize,
+    pub size: usize,
+}
 
-        impl Entry {
-            fn new(block: Block) -> Self {
-                Entry {
-                    block,
-                    next: AtomicPtr::new(ptr::null_mut()),
-                }
-            }
+impl FreeList {
+    pub fn new() -> Self {
+        FreeList {
+            free_list_heads: [std::ptr::null_mut(); PAGE_SIZE_BUCKETS],
+            biggest_free_list_index: 0,
         }
+    }
 
-        #[derive(Debug)]
-        pub struct FreeList {
-            free_list_heads_: [AtomicPtr<Entry>; K_PAGE_SIZE_LOG2],
-            free_list_tails_: [*mut Entry; K_PAGE_SIZE_LOG2],
-            biggest_free_list_index_: usize,
+    /// Adds the block `[address, address + size)`. Blocks too small to hold a
+    /// link become filler that is only reclaimed by the sweeper. Returns the
+    /// number of bytes wasted that way.
+    ///
+    /// # Safety
+    ///
+    /// The block must be unused heap memory on a normal page.
+    pub unsafe fn add(&mut self, block: Block) -> usize {
+        let header = unsafe {
+            HeapObjectHeader::create(block.address, block.size, FREE_LIST_GC_INFO_INDEX as u16)
+        };
+        debug_assert!(header.is_free());
+        if block.size < std::mem::size_of::<Entry>() {
+            return block.size;
         }
+        let entry = block.address as *mut Entry;
+        let index = bucket_index_for_size(block.size);
+        unsafe { (*entry).next = self.free_list_heads[index] };
+        self.free_list_heads[index] = entry;
+        self.biggest_free_list_index = self.biggest_free_list_index.max(index);
+        0
+    }
 
-        impl FreeList {
-            pub fn new() -> Self {
-                FreeList {
-                    free_list_heads_: array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
-                    free_list_tails_: [ptr::null_mut(); K_PAGE_SIZE_LOG2],
-                    biggest_free_list_index_: 0,
-                }
-            }
-
-            pub fn allocate(&mut self, size: usize) -> Block {
-                let index = (size as f64).log2().ceil() as usize;
-                for i in index..K_PAGE_SIZE_LOG2 {
-                    let head_ptr = self.free_list_heads_[i].load(Ordering::Relaxed);
-                    if !head_ptr.is_null() {
-                        let head = unsafe { &*head_ptr };
-                        let block = head.block;
-                        self.free_list_heads_[i].store(head.next.load(Ordering::Relaxed), Ordering::Relaxed);
-                        if self.free_list_heads_[i].load(Ordering::Relaxed).is_null() {
-                            self.free_list_tails_[i] = ptr::null_mut();
-                            if i == self.biggest_free_list_index_ {
-                                while self.biggest_free_list_index_ > 0 && self.free_list_heads_[self.biggest_free_list_index_].load(Ordering::Relaxed).is_null(){
-                                    self.biggest_free_list_index_ -=1;
-                                }
-                            }
-                        }
-                        return block;
-                    }
-                }
-                Block {
-                    address: ptr::null_mut(),
-                    size: 0,
+    /// Removes a block of at least `allocation_size` bytes. The caller is
+    /// responsible for returning any unused remainder.
+    pub fn allocate(&mut self, allocation_size: usize) -> Option<Block> {
+        // Every block in the next bucket up is large enough; in the bucket of
+        // the size itself only some blocks are, so only its head is checked.
+        let exact_index = bucket_index_for_size(allocation_size);
+        for index in (exact_index..=self.biggest_free_list_index).rev() {
+            let entry = self.free_list_heads[index];
+            if entry.is_null() {
+                if index == self.biggest_free_list_index && index > 0 {
+                    self.biggest_free_list_index -= 1;
                 }
+                continue;
             }
-
-            fn get_index(size: usize) -> usize {
-                (size as f64).log2().ceil() as usize
+            let size = unsafe { (*entry).header.allocated_size() };
+            if size < allocation_size {
+                debug_assert_eq!(index, exact_index);
+                continue;
             }
+            self.free_list_heads[index] = unsafe { (*entry).next };
+            return Some(Block {
+                address: entry as usize,
+                size,
+            });
+        }
+        None
+    }
 
-            pub fn add(&mut self, block: Block) {
-                let index = Self::get_index(block.size);
-
-                let new_entry = Box::new(Entry::new(block));
-                let new_entry_ptr = Box::into_raw(new_entry);
-
-                if self.free_list_heads_[index].load(Ordering::Relaxed).is_null() {
-                    self.free_list_heads_[index].store(new_entry_ptr, Ordering::Relaxed);
-                    self.free_list_tails_[index] = new_entry_ptr;
-                } else {
-                    unsafe {
-                        let tail = &mut *self.free_list_tails_[index];
-                        tail.next = AtomicPtr::new(new_entry_ptr);
-                    }
-                    self.free_list_tails_[index] = new_entry_ptr;
-                }
-                self.biggest_free_list_index_ = self.biggest_free_list_index_.max(index);
-            }
+    pub fn clear(&mut self) {
+        self.free_list_heads = [std::ptr::null_mut(); PAGE_SIZE_BUCKETS];
+        self.biggest_free_list_index = 0;
+    }
 
-            pub fn add_returning_unused_bounds(&mut self, block: Block) -> (usize, usize) {
-                 self.add(block);
-                (0,0) // Placeholder
-            }
+    pub fn is_empty(&self) -> bool {
+        self.free_list_heads.iter().all(|head| head.is_null())
+    }
 
-            pub fn append(&mut self, mut other: FreeList) {
-                for i in 0..K_PAGE_SIZE_LOG2 {
-                    if !other.free_list_heads_[i].load(Ordering::Relaxed).is_null() {
-                        if self.free_list_heads_[i].load(Ordering::Relaxed).is_null() {
-                            self.free_list_heads_[i].store(other.free_list_heads_[i].load(Ordering::Relaxed), Ordering::Relaxed);
-                            self.free_list_tails_[i] = other.free_list_tails_[i];
-                        } else {
-                            unsafe {
-                                let tail = &mut *self.free_list_tails_[i];
-                                tail.next = AtomicPtr::new(other.free_list_heads_[i].load(Ordering::Relaxed));
-                            }
-                            self.free_list_tails_[i] = other.free_list_tails_[i];
-                        }
-                        other.free_list_heads_[i].store(ptr::null_mut(), Ordering::Relaxed);
-                        other.free_list_tails_[i] = ptr::null_mut();
-                    }
+    /// Total number of bytes on the list.
+    pub fn size(&self) -> usize {
+        let mut size = 0;
+        for &head in &self.free_list_heads {
+            let mut entry = head;
+            while !entry.is_null() {
+                unsafe {
+                    size += (*entry).header.allocated_size();
+                    entry = (*entry).next;
                 }
-                 self.biggest_free_list_index_ = self.biggest_free_list_index_.max(other.biggest_free_list_index_);
-                 other.biggest_free_list_index_ = 0;
-            }
-
-            pub fn clear(&mut self) {
-                 for i in 0..K_PAGE_SIZE_LOG2{
-                    self.free_list_heads_[i].store(ptr::null_mut(), Ordering::Relaxed);
-                    self.free_list_tails_[i] = ptr::null_mut();
-                 }
-                 self.biggest_free_list_index_ = 0;
             }
+        }
+        size
+    }
 
-            pub fn size(&self) -> usize {
-                let mut size = 0;
-                for i in 0..K_PAGE_SIZE_LOG2 {
-                    let mut current = self.free_list_heads_[i].load(Ordering::Relaxed);
-                    while !current.is_null() {
-                        size += 1;
-                        unsafe {
-                            current = (&*current).next.load(Ordering::Relaxed);
-                        }
-                    }
+    /// Returns true if a block starts at `address`.
+    pub fn contains_for_testing(&self, address: usize) -> bool {
+        self.free_list_heads.iter().any(|&head| {
+            let mut entry = head;
+            while !entry.is_null() {
+                if entry as usize == address {
+                    return true;
                 }
-                size
-            }
-
-            pub fn is_empty(&self) -> bool {
-                self.size() == 0
-            }
-
-            pub fn collect_statistics(&self, _stats: &mut HeapStatisticsFreeListStatistics) {
-                // TODO: Implement statistics collection
+                entry = unsafe { (*entry).next };
             }
+            false
+        })
+    }
+}
 
-            pub fn contains_for_testing(&self, block: Block) -> bool {
-                for i in 0..K_PAGE_SIZE_LOG2 {
-                    let mut current = self.free_list_heads_[i].load(Ordering::Relaxed);
-                    while !current.is_null() {
-                        unsafe {
-                            let entry = &*current;
-                            if entry.block.address == block.address && entry.block.size == block.size {
-                                return true;
-                            }
-                            current = entry.next.load(Ordering::Relaxed);
-                        }
-                    }
-                }
-                false
-            }
+impl Default for FreeList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            fn is_consistent(&self, _size: usize) -> bool {
-                true // Placeholder
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-         #[derive(Debug, Default)]
-        pub struct HeapStatisticsFreeListStatistics {
-            // Define fields here according to original C++ struct
+    #[repr(C, align(8))]
+    struct Memory([u8; 1024]);
+
+    #[test]
+    fn allocate_returns_large_enough_blocks() {
+        let mut memory = Memory([0; 1024]);
+        let base = memory.0.as_mut_ptr() as usize;
+        let mut free_list = FreeList::new();
+        unsafe {
+            assert_eq!(free_list.add(Block { address: base, size: 32 }), 0);
+            assert_eq!(free_list.add(Block { address: base + 32, size: 48 }), 0);
+            assert_eq!(free_list.add(Block { address: base + 80, size: 512 }), 0);
+            assert_eq!(free_list.add(Block { address: base + 592, size: 8 }), 8);
         }
-
+        assert_eq!(free_list.size(), 592);
+        assert!(!free_list.contains_for_testing(base + 592));
+
+        let block = free_list.allocate(300).unwrap();
+        assert_eq!(block.address, base + 80);
+        let block = free_list.allocate(40).unwrap();
+        assert_eq!(block.address, base + 32);
+        assert!(free_list.allocate(40).is_none());
+        assert!(free_list.allocate(600).is_none());
+        free_list.clear();
+        assert!(free_list.is_empty());
     }
-}
\ No newline at end of file
+}
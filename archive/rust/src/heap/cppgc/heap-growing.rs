@@ -2,117 +2,86 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod heap_growing {
-    use std::ptr::NonNull;
-
-    pub use crate::cppgc::heap::ResourceConstraints;
-    pub use crate::cppgc::heap::{MarkingType, SweepingType};
-
-    const K_PAGE_SIZE: usize = 4096; // Example, replace with actual page size if available
-    const K_NUMBER_OF_REGULAR_SPACES: usize = 2; // Example value.  Replace with actual.
-    const K_MIN_LIMIT_INCREASE: usize = K_PAGE_SIZE * K_NUMBER_OF_REGULAR_SPACES;
-
-    /// Growing strategy that invokes garbage collection using GarbageCollector based
-    /// on allocation statistics provided by StatsCollector and ResourceConstraints.
-    ///
-    /// Implements a fixed-ratio growing strategy with an initial heap size that the
-    /// GC can ignore to avoid excessive GCs for smaller heaps.
-    pub struct HeapGrowing {
-        impl_: Box<HeapGrowingImpl>,
+use crate::heap::cppgc::globals::cppgc::internal::{MB, PAGE_SIZE};
+use crate::heap::heap::HeapGrowingMode;
+use crate::heap::heap_controller::{HeapGrowingInput, HeapGrowingPolicy};
+
+/// Number of regular spaces that allocate from linear allocation buffers.
+const NUMBER_OF_REGULAR_SPACES: usize = 2;
+
+/// Default growing policy of cppgc heaps, see `Heap::set_heap_growing_policy()`.
+///
+/// Implements a fixed-ratio growing strategy with an initial heap size that the
+/// GC can ignore to avoid excessive GCs for smaller heaps. The heap passes its
+/// initial size as `HeapGrowingInput::min_old_generation_size` and the size of
+/// the objects marked by the last garbage collection as
+/// `HeapGrowingInput::old_generation_size`.
+#[derive(Default)]
+pub struct HeapGrowing;
+
+impl HeapGrowing {
+    /// Constant growing factor for growing the heap limit.
+    pub const K_GROWING_FACTOR: f64 = 2.0;
+
+    /// For smaller heaps, allow allocating at least LAB in each regular space
+    /// before triggering GC again.
+    pub const K_MIN_LIMIT_INCREASE: usize = PAGE_SIZE * NUMBER_OF_REGULAR_SPACES;
+
+    /// Minimum heap size before allocation triggers a garbage collection.
+    pub const K_MIN_LIMIT_FOR_GC: usize = MB;
+
+    pub fn new() -> Self {
+        HeapGrowing
     }
+}
 
-    impl HeapGrowing {
-        /// Constant growing factor for growing the heap limit.
-        pub const K_GROWING_FACTOR: f64 = 1.5;
-
-        /// For smaller heaps, allow allocating at least LAB in each regular space
-        /// before triggering GC again.
-        pub const MIN_LIMIT_INCREASE: usize = K_MIN_LIMIT_INCREASE;
-
-        pub fn new(
-            garbage_collector: *mut GarbageCollector,
-            stats_collector: *mut StatsCollector,
-            resource_constraints: ResourceConstraints,
-            marking_type: MarkingType,
-            sweeping_type: SweepingType,
-        ) -> Self {
-            let impl_ = HeapGrowingImpl::new(
-                garbage_collector,
-                stats_collector,
-                resource_constraints,
-                marking_type,
-                sweeping_type,
-            );
-            HeapGrowing { impl_: Box::new(impl_) }
-        }
-
-        pub fn limit_for_atomic_gc(&self) -> usize {
-            self.impl_.limit_for_atomic_gc()
-        }
-
-        pub fn limit_for_incremental_gc(&self) -> usize {
-            self.impl_.limit_for_incremental_gc()
-        }
-
-        pub fn disable_for_testing(&mut self) {
-            self.impl_.disable_for_testing();
-        }
+impl HeapGrowingPolicy for HeapGrowing {
+    fn name(&self) -> &'static str {
+        "CppHeap"
     }
 
-    struct HeapGrowingImpl {
-        garbage_collector: *mut GarbageCollector,
-        stats_collector: *mut StatsCollector,
-        resource_constraints: ResourceConstraints,
-        marking_type: MarkingType,
-        sweeping_type: SweepingType,
-        disabled: bool,
+    fn allocation_limit(&mut self, input: &HeapGrowingInput) -> usize {
+        let live_size = input.old_generation_size;
+        let limit = match input.growing_mode {
+            HeapGrowingMode::kMinimal => live_size,
+            _ => (live_size as f64 * Self::K_GROWING_FACTOR) as usize,
+        };
+        limit
+            .max(live_size + Self::K_MIN_LIMIT_INCREASE)
+            .max(input.min_old_generation_size)
+            .min(input.max_old_generation_size)
     }
+}
 
-    impl HeapGrowingImpl {
-        fn new(
-            garbage_collector: *mut GarbageCollector,
-            stats_collector: *mut StatsCollector,
-            resource_constraints: ResourceConstraints,
-            marking_type: MarkingType,
-            sweeping_type: SweepingType,
-        ) -> Self {
-            HeapGrowingImpl {
-                garbage_collector,
-                stats_collector,
-                resource_constraints,
-                marking_type,
-                sweeping_type,
-                disabled: false,
-            }
-        }
-
-        fn limit_for_atomic_gc(&self) -> usize {
-            // Placeholder implementation
-            1024
-        }
-
-        fn limit_for_incremental_gc(&self) -> usize {
-            // Placeholder implementation
-            512
-        }
-
-        fn disable_for_testing(&mut self) {
-            self.disabled = true;
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn input(live_size: usize, growing_mode: HeapGrowingMode) -> HeapGrowingInput {
+        HeapGrowingInput {
+            old_generation_size: live_size,
+            allocated_bytes: 0,
+            mutator_duration: Duration::ZERO,
+            gc_bytes: 0,
+            gc_duration: Duration::ZERO,
+            gc_speed: None,
+            min_old_generation_size: 4 * MB,
+            max_old_generation_size: usize::MAX,
+            new_space_capacity: 0,
+            growing_mode,
         }
     }
 
-    // Dummy structs for now to make the code compile
-    pub struct GarbageCollector {}
-    pub struct StatsCollector {}
-}
-
-pub mod cppgc {
-    pub mod heap {
-        #[derive(Clone, Copy)]
-        pub struct ResourceConstraints {}
-        #[derive(Clone, Copy)]
-        pub enum MarkingType {}
-        #[derive(Clone, Copy)]
-        pub enum SweepingType {}
+    #[test]
+    fn limit_grows_by_fixed_ratio_beyond_initial_size() {
+        let mut policy = HeapGrowing::new();
+        assert_eq!(policy.allocation_limit(&input(MB, HeapGrowingMode::kDefault)), 4 * MB);
+        assert_eq!(policy.allocation_limit(&input(8 * MB, HeapGrowingMode::kDefault)), 16 * MB);
+        assert_eq!(
+            policy.allocation_limit(&input(8 * MB, HeapGrowingMode::kMinimal)),
+            8 * MB + HeapGrowing::K_MIN_LIMIT_INCREASE
+        );
     }
-}
\ No newline at end of file
+}
@@ -2,400 +2,174 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+use std::cell::Cell;
 use std::sync::atomic::{AtomicU16, Ordering};
-use std::{marker::PhantomData, mem::size_of};
 
-// use cppgc::internal::GCInfo;  // Assuming this is defined elsewhere
-// use cppgc::internal::HeapObjectName;  // Assuming this is defined elsewhere
+use crate::heap::cppgc::gc_info_table::{GCInfoIndex, GlobalGCInfoTable};
+use crate::heap::cppgc::globals::cppgc::internal::{ALLOCATION_GRANULARITY, FREE_LIST_GC_INFO_INDEX};
+use crate::heap::cppgc::heap_page::BasePage;
 
-const K_ALLOCATION_GRANULARITY: usize = 8; // Example value, ensure it matches C++
-const K_BLINK_PAGE_SIZE: usize = 1 << 17;   // Example value, ensure it matches C++
+const MARK_BIT: u16 = 1 << 0;
 
-pub mod internal {
-    use super::*;
-    use crate::globals::GlobalGCInfoTable; // Assuming this is defined elsewhere
-    use crate::globals::GCInfo;
-    use crate::globals::HeapObjectName;
-
-    pub type GCInfoIndex = u16; // Example, adjust as necessary
-
-    #[repr(C)]
-    pub struct HeapObjectHeader {
-        #[cfg(all(target_arch = "x86_64", feature = "caged_heap"))]
-        next_unfinalized_: u32,
-        #[cfg(all(target_arch = "x86_64", not(feature = "caged_heap")))]
-        padding_: u32,
-
-        encoded_high_: AtomicU16,
-        encoded_low_: AtomicU16,
-    }
-
-    impl HeapObjectHeader {
-        pub const K_SIZE_LOG2: usize = 17;
-        pub const K_MAX_SIZE: usize = (1 << Self::K_SIZE_LOG2) - 1;
-        pub const K_LARGE_OBJECT_SIZE_IN_HEADER: u16 = 0;
-
-        #[inline]
-        pub fn from_object(address: *mut std::ffi::c_void) -> &'static mut HeapObjectHeader {
-            unsafe {
-                &mut *(address as *mut u8).sub(size_of::<HeapObjectHeader>()) as &'static mut HeapObjectHeader
-            }
-        }
-
-        #[inline]
-        pub fn from_object_const(address: *const std::ffi::c_void) -> &'static HeapObjectHeader {
-            unsafe {
-                &*(address as *const u8).sub(size_of::<HeapObjectHeader>()) as &'static HeapObjectHeader
-            }
-        }
-
-        #[inline]
-        pub fn new(size: usize, gc_info_index: GCInfoIndex) -> HeapObjectHeader {
-            assert!(gc_info_index < crate::globals::GCInfoTable::k_max_index() as u16);
-            assert_eq!(0, size & (size_of::<HeapObjectHeader>() - 1));
-            assert!(Self::K_MAX_SIZE >= size);
-
-            let encoded_low_ = Self::encode_size(size);
-            let encoded_high_ = FullyConstructedField::encode(false) | UnusedField1::encode(false) | GCInfoIndexField::encode(gc_info_index);
-
-            HeapObjectHeader {
-                #[cfg(all(target_arch = "x86_64", feature = "caged_heap"))]
-                next_unfinalized_: 0,
-                #[cfg(all(target_arch = "x86_64", not(feature = "caged_heap")))]
-                padding_: 0,
-                encoded_high_: AtomicU16::new(encoded_high_),
-                encoded_low_: AtomicU16::new(encoded_low_),
-            }
-        }
-
-        #[inline]
-        pub fn object_start(&self) -> *mut std::ffi::c_void {
-            (self as *const Self as *mut std::ffi::c_void as usize + size_of::<HeapObjectHeader>()) as *mut std::ffi::c_void
-        }
-
-        #[inline]
-        pub fn object_end<const MODE: bool>(&self) -> *mut std::ffi::c_void {
-            assert!(!self.is_large_object::<MODE>());
-            (self as *const Self as *mut std::ffi::c_void as usize + self.allocated_size::<MODE>()) as *mut std::ffi::c_void
-        }
-
-        #[inline]
-        pub fn get_gc_info_index<const MODE: bool>(&self) -> GCInfoIndex {
-            let encoded = self.load_encoded::<MODE, EncodedHalf::High, Ordering::Acquire>();
-            GCInfoIndexField::decode(encoded) as GCInfoIndex
-        }
-
-        #[inline]
-        pub fn allocated_size<const MODE: bool>(&self) -> usize {
-            let encoded_low_value = self.load_encoded::<MODE, EncodedHalf::Low, Ordering::Relaxed>();
-            let size = Self::decode_size(encoded_low_value);
-            size
-        }
-
-        pub fn set_allocated_size(&mut self, size: usize) {
-            // With sticky bits, marked objects correspond to old objects.
-            // TODO(bikineev:1029379): Consider disallowing old/marked objects to be
-            // resized.
-            assert!(!self.is_marked::<false>());
-
-            let encoded_low_value = self.encoded_low_.load(Ordering::Relaxed);
-            let mut new_encoded_low_value = encoded_low_value & !SizeField::encode(SizeField::k_max());
-            new_encoded_low_value |= Self::encode_size(size);
-            self.encoded_low_.store(new_encoded_low_value, Ordering::Relaxed);
-        }
-
-        #[inline]
-        pub fn object_size<const MODE: bool>(&self) -> usize {
-            assert!(self.allocated_size::<MODE>() > size_of::<HeapObjectHeader>());
-            self.allocated_size::<MODE>() - size_of::<HeapObjectHeader>()
-        }
-
-        #[inline]
-        pub fn is_large_object<const MODE: bool>(&self) -> bool {
-            self.allocated_size::<MODE>() == Self::K_LARGE_OBJECT_SIZE_IN_HEADER as usize
-        }
-
-        #[inline]
-        pub fn is_in_construction<const MODE: bool>(&self) -> bool {
-            let encoded = self.load_encoded::<MODE, EncodedHalf::High, Ordering::Acquire>();
-            !FullyConstructedField::decode(encoded)
-        }
-
-        pub fn mark_as_fully_constructed(&self) {
-            let encoded = FullyConstructedField::encode(true);
-            let mask = FullyConstructedField::k_mask();
-            self.store_encoded::<false, EncodedHalf::High, Ordering::SeqCst>(encoded, mask);
-        }
-
-        #[inline]
-        pub fn is_marked<const MODE: bool>(&self) -> bool {
-            let encoded = self.load_encoded::<MODE, EncodedHalf::Low, Ordering::Relaxed>();
-            MarkBitField::decode(encoded)
-        }
-
-        #[inline]
-        pub fn unmark<const MODE: bool>(&self) {
-            assert!(self.is_marked::<MODE>());
-            self.store_encoded::<MODE, EncodedHalf::Low, Ordering::Relaxed>(
-                MarkBitField::encode(false),
-                MarkBitField::k_mask(),
-            );
-        }
-
-        pub fn try_mark_atomic(&self) -> bool {
-            let atomic_encoded = &self.encoded_low_;
-            let old_value = atomic_encoded.load(Ordering::Relaxed);
-            let new_value = old_value | MarkBitField::encode(true);
-            if new_value == old_value {
-                return false;
-            }
-            atomic_encoded
-                .compare_exchange(old_value, new_value, Ordering::Relaxed, Ordering::Relaxed)
-                .is_ok()
-        }
-
-        pub fn mark_non_atomic(&mut self) {
-            assert!(!self.is_marked::<false>());
-            let encoded_low_value = self.encoded_low_.load(Ordering::Relaxed);
-            self.encoded_low_.store(encoded_low_value | MarkBitField::encode(true), Ordering::Relaxed);
-        }
-
-        #[inline]
-        pub fn is_young<const MODE: bool>(&self) -> bool {
-            !self.is_marked::<MODE>()
-        }
-
-        #[inline]
-        pub fn is_free<const MODE: bool>(&self) -> bool {
-            self.get_gc_info_index::<MODE>() == crate::globals::k_free_list_gc_info_index()
-        }
-
-        pub fn is_finalizable(&self) -> bool {
-            let gc_info =
-                GlobalGCInfoTable::gc_info_from_index(self.get_gc_info_index::<false>());
-            gc_info.finalize
-        }
-
-        pub fn finalize(&self) {
-            todo!()
-        }
-
-        #[cfg(feature = "caged_heap")]
-        pub fn set_next_unfinalized(&mut self, next: *mut HeapObjectHeader) {
-            // Implement pointer compression or offset calculation
-            // Depending on CPPGC_POINTER_COMPRESSION
-            // This is a placeholder
-            self.next_unfinalized_ = next as u32;
-        }
-
-        #[cfg(feature = "caged_heap")]
-        pub fn get_next_unfinalized(&self, cage_base_or_mask: usize) -> *mut HeapObjectHeader {
-            // Implement pointer decompression or offset calculation
-            // Depending on CPPGC_POINTER_COMPRESSION
-            // This is a placeholder
-            self.next_unfinalized_ as *mut HeapObjectHeader
-        }
-
-        pub fn get_name(&self) -> HeapObjectName {
-            self.get_name_with_option(crate::globals::HeapObjectNameForUnnamedObject::Runtime)
-        }
+thread_local! {
+    static IN_FINALIZER: Cell<usize> = const { Cell::new(0) };
+}
 
-        pub fn get_name_with_option(&self, option: crate::globals::HeapObjectNameForUnnamedObject) -> HeapObjectName {
-            todo!()
-        }
+/// Returns true while the current thread runs the destructor of a
+/// garbage-collected object. Other objects may already have been reclaimed
+/// at that point and must not be dereferenced.
+#[inline]
+pub fn is_finalizing() -> bool {
+    IN_FINALIZER.with(|depth| depth.get() != 0)
+}
 
-        pub fn trace<const MODE: bool>(&self, visitor: &mut dyn Visitor) {
-            let gc_info =
-                GlobalGCInfoTable::gc_info_from_index(self.get_gc_info_index::<MODE>());
-            gc_info.trace(visitor, self.object_start());
-        }
+/// Header preceding every block on the heap, whether it holds an object or
+/// a free-list entry.
+///
+/// ```text
+/// | size (u32) | gc_info_index (u16) | flags (u16) | payload ...
+/// ```
+///
+/// The size covers the header and the payload. Objects on large pages encode
+/// a size of 0 and take their size from the page instead.
+#[repr(C, align(8))]
+pub struct HeapObjectHeader {
+    encoded_size: Cell<u32>,
+    gc_info_index: Cell<GCInfoIndex>,
+    flags: AtomicU16,
+}
 
-        fn check_api_constants(&self) {
-            todo!()
+const _: () = assert!(std::mem::size_of::<HeapObjectHeader>() == 8);
+const _: () = assert!(std::mem::size_of::<HeapObjectHeader>().is_multiple_of(ALLOCATION_GRANULARITY));
+
+// The non-atomic fields are only written by the mutator while no concurrent
+// marker runs; markers only read the GCInfo index of published objects.
+unsafe impl Sync for HeapObjectHeader {}
+
+impl HeapObjectHeader {
+    /// Size of the header in bytes.
+    pub const SIZE: usize = std::mem::size_of::<HeapObjectHeader>();
+    /// Encoded size for objects on large pages.
+    pub const LARGE_OBJECT_SIZE_IN_HEADER: usize = 0;
+
+    /// Writes a new header at `address` and returns it.
+    ///
+    /// # Safety
+    ///
+    /// `address` must point to `size` writable bytes on a heap page.
+    pub unsafe fn create<'a>(address: usize, size: usize, gc_info_index: GCInfoIndex) -> &'a Self {
+        debug_assert_eq!(address % ALLOCATION_GRANULARITY, 0);
+        debug_assert!(size <= u32::MAX as usize);
+        let header = address as *mut HeapObjectHeader;
+        unsafe {
+            header.write(HeapObjectHeader {
+                encoded_size: Cell::new(size as u32),
+                gc_info_index: Cell::new(gc_info_index),
+                flags: AtomicU16::new(0),
+            });
+            &*header
         }
+    }
 
-        #[inline]
-        fn load_encoded<const MODE: bool, const PART: EncodedHalf, const ORDERING: Ordering>(
-            &self,
-        ) -> u16 {
-            let half = match PART {
-                EncodedHalf::Low => &self.encoded_low_,
-                EncodedHalf::High => &self.encoded_high_,
-            };
-            if MODE {
-                half.load(ORDERING)
-            } else {
-                unsafe { *half.as_ptr() }
-            }
-        }
+    /// Returns the header of the object whose payload starts at `payload`.
+    ///
+    /// # Safety
+    ///
+    /// `payload` must be the payload start of a live block.
+    #[inline]
+    pub unsafe fn from_object<'a>(payload: *const u8) -> &'a Self {
+        unsafe { &*(payload.sub(Self::SIZE) as *const HeapObjectHeader) }
+    }
 
-        #[inline]
-        fn store_encoded<const MODE: bool, const PART: EncodedHalf, const ORDERING: Ordering>(
-            &self,
-            bits: u16,
-            mask: u16,
-        ) {
-            assert_eq!(0, bits & !mask);
+    /// Returns the address of the header.
+    #[inline]
+    pub fn address(&self) -> usize {
+        self as *const Self as usize
+    }
 
-            let half = match PART {
-                EncodedHalf::Low => &self.encoded_low_,
-                EncodedHalf::High => &self.encoded_high_,
-            };
+    /// Returns the payload start.
+    #[inline]
+    pub fn object_start(&self) -> *mut u8 {
+        (self.address() + Self::SIZE) as *mut u8
+    }
 
-            if MODE {
-                let mut value = half.load(Ordering::Relaxed);
-                value = (value & !mask) | bits;
-                half.store(value, ORDERING);
-            } else {
-                unsafe {
-                    let value = half.load(Ordering::Relaxed);
-                    let new_value = (value & !mask) | bits;
-                    *half.as_ptr() = new_value;
-                }
+    /// Returns the size of the block including the header.
+    pub fn allocated_size(&self) -> usize {
+        match self.encoded_size.get() as usize {
+            Self::LARGE_OBJECT_SIZE_IN_HEADER => {
+                let page = unsafe { BasePage::from_payload(self.object_start()) };
+                page.as_large().payload_size()
             }
-        }
-
-        const fn decode_size(encoded: u16) -> usize {
-            SizeField::decode(encoded) * K_ALLOCATION_GRANULARITY
-        }
-
-        const fn encode_size(size: usize) -> u16 {
-            SizeField::encode(size / K_ALLOCATION_GRANULARITY)
+            size => size,
         }
     }
 
-    #[derive(PartialEq, Eq)]
-    enum EncodedHalf {
-        Low,
-        High,
+    /// Updates the block size. Only used for blocks on normal pages.
+    pub fn set_allocated_size(&self, size: usize) {
+        debug_assert!(!self.is_large_object());
+        debug_assert!(size != Self::LARGE_OBJECT_SIZE_IN_HEADER && size <= u32::MAX as usize);
+        self.encoded_size.set(size as u32);
     }
 
-    mod bitfield {
-        pub trait BitField<T, const OFFSET: usize, const SIZE: usize> {
-            const MASK: T;
-            const OFFSET: usize = OFFSET;
-            const SIZE: usize = SIZE;
-
-            fn encode(value: bool) -> T;
-            fn decode(encoded: T) -> bool;
-            fn k_mask() -> T;
-        }
+    /// Returns the payload size.
+    pub fn object_size(&self) -> usize {
+        self.allocated_size() - Self::SIZE
     }
 
-    struct FullyConstructedField;
-    impl FullyConstructedField {
-        const OFFSET: usize = 0;
-        const SIZE: usize = 1;
-
-        const fn encode(value: bool) -> u16 {
-            (value as u16) << Self::OFFSET
-        }
-
-        const fn decode(encoded: u16) -> bool {
-            ((encoded >> Self::OFFSET) & ((1 << Self::SIZE) - 1)) != 0
-        }
-
-        const fn k_mask() -> u16 {
-            ((1 << Self::SIZE) - 1) << Self::OFFSET
-        }
+    pub fn is_large_object(&self) -> bool {
+        self.encoded_size.get() as usize == Self::LARGE_OBJECT_SIZE_IN_HEADER
     }
 
-    struct UnusedField1;
-
-    impl UnusedField1 {
-        const OFFSET: usize = FullyConstructedField::OFFSET + FullyConstructedField::SIZE;
-        const SIZE: usize = 1;
-
-        const fn encode(value: bool) -> u16 {
-            (value as u16) << Self::OFFSET
-        }
-
-        const fn decode(encoded: u16) -> bool {
-            ((encoded >> Self::OFFSET) & ((1 << Self::SIZE) - 1)) != 0
-        }
+    #[inline]
+    pub fn gc_info_index(&self) -> GCInfoIndex {
+        self.gc_info_index.get()
     }
 
-    struct GCInfoIndexField;
-
-    impl GCInfoIndexField {
-        const OFFSET: usize = UnusedField1::OFFSET + UnusedField1::SIZE;
-        const SIZE: usize = 14;
-
-        const fn encode(value: u16) -> u16 {
-            (value as u16) << Self::OFFSET
-        }
-
-        const fn decode(encoded: u16) -> u16 {
-            ((encoded >> Self::OFFSET) & ((1 << Self::SIZE) - 1)) as u16
-        }
+    /// Returns true for free-list entries.
+    #[inline]
+    pub fn is_free(&self) -> bool {
+        self.gc_info_index() as usize == FREE_LIST_GC_INFO_INDEX
     }
 
-    struct MarkBitField;
-    impl MarkBitField {
-        const OFFSET: usize = 0;
-        const SIZE: usize = 1;
-
-        const fn encode(value: bool) -> u16 {
-            (value as u16) << Self::OFFSET
-        }
-
-        const fn decode(encoded: u16) -> bool {
-            ((encoded >> Self::OFFSET) & ((1 << Self::SIZE) - 1)) != 0
-        }
-
-        const fn k_mask() -> u16 {
-            ((1 << Self::SIZE) - 1) << Self::OFFSET
-        }
+    #[inline]
+    pub fn is_marked(&self) -> bool {
+        self.flags.load(Ordering::Acquire) & MARK_BIT != 0
     }
 
-    struct SizeField;
-
-    impl SizeField {
-        const OFFSET: usize = MarkBitField::OFFSET + MarkBitField::SIZE;
-        const SIZE: usize = 15;
-
-        const fn encode(value: usize) -> u16 {
-            (value as u16) << Self::OFFSET
-        }
-
-        const fn decode(encoded: u16) -> usize {
-            ((encoded >> Self::OFFSET) & ((1 << Self::SIZE) - 1)) as usize
-        }
-        const fn k_max() -> u16 {
-            ((1 << Self::SIZE) - 1) as u16
-        }
-    }
-    pub trait Visitor {
-        fn visit(&mut self, object: *mut std::ffi::c_void);
-    }
-}
-pub mod globals {
-    pub const fn k_free_list_gc_info_index() -> u16 {
-        0
+    /// Sets the mark bit. Returns true if this call marked the object.
+    #[inline]
+    pub fn try_mark_atomic(&self) -> bool {
+        self.flags.fetch_or(MARK_BIT, Ordering::AcqRel) & MARK_BIT == 0
     }
-    pub struct GCInfo {
-        pub finalize: bool,
-        pub trace: fn(&mut dyn internal::Visitor, *mut std::ffi::c_void),
-    }
-
-    pub struct GlobalGCInfoTable {}
 
-    impl GlobalGCInfoTable {
-        pub fn gc_info_from_index(index: u16) -> &'static GCInfo {
-            todo!()
-        }
+    pub fn unmark(&self) {
+        self.flags.fetch_and(!MARK_BIT, Ordering::Relaxed);
     }
 
-    pub struct HeapObjectName {}
-    pub enum HeapObjectNameForUnnamedObject {
-        Runtime,
+    /// Returns the name of the object's type.
+    pub fn name(&self) -> &'static str {
+        debug_assert!(!self.is_free());
+        GlobalGCInfoTable::gc_info_from_index(self.gc_info_index()).name
     }
 
-    pub struct GCInfoTable {}
-
-    impl GCInfoTable {
-        pub const fn k_max_index() -> usize {
-            16383
+    /// Runs the object's destructor.
+    ///
+    /// # Safety
+    ///
+    /// The header must belong to a dead object that is finalized exactly once.
+    pub unsafe fn finalize(&self) {
+        debug_assert!(!self.is_free());
+        let info = GlobalGCInfoTable::gc_info_from_index(self.gc_info_index());
+        if let Some(finalize) = info.finalize {
+            struct FinalizerScope;
+            impl Drop for FinalizerScope {
+                fn drop(&mut self) {
+                    IN_FINALIZER.with(|depth| depth.set(depth.get() - 1));
+                }
+            }
+            IN_FINALIZER.with(|depth| depth.set(depth.get() + 1));
+            let _scope = FinalizerScope;
+            unsafe { finalize(self.object_start()) };
         }
     }
-}
\ No newline at end of file
+}
@@ -2,92 +2,85 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::{Mutex, MutexGuard};
-//use std::result::Result;
-
-// TODO: Add logging crate
-// use log::{debug, info, warn};
-//use crate::base::platform::mutex::Mutex;
-//use crate::heap::cppgc::heap_page::HeapPage; // Assuming this is in a separate module
-//use crate::heap::cppgc::object_start_bitmap::ObjectStartBitmap; // Assuming this is in a separate module
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum PageType {
-    kNormal,
-    kLarge,
-}
+use std::cell::RefCell;
+use std::ptr::NonNull;
+
+use crate::heap::cppgc::free_list::FreeList;
+use crate::heap::cppgc::heap_page::{LargePage, NormalPage};
 
-pub struct BaseSpace {
-    heap_: *mut RawHeap, // TODO: Replace with a proper lifetime and ownership model
-    index_: usize,
-    type_: PageType,
-    is_compactable_: bool,
-    pages_: Vec<*mut BasePage>, // TODO: Replace with a proper lifetime and ownership model (Box<>)
-    pages_mutex_: Mutex<()>,
+/// Space holding normal pages and the free list of their swept memory.
+pub struct NormalPageSpace {
+    pages: RefCell<Vec<NonNull<NormalPage>>>,
+    free_list: RefCell<FreeList>,
 }
 
-impl BaseSpace {
-    pub fn new(heap: *mut RawHeap, index: usize, type_: PageType, is_compactable: bool) -> Self {
-        BaseSpace {
-            heap_: heap,
-            index_: index,
-            type_: type_,
-            is_compactable_: is_compactable,
-            pages_: Vec::new(),
-            pages_mutex_: Mutex::new(()),
+impl NormalPageSpace {
+    pub fn new() -> Self {
+        NormalPageSpace {
+            pages: RefCell::new(Vec::new()),
+            free_list: RefCell::new(FreeList::new()),
         }
     }
 
-    // This simulates the USE() macro
-    #[allow(dead_code)]
-    fn use_is_compactable(&self) {
-        let _ = self.is_compactable_;
+    pub fn add_page(&self, page: NonNull<NormalPage>) {
+        self.pages.borrow_mut().push(page);
     }
 
-    pub fn add_page(&mut self, page: *mut BasePage) {
-        let _lock = self.pages_mutex_.lock().unwrap();
-        assert!(!self.pages_.iter().any(|&p| p == page));
-        self.pages_.push(page);
+    /// Removes and returns all pages, e.g. to hand them to the sweeper.
+    pub fn remove_all_pages(&self) -> Vec<NonNull<NormalPage>> {
+        std::mem::take(&mut *self.pages.borrow_mut())
     }
 
-    pub fn remove_page(&mut self, page: *mut BasePage) {
-        let _lock = self.pages_mutex_.lock().unwrap();
-        let index = self.pages_.iter().position(|&p| p == page).unwrap();
-        self.pages_.remove(index);
+    pub fn pages(&self) -> Vec<NonNull<NormalPage>> {
+        self.pages.borrow().clone()
     }
 
-    pub fn remove_all_pages(&mut self) -> Vec<*mut BasePage> {
-        let mut pages = Vec::new();
-        std::mem::swap(&mut pages, &mut self.pages_);
-        pages
+    pub fn size(&self) -> usize {
+        self.pages.borrow().len()
     }
-}
 
-pub struct NormalPageSpace {
-    base: BaseSpace,
+    pub fn free_list(&self) -> &RefCell<FreeList> {
+        &self.free_list
+    }
 }
 
-impl NormalPageSpace {
-    pub fn new(heap: *mut RawHeap, index: usize, is_compactable: bool) -> Self {
-        NormalPageSpace {
-            base: BaseSpace::new(heap, index, PageType::kNormal, is_compactable),
-        }
+impl Default for NormalPageSpace {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// Space holding one large page per large object.
 pub struct LargePageSpace {
-    base: BaseSpace,
+    pages: RefCell<Vec<NonNull<LargePage>>>,
 }
 
 impl LargePageSpace {
-    pub fn new(heap: *mut RawHeap, index: usize) -> Self {
+    pub fn new() -> Self {
         LargePageSpace {
-            base: BaseSpace::new(heap, index, PageType::kLarge, false),
+            pages: RefCell::new(Vec::new()),
         }
     }
+
+    pub fn add_page(&self, page: NonNull<LargePage>) {
+        self.pages.borrow_mut().push(page);
+    }
+
+    pub fn remove_all_pages(&self) -> Vec<NonNull<LargePage>> {
+        std::mem::take(&mut *self.pages.borrow_mut())
+    }
+
+    pub fn pages(&self) -> Vec<NonNull<LargePage>> {
+        self.pages.borrow().clone()
+    }
+
+    pub fn size(&self) -> usize {
+        self.pages.borrow().len()
+    }
 }
 
-// Dummy types to allow compilation.  These must be replaced with the
-// correct types from the V8 codebase.
-pub struct RawHeap {}
-pub struct BasePage {}
\ No newline at end of file
+impl Default for LargePageSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,198 +2,651 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
-use std::sync::{Arc, Mutex};
-
-// Placeholder for cppgc::Platform.  In a real implementation, this
-// would likely be a trait or struct representing the platform's
-// memory management and threading capabilities.
-pub trait Platform {}
-
-// Placeholder for cppgc::Heap::HeapOptions.  This would define
-// options for configuring the heap.
-pub struct HeapOptions {}
-
-// Placeholder for cppgc::Heap.  This is a simplified version.
-pub trait CppgcHeap {
-    fn collect_garbage(&self, config: GCConfig);
-    fn start_incremental_garbage_collection(&self, config: GCConfig);
-    fn finalize_incremental_garbage_collection_if_running(&self, config: GCConfig);
-    fn epoch(&self) -> usize;
-    fn overridden_stack_state(&self) -> Option<EmbedderStackState>;
-    fn set_override_stack_state(&mut self, state: EmbedderStackState);
-    fn clear_overridden_stack_state(&mut self);
-    // #[cfg(feature = "V8_ENABLE_ALLOCATION_TIMEOUT")]
-    fn update_allocation_timeout(&self) -> Option<i32>;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::heap::base::stack::Stack;
+use crate::heap::cppgc::gc_info_table::{GCInfoIndex, GlobalGCInfoTable};
+use crate::heap::cppgc::globals::cppgc::internal::PAGE_SIZE;
+use crate::heap::cppgc::heap_growing::HeapGrowing;
+use crate::heap::cppgc::heap_object_header::HeapObjectHeader;
+use crate::heap::cppgc::heap_space::{LargePageSpace, NormalPageSpace};
+use crate::heap::cppgc::heap_page::{LargePage, NormalPage};
+use crate::heap::cppgc::metric_recorder::{
+    GCCycle, GCCycleType, IncrementalPhases, MainThreadIncrementalMark, MainThreadIncrementalSweep,
+    MetricRecorder, Phases, Sizes,
+};
+use crate::heap::cppgc::marker::{Marker, MarkingConfig};
+use crate::heap::cppgc::marking_worklists::MarkingWorklists;
+use crate::heap::cppgc::object_allocator::ObjectAllocator;
+use crate::heap::cppgc::persistent_node::PersistentRegion;
+use crate::heap::cppgc::prefinalizer_handler::{PreFinalizer, PrefinalizerHandler};
+use crate::heap::cppgc::sweeper::Sweeper;
+use crate::heap::cppgc::write_barrier::WriteBarrier;
+use crate::heap::heap::HeapGrowingMode;
+use crate::heap::heap_controller::{HeapGrowingInput, HeapGrowingPolicy};
+use crate::include::cppgc::common::EmbedderStackState as StackState;
+use crate::include::cppgc::heap::{HeapOptions, MarkingType, StackSupport, SweepingType};
+
+/// Allocation volume between incremental marking and sweeping steps.
+const ALLOCATED_BYTES_PER_STEP: usize = 64 * 1024;
+/// Bytes marked per allocated byte during incremental marking.
+const MARKING_BYTES_PER_ALLOCATED_BYTE: usize = 4;
+/// Pages swept per incremental sweeping step.
+const PAGES_PER_SWEEP_STEP: usize = 4;
+
+/// Phase of a garbage collection as seen by write barriers, which may run on
+/// any thread.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum MarkingPhase {
+    Idle,
+    Marking,
+    InvokingPreFinalizers,
 }
 
-pub mod internal {
-    use super::*;
-    use std::cell::RefCell;
-    use std::rc::Rc;
+/// A standalone garbage-collected heap: pages and allocator, marker,
+/// sweeper, persistent roots and prefinalizers.
+///
+/// The heap is bound to the thread that created it. Garbage collections are
+/// triggered by allocation once the heap reaches the limit set by its
+/// `HeapGrowingPolicy`, or explicitly by the embedder.
+pub struct Heap {
+    marking_support: MarkingType,
+    sweeping_support: SweepingType,
+    initial_heap_size: usize,
+
+    normal_space: NormalPageSpace,
+    large_space: LargePageSpace,
+    object_allocator: ObjectAllocator,
+    sweeper: Sweeper,
+    marker: RefCell<Option<Marker>>,
+    marking_worklists: Arc<MarkingWorklists>,
+    marking_phase: AtomicU8,
+    strong_persistent_region: Rc<PersistentRegion>,
+    weak_persistent_region: Rc<PersistentRegion>,
+    prefinalizer_handler: PrefinalizerHandler,
+    stack: Option<Stack>,
+
+    /// Bytes marked by the last garbage collection plus bytes allocated
+    /// since.
+    allocated_object_size: Cell<usize>,
+    allocated_bytes_since_last_step: Cell<usize>,
+    limit_for_next_gc: Cell<usize>,
+    heap_growing_policy: RefCell<Box<dyn HeapGrowingPolicy>>,
+    /// Bytes marked by the last garbage collection and the time it ended,
+    /// from which the allocation rate is derived.
+    marked_bytes_at_last_gc: Cell<usize>,
+    last_gc_end: Cell<Instant>,
+    epoch: Cell<usize>,
+    in_atomic_pause: Cell<bool>,
+    driven_by_embedder: Cell<bool>,
+
+    metric_recorder: RefCell<Option<Box<dyn MetricRecorder>>>,
+    cycle_stats: RefCell<Option<CycleStats>>,
+}
 
-    // Placeholder for GCConfig.  This would define configuration
-    // parameters for the garbage collector.
-    #[derive(Clone, Copy)]
-    pub struct GCConfig {}
+/// Statistics of the garbage collection in progress, reported to the metric
+/// recorder once sweeping finishes. Concurrent marking time is not
+/// measured, so totals only cover the main thread.
+struct CycleStats {
+    objects_before: usize,
+    memory_before: usize,
+    objects_after: usize,
+    incremental_mark: Duration,
+    incremental_sweep: Duration,
+    atomic_mark: Duration,
+    atomic_sweep: Duration,
+}
 
-    // Placeholder for EmbedderStackState
-    #[derive(Clone, Copy)]
-    pub struct EmbedderStackState {}
+impl Heap {
+    /// Creates a heap. The heap is boxed as pages refer back to it.
+    pub fn create(options: &HeapOptions) -> Box<Heap> {
+        let stack = match options.stack_support {
+            StackSupport::SupportsConservativeStackScan => Some(Stack::new()),
+            StackSupport::NoConservativeStackScan => None,
+        };
+        let initial_heap_size = options
+            .resource_constraints
+            .initial_heap_size_bytes
+            .max(HeapGrowing::K_MIN_LIMIT_FOR_GC);
+        Box::new(Heap {
+            marking_support: options.marking_support,
+            sweeping_support: options.sweeping_support,
+            initial_heap_size,
+            normal_space: NormalPageSpace::new(),
+            large_space: LargePageSpace::new(),
+            object_allocator: ObjectAllocator::new(),
+            sweeper: Sweeper::new(),
+            marker: RefCell::new(None),
+            marking_worklists: Arc::new(MarkingWorklists::new()),
+            marking_phase: AtomicU8::new(MarkingPhase::Idle as u8),
+            strong_persistent_region: Rc::new(PersistentRegion::new()),
+            weak_persistent_region: Rc::new(PersistentRegion::new()),
+            prefinalizer_handler: PrefinalizerHandler::new(),
+            stack,
+            allocated_object_size: Cell::new(0),
+            allocated_bytes_since_last_step: Cell::new(0),
+            limit_for_next_gc: Cell::new(initial_heap_size),
+            heap_growing_policy: RefCell::new(Box::new(HeapGrowing::new())),
+            marked_bytes_at_last_gc: Cell::new(0),
+            last_gc_end: Cell::new(Instant::now()),
+            epoch: Cell::new(0),
+            in_atomic_pause: Cell::new(false),
+            driven_by_embedder: Cell::new(false),
+            metric_recorder: RefCell::new(None),
+            cycle_stats: RefCell::new(None),
+        })
+    }
 
-    // Placeholder for GCInvoker.  This would be responsible for
-    // triggering garbage collection cycles.
-    pub struct GCInvoker {}
+    pub fn normal_space(&self) -> &NormalPageSpace {
+        &self.normal_space
+    }
 
-    impl GCInvoker {
-        pub fn new() -> Self {
-            GCInvoker {}
-        }
+    pub fn large_space(&self) -> &LargePageSpace {
+        &self.large_space
     }
 
-    // Placeholder for HeapGrowing.  This would be responsible for
-    // managing the growth of the heap.
-    pub struct HeapGrowing {}
+    pub fn object_allocator(&self) -> &ObjectAllocator {
+        &self.object_allocator
+    }
 
-    impl HeapGrowing {
-        pub fn new() -> Self {
-            HeapGrowing {}
-        }
+    pub fn sweeper(&self) -> &Sweeper {
+        &self.sweeper
     }
 
-    // Placeholder for HeapBase (likely containing base heap functionality)
-    pub struct HeapBase {}
+    pub fn marking_worklists(&self) -> &Arc<MarkingWorklists> {
+        &self.marking_worklists
+    }
 
-    impl HeapBase {
-      pub fn new() -> Self {
-        HeapBase {}
-      }
+    pub fn strong_persistent_region(&self) -> &Rc<PersistentRegion> {
+        &self.strong_persistent_region
     }
 
-    pub struct Heap {
-        base: HeapBase,
-        platform: Rc<dyn Platform>,
-        options: HeapOptions,
-        config: GCConfig,
-        gc_invoker: GCInvoker,
-        growing: HeapGrowing,
-        generational_gc_enabled: bool,
-        epoch: usize,
-        override_stack_state: Option<EmbedderStackState>,
+    pub fn weak_persistent_region(&self) -> &Rc<PersistentRegion> {
+        &self.weak_persistent_region
     }
 
-    impl Heap {
-        pub fn new(platform: Rc<dyn Platform>, options: HeapOptions) -> Self {
-            Heap {
-                base: HeapBase::new(),
-                platform,
-                options,
-                config: GCConfig {},
-                gc_invoker: GCInvoker::new(),
-                growing: HeapGrowing::new(),
-                generational_gc_enabled: false,
-                epoch: 0,
-                override_stack_state: None,
-            }
-        }
+    pub fn prefinalizer_handler(&self) -> &PrefinalizerHandler {
+        &self.prefinalizer_handler
+    }
 
-        pub fn from(heap: &dyn CppgcHeap) -> &Heap {
-            // This is unsafe because we're casting a trait object to a concrete type.
-            // Make sure that `heap` is actually a `Heap` instance.
-            unsafe { &*(heap as *const dyn CppgcHeap as *const Heap) }
-        }
+    /// The stack of the owning thread, if conservative stack scanning is
+    /// supported.
+    pub fn stack(&self) -> Option<&Stack> {
+        self.stack.as_ref()
+    }
 
-        pub fn from_mut(heap: &mut dyn CppgcHeap) -> &mut Heap {
-            // This is unsafe because we're casting a trait object to a concrete type.
-            // Make sure that `heap` is actually a `Heap` instance.
-            unsafe { &mut *(heap as *mut dyn CppgcHeap as *mut Heap) }
+    pub fn marking_phase(&self) -> MarkingPhase {
+        match self.marking_phase.load(Ordering::Acquire) {
+            0 => MarkingPhase::Idle,
+            1 => MarkingPhase::Marking,
+            _ => MarkingPhase::InvokingPreFinalizers,
         }
+    }
 
-        pub fn as_base(&self) -> &HeapBase {
-            &self.base
-        }
+    fn set_marking_phase(&self, phase: MarkingPhase) {
+        self.marking_phase.store(phase as u8, Ordering::Release);
+    }
 
-        pub fn as_base_mut(&mut self) -> &mut HeapBase {
-            &mut self.base
-        }
+    pub fn is_marking(&self) -> bool {
+        self.marker.borrow().is_some()
+    }
 
-        fn start_garbage_collection(&mut self, _config: GCConfig) {
-            // Implementation of StartGarbageCollection
-        }
+    pub fn is_sweeping(&self) -> bool {
+        self.sweeper.is_sweeping_in_progress()
+    }
 
-        fn finalize_garbage_collection(&mut self, _stack_state: StackState) {
-            // Implementation of FinalizeGarbageCollection
+    /// Reports finished garbage collections and incremental marking and
+    /// sweeping steps to `recorder`.
+    pub fn set_metric_recorder(&self, recorder: Option<Box<dyn MetricRecorder>>) {
+        *self.metric_recorder.borrow_mut() = recorder;
+    }
+
+    /// Number of completed garbage collections.
+    pub fn epoch(&self) -> usize {
+        self.epoch.get()
+    }
+
+    /// Size of the objects that survived the last garbage collection plus
+    /// the size of everything allocated since.
+    /// When set, allocation only advances marking and sweeping; garbage
+    /// collections are started and finalized by the embedder, e.g. by the V8
+    /// heap a `CppHeap` is attached to.
+    pub fn set_driven_by_embedder(&self, driven_by_embedder: bool) {
+        self.driven_by_embedder.set(driven_by_embedder);
+    }
+
+    /// Replaces the policy deciding the limit for the next garbage
+    /// collection, `HeapGrowing` by default.
+    pub fn set_heap_growing_policy(&self, policy: Box<dyn HeapGrowingPolicy>) {
+        *self.heap_growing_policy.borrow_mut() = policy;
+    }
+
+    /// Size of the heap that triggers the next garbage collection.
+    pub fn limit_for_next_gc(&self) -> usize {
+        self.limit_for_next_gc.get()
+    }
+
+    /// Returns true if the heap grew beyond the limit for the next garbage
+    /// collection.
+    pub fn is_allocation_limit_reached(&self) -> bool {
+        self.allocated_object_size.get() >= self.limit_for_next_gc.get()
+    }
+
+    pub fn allocated_object_size(&self) -> usize {
+        self.allocated_object_size.get()
+    }
+
+    /// Allocates memory for an object of `size` bytes, possibly triggering a
+    /// garbage collection first. Returns the uninitialized payload; the
+    /// caller must initialize it and then call `on_object_constructed`.
+    pub fn allocate(&self, size: usize, gc_info_index: GCInfoIndex) -> *mut u8 {
+        self.advance_gc_on_allocation(ObjectAllocator::allocation_size(size));
+        let header = self
+            .object_allocator
+            .allocate_object(self, size, gc_info_index);
+        self.allocated_object_size
+            .set(self.allocated_object_size.get() + header.allocated_size());
+        header.object_start()
+    }
+
+    /// Finishes an allocation once the payload is initialized.
+    ///
+    /// # Safety
+    ///
+    /// `object` must be a payload returned by `allocate` holding an
+    /// initialized object.
+    pub unsafe fn on_object_constructed(&self, object: *const u8) {
+        let header = unsafe { HeapObjectHeader::from_object(object) };
+        let info = GlobalGCInfoTable::gc_info_from_index(header.gc_info_index());
+        if let Some(callback) = info.pre_finalizer {
+            self.prefinalizer_handler
+                .register_prefinalizer(PreFinalizer { object, callback });
         }
+        if let Some(marker) = self.marker.borrow_mut().as_mut() {
+            // Objects allocated during marking are live; their fields must
+            // still be traced as they may refer to unmarked objects.
+            marker.on_object_allocated(header);
+        }
+    }
 
-        fn finalize_garbage_collection_impl(&mut self, _stack_state: StackState) {
-            // Implementation of FinalizeGarbageCollectionImpl
+    fn advance_gc_on_allocation(&self, allocation_size: usize) {
+        if self.in_atomic_pause.get() || !self.object_allocator.is_allocation_allowed() {
+            return;
+        }
+        let since_step = self.allocated_bytes_since_last_step.get() + allocation_size;
+        if since_step < ALLOCATED_BYTES_PER_STEP {
+            self.allocated_bytes_since_last_step.set(since_step);
+            return;
         }
+        self.allocated_bytes_since_last_step.set(0);
 
-        fn finalize_incremental_garbage_collection_if_needed(&mut self, _stack_state: StackState) {
-            // Implementation of FinalizeIncrementalGarbageCollectionIfNeeded
+        if self.is_sweeping() {
+            let _scope = self.object_allocator.no_allocation_scope();
+            self.sweep_incrementally(|| self.sweeper.perform_sweep_step(self, PAGES_PER_SWEEP_STEP));
+        }
+        if self.is_marking() {
+            let done = self.advance_marking(since_step * MARKING_BYTES_PER_ALLOCATED_BYTE);
+            if done && self.stack.is_some() && !self.driven_by_embedder.get() {
+                self.finalize_garbage_collection(StackState::MayContainHeapPointers);
+            }
+            return;
         }
+        if self.driven_by_embedder.get() || !self.is_allocation_limit_reached() {
+            return;
+        }
+        match self.marking_support {
+            MarkingType::Atomic => {
+                // Without stack scanning the embedder has to trigger
+                // collections at points without on-stack references.
+                if self.stack.is_some() {
+                    self.collect_garbage(StackState::MayContainHeapPointers);
+                }
+            }
+            marking_type => self.start_garbage_collection(marking_type),
+        }
+    }
 
-        fn start_incremental_garbage_collection_for_testing(&mut self) {
-            // Implementation of StartIncrementalGarbageCollectionForTesting
+    /// Performs a full atomic garbage collection, finishing any incremental
+    /// cycle in progress.
+    pub fn collect_garbage(&self, stack_state: StackState) {
+        if !self.is_marking() {
+            self.start_garbage_collection(MarkingType::Atomic);
         }
+        self.finalize_garbage_collection(stack_state);
+    }
 
-        fn finalize_incremental_garbage_collection_for_testing(&mut self, _stack_state: EmbedderStackState) {
-            // Implementation of FinalizeIncrementalGarbageCollectionForTesting
+    /// Starts marking. Incremental and concurrent marking then progress on
+    /// allocation, through `advance_marking`, or on background threads until
+    /// `finalize_garbage_collection`.
+    pub fn start_garbage_collection(&self, marking_type: MarkingType) {
+        if self.is_marking() {
+            return;
         }
+        {
+            let _scope = self.object_allocator.no_allocation_scope();
+            self.sweep_incrementally(|| self.sweeper.finish_if_running(self));
+        }
+        *self.cycle_stats.borrow_mut() = Some(CycleStats {
+            objects_before: self.allocated_object_size.get(),
+            memory_before: self.committed_memory(),
+            objects_after: 0,
+            incremental_mark: Duration::ZERO,
+            incremental_sweep: Duration::ZERO,
+            atomic_mark: Duration::ZERO,
+            atomic_sweep: Duration::ZERO,
+        });
+        let marking_type = Self::restrict_marking_type(marking_type, self.marking_support);
+        let mut marker = Marker::new(
+            self.marking_worklists.clone(),
+            MarkingConfig {
+                marking_type,
+                stack_state: StackState::NoHeapPointers,
+            },
+        );
+        self.set_marking_phase(MarkingPhase::Marking);
+        WriteBarrier::enter_marking();
+        marker.start_marking(self);
+        *self.marker.borrow_mut() = Some(marker);
+    }
 
-        pub fn enable_generational_gc(&mut self) {
-            self.generational_gc_enabled = true;
+    fn restrict_marking_type(requested: MarkingType, supported: MarkingType) -> MarkingType {
+        match (requested, supported) {
+            (_, MarkingType::Atomic) | (MarkingType::Atomic, _) => MarkingType::Atomic,
+            (_, MarkingType::Incremental) | (MarkingType::Incremental, _) => {
+                MarkingType::Incremental
+            }
+            _ => MarkingType::IncrementalAndConcurrent,
         }
+    }
 
-        pub fn disable_heap_growing_for_testing(&mut self) {
-            // Implementation of DisableHeapGrowingForTesting
-            // Since HeapGrowing is a placeholder, this is a no-op.
+    /// Performs an incremental marking step tracing roughly `byte_budget`
+    /// bytes. Returns true if marking has no work left and the collection
+    /// can be finalized.
+    pub fn advance_marking(&self, byte_budget: usize) -> bool {
+        let _scope = self.object_allocator.no_allocation_scope();
+        let start = Instant::now();
+        let done = match self.marker.borrow_mut().as_mut() {
+            Some(marker) => marker.advance_marking_with_limits(byte_budget),
+            None => return true,
+        };
+        let duration = start.elapsed();
+        if self.in_atomic_pause.get() {
+            self.add_cycle_time(|stats| &mut stats.atomic_mark, duration);
+        } else {
+            self.add_cycle_time(|stats| &mut stats.incremental_mark, duration);
+            if let Some(recorder) = self.metric_recorder.borrow().as_ref() {
+                recorder.add_main_thread_event_incremental_mark(&MainThreadIncrementalMark {
+                    duration_us: in_us(duration),
+                });
+            }
         }
+        done
     }
 
-    // Placeholder for StackState
-    #[derive(Clone, Copy)]
-    pub struct StackState {}
+    /// Finishes marking in an atomic pause, clears weak references, invokes
+    /// prefinalizers and starts sweeping. Does nothing if no collection is in
+    /// progress.
+    pub fn finalize_garbage_collection(&self, stack_state: StackState) {
+        self.enter_final_pause(stack_state);
+        self.finish_garbage_collection();
+    }
 
-    impl CppgcHeap for Heap {
-        fn collect_garbage(&self, config: GCConfig) {
-          let mut mutable_self = unsafe { &mut *(self as *const Self as *mut Self) };
-            mutable_self.start_garbage_collection(config);
-            mutable_self.finalize_garbage_collection(StackState{});
+    /// Enters the atomic pause of the collection in progress: stops
+    /// concurrent marking and rescans the roots. Marking may still be
+    /// advanced afterwards, e.g. to reach a fixpoint with another heap,
+    /// before `finish_garbage_collection`.
+    pub fn enter_final_pause(&self, stack_state: StackState) {
+        assert!(
+            stack_state == StackState::NoHeapPointers || self.stack.is_some(),
+            "conservative stack scanning is not supported by this heap"
+        );
+        let mut marker = self.marker.borrow_mut();
+        let Some(marker) = marker.as_mut() else {
+            return;
+        };
+        if self.in_atomic_pause.replace(true) {
+            return;
         }
+        let _scope = self.object_allocator.no_allocation_scope();
+        let start = Instant::now();
+        marker.enter_atomic_pause(self, stack_state);
+        self.add_cycle_time(|stats| &mut stats.atomic_mark, start.elapsed());
+    }
 
-        fn start_incremental_garbage_collection(&self, config: GCConfig) {
-          let mut mutable_self = unsafe { &mut *(self as *const Self as *mut Self) };
-            mutable_self.config = config;
-            mutable_self.start_incremental_garbage_collection_for_testing();
+    /// Completes marking in the atomic pause, see `enter_final_pause`.
+    pub fn finish_garbage_collection(&self) {
+        if !self.in_atomic_pause.get() {
+            return;
         }
-
-        fn finalize_incremental_garbage_collection_if_running(&self, config: GCConfig) {
-          let mut mutable_self = unsafe { &mut *(self as *const Self as *mut Self) };
-            mutable_self.config = config;
-            mutable_self.finalize_incremental_garbage_collection_if_needed(StackState{});
+        let Some(mut marker) = self.marker.borrow_mut().take() else {
+            return;
+        };
+        let start = Instant::now();
+        {
+            let _scope = self.object_allocator.no_allocation_scope();
+            marker.finish_marking(self);
+        }
+        let sweep_start = Instant::now();
+        self.add_cycle_time(|stats| &mut stats.atomic_mark, sweep_start - start);
+        {
+            let _scope = self.object_allocator.no_allocation_scope();
+            self.set_marking_phase(MarkingPhase::InvokingPreFinalizers);
+            self.prefinalizer_handler.invoke_pre_finalizers();
+        }
+        self.set_marking_phase(MarkingPhase::Idle);
+        WriteBarrier::leave_marking();
+
+        let marked_bytes = marker.marked_bytes();
+        drop(marker);
+        self.recompute_limit(marked_bytes);
+        self.allocated_object_size.set(marked_bytes);
+        self.epoch.set(self.epoch.get() + 1);
+        if let Some(stats) = self.cycle_stats.borrow_mut().as_mut() {
+            stats.objects_after = marked_bytes;
         }
 
-        fn epoch(&self) -> usize {
-            self.epoch
+        {
+            let _scope = self.object_allocator.no_allocation_scope();
+            let sweeping_type = match self.sweeping_support {
+                SweepingType::Atomic => SweepingType::Atomic,
+                _ => SweepingType::Incremental,
+            };
+            self.sweeper.start(self, sweeping_type);
         }
+        self.add_cycle_time(|stats| &mut stats.atomic_sweep, sweep_start.elapsed());
+        self.in_atomic_pause.set(false);
+        self.report_cycle_if_swept();
+    }
+
+    /// Computes the limit for the next garbage collection through the
+    /// `HeapGrowingPolicy` once `marked_bytes` survived. Must be called
+    /// before `allocated_object_size` is reset.
+    fn recompute_limit(&self, marked_bytes: usize) {
+        let now = Instant::now();
+        let (gc_bytes, gc_duration) = match self.cycle_stats.borrow().as_ref() {
+            Some(stats) => (stats.objects_before, stats.incremental_mark + stats.atomic_mark),
+            None => (self.allocated_object_size.get(), Duration::ZERO),
+        };
+        let gc_time_ms = gc_duration.as_secs_f64() * 1000.0;
+        let input = HeapGrowingInput {
+            old_generation_size: marked_bytes,
+            allocated_bytes: gc_bytes.saturating_sub(self.marked_bytes_at_last_gc.get()),
+            mutator_duration: now.duration_since(self.last_gc_end.get()).saturating_sub(gc_duration),
+            gc_bytes,
+            gc_duration,
+            gc_speed: (gc_time_ms > 0.0).then(|| gc_bytes as f64 / gc_time_ms),
+            min_old_generation_size: self.initial_heap_size,
+            max_old_generation_size: usize::MAX,
+            new_space_capacity: 0,
+            growing_mode: HeapGrowingMode::kDefault,
+        };
+        self.limit_for_next_gc
+            .set(self.heap_growing_policy.borrow_mut().allocation_limit(&input));
+        self.marked_bytes_at_last_gc.set(marked_bytes);
+        self.last_gc_end.set(now);
+    }
+
+    /// Completes sweeping if it is in progress.
+    pub fn finish_sweeping(&self) {
+        let _scope = self.object_allocator.no_allocation_scope();
+        self.sweep_incrementally(|| self.sweeper.finish_if_running(self));
+    }
 
-        fn overridden_stack_state(&self) -> Option<EmbedderStackState> {
-            self.override_stack_state
+    /// Sweeps until a block of `size` bytes is free, see
+    /// `Sweeper::sweep_for_allocation`.
+    pub fn sweep_for_allocation(&self, size: usize) -> bool {
+        self.sweep_incrementally(|| self.sweeper.sweep_for_allocation(self, size))
+    }
+
+    /// Runs a sweeping step on the mutator thread outside of the atomic
+    /// pause and reports the collection if sweeping completed.
+    fn sweep_incrementally<R>(&self, sweep: impl FnOnce() -> R) -> R {
+        if !self.is_sweeping() {
+            return sweep();
+        }
+        let start = Instant::now();
+        let result = sweep();
+        let duration = start.elapsed();
+        self.add_cycle_time(|stats| &mut stats.incremental_sweep, duration);
+        if let Some(recorder) = self.metric_recorder.borrow().as_ref() {
+            recorder.add_main_thread_event_incremental_sweep(&MainThreadIncrementalSweep {
+                duration_us: in_us(duration),
+            });
         }
+        self.report_cycle_if_swept();
+        result
+    }
 
-        fn set_override_stack_state(&mut self, state: EmbedderStackState) {
-            self.override_stack_state = Some(state);
+    fn add_cycle_time(&self, phase: impl FnOnce(&mut CycleStats) -> &mut Duration, duration: Duration) {
+        if let Some(stats) = self.cycle_stats.borrow_mut().as_mut() {
+            *phase(stats) += duration;
         }
+    }
 
-        fn clear_overridden_stack_state(&mut self) {
-            self.override_stack_state = None;
+    /// Size of all pages. Only complete while sweeping is not in progress,
+    /// as the sweeper holds the pages it has yet to sweep.
+    fn committed_memory(&self) -> usize {
+        let large_pages: usize = self
+            .large_space
+            .pages()
+            .iter()
+            .map(|page| LargePage::PAYLOAD_OFFSET + unsafe { page.as_ref() }.payload_size())
+            .sum();
+        self.normal_space.size() * PAGE_SIZE + large_pages
+    }
+
+    fn report_cycle_if_swept(&self) {
+        if self.is_marking() || self.is_sweeping() {
+            return;
         }
+        let Some(stats) = self.cycle_stats.borrow_mut().take() else {
+            return;
+        };
+        let recorder = self.metric_recorder.borrow();
+        let Some(recorder) = recorder.as_ref() else {
+            return;
+        };
+        let main_thread_atomic = Phases {
+            mark_duration_us: in_us(stats.atomic_mark),
+            sweep_duration_us: in_us(stats.atomic_sweep),
+            weak_duration_us: -1,
+            compact_duration_us: 0,
+        };
+        let main_thread_incremental = IncrementalPhases {
+            mark_duration_us: in_us(stats.incremental_mark),
+            sweep_duration_us: in_us(stats.incremental_sweep),
+        };
+        let main_thread = Phases {
+            mark_duration_us: in_us(stats.atomic_mark + stats.incremental_mark),
+            sweep_duration_us: in_us(stats.atomic_sweep + stats.incremental_sweep),
+            ..main_thread_atomic
+        };
+        let objects = sizes(stats.objects_before, stats.objects_after);
+        let duration_us = main_thread.mark_duration_us + main_thread.sweep_duration_us;
+        let efficiency = if duration_us > 0 {
+            objects.freed_bytes as f64 / duration_us as f64
+        } else {
+            -1.0
+        };
+        recorder.add_main_thread_event_gc_cycle(&GCCycle {
+            type_: GCCycleType::Major,
+            total: main_thread,
+            main_thread,
+            main_thread_atomic,
+            main_thread_incremental,
+            objects,
+            memory: sizes(stats.memory_before, self.committed_memory()),
+            collection_rate_in_percent: if objects.before_bytes > 0 {
+                100.0 * objects.freed_bytes as f64 / objects.before_bytes as f64
+            } else {
+                -1.0
+            },
+            efficiency_in_bytes_per_us: efficiency,
+            main_thread_efficiency_in_bytes_per_us: efficiency,
+        });
+    }
+
+    /// Aborts marking in progress, dropping all marking state.
+    fn cancel_marking(&self) {
+        let Some(marker) = self.marker.borrow_mut().take() else {
+            return;
+        };
+        self.in_atomic_pause.set(false);
+        self.cycle_stats.borrow_mut().take();
+        // Dropping the marker joins concurrent markers.
+        drop(marker);
+        self.marking_worklists.clear();
+        self.set_marking_phase(MarkingPhase::Idle);
+        WriteBarrier::leave_marking();
+    }
+}
 
-        // #[cfg(feature = "V8_ENABLE_ALLOCATION_TIMEOUT")]
-        fn update_allocation_timeout(&self) -> Option<i32> {
-            None
+impl Drop for Heap {
+    /// Terminates the heap: clears all persistents, then invokes all
+    /// prefinalizers and destructors and releases the pages.
+    fn drop(&mut self) {
+        self.cancel_marking();
+        self.finish_sweeping();
+        self.strong_persistent_region.clear_all_used_nodes();
+        self.weak_persistent_region.clear_all_used_nodes();
+
+        let _scope = self.object_allocator.no_allocation_scope();
+        self.prefinalizer_handler.invoke_all_pre_finalizers();
+        let normal_pages = self.normal_space.remove_all_pages();
+        let large_pages = self.large_space.remove_all_pages();
+        for page in &normal_pages {
+            unsafe { page.as_ref() }.iterate_headers(|header| {
+                if !header.is_free() {
+                    unsafe { header.finalize() };
+                }
+            });
+        }
+        for page in &large_pages {
+            unsafe { page.as_ref().object_header().finalize() };
+        }
+        self.normal_space.free_list().borrow_mut().clear();
+        for page in normal_pages {
+            unsafe { NormalPage::destroy(page) };
         }
+        for page in large_pages {
+            unsafe { LargePage::destroy(page) };
+        }
+    }
+}
+
+fn in_us(duration: Duration) -> i64 {
+    duration.as_micros() as i64
+}
+
+fn sizes(before: usize, after: usize) -> Sizes {
+    Sizes {
+        before_bytes: before as i64,
+        after_bytes: after as i64,
+        freed_bytes: before as i64 - after as i64,
     }
-}
\ No newline at end of file
+}
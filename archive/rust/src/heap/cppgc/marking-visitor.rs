@@ -1,499 +1,51 @@
-// src/heap/cppgc/marking_visitor.rs
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-use std::sync::atomic::{AtomicPtr, Ordering};
+use crate::heap::cppgc::heap::Heap;
+use crate::heap::cppgc::heap_object_header::HeapObjectHeader;
+use crate::heap::cppgc::heap_page::BasePage;
+use crate::heap::cppgc::marking_state::MarkingState;
+use crate::include::cppgc::member::MemberBase;
+use crate::include::cppgc::visitor::VisitorBase;
 
-// Assuming these are defined elsewhere in the codebase or external crates
-// and that they have appropriate Rust equivalents.
-mod cppgc {
-    pub mod internal {
-        pub const K_SIZE_OF_UNCOMPRESSED_MEMBER: usize = std::mem::size_of::<usize>(); // Assuming usize is a good proxy for raw pointer size
-        pub const K_SIZEOF_COMPRESSED_MEMBER: usize = std::mem::size_of::<u32>(); //Assuming a compressed pointer uses 4 bytes (u32)
-        use std::sync::atomic::{AtomicPtr, Ordering};
-
-        #[derive(Debug)]
-        pub struct RawPointer(AtomicPtr<std::ffi::c_void>);
-
-        impl RawPointer {
-            pub fn new(ptr: *mut std::ffi::c_void) -> Self {
-                RawPointer(AtomicPtr::new(ptr))
-            }
-
-            pub fn load_atomic(&self) -> *mut std::ffi::c_void {
-                self.0.load(Ordering::Relaxed)
-            }
-        }
-
-        #[derive(Debug)]
-        pub struct CompressedPointer(AtomicPtr<std::ffi::c_void>); // TODO: Replace with compressed pointer implementation
-
-        impl CompressedPointer {
-            pub fn new(ptr: *mut std::ffi::c_void) -> Self {
-                CompressedPointer(AtomicPtr::new(ptr))
-            }
-
-            pub fn load_atomic(&self) -> *mut std::ffi::c_void {
-                self.0.load(Ordering::Relaxed)
-            }
-        }
-    }
-}
-
-// Dummy type (no functionality implemented)
-struct Dummy;
-
-type TraceDescriptor = u32; // Replace with actual TraceDescriptor type
-type WeakCallback = fn(*mut std::ffi::c_void);
-type TraceCallback = fn(*mut std::ffi::c_void);
-type TraceConservativelyCallback = fn(&mut ConservativeMarkingVisitor, &HeapObjectHeader);
-
-trait HeapBaseTrait {
-    fn page_backend(&self) -> &PageBackend;
-    fn generational_gc_supported(&self) -> bool;
-    fn remembered_set(&self) -> &RememberedSet;
-}
-
-struct HeapBase {
-    page_backend: PageBackend,
-    generational_gc_support: bool,
-    remembered_set: RememberedSet,
-}
-
-impl HeapBase {
-    fn new(page_backend: PageBackend, generational_gc_support: bool, remembered_set: RememberedSet) -> Self {
-        HeapBase { page_backend, generational_gc_support, remembered_set }
-    }
-}
-
-impl HeapBaseTrait for HeapBase {
-    fn page_backend(&self) -> &PageBackend {
-        &self.page_backend
-    }
-
-    fn generational_gc_supported(&self) -> bool {
-        self.generational_gc_support
-    }
-
-    fn remembered_set(&self) -> &RememberedSet {
-        &self.remembered_set
-    }
-}
-
-// Dummy types
-struct BasicMarkingState;
-struct MutatorMarkingState;
-struct ConcurrentMarkingState;
-struct PageBackend;
-struct RememberedSet;
-struct HeapObjectHeader;
-struct BasePage;
-struct Visitor;
-
-impl BasicMarkingState {
-    fn mark_and_push(&mut self, _object: *const std::ffi::c_void, _desc: TraceDescriptor) {}
-    fn register_weak_reference_if_needed(&mut self, _object: *const std::ffi::c_void, _desc: TraceDescriptor, _weak_callback: WeakCallback, _weak_member: *const std::ffi::c_void) {}
-    fn process_ephemeron(&mut self, _key: *const std::ffi::c_void, _value: *const std::ffi::c_void, _value_desc: TraceDescriptor, _visitor: &mut dyn MarkingVisitor) {}
-    fn process_weak_container(&mut self, _object: *const std::ffi::c_void, _weak_desc: TraceDescriptor, _callback: WeakCallback, _data: *const std::ffi::c_void) {}
-    fn register_weak_custom_callback(&mut self, _callback: WeakCallback, _object: *const std::ffi::c_void) {}
-    fn register_movable_reference(&mut self, _slot: *const *const std::ffi::c_void) {}
-    fn is_marked_weak_container(&self, _header: &HeapObjectHeader) -> bool {false}
-    fn re_trace_marked_weak_container(&mut self, _visitor: &mut Visitor, _header: &HeapObjectHeader) {}
-    fn mark_no_push(&mut self, _header: &HeapObjectHeader) -> bool {true}
-    fn account_marked_bytes(&mut self, _header: &HeapObjectHeader) {}
-    fn concurrent_marking_bailout_worklist(&mut self) -> &mut ConcurrentMarkingBailoutWorklist {
-        todo!()
-    }
-}
-
-struct ConcurrentMarkingBailoutWorklist {}
-
-impl ConcurrentMarkingBailoutWorklist {
-    fn push(&mut self, _item: ConcurrentMarkingBailoutWorklistItem) {}
-}
-
-struct ConcurrentMarkingBailoutWorklistItem {
-    parameter: *mut std::ffi::c_void,
-    callback: TraceCallback,
-    deferred_size: usize
-}
-
-impl ConcurrentMarkingState {
-    fn account_deferred_marked_bytes(&mut self, _page: &BasePage, _size: usize) {}
-}
-
-trait MarkingVisitor {
-    fn visit(&mut self, object: *const std::ffi::c_void, desc: TraceDescriptor);
-    fn visit_multiple_uncompressed_member(&mut self, start: *const std::ffi::c_void, len: usize, get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor);
-    #[cfg(feature = "cppgc_pointer_compression")]
-    fn visit_multiple_compressed_member(&mut self, start: *const std::ffi::c_void, len: usize, get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor);
-    fn visit_weak(&mut self, object: *const std::ffi::c_void, desc: TraceDescriptor, weak_callback: WeakCallback, weak_member: *const std::ffi::c_void);
-    fn visit_ephemeron(&mut self, key: *const std::ffi::c_void, value: *const std::ffi::c_void, value_desc: TraceDescriptor);
-    fn visit_weak_container(&mut self, object: *const std::ffi::c_void, strong_desc: TraceDescriptor, weak_desc: TraceDescriptor, callback: WeakCallback, data: *const std::ffi::c_void);
-    fn register_weak_callback(&mut self, callback: WeakCallback, object: *const std::ffi::c_void);
-    fn handle_movable_reference(&mut self, slot: *const *const std::ffi::c_void);
-}
-
-// MarkingVisitorBase
-struct MarkingVisitorBase<'a> {
-    marking_state_: &'a mut BasicMarkingState,
-}
-
-impl<'a> MarkingVisitorBase<'a> {
-    fn new(marking_state: &'a mut BasicMarkingState) -> Self {
-        MarkingVisitorBase {
-            marking_state_: marking_state,
-        }
-    }
-}
-
-impl<'a> MarkingVisitor for MarkingVisitorBase<'a> {
-    fn visit(&mut self, object: *const std::ffi::c_void, desc: TraceDescriptor) {
-        self.marking_state_.mark_and_push(object, desc);
-    }
-
-    fn visit_multiple_uncompressed_member(
-        &mut self,
-        start: *const std::ffi::c_void,
-        len: usize,
-        get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor,
-    ) {
-        let it = start as *const u8;
-        let end = unsafe { it.add(len * cppgc::internal::K_SIZE_OF_UNCOMPRESSED_MEMBER) };
-        let mut current = it;
-
-        while current < end {
-            unsafe {
-                let raw_ptr = current as *const cppgc::internal::RawPointer;
-                let object = (*raw_ptr).load_atomic() as *const std::ffi::c_void;
-
-                if !object.is_null() {
-                    self.marking_state_.mark_and_push(object, get_trace_descriptor(object));
-                }
-
-                current = current.add(cppgc::internal::K_SIZE_OF_UNCOMPRESSED_MEMBER);
-            }
-        }
-    }
-
-    #[cfg(feature = "cppgc_pointer_compression")]
-    fn visit_multiple_compressed_member(
-        &mut self,
-        start: *const std::ffi::c_void,
-        len: usize,
-        get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor,
-    ) {
-        let it = start as *const u8;
-        let end = unsafe { it.add(len * cppgc::internal::K_SIZEOF_COMPRESSED_MEMBER) };
-        let mut current = it;
-
-        while current < end {
-            unsafe {
-                let compressed_ptr = current as *const cppgc::internal::CompressedPointer;
-                let object = (*compressed_ptr).load_atomic() as *const std::ffi::c_void;
-
-                if !object.is_null() {
-                    self.marking_state_.mark_and_push(object, get_trace_descriptor(object));
-                }
-
-                current = current.add(cppgc::internal::K_SIZEOF_COMPRESSED_MEMBER);
-            }
-        }
-    }
-
-    #[cfg(not(feature = "cppgc_pointer_compression"))]
-    fn visit_multiple_compressed_member(
-        &mut self,
-        _start: *const std::ffi::c_void,
-        _len: usize,
-        _get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor,
-    ) {
-        //Implement only if feature is active
-    }
-
-    fn visit_weak(
-        &mut self,
-        object: *const std::ffi::c_void,
-        desc: TraceDescriptor,
-        weak_callback: WeakCallback,
-        weak_member: *const std::ffi::c_void,
-    ) {
-        self.marking_state_
-            .register_weak_reference_if_needed(object, desc, weak_callback, weak_member);
-    }
-
-    fn visit_ephemeron(
-        &mut self,
-        key: *const std::ffi::c_void,
-        value: *const std::ffi::c_void,
-        value_desc: TraceDescriptor,
-    ) {
-        self.marking_state_.process_ephemeron(key, value, value_desc, self);
-    }
-
-    fn visit_weak_container(
-        &mut self,
-        object: *const std::ffi::c_void,
-        strong_desc: TraceDescriptor,
-        weak_desc: TraceDescriptor,
-        callback: WeakCallback,
-        data: *const std::ffi::c_void,
-    ) {
-        self.marking_state_.process_weak_container(object, weak_desc, callback, data);
-    }
-
-    fn register_weak_callback(&mut self, callback: WeakCallback, object: *const std::ffi::c_void) {
-        self.marking_state_.register_weak_custom_callback(callback, object);
-    }
-
-    fn handle_movable_reference(&mut self, slot: *const *const std::ffi::c_void) {
-        self.marking_state_.register_movable_reference(slot);
-    }
+/// Marks objects found through conservative stack scanning. Any word that
+/// points into a live object of `heap`, including inner pointers, keeps the
+/// object alive.
+pub struct ConservativeMarkingVisitor<'a> {
+    heap: &'a Heap,
+    state: &'a mut MarkingState,
 }
 
-// ConservativeTracingVisitor
-struct ConservativeTracingVisitor<'a> {
-    heap: &'a HeapBase,
-    page_backend: &'a PageBackend,
-    visitor: &'a mut Visitor,
-}
-
-impl <'a> ConservativeTracingVisitor<'a> {
-    fn new(heap: &'a HeapBase, page_backend: &'a PageBackend, visitor: &'a mut Visitor) -> Self {
-        ConservativeTracingVisitor {
-            heap,
-            page_backend,
-            visitor
-        }
+impl<'a> ConservativeMarkingVisitor<'a> {
+    pub fn new(heap: &'a Heap, state: &'a mut MarkingState) -> Self {
+        ConservativeMarkingVisitor { heap, state }
     }
 
-    fn visit_fully_constructed_conservatively(&mut self, _header: &HeapObjectHeader) {}
-    fn trace_conservatively_if_needed(&mut self, _address: *const std::ffi::c_void) {}
-}
-
-// ConservativeMarkingVisitor
-struct ConservativeMarkingVisitor<'a> {
-    conservative_tracing_visitor: ConservativeTracingVisitor<'a>,
-    marking_state_: &'a mut MutatorMarkingState,
-    visitor_: &'a mut Visitor,
-    heap_: &'a HeapBase,
-}
-
-impl <'a> ConservativeMarkingVisitor<'a> {
-    fn new(heap: &'a HeapBase, marking_state: &'a mut MutatorMarkingState, visitor: &'a mut Visitor) -> Self {
-        let conservative_tracing_visitor = ConservativeTracingVisitor::new(heap, heap.page_backend(), visitor);
-        ConservativeMarkingVisitor {
-            conservative_tracing_visitor,
-            marking_state_: marking_state,
-            visitor_: visitor,
-            heap_: heap,
-        }
-    }
-
-    fn visit_fully_constructed_conservatively(&mut self, header: &HeapObjectHeader) {
-        // Assuming HeapObjectHeader has a method `is_marked`
-        // and AccessMode has a Rust equivalent.
-        if true { //header.IsMarked<AccessMode::kAtomic>() {
-            if self.marking_state_.is_marked_weak_container(header) {
-                self.marking_state_.re_trace_marked_weak_container(self.visitor_, header);
-            }
+    pub fn visit_pointer(&mut self, address: usize) {
+        let Some(page) = BasePage::try_from_address(address) else {
             return;
-        }
-        self.conservative_tracing_visitor.visit_fully_constructed_conservatively(header);
-    }
-
-    fn visit_in_construction_conservatively(&mut self, header: &HeapObjectHeader, callback: TraceConservativelyCallback) {
-        //Assuming HeapObjectHeader has methods for marking and accounting
-        assert!(!self.marking_state_.is_marked_weak_container(header));
-        if !self.marking_state_.mark_no_push(header) {
+        };
+        if !std::ptr::eq(page.heap_ptr(), self.heap) {
             return;
         }
-        self.marking_state_.account_marked_bytes(header);
-
-        if self.heap_.generational_gc_supported() {
-            //Assuming remembered_set() and add_in_construction_object_to_be_retraced exist
-            //self.heap_.remembered_set().add_in_construction_object_to_be_retraced(header)
+        if let Some(header) = page.try_object_header_from_inner_address(address) {
+            self.state.mark_and_push(header);
         }
-
-        callback(self, header);
-    }
-
-    fn visit_pointer(&mut self, address: *const std::ffi::c_void) {
-        self.conservative_tracing_visitor.trace_conservatively_if_needed(address);
     }
 }
 
-// MutatorMarkingVisitor
-struct MutatorMarkingVisitor<'a> {
-    marking_visitor_base: MarkingVisitorBase<'a>,
-}
+/// Clears weak references to objects that were not marked. Strong
+/// references are ignored.
+pub struct WeakClearingVisitor;
 
-impl<'a> MutatorMarkingVisitor<'a> {
-    fn new(heap: &'a mut HeapBase, marking_state: &'a mut MutatorMarkingState) -> Self {
-        MutatorMarkingVisitor {
-            marking_visitor_base: MarkingVisitorBase::new(marking_state),
-        }
-    }
-}
+impl VisitorBase for WeakClearingVisitor {
+    fn visit(&mut self, _object: *const u8) {}
 
-impl <'a> MarkingVisitor for MutatorMarkingVisitor<'a> {
-    fn visit(&mut self, object: *const std::ffi::c_void, desc: TraceDescriptor) {
-        self.marking_visitor_base.visit(object, desc);
-    }
-
-    fn visit_multiple_uncompressed_member(
-        &mut self,
-        start: *const std::ffi::c_void,
-        len: usize,
-        get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor,
-    ) {
-        self.marking_visitor_base.visit_multiple_uncompressed_member(start, len, get_trace_descriptor);
-    }
-
-    #[cfg(feature = "cppgc_pointer_compression")]
-    fn visit_multiple_compressed_member(
-        &mut self,
-        start: *const std::ffi::c_void,
-        len: usize,
-        get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor,
-    ) {
-        self.marking_visitor_base.visit_multiple_compressed_member(start, len, get_trace_descriptor);
-    }
-
-    #[cfg(not(feature = "cppgc_pointer_compression"))]
-    fn visit_multiple_compressed_member(
-        &mut self,
-        _start: *const std::ffi::c_void,
-        _len: usize,
-        _get_trace_descriptor: fn(*const std::ffi::c_void) -> TraceDescriptor,
-    ) {
-        //Implement only if feature is active
-    }
-
-    fn visit_weak(
-        &mut self,
-        object: *const std::ffi::c_void,
-        desc: TraceDescriptor,
-        weak_callback: WeakCallback,
-        weak_member: *const std::ffi::c_void,
-    ) {
-        self.marking_visitor_base.visit_weak(object, desc, weak_callback, weak_member);
-    }
-
-    fn visit_ephemeron(
-        &mut self,
-        key: *const std::ffi::c_void,
-        value: *const std::ffi::c_void,
-        value_desc: TraceDescriptor,
-    ) {
-        self.marking_visitor_base.visit_ephemeron(key, value, value_desc);
-    }
-
-    fn visit_weak_container(
-        &mut self,
-        object: *const std::ffi::c_void,
-        strong_desc: TraceDescriptor,
-        weak_desc: TraceDescriptor,
-        callback: WeakCallback,
-        data: *const std::ffi::c_void,
-    ) {
-        self.marking_visitor_base.visit_weak_container(object, strong_desc, weak_desc, callback, data);
-    }
-
-    fn register_weak_callback(&mut self, callback: WeakCallback, object: *const std::ffi::c_void) {
-        self.marking_visitor_base.register_weak_callback(callback, object);
-    }
-
-    fn handle_movable_reference(&mut self, slot: *const *const std::ffi::c_void) {
-        self.marking_visitor_base.handle_movable_reference(slot);
-    }
-}
-
-// RootMarkingVisitor
-struct RootMarkingVisitor<'a> {
-    mutator_marking_state_: &'a mut MutatorMarkingState,
-}
-
-impl<'a> RootMarkingVisitor<'a> {
-    fn new(marking_state: &'a mut MutatorMarkingState) -> Self {
-        RootMarkingVisitor {
-            mutator_marking_state_: marking_state,
+    fn visit_weak(&mut self, slot: &MemberBase) {
+        let object = slot.load();
+        if !object.is_null() && !unsafe { HeapObjectHeader::from_object(object) }.is_marked() {
+            slot.clear();
         }
     }
-
-    fn visit_root(&mut self, object: *const std::ffi::c_void, desc: TraceDescriptor, _location: &SourceLocation) {
-        self.mutator_marking_state_.mark_and_push(object, desc);
-    }
-
-    fn visit_weak_root(
-        &mut self,
-        object: *const std::ffi::c_void,
-        desc: TraceDescriptor,
-        weak_callback: WeakCallback,
-        weak_root: *const std::ffi::c_void,
-        _location: &SourceLocation,
-    ) {
-        self.mutator_marking_state_.invoke_weak_roots_callback_if_needed(object, desc, weak_callback, weak_root);
-    }
 }
-
-impl<'a> RootMarkingVisitor<'a> {
-    fn invoke_weak_roots_callback_if_needed(
-        &mut self,
-        object: *const std::ffi::c_void,
-        desc: TraceDescriptor,
-        weak_callback: WeakCallback,
-        weak_root: *const std::ffi::c_void,
-    ) {
-        // This is a placeholder for the actual logic to invoke weak root callbacks.
-        // In a real implementation, this would handle the logic for dealing with
-        // weak roots and potentially calling the callback.
-        println!(
-            "Visiting weak root: object={:?}, desc={}, weak_callback={:?}, weak_root={:?}",
-            object, desc, weak_callback, weak_root
-        );
-    }
-}
-
-// SourceLocation struct
-struct SourceLocation;
-
-// ConcurrentMarkingVisitor
-struct ConcurrentMarkingVisitor<'a> {
-    marking_visitor_base: MarkingVisitorBase<'a>,
-}
-
-impl<'a> ConcurrentMarkingVisitor<'a> {
-    fn new(heap: &'a mut HeapBase, marking_state: &'a mut ConcurrentMarkingState) -> Self {
-        ConcurrentMarkingVisitor {
-            marking_visitor_base: MarkingVisitorBase::new(marking_state),
-        }
-    }
-
-    fn defer_trace_to_mutator_thread_if_concurrent(
-        &mut self,
-        parameter: *const std::ffi::c_void,
-        callback: TraceCallback,
-        deferred_size: usize,
-    ) -> bool {
-        let state = self.marking_visitor_base.marking_state_ as *mut BasicMarkingState as *mut ConcurrentMarkingState;
-        let state_ref = unsafe {&mut *state};
-        state_ref.concurrent_marking_bailout_worklist().push(ConcurrentMarkingBailoutWorklistItem{
-            parameter: parameter as *mut std::ffi::c_void,
-            callback,
-            deferred_size
-        });
-        let state = self.marking_visitor_base.marking_state_ as *mut BasicMarkingState as *mut ConcurrentMarkingState;
-        let state_ref = unsafe {&mut *state};
-
-        unsafe{
-            state_ref.account_deferred_marked_bytes(&BasePage::from_payload(parameter as *mut std::ffi::c_void), deferred_size);
-        }
-
-        true
-    }
-}
-
-impl BasePage {
-    unsafe fn from_payload(_payload: *mut std::ffi::c_void) -> Self {
-        BasePage {} // Replace with actual logic
-    }
-}
\ No newline at end of file
@@ -2,242 +2,158 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::collections::HashSet;
-use std::sync::{Arc, Mutex, MutexGuard};
-
-// Placeholder for cppgc::Visitor
-pub struct TraceDescriptor {}
-
-// Placeholder for heap::base::Worklist
-pub struct Worklist<T, const N: usize> {
-    items: Vec<T>, // Simulate a worklist with a Vec
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Worklist entries are addresses of `HeapObjectHeader`s.
+pub type WorklistItem = usize;
+
+/// Number of entries moved between local and global worklists at once.
+pub const SEGMENT_SIZE: usize = 64;
+
+/// Global worklists shared between the mutator, the write barrier and
+/// concurrent markers. Markers keep local buffers and exchange segments with
+/// these lists.
+pub struct MarkingWorklists {
+    marking_worklist: Mutex<Vec<WorklistItem>>,
+    work_available: Condvar,
+    /// Objects that concurrent markers found but may not trace because
+    /// their type does not support concurrent tracing.
+    concurrent_marking_bailout_worklist: Mutex<Vec<WorklistItem>>,
+    /// Marked objects holding weak references, processed after marking.
+    weak_callback_worklist: Mutex<Vec<WorklistItem>>,
+    /// Marked objects holding interior-mutable containers, which are traced
+    /// again in the atomic pause.
+    retrace_marked_objects_worklist: Mutex<Vec<WorklistItem>>,
+    /// Traced nodes of the `TracedReference`s found while marking, consumed
+    /// by the V8 marker when the heap is attached to an isolate.
+    traced_references_worklist: Mutex<Vec<usize>>,
 }
 
-impl<T, const N: usize> Worklist<T, N> {
+impl MarkingWorklists {
     pub fn new() -> Self {
-        Worklist { items: Vec::new() }
+        MarkingWorklists {
+            marking_worklist: Mutex::new(Vec::new()),
+            work_available: Condvar::new(),
+            concurrent_marking_bailout_worklist: Mutex::new(Vec::new()),
+            weak_callback_worklist: Mutex::new(Vec::new()),
+            retrace_marked_objects_worklist: Mutex::new(Vec::new()),
+            traced_references_worklist: Mutex::new(Vec::new()),
+        }
     }
 
-    pub fn push(&mut self, item: T) {
-        self.items.push(item);
+    /// Pushes a single object, e.g. from the write barrier.
+    pub fn push(&self, item: WorklistItem) {
+        self.marking_worklist.lock().unwrap().push(item);
+        self.work_available.notify_one();
     }
 
-    pub fn pop(&mut self) -> Option<T> {
-        self.items.pop()
+    /// Moves all entries of `segment` to the global worklist.
+    pub fn push_segment(&self, segment: &mut Vec<WorklistItem>) {
+        if segment.is_empty() {
+            return;
+        }
+        self.marking_worklist.lock().unwrap().append(segment);
+        self.work_available.notify_all();
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.items.is_empty()
+    /// Moves up to `SEGMENT_SIZE` entries to `local`. Returns false if the
+    /// global worklist is empty.
+    pub fn pop_segment(&self, local: &mut Vec<WorklistItem>) -> bool {
+        let mut global = self.marking_worklist.lock().unwrap();
+        if global.is_empty() {
+            return false;
+        }
+        let split = global.len().saturating_sub(SEGMENT_SIZE);
+        local.extend(global.drain(split..));
+        true
     }
 
-    pub fn clear(&mut self) {
-        self.items.clear();
+    /// Blocks until work is available or `timeout` elapses.
+    pub fn wait_for_work(&self, timeout: Duration) {
+        let global = self.marking_worklist.lock().unwrap();
+        if global.is_empty() {
+            let _ = self.work_available.wait_timeout(global, timeout).unwrap();
+        }
     }
-}
 
-// Placeholder for HeapObjectHeader
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct HeapObjectHeader {
-    address: usize,
-}
-
-impl HeapObjectHeader {
-    pub fn new(address: usize) -> Self {
-        HeapObjectHeader { address }
+    /// Wakes up all markers waiting for work.
+    pub fn notify_all(&self) {
+        self.work_available.notify_all();
     }
-}
-
-// Placeholder for cppgc::WeakCallback
-pub type WeakCallback = fn();
-// Placeholder for TraceCallback
-pub type TraceCallback = fn(parameter: *const std::ffi::c_void);
-
-pub mod internal {
-    use super::*;
-
-    pub struct MarkingWorklists {
-        marking_worklist: MarkingWorklist,
-        not_fully_constructed_worklist: NotFullyConstructedWorklist,
-        previously_not_fully_constructed_worklist: PreviouslyNotFullyConstructedWorklist,
-        write_barrier_worklist: WriteBarrierWorklist,
-        weak_container_callback_worklist: WeakCallbackWorklist,
-        weak_custom_callback_worklist: WeakCustomCallbackWorklist,
-        parallel_weak_callback_worklist: WeakCallbackWorklist,
-        concurrent_marking_bailout_worklist: ConcurrentMarkingBailoutWorklist,
-        discovered_ephemeron_pairs_worklist: EphemeronPairsWorklist,
-        ephemeron_pairs_for_processing_worklist: EphemeronPairsWorklist,
-        weak_containers_worklist: WeakContainersWorklist,
-        retrace_marked_objects_worklist: RetraceMarkedObjectsWorklist,
-    }
-
-    impl MarkingWorklists {
-        pub const K_MUTATOR_THREAD_ID: i32 = 0;
-
-        pub type MarkingItem = TraceDescriptor;
-
-        #[derive(Clone, Copy)]
-        pub struct WeakCallbackItem {
-            pub callback: WeakCallback,
-            pub parameter: *const std::ffi::c_void,
-        }
-
-        #[derive(Clone, Copy)]
-        pub struct ConcurrentMarkingBailoutItem {
-            pub parameter: *const std::ffi::c_void,
-            pub callback: TraceCallback,
-            pub bailedout_size: usize,
-        }
-
-        #[derive(Clone, Copy)]
-        pub struct EphemeronPairItem {
-            pub key: *const std::ffi::c_void,
-            pub value: *const std::ffi::c_void,
-            pub value_desc: TraceDescriptor,
-        }
-
-        pub fn new() -> Self {
-            MarkingWorklists {
-                marking_worklist: MarkingWorklist::new(),
-                not_fully_constructed_worklist: NotFullyConstructedWorklist::new(),
-                previously_not_fully_constructed_worklist:
-                    PreviouslyNotFullyConstructedWorklist::new(),
-                write_barrier_worklist: WriteBarrierWorklist::new(),
-                weak_container_callback_worklist: WeakCallbackWorklist::new(),
-                weak_custom_callback_worklist: WeakCustomCallbackWorklist::new(),
-                parallel_weak_callback_worklist: WeakCallbackWorklist::new(),
-                concurrent_marking_bailout_worklist: ConcurrentMarkingBailoutWorklist::new(),
-                discovered_ephemeron_pairs_worklist: EphemeronPairsWorklist::new(),
-                ephemeron_pairs_for_processing_worklist: EphemeronPairsWorklist::new(),
-                weak_containers_worklist: WeakContainersWorklist::new(),
-                retrace_marked_objects_worklist: RetraceMarkedObjectsWorklist::new(),
-            }
-        }
-
-        pub fn marking_worklist(&mut self) -> &mut MarkingWorklist {
-            &mut self.marking_worklist
-        }
-
-        pub fn not_fully_constructed_worklist(&mut self) -> &mut NotFullyConstructedWorklist {
-            &mut self.not_fully_constructed_worklist
-        }
-
-        pub fn previously_not_fully_constructed_worklist(
-            &mut self,
-        ) -> &mut PreviouslyNotFullyConstructedWorklist {
-            &mut self.previously_not_fully_constructed_worklist
-        }
-
-        pub fn write_barrier_worklist(&mut self) -> &mut WriteBarrierWorklist {
-            &mut self.write_barrier_worklist
-        }
-
-        pub fn weak_container_callback_worklist(&mut self) -> &mut WeakCallbackWorklist {
-            &mut self.weak_container_callback_worklist
-        }
 
-        pub fn parallel_weak_callback_worklist(&mut self) -> &mut WeakCallbackWorklist {
-            &mut self.parallel_weak_callback_worklist
-        }
-
-        pub fn weak_custom_callback_worklist(&mut self) -> &mut WeakCustomCallbackWorklist {
-            &mut self.weak_custom_callback_worklist
-        }
-
-        pub fn concurrent_marking_bailout_worklist(
-            &self,
-        ) -> &ConcurrentMarkingBailoutWorklist {
-            &self.concurrent_marking_bailout_worklist
-        }
+    pub fn is_marking_worklist_empty(&self) -> bool {
+        self.marking_worklist.lock().unwrap().is_empty()
+    }
 
-        pub fn concurrent_marking_bailout_worklist_mut(
-            &mut self,
-        ) -> &mut ConcurrentMarkingBailoutWorklist {
-            &mut self.concurrent_marking_bailout_worklist
-        }
+    pub fn push_bailout(&self, item: WorklistItem) {
+        self.concurrent_marking_bailout_worklist
+            .lock()
+            .unwrap()
+            .push(item);
+    }
 
-        pub fn discovered_ephemeron_pairs_worklist(&mut self) -> &mut EphemeronPairsWorklist {
-            &mut self.discovered_ephemeron_pairs_worklist
-        }
+    pub fn take_bailout(&self) -> Vec<WorklistItem> {
+        std::mem::take(&mut *self.concurrent_marking_bailout_worklist.lock().unwrap())
+    }
 
-        pub fn ephemeron_pairs_for_processing_worklist(
-            &mut self,
-        ) -> &mut EphemeronPairsWorklist {
-            &mut self.ephemeron_pairs_for_processing_worklist
+    pub fn push_weak_callbacks(&self, items: &mut Vec<WorklistItem>) {
+        if !items.is_empty() {
+            self.weak_callback_worklist.lock().unwrap().append(items);
         }
+    }
 
-        pub fn weak_containers_worklist(&mut self) -> &mut WeakContainersWorklist {
-            &mut self.weak_containers_worklist
-        }
+    pub fn take_weak_callbacks(&self) -> Vec<WorklistItem> {
+        std::mem::take(&mut *self.weak_callback_worklist.lock().unwrap())
+    }
 
-        pub fn retrace_marked_objects_worklist(&mut self) -> &mut RetraceMarkedObjectsWorklist {
-            &mut self.retrace_marked_objects_worklist
+    pub fn push_retrace(&self, items: &mut Vec<WorklistItem>) {
+        if !items.is_empty() {
+            self.retrace_marked_objects_worklist
+                .lock()
+                .unwrap()
+                .append(items);
         }
+    }
 
-        pub fn clear_for_testing(&mut self) {
-            self.marking_worklist.clear();
-            self.not_fully_constructed_worklist.clear();
-            self.previously_not_fully_constructed_worklist.clear();
-            self.write_barrier_worklist.clear();
-            self.weak_container_callback_worklist.clear();
-            self.weak_custom_callback_worklist.clear();
-            self.parallel_weak_callback_worklist.clear();
-            self.concurrent_marking_bailout_worklist.clear();
-            self.discovered_ephemeron_pairs_worklist.clear();
-            self.ephemeron_pairs_for_processing_worklist.clear();
-            self.weak_containers_worklist.clear();
-            self.retrace_marked_objects_worklist.clear();
-        }
+    pub fn take_retrace(&self) -> Vec<WorklistItem> {
+        std::mem::take(&mut *self.retrace_marked_objects_worklist.lock().unwrap())
     }
 
-    pub type MarkingWorklist = Worklist<MarkingItem, 512>;
-    pub type NotFullyConstructedWorklist = ExternalMarkingWorklist;
-    pub type PreviouslyNotFullyConstructedWorklist = Worklist<HeapObjectHeader, 16>;
-    pub type WeakCallbackWorklist = Worklist<WeakCallbackItem, 64>;
-    pub type WeakCustomCallbackWorklist = Worklist<WeakCallbackItem, 16>;
-    pub type WriteBarrierWorklist = Worklist<HeapObjectHeader, 64>;
-    pub type ConcurrentMarkingBailoutWorklist = Worklist<ConcurrentMarkingBailoutItem, 64>;
-    pub type EphemeronPairsWorklist = Worklist<EphemeronPairItem, 64>;
-    pub type WeakContainersWorklist = ExternalMarkingWorklist;
-    pub type RetraceMarkedObjectsWorklist = Worklist<HeapObjectHeader, 16>;
-
-    pub struct ExternalMarkingWorklist {
-        lock: Arc<Mutex<()>>,
-        objects: Arc<Mutex<HashSet<HeapObjectHeader>>>,
-    }
-
-    impl ExternalMarkingWorklist {
-        pub fn new() -> Self {
-            ExternalMarkingWorklist {
-                lock: Arc::new(Mutex::new(())),
-                objects: Arc::new(Mutex::new(HashSet::new())),
-            }
-        }
+    pub fn push_traced_reference(&self, node: usize) {
+        self.traced_references_worklist.lock().unwrap().push(node);
+    }
 
-        pub fn push(&self, object: HeapObjectHeader) {
-            let _guard = self.lock.lock().unwrap();
-            self.objects.lock().unwrap().insert(object);
-        }
+    pub fn take_traced_references(&self) -> Vec<usize> {
+        std::mem::take(&mut *self.traced_references_worklist.lock().unwrap())
+    }
 
-        pub fn contains(&self, object: HeapObjectHeader) -> bool {
-            let _guard = self.lock.lock().unwrap();
-            self.objects.lock().unwrap().contains(&object)
-        }
+    pub fn is_traced_references_worklist_empty(&self) -> bool {
+        self.traced_references_worklist.lock().unwrap().is_empty()
+    }
 
-        pub fn extract(&self) -> HashSet<HeapObjectHeader> {
-            let _guard = self.lock.lock().unwrap();
-            let mut extracted = HashSet::new();
-            std::mem::swap(&mut extracted, &mut self.objects.lock().unwrap());
-            extracted
-        }
+    /// Returns true if no marking work is left anywhere.
+    pub fn is_empty(&self) -> bool {
+        self.is_marking_worklist_empty()
+            && self
+                .concurrent_marking_bailout_worklist
+                .lock()
+                .unwrap()
+                .is_empty()
+    }
 
-        pub fn clear(&self) {
-            let _guard = self.lock.lock().unwrap();
-            self.objects.lock().unwrap().clear();
-        }
+    /// Drops all entries, e.g. when marking is aborted on heap termination.
+    pub fn clear(&self) {
+        self.marking_worklist.lock().unwrap().clear();
+        self.concurrent_marking_bailout_worklist.lock().unwrap().clear();
+        self.weak_callback_worklist.lock().unwrap().clear();
+        self.retrace_marked_objects_worklist.lock().unwrap().clear();
+        self.traced_references_worklist.lock().unwrap().clear();
+    }
+}
 
-        pub fn is_empty(&self) -> bool {
-            let _guard = self.lock.lock().unwrap();
-            self.objects.lock().unwrap().is_empty()
-        }
+impl Default for MarkingWorklists {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
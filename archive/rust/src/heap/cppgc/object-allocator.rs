@@ -2,293 +2,152 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod internal {
-    pub struct ObjectAllocator;
-    pub struct PreFinalizerHandler;
+use std::cell::Cell;
+
+use crate::heap::cppgc::free_list::Block;
+use crate::heap::cppgc::gc_info_table::GCInfoIndex;
+use crate::heap::cppgc::globals::cppgc::internal::{
+    ALLOCATION_GRANULARITY, FREE_LIST_ENTRY_SIZE, LARGE_OBJECT_SIZE_THRESHOLD,
+};
+use crate::heap::cppgc::heap::Heap;
+use crate::heap::cppgc::heap_object_header::HeapObjectHeader;
+use crate::heap::cppgc::heap_page::{BasePage, LargePage, NormalPage};
+
+/// Allocates object memory from the heap's spaces: normal objects from the
+/// free list (sweeping on demand and adding pages as needed), large objects
+/// on dedicated pages.
+pub struct ObjectAllocator {
+    no_allocation_scope_depth: Cell<usize>,
 }
 
-pub struct AllocationHandle {
+/// Forbids allocation while alive, e.g. while the collector runs trace
+/// callbacks, prefinalizers or destructors.
+pub struct NoAllocationScope<'a> {
+    allocator: &'a ObjectAllocator,
 }
 
-impl AllocationHandle {
-    fn new() -> AllocationHandle {
-        AllocationHandle {}
+impl Drop for NoAllocationScope<'_> {
+    fn drop(&mut self) {
+        let depth = &self.allocator.no_allocation_scope_depth;
+        depth.set(depth.get() - 1);
     }
 }
 
-pub mod internal_ {
-    use std::{alloc::Layout, marker::PhantomData, mem::MaybeUninit, ptr::NonNull};
-
-    use crate::{AllocationHandle, internal::PreFinalizerHandler};
-
-    pub struct StatsCollector;
-    pub struct PageBackend;
-    pub struct GarbageCollector;
-
-    const K_ALLOCATION_GRANULARITY: usize = 8;
-
-    macro_rules! round_up {
-        ($value:expr, $alignment:expr) => {
-            (($value + $alignment - 1) / $alignment) * $alignment
-        };
-    }
-
-    pub struct ObjectAllocator<'a> {
-        raw_heap: &'a mut RawHeap,
-        page_backend: &'a mut PageBackend,
-        stats_collector: &'a mut StatsCollector,
-        prefinalizer_handler: &'a mut PreFinalizerHandler,
-        oom_handler: &'a mut FatalOutOfMemoryHandler,
-        garbage_collector: &'a mut GarbageCollector,
-        #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-        allocation_timeout: Option<i32>,
-    }
-
-    impl<'a> ObjectAllocator<'a> {
-        pub const K_SMALLEST_SPACE_SIZE: usize = 32;
-
-        pub fn new(
-            raw_heap: &'a mut RawHeap,
-            page_backend: &'a mut PageBackend,
-            stats_collector: &'a mut StatsCollector,
-            prefinalizer_handler: &'a mut PreFinalizerHandler,
-            oom_handler: &'a mut FatalOutOfMemoryHandler,
-            garbage_collector: &'a mut GarbageCollector,
-        ) -> Self {
-            ObjectAllocator {
-                raw_heap,
-                page_backend,
-                stats_collector,
-                prefinalizer_handler,
-                oom_handler,
-                garbage_collector,
-                #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-                allocation_timeout: None,
-            }
-        }
-
-        #[inline]
-        pub fn allocate_object(&mut self, size: usize, gcinfo: GCInfoIndex) -> *mut u8 {
-            self.allocate_object_aligned(size, std::alloc::Layout::new::<u8>().align(), gcinfo)
-        }
-
-        #[inline]
-        pub fn allocate_object_aligned(&mut self, size: usize, alignment: usize, gcinfo: GCInfoIndex) -> *mut u8 {
-            #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-            self.trigger_gc_on_allocation_timeout_if_needed();
-
-            let allocation_size = round_up!(size + std::mem::size_of::<HeapObjectHeader>(), K_ALLOCATION_GRANULARITY);
-            let space_type = Self::get_initial_space_index_for_size(allocation_size);
-            let space = NormalPageSpace::from(self.raw_heap.space(space_type));
-            self.allocate_object_on_space_aligned(space, allocation_size, alignment, gcinfo)
-        }
-
-        #[inline]
-        pub fn allocate_object_custom_space(&mut self, size: usize, gcinfo: GCInfoIndex, space_index: CustomSpaceIndex) -> *mut u8 {
-            self.allocate_object_custom_space_aligned(size, std::alloc::Layout::new::<u8>().align(), gcinfo, space_index)
-        }
-
-        #[inline]
-        pub fn allocate_object_custom_space_aligned(&mut self, size: usize, alignment: usize, gcinfo: GCInfoIndex, space_index: CustomSpaceIndex) -> *mut u8 {
-            #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-            self.trigger_gc_on_allocation_timeout_if_needed();
-
-            let allocation_size = round_up!(size + std::mem::size_of::<HeapObjectHeader>(), K_ALLOCATION_GRANULARITY);
-            let space = NormalPageSpace::from(self.raw_heap.custom_space(space_index));
-            self.allocate_object_on_space_aligned(space, allocation_size, alignment, gcinfo)
+impl ObjectAllocator {
+    pub fn new() -> Self {
+        ObjectAllocator {
+            no_allocation_scope_depth: Cell::new(0),
         }
-
-        pub fn reset_linear_allocation_buffers(&mut self) {
-            todo!()
-        }
-        pub fn mark_all_pages_as_young(&mut self) {
-            todo!()
-        }
-
-        #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-        pub fn update_allocation_timeout(&mut self) {
-            todo!()
-        }
-
-        #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-        pub fn get_allocation_timeout_for_testing(&self) -> Option<i32> {
-            self.allocation_timeout
-        }
-
-        fn in_disallow_gc_scope(&self) -> bool {
-            false // Placeholder. Requires access to a GC state.
-        }
-
-        #[inline]
-        fn get_initial_space_index_for_size(size: usize) -> RawHeapRegularSpaceType {
-            if size < 64 {
-                if size < Self::K_SMALLEST_SPACE_SIZE {
-                    RawHeapRegularSpaceType::kNormal1
-                } else {
-                    RawHeapRegularSpaceType::kNormal2
-                }
-            } else if size < 128 {
-                RawHeapRegularSpaceType::kNormal3
-            } else {
-                RawHeapRegularSpaceType::kNormal4
-            }
-        }
-
-        #[inline]
-        fn allocate_object_on_space(&mut self, space: NormalPageSpace, size: usize, gcinfo: GCInfoIndex) -> *mut u8 {
-            todo!()
-        }
-
-        #[inline]
-        fn allocate_object_on_space_aligned(&mut self, space: NormalPageSpace, size: usize, alignment: usize, gcinfo: GCInfoIndex) -> *mut u8 {
-            static_assertions::const_assert!(2 * K_ALLOCATION_GRANULARITY == api_constants::K_MAX_SUPPORTED_ALIGNMENT);
-            static_assertions::const_assert!(K_ALLOCATION_GRANULARITY == std::mem::size_of::<HeapObjectHeader>());
-            static_assertions::const_assert!(K_ALLOCATION_GRANULARITY == api_constants::K_ALLOCATION_GRANULARITY);
-            debug_assert_eq!(2 * std::mem::size_of::<HeapObjectHeader>(), alignment);
-
-            const K_ALIGNMENT: usize = 2 * K_ALLOCATION_GRANULARITY;
-            const K_ALIGNMENT_MASK: usize = K_ALIGNMENT - 1;
-            const K_PADDING_SIZE: usize = K_ALIGNMENT - std::mem::size_of::<HeapObjectHeader>();
-
-            let current_lab = space.linear_allocation_buffer();
-            let current_lab_size = current_lab.size();
-
-            let mut lab_allocation_will_succeed =
-                current_lab_size >= size &&
-                    ((current_lab.start() as usize + std::mem::size_of::<HeapObjectHeader>()) & K_ALIGNMENT_MASK) == 0;
-
-            if !lab_allocation_will_succeed && current_lab_size >= (size + K_PADDING_SIZE) {
-                // let filler_memory = current_lab.allocate(K_PADDING_SIZE);
-                // let filler = Filler::create_at(filler_memory, K_PADDING_SIZE);
-                // NormalPage::from(BasePage::from_payload(&filler))
-                //     .object_start_bitmap()
-                //     .set_bit::<AccessMode::kAtomic>(filler as *const _ as usize);
-                lab_allocation_will_succeed = true;
-            }
-
-            if !lab_allocation_will_succeed {
-                return self.out_of_line_allocate(space, size, alignment, gcinfo);
-            }
-
-            let object = self.allocate_object_on_space(space, size, gcinfo);
-            debug_assert_eq!(0, object as usize & K_ALIGNMENT_MASK);
-            object
-        }
-
-        #[inline]
-        fn out_of_line_allocate(&mut self, space: NormalPageSpace, size: usize, alignment: usize, gcinfo: GCInfoIndex) -> *mut u8 {
-            let mut object: *mut u8 = std::ptr::null_mut();
-            self.out_of_line_allocate_gc_safe_point(space, size, alignment, gcinfo, &mut object);
-            object
-        }
-
-        fn out_of_line_allocate_gc_safe_point(&mut self, space: NormalPageSpace, size: usize, alignment: usize, gcinfo: GCInfoIndex, out_object: &mut *mut u8) {
-            *out_object = self.out_of_line_allocate_impl(space, size, alignment, gcinfo);
-        }
-
-        fn out_of_line_allocate_impl(&mut self, space: NormalPageSpace, size: usize, alignment: usize, gcinfo: GCInfoIndex) -> *mut u8 {
-            todo!()
-        }
-
-        fn try_refill_linear_allocation_buffer(&mut self, space: NormalPageSpace, size: usize) -> bool {
-            todo!()
-        }
-
-        fn try_refill_linear_allocation_buffer_from_free_list(&mut self, space: NormalPageSpace, size: usize) -> bool {
-            todo!()
-        }
-
-        fn try_expand_and_refill_linear_allocation_buffer(&mut self, space: NormalPageSpace) -> bool {
-            todo!()
-        }
-
-        #[cfg(V8_ENABLE_ALLOCATION_TIMEOUT)]
-        fn trigger_gc_on_allocation_timeout_if_needed(&mut self) {
-            todo!()
-        }
-    }
-
-    // Mocked dependencies (replace with actual implementations)
-    #[derive(Clone, Copy)]
-    pub enum RawHeapRegularSpaceType {
-        kNormal1,
-        kNormal2,
-        kNormal3,
-        kNormal4,
-    }
-
-    pub struct RawHeap {
     }
 
-    impl RawHeap {
-        pub fn space(&mut self, space_type: RawHeapRegularSpaceType) -> &mut Space {
-            todo!()
-        }
-        pub fn custom_space(&mut self, space_index: CustomSpaceIndex) -> &mut Space {
-            todo!()
+    /// Size of the block holding an object of `object_size` bytes.
+    pub const fn allocation_size(object_size: usize) -> usize {
+        let size = (object_size + HeapObjectHeader::SIZE + ALLOCATION_GRANULARITY - 1)
+            & !(ALLOCATION_GRANULARITY - 1);
+        // Every block must be able to turn into a free-list entry later.
+        if size < FREE_LIST_ENTRY_SIZE {
+            FREE_LIST_ENTRY_SIZE
+        } else {
+            size
         }
     }
 
-    pub struct Space {
+    pub fn no_allocation_scope(&self) -> NoAllocationScope<'_> {
+        let depth = &self.no_allocation_scope_depth;
+        depth.set(depth.get() + 1);
+        NoAllocationScope { allocator: self }
     }
 
-    #[derive(Clone, Copy)]
-    pub struct NormalPageSpace;
-
-    impl NormalPageSpace {
-        pub fn from(space: &mut Space) -> NormalPageSpace {
-            todo!()
-        }
-        pub fn linear_allocation_buffer(&mut self) -> LinearAllocationBuffer {
-            todo!()
-        }
+    pub fn is_allocation_allowed(&self) -> bool {
+        self.no_allocation_scope_depth.get() == 0
     }
 
-    #[derive(Clone, Copy)]
-    pub struct LinearAllocationBuffer {
+    /// Allocates a block for an object of `object_size` bytes and returns
+    /// the header of the block. The payload is uninitialized.
+    pub fn allocate_object(
+        &self,
+        heap: &Heap,
+        object_size: usize,
+        gc_info_index: GCInfoIndex,
+    ) -> &HeapObjectHeader {
+        assert!(
+            self.is_allocation_allowed(),
+            "garbage-collected objects may not be allocated while the collector \
+             runs trace callbacks, prefinalizers or destructors"
+        );
+        let allocation_size = Self::allocation_size(object_size);
+        if allocation_size >= LARGE_OBJECT_SIZE_THRESHOLD {
+            return self.allocate_large_object(heap, allocation_size, gc_info_index);
+        }
+        let address = self.allocate_from_free_list(heap, allocation_size).unwrap_or_else(|| {
+            let swept = {
+                let _scope = self.no_allocation_scope();
+                heap.sweep_for_allocation(allocation_size)
+            };
+            if let Some(address) = swept
+                .then(|| self.allocate_from_free_list(heap, allocation_size))
+                .flatten()
+            {
+                return address;
+            }
+            self.add_page(heap);
+            self.allocate_from_free_list(heap, allocation_size)
+                .expect("fresh page must satisfy a normal allocation")
+        });
+        unsafe { HeapObjectHeader::create(address, allocation_size, gc_info_index) }
     }
 
-    impl LinearAllocationBuffer {
-        pub fn size(&self) -> usize {
-            todo!()
-        }
-        pub fn start(&self) -> *mut u8 {
-            todo!()
-        }
-        pub fn allocate(&mut self, size: usize) -> *mut u8 {
-            todo!()
+    fn allocate_large_object(
+        &self,
+        heap: &Heap,
+        allocation_size: usize,
+        gc_info_index: GCInfoIndex,
+    ) -> &HeapObjectHeader {
+        let page = LargePage::create(heap, allocation_size);
+        heap.large_space().add_page(page);
+        let address = unsafe { page.as_ref() }.payload_start();
+        unsafe {
+            HeapObjectHeader::create(
+                address,
+                HeapObjectHeader::LARGE_OBJECT_SIZE_IN_HEADER,
+                gc_info_index,
+            )
         }
     }
 
-    pub struct HeapObjectHeader {
-        size: usize,
-        gcinfo: GCInfoIndex,
-    }
-
-    impl HeapObjectHeader {
-        pub fn new(size: usize, gcinfo: GCInfoIndex) -> Self {
-            HeapObjectHeader { size, gcinfo }
-        }
-        pub fn object_start(&self) -> *mut u8 {
-            todo!()
+    fn allocate_from_free_list(&self, heap: &Heap, allocation_size: usize) -> Option<usize> {
+        let mut free_list = heap.normal_space().free_list().borrow_mut();
+        let block = free_list.allocate(allocation_size)?;
+        let remainder = block.size - allocation_size;
+        if remainder > 0 {
+            let remainder_address = block.address + allocation_size;
+            let page = unsafe { BasePage::from_payload(block.address as *const u8) };
+            page.as_normal().object_start_bitmap().set_bit(remainder_address);
+            unsafe {
+                free_list.add(Block {
+                    address: remainder_address,
+                    size: remainder,
+                })
+            };
         }
+        Some(block.address)
     }
 
-    pub type GCInfoIndex = u32;
-    pub type CustomSpaceIndex = u32;
-
-    pub struct FatalOutOfMemoryHandler;
-
-    pub mod api_constants {
-        pub const K_MAX_SUPPORTED_ALIGNMENT: usize = 16;
-        pub const K_ALLOCATION_GRANULARITY: usize = 8;
+    fn add_page(&self, heap: &Heap) {
+        let page = NormalPage::create(heap);
+        heap.normal_space().add_page(page);
+        let page = unsafe { page.as_ref() };
+        let block = Block {
+            address: page.payload_start(),
+            size: NormalPage::PAYLOAD_SIZE,
+        };
+        page.object_start_bitmap().set_bit(block.address);
+        unsafe { heap.normal_space().free_list().borrow_mut().add(block) };
     }
-    pub mod static_assertions{
-        macro_rules! const_assert {
-            ($condition:expr) => {
-                const _: [(); 0 - !($condition) as usize] = [];
-            };
-        }
-        pub(crate) use const_assert;
+}
+
+impl Default for ObjectAllocator {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
@@ -2,118 +2,81 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod internal {
-    use std::{mem, sync::atomic::{AtomicBool, AtomicUsize, Ordering}, vec};
-
-    // Since cppgc::prefinalizer.h doesn't exist in standard Rust, we define a basic equivalent
-    pub mod cppgc_prefinalizer {
-        pub type Callback = Box<dyn Fn(*mut std::ffi::c_void) + Send + Sync>;
-
-        pub struct PrefinalizerRegistration {} // Placeholder, actual registration logic will be different in Rust
-
-        impl PrefinalizerRegistration {
-            pub fn new() -> Self {
-                PrefinalizerRegistration {}
-            }
-
-            pub fn register(_callback: Callback) -> Self {
-                PrefinalizerRegistration {}
-            }
+use std::cell::{Cell, RefCell};
+
+use crate::heap::cppgc::gc_info_table::PreFinalizerCallback;
+use crate::heap::cppgc::heap_object_header::HeapObjectHeader;
+
+/// A registered prefinalizer: the object and the callback to invoke on it.
+#[derive(Clone, Copy)]
+pub struct PreFinalizer {
+    pub object: *const u8,
+    pub callback: PreFinalizerCallback,
+}
+
+/// Keeps track of objects with prefinalizers and invokes them for objects
+/// found dead by marking, before any object is swept. At that point all
+/// objects are still intact, so prefinalizers may access other (even dead)
+/// objects.
+pub struct PrefinalizerHandler {
+    ordered_pre_finalizers: RefCell<Vec<PreFinalizer>>,
+    is_invoking: Cell<bool>,
+}
+
+impl PrefinalizerHandler {
+    pub fn new() -> Self {
+        PrefinalizerHandler {
+            ordered_pre_finalizers: RefCell::new(Vec::new()),
+            is_invoking: Cell::new(false),
         }
     }
 
-    pub(crate) trait HeapBase {
-        // Define common heap operations that PreFinalizerHandler depends on.
-        // The actual implementation will be in a concrete Heap struct
-        fn is_valid(&self) -> bool;
-        // more heap methods
-    }
-
-    /// Represents a pre-finalizer with its associated object and callback.
-    #[derive(PartialEq, Eq)]
-    pub struct PreFinalizer {
-        /// The object associated with the pre-finalizer.
-        pub object: *mut std::ffi::c_void,
-        /// The callback to be executed during pre-finalization.
-        pub callback: cppgc_prefinalizer::Callback,
+    pub fn register_prefinalizer(&self, pre_finalizer: PreFinalizer) {
+        debug_assert!(!self.is_invoking.get());
+        self.ordered_pre_finalizers.borrow_mut().push(pre_finalizer);
     }
 
-    impl PreFinalizer {
-        fn new(object: *mut std::ffi::c_void, callback: cppgc_prefinalizer::Callback) -> Self {
-            PreFinalizer { object, callback }
-        }
+    /// Invokes the prefinalizers of all unmarked objects in reverse
+    /// registration order and unregisters them.
+    pub fn invoke_pre_finalizers(&self) {
+        self.invoke_if(|object| {
+            !unsafe { HeapObjectHeader::from_object(object) }.is_marked()
+        });
     }
 
-    /// Manages the registration and invocation of pre-finalizers.
-    pub struct PreFinalizerHandler<'a> {
-        ordered_pre_finalizers: Vec<PreFinalizer>,
-        current_ordered_pre_finalizers: *mut Vec<PreFinalizer>, //Mutable pointer is used for matching C++ implementation but unsafe
-        heap: &'a dyn HeapBase,
-        is_invoking: AtomicBool,
-        bytes_allocated_in_prefinalizers: AtomicUsize,
+    /// Invokes all prefinalizers. Used on heap termination.
+    pub fn invoke_all_pre_finalizers(&self) {
+        self.invoke_if(|_| true);
     }
 
-    impl<'a> PreFinalizerHandler<'a> {
-        /// Creates a new `PreFinalizerHandler` instance.
-        pub fn new(heap: &'a dyn HeapBase) -> Self {
-            let ordered_pre_finalizers = Vec::new();
-            PreFinalizerHandler {
-                ordered_pre_finalizers,
-                current_ordered_pre_finalizers: std::ptr::null_mut(),
-                heap,
-                is_invoking: AtomicBool::new(false),
-                bytes_allocated_in_prefinalizers: AtomicUsize::new(0),
+    fn invoke_if(&self, is_dead: impl Fn(*const u8) -> bool) {
+        self.is_invoking.set(true);
+        // Callbacks run without the list borrowed.
+        let pre_finalizers = std::mem::take(&mut *self.ordered_pre_finalizers.borrow_mut());
+        let mut survivors = Vec::with_capacity(pre_finalizers.len());
+        for pre_finalizer in pre_finalizers.into_iter().rev() {
+            if is_dead(pre_finalizer.object) {
+                unsafe { (pre_finalizer.callback)(pre_finalizer.object) };
+            } else {
+                survivors.push(pre_finalizer);
             }
         }
+        survivors.reverse();
+        *self.ordered_pre_finalizers.borrow_mut() = survivors;
+        self.is_invoking.set(false);
+    }
 
-        /// Registers a pre-finalizer to be invoked before the object is finalized.
-        pub fn register_prefinalizer(&mut self, pre_finalizer: PreFinalizer) {
-            self.ordered_pre_finalizers.push(pre_finalizer);
-        }
-
-        /// Invokes all registered pre-finalizers in reverse order of registration.
-        pub fn invoke_pre_finalizers(&mut self) {
-            if self.is_invoking_.load(Ordering::Relaxed) {
-                return;
-            }
-
-            self.is_invoking_.store(true, Ordering::Relaxed);
-            self.current_ordered_pre_finalizers = &mut self.ordered_pre_finalizers as *mut Vec<PreFinalizer>; //Set to the address of `ordered_pre_finalizers`
-
-            // Note: Ensure proper error handling and resource management during pre-finalizer invocation.
-            //       This might involve using `catch_unwind` to prevent panics from unwinding into C++ code.
-            unsafe {
-                if !self.current_ordered_pre_finalizers.is_null() {
-                    let pre_finalizers = &mut *self.current_ordered_pre_finalizers; // Dereference the raw pointer
-
-                    for pre_finalizer in pre_finalizers.iter().rev() {
-                        (pre_finalizer.callback)(pre_finalizer.object);
-                    }
-                }
-            }
-
-            self.is_invoking_.store(false, Ordering::Relaxed);
-            self.current_ordered_pre_finalizers = std::ptr::null_mut(); // Reset to null after use
-        }
-
-        /// Checks if pre-finalizers are currently being invoked.
-        pub fn is_invoking_pre_finalizers(&self) -> bool {
-            self.is_invoking_.load(Ordering::Relaxed)
-        }
-
-        pub fn notify_allocation_in_prefinalizer(&self, size: usize) {
-            self.bytes_allocated_in_prefinalizers.fetch_add(size, Ordering::Relaxed);
-        }
+    pub fn is_invoking_pre_finalizers(&self) -> bool {
+        self.is_invoking.get()
+    }
 
-        pub fn extract_bytes_allocated_in_prefinalizers(&self) -> usize {
-            self.bytes_allocated_in_prefinalizers.swap(0, Ordering::Relaxed)
-        }
+    pub fn size(&self) -> usize {
+        self.ordered_pre_finalizers.borrow().len()
+    }
+}
 
-        fn current_thread_is_creation_thread(&self) -> bool {
-            // In Rust, we might need to use thread IDs and compare them.
-            // This is a placeholder and requires proper implementation based on
-            // how the heap is created and managed in the Rust environment.
-            true // Placeholder - needs actual thread ID comparison logic
-        }
+impl Default for PrefinalizerHandler {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
@@ -2,212 +2,46 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::{Mutex, MutexGuard, OnceLock};
-use std::vec::Vec;
-//use perfetto; // Placeholder for perfetto crate
-use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
 
-mod base {
-    pub mod macros {
-        #[macro_export]
-        macro_rules! V8_INLINE {
-            ($x:item) => {
-                #[inline]
-                $x
-            };
-        }
-    }
-    pub mod platform {
-        use std::sync::Mutex;
-
-        pub struct LazyMutex {
-            mutex: OnceLock<Mutex<()>>,
-        }
-
-        impl LazyMutex {
-            pub const fn new() -> Self {
-                Self {
-                    mutex: OnceLock::new(),
-                }
-            }
-
-            pub fn lock(&self) -> MutexGuard<'_, ()> {
-                self.mutex.get_or_init(|| Mutex::new(())).lock().unwrap()
-            }
+use crate::heap::cppgc::heap::Heap;
 
-            pub fn pointer(&self) -> &Mutex<()> {
-                self.mutex.get_or_init(|| Mutex::new(()))
-            }
-            pub fn assert_held(&self){
-                // No direct equivalent in Rust's Mutex.  Can be approximated with thread-local state.
-                // Leaving this empty for now.
-                //panic!("AssertHeld is not implemented.");
-            }
-        }
-    }
+struct PageEntry {
+    end: usize,
+    heap: usize,
 }
 
-mod heap {
-    pub mod cppgc {
-        pub mod trace_event {
-            // Placeholder for trace event functionality.
-            // Can be implemented using tracing crate or custom logic.
-            #[macro_export]
-            macro_rules! TRACE_EVENT_BEGIN {
-                ($category:expr, $name:expr, $track:expr) => {
-                    //println!("TRACE_EVENT_BEGIN: category={}, name={}, track={}", $category, $name, stringify!($track));
-                    // Add implementation for tracing here
-                };
-            }
-
-            #[macro_export]
-            macro_rules! TRACE_EVENT_END {
-                ($category:expr, $track:expr) => {
-                    //println!("TRACE_EVENT_END: category={}, track={}", $category, stringify!($track));
-                    // Add implementation for tracing here
-                };
-            }
-
-            #[macro_export]
-            macro_rules! TRACE_DISABLED_BY_DEFAULT {
-                ($name:expr) => {
-                    $name
-                }
-            }
-        }
-
-        pub mod process_heap {
-            use std::sync::{Mutex, MutexGuard, OnceLock};
-            use std::vec::Vec;
-            use std::sync::atomic::{AtomicPtr, Ordering};
-            use super::super::super::base;
-            use super::super::super::base::macros::V8_INLINE;
-
-            //use super::perfetto; // Placeholder for perfetto crate
-            use super::trace_event::{TRACE_EVENT_BEGIN, TRACE_EVENT_END, TRACE_DISABLED_BY_DEFAULT};
-            
-            
-            #[derive(Default)]
-            struct LazyMutex(base::platform::LazyMutex);
-
-            impl LazyMutex {
-                const fn new() -> Self {
-                    Self(base::platform::LazyMutex::new())
-                }
-                fn lock(&self) -> MutexGuard<'_, ()> {
-                    self.0.lock()
-                }
-            }
-            
-            static PROCESS_MUTEX: LazyMutex = LazyMutex::new();
-
-            pub struct ProcessGlobalLock {}
-
-            impl ProcessGlobalLock {
-                pub enum Reason {
-                    kForGC,
-                    kForCrossThreadHandleCreation,
-                }
-
-                #[V8_INLINE]
-                pub fn lock<const REASON: u32>() {
-                    PROCESS_MUTEX.lock();
-                    // C++ Template Enum workaround
-                    if REASON == 0 { // Reason::kForGC
-                        #[cfg(feature = "v8_use_perfetto")]
-                        unsafe {
-                            TRACE_EVENT_BEGIN!(TRACE_DISABLED_BY_DEFAULT!("cppgc"), "AcquiredForGC", perfetto::NamedTrack("CppGC.ProcessGlobalLock"));
-                        }
-                    } else if REASON == 1 { // Reason::kForCrossThreadHandleCreation
-                        #[cfg(all(feature = "v8_use_perfetto", debug_assertions))]
-                        unsafe {
-                            TRACE_EVENT_BEGIN!(TRACE_DISABLED_BY_DEFAULT!("cppgc"), "AcquiredForCrossThreadHandleCreation", perfetto::NamedTrack("CppGC.ProcessGlobalLock"));
-                        }
-                    }
-                }
-
-                #[V8_INLINE]
-                pub fn unlock<const REASON: u32>() {
-                    if REASON == 0 { // Reason::kForGC
-                        #[cfg(feature = "v8_use_perfetto")]
-                        unsafe {
-                            TRACE_EVENT_END!(TRACE_DISABLED_BY_DEFAULT!("cppgc"), perfetto::NamedTrack("CppGC.ProcessGlobalLock"));
-                        }
-                    } else if REASON == 1 { // Reason::kForCrossThreadHandleCreation
-                         #[cfg(all(feature = "v8_use_perfetto", debug_assertions))]
-                        unsafe {
-                           TRACE_EVENT_END!(TRACE_DISABLED_BY_DEFAULT!("cppgc"), perfetto::NamedTrack("CppGC.ProcessGlobalLock"));
-                        }
-                    }
-                    PROCESS_MUTEX.lock();
-                }
-
-                 #[V8_INLINE]
-                pub fn assert_held(){
-                    PROCESS_MUTEX.0.assert_held();
-                }
-            }
-
-            // Forward declaration
-            pub trait HeapBase {
-                fn as_any(&self) -> &dyn Any;
-            }
-
-            pub struct HeapRegistry {}
-
-            impl HeapRegistry {
-                pub type Storage = Vec<*mut dyn HeapBase>;
-
-                pub struct Subscription<'a> {
-                    heap_: *mut dyn HeapBase,
-                    _phantom: std::marker::PhantomData<&'a dyn HeapBase>,
-                }
-
-                impl<'a> Subscription<'a> {
-                    #[inline]
-                    pub fn new(heap: &'a mut dyn HeapBase) -> Self {
-                        let heap_ptr: *mut dyn HeapBase = heap;
-                        HeapRegistry::register_heap(heap_ptr);
-                        Subscription { heap_: heap_ptr, _phantom: std::marker::PhantomData }
-                    }
-                }
-
-                impl<'a> Drop for Subscription<'a> {
-                    fn drop(&mut self) {
-                        HeapRegistry::unregister_heap(self.heap_);
-                    }
-                }
-
-                pub fn try_from_managed_pointer(needle: *const std::ffi::c_void) -> Option<*mut dyn HeapBase> {
-                    let guard = HEAPS.lock().unwrap();
-                    for &heap in &*guard {
-                        //TODO Implement logic to check if needle is a managed pointer
-                        //The placeholder returns an option with the first item
-                        return Some(heap);
-                    }
-                    None
-                }
-
-                // Does not take the registry mutex and is thus only useful for testing.
-                pub fn get_registered_heaps_for_testing() -> Storage {
-                    let guard = HEAPS.lock().unwrap();
-                    guard.clone()
-                }
-
-                fn register_heap(heap: *mut dyn HeapBase) {
-                    let mut guard = HEAPS.lock().unwrap();
-                    guard.push(heap);
-                }
+/// Process-wide registry of the memory ranges of all heap pages. Allows
+/// mapping arbitrary addresses to the heap (and page) owning them.
+pub struct HeapRegistry;
+
+static PAGES: RwLock<BTreeMap<usize, PageEntry>> = RwLock::new(BTreeMap::new());
+
+impl HeapRegistry {
+    /// Registers the page `[start, end)` as owned by `heap`.
+    pub fn register_page(start: usize, end: usize, heap: *const Heap) {
+        let previous = PAGES.write().unwrap().insert(
+            start,
+            PageEntry {
+                end,
+                heap: heap as usize,
+            },
+        );
+        debug_assert!(previous.is_none());
+    }
 
-                fn unregister_heap(heap: *mut dyn HeapBase) {
-                    let mut guard = HEAPS.lock().unwrap();
-                    guard.retain(|&x| x != heap);
-                }
-            }
+    /// Removes the page starting at `start`.
+    pub fn unregister_page(start: usize) {
+        let removed = PAGES.write().unwrap().remove(&start);
+        debug_assert!(removed.is_some());
+    }
 
-            lazy_static::lazy_static! {
-                static ref HEAPS: Mutex<HeapRegistry::Storage> = Mutex::new(Vec::new());
-            }
-        }
+    /// Returns the start of the page containing `address` and the heap owning
+    /// it, or None if `address` is not managed by any heap.
+    pub fn try_from_managed_pointer(address: usize) -> Option<(usize, *const Heap)> {
+        let pages = PAGES.read().unwrap();
+        let (&start, entry) = pages.range(..=address).next_back()?;
+        (address < entry.end).then_some((start, entry.heap as *const Heap))
     }
-}
\ No newline at end of file
+}
@@ -2,415 +2,83 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/cppgc/write-barrier.h (Placeholder - Define module structure here)
-mod write_barrier {
-    use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-    pub struct Params {
-        pub type_: Type,
-    }
-
-    #[derive(PartialEq, Eq, Copy, Clone)]
-    pub enum Type {
-        kNone,
-        kMarking,
-        kGenerational,
-    }
-
-    pub enum GenerationalBarrierType {
-        kPreciseSlot,
-    }
-
-    pub enum WriteBarrierSlotType {
-        kUncompressed,
-        kCompressed,
-    }
-
-    pub trait FlagUpdater {
-        fn enter();
-        fn exit();
-    }
-
-    // Placeholder for HeapHandle, HeapBase, AgeTable, etc.
-    pub struct HeapHandle {}
-    pub struct HeapBase {}
-    pub struct AgeTable {}
-    pub struct CagedHeapLocalData {}
-    pub struct HeapObjectHeader {}
-    pub struct BasePage {}
-    pub struct Marker {}
-    pub struct MarkerBase {}
-    pub struct RememberedSet {}
-    pub struct CompressedPointer {}
-    pub struct YoungGenerationEnabler {}
-
-    impl HeapBase {
-        pub fn from(_handle: &HeapHandle) -> &mut Self {
-            todo!()
-        }
-        pub fn marker(&self) -> Option<&Marker> {
-            todo!()
-        }
-        pub fn in_atomic_pause(&self) -> bool {
-            todo!()
-        }
-        pub fn remembered_set(&mut self) -> &mut RememberedSet {
-            todo!()
-        }
-        pub fn is_incremental_marking_in_progress(&self) -> bool {
-            todo!()
-        }
-    }
-    impl AgeTable {
-        pub enum Age {
-            kOld,
-            kYoung,
-        }
-        pub fn get_age(&self, _offset: uintptr_t) -> Age {
-            todo!()
-        }
-    }
-    impl RememberedSet {
-        pub fn add_slot(&mut self, _slot: *mut std::ffi::c_void) {
-            todo!()
-        }
-        pub fn add_uncompressed_slot(&mut self, _slot: *mut std::ffi::c_void) {
-            todo!()
-        }
-        pub fn add_source_object(&mut self, _header: &HeapObjectHeader) {
-            todo!()
-        }
-    }
-    impl BasePage {
-        pub fn from_payload(_value: *const std::ffi::c_void) -> *mut Self {
-            todo!()
-        }
-        pub fn heap(&self) -> &Heap {
-            todo!()
-        }
-        pub fn object_header_from_inner_address<T>(&self, _value: *const std::ffi::c_void) -> HeapObjectHeader {
-            todo!()
-        }
-    }
-    impl Marker {
-        pub fn visitor(&mut self) -> &mut MarkingVisitor {
-            todo!()
-        }
-        pub fn write_barrier_for_object<T>(&mut self, _header: HeapObjectHeader) {
-            todo!()
-        }
-    }
-    pub struct Heap {}
-    impl Heap {
-        pub fn marker(&self) -> Option<&Marker> {
-            todo!()
-        }
-        pub fn in_atomic_pause(&self) -> bool {
-            todo!()
-        }
-        pub fn is_incremental_marking_in_progress(&self) -> bool {
-            todo!()
-        }
-    }
-    pub struct MarkingVisitor {}
-    impl CompressedPointer {
-        pub type IntegralType = usize;
-        pub fn decompress(_value: Self::IntegralType) -> *const std::ffi::c_void {
-            todo!()
-        }
-    }
-    pub type uintptr_t = usize;
-
-    // src/heap/cppgc/write-barrier.cc
-    use std::sync::{Mutex, MutexGuard};
-    use std::sync::Once;
-
-    static SENTINEL_POINTER: *const std::ffi::c_void = std::ptr::null();
-
-    // Placeholder for constants
-    mod api_constants {
-        pub const K_CAGED_HEAP_MAX_RESERVATION_SIZE: usize = 1024; // Dummy value
-    }
-
-    // static
-    static WRITE_BARRIER_ENABLED: AtomicBool = AtomicBool::new(false);
-
-    // static
-    pub fn dijkstra_marking_barrier_slow_with_sentinel_check(value: *const std::ffi::c_void) {
-        if value.is_null() || value == SENTINEL_POINTER {
-            return;
-        }
-
-        dijkstra_marking_barrier_slow(value);
-    }
-
-    // static
-    pub fn dijkstra_marking_barrier_slow(value: *const std::ffi::c_void) {
-        let page = unsafe { &mut *BasePage::from_payload(value) };
-        let heap = page.heap();
-
-        // GetWriteBarrierType() checks marking state.
-        if let Some(_marker) = heap.marker() {
-            // No write barriers should be executed from atomic pause marking.
-            assert!(!heap.in_atomic_pause());
-            assert!(heap.is_incremental_marking_in_progress());
-
-            let header = page.object_header_from_inner_address::<u8>(value);
-            if let Some(marker) = heap.marker() {
-              marker.write_barrier_for_object::<MarkerBase::WriteBarrierType>(header);
-            }
-        }
-    }
-
-    type TraceCallback = fn(&mut MarkingVisitor, *const i8);
+use crate::heap::cppgc::heap::MarkingPhase;
+use crate::heap::cppgc::heap_object_header::HeapObjectHeader;
+use crate::heap::cppgc::heap_page::BasePage;
 
-    // static
-    pub fn dijkstra_marking_barrier_range_slow(
-        _heap_handle: &mut HeapHandle,
-        first_element: *const std::ffi::c_void,
-        element_size: usize,
-        number_of_elements: usize,
-        trace_callback: TraceCallback,
-    ) {
-        //Placeholder DisallowGarbageCollectionScope
-        // let heap_base = HeapBase::from(heap_handle);
+/// Number of heaps currently marking. The barrier fast path only checks
+/// whether this is non-zero.
+static MARKING_HEAPS: AtomicUsize = AtomicUsize::new(0);
 
-        // // GetWriteBarrierType() checks marking state.
-        // if let Some(_marker) = heap_base.marker() {
-        //     // No write barriers should be executed from atomic pause marking.
-        //     assert!(!heap_base.in_atomic_pause());
+/// Write barriers for `Member` writes.
+pub struct WriteBarrier;
 
-        //     let _disallow_gc_scope = DisallowGarbageCollectionScope { heap_base };
-        //     let mut array = first_element as *const i8;
-        //     let mut elements_left = number_of_elements;
-        //     while elements_left > 0 {
-        //         trace_callback(&mut heap_base.marker().unwrap().visitor(), array);
-        //         array = unsafe { array.add(element_size) };
-        //         elements_left -= 1;
-        //     }
-        // }
-        todo!()
+impl WriteBarrier {
+    /// Returns true if any heap in the process is marking.
+    #[inline]
+    pub fn is_enabled() -> bool {
+        MARKING_HEAPS.load(Ordering::Relaxed) > 0
     }
 
-    // Placeholder
-    struct DisallowGarbageCollectionScope<'a> {
-      heap_base: &'a HeapBase,
+    /// Called when a heap starts marking.
+    pub fn enter_marking() {
+        MARKING_HEAPS.fetch_add(1, Ordering::Relaxed);
     }
 
-    // static
-    pub fn steele_marking_barrier_slow_with_sentinel_check(value: *const std::ffi::c_void) {
-        if value.is_null() || value == SENTINEL_POINTER {
-            return;
-        }
-
-        steele_marking_barrier_slow(value);
-    }
-
-    // static
-    pub fn steele_marking_barrier_slow(value: *const std::ffi::c_void) {
-        let page = unsafe { &mut *BasePage::from_payload(value) };
-        let heap = page.heap();
-
-        // GetWriteBarrierType() checks marking state.
-        if let Some(_marker) = heap.marker() {
-            // No write barriers should be executed from atomic pause marking.
-            assert!(!heap.in_atomic_pause());
-            assert!(heap.is_incremental_marking_in_progress());
-
-            let header = page.object_header_from_inner_address::<u8>(value);
-            if let Some(marker) = heap.marker() {
-              marker.write_barrier_for_object::<MarkerBase::WriteBarrierType>(header);
-            }
-        }
+    /// Called when a heap is done marking.
+    pub fn leave_marking() {
+        let previous = MARKING_HEAPS.fetch_sub(1, Ordering::Relaxed);
+        debug_assert!(previous > 0);
     }
 
-    // static
-    pub fn generational_barrier_slow(
-        _local_data: &CagedHeapLocalData,
-        age_table: &AgeTable,
-        slot: *const std::ffi::c_void,
-        value_offset: uintptr_t,
-        heap_handle: &mut HeapHandle,
-    ) {
-        assert!(!slot.is_null());
-        assert!(!heap_handle.is_null());
-        assert!(api_constants::K_CAGED_HEAP_MAX_RESERVATION_SIZE > value_offset);
-
-        let heap = HeapBase::from(heap_handle);
-        if heap.in_atomic_pause() {
-            return;
-        }
-
-        if value_offset > 0 && age_table.get_age(value_offset) == AgeTable::Age::kOld {
+    /// Dijkstra-style insertion barrier: marks `value`, the new target of a
+    /// reference, if its heap is marking, so that it cannot be missed by a
+    /// marker that already traced the object holding the reference.
+    #[inline]
+    pub fn dijkstra_marking_barrier(value: *const u8) {
+        if value.is_null() || !Self::is_enabled() {
             return;
         }
-
-        // Record slot.
-        heap.remembered_set().add_slot(slot as *mut std::ffi::c_void);
+        Self::dijkstra_marking_barrier_slow(value);
     }
 
-    // static
-    pub fn generational_barrier_for_uncompressed_slot_slow(
-        _local_data: &CagedHeapLocalData,
-        age_table: &AgeTable,
-        slot: *const std::ffi::c_void,
-        value_offset: uintptr_t,
-        heap_handle: &mut HeapHandle,
-    ) {
-        assert!(!slot.is_null());
-        assert!(!heap_handle.is_null());
-        assert!(api_constants::K_CAGED_HEAP_MAX_RESERVATION_SIZE > value_offset);
-
-        let heap = HeapBase::from(heap_handle);
-        if heap.in_atomic_pause() {
+    /// Barrier for weak references. Weak references need no marking but must
+    /// not be pointed to dead objects once weakness has been processed.
+    #[inline]
+    pub fn weak_barrier(value: *const u8) {
+        if value.is_null() || !Self::is_enabled() {
             return;
         }
-
-        if value_offset > 0 && age_table.get_age(value_offset) == AgeTable::Age::kOld {
-            return;
-        }
-
-        // Record slot.
-        heap.remembered_set().add_uncompressed_slot(slot as *mut std::ffi::c_void);
-    }
-
-    // static
-    pub fn generational_barrier_for_source_object_slow(
-        _local_data: &CagedHeapLocalData,
-        inner_pointer: *const std::ffi::c_void,
-        heap_handle: &mut HeapHandle,
-    ) {
-        assert!(!inner_pointer.is_null());
-        assert!(!heap_handle.is_null());
-
-        let heap = HeapBase::from(heap_handle);
-        let page = unsafe { &mut *BasePage::from_inner_address(&heap, inner_pointer) };
-
-        let object_header = page.object_header_from_inner_address::<u8>(inner_pointer);
-
-        // Record the source object.
-        heap.remembered_set().add_source_object(&object_header);
+        Self::barrier_slow(value, false);
     }
 
-    // static
-    #[cfg(debug_assertions)]
-    pub fn check_params(expected_type: Type, params: &Params) {
-        assert_eq!(expected_type, params.type_);
+    #[cold]
+    fn dijkstra_marking_barrier_slow(value: *const u8) {
+        Self::barrier_slow(value, true);
     }
 
-    // static
-    struct LeakyObject<T> {
-      value: T,
-    }
-    impl<T> LeakyObject<T> {
-      const fn new(value: T) -> Self {
-        Self {value}
-      }
-      fn get(&'static self) -> &'static T {
-        &self.value
-      }
-    }
-    impl<T> std::ops::Deref for LeakyObject<T> {
-        type Target = T;
-        fn deref(&self) -> &Self::Target {
-            &self.value
-        }
-    }
-    impl<T> std::ops::DerefMut for LeakyObject<T> {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.value
-        }
-    }
-
-    impl YoungGenerationEnabler {
-      fn get_instance() -> &'static LeakyObject<YoungGenerationEnabler> {
-        static INSTANCE: LeakyObject<YoungGenerationEnabler> = LeakyObject::new(YoungGenerationEnabler {
-          mutex_: Mutex::new(()),
-          is_enabled_: 0,
-        });
-        &INSTANCE
-      }
-
-      pub fn enable() {
-        let instance = Self::get_instance();
-        let mut guard = instance.mutex_.lock().unwrap();
-        instance.is_enabled_ += 1;
-        if instance.is_enabled_ == 1 {
-          // Enter the flag so that the check in the write barrier will always trigger
-          // when young generation is enabled.
-          FlagUpdaterImpl::enter();
-        }
-      }
-
-      pub fn disable() {
-        let instance = Self::get_instance();
-        let mut guard = instance.mutex_.lock().unwrap();
-        assert!(instance.is_enabled_ > 0);
-        instance.is_enabled_ -= 1;
-        if instance.is_enabled_ == 0 {
-          FlagUpdaterImpl::exit();
-        }
-      }
-
-      pub fn is_enabled() -> bool {
-        let instance = Self::get_instance();
-        let guard = instance.mutex_.lock().unwrap();
-        instance.is_enabled_ > 0
-      }
-    }
-
-    impl FlagUpdater for FlagUpdaterImpl {
-        fn enter() {
-          WRITE_BARRIER_ENABLED.store(true, Ordering::SeqCst);
-        }
-        fn exit() {
-            WRITE_BARRIER_ENABLED.store(false, Ordering::SeqCst);
-        }
-    }
-
-    struct FlagUpdaterImpl; // Dummy type for FlagUpdater implementation
-
-    struct YoungGenerationEnabler {
-        mutex_: Mutex<()>,
-        is_enabled_: i32,
-    }
-
-    // static
-    pub fn combined_write_barrier_slow<const SLOT_TYPE: WriteBarrierSlotType>(slot: *const std::ffi::c_void) {
-        assert!(!slot.is_null());
-
-        let value: *const std::ffi::c_void;
-        if SLOT_TYPE == WriteBarrierSlotType::kCompressed {
-            let compressed_ptr: CompressedPointer::IntegralType = unsafe { *(slot as *const CompressedPointer::IntegralType) };
-            value = CompressedPointer::decompress(compressed_ptr);
-        } else {
-            value = unsafe { *(slot as *const *const std::ffi::c_void) };
-        }
-
-        let mut params = Params { type_: Type::kNone };
-        let type_ = get_write_barrier_type(slot, value, &mut params);
-        match type_ {
-            Type::kGenerational => {
-                generational_barrier::<GenerationalBarrierType::kPreciseSlot>(&params, slot);
-            }
-            Type::kMarking => {
-                dijkstra_marking_barrier(&params, value);
+    fn barrier_slow(value: *const u8, is_strong: bool) {
+        let page = unsafe { BasePage::from_payload(value) };
+        let heap = page.heap();
+        let header = unsafe { HeapObjectHeader::from_object(value) };
+        match heap.marking_phase() {
+            MarkingPhase::Idle => {}
+            MarkingPhase::Marking => {
+                if !is_strong {
+                    return;
+                }
+                if header.try_mark_atomic() {
+                    heap.marking_worklists().push(header.address());
+                }
             }
-            Type::kNone => {
-                // The fast checks are approximate and may trigger spuriously if any heap
-                // has marking in progress. `GetWriteBarrierType()` above is exact which
-                // is the reason we could still observe a bailout here.
+            MarkingPhase::InvokingPreFinalizers => {
+                assert!(
+                    header.is_marked(),
+                    "prefinalizers must not resurrect dead objects"
+                );
             }
         }
     }
-
-    fn generational_barrier<const T: GenerationalBarrierType>(params: &Params, slot: *const std::ffi::c_void) {
-        todo!()
-    }
-    fn get_write_barrier_type(slot: *const std::ffi::c_void, value: *const std::ffi::c_void, params: &mut Params) -> Type {
-        todo!()
-    }
-    fn dijkstra_marking_barrier(params: &Params, value: *const std::ffi::c_void) {
-        todo!()
-    }
-}
\ No newline at end of file
+}
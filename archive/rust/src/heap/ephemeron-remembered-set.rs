@@ -2,97 +2,67 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Mutex;
 
-// Placeholder for HeapObjectSlot and HeapObject types, as they are V8-specific.
-// Replace with your actual definitions if available.
-type Address = usize;
+use crate::heap::heap_layout::{HeapLayout, HeapObject};
 
-// Placeholder type for Tagged<T>
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-struct Tagged<T>(usize, std::marker::PhantomData<T>);
+/// Entry indices of one table with young keys.
+pub type IndicesSet = BTreeSet<usize>;
+pub type TableMap = HashMap<HeapObject, IndicesSet>;
 
-impl<T> Tagged<T> {
-    fn address(&self) -> usize {
-        self.0
-    }
-}
-
-// Placeholder types for ephemeron specific types
-type EphemeronHashTable = usize;
-type InternalIndex = usize;
-
-// Placeholder for HeapLayout::InYoungGeneration and related methods.
-// Replace with your actual implementation if available.
-fn in_young_generation(_obj: usize) -> bool {
-    // Dummy implementation.
-    true
+/// Stores ephemeron entries where the EphemeronHashTable is in old space
+/// and the key of the entry is in new space. Keys are weak, so these slots
+/// are not part of the OLD_TO_NEW remembered set; the scavenger updates or
+/// clears them after evacuation.
+#[derive(Default)]
+pub struct EphemeronRememberedSet {
+    tables: Mutex<TableMap>,
 }
 
-mod ephemeron_remembered_set {
-    use super::*;
-
-    /// Represents the remembered set for ephemerons.
-    pub struct EphemeronRememberedSet {
-        insertion_mutex_: Mutex<()>,
-        tables_: HashMap<Tagged<EphemeronHashTable>, IndicesSet>,
+impl EphemeronRememberedSet {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// A set of indices.
-    pub type IndicesSet = HashSet<usize>;
+    /// Records that the key of entry `entry` of the old `table` is young.
+    pub fn record_ephemeron_key_write(&self, table: HeapObject, entry: usize) {
+        debug_assert!(!HeapLayout::in_young_generation(table));
+        self.tables
+            .lock()
+            .unwrap()
+            .entry(table)
+            .or_default()
+            .insert(entry);
+    }
 
-    impl EphemeronRememberedSet {
-        /// Creates a new `EphemeronRememberedSet`.
-        pub fn new() -> Self {
-            EphemeronRememberedSet {
-                insertion_mutex_: Mutex::new(()),
-                tables_: HashMap::new(),
-            }
+    /// Records all given entries of `table`.
+    pub fn record_ephemeron_key_writes(&self, table: HeapObject, indices: IndicesSet) {
+        if indices.is_empty() {
+            return;
         }
+        self.tables
+            .lock()
+            .unwrap()
+            .entry(table)
+            .or_default()
+            .extend(indices);
+    }
 
-        /// Records an ephemeron key write.
-        pub fn record_ephemeron_key_write(
-            &mut self,
-            table: Tagged<EphemeronHashTable>,
-            slot: Address,
-        ) {
-            // let heap_object_slot = HeapObjectSlot(slot);
-            // let heap_object = heap_object_slot.to_heap_object();
-            let heap_object = slot; // placeholder
-
-            if in_young_generation(heap_object) {
-                let slot_index = Self::slot_to_index(table.address(), slot);
-                let entry = Self::index_to_entry(slot_index);
-
-                let _guard = self.insertion_mutex_.lock().unwrap();
-                let entry_int = entry;
-                let indices_set = self.tables_.entry(table).or_insert_with(HashSet::new);
-                indices_set.insert(entry_int);
-            }
-        }
+    pub fn contains(&self, table: HeapObject, entry: usize) -> bool {
+        self.tables
+            .lock()
+            .unwrap()
+            .get(&table)
+            .is_some_and(|indices| indices.contains(&entry))
+    }
 
-        /// Records multiple ephemeron key writes.
-        pub fn record_ephemeron_key_writes(
-            &mut self,
-            table: Tagged<EphemeronHashTable>,
-            mut indices: IndicesSet,
-        ) {
-            let _guard = self.insertion_mutex_.lock().unwrap();
-            if let Some(existing_indices) = self.tables_.get_mut(&table) {
-                existing_indices.extend(indices.drain());
-            } else {
-                self.tables_.insert(table, indices);
-            }
-        }
+    /// Removes and returns all recorded entries.
+    pub fn take(&self) -> TableMap {
+        std::mem::take(&mut *self.tables.lock().unwrap())
+    }
 
-        fn slot_to_index(_table_address: usize, slot: Address) -> usize {
-            // Placeholder implementation
-            slot
-        }
-        
-        fn index_to_entry(index: usize) -> usize{
-            index
-        }
+    pub fn is_empty(&self) -> bool {
+        self.tables.lock().unwrap().is_empty()
     }
-}
\ No newline at end of file
+}
@@ -1,381 +1,106 @@
-// src/heap/evacuation_allocator.rs
-
-use std::mem::size_of;
-
-// Placeholder for Heap, NewSpace, CompactionSpaces, HeapObject, Isolate, etc.
-// These would need to be defined based on their actual structure in the V8 codebase.
-// For now, they are simple structs/enums.
-
-#[derive(Debug, Copy, Clone)]
-#[repr(C)]
-struct Address(usize);
-
-impl Address {
-    fn is_null(&self) -> bool {
-        self.0 == 0
-    }
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum AllocationSpace {
-    NEW_SPACE,
-    OLD_SPACE,
-    SHARED_SPACE,
-    CODE_SPACE,
-    TRUSTED_SPACE,
-}
-
-#[derive(Debug, Copy, Clone)]
-enum CompactionSpaceKind {
-    OLD_SPACE,
-    CODE_SPACE,
-    SHARED_SPACE,
-    TRUSTED_SPACE,
-}
-
-#[derive(Debug)]
-struct Heap {
-    new_space_: Option<NewSpace>,
-    allocator_: Box<Allocator>,
-    old_space_: Box<OldSpace>,
-    code_space_: Box<CodeSpace>,
-    shared_allocation_space_: Box<SharedSpace>,
-    trusted_space_: Box<TrustedSpace>,
-    isolate_: Box<Isolate>,
-}
-
-impl Heap {
-    fn new(new_space: Option<NewSpace>, allocator: Allocator, old_space: OldSpace, code_space: CodeSpace, shared_allocation_space: SharedSpace, trusted_space: TrustedSpace, isolate: Isolate) -> Self {
-        Heap {
-            new_space_: new_space,
-            allocator_: Box::new(allocator),
-            old_space_: Box::new(old_space),
-            code_space_: Box::new(code_space),
-            shared_allocation_space_: Box::new(shared_allocation_space),
-            trusted_space_: Box::new(trusted_space),
-            isolate_: Box::new(isolate),
-        }
-    }
-    fn new_space(&self) -> Option<&NewSpace> {
-        self.new_space_.as_ref()
-    }
-    fn allocator(&self) -> &Allocator {
-        &self.allocator_
-    }
-    fn old_space(&self) -> &OldSpace {
-        &self.old_space_
-    }
-    fn code_space(&self) -> &CodeSpace {
-        &self.code_space_
-    }
-    fn shared_allocation_space(&self) -> &SharedSpace {
-        &self.shared_allocation_space_
-    }
-    fn trusted_space(&self) -> &TrustedSpace {
-        &self.trusted_space_
-    }
-    fn isolate(&self) -> &Isolate {
-        &self.isolate_
-    }
-    fn create_filler_object_at(&mut self, address: Address, size: i32) {
-        // Placeholder for heap implementation details
-        println!("Creating filler object at {:?} with size {}", address, size);
-    }
-}
-
-#[derive(Debug)]
-struct NewSpace {}
-
-#[derive(Debug)]
-struct OldSpace {}
-impl OldSpace {
-    fn merge_compaction_space(&self, space: &CompactionSpace) {
-        // Placeholder for merge_compaction_space implementation details
-        println!("Merging compaction space");
-    }
-}
-
-#[derive(Debug)]
-struct CodeSpace {}
-impl CodeSpace {
-    fn merge_compaction_space(&self, space: &CompactionSpace) {
-        // Placeholder for merge_compaction_space implementation details
-        println!("Merging compaction space");
-    }
-}
-
-#[derive(Debug)]
-struct SharedSpace {}
-impl SharedSpace {
-    fn merge_compaction_space(&self, space: &CompactionSpace) {
-        // Placeholder for merge_compaction_space implementation details
-        println!("Merging compaction space");
-    }
-}
-
-#[derive(Debug)]
-struct TrustedSpace {}
-impl TrustedSpace {
-    fn merge_compaction_space(&self, space: &CompactionSpace) {
-        // Placeholder for merge_compaction_space implementation details
-        println!("Merging compaction space");
-    }
-}
-
-#[derive(Debug)]
-struct Isolate {
-    has_shared_space_: bool,
-}
-impl Isolate {
-    fn has_shared_space(&self) -> bool {
-        self.has_shared_space_
-    }
-}
-
-#[derive(Debug)]
-struct Allocator {
-    new_space_allocator_: Option<NewSpaceAllocator>,
-}
-impl Allocator {
-    fn new(new_space_allocator: Option<NewSpaceAllocator>) -> Self {
-        Allocator {
-            new_space_allocator_: new_space_allocator,
-        }
-    }
-
-    fn new_space_allocator(&self) -> Option<&NewSpaceAllocator> {
-        self.new_space_allocator_.as_ref()
-    }
-}
-
-#[derive(Debug)]
-struct NewSpaceAllocator {}
-impl NewSpaceAllocator {
-    fn is_lab_valid(&self) -> bool {
-        false // Placeholder implementation
-    }
-}
-
-#[derive(Debug)]
-struct CompactionSpaces<'a> {
-    heap_: &'a Heap,
-    compaction_space_kind_: CompactionSpaceKind,
-    old_space_: CompactionSpace,
-    code_space_: CompactionSpace,
-    shared_space_: Option<CompactionSpace>,
-    trusted_space_: CompactionSpace,
-}
-
-impl <'a> CompactionSpaces<'a> {
-    fn new(heap: &'a Heap, compaction_space_kind: CompactionSpaceKind) -> Self {
-        CompactionSpaces {
-            heap_: heap,
-            compaction_space_kind_: compaction_space_kind,
-            old_space_: CompactionSpace{},
-            code_space_: CompactionSpace{},
-            shared_space_: if heap.isolate().has_shared_space() { Some(CompactionSpace{}) } else { None },
-            trusted_space_: CompactionSpace{},
-        }
-    }
-    fn get(&self, space: AllocationSpace) -> &CompactionSpace {
-        match space {
-            AllocationSpace::OLD_SPACE => &self.old_space_,
-            AllocationSpace::CODE_SPACE => &self.code_space_,
-            AllocationSpace::SHARED_SPACE => self.shared_space_.as_ref().expect("Shared space expected"),
-            AllocationSpace::TRUSTED_SPACE => &self.trusted_space_,
-            _ => panic!("Unexpected allocation space"),
-        }
-    }
-}
-
-#[derive(Debug)]
-struct CompactionSpace {}
-
-#[derive(Debug, Copy, Clone)]
-#[repr(C)]
-struct TaggedHeapObject(Address);
-
-#[allow(dead_code)]
-impl TaggedHeapObject {
-    fn address(&self) -> Address {
-        self.0
-    }
-}
-
-#[macro_export]
-macro_rules! align_to_allocation_alignment {
-    ($size:expr) => {
-        (($size + 8 - 1) / 8) * 8
-    };
-}
-
-#[macro_export]
-macro_rules! dcheck_implies {
-    ($condition:expr, $implication:expr) => {
-        if $condition {
-            assert!($implication);
-        }
-    };
-}
-
-#[macro_export]
-macro_rules! unreachable {
-    () => {
-        panic!("Unreachable code reached");
-    };
-}
-
-/// Allocator used during evacuation (copying objects).
-#[derive(Debug)]
+// Copyright 2017 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use crate::heap::heap_layout::{create_filler_object_at, Address};
+use crate::heap::linear_allocation_area::LinearAllocationArea;
+use crate::heap::memory_chunk::AllocationSpace;
+use crate::heap::new_spaces::SemiSpaceNewSpace;
+use crate::heap::paged_spaces::PagedSpace;
+
+/// Allocator used by collector tasks when evacuating objects. Allocation
+/// goes through task-local LABs; only refills synchronize with other tasks.
 pub struct EvacuationAllocator<'a> {
-    heap_: &'a mut Heap,
-    new_space_: Option<&'a NewSpace>,
-    compaction_spaces_: CompactionSpaces<'a>,
-    new_space_allocator_: Option<MainAllocator<'a>>,
-    old_space_allocator_: MainAllocator<'a>,
-    code_space_allocator_: MainAllocator<'a>,
-    shared_space_allocator_: Option<MainAllocator<'a>>,
-    trusted_space_allocator_: MainAllocator<'a>,
+    new_space: &'a SemiSpaceNewSpace,
+    old_space: &'a PagedSpace,
+    new_space_lab: LinearAllocationArea,
+    old_space_lab: LinearAllocationArea,
 }
 
 impl<'a> EvacuationAllocator<'a> {
-    /// Creates a new `EvacuationAllocator`.
-    pub fn new(heap: &'a mut Heap, compaction_space_kind: CompactionSpaceKind) -> Self {
-        let new_space = heap.new_space();
-        let compaction_spaces = CompactionSpaces::new(heap, compaction_space_kind);
-
-        let new_space_allocator = new_space.map(|_| {
-            assert!(heap.allocator().new_space_allocator().map_or(true, |a| !a.is_lab_valid()));
-            MainAllocator::new(heap, AllocationSpace::NEW_SPACE, MainAllocatorMode::InGC)
-        });
-
-        let old_space_allocator = MainAllocator::new(heap, AllocationSpace::OLD_SPACE, MainAllocatorMode::InGC);
-        let code_space_allocator = MainAllocator::new(heap, AllocationSpace::CODE_SPACE, MainAllocatorMode::InGC);
-        let shared_space_allocator = if heap.isolate().has_shared_space() {
-            Some(MainAllocator::new(heap, AllocationSpace::SHARED_SPACE, MainAllocatorMode::InGC))
-        } else {
-            None
-        };
-        let trusted_space_allocator = MainAllocator::new(heap, AllocationSpace::TRUSTED_SPACE, MainAllocatorMode::InGC);
+    pub const K_LAB_SIZE: usize = 32 * 1024;
+    /// Objects larger than this bypass the LABs to avoid wasting space.
+    pub const K_MAX_LAB_OBJECT_SIZE: usize = 8 * 1024;
 
+    pub fn new(new_space: &'a SemiSpaceNewSpace, old_space: &'a PagedSpace) -> Self {
         EvacuationAllocator {
-            heap_: heap,
-            new_space_: new_space,
-            compaction_spaces_: compaction_spaces,
-            new_space_allocator_: new_space_allocator,
-            old_space_allocator_: old_space_allocator,
-            code_space_allocator_: code_space_allocator,
-            shared_space_allocator_: shared_space_allocator,
-            trusted_space_allocator_: trusted_space_allocator,
+            new_space,
+            old_space,
+            new_space_lab: LinearAllocationArea::new(),
+            old_space_lab: LinearAllocationArea::new(),
         }
     }
 
-    /// Frees the last allocated object in the given space.
-    pub fn free_last(&mut self, space: AllocationSpace, object: TaggedHeapObject, object_size: i32) {
-        dcheck_implies!(self.shared_space_allocator_.is_none(), space != AllocationSpace::SHARED_SPACE);
-        let object_size = align_to_allocation_alignment!(object_size);
+    /// Allocates `size` bytes in the given space. Allocation in new space
+    /// fails once to-space is exhausted; old space grows as needed.
+    pub fn allocate(&mut self, space: AllocationSpace, size: usize) -> Option<Address> {
         match space {
-            AllocationSpace::NEW_SPACE => {
-                if let Some(ref mut allocator) = self.new_space_allocator_ {
-                    self.free_last_in_main_allocator(allocator, object, object_size);
-                } else {
-                    unreachable!();
-                }
-            }
-            AllocationSpace::OLD_SPACE => {
-                self.free_last_in_main_allocator(&mut self.old_space_allocator_, object, object_size);
-            }
-            AllocationSpace::SHARED_SPACE => {
-                if let Some(ref mut allocator) = self.shared_space_allocator_ {
-                    self.free_last_in_main_allocator(allocator, object, object_size);
-                } else {
-                    unreachable!();
-                }
-            }
-            _ => {
-                // Only new and old space supported.
-                unreachable!();
-            }
+            AllocationSpace::NEW_SPACE => self.allocate_in_new_space(size),
+            AllocationSpace::OLD_SPACE => Some(self.allocate_in_old_space(size)),
+            AllocationSpace::LO_SPACE => unreachable!("large objects are not evacuated"),
         }
     }
 
-    fn free_last_in_main_allocator(&mut self, allocator: &mut MainAllocator<'a>, object: TaggedHeapObject, object_size: i32) {
-        if !allocator.try_free_last(object.address(), object_size) {
-            // We couldn't free the last object so we have to write a proper filler.
-            self.heap_.create_filler_object_at(object.address(), object_size);
+    /// Gives back the most recent allocation of `size` bytes at `address`,
+    /// e.g. after losing an evacuation race.
+    pub fn free_last(&mut self, space: AllocationSpace, address: Address, size: usize) {
+        let lab = match space {
+            AllocationSpace::NEW_SPACE => &mut self.new_space_lab,
+            _ => &mut self.old_space_lab,
+        };
+        if !lab.decrement_top_if_adjacent(address, size) {
+            // SAFETY: the memory was allocated by this allocator and holds
+            // no object.
+            unsafe { create_filler_object_at(address, size) };
         }
     }
 
-    /// Finalizes the evacuation allocator.
+    /// Returns the unused parts of the LABs to their spaces.
     pub fn finalize(&mut self) {
-        if let Some(ref mut allocator) = self.new_space_allocator_ {
-            allocator.free_linear_allocation_area();
-        }
-
-        self.old_space_allocator_.free_linear_allocation_area();
-        self.heap_.old_space().merge_compaction_space(self.compaction_spaces_.get(AllocationSpace::OLD_SPACE));
-
-        self.code_space_allocator_.free_linear_allocation_area();
-        self.heap_.code_space().merge_compaction_space(self.compaction_spaces_.get(AllocationSpace::CODE_SPACE));
-
-        if let Some(ref mut allocator) = self.shared_space_allocator_ {
-            allocator.free_linear_allocation_area();
-            self.heap_.shared_allocation_space().merge_compaction_space(self.compaction_spaces_.get(AllocationSpace::SHARED_SPACE));
-        }
-
-        self.trusted_space_allocator_.free_linear_allocation_area();
-        self.heap_.trusted_space().merge_compaction_space(self.compaction_spaces_.get(AllocationSpace::TRUSTED_SPACE));
+        self.new_space
+            .free_lab_remainder(self.new_space_lab.top(), self.new_space_lab.limit());
+        self.new_space_lab.reset(0, 0);
+        self.old_space
+            .free_lab_remainder(self.old_space_lab.top(), self.old_space_lab.limit());
+        self.old_space_lab.reset(0, 0);
     }
-}
-
-#[derive(Debug)]
-enum MainAllocatorMode {
-    Regular,
-    InGC,
-}
-
-/// A main allocator.
-#[derive(Debug)]
-struct MainAllocator<'a> {
-    heap_: &'a mut Heap,
-    space_: AllocationSpace,
-    mode_: MainAllocatorMode,
-}
 
-impl<'a> MainAllocator<'a> {
-    fn new(heap: &'a mut Heap, space: AllocationSpace, mode: MainAllocatorMode) -> Self {
-        MainAllocator {
-            heap_: heap,
-            space_: space,
-            mode_: mode,
+    fn allocate_in_new_space(&mut self, size: usize) -> Option<Address> {
+        if self.new_space_lab.can_increment_top(size) {
+            return Some(self.new_space_lab.increment_top(size));
         }
+        if size > Self::K_MAX_LAB_OBJECT_SIZE {
+            let (start, _) = self.new_space.allocate_lab(size, size)?;
+            return Some(start);
+        }
+        self.new_space
+            .free_lab_remainder(self.new_space_lab.top(), self.new_space_lab.limit());
+        self.new_space_lab.reset(0, 0);
+        let (start, end) = self.new_space.allocate_lab(size, Self::K_LAB_SIZE)?;
+        self.new_space_lab.reset(start, end);
+        Some(self.new_space_lab.increment_top(size))
     }
 
-    fn try_free_last(&mut self, address: Address, object_size: i32) -> bool {
-        // Placeholder implementation
-        println!("Trying to free last at {:?} with size {}", address, object_size);
-        false
-    }
-
-    fn free_linear_allocation_area(&mut self) {
-        // Placeholder implementation
-        println!("Freeing linear allocation area");
+    fn allocate_in_old_space(&mut self, size: usize) -> Address {
+        if self.old_space_lab.can_increment_top(size) {
+            return self.old_space_lab.increment_top(size);
+        }
+        if size > Self::K_MAX_LAB_OBJECT_SIZE {
+            return self.old_space.allocate_raw(size);
+        }
+        self.old_space
+            .free_lab_remainder(self.old_space_lab.top(), self.old_space_lab.limit());
+        let (start, end) = self.old_space.allocate_lab(size, Self::K_LAB_SIZE);
+        self.old_space_lab.reset(start, end);
+        self.old_space_lab.increment_top(size)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_evacuation_allocator() {
-        let mut heap = Heap::new(None, Allocator::new(None), OldSpace{}, CodeSpace{}, SharedSpace{}, TrustedSpace{}, Isolate { has_shared_space_: true });
-        let mut evacuation_allocator = EvacuationAllocator::new(&mut heap, CompactionSpaceKind::OLD_SPACE);
-
-        let object = TaggedHeapObject(Address(1024));
-        let object_size = 128;
-
-        evacuation_allocator.free_last(AllocationSpace::OLD_SPACE, object, object_size);
-        evacuation_allocator.finalize();
+impl Drop for EvacuationAllocator<'_> {
+    fn drop(&mut self) {
+        debug_assert!(
+            std::thread::panicking() || self.new_space_lab.top() == self.new_space_lab.limit(),
+            "EvacuationAllocator dropped without finalize()"
+        );
     }
-}
\ No newline at end of file
+}
@@ -2,827 +2,707 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use std::{fmt, mem, sync::Mutex};
-
-// use v8_metrics; // Assuming a Rust crate equivalent to include/v8-metrics.h
-use crate::base::{BoundedAverageSpeed, RingBuffer, SmoothedBytesAndDuration};
-use crate::common::globals::*;
-// use crate::execution::thread_id::ThreadId; // Assuming a Rust equivalent
-// use crate::heap::cppgc_js::cpp_heap::CppHeap; // Assuming a Rust equivalent
-// use crate::heap::cppgc::metric_recorder::MetricRecorder; // Assuming a Rust equivalent
-// use crate::heap::gc_tracer_inl::*; // Assuming a Rust equivalent (probably inline functions)
-// use crate::heap::heap_inl::*; // Assuming a Rust equivalent (probably inline functions)
-use crate::heap::heap::Heap;
-// use crate::heap::incremental_marking::IncrementalMarking; // Assuming a Rust equivalent
-// use crate::heap::memory_balancer::MemoryBalancer; // Assuming a Rust equivalent
-// use crate::heap::spaces::*; // Assuming a Rust equivalent
-// use crate::logging::counters::Counters; // Assuming a Rust equivalent
-// use crate::logging::metrics::*; // Assuming a Rust equivalent
-// use crate::logging::tracing_flags::*; // Assuming a Rust equivalent
-// use crate::tracing::tracing_category_observer::*; // Assuming a Rust equivalent
-
-// Placeholder for tracing macros
-macro_rules! trace_gc_note {
-    ($message:expr) => {
-        // Implement tracing functionality here if needed
-        println!("TRACE_GC_NOTE: {}", $message);
-    };
-}
-
-// Placeholder for tracing macros
-macro_rules! trace_event_instant1 {
-    ($category:expr, $name:expr, $scope:expr, $arg_name:expr, $arg_value:expr) => {
-        // Implement tracing functionality here if needed
-        println!(
-            "TRACE_EVENT_INSTANT1: category={}, name={}, scope={}, arg_name={}, arg_value={}",
-            $category, $name, $scope, $arg_name, $arg_value
-        );
-    };
-}
-
-macro_rules! trace_event_scope_thread {
-    () => {
-        // Placeholder for thread scope
-    };
-}
-
-macro_rules! trace_str_copy {
-    ($str:expr) => {
-        $str // Placeholder; in C++, this would copy the string.  Handle appropriately in Rust.
-    };
-}
-
-const GB: usize = 1024 * 1024 * 1024;
-
-fn count_total_holes_size(heap: &Heap) -> usize {
-    // This function needs access to the internals of `PagedSpaceIterator` and
-    // `PagedSpace`, which are assumed to be translated to Rust data structures.
-    // Since direct translation without these definitions is impossible,
-    // the following is a placeholder implementation:
-
-    // Assuming a method on Heap that provides the total waste and available memory
-    heap.total_waste_and_available()
-}
-
-mod base {
-    use std::{
-        fmt,
-        ops::{Add, Div},
-        time::Duration,
-    };
-
-    #[derive(Clone, Copy, Debug, Default)]
-    pub struct BytesAndDuration {
-        pub bytes: usize,
-        pub duration: Duration,
-    }
 
-    impl Add for BytesAndDuration {
-        type Output = Self;
+use crate::heap::heap_controller::HeapLimitDecision;
+use crate::include::v8_metrics::{
+    ContextId, GarbageCollectionFullCycle, GarbageCollectionPhases, GarbageCollectionSizes,
+    GarbageCollectionYoungCycle,
+};
+use crate::logging::metrics::Recorder;
 
-        fn add(self, other: Self) -> Self {
-            Self {
-                bytes: self.bytes + other.bytes,
-                duration: self.duration + other.duration,
-            }
-        }
-    }
-
-    impl Div<usize> for BytesAndDuration {
-        type Output = Self;
-
-        fn div(self, rhs: usize) -> Self {
-            Self {
-                bytes: self.bytes / rhs,
-                duration: self.duration / rhs as u32, // Duration doesn't implement div for usize, so convert
-            }
-        }
-    }
-
-    impl fmt::Display for BytesAndDuration {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(
-                f,
-                "Bytes: {}, Duration: {:?}",
-                self.bytes, self.duration
-            )
-        }
-    }
-
-    // A simple smoothed value
-    #[derive(Clone, Copy, Debug, Default)]
-    pub struct SmoothedBytesAndDuration {
-        pub bytes_per_ms: f64,
-    }
-
-    impl SmoothedBytesAndDuration {
-        pub fn update(&mut self, bytes: usize, duration: Duration) {
-            if duration.is_zero() {
-                return;
-            }
-            let new_bytes_per_ms = bytes as f64 / duration.as_millis() as f64;
-            self.bytes_per_ms = (self.bytes_per_ms + new_bytes_per_ms) / 2.0;
-        }
-
-        pub fn get_throughput(&self) -> f64 {
-            self.bytes_per_ms
-        }
-    }
-
-    // Ring buffer implementation with a fixed size
-    #[derive(Debug)]
-    pub struct RingBuffer<T: Copy + Default> {
-        buffer: Vec<T>,
-        capacity: usize,
-        head: usize,
-        size: usize,
-    }
-
-    impl<T: Copy + Default> RingBuffer<T> {
-        pub fn new(capacity: usize) -> Self {
-            RingBuffer {
-                buffer: vec![T::default(); capacity],
-                capacity,
-                head: 0,
-                size: 0,
-            }
-        }
-
-        pub fn push(&mut self, value: T) {
-            self.buffer[self.head] = value;
-            self.head = (self.head + 1) % self.capacity;
-            if self.size < self.capacity {
-                self.size += 1;
-            }
-        }
-
-        pub fn is_empty(&self) -> bool {
-            self.size == 0
-        }
-
-        pub fn size(&self) -> usize {
-            self.size
-        }
-
-        pub fn reduce<F, A>(&self, op: F, initial_value: A) -> A
-        where
-            F: Fn(A, T) -> A,
-            A: Copy,
-        {
-            let mut accumulator = initial_value;
-            for i in 0..self.size {
-                let index = (self.head + self.capacity - self.size + i) % self.capacity;
-                accumulator = op(accumulator, self.buffer[index]);
-            }
-            accumulator
-        }
-
-        pub fn iter(&self) -> RingBufferIterator<'_, T> {
-            RingBufferIterator {
-                buffer: &self.buffer,
-                head: self.head,
-                size: self.size,
-                capacity: self.capacity,
-                index: 0,
-            }
-        }
-    }
-
-    pub struct RingBufferIterator<'a, T> {
-        buffer: &'a Vec<T>,
-        head: usize,
-        size: usize,
-        capacity: usize,
-        index: usize,
-    }
-
-    impl<'a, T: Copy + Default> Iterator for RingBufferIterator<'a, T> {
-        type Item = T;
-
-        fn next(&mut self) -> Option<Self::Item> {
-            if self.index < self.size {
-                let index = (self.head + self.capacity - self.size + self.index) % self.capacity;
-                self.index += 1;
-                Some(self.buffer[index])
-            } else {
-                None
-            }
-        }
-    }
-    pub fn bounded_average_speed(
-        buffer: &RingBuffer<BytesAndDuration>,
-    ) -> Option<f64> {
-        const K_MIN_NON_EMPTY_SPEED_IN_BYTES_PER_MS: usize = 1;
-        const K_MAX_SPEED_IN_BYTES_PER_MS: usize = super::GB; // Assuming GB is defined in the parent scope
-
-        let mut total_bytes: usize = 0;
-        let mut total_duration: Duration = Duration::from_secs(0);
-
-        for &bd in buffer.iter() {
-            total_bytes += bd.bytes;
-            total_duration += bd.duration;
-        }
-
-        if total_bytes == 0 || total_duration.is_zero() {
-            return None;
-        }
-
-        let bytes_per_ms =
-            total_bytes as f64 / total_duration.as_millis() as f64;
-
-        if bytes_per_ms < K_MIN_NON_EMPTY_SPEED_IN_BYTES_PER_MS as f64 {
-            return Some(K_MIN_NON_EMPTY_SPEED_IN_BYTES_PER_MS as f64);
-        }
-
-        if bytes_per_ms > K_MAX_SPEED_IN_BYTES_PER_MS as f64 {
-            return Some(K_MAX_SPEED_IN_BYTES_PER_MS as f64);
-        }
-        Some(bytes_per_ms)
-    }
-
-    pub fn bounded_throughput(buffer: &SmoothedBytesAndDuration) -> f64 {
-        const K_MAX_SPEED_IN_BYTES_PER_MS: f64 = super::GB as f64;
-        buffer.get_throughput().min(K_MAX_SPEED_IN_BYTES_PER_MS)
-    }
-}
-
-static GLOBAL_EPOCH: AtomicU64 = AtomicU64::new(0);
-
-fn next_epoch() -> CollectionEpoch {
-    GLOBAL_EPOCH.fetch_add(1, Ordering::Relaxed) + 1
-}
-
-pub type CollectionEpoch = u64;
+const KB: f64 = 1024.0;
+const MB: f64 = (1024 * 1024) as f64;
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GarbageCollectionReason {
+    kAllocationFailure,
+    kAllocationLimit,
+    kFinalizeMarkingViaStackGuard,
+    kFinalizeMarkingViaTask,
+    kTask,
     kTesting,
+    kStressTesting,
     kMeasureMemory,
-    kAllocationLimit,
-    kLast ditch,
-    kFirst,
-    kDebugger,
-    kLowMemory,
-    // Add other reasons as needed
+    kMemoryReducer,
 }
 
-impl fmt::Display for GarbageCollectionReason {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+impl GarbageCollectionReason {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            GarbageCollectionReason::kAllocationFailure => "allocation failure",
+            GarbageCollectionReason::kAllocationLimit => "allocation limit",
+            GarbageCollectionReason::kFinalizeMarkingViaStackGuard => "finalize incremental marking via stack guard",
+            GarbageCollectionReason::kFinalizeMarkingViaTask => "finalize incremental marking via task",
+            GarbageCollectionReason::kTask => "task",
+            GarbageCollectionReason::kTesting => "testing",
+            GarbageCollectionReason::kStressTesting => "stress testing",
+            GarbageCollectionReason::kMeasureMemory => "measure memory",
+            GarbageCollectionReason::kMemoryReducer => "memory reducer",
+        }
     }
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum GarbageCollector {
     SCAVENGER,
     MARK_COMPACTOR,
-    MINOR_MARK_SWEEPER,
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum MarkingType {
-    kAtomic,
-    kIncremental,
+pub enum EventType {
+    SCAVENGER,
+    MARK_COMPACTOR,
+    INCREMENTAL_MARK_COMPACTOR,
+    START,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-pub enum Priority {
-    High,
-    Low,
-}
+impl EventType {
+    pub fn to_str(self, short_name: bool) -> &'static str {
+        match (self, short_name) {
+            (EventType::SCAVENGER, true) => "s",
+            (EventType::SCAVENGER, false) => "Scavenge",
+            (EventType::MARK_COMPACTOR | EventType::INCREMENTAL_MARK_COMPACTOR, true) => "ms",
+            (EventType::MARK_COMPACTOR | EventType::INCREMENTAL_MARK_COMPACTOR, false) => "Mark-Compact",
+            (EventType::START, true) => "st",
+            (EventType::START, false) => "Start",
+        }
+    }
 
-#[derive(Debug)]
-pub struct GCTracer {
-    heap_: *mut Heap, // Assuming `Heap` struct is defined elsewhere
-    current_: Event,
-    previous_: Event,
-    allocation_time_: Instant,
-    previous_mark_compact_end_time_: Instant,
-    new_space_allocation_counter_bytes_: usize,
-    old_generation_allocation_counter_bytes_: usize,
-    embedder_allocation_counter_bytes_: usize,
-    new_generation_allocations_: SmoothedBytesAndDuration,
-    old_generation_allocations_: SmoothedBytesAndDuration,
-    embedder_generation_allocations_: SmoothedBytesAndDuration,
-    recorded_minor_gc_atomic_pause_: RingBuffer<BytesAndDuration>,
-    recorded_minor_gc_per_thread_: RingBuffer<BytesAndDuration>,
-    recorded_mark_compacts_: RingBuffer<BytesAndDuration>,
-    recorded_incremental_mark_compacts_: RingBuffer<BytesAndDuration>,
-    recorded_compactions_: RingBuffer<BytesAndDuration>,
-    recorded_embedder_marking_: RingBuffer<BytesAndDuration>,
-    recorded_survival_ratios_: RingBuffer<f64>,
-    epoch_young_: CollectionEpoch,
-    epoch_full_: CollectionEpoch,
-    recorded_major_totals_: RingBuffer<BytesAndDuration>,
-    average_time_to_incremental_marking_task_: Option<Duration>,
-    average_mark_compact_duration_: f64,
-    average_mutator_duration_: f64,
-    current_mark_compact_mutator_utilization_: f64,
-    recorded_major_incremental_marking_speed_: f64,
-    combined_mark_compact_speed_cache_: Option<f64>,
-    start_of_observable_pause_: Option<Instant>,
-    incremental_scopes_: [IncrementalInfos; Scope::NUMBER_OF_INCREMENTAL_SCOPES],
-    background_scopes_: [Duration; Scope::NUMBER_OF_BACKGROUND_SCOPES],
-    background_scopes_mutex_: Mutex<()>,
-    code_flushing_increase_s_: u16,
-    last_marking_start_time_for_code_flushing_: Option<Instant>,
-    young_gc_while_full_gc_: bool,
-    notified_full_sweeping_completed_: bool,
-    notified_full_cppgc_completed_: bool,
-    notified_young_sweeping_completed_: bool,
-    notified_young_cppgc_running_: bool,
-    notified_young_cppgc_completed_: bool,
-    full_cppgc_completed_during_minor_gc_: bool,
-    incremental_mark_batched_events_:
-        v8::metrics::GarbageCollectionBatchedEvents<v8::metrics::GarbageCollectionIncrementalMark>,
-    incremental_sweep_batched_events_:
-        v8::metrics::GarbageCollectionBatchedEvents<v8::metrics::GarbageCollectionIncrementalSweep>,
-    // parent_track_: Assuming a Rust equivalent for perfetto::ThreadTrack
-}
+    pub fn is_young_generation_event(self) -> bool {
+        self == EventType::SCAVENGER
+    }
 
-impl fmt::Display for GCTracer {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "GCTracer")
+    pub fn is_incremental(self) -> bool {
+        self == EventType::INCREMENTAL_MARK_COMPACTOR
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct IncrementalInfos {
-    pub duration: Duration,
-    pub steps: usize,
-    pub longest_step: Duration,
+/// Phases of a garbage collection whose durations are recorded per event.
+/// Background phases record the duration accumulated over all tasks.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeId {
+    SCAVENGER_SCAVENGE,
+    SCAVENGER_SCAVENGE_ROOTS,
+    SCAVENGER_SCAVENGE_PARALLEL,
+    SCAVENGER_SCAVENGE_WEAK,
+    SCAVENGER_SCAVENGE_FINALIZE,
+    SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL,
+    MC_INCREMENTAL,
+    MC_INCREMENTAL_START,
+    MC_MARK,
+    MC_MARK_ROOTS,
+    MC_MARK_WEAK_CLOSURE_EPHEMERON,
+    MC_CLEAR,
+    MC_EVACUATE,
+    MC_EVACUATE_COPY,
+    MC_EVACUATE_UPDATE_POINTERS,
+    MC_SWEEP,
+    MC_FINISH,
+    MC_BACKGROUND_MARKING,
+    MC_BACKGROUND_EVACUATE_COPY,
+    MC_BACKGROUND_SWEEPING,
 }
 
-impl IncrementalInfos {
-    pub fn new() -> Self {
-        IncrementalInfos {
-            duration: Duration::from_secs(0),
-            steps: 0,
-            longest_step: Duration::from_secs(0),
+impl ScopeId {
+    pub const ALL: &'static [ScopeId] = &[
+        ScopeId::SCAVENGER_SCAVENGE,
+        ScopeId::SCAVENGER_SCAVENGE_ROOTS,
+        ScopeId::SCAVENGER_SCAVENGE_PARALLEL,
+        ScopeId::SCAVENGER_SCAVENGE_WEAK,
+        ScopeId::SCAVENGER_SCAVENGE_FINALIZE,
+        ScopeId::SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL,
+        ScopeId::MC_INCREMENTAL,
+        ScopeId::MC_INCREMENTAL_START,
+        ScopeId::MC_MARK,
+        ScopeId::MC_MARK_ROOTS,
+        ScopeId::MC_MARK_WEAK_CLOSURE_EPHEMERON,
+        ScopeId::MC_CLEAR,
+        ScopeId::MC_EVACUATE,
+        ScopeId::MC_EVACUATE_COPY,
+        ScopeId::MC_EVACUATE_UPDATE_POINTERS,
+        ScopeId::MC_SWEEP,
+        ScopeId::MC_FINISH,
+        ScopeId::MC_BACKGROUND_MARKING,
+        ScopeId::MC_BACKGROUND_EVACUATE_COPY,
+        ScopeId::MC_BACKGROUND_SWEEPING,
+    ];
+    pub const NUMBER_OF_SCOPES: usize = Self::ALL.len();
+
+    /// Name used for trace events.
+    pub fn name(self) -> &'static str {
+        match self {
+            ScopeId::SCAVENGER_SCAVENGE => "V8.GC_SCAVENGER_SCAVENGE",
+            ScopeId::SCAVENGER_SCAVENGE_ROOTS => "V8.GC_SCAVENGER_SCAVENGE_ROOTS",
+            ScopeId::SCAVENGER_SCAVENGE_PARALLEL => "V8.GC_SCAVENGER_SCAVENGE_PARALLEL",
+            ScopeId::SCAVENGER_SCAVENGE_WEAK => "V8.GC_SCAVENGER_SCAVENGE_WEAK",
+            ScopeId::SCAVENGER_SCAVENGE_FINALIZE => "V8.GC_SCAVENGER_SCAVENGE_FINALIZE",
+            ScopeId::SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL => "V8.GC_SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL",
+            ScopeId::MC_INCREMENTAL => "V8.GC_MC_INCREMENTAL",
+            ScopeId::MC_INCREMENTAL_START => "V8.GC_MC_INCREMENTAL_START",
+            ScopeId::MC_MARK => "V8.GC_MC_MARK",
+            ScopeId::MC_MARK_ROOTS => "V8.GC_MC_MARK_ROOTS",
+            ScopeId::MC_MARK_WEAK_CLOSURE_EPHEMERON => "V8.GC_MC_MARK_WEAK_CLOSURE_EPHEMERON",
+            ScopeId::MC_CLEAR => "V8.GC_MC_CLEAR",
+            ScopeId::MC_EVACUATE => "V8.GC_MC_EVACUATE",
+            ScopeId::MC_EVACUATE_COPY => "V8.GC_MC_EVACUATE_COPY",
+            ScopeId::MC_EVACUATE_UPDATE_POINTERS => "V8.GC_MC_EVACUATE_UPDATE_POINTERS",
+            ScopeId::MC_SWEEP => "V8.GC_MC_SWEEP",
+            ScopeId::MC_FINISH => "V8.GC_MC_FINISH",
+            ScopeId::MC_BACKGROUND_MARKING => "V8.GC_MC_BACKGROUND_MARKING",
+            ScopeId::MC_BACKGROUND_EVACUATE_COPY => "V8.GC_MC_BACKGROUND_EVACUATE_COPY",
+            ScopeId::MC_BACKGROUND_SWEEPING => "V8.GC_MC_BACKGROUND_SWEEPING",
         }
     }
-}
 
-impl Default for IncrementalInfos {
-    fn default() -> Self {
-        Self::new()
+    /// Short name used by `--trace-gc-nvp`.
+    pub fn nvp_name(self) -> &'static str {
+        match self {
+            ScopeId::SCAVENGER_SCAVENGE => "scavenge",
+            ScopeId::SCAVENGER_SCAVENGE_ROOTS => "scavenge.roots",
+            ScopeId::SCAVENGER_SCAVENGE_PARALLEL => "scavenge.parallel",
+            ScopeId::SCAVENGER_SCAVENGE_WEAK => "scavenge.weak",
+            ScopeId::SCAVENGER_SCAVENGE_FINALIZE => "scavenge.finalize",
+            ScopeId::SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL => "background.scavenge.parallel",
+            ScopeId::MC_INCREMENTAL => "incremental",
+            ScopeId::MC_INCREMENTAL_START => "incremental.start",
+            ScopeId::MC_MARK => "mark",
+            ScopeId::MC_MARK_ROOTS => "mark.roots",
+            ScopeId::MC_MARK_WEAK_CLOSURE_EPHEMERON => "mark.weak_closure.ephemeron",
+            ScopeId::MC_CLEAR => "clear",
+            ScopeId::MC_EVACUATE => "evacuate",
+            ScopeId::MC_EVACUATE_COPY => "evacuate.copy",
+            ScopeId::MC_EVACUATE_UPDATE_POINTERS => "evacuate.update_pointers",
+            ScopeId::MC_SWEEP => "sweep",
+            ScopeId::MC_FINISH => "finish",
+            ScopeId::MC_BACKGROUND_MARKING => "background.mark",
+            ScopeId::MC_BACKGROUND_EVACUATE_COPY => "background.evacuate.copy",
+            ScopeId::MC_BACKGROUND_SWEEPING => "background.sweep",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
     }
 }
 
-#[derive(Debug)]
+/// Statistics of a single garbage collection cycle.
+#[derive(Debug, Clone)]
 pub struct Event {
-    type_: Event::Type,
-    state: Event::State,
-    gc_reason: GarbageCollectionReason,
-    collector_reason: Option<&'static str>,
-    priority: Option<Priority>,
-    start_time: Instant,
-    end_time: Instant,
-    start_object_size: usize,
-    end_object_size: usize,
-    start_memory_size: usize,
-    end_memory_size: usize,
-    start_holes_size: usize,
-    end_holes_size: usize,
-    young_object_size: usize,
-    survived_young_object_size: usize,
-    start_atomic_pause_time: Instant,
-    end_atomic_pause_time: Instant,
-    incremental_marking_start_time: Instant,
-    incremental_marking_bytes: usize,
-    incremental_marking_duration: Duration,
-    incremental_scopes: [IncrementalInfos; Scope::NUMBER_OF_INCREMENTAL_SCOPES],
-    scopes: [Duration; Scope::NUMBER_OF_INCREMENTAL_SCOPES],
-    concurrency_estimate: usize,
-    reduce_memory: bool,
+    pub type_: EventType,
+    pub gc_reason: Option<GarbageCollectionReason>,
+    /// Times relative to the creation of the tracer.
+    pub start_time: Duration,
+    pub end_time: Duration,
+    /// Size of objects in the heap before and after the cycle.
+    pub start_object_size: usize,
+    pub end_object_size: usize,
+    /// Committed memory before and after the cycle.
+    pub start_memory_size: usize,
+    pub end_memory_size: usize,
+    /// Size of young objects before the cycle, and the parts of it that
+    /// stayed in the young generation or were promoted.
+    pub young_object_size: usize,
+    pub survived_young_object_size: usize,
+    pub promoted_object_size: usize,
+    pub scopes: [Duration; ScopeId::NUMBER_OF_SCOPES],
+    /// Incremental marking that preceded an incremental mark-compact: the
+    /// number of steps, their total and longest duration, and when marking
+    /// started.
+    pub incremental_marking_steps: usize,
+    pub incremental_marking_duration: Duration,
+    pub longest_incremental_marking_step: Duration,
+    pub incremental_marking_start_time: Duration,
 }
 
 impl Event {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub enum Type {
-        SCAVENGER,
-        MARK_COMPACTOR,
-        INCREMENTAL_MARK_COMPACTOR,
-        MINOR_MARK_SWEEPER,
-        INCREMENTAL_MINOR_MARK_SWEEPER,
-        START,
-    }
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub enum State {
-        NOT_RUNNING,
-        MARKING,
-        ATOMIC,
-        SWEEPING,
-    }
-
-    const NUMBER_OF_STATES: usize = 4;
-
-    fn new(
-        type_: Event::Type,
-        state: Event::State,
-        gc_reason: GarbageCollectionReason,
-        collector_reason: Option<&'static str>,
-        priority: Option<Priority>,
-    ) -> Self {
+    fn new(type_: EventType, gc_reason: Option<GarbageCollectionReason>) -> Self {
         Event {
             type_,
-            state,
             gc_reason,
-            collector_reason,
-            priority,
-            start_time: Instant::now(), // Initialized to current time, can be updated later
-            end_time: Instant::now(),   // Initialized to current time, can be updated later
+            start_time: Duration::ZERO,
+            end_time: Duration::ZERO,
             start_object_size: 0,
             end_object_size: 0,
             start_memory_size: 0,
             end_memory_size: 0,
-            start_holes_size: 0,
-            end_holes_size: 0,
             young_object_size: 0,
             survived_young_object_size: 0,
-            start_atomic_pause_time: Instant::now(), // Initialized to current time
-            end_atomic_pause_time: Instant::now(),   // Initialized to current time
-            incremental_marking_start_time: Instant::now(), // Initialized to current time
-            incremental_marking_bytes: 0,
-            incremental_marking_duration: Duration::from_secs(0),
-            incremental_scopes: [IncrementalInfos::default(); Scope::NUMBER_OF_INCREMENTAL_SCOPES],
-            scopes: [Duration::from_secs(0); Scope::NUMBER_OF_INCREMENTAL_SCOPES],
-            concurrency_estimate: 1,
-            reduce_memory: false,
+            promoted_object_size: 0,
+            scopes: [Duration::ZERO; ScopeId::NUMBER_OF_SCOPES],
+            incremental_marking_steps: 0,
+            incremental_marking_duration: Duration::ZERO,
+            longest_incremental_marking_step: Duration::ZERO,
+            incremental_marking_start_time: Duration::ZERO,
         }
     }
 
-    pub fn is_young_generation_event(type_: Event::Type) -> bool {
-        type_ == Event::Type::SCAVENGER
-            || type_ == Event::Type::MINOR_MARK_SWEEPER
-            || type_ == Event::Type::INCREMENTAL_MINOR_MARK_SWEEPER
+    pub fn duration(&self) -> Duration {
+        self.end_time.saturating_sub(self.start_time)
     }
-}
 
-impl fmt::Display for Event {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Event {{ type: {:?}, state: {:?}, gc_reason: {:?} }}",
-            self.type_, self.state, self.gc_reason
-        )
+    pub fn scope(&self, id: ScopeId) -> Duration {
+        self.scopes[id.index()]
     }
 }
 
-pub fn to_string(type_: Event::Type, short_name: bool) -> &'static str {
-    match type_ {
-        Event::Type::SCAVENGER => {
-            if short_name {
-                "s"
-            } else {
-                "Scavenge"
-            }
-        }
-        Event::Type::MARK_COMPACTOR | Event::Type::INCREMENTAL_MARK_COMPACTOR => {
-            if short_name {
-                "mc"
-            } else {
-                "Mark-Compact"
-            }
-        }
-        Event::Type::MINOR_MARK_SWEEPER | Event::Type::INCREMENTAL_MINOR_MARK_SWEEPER => {
-            if short_name {
-                "mms"
-            } else {
-                "Minor Mark-Sweep"
-            }
-        }
-        Event::Type::START => {
-            if short_name {
-                "st"
-            } else {
-                "Start"
-            }
-        }
-    }
+/// Incremental marking work done outside of a cycle, which is accounted to
+/// the next mark-compact.
+struct IncrementalMarkingInfo {
+    start_time: Option<Duration>,
+    steps: usize,
+    duration: Duration,
+    longest_step: Duration,
+    scopes: [Duration; ScopeId::NUMBER_OF_SCOPES],
 }
 
-pub mod v8 {
-    pub mod metrics {
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionPhases {
-            pub compact_wall_clock_duration_in_us: i64,
-            pub mark_wall_clock_duration_in_us: i64,
-            pub sweep_wall_clock_duration_in_us: i64,
-            pub weak_wall_clock_duration_in_us: i64,
-            pub total_wall_clock_duration_in_us: i64,
+impl IncrementalMarkingInfo {
+    fn new() -> Self {
+        IncrementalMarkingInfo {
+            start_time: None,
+            steps: 0,
+            duration: Duration::ZERO,
+            longest_step: Duration::ZERO,
+            scopes: [Duration::ZERO; ScopeId::NUMBER_OF_SCOPES],
         }
+    }
+}
 
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionSizes {
-            pub bytes_before: usize,
-            pub bytes_after: usize,
-            pub bytes_freed: usize,
-        }
+/// Records statistics of garbage collection cycles and optionally prints
+/// them, see `set_trace_gc()` and `set_trace_gc_nvp()`.
+///
+/// Samples taken outside of a cycle, i.e. by incremental marking steps and
+/// concurrent work, are added to the next mark-compact event.
+pub struct GCTracer {
+    time_origin: Instant,
+    current: RefCell<Event>,
+    previous: RefCell<Event>,
+    incremental_marking: RefCell<IncrementalMarkingInfo>,
+    recorded_events: RefCell<VecDeque<Event>>,
+    heap_limit_decisions: RefCell<VecDeque<HeapLimitDecision>>,
+    trace_gc: Cell<bool>,
+    trace_gc_nvp: Cell<bool>,
+    trace_heap_limits: Cell<bool>,
+    metrics_recorder: RefCell<Option<Arc<Recorder>>>,
+    recorder_context_id: Cell<ContextId>,
+}
 
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionFullCycle {
-            pub reason: i32,
-            pub priority: Option<super::Priority>,
-            pub total_cpp: GarbageCollectionPhases,
-            pub main_thread_cpp: GarbageCollectionPhases,
-            pub main_thread_atomic_cpp: GarbageCollectionPhases,
-            pub main_thread_incremental_cpp: GarbageCollectionPhases,
-            pub objects_cpp: GarbageCollectionSizes,
-            pub memory_cpp: GarbageCollectionSizes,
-            pub collection_rate_cpp_in_percent: f64,
-            pub efficiency_cpp_in_bytes_per_us: f64,
-            pub main_thread_efficiency_cpp_in_bytes_per_us: f64,
-            pub collection_weight_cpp_in_percent: f64,
-            pub main_thread_collection_weight_cpp_in_percent: f64,
-            pub total: GarbageCollectionPhases,
-            pub main_thread: GarbageCollectionPhases,
-            pub main_thread_atomic: GarbageCollectionPhases,
-            pub objects: GarbageCollectionSizes,
-            pub memory: GarbageCollectionSizes,
-            pub collection_rate_in_percent: f64,
-            pub efficiency_in_bytes_per_us: f64,
-            pub main_thread_efficiency_in_bytes_per_us: f64,
-            pub collection_weight_in_percent: f64,
-            pub main_thread_collection_weight_in_percent: f64,
-            pub main_thread_incremental: GarbageCollectionPhases,
-            pub incremental_marking_start_stop_wall_clock_duration_in_us: i64,
-        }
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionYoungCycle {
-            pub reason: i32,
-            pub priority: Option<super::Priority>,
-            pub total_cpp: GarbageCollectionPhases,
-            pub objects_cpp: GarbageCollectionSizes,
-            pub memory_cpp: GarbageCollectionSizes,
-            pub collection_rate_cpp_in_percent: f64,
-            pub efficiency_cpp_in_bytes_per_us: f64,
-            pub main_thread_efficiency_cpp_in_bytes_per_us: f64,
-            pub total_wall_clock_duration_in_us: i64,
-            pub main_thread_wall_clock_duration_in_us: i64,
-            pub collection_rate_in_percent: f64,
-            pub efficiency_in_bytes_per_us: f64,
-            pub main_thread_efficiency_in_bytes_per_us: f64,
-        }
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionIncrementalMark {
-            pub wall_clock_duration_in_us: i64,
-            pub cpp_wall_clock_duration_in_us: i64,
-        }
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionIncrementalSweep {
-            pub wall_clock_duration_in_us: i64,
-        }
+impl GCTracer {
+    /// Number of most recent events kept by `recorded_events()`.
+    pub const K_MAX_RECORDED_EVENTS: usize = 32;
 
-        #[derive(Debug, Default, Clone)]
-        pub struct GarbageCollectionBatchedEvents<T> {
-            pub events: Vec<T>,
-        }
-        pub trait Recorder {
-            fn add_main_thread_event<T: std::fmt::Debug + Clone>(
-                &self,
-                event: T,
-                context_id: Recorder::ContextId,
-            );
-            fn has_embedder_recorder(&self) -> bool;
-            type ContextId;
-        }
-        impl<T> GarbageCollectionBatchedEvents<T> {
-            pub fn new() -> Self {
-                Self { events: Vec::new() }
-            }
+    pub fn new() -> Self {
+        GCTracer {
+            time_origin: Instant::now(),
+            current: RefCell::new(Event::new(EventType::START, None)),
+            previous: RefCell::new(Event::new(EventType::START, None)),
+            incremental_marking: RefCell::new(IncrementalMarkingInfo::new()),
+            recorded_events: RefCell::new(VecDeque::new()),
+            heap_limit_decisions: RefCell::new(VecDeque::new()),
+            trace_gc: Cell::new(false),
+            trace_gc_nvp: Cell::new(false),
+            trace_heap_limits: Cell::new(false),
+            metrics_recorder: RefCell::new(None),
+            recorder_context_id: Cell::new(ContextId::empty()),
         }
+    }
 
-        impl Recorder for () {
-            fn add_main_thread_event<T: std::fmt::Debug + Clone>(
-                &self,
-                event: T,
-                context_id: <Self as Recorder>::ContextId,
-            ) {
-                println!("Adding event: {:?} with context {:?}", event, context_id);
-            }
-
-            fn has_embedder_recorder(&self) -> bool {
-                false
-            }
+    /// Prints a line per cycle, like `--trace-gc`.
+    pub fn set_trace_gc(&self, enabled: bool) {
+        self.trace_gc.set(enabled);
+    }
 
-            type ContextId = u32;
-        }
+    /// Prints name=value pairs with phase durations per cycle, like
+    /// `--trace-gc-nvp`.
+    pub fn set_trace_gc_nvp(&self, enabled: bool) {
+        self.trace_gc_nvp.set(enabled);
+    }
 
-        impl Recorder {
-            pub enum ContextId {
-                Empty,
-                Native(u32),
-            }
-        }
+    /// Prints a line per heap limit decision, like
+    /// `--trace-memory-balancer`.
+    pub fn set_trace_heap_limits(&self, enabled: bool) {
+        self.trace_heap_limits.set(enabled);
     }
-}
 
-impl GCTracer {
-    pub fn new(
-        heap: *mut Heap,
-        startup_time: Instant,
-        initial_gc_reason: GarbageCollectionReason,
-    ) -> Self {
-        GCTracer {
-            heap_: heap,
-            current_: Event::new(
-                Event::Type::START,
-                Event::State::NOT_RUNNING,
-                initial_gc_reason,
-                None,
-                // FIXME: Find a good default for priority
-                None,
-            ),
-            previous_: Event::new(
-                Event::Type::START,
-                Event::State::NOT_RUNNING,
-                initial_gc_reason,
-                None,
-                // FIXME: Find a good default for priority
-                None,
-            ),
-            allocation_time_: startup_time,
-            previous_mark_compact_end_time_: startup_time,
-            new_space_allocation_counter_bytes_: 0,
-            old_generation_allocation_counter_bytes_: 0,
-            embedder_allocation_counter_bytes_: 0,
-            new_generation_allocations_: SmoothedBytesAndDuration::default(),
-            old_generation_allocations_: SmoothedBytesAndDuration::default(),
-            embedder_generation_allocations_: SmoothedBytesAndDuration::default(),
-            recorded_minor_gc_atomic_pause_: RingBuffer::new(16), // Example size
-            recorded_minor_gc_per_thread_: RingBuffer::new(16), // Example size
-            recorded_mark_compacts_: RingBuffer::new(16),       // Example size
-            recorded_incremental_mark_compacts_: RingBuffer::new(16), // Example size
-            recorded_compactions_: RingBuffer::new(16),         // Example size
-            recorded_embedder_marking_: RingBuffer::new(16),    // Example size
-            recorded_survival_ratios_: RingBuffer::new(16),      // Example size
-            epoch_young_: 0,
-            epoch_full_: 0,
-            recorded_major_totals_: RingBuffer::new(16),
-            average_time_to_incremental_marking_task_: None,
-            average_mark_compact_duration_: 0.0,
-            average_mutator_duration_: 0.0,
-            current_mark_compact_mutator_utilization_: 0.0,
-            recorded_major_incremental_marking_speed_: 0.0,
-            combined_mark_compact_speed_cache_: None,
-            start_of_observable_pause_: None,
-            incremental_scopes_: [IncrementalInfos::default(); Scope::NUMBER_OF_INCREMENTAL_SCOPES],
-            background_scopes_: [Duration::from_secs(0); Scope::NUMBER_OF_BACKGROUND_SCOPES],
-            background_scopes_mutex_: Mutex::new(()),
-            code_flushing_increase_s_: 0,
-            last_marking_start_time_for_code_flushing_: None,
-            young_gc_while_full_gc_: false,
-            notified_full_sweeping_completed_: false,
-            notified_full_cppgc_completed_: false,
-            notified_young_sweeping_completed_: false,
-            notified_young_cppgc_running_: false,
-            notified_young_cppgc_completed_: false,
-            full_cppgc_completed_during_minor_gc_: false,
-            incremental_mark_batched_events_:
-                v8::metrics::GarbageCollectionBatchedEvents::default(),
-            incremental_sweep_batched_events_:
-                v8::metrics::GarbageCollectionBatchedEvents::default(),
-            // parent_track_: Assuming a Rust equivalent for perfetto::ThreadTrack
-        }
+    /// Reports every finished cycle to `recorder` as a
+    /// `GarbageCollectionFullCycle` or `GarbageCollectionYoungCycle` event.
+    pub fn set_metrics_recorder(&self, recorder: Option<Arc<Recorder>>) {
+        *self.metrics_recorder.borrow_mut() = recorder;
     }
 
-    // ResetForTesting is not implemented because it uses placement new,
-    // which is not directly supported in Rust. Instead, a new instance
-    // should be created.
+    /// Sets the context that reported cycles are attributed to, usually
+    /// the context that was entered last.
+    pub fn set_recorder_context_id(&self, context_id: ContextId) {
+        self.recorder_context_id.set(context_id);
+    }
 
-    pub fn start_observable_pause(&mut self, time: Instant) {
-        assert!(self.start_of_observable_pause_.is_none());
-        self.start_of_observable_pause_ = Some(time);
+    pub fn monotonically_increasing_time(&self) -> Duration {
+        self.time_origin.elapsed()
     }
 
-    pub fn update_current_event(
-        &mut self,
-        gc_reason: GarbageCollectionReason,
-        collector_reason: Option<&'static str>,
-    ) {
-        assert!(
-            self.current_.type_ == Event::Type::INCREMENTAL_MARK_COMPACTOR
-                || self.current_.type_ == Event::Type::INCREMENTAL_MINOR_MARK_SWEEPER
-        );
-        assert_eq!(self.current_.state, Event::State::ATOMIC);
-        assert!(self.is_in_observable_pause());
-        self.current_.gc_reason = gc_reason;
-        self.current_.collector_reason = collector_reason;
-        self.current_.start_time = self.start_of_observable_pause_.unwrap();
-        self.current_.reduce_memory = self.should_reduce_memory();
+    pub fn is_in_cycle(&self) -> bool {
+        self.current.borrow().type_ != EventType::START
     }
 
     pub fn start_cycle(
-        &mut self,
+        &self,
         collector: GarbageCollector,
         gc_reason: GarbageCollectionReason,
-        collector_reason: Option<&'static str>,
-        marking: MarkingType,
+        object_size: usize,
+        memory_size: usize,
+        young_object_size: usize,
     ) {
-        assert_ne!(self.current_.state, Event::State::ATOMIC);
-        assert!(!self.young_gc_while_full_gc_);
+        debug_assert!(!self.is_in_cycle());
+        let mut incremental_marking = None;
+        let type_ = match collector {
+            GarbageCollector::SCAVENGER => EventType::SCAVENGER,
+            GarbageCollector::MARK_COMPACTOR => {
+                let info = std::mem::replace(&mut *self.incremental_marking.borrow_mut(), IncrementalMarkingInfo::new());
+                let type_ = if info.start_time.is_some() {
+                    EventType::INCREMENTAL_MARK_COMPACTOR
+                } else {
+                    EventType::MARK_COMPACTOR
+                };
+                incremental_marking = Some(info);
+                type_
+            }
+        };
+        let mut event = Event::new(type_, Some(gc_reason));
+        if let Some(info) = incremental_marking {
+            event.scopes = info.scopes;
+            event.incremental_marking_steps = info.steps;
+            event.incremental_marking_duration = info.duration;
+            event.longest_incremental_marking_step = info.longest_step;
+            event.incremental_marking_start_time = info.start_time.unwrap_or_default();
+        }
+        event.start_time = self.monotonically_increasing_time();
+        event.start_object_size = object_size;
+        event.start_memory_size = memory_size;
+        event.young_object_size = young_object_size;
+        *self.current.borrow_mut() = event;
+    }
+
+    /// Records the sizes of young objects that survived the cycle.
+    pub fn notify_young_survivors(&self, survived_young_object_size: usize, promoted_object_size: usize) {
+        let mut current = self.current.borrow_mut();
+        current.survived_young_object_size = survived_young_object_size;
+        current.promoted_object_size = promoted_object_size;
+    }
 
-        self.young_gc_while_full_gc_ = self.current_.state != Event::State::NOT_RUNNING;
-        if self.young_gc_while_full_gc_ {
-            self.fetch_background_counters();
+    pub fn stop_cycle(&self, object_size: usize, memory_size: usize) {
+        debug_assert!(self.is_in_cycle());
+        let event = {
+            let mut current = self.current.borrow_mut();
+            current.end_time = self.monotonically_increasing_time();
+            current.end_object_size = object_size;
+            current.end_memory_size = memory_size;
+            std::mem::replace(&mut *current, Event::new(EventType::START, None))
+        };
+        if self.trace_gc.get() {
+            println!("{}", self.format_event(&event));
+        }
+        if self.trace_gc_nvp.get() {
+            println!("{}", self.format_nvp(&event));
+        }
+        self.report_to_recorder(&event);
+        let mut recorded = self.recorded_events.borrow_mut();
+        if recorded.len() == Self::K_MAX_RECORDED_EVENTS {
+            recorded.pop_front();
         }
+        recorded.push_back(event.clone());
+        *self.previous.borrow_mut() = event;
+    }
+
+    /// Adds `duration` to the phase `id` of the current cycle, or of the
+    /// next mark-compact outside of a cycle. Background tasks report their
+    /// samples through the thread that joins them.
+    pub fn add_scope_sample(&self, id: ScopeId, duration: Duration) {
+        if self.is_in_cycle() {
+            self.current.borrow_mut().scopes[id.index()] += duration;
+        } else {
+            self.incremental_marking.borrow_mut().scopes[id.index()] += duration;
+        }
+    }
+
+    pub fn notify_incremental_marking_start(&self) {
+        debug_assert!(!self.is_in_cycle());
+        let mut info = self.incremental_marking.borrow_mut();
+        *info = IncrementalMarkingInfo::new();
+        info.start_time = Some(self.monotonically_increasing_time());
+    }
 
-        assert!(!self.young_gc_while_full_gc_ || Heap::is_young_generation_collector(collector));
-        assert!(
-            !self.young_gc_while_full_gc_
-                || !matches!(
-                    self.current_.type_,
-                    Event::Type::SCAVENGER
-                        | Event::Type::MINOR_MARK_SWEEPER
-                        | Event::Type::INCREMENTAL_MINOR_MARK_SWEEPER
+    /// Records an incremental marking step on the main thread.
+    pub fn add_incremental_marking_step(&self, duration: Duration) {
+        self.add_scope_sample(ScopeId::MC_INCREMENTAL, duration);
+        let mut info = self.incremental_marking.borrow_mut();
+        info.steps += 1;
+        info.duration += duration;
+        info.longest_step = info.longest_step.max(duration);
+    }
+
+    /// Speed of marking and compacting the old generation, in bytes per
+    /// millisecond, over the recorded mark-compacts including their
+    /// incremental marking.
+    pub fn combined_mark_compact_speed_in_bytes_per_millisecond(&self) -> Option<f64> {
+        let (bytes, time) = self
+            .recorded_events
+            .borrow()
+            .iter()
+            .filter(|event| matches!(event.type_, EventType::MARK_COMPACTOR | EventType::INCREMENTAL_MARK_COMPACTOR))
+            .fold((0, Duration::ZERO), |(bytes, time), event| {
+                (
+                    bytes + event.start_object_size,
+                    time + event.duration() + event.incremental_marking_duration,
                 )
-        );
+            });
+        let time_ms = time.as_secs_f64() * 1000.0;
+        (time_ms > 0.0).then(|| bytes as f64 / time_ms)
+    }
 
-        assert!(!self.young_gc_while_full_gc_ || self.current_.state == Event::State::SWEEPING);
-        assert!(!self.young_gc_while_full_gc_ || !Event::is_young_generation_event(self.current_.type_));
+    /// Returns a guard that records the time until it is dropped as phase
+    /// `id` of the current cycle.
+    pub fn scope(&self, id: ScopeId) -> GCTracerScope<'_> {
+        GCTracerScope {
+            tracer: self,
+            id,
+            start: Instant::now(),
+        }
+    }
 
-        assert!(!self.young_gc_while_full_gc_ || self.current_.state == Event::State::NOT_RUNNING);
-        assert_eq!(self.previous_.state, Event::State::NOT_RUNNING);
+    /// Returns the most recently finished cycle.
+    pub fn last_event(&self) -> Event {
+        self.previous.borrow().clone()
+    }
 
-        let type_ = match collector {
-            GarbageCollector::SCAVENGER => Event::Type::SCAVENGER,
-            GarbageCollector::MINOR_MARK_SWEEPER => {
-                if marking == MarkingType::kIncremental {
-                    Event::Type::INCREMENTAL_MINOR_MARK_SWEEPER
-                } else {
-                    Event::Type::MINOR_MARK_SWEEPER
-                }
-            }
-            GarbageCollector::MARK_COMPACTOR => {
-                if marking == MarkingType::kIncremental {
-                    Event::Type::INCREMENTAL_MARK_COMPACTOR
-                } else {
-                    Event::Type::MARK_COMPACTOR
-                }
-            }
+    /// Returns the most recently finished cycles, oldest first.
+    pub fn recorded_events(&self) -> Vec<Event> {
+        self.recorded_events.borrow().iter().cloned().collect()
+    }
+
+    /// Records a change of a limit of the old generation, stamped with the
+    /// current time.
+    pub fn notify_heap_limit_decision(&self, mut decision: HeapLimitDecision) {
+        decision.time = self.monotonically_increasing_time();
+        if self.trace_heap_limits.get() {
+            println!("{}", self.format_heap_limit_decision(&decision));
+        }
+        let mut decisions = self.heap_limit_decisions.borrow_mut();
+        if decisions.len() == Self::K_MAX_RECORDED_EVENTS {
+            decisions.pop_front();
+        }
+        decisions.push_back(decision);
+    }
+
+    /// Returns the most recent heap limit decisions, oldest first.
+    pub fn heap_limit_decisions(&self) -> Vec<HeapLimitDecision> {
+        self.heap_limit_decisions.borrow().iter().cloned().collect()
+    }
+
+    fn report_to_recorder(&self, event: &Event) {
+        let recorder = self.metrics_recorder.borrow();
+        let Some(recorder) = recorder.as_ref().filter(|recorder| recorder.has_embedder_recorder()) else {
+            return;
         };
+        let context_id = self.recorder_context_id.get();
+        if event.type_.is_young_generation_event() {
+            recorder.delay_main_thread_event(Self::young_cycle_event(event), context_id);
+        } else {
+            recorder.delay_main_thread_event(Self::full_cycle_event(event), context_id);
+        }
+    }
 
-        assert!(!self.young_gc_while_full_gc_ || self.current_.state == Event::State::NOT_RUNNING);
-        assert_eq!(self.previous_.state, Event::State::NOT_RUNNING);
+    fn full_cycle_event(event: &Event) -> GarbageCollectionFullCycle {
+        let incremental_mark = event.scope(ScopeId::MC_INCREMENTAL) + event.scope(ScopeId::MC_INCREMENTAL_START);
+        let atomic = GarbageCollectionPhases {
+            total_wall_clock_duration_in_us: in_us(event.duration()),
+            compact_wall_clock_duration_in_us: in_us(event.scope(ScopeId::MC_EVACUATE)),
+            mark_wall_clock_duration_in_us: in_us(event.scope(ScopeId::MC_MARK)),
+            sweep_wall_clock_duration_in_us: in_us(event.scope(ScopeId::MC_SWEEP)),
+            weak_wall_clock_duration_in_us: in_us(event.scope(ScopeId::MC_CLEAR)),
+        };
+        // Sweeping is done atomically and by background tasks, never in
+        // incremental steps.
+        let incremental = GarbageCollectionPhases {
+            total_wall_clock_duration_in_us: in_us(incremental_mark),
+            compact_wall_clock_duration_in_us: 0,
+            mark_wall_clock_duration_in_us: in_us(incremental_mark),
+            sweep_wall_clock_duration_in_us: 0,
+            weak_wall_clock_duration_in_us: 0,
+        };
+        let main_thread = GarbageCollectionPhases {
+            total_wall_clock_duration_in_us: atomic.total_wall_clock_duration_in_us
+                + incremental.total_wall_clock_duration_in_us,
+            mark_wall_clock_duration_in_us: atomic.mark_wall_clock_duration_in_us
+                + incremental.mark_wall_clock_duration_in_us,
+            ..atomic
+        };
+        let background_mark = event.scope(ScopeId::MC_BACKGROUND_MARKING);
+        let background_compact = event.scope(ScopeId::MC_BACKGROUND_EVACUATE_COPY);
+        let background_sweep = event.scope(ScopeId::MC_BACKGROUND_SWEEPING);
+        let total = GarbageCollectionPhases {
+            total_wall_clock_duration_in_us: main_thread.total_wall_clock_duration_in_us
+                + in_us(background_mark + background_compact + background_sweep),
+            compact_wall_clock_duration_in_us: main_thread.compact_wall_clock_duration_in_us
+                + in_us(background_compact),
+            mark_wall_clock_duration_in_us: main_thread.mark_wall_clock_duration_in_us + in_us(background_mark),
+            sweep_wall_clock_duration_in_us: main_thread.sweep_wall_clock_duration_in_us + in_us(background_sweep),
+            weak_wall_clock_duration_in_us: main_thread.weak_wall_clock_duration_in_us,
+        };
+        let objects = sizes(event.start_object_size, event.end_object_size);
+        let memory = sizes(event.start_memory_size, event.end_memory_size);
+        let mut cycle = GarbageCollectionFullCycle {
+            reason: event.gc_reason.map_or(-1, |reason| reason as i32),
+            total,
+            main_thread,
+            main_thread_atomic: atomic,
+            main_thread_incremental: incremental,
+            objects,
+            memory,
+            collection_rate_in_percent: rate_in_percent(objects.bytes_freed, objects.bytes_before),
+            efficiency_in_bytes_per_us: bytes_per_us(objects.bytes_freed, total.total_wall_clock_duration_in_us),
+            main_thread_efficiency_in_bytes_per_us: bytes_per_us(
+                objects.bytes_freed,
+                main_thread.total_wall_clock_duration_in_us,
+            ),
+            ..GarbageCollectionFullCycle::new()
+        };
+        if event.type_.is_incremental() {
+            cycle.incremental_marking_start_stop_wall_clock_duration_in_us =
+                in_us(event.end_time.saturating_sub(event.incremental_marking_start_time));
+        }
+        cycle
+    }
 
-        self.previous_ = self.current_;
-        self.current_ = Event::new(
-            type_,
-            Event::State::MARKING,
-            gc_reason,
-            collector_reason,
-            self.isolate_priority(),
-        );
+    fn young_cycle_event(event: &Event) -> GarbageCollectionYoungCycle {
+        let main_thread = in_us(event.duration());
+        let total = main_thread + in_us(event.scope(ScopeId::SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL));
+        let freed = event.young_object_size as i64
+            - event.survived_young_object_size as i64
+            - event.promoted_object_size as i64;
+        GarbageCollectionYoungCycle {
+            reason: event.gc_reason.map_or(-1, |reason| reason as i32),
+            total_wall_clock_duration_in_us: total,
+            main_thread_wall_clock_duration_in_us: main_thread,
+            collection_rate_in_percent: rate_in_percent(freed, event.young_object_size as i64),
+            efficiency_in_bytes_per_us: bytes_per_us(freed, total),
+            main_thread_efficiency_in_bytes_per_us: bytes_per_us(freed, main_thread),
+            ..GarbageCollectionYoungCycle::new()
+        }
+    }
 
-        match marking {
-            MarkingType::kAtomic => {
-                assert!(self.is_in_observable_pause());
-                self.current_.start_time = self.start_of_observable_pause_.unwrap();
-                self.current_.reduce_memory = self.should_reduce_memory();
-            }
-            MarkingType::kIncremental => {
-                assert!(!self.is_in_observable_pause());
-            }
+    fn format_heap_limit_decision(&self, decision: &HeapLimitDecision) -> String {
+        let mut line = format!(
+            "[{}] {:8.0} ms: {}: {} {:.1} -> {:.1} MB, old generation {:.1} MB",
+            std::process::id(),
+            decision.time.as_secs_f64() * 1000.0,
+            decision.source,
+            decision.kind.to_str(),
+            decision.previous_limit as f64 / MB,
+            decision.limit as f64 / MB,
+            decision.old_generation_size as f64 / MB,
+        );
+        if let Some(allocation_rate) = decision.allocation_rate {
+            let _ = write!(line, ", allocation rate {:.1} KB/ms", allocation_rate / KB);
         }
+        if let Some(gc_speed) = decision.gc_speed {
+            let _ = write!(line, ", gc speed {:.1} KB/ms", gc_speed / KB);
+        }
+        line
+    }
 
-        if Heap::is_young_generation_collector(collector) {
-            self.epoch_young_ = next_epoch();
+    fn format_event(&self, event: &Event) -> String {
+        let incremental = if event.type_.is_incremental() {
+            format!(
+                " (+ {:.1} ms in {} steps since start of marking, biggest step {:.1} ms, \
+                 walltime since start of marking {:.0} ms)",
+                event.incremental_marking_duration.as_secs_f64() * 1000.0,
+                event.incremental_marking_steps,
+                event.longest_incremental_marking_step.as_secs_f64() * 1000.0,
+                event.end_time.saturating_sub(event.incremental_marking_start_time).as_secs_f64() * 1000.0,
+            )
         } else {
-            self.epoch_full_ = next_epoch();
+            String::new()
+        };
+        format!(
+            "[{}] {:8.0} ms: {} {:.1} ({:.1}) -> {:.1} ({:.1}) MB, {:.2} ms{}  {};",
+            std::process::id(),
+            event.start_time.as_secs_f64() * 1000.0,
+            event.type_.to_str(false),
+            event.start_object_size as f64 / MB,
+            event.start_memory_size as f64 / MB,
+            event.end_object_size as f64 / MB,
+            event.end_memory_size as f64 / MB,
+            event.duration().as_secs_f64() * 1000.0,
+            incremental,
+            event.gc_reason.map_or("", GarbageCollectionReason::to_str),
+        )
+    }
+
+    fn format_nvp(&self, event: &Event) -> String {
+        let mut line = format!(
+            "pause={:.2} gc={} start_object_size={} end_object_size={} \
+             young_object_size={} survived={} promoted={} incremental.steps_count={}",
+            event.duration().as_secs_f64() * 1000.0,
+            event.type_.to_str(true),
+            event.start_object_size,
+            event.end_object_size,
+            event.young_object_size,
+            event.survived_young_object_size,
+            event.promoted_object_size,
+            event.incremental_marking_steps,
+        );
+        for &id in ScopeId::ALL {
+            let _ = write!(line, " {}={:.2}", id.nvp_name(), event.scope(id).as_secs_f64() * 1000.0);
         }
+        line
     }
+}
 
-    pub fn start_atomic_pause(&mut self) {
-        assert_eq!(self.current_.state, Event::State::MARKING);
-        self.current_.state = Event::State::ATOMIC;
+impl Default for GCTracer {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn start_in_safepoint(&mut self, time: Instant) {
-        self.sample_allocation(
-            self.current_.start_time,
-            self.new_space_allocation_counter(),
-            self.old_generation_allocation_counter(),
-            self.embedder_allocation_counter(),
-        );
+fn in_us(duration: Duration) -> i64 {
+    duration.as_micros() as i64
+}
+
+fn sizes(before: usize, after: usize) -> GarbageCollectionSizes {
+    GarbageCollectionSizes {
+        bytes_before: before as i64,
+        bytes_after: after as i64,
+        bytes_freed: before as i64 - after as i64,
+    }
+}
+
+/// Ratios with an empty denominator are reported as not measured.
+fn rate_in_percent(freed: i64, before: i64) -> f64 {
+    if before > 0 { 100.0 * freed as f64 / before as f64 } else { -1.0 }
+}
+
+fn bytes_per_us(freed: i64, duration_in_us: i64) -> f64 {
+    if duration_in_us > 0 { freed as f64 / duration_in_us as f64 } else { -1.0 }
+}
 
-        self.current_.start_object_size = self.size_of_objects();
-        self.current_.start_memory_size = self.memory_allocator_size();
-        self.current_.start_holes_size = count_total_holes_size(self.heap());
-        let new_space_size = self.new_space().map_or(0, |space| space.size());
-        let new_lo_space_size = self
-            .new_lo_space()
-            .map_or(0, |space| space.size_of_objects());
-        self.current_.young_object_size = new_space_size + new_lo_space_size;
-        self.current_.start_atomic_pause_time = time;
-    }
-
-    pub fn stop_in_safepoint(&mut self, time: Instant) {
-        self.current_.end_object_size = self.size_of_objects();
-        self.current_.end_memory_size = self.memory_allocator_size();
-        self.current_.end_holes_size = count_total_holes_size(self.heap());
-        self.current_.survived_young_object_size = self.survived_young_object_size();
-        self.
\ No newline at end of file
+/// Records the duration of a phase of the current cycle, see
+/// `GCTracer::scope()`.
+pub struct GCTracerScope<'a> {
+    tracer: &'a GCTracer,
+    id: ScopeId,
+    start: Instant,
+}
+
+impl Drop for GCTracerScope<'_> {
+    fn drop(&mut self) {
+        self.tracer.add_scope_sample(self.id, self.start.elapsed());
+    }
+}
@@ -2,51 +2,104 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/heap-controller.rs
-
-use std::cmp::{max, min};
 use std::marker::PhantomData;
-use std::optional::Option;
-
-// Placeholder for Isolate, Spaces, PageMetadata, TraceEvent, and v8_flags
-// Since we don't have the definitions for these, we'll use simple placeholders.
-
-// pub struct Isolate {}
-
-// impl Isolate {
-//     pub fn from_heap(_heap: &Heap) -> &mut Self {
-//         todo!()
-//     }
-//     pub fn print_with_timestamp(&mut self, _format: &str, _args: ...) {
-//         // Implementation for printing with timestamp, using format! and println!
-//         // Placeholder for varargs
-//         todo!()
-//     }
-// }
-
-// pub struct Heap {}
-
-// impl Heap {
-//     pub enum HeapGrowingMode {
-//         kConservative,
-//         kSlow,
-//         kMinimal,
-//         kDefault,
-//     }
-// }
-
-// pub mod v8_flags {
-//     pub static heap_growing_percent: f64 = 0.0;
-//     pub static trace_gc_verbose: bool = false;
-// }
-
-// pub mod PageMetadata {
-//   pub const kPageSize: usize = 4096; // Example value, replace with actual
-// }
+use std::time::Duration;
+
+use crate::heap::heap::HeapGrowingMode;
+use crate::heap::memory_chunk::K_PAGE_SIZE;
 
 const KB: usize = 1024;
 const MB: usize = 1024 * KB;
 
+/// What the allocation limit of the old generation is based on, sampled at
+/// the end of a mark-compact.
+#[derive(Clone, Copy, Debug)]
+pub struct HeapGrowingInput {
+    /// Size of the old generation after the mark-compact.
+    pub old_generation_size: usize,
+    /// Bytes allocated in the old generation since the previous
+    /// mark-compact, and the time the mutator ran in between.
+    pub allocated_bytes: usize,
+    pub mutator_duration: Duration,
+    /// Size of the heap collected by the mark-compact, and the time spent
+    /// including incremental marking.
+    pub gc_bytes: usize,
+    pub gc_duration: Duration,
+    /// Speed of the recent mark-compacts in bytes per millisecond, see
+    /// `GCTracer::combined_mark_compact_speed_in_bytes_per_millisecond()`.
+    pub gc_speed: Option<f64>,
+    pub min_old_generation_size: usize,
+    pub max_old_generation_size: usize,
+    pub new_space_capacity: usize,
+    /// `kMinimal` for mark-compacts that reduce memory, e.g. those started
+    /// by the `MemoryReducer`; policies should then grant minimal room.
+    pub growing_mode: HeapGrowingMode,
+}
+
+impl HeapGrowingInput {
+    /// Old-generation allocation rate in bytes per millisecond.
+    pub fn mutator_speed(&self) -> f64 {
+        let mutator_time_ms = self.mutator_duration.as_secs_f64() * 1000.0;
+        if mutator_time_ms > 0.0 {
+            self.allocated_bytes as f64 / mutator_time_ms
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Which limit of the old generation a `HeapLimitDecision` changed.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapLimitKind {
+    /// The size that triggers the next mark-compact.
+    kAllocationLimit,
+    /// The size beyond which the heap runs out of memory.
+    kMaxOldGenerationSize,
+}
+
+impl HeapLimitKind {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            HeapLimitKind::kAllocationLimit => "allocation limit",
+            HeapLimitKind::kMaxOldGenerationSize => "max old generation size",
+        }
+    }
+}
+
+/// A change of a limit of the old generation, recorded by the `GCTracer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeapLimitDecision {
+    pub kind: HeapLimitKind,
+    /// The heap growing policy or the API that decided.
+    pub source: &'static str,
+    /// Time relative to the creation of the tracer.
+    pub time: Duration,
+    pub old_generation_size: usize,
+    pub previous_limit: usize,
+    pub limit: usize,
+    /// Old-generation allocation rate and collector speed in bytes per
+    /// millisecond, for allocation limits.
+    pub allocation_rate: Option<f64>,
+    pub gc_speed: Option<f64>,
+}
+
+/// Decides the allocation limit of the old generation after each
+/// mark-compact, i.e. how much the heap may grow before the next one.
+/// Larger limits trade memory for throughput.
+///
+/// Policies are set per heap through `Heap::set_heap_growing_policy()`;
+/// `MemoryController<V8HeapTrait>` is the default and `MemoryBalancer` a
+/// rate-based alternative.
+pub trait HeapGrowingPolicy {
+    /// Name used when tracing limit decisions.
+    fn name(&self) -> &'static str;
+
+    /// Returns the new allocation limit. The heap does not bound it
+    /// further, except by `max_old_generation_size`.
+    fn allocation_limit(&mut self, input: &HeapGrowingInput) -> usize;
+}
+
 pub trait MemoryControllerTrait {
     const K_NAME: &'static str;
     const K_MIN_GROWING_FACTOR: f64;
@@ -57,52 +110,40 @@ pub trait MemoryControllerTrait {
     const K_TARGET_MUTATOR_UTILIZATION: f64;
 }
 
+/// Computes allocation limits after a full garbage collection. The limit
+/// grows the heap by a factor that keeps the share of time spent in the
+/// collector at `1 - K_TARGET_MUTATOR_UTILIZATION`, given the speeds of
+/// the collector and the mutator.
 pub struct MemoryController<Trait: MemoryControllerTrait> {
     _trait: PhantomData<Trait>,
 }
 
 impl<Trait: MemoryControllerTrait> MemoryController<Trait> {
-    /// Calculates the growing factor for the heap.
+    pub fn new() -> Self {
+        MemoryController { _trait: PhantomData }
+    }
+
+    /// Calculates the growing factor for the heap. Speeds are in bytes per
+    /// millisecond; without a collector speed the maximum factor is used.
     pub fn growing_factor(
-        _heap: &(), //Heap
         max_heap_size: usize,
         gc_speed: Option<f64>,
         mutator_speed: f64,
-        growing_mode: i32, //Heap::HeapGrowingMode,
+        growing_mode: HeapGrowingMode,
     ) -> f64 {
         let max_factor = Self::max_growing_factor(max_heap_size);
-        let mut factor = Self::dynamic_growing_factor(gc_speed, mutator_speed, max_factor);
+        let factor = Self::dynamic_growing_factor(gc_speed, mutator_speed, max_factor);
         match growing_mode {
-            0 => { //Heap::HeapGrowingMode::kConservative =>
-                factor = min(factor, Trait::K_CONSERVATIVE_GROWING_FACTOR);
+            HeapGrowingMode::kConservative | HeapGrowingMode::kSlow => {
+                factor.min(Trait::K_CONSERVATIVE_GROWING_FACTOR)
             }
-            1 => { //Heap::HeapGrowingMode::kSlow =>
-                factor = min(factor, Trait::K_CONSERVATIVE_GROWING_FACTOR);
-            }
-            2 => { //Heap::HeapGrowingMode::kMinimal =>
-                factor = Trait::K_MIN_GROWING_FACTOR;
-            }
-            3 => {} //Heap::HeapGrowingMode::kDefault => {}
-            _ => {}
+            HeapGrowingMode::kMinimal => Trait::K_MIN_GROWING_FACTOR,
+            HeapGrowingMode::kDefault => factor,
         }
-
-        // if v8_flags::heap_growing_percent > 0.0 {
-        //     factor = 1.0 + v8_flags::heap_growing_percent / 100.0;
-        // }
-
-        // if v8_flags::trace_gc_verbose {
-        //     //Isolate::from_heap(heap).print_with_timestamp(
-        //     //    format!("[{}] factor {:.1} based on mu={:.3}, speed_ratio={:.f} (gc={:.f}, mutator={:.f})\n",
-        //     //            Trait::K_NAME, factor, Trait::K_TARGET_MUTATOR_UTILIZATION,
-        //     //            gc_speed.unwrap_or(0.0) / mutator_speed, gc_speed.unwrap_or(0.0),
-        //     //            mutator_speed).as_str()
-        //     //);
-        // }
-        factor
     }
 
     /// Calculates the maximum growing factor based on the maximum heap size.
-    fn max_growing_factor(max_heap_size: usize) -> f64 {
+    pub fn max_growing_factor(max_heap_size: usize) -> f64 {
         const K_MIN_SMALL_FACTOR: f64 = 1.3;
         const K_MAX_SMALL_FACTOR: f64 = 2.0;
         const K_HIGH_FACTOR: f64 = 4.0;
@@ -113,74 +154,96 @@ impl<Trait: MemoryControllerTrait> MemoryController<Trait> {
             return K_HIGH_FACTOR;
         }
 
-        let max_size = max(max_heap_size, Trait::K_MIN_SIZE);
+        let max_size = max_heap_size.max(Trait::K_MIN_SIZE);
 
         // On smaller devices we linearly scale the factor: C+(D-C)*(X-A)/(B-A)
-        let factor = K_MIN_SMALL_FACTOR
-            + (K_MAX_SMALL_FACTOR - K_MIN_SMALL_FACTOR)
-                * ((max_size - Trait::K_MIN_SIZE) as f64)
-                / ((Trait::K_MAX_SIZE - Trait::K_MIN_SIZE) as f64);
-        factor
+        K_MIN_SMALL_FACTOR
+            + (K_MAX_SMALL_FACTOR - K_MIN_SMALL_FACTOR) * ((max_size - Trait::K_MIN_SIZE) as f64)
+                / ((Trait::K_MAX_SIZE - Trait::K_MIN_SIZE) as f64)
     }
 
-    /// Calculates the dynamic growing factor based on GC speed and mutator speed.
-    fn dynamic_growing_factor(
-        gc_speed: Option<f64>,
-        mutator_speed: f64,
-        max_factor: f64,
-    ) -> f64 {
-        if gc_speed.is_none() || mutator_speed == 0.0 {
+    /// Calculates the dynamic growing factor based on GC speed and mutator
+    /// speed.
+    ///
+    /// The factor F is chosen so that the mutator utilization MU, i.e. the
+    /// share of time not spent in the collector, reaches the target. With
+    /// R = gc_speed / mutator_speed this gives F = R * (1 - MU) /
+    /// (R * (1 - MU) - MU).
+    pub fn dynamic_growing_factor(gc_speed: Option<f64>, mutator_speed: f64, max_factor: f64) -> f64 {
+        let Some(gc_speed) = gc_speed else {
+            return max_factor;
+        };
+        if gc_speed == 0.0 || mutator_speed == 0.0 {
             return max_factor;
         }
 
-        let speed_ratio = gc_speed.unwrap() / mutator_speed;
+        let speed_ratio = gc_speed / mutator_speed;
 
         let a = speed_ratio * (1.0 - Trait::K_TARGET_MUTATOR_UTILIZATION);
-        let b = speed_ratio * (1.0 - Trait::K_TARGET_MUTATOR_UTILIZATION)
-            - Trait::K_TARGET_MUTATOR_UTILIZATION;
+        let b = speed_ratio * (1.0 - Trait::K_TARGET_MUTATOR_UTILIZATION) - Trait::K_TARGET_MUTATOR_UTILIZATION;
 
         // The factor is a / b, but we need to check for small b first.
-        let factor = if a < b * max_factor {
-            a / b
-        } else {
-            max_factor
-        };
-        max(factor, Trait::K_MIN_GROWING_FACTOR)
+        let factor = if a < b * max_factor { a / b } else { max_factor };
+        factor.max(Trait::K_MIN_GROWING_FACTOR)
     }
 
-    fn minimum_allocation_limit_growing_step(growing_mode: i32) -> usize { //Heap::HeapGrowingMode
-      const K_REGULAR_ALLOCATION_LIMIT_GROWING_STEP: usize = 8;
-      const K_LOW_MEMORY_ALLOCATION_LIMIT_GROWING_STEP: usize = 2;
-      let limit = if 4096 > MB { 4096 } else { MB }; //PageMetadata::kPageSize
-
-      return limit * (if growing_mode == 0 { //Heap::HeapGrowingMode::kConservative
-                          K_LOW_MEMORY_ALLOCATION_LIMIT_GROWING_STEP
-                      } else {
-                          K_REGULAR_ALLOCATION_LIMIT_GROWING_STEP
-                      });
+    fn minimum_allocation_limit_growing_step(growing_mode: HeapGrowingMode) -> usize {
+        const K_REGULAR_ALLOCATION_LIMIT_GROWING_STEP: usize = 8;
+        const K_LOW_MEMORY_ALLOCATION_LIMIT_GROWING_STEP: usize = 2;
+        let limit = K_PAGE_SIZE.max(MB);
+        limit
+            * if growing_mode == HeapGrowingMode::kConservative {
+                K_LOW_MEMORY_ALLOCATION_LIMIT_GROWING_STEP
+            } else {
+                K_REGULAR_ALLOCATION_LIMIT_GROWING_STEP
+            }
     }
 
-    fn bound_allocation_limit(
-        _heap: &(), //Heap,
+    /// Bounds the proposed `limit` for a heap of `current_size` bytes: the
+    /// limit grows at least by a minimum step plus the young generation,
+    /// which may be promoted at once, and at most halfway to `max_size`.
+    pub fn bound_allocation_limit(
         current_size: usize,
         limit: u64,
         min_size: usize,
         max_size: usize,
         new_space_capacity: usize,
-        growing_mode: i32, //Heap::HeapGrowingMode,
+        growing_mode: HeapGrowingMode,
     ) -> usize {
-        let mut limit = max(limit, (current_size as u64) + Self::minimum_allocation_limit_growing_step(growing_mode) as u64) + new_space_capacity as u64;
-        let halfway_to_the_max = ((current_size as u64) + max_size as u64) / 2;
-        let limit_or_halfway = min(limit, halfway_to_the_max);
-        let result = max(limit_or_halfway, min_size as u64) as usize;
-
-        //if v8_flags::trace_gc_verbose {
-        //    //Isolate::from_heap(heap).print_with_timestamp(
-        //    //    format!("[{}] Limit: old size: {} KB, new limit: {} KB\n", Trait::K_NAME, current_size / KB, result / KB).as_str()
-        //    //);
-        //}
-
-        result
+        let limit = limit.max(current_size as u64 + Self::minimum_allocation_limit_growing_step(growing_mode) as u64)
+            + new_space_capacity as u64;
+        let halfway_to_the_max = (current_size as u64 + max_size as u64) / 2;
+        let limit_or_halfway = limit.min(halfway_to_the_max);
+        limit_or_halfway.max(min_size as u64) as usize
+    }
+}
+
+impl<Trait: MemoryControllerTrait> Default for MemoryController<Trait> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Trait: MemoryControllerTrait> HeapGrowingPolicy for MemoryController<Trait> {
+    fn name(&self) -> &'static str {
+        Trait::K_NAME
+    }
+
+    fn allocation_limit(&mut self, input: &HeapGrowingInput) -> usize {
+        let factor = Self::growing_factor(
+            input.max_old_generation_size,
+            input.gc_speed,
+            input.mutator_speed(),
+            input.growing_mode,
+        );
+        Self::bound_allocation_limit(
+            input.old_generation_size,
+            (input.old_generation_size as f64 * factor) as u64,
+            input.min_old_generation_size,
+            input.max_old_generation_size,
+            input.new_space_capacity,
+            input.growing_mode,
+        )
     }
 }
 
@@ -206,4 +269,89 @@ impl MemoryControllerTrait for GlobalMemoryTrait {
     const K_MIN_SIZE: usize = 16 * MB;
     const K_MAX_SIZE: usize = 2048 * MB;
     const K_TARGET_MUTATOR_UTILIZATION: f64 = 0.92;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::heap::heap::{Heap, HeapOptions};
+    use crate::heap::heap_test_utils::{allocate_retained, full_gc, heap_options};
+    use crate::heap::memory_balancer::MemoryBalancer;
+
+    fn new_heap(max_old_generation_size: usize) -> Heap {
+        Heap::new(HeapOptions {
+            max_old_generation_size,
+            initial_old_generation_size: 4 * MB,
+            ..heap_options(false)
+        })
+    }
+
+    #[test]
+    fn heap_growing_policy_decides_allocation_limit() {
+        for (policy, name) in [
+            (None, "V8Heap"),
+            (Some(Box::new(MemoryBalancer::default()) as Box<dyn HeapGrowingPolicy>), "MemoryBalancer"),
+        ] {
+            let heap = new_heap(256 * MB);
+            if let Some(policy) = policy {
+                heap.set_heap_growing_policy(policy);
+            }
+            allocate_retained(&heap, 8 * MB);
+            full_gc(&heap);
+
+            let decision = heap.tracer().heap_limit_decisions().pop().unwrap();
+            assert_eq!(decision.kind, HeapLimitKind::kAllocationLimit);
+            assert_eq!(decision.source, name);
+            assert_eq!(decision.limit, heap.old_generation_allocation_limit());
+            assert_eq!(decision.old_generation_size, heap.old_generation_size_of_objects());
+            assert!(decision.limit >= decision.old_generation_size + 2 * MB);
+            assert!(decision.allocation_rate.unwrap() > 0.0);
+        }
+    }
+
+    #[test]
+    fn near_heap_limit_callback_raises_max_size() {
+        let heap = new_heap(8 * MB);
+        let invocations = Rc::new(Cell::new(0));
+        let handle = heap.add_near_heap_limit_callback(Box::new({
+            let invocations = Rc::clone(&invocations);
+            move |current_limit, initial_limit| {
+                assert_eq!(initial_limit, 8 * MB);
+                invocations.set(invocations.get() + 1);
+                current_limit + 4 * MB
+            }
+        }));
+
+        let list = allocate_retained(&heap, 12 * MB);
+
+        assert!(invocations.get() >= 1);
+        assert_eq!(heap.max_old_generation_size(), 8 * MB + invocations.get() * 4 * MB);
+        let decisions = heap.tracer().heap_limit_decisions();
+        let raised: Vec<_> = decisions
+            .iter()
+            .filter(|decision| decision.kind == HeapLimitKind::kMaxOldGenerationSize)
+            .collect();
+        assert_eq!(raised.len(), invocations.get());
+        assert_eq!(raised[0].source, "NearHeapLimitCallback");
+        assert_eq!((raised[0].previous_limit, raised[0].limit), (8 * MB, 12 * MB));
+
+        // Incremental marking may have marked the list already.
+        heap.dispose_root(list);
+        for _ in 0..2 {
+            full_gc(&heap);
+        }
+        heap.remove_near_heap_limit_callback(handle, 8 * MB);
+        assert_eq!(heap.max_old_generation_size(), 8 * MB);
+        assert_eq!(heap.tracer().heap_limit_decisions().pop().unwrap().source, "RestoreHeapLimit");
+    }
+
+    #[test]
+    #[should_panic(expected = "reached heap limit")]
+    fn heap_runs_out_of_memory_without_callback() {
+        let heap = new_heap(8 * MB);
+        allocate_retained(&heap, 12 * MB);
+    }
+}
@@ -2,174 +2,575 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// Clients of this interface shouldn't depend on lots of heap internals.
-// Do not include anything from src/heap here!
-
-// src/base/macros.h is implicit through attribute macros
-// src/common/globals.h is implicit through type definitions and constants
-// src/objects/objects.h
-// src/objects/tagged.h
-
-pub mod heap_layout {
-    use std::marker::PhantomData;
-
-    // Assume Tagged<T> is a pointer type with optional tag bits
-    // Here's a simplified representation, adjust as needed
-    #[derive(Copy, Clone, Debug)]
-    pub struct Tagged<T> {
-        ptr: *mut T,
-        _phantom: PhantomData<T>,
-    }
-
-    impl<T> Tagged<T> {
-        pub fn new(ptr: *mut T) -> Self {
-            Tagged {
-                ptr,
-                _phantom: PhantomData,
-            }
-        }
+//! Layout of objects on the managed heap and of the tagged values referring
+//! to them.
+//!
+//! Every object starts with its map word. All objects but one-word fillers
+//! store their length in the following word, and their body follows:
+//!
+//! ```text
+//! +----------+--------+------------------------+
+//! | map word | length | length * kTaggedSize   |
+//! +----------+--------+------------------------+
+//! ```
+//!
+//! The lengths of byte arrays, strings and the other untagged objects but
+//! free space count bytes instead; their bodies are padded to the object
+//! alignment. The map determines how the body is interpreted, see
+//! `Map::body_kind()`.
+//! During garbage collections the map word of an evacuated object holds
+//! its forwarding address instead.
 
-        pub fn raw_ptr(&self) -> *mut T {
-            self.ptr
-        }
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::heap::memory_chunk::MemoryChunk;
+
+pub type Address = usize;
+
+pub const K_NULL_ADDRESS: Address = 0;
+pub const K_TAGGED_SIZE: usize = 8;
+pub const K_OBJECT_ALIGNMENT: usize = K_TAGGED_SIZE;
+
+pub const K_SMI_TAG_MASK: usize = 1;
+pub const K_HEAP_OBJECT_TAG: usize = 1;
+pub const K_WEAK_HEAP_OBJECT_TAG: usize = 3;
+pub const K_HEAP_OBJECT_TAG_MASK: usize = 3;
+/// Value of cleared weak references, and of empty ephemeron table entries.
+pub const K_CLEARED_WEAK_HEAP_OBJECT: usize = 3;
+
+/// Size of the map word and length shared by all objects but one-word
+/// fillers.
+pub const K_HEADER_SIZE: usize = 2 * K_TAGGED_SIZE;
+
+/// A tagged value: a Smi, a strong or weak reference to a heap object, or a
+/// cleared weak reference.
+#[repr(transparent)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tagged(usize);
+
+impl Tagged {
+    pub const ZERO: Tagged = Tagged(0);
+    pub const CLEARED: Tagged = Tagged(K_CLEARED_WEAK_HEAP_OBJECT);
+
+    pub const fn from_raw(raw: usize) -> Tagged {
+        Tagged(raw)
     }
 
-    // Assume HeapObject is a trait or struct; adjust as needed
-    pub trait HeapObject {}
+    pub const fn raw(self) -> usize {
+        self.0
+    }
 
-    // Assume Object is a trait or struct; adjust as needed
-    pub trait Object {}
+    pub const fn from_smi(value: isize) -> Tagged {
+        Tagged((value << 1) as usize)
+    }
 
-    // Assume MaybeObject is a trait or struct; adjust as needed
-    pub trait MaybeObject {}
+    pub fn strong(object: HeapObject) -> Tagged {
+        Tagged(object.address() | K_HEAP_OBJECT_TAG)
+    }
 
-    // Assume MemoryChunk is a struct; adjust as needed
-    pub struct MemoryChunk {}
+    pub fn weak(object: HeapObject) -> Tagged {
+        Tagged(object.address() | K_WEAK_HEAP_OBJECT_TAG)
+    }
 
-    // Assume HeapObjectLayout is a struct; adjust as needed
-    pub struct HeapObjectLayout {}
+    pub const fn is_smi(self) -> bool {
+        self.0 & K_SMI_TAG_MASK == 0
+    }
 
-    // Assume PtrComprCageBase is a struct; adjust as needed
-    pub struct PtrComprCageBase {}
+    pub fn smi_value(self) -> Option<isize> {
+        self.is_smi().then_some(self.0 as isize >> 1)
+    }
 
-    // Assume MapWord is a struct; adjust as needed
-    pub struct MapWord {}
+    pub const fn is_strong(self) -> bool {
+        self.0 & K_HEAP_OBJECT_TAG_MASK == K_HEAP_OBJECT_TAG
+    }
 
-    /// Checks for heap layouts. The checks generally use Heap infrastructure (heap,
-    /// space, page, mark bits, etc) and do not rely on instance types.
-    pub struct HeapLayout {}
+    pub const fn is_weak(self) -> bool {
+        self.0 & K_HEAP_OBJECT_TAG_MASK == K_WEAK_HEAP_OBJECT_TAG && !self.is_cleared()
+    }
 
-    impl HeapLayout {
-        /// Returns whether `object` is part of a read-only space.
-        #[inline]
-        pub fn in_read_only_space(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
-        }
+    pub const fn is_cleared(self) -> bool {
+        self.0 == K_CLEARED_WEAK_HEAP_OBJECT
+    }
 
-        #[inline]
-        pub fn in_young_generation_object(object: Tagged<dyn Object>) -> bool {
-            // Placeholder implementation
-            false
-        }
+    /// Returns the referenced object of strong and weak references.
+    pub fn get_heap_object(self) -> Option<HeapObject> {
+        (self.is_strong() || self.is_weak()).then_some(HeapObject(self.0 & !K_HEAP_OBJECT_TAG_MASK))
+    }
 
-        #[inline]
-        pub fn in_young_generation_heap_object(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
+    /// Returns a reference to `object` with the strength of this reference.
+    pub fn with_object(self, object: HeapObject) -> Tagged {
+        if self.is_weak() {
+            Tagged::weak(object)
+        } else {
+            Tagged::strong(object)
         }
+    }
+}
 
-        #[inline]
-        pub fn in_young_generation_maybe_object(object: Tagged<dyn MaybeObject>) -> bool {
-            // Placeholder implementation
-            false
+impl fmt::Debug for Tagged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(value) = self.smi_value() {
+            write!(f, "Smi({value})")
+        } else if self.is_cleared() {
+            write!(f, "Cleared")
+        } else if self.is_weak() {
+            write!(f, "Weak({:#x})", self.0 & !K_HEAP_OBJECT_TAG_MASK)
+        } else {
+            write!(f, "Strong({:#x})", self.0 & !K_HEAP_OBJECT_TAG_MASK)
         }
+    }
+}
 
-        #[inline]
-        pub fn in_young_generation_layout(object: &HeapObjectLayout) -> bool {
-            // Placeholder implementation
-            false
-        }
+/// A slot holding a tagged value.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct ObjectSlot(Address);
 
-        #[inline]
-        pub fn in_young_generation_chunk(chunk: &MemoryChunk, object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
-        }
+impl ObjectSlot {
+    pub const fn new(address: Address) -> Self {
+        ObjectSlot(address)
+    }
 
-        /// Returns whether `object` is in a writable shared space. The is agnostic to
-        /// how the shared space itself is managed.
-        #[inline]
-        pub fn in_writable_shared_space(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
-        }
+    pub const fn address(self) -> Address {
+        self.0
+    }
 
-        /// Returns whether `object` is in a shared space.
-        #[inline]
-        pub fn in_any_shared_space(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
-        }
+    pub const fn offset(self, slots: usize) -> ObjectSlot {
+        ObjectSlot(self.0 + slots * K_TAGGED_SIZE)
+    }
 
-        /// Returns whether `object` is in code space. Note that there's various kinds
-        /// of different code spaces (regular, external, large object) which are all
-        /// covered by this check.
-        #[inline]
-        pub fn in_code_space(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
-        }
+    /// Loads the slot. Pairs with `store()` so that concurrent markers see
+    /// initialized objects through freshly stored references.
+    #[inline]
+    pub fn load(self) -> Tagged {
+        Tagged(self.cell().load(Ordering::Acquire))
+    }
+
+    #[inline]
+    pub fn store(self, value: Tagged) {
+        self.cell().store(value.0, Ordering::Release);
+    }
+
+    #[inline]
+    fn cell(self) -> &'static AtomicUsize {
+        debug_assert_eq!(self.0 % K_TAGGED_SIZE, 0);
+        // SAFETY: slots point into live objects or root lists; all accesses
+        // go through atomics so concurrent collector threads are fine.
+        unsafe { &*(self.0 as *const AtomicUsize) }
+    }
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum InstanceType {
+    FREE_SPACE_TYPE,
+    ONE_POINTER_FILLER_TYPE,
+    FIXED_ARRAY_TYPE,
+    WEAK_FIXED_ARRAY_TYPE,
+    BYTE_ARRAY_TYPE,
+    EPHEMERON_HASH_TABLE_TYPE,
+    NATIVE_CONTEXT_TYPE,
+    STRING_TYPE,
+    INTERNALIZED_STRING_TYPE,
+    ODDBALL_TYPE,
+    SCRIPT_TYPE,
+    SHARED_FUNCTION_INFO_TYPE,
+    BYTECODE_ARRAY_TYPE,
+    SCOPE_INFO_TYPE,
+    FEEDBACK_METADATA_TYPE,
+    JS_FUNCTION_TYPE,
+    JS_GLOBAL_OBJECT_TYPE,
+    JS_GLOBAL_PROXY_TYPE,
+    JS_API_OBJECT_TYPE,
+}
+
+impl InstanceType {
+    /// Returns the instance type encoded as `instance_type as u16`, e.g. by
+    /// the snapshot serializer.
+    pub fn from_raw(raw: u16) -> Option<InstanceType> {
+        ALL_MAPS
+            .iter()
+            .map(|map| map.instance_type())
+            .find(|&instance_type| instance_type as u16 == raw)
+    }
+}
 
-        /// Returns whether `object` is allocated in trusted space. See
-        /// src/sandbox/GLOSSARY.md for details.
-        #[inline]
-        pub fn in_trusted_space(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
+/// How the body of an object is visited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyKind {
+    /// The body holds no tagged values.
+    Data,
+    /// The body holds Smis and strong references.
+    Strong,
+    /// The body holds Smis and strong or weak references.
+    MaybeWeak,
+    /// The body holds key/value pairs; a value is only reachable through
+    /// its table while its key is reachable.
+    Ephemeron,
+}
+
+/// Describes the layout of objects. Maps live outside of the managed heap
+/// and are never moved or collected.
+#[repr(align(8))]
+#[derive(Debug)]
+pub struct Map {
+    instance_type: InstanceType,
+    name: &'static str,
+}
+
+impl Map {
+    pub const fn new(instance_type: InstanceType, name: &'static str) -> Self {
+        Map { instance_type, name }
+    }
+
+    pub fn instance_type(&self) -> InstanceType {
+        self.instance_type
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn body_kind(&self) -> BodyKind {
+        match self.instance_type {
+            InstanceType::FREE_SPACE_TYPE
+            | InstanceType::ONE_POINTER_FILLER_TYPE
+            | InstanceType::BYTE_ARRAY_TYPE
+            | InstanceType::STRING_TYPE
+            | InstanceType::INTERNALIZED_STRING_TYPE
+            | InstanceType::SCOPE_INFO_TYPE
+            | InstanceType::FEEDBACK_METADATA_TYPE => BodyKind::Data,
+            InstanceType::FIXED_ARRAY_TYPE
+            | InstanceType::NATIVE_CONTEXT_TYPE
+            | InstanceType::ODDBALL_TYPE
+            | InstanceType::SCRIPT_TYPE
+            | InstanceType::SHARED_FUNCTION_INFO_TYPE
+            | InstanceType::BYTECODE_ARRAY_TYPE
+            | InstanceType::JS_FUNCTION_TYPE
+            | InstanceType::JS_GLOBAL_OBJECT_TYPE
+            | InstanceType::JS_GLOBAL_PROXY_TYPE
+            | InstanceType::JS_API_OBJECT_TYPE => BodyKind::Strong,
+            InstanceType::WEAK_FIXED_ARRAY_TYPE => BodyKind::MaybeWeak,
+            InstanceType::EPHEMERON_HASH_TABLE_TYPE => BodyKind::Ephemeron,
         }
+    }
+
+    pub fn is_filler(&self) -> bool {
+        matches!(
+            self.instance_type,
+            InstanceType::FREE_SPACE_TYPE | InstanceType::ONE_POINTER_FILLER_TYPE
+        )
+    }
+
+    /// Whether the length of objects with this map counts bytes rather
+    /// than words: true for all untagged objects but fillers.
+    pub fn has_byte_length(&self) -> bool {
+        self.body_kind() == BodyKind::Data && !self.is_filler()
+    }
 
-        /// Returns whether `object` is allocated on a black page (during
-        /// incremental/concurrent marking).
-        #[inline]
-        pub fn in_black_allocated_page(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
+    /// Size of an object with this map and the given length.
+    pub fn object_size(&self, length: usize) -> usize {
+        if self.instance_type == InstanceType::ONE_POINTER_FILLER_TYPE {
+            K_TAGGED_SIZE
+        } else if self.has_byte_length() {
+            K_HEADER_SIZE + length.next_multiple_of(K_OBJECT_ALIGNMENT)
+        } else {
+            K_HEADER_SIZE + length * K_TAGGED_SIZE
         }
+    }
+
+    /// Returns the map of objects of `instance_type`.
+    pub fn for_instance_type(instance_type: InstanceType) -> &'static Map {
+        ALL_MAPS
+            .iter()
+            .find(|map| map.instance_type() == instance_type)
+            .expect("every instance type has a map")
+    }
+
+    fn address(&'static self) -> Address {
+        self as *const Map as Address
+    }
+}
+
+pub static FREE_SPACE_MAP: Map = Map::new(InstanceType::FREE_SPACE_TYPE, "FreeSpace");
+pub static ONE_POINTER_FILLER_MAP: Map = Map::new(InstanceType::ONE_POINTER_FILLER_TYPE, "OnePointerFiller");
+pub static FIXED_ARRAY_MAP: Map = Map::new(InstanceType::FIXED_ARRAY_TYPE, "FixedArray");
+pub static WEAK_FIXED_ARRAY_MAP: Map = Map::new(InstanceType::WEAK_FIXED_ARRAY_TYPE, "WeakFixedArray");
+pub static BYTE_ARRAY_MAP: Map = Map::new(InstanceType::BYTE_ARRAY_TYPE, "ByteArray");
+pub static EPHEMERON_HASH_TABLE_MAP: Map =
+    Map::new(InstanceType::EPHEMERON_HASH_TABLE_TYPE, "EphemeronHashTable");
+pub static NATIVE_CONTEXT_MAP: Map = Map::new(InstanceType::NATIVE_CONTEXT_TYPE, "NativeContext");
+pub static STRING_MAP: Map = Map::new(InstanceType::STRING_TYPE, "String");
+pub static INTERNALIZED_STRING_MAP: Map = Map::new(InstanceType::INTERNALIZED_STRING_TYPE, "InternalizedString");
+pub static ODDBALL_MAP: Map = Map::new(InstanceType::ODDBALL_TYPE, "Oddball");
+pub static SCRIPT_MAP: Map = Map::new(InstanceType::SCRIPT_TYPE, "Script");
+pub static SHARED_FUNCTION_INFO_MAP: Map =
+    Map::new(InstanceType::SHARED_FUNCTION_INFO_TYPE, "SharedFunctionInfo");
+pub static BYTECODE_ARRAY_MAP: Map = Map::new(InstanceType::BYTECODE_ARRAY_TYPE, "BytecodeArray");
+pub static SCOPE_INFO_MAP: Map = Map::new(InstanceType::SCOPE_INFO_TYPE, "ScopeInfo");
+pub static FEEDBACK_METADATA_MAP: Map = Map::new(InstanceType::FEEDBACK_METADATA_TYPE, "FeedbackMetadata");
+pub static JS_FUNCTION_MAP: Map = Map::new(InstanceType::JS_FUNCTION_TYPE, "JSFunction");
+pub static JS_GLOBAL_OBJECT_MAP: Map = Map::new(InstanceType::JS_GLOBAL_OBJECT_TYPE, "JSGlobalObject");
+pub static JS_GLOBAL_PROXY_MAP: Map = Map::new(InstanceType::JS_GLOBAL_PROXY_TYPE, "JSGlobalProxy");
+/// Wrapper objects of an embedder. The first body slot holds the wrapped
+/// object of the attached `CppHeap`, see `Heap::set_wrappable()`.
+pub static JS_API_OBJECT_MAP: Map = Map::new(InstanceType::JS_API_OBJECT_TYPE, "JSApiObject");
+
+/// Body slot of API objects holding the wrappable. The wrappable is an
+/// 8-byte aligned pointer and thus reads as a Smi, so the V8 collectors
+/// neither follow nor update it.
+pub const K_CPP_HEAP_WRAPPABLE_INDEX: usize = 0;
 
-        /// Returns whether `object` is allocated on a page which is owned by some Heap
-        /// instance. This is equivalent to !InReadOnlySpace except during
-        /// serialization.
-        #[inline]
-        pub fn is_owned_by_any_heap(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
+static ALL_MAPS: [&Map; 19] = [
+    &FREE_SPACE_MAP,
+    &ONE_POINTER_FILLER_MAP,
+    &FIXED_ARRAY_MAP,
+    &WEAK_FIXED_ARRAY_MAP,
+    &BYTE_ARRAY_MAP,
+    &EPHEMERON_HASH_TABLE_MAP,
+    &NATIVE_CONTEXT_MAP,
+    &STRING_MAP,
+    &INTERNALIZED_STRING_MAP,
+    &ODDBALL_MAP,
+    &SCRIPT_MAP,
+    &SHARED_FUNCTION_INFO_MAP,
+    &BYTECODE_ARRAY_MAP,
+    &SCOPE_INFO_MAP,
+    &FEEDBACK_METADATA_MAP,
+    &JS_FUNCTION_MAP,
+    &JS_GLOBAL_OBJECT_MAP,
+    &JS_GLOBAL_PROXY_MAP,
+    &JS_API_OBJECT_MAP,
+];
+
+/// Contents of the first word of an object.
+#[derive(Clone, Copy, Debug)]
+pub enum MapWord {
+    Map(&'static Map),
+    /// The object was evacuated to the given location.
+    Forwarding(HeapObject),
+}
+
+impl MapWord {
+    fn encode(self) -> usize {
+        match self {
+            MapWord::Map(map) => map.address() | K_HEAP_OBJECT_TAG,
+            MapWord::Forwarding(target) => target.address(),
         }
+    }
 
-        /// Returns whether the map word of `object` is a self forwarding address.
-        /// This represents pinned objects and live large objects in Scavenger.
-        pub fn is_self_forwarded_tagged(object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
+    fn decode(raw: usize) -> MapWord {
+        if raw & K_HEAP_OBJECT_TAG_MASK == K_HEAP_OBJECT_TAG {
+            // SAFETY: map words with the heap object tag are only ever
+            // written by `encode()` from a `&'static Map`.
+            MapWord::Map(unsafe { &*((raw & !K_HEAP_OBJECT_TAG_MASK) as *const Map) })
+        } else {
+            debug_assert!(raw != 0, "map word of an uninitialized object");
+            MapWord::Forwarding(HeapObject(raw))
         }
+    }
 
-        pub fn is_self_forwarded_tagged_cage(object: Tagged<dyn HeapObject>, cage_base: PtrComprCageBase) -> bool {
-            // Placeholder implementation
-            false
+    /// Decodes a map word that may be corrupted. Returns `None` unless it
+    /// refers to one of the known maps or is a plausible forwarding
+    /// address; used by the heap verifier.
+    pub fn try_decode(raw: usize) -> Option<MapWord> {
+        if raw & K_HEAP_OBJECT_TAG_MASK == K_HEAP_OBJECT_TAG {
+            let address = raw & !K_HEAP_OBJECT_TAG_MASK;
+            ALL_MAPS
+                .iter()
+                .find(|map| map.address() == address)
+                .map(|&map| MapWord::Map(map))
+        } else if raw != 0 && raw & K_HEAP_OBJECT_TAG_MASK == 0 {
+            Some(MapWord::Forwarding(HeapObject(raw)))
+        } else {
+            None
         }
+    }
+}
+
+/// An object on the managed heap.
+///
+/// This is a plain address: objects may move during garbage collections, so
+/// values of this type must not be held across allocations. Keep objects
+/// alive and up to date through roots (`Heap::create_root`) instead.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HeapObject(Address);
 
-        pub fn is_self_forwarded_tagged_mapword(object: Tagged<dyn HeapObject>, map_word: MapWord) -> bool {
-            // Placeholder implementation
-            false
+impl HeapObject {
+    /// # Safety
+    ///
+    /// `address` must be the start of an initialized object.
+    pub const unsafe fn from_address(address: Address) -> Self {
+        HeapObject(address)
+    }
+
+    pub const fn address(self) -> Address {
+        self.0
+    }
+
+    #[inline]
+    fn map_word_cell(self) -> &'static AtomicUsize {
+        // SAFETY: see `ObjectSlot::cell()`.
+        unsafe { &*(self.0 as *const AtomicUsize) }
+    }
+
+    #[inline]
+    pub fn map_word(self) -> MapWord {
+        MapWord::decode(self.map_word_cell().load(Ordering::Acquire))
+    }
+
+    /// Returns the undecoded first word of the object.
+    #[inline]
+    pub fn raw_map_word(self) -> usize {
+        self.map_word_cell().load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn set_map_word(self, map_word: MapWord) {
+        self.map_word_cell().store(map_word.encode(), Ordering::Release);
+    }
+
+    /// Installs a forwarding address unless another thread evacuated the
+    /// object first, in which case that thread's target is returned.
+    pub fn try_set_forwarding_address(self, map: &'static Map, target: HeapObject) -> Result<(), HeapObject> {
+        match self.map_word_cell().compare_exchange(
+            MapWord::Map(map).encode(),
+            MapWord::Forwarding(target).encode(),
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => Ok(()),
+            Err(raw) => match MapWord::decode(raw) {
+                MapWord::Forwarding(winner) => Err(winner),
+                MapWord::Map(_) => unreachable!("map of {self:?} changed during evacuation"),
+            },
         }
+    }
 
-        fn in_young_generation_for_sticky_markbits(chunk: &MemoryChunk, object: Tagged<dyn HeapObject>) -> bool {
-            // Placeholder implementation
-            false
+    /// Returns the map. Must not be called on evacuated objects.
+    #[inline]
+    pub fn map(self) -> &'static Map {
+        match self.map_word() {
+            MapWord::Map(map) => map,
+            MapWord::Forwarding(target) => panic!("{self:?} was evacuated to {target:?}"),
         }
+    }
+
+    /// Returns the number of body words, or of body bytes for objects with
+    /// a byte length.
+    #[inline]
+    pub fn length(self) -> usize {
+        self.length_for(self.map())
+    }
 
-        fn check_young_generation_consistency(chunk: &MemoryChunk) {
-            // Placeholder implementation
+    #[inline]
+    pub fn length_for(self, map: &Map) -> usize {
+        if map.instance_type() == InstanceType::ONE_POINTER_FILLER_TYPE {
+            0
+        } else {
+            ObjectSlot(self.0 + K_TAGGED_SIZE).load().raw()
         }
     }
-}
\ No newline at end of file
+
+    #[inline]
+    pub fn size(self) -> usize {
+        let map = self.map();
+        map.object_size(self.length_for(map))
+    }
+
+    pub fn is_filler(self) -> bool {
+        self.map().is_filler()
+    }
+
+    /// Returns the slot of body word `index`.
+    #[inline]
+    pub fn raw_field(self, index: usize) -> ObjectSlot {
+        ObjectSlot(self.0 + K_HEADER_SIZE + index * K_TAGGED_SIZE)
+    }
+
+    /// Reads tagged body word `index`.
+    pub fn get(self, index: usize) -> Tagged {
+        let map = self.map();
+        assert!(map.body_kind() != BodyKind::Data, "{} holds no tagged values", map.name());
+        assert!(index < self.length_for(map), "index {index} out of bounds");
+        self.raw_field(index).load()
+    }
+
+    /// Returns the body of an object without tagged values, excluding the
+    /// padding of objects with a byte length.
+    pub fn data(self) -> &'static mut [u8] {
+        let map = self.map();
+        assert!(map.body_kind() == BodyKind::Data, "{} holds tagged values", map.name());
+        let length = if map.has_byte_length() {
+            self.length_for(map)
+        } else {
+            self.length_for(map) * K_TAGGED_SIZE
+        };
+        // SAFETY: the body is part of the object and holds plain bytes.
+        unsafe { std::slice::from_raw_parts_mut((self.0 + K_HEADER_SIZE) as *mut u8, length) }
+    }
+
+    pub fn chunk(self) -> &'static MemoryChunk {
+        MemoryChunk::from_heap_object(self)
+    }
+}
+
+impl fmt::Debug for HeapObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HeapObject({:#x})", self.0)
+    }
+}
+
+/// Initializes an object at `address` without initializing its body.
+///
+/// # Safety
+///
+/// `address` must point to at least `map.object_size(length)` writable bytes.
+pub unsafe fn initialize_object_header(address: Address, map: &'static Map, length: usize) -> HeapObject {
+    let object = HeapObject(address);
+    object.set_map_word(MapWord::Map(map));
+    if map.instance_type() != InstanceType::ONE_POINTER_FILLER_TYPE {
+        ObjectSlot(address + K_TAGGED_SIZE).store(Tagged::from_raw(length));
+    }
+    object
+}
+
+/// Writes a filler object over `size` bytes at `address` so that the area
+/// stays iterable.
+///
+/// # Safety
+///
+/// The area must be writable and not contain live objects.
+pub unsafe fn create_filler_object_at(address: Address, size: usize) {
+    debug_assert_eq!(size % K_TAGGED_SIZE, 0);
+    match size {
+        0 => {}
+        // SAFETY: the caller guarantees that the area is writable and free.
+        K_TAGGED_SIZE => unsafe {
+            initialize_object_header(address, &ONE_POINTER_FILLER_MAP, 0);
+        },
+        // SAFETY: as above.
+        _ => unsafe {
+            initialize_object_header(address, &FREE_SPACE_MAP, (size - K_HEADER_SIZE) / K_TAGGED_SIZE);
+        },
+    }
+}
+
+/// Predicates on which part of the heap an object lives in.
+pub struct HeapLayout;
+
+impl HeapLayout {
+    #[inline]
+    pub fn in_young_generation(object: HeapObject) -> bool {
+        object.chunk().in_young_generation()
+    }
+
+    /// Returns true for references to young objects, i.e. those that a
+    /// remembered set has to record.
+    #[inline]
+    pub fn is_young_reference(value: Tagged) -> bool {
+        value
+            .get_heap_object()
+            .is_some_and(Self::in_young_generation)
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2020 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Fixtures shared by the heap tests.
+
+use crate::heap::gc_tracer::GarbageCollectionReason;
+use crate::heap::heap::{AllocationType, Heap, HeapOptions, RootHandle};
+use crate::heap::heap_layout::Tagged;
+use crate::heap::memory_chunk::AllocationSpace;
+
+/// Returns the default options with all background work of the GCs, i.e.
+/// parallel scavenging, concurrent marking and sweeping and parallel
+/// compaction, enabled or disabled as a whole.
+pub fn heap_options(concurrent: bool) -> HeapOptions {
+    HeapOptions {
+        parallel_scavenge: concurrent,
+        concurrent_marking: concurrent,
+        concurrent_sweeping: concurrent,
+        parallel_compaction: concurrent,
+        ..HeapOptions::default()
+    }
+}
+
+pub fn scavenge(heap: &Heap) {
+    heap.collect_garbage(AllocationSpace::NEW_SPACE, GarbageCollectionReason::kTesting);
+}
+
+/// Creates a heap with `heap_options(concurrent)`.
+pub fn new_heap(concurrent: bool) -> Heap {
+    Heap::new(heap_options(concurrent))
+}
+
+pub fn full_gc(heap: &Heap) {
+    heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kTesting);
+}
+
+/// Allocates a list of `length` nodes [value, next] rooted in a root.
+pub fn allocate_list(heap: &Heap, length: usize, allocation: AllocationType) -> RootHandle {
+    let root = heap.create_root(Tagged::ZERO);
+    for i in 0..length {
+        let node = heap.allocate_fixed_array(2, allocation);
+        heap.set(node, 0, Tagged::from_smi(i as isize));
+        heap.set(node, 1, heap.root(root));
+        heap.set_root(root, Tagged::strong(node));
+    }
+    root
+}
+
+/// Checks that the list of `root` still holds the values given to it by
+/// `allocate_list()`.
+pub fn verify_list(heap: &Heap, root: RootHandle, length: usize) {
+    let mut node = heap.root(root);
+    for i in (0..length).rev() {
+        let object = node.get_heap_object().unwrap();
+        assert_eq!(object.get(0), Tagged::from_smi(i as isize));
+        node = object.get(1);
+    }
+    assert_eq!(node, Tagged::ZERO);
+}
+
+/// Allocates a list of 1 KB nodes in old space holding `bytes` in total,
+/// rooted in a root.
+pub fn allocate_retained(heap: &Heap, bytes: usize) -> RootHandle {
+    let root = heap.create_root(Tagged::ZERO);
+    for _ in 0..bytes / 1024 {
+        let node = heap.allocate_fixed_array(126, AllocationType::kOld);
+        heap.set(node, 0, heap.root(root));
+        heap.set_root(root, Tagged::strong(node));
+    }
+    root
+}
@@ -2,100 +2,382 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod heap_verifier {
-    use std::fmt;
+use std::collections::HashSet;
+use std::fmt;
 
-    // Placeholder for globals.h
-    pub struct Globals {}
+use crate::heap::heap::{Heap, HeapState};
+use crate::heap::heap_layout::{Address, HeapObject, MapWord, ObjectSlot, Tagged, K_HEADER_SIZE, K_TAGGED_SIZE};
+use crate::heap::heap_visitor::{iterate_body, ObjectVisitor};
+use crate::heap::memory_chunk::{MemoryChunk, K_PAGE_ALIGNMENT_MASK};
+use crate::heap::remembered_set::{RememberedSet, OLD_TO_NEW};
 
-    // Placeholder for flags.h
-    pub mod flags {
-        pub static mut verify_heap: bool = false;
+/// A heap invariant found violated by the `HeapVerifier` or the
+/// `EvacuationVerifier`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapVerificationFailure {
+    /// The map word is neither a known map nor a forwarding address.
+    InvalidMap,
+    /// The object was evacuated, but its page still holds it outside of a
+    /// garbage collection.
+    ForwardedObject,
+    /// The object extends beyond the allocated area of its page.
+    ObjectOutOfBounds,
+    /// The slot refers outside of all pages in use, e.g. into a page
+    /// released after evacuation.
+    PointerOutsideHeap,
+    /// The slot refers into from-space or an evacuation candidate, whose
+    /// objects were moved.
+    PointerIntoEvacuatedPage,
+    /// The slot refers into a page, but not to the start of an object.
+    PointerToNonObject,
+    /// An old-to-new reference is missing from the OLD_TO_NEW remembered
+    /// set of its page.
+    MissingOldToNewSlot,
+    /// A young key of an old ephemeron table is missing from the ephemeron
+    /// remembered set.
+    MissingEphemeronKeyEntry,
+}
+
+impl HeapVerificationFailure {
+    pub fn to_str(self) -> &'static str {
+        match self {
+            HeapVerificationFailure::InvalidMap => "invalid map",
+            HeapVerificationFailure::ForwardedObject => "forwarded object outside of GC",
+            HeapVerificationFailure::ObjectOutOfBounds => "object exceeds its page",
+            HeapVerificationFailure::PointerOutsideHeap => "pointer outside of the heap",
+            HeapVerificationFailure::PointerIntoEvacuatedPage => "pointer into an evacuated page",
+            HeapVerificationFailure::PointerToNonObject => "pointer to a non-object",
+            HeapVerificationFailure::MissingOldToNewSlot => "old-to-new slot missing from the remembered set",
+            HeapVerificationFailure::MissingEphemeronKeyEntry => {
+                "young ephemeron key missing from the ephemeron remembered set"
+            }
+        }
     }
+}
 
-    // Placeholder for memory-chunk-metadata.h
-    pub struct MemoryChunkMetadata {}
+/// Describes a violated invariant: the offending object, or `None` for a
+/// root, and for invalid references the slot and the value it holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeapVerificationError {
+    pub failure: HeapVerificationFailure,
+    pub object: Option<HeapObject>,
+    pub slot: Option<ObjectSlot>,
+    pub value: Option<Tagged>,
+}
 
-    // Placeholder for read-only-heap.h
-    pub struct ReadOnlyHeap {}
+impl HeapVerificationError {
+    pub(crate) fn in_object(failure: HeapVerificationFailure, object: HeapObject) -> Self {
+        HeapVerificationError { failure, object: Some(object), slot: None, value: None }
+    }
 
-    // Placeholder for map.h
-    pub struct Map {}
+    pub(crate) fn in_slot(failure: HeapVerificationFailure, host: Option<HeapObject>, slot: ObjectSlot) -> Self {
+        HeapVerificationError { failure, object: host, slot: Some(slot), value: Some(slot.load()) }
+    }
+}
 
-    // Placeholder for Heap
-    pub struct Heap {}
+impl fmt::Display for HeapVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Heap verification failed: {}", self.failure.to_str())?;
+        match (self.object, self.slot) {
+            (Some(object), Some(slot)) => {
+                let index = (slot.address() - object.address() - K_HEADER_SIZE) / K_TAGGED_SIZE;
+                write!(f, " in object {:#x} at slot {:#x} (index {index})", object.address(), slot.address())?;
+            }
+            (Some(object), None) => write!(f, " in object {:#x}", object.address())?,
+            (None, Some(slot)) => write!(f, " in root slot {:#x}", slot.address())?,
+            (None, None) => {}
+        }
+        if let Some(value) = self.value {
+            write!(f, ", value {value:?}")?;
+        }
+        Ok(())
+    }
+}
 
-    // Placeholder for Isolate
-    pub struct Isolate {}
+impl std::error::Error for HeapVerificationError {}
 
-    pub trait HeapObject: fmt::Debug {}
+/// Page holding `address`, without touching memory that may be released.
+#[inline]
+pub(crate) fn page_address(address: Address) -> Address {
+    address & !K_PAGE_ALIGNMENT_MASK
+}
 
-    pub trait Tagged<T> {}
+/// Verifies the invariants of the whole heap, like `--verify-heap`:
+///
+/// - every object in to-space, old space and large object space has a
+///   valid map and fits into its page,
+/// - every root and every slot refers to the start of an object in a page
+///   in use, not into from-space or a released page,
+/// - every old-to-new reference is recorded in the OLD_TO_NEW remembered
+///   set, or in the ephemeron remembered set for ephemeron keys.
+///
+/// Enabled by `HeapOptions::verify_heap` after every garbage collection.
+/// Invalid references are reported with the host object and slot, which
+/// usually points straight at a missing write barrier or a stale
+/// `HeapObject` held across an allocation.
+pub struct HeapVerifier<'h> {
+    heap: &'h Heap,
+    /// Pages that may hold objects.
+    pages: HashSet<Address>,
+    from_pages: HashSet<Address>,
+    /// Start addresses of all objects other than fillers.
+    objects: HashSet<Address>,
+}
 
-    impl<T: HeapObject> Tagged<T> for T {}
-    impl Tagged<Map> for Map {}
+impl<'h> HeapVerifier<'h> {
+    /// Verifies the heap. Completes sweeping and makes new space iterable
+    /// first. Must not be called during a garbage collection.
+    pub fn verify_heap(heap: &'h Heap) -> Result<(), HeapVerificationError> {
+        assert_eq!(heap.gc_state(), HeapState::NOT_IN_GC, "heap verification during GC");
+        heap.complete_sweeping();
+        heap.free_main_thread_linear_allocation_area();
 
-    /// Interface for verifying spaces in the heap.
-    pub trait SpaceVerificationVisitor {
-        /// This method will be invoked for every object in the space.
-        fn verify_object(&mut self, object: &dyn HeapObject);
+        let mut pages = Vec::new();
+        heap.new_space().for_each_to_page(|page| pages.push(MemoryChunk::from_address(page.address())));
+        pages.extend(heap.old_space().pages());
+        pages.extend(heap.lo_space().pages());
+        let mut from_pages = HashSet::new();
+        heap.new_space().for_each_from_page(|page| {
+            from_pages.insert(page.address());
+        });
+        let mut verifier = HeapVerifier {
+            heap,
+            pages: pages.iter().map(|page| page.address()).collect(),
+            from_pages,
+            objects: HashSet::new(),
+        };
 
-        /// This method will be invoked for each page in the space before verifying an
-        /// object on it.
-        fn verify_page(&mut self, chunk: &MemoryChunkMetadata);
+        let mut objects = Vec::new();
+        for page in pages {
+            verifier.collect_objects(page, &mut objects)?;
+        }
+        for object in objects {
+            verifier.verify_object(object)?;
+        }
+        verifier.verify_roots()
+    }
 
-        /// This method will be invoked after verifying all objects on that page.
-        fn verify_page_done(&mut self, chunk: &MemoryChunkMetadata);
+    /// Walks the objects of `page` without trusting their maps.
+    fn collect_objects(&mut self, page: &MemoryChunk, objects: &mut Vec<HeapObject>) -> Result<(), HeapVerificationError> {
+        let mut current = page.area_start();
+        let end = page.high_water_mark();
+        while current < end {
+            // SAFETY: the area below the high water mark is iterable unless
+            // it is corrupted, which is what is verified here; only the
+            // page's memory is read.
+            let object = unsafe { HeapObject::from_address(current) };
+            let map = match MapWord::try_decode(object.raw_map_word()) {
+                Some(MapWord::Map(map)) => map,
+                Some(MapWord::Forwarding(_)) => {
+                    return Err(HeapVerificationError::in_object(HeapVerificationFailure::ForwardedObject, object))
+                }
+                None => return Err(HeapVerificationError::in_object(HeapVerificationFailure::InvalidMap, object)),
+            };
+            let length = object.length_for(map);
+            if length > (end - current) / K_TAGGED_SIZE || map.object_size(length) > end - current {
+                return Err(HeapVerificationError::in_object(HeapVerificationFailure::ObjectOutOfBounds, object));
+            }
+            if !map.is_filler() {
+                self.objects.insert(current);
+                objects.push(object);
+            }
+            current += map.object_size(length);
+        }
+        Ok(())
     }
 
-    pub struct HeapVerifier {}
+    fn verify_object(&self, object: HeapObject) -> Result<(), HeapVerificationError> {
+        let mut visitor = VerifyPointersVisitor {
+            verifier: self,
+            host_is_old: !object.chunk().in_young_generation(),
+            result: Ok(()),
+        };
+        iterate_body(object, object.map(), &mut visitor);
+        visitor.result
+    }
 
-    impl HeapVerifier {
-        // The following functions are conditionally compiled based on the VERIFY_HEAP flag.
-        // Since Rust does not support conditional compilation of functions within an impl block
-        // in the same way as C++ with preprocessor directives, we will leave empty functions
-        // for now and add the logic once we have concrete implementations for Heap, Map and HeapObject.
+    fn verify_roots(&self) -> Result<(), HeapVerificationError> {
+        let mut result = Ok(());
+        let mut verify = |slot| {
+            if result.is_ok() {
+                result = self.verify_pointer(None, slot).map(|_| ());
+            }
+        };
+        self.heap.iterate_roots(&mut verify);
+        self.heap.iterate_weak_roots(&mut verify);
+        self.heap.iterate_traced_handles(&mut verify);
+        result
+    }
 
-        /// Verify the heap is in its normal state before or after a GC.
-        pub fn verify_heap(heap: &Heap) {
-            // Implementation depends on Heap structure
-        }
+    /// Checks that `slot` holds a Smi, a cleared reference or a reference
+    /// to an object in a page in use. Returns the referenced object.
+    fn verify_pointer(&self, host: Option<HeapObject>, slot: ObjectSlot) -> Result<Option<HeapObject>, HeapVerificationError> {
+        let Some(target) = slot.load().get_heap_object() else {
+            return Ok(None);
+        };
+        let page = page_address(target.address());
+        let failure = if self.from_pages.contains(&page) {
+            HeapVerificationFailure::PointerIntoEvacuatedPage
+        } else if !self.pages.contains(&page) {
+            HeapVerificationFailure::PointerOutsideHeap
+        } else if !self.objects.contains(&target.address()) {
+            HeapVerificationFailure::PointerToNonObject
+        } else {
+            return Ok(Some(target));
+        };
+        Err(HeapVerificationError::in_slot(failure, host, slot))
+    }
+}
 
-        /// Verify the read-only heap after all read-only heap objects have been
-        /// created.
-        pub fn verify_read_only_heap(heap: &Heap) {
-            // Implementation depends on Heap structure
-        }
+/// Verifies the slots of an object and, for old hosts, that old-to-new
+/// references are remembered.
+struct VerifyPointersVisitor<'v, 'h> {
+    verifier: &'v HeapVerifier<'h>,
+    host_is_old: bool,
+    result: Result<(), HeapVerificationError>,
+}
 
-        /// Checks that this is a safe map transition.
-        pub fn verify_safe_map_transition(heap: &Heap, object: &dyn HeapObject, new_map: &Map) {
-            // Implementation depends on Heap and Map structures
+impl VerifyPointersVisitor<'_, '_> {
+    fn verify_slot(&self, host: HeapObject, slot: ObjectSlot, is_ephemeron_key: Option<usize>) -> Result<(), HeapVerificationError> {
+        let Some(target) = self.verifier.verify_pointer(Some(host), slot)? else {
+            return Ok(());
+        };
+        if !self.host_is_old || !target.chunk().in_young_generation() {
+            return Ok(());
         }
-
-        /// This function checks that either
-        /// - the map transition is safe,
-        /// - or it was communicated to GC using NotifyObjectLayoutChange.
-        pub fn verify_object_layout_change(heap: &Heap, object: &dyn HeapObject, new_map: &Map) {
-            // Implementation depends on Heap, Map and HeapObject structures
+        let remembered = match is_ephemeron_key {
+            Some(entry) => self.verifier.heap.ephemeron_remembered_set().contains(host, entry),
+            None => RememberedSet::<OLD_TO_NEW>::contains(host.chunk(), slot),
+        };
+        if remembered {
+            return Ok(());
         }
+        let failure = if is_ephemeron_key.is_some() {
+            HeapVerificationFailure::MissingEphemeronKeyEntry
+        } else {
+            HeapVerificationFailure::MissingOldToNewSlot
+        };
+        Err(HeapVerificationError::in_slot(failure, Some(host), slot))
+    }
+}
 
-        /// Verifies that that the object is allowed to change layout. Checks that if
-        /// the object is in shared space, it must be a string as no other objects in
-        /// shared space change layouts.
-        pub fn verify_object_layout_change_is_allowed(heap: &Heap, object: &dyn HeapObject) {
-            // Implementation depends on Heap and HeapObject structures
+impl ObjectVisitor for VerifyPointersVisitor<'_, '_> {
+    fn visit_pointers(&mut self, host: HeapObject, start: ObjectSlot, end: ObjectSlot) {
+        let mut slot = start;
+        while slot < end && self.result.is_ok() {
+            self.result = self.verify_slot(host, slot, None);
+            slot = slot.offset(1);
         }
+    }
 
-        pub fn set_pending_layout_change_object(heap: &Heap, object: &dyn HeapObject) {
-          // Implementation depends on Heap and HeapObject structures
+    fn visit_ephemeron(&mut self, host: HeapObject, entry: usize, key: ObjectSlot, value: ObjectSlot) {
+        if self.result.is_ok() {
+            self.result = self.verify_slot(host, key, Some(entry));
+        }
+        if self.result.is_ok() {
+            self.result = self.verify_slot(host, value, None);
         }
+    }
+}
 
-        pub fn verify_heap_if_enabled(heap: &Heap) {
-            unsafe {
-                if flags::verify_heap {
-                    Self::verify_heap(heap);
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap::heap::{AllocationType, HeapOptions};
+    use crate::heap::heap_layout::HeapLayout;
+    use crate::heap::heap_test_utils::{heap_options, new_heap, scavenge};
+
+    #[test]
+    fn stress_mode_verifies_heap_across_forced_gcs() {
+        let heap = Heap::new(HeapOptions {
+            verify_heap: true,
+            stress_scavenge: Some(100),
+            gc_interval: Some(1000),
+            ..heap_options(false)
+        });
+        let old_table = heap.allocate_ephemeron_hash_table(16, AllocationType::kOld);
+        let table = heap.create_root(Tagged::strong(old_table));
+        let list = heap.create_root(Tagged::ZERO);
+        for i in 0..5000 {
+            let allocation = if i % 7 == 0 { AllocationType::kOld } else { AllocationType::kYoung };
+            let node = heap.allocate_weak_fixed_array(3, allocation);
+            heap.set(node, 0, Tagged::from_smi(i));
+            heap.set(node, 1, heap.root(list));
+            heap.set(node, 2, Tagged::weak(heap.root_object(table)));
+            heap.set_root(list, Tagged::strong(node));
+            if i % 100 == 0 {
+                let entry = (i / 100) as usize % 16;
+                heap.set(heap.root_object(table), 2 * entry, Tagged::strong(node));
+                heap.set(heap.root_object(table), 2 * entry + 1, Tagged::from_smi(i));
             }
         }
+
+        let young_allocations = (0..5000).filter(|i| i % 7 != 0).count();
+        assert!(heap.gc_count() >= young_allocations / 100 + 5000 / 1000);
+        HeapVerifier::verify_heap(&heap).unwrap();
+        let mut node = heap.root(list);
+        for i in (0..5000).rev() {
+            let object = node.get_heap_object().unwrap();
+            assert_eq!(object.get(0), Tagged::from_smi(i));
+            node = object.get(1);
+        }
+    }
+
+    #[test]
+    fn detects_missing_write_barrier() {
+        let heap = new_heap(false);
+        let host = heap.allocate_fixed_array(4, AllocationType::kOld);
+        let _host_root = heap.create_root(Tagged::strong(host));
+        let young = heap.allocate_fixed_array(1, AllocationType::kYoung);
+        assert!(HeapLayout::in_young_generation(young));
+        // A store that bypasses the write barrier, as buggy native code would.
+        host.raw_field(2).store(Tagged::strong(young));
+
+        let error = HeapVerifier::verify_heap(&heap).unwrap_err();
+        assert_eq!(error.failure, HeapVerificationFailure::MissingOldToNewSlot);
+        assert_eq!(error.object, Some(host));
+        assert_eq!(error.slot, Some(host.raw_field(2)));
+        assert_eq!(error.value, Some(Tagged::strong(young)));
+        let message = error.to_string();
+        assert!(message.contains(&format!("{:#x}", host.address())), "{message}");
+        assert!(message.contains("index 2"), "{message}");
+
+        // Unnoticed, the scavenge leaves the slot referring into from-space.
+        scavenge(&heap);
+        let error = HeapVerifier::verify_heap(&heap).unwrap_err();
+        assert_eq!(error.failure, HeapVerificationFailure::PointerIntoEvacuatedPage);
+        assert_eq!(error.slot, Some(host.raw_field(2)));
+    }
+
+    #[test]
+    fn detects_corrupted_objects() {
+        let heap = new_heap(false);
+        let object = heap.allocate_fixed_array(4, AllocationType::kYoung);
+        let root = heap.create_root(Tagged::strong(object));
+        HeapVerifier::verify_heap(&heap).unwrap();
+
+        heap.set_root(root, Tagged::strong(unsafe { HeapObject::from_address(object.address() + K_TAGGED_SIZE) }));
+        let error = HeapVerifier::verify_heap(&heap).unwrap_err();
+        assert_eq!(error.failure, HeapVerificationFailure::PointerToNonObject);
+        assert_eq!(error.object, None);
+
+        ObjectSlot::new(object.address()).store(Tagged::from_raw(0x1235));
+        let error = HeapVerifier::verify_heap(&heap).unwrap_err();
+        assert_eq!(error.failure, HeapVerificationFailure::InvalidMap);
+        assert_eq!(error.object, Some(object));
+    }
+
+    #[test]
+    #[should_panic(expected = "old-to-new slot missing from the remembered set")]
+    fn verify_heap_option_panics_on_corruption() {
+        let heap = Heap::new(HeapOptions {
+            verify_heap: true,
+            ..heap_options(false)
+        });
+        let host = heap.allocate_fixed_array(1, AllocationType::kOld);
+        let _root = heap.create_root(Tagged::strong(host));
+        host.raw_field(0)
+            .store(Tagged::strong(heap.allocate_fixed_array(1, AllocationType::kYoung)));
+        scavenge(&heap);
     }
-}
\ No newline at end of file
+}
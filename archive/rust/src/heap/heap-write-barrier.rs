@@ -2,483 +2,58 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// Clients of this interface shouldn't depend on lots of heap internals.
-// Do not include anything from src/heap here!
-
-//use v8_internal; // Assuming a crate for v8-internal.h
-use std::any::Any;
-use std::ptr::NonNull;
-
-mod common {
-    pub mod globals {}
-}
+use crate::heap::heap::Heap;
+use crate::heap::heap_layout::{HeapLayout, HeapObject, ObjectSlot, Tagged, K_TAGGED_SIZE};
+use crate::heap::heap_visitor::EphemeronHashTableShape;
+use crate::heap::remembered_set::{RememberedSet, OLD_TO_NEW};
+
+/// Write barriers run after every store of a tagged value into an object
+/// and keep the remembered sets in sync with the heap graph. While full
+/// marking is in progress they also inform the `MarkingBarrier`.
+pub struct WriteBarrier;
+
+impl WriteBarrier {
+    /// Barrier for a store of `value` into `slot` of `host`.
+    #[inline]
+    pub fn combined_write_barrier(heap: &Heap, host: HeapObject, slot: ObjectSlot, value: Tagged) {
+        if Self::is_old_to_new(host, value) {
+            Self::generational_barrier_slow(host, slot);
+        }
+        Self::marking_barrier(heap, host, slot, value);
+    }
 
-mod objects {
-    pub mod heap_object {
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct HeapObject {
-            address: usize, // Or another representation
+    /// Barrier for a store of `value` into the key or value `slot` of the
+    /// ephemeron table `table`. Young keys of old tables are not recorded
+    /// in OLD_TO_NEW since keys are weak, see `EphemeronRememberedSet`.
+    #[inline]
+    pub fn combined_ephemeron_write_barrier(heap: &Heap, table: HeapObject, slot: ObjectSlot, value: Tagged) {
+        Self::marking_barrier(heap, table, slot, value);
+        if !Self::is_old_to_new(table, value) {
+            return;
+        }
+        let index = (slot.address() - table.raw_field(0).address()) / K_TAGGED_SIZE;
+        if EphemeronHashTableShape::is_key_index(index) {
+            heap.ephemeron_remembered_set()
+                .record_ephemeron_key_write(table, EphemeronHashTableShape::entry_for_index(index));
+        } else {
+            Self::generational_barrier_slow(table, slot);
         }
     }
-}
-
-use objects::heap_object::HeapObject;
-
-// Placeholder for v8::internal namespace
-pub mod internal {
-
-    use super::*;
-    use std::sync::Mutex;
-
-    // Forward declarations (using opaque types)
-    pub struct ArrayBufferExtension;
-    pub struct InstructionStream;
-    pub struct DescriptorArray;
-    pub struct EphemeronHashTable;
-    pub struct FixedArray;
-    pub struct Heap;
-    pub struct JSArrayBuffer;
-    pub struct Map;
-    pub struct MarkCompactCollector;
-    pub struct MarkingBarrier;
-    pub struct RelocInfo;
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct Address {
-        address: usize,
+    #[inline]
+    fn marking_barrier(heap: &Heap, host: HeapObject, slot: ObjectSlot, value: Tagged) {
+        let marking_barrier = heap.marking_barrier();
+        if marking_barrier.is_activated() {
+            marking_barrier.write(host, slot, value);
+        }
     }
 
-    pub struct JSObject;
-    pub struct TrustedObject;
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct Object;
-
-    // TODO: Define Tagged<T>, MaybeObjectSlot, ObjectSlot, RelocInfo, ExternalPointerSlot, IndirectPointerSlot, ProtectedPointerSlot, CppHeapPointerSlot, JSDispatchHandle, HeapObjectLayout, TaggedMemberBase, WriteBarrierMode, Isolate
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct Tagged<T> {
-        value: T,
+    #[inline]
+    fn is_old_to_new(host: HeapObject, value: Tagged) -> bool {
+        !HeapLayout::in_young_generation(host) && HeapLayout::is_young_reference(value)
     }
 
-    pub type MaybeObjectSlot = usize;
-    pub type ObjectSlot = usize;
-    pub type ExternalPointerSlot = usize;
-    pub type IndirectPointerSlot = usize;
-    pub type ProtectedPointerSlot = usize;
-    pub type CppHeapPointerSlot = usize;
-    pub type JSDispatchHandle = usize;
-
-    pub struct HeapObjectLayout;
-    pub struct TaggedMemberBase;
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum WriteBarrierMode {
-        UPDATE_WRITE_BARRIER, // Placeholder
+    fn generational_barrier_slow(host: HeapObject, slot: ObjectSlot) {
+        RememberedSet::<OLD_TO_NEW>::insert(host.chunk(), slot);
     }
-
-    pub struct Isolate;
-
-    pub struct DisallowGarbageCollection {}
-
-    /// Write barrier interface. It's preferred to use the macros defined in
-    /// `object-macros.h`.
-    ///
-    /// Refer to the `ForFoo()` versions which will dispatch to all relevant barriers
-    /// instead of emiting marking, compaction, generational, and shared barriers
-    /// separately.
-    pub struct WriteBarrier;
-
-    impl WriteBarrier {
-        /// Trampolines for generated code. Have to take raw addresses.
-        pub extern "C" fn ephemeron_key_write_barrier_from_code(
-            raw_object: Address,
-            key_slot_address: Address,
-            isolate: *mut Isolate,
-        ) {
-            // Implementation
-        }
-
-        pub extern "C" fn marking_from_code(raw_host: Address, raw_slot: Address) -> i32 {
-            // Implementation
-            0
-        }
-
-        pub extern "C" fn indirect_pointer_marking_from_code(
-            raw_host: Address,
-            raw_slot: Address,
-            raw_tag: Address,
-        ) -> i32 {
-            // Implementation
-            0
-        }
-
-        pub extern "C" fn shared_marking_from_code(raw_host: Address, raw_slot: Address) -> i32 {
-            // Implementation
-            0
-        }
-
-        pub extern "C" fn shared_from_code(raw_host: Address, raw_slot: Address) -> i32 {
-            // Implementation
-            0
-        }
-
-        pub fn get_write_barrier_mode_for_object(
-            object: Tagged<HeapObject>,
-            promise: &DisallowGarbageCollection,
-        ) -> WriteBarrierMode {
-            // Implementation
-            WriteBarrierMode::UPDATE_WRITE_BARRIER
-        }
-
-        pub fn for_value<T>(
-            host: Tagged<HeapObject>,
-            slot: MaybeObjectSlot,
-            value: Tagged<T>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_value_layout<T>(
-            host: &mut HeapObjectLayout,
-            slot: &mut TaggedMemberBase,
-            value: Tagged<T>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_ephemeron_hash_table(
-            host: Tagged<EphemeronHashTable>,
-            slot: ObjectSlot,
-            value: Tagged<Object>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_reloc_info(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            value: Tagged<HeapObject>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_descriptor_array(
-            descriptor_array: Tagged<DescriptorArray>,
-            number_of_own_descriptors: i32,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_array_buffer_extension(
-            host: Tagged<JSArrayBuffer>,
-            extension: *mut ArrayBufferExtension,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_external_pointer(
-            host: Tagged<HeapObject>,
-            slot: ExternalPointerSlot,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_indirect_pointer(
-            host: Tagged<HeapObject>,
-            slot: IndirectPointerSlot,
-            value: Tagged<HeapObject>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_protected_pointer(
-            host: Tagged<TrustedObject>,
-            slot: ProtectedPointerSlot,
-            value: Tagged<TrustedObject>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_cpp_heap_pointer(
-            host: Tagged<JSObject>,
-            slot: CppHeapPointerSlot,
-            value: *mut std::ffi::c_void,
-        ) {
-            // Implementation
-        }
-
-        pub fn for_js_dispatch_handle(
-            host: Tagged<HeapObject>,
-            handle: JSDispatchHandle,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        /// Executes generational and/or marking write barrier for a [start, end) range
-        /// of non-weak slots inside |object|.
-        pub fn for_range<TSlot>(heap: *mut Heap, object: Tagged<HeapObject>, start: TSlot, end: TSlot) {
-            // Implementation
-        }
-
-        static MARKING_BARRIER: Mutex<*mut MarkingBarrier> = Mutex::new(std::ptr::null_mut());
-
-        pub fn set_for_thread(marking_barrier: *mut MarkingBarrier) -> *mut MarkingBarrier {
-            let mut guard = Self::MARKING_BARRIER.lock().unwrap();
-            let old = *guard;
-            *guard = marking_barrier;
-            old
-        }
-
-        pub fn current_marking_barrier(
-            verification_candidate: Tagged<HeapObject>,
-        ) -> *mut MarkingBarrier {
-             let guard = Self::MARKING_BARRIER.lock().unwrap();
-             *guard
-        }
-
-        /// Invoked from traced handles where no host object is available.
-        pub fn marking_from_traced_handle(value: Tagged<Object>) {
-            // Implementation
-        }
-
-        pub fn generational_for_reloc_info(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            object: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        pub fn shared_for_reloc_info(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        pub fn marking_for_testing(
-            host: Tagged<HeapObject>,
-            slot: ObjectSlot,
-            value: Tagged<Object>,
-        ) {
-            // Implementation
-        }
-
-        #[cfg(feature = "slow_dchecks")]
-        pub fn is_required<T>(host: Tagged<HeapObject>, value: T) -> bool {
-            // Implementation
-            true
-        }
-
-        #[cfg(feature = "slow_dchecks")]
-        pub fn is_required_layout<T>(host: &HeapObjectLayout, value: T) -> bool {
-            // Implementation
-            true
-        }
-
-        #[cfg(feature = "slow_dchecks")]
-        pub fn verify_dispatch_handle_marking_state(
-            host: Tagged<HeapObject>,
-            value: JSDispatchHandle,
-            mode: WriteBarrierMode,
-        ) -> bool {
-            // Implementation
-            true
-        }
-
-        fn page_flags_are_consistent(object: Tagged<HeapObject>) -> bool {
-            // Implementation
-            true
-        }
-
-        fn is_immortal_immovable_heap_object(object: Tagged<HeapObject>) -> bool {
-            // Implementation
-            true
-        }
-
-        fn is_marking(object: Tagged<HeapObject>) -> bool {
-            // Implementation
-            true
-        }
-
-        fn marking(host: Tagged<HeapObject>, slot: ObjectSlot, value: Tagged<HeapObject>) {
-            // Implementation
-        }
-
-        fn marking_maybe_object(
-            host: Tagged<HeapObject>,
-            slot: MaybeObjectSlot,
-            value: Tagged<MaybeObject>,
-        ) {
-            // Implementation
-        }
-
-        fn marking_for_reloc_info(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        fn marking_external_pointer(host: Tagged<HeapObject>, slot: ExternalPointerSlot) {
-            // Implementation
-        }
-
-        fn marking_indirect_pointer(host: Tagged<HeapObject>, slot: IndirectPointerSlot) {
-            // Implementation
-        }
-
-        fn marking_protected_pointer(
-            host: Tagged<TrustedObject>,
-            slot: ProtectedPointerSlot,
-            value: Tagged<TrustedObject>,
-        ) {
-            // Implementation
-        }
-
-        fn marking_js_dispatch_handle(host: Tagged<HeapObject>, handle: JSDispatchHandle) {
-            // Implementation
-        }
-
-        fn marking_slow(host: Tagged<HeapObject>, slot: ObjectSlot, value: Tagged<HeapObject>) {
-            // Implementation
-        }
-
-        fn marking_slow_reloc_info(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        fn marking_slow_array_buffer(
-            host: Tagged<JSArrayBuffer>,
-            extension: *mut ArrayBufferExtension,
-        ) {
-            // Implementation
-        }
-
-        fn marking_slow_descriptor_array(
-            descriptor_array: Tagged<DescriptorArray>,
-            number_of_own_descriptors: i32,
-        ) {
-            // Implementation
-        }
-
-        fn marking_slow_external_pointer(host: Tagged<HeapObject>, slot: ExternalPointerSlot) {
-            // Implementation
-        }
-
-        fn marking_slow_indirect_pointer(host: Tagged<HeapObject>, slot: IndirectPointerSlot) {
-            // Implementation
-        }
-
-        fn marking_slow_protected_pointer(
-            host: Tagged<TrustedObject>,
-            slot: ProtectedPointerSlot,
-            value: Tagged<TrustedObject>,
-        ) {
-            // Implementation
-        }
-
-        fn marking_slow_js_dispatch_handle(host: Tagged<HeapObject>, handle: JSDispatchHandle) {
-            // Implementation
-        }
-
-        fn marking_slow_from_traced_handle(value: Tagged<HeapObject>) {
-            // Implementation
-        }
-
-        fn marking_slow_from_cpp_heap_wrappable(
-            heap: *mut Heap,
-            host: Tagged<JSObject>,
-            slot: CppHeapPointerSlot,
-            object: *mut std::ffi::c_void,
-        ) {
-            // Implementation
-        }
-
-        fn generational_barrier_slow(
-            object: Tagged<HeapObject>,
-            slot: Address,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        fn generational_barrier_for_cpp_heap_pointer(
-            host: Tagged<JSObject>,
-            value: *mut std::ffi::c_void,
-        ) {
-            // Implementation
-        }
-
-        fn shared_slow(
-            host: Tagged<TrustedObject>,
-            slot: ProtectedPointerSlot,
-            value: Tagged<TrustedObject>,
-        ) {
-            // Implementation
-        }
-
-        fn shared_slow_reloc_info(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        fn shared_heap_barrier_slow(object: Tagged<HeapObject>, slot: Address) {
-            // Implementation
-        }
-
-        fn combined_write_barrier_internal(
-            host: Tagged<HeapObject>,
-            slot: ObjectSlot,
-            value: Tagged<HeapObject>,
-            mode: WriteBarrierMode,
-        ) {
-            // Implementation
-        }
-
-        fn combined_generational_and_shared_barrier_slow(
-            object: Tagged<HeapObject>,
-            slot: Address,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        fn combined_generational_and_shared_ephemeron_barrier_slow(
-            table: Tagged<EphemeronHashTable>,
-            slot: Address,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-
-        fn generational_barrier_for_code_slow(
-            host: Tagged<InstructionStream>,
-            rinfo: *mut RelocInfo,
-            value: Tagged<HeapObject>,
-        ) {
-            // Implementation
-        }
-    }
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct MaybeObject {
-        address: usize,
-    }
-} // namespace v8::internal
\ No newline at end of file
+}
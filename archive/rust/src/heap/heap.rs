@@ -2,873 +2,1188 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// Note: This is a partial translation. Many parts of the original C++
-// header rely on other V8 internal components and require further elaboration.
-
-use std::sync::{Mutex, MutexGuard, Arc};
-use std::collections::{HashMap, HashSet};
-use std::marker::PhantomData;
-use std::sync::atomic::{AtomicU64, AtomicU8, AtomicBool, Ordering};
-use std::time::{Duration, Instant};
-use std::ops::{Add, Sub, BitOr, BitAnd, BitXor, Not, Shl, Shr};
-use std::fmt;
-
-// Minimal definitions for include files
-mod v8_callbacks {
-    pub type NearHeapLimitCallback = fn(data: *mut std::ffi::c_void);
+use std::cell::{Cell, OnceCell, RefCell};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::heap::allocation_observer::allocation_observer::{AllocationCounter, AllocationObserver};
+use crate::handles::traced_handles::TracedHandles;
+use crate::heap::concurrent_marking::ConcurrentMarking;
+use crate::heap::cppgc_js::cpp_heap::CppHeap;
+use crate::heap::ephemeron_remembered_set::EphemeronRememberedSet;
+use crate::heap::gc_tracer::{GCTracer, GarbageCollectionReason, GarbageCollector, ScopeId};
+use crate::heap::heap_controller::{
+    HeapGrowingInput, HeapGrowingPolicy, HeapLimitDecision, HeapLimitKind, MemoryController, V8HeapTrait,
+};
+use crate::heap::heap_verifier::HeapVerifier;
+use crate::heap::heap_layout::{
+    initialize_object_header, Address, BodyKind, HeapObject, InstanceType, Map, ObjectSlot, Tagged, BYTE_ARRAY_MAP,
+    EPHEMERON_HASH_TABLE_MAP, FIXED_ARRAY_MAP, INTERNALIZED_STRING_MAP, JS_API_OBJECT_MAP,
+    K_CPP_HEAP_WRAPPABLE_INDEX, K_TAGGED_SIZE, NATIVE_CONTEXT_MAP, STRING_MAP, WEAK_FIXED_ARRAY_MAP,
+};
+use crate::heap::heap_visitor::EphemeronHashTableShape;
+use crate::heap::heap_write_barrier::WriteBarrier;
+use crate::heap::incremental_marking::IncrementalMarking;
+use crate::heap::incremental_marking_job::IncrementalMarkingJob;
+use crate::heap::large_spaces::OldLargeObjectSpace;
+use crate::heap::linear_allocation_area::LinearAllocationArea;
+use crate::heap::mark_compact::MarkCompactCollector;
+use crate::heap::marking_barrier::MarkingBarrier;
+use crate::heap::marking_state::MarkingState;
+use crate::heap::marking_worklist::MarkingWorklists;
+use crate::heap::memory_chunk::{AllocationSpace, K_MAX_REGULAR_HEAP_OBJECT_SIZE};
+use crate::heap::memory_measurement::{MeasureMemoryDelegate, MeasureMemoryExecution, MemoryMeasurement};
+use crate::heap::memory_reducer::MemoryReducer;
+use crate::heap::new_spaces::SemiSpaceNewSpace;
+use crate::heap::paged_spaces::{OldSpace, PagedSpace};
+use crate::heap::scavenger::ScavengerCollector;
+use crate::heap::stress_scavenge_observer::StressScavengeObserver;
+use crate::heap::sweeper::Sweeper;
+use crate::logging::runtime_call_stats::{RuntimeCallCounterId, RuntimeCallStatsTables};
+use crate::logging::runtime_call_stats_scope::HasRuntimeCallStats;
+use crate::heap::weak_object_worklists::WeakObjects;
+
+const MB: usize = 1024 * 1024;
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocationType {
+    kYoung,
+    kOld,
 }
 
-mod v8_embedder_heap {
-    // Placeholder
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapState {
+    NOT_IN_GC,
+    SCAVENGE,
+    MARK_COMPACT,
 }
 
-mod v8_internal {
-    // Placeholder
+/// How aggressively the old-generation allocation limit grows after a
+/// mark-compact.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapGrowingMode {
+    kSlow,
+    kConservative,
+    kMinimal,
+    kDefault,
 }
 
-mod v8_isolate {
-    // Placeholder
-    pub type GCCallbackWithData = fn(data: *mut std::ffi::c_void);
-    pub enum UseCounterFeature {}
-    pub enum MemoryPressureLevel {
-        kNone,
-        kModerate,
-        kCritical,
-    }
+/// Configuration of a `Heap`.
+#[derive(Clone, Debug)]
+pub struct HeapOptions {
+    /// Capacity of each of the two semi-spaces of the young generation.
+    pub max_semi_space_size: usize,
+    /// Whether scavenges use background tasks.
+    pub parallel_scavenge: bool,
+    /// Upper bound for the number of tasks, including the main thread, that
+    /// evacuate objects during a scavenge.
+    pub max_scavenger_tasks: usize,
+    /// Maximum size of the old generation. Exceeding it is fatal.
+    pub max_old_generation_size: usize,
+    /// Allocation limit of the old generation before the first
+    /// mark-compact; later limits do not drop below it.
+    pub initial_old_generation_size: usize,
+    /// Whether full marking starts incrementally before the old generation
+    /// reaches its allocation limit.
+    pub incremental_marking: bool,
+    /// Whether background tasks mark alongside incremental marking.
+    pub concurrent_marking: bool,
+    /// Whether memory-reducing garbage collections are started once the
+    /// heap stopped growing, see `MemoryReducer`. Requires incremental
+    /// marking.
+    pub memory_reducer: bool,
+    /// Whether old space is swept by background tasks after a mark-compact.
+    pub concurrent_sweeping: bool,
+    /// Whether mark-compacts evacuate fragmented old pages.
+    pub compaction: bool,
+    /// Whether mark-compacts evacuate objects with background tasks.
+    pub parallel_compaction: bool,
+    /// Prints a line per garbage collection, like `--trace-gc`.
+    pub trace_gc: bool,
+    /// Prints a line per change of the old-generation limits, see
+    /// `HeapGrowingPolicy`.
+    pub trace_heap_limits: bool,
+    /// Verifies the heap before and after every garbage collection, like
+    /// `--verify-heap`, and panics with the offending object and slot on
+    /// corruption. See `HeapVerifier`.
+    pub verify_heap: bool,
+    /// Forces a scavenge on every N-th young allocation, see
+    /// `StressScavengeObserver`.
+    pub stress_scavenge: Option<usize>,
+    /// Forces a garbage collection on every N-th allocation, like
+    /// `--gc-interval`: a scavenge for young allocations and a
+    /// mark-compact otherwise.
+    pub gc_interval: Option<usize>,
 }
 
-mod src_base {
-    pub mod atomic_utils {
-        // Placeholder
+impl Default for HeapOptions {
+    fn default() -> Self {
+        HeapOptions {
+            max_semi_space_size: MB,
+            parallel_scavenge: true,
+            max_scavenger_tasks: std::thread::available_parallelism()
+                .map_or(1, |cores| cores.get())
+                .min(ScavengerCollector::K_MAX_SCAVENGER_TASKS),
+            max_old_generation_size: 256 * MB,
+            initial_old_generation_size: 16 * MB,
+            incremental_marking: true,
+            concurrent_marking: true,
+            memory_reducer: true,
+            concurrent_sweeping: true,
+            compaction: true,
+            parallel_compaction: true,
+            trace_gc: false,
+            trace_heap_limits: false,
+            verify_heap: false,
+            stress_scavenge: None,
+            gc_interval: None,
+        }
     }
-    pub mod enum_set {
-        use std::ops::{BitOr, BitAnd, BitXor, Not};
-        use std::fmt;
+}
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct EnumSet<T: Copy + Eq + std::hash::Hash, U: Copy> {
-            bits: U,
-            _phantom: std::marker::PhantomData<T>,
-        }
+/// Called when the old generation is about to exceed its maximum size,
+/// like `v8::NearHeapLimitCallback`. Receives the current and the initial
+/// maximum size and returns the new maximum size; the heap runs out of
+/// memory unless it is larger than the current one.
+pub type NearHeapLimitCallback = Box<dyn FnMut(usize, usize) -> usize>;
+
+/// Identifies a near-heap-limit callback, see
+/// `Heap::add_near_heap_limit_callback()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NearHeapLimitCallbackHandle(usize);
+
+/// Follows individual objects across garbage collections without keeping
+/// them alive, like the sampling heap profiler does for its samples. See
+/// `Heap::add_object_tracker()`.
+///
+/// Trackers are notified on the main thread at the end of each collection,
+/// while the old locations of moved objects are still readable. They must
+/// not call into the heap.
+pub trait HeapObjectTracker {
+    /// Addresses of the tracked objects.
+    fn tracked_objects(&self) -> Vec<Address>;
+    /// The tracked object at `from` was moved to `to`.
+    fn on_object_moved(&self, from: Address, to: Address);
+    /// `collector` finished; `is_live` tells whether the object at a tracked
+    /// address survived. Moves have been reported before.
+    fn on_garbage_collection(&self, collector: GarbageCollector, is_live: &dyn Fn(Address) -> bool);
+}
 
-        impl<T: Copy + Eq + std::hash::Hash, U: Copy + Default> EnumSet<T, U> {
-            pub fn new() -> Self {
-                EnumSet {
-                    bits: U::default(),
-                    _phantom: std::marker::PhantomData,
-                }
-            }
+/// Index of a strong root, see `Heap::create_root()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RootHandle(usize);
+
+/// The managed heap: a young generation of two semi-spaces that is
+/// collected by the scavenger, and an old generation of regular and large
+/// pages that objects are promoted to. The whole heap is collected by the
+/// mark-compact collector once the old generation reaches its allocation
+/// limit; marking starts incrementally ahead of the limit.
+///
+/// Objects move during garbage collections, so embedders must keep
+/// `HeapObject`s across allocations alive and up to date through roots.
+pub struct Heap {
+    options: HeapOptions,
+    new_space: SemiSpaceNewSpace,
+    old_space: OldSpace,
+    lo_space: OldLargeObjectSpace,
+    /// The mutator's linear allocation area in new space.
+    new_allocation_info: RefCell<LinearAllocationArea>,
+    roots: RefCell<Vec<Tagged>>,
+    free_roots: RefCell<Vec<usize>>,
+    /// Weak references to all native contexts; cleared entries are
+    /// dropped after each mark-compact.
+    native_contexts: RefCell<Vec<Tagged>>,
+    traced_handles: TracedHandles,
+    /// The `CppHeap` attached through `CppHeap::attach_isolate()`.
+    cpp_heap: Cell<Option<*const CppHeap>>,
+    memory_measurement: MemoryMeasurement,
+    ephemeron_remembered_set: EphemeronRememberedSet,
+    tracer: GCTracer,
+    gc_state: Cell<HeapState>,
+    gc_count: Cell<usize>,
+    /// Bytes promoted into the old generation by the last scavenge.
+    promoted_objects_size: Cell<usize>,
+    /// Bytes that survived the last scavenge in the young generation.
+    semi_space_copied_object_size: Cell<usize>,
+    mark_compact_collector: MarkCompactCollector,
+    incremental_marking: IncrementalMarking,
+    incremental_marking_job: IncrementalMarkingJob,
+    concurrent_marking: ConcurrentMarking,
+    marking_barrier: MarkingBarrier,
+    sweeper: Sweeper,
+    ms_count: Cell<usize>,
+    /// Size of the old generation that triggers a mark-compact.
+    old_generation_allocation_limit: Cell<usize>,
+    /// Size of the old generation beyond which the heap is out of memory.
+    /// Starts at `HeapOptions::max_old_generation_size`.
+    max_old_generation_size: Cell<usize>,
+    heap_growing_policy: RefCell<Box<dyn HeapGrowingPolicy>>,
+    memory_reducer: Option<MemoryReducer>,
+    near_heap_limit_callbacks: RefCell<Vec<(NearHeapLimitCallbackHandle, NearHeapLimitCallback)>>,
+    next_near_heap_limit_callback: Cell<usize>,
+    old_generation_size_at_last_gc: Cell<usize>,
+    last_mark_compact_time: Cell<Duration>,
+    stress_scavenge_observer: Option<StressScavengeObserver>,
+    /// Allocations left until the next GC forced by `gc_interval`.
+    allocations_until_gc: Cell<usize>,
+    /// Observers of all allocations; see `add_allocation_observer()`.
+    allocation_counter: RefCell<AllocationCounter<'static>>,
+    pause_allocation_observers_depth: Cell<usize>,
+    /// Nesting depth of `AlwaysAllocateScope`s.
+    always_allocate_depth: Cell<usize>,
+    object_trackers: RefCell<Vec<*const dyn HeapObjectTracker>>,
+    /// The runtime call stats tables of the owning isolate, see
+    /// `set_runtime_call_stats()`.
+    runtime_call_stats: OnceCell<Arc<RuntimeCallStatsTables>>,
+}
+
+impl Heap {
+    /// Size of the linear allocation areas handed to the mutator.
+    pub const K_LAB_SIZE: usize = 32 * 1024;
+
+    /// Allocated bytes in the old generation between checks whether
+    /// incremental marking should start.
+    const K_OLD_GENERATION_LIMIT_CHECK_INTERVAL: usize = 64 * 1024;
+
+    pub fn new(options: HeapOptions) -> Self {
+        let tracer = GCTracer::new();
+        tracer.set_trace_gc(options.trace_gc);
+        tracer.set_trace_heap_limits(options.trace_heap_limits);
+        let marking_worklists = Arc::new(MarkingWorklists::new());
+        let weak_objects = Arc::new(WeakObjects::new());
+        let concurrent_marking_tasks = if options.concurrent_marking {
+            std::thread::available_parallelism().map_or(1, |cores| cores.get().saturating_sub(1).max(1))
+        } else {
+            0
+        };
+        Heap {
+            new_space: SemiSpaceNewSpace::new(options.max_semi_space_size),
+            old_space: PagedSpace::new(AllocationSpace::OLD_SPACE),
+            lo_space: OldLargeObjectSpace::new(),
+            new_allocation_info: RefCell::new(LinearAllocationArea::new()),
+            roots: RefCell::new(Vec::new()),
+            free_roots: RefCell::new(Vec::new()),
+            native_contexts: RefCell::new(Vec::new()),
+            traced_handles: TracedHandles::new(),
+            cpp_heap: Cell::new(None),
+            memory_measurement: MemoryMeasurement::new(),
+            ephemeron_remembered_set: EphemeronRememberedSet::new(),
+            tracer,
+            gc_state: Cell::new(HeapState::NOT_IN_GC),
+            gc_count: Cell::new(0),
+            promoted_objects_size: Cell::new(0),
+            semi_space_copied_object_size: Cell::new(0),
+            mark_compact_collector: MarkCompactCollector::new(Arc::clone(&marking_worklists), Arc::clone(&weak_objects)),
+            incremental_marking: IncrementalMarking::new(),
+            incremental_marking_job: IncrementalMarkingJob::new(),
+            concurrent_marking: ConcurrentMarking::new(
+                Arc::clone(&marking_worklists),
+                weak_objects,
+                concurrent_marking_tasks,
+            ),
+            marking_barrier: MarkingBarrier::new(marking_worklists),
+            sweeper: Sweeper::new(),
+            ms_count: Cell::new(0),
+            old_generation_allocation_limit: Cell::new(options.initial_old_generation_size),
+            max_old_generation_size: Cell::new(options.max_old_generation_size),
+            heap_growing_policy: RefCell::new(Box::new(MemoryController::<V8HeapTrait>::new())),
+            memory_reducer: (options.memory_reducer && options.incremental_marking).then(MemoryReducer::new),
+            near_heap_limit_callbacks: RefCell::new(Vec::new()),
+            next_near_heap_limit_callback: Cell::new(0),
+            old_generation_size_at_last_gc: Cell::new(0),
+            last_mark_compact_time: Cell::new(Duration::ZERO),
+            stress_scavenge_observer: options.stress_scavenge.map(StressScavengeObserver::new),
+            allocations_until_gc: Cell::new(options.gc_interval.map_or(0, |interval| interval.max(1))),
+            allocation_counter: RefCell::new(AllocationCounter::new()),
+            pause_allocation_observers_depth: Cell::new(0),
+            always_allocate_depth: Cell::new(0),
+            object_trackers: RefCell::new(Vec::new()),
+            runtime_call_stats: OnceCell::new(),
+            options,
         }
+    }
 
-        impl<T: Copy + Eq + std::hash::Hash, U: Copy + BitOr<Output = U> + BitAnd<Output = U> + BitXor<Output = U> + Not<Output = U> + PartialEq + From<u8> + Into<u8> + Default> EnumSet<T, U> {
-            pub fn contains(&self, flag: T) -> bool {
-                // Requires From and Into implementations for U to u8
-                let flag_u8: u8 = unsafe { std::mem::transmute_copy(&flag) };
-                let bits_u8: u8 = self.bits.into();
-                (bits_u8 & flag_u8) != 0
-            }
+    pub fn options(&self) -> &HeapOptions {
+        &self.options
+    }
 
-            pub fn insert(&mut self, flag: T) {
-                let flag_u8: u8 = unsafe { std::mem::transmute_copy(&flag) };
-                let bits_u8: u8 = self.bits.into();
-                self.bits = (bits_u8 | flag_u8).into();
-            }
+    pub fn new_space(&self) -> &SemiSpaceNewSpace {
+        &self.new_space
+    }
 
-            pub fn remove(&mut self, flag: T) {
-                let flag_u8: u8 = unsafe { std::mem::transmute_copy(&flag) };
-                let bits_u8: u8 = self.bits.into();
-                self.bits = (bits_u8 & !flag_u8).into();
-            }
-        }
+    pub fn old_space(&self) -> &OldSpace {
+        &self.old_space
+    }
 
-        impl<T: Copy + Eq + std::hash::Hash, U: Copy + BitOr<Output = U> + BitAnd<Output = U> + BitXor<Output = U> + Not<Output = U> + PartialEq + From<u8> + Into<u8> + Default> BitOr for EnumSet<T, U> {
-            type Output = Self;
+    pub fn lo_space(&self) -> &OldLargeObjectSpace {
+        &self.lo_space
+    }
 
-            fn bitor(self, other: Self) -> Self {
-                let bits_u8_self: u8 = self.bits.into();
-                let bits_u8_other: u8 = other.bits.into();
-                EnumSet {
-                    bits: (bits_u8_self | bits_u8_other).into(),
-                    _phantom: std::marker::PhantomData,
-                }
-            }
-        }
+    pub fn ephemeron_remembered_set(&self) -> &EphemeronRememberedSet {
+        &self.ephemeron_remembered_set
+    }
 
-        // Implement other bitwise operators similarly (BitAnd, BitXor, Not)
-        macro_rules! define_operators_for_flags {
-            ($flags_type:ident) => {
-                impl std::ops::BitOr for $flags_type {
-                    type Output = Self;
-                    fn bitor(self, other: Self) -> Self {
-                        $flags_type(self.0 | other.0)
-                    }
-                }
+    pub fn memory_measurement(&self) -> &MemoryMeasurement {
+        &self.memory_measurement
+    }
 
-                impl std::ops::BitAnd for $flags_type {
-                    type Output = Self;
-                    fn bitand(self, other: Self) -> Self {
-                        $flags_type(self.0 & other.0)
-                    }
-                }
-            }
-        }
+    pub fn traced_handles(&self) -> &TracedHandles {
+        &self.traced_handles
     }
-    pub mod platform {
-        pub struct Mutex {
-            inner: std::sync::Mutex<()>,
-        }
 
-        impl Mutex {
-            pub fn new() -> Self {
-                Mutex { inner: std::sync::Mutex::new(()) }
-            }
+    /// Returns the attached `CppHeap`, if any.
+    pub fn cpp_heap(&self) -> Option<&CppHeap> {
+        // SAFETY: an attached `CppHeap` detaches itself before it goes away.
+        self.cpp_heap.get().map(|cpp_heap| unsafe { &*cpp_heap })
+    }
 
-            pub fn lock(&self) -> Result<MutexGuard<()>, std::sync::PoisonError<MutexGuard<()>>> {
-                self.inner.lock()
-            }
-        }
-        pub struct ConditionVariable {
-            inner: std::sync::Condvar,
-        }
+    /// Called by `CppHeap::attach_isolate()`.
+    pub(crate) fn attach_cpp_heap(&self, cpp_heap: *const CppHeap) {
+        assert!(self.cpp_heap.get().is_none(), "a CppHeap is attached already");
+        assert!(
+            !self.incremental_marking.is_marking(),
+            "a CppHeap may not be attached while marking"
+        );
+        self.cpp_heap.set(Some(cpp_heap));
+    }
 
-        impl ConditionVariable {
-            pub fn new() -> Self {
-                ConditionVariable { inner: std::sync::Condvar::new() }
-            }
+    /// Called by `CppHeap::detach_isolate()`.
+    pub(crate) fn detach_cpp_heap(&self) {
+        assert!(
+            !self.incremental_marking.is_marking(),
+            "a CppHeap may not be detached while marking"
+        );
+        self.cpp_heap.set(None);
+    }
 
-            pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> Result<MutexGuard<'a, T>, std::sync::PoisonError<MutexGuard<'a, T>>> {
-                // This requires more sophisticated handling in a real implementation.
-                // The Rust Condvar API differs significantly from the C++ one.
-                // This placeholder returns the guard immediately, which is incorrect.
-                Ok(guard)
-            }
-        }
+    /// Makes garbage collections count into the runtime call stats tables
+    /// of the owning isolate.
+    pub fn set_runtime_call_stats(&self, runtime_call_stats: Arc<RuntimeCallStatsTables>) {
+        assert!(self.runtime_call_stats.set(runtime_call_stats).is_ok(), "runtime call stats are set already");
     }
-    pub mod small_vector {
-        // Placeholder
+
+    pub fn tracer(&self) -> &GCTracer {
+        &self.tracer
     }
-}
 
-mod src_builtins {
-    pub mod accessors {
-        // Placeholder
+    pub fn mark_compact_collector(&self) -> &MarkCompactCollector {
+        &self.mark_compact_collector
     }
-}
 
-mod src_common {
-    pub mod assert_scope {
-        // Placeholder
+    pub fn incremental_marking(&self) -> &IncrementalMarking {
+        &self.incremental_marking
     }
-    pub mod code_memory_access {
-        // Placeholder
+
+    pub fn incremental_marking_job(&self) -> &IncrementalMarkingJob {
+        &self.incremental_marking_job
     }
-    pub mod globals {
-        // Placeholder
-        pub const KB: usize = 1024;
-        pub const MB: usize = 1024 * KB;
-        pub const kTaggedSize: usize = 8;
-        pub const kSystemPointerSize: usize = 8;
+
+    pub fn concurrent_marking(&self) -> &ConcurrentMarking {
+        &self.concurrent_marking
     }
-}
 
-mod src_heap {
-    pub mod allocation_observer {
-        // Placeholder
+    pub fn marking_barrier(&self) -> &MarkingBarrier {
+        &self.marking_barrier
     }
-    pub mod allocation_result {
-        // Placeholder
-        pub enum AllocationResult {
-            Success,
-            Failure,
-        }
+
+    pub fn sweeper(&self) -> &Sweeper {
+        &self.sweeper
     }
-    pub mod gc_callbacks {
-        // Placeholder
+
+    pub fn gc_state(&self) -> HeapState {
+        self.gc_state.get()
     }
-    pub mod heap_allocator {
-        // Placeholder
+
+    pub fn gc_count(&self) -> usize {
+        self.gc_count.get()
     }
-    pub mod marking_state {
-        // Placeholder
+
+    /// Number of mark-compacts so far.
+    pub fn ms_count(&self) -> usize {
+        self.ms_count.get()
     }
-    pub mod minor_gc_job {
-        // Placeholder
+
+    pub fn promoted_objects_size(&self) -> usize {
+        self.promoted_objects_size.get()
     }
-    pub mod pretenuring_handler {
-        // Placeholder
+
+    pub fn semi_space_copied_object_size(&self) -> usize {
+        self.semi_space_copied_object_size.get()
     }
-    pub mod sweeper {
-        // Placeholder
+
+    /// Size of all objects, including fillers in new space.
+    pub fn size_of_objects(&self) -> usize {
+        self.new_space.size() + self.old_space.size_of_objects() + self.lo_space.size_of_objects()
     }
-}
 
-mod src_init {
-    pub mod heap_symbols {
-        // Placeholder
+    pub fn committed_memory(&self) -> usize {
+        2 * self.new_space.capacity() + self.old_space.committed_memory() + self.lo_space.committed_memory()
     }
-}
 
-mod src_objects {
-    pub mod allocation_site {
-        // Placeholder
+    pub fn old_generation_size_of_objects(&self) -> usize {
+        self.old_space.size_of_objects() + self.lo_space.size_of_objects()
     }
-    pub mod fixed_array {
-        // Placeholder
-        pub struct FixedArrayBase {}
+
+    pub fn old_generation_allocation_limit(&self) -> usize {
+        self.old_generation_allocation_limit.get()
     }
-    pub mod hash_table {
-        // Placeholder
+
+    pub fn max_old_generation_size(&self) -> usize {
+        self.max_old_generation_size.get()
     }
-    pub mod heap_object {
-        // Placeholder
-        use std::hash::{Hasher, BuildHasher};
-        use std::any::Any;
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct HeapObject {}
+    /// Replaces the policy that computes the allocation limit after each
+    /// mark-compact. The current limit stays until the next mark-compact.
+    pub fn set_heap_growing_policy(&self, policy: Box<dyn HeapGrowingPolicy>) {
+        *self.heap_growing_policy.borrow_mut() = policy;
+    }
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct MaybeObject {}
+    pub fn memory_reducer(&self) -> Option<&MemoryReducer> {
+        self.memory_reducer.as_ref()
+    }
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct Tagged<T> {
-            pub ptr: *mut T,
-            _phantom: PhantomData<T>,
+    /// Growing mode passed to the `HeapGrowingPolicy` at the end of the
+    /// current mark-compact: minimal for garbage collections started by the
+    /// `MemoryReducer`, and slow while the memory reducer is done.
+    pub fn current_heap_growing_mode(&self) -> HeapGrowingMode {
+        if self.incremental_marking.start_reason() == Some(GarbageCollectionReason::kMemoryReducer) {
+            return HeapGrowingMode::kMinimal;
         }
+        match &self.memory_reducer {
+            Some(memory_reducer) if memory_reducer.should_grow_heap_slowly() => HeapGrowingMode::kSlow,
+            _ => HeapGrowingMode::kDefault,
+        }
+    }
 
-        impl<T> Tagged<T> {
-            pub fn new(ptr: *mut T) -> Self {
-                Tagged { ptr, _phantom: PhantomData }
-            }
+    /// Registers `observer` to be stepped by allocations in all spaces.
+    /// Observers must not allocate or add and remove observers while
+    /// being stepped.
+    ///
+    /// # Safety
+    ///
+    /// `observer` must stay valid until it is removed again.
+    pub unsafe fn add_allocation_observer(&self, observer: *const dyn AllocationObserver) {
+        self.allocation_counter.borrow_mut().add_allocation_observer(unsafe { &*observer });
+    }
 
-            pub fn from_raw(ptr: *mut T) -> Self {
-              Tagged { ptr, _phantom: PhantomData }
-            }
+    /// # Safety
+    ///
+    /// `observer` must have been added by `add_allocation_observer()`.
+    pub unsafe fn remove_allocation_observer(&self, observer: *const dyn AllocationObserver) {
+        self.allocation_counter.borrow_mut().remove_allocation_observer(unsafe { &*observer });
+    }
 
-            pub fn as_ptr(&self) -> *mut T {
-                self.ptr
-            }
-        }
+    /// Stops stepping allocation observers until the matching
+    /// `resume_allocation_observers()`; see `PauseAllocationObserversScope`.
+    pub fn pause_allocation_observers(&self) {
+        self.pause_allocation_observers_depth
+            .set(self.pause_allocation_observers_depth.get() + 1);
+    }
 
-        impl Tagged<HeapObject> {
-          pub fn unsafe_from_address(address: usize) -> Self {
-              Tagged::new(address as *mut HeapObject)
-          }
-        }
+    pub fn resume_allocation_observers(&self) {
+        self.pause_allocation_observers_depth
+            .set(self.pause_allocation_observers_depth.get() - 1);
+    }
 
-        pub trait Object : Any {
-            fn is_identical_to(&self, other: &dyn Object) -> bool;
-            fn get_hash_code(&self) -> usize;
-        }
+    /// Makes allocations succeed without garbage collections until the
+    /// matching `leave_always_allocate()`; see `AlwaysAllocateScope`.
+    pub fn enter_always_allocate(&self) {
+        self.always_allocate_depth.set(self.always_allocate_depth.get() + 1);
+    }
+
+    pub fn leave_always_allocate(&self) {
+        self.always_allocate_depth.set(self.always_allocate_depth.get() - 1);
+    }
+
+    pub fn always_allocate(&self) -> bool {
+        self.always_allocate_depth.get() > 0
+    }
+
+    /// Registers `tracker` to be told about the moves and deaths of the
+    /// objects it tracks, see `HeapObjectTracker`.
+    ///
+    /// # Safety
+    ///
+    /// `tracker` must stay valid until it is removed again.
+    pub unsafe fn add_object_tracker(&self, tracker: *const dyn HeapObjectTracker) {
+        self.object_trackers.borrow_mut().push(tracker);
+    }
 
-        pub struct Hasher {}
+    pub fn remove_object_tracker(&self, tracker: *const dyn HeapObjectTracker) {
+        self.object_trackers
+            .borrow_mut()
+            .retain(|&registered| !std::ptr::addr_eq(registered, tracker));
+    }
 
-        impl Hasher {
-            pub fn finish(&self) -> u64 { 0 }
-            pub fn write(&mut self, bytes: &[u8]) {}
-            pub fn write_u8(&mut self, i: u8) {}
-            pub fn write_u32(&mut self, i: u32) {}
-            pub fn write_u64(&mut self, i: u64) {}
+    /// Reports the tracked objects that `collector` moved or freed to the
+    /// object trackers. `forwarding_address` returns the new location of a
+    /// moved object and `is_live` whether an object survived, given its
+    /// location after the moves.
+    pub(crate) fn notify_object_trackers(
+        &self,
+        collector: GarbageCollector,
+        forwarding_address: impl Fn(HeapObject) -> Option<HeapObject>,
+        is_live: impl Fn(HeapObject) -> bool,
+    ) {
+        let trackers = self.object_trackers.borrow().clone();
+        for tracker in trackers {
+            // SAFETY: trackers stay valid until they are removed, and tracked
+            // objects were live at the start of the collection.
+            let tracker = unsafe { &*tracker };
+            for address in tracker.tracked_objects() {
+                let object = unsafe { HeapObject::from_address(address) };
+                if let Some(target) = forwarding_address(object) {
+                    tracker.on_object_moved(address, target.address());
+                }
+            }
+            tracker.on_garbage_collection(collector, &|address| is_live(unsafe { HeapObject::from_address(address) }));
         }
+    }
+
+    /// Adds a callback that may raise the maximum size of the old
+    /// generation when it is reached, like
+    /// `v8::Isolate::AddNearHeapLimitCallback()`. Only the most recently
+    /// added callback is invoked. Callbacks must not call into the heap.
+    pub fn add_near_heap_limit_callback(&self, callback: NearHeapLimitCallback) -> NearHeapLimitCallbackHandle {
+        let handle = NearHeapLimitCallbackHandle(self.next_near_heap_limit_callback.get());
+        self.next_near_heap_limit_callback.set(handle.0 + 1);
+        self.near_heap_limit_callbacks.borrow_mut().push((handle, callback));
+        handle
+    }
 
-        pub trait KeyEqualSafe {
-            fn key_equal_safe(a: &Tagged<HeapObject>, b: &Tagged<HeapObject>) -> bool;
+    /// Removes a callback. A nonzero `heap_limit` restores the maximum size
+    /// to it, though not below the size of all objects plus a quarter.
+    pub fn remove_near_heap_limit_callback(&self, handle: NearHeapLimitCallbackHandle, heap_limit: usize) {
+        self.near_heap_limit_callbacks
+            .borrow_mut()
+            .retain(|(callback, _)| *callback != handle);
+        if heap_limit != 0 {
+            let min_limit = self.size_of_objects() + self.size_of_objects() / 4;
+            let limit = self.max_old_generation_size().min(heap_limit.max(min_limit));
+            self.set_max_old_generation_size(limit, "RestoreHeapLimit");
         }
+    }
 
-        impl Object {
-          pub fn Hasher() -> Hasher {
-              Hasher {}
-          }
-          pub fn KeyEqualSafe(a: &Tagged<HeapObject>, b: &Tagged<HeapObject>) -> bool {
-              a.ptr == b.ptr
-          }
+    /// Invokes the most recent near-heap-limit callback. Returns true if it
+    /// raised the maximum size.
+    fn invoke_near_heap_limit_callback(&self) -> bool {
+        let heap_limit = {
+            let mut callbacks = self.near_heap_limit_callbacks.borrow_mut();
+            let Some((_, callback)) = callbacks.last_mut() else {
+                return false;
+            };
+            callback(self.max_old_generation_size(), self.options.max_old_generation_size)
+        };
+        if heap_limit <= self.max_old_generation_size() {
+            return false;
         }
+        self.set_max_old_generation_size(heap_limit, "NearHeapLimitCallback");
+        true
     }
-    pub mod js_array_buffer {
-        // Placeholder
+
+    fn set_max_old_generation_size(&self, limit: usize, source: &'static str) {
+        let previous_limit = self.max_old_generation_size.replace(limit);
+        self.tracer.notify_heap_limit_decision(HeapLimitDecision {
+            kind: HeapLimitKind::kMaxOldGenerationSize,
+            source,
+            time: Duration::ZERO,
+            old_generation_size: self.old_generation_size_of_objects(),
+            previous_limit,
+            limit,
+            allocation_rate: None,
+            gc_speed: None,
+        });
     }
-    pub mod objects {
-        // Placeholder
+
+    fn can_expand_old_generation(&self, size: usize) -> bool {
+        self.old_generation_size_of_objects() + size <= self.max_old_generation_size()
     }
-    pub mod smi {
-        // Placeholder
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct Smi(i32);
 
-        impl Smi {
-            pub fn zero() -> Self {
-                Smi(0)
-            }
+    /// Returns true once the old generation is close enough to its
+    /// allocation limit that incremental marking should start: marking
+    /// has to finish before the young generation is promoted at the limit.
+    pub fn incremental_marking_limit_reached(&self) -> bool {
+        self.old_generation_size_of_objects() + self.new_space.capacity() >= self.old_generation_allocation_limit()
+    }
+
+    // Roots.
+
+    /// Adds a strong root holding `value`. Roots are updated when the
+    /// objects they refer to move.
+    pub fn create_root(&self, value: Tagged) -> RootHandle {
+        let mut roots = self.roots.borrow_mut();
+        if let Some(index) = self.free_roots.borrow_mut().pop() {
+            roots[index] = value;
+            return RootHandle(index);
         }
+        roots.push(value);
+        RootHandle(roots.len() - 1)
     }
-    pub mod visitors {
-        // Placeholder
+
+    pub fn root(&self, handle: RootHandle) -> Tagged {
+        self.roots.borrow()[handle.0]
     }
-}
 
-mod src_roots {
-    pub mod roots {
-        // Placeholder
-        pub struct RootsTable {}
+    /// Returns the object referenced by a root. Panics for Smi roots.
+    pub fn root_object(&self, handle: RootHandle) -> HeapObject {
+        self.root(handle)
+            .get_heap_object()
+            .expect("root does not refer to an object")
     }
-}
 
-mod src_sandbox {
-    pub mod code_pointer_table {
-        // Placeholder
+    pub fn set_root(&self, handle: RootHandle, value: Tagged) {
+        self.roots.borrow_mut()[handle.0] = value;
     }
-    pub mod external_pointer_table {
-        // Placeholder
+
+    pub fn dispose_root(&self, handle: RootHandle) {
+        self.roots.borrow_mut()[handle.0] = Tagged::ZERO;
+        self.free_roots.borrow_mut().push(handle.0);
     }
-    pub mod js_dispatch_table {
-        // Placeholder
+
+    /// Invokes `f` on every root slot. Must not be called re-entrantly.
+    pub fn iterate_roots(&self, mut f: impl FnMut(ObjectSlot)) {
+        let mut roots = self.roots.borrow_mut();
+        for root in roots.iter_mut() {
+            f(ObjectSlot::new(root as *mut Tagged as Address));
+        }
     }
-    pub mod trusted_pointer_table {
-        // Placeholder
+
+    /// Invokes `f` on every weak root slot: the native context list and the
+    /// contexts of memory measurement requests. Weak roots neither keep
+    /// their targets alive nor refer to young objects. Must not be called
+    /// re-entrantly.
+    pub fn iterate_weak_roots(&self, mut f: impl FnMut(ObjectSlot)) {
+        let mut native_contexts = self.native_contexts.borrow_mut();
+        for context in native_contexts.iter_mut() {
+            f(ObjectSlot::new(context as *mut Tagged as Address));
+        }
+        self.memory_measurement.iterate_weak_roots(f);
     }
-}
 
-mod src_utils {
-    pub mod allocation {
-        // Placeholder
+    /// Invokes `f` on the slot of every traced handle in use. Traced
+    /// handles are roots unless a `CppHeap` is attached, in which case
+    /// cppgc marking reports the live ones.
+    pub fn iterate_traced_handles(&self, f: impl FnMut(ObjectSlot)) {
+        self.traced_handles.iterate(f);
+    }
+
+    // Allocation.
+
+    /// Allocates `size` bytes in the given generation and returns the
+    /// uninitialized memory. Young allocations that do not fit trigger a
+    /// scavenge and fall back to old space if they still do not fit.
+    ///
+    /// Old-generation allocations beyond the allocation limit trigger a
+    /// mark-compact. While incremental marking is running, allocations
+    /// advance marking and old-generation allocations are marked.
+    /// Allocation observers are stepped with the new object.
+    ///
+    /// Within an `AlwaysAllocateScope` allocations neither collect garbage
+    /// nor drive incremental marking; they still fail fatally beyond the
+    /// maximum old-generation size.
+    pub fn allocate_raw(&self, size: usize, allocation: AllocationType) -> Address {
+        debug_assert_eq!(self.gc_state(), HeapState::NOT_IN_GC, "allocation during GC");
+        debug_assert_eq!(size % K_TAGGED_SIZE, 0);
+        if !self.always_allocate() {
+            self.stress_gc_on_allocation(size, allocation);
+            self.incremental_marking.advance_on_allocation(self, size);
+        }
+        let address = self.allocate_raw_with_retry(size, allocation);
+        self.step_allocation_observers(address, size);
+        address
     }
-}
 
-// Testing only
-mod testing {
-    pub mod gtest {
-        pub mod include {
-            pub mod gtest {
-                // Placeholder
+    fn allocate_raw_with_retry(&self, size: usize, allocation: AllocationType) -> Address {
+        if size > K_MAX_REGULAR_HEAP_OBJECT_SIZE {
+            return self.allocate_raw_old_generation(size, AllocationSpace::LO_SPACE);
+        }
+        if allocation == AllocationType::kYoung {
+            if let Some(address) = self.allocate_raw_young(size) {
+                return address;
+            }
+            if !self.always_allocate() {
+                self.collect_garbage(AllocationSpace::NEW_SPACE, GarbageCollectionReason::kAllocationFailure);
+                if let Some(address) = self.allocate_raw_young(size) {
+                    return address;
+                }
             }
         }
+        self.allocate_raw_old_generation(size, AllocationSpace::OLD_SPACE)
     }
-}
 
-mod cppgc {
-    pub mod internal {
-        pub enum HeapObjectNameForUnnamedObject { }
-        pub struct ClassNameAsHeapObjectNameScope {}
+    fn step_allocation_observers(&self, soon_object: Address, size: usize) {
+        let mut counter = self.allocation_counter.borrow_mut();
+        if !counter.is_active() || self.pause_allocation_observers_depth.get() > 0 {
+            return;
+        }
+        if size < counter.next_bytes() {
+            counter.advance_allocation_observers(size);
+        } else {
+            counter.invoke_allocation_observers(soon_object, size, size);
+            counter.advance_allocation_observers(size);
+        }
     }
-}
 
-mod heap_base {
-    pub mod stack {
-        pub struct Stack {}
-        pub struct StackVisitor {}
+    /// Forces the garbage collections requested by
+    /// `HeapOptions::stress_scavenge` and `HeapOptions::gc_interval`.
+    fn stress_gc_on_allocation(&self, size: usize, allocation: AllocationType) {
+        let young = allocation == AllocationType::kYoung && size <= K_MAX_REGULAR_HEAP_OBJECT_SIZE;
+        if let Some(observer) = self.stress_scavenge_observer.as_ref().filter(|_| young) {
+            observer.step(self);
+            if observer.has_requested_gc() {
+                self.collect_garbage(AllocationSpace::NEW_SPACE, GarbageCollectionReason::kStressTesting);
+                observer.requested_gc_done();
+            }
+        }
+        if let Some(interval) = self.options.gc_interval {
+            let remaining = self.allocations_until_gc.get() - 1;
+            if remaining > 0 {
+                self.allocations_until_gc.set(remaining);
+                return;
+            }
+            self.allocations_until_gc.set(interval.max(1));
+            let space = if young {
+                AllocationSpace::NEW_SPACE
+            } else {
+                AllocationSpace::OLD_SPACE
+            };
+            self.collect_garbage(space, GarbageCollectionReason::kStressTesting);
+        }
     }
-}
 
-mod debug {
-    pub type OutOfMemoryCallback = fn(data: *mut std::ffi::c_void);
-}
+    fn allocate_raw_old_generation(&self, size: usize, space: AllocationSpace) -> Address {
+        let old_generation_size = self.old_generation_size_of_objects();
+        if self.always_allocate() {
+            if !self.can_expand_old_generation(size) {
+                Self::fatal_process_out_of_memory("Heap::allocate_raw_old_generation");
+            }
+        } else if old_generation_size + size > self.old_generation_allocation_limit() {
+            self.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kAllocationFailure);
+            if !self.can_expand_old_generation(size)
+                && (!self.invoke_near_heap_limit_callback() || !self.can_expand_old_generation(size))
+            {
+                Self::fatal_process_out_of_memory("Heap::allocate_raw_old_generation");
+            }
+        } else if old_generation_size / Self::K_OLD_GENERATION_LIMIT_CHECK_INTERVAL
+            != (old_generation_size + size) / Self::K_OLD_GENERATION_LIMIT_CHECK_INTERVAL
+        {
+            self.start_incremental_marking_if_allocation_limit_is_reached();
+        }
+        let address = match space {
+            AllocationSpace::LO_SPACE => self.lo_space.allocate_raw(size),
+            _ => {
+                self.sweeper.refill_free_list(&self.old_space);
+                self.old_space.allocate_raw(size)
+            }
+        };
+        if self.incremental_marking.black_allocation() {
+            // SAFETY: only the mark bit of the new object is accessed.
+            let object = unsafe { HeapObject::from_address(address) };
+            MarkingState::try_mark_and_account_live_bytes(object, size);
+        }
+        address
+    }
 
-mod heap {
-    pub struct HeapTester {}
-    pub struct TestMemoryAllocatorScope {}
-}
+    fn fatal_process_out_of_memory(location: &str) -> ! {
+        panic!("Fatal JavaScript out of memory: {location}: reached heap limit");
+    }
 
-mod internal {
-    pub struct ArrayBufferCollector {}
-    pub struct ArrayBufferSweeper {}
-    pub struct BackingStore {}
-    pub struct MemoryChunkMetadata {}
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct Boolean {}
-    pub struct CodeLargeObjectSpace {}
-    pub struct CodeRange {}
-    pub struct CollectionBarrier {}
-    pub struct ConcurrentMarking {}
-    pub struct CppHeap {}
-    pub struct EphemeronRememberedSet {}
-    pub struct GCTracer {}
-    pub struct IncrementalMarking {}
-    pub struct IsolateSafepoint {}
-    pub struct HeapObjectAllocationTracker {}
-    pub struct HeapObjectsFilter {}
-    pub struct HeapProfiler {}
-    pub struct HeapStats {}
-    pub struct Isolate {}
-    pub struct JSArrayBuffer {}
-    pub struct JSFinalizationRegistry {}
-    pub struct JSPromise {}
-    pub struct LinearAllocationArea {}
-    pub struct LocalHeap {}
-    pub struct MemoryAllocator {}
-    pub struct MemoryBalancer {}
-    pub struct MutablePageMetadata {}
-    pub struct MemoryMeasurement {}
-    pub struct MemoryReducer {}
-    pub struct MinorMarkSweepCollector {}
-    pub struct NativeContext {}
-    pub struct NopRwxMemoryWriteScope {}
-    pub struct ObjectIterator {}
-    pub struct ObjectStats {}
-    pub struct PageMetadata {}
-    pub struct PagedSpace {}
-    pub struct PagedNewSpace {}
-    pub struct ReadOnlyHeap {}
-    pub struct RootVisitor {}
-    pub struct RwxMemoryWriteScope {}
-    pub struct SafepointScope {}
-    pub struct Scavenger {}
-    pub struct ScavengerCollector {}
-    pub struct SemiSpaceNewSpace {}
-    pub struct SharedLargeObjectSpace {}
-    pub struct SharedReadOnlySpace {}
-    pub struct SharedSpace {}
-    pub struct SharedTrustedLargeObjectSpace {}
-    pub struct SharedTrustedSpace {}
-    pub struct Space {}
-    pub struct StickySpace {}
-    pub struct StressScavengeObserver {}
-    pub struct TimedHistogram {}
-    pub struct TrustedLargeObjectSpace {}
-    pub struct TrustedRange {}
-    pub struct TrustedSpace {}
-    pub struct WeakObjectRetainer {}
-    pub struct GlobalHandleVector<T> {
-        _phantom: PhantomData<T>
-    }
-    impl<T> GlobalHandleVector<T> {
-        pub fn new() -> Self {
-          GlobalHandleVector{_phantom: PhantomData}
-        }
-    }
-
-    use src_objects::heap_object::{Tagged, HeapObject, Object};
-    use src_base::enum_set::EnumSet;
-    use v8_isolate::{MemoryPressureLevel};
-    use std::cell::Cell;
-
-    pub enum ClearRecordedSlots { kYes, kNo }
-
-    pub enum InvalidateRecordedSlots { kYes, kNo }
-
-    pub enum InvalidateExternalPointerSlots { kYes, kNo }
-
-    pub enum ClearFreedMemoryMode { kClearFreedMemory, kDontClearFreedMemory }
-
-    pub enum SkipRoot {
-        kExternalStringTable,
-        kGlobalHandles,
-        kTracedHandles,
-        kOldGeneration,
-        kStack,
-        kMainThreadHandles,
-        kUnserializable,
-        kWeak,
-        kConservativeStack,
-        kReadOnlyBuiltins,
-    }
-
-    pub enum EmbedderStackStateOrigin {
-      kImplicitThroughTask,
-      kExplicitInvocation,
-    }
-
-    pub struct StrongRootsEntry {
-        label: &'static str,
-        start: *mut HeapObject, //Placeholder
-        end: *mut HeapObject,   //Placeholder
-        prev: *mut StrongRootsEntry,
-        next: *mut StrongRootsEntry,
-    }
-
-    impl StrongRootsEntry {
-        fn new(label: &'static str) -> Self {
-            StrongRootsEntry {
-                label,
-                start: std::ptr::null_mut(),
-                end: std::ptr::null_mut(),
-                prev: std::ptr::null_mut(),
-                next: std::ptr::null_mut(),
-            }
+    fn allocate_raw_young(&self, size: usize) -> Option<Address> {
+        let mut lab = self.new_allocation_info.borrow_mut();
+        if lab.can_increment_top(size) {
+            return Some(lab.increment_top(size));
         }
+        self.new_space.free_lab_remainder(lab.top(), lab.limit());
+        lab.reset(0, 0);
+        let (start, end) = self.new_space.allocate_lab(size, size.max(Self::K_LAB_SIZE))?;
+        lab.reset(start, end);
+        Some(lab.increment_top(size))
     }
 
-    pub type UnorderedHeapObjectMap<T> = HashMap<Tagged<HeapObject>, T>;
+    /// Returns the unused part of the mutator's LAB to new space so that
+    /// to-space is iterable.
+    pub fn free_main_thread_linear_allocation_area(&self) {
+        let mut lab = self.new_allocation_info.borrow_mut();
+        self.new_space.free_lab_remainder(lab.top(), lab.limit());
+        lab.reset(0, 0);
+    }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum GCFlag {
-        kNoFlags = 0,
-        kReduceMemoryFootprint = 1 << 0,
-        kForced = 1 << 1,
-        kLastResort = 1 << 2,
+    fn allocate_object(&self, map: &'static Map, length: usize, allocation: AllocationType) -> HeapObject {
+        let address = self.allocate_raw(map.object_size(length), allocation);
+        // SAFETY: the memory was just allocated for an object of this size.
+        unsafe { initialize_object_header(address, map, length) }
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
-    pub struct GCFlags(u8);
+    /// Allocates an object of `map` and initializes its body: data bodies
+    /// are zeroed, tagged slots hold Smi zeros and ephemeron entries are
+    /// cleared. `length` counts bytes for maps with a byte length.
+    pub fn allocate_with_map(&self, map: &'static Map, length: usize, allocation: AllocationType) -> HeapObject {
+        assert!(!map.is_filler(), "{} cannot be allocated", map.name());
+        let object = self.allocate_object(map, length, allocation);
+        match map.body_kind() {
+            BodyKind::Data => object.data().fill(0),
+            BodyKind::Strong | BodyKind::MaybeWeak => Self::fill_body(object, length, Tagged::ZERO),
+            BodyKind::Ephemeron => Self::fill_body(object, length, Tagged::CLEARED),
+        }
+        object
+    }
 
-    impl GCFlags {
-        pub const kNoFlags: GCFlags = GCFlags(0);
+    /// Allocates a native context with `length` slots of Smi zeros in old
+    /// space. The heap tracks native contexts weakly, see
+    /// `native_contexts()`.
+    pub fn allocate_native_context(&self, length: usize) -> HeapObject {
+        let object = self.allocate_object(&NATIVE_CONTEXT_MAP, length, AllocationType::kOld);
+        Self::fill_body(object, length, Tagged::ZERO);
+        self.native_contexts.borrow_mut().push(Tagged::weak(object));
+        object
+    }
 
-        pub fn contains(&self, flag: GCFlag) -> bool {
-            (self.0 & (flag as u8)) != 0
+    /// Returns the native contexts that were not collected yet.
+    pub fn native_contexts(&self) -> Vec<HeapObject> {
+        self.native_contexts
+            .borrow()
+            .iter()
+            .filter_map(|context| context.get_heap_object())
+            .collect()
+    }
+
+    /// Allocates an API wrapper object with `length` body slots of Smi
+    /// zeros. Slot `K_CPP_HEAP_WRAPPABLE_INDEX` is reserved for the
+    /// wrappable, see `set_wrappable()`.
+    pub fn allocate_js_api_object(&self, length: usize, allocation: AllocationType) -> HeapObject {
+        assert!(length > K_CPP_HEAP_WRAPPABLE_INDEX, "API objects need a wrappable slot");
+        self.allocate_with_map(&JS_API_OBJECT_MAP, length, allocation)
+    }
+
+    /// Makes the API object `wrapper` wrap the object `wrappable` of the
+    /// attached `CppHeap`, which keeps it alive as long as `wrapper` is.
+    /// A null `wrappable` clears the slot.
+    pub fn set_wrappable(&self, wrapper: HeapObject, wrappable: *const u8) {
+        assert_eq!(wrapper.map().instance_type(), InstanceType::JS_API_OBJECT_TYPE);
+        assert_eq!(wrappable as usize % K_TAGGED_SIZE, 0, "wrappables must be aligned");
+        let cpp_heap = self.cpp_heap();
+        assert!(
+            wrappable.is_null() || cpp_heap.is_some(),
+            "wrapping requires an attached CppHeap"
+        );
+        // The aligned pointer reads as a Smi, so no write barrier is needed
+        // on the V8 side.
+        wrapper
+            .raw_field(K_CPP_HEAP_WRAPPABLE_INDEX)
+            .store(Tagged::from_raw(wrappable as usize));
+        let Some(cpp_heap) = cpp_heap else {
+            return;
+        };
+        let remembered_set = cpp_heap.cross_heap_remembered_set();
+        remembered_set.forget_references_of(wrapper.address());
+        if wrappable.is_null() {
+            return;
+        }
+        remembered_set.remember_reference_if_needed(wrapper.address(), wrappable);
+        if self.incremental_marking.is_marking() {
+            // The wrapper may have been visited already.
+            cpp_heap.cpp_marking_state().mark_and_push(wrappable);
         }
     }
 
-    impl From<GCFlag> for GCFlags {
-        fn from(flag: GCFlag) -> Self {
-            GCFlags(flag as u8)
+    /// Returns the wrappable of the API object `wrapper`, if any.
+    pub fn wrappable(&self, wrapper: HeapObject) -> Option<*const u8> {
+        debug_assert_eq!(wrapper.map().instance_type(), InstanceType::JS_API_OBJECT_TYPE);
+        match wrapper.get(K_CPP_HEAP_WRAPPABLE_INDEX).raw() {
+            0 => None,
+            wrappable => Some(wrappable as *const u8),
         }
     }
 
-    impl std::ops::BitOr for GCFlags {
-        type Output = Self;
+    /// Allocates a fixed array of `length` Smi zeros.
+    pub fn allocate_fixed_array(&self, length: usize, allocation: AllocationType) -> HeapObject {
+        let object = self.allocate_object(&FIXED_ARRAY_MAP, length, allocation);
+        Self::fill_body(object, length, Tagged::ZERO);
+        object
+    }
 
-        fn bitor(self, other: Self) -> Self {
-            GCFlags(self.0 | other.0)
-        }
+    /// Allocates a fixed array of `length` Smi zeros whose elements may
+    /// hold weak references.
+    pub fn allocate_weak_fixed_array(&self, length: usize, allocation: AllocationType) -> HeapObject {
+        let object = self.allocate_object(&WEAK_FIXED_ARRAY_MAP, length, allocation);
+        Self::fill_body(object, length, Tagged::ZERO);
+        object
     }
 
-    // Placeholder for Allocationspace and GarbageCollector enums
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum AllocationSpace {}
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum GarbageCollector {
-        SCAVENGER,
-        MARK_COMPACTOR,
-        MINOR_MARK_SWEEPER
+    /// Allocates a zeroed byte array of `length` bytes.
+    pub fn allocate_byte_array(&self, length: usize, allocation: AllocationType) -> HeapObject {
+        self.allocate_with_map(&BYTE_ARRAY_MAP, length, allocation)
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum GarbageCollectionReason {}
+    /// Allocates a sequential one-byte string holding `value`; internalized
+    /// strings are the ones owned by a string table.
+    pub fn allocate_string(&self, value: &[u8], internalized: bool, allocation: AllocationType) -> HeapObject {
+        let map = if internalized { &INTERNALIZED_STRING_MAP } else { &STRING_MAP };
+        let object = self.allocate_object(map, value.len(), allocation);
+        object.data().copy_from_slice(value);
+        object
+    }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct GCCallbackFlags {}
+    /// Allocates an ephemeron table with `capacity` empty entries.
+    pub fn allocate_ephemeron_hash_table(&self, capacity: usize, allocation: AllocationType) -> HeapObject {
+        let length = capacity * EphemeronHashTableShape::K_ENTRY_SIZE;
+        let object = self.allocate_object(&EPHEMERON_HASH_TABLE_MAP, length, allocation);
+        Self::fill_body(object, length, Tagged::CLEARED);
+        object
+    }
 
-    impl GCCallbackFlags {
-        pub const kNoGCCallbackFlags: Self = GCCallbackFlags{};
+    fn fill_body(object: HeapObject, length: usize, value: Tagged) {
+        for index in 0..length {
+            object.raw_field(index).store(value);
+        }
+    }
+
+    /// Stores `value` into body word `index` of `host` and records the
+    /// store for the garbage collector.
+    pub fn set(&self, host: HeapObject, index: usize, value: Tagged) {
+        let map = host.map();
+        assert!(index < host.length_for(map), "index {index} out of bounds");
+        let slot = host.raw_field(index);
+        match map.body_kind() {
+            BodyKind::Data => panic!("{} holds no tagged values", map.name()),
+            BodyKind::Strong => {
+                debug_assert!(!value.is_weak(), "weak reference stored in {}", map.name());
+                slot.store(value);
+                WriteBarrier::combined_write_barrier(self, host, slot, value);
+            }
+            BodyKind::MaybeWeak => {
+                slot.store(value);
+                WriteBarrier::combined_write_barrier(self, host, slot, value);
+            }
+            BodyKind::Ephemeron => {
+                slot.store(value);
+                WriteBarrier::combined_ephemeron_write_barrier(self, host, slot, value);
+            }
+        }
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum AllocationAlignment {}
+    // Garbage collection.
 
-    pub enum AllocationType {
-      kYoung,
-      kOld
+    /// Performs a garbage collection for `space`: a scavenge for new space
+    /// unless the old generation cannot absorb the promoted objects, and a
+    /// mark-compact otherwise.
+    pub fn collect_garbage(&self, space: AllocationSpace, gc_reason: GarbageCollectionReason) {
+        assert_eq!(self.gc_state(), HeapState::NOT_IN_GC, "recursive garbage collection");
+        if self.options.verify_heap {
+            self.verify();
+        }
+        match self.select_garbage_collector(space) {
+            GarbageCollector::SCAVENGER => self.scavenge(gc_reason),
+            GarbageCollector::MARK_COMPACTOR => self.mark_compact(gc_reason),
+        }
+        if self.options.verify_heap {
+            self.verify();
+        }
+        self.start_incremental_marking_if_allocation_limit_is_reached();
     }
 
-    pub enum AllocationOrigin {
-      kRuntime
+    /// Verifies the heap and panics with the offending object and slot if
+    /// it is corrupted, see `HeapVerifier`. Completes sweeping.
+    pub fn verify(&self) {
+        if let Err(error) = HeapVerifier::verify_heap(self) {
+            panic!("{error}");
+        }
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum InstanceType {}
+    fn select_garbage_collector(&self, space: AllocationSpace) -> GarbageCollector {
+        if space != AllocationSpace::NEW_SPACE {
+            return GarbageCollector::MARK_COMPACTOR;
+        }
+        if self.old_generation_size_of_objects() + self.new_space.size() > self.old_generation_allocation_limit() {
+            return GarbageCollector::MARK_COMPACTOR;
+        }
+        GarbageCollector::SCAVENGER
+    }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum ElementsKind {}
+    /// Starts incremental marking once the old generation approaches its
+    /// allocation limit.
+    pub fn start_incremental_marking_if_allocation_limit_is_reached(&self) {
+        if self.incremental_marking.can_be_started(self) && self.incremental_marking_limit_reached() {
+            self.incremental_marking.start(self, GarbageCollectionReason::kAllocationLimit);
+        }
+    }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum WriteBarrierMode {}
+    /// Starts incremental marking unless it is running or disabled.
+    pub fn start_incremental_marking(&self, gc_reason: GarbageCollectionReason) {
+        if self.incremental_marking.can_be_started(self) {
+            self.incremental_marking.start(self, gc_reason);
+        }
+    }
 
-    pub enum DevToolsTraceEventScope {}
+    /// Runs the pending incremental marking task, if any; see
+    /// `IncrementalMarkingJob`. Returns false if no task was pending.
+    pub fn run_incremental_marking_task(&self) -> bool {
+        self.incremental_marking_job.run_task(self)
+    }
 
-    pub struct WritableFreeSpace {}
+    /// Runs the timer task of the `MemoryReducer` if it is due. Returns
+    /// false if no task ran.
+    pub fn run_memory_reducer_task(&self) -> bool {
+        self.memory_reducer
+            .as_ref()
+            .is_some_and(|memory_reducer| memory_reducer.run_task(self))
+    }
 
-    pub struct Code {}
+    /// Tells the `MemoryReducer` that the embedder expects garbage, e.g.
+    /// after disposing a context, like `Isolate::ContextDisposedNotification`.
+    pub fn notify_possible_garbage(&self) {
+        if let Some(memory_reducer) = &self.memory_reducer {
+            memory_reducer.notify_possible_garbage(self);
+        }
+    }
 
-    pub struct GcSafeCode {}
+    /// Measures the memory of the native contexts selected by `delegate`,
+    /// see `MemoryMeasurement`.
+    pub fn measure_memory(&self, delegate: Box<dyn MeasureMemoryDelegate>, execution: MeasureMemoryExecution) {
+        let contexts: Vec<HeapObject> = self
+            .native_contexts()
+            .into_iter()
+            .filter(|&context| delegate.should_measure(context))
+            .collect();
+        self.memory_measurement.enqueue_request(delegate, execution, &contexts);
+    }
 
-    pub struct InstructionStream {}
+    /// Runs the pending memory measurement tasks that are due. Returns
+    /// false if no task ran.
+    pub fn run_memory_measurement_tasks(&self) -> bool {
+        self.memory_measurement.run_tasks(self)
+    }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct Map {}
+    /// Finishes sweeping of old space on the main thread.
+    pub fn complete_sweeping(&self) {
+        if !self.sweeper.sweeping_in_progress() {
+            return;
+        }
+        self.sweeper.ensure_completed(&self.old_space);
+        self.tracer
+            .add_scope_sample(ScopeId::MC_BACKGROUND_SWEEPING, self.sweeper.take_background_time());
+    }
 
-    pub struct ArrayList {}
-    pub struct ByteArray {}
-    pub struct FixedArray {}
-    pub struct FixedDoubleArray {}
-    pub struct TransitionArray {}
-    pub struct WeakFixedArray {}
+    /// Number of background tasks sweeping after a mark-compact.
+    pub fn number_of_sweeper_tasks(&self) -> usize {
+        if !self.options.concurrent_sweeping {
+            return 0;
+        }
+        std::thread::available_parallelism().map_or(1, |cores| cores.get().saturating_sub(1).max(1))
+    }
 
-    pub enum TaskPriority {}
+    fn mark_compact(&self, gc_reason: GarbageCollectionReason) {
+        crate::rcs_scope!(self, RuntimeCallCounterId::kGC_MARK_COMPACTOR);
+        self.free_main_thread_linear_allocation_area();
+        let old_generation_size = self.old_generation_size_of_objects();
+        let committed_memory_before = self.committed_memory();
+        let growing_mode = match gc_reason {
+            GarbageCollectionReason::kMemoryReducer => HeapGrowingMode::kMinimal,
+            _ => self.current_heap_growing_mode(),
+        };
+        self.tracer.start_cycle(
+            GarbageCollector::MARK_COMPACTOR,
+            gc_reason,
+            self.size_of_objects(),
+            self.committed_memory(),
+            self.new_space.size(),
+        );
+        self.gc_state.set(HeapState::MARK_COMPACT);
+        let result = self.mark_compact_collector.collect_garbage(self);
+        self.native_contexts.borrow_mut().retain(|context| !context.is_cleared());
+        self.gc_state.set(HeapState::NOT_IN_GC);
+        self.gc_count.set(self.gc_count.get() + 1);
+        self.ms_count.set(self.ms_count.get() + 1);
+        self.promoted_objects_size.set(result.promoted_size);
+        self.semi_space_copied_object_size.set(0);
+        self.tracer.notify_young_survivors(0, result.promoted_size);
+        self.tracer.stop_cycle(self.size_of_objects(), self.committed_memory());
+        self.recompute_limits(old_generation_size, growing_mode);
+        if let Some(memory_reducer) = &self.memory_reducer {
+            memory_reducer.notify_mark_compact(self, committed_memory_before);
+        }
+    }
 
-    pub struct VirtualMemory {}
+    /// Computes the allocation limit of the old generation from its size
+    /// after a mark-compact through the `HeapGrowingPolicy`, and records
+    /// the decision with the tracer.
+    fn recompute_limits(&self, old_generation_size_before_gc: usize, growing_mode: HeapGrowingMode) {
+        let old_generation_size = self.old_generation_size_of_objects();
+        let now = self.tracer.monotonically_increasing_time();
+        let event = self.tracer.last_event();
+        let input = HeapGrowingInput {
+            old_generation_size,
+            allocated_bytes: old_generation_size_before_gc.saturating_sub(self.old_generation_size_at_last_gc.get()),
+            mutator_duration: now.saturating_sub(self.last_mark_compact_time.get()),
+            gc_bytes: event.start_object_size,
+            gc_duration: event.duration() + event.incremental_marking_duration,
+            gc_speed: self.tracer.combined_mark_compact_speed_in_bytes_per_millisecond(),
+            min_old_generation_size: self.options.initial_old_generation_size,
+            max_old_generation_size: self.max_old_generation_size(),
+            new_space_capacity: self.new_space.capacity(),
+            growing_mode,
+        };
+        let mut policy = self.heap_growing_policy.borrow_mut();
+        let limit = policy.allocation_limit(&input).min(input.max_old_generation_size);
+        let previous_limit = self.old_generation_allocation_limit.replace(limit);
+        self.tracer.notify_heap_limit_decision(HeapLimitDecision {
+            kind: HeapLimitKind::kAllocationLimit,
+            source: policy.name(),
+            time: Duration::ZERO,
+            old_generation_size,
+            previous_limit,
+            limit,
+            allocation_rate: Some(input.mutator_speed()),
+            gc_speed: input.gc_speed,
+        });
+        self.old_generation_size_at_last_gc.set(old_generation_size);
+        self.last_mark_compact_time.set(now);
+    }
 
-    pub struct DirectHandle<T> {
-      _phantom: PhantomData<T>
+    fn scavenge(&self, gc_reason: GarbageCollectionReason) {
+        crate::rcs_scope!(self, RuntimeCallCounterId::kGC_SCAVENGER);
+        self.free_main_thread_linear_allocation_area();
+        if self.incremental_marking.is_marking() {
+            // Objects move; concurrent markers must not see them meanwhile.
+            self.concurrent_marking.join();
+            self.marking_barrier.publish();
+        }
+        self.sweeper.refill_free_list(&self.old_space);
+        let young_object_size = self.new_space.size();
+        self.tracer.start_cycle(
+            GarbageCollector::SCAVENGER,
+            gc_reason,
+            self.size_of_objects(),
+            self.committed_memory(),
+            young_object_size,
+        );
+        self.gc_state.set(HeapState::SCAVENGE);
+        let result = ScavengerCollector::new(self).collect_garbage();
+        self.gc_state.set(HeapState::NOT_IN_GC);
+        self.gc_count.set(self.gc_count.get() + 1);
+        self.promoted_objects_size.set(result.promoted_size);
+        self.semi_space_copied_object_size.set(result.copied_size);
+        self.tracer.notify_young_survivors(result.copied_size, result.promoted_size);
+        self.tracer.stop_cycle(self.size_of_objects(), self.committed_memory());
+        if self.incremental_marking.is_marking() {
+            self.concurrent_marking
+                .reschedule_job_if_needed(self.mark_compact_collector.is_compacting());
+        }
     }
-    impl<T> DirectHandle<T> {
-      pub fn new() -> Self {
-        DirectHandle{_phantom: PhantomData}
-      }
+}
+
+impl HasRuntimeCallStats for Heap {
+    fn runtime_call_stats(&self) -> Option<&RuntimeCallStatsTables> {
+        self.runtime_call_stats.get().map(|tables| &**tables)
     }
+}
 
-    pub struct MaybeDirectHandle<T> {
-      _phantom: PhantomData<T>
+impl Drop for Heap {
+    fn drop(&mut self) {
+        self.concurrent_marking.join();
+        self.sweeper.ensure_completed(&self.old_space);
     }
-    impl<T> MaybeDirectHandle<T> {
-      pub fn new() -> Self {
-        MaybeDirectHandle{_phantom: PhantomData}
-      }
-    }
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum StackState {
-      kMayContainHeapPointers
-    }
-    pub struct EmbedderRootsHandler {}
-
-    pub enum ExternalBackingStoreType {}
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub struct Address(usize);
-
-    impl Address {
-        pub fn from_usize(address: usize) -> Self {
-            Address(address)
-        }
-        pub fn as_usize(&self) -> usize {
-            self.0
-        }
-    }
-    pub struct ResourceConstraints {}
-
-    pub struct WasmCanonicalRttsAndJSToWasmWrappers {}
-
-    pub struct Heap {
-        relocation_mutex_: Mutex<()>,
-        isolate_: *mut Isolate,
-        heap_allocator_: *mut MemoryAllocator,
-        code_range_size_: usize,
-        max_semi_space_size_: usize,
-        min_semi_space_size_: usize,
-        initial_semispace_size_: usize,
-        min_old_generation_size_: usize,
-        max_old_generation_size_: AtomicU64,
-        min_global_memory_size_: usize,
-        max_global_memory_size_: usize,
-        initial_max_old_generation_size_: usize,
-        initial_max_old_generation_size_threshold_: usize,
-        initial_old_generation_size_: usize,
-        using_initial_limit_: AtomicBool,
-        initial_size_overwritten_: bool,
-        maximum_committed_: usize,
-        old_generation_capacity_after_bootstrap_: usize,
-        backing_store_bytes_: AtomicU64,
-        survived_since_last_expansion_: usize,
-        always_allocate_scope_count_: AtomicU64,
-        memory_pressure_level_: AtomicU8,
-        near_heap_limit_callbacks_: Vec<(*mut v8_callbacks::NearHeapLimitCallback, *mut std::ffi::c_void)>,
-        contexts_disposed_: i32,
-        new_space_: *mut NewSpace,
-        old_space_: *mut OldSpace,
-        code_space_: *mut CodeSpace,
-        shared_space_: *mut SharedSpace,
-        lo_space_: *mut OldLargeObjectSpace,
-        code_lo_space_: *mut CodeLargeObjectSpace,
-        new_lo_space_: *mut NewLargeObjectSpace,
-        shared_lo_space_: *mut SharedLargeObjectSpace,
-        read_only_space_: *mut ReadOnlySpace,
-        trusted_space_: *mut TrustedSpace,
-        shared_trusted_space_: *mut SharedTrustedSpace,
-        trusted_lo_space_: *mut TrustedLargeObjectSpace,
-        shared_trusted_lo_space_: *mut SharedTrustedLargeObjectSpace,
-        shared_allocation_space_: *mut PagedSpace,
-        shared_lo_allocation_space_: *mut OldLargeObjectSpace,
-        shared_trusted_allocation_space_: *mut SharedTrustedSpace,
-        shared_trusted_lo_allocation_space_: *mut SharedTrustedLargeObjectSpace,
-        space_: [*mut Space; 18], // LAST_SPACE + 1 = 17, but Space type may require 18?
-        main_thread_local_heap_: *mut LocalHeap,
-        gc_state_: AtomicU8,
-        stress_marking_percentage_: i32,
-        stress_scavenge_observer_: *mut StressScavengeObserver,
-        max_marking_limit_reached_: AtomicU64,
-        ms_count_: u32,
-        gc_count_: u32,
-        consecutive_ineffective_mark_compacts_: i32,
-        mmap_region_base_: usize,
-        remembered_unmapped_pages_index_: i32,
-        remembered_unmapped_pages_: [Address; 128], // kRememberedUnmappedPages = 128
-        old_generation_allocation_limit_: AtomicU64,
-        global_allocation_limit_: AtomicU64,
-        native_contexts_list_: AtomicU64,
-        allocation_sites_list_: Tagged<Object>,
-        dirty_js_finalization_registries_list_: Tagged<Object>,
-        dirty_js_finalization_registries_list_tail_: Tagged<Object>,
-        gc_prologue_callbacks_: GCCallbacks,
-        gc_epilogue_callbacks_: GCCallbacks,
-        external_memory_callback_: *mut std::ffi::c_void, // Placeholder
-        deferred_counters_: Vec<v8_isolate::UseCounterFeature>,
-        promoted_objects_size_: usize,
-        promotion_ratio_: f64,
-        promotion_rate_: f64,
-        new_space_surviving_object_size_: usize,
-        previous_new_space_surviving_object_size_: usize,
-        new_space_surviving_rate_: f64,
-        nodes_died_in_new_space_: i32,
-        nodes_copied_in_new_space_: i32,
-        nodes_promoted_: i32,
-        total_gc_time_ms_: Duration,
-        last_gc_time_: f64,
-        tracer_: *mut GCTracer,
-        sweeper_: *mut Sweeper,
-        mark_compact_collector_: *mut MarkCompactCollector,
-        minor_mark_sweep_collector_: *mut MinorMarkSweepCollector,
-        scavenger_collector_: *mut ScavengerCollector,
-        array_buffer_sweeper_: *mut ArrayBufferSweeper,
-        memory_allocator_: *mut MemoryAllocator,
-        incremental_marking_: *mut IncrementalMarking,
-        concurrent_marking_: *mut ConcurrentMarking,
-        memory_measurement_: *mut MemoryMeasurement,
-        memory_reducer_: *mut MemoryReducer,
-        live_object_stats_: *mut ObjectStats,
-        dead_object_stats_: *mut ObjectStats,
-        minor_gc_job_: *mut MinorGCJob,
-        stress_concurrent_allocation_observer_: *mut AllocationObserver,
-        allocation_tracker_for_debugging_: *mut AllocationTrackerForDebugging,
-        ephemeron_remembered_set_: *mut EphemeronRememberedSet,
-        heap_profiler_: *mut HeapProfiler,
-        task_runner_: *mut std::ffi::c_void, //Placeholder
-        code_range_: *mut CodeRange,
-        owning_cpp_heap_: *mut CppHeap,
-        cpp_heap_: *mut CppHeap,
-        embedder_roots_handler_: *mut EmbedderRootsHandler,
-        embedder_stack_state_: StackState,
-        embedder_stack_state_origin_: Option<EmbedderStackStateOrigin>,
-        strong_roots_head_: *mut StrongRootsEntry,
-        strong_roots_mutex_: Mutex<()>,
-        heap_expansion_mutex_: Mutex<()>,
-        need_to_remove_stress_concurrent_allocation_observer_: bool,
-        new_space_allocation_counter_: usize,
-        old_generation_allocation_counter_at_last_gc_: usize,
-        old_generation_size_at_last_gc_: usize,
-        old_generation_wasted_at_last_gc_: usize,
-        embedder_size_at_last_gc_: usize,
-        trace_ring_buffer_: [u8; 512], // kTraceRingBufferSize = 512
-        ring_buffer_full_: bool,
-        ring_buffer_end_: usize,
-        configured_: bool,
-        current_gc_flags_: GCFlags,
-        current_gc_callback_flags_: GCCallbackFlags,
-        safepoint_: *mut IsolateSafepoint,
-        is_current_gc_forced_: bool,
-        is_current_gc_for_heap_profiler_: bool,
-        current_or_last_garbage_collector_: GarbageCollector,
-        external_string_table_: ExternalStringTable,
-        allocation_type_for_in_place_internalizable_strings_: AllocationType,
-        relocation_mutex_: Mutex<()>,
-        collection_barrier_: *mut CollectionBarrier,
-        ignore_local_gc_requests_depth_: i32,
-        gc_callbacks_depth_: i32,
-        deserialization_complete_: bool,
-        max_regular_code_object_size_: i32,
-        inline_allocation_enabled_: bool,
-        pause_allocation_observers_depth_: i32,
-        force_oom_: bool,
-        force_gc_on_next_allocation_: bool,
-        delay_sweeper_tasks_for_testing_: bool,
-        allocation_trackers_: Vec<*mut HeapObjectAllocationTracker>,
-        is_finalization_registry_cleanup_task_posted_: bool,
-        marking_state_: MarkingState,
-        non_atomic_marking_state_: NonAtomicMarkingState,
-        pretenuring_handler_: PretenuringHandler,
-        resize_new_space_mode_: ResizeNewSpaceMode,
-        mb_: *mut MemoryBalancer,
-        load_start_time_ms_: AtomicU64,
-        update_allocation_limits_after_loading_: bool,
-        is_full_gc_during_loading_: bool,
-    }
-
-    impl Heap {
-        const kPointerMultiplier: usize = 2;
-        const kHeapLimitMultiplier: usize = 2;
-        const kMaxInitialOldGenerationSize: usize = 256 * 1024 * 1024 * Heap::kHeapLimitMultiplier;
-        const kPhysicalMemoryToOldGenerationRatio: usize = 4;
-        const kOldGenerationLowMemory: usize = 128 * 1024 * 1024 * Heap::kHeapLimitMultiplier;
-        const kNewLargeObjectSpaceToSemiSpaceRatio: usize = 1;
-        const kTraceRingBufferSize: usize = 512;
-        const kStacktraceBufferSize: usize = 512;
-        const kMinObjectSizeInTaggedWords: usize = 2;
-        const kInitialEvalCacheSize: usize = 64;
-        const kInitialNumberStringCacheSize: usize = 256;
-        const kRememberedUnmappedPages: usize = 128;
-        const kYoungSurvivalRateHighThreshold: i32 = 90;
-        const kYoungSurvivalRateAllowedDeviation: i32 = 15;
-        const kOldSurvivalRateLowThreshold: i32 = 10;
-        const kMaxMarkCompactsInIdleRound: i32 = 7;
-        const kRetainMapEntrySize: i32 = 2;
-        const kLoadTimeNotLoading: f64 = -1.0;
-
-        pub fn new() -> Self {
-            Heap {
-                relocation_mutex_: Mutex::new(()),
-                isolate_: std::ptr::null_mut(),
-                heap_allocator_: std::ptr::null_mut(),
-                code_range_size_: 0,
-                max_semi_space_size_: 0,
-                min_semi_space_size_: 0,
-                initial_semispace_size_: 0,
-                min_old_generation_size_: 0,
-                max_old_generation_size_: AtomicU64::new(0),
-                min_global_memory_size_: 0,
-                max_global_memory_size_: 0,
-                initial_max_old_generation_size_: 0,
-                initial_max_old_generation_size_threshold_: 0,
-                initial_old_generation_size_: 0,
-                using_initial_limit_: AtomicBool::new(true),
-                initial_size_overwritten_: false,
-                maximum_committed_: 0,
-                old_generation_capacity_after_bootstrap_: 0,
-                backing_store_bytes_: AtomicU64::new(0),
-                survived_since_last_expansion_: 0,
-                always_allocate_scope_count_: AtomicU64::new(0),
-                memory_pressure_level_: AtomicU8::new(0),
-                near_heap_limit_callbacks_: Vec::new(),
-                contexts_disposed_: 0,
-                new_space_: std::ptr::null_mut(),
-                old_space_: std::ptr::null_mut(),
-                code_space_: std::ptr::null_mut(),
-                shared_space_: std::ptr::null_mut(),
-                lo_space_: std::ptr::null_mut(),
-                code_lo_space_: std::ptr::null_mut(),
-                new_lo_space_: std::ptr::null_mut(),
-                shared_lo_space_: std::ptr::null_mut(),
-                read_only_space_: std::ptr::null_mut(),
-                trusted_space_: std::ptr::null_mut(),
-                shared_trusted_space_: std::ptr::null_mut(),
-                trusted_lo_space_: std::ptr::null_mut(),
-                shared_trusted_lo_space_: std::ptr::null_mut(),
-                shared_allocation_space_: std::ptr::null_mut(),
-                shared_lo_allocation_space_: std::ptr::null_mut(),
-                shared_trusted_allocation_space_: std::ptr::null_mut(),
-                shared_trusted_lo_allocation_space_: std::ptr::null_mut(),
-                space_: [std::ptr::null_mut(); 18],
-                main_thread_local_heap_: std::ptr::null_mut(),
-                gc_state_: AtomicU8::new(0),
-                stress_marking_percentage_: 0,
-                stress_scavenge_observer_: std::ptr::null_mut(),
-                max_marking_limit_reached_: AtomicU64::new(0),
-                ms_count_: 0,
-                gc_count_: 0,
-                consecutive_ineffective_mark_compacts_: 0,
-                mmap_region_base_: 0,
-                remembered_unmapped_pages_index_: 0,
-                remembered_unmapped_pages_: [Address::from_usize(0); 128],
-                old_generation_allocation_limit_: AtomicU64::new(0),
-                global_allocation_limit_: AtomicU64::new(0),
-                native_contexts_list_: AtomicU64::new(0),
-                allocation_sites_list_: Tagged { ptr: std::ptr::null_mut(), _phantom: PhantomData },
-                dirty_js_finalization_registries_list_: Tagged { ptr: std::ptr::null_mut(), _phantom: PhantomData },
-                dirty_js_finalization_registries_list_tail_: Tagged { ptr: std::ptr::null_mut(), _phantom: PhantomData },
-                gc_prologue_callbacks_: GCCallbacks::new(),
-                gc_epilogue_callbacks_: GCCallbacks::new(),
-                external_memory_callback_: std::ptr::null_mut(),
-                deferred_counters_: Vec::new(),
-                promoted_objects_size_: 0,
-                promotion_ratio_: 0.0,
-                promotion_rate_: 0.0,
-                new_space_surviving_object_size_: 0,
-                previous_new_space_surviving_object_size_: 0,
-                new_space_surviving_rate_: 0.0,
-                nodes_died_in_new_space_: 0,
-                nodes_copied_in_new_space_: 0,
-                nodes_promoted_: 0,
-                total_gc_time_ms_: Duration::from_millis(0),
-                last_gc_time_: 0.0,
-                tracer_: std::ptr::null_mut(),
-                sweeper_: std::ptr::null_mut(),
-                mark_compact_
\ No newline at end of file
+}
@@ -2,110 +2,78 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use v8::Platform;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
 
-mod v8 {
-    pub trait Platform {
-        fn GetCurrentTimeTicks() -> std::time::Instant;
-    }
-}
-
-mod base {
-    pub mod platform {
-        pub type TimeDelta = std::time::Duration;
-        pub type TimeTicks = std::time::Instant;
-        pub use std::sync::Mutex;
-    }
-}
-
-#[derive(Debug, Clone, Copy)]
-pub enum TaskPriority {
-    kUserBlocking,
-}
-
-pub trait TaskRunner {
-    fn PostTask(&self, task: Box<dyn FnOnce()>);
-    fn PostDelayedTask(&self, task: Box<dyn FnOnce()>, delay: std::time::Duration);
-}
+use crate::heap::gc_tracer::GarbageCollectionReason;
+use crate::heap::heap::Heap;
+use crate::heap::memory_chunk::AllocationSpace;
 
+/// Schedules incremental marking steps as tasks on the main thread, so that
+/// marking makes progress while the mutator is idle and does not allocate.
+/// A scheduled task runs on the next `Heap::run_incremental_marking_task()`.
 pub struct IncrementalMarkingJob {
-    heap_: *mut Heap,
-    user_blocking_task_runner_: Arc<dyn TaskRunner + Send + Sync>,
-    user_visible_task_runner_: Arc<dyn TaskRunner + Send + Sync>,
-    mutex_: Mutex<IncrementalMarkingJobMutexState>,
-}
-
-struct IncrementalMarkingJobMutexState {
-    scheduled_time_: std::time::Instant,
-    pending_task_: bool,
-    time_to_task_samples: Vec<std::time::Duration>,
+    pending_task: Cell<bool>,
+    scheduled_time: Cell<Option<Instant>>,
 }
 
 impl IncrementalMarkingJob {
-    pub fn new(heap: *mut Heap, user_blocking_task_runner: Arc<dyn TaskRunner + Send + Sync>, user_visible_task_runner: Arc<dyn TaskRunner + Send + Sync>) -> Self {
+    /// Bytes marked by a single task.
+    pub const K_STEP_SIZE_IN_BYTES: usize = 512 * 1024;
+
+    pub fn new() -> Self {
         IncrementalMarkingJob {
-            heap_: heap,
-            user_blocking_task_runner_: user_blocking_task_runner,
-            user_visible_task_runner_: user_visible_task_runner,
-            mutex_: Mutex::new(IncrementalMarkingJobMutexState {
-                scheduled_time_: std::time::Instant::now(),
-                pending_task_: false,
-                time_to_task_samples: Vec::new(),
-            }),
+            pending_task: Cell::new(false),
+            scheduled_time: Cell::new(None),
         }
     }
 
-    pub fn schedule_task(&self, priority: TaskPriority) {
-        let task_runner = match priority {
-            TaskPriority::kUserBlocking => self.user_blocking_task_runner_.clone(),
-        };
-
-        let heap_ptr = self.heap_;
-
-        let task = move || {
-            unsafe {
-                // TODO: Implement the task logic here
-                // This is where the incremental marking action would be performed
-                // Access the heap using the raw pointer heap_ptr
-                println!("Incremental marking task executed");
-            }
-        };
-        let task_boxed = Box::new(task);
-
-        let mut mutex_guard = self.mutex_.lock().unwrap();
-        if !mutex_guard.pending_task_ {
-            mutex_guard.pending_task_ = true;
-            mutex_guard.scheduled_time_ = std::time::Instant::now();
-
-            let task_runner_clone = task_runner.clone();
-            std::thread::spawn(move || {
-                task_runner_clone.PostTask(task_boxed);
-            });
+    /// Schedules a task unless one is pending already.
+    pub fn schedule_task(&self) {
+        if self.pending_task.replace(true) {
+            return;
         }
+        self.scheduled_time.set(Some(Instant::now()));
     }
 
-    pub fn average_time_to_task(&self) -> Option<std::time::Duration> {
-        let mutex_guard = self.mutex_.lock().unwrap();
-        if mutex_guard.time_to_task_samples.is_empty() {
-            return None;
-        }
+    pub fn is_task_pending(&self) -> bool {
+        self.pending_task.get()
+    }
 
-        let sum: std::time::Duration = mutex_guard.time_to_task_samples.iter().sum();
-        let count = mutex_guard.time_to_task_samples.len() as u32;
-        Some(sum / count)
+    /// Time since the pending task was scheduled.
+    pub fn current_time_to_task(&self) -> Option<Duration> {
+        self.is_task_pending()
+            .then(|| self.scheduled_time.get().map(|time| time.elapsed()))
+            .flatten()
     }
 
-    pub fn current_time_to_task(&self) -> Option<std::time::Duration> {
-        let mutex_guard = self.mutex_.lock().unwrap();
-        if mutex_guard.pending_task_ {
-            Some(std::time::Instant::now().duration_since(mutex_guard.scheduled_time_))
+    /// Runs the pending task: starts marking once the allocation limit is
+    /// reached, or performs a step and finalizes marking once it is
+    /// complete. Returns false if no task was pending.
+    pub fn run_task(&self, heap: &Heap) -> bool {
+        if !self.pending_task.replace(false) {
+            return false;
+        }
+        self.scheduled_time.set(None);
+        let incremental_marking = heap.incremental_marking();
+        if incremental_marking.is_stopped() {
+            if heap.incremental_marking_limit_reached() && incremental_marking.can_be_started(heap) {
+                incremental_marking.start(heap, GarbageCollectionReason::kTask);
+            }
+            return true;
+        }
+        incremental_marking.step(heap, Self::K_STEP_SIZE_IN_BYTES);
+        if incremental_marking.should_finalize() {
+            heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kFinalizeMarkingViaTask);
         } else {
-            None
+            self.schedule_task();
         }
+        true
     }
 }
 
-// Dummy Heap type, replace with actual heap implementation
-pub struct Heap {}
\ No newline at end of file
+impl Default for IncrementalMarkingJob {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,841 +2,200 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// Note: Many parts of the original C++ code rely on V8's internal structures and APIs.
-// A direct translation is not possible without access to those internal details.
-// The following code provides a high-level, conceptual translation, omitting details
-// that are specific to V8's implementation.
-
-use std::sync::{Mutex, Arc};
-use std::time::Duration;
-use std::{collections::HashMap, hint::black_box};
-
-// Placeholder types and functions
-type Address = usize;
-type SizeT = usize;
-type IntptrT = isize;
-type Tagged<T> = *mut T;
-type HeapObject = u8; // Placeholder
-type Map = u8; // Placeholder
-type Isolate = u8; // Placeholder
-type Heap = u8; // Placeholder
-type MarkCompactCollector = u8; // Placeholder
-type MinorMarkSweepCollector = u8; // Placeholder
-type WeakObjects = u8; // Placeholder
-type MarkingState = u8; // Placeholder
-type MemoryChunk = u8; // Placeholder
-type MutablePageMetadata = u8; // Placeholder
-type PagedSpace = u8; // Placeholder
-type LocalHeap = u8; // Placeholder
-type CppHeap = u8; // Placeholder
-type Safepoint = u8; // Placeholder
-type SpaceId = u8; // Placeholder
-type Sweeper = u8; // Placeholder
-type HeapLayout = u8; // Placeholder
-type DescriptorArray = u8; // Placeholder
-type SharedSpaceIsolate = u8;
-type ExternalPointerHandle = u8;
-type PageMetadata = u8;
-type MapWord = u8;
-type RootMarkingVisitor = u8;
-type YoungGenerationRootMarkingVisitor = u8;
-type GlobalHandles = u8;
-type TracedHandles = u8;
-type ConcurrentMarking = u8;
-type Allocator = u8;
-type OldExternalPointerSpace = u8;
-type CppHeapPointerSpace = u8;
-type CodePointerSpace = u8;
-type TrustedPointerSpace = u8;
-type JsDispatchTableSpace = u8;
-type SharedTrustedPointerSpace = u8;
-type ExternalPointerTable = u8;
-type YoungExternalPointerSpace = u8;
-
-const KB: usize = 1024;
-const MB: usize = 1024 * KB;
-const K_NULL_ADDRESS: Address = 0;
-
-// enum class GarbageCollector
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum GarbageCollector {
-  MARK_COMPACTOR,
-  MINOR_MARK_SWEEPER,
-}
-
-// enum class GarbageCollectionReason
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum GarbageCollectionReason {
-  kFinalizeMarkingViaTask,
-  kFinalizeMarkingViaStackGuard,
-  // Add other reasons as needed
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum StepOrigin {
-  kTask,
-  kV8,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum MarkingMode {
-  kNoMarking,
-  kMajorMarking,
-  kMinorMarking,
-}
-
-// Placeholder for Flags
-struct Flags {
-  incremental_marking: bool,
-  incremental_marking_task: bool,
-  trace_incremental_marking: bool,
-  concurrent_marking: bool,
-  concurrent_minor_ms_marking: bool,
-  black_allocated_pages: bool,
-  separate_gc_phases: bool,
-  minor_ms: bool,
-  incremental_marking_unified_schedule: bool,
-  predictable: bool,
-}
-
-// Global flags (replace with proper initialization)
-static mut v8_flags: Flags = Flags {
-  incremental_marking: false,
-  incremental_marking_task: false,
-  trace_incremental_marking: false,
-  concurrent_marking: false,
-  concurrent_minor_ms_marking: false,
-  black_allocated_pages: false,
-  separate_gc_phases: false,
-  minor_ms: false,
-  incremental_marking_unified_schedule: false,
-  predictable: false,
-};
-
-// Placeholder for base::TimeDelta
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
-struct TimeDelta {
-  millis: u64,
-}
-
-impl TimeDelta {
-  fn from_milliseconds(millis: u64) -> Self {
-    TimeDelta { millis }
-  }
-
-  fn max() -> Self {
-    TimeDelta { millis: u64::MAX }
-  }
-
-  fn in_milliseconds_f64(&self) -> f64 {
-    self.millis as f64
-  }
-
-  fn in_milliseconds_f32(&self) -> f32 {
-    self.millis as f32
-  }
-
-  fn in_milliseconds(&self) -> u64 {
-    self.millis
-  }
-}
-
-impl std::ops::Sub for TimeDelta {
-    type Output = Self;
-
-    fn sub(self, other: Self) -> Self {
-        TimeDelta { millis: self.millis.saturating_sub(other.millis) }
-    }
-}
-
-impl std::ops::Add for TimeDelta {
-  type Output = Self;
-
-  fn add(self, other: Self) -> Self {
-    TimeDelta { millis: self.millis + other.millis }
-  }
-}
-
-// Placeholder for base::TimeTicks
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord)]
-struct TimeTicks {
-  nanos: u64,
-}
-
-impl TimeTicks {
-  fn now() -> Self {
-    TimeTicks {
-      nanos: std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_nanos() as u64,
-    }
-  }
-}
-
-impl std::ops::Sub for TimeTicks {
-  type Output = TimeDelta;
-
-  fn sub(self, other: Self) -> Self::Output {
-    TimeDelta { millis: (self.nanos - other.nanos) / 1_000_000 }
-  }
-}
-
-static K_MAX_STEP_SIZE_ON_TASK: TimeDelta = TimeDelta::from_milliseconds(1);
-static K_MAX_STEP_SIZE_ON_ALLOCATION: TimeDelta = TimeDelta::from_milliseconds(5);
-static K_V8_ACTIVATION_THRESHOLD: SizeT = 8 * MB;
-static K_EMBEDDER_ACTIVATION_THRESHOLD: SizeT = 8 * MB;
-static K_MAJOR_GC_YOUNG_GENERATION_ALLOCATION_OBSERVER_STEP: SizeT = 64 * KB;
-static K_MAJOR_GC_OLD_GENERATION_ALLOCATION_OBSERVER_STEP: SizeT = 256 * KB;
-
-fn get_max_duration(step_origin: StepOrigin) -> TimeDelta {
-  unsafe {
-    if v8_flags.predictable {
-      return TimeDelta::max();
-    }
-    match step_origin {
-      StepOrigin::kTask => K_MAX_STEP_SIZE_ON_TASK,
-      StepOrigin::kV8 => K_MAX_STEP_SIZE_ON_ALLOCATION,
-    }
-  }
-}
-
-// Placeholder functions
-fn to_string(reason: GarbageCollectionReason) -> &'static str {
-    match reason {
-        GarbageCollectionReason::kFinalizeMarkingViaTask => "kFinalizeMarkingViaTask",
-        GarbageCollectionReason::kFinalizeMarkingViaStackGuard => "kFinalizeMarkingViaStackGuard",
-    }
-}
-
-fn to_string_step(origin: StepOrigin) -> &'static str {
-  match origin {
-    StepOrigin::kTask => "Task",
-    StepOrigin::kV8 => "V8",
-  }
-}
-
-struct AllocationObserver {
-    step_size: IntptrT,
-}
-
-impl AllocationObserver {
-    fn new(step_size: IntptrT) -> Self {
-        Self { step_size }
-    }
-
-    fn step(&self, count: i32, address: Address, size: SizeT) {
-      black_box((count, address, size));
-    }
-}
-
-struct IncrementalMarkingJob {
-  heap: *mut Heap,
-  // Add fields as needed
-}
-
-impl IncrementalMarkingJob {
-  fn new(heap: *mut Heap) -> Self {
-    IncrementalMarkingJob { heap }
-  }
-
-  fn schedule_task(&self) {
-    // Placeholder: Implement task scheduling logic
-    println!("Scheduling incremental marking task");
-  }
-
-  fn average_time_to_task(&self) -> Option<TimeDelta> {
-      Some(TimeDelta::from_milliseconds(10))
-  }
-
-  fn current_time_to_task(&self) -> Option<TimeDelta> {
-      Some(TimeDelta::from_milliseconds(5))
-  }
-}
-
-struct IncrementalMarking {
-  heap_: *mut Heap,
-  major_collector_: *mut MarkCompactCollector,
-  minor_collector_: *mut MinorMarkSweepCollector,
-  weak_objects_: *mut WeakObjects,
-  marking_state_: *mut MarkingState,
-  incremental_marking_job_: Option<Box<IncrementalMarkingJob>>,
-  new_generation_observer_: AllocationObserver,
-  old_generation_observer_: AllocationObserver,
-  marking_mode_: MarkingMode,
-  is_compacting_: bool,
-  black_allocation_: bool,
-  start_time_: TimeTicks,
-  completion_task_scheduled_: bool,
-  completion_task_timeout_: TimeTicks,
-  major_collection_requested_via_stack_guard_: bool,
-  current_local_marking_worklists_: *mut u8, //Placeholder type
-  background_live_bytes_mutex_: Mutex<HashMap<*mut MutablePageMetadata, IntptrT>>,
-  background_live_bytes_: HashMap<*mut MutablePageMetadata, IntptrT>,
-  schedule_: Option<Arc<IncrementalMarkingSchedule>>,
-  bytes_marked_concurrently_: SizeT,
-  main_thread_marked_bytes_: SizeT,
-  current_trace_id_: Option<u64>,
+use std::cell::Cell;
+use std::time::Instant;
+
+use crate::heap::gc_tracer::{GarbageCollectionReason, ScopeId};
+use crate::heap::heap::{Heap, HeapState};
+use crate::heap::memory_chunk::AllocationSpace;
+use crate::heap::weak_object_worklists::forwarding_address_after_scavenge;
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum State {
+    STOPPED,
+    MARKING,
+    /// All known work is done; the next step or task finalizes marking
+    /// with a mark-compact.
+    COMPLETE,
+}
+
+/// Drives full marking in small steps interleaved with the mutator, so
+/// that the atomic pause of the mark-compact only has to finish marking.
+///
+/// Marking starts when the old generation approaches its allocation limit.
+/// Steps run on allocation, proportionally to the allocated bytes, and
+/// from `IncrementalMarkingJob` tasks; concurrent marking tasks help in
+/// between. While marking, the marking barrier marks all stored objects
+/// and old-generation allocations are black, i.e. marked right away.
+pub struct IncrementalMarking {
+    state: Cell<State>,
+    black_allocation: Cell<bool>,
+    /// Bytes allocated since the last step on allocation.
+    allocated_since_last_step: Cell<usize>,
+    /// Bytes visited by steps on the main thread in the current cycle.
+    bytes_marked: Cell<usize>,
+    start_reason: Cell<Option<GarbageCollectionReason>>,
 }
 
 impl IncrementalMarking {
-  fn new(heap: *mut Heap, weak_objects: *mut WeakObjects) -> Self {
-    unsafe {
-      IncrementalMarking {
-        heap_: heap,
-        major_collector_: (*heap).offset(0) as *mut MarkCompactCollector, //Placeholder
-        minor_collector_: (*heap).offset(0) as *mut MinorMarkSweepCollector, //Placeholder
-        weak_objects_: weak_objects,
-        marking_state_: (*heap).offset(0) as *mut MarkingState, //Placeholder
-        incremental_marking_job_: if v8_flags.incremental_marking_task {
-          Some(Box::new(IncrementalMarkingJob::new(heap)))
-        } else {
-          None
-        },
-        new_generation_observer_: AllocationObserver::new(
-          K_MAJOR_GC_YOUNG_GENERATION_ALLOCATION_OBSERVER_STEP as IntptrT,
-        ),
-        old_generation_observer_: AllocationObserver::new(
-          K_MAJOR_GC_OLD_GENERATION_ALLOCATION_OBSERVER_STEP as IntptrT,
-        ),
-        marking_mode_: MarkingMode::kNoMarking,
-        is_compacting_: false,
-        black_allocation_: false,
-        start_time_: TimeTicks::now(),
-        completion_task_scheduled_: false,
-        completion_task_timeout_: TimeTicks::now(),
-        major_collection_requested_via_stack_guard_: false,
-        current_local_marking_worklists_: std::ptr::null_mut(),
-        background_live_bytes_mutex_: Mutex::new(HashMap::new()),
-        background_live_bytes_: HashMap::new(),
-        schedule_: None,
-        bytes_marked_concurrently_: 0,
-        main_thread_marked_bytes_: 0,
-        current_trace_id_: None,
-      }
-    }
-  }
-
-  fn mark_black_background(&mut self, obj: Tagged<HeapObject>, object_size: i32) {
-    // Placeholder: Implement mark_black_background logic
-    println!("Marking object black in background");
-    unsafe {
-      black_box(obj);
-      black_box(object_size);
-    }
-  }
-
-  fn can_and_should_be_started(&self) -> bool {
-    self.can_be_started() && self.should_use_incremental_marking()
-  }
-
-  fn can_be_started(&self) -> bool {
-    unsafe {
-      v8_flags.incremental_marking
-        && self.gc_state() == 0 // Heap::NOT_IN_GC
-        && self.deserialization_complete()
-        && !self.serializer_enabled()
-    }
-  }
-
-  fn is_below_activation_thresholds(&self) -> bool {
-    self.old_generation_size_of_objects() <= K_V8_ACTIVATION_THRESHOLD
-      && self.embedder_size_of_objects() <= K_EMBEDDER_ACTIVATION_THRESHOLD
-  }
-
-  fn start(
-    &mut self,
-    garbage_collector: GarbageCollector,
-    gc_reason: GarbageCollectionReason,
-  ) {
-    if self.is_stopped() == false {
-        return;
-    }
-
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        let old_generation_size_mb = self.old_generation_size_of_objects() / MB;
-        let old_generation_waste_mb = self.old_generation_wasted_bytes() / MB;
-        let old_generation_limit_mb = self.old_generation_allocation_limit() / MB;
-        let global_size_mb = self.global_size_of_objects() / MB;
-        let global_waste_mb = self.global_wasted_bytes() / MB;
-        let global_limit_mb = self.global_allocation_limit() / MB;
-
-        println!(
-          "[IncrementalMarking] Start ({:?}): (size/waste/limit/slack) v8: {}MB / {}MB / {}MB / {}MB global: {}MB / {}MB / {}MB / {}MB",
-          gc_reason,
-          old_generation_size_mb,
-          old_generation_waste_mb,
-          old_generation_limit_mb,
-          if old_generation_size_mb + old_generation_waste_mb > old_generation_limit_mb {
-            0
-          } else {
-            old_generation_limit_mb - old_generation_size_mb
-          },
-          global_size_mb,
-          global_waste_mb,
-          global_limit_mb,
-          if global_size_mb + global_waste_mb > global_limit_mb {
-            0
-          } else {
-            global_limit_mb - global_size_mb
-          }
-        );
-      }
-    }
-
-    self.start_time_ = TimeTicks::now();
-    self.completion_task_scheduled_ = false;
-    self.completion_task_timeout_ = TimeTicks::now();
-    self.main_thread_marked_bytes_ = 0;
-    self.bytes_marked_concurrently_ = 0;
-
-    if garbage_collector == GarbageCollector::MARK_COMPACTOR {
-      self.start_marking_major();
-    } else {
-      self.start_marking_minor();
+    /// Allocated bytes that trigger a step.
+    pub const K_ALLOCATION_STEP_SIZE: usize = 64 * 1024;
+    pub const K_MIN_STEP_SIZE_IN_BYTES: usize = 64 * 1024;
+    /// Steps on allocation visit this many times the allocated bytes, so
+    /// that marking outpaces the mutator.
+    pub const K_ALLOCATION_STEP_FACTOR: usize = 4;
+
+    pub fn new() -> Self {
+        IncrementalMarking {
+            state: Cell::new(State::STOPPED),
+            black_allocation: Cell::new(false),
+            allocated_since_last_step: Cell::new(0),
+            bytes_marked: Cell::new(0),
+            start_reason: Cell::new(None),
+        }
     }
-  }
 
-  fn mark_roots(&mut self) {
-    if self.is_major_marking() {
-      //Placeholder impl
-    } else {
-      //Placeholder impl
+    pub fn state(&self) -> State {
+        self.state.get()
     }
-  }
-
-  fn mark_roots_for_testing(&mut self) {
-    self.mark_roots();
-  }
-
-  fn start_marking_major(&mut self) {
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] Start marking");
-      }
-      self.marking_mode_ = MarkingMode::kMajorMarking;
-      self.is_compacting_ = true; // Placeholder
-      self.schedule_ = Some(Arc::new(IncrementalMarkingSchedule::create(v8_flags.predictable))); //placeholder
 
-      if let Some(schedule) = &self.schedule_ {
-        self.major_collector_start_marking(schedule);
-      }
-
-      self.current_local_marking_worklists_ = self.get_local_marking_worklists();
-      self.set_is_marking_flag(true);
-      self.start_black_allocation();
-
-      // Simulate marking roots
-      self.mark_roots();
-
-      if v8_flags.concurrent_marking && !self.is_tearing_down() {
-        // self.try_schedule_job(GarbageCollector::MARK_COMPACTOR);
-        println!("[IncrementalMarking] Try scheduling concurrent job");
-      }
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] Running");
-      }
-
-      // self.add_allocation_observer(&self.old_generation_observer_, &self.new_generation_observer_);
-      if let Some(job) = &self.incremental_marking_job_ {
-        job.schedule_task();
-      }
+    pub fn is_stopped(&self) -> bool {
+        self.state() == State::STOPPED
     }
-  }
-
-  fn start_marking_minor(&mut self) {
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] (MinorMS) Start marking");
-      }
-
-      self.marking_mode_ = MarkingMode::kMinorMarking;
-      self.set_is_marking_flag(true);
-      self.set_is_minor_marking_flag(true);
-
-      // Simulate marking roots
-      self.mark_roots();
-
-      if v8_flags.concurrent_minor_ms_marking && !self.is_tearing_down() {
-        println!("[IncrementalMarking] Try scheduling concurrent job");
-      }
-
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] (MinorMS) Running");
-      }
 
-      self.is_compacting_ = false;
+    pub fn is_marking(&self) -> bool {
+        self.state() != State::STOPPED
     }
-  }
 
-  fn start_black_allocation(&mut self) {
-    unsafe {
-      self.black_allocation_ = true;
-      if v8_flags.black_allocated_pages {
-        println!("[IncrementalMarking] Free linear allocation areas and reset free lists");
-      } else {
-        println!("[IncrementalMarking] Mark linear allocation areas black");
-      }
-      self.start_pointer_table_black_allocation();
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] Black allocation started");
-      }
+    pub fn is_complete(&self) -> bool {
+        self.state() == State::COMPLETE
     }
-  }
 
-  fn pause_black_allocation(&mut self) {
-    unsafe {
-      if !v8_flags.black_allocated_pages {
-        println!("[IncrementalMarking] Unmark linear allocations area");
-      }
-      self.stop_pointer_table_black_allocation();
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] Black allocation paused");
-      }
-      self.black_allocation_ = false;
+    pub fn should_finalize(&self) -> bool {
+        self.is_complete()
     }
-  }
 
-  fn finish_black_allocation(&mut self) {
-    if !self.black_allocation_ {
-      return;
+    /// Whether old-generation allocations are marked.
+    #[inline]
+    pub fn black_allocation(&self) -> bool {
+        self.black_allocation.get()
     }
-    self.black_allocation_ = false;
-    self.stop_pointer_table_black_allocation();
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] Black allocation finished");
-      }
-    }
-  }
-
-  fn start_pointer_table_black_allocation(&mut self) {
-    println!("[IncrementalMarking] Start pointer table black allocation");
-  }
-
-  fn stop_pointer_table_black_allocation(&mut self) {
-    println!("[IncrementalMarking] Stop pointer table black allocation");
-  }
 
-  fn update_marking_worklist_after_scavenge(&mut self) {
-    // Placeholder: Implement scavenge logic
-    if !self.is_major_marking() {
-      return;
+    pub fn bytes_marked(&self) -> usize {
+        self.bytes_marked.get()
     }
-    println!("Update marking worklist after scavenge");
-  }
-
-  fn update_external_pointer_table_after_scavenge(&mut self) {
-    // Placeholder: Implement external pointer table update after scavenge
-    println!("Update external pointer table after scavenge");
-  }
-
-  fn update_marked_bytes_after_scavenge(&mut self, dead_bytes_in_new_space: SizeT) {
-      if !self.is_major_marking() {
-        return;
-      }
-      let dead_bytes_marked = std::cmp::min(self.main_thread_marked_bytes_, dead_bytes_in_new_space);
-      if let Some(schedule) = &mut self.schedule_ {
-          schedule.remove_mutator_thread_marked_bytes(dead_bytes_marked);
-      }
-      self.main_thread_marked_bytes_ -= dead_bytes_marked;
-  }
 
-  fn cpp_heap_step(&mut self, max_duration: TimeDelta, marked_bytes_limit: SizeT) -> (TimeDelta, SizeT) {
-    if !self.is_marking() {
-        return (TimeDelta::from_milliseconds(0), 0);
+    pub fn start_reason(&self) -> Option<GarbageCollectionReason> {
+        self.start_reason.get()
     }
-    
-    println!("CppHeapStep");
-    (TimeDelta::from_milliseconds(0), 0) //placeholder
-  }
 
-  fn stop(&mut self) -> bool {
-    if self.is_stopped() {
-        return false;
+    pub fn can_be_started(&self, heap: &Heap) -> bool {
+        heap.options().incremental_marking && self.is_stopped() && heap.gc_state() == HeapState::NOT_IN_GC
     }
 
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        let old_generation_size_mb = (self.old_generation_size_of_objects() / MB) as i32;
-        let old_generation_waste_mb = (self.old_generation_wasted_bytes() / MB) as i32;
-        let old_generation_limit_mb = (self.old_generation_allocation_limit() / MB) as i32;
-        println!(
-          "[IncrementalMarking] Stopping: old generation size {}MB, waste {}MB, limit {}MB, overshoot {}MB",
-          old_generation_size_mb,
-          old_generation_waste_mb,
-          old_generation_limit_mb,
-          std::cmp::max(
-            0,
-            old_generation_size_mb + old_generation_waste_mb - old_generation_limit_mb
-          )
-        );
-      }
-
+    /// Starts a marking cycle: completes sweeping, selects evacuation
+    /// candidates, activates the marking barrier, marks the roots and
+    /// starts concurrent marking.
+    pub fn start(&self, heap: &Heap, gc_reason: GarbageCollectionReason) {
+        debug_assert!(self.can_be_started(heap));
+        let tracer = heap.tracer();
+        tracer.notify_incremental_marking_start();
+        let _scope = tracer.scope(ScopeId::MC_INCREMENTAL_START);
+        heap.complete_sweeping();
+        let collector = heap.mark_compact_collector();
+        let is_compacting = collector.start_compaction(heap);
+        collector.start_marking(heap);
+        self.state.set(State::MARKING);
+        self.black_allocation.set(true);
+        self.allocated_since_last_step.set(0);
+        self.bytes_marked.set(0);
+        self.start_reason.set(Some(gc_reason));
+        heap.concurrent_marking().schedule_job(is_compacting);
+        heap.incremental_marking_job().schedule_task();
     }
 
-    self.marking_mode_ = MarkingMode::kNoMarking;
-    self.current_local_marking_worklists_ = std::ptr::null_mut();
-    self.current_trace_id_ = None;
-
-    self.set_is_marking_flag(false);
-    self.set_is_minor_marking_flag(false);
-    self.is_compacting_ = false;
-    self.finish_black_allocation();
-
-    // Merge live bytes counters of background threads
-    for (memory_chunk, live_bytes) in self.background_live_bytes_.drain() {
-        if live_bytes != 0 {
-          self.increment_live_bytes_atomically(memory_chunk, live_bytes);
+    /// Allocation observer: performs a step for every
+    /// `K_ALLOCATION_STEP_SIZE` allocated bytes and finalizes marking once
+    /// it is complete. Must be called before the allocation happens, when
+    /// the heap is iterable.
+    pub fn advance_on_allocation(&self, heap: &Heap, size: usize) {
+        if !self.is_marking() {
+            return;
         }
-    }
-    self.schedule_ = None;
-
-    true
-  }
-
-  fn old_generation_size_of_objects(&self) -> SizeT {
-    // Placeholder: Implement size calculation
-    0
-  }
-
-  fn should_wait_for_task(&mut self) -> bool {
-    if !self.completion_task_scheduled_ {
-      if self.incremental_marking_job_.is_none() {
-        return false;
-      }
-      if let Some(job) = &self.incremental_marking_job_ {
-          job.schedule_task();
-      }
-      self.completion_task_scheduled_ = true;
-      if !self.try_initialize_task_timeout() {
-        return false;
-      }
-    }
-
-    let now = TimeTicks::now();
-    let wait_for_task = now < self.completion_task_timeout_;
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        println!(
-          "[IncrementalMarking] Completion: {} GC via stack guard, time left: {:.1}ms",
-          if wait_for_task { "Delaying" } else { "Not delaying" },
-          (self.completion_task_timeout_ - now).millis as f64
+        let allocated = self.allocated_since_last_step.get() + size;
+        if allocated < Self::K_ALLOCATION_STEP_SIZE {
+            self.allocated_since_last_step.set(allocated);
+            return;
+        }
+        self.allocated_since_last_step.set(0);
+        self.step(
+            heap,
+            (allocated * Self::K_ALLOCATION_STEP_FACTOR).max(Self::K_MIN_STEP_SIZE_IN_BYTES),
         );
-      }
-    }
-    wait_for_task
-  }
-
-  fn try_initialize_task_timeout(&mut self) -> bool {
-    if self.incremental_marking_job_.is_none() {
-      return false;
-    }
-    // Allowed overshoot percentage of incremental marking walltime.
-    const K_ALLOWED_OVERSHOOT_PERCENT_BASED_ON_WALLTIME: f64 = 0.1;
-    // Minimum overshoot in ms. This is used to allow moving away from stack
-    // when marking was fast.
-    const K_MIN_ALLOWED_OVERSHOOT: TimeDelta = TimeDelta::from_milliseconds(50);
-    let now = TimeTicks::now();
-    let allowed_overshoot = std::cmp::max(
-      K_MIN_ALLOWED_OVERSHOOT,
-      TimeDelta::from_milliseconds(
-        ((now - self.start_time_).millis as f64 * K_ALLOWED_OVERSHOOT_PERCENT_BASED_ON_WALLTIME)
-          as u64,
-      ),
-    );
-    let optional_avg_time_to_marking_task = self.incremental_marking_job_.as_ref().map(|job| job.average_time_to_task()).flatten();
-    // Only allowed to delay if the recorded average exists and is below the
-    // threshold.
-    let mut delaying = optional_avg_time_to_marking_task.is_some()
-      && optional_avg_time_to_marking_task.unwrap() <= allowed_overshoot;
-    let optional_time_to_current_task = self.incremental_marking_job_.as_ref().map(|job| job.current_time_to_task()).flatten();
-    // Don't bother delaying if the currently scheduled task is already waiting
-    // too long.
-    delaying = delaying
-      && (optional_time_to_current_task.is_none()
-        || optional_time_to_current_task.unwrap() <= allowed_overshoot);
-
-    if delaying {
-      let delta = match optional_time_to_current_task {
-        None => allowed_overshoot,
-        Some(task_time) => allowed_overshoot - task_time,
-      };
-      self.completion_task_timeout_ = now + delta;
-    }
-    
-    if unsafe { v8_flags.trace_incremental_marking } {
-      println!(
-        "[IncrementalMarking] Completion: {} GC via stack guard, avg time to task: {:.1}ms, current time to task: {:.1}ms allowed overshoot: {:.1}ms",
-        if delaying { "Delaying" } else { "Not delaying" },
-        optional_avg_time_to_marking_task.map(|t| t.millis as f64).unwrap_or(f64::NAN),
-        optional_time_to_current_task.map(|t| t.millis as f64).unwrap_or(f64::NAN),
-        allowed_overshoot.millis as f64
-      );
-    }
-
-    delaying
-  }
-
-  fn get_scheduled_bytes(&mut self, step_origin: StepOrigin) -> SizeT {
-    self.fetch_bytes_marked_concurrently();
-    let estimated_live_bytes = self.old_generation_size_of_objects();
-    
-    let marked_bytes_limit = if let Some(schedule) = &self.schedule_ {
-        schedule.get_next_incremental_step_duration(estimated_live_bytes)
-    } else {
-        0
-    };
-    
-    unsafe {
-        if v8_flags.trace_incremental_marking {
-            let step_info = self.schedule_.as_ref().map(|s| s.get_current_step_info());
-
-            println!(
-                "[IncrementalMarking] Schedule: {}KB to mark, origin: {}, elapsed: {:.1}, marked: {}KB (mutator: {}KB, concurrent {}KB), expected marked: {}KB, estimated live: {}KB, schedule delta: {}{}KB",
-                marked_bytes_limit / KB,
-                to_string_step(step_origin),
-                step_info.map(|s| s.elapsed_time.millis as f64).unwrap_or(0.0),
-                step_info.map(|s| s.marked_bytes).unwrap_or(0) / KB,
-                step_info.map(|s| s.mutator_marked_bytes).unwrap_or(0) / KB,
-                step_info.map(|s| s.concurrent_marked_bytes).unwrap_or(0) / KB,
-                step_info.map(|s| s.expected_marked_bytes).unwrap_or(0) / KB,
-                step_info.map(|s| s.estimated_live_bytes).unwrap_or(0) / KB,
-                if step_info.is_some() {
-                  if step_info.unwrap().scheduled_delta_bytes().is_negative() { "-" } else { "+" }
-                } else {
-                  ""
-                },
-                step_info.map(|s| s.scheduled_delta_bytes().abs()).unwrap_or(0) / KB
+        if self.should_finalize() {
+            heap.collect_garbage(
+                AllocationSpace::OLD_SPACE,
+                GarbageCollectionReason::kFinalizeMarkingViaStackGuard,
             );
         }
     }
 
-    marked_bytes_limit
-  }
-
-  fn advance_and_finalize_if_complete(&mut self) {
-    let max_bytes_to_process = self.get_scheduled_bytes(StepOrigin::kTask);
-    self.step(get_max_duration(StepOrigin::kTask), max_bytes_to_process, StepOrigin::kTask);
-    if self.is_major_marking_complete() {
-      self.finalize_incremental_marking_atomically(GarbageCollectionReason::kFinalizeMarkingViaTask);
-    }
-  }
-
-  fn advance_and_finalize_if_necessary(&mut self) {
-    if !self.is_major_marking() {
-        return;
-    }
-    self.advance_on_allocation();
-    if self.major_collection_requested_via_stack_guard_ && self.is_major_marking_complete() {
-      self.finalize_incremental_marking_atomically(
-        GarbageCollectionReason::kFinalizeMarkingViaStackGuard,
-      );
-    }
-  }
-
-  fn advance_for_testing(&mut self, max_duration: TimeDelta, max_bytes_to_mark: SizeT) {
-    self.step(max_duration, max_bytes_to_mark, StepOrigin::kV8);
-  }
-
-  fn advance_on_allocation(&mut self) {
-    unsafe {
-        if v8_flags.incremental_marking == false {
-          return;
+    /// Visits up to `max_bytes` bytes of objects on the main thread, and
+    /// traces as many bytes of the attached `CppHeap`. Marking is complete
+    /// once no work is left on the main thread, in concurrent tasks or in
+    /// the `CppHeap`. Returns the number of V8 bytes visited.
+    pub fn step(&self, heap: &Heap, max_bytes: usize) -> usize {
+        debug_assert!(self.is_marking());
+        let start = Instant::now();
+        let collector = heap.mark_compact_collector();
+        heap.marking_barrier().publish();
+        let mut visitor = collector.create_marking_visitor();
+        let cpp_heap = heap.cpp_heap();
+        if let Some(cpp_heap) = cpp_heap {
+            cpp_heap.advance_tracing(max_bytes, &mut visitor);
         }
+        let bytes_marked = visitor.process_marking_worklist(max_bytes);
+        visitor.publish();
+        drop(visitor);
+        self.bytes_marked.set(self.bytes_marked.get() + bytes_marked);
+
+        let concurrent_marking = heap.concurrent_marking();
+        concurrent_marking.reschedule_job_if_needed(collector.is_compacting());
+        if !concurrent_marking.is_working()
+            && collector.marking_worklists().is_empty()
+            && cpp_heap.is_none_or(|cpp_heap| cpp_heap.is_tracing_done())
+        {
+            self.state.set(State::COMPLETE);
+        } else {
+            self.state.set(State::MARKING);
+        }
+        heap.tracer().add_incremental_marking_step(start.elapsed());
+        bytes_marked
     }
 
-    if !self.is_major_marking() {
-        return;
-    }
-
-    let max_bytes_to_process = self.get_scheduled_bytes(StepOrigin::kV8);
-    self.step(get_max_duration(StepOrigin::kV8), max_bytes_to_process, StepOrigin::kV8);
-
-    if self.is_major_marking_complete() && !self.should_wait_for_task() {
-      self.major_collection_requested_via_stack_guard_ = true;
-      println!("Request GC");
-    }
-  }
-
-  fn should_finalize(&self) -> bool {
-    if !self.is_marking() {
-        return false;
+    /// Updates the marking worklists after a scavenge moved young objects.
+    /// Must run before from-space is zapped.
+    pub fn update_marking_worklist_after_scavenge(&self, heap: &Heap) {
+        debug_assert!(self.is_marking());
+        let collector = heap.mark_compact_collector();
+        collector
+            .marking_worklists()
+            .update(forwarding_address_after_scavenge);
+        collector.weak_objects().update_after_scavenge();
     }
 
-    println!("ShouldFinalize placeholder return true");
-    true // Placeholder
-  }
-
-  fn fetch_bytes_marked_concurrently(&mut self) {
-      unsafe {
-          if !v8_flags.concurrent_marking {
-              return;
-          }
-      }
-
-      println!("Fetch bytes marked concurrently placeholder");
-  }
-
-  fn step(&mut self, max_duration: TimeDelta, marked_bytes_limit: SizeT, step_origin: StepOrigin) {
-    
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        println!("[IncrementalMarking] GCIncrementalMarking");
-      }
-    }
-
-    let (cpp_heap_duration, cpp_heap_marked_bytes) = self.cpp_heap_step(max_duration, marked_bytes_limit);
-
-    let mut v8_marked_bytes: SizeT = 0;
-    let mut v8_time: TimeDelta = TimeDelta::from_milliseconds(0);
-
-    if cpp_heap_duration < max_duration &&
-        (unsafe { !v8_flags.incremental_marking_unified_schedule } || (cpp_heap_marked_bytes < marked_bytes_limit)) {
-
-      let v8_start = TimeTicks::now();
-      let v8_marked_bytes_limit = if unsafe { v8_flags.incremental_marking_unified_schedule } {
-          marked_bytes_limit - cpp_heap_marked_bytes
-      } else {
-          marked_bytes_limit
-      };
-
-        let temp = self.major_collector_process_marking_worklist(
-            max_duration - cpp_heap_duration,
-            v8_marked_bytes_limit
-        );
-        
-        v8_marked_bytes = temp.0;
-        v8_time = TimeTicks::now() - v8_start;
+    /// Ends the cycle after the atomic pause.
+    pub fn stop(&self) {
+        self.state.set(State::STOPPED);
+        self.black_allocation.set(false);
+        self.allocated_since_last_step.set(0);
+        self.start_reason.set(None);
     }
-    
+}
 
-    unsafe {
-      if v8_flags.trace_incremental_marking {
-        let v8_max_duration = max_duration - cpp_heap_duration;
-        let v8_marked_bytes_limit = marked_bytes_limit - cpp_heap_marked_bytes;
-        println!(
-          "[IncrementalMaring] Step: origin: {} overall: {:.1}ms V8: {}KB ({}KB), {:.1}ms ({:.1}ms), {:.1}MB/s CppHeap: {}KB ({}KB), {:.1}ms ({:.1}ms)",
-          to_string_step(step_origin),
-          0.0,
-          v8_marked_bytes,
-          v8_marked_bytes_limit,
-          v8_time.millis as f64,
-          v8_max_duration.millis as f64,
-          0.0,
-          cpp_heap_marked_bytes,
-          marked_bytes_limit,
-          cpp_heap_duration.millis as f64,
-          max_duration.millis as f64
-        );
-      }
+impl Default for IncrementalMarking {
+    fn default() -> Self {
+        Self::new()
     }
-  }
-
-  fn isolate(&self) -> *mut Isolate {
-    self.get_isolate()
-  }
-
-  fn get_isolate(&self) -> *mut Isolate {
-    
\ No newline at end of file
+}
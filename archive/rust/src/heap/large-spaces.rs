@@ -2,490 +2,99 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/large-spaces.h (partially converted, not a direct translation)
-
-mod large_spaces {
-    use std::sync::{Mutex, Arc};
-    use crate::heap::*;
-    use crate::objects::*;
-    use crate::base::*;
-    use crate::execution::*;
-    use crate::logging::*;
-
-    // TODO: Define Rust equivalents for C++ types used, like Heap, Isolate, etc.
-    //       These are placeholders and need to be replaced with actual definitions.
-    pub struct Heap {
-        isolate: Arc<Isolate>, // Example use of Arc
-    }
-    pub struct Isolate {}
-    pub struct AllocationResult {}
-    pub struct LocalHeap {}
-    pub struct MemoryChunk {}
-    pub struct LargePageMetadata {}
-    pub struct AllocationObserver {}
-
-    impl Heap {
-        pub fn isolate(&self) -> &Arc<Isolate> {
-            &self.isolate
-        }
-    }
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub enum AllocationSpace {
-        LO_SPACE,
-        NEW_LO_SPACE,
-        CODE_LO_SPACE,
-        SHARED_LO_SPACE,
-        SHARED_TRUSTED_LO_SPACE,
-        TRUSTED_LO_SPACE,
-    }
-
-    pub struct LargeObjectSpace {
-        heap: *mut Heap, // Raw pointer, managed externally.
-        id: AllocationSpace,
-        size: usize,
-        page_count: usize,
-        objects_size: usize,
-        pending_object: usize, // Address, should be a safer abstraction
-        allocation_mutex: Mutex<()>,
-        pending_allocation_mutex: Mutex<()>,
-        memory_chunk_list_: Vec<*mut LargePageMetadata>, // Needs proper List implementation
-    }
-
-    impl LargeObjectSpace {
-        pub fn new(heap: *mut Heap, id: AllocationSpace) -> Self {
-            LargeObjectSpace {
-                heap,
-                id,
-                size: 0,
-                page_count: 0,
-                objects_size: 0,
-                pending_object: 0,
-                allocation_mutex: Mutex::new(()),
-                pending_allocation_mutex: Mutex::new(()),
-                memory_chunk_list_: Vec::new(),
-            }
-        }
-
-        pub fn available(&self) -> usize {
-            0 // We return zero here since we cannot take advantage of already allocated
-              // large object memory.
-        }
-
-        // TODO: Implement other LargeObjectSpace methods, converting the C++ logic to Rust.
-        //       This would include memory management, thread safety, and interaction with
-        //       other heap components.
-
-        fn first_page(&self) -> *mut LargePageMetadata {
-            *self.memory_chunk_list_.first().unwrap_or(&std::ptr::null_mut())
-        }
-
-        // Example of adding a page (needs proper integration and memory management)
-        fn add_page(&mut self, page: *mut LargePageMetadata, object_size: usize) {
-            let _guard = self.allocation_mutex.lock().unwrap();
-            self.size += unsafe { (*page).size() } as usize;
-            self.objects_size += object_size;
-            self.page_count += 1;
-            self.memory_chunk_list_.push(page);
-             unsafe {(*page).set_owner(self);}
-        }
-
-        fn remove_page(&mut self, page: *mut LargePageMetadata) {
-          let _guard = self.allocation_mutex.lock().unwrap();
-            let index = self.memory_chunk_list_.iter().position(|&p| p == page).unwrap();
-            self.size -= unsafe { (*page).size() } as usize;
-            self.page_count -= 1;
-            self.memory_chunk_list_.remove(index);
-        }
-
-        fn id(&self) -> AllocationSpace {
-            self.id
-        }
-
-         fn heap(&self) -> *mut Heap {
-            self.heap
-         }
-
-        fn pending_object(&self) -> usize {
-            self.pending_object
-        }
-    }
-
-    impl Drop for LargeObjectSpace {
-        fn drop(&mut self) {
-            // TODO: Implement proper TearDown logic, freeing allocated memory.
-            //       This requires careful handling of ownership and lifetimes.
-            println!("LargeObjectSpace dropped, but TearDown not fully implemented.");
-        }
-    }
-
-    pub struct OldLargeObjectSpace {
-        base: LargeObjectSpace,
-    }
-
-    impl OldLargeObjectSpace {
-        pub fn new(heap: *mut Heap) -> Self {
-            OldLargeObjectSpace {
-                base: LargeObjectSpace::new(heap, AllocationSpace::LO_SPACE),
-            }
-        }
-    }
-        
-    impl std::ops::Deref for OldLargeObjectSpace {
-        type Target = LargeObjectSpace;
-
-        fn deref(&self) -> &Self::Target {
-            &self.base
-        }
-    }
-
-    impl std::ops::DerefMut for OldLargeObjectSpace {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.base
-        }
-    }
-
-    pub struct NewLargeObjectSpace {
-        base: LargeObjectSpace,
-        capacity: usize,
-    }
-
-    impl NewLargeObjectSpace {
-        pub fn new(heap: *mut Heap, capacity: usize) -> Self {
-            NewLargeObjectSpace {
-                base: LargeObjectSpace::new(heap, AllocationSpace::NEW_LO_SPACE),
-                capacity,
-            }
-        }
-    }
-
-     impl std::ops::Deref for NewLargeObjectSpace {
-        type Target = LargeObjectSpace;
-
-        fn deref(&self) -> &Self::Target {
-            &self.base
-        }
-    }
-
-    impl std::ops::DerefMut for NewLargeObjectSpace {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.base
-        }
-    }
-
-    pub struct CodeLargeObjectSpace {
-        base: OldLargeObjectSpace,
-    }
-
-   impl CodeLargeObjectSpace {
-        pub fn new(heap: *mut Heap) -> Self {
-            CodeLargeObjectSpace {
-                base: OldLargeObjectSpace {
-                    base: LargeObjectSpace::new(heap, AllocationSpace::CODE_LO_SPACE),
-                }
-            }
-        }
-    }
-
-     impl std::ops::Deref for CodeLargeObjectSpace {
-        type Target = OldLargeObjectSpace;
-
-        fn deref(&self) -> &Self::Target {
-            &self.base
-        }
-    }
-
-    impl std::ops::DerefMut for CodeLargeObjectSpace {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.base
-        }
-    }
-
-    pub struct SharedLargeObjectSpace {
-        base: OldLargeObjectSpace,
-    }
-
-    impl SharedLargeObjectSpace {
-        pub fn new(heap: *mut Heap) -> Self {
-            SharedLargeObjectSpace {
-                base: OldLargeObjectSpace {
-                    base: LargeObjectSpace::new(heap, AllocationSpace::SHARED_LO_SPACE),
-                }
-            }
-        }
-    }
-
-     impl std::ops::Deref for SharedLargeObjectSpace {
-        type Target = OldLargeObjectSpace;
-
-        fn deref(&self) -> &Self::Target {
-            &self.base
-        }
-    }
-
-    impl std::ops::DerefMut for SharedLargeObjectSpace {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.base
-        }
-    }
-
-    pub struct SharedTrustedLargeObjectSpace {
-        base: OldLargeObjectSpace,
-    }
-
-    impl SharedTrustedLargeObjectSpace {
-        pub fn new(heap: *mut Heap) -> Self {
-            SharedTrustedLargeObjectSpace {
-                base: OldLargeObjectSpace {
-                    base: LargeObjectSpace::new(heap, AllocationSpace::SHARED_TRUSTED_LO_SPACE),
-                }
-            }
-        }
-    }
-
-     impl std::ops::Deref for SharedTrustedLargeObjectSpace {
-        type Target = OldLargeObjectSpace;
-
-        fn deref(&self) -> &Self::Target {
-            &self.base
-        }
-    }
-
-    impl std::ops::DerefMut for SharedTrustedLargeObjectSpace {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.base
-        }
-    }
-
-    pub struct TrustedLargeObjectSpace {
-        base: OldLargeObjectSpace,
-    }
-
-    impl TrustedLargeObjectSpace {
-        pub fn new(heap: *mut Heap) -> Self {
-            TrustedLargeObjectSpace {
-                base: OldLargeObjectSpace {
-                    base: LargeObjectSpace::new(heap, AllocationSpace::TRUSTED_LO_SPACE),
-                }
-            }
-        }
-    }
-
-     impl std::ops::Deref for TrustedLargeObjectSpace {
-        type Target = OldLargeObjectSpace;
-
-        fn deref(&self) -> &Self::Target {
-            &self.base
-        }
-    }
-
-    impl std::ops::DerefMut for TrustedLargeObjectSpace {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.base
-        }
-    }
-
-    //Iterators
-    pub struct LargeObjectSpaceObjectIterator {
-        current_: *mut LargePageMetadata,
-    }
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::heap::heap_layout::{Address, HeapObject};
+use crate::heap::marking_state::MarkingState;
+use crate::heap::memory_chunk::{AllocationSpace, MemoryChunk, K_PAGE_SIZE};
+
+/// Space for objects larger than `K_MAX_REGULAR_HEAP_OBJECT_SIZE`. Every
+/// object gets its own page and is never moved. Large objects are always
+/// allocated in the old generation.
+pub struct OldLargeObjectSpace {
+    pages: Mutex<Vec<NonNull<MemoryChunk>>>,
+    size_of_objects: AtomicUsize,
+}
 
-    impl LargeObjectSpaceObjectIterator {
-        pub fn new(space: &LargeObjectSpace) -> Self {
-            LargeObjectSpaceObjectIterator {
-                current_: space.first_page(),
+// SAFETY: pages are only handed out under the lock or by address.
+unsafe impl Send for OldLargeObjectSpace {}
+unsafe impl Sync for OldLargeObjectSpace {}
+
+impl OldLargeObjectSpace {
+    pub fn new() -> Self {
+        OldLargeObjectSpace {
+            pages: Mutex::new(Vec::new()),
+            size_of_objects: AtomicUsize::new(0),
+        }
+    }
+
+    /// Allocates a page for an object of `object_size` bytes and returns
+    /// the object's address. The memory is not initialized.
+    pub fn allocate_raw(&self, object_size: usize) -> Address {
+        let chunk_size = (MemoryChunk::K_HEADER_SIZE + object_size).next_multiple_of(K_PAGE_SIZE);
+        let page = MemoryChunk::allocate(chunk_size, AllocationSpace::LO_SPACE, MemoryChunk::LARGE_PAGE);
+        // SAFETY: the page was just allocated.
+        let page_ref = unsafe { page.as_ref() };
+        let object = page_ref.area_start();
+        page_ref.set_high_water_mark(object + object_size);
+        self.pages.lock().unwrap().push(page);
+        self.size_of_objects.fetch_add(object_size, Ordering::Relaxed);
+        object
+    }
+
+    /// Frees the pages of unmarked objects after marking and clears the mark
+    /// bits of the others. Returns the number of bytes freed.
+    pub fn free_dead_objects(&self) -> usize {
+        let mut freed = 0;
+        self.pages.lock().unwrap().retain(|&page| {
+            // SAFETY: pages live as long as the space.
+            let page_ref = unsafe { page.as_ref() };
+            // SAFETY: every large page holds one object at its area start.
+            let object = unsafe { HeapObject::from_address(page_ref.area_start()) };
+            if MarkingState::is_marked(object) {
+                page_ref.clear_live_objects();
+                return true;
             }
-        }
-
-        // TODO: Implement Next() method, handling object iteration and checking for free space.
-        pub fn next(&mut self) -> Option<TaggedHeapObject> {
-           if self.current_.is_null() {
-              return None;
-           }
-
-           let current = unsafe { &*self.current_ };
-           let object = current.get_object();
-           self.current_ = current.next_page();
-
-           // TODO: Implement IsFreeSpaceOrFiller check
-           Some(object)
-
-           // Placeholder
-        }
-    }
-
-    pub trait ObjectIterator {}
-
-    impl ObjectIterator for LargeObjectSpaceObjectIterator {}
-
-    pub trait Space {
-        // Common interface for spaces
-        fn id(&self) -> AllocationSpace;
-        fn heap(&self) -> *mut Heap;
-    }
-
-    impl Space for LargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
-    }
-
-    impl Space for OldLargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
-    }
-
-    impl Space for NewLargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
+            freed += page_ref.allocated_bytes();
+            // SAFETY: the object is dead and its page is not used anymore.
+            unsafe { MemoryChunk::free(page) };
+            false
+        });
+        self.size_of_objects.fetch_sub(freed, Ordering::Relaxed);
+        freed
     }
 
-    impl Space for CodeLargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
+    /// Returns a snapshot of the space's pages.
+    pub fn pages(&self) -> Vec<&MemoryChunk> {
+        let pages = self.pages.lock().unwrap();
+        // SAFETY: pages live as long as the space.
+        pages.iter().map(|page| unsafe { &*page.as_ptr() }).collect()
     }
 
-    impl Space for SharedLargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
+    pub fn size_of_objects(&self) -> usize {
+        self.size_of_objects.load(Ordering::Relaxed)
     }
 
-     impl Space for SharedTrustedLargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
+    pub fn committed_memory(&self) -> usize {
+        let pages = self.pages.lock().unwrap();
+        // SAFETY: see `pages()`.
+        pages.iter().map(|page| unsafe { page.as_ref() }.size()).sum()
     }
-
-     impl Space for TrustedLargeObjectSpace {
-        fn id(&self) -> AllocationSpace {
-            self.id()
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap()
-        }
-    }
-
-     impl LargePageMetadata {
-        //Example implementation of methods in LargePageMetadata
-        fn size(&self) -> u32{
-            1024 //Placeholder
-        }
-
-        fn get_object(&self) -> TaggedHeapObject {
-          TaggedHeapObject { address: 0 } //Placeholder
-        }
-
-        fn next_page(&self) -> *mut LargePageMetadata {
-           std::ptr::null_mut() //Placeholder
-        }
-
-        fn set_owner(&mut self, space: &LargeObjectSpace) {
-           //Placeholder implementation
-           //set the owner for LargeObjectSpace
-        }
-     }
 }
 
-mod base {
-    // Placeholder module for base functionalities
-    pub struct MutexGuard {}
-    pub struct RecursiveMutexGuard {}
-}
-
-mod common {
-    // Placeholder module for common functionalities
-    pub type Address = usize;
-}
-
-mod execution {
-    // Placeholder module for execution functionalities
-}
-
-mod logging {
-  pub trait LogEvent {}
-}
-
-mod heap {
-    // Placeholder module for heap functionalities
-    pub struct Heap {}
-    pub struct LocalHeap {}
-    pub struct AllocationResult {}
-    pub struct MemoryChunk {}
-    pub struct LargePageMetadata {}
-    pub struct AllocationObserver {}
-    pub struct MarkingState {}
-    pub struct ConcurrentMarking {}
-    pub struct IncrementalMarking {}
-    pub struct MemoryAllocator {}
-
-    pub enum FreeMode {
-        kImmediately,
-    }
-
-    impl MemoryAllocator {
-        pub fn Free(&self, mode: FreeMode, page: *mut LargePageMetadata) {
-            //Placeholder implementation
-        }
+impl Default for OldLargeObjectSpace {
+    fn default() -> Self {
+        Self::new()
     }
-
-    pub struct HeapExpansionMutex {}
-
-     impl HeapExpansionMutex {
-        //Example implementation of methods in LargePageMetadata
-        fn lock(&self) -> HeapExpansionMutexGuard {
-           HeapExpansionMutexGuard{}
-        }
-     }
-
-     pub struct HeapExpansionMutexGuard {}
 }
 
-mod objects {
-    // Placeholder module for objects functionalities
-    #[derive(Debug, Copy, Clone)]
-    pub struct TaggedHeapObject {
-        address: usize,
-    }
-
-    impl TaggedHeapObject {
-        pub fn is_null(&self) -> bool {
-            self.address == 0
-        }
-
-        pub fn size(&self, _base: PtrComprCageBase) -> usize {
-            //Placeholder size
-            100
+impl Drop for OldLargeObjectSpace {
+    fn drop(&mut self) {
+        for page in self.pages.get_mut().unwrap().drain(..) {
+            // SAFETY: the pages were allocated by this space.
+            unsafe { MemoryChunk::free(page) };
         }
     }
-
-    pub struct PtrComprCageBase {}
-}
\ No newline at end of file
+}
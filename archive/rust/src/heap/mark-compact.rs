@@ -1,931 +1,874 @@
-// This is a placeholder for the crate imports and module declarations
-// since the original C++ file heavily depends on the V8 engine's internal
-// structures.  A complete conversion would require defining all those
-// structures and their relationships, which is beyond the scope of this example.
-
-// Placeholder for v8 crate.  In real implementation, this would need to
-// implement the V8 API and data structures.
-extern crate v8;
-
-// Placeholder for base crate. Mimics base library
-extern crate base;
-
-// Placeholder for logging crate
-extern crate logging;
-
-// Placeholder for tracing
-extern crate tracing;
-
-// Placeholder for common
-extern crate common;
-
-// Placeholder for compiler
-extern crate compiler;
-
-// Placeholder for sandbox
-extern crate sandbox;
-
-// Placeholder for wasm
-extern crate wasm;
-
-// Placeholder for init
-extern crate init;
-
-// Placeholder for objects
-extern crate objects;
-
-// Placeholder for snapshot
-extern crate snapshot;
-
-// Placeholder for tasks
-extern crate tasks;
-
-use std::sync::{Mutex, Arc};
-use std::collections::{HashMap, HashSet};
-use base::{EnumSet, RandomNumberGenerator};
-use std::time::Duration;
-use std::any::Any;
-
-// Example macro definition (replace with actual definitions as needed)
-macro_rules! DCHECK {
-    ($condition:expr) => {
-        if !$condition {
-            panic!("DCHECK failed: {}", stringify!($condition));
-        }
-    };
+// Copyright 2012 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::heap::evacuation_allocator::EvacuationAllocator;
+use crate::heap::evacuation_verifier::EvacuationVerifier;
+use crate::heap::gc_tracer::{GarbageCollector, ScopeId};
+use crate::heap::heap::Heap;
+use crate::heap::heap_layout::{HeapObject, MapWord, ObjectSlot, Tagged};
+use crate::heap::heap_visitor::{iterate_body, EphemeronHashTableShape, ObjectVisitor};
+use crate::heap::marking_state::MarkingState;
+use crate::heap::marking_visitor::{record_slot, MarkingVisitor};
+use crate::heap::marking_worklist::MarkingWorklists;
+use crate::heap::memory_chunk::{AllocationSpace, MemoryChunk};
+use crate::heap::new_spaces::SemiSpaceNewSpace;
+use crate::heap::paged_spaces::PagedSpace;
+use crate::heap::parallel_work_item::internal::ParallelWorkItem;
+use crate::heap::remembered_set::{RememberedSet, OLD_TO_NEW, OLD_TO_OLD};
+use crate::heap::slot_set::SlotCallbackResult;
+use crate::heap::weak_object_worklists::{Ephemeron, WeakObjects};
+
+const MB: usize = 1024 * 1024;
+
+/// Sizes of the objects evacuated by a mark-compact.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MarkCompactResult {
+    /// Bytes of young objects moved into the old generation.
+    pub promoted_size: usize,
+    /// Bytes moved off evacuation candidates.
+    pub compacted_size: usize,
 }
 
-macro_rules! UNREACHABLE {
-  () => {
-    panic!("UNREACHABLE");
-  };
+/// Collector for the whole heap.
+///
+/// Marking usually starts incrementally, see `IncrementalMarking`; the
+/// atomic pause then re-marks the roots, drains the remaining work and
+/// computes the ephemeron fixpoint. Dead weak references and ephemerons
+/// are cleared, all live young objects and the live objects of evacuation
+/// candidates are evacuated into old space in parallel, and pointers to
+/// them are updated through the roots, the remembered sets and the bodies
+/// of evacuated objects. Finally the old generation is swept concurrently
+/// by the `Sweeper`.
+///
+/// With a `CppHeap` attached, both heaps are marked in one unified cycle:
+/// wrappables of API objects are marked through the `CppMarkingState` and
+/// V8 objects reached from cppgc objects through `TracedReference`s are
+/// marked by the `MarkingVisitor`, until neither heap has work left in the
+/// atomic pause. Traced handles are roots otherwise.
+pub struct MarkCompactCollector {
+    marking_worklists: Arc<MarkingWorklists>,
+    weak_objects: Arc<WeakObjects>,
+    /// Fragmented old pages selected at the start of marking, whose live
+    /// objects are moved in the atomic pause.
+    evacuation_candidates: RefCell<Vec<&'static MemoryChunk>>,
+    compacting: Cell<bool>,
 }
 
-macro_rules! TRACE_EVENT0 {
-    ($category:expr, $name:expr) => {
-        println!("TRACE_EVENT: Category={}, Name={}", $category, $name);
-    };
-}
-
-macro_rules! TRACE_EVENT_INSTANT2 {
-    ($category:expr, $name:expr, $scope:expr, $arg1_name:expr, $arg1_value:expr, $arg2_name:expr, $arg2_value:expr) => {
-        println!("TRACE_EVENT_INSTANT2: Category={}, Name={}, Scope={}, Arg1_Name={}, Arg1_Value={}, Arg2_Name={}, Arg2_Value={}",
-                 $category, $name, $scope, $arg1_name, $arg1_value, $arg2_name, $arg2_value);
-    };
-}
-
-macro_rules! TRACE_GC {
-    ($tracer:expr, $scope:expr, $thread_kind:expr) => {
-        println!("TRACE_GC: Scope={:?}, ThreadKind={:?}", $scope, $thread_kind);
-    };
-    ($tracer:expr, $scope:expr) => {
-        println!("TRACE_GC: Scope={:?}", $scope);
-    };
-}
+impl MarkCompactCollector {
+    pub const K_MAX_EVACUATION_TASKS: usize = 8;
+    /// Old pages with at least this percentage of free memory become
+    /// evacuation candidates.
+    const K_EVACUATION_CANDIDATE_FREE_PERCENT: usize = 50;
+    /// Upper bound for the live bytes on evacuation candidates, which
+    /// bounds the time spent evacuating them in the atomic pause.
+    const K_MAX_EVACUATED_BYTES: usize = 4 * MB;
 
-macro_rules! TRACE_GC_ARG1 {
-    ($tracer:expr, $scope:expr, $arg_name:expr, $arg_value:expr) => {
-        println!("TRACE_GC_ARG1: Scope={:?}, Arg_Name={}, Arg_Value={}", $scope, $arg_name, $arg_value);
-    };
-}
-
-macro_rules! TRACE_GC_EPOCH_WITH_FLOW {
-    ($tracer:expr, $scope:expr, $thread_kind:expr, $trace_id:expr, $flags:expr) => {
-        println!("TRACE_GC_EPOCH_WITH_FLOW: Scope={:?}, ThreadKind={:?}, TraceId={}, Flags={}", $scope, $thread_kind, $trace_id, $flags);
-    };
-}
-
-macro_rules! TRACE_GC_WITH_FLOW {
-    ($tracer:expr, $scope:expr, $trace_id:expr, $flags:expr) => {
-        println!("TRACE_GC_WITH_FLOW: Scope={:?}, TraceId={}, Flags={}", $scope, $trace_id, $flags);
-    };
-}
-
-mod heap {
-    use std::sync::{Mutex, Arc};
-    use crate::base::EnumSet;
-    use v8::base::IncrementalMarkingSchedule;
-    use crate::v8::objects::Map;
-    use crate::base::RandomNumberGenerator;
-    use crate::v8::objects::HeapObject;
-
-    // Example enum definition (replace with actual definitions as needed)
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum SpaceId {
-        NewSpace,
-        OldSpace,
-        CodeSpace,
-        SharedSpace,
-        LoSpace,
-        CodeLoSpace,
-        NewLoSpace,
-        TrustedSpace,
-        TrustedLoSpace,
-    }
-
-    // Example enum definition (replace with actual definitions as needed)
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum GarbageCollector {
-        MARK_COMPACTOR,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum CodeFlushMode {
-        kFlushBytecode,
-        kFlushBaselineCode,
-        kForceFlush,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum Root {
-        kClientHeap,
-        kStringTable,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum CallOrigin {
-        kAtomicGC,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum AllocationSpace {
-        NEW_SPACE,
-        OLD_SPACE,
-        CODE_SPACE,
-        SHARED_SPACE,
-        LO_SPACE,
-        CODE_LO_SPACE,
-        TRUSTED_SPACE,
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum ThreadKind {
-        kMain,
-        kBackground
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum GCTracerScope {
-        MC_MARK,
-        MC_MARK_ROOTS,
-        MC_MARK_CLIENT_HEAPS,
-        MC_MARK_RETAIN_MAPS,
-        MC_MARK_FULL_CLOSURE_PARALLEL,
-        MC_MARK_FULL_CLOSURE_PARALLEL_JOIN,
-        MC_MARK_FULL_CLOSURE_SERIAL,
-        MC_MARK_WEAK_CLOSURE_EPHEMERON_MARKING,
-        MC_MARK_WEAK_CLOSURE_EPHEMERON_LINEAR,
-        MC_SWEEP,
-        MC_SWEEP_NEW,
-        MC_EVACUATE,
-        MC_EVACUATE_REBALANCE,
-        MC_FINISH,
-        MC_CLEAR_STRING_TABLE,
-        MC_MARK_EMBEDDER_TRACING,
-        CONSERVATIVE_STACK_SCANNING,
-        MC_MARK_FINISH_INCREMENTAL,
-        MC_MARK_VERIFY,
-        MC_MARK_EMBEDDER_PROLOGUE
-    }
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum MarkingWorklistProcessingMode {
-        kProcessRegular,
-        kProcessRememberedEphemerons,
-    }
-
-    #[derive(Debug, Copy, Clone)]
-    pub enum WorklistTarget {
-        kRegular
-    }
-
-    #[derive(Debug, Copy, Clone)]
-    pub enum TaskPriority {
-        kUserBlocking
-    }
-
-    pub trait PageMetadataTrait {
-        fn area_size(&self) -> usize;
-        fn allocated_bytes(&self) -> usize;
-        fn live_bytes(&self) -> usize;
-    }
-
-    pub struct PageMetadata {
-        // Placeholder for actual fields
-        area_size: usize,
-        allocated_bytes: usize,
-        live_bytes: usize,
-    }
-
-    impl PageMetadataTrait for PageMetadata {
-        fn area_size(&self) -> usize {
-            self.area_size
-        }
-        fn allocated_bytes(&self) -> usize {
-            self.allocated_bytes
-        }
-        fn live_bytes(&self) -> usize {
-            self.live_bytes
+    pub fn new(marking_worklists: Arc<MarkingWorklists>, weak_objects: Arc<WeakObjects>) -> Self {
+        MarkCompactCollector {
+            marking_worklists,
+            weak_objects,
+            evacuation_candidates: RefCell::new(Vec::new()),
+            compacting: Cell::new(false),
         }
     }
 
-    pub struct Heap {
-        isolate: Box<dyn IsolateTrait>,
-        cpp_heap_: usize, // Placeholder: CppHeap::From(heap_->cpp_heap_) requires usize
-        incremental_marking: Box<IncrementalMarking>,
-        tracer: Box<dyn GCTracerTrait>,
-        sweeper: Box<dyn SweeperTrait>,
-        memory_allocator: usize, //Placeholder for memory allocator
-        non_atomic_marking_state: usize, // Placeholder: marking state needs usize
-        marking_state: usize, // Placeholder: marking state needs usize
-        live_object_stats_: usize, // Placeholder: Object state collector requires usize
-        dead_object_stats_: usize, // Placeholder: Object state collector requires usize
-        new_space: usize, // Placeholder: new space requires usize
-        new_lo_space: usize, // Placeholder: new large object space
-        old_space: usize, // Placeholder: old space
-        code_space: usize, // Placeholder: code space
-        shared_space: usize, // Placeholder: shared space
-        lo_space: usize, // Placeholder: large object space
-        code_lo_space: usize, // Placeholder: code large object space
-        new_lo_space: usize, // Placeholder: new large object space
-        trusted_space: usize, // Placeholder: trusted space
-        trusted_lo_space: usize, // Placeholder: trusted large object space
-        allocator: usize,
-        main_thread_local_heap_: usize,
-        array_buffer_sweeper_: usize,
-        pretenuring_handler: usize,
-        young_external_pointer_space: usize,
-        old_external_pointer_space: usize,
-        cpp_heap_pointer_space: usize,
-        paged_new_space: usize, //Placeholder for paged_new_space
-
-        // Add other heap-related fields as needed
+    pub fn marking_worklists(&self) -> &Arc<MarkingWorklists> {
+        &self.marking_worklists
     }
 
-    pub trait HeapTrait {
-        fn should_use_background_threads(&self) -> bool;
-        fn fatal_process_out_of_memory(&self, message: &str);
-        fn isolate(&self) -> &dyn IsolateTrait;
-        fn resize_new_space(&mut self);
-        fn contains(&self, object: usize) -> bool; //Placeholder for the contains function
-        fn memory_measurement(&self) -> &dyn MemoryMeasurementTrait;
-        fn should_reduce_memory(&self) -> bool;
-        fn should_optimize_for_memory_usage(&self) -> bool;
-        fn should_current_gc_keep_ages_unchanged(&self) -> bool;
-        fn unmark(&mut self);
-        fn concurrent_marking(&self) -> &dyn ConcurrentMarkingTrait;
-        fn use_new_space(&self) -> bool;
-        fn find_all_native_contexts(&self) -> Vec<usize>;
-        fn find_all_retained_maps(&self) -> Vec<usize>;
-        fn iterate_roots(&self, root_visitor: &mut dyn RootVisitorTrait, skip_root: EnumSet<SkipRoot>);
-        fn iterate_conservative_stack_roots(&self, root_visitor: &mut dyn RootVisitorTrait, iterate_roots_mode: IterateRootsMode);
-        fn cpp_heap(&self) -> usize; // Placeholder for cpp_heap
-        fn on_move_event(&self, src: usize, dst: usize, size: i32);
-        fn allowed_to_be_migrated(&self, map: usize, src: usize, dest: AllocationSpace) -> bool;
-        fn pretenuring_handler(&self) -> &dyn PretenuringHandlerTrait;
-        fn verify_counters_before_concurrent_sweeping(&self, gc: GarbageCollector);
+    pub fn weak_objects(&self) -> &Arc<WeakObjects> {
+        &self.weak_objects
     }
 
-    impl HeapTrait for Heap {
-        fn should_use_background_threads(&self) -> bool {
-            // Placeholder implementation
-            true
-        }
-
-        fn fatal_process_out_of_memory(&self, message: &str) {
-            // Placeholder implementation
-            println!("Fatal out of memory: {}", message);
-            panic!("Out of memory");
-        }
-
-        fn isolate(&self) -> &dyn IsolateTrait {
-            self.isolate.as_ref()
-        }
-
-        fn resize_new_space(&mut self) {
-            // Placeholder implementation
-            println!("Resizing new space");
-        }
-
-        fn contains(&self, _object: usize) -> bool {
-            true // Placeholder Implementation
-        }
-
-        fn memory_measurement(&self) -> &dyn MemoryMeasurementTrait {
-            todo!()
-        }
-
-        fn should_reduce_memory(&self) -> bool {
-            false
-        }
-
-        fn should_optimize_for_memory_usage(&self) -> bool {
-            false
-        }
-
-        fn should_current_gc_keep_ages_unchanged(&self) -> bool {
-            false
-        }
-
-        fn unmark(&mut self) {
-            // Placeholder implementation
-            println!("Unmarking heap");
-        }
-
-        fn concurrent_marking(&self) -> &dyn ConcurrentMarkingTrait {
-            todo!()
-        }
-
-        fn use_new_space(&self) -> bool {
-            true //Placeholder implementation
-        }
-
-        fn find_all_native_contexts(&self) -> Vec<usize> {
-            Vec::new() //Placeholder implementation
-        }
-
-        fn find_all_retained_maps(&self) -> Vec<usize> {
-            Vec::new() //Placeholder implementation
-        }
-
-        fn iterate_roots(&self, _root_visitor: &mut dyn RootVisitorTrait, _skip_root: EnumSet<SkipRoot>) {
-            // Placeholder implementation
-            println!("Iterating roots");
-        }
-
-        fn iterate_conservative_stack_roots(&self, _root_visitor: &mut dyn RootVisitorTrait, _iterate_roots_mode: IterateRootsMode) {
-            println!("Iterating stack roots conservatively.");
-        }
-
-        fn cpp_heap(&self) -> usize {
-            self.cpp_heap_ // Placeholder implementation
-        }
-
-        fn on_move_event(&self, _src: usize, _dst: usize, _size: i32) {
-            // Placeholder implementation
-            println!("On move event");
-        }
-
-        fn allowed_to_be_migrated(&self, _map: usize, _src: usize, _dest: AllocationSpace) -> bool {
-            // Placeholder implementation
-            true
-        }
-
-        fn pretenuring_handler(&self) -> &dyn PretenuringHandlerTrait {
-            todo!()
-        }
-
-        fn verify_counters_before_concurrent_sweeping(&self, _gc: GarbageCollector) {
-            // Placeholder implementation
-            println!("Verify counters before concurrent sweeping");
-        }
-    }
-
-    pub trait MemoryMeasurementTrait {
-        fn start_processing(&self) -> Vec<usize>;
-        fn finish_processing(&self, native_context_stats: &NativeContextStats);
-    }
-
-    pub struct NativeContextStats {
-        // Placeholder for actual fields
-    }
-
-    impl NativeContextStats {
-        pub fn increment_size(&mut self, _context: usize, _map: usize, _object: usize, _size: usize) {
-            //Placeholder implementation
-        }
-        pub fn clear(&mut self) {
-            //Placeholder implementation
-        }
-    }
-
-    pub trait GCTracerTrait {
-        fn compaction_speed_in_bytes_per_millisecond(&self) -> Option<f64>;
-        fn notify_marking_start(&self);
-        fn code_flushing_increase(&self) -> u16;
-        fn current_epoch(&self, scope: GCTracerScope) -> u64;
-    }
-
-    pub trait SweeperTrait {
-        fn sweeping_in_progress(&self) -> bool;
-        fn sweep_empty_new_space_page(&self, page: &PageMetadata);
-        fn start_major_sweeper_tasks(&self);
-        fn finish_minor_jobs(&self);
-        fn finish_major_jobs(&self);
-        fn get_trace_id_for_flow_event(&self, scope: GCTracerScope) -> u64;
-    }
-
-    pub trait IsolateTrait {
-        fn has_shared_space(&self) -> bool;
-        fn is_shared_space_isolate(&self) -> bool;
-        fn allows_code_compaction(&self) -> bool;
-        fn serializer_enabled(&self) -> bool;
-    }
-
-    // Placeholder enums for skipped roots in IterateRoots
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum SkipRoot {
-        kWeak,
-        kTracedHandles,
-        kConservativeStack,
-        kReadOnlyBuiltins,
+    pub fn is_compacting(&self) -> bool {
+        self.compacting.get()
     }
 
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub enum IterateRootsMode {
-        kMainIsolate,
-        kClientIsolate,
+    pub fn evacuation_candidates(&self) -> Vec<&'static MemoryChunk> {
+        self.evacuation_candidates.borrow().clone()
     }
 
-    pub trait RootVisitorTrait {
-        fn visit_root_pointers(&mut self, root: Root, description: &str, start: usize, end: usize); // Placeholder addresses with usize
-    }
-
-    pub trait ConcurrentMarkingTrait {
-        fn garbage_collector(&self) -> GarbageCollector;
-        fn join(&self);
-        fn flush_memory_chunk_data(&self);
-        fn flush_native_contexts(&self, native_context_stats: &mut NativeContextStats);
-        fn set_another_ephemeron_iteration(&self, value: bool);
-        fn another_ephemeron_iteration(&self) -> bool;
-        fn reschedule_job_if_needed(&self, gc: GarbageCollector, priority: TaskPriority);
-    }
-
-    pub trait PretenuringHandlerTrait {
-
-    }
-
-    pub struct PretenuringHandler {
-        // Placeholder for pretenuring handler data.
-    }
-
-    impl PretenuringHandlerTrait for PretenuringHandler {
-
-    }
-
-    pub struct IncrementalMarking {
-        is_major_marking: bool,
-    }
-
-    impl IncrementalMarking {
-        pub fn is_major_marking(&self) -> bool {
-            self.is_major_marking
-        }
-        pub fn is_stopped(&self) -> bool {
-            true // placeholder
-        }
-        pub fn stop(&mut self) {
-            //placeholder
+    /// Returns a marking visitor for the main thread.
+    pub fn create_marking_visitor(&self) -> MarkingVisitor<'_> {
+        MarkingVisitor::new(&self.marking_worklists, &self.weak_objects, self.is_compacting())
+    }
+
+    /// Selects evacuation candidates among the old pages: the most
+    /// fragmented pages whose live objects fit the evacuation budget.
+    /// Nothing is allocated on candidates anymore. Returns whether the
+    /// cycle compacts.
+    pub fn start_compaction(&self, heap: &Heap) -> bool {
+        debug_assert!(!self.is_compacting());
+        if !heap.options().compaction {
+            return false;
+        }
+        let old_space = heap.old_space();
+        let mut candidates: Vec<&'static MemoryChunk> = old_space
+            .pages()
+            .into_iter()
+            .map(|page| MemoryChunk::from_address(page.address()))
+            .filter(|page| page.free_bytes() * 100 >= page.area_size() * Self::K_EVACUATION_CANDIDATE_FREE_PERCENT)
+            .collect();
+        candidates.sort_by_key(|page| page.allocated_bytes_on_page());
+        let mut evacuated_bytes = 0;
+        candidates.retain(|page| {
+            evacuated_bytes += page.allocated_bytes_on_page();
+            evacuated_bytes <= Self::K_MAX_EVACUATED_BYTES
+        });
+        if candidates.is_empty() {
+            return false;
+        }
+        old_space.free_linear_allocation_area();
+        for page in &candidates {
+            page.set_flag(MemoryChunk::EVACUATION_CANDIDATE);
+            old_space.evict_free_list_items(page);
+        }
+        *self.evacuation_candidates.borrow_mut() = candidates;
+        self.compacting.set(true);
+        true
+    }
+
+    /// Activates the marking barrier, starts tracing the attached
+    /// `CppHeap` and marks the roots. Marking runs in per-context mode if
+    /// memory measurement requests are pending.
+    pub fn start_marking(&self, heap: &Heap) {
+        if let Some(contexts) = heap.memory_measurement().start_processing() {
+            self.marking_worklists.create_context_worklists(&contexts);
+        }
+        if let Some(cpp_heap) = heap.cpp_heap() {
+            cpp_heap.start_tracing();
+            self.marking_worklists
+                .set_cpp_marking_state(Some(cpp_heap.cpp_marking_state()));
+        }
+        heap.marking_barrier().activate(self.is_compacting());
+        let mut visitor = self.create_marking_visitor();
+        Self::mark_roots(heap, &mut visitor);
+        visitor.publish();
+    }
+
+    fn mark_roots(heap: &Heap, visitor: &mut MarkingVisitor<'_>) {
+        heap.iterate_roots(|slot| visitor.mark_root(slot));
+        if heap.cpp_heap().is_none() {
+            heap.iterate_traced_handles(|slot| visitor.mark_root(slot));
+        }
+    }
+
+    /// Performs the atomic pause. Marking is started here unless it was
+    /// started incrementally.
+    pub fn collect_garbage(&self, heap: &Heap) -> MarkCompactResult {
+        let tracer = heap.tracer();
+        if !heap.incremental_marking().is_marking() {
+            heap.complete_sweeping();
+            self.start_compaction(heap);
+            self.start_marking(heap);
         }
-        pub fn current_trace_id(&self) -> u64 {
-            0 // placeholder
-        }
-    }
-}
+        let concurrent_marking = heap.concurrent_marking();
+        concurrent_marking.join();
+        tracer.add_scope_sample(ScopeId::MC_BACKGROUND_MARKING, concurrent_marking.take_background_time());
 
-mod mark_compact {
-    use std::sync::{Mutex, Arc};
-    use crate::heap::{Heap, HeapTrait, GarbageCollector, SpaceId, PageMetadata, PageMetadataTrait,
-                       RootVisitorTrait, Root, IsolateTrait, NativeContextStats, GCTracerScope,
-                       CallOrigin, CodeFlushMode, IncrementalMarking, SkipRoot, ThreadKind, MarkingWorklistProcessingMode, WorklistTarget};
-    use crate::base::{EnumSet, RandomNumberGenerator};
-    use crate::v8::objects::HeapObject;
-    use std::collections::HashMap;
-    use crate::v8::objects::Map;
-
-    pub struct MarkCompactCollector {
-        heap_: *mut Heap, // Placeholder for heap
-        uses_shared_heap_: bool,
-        is_shared_space_isolate_: bool,
-        marking_state_: usize, // Placeholder for marking state
-        non_atomic_marking_state_: usize, // Placeholder for non-atomic marking state
-        sweeper_: usize, // Placeholder for sweeper
-        compacting_: bool,
-        evacuation_candidates_: Vec<*mut PageMetadata>,
-        epoch_: u32,
-        code_flush_mode_: EnumSet<CodeFlushMode>,
-        marking_worklists_: MarkingWorklists,
-        local_marking_worklists_: Option<Box<MarkingWorklistsLocal>>,
-        local_weak_objects_: Option<Box<WeakObjectsLocal>>,
-        weak_objects_: WeakObjects,
-        native_context_stats_: NativeContextStats,
-        key_to_values_: HashMap<usize, Vec<usize>>,
-        marking_visitor_: Option<Box<MainMarkingVisitor>>,
-        use_background_threads_in_cycle_: bool,
-        parallel_marking_: bool,
-        have_code_to_deoptimize_: bool,
-    }
-
-    impl MarkCompactCollector {
-        pub fn new(heap: *mut Heap) -> MarkCompactCollector {
-            MarkCompactCollector {
-                heap_: heap,
-                uses_shared_heap_: false, // Placeholder
-                is_shared_space_isolate_: false, // Placeholder
-                marking_state_: 0, // Placeholder
-                non_atomic_marking_state_: 0, // Placeholder
-                sweeper_: 0, // Placeholder
-                compacting_: false,
-                evacuation_candidates_: Vec::new(),
-                epoch_: 0,
-                code_flush_mode_: EnumSet::new(),
-                marking_worklists_: MarkingWorklists::new(),
-                local_marking_worklists_: None,
-                local_weak_objects_: None,
-                weak_objects_: WeakObjects::new(),
-                native_context_stats_: NativeContextStats {},
-                key_to_values_: HashMap::new(),
-                marking_visitor_: None,
-                use_background_threads_in_cycle_: false,
-                parallel_marking_: false,
-                have_code_to_deoptimize_: false,
+        self.mark_live_objects(heap);
+        self.clear_non_live_references(heap);
+        let result = self.evacuate(heap);
+        self.sweep(heap);
+        self.finish(heap);
+        result
+    }
+
+    fn mark_live_objects(&self, heap: &Heap) {
+        let tracer = heap.tracer();
+        let _scope = tracer.scope(ScopeId::MC_MARK);
+        let mut visitor = self.create_marking_visitor();
+        {
+            let _scope = tracer.scope(ScopeId::MC_MARK_ROOTS);
+            Self::mark_roots(heap, &mut visitor);
+        }
+        heap.marking_barrier().publish();
+        let cpp_heap = heap.cpp_heap();
+        if let Some(cpp_heap) = cpp_heap {
+            cpp_heap.enter_final_pause(cpp_heap.embedder_stack_state());
+        }
+        loop {
+            if let Some(cpp_heap) = cpp_heap {
+                cpp_heap.advance_tracing(usize::MAX, &mut visitor);
             }
-        }
-
-        pub fn add_evacuation_candidate(&mut self, p: *mut PageMetadata) {
-            // Placeholder implementation
-            println!("Adding evacuation candidate");
-            self.evacuation_candidates_.push(p);
-        }
-
-        fn collect_evacuation_candidates(&mut self, space: SpaceId) {
-            // Placeholder implementation
-            println!("Collecting evacuation candidates for space: {:?}", space);
-        }
-
-        pub fn prepare(&mut self) {
-            // Placeholder implementation
-            println!("Preparing mark compact collector");
-            self.start_compaction();
-            self.start_marking();
-        }
-
-        pub fn finish_concurrent_marking(&mut self) {
-            // Placeholder implementation
-            println!("Finishing concurrent marking");
-        }
-
-        pub fn verify_marking(&self) {
-            // Placeholder implementation
-            println!("Verifying marking");
-        }
-
-        pub fn finish(&mut self) {
-            // Placeholder implementation
-            println!("Finishing mark compact collector");
-        }
-
-        pub fn collect_garbage(&mut self) {
-            // Placeholder implementation
-            println!("Collecting garbage");
-        }
-
-        pub fn start_compaction(&mut self) {
-            // Placeholder implementation
-            println!("Starting compaction");
-        }
-
-        pub fn start_marking(&mut self) {
-            // Placeholder implementation
-            println!("Starting marking");
-        }
-
-        pub fn mark_roots(&mut self, root_visitor: &mut dyn RootVisitorTrait) {
-            // Placeholder implementation
-            println!("Marking roots");
-            unsafe {
-                let heap = &mut *self.heap_;
-                heap.iterate_roots(root_visitor, EnumSet::new());
+            visitor.process_marking_worklist(usize::MAX);
+            {
+                let _scope = tracer.scope(ScopeId::MC_MARK_WEAK_CLOSURE_EPHEMERON);
+                self.process_ephemerons(&mut visitor);
+            }
+            // V8 marking may have marked further wrappables.
+            if cpp_heap.is_none_or(|cpp_heap| cpp_heap.is_tracing_done()) {
+                break;
             }
         }
-
-        pub fn mark_live_objects(&mut self) {
-            // Placeholder implementation
-            println!("Marking live objects");
-            let mut root_visitor = RootMarkingVisitor::new(self);
-            self.mark_roots(&mut root_visitor);
-        }
-
-        pub fn clear_non_live_references(&self) {
-            // Placeholder implementation
-            println!("Clearing non-live references");
-        }
-
-        pub fn sweep(&self) {
-            // Placeholder implementation
-            println!("Sweeping");
-        }
-
-        pub fn evacuate(&self) {
-            // Placeholder implementation
-            println!("Evacuating");
-        }
-
-        pub fn record_object_stats(&self) {
-            // Placeholder implementation
-            println!("Recording object stats");
-        }
-
-        pub fn epoch(&self) -> u32 {
-            self.epoch_
-        }
-
-        pub fn code_flush_mode(&self) -> EnumSet<CodeFlushMode> {
-            self.code_flush_mode_
-        }
-
-        pub fn heap(&self) -> &Heap {
-            unsafe {&*self.heap_}
-        }
-
-        pub fn should_use_background_threads_in_cycle(&self) -> bool {
-            self.use_background_threads_in_cycle_
-        }
-
-        pub fn maybe_enable_background_threads_in_cycle(&mut self, _origin: CallOrigin) {
-            // Placeholder implementation
-            println!("Maybe enable background threads in cycle");
-        }
-
-        pub fn mark_object(&mut self, _host: usize, _heap_object: usize, _target_worklist: WorklistTarget) {
-            // Placeholder implementation
-            println!("Mark object");
-        }
-
-        pub fn mark_root_object(&mut self, _root: Root, _heap_object: usize, _target_worklist: WorklistTarget) {
-            // Placeholder implementation
-            println!("Mark Root object");
-        }
-
-        pub fn record_slot(_object: usize, _slot: usize, _target: usize) {
-            // Placeholder implementation
-            println!("Record Slot");
-        }
-
-        pub fn record_reloc_slot(_host: usize, _rinfo: usize, _target: usize) {
-             // Placeholder implementation
-            println!("Record Reloc Slot");
+        if let Some(cpp_heap) = cpp_heap {
+            cpp_heap.finish_tracing();
+        }
+        visitor.publish();
+        debug_assert!(self.marking_worklists.is_empty());
+        if self.marking_worklists.is_per_context_mode() {
+            let stats = self.marking_worklists.take_native_context_stats();
+            heap.memory_measurement().finish_processing(&stats, Self::live_bytes(heap));
+            self.marking_worklists.release_context_worklists();
+        }
+    }
+
+    /// Size of the marked objects.
+    fn live_bytes(heap: &Heap) -> usize {
+        let mut live_bytes = 0;
+        heap.new_space().for_each_to_page(|page| live_bytes += page.live_bytes());
+        heap.old_space()
+            .pages()
+            .into_iter()
+            .chain(heap.lo_space().pages())
+            .for_each(|page| live_bytes += page.live_bytes());
+        live_bytes
+    }
+
+    /// Marks the values of ephemerons with live keys until a fixpoint is
+    /// reached. Ephemerons whose keys stay unmarked are left in the
+    /// discovered list; their entries are cleared afterwards.
+    fn process_ephemerons(&self, visitor: &mut MarkingVisitor<'_>) {
+        loop {
+            visitor.publish();
+            let mut newly_marked = false;
+            while let Some(segment) = self.weak_objects.discovered_ephemerons.pop() {
+                for ephemeron in segment {
+                    if Self::is_ephemeron_key_live(ephemeron) {
+                        newly_marked |= visitor.process_ephemeron(ephemeron);
+                    } else {
+                        visitor.push_discovered_ephemeron(ephemeron);
+                    }
+                }
+            }
+            if !newly_marked {
+                break;
+            }
+            visitor.process_marking_worklist(usize::MAX);
         }
     }
 
-    pub struct MarkingWorklists {
-        // Placeholder for actual fields
+    fn is_ephemeron_key_live(ephemeron: Ephemeron) -> bool {
+        let key = ephemeron
+            .table
+            .raw_field(EphemeronHashTableShape::key_index(ephemeron.entry));
+        key.load().get_heap_object().is_none_or(MarkingState::is_marked)
     }
 
-    impl MarkingWorklists {
-        pub fn new() -> Self {
-            MarkingWorklists {}
-        }
-
-        pub fn create_context_worklists(&mut self, _contexts: Vec<usize>) {
-            //Placeholder implementation
+    /// Clears weak references and weak roots to unmarked objects and
+    /// ephemeron entries with unmarked keys. Surviving slots to evacuation
+    /// candidates are recorded.
+    fn clear_non_live_references(&self, heap: &Heap) {
+        let _scope = heap.tracer().scope(ScopeId::MC_CLEAR);
+        heap.iterate_weak_roots(|slot| {
+            if slot.load().get_heap_object().is_some_and(|object| !MarkingState::is_marked(object)) {
+                slot.store(Tagged::CLEARED);
+            }
+        });
+        if heap.cpp_heap().is_some() {
+            // Traced handles of dead cppgc objects were not reported.
+            heap.iterate_traced_handles(|slot| {
+                if slot.load().get_heap_object().is_some_and(|object| !MarkingState::is_marked(object)) {
+                    slot.store(Tagged::ZERO);
+                }
+            });
+        }
+        while let Some(segment) = self.weak_objects.weak_references.pop() {
+            for entry in segment {
+                let slot = entry.host.raw_field(entry.index);
+                let value = slot.load();
+                let Some(object) = value.get_heap_object() else {
+                    continue;
+                };
+                if !value.is_weak() {
+                    // Overwritten with a strong reference after marking
+                    // visited the slot; the barrier marked the target.
+                    continue;
+                }
+                if MarkingState::is_marked(object) {
+                    self.record_slot(entry.host, slot, object);
+                } else {
+                    slot.store(Tagged::CLEARED);
+                }
+            }
         }
-
-        pub fn release_context_worklists(&mut self) {
-            //Placeholder implementation
+        while let Some(segment) = self.weak_objects.ephemeron_hash_tables.pop() {
+            for table in segment {
+                self.clear_ephemeron_hash_table(table);
+            }
         }
+        self.weak_objects.clear();
     }
 
-    pub struct MarkingWorklistsLocal {
-        // Placeholder for actual fields
-    }
-
-    impl MarkingWorklistsLocal {
-        pub fn is_empty(&self) -> bool {
-            true //Placeholder implementation
-        }
-
-        pub fn pop(&mut self, _object: &mut usize) -> bool {
-            false //Placeholder implementation
-        }
-
-        pub fn pop_on_hold(&mut self, _object: &mut usize) -> bool {
-            false //Placeholder implementation
-        }
-
-        pub fn is_per_context_mode(&self) -> bool {
-            false //Placeholder implementation
-        }
-
-        pub fn switch_to_context(&mut self, _context: usize) {
-            //Placeholder implementation
-        }
-
-        pub fn context(&self) -> usize {
-            0 //Placeholder implementation
+    fn clear_ephemeron_hash_table(&self, table: HeapObject) {
+        for entry in 0..table.length() / EphemeronHashTableShape::K_ENTRY_SIZE {
+            let key_slot = table.raw_field(EphemeronHashTableShape::key_index(entry));
+            let value_slot = table.raw_field(EphemeronHashTableShape::value_index(entry));
+            if let Some(key) = key_slot.load().get_heap_object() {
+                if !MarkingState::is_marked(key) {
+                    key_slot.store(Tagged::CLEARED);
+                    value_slot.store(Tagged::CLEARED);
+                    continue;
+                }
+                self.record_slot(table, key_slot, key);
+            }
+            if let Some(value) = value_slot.load().get_heap_object() {
+                self.record_slot(table, value_slot, value);
+            }
         }
     }
 
-    pub struct WeakObjects {
-        // Placeholder for actual fields
-        current_ephemerons: EphemeronQueue,
-        next_ephemerons: EphemeronQueue,
-    }
+    #[inline]
+    fn record_slot(&self, host: HeapObject, slot: ObjectSlot, target: HeapObject) {
+        if self.is_compacting() {
+            record_slot(host, slot, target);
+        }
+    }
+
+    /// Returns the number of tasks, including the main thread, that
+    /// evacuate `num_pages` pages.
+    fn number_of_evacuation_tasks(heap: &Heap, num_pages: usize) -> usize {
+        if !heap.options().parallel_compaction {
+            return 1;
+        }
+        std::thread::available_parallelism()
+            .map_or(1, |cores| cores.get())
+            .min(Self::K_MAX_EVACUATION_TASKS)
+            .min(num_pages)
+            .max(1)
+    }
+
+    /// Moves all live young objects and the live objects of evacuation
+    /// candidates into old space, updates pointers to them and releases
+    /// the evacuated pages.
+    fn evacuate(&self, heap: &Heap) -> MarkCompactResult {
+        let tracer = heap.tracer();
+        let _scope = tracer.scope(ScopeId::MC_EVACUATE);
+        let new_space = heap.new_space();
+        let old_space = heap.old_space();
+        let candidates = self.evacuation_candidates();
+        let items: Vec<(&MemoryChunk, ParallelWorkItem)> = new_space
+            .to_space_pages()
+            .into_iter()
+            .chain(candidates.iter().copied())
+            .map(|page| (page, ParallelWorkItem::new()))
+            .collect();
+        let num_tasks = Self::number_of_evacuation_tasks(heap, items.len());
+
+        let mut finished = Vec::with_capacity(num_tasks);
+        {
+            let _scope = tracer.scope(ScopeId::MC_EVACUATE_COPY);
+            let mut background_time = Duration::ZERO;
+            std::thread::scope(|scope| {
+                let items = &items;
+                let handles: Vec<_> = (1..num_tasks)
+                    .map(|_| {
+                        scope.spawn(move || {
+                            let start = Instant::now();
+                            let mut evacuator = Evacuator::new(new_space, old_space);
+                            evacuator.run(items);
+                            (evacuator.finalize(), start.elapsed())
+                        })
+                    })
+                    .collect();
+                let mut evacuator = Evacuator::new(new_space, old_space);
+                evacuator.run(items);
+                finished.push(evacuator.finalize());
+                for handle in handles {
+                    let (result, duration) = handle.join().unwrap();
+                    finished.push(result);
+                    background_time += duration;
+                }
+            });
+            tracer.add_scope_sample(ScopeId::MC_BACKGROUND_EVACUATE_COPY, background_time);
+        }
+
+        let mut result = MarkCompactResult::default();
+        for evacuator in &finished {
+            result.promoted_size += evacuator.promoted_size;
+            result.compacted_size += evacuator.compacted_size;
+        }
+        {
+            let _scope = tracer.scope(ScopeId::MC_EVACUATE_UPDATE_POINTERS);
+            self.update_pointers(heap, finished, num_tasks);
+        }
+        if heap.options().verify_heap
+            && let Err(error) = EvacuationVerifier::verify_after_mark_compact(heap)
+        {
+            panic!("{error}");
+        }
+        // Evacuated objects keep their forwarding address until their pages
+        // are released; copies are marked.
+        heap.notify_object_trackers(
+            GarbageCollector::MARK_COMPACTOR,
+            |object| match object.map_word() {
+                MapWord::Forwarding(target) => Some(target),
+                MapWord::Map(_) => None,
+            },
+            MarkingState::is_marked,
+        );
+
+        for page in self.evacuation_candidates.take() {
+            old_space.release_page(page);
+        }
+        new_space.reset();
+        result
+    }
+
+    /// Updates all slots referring to evacuated objects: the strong and
+    /// weak roots, the traced handles, the bodies of the evacuated objects,
+    /// the OLD_TO_NEW and OLD_TO_OLD slots of the remaining old pages and
+    /// the young keys of old ephemeron tables. The remembered sets are
+    /// dropped afterwards since the young generation is empty and
+    /// compaction is over.
+    fn update_pointers(&self, heap: &Heap, evacuated: Vec<FinishedEvacuator>, num_tasks: usize) {
+        heap.iterate_roots(update_slot);
+        heap.iterate_weak_roots(update_slot);
+        heap.iterate_traced_handles(update_slot);
+
+        let pages: Vec<&MemoryChunk> = heap
+            .old_space()
+            .pages()
+            .into_iter()
+            .chain(heap.lo_space().pages())
+            .filter(|page| !page.is_evacuation_candidate())
+            .collect();
+        let page_items: Vec<(&MemoryChunk, ParallelWorkItem)> = pages
+            .iter()
+            .filter(|page| !RememberedSet::<OLD_TO_NEW>::is_empty(page) || !RememberedSet::<OLD_TO_OLD>::is_empty(page))
+            .map(|&page| (page, ParallelWorkItem::new()))
+            .collect();
+        let evacuated_items: Vec<(Vec<HeapObject>, ParallelWorkItem)> = evacuated
+            .into_iter()
+            .map(|evacuator| (evacuator.evacuated_objects, ParallelWorkItem::new()))
+            .collect();
+        let run = || {
+            for (objects, item) in &evacuated_items {
+                if item.try_acquire() {
+                    for &object in objects {
+                        iterate_body(object, object.map(), &mut PointersUpdatingVisitor);
+                    }
+                }
+            }
+            for (page, item) in &page_items {
+                if item.try_acquire() {
+                    RememberedSet::<OLD_TO_NEW>::iterate(page, |slot| {
+                        update_slot(slot);
+                        SlotCallbackResult::KEEP_SLOT
+                    });
+                    RememberedSet::<OLD_TO_OLD>::iterate(page, |slot| {
+                        update_slot(slot);
+                        SlotCallbackResult::KEEP_SLOT
+                    });
+                }
+            }
+        };
+        std::thread::scope(|scope| {
+            let run = &run;
+            for _ in 1..num_tasks {
+                scope.spawn(run);
+            }
+            run();
+        });
 
-    impl WeakObjects {
-        pub fn new() -> Self {
-            WeakObjects{
-                current_ephemerons: EphemeronQueue::new(),
-                next_ephemerons: EphemeronQueue::new(),
+        for (table, entries) in heap.ephemeron_remembered_set().take() {
+            if table.chunk().is_evacuation_candidate() {
+                // Moved, or dead; the body of a moved table was updated.
+                continue;
+            }
+            for entry in entries {
+                update_slot(table.raw_field(EphemeronHashTableShape::key_index(entry)));
             }
         }
-    }
-
-    pub struct WeakObjectsLocal {
-        // Placeholder for actual fields
-        current_ephemerons_local: EphemeronQueueLocal,
-        next_ephemerons_local: EphemeronQueueLocal,
-    }
-
-    impl WeakObjectsLocal {
-        pub fn new() -> Self {
-            WeakObjectsLocal {
-                current_ephemerons_local: EphemeronQueueLocal::new(),
-                next_ephemerons_local: EphemeronQueueLocal::new(),
+        for page in pages {
+            // SAFETY: no task accesses the slot sets anymore.
+            unsafe {
+                RememberedSet::<OLD_TO_NEW>::clear(page);
+                RememberedSet::<OLD_TO_OLD>::clear(page);
             }
         }
     }
 
-    #[derive(Copy, Clone)]
-    pub struct Ephemeron {
-        key: usize,
-        value: usize,
-    }
-
-    pub struct EphemeronQueue {
-        //Placeholder for Queue data structure
-    }
-
-    impl EphemeronQueue {
-        pub fn new() -> Self {
-            EphemeronQueue {}
-        }
-
-        pub fn merge(&mut self, _other: EphemeronQueue) {
-            //Placeholder
-        }
-
-        pub fn is_empty(&self) -> bool {
-            true //Placeholder
+    /// Frees dead large objects and starts sweeping old space.
+    fn sweep(&self, heap: &Heap) {
+        let _scope = heap.tracer().scope(ScopeId::MC_SWEEP);
+        heap.lo_space().free_dead_objects();
+        let pages = heap
+            .old_space()
+            .prepare_for_sweeping()
+            .into_iter()
+            .map(|page| MemoryChunk::from_address(page.address()))
+            .collect();
+        heap.sweeper().start_sweeping(pages, heap.number_of_sweeper_tasks());
+        if !heap.options().concurrent_sweeping {
+            heap.complete_sweeping();
         }
     }
 
-    pub struct EphemeronQueueLocal {
-        //Placeholder for Queue data structure
+    fn finish(&self, heap: &Heap) {
+        let _scope = heap.tracer().scope(ScopeId::MC_FINISH);
+        heap.marking_barrier().deactivate();
+        heap.incremental_marking().stop();
+        self.marking_worklists.set_cpp_marking_state(None);
+        self.marking_worklists.clear();
+        heap.traced_handles().free_unused_nodes();
+        self.weak_objects.clear();
+        self.compacting.set(false);
     }
+}
 
-    impl EphemeronQueueLocal {
-        pub fn new() -> Self {
-            EphemeronQueueLocal {}
-        }
-
-        pub fn pop(&mut self, _ephemeron: &mut Ephemeron) -> bool {
-            false //Placeholder
-        }
-
-        pub fn push(&mut self, _ephemeron: Ephemeron) {
-            //Placeholder
-        }
-
-        pub fn is_local_and_global_empty(&self) -> bool {
-            true //Placeholder
-        }
-
-        pub fn is_local_empty(&self) -> bool {
-            true //Placeholder
-        }
-
-        pub fn publish(&mut self) {
-            //Placeholder
-        }
+/// Replaces a reference to an evacuated object by its new location.
+#[inline]
+fn update_slot(slot: ObjectSlot) {
+    let value = slot.load();
+    let Some(object) = value.get_heap_object() else {
+        return;
+    };
+    let chunk = object.chunk();
+    if !chunk.is_to_page() && !chunk.is_evacuation_candidate() {
+        return;
     }
-
-    struct RootMarkingVisitor<'a> {
-        collector: &'a mut MarkCompactCollector,
+    if let MapWord::Forwarding(target) = object.map_word() {
+        slot.store(value.with_object(target));
     }
+}
 
-    impl<'a> RootMarkingVisitor<'a> {
-        fn new(collector: &'a mut MarkCompactCollector) -> Self {
-            RootMarkingVisitor { collector }
-        }
-    }
+struct PointersUpdatingVisitor;
 
-    impl<'a> RootVisitorTrait for RootMarkingVisitor<'a> {
-        fn visit_root_pointers(&mut self, root: Root, description: &str, start: usize, end: usize) {
-            println!("Visiting root pointers: root={:?}, description={}, start={}, end={}", root, description, start, end);
+impl ObjectVisitor for PointersUpdatingVisitor {
+    #[inline]
+    fn visit_pointers(&mut self, _host: HeapObject, start: ObjectSlot, end: ObjectSlot) {
+        let mut slot = start;
+        while slot < end {
+            update_slot(slot);
+            slot = slot.offset(1);
         }
     }
-
-    struct MainMarkingVisitor {
-        //placeholder
-    }
-
-    impl MainMarkingVisitor {
-        //placeholder
-    }
 }
 
-mod flags {
-    //Placeholder for flag values
-    pub const parallel_compaction: bool = false;
+/// Per-task state of the evacuation phase.
+struct Evacuator<'a> {
+    allocator: EvacuationAllocator<'a>,
+    evacuated_objects: Vec<HeapObject>,
+    promoted_size: usize,
+    compacted_size: usize,
 }
 
-mod v8 {
-    pub mod base {
-        pub struct IncrementalMarkingSchedule {}
-    }
-    pub mod objects {
-        #[derive(Copy, Clone)]
-        pub struct HeapObject {}
-
-        pub struct Map {}
-    }
+/// Results of a task that are processed on the main thread.
+struct FinishedEvacuator {
+    evacuated_objects: Vec<HeapObject>,
+    promoted_size: usize,
+    compacted_size: usize,
 }
 
-mod base {
-    use std::any::Any;
-    use std::collections::HashSet;
-
-    #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-    pub struct EnumSet<T> {
-        // Placeholder
-        _phantom: std::marker::PhantomData<T>,
-    }
-
-    impl<T> EnumSet<T> {
-        pub fn new() -> Self {
-            EnumSet { _phantom: std::marker::PhantomData }
-        }
-
-        pub fn add(&mut self, _value: T) {
-            // Placeholder
-        }
-
-        pub fn contains(&self, _value: &T) -> bool {
-            // Placeholder
-            false
-        }
-
-        pub fn remove(&mut self, _value: &T) {
-            // Placeholder
+impl<'a> Evacuator<'a> {
+    fn new(new_space: &'a SemiSpaceNewSpace, old_space: &'a PagedSpace) -> Self {
+        Evacuator {
+            allocator: EvacuationAllocator::new(new_space, old_space),
+            evacuated_objects: Vec::new(),
+            promoted_size: 0,
+            compacted_size: 0,
         }
     }
 
-    pub struct RandomNumberGenerator {
-        // Placeholder
-    }
-
-    impl RandomNumberGenerator {
-        pub fn next_double(&self) -> f64 {
-            0.0 // Placeholder
-        }
-        pub fn next_int64(&self) -> i64 {
-            0 // Placeholder
-        }
-        pub fn next_sample(&self, _pages_size: usize, _pages_to_mark_count: usize) -> Vec<u64> {
-            Vec::new() // Placeholder
+    fn run(&mut self, items: &[(&MemoryChunk, ParallelWorkItem)]) {
+        for (page, item) in items {
+            if !item.try_acquire() {
+                continue;
+            }
+            let young = page.in_young_generation();
+            page.iterate_objects(|object| {
+                if MarkingState::is_marked(object) {
+                    self.evacuate_object(object, young);
+                }
+            });
+        }
+    }
+
+    /// Copies `object` into old space and installs the forwarding address.
+    /// The copy is marked so that the sweeper keeps it.
+    fn evacuate_object(&mut self, object: HeapObject, young: bool) {
+        let size = object.size();
+        let address = self
+            .allocator
+            .allocate(AllocationSpace::OLD_SPACE, size)
+            .expect("old space allocation cannot fail");
+        // SAFETY: `address` is fresh memory of `size` bytes and the mutator
+        // is paused.
+        let target = unsafe {
+            std::ptr::copy_nonoverlapping(object.address() as *const u8, address as *mut u8, size);
+            HeapObject::from_address(address)
+        };
+        MarkingState::try_mark_and_account_live_bytes(target, size);
+        object.set_map_word(MapWord::Forwarding(target));
+        if young {
+            self.promoted_size += size;
+        } else {
+            self.compacted_size += size;
+        }
+        self.evacuated_objects.push(target);
+    }
+
+    fn finalize(mut self) -> FinishedEvacuator {
+        self.allocator.finalize();
+        FinishedEvacuator {
+            evacuated_objects: self.evacuated_objects,
+            promoted_size: self.promoted_size,
+            compacted_size: self.compacted_size,
         }
     }
-
-}
-
-mod isolate {
-
-    pub trait IsolateTrait {
-    }
 }
 
-// Example usage
-fn main() {
-    use mark_compact::MarkCompactCollector;
-    use heap::Heap;
-
-    // Create a mock isolate, heap etc.
-    struct MockIsolate {}
-    impl isolate::IsolateTrait for MockIsolate{}
-
-    struct MockHeap {
-        isolate: Box<dyn isolate::IsolateTrait>,
-    }
-
-    impl Heap {
-        fn new(isolate: Box<dyn isolate::IsolateTrait>) -> Self {
-            MockHeap {
-                isolate: isolate,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap::gc_tracer::{EventType, GarbageCollectionReason};
+    use crate::heap::heap::{AllocationType, HeapOptions};
+    use crate::heap::heap_layout::HeapLayout;
+    use crate::heap::heap_test_utils::{allocate_list, full_gc, new_heap, verify_list};
+    use crate::include::libplatform::libplatform::IdleTaskSupport;
+    use crate::include::v8_metrics::{self, ContextId, GarbageCollectionFullCycle, GarbageCollectionYoungCycle};
+    use crate::libplatform::default_foreground_task_runner::DefaultForegroundTaskRunner;
+    use crate::logging::metrics::Recorder;
+    use std::sync::Mutex;
+
+    #[test]
+    fn full_gc_reclaims_garbage_and_preserves_object_graph() {
+        let heap = new_heap(false);
+        let old_list = allocate_list(&heap, 1000, AllocationType::kOld);
+        let young_list = allocate_list(&heap, 1000, AllocationType::kYoung);
+        for _ in 0..10_000 {
+            heap.allocate_fixed_array(8, AllocationType::kOld);
+        }
+
+        full_gc(&heap);
+
+        verify_list(&heap, old_list, 1000);
+        verify_list(&heap, young_list, 1000);
+        assert!(!HeapLayout::in_young_generation(heap.root_object(young_list)));
+        assert_eq!(heap.new_space().size(), 0);
+        assert_eq!(heap.old_generation_size_of_objects(), 2000 * 4 * 8);
+        assert_eq!(heap.promoted_objects_size(), 1000 * 4 * 8);
+        assert_eq!(heap.ms_count(), 1);
+        let event = heap.tracer().last_event();
+        assert_eq!(event.type_, EventType::MARK_COMPACTOR);
+        assert!(event.scope(ScopeId::MC_MARK) > Duration::ZERO);
+        assert!(heap.old_generation_allocation_limit() >= heap.options().initial_old_generation_size);
+    }
+
+    #[derive(Default)]
+    struct CycleRecorder {
+        full_cycles: Mutex<Vec<(GarbageCollectionFullCycle, ContextId)>>,
+        young_cycles: Mutex<Vec<GarbageCollectionYoungCycle>>,
+    }
+
+    impl v8_metrics::Recorder for CycleRecorder {
+        fn add_main_thread_event_garbage_collection_full_cycle(
+            &self,
+            event: &GarbageCollectionFullCycle,
+            context_id: ContextId,
+        ) {
+            self.full_cycles.lock().unwrap().push((event.clone(), context_id));
+        }
+
+        fn add_main_thread_event_garbage_collection_young_cycle(
+            &self,
+            event: &GarbageCollectionYoungCycle,
+            _context_id: ContextId,
+        ) {
+            self.young_cycles.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[test]
+    fn cycles_are_reported_to_the_metrics_recorder() {
+        let heap = new_heap(false);
+        let embedder_recorder = Arc::new(CycleRecorder::default());
+        let recorder = Arc::new(Recorder::new());
+        let task_runner = Arc::new(DefaultForegroundTaskRunner::new(IdleTaskSupport::kDisabled, Arc::new(|| 0.0)));
+        recorder.set_embedder_recorder(embedder_recorder.clone(), task_runner);
+        let context_id = recorder.new_context_id();
+        heap.tracer().set_metrics_recorder(Some(recorder.clone()));
+        heap.tracer().set_recorder_context_id(context_id);
+        allocate_list(&heap, 1000, AllocationType::kYoung);
+        for _ in 0..10_000 {
+            heap.allocate_fixed_array(8, AllocationType::kOld);
+        }
+
+        heap.collect_garbage(AllocationSpace::NEW_SPACE, GarbageCollectionReason::kTesting);
+        full_gc(&heap);
+        recorder.flush_delayed_events();
+
+        let young_cycles = embedder_recorder.young_cycles.lock().unwrap();
+        assert_eq!(young_cycles.len(), 1);
+        assert!(young_cycles[0].main_thread_wall_clock_duration_in_us >= 0);
+        let full_cycles = embedder_recorder.full_cycles.lock().unwrap();
+        assert_eq!(full_cycles.len(), 1);
+        let (cycle, id) = &full_cycles[0];
+        assert_eq!(*id, context_id);
+        assert_eq!(cycle.reason, GarbageCollectionReason::kTesting as i32);
+        assert!(cycle.main_thread_atomic.mark_wall_clock_duration_in_us >= 0);
+        assert!(cycle.total.total_wall_clock_duration_in_us >= cycle.main_thread.total_wall_clock_duration_in_us);
+        assert_eq!(cycle.objects.bytes_freed, cycle.objects.bytes_before - cycle.objects.bytes_after);
+        assert!(cycle.objects.bytes_freed >= 10_000 * 10 * 8);
+        assert!(cycle.collection_rate_in_percent > 0.0 && cycle.collection_rate_in_percent <= 100.0);
+    }
+
+    #[test]
+    fn compaction_evacuates_fragmented_pages() {
+        let heap = new_heap(false);
+        const K_OBJECTS: usize = 20_000;
+        let holder = heap.allocate_fixed_array(K_OBJECTS / 16, AllocationType::kOld);
+        let root = heap.create_root(Tagged::strong(holder));
+        for i in 0..K_OBJECTS {
+            let object = heap.allocate_fixed_array(4, AllocationType::kOld);
+            heap.set(object, 0, Tagged::from_smi(i as isize));
+            if i % 16 == 0 {
+                heap.set(holder, i / 16, Tagged::strong(object));
             }
         }
+        full_gc(&heap);
+        let addresses: Vec<_> = (0..K_OBJECTS / 16)
+            .map(|i| holder.get(i).get_heap_object().unwrap().address())
+            .collect();
+        let pages_before = heap.old_space().pages().len();
+
+        full_gc(&heap);
+
+        assert!(heap.tracer().last_event().scope(ScopeId::MC_EVACUATE_COPY) > Duration::ZERO);
+        assert!(heap.old_space().pages().len() < pages_before);
+        let holder = heap.root_object(root);
+        let mut moved = 0;
+        for (i, address) in addresses.into_iter().enumerate() {
+            let object = holder.get(i).get_heap_object().unwrap();
+            assert_eq!(object.get(0), Tagged::from_smi((i * 16) as isize));
+            moved += usize::from(object.address() != address);
+        }
+        assert!(moved > 0);
+    }
+
+    #[test]
+    fn marking_barrier_preserves_objects_moved_behind_the_wavefront() {
+        let heap = new_heap(false);
+        let holder = heap.allocate_fixed_array(1, AllocationType::kOld);
+        let holder_root = heap.create_root(Tagged::strong(holder));
+        let object = heap.allocate_fixed_array(1, AllocationType::kOld);
+        heap.set(object, 0, Tagged::from_smi(7));
+        heap.set(holder, 0, Tagged::strong(object));
+
+        heap.incremental_marking().start(&heap, GarbageCollectionReason::kTesting);
+        assert!(heap.marking_barrier().is_activated());
+        // Black allocated, i.e. never visited by the marker.
+        let black = heap.allocate_fixed_array(1, AllocationType::kOld);
+        let black_root = heap.create_root(Tagged::strong(black));
+        heap.set(black, 0, Tagged::strong(object));
+        heap.set(holder, 0, Tagged::ZERO);
+        heap.incremental_marking().step(&heap, usize::MAX);
+        assert!(heap.incremental_marking().should_finalize());
+        heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kFinalizeMarkingViaTask);
+
+        assert!(!heap.marking_barrier().is_activated());
+        assert!(heap.incremental_marking().is_stopped());
+        assert_eq!(heap.root_object(holder_root).get(0), Tagged::ZERO);
+        let object = heap.root_object(black_root).get(0).get_heap_object().unwrap();
+        assert_eq!(object.get(0), Tagged::from_smi(7));
+        let event = heap.tracer().last_event();
+        assert_eq!(event.type_, EventType::INCREMENTAL_MARK_COMPACTOR);
+        assert_eq!(event.incremental_marking_steps, 1);
+        assert!(event.scope(ScopeId::MC_INCREMENTAL_START) > Duration::ZERO);
+    }
+
+    #[test]
+    fn scavenges_during_incremental_marking_preserve_marks() {
+        let heap = new_heap(true);
+        let holder = heap.allocate_fixed_array(1, AllocationType::kOld);
+        let root = heap.create_root(Tagged::strong(holder));
+        heap.incremental_marking().start(&heap, GarbageCollectionReason::kTesting);
+        let young = heap.allocate_fixed_array(1, AllocationType::kYoung);
+        heap.set(young, 0, Tagged::from_smi(3));
+        heap.set(holder, 0, Tagged::strong(young));
+        heap.collect_garbage(AllocationSpace::NEW_SPACE, GarbageCollectionReason::kTesting);
+        heap.collect_garbage(AllocationSpace::NEW_SPACE, GarbageCollectionReason::kTesting);
+        let promoted = heap.root_object(root).get(0).get_heap_object().unwrap();
+        assert!(!HeapLayout::in_young_generation(promoted));
+        assert!(MarkingState::is_marked(promoted));
+
+        full_gc(&heap);
+        heap.complete_sweeping();
+        for _ in 0..1000 {
+            let filler = heap.allocate_fixed_array(1, AllocationType::kOld);
+            heap.set(filler, 0, Tagged::from_smi(-1));
+        }
+
+        let promoted = heap.root_object(root).get(0).get_heap_object().unwrap();
+        assert_eq!(promoted.get(0), Tagged::from_smi(3));
+        assert_eq!(heap.old_generation_size_of_objects(), 1002 * 3 * 8);
+    }
+
+    #[test]
+    fn weak_references_and_ephemerons_are_cleared() {
+        let heap = new_heap(false);
+        let holder = heap.allocate_weak_fixed_array(2, AllocationType::kOld);
+        let holder_root = heap.create_root(Tagged::strong(holder));
+        let table = heap.allocate_ephemeron_hash_table(2, AllocationType::kOld);
+        let table_root = heap.create_root(Tagged::strong(table));
+        let alive = heap.allocate_fixed_array(0, AllocationType::kOld);
+        let alive_root = heap.create_root(Tagged::strong(alive));
+        let dead = heap.allocate_fixed_array(0, AllocationType::kOld);
+        heap.set(holder, 0, Tagged::weak(alive));
+        heap.set(holder, 1, Tagged::weak(dead));
+        // The value of the live key is only reachable through the table.
+        let value = heap.allocate_fixed_array(1, AllocationType::kOld);
+        heap.set(value, 0, Tagged::from_smi(11));
+        heap.set(table, EphemeronHashTableShape::key_index(0), Tagged::strong(alive));
+        heap.set(table, EphemeronHashTableShape::value_index(0), Tagged::strong(value));
+        heap.set(table, EphemeronHashTableShape::key_index(1), Tagged::strong(dead));
+        heap.set(table, EphemeronHashTableShape::value_index(1), Tagged::from_smi(2));
+
+        full_gc(&heap);
+
+        let alive = Tagged::strong(heap.root_object(alive_root));
+        let holder = heap.root_object(holder_root);
+        assert_eq!(holder.get(0), Tagged::weak(alive.get_heap_object().unwrap()));
+        assert!(holder.get(1).is_cleared());
+        let table = heap.root_object(table_root);
+        assert_eq!(table.get(EphemeronHashTableShape::key_index(0)), alive);
+        let value = table.get(EphemeronHashTableShape::value_index(0)).get_heap_object().unwrap();
+        assert_eq!(value.get(0), Tagged::from_smi(11));
+        assert!(table.get(EphemeronHashTableShape::key_index(1)).is_cleared());
+        assert!(table.get(EphemeronHashTableShape::value_index(1)).is_cleared());
+    }
+
+    #[test]
+    fn allocation_drives_incremental_marking_to_completion() {
+        let heap = Heap::new(HeapOptions {
+            max_semi_space_size: MB,
+            initial_old_generation_size: 4 * MB,
+            ..HeapOptions::default()
+        });
+        let list = allocate_list(&heap, 5000, AllocationType::kYoung);
+        for i in 0..200_000 {
+            let allocation = if i % 2 == 0 {
+                AllocationType::kOld
+            } else {
+                AllocationType::kYoung
+            };
+            heap.allocate_fixed_array(16, allocation);
+        }
+        heap.run_incremental_marking_task();
+
+        assert!(heap.ms_count() > 0);
+        verify_list(&heap, list, 5000);
+        assert!(heap
+            .tracer()
+            .recorded_events()
+            .iter()
+            .any(|event| event.type_ == EventType::INCREMENTAL_MARK_COMPACTOR && event.incremental_marking_steps > 0));
+        assert!(heap.old_generation_size_of_objects() < heap.options().max_old_generation_size);
     }
-
-    impl MockHeap {
-        fn get_isolate(&self) -> &dyn isolate::IsolateTrait {
-            &self.isolate
-        }
-    }
-
-    let mock_isolate = Box::new(MockIsolate {});
-    let mock_heap = Box::new(Heap::new(mock_isolate));
-
-    let mut collector = MarkCompactCollector::new(Box::into_raw(mock_heap));
-    collector.prepare();
-    collector.collect_garbage();
-}
\ No newline at end of file
+}
@@ -2,918 +2,81 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/marking-barrier.h (module declaration)
-pub mod marking_barrier {
-    use crate::heap::*;
-    use crate::objects::*;
-    use std::collections::HashMap;
-
-    pub struct MarkingBarrier<'a> {
-        heap_: &'a Heap,
-        major_collector_: &'a MarkCompactCollector,
-        minor_collector_: &'a MinorMarkSweep,
-        incremental_marking_: &'a IncrementalMarking,
-        marking_state_: MarkingState<'a>,
-        is_main_thread_barrier_: bool,
-        uses_shared_heap_: bool,
-        is_shared_space_isolate_: bool,
-        current_worklists_: Option<MarkingWorklistsLocal<'a>>,
-        shared_heap_worklists_: Option<MarkingWorklistsLocal<'a>>,
-        is_compacting_: bool,
-        marking_mode_: MarkingMode,
-        typed_slots_map_: HashMap<*mut MutablePageMetadata, Option<TypedSlots>>,
-    }
-
-    impl<'a> MarkingBarrier<'a> {
-        pub fn new(local_heap: &'a LocalHeap) -> Self {
-            let heap_ = local_heap.heap();
-            let major_collector_ = heap_.mark_compact_collector();
-            let minor_collector_ = heap_.minor_mark_sweep_collector();
-            let incremental_marking_ = heap_.incremental_marking();
-            let marking_state_ = MarkingState::new(heap_.isolate());
-            let is_main_thread_barrier_ = local_heap.is_main_thread();
-            let uses_shared_heap_ = heap_.isolate().has_shared_space();
-            let is_shared_space_isolate_ = heap_.isolate().is_shared_space_isolate();
-
-            MarkingBarrier {
-                heap_: heap_,
-                major_collector_: major_collector_,
-                minor_collector_: minor_collector_,
-                incremental_marking_: incremental_marking_,
-                marking_state_: marking_state_,
-                is_main_thread_barrier_: is_main_thread_barrier_,
-                uses_shared_heap_: uses_shared_heap_,
-                is_shared_space_isolate_: is_shared_space_isolate_,
-                current_worklists_: None,
-                shared_heap_worklists_: None,
-                is_compacting_: false,
-                marking_mode_: MarkingMode::NoMarking,
-                typed_slots_map_: HashMap::new(),
-            }
-        }
-
-        pub fn write(&mut self, host: TaggedHeapObject, slot: IndirectPointerSlot) {
-            if cfg!(feature = "v8_enable_sandbox") {
-                debug_assert!(self.is_current_marking_barrier(host));
-                debug_assert!(self.is_activated() || self.shared_heap_worklists_.is_some());
-                debug_assert!(MemoryChunk::from_heap_object(host).is_marking());
-
-                // An indirect pointer slot can only contain a Smi if it is uninitialized (in
-                // which case the vaue will be Smi::zero()). However, at this point the slot
-                // must have been initialized because it was just written to.
-                let value = slot.load(self.isolate()).cast::<HeapObject>().unwrap();
-
-                // If the host is in shared space, the target must be in the shared trusted
-                // space. No other edges indirect pointers are currently possible in shared
-                // space.
-                debug_assert!(
-                    !HeapLayout::in_writable_shared_space(host) ||
-                        MemoryChunk::from_heap_object(value).metadata().owner().identity() ==
-                            SHARED_TRUSTED_SPACE
-                );
-
-                if HeapLayout::in_read_only_space(value) {
-                    return;
-                }
-
-                debug_assert!(!HeapLayout::in_young_generation(value));
-
-                if self.uses_shared_heap_ && !self.is_shared_space_isolate_ {
-                    if HeapLayout::in_writable_shared_space(value) {
-                        // References to the shared trusted space may only originate from the
-                        // shared space.
-                        assert!(HeapLayout::in_writable_shared_space(host));
-                        debug_assert!(MemoryChunk::from_heap_object(value).is_trusted());
-                        self.mark_value_shared(value);
-                    } else {
-                        self.mark_value_local(value);
-                    }
-                } else {
-                    self.mark_value_local(value);
-                }
-
-                // We don't need to record a slot here because the entries in the pointer
-                // tables are not compacted and because the pointers stored in the table
-                // entries are updated after compacting GC.
-                static_assertions::const_assert!(!CodePointerTable::k_supports_compaction);
-                static_assertions::const_assert!(!TrustedPointerTable::k_supports_compaction);
-            } else {
-                unreachable!();
-            }
-        }
-
-        pub fn write_without_host(&mut self, value: TaggedHeapObject) {
-            debug_assert!(self.is_main_thread_barrier_);
-            debug_assert!(self.is_activated());
-
-            // Without a shared heap and on the shared space isolate (= main isolate) all
-            // objects are considered local.
-            if self.uses_shared_heap_ && !self.is_shared_space_isolate_ {
-                // On client isolates (= worker isolates) shared values can be ignored.
-                if HeapLayout::in_writable_shared_space(value) {
-                    return;
-                }
-            }
-            if HeapLayout::in_read_only_space(value) {
-                return;
-            }
-            self.mark_value_local(value);
-        }
-
-        pub fn write_instruction_stream(
-            &mut self,
-            host: TaggedInstructionStream,
-            reloc_info: &mut RelocInfo,
-            value: TaggedHeapObject,
-        ) {
-            debug_assert!(self.is_current_marking_barrier(host.cast()));
-            debug_assert!(!HeapLayout::in_writable_shared_space(host.cast()));
-            debug_assert!(self.is_activated() || self.shared_heap_worklists_.is_some());
-            debug_assert!(MemoryChunk::from_heap_object(host.cast()).is_marking());
-
-            self.mark_value(host.cast(), value);
-
-            if self.is_compacting_ {
-                debug_assert!(self.is_major());
-                if self.is_main_thread_barrier_ {
-                    // An optimization to avoid allocating additional typed slots for the
-                    // main thread.
-                    self.major_collector_
-                        .record_reloc_slot(host.cast(), reloc_info, value);
-                } else {
-                    self.record_reloc_slot(host.cast(), reloc_info, value);
-                }
-            }
-        }
-
-        pub fn write_js_array_buffer(
-            &mut self,
-            host: TaggedJsArrayBuffer,
-            extension: &mut ArrayBufferExtension,
-        ) {
-            debug_assert!(self.is_current_marking_barrier(host.cast()));
-            debug_assert!(!HeapLayout::in_writable_shared_space(host.cast()));
-            debug_assert!(MemoryChunk::from_heap_object(host.cast()).is_marking());
-
-            if self.is_minor() {
-                if HeapLayout::in_young_generation(host.cast()) {
-                    extension.young_mark();
-                }
-            } else {
-                extension.mark();
-            }
-        }
-
-        pub fn write_descriptor_array(
-            &mut self,
-            descriptor_array: TaggedDescriptorArray,
-            number_of_own_descriptors: i32,
-        ) {
-            debug_assert!(self.is_current_marking_barrier(descriptor_array.cast()));
-            debug_assert!(HeapLayout::in_read_only_space(descriptor_array.map()));
-            debug_assert!(MemoryChunk::from_heap_object(descriptor_array.cast()).is_marking());
-
-            // Only major GC uses custom liveness.
-            if self.is_minor() || is_strong_descriptor_array(descriptor_array) {
-                self.mark_value_local(descriptor_array.cast());
-                return;
-            }
-
-            let gc_epoch: u32;
-            let worklist: &mut MarkingWorklistsLocal;
-
-            if self.uses_shared_heap_
-                && HeapLayout::in_writable_shared_space(descriptor_array.cast())
-                && !self.is_shared_space_isolate_
-            {
-                gc_epoch = self
-                    .isolate()
-                    .shared_space_isolate()
-                    .heap()
-                    .mark_compact_collector()
-                    .epoch();
-                debug_assert!(self.shared_heap_worklists_.is_some());
-                worklist = self.shared_heap_worklists_.as_mut().unwrap();
-            } else {
-                #[cfg(debug_assertions)]
-                {
-                    if let Some(target_worklist) =
-                        MarkingHelper::should_mark_object(self.heap_, descriptor_array.cast())
-                    {
-                        debug_assert_eq!(
-                            target_worklist,
-                            MarkingHelperWorklistTarget::Regular
-                        );
-                    } else {
-                        debug_assert!(HeapLayout::in_black_allocated_page(descriptor_array.cast()));
-                    }
-                }
-                gc_epoch = self.major_collector_.epoch();
-                worklist = self.current_worklists_.as_mut().unwrap();
-            }
-
-            // The DescriptorArray needs to be marked black here to ensure that slots
-            // are recorded by the Scavenger in case the DescriptorArray is promoted
-            // while incremental marking is running. This is needed as the regular
-            // marking visitor does not re-process any already marked descriptors. If we
-            // don't mark it black here, the Scavenger may promote a DescriptorArray and
-            // any already marked descriptors will not have any slots recorded.
-            if v8_flags::black_allocated_pages {
-                // Make sure to only mark the descriptor array for non black allocated
-                // pages. The atomic pause will fix it afterwards.
-                if MarkingHelper::should_mark_object(self.heap_, descriptor_array.cast()).is_some()
-                {
-                    self.marking_state_.try_mark(descriptor_array.cast());
-                }
-            } else {
-                self.marking_state_.try_mark(descriptor_array.cast());
-            }
-
-            // `TryUpdateIndicesToMark()` acts as a barrier that publishes the slots'
-            // values corresponding to `number_of_own_descriptors`.
-            if DescriptorArrayMarkingState::try_update_indices_to_mark(
-                gc_epoch,
-                descriptor_array,
-                number_of_own_descriptors,
-            ) {
-                worklist.push(descriptor_array.cast());
-            }
-        }
-
-        fn record_reloc_slot(
-            &mut self,
-            host: TaggedInstructionStream,
-            rinfo: &mut RelocInfo,
-            target: TaggedHeapObject,
-        ) {
-            debug_assert!(self.is_current_marking_barrier(host.cast()));
-            if !MarkCompactCollector::should_record_reloc_slot(host.cast(), rinfo, target) {
-                return;
-            }
-
-            let info = MarkCompactCollector::process_reloc_info(host.cast(), rinfo, target);
-
-            let typed_slots = self
-                .typed_slots_map_
-                .entry(info.page_metadata)
-                .or_insert(None);
-
-            if typed_slots.is_none() {
-                *typed_slots = Some(TypedSlots::new());
-            }
-
-            typed_slots
-                .as_mut()
-                .unwrap()
-                .insert(info.slot_type, info.offset);
-        }
-
-        fn mark_value_local(&mut self, value: TaggedHeapObject) {
-            // Placeholder for marking value locally. Implementation depends on the specifics of the V8 heap.
-            println!("Marking value locally: {:?}", value);
-            unimplemented!()
-        }
-
-        fn mark_value_shared(&mut self, value: TaggedHeapObject) {
-            // Placeholder for marking value in shared heap. Implementation depends on specifics of V8 shared heap.
-            println!("Marking value in shared heap: {:?}", value);
-            unimplemented!()
-        }
-
-        fn mark_value(&mut self, host: TaggedHeapObject, value: TaggedHeapObject) {
-            // Placeholder for marking value. Implementation depends on the specifics of the V8 heap.
-            println!("Marking value: {:?} from host: {:?}", value, host);
-            unimplemented!()
-        }
-
-        pub fn activate_all(heap: &mut Heap, is_compacting: bool) {
-            activate_spaces(heap, MarkingMode::MajorMarking);
-
-            heap.safepoint().iterate_local_heaps(|local_heap| {
-                local_heap
-                    .marking_barrier()
-                    .activate(is_compacting, MarkingMode::MajorMarking);
-            });
-
-            if heap.isolate().is_shared_space_isolate() {
-                heap.isolate()
-                    .shared_space_isolate()
-                    .global_safepoint()
-                    .iterate_client_isolates(|client| {
-                        // Force the RecordWrite builtin into the incremental marking code
-                        // path.
-                        client.heap().set_is_marking_flag(true);
-                        client
-                            .heap()
-                            .safepoint()
-                            .iterate_local_heaps(|local_heap| {
-                                local_heap.marking_barrier().activate_shared();
-                            });
-                    });
-            }
-        }
-
-        pub fn activate_young(heap: &mut Heap) {
-            activate_spaces(heap, MarkingMode::MinorMarking);
-
-            heap.safepoint().iterate_local_heaps(|local_heap| {
-                local_heap
-                    .marking_barrier()
-                    .activate(false, MarkingMode::MinorMarking);
-            });
-        }
-
-        pub fn activate(&mut self, is_compacting: bool, marking_mode: MarkingMode) {
-            debug_assert!(!self.is_activated_);
-            self.is_compacting_ = is_compacting;
-            self.marking_mode_ = marking_mode;
-            self.current_worklists_ = Some(MarkingWorklistsLocal::new(
-                if self.is_minor() {
-                    self.minor_collector_.marking_worklists()
-                } else {
-                    self.major_collector_.marking_worklists()
-                },
-            ));
-            self.is_activated_ = true;
-        }
-
-        pub fn activate_shared(&mut self) {
-            debug_assert!(self.shared_heap_worklists_.is_none());
-            let shared_isolate = self.isolate().shared_space_isolate();
-            self.shared_heap_worklists_ = Some(MarkingWorklistsLocal::new(
-                shared_isolate.heap().mark_compact_collector().marking_worklists(),
-            ));
-        }
-
-        pub fn deactivate_all(heap: &mut Heap) {
-            deactivate_spaces(heap, MarkingMode::MajorMarking);
-
-            heap.safepoint().iterate_local_heaps(|local_heap| {
-                local_heap.marking_barrier().deactivate();
-            });
-
-            if heap.isolate().is_shared_space_isolate() {
-                heap.isolate()
-                    .shared_space_isolate()
-                    .global_safepoint()
-                    .iterate_client_isolates(|client| {
-                        // We can't just simply disable the marking barrier for all clients. A
-                        // client may still need it to be set for incremental marking in the
-                        // local heap.
-                        let is_marking = client.heap().incremental_marking().is_marking();
-                        client.heap().set_is_marking_flag(is_marking);
-                        client
-                            .heap()
-                            .safepoint()
-                            .iterate_local_heaps(|local_heap| {
-                                local_heap.marking_barrier().deactivate_shared();
-                            });
-                    });
-            }
-        }
-
-        pub fn deactivate_young(heap: &mut Heap) {
-            deactivate_spaces(heap, MarkingMode::MinorMarking);
-
-            heap.safepoint().iterate_local_heaps(|local_heap| {
-                local_heap.marking_barrier().deactivate();
-            });
-        }
-
-        pub fn deactivate(&mut self) {
-            debug_assert!(self.is_activated_);
-            self.is_activated_ = false;
-            self.is_compacting_ = false;
-            self.marking_mode_ = MarkingMode::NoMarking;
-            debug_assert!(self.typed_slots_map_.is_empty());
-            debug_assert!(self.current_worklists_.as_ref().unwrap().is_empty());
-            self.current_worklists_ = None;
-        }
-
-        pub fn deactivate_shared(&mut self) {
-            debug_assert!(self.shared_heap_worklists_.as_ref().unwrap().is_empty());
-            self.shared_heap_worklists_ = None;
-        }
-
-        pub fn publish_all(heap: &mut Heap) {
-            heap.safepoint().iterate_local_heaps(|local_heap| {
-                local_heap.marking_barrier().publish_if_needed();
-            });
-
-            if heap.isolate().is_shared_space_isolate() {
-                heap.isolate()
-                    .shared_space_isolate()
-                    .global_safepoint()
-                    .iterate_client_isolates(|client| {
-                        client
-                            .heap()
-                            .safepoint()
-                            .iterate_local_heaps(|local_heap| {
-                                local_heap.marking_barrier().publish_shared_if_needed();
-                            });
-                    });
-            }
-        }
-
-        pub fn publish_young(heap: &mut Heap) {
-            heap.safepoint().iterate_local_heaps(|local_heap| {
-                local_heap.marking_barrier().publish_if_needed();
-            });
-        }
-
-        pub fn publish_if_needed(&mut self) {
-            if self.is_activated_ {
-                self.current_worklists_.as_mut().unwrap().publish();
-                for (memory_chunk, typed_slots) in &mut self.typed_slots_map_ {
-                    // Access to TypeSlots need to be protected, since LocalHeaps might
-                    // publish code in the background thread.
-                    //let guard = base::MutexGuard(memory_chunk.mutex()); //MutexGuard is a placeholder here, you would need to use a proper Rust Mutex
-                    if let Some(slots) = typed_slots.take() {
-                         unsafe {
-                            RememberedSet::<OldToOld>::merge_typed(*memory_chunk, slots);
-                        }
-                    }
-                }
-                self.typed_slots_map_.clear();
-            }
-        }
-
-        pub fn publish_shared_if_needed(&mut self) {
-            if self.shared_heap_worklists_.is_some() {
-                self.shared_heap_worklists_.as_mut().unwrap().publish();
-            }
-        }
-
-        pub fn is_current_marking_barrier(&self, verification_candidate: TaggedHeapObject) -> bool {
-            WriteBarrier::current_marking_barrier(verification_candidate) as *const _ == self as *const _
-        }
-
-        pub fn isolate(&self) -> &Isolate {
-            self.heap_.isolate()
-        }
-
-        #[cfg(debug_assertions)]
-        pub fn assert_marking_is_activated(&self) {
-            debug_assert!(self.is_activated_);
-        }
-
-        #[cfg(debug_assertions)]
-        pub fn assert_shared_marking_is_activated(&self) {
-            debug_assert!(self.shared_heap_worklists_.is_some());
-        }
-
-        #[cfg(debug_assertions)]
-        pub fn is_marked(&self, value: TaggedHeapObject) -> bool {
-            self.marking_state_.is_marked(value)
-        }
-
-        fn is_minor(&self) -> bool {
-            self.marking_mode_ == MarkingMode::MinorMarking
-        }
-
-        fn is_major(&self) -> bool {
-            self.marking_mode_ == MarkingMode::MajorMarking
-        }
-
-        fn is_activated(&self) -> bool {
-            self.is_activated_
-        }
-    }
-
-    //Helper enums and functions
-    #[derive(PartialEq, Copy, Clone)]
-    pub enum MarkingMode {
-        NoMarking,
-        MinorMarking,
-        MajorMarking,
-    }
-
-    mod v8_flags {
-        pub const black_allocated_pages: bool = false;
-    }
-
-    mod static_assertions {
-        pub mod const_assert {
-             // Placeholder for static assertions; replace with actual implementation if needed
-            #[macro_export]
-            macro_rules! const_assert {
-                ($condition:expr) => {
-                    // In a real implementation, this would cause a compile-time error if $condition is false
-                    const _: [(); if $condition { 1 } else { 0 }] = [];
-                };
-            }
-            pub(crate) use const_assert;
-        }
-
-    }
-
-    const SHARED_TRUSTED_SPACE: i32 = 0;
-
-    pub struct MarkingState<'a> {
-        isolate_: &'a Isolate,
-    }
-
-    impl<'a> MarkingState<'a> {
-        pub fn new(isolate: &'a Isolate) -> Self {
-            MarkingState { isolate_: isolate }
-        }
-
-        pub fn try_mark(&mut self, object: TaggedHeapObject) -> bool {
-            // Placeholder. Marking logic from V8 is missing
-            println!("Marking object: {:?}", object);
-            true
-        }
-
-        pub fn is_marked(&self, object: TaggedHeapObject) -> bool {
-            // Placeholder. Marking logic from V8 is missing
-            println!("Checking if object is marked: {:?}", object);
-            false
-        }
-    }
-
-    pub struct MarkingWorklistsLocal<'a> {
-        marking_worklists: &'a MarkingWorklists,
-    }
-
-    impl<'a> MarkingWorklistsLocal<'a> {
-        pub fn new(marking_worklists: &'a MarkingWorklists) -> Self {
-            MarkingWorklistsLocal {
-                marking_worklists,
-            }
-        }
-        pub fn push(&mut self, object: TaggedHeapObject){
-            println!("Pushing object to worklist: {:?}", object);
-        }
-
-        pub fn publish(&mut self){
-            println!("Publishing worklist");
-        }
-
-        pub fn is_empty(&self) -> bool {
-            true
-        }
-    }
-    // Placeholder structs and enums, needs implementation based on V8 details
-    pub struct Heap {
-        isolate_: Isolate,
-        mark_compact_collector_: MarkCompactCollector,
-        minor_mark_sweep_collector_: MinorMarkSweep,
-        incremental_marking_: IncrementalMarking,
-        old_space_: OldSpace,
-        lo_space_: OldLargeObjectSpace,
-        new_space_: Option<NewSpace>,
-        new_lo_space_: NewLargeObjectSpace,
-        code_space_: CodeSpace,
-        code_lo_space_: CodeLargeObjectSpace,
-        shared_space_: Option<SharedSpace>,
-        shared_lo_space_: Option<SharedLargeObjectSpace>,
-        trusted_space_: TrustedSpace,
-        trusted_lo_space_: TrustedLargeObjectSpace,
-        safepoint_: Safepoint,
-    }
-
-    impl Heap {
-        pub fn isolate(&self) -> &Isolate {
-            &self.isolate_
-        }
-        pub fn mark_compact_collector(&self) -> &MarkCompactCollector {
-            &self.mark_compact_collector_
-        }
-        pub fn minor_mark_sweep_collector(&self) -> &MinorMarkSweep {
-            &self.minor_mark_sweep_collector_
-        }
-        pub fn incremental_marking(&self) -> &IncrementalMarking {
-            &self.incremental_marking_
-        }
-        pub fn old_space(&mut self) -> &mut OldSpace {
-            &mut self.old_space_
-        }
-        pub fn lo_space(&mut self) -> &mut OldLargeObjectSpace {
-            &mut self.lo_space_
-        }
-        pub fn new_space(&mut self) -> &mut Option<NewSpace> {
-            &mut self.new_space_
-        }
-        pub fn new_lo_space(&mut self) -> &mut NewLargeObjectSpace {
-            &mut self.new_lo_space_
-        }
-        pub fn code_space(&mut self) -> &mut CodeSpace {
-            &mut self.code_space_
-        }
-        pub fn code_lo_space(&mut self) -> &mut CodeLargeObjectSpace {
-            &mut self.code_lo_space_
-        }
-        pub fn shared_space(&mut self) -> &mut Option<SharedSpace> {
-            &mut self.shared_space_
-        }
-        pub fn shared_lo_space(&mut self) -> &mut Option<SharedLargeObjectSpace> {
-            &mut self.shared_lo_space_
-        }
-        pub fn trusted_space(&mut self) -> &mut TrustedSpace {
-            &mut self.trusted_space_
-        }
-        pub fn trusted_lo_space(&mut self) -> &mut TrustedLargeObjectSpace {
-            &mut self.trusted_lo_space_
-        }
-
-        pub fn safepoint(&mut self) -> &mut Safepoint {
-            &mut self.safepoint_
-        }
-        pub fn set_is_marking_flag(&mut self, is_marking: bool) {
-            // Placeholder; Add actual implementation if needed
-            println!("Setting is marking flag to {:?}", is_marking);
-        }
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+use crate::heap::heap_layout::{HeapObject, ObjectSlot, Tagged};
+use crate::heap::marking_state::MarkingState;
+use crate::heap::marking_visitor::record_slot;
+use crate::heap::marking_worklist::{MarkingWorklists, K_MARKING_WORKLIST_SEGMENT_SIZE};
+
+/// The write barrier of full marking. While marking is active, every object
+/// stored into the heap is marked, so that objects the mutator moves behind
+/// already visited objects are not missed (a Dijkstra-style insertion
+/// barrier). Newly marked objects are buffered and published to the shared
+/// marking worklist in segments.
+///
+/// While compacting, stored slots referring to evacuation candidates are
+/// recorded as well.
+pub struct MarkingBarrier {
+    worklists: Arc<MarkingWorklists>,
+    is_activated: Cell<bool>,
+    is_compacting: Cell<bool>,
+    buffer: RefCell<Vec<HeapObject>>,
+}
 
-        pub fn has_shared_space(&self) -> bool {
-            self.shared_space_.is_some()
+impl MarkingBarrier {
+    pub fn new(worklists: Arc<MarkingWorklists>) -> Self {
+        MarkingBarrier {
+            worklists,
+            is_activated: Cell::new(false),
+            is_compacting: Cell::new(false),
+            buffer: RefCell::new(Vec::new()),
         }
     }
 
-    pub struct Isolate {
-        shared_space_isolate_: Option<Box<Isolate>>,
-        is_shared_space_isolate_: bool,
-        heap_: Heap,
+    #[inline]
+    pub fn is_activated(&self) -> bool {
+        self.is_activated.get()
     }
 
-    impl Isolate {
-        pub fn shared_space_isolate(&self) -> &Isolate {
-            self.shared_space_isolate_.as_ref().unwrap()
-        }
-        pub fn is_shared_space_isolate(&self) -> bool {
-            self.is_shared_space_isolate_
-        }
-
-        pub fn heap(&mut self) -> &mut Heap {
-            &mut self.heap_
-        }
-        pub fn has_shared_space(&self) -> bool {
-            self.heap_.has_shared_space()
-        }
+    pub fn activate(&self, is_compacting: bool) {
+        debug_assert!(!self.is_activated());
+        self.is_activated.set(true);
+        self.is_compacting.set(is_compacting);
     }
 
-    pub struct MarkCompactCollector {
-        epoch_: u32,
-        marking_worklists_: MarkingWorklists,
+    pub fn deactivate(&self) {
+        debug_assert!(self.buffer.borrow().is_empty());
+        self.is_activated.set(false);
+        self.is_compacting.set(false);
     }
 
-    impl MarkCompactCollector {
-        pub fn epoch(&self) -> u32 {
-            self.epoch_
-        }
-        pub fn marking_worklists(&self) -> &MarkingWorklists {
-            &self.marking_worklists_
-        }
-        pub fn record_reloc_slot(&mut self, host: TaggedHeapObject, reloc_info: &mut RelocInfo, target: TaggedHeapObject){
-            println!("Recording reloc slot. host: {:?}, target: {:?}", host, target);
-        }
-
-        pub fn should_record_reloc_slot(host: TaggedHeapObject, rinfo: &mut RelocInfo, target: TaggedHeapObject) -> bool {
-            true
-        }
-
-        pub fn process_reloc_info(host: TaggedHeapObject, rinfo: &mut RelocInfo, target: TaggedHeapObject) -> MarkCompactCollectorRecordRelocSlotInfo {
-            MarkCompactCollectorRecordRelocSlotInfo {
-                page_metadata: unsafe {std::mem::transmute(1u64)}, //dummy value
-                slot_type: SlotType::CodeTarget,
-                offset: 0,
+    /// Barrier for a store of `value` into `slot` of `host`. Weak values
+    /// are marked as well; they are kept alive conservatively until the
+    /// next cycle.
+    pub fn write(&self, host: HeapObject, slot: ObjectSlot, value: Tagged) {
+        debug_assert!(self.is_activated());
+        let Some(object) = value.get_heap_object() else {
+            return;
+        };
+        if MarkingState::try_mark_and_account_live_bytes(object, object.size()) {
+            let mut buffer = self.buffer.borrow_mut();
+            buffer.push(object);
+            if buffer.len() == K_MARKING_WORKLIST_SEGMENT_SIZE {
+                self.worklists.shared().push(std::mem::take(&mut *buffer));
             }
         }
-    }
-
-    pub struct MarkCompactCollectorRecordRelocSlotInfo {
-        pub page_metadata: *mut MutablePageMetadata,
-        pub slot_type: SlotType,
-        pub offset: usize,
-    }
-
-    pub struct MinorMarkSweep {
-        marking_worklists_: MarkingWorklists,
-    }
-
-    impl MinorMarkSweep {
-        pub fn marking_worklists(&self) -> &MarkingWorklists {
-            &self.marking_worklists_
-        }
-    }
-    pub struct IncrementalMarking {}
-    impl IncrementalMarking {
-         pub fn is_marking(&self) -> bool {
-            false
-        }
-    }
-    pub struct LocalHeap {
-        heap_: Heap,
-        is_main_thread_: bool,
-        marking_barrier_: MarkingBarrier<'static>,
-    }
-
-    impl LocalHeap {
-        pub fn heap(&self) -> &Heap {
-            &self.heap_
-        }
-        pub fn is_main_thread(&self) -> bool {
-            self.is_main_thread_
-        }
-        pub fn marking_barrier(&mut self) -> &mut MarkingBarrier<'static>{
-            unsafe { std::mem::transmute(&mut self.marking_barrier_)}
-        }
-    }
-
-    pub struct Safepoint {}
-    impl Safepoint {
-        pub fn iterate_local_heaps<F>(&mut self, mut f: F)
-        where
-            F: FnMut(&mut LocalHeap),
-        {
-            // Placeholder, add real iteration logic if needed
-            println!("Iterating over local heaps");
-        }
-
-        pub fn iterate_client_isolates<F>(&mut self, mut f: F)
-        where
-            F: FnMut(&mut Isolate),
-        {
-            // Placeholder, add real iteration logic if needed
-            println!("Iterating over client isolates");
-        }
-    }
-
-    pub struct MarkingWorklists {}
-
-    pub struct TypedSlots {}
-    impl TypedSlots {
-        pub fn new() -> Self {
-            TypedSlots {}
-        }
-
-        pub fn insert(&mut self, slot_type: SlotType, offset: usize){
-            println!("Inserting typed slot. SlotType: {:?}, offset: {}", slot_type, offset);
-        }
-    }
-
-    pub struct RememberedSet<T> {
-        phantom: std::marker::PhantomData<T>,
-    }
-
-    impl<T> RememberedSet<T> {
-        pub fn merge_typed(memory_chunk: *mut MutablePageMetadata, typed_slots: TypedSlots) {
-            println!("Merging typed remembered set on chunk: {:?}", memory_chunk);
-        }
-    }
-
-    pub enum OldToOld {}
-
-    pub struct MemoryChunk {}
-    impl MemoryChunk {
-        pub fn from_heap_object(obj: TaggedHeapObject) -> MemoryChunkRef {
-            MemoryChunkRef {}
+        if self.is_compacting.get() {
+            record_slot(host, slot, object);
         }
     }
 
-    pub struct MemoryChunkRef {}
-
-    impl MemoryChunkRef {
-        pub fn is_marking(&self) -> bool {
-            false
-        }
-
-        pub fn metadata(&self) -> MemoryChunkMetadataRef {
-            MemoryChunkMetadataRef {}
-        }
-    }
-
-    pub struct MemoryChunkMetadataRef {}
-    impl MemoryChunkMetadataRef {
-        pub fn owner(&self) -> MemoryChunkMetadataOwnerRef {
-            MemoryChunkMetadataOwnerRef {}
-        }
-    }
-
-    pub struct MemoryChunkMetadataOwnerRef {}
-    impl MemoryChunkMetadataOwnerRef {
-        pub fn identity(&self) -> i32 {
-            0
-        }
-    }
-
-    pub struct OldSpace {}
-    pub struct OldLargeObjectSpace {}
-    pub struct NewSpace {}
-    pub struct NewLargeObjectSpace {}
-    pub struct CodeSpace {}
-    pub struct CodeLargeObjectSpace {}
-    pub struct SharedSpace {}
-    pub struct SharedLargeObjectSpace {}
-    pub struct TrustedSpace {}
-    pub struct TrustedLargeObjectSpace {}
-    pub struct MutablePageMetadata {
-
-    }
-
-    pub struct MarkingHelper {}
-
-    impl MarkingHelper {
-        pub fn should_mark_object(heap: &Heap, obj: TaggedHeapObject) -> Option<MarkingHelperWorklistTarget> {
-           None
-        }
-    }
-
-    #[derive(PartialEq, Eq)]
-    pub enum MarkingHelperWorklistTarget {
-        Regular
-    }
-
-    pub struct DescriptorArrayMarkingState {}
-
-    impl DescriptorArrayMarkingState {
-        pub fn try_update_indices_to_mark(gc_epoch: u32, array: TaggedDescriptorArray, number_of_own_descriptors: i32) -> bool {
-            true
+    /// Publishes buffered objects to the shared marking worklist.
+    pub fn publish(&self) {
+        let mut buffer = self.buffer.borrow_mut();
+        if !buffer.is_empty() {
+            self.worklists.shared().push(std::mem::take(&mut *buffer));
         }
     }
 }
-
-mod heap {
-    use super::objects::*;
-
-    pub struct RelocInfo {}
-    pub struct ArrayBufferExtension {}
-
-    impl ArrayBufferExtension {
-        pub fn young_mark(&mut self){
-            println!("Young Mark called");
-        }
-
-        pub fn mark(&mut self){
-            println!("Mark called");
-        }
-    }
-
-    #[derive(Debug)]
-    pub enum SlotType {
-        CodeTarget,
-    }
-
-    // Placeholder for WriteBarrier
-    pub struct WriteBarrier {}
-
-    impl WriteBarrier {
-        pub fn current_marking_barrier(verification_candidate: TaggedHeapObject) -> *const (){
-            std::ptr::null()
-        }
-    }
-
-    pub struct HeapLayout {}
-
-    impl HeapLayout {
-        pub fn in_writable_shared_space(obj: TaggedHeapObject) -> bool {
-            false
-        }
-
-        pub fn in_read_only_space(obj: TaggedHeapObject) -> bool {
-            false
-        }
-
-        pub fn in_young_generation(obj: TaggedHeapObject) -> bool {
-            false
-        }
-
-        pub fn in_black_allocated_page(obj: TaggedHeapObject) -> bool {
-            false
-        }
-    }
-}
-
-mod objects {
-    #[derive(Copy, Clone, Debug)]
-    pub struct TaggedHeapObject(u64); // Placeholder
-
-    impl TaggedHeapObject {
-        pub fn cast<T>(&self) -> Tagged<T> {
-            Tagged(self.0)
-        }
-        pub fn map(&self) -> Self {
-            *self
-        }
-    }
-
-    #[derive(Copy, Clone, Debug)]
-    pub struct TaggedInstructionStream(u64);
-
-    impl TaggedInstructionStream {
-         pub fn cast<T>(&self) -> Tagged<T> {
-            Tagged(self.0)
-        }
-    }
-
-    #[derive(Copy, Clone, Debug)]
-    pub struct TaggedJsArrayBuffer(u64);
-    impl TaggedJsArrayBuffer {
-        pub fn cast<T>(&self) -> Tagged<T> {
-            Tagged(self.0)
-        }
-    }
-
-    #[derive(Copy, Clone, Debug)]
-    pub struct TaggedDescriptorArray(u64);
-    impl TaggedDescriptorArray {
-         
\ No newline at end of file
@@ -2,406 +2,328 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/marking-worklist.rs
+use std::sync::{Arc, Mutex, RwLock};
 
-use std::collections::HashMap;
-use std::cmp::Ordering;
-use std::sync::Mutex;
-use std::any::Any;
+use crate::heap::base::worklist::{Local, Worklist};
+use crate::heap::cppgc_js::cpp_marking_state::CppMarkingState;
+use crate::heap::heap_layout::{Address, HeapObject};
+use crate::heap::memory_measurement::NativeContextStats;
 
-// Placeholder for cppgc-js/cpp-heap.h
-mod cppgc_js {
-    pub struct CppHeap {}
-    pub struct CppMarkingState {}
-}
-
-// Placeholder for objects/heap-object-inl.h
-mod heap_object_inl {
-    use super::objects::{HeapObject, Map, InstanceType};
-
-    impl HeapObject {
-        pub fn map(&self) -> &Map {
-            // Placeholder implementation
-            &Map { instance_type: InstanceType::HeapNumberType } // Dummy value
-        }
-    }
-}
-
-// Placeholder for objects/heap-object.h
-mod objects {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-    pub enum InstanceType {
-        MapType,
-        HeapNumberType,
-        FreeSpaceType,
-        // ... other types
-    }
-
-    #[derive(Debug)]
-    pub struct HeapObject {
-        // Some data fields
-    }
-
-    #[derive(Debug)]
-    pub struct Map {
-        pub instance_type: InstanceType,
-    }
-}
+pub const K_MARKING_WORKLIST_SEGMENT_SIZE: usize = 64;
 
-// Placeholder for objects/instance-type-inl.h
-mod instance_type_inl {}
+/// Marked objects whose bodies still have to be visited.
+pub type MarkingWorklist = Worklist<HeapObject, K_MARKING_WORKLIST_SEGMENT_SIZE>;
+pub type MarkingWorklistLocal<'a> = Local<'a, HeapObject, K_MARKING_WORKLIST_SEGMENT_SIZE>;
 
-// Placeholder for objects/instance-type.h
-mod instance_type {
-    pub use super::objects::InstanceType;
-}
+/// Context of the shared worklist: objects reachable from the roots
+/// without passing through a native context.
+pub const K_SHARED_CONTEXT: Address = 0;
+/// Context of objects reachable from native contexts that are not
+/// measured.
+pub const K_OTHER_CONTEXT: Address = 8;
 
-// Placeholder for objects/map.h
-mod map {
-    pub use super::objects::Map;
-}
-
-// Placeholder for objects/objects-definitions.h
-mod objects_definitions {}
-
-// src/heap/marking-worklist-inl.rs
-mod marking_worklist_inl {
-    use super::MarkingWorklist;
-    use super::objects::HeapObject;
-    use std::sync::MutexGuard;
-    impl MarkingWorklist {
-        pub fn Iterate<F>(&self, mut f: F)
-        where
-            F: FnMut(&HeapObject),
-        {
-            let guard = self.0.lock().unwrap();
-            for obj in guard.queue.iter() {
-                f(obj);
-            }
-        }
-
-        pub fn IsLocalEmpty(&self) -> bool {
-            let guard = self.0.lock().unwrap();
-            guard.queue.is_empty()
-        }
-
-        pub fn IsGlobalEmpty(&self) -> bool {
-            // Assuming global queue is managed similarly and its state can be accessed through self
-            self.IsLocalEmpty() // Placeholder, replace with actual logic
-        }
-
-        pub fn Publish(&self) {
-            // Placeholder, replace with actual logic for publishing the local queue to a global queue
-        }
-
-        pub fn Merge(&self, other: &MarkingWorklist) {
-            // Placeholder, replace with actual logic for merging another worklist's contents into this one
-            let mut guard = self.0.lock().unwrap();
-            let other_guard = other.0.lock().unwrap();
-            guard.queue.extend_from_slice(&other_guard.queue);
-        }
-
-        pub fn Pop(&self, object: &mut Option<objects::HeapObject>) -> bool {
-            let mut guard = self.0.lock().unwrap();
-            if let Some(obj) = guard.queue.pop() {
-                *object = Some(obj);
-                true
-            } else {
-                false
-            }
-        }
-    }
-}
-
-#[derive(Default)]
-pub struct MarkingWorklist {
-    0: Mutex<MarkingWorklistInternal>,
+struct ContextWorklist {
+    context: Address,
+    worklist: Arc<MarkingWorklist>,
 }
 
+/// The worklists of a full marking cycle. The main thread, concurrent
+/// markers and the marking barrier push to and steal from the shared
+/// worklist through their local views.
+///
+/// While memory is measured, see `MemoryMeasurement`, marking runs in
+/// per-context mode: there is an additional worklist per measured native
+/// context and one for all other contexts. Objects are pushed to the
+/// worklist of the context of the object that referred to them, so that
+/// their sizes can be attributed to that context.
 #[derive(Default)]
-struct MarkingWorklistInternal {
-    queue: Vec<objects::HeapObject>,
-}
-
-impl MarkingWorklist {
-    pub fn Clear(&self) {
-        let mut guard = self.0.lock().unwrap();
-        guard.queue.clear();
-    }
-}
-
 pub struct MarkingWorklists {
-    shared_: MarkingWorklist,
-    on_hold_: MarkingWorklist,
-    other_: MarkingWorklist,
-    context_worklists_: Vec<ContextWorklistEntry>,
-}
-
-struct ContextWorklistEntry {
-    context: usize, // Address -> usize
-    worklist: Box<MarkingWorklist>,
+    shared: MarkingWorklist,
+    /// Sorted by context, including `K_OTHER_CONTEXT`. Empty unless in
+    /// per-context mode.
+    context_worklists: RwLock<Vec<ContextWorklist>>,
+    /// Sizes of the objects visited in per-context mode, merged from the
+    /// markers when they publish.
+    native_context_stats: Mutex<NativeContextStats>,
+    /// Marks the wrappables of API objects while a `CppHeap` is attached.
+    cpp_marking_state: RwLock<Option<CppMarkingState>>,
 }
 
 impl MarkingWorklists {
     pub fn new() -> Self {
-        MarkingWorklists {
-            shared_: MarkingWorklist::default(),
-            on_hold_: MarkingWorklist::default(),
-            other_: MarkingWorklist::default(),
-            context_worklists_: Vec::new(),
-        }
+        Self::default()
     }
 
-    pub fn Clear(&mut self) {
-        self.shared_.Clear();
-        self.on_hold_.Clear();
-        self.other_.Clear();
-        for cw in &self.context_worklists_ {
-            cw.worklist.Clear();
-        }
-        self.ReleaseContextWorklists();
-    }
-
-    pub fn Print(&self) {
-        self.PrintWorklist("shared", &self.shared_);
-        self.PrintWorklist("on_hold", &self.on_hold_);
+    pub fn shared(&self) -> &MarkingWorklist {
+        &self.shared
     }
 
-    fn PrintWorklist(&self, worklist_name: &str, worklist: &MarkingWorklist) {
-        #[cfg(debug_assertions)]
-        {
-            let mut count: HashMap<objects::InstanceType, i32> = HashMap::new();
-            let mut total_count = 0;
-            worklist.Iterate(|obj| {
-                total_count += 1;
-                *count.entry(obj.map().instance_type).or_insert(0) += 1;
-            });
-
-            let mut rank: Vec<(i32, objects::InstanceType)> =
-                count.into_iter().map(|(k, v)| (v, k)).collect();
-            rank.sort_by(|a, b| b.0.cmp(&a.0));
-
-            let mut instance_type_name: HashMap<objects::InstanceType, String> = HashMap::new();
-
-            macro_rules! instance_type_name {
-                ($name:ident) => {
-                    instance_type_name.insert(objects::InstanceType::$name, stringify!($name).to_string());
-                };
-            }
-
-            instance_type_name!(MapType);
-            instance_type_name!(HeapNumberType);
-            instance_type_name!(FreeSpaceType);
-
-            println!("Worklist {}: {}", worklist_name, total_count);
-            for i in rank {
-                let type_name = instance_type_name.get(&i.1).unwrap_or(&"Unknown".to_string());
-                println!("  [{}]: {}", type_name, i.0);
-            }
+    /// Returns a new local view on the worklists. Views created before
+    /// the context worklists or the `CppMarkingState` do not see them.
+    pub fn local(&self) -> MarkingWorklistsLocal<'_> {
+        let context_worklists = self
+            .context_worklists
+            .read()
+            .unwrap()
+            .iter()
+            .map(ContextWorklistLocal::new)
+            .collect();
+        MarkingWorklistsLocal {
+            shared: Local::new(&self.shared),
+            context_worklists,
+            active: None,
+            cpp_marking_state: self.cpp_marking_state.read().unwrap().clone(),
         }
     }
 
-    pub fn CreateContextWorklists(&mut self, contexts: &Vec<usize>) { // Address -> usize
-        assert!(self.context_worklists_.is_empty());
-        if contexts.is_empty() {
-            return;
-        }
+    /// Sets the state through which markers mark wrappables of API
+    /// objects for the cycle. Must be called before any local view is
+    /// created in the cycle.
+    pub fn set_cpp_marking_state(&self, cpp_marking_state: Option<CppMarkingState>) {
+        *self.cpp_marking_state.write().unwrap() = cpp_marking_state;
+    }
 
-        self.context_worklists_.reserve(contexts.len());
-        for &context in contexts {
-            self.context_worklists_.push(ContextWorklistEntry {
+    /// Switches to per-context mode with a worklist per given context.
+    /// Must be called before any local view is created in the cycle.
+    pub fn create_context_worklists(&self, contexts: &[Address]) {
+        let mut context_worklists = self.context_worklists.write().unwrap();
+        debug_assert!(context_worklists.is_empty());
+        let mut contexts = contexts.to_vec();
+        contexts.push(K_OTHER_CONTEXT);
+        contexts.sort_unstable();
+        contexts.dedup();
+        *context_worklists = contexts
+            .into_iter()
+            .map(|context| ContextWorklist {
                 context,
-                worklist: Box::new(MarkingWorklist::default()),
-            });
-        }
+                worklist: Arc::new(MarkingWorklist::new()),
+            })
+            .collect();
     }
 
-    pub fn ReleaseContextWorklists(&mut self) {
-        self.context_worklists_.clear();
+    /// Leaves per-context mode. The context worklists must be empty.
+    pub fn release_context_worklists(&self) {
+        let mut context_worklists = self.context_worklists.write().unwrap();
+        debug_assert!(context_worklists
+            .iter()
+            .all(|context_worklist| context_worklist.worklist.is_empty()));
+        context_worklists.clear();
     }
 
-    pub fn shared(&self) -> &MarkingWorklist {
-        &self.shared_
+    pub fn is_per_context_mode(&self) -> bool {
+        !self.context_worklists.read().unwrap().is_empty()
     }
 
-    pub fn on_hold(&self) -> &MarkingWorklist {
-        &self.on_hold_
+    /// Adds the sizes visited by a marker.
+    pub fn merge_native_context_stats(&self, stats: &NativeContextStats) {
+        self.native_context_stats.lock().unwrap().merge(stats);
     }
 
-    pub fn other(&self) -> &MarkingWorklist {
-        &self.other_
+    /// Returns the sizes visited by all markers since the last call.
+    pub fn take_native_context_stats(&self) -> NativeContextStats {
+        std::mem::take(&mut *self.native_context_stats.lock().unwrap())
     }
 
-    pub fn context_worklists(&self) -> &Vec<ContextWorklistEntry> {
-        &self.context_worklists_
+    /// Returns true if no published work is left. Work in local views is
+    /// not considered.
+    pub fn is_empty(&self) -> bool {
+        self.shared.is_empty()
+            && self
+                .context_worklists
+                .read()
+                .unwrap()
+                .iter()
+                .all(|context_worklist| context_worklist.worklist.is_empty())
     }
-}
-
-pub struct MarkingWorklistsLocal<'a> {
-    active_: &'a MarkingWorklist,
-    shared_: MarkingWorklist,
-    on_hold_: MarkingWorklist,
-    active_context_: usize, //Address -> usize
-    is_per_context_mode_: bool,
-    other_: MarkingWorklist,
-    cpp_marking_state_: Option<cppgc_js::CppMarkingState>,
-    context_worklists_: Vec<MarkingWorklist>,
-    worklist_by_context_: HashMap<usize, usize>, //Address -> usize
-}
 
-impl<'a> MarkingWorklistsLocal<'a> {
-    const K_SHARED_CONTEXT: usize = 0; //Address -> usize
-    const K_OTHER_CONTEXT: usize = 1; //Address -> usize
-    //std::marker::PhantomData<&'a MarkingWorklists>
-
-    pub fn new(
-        global: &'a MarkingWorklists,
-        cpp_marking_state: Option<cppgc_js::CppMarkingState>,
-    ) -> Self {
-        let mut local = MarkingWorklistsLocal {
-            active_: &global.shared_,
-            shared_: MarkingWorklist::default(),
-            on_hold_: MarkingWorklist::default(),
-            active_context_: Self::K_SHARED_CONTEXT,
-            is_per_context_mode_: !global.context_worklists().is_empty(),
-            other_: MarkingWorklist::default(),
-            cpp_marking_state_: cpp_marking_state,
-            context_worklists_: Vec::new(),
-            worklist_by_context_: HashMap::new(),
-        };
+    /// Returns the number of published segments in all worklists.
+    pub fn size(&self) -> usize {
+        self.shared.size()
+            + self
+                .context_worklists
+                .read()
+                .unwrap()
+                .iter()
+                .map(|context_worklist| context_worklist.worklist.size())
+                .sum::<usize>()
+    }
 
-        if local.is_per_context_mode_ {
-            local.context_worklists_.reserve(global.context_worklists().len());
-            let mut index = 0;
-            for cw in global.context_worklists() {
-                local.context_worklists_.push(MarkingWorklist::default());
-                local.worklist_by_context_.insert(cw.context, index);
-                index += 1;
-            }
+    pub fn clear(&self) {
+        self.shared.clear();
+        for context_worklist in self.context_worklists.read().unwrap().iter() {
+            context_worklist.worklist.clear();
         }
-
-        local
+        self.native_context_stats.lock().unwrap().clear();
     }
 
-    pub fn Publish(&mut self) {
-        self.shared_.Publish();
-        self.on_hold_.Publish();
-        self.other_.Publish();
-        if self.is_per_context_mode_ {
-            for (context, &index) in &self.worklist_by_context_ {
-                self.context_worklists_[index].Publish();
-            }
+    /// Replaces published entries by the result of `callback`, dropping
+    /// those for which it returns `None`. Used when objects move while
+    /// marking is in progress.
+    pub fn update(&self, mut callback: impl FnMut(HeapObject) -> Option<HeapObject>) {
+        self.shared.update(&mut callback);
+        for context_worklist in self.context_worklists.read().unwrap().iter() {
+            context_worklist.worklist.update(&mut callback);
         }
-        self.PublishCppHeapObjects();
     }
+}
 
-    fn PublishCppHeapObjects(&mut self) {
-        // Placeholder: Add implementation to publish Cpp Heap objects.
-    }
+/// A marker's view on the worklists. Objects are pushed to and popped from
+/// the worklist of the active context; once it runs dry, the view switches
+/// to any other context with work left.
+pub struct MarkingWorklistsLocal<'a> {
+    shared: MarkingWorklistLocal<'a>,
+    context_worklists: Vec<ContextWorklistLocal>,
+    /// Index into `context_worklists`; `None` for the shared worklist.
+    active: Option<usize>,
+    cpp_marking_state: Option<CppMarkingState>,
+}
 
-    pub fn IsEmpty(&mut self) -> bool {
-        if !self.active_.IsLocalEmpty() || !self.on_hold_.IsLocalEmpty() ||
-           !self.active_.IsGlobalEmpty() || !self.on_hold_.IsGlobalEmpty() {
-            return false;
+impl MarkingWorklistsLocal<'_> {
+    #[inline]
+    pub fn push(&mut self, object: HeapObject) {
+        match self.active {
+            None => self.shared.push(object),
+            Some(index) => self.context_worklists[index].push(object),
         }
+    }
 
-        if !self.is_per_context_mode_ {
-            return true;
+    #[inline]
+    pub fn pop(&mut self) -> Option<HeapObject> {
+        let object = match self.active {
+            None => self.shared.pop(),
+            Some(index) => self.context_worklists[index].pop(),
+        };
+        if object.is_some() || !self.is_per_context_mode() {
+            return object;
         }
+        self.pop_context()
+    }
 
-        if !self.shared_.IsLocalEmpty() || !self.other_.IsLocalEmpty() ||
-           !self.shared_.IsGlobalEmpty() || !self.other_.IsGlobalEmpty() {
-            return false;
+    /// Pops from the first other worklist with work and makes its context
+    /// the active one.
+    fn pop_context(&mut self) -> Option<HeapObject> {
+        if self.active.is_some()
+            && let Some(object) = self.shared.pop()
+        {
+            self.active = None;
+            return Some(object);
         }
-
-        for (context, &index) in &self.worklist_by_context_ {
-            let worklist = &self.context_worklists_[index];
-            if *context != self.active_context_ &&
-               !(worklist.IsLocalEmpty() && worklist.IsGlobalEmpty()) {
-                self.SwitchToContextImpl(*context, worklist);
-                return false;
+        for index in 0..self.context_worklists.len() {
+            if self.active == Some(index) {
+                continue;
+            }
+            if let Some(object) = self.context_worklists[index].pop() {
+                self.active = Some(index);
+                return Some(object);
             }
         }
+        None
+    }
 
-        true
+    pub fn is_per_context_mode(&self) -> bool {
+        !self.context_worklists.is_empty()
     }
 
-    pub fn IsWrapperEmpty(&self) -> bool {
-        self.cpp_marking_state_.is_none() //|| self.cpp_marking_state_.as_ref().map_or(true, |state| state.IsLocalEmpty())
+    /// Returns the state marking wrappables, if a `CppHeap` is attached.
+    pub fn cpp_marking_state(&self) -> Option<&CppMarkingState> {
+        self.cpp_marking_state.as_ref()
     }
 
-    pub fn ShareWork(&mut self) {
-        if !self.active_.IsLocalEmpty() && self.active_.IsGlobalEmpty() {
-            self.active_.Publish();
-        }
+    /// Returns the active context.
+    pub fn context(&self) -> Address {
+        self.active
+            .map_or(K_SHARED_CONTEXT, |index| self.context_worklists[index].context)
+    }
 
-        if self.is_per_context_mode_ && self.active_context_ != Self::K_SHARED_CONTEXT {
-            if !self.shared_.IsLocalEmpty() && self.shared_.IsGlobalEmpty() {
-                self.shared_.Publish();
-            }
+    /// Makes `context` the active context. Contexts that are not measured
+    /// share the worklist of `K_OTHER_CONTEXT`.
+    pub fn switch_to_context(&mut self, context: Address) {
+        debug_assert!(self.is_per_context_mode());
+        if context == K_SHARED_CONTEXT {
+            self.active = None;
+            return;
         }
+        let find = |context| {
+            self.context_worklists
+                .binary_search_by_key(&context, |context_worklist| context_worklist.context)
+        };
+        self.active = Some(find(context).or_else(|_| find(K_OTHER_CONTEXT)).unwrap());
     }
 
-    pub fn PublishWork(&mut self) {
-        assert!(!self.is_per_context_mode_);
-        self.shared_.Publish();
+    pub fn is_local_empty(&self) -> bool {
+        self.shared.is_local_empty()
+            && self
+                .context_worklists
+                .iter()
+                .all(ContextWorklistLocal::is_local_empty)
     }
 
-    pub fn MergeOnHold(&mut self) {
-        self.shared_.Merge(&self.on_hold_);
+    /// Publishes all local work.
+    pub fn publish(&mut self) {
+        self.shared.publish();
+        for context_worklist in &mut self.context_worklists {
+            context_worklist.publish();
+        }
     }
+}
 
-    pub fn PopContext(&mut self, object: &mut Option<objects::HeapObject>) -> bool {
-        assert!(self.is_per_context_mode_);
+/// Local view on a context worklist. Context worklists only live for one
+/// cycle, so the view shares ownership of the global worklist.
+struct ContextWorklistLocal {
+    context: Address,
+    worklist: Arc<MarkingWorklist>,
+    push_segment: Vec<HeapObject>,
+    pop_segment: Vec<HeapObject>,
+}
 
-        for (context, &index) in &self.worklist_by_context_ {
-            let worklist = &self.context_worklists_[index];
-            if *context != self.active_context_ && !worklist.IsLocalEmpty() {
-                self.SwitchToContextImpl(*context, worklist);
-                return self.active_.Pop(object);
-            }
+impl ContextWorklistLocal {
+    fn new(context_worklist: &ContextWorklist) -> Self {
+        ContextWorklistLocal {
+            context: context_worklist.context,
+            worklist: Arc::clone(&context_worklist.worklist),
+            push_segment: Vec::new(),
+            pop_segment: Vec::new(),
         }
+    }
 
-        for (context, &index) in &self.worklist_by_context_ {
-            let worklist = &self.context_worklists_[index];
-            if *context != self.active_context_ && worklist.Pop(object) {
-                self.SwitchToContextImpl(*context, worklist);
-                return true;
-            }
+    fn push(&mut self, object: HeapObject) {
+        if self.push_segment.len() >= K_MARKING_WORKLIST_SEGMENT_SIZE {
+            self.worklist.push(std::mem::take(&mut self.push_segment));
         }
-
-        self.SwitchToContext(Self::K_SHARED_CONTEXT);
-        false
+        self.push_segment.push(object);
     }
 
-    pub fn SwitchToContextSlow(&mut self, context: usize) -> usize { //Address -> usize
-        if let Some(&index) = self.worklist_by_context_.get(&context) {
-            self.SwitchToContextImpl(context, &self.context_worklists_[index]);
+    fn pop(&mut self) -> Option<HeapObject> {
+        if let Some(object) = self.pop_segment.pop() {
+            return Some(object);
+        }
+        if !self.push_segment.is_empty() {
+            std::mem::swap(&mut self.push_segment, &mut self.pop_segment);
         } else {
-            if context == Self::K_SHARED_CONTEXT {
-                self.SwitchToContextImpl(Self::K_SHARED_CONTEXT, &self.shared_);
-            } else {
-                self.SwitchToContextImpl(Self::K_OTHER_CONTEXT, &self.other_);
-            }
+            self.pop_segment = self.worklist.pop()?;
         }
-        self.active_context_
+        self.pop_segment.pop()
     }
 
-    fn SwitchToContextImpl(&mut self, context: usize, worklist: &MarkingWorklist) {
-        self.active_context_ = context;
-        self.active_ = worklist;
+    fn is_local_empty(&self) -> bool {
+        self.push_segment.is_empty() && self.pop_segment.is_empty()
     }
 
-    pub fn SwitchToContext(&mut self, context: usize) -> usize { //Address -> usize
-        if self.worklist_by_context_.contains_key(&context) ||
-           context == Self::K_SHARED_CONTEXT || context == Self::K_OTHER_CONTEXT {
-            self.SwitchToContextSlow(context)
-        } else {
-            panic!("Unexpected context");
+    fn publish(&mut self) {
+        if !self.push_segment.is_empty() {
+            self.worklist.push(std::mem::take(&mut self.push_segment));
+        }
+        if !self.pop_segment.is_empty() {
+            self.worklist.push(std::mem::take(&mut self.pop_segment));
         }
     }
+}
 
-    pub fn SwitchToSharedForTesting(&mut self) -> usize {
-        self.SwitchToContext(Self::K_SHARED_CONTEXT)
+impl Drop for ContextWorklistLocal {
+    fn drop(&mut self) {
+        debug_assert!(
+            std::thread::panicking() || self.is_local_empty(),
+            "local worklist dropped with unpublished entries"
+        );
     }
-}
\ No newline at end of file
+}
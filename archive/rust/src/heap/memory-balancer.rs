@@ -2,222 +2,197 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::cmp::{max, min};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-//use base::TimeTicks;  // Assuming a simple Instant replacement
-//use base::TimeDelta;  // Assuming a simple Duration replacement
+use crate::heap::heap::HeapGrowingMode;
+use crate::heap::heap_controller::{HeapGrowingInput, HeapGrowingPolicy};
 
 const KB: usize = 1024;
 const MB: usize = 1024 * KB;
 
-mod flags {
-    pub static mut trace_memory_balancer: bool = false;
-    pub static mut memory_balancer_c_value: f64 = 1.0; // Default value
-}
-
+/// Exponentially smoothed bytes and duration, whose ratio is a rate.
+#[derive(Clone, Copy, Debug)]
 struct SmoothedBytesAndDuration {
-    bytes: usize,
+    bytes: f64,
     duration_ms: f64,
 }
 
 impl SmoothedBytesAndDuration {
-    fn new(bytes: usize, duration_ms: f64) -> Self {
+    fn new(bytes: usize, duration: Duration) -> Self {
         SmoothedBytesAndDuration {
-            bytes,
-            duration_ms,
+            bytes: bytes as f64,
+            duration_ms: duration.as_secs_f64() * 1000.0,
         }
     }
 
-    fn update(&mut self, new_bytes: usize, new_duration_ms: f64, decay_rate: f64) {
-        self.bytes = (decay_rate * self.bytes as f64 + (1.0 - decay_rate) * new_bytes as f64) as usize;
-        self.duration_ms = (decay_rate * self.duration_ms + (1.0 - decay_rate) * new_duration_ms) as f64;
+    fn update(&mut self, bytes: usize, duration: Duration, decay_rate: f64) {
+        self.bytes = self.bytes * decay_rate + bytes as f64 * (1.0 - decay_rate);
+        self.duration_ms = self.duration_ms * decay_rate + duration.as_secs_f64() * 1000.0 * (1.0 - decay_rate);
     }
 
+    /// Bytes per millisecond.
     fn rate(&self) -> f64 {
-        if self.duration_ms == 0.0 {
-            0.0
+        if self.duration_ms > 0.0 {
+            self.bytes / self.duration_ms
         } else {
-            self.bytes as f64 / self.duration_ms
+            0.0
         }
     }
 }
 
-trait ForegroundTaskRunner {
-    fn post_delayed_task(&self, task: Box<dyn FnOnce() + Send + 'static>, delay: Duration);
-}
-
-trait Isolate {
-    fn print_with_timestamp(&self, format: &str, args: &[f64]);
-}
-
-trait Heap {
-    fn old_generation_size_of_objects(&self) -> usize;
-    fn max_old_generation_size(&self) -> usize;
-    fn min_old_generation_size(&self) -> usize;
-    fn set_old_generation_and_global_allocation_limit(&self, limit: usize, global_limit: usize);
-    fn get_foreground_task_runner(&self) -> &dyn ForegroundTaskRunner;
-    fn isolate(&self) -> &dyn Isolate;
+/// Heap growing policy after "MemBalancer" (Kirisame et al., 2022): the
+/// extra memory granted over the live size L is sqrt(L * g / (s * c)),
+/// where g is the allocation rate, s the collector speed and c a tuning
+/// constant. This balances memory against time spent in the collector
+/// across heaps: a heap that allocates fast or collects slowly gets more
+/// room. Smaller values of c grant more memory for higher throughput.
+///
+/// Unlike in V8, the rates are sampled at each mark-compact rather than
+/// by a heartbeat task in between.
+pub struct MemoryBalancer {
+    c_value: f64,
+    major_allocation_rate: Option<SmoothedBytesAndDuration>,
+    major_gc_speed: Option<SmoothedBytesAndDuration>,
 }
 
-const kMajorGCDecayRate: f64 = 0.9;
-const kMajorAllocationDecayRate: f64 = 0.9;
-
-pub struct MemoryBalancer<'a> {
-    heap_: &'a dyn Heap,
-    last_measured_at_: Instant,
-    last_measured_memory_: usize,
-    live_memory_after_gc_: usize,
-    embedder_allocation_limit_: usize,
-    major_allocation_rate_: Option<SmoothedBytesAndDuration>,
-    major_gc_speed_: Option<SmoothedBytesAndDuration>,
-    heartbeat_task_started_: bool,
-}
+impl MemoryBalancer {
+    pub const K_DEFAULT_C_VALUE: f64 = 3e-10;
+    pub const K_MAJOR_ALLOCATION_DECAY_RATE: f64 = 0.95;
+    pub const K_MAJOR_GC_DECAY_RATE: f64 = 0.5;
+    /// Minimum extra memory over the live size.
+    pub const K_MIN_HEAP_EXTRA_SPACE: usize = 2 * MB;
 
-impl<'a> MemoryBalancer<'a> {
-    pub fn new(heap_: &'a dyn Heap, startup_time: Instant) -> Self {
+    pub fn new(c_value: f64) -> Self {
+        assert!(c_value > 0.0, "MemoryBalancer c value must be positive");
         MemoryBalancer {
-            heap_,
-            last_measured_at_: startup_time,
-            last_measured_memory_: 0,
-            live_memory_after_gc_: 0,
-            embedder_allocation_limit_: 0,
-            major_allocation_rate_: None,
-            major_gc_speed_: None,
-            heartbeat_task_started_: false,
+            c_value,
+            major_allocation_rate: None,
+            major_gc_speed: None,
         }
     }
 
-    pub fn recompute_limits(&mut self, embedder_allocation_limit: usize, time: Instant) {
-        self.embedder_allocation_limit_ = embedder_allocation_limit;
-        self.last_measured_memory_ = self.heap_.old_generation_size_of_objects();
-        self.live_memory_after_gc_ = self.last_measured_memory_;
-        self.last_measured_at_ = time;
-        self.refresh_limit();
-        self.post_heartbeat_task();
-    }
-
-    fn refresh_limit(&mut self) {
-        assert!(self.major_allocation_rate_.is_some());
-        assert!(self.major_gc_speed_.is_some());
-
-        let computed_limit = (self.live_memory_after_gc_ as f64 +
-            (self.live_memory_after_gc_ as f64 * self.major_allocation_rate_.as_ref().unwrap().rate() /
-             self.major_gc_speed_.as_ref().unwrap().rate() / unsafe { flags::memory_balancer_c_value })
-                .sqrt()) as usize;
-
-        const K_MIN_HEAP_EXTRA_SPACE: usize = 2 * MB;
-        let minimum_limit = self.live_memory_after_gc_ + K_MIN_HEAP_EXTRA_SPACE;
-
-        let mut new_limit = max(minimum_limit, computed_limit);
-        new_limit = min(new_limit, self.heap_.max_old_generation_size());
-        new_limit = max(new_limit, self.heap_.min_old_generation_size());
-
-        if unsafe { flags::trace_memory_balancer } {
-            self.heap_.isolate().print_with_timestamp(
-                "MemoryBalancer: allocation-rate=%.1lfKB/ms gc-speed=%.1lfKB/ms minium-limit=%.1lfM computed-limit=%.1lfM new-limit=%.1lfM\n",
-                &[
-                    self.major_allocation_rate_.as_ref().unwrap().rate() / KB as f64,
-                    self.major_gc_speed_.as_ref().unwrap().rate() / KB as f64,
-                    minimum_limit as f64 / MB as f64,
-                    computed_limit as f64 / MB as f64,
-                    new_limit as f64 / MB as f64,
-                ],
-            );
-        }
+    /// Smoothed old-generation allocation rate in bytes per millisecond.
+    pub fn allocation_rate(&self) -> Option<f64> {
+        self.major_allocation_rate.map(|rate| rate.rate())
+    }
 
-        self.heap_.set_old_generation_and_global_allocation_limit(
-            new_limit,
-            new_limit + self.embedder_allocation_limit_,
-        );
+    /// Smoothed mark-compact speed in bytes per millisecond.
+    pub fn gc_speed(&self) -> Option<f64> {
+        self.major_gc_speed.map(|speed| speed.rate())
     }
 
-    pub fn update_gc_speed(&mut self, major_gc_bytes: usize, major_gc_duration: Duration) {
-        if self.major_gc_speed_.is_none() {
-            self.major_gc_speed_ = Some(SmoothedBytesAndDuration::new(
-                major_gc_bytes,
-                major_gc_duration.as_millis() as f64,
-            ));
-        } else {
-            self.major_gc_speed_.as_mut().unwrap().update(
-                major_gc_bytes,
-                major_gc_duration.as_millis() as f64,
-                kMajorGCDecayRate,
-            );
+    pub fn update_allocation_rate(&mut self, bytes: usize, duration: Duration) {
+        match &mut self.major_allocation_rate {
+            Some(rate) => rate.update(bytes, duration, Self::K_MAJOR_ALLOCATION_DECAY_RATE),
+            None => self.major_allocation_rate = Some(SmoothedBytesAndDuration::new(bytes, duration)),
         }
     }
 
-    pub fn update_allocation_rate(
-        &mut self,
-        major_allocation_bytes: usize,
-        major_allocation_duration: Duration,
-    ) {
-        if self.major_allocation_rate_.is_none() {
-            self.major_allocation_rate_ = Some(SmoothedBytesAndDuration::new(
-                major_allocation_bytes,
-                major_allocation_duration.as_millis() as f64,
-            ));
-        } else {
-            self.major_allocation_rate_.as_mut().unwrap().update(
-                major_allocation_bytes,
-                major_allocation_duration.as_millis() as f64,
-                kMajorAllocationDecayRate,
-            );
+    pub fn update_gc_speed(&mut self, bytes: usize, duration: Duration) {
+        match &mut self.major_gc_speed {
+            Some(speed) => speed.update(bytes, duration, Self::K_MAJOR_GC_DECAY_RATE),
+            None => self.major_gc_speed = Some(SmoothedBytesAndDuration::new(bytes, duration)),
         }
     }
 
-    fn heartbeat_update(&mut self) {
-        self.heartbeat_task_started_ = false;
-        let time = Instant::now();
-        let memory = self.heap_.old_generation_size_of_objects();
-
-        let duration = time.duration_since(self.last_measured_at_);
-        let allocated_bytes = if memory > self.last_measured_memory_ {
-            memory - self.last_measured_memory_
+    /// Computes the limit for `live_memory` bytes of live objects from the
+    /// current rates.
+    pub fn compute_limit(&self, live_memory: usize, min_size: usize, max_size: usize) -> usize {
+        let allocation_rate = self.allocation_rate().unwrap_or(0.0);
+        let gc_speed = self.gc_speed().unwrap_or(0.0);
+        let extra = if allocation_rate > 0.0 && gc_speed > 0.0 {
+            (live_memory as f64 * allocation_rate / gc_speed / self.c_value).sqrt()
         } else {
-            0
+            0.0
         };
-        self.update_allocation_rate(allocated_bytes, duration);
+        let computed_limit = live_memory.saturating_add(extra as usize);
+        let minimum_limit = live_memory + Self::K_MIN_HEAP_EXTRA_SPACE;
+        computed_limit.max(minimum_limit).min(max_size).max(min_size)
+    }
+}
+
+impl Default for MemoryBalancer {
+    fn default() -> Self {
+        Self::new(Self::K_DEFAULT_C_VALUE)
+    }
+}
 
-        self.last_measured_memory_ = memory;
-        self.last_measured_at_ = time;
-        self.refresh_limit();
-        self.post_heartbeat_task();
+impl HeapGrowingPolicy for MemoryBalancer {
+    fn name(&self) -> &'static str {
+        "MemoryBalancer"
     }
 
-    fn post_heartbeat_task(&mut self) {
-        if self.heartbeat_task_started_ {
-            return;
+    fn allocation_limit(&mut self, input: &HeapGrowingInput) -> usize {
+        self.update_allocation_rate(input.allocated_bytes, input.mutator_duration);
+        self.update_gc_speed(input.gc_bytes, input.gc_duration);
+        if input.growing_mode == HeapGrowingMode::kMinimal {
+            return (input.old_generation_size + Self::K_MIN_HEAP_EXTRA_SPACE)
+                .min(input.max_old_generation_size)
+                .max(input.min_old_generation_size);
         }
-        self.heartbeat_task_started_ = true;
-        let isolate = self.heap_.isolate();
-        let mb_ptr = self as *mut Self;
-        self.heap_.get_foreground_task_runner().post_delayed_task(
-            Box::new(move || {
-                //This is unsafe, but it's mirroring the original C++ code's behavior
-                // of passing a raw pointer to the task.
-                unsafe {
-                    (*mb_ptr).heartbeat_update();
-                }
-            }),
-            Duration::from_millis(1),
-        );
+        self.compute_limit(
+            input.old_generation_size,
+            input.min_old_generation_size,
+            input.max_old_generation_size,
+        )
     }
 }
 
-struct HeartbeatTask<'a> {
-    isolate: &'a dyn Isolate,
-    mb_: *mut MemoryBalancer<'a>, //raw pointer mirrors C++ version.  Rust usually avoids this.
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(allocated_bytes: usize, gc_bytes: usize) -> HeapGrowingInput {
+        HeapGrowingInput {
+            old_generation_size: 64 * MB,
+            allocated_bytes,
+            mutator_duration: Duration::from_millis(100),
+            gc_bytes,
+            gc_duration: Duration::from_millis(10),
+            gc_speed: None,
+            min_old_generation_size: 16 * MB,
+            max_old_generation_size: 1024 * MB,
+            new_space_capacity: MB,
+            growing_mode: HeapGrowingMode::kDefault,
+        }
+    }
 
-impl<'a> HeartbeatTask<'a> {
-    fn new(isolate: &'a dyn Isolate, mb_: &mut MemoryBalancer<'a>) -> Self {
-        HeartbeatTask { isolate, mb_: mb_ }
+    #[test]
+    fn limit_grows_with_allocation_rate_and_shrinks_with_gc_speed() {
+        let limit = |allocated_bytes, gc_bytes| MemoryBalancer::default().allocation_limit(&input(allocated_bytes, gc_bytes));
+        let base = limit(10 * MB, 64 * MB);
+        assert!(base > 64 * MB + MemoryBalancer::K_MIN_HEAP_EXTRA_SPACE);
+        assert!(limit(40 * MB, 64 * MB) > base);
+        assert!(limit(10 * MB, 256 * MB) < base);
+        // Without allocation the minimum extra space is granted.
+        assert_eq!(limit(0, 64 * MB), 64 * MB + MemoryBalancer::K_MIN_HEAP_EXTRA_SPACE);
+        // sqrt(L * g / (s * c)) with L = 64 MB, g = 0.1 MB/ms, s = 6.4 MB/ms.
+        let extra = (64.0 * MB as f64 * (10 * MB) as f64 / 100.0 / ((64 * MB) as f64 / 10.0) / 3e-10).sqrt();
+        assert_eq!(base, 64 * MB + extra as usize);
     }
 
-    fn run_internal(&mut self) {
-        unsafe {
-            (*self.mb_).heartbeat_update();
-        }
+    #[test]
+    fn smaller_c_value_trades_memory_for_throughput() {
+        let mut frugal = MemoryBalancer::new(MemoryBalancer::K_DEFAULT_C_VALUE * 4.0);
+        let mut generous = MemoryBalancer::new(MemoryBalancer::K_DEFAULT_C_VALUE / 4.0);
+        let input = input(10 * MB, 64 * MB);
+        let frugal_extra = frugal.allocation_limit(&input) - input.old_generation_size;
+        let generous_extra = generous.allocation_limit(&input) - input.old_generation_size;
+        assert!(generous_extra.abs_diff(4 * frugal_extra) <= 4);
+        // Capped by the maximum size.
+        let mut unbounded = MemoryBalancer::new(1e-20);
+        assert_eq!(unbounded.allocation_limit(&input), input.max_old_generation_size);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn memory_reducing_mark_compacts_grant_minimal_room() {
+        let input = HeapGrowingInput {
+            growing_mode: HeapGrowingMode::kMinimal,
+            ..input(40 * MB, 64 * MB)
+        };
+        assert_eq!(
+            MemoryBalancer::default().allocation_limit(&input),
+            64 * MB + MemoryBalancer::K_MIN_HEAP_EXTRA_SPACE
+        );
+    }
+}
@@ -2,174 +2,23 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod memory_measurement {
-    // This module would contain the Rust equivalent of "src/heap/memory-measurement.h"
-    // Since we don't have that file, we'll define a placeholder.
-    pub trait MemoryMeasurement {}
-}
-
-pub mod objects {
-    pub mod contexts {
-        // This module would contain the Rust equivalent of "src/objects/contexts.h"
-        // Since we don't have that file, we'll define a placeholder.
-        pub struct Context {}
-    }
-
-    pub mod contexts_inl {
-        // This module would contain the Rust equivalent of "src/objects/contexts-inl.h"
-        // Since we don't have that file, we'll define a placeholder.
-    }
-
-    pub mod instance_type {
-        // This module would contain the Rust equivalent of "src/objects/instance-type.h"
-        // Since we don't have that file, we'll define a placeholder.
-        #[derive(PartialEq, Eq, Copy, Clone)]
-        pub struct InstanceType(u8);
-
-        impl InstanceType {
-            pub const JS_ARRAY_BUFFER_TYPE: InstanceType = InstanceType(1); // Placeholder value
-        }
-    }
-
-    pub mod instance_type_inl {
-        // This module would contain the Rust equivalent of "src/objects/instance-type-inl.h"
-        // Since we don't have that file, we'll define a placeholder.
-    }
-
-    pub mod map {
-        // This module would contain the Rust equivalent of "src/objects/map.h"
-        // Since we don't have that file, we'll define a placeholder.
-        use crate::objects::instance_type::InstanceType;
-
-        #[derive(Copy, Clone)]
-        pub struct Map {
-            instance_type: InstanceType,
-            raw_native_context_or_null: Tagged<Object>
-        }
-
-        impl Map {
-            pub fn instance_type(&self) -> InstanceType {
-                self.instance_type
-            }
-
-            pub fn raw_native_context_or_null(&self) -> Tagged<Object> {
-                self.raw_native_context_or_null
-            }
-        }
-    }
-
-    pub mod map_inl {
-        // This module would contain the Rust equivalent of "src/objects/map-inl.h"
-        // Since we don't have that file, we'll define a placeholder.
-    }
-
-    #[derive(Copy, Clone)]
-    pub struct HeapObject {}
-}
-
-// Placeholder type for Tagged<T>
-#[derive(Copy, Clone)]
-pub struct Tagged<T>(*mut T);
-
-impl<T> Tagged<T> {
-    pub fn ptr(&self) -> usize {
-        self.0 as usize
+use crate::heap::heap_layout::{Address, HeapObject, InstanceType};
+use crate::heap::memory_measurement::{NativeContextInferrer, NativeContextStats};
+
+impl NativeContextInferrer {
+    /// Returns the native context that `object` belongs to, if it can be
+    /// told from the object alone. Objects carry no back pointer to their
+    /// context, so only native contexts themselves are inferred; everything
+    /// else inherits the context of the object that referred to it.
+    #[inline]
+    pub fn infer(object: HeapObject) -> Option<Address> {
+        (object.map().instance_type() == InstanceType::NATIVE_CONTEXT_TYPE).then(|| object.address())
     }
 }
 
-// Placeholder implementation for Tagged<Map>->map()
-impl Tagged<crate::objects::map::Map> {
-    pub fn map(&self) -> &crate::objects::map::Map {
-        unsafe { &*self.0 }
+impl NativeContextStats {
+    #[inline]
+    pub fn increment_size(&mut self, context: Address, size: usize) {
+        *self.size_by_context.entry(context).or_insert(0) += size;
     }
 }
-
-// Placeholder type for Object
-#[derive(Copy, Clone)]
-pub struct Object {}
-
-pub struct PtrComprCageBase {} // Placeholder
-
-pub mod internal {
-    use crate::objects::map::Map;
-    use crate::Tagged;
-    use crate::objects::HeapObject;
-    use crate::PtrComprCageBase;
-    use crate::objects::instance_type::InstanceType;
-    use crate::objects::instance_type::InstanceType::JS_ARRAY_BUFFER_TYPE;
-    use std::collections::HashMap;
-
-    pub struct NativeContextInferrer {}
-
-    impl NativeContextInferrer {
-        pub fn infer(
-            _cage_base: PtrComprCageBase,
-            map: Tagged<Map>,
-            _object: Tagged<HeapObject>,
-            native_context: &mut usize,
-        ) -> bool {
-            let maybe_native_context = map.map().raw_native_context_or_null();
-            *native_context = maybe_native_context.ptr();
-
-            // The value might be equal to Smi::uninitialized_deserialization_value()
-            // during NativeContext deserialization.
-            !is_smi(maybe_native_context) && !is_null(maybe_native_context)
-        }
-    }
-
-    // Placeholder functions to simulate IsSmi and IsNull from C++
-    fn is_smi<T>(_obj: Tagged<T>) -> bool {
-        false // Placeholder implementation
-    }
-
-    fn is_null<T>(_obj: Tagged<T>) -> bool {
-        false // Placeholder implementation
-    }
-
-    pub struct NativeContextStats {
-        size_by_context_: HashMap<usize, usize>,
-    }
-
-    impl NativeContextStats {
-        pub fn new() -> Self {
-            NativeContextStats {
-                size_by_context_: HashMap::new(),
-            }
-        }
-
-        pub fn has_external_bytes(map: Tagged<Map>) -> bool {
-            let instance_type = map.map().instance_type();
-            instance_type == JS_ARRAY_BUFFER_TYPE || InstanceTypeChecker::is_external_string(instance_type)
-        }
-
-        pub fn increment_size(
-            &mut self,
-            context: usize,
-            map: Tagged<Map>,
-            object: Tagged<HeapObject>,
-            size: usize,
-        ) {
-            *self.size_by_context_.entry(context).or_insert(0) += size;
-            if NativeContextStats::has_external_bytes(map) {
-                self.increment_external_size(context, map, object);
-            }
-        }
-
-        fn increment_external_size(
-            &mut self,
-            _context: usize,
-            _map: Tagged<Map>,
-            _object: Tagged<HeapObject>,
-        ) {
-            // Placeholder implementation
-        }
-    }
-
-    pub struct InstanceTypeChecker {}
-
-    impl InstanceTypeChecker {
-        pub fn is_external_string(_instance_type: InstanceType) -> bool {
-            false // Placeholder implementation
-        }
-    }
-}
\ No newline at end of file
@@ -2,322 +2,454 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
-use std::time::Duration;
-use std::ptr::NonNull;
-use std::sync::{Arc, Mutex};
-use rand::Rng;
-
-pub mod v8 {
-    pub struct Isolate {}
-    pub struct Context {}
-    pub struct PromiseResolver {}
-    pub enum MeasureMemoryMode {}
-    pub trait MeasureMemoryDelegate {}
-    pub enum MeasureMemoryExecution {
-        Eager,
-        Delayed,
-    }
-    pub type Handle<T> = *mut T;
-    pub type Local<'a, T> = &'a T;
-    pub trait TaskRunner {
-        fn post_task(&self, task: Box<dyn FnOnce()>);
-    }
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::heap::gc_tracer::GarbageCollectionReason;
+use crate::heap::heap::Heap;
+use crate::heap::heap_layout::{Address, HeapObject, ObjectSlot, Tagged};
+use crate::heap::marking_worklist::K_SHARED_CONTEXT;
+use crate::heap::memory_chunk::AllocationSpace;
+
+/// When the garbage collection that measures memory happens, like
+/// `v8::MeasureMemoryExecution`.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeasureMemoryExecution {
+    /// A garbage collection is started after a randomized delay unless one
+    /// happens before.
+    kDefault,
+    /// A garbage collection is started right away.
+    kEager,
+    /// The measurement waits for the next garbage collection.
+    kLazy,
 }
 
-mod base {
-    pub mod platform {
-        use std::time::{Instant, Duration};
+/// The result of a memory measurement.
+///
+/// Sizes are those of the live objects at the end of marking.
+/// `unattributed_size_in_bytes` covers the objects of contexts that were
+/// not measured and objects that were allocated during marking.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeasureMemoryResult {
+    /// The measured native contexts that are still alive, valid until the
+    /// next allocation.
+    pub contexts: Vec<HeapObject>,
+    pub sizes_for_contexts: Vec<usize>,
+    /// Bytes reachable from the roots without passing through a native
+    /// context.
+    pub shared_size_in_bytes: usize,
+    pub unattributed_size_in_bytes: usize,
+}
 
-        pub struct ElapsedTimer {
-            start: Instant,
-        }
+/// Embedder side of a memory measurement, like `v8::MeasureMemoryDelegate`.
+pub trait MeasureMemoryDelegate {
+    /// Whether the size of `context` should be measured.
+    fn should_measure(&self, context: HeapObject) -> bool;
 
-        impl ElapsedTimer {
-            pub fn new() -> Self {
-                ElapsedTimer { start: Instant::now() }
-            }
+    /// Called with the result once the measurement is done.
+    fn measurement_complete(self: Box<Self>, result: MeasureMemoryResult);
+}
 
-            pub fn start(&mut self) {
-                self.start = Instant::now();
-            }
+/// Sizes of objects by native context, see `NativeContextInferrer`.
+#[derive(Clone, Debug, Default)]
+pub struct NativeContextStats {
+    pub(crate) size_by_context: HashMap<Address, usize>,
+}
 
-            pub fn elapsed(&self) -> Duration {
-                self.start.elapsed()
-            }
-        }
+impl NativeContextStats {
+    pub fn get(&self, context: Address) -> usize {
+        self.size_by_context.get(&context).copied().unwrap_or(0)
     }
 
-    pub mod utils {
-        use rand::{Rng, SeedableRng, rngs::SmallRng};
-
-        pub struct RandomNumberGenerator {
-            rng: SmallRng,
+    pub fn merge(&mut self, other: &NativeContextStats) {
+        for (&context, &size) in &other.size_by_context {
+            self.increment_size(context, size);
         }
+    }
 
-        impl RandomNumberGenerator {
-            pub fn new() -> Self {
-                RandomNumberGenerator {
-                    rng: SmallRng::from_entropy(),
-                }
-            }
+    pub fn clear(&mut self) {
+        self.size_by_context.clear();
+    }
 
-            pub fn next_uint32(&mut self) -> u32 {
-                self.rng.gen()
-            }
-        }
+    pub fn is_empty(&self) -> bool {
+        self.size_by_context.is_empty()
     }
 }
 
-mod common {
-    pub mod globals {
-        // Define any global constants or types here, if needed.
-    }
+/// Infers the native context of objects during marking.
+pub struct NativeContextInferrer;
+
+struct Request {
+    delegate: Box<dyn MeasureMemoryDelegate>,
+    /// Weak references to the measured native contexts.
+    contexts: Vec<Tagged>,
+    sizes: Vec<usize>,
+    shared: usize,
+    unattributed: usize,
 }
 
-mod objects {
-    pub type Address = usize;
-    pub struct Map {}
-    pub struct HeapObject {}
-    pub struct Contexts {}
-    pub struct WeakFixedArray {}
+/// Measures the memory of native contexts, like
+/// `performance.measureUserAgentSpecificMemory()`.
+///
+/// Requests are received until a full marking cycle starts, which then
+/// runs in per-context mode: objects are attributed to the native context
+/// they were first reached from, see `MarkingWorklists`. At the end of
+/// marking the sizes are stored in the requests, and the results are
+/// reported to the delegates from a task. Due tasks, including the delayed
+/// garbage collection, run in `Heap::run_memory_measurement_tasks()`.
+pub struct MemoryMeasurement {
+    received: RefCell<Vec<Request>>,
+    processing: RefCell<Vec<Request>>,
+    done: RefCell<Vec<Request>>,
+    reporting_task_pending: Cell<bool>,
+    eager_gc_task_pending: Cell<bool>,
+    delayed_gc_task_deadline: Cell<Option<Instant>>,
 }
 
-mod heap {
-    use std::collections::HashMap;
-    use std::sync::{Arc, Mutex};
-    use crate::v8;
-    use crate::base;
-    use crate::objects;
+impl MemoryMeasurement {
+    /// Minimum delay of the garbage collection for `kDefault` requests; a
+    /// random delay of up to the same amount is added.
+    pub const K_GC_TASK_DELAY_IN_SECONDS: u64 = 10;
+
+    pub fn new() -> Self {
+        MemoryMeasurement {
+            received: RefCell::new(Vec::new()),
+            processing: RefCell::new(Vec::new()),
+            done: RefCell::new(Vec::new()),
+            reporting_task_pending: Cell::new(false),
+            eager_gc_task_pending: Cell::new(false),
+            delayed_gc_task_deadline: Cell::new(None),
+        }
+    }
 
-    pub struct Heap {}
-    pub struct NativeContextStats {
-        size_by_context_: HashMap<objects::Address, usize>,
+    /// Queues a request to measure `contexts` and schedules the garbage
+    /// collection that measures them.
+    pub fn enqueue_request(
+        &self,
+        delegate: Box<dyn MeasureMemoryDelegate>,
+        execution: MeasureMemoryExecution,
+        contexts: &[HeapObject],
+    ) {
+        self.received.borrow_mut().push(Request {
+            delegate,
+            contexts: contexts.iter().map(|&context| Tagged::weak(context)).collect(),
+            sizes: vec![0; contexts.len()],
+            shared: 0,
+            unattributed: 0,
+        });
+        self.schedule_gc_task(execution);
     }
 
-    impl NativeContextStats {
-        #[inline]
-        pub fn increment_size(&mut self, context: objects::Address, _map: &objects::Map, _object: &objects::HeapObject, size: usize) {
-            *self.size_by_context_.entry(context).or_insert(0) += size;
+    /// Called when marking starts. Returns the native contexts to measure
+    /// in this cycle, or `None` if no request is pending.
+    pub fn start_processing(&self) -> Option<Vec<Address>> {
+        let mut received = self.received.borrow_mut();
+        if received.is_empty() {
+            return None;
         }
+        let mut processing = self.processing.borrow_mut();
+        debug_assert!(processing.is_empty());
+        *processing = std::mem::take(&mut *received);
+        let mut contexts: Vec<Address> = processing
+            .iter()
+            .flat_map(|request| request.contexts.iter())
+            .filter_map(|context| context.get_heap_object())
+            .map(|context| context.address())
+            .collect();
+        contexts.sort_unstable();
+        contexts.dedup();
+        Some(contexts)
+    }
 
-        pub fn get(&self, context: objects::Address) -> usize {
-            *self.size_by_context_.get(&context).unwrap_or(&0)
+    /// Called at the end of marking with the sizes visited per context and
+    /// the size of all live objects.
+    pub fn finish_processing(&self, stats: &NativeContextStats, live_bytes: usize) {
+        let mut processing = self.processing.borrow_mut();
+        if processing.is_empty() {
+            return;
         }
-
-        pub fn clear(&mut self) {
-            self.size_by_context_.clear();
+        let shared = stats.get(K_SHARED_CONTEXT);
+        for request in processing.iter_mut() {
+            for (size, context) in request.sizes.iter_mut().zip(&request.contexts) {
+                *size = context
+                    .get_heap_object()
+                    .map_or(0, |context| stats.get(context.address()));
+            }
+            request.shared = shared;
+            request.unattributed = live_bytes
+                .saturating_sub(shared)
+                .saturating_sub(request.sizes.iter().sum());
         }
+        self.done.borrow_mut().append(&mut processing);
+        self.reporting_task_pending.set(true);
+    }
 
-        pub fn merge(&mut self, other: &NativeContextStats) {
-            for (&context, &size) in &other.size_by_context_ {
-                *self.size_by_context_.entry(context).or_insert(0) += size;
+    /// Invokes `f` on the weak references to the measured contexts.
+    pub fn iterate_weak_roots(&self, mut f: impl FnMut(ObjectSlot)) {
+        for requests in [&self.received, &self.processing, &self.done] {
+            for request in requests.borrow_mut().iter_mut() {
+                for context in request.contexts.iter_mut() {
+                    f(ObjectSlot::new(context as *mut Tagged as Address));
+                }
             }
         }
+    }
 
-        pub fn empty(&self) -> bool {
-            self.size_by_context_.is_empty()
-        }
+    /// When the pending `kDefault` garbage collection is due, if any.
+    pub fn delayed_gc_task_deadline(&self) -> Option<Instant> {
+        self.delayed_gc_task_deadline.get()
+    }
 
-        #[inline]
-        fn has_external_bytes(&self, _map: &objects::Map) -> bool {
-            // Implementation needed based on Map details.
-            false
+    /// Runs the pending tasks that are due: the garbage collection tasks
+    /// and reporting the results. Returns false if no task ran.
+    pub fn run_tasks(&self, heap: &Heap) -> bool {
+        let mut ran = false;
+        if self.eager_gc_task_pending.replace(false) {
+            self.run_gc_task(heap, MeasureMemoryExecution::kEager);
+            ran = true;
         }
-
-        fn increment_external_size(&mut self, context: objects::Address, _map: &objects::Map, _object: &objects::HeapObject) {
-            // Implementation needed based on Map details.
+        if self
+            .delayed_gc_task_deadline
+            .get()
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            self.delayed_gc_task_deadline.set(None);
+            self.run_gc_task(heap, MeasureMemoryExecution::kDefault);
+            ran = true;
+        }
+        if self.reporting_task_pending.replace(false) {
+            self.report_results();
+            ran = true;
         }
+        ran
     }
 
-    pub struct MemoryMeasurement {
-        received_: Mutex<Vec<Request>>,
-        processing_: Mutex<Vec<Request>>,
-        done_: Mutex<Vec<Request>>,
-        isolate_: *mut v8::Isolate,
-        task_runner_: Arc<dyn v8::TaskRunner + Send + Sync>,
-        reporting_task_pending_: Mutex<bool>,
-        delayed_gc_task_pending_: Mutex<bool>,
-        eager_gc_task_pending_: Mutex<bool>,
-        random_number_generator_: Mutex<base::utils::RandomNumberGenerator>,
+    fn schedule_gc_task(&self, execution: MeasureMemoryExecution) {
+        match execution {
+            MeasureMemoryExecution::kLazy => {}
+            MeasureMemoryExecution::kEager => self.eager_gc_task_pending.set(true),
+            MeasureMemoryExecution::kDefault => {
+                if self.delayed_gc_task_deadline.get().is_none() {
+                    self.delayed_gc_task_deadline
+                        .set(Some(Instant::now() + Self::next_gc_task_delay()));
+                }
+            }
+        }
     }
 
-    struct Request {
-        delegate: Box<dyn v8::MeasureMemoryDelegate>,
-        contexts: *mut objects::WeakFixedArray,
-        sizes: Vec<usize>,
-        shared: usize,
-        wasm_code: usize,
-        wasm_metadata: usize,
-        timer: base::platform::ElapsedTimer,
+    /// Starts incremental marking, which measures the received requests.
+    /// If marking is already running without them, eager requests
+    /// finalize it; the task is repeated to start the next cycle.
+    fn run_gc_task(&self, heap: &Heap, execution: MeasureMemoryExecution) {
+        if self.received.borrow().is_empty() {
+            return;
+        }
+        if !heap.options().incremental_marking {
+            heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kMeasureMemory);
+            return;
+        }
+        let incremental_marking = heap.incremental_marking();
+        if incremental_marking.is_stopped() {
+            incremental_marking.start(heap, GarbageCollectionReason::kMeasureMemory);
+            return;
+        }
+        if execution == MeasureMemoryExecution::kEager {
+            heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kMeasureMemory);
+        }
+        self.schedule_gc_task(execution);
     }
 
-    impl MemoryMeasurement {
-        pub fn new(isolate: *mut v8::Isolate) -> Self {
-            MemoryMeasurement {
-                received_: Mutex::new(Vec::new()),
-                processing_: Mutex::new(Vec::new()),
-                done_: Mutex::new(Vec::new()),
-                isolate_: isolate,
-                task_runner_: Arc::new(DummyTaskRunner{}), // Replace with actual TaskRunner if available
-                reporting_task_pending_: Mutex::new(false),
-                delayed_gc_task_pending_: Mutex::new(false),
-                eager_gc_task_pending_: Mutex::new(false),
-                random_number_generator_: Mutex::new(base::utils::RandomNumberGenerator::new()),
+    fn report_results(&self) {
+        let done = self.done.take();
+        for request in done {
+            let mut result = MeasureMemoryResult {
+                shared_size_in_bytes: request.shared,
+                unattributed_size_in_bytes: request.unattributed,
+                ..MeasureMemoryResult::default()
+            };
+            for (context, &size) in request.contexts.iter().zip(&request.sizes) {
+                // Contexts that died since are not reported.
+                if let Some(context) = context.get_heap_object() {
+                    result.contexts.push(context);
+                    result.sizes_for_contexts.push(size);
+                }
             }
+            request.delegate.measurement_complete(result);
         }
+    }
 
-        pub fn enqueue_request(&self, delegate: Box<dyn v8::MeasureMemoryDelegate>,
-                               execution: v8::MeasureMemoryExecution,
-                               contexts: &Vec<v8::Handle<v8::Context>>) -> bool {
-            let mut weak_fixed_array = Vec::new();
-            for &context in contexts {
-                weak_fixed_array.push(context);
-            }
+    fn next_gc_task_delay() -> Duration {
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_secs(Self::K_GC_TASK_DELAY_IN_SECONDS + random % Self::K_GC_TASK_DELAY_IN_SECONDS)
+    }
+}
 
-            let mut request = Request {
-                delegate: delegate,
-                contexts: std::ptr::null_mut(), // Assign valid address if converting Handle<Context> -> WeakFixedArray
-                sizes: Vec::new(),
-                shared: 0,
-                wasm_code: 0,
-                wasm_metadata: 0,
-                timer: base::platform::ElapsedTimer::new(),
-            };
+impl Default for MemoryMeasurement {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            self.received_.lock().unwrap().push(request);
-            self.schedule_gc_task(execution);
-            true
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::heap::heap::{AllocationType, HeapOptions, RootHandle};
+    use crate::heap::heap_layout::FIXED_ARRAY_MAP;
+    use crate::heap::heap_test_utils::{allocate_list, full_gc, heap_options, new_heap};
+
+    /// Allocates a native context holding a young and an old list of
+    /// `length` nodes each. Returns the root of the context and its size
+    /// including the lists.
+    fn allocate_tenant(heap: &Heap, length: usize) -> (RootHandle, usize) {
+        let context = heap.create_root(Tagged::strong(heap.allocate_native_context(2)));
+        for (index, allocation) in [AllocationType::kYoung, AllocationType::kOld].into_iter().enumerate() {
+            let list = allocate_list(heap, length, allocation);
+            heap.set(heap.root_object(context), index, heap.root(list));
+            heap.dispose_root(list);
         }
+        let size = heap.root_object(context).size() + 2 * length * FIXED_ARRAY_MAP.object_size(2);
+        (context, size)
+    }
 
-        pub fn start_processing(&self) -> Vec<objects::Address> {
-            let mut received = self.received_.lock().unwrap();
-            let mut processing = self.processing_.lock().unwrap();
+    struct Tenants {
+        a: RootHandle,
+        b: RootHandle,
+        a_size: usize,
+        b_size: usize,
+        c_size: usize,
+        shared_size: usize,
+    }
 
-            processing.extend(received.drain(..));
-            Vec::new() // Placeholder, original returns vector of Addresses
+    /// Tenants `a` and `b` are measured, a third one is not. A list that is only
+    /// reachable from the roots is shared.
+    fn allocate_tenants(heap: &Heap) -> Tenants {
+        let (a, a_size) = allocate_tenant(heap, 1000);
+        let (b, b_size) = allocate_tenant(heap, 3000);
+        let (_, c_size) = allocate_tenant(heap, 500);
+        allocate_list(heap, 700, AllocationType::kOld);
+        for _ in 0..10_000 {
+            heap.allocate_fixed_array(8, AllocationType::kOld);
         }
-
-        pub fn finish_processing(&self, stats: &NativeContextStats) {
-            let mut processing = self.processing_.lock().unwrap();
-            let mut done = self.done_.lock().unwrap();
-
-            if let Some(mut request) = processing.pop() {
-                // Assuming NativeContextStats is used to populate request details
-                done.push(request);
-            }
-            self.schedule_reporting_task();
+        Tenants {
+            a,
+            b,
+            a_size,
+            b_size,
+            c_size,
+            shared_size: 700 * FIXED_ARRAY_MAP.object_size(2),
         }
+    }
 
-        fn default_delegate(isolate: *mut v8::Isolate, context: &v8::Context, promise: &v8::PromiseResolver, mode: v8::MeasureMemoryMode) -> Box<dyn v8::MeasureMemoryDelegate> {
-            // Placeholder implementation
-            panic!("Unimplemented: Default Delegate creation.");
-        }
+    type Results = Rc<RefCell<Vec<MeasureMemoryResult>>>;
 
-        fn schedule_reporting_task(&self) {
-            let mut reporting_task_pending = self.reporting_task_pending_.lock().unwrap();
-            if !*reporting_task_pending {
-                *reporting_task_pending = true;
-                let task_runner = self.task_runner_.clone();
-                let this = Arc::new(self); // Create an Arc to safely share 'self'
-                let task = move || {
-                    this.report_results();
-                    let mut reporting_task_pending = this.reporting_task_pending_.lock().unwrap();
-                    *reporting_task_pending = false;
-                };
-                task_runner.post_task(Box::new(task));
-            }
-        }
+    struct Delegate {
+        contexts: Vec<HeapObject>,
+        results: Results,
+    }
 
-        fn report_results(&self) {
-            let mut done = self.done_.lock().unwrap();
-            // Iterate through the `done_` list and report results
-            for request in done.drain(..) {
-               // Placeholder reporting logic
-            }
+    impl MeasureMemoryDelegate for Delegate {
+        fn should_measure(&self, context: HeapObject) -> bool {
+            self.contexts.contains(&context)
         }
 
-        fn schedule_gc_task(&self, execution: v8::MeasureMemoryExecution) {
-            match execution {
-                v8::MeasureMemoryExecution::Eager => {
-                    let mut eager_gc_task_pending = self.eager_gc_task_pending_.lock().unwrap();
-                    if !*eager_gc_task_pending {
-                        *eager_gc_task_pending = true;
-                        let task_runner = self.task_runner_.clone();
-                        let this = Arc::new(self);
-                        let task = move || {
-                            // Placeholder GC task
-                            let mut eager_gc_task_pending = this.eager_gc_task_pending_.lock().unwrap();
-                            *eager_gc_task_pending = false;
-                        };
-                        task_runner.post_task(Box::new(task));
-                    }
-                }
-                v8::MeasureMemoryExecution::Delayed => {
-                    let mut delayed_gc_task_pending = self.delayed_gc_task_pending_.lock().unwrap();
-                    if !*delayed_gc_task_pending {
-                        *delayed_gc_task_pending = true;
-                        let task_runner = self.task_runner_.clone();
-                        let this = Arc::new(self);
-                        let delay = this.next_gc_task_delay_in_seconds();
-                        let task = move || {
-                            // Placeholder GC task
-                            let mut delayed_gc_task_pending = this.delayed_gc_task_pending_.lock().unwrap();
-                            *delayed_gc_task_pending = false;
-                        };
-                        task_runner.post_task(Box::new(task));
-                    }
-                }
-            }
+        fn measurement_complete(self: Box<Self>, result: MeasureMemoryResult) {
+            self.results.borrow_mut().push(result);
         }
+    }
 
-        fn is_gc_task_pending(&self, execution: v8::MeasureMemoryExecution) -> bool {
-            match execution {
-                v8::MeasureMemoryExecution::Eager => *self.eager_gc_task_pending_.lock().unwrap(),
-                v8::MeasureMemoryExecution::Delayed => *self.delayed_gc_task_pending_.lock().unwrap(),
-            }
-        }
+    fn measure(heap: &Heap, tenants: &Tenants, execution: MeasureMemoryExecution) -> Results {
+        let results = Results::default();
+        let delegate = Delegate {
+            contexts: vec![heap.root_object(tenants.a), heap.root_object(tenants.b)],
+            results: Rc::clone(&results),
+        };
+        heap.measure_memory(Box::new(delegate), execution);
+        results
+    }
 
-        fn set_gc_task_pending(&self, execution: v8::MeasureMemoryExecution) {
-            match execution {
-                v8::MeasureMemoryExecution::Eager => *self.eager_gc_task_pending_.lock().unwrap() = true,
-                v8::MeasureMemoryExecution::Delayed => *self.delayed_gc_task_pending_.lock().unwrap() = true,
-            }
-        }
+    fn check_result(heap: &Heap, tenants: &Tenants, result: &MeasureMemoryResult) {
+        assert_eq!(result.contexts, [heap.root_object(tenants.a), heap.root_object(tenants.b)]);
+        assert_eq!(result.sizes_for_contexts, [tenants.a_size, tenants.b_size]);
+        assert_eq!(result.shared_size_in_bytes, tenants.shared_size);
+        assert_eq!(result.unattributed_size_in_bytes, tenants.c_size);
+    }
 
-        fn set_gc_task_done(&self, execution: v8::MeasureMemoryExecution) {
-            match execution {
-                v8::MeasureMemoryExecution::Eager => *self.eager_gc_task_pending_.lock().unwrap() = false,
-                v8::MeasureMemoryExecution::Delayed => *self.delayed_gc_task_pending_.lock().unwrap() = false,
+    #[test]
+    fn eager_measurement_attributes_sizes_to_native_contexts() {
+        for (incremental, concurrent) in [(false, false), (true, false), (true, true)] {
+            let heap = Heap::new(HeapOptions {
+                incremental_marking: incremental,
+                ..heap_options(concurrent)
+            });
+            let tenants = allocate_tenants(&heap);
+            assert_eq!(heap.native_contexts().len(), 3);
+
+            let results = measure(&heap, &tenants, MeasureMemoryExecution::kEager);
+            assert!(heap.run_memory_measurement_tasks());
+            if incremental {
+                assert_eq!(
+                    heap.incremental_marking().start_reason(),
+                    Some(GarbageCollectionReason::kMeasureMemory)
+                );
+                while heap.run_incremental_marking_task() {}
+                assert!(results.borrow().is_empty(), "results are reported from a task");
+                assert!(heap.run_memory_measurement_tasks());
             }
-        }
+            assert!(!heap.run_memory_measurement_tasks());
 
-        fn next_gc_task_delay_in_seconds(&self) -> u32 {
-            let mut rng = self.random_number_generator_.lock().unwrap();
-            let random_delay = rng.next_uint32() % Self::K_GC_TASK_DELAY_IN_SECONDS;
-            Self::K_GC_TASK_DELAY_IN_SECONDS + random_delay
+            assert_eq!(heap.ms_count(), 1);
+            assert_eq!(results.borrow().len(), 1);
+            check_result(&heap, &tenants, &results.borrow()[0]);
+            assert!(!heap.mark_compact_collector().marking_worklists().is_per_context_mode());
         }
-
-        const K_GC_TASK_DELAY_IN_SECONDS: u32 = 10;
     }
 
-    // Dummy TaskRunner for compilation
-    struct DummyTaskRunner {}
-
-    impl v8::TaskRunner for DummyTaskRunner {
-        fn post_task(&self, task: Box<dyn FnOnce()>) {
-            task();
-        }
+    #[test]
+    fn eager_measurement_during_marking_waits_for_next_cycle() {
+        let heap = new_heap(false);
+        let tenants = allocate_tenants(&heap);
+        heap.incremental_marking().start(&heap, GarbageCollectionReason::kTesting);
+
+        let results = measure(&heap, &tenants, MeasureMemoryExecution::kEager);
+        assert!(heap.run_memory_measurement_tasks());
+        assert_eq!(heap.ms_count(), 1);
+        assert!(results.borrow().is_empty());
+
+        assert!(heap.run_memory_measurement_tasks());
+        assert!(heap.incremental_marking().is_marking());
+        while heap.run_incremental_marking_task() {}
+        assert!(heap.run_memory_measurement_tasks());
+        assert_eq!(heap.ms_count(), 2);
+        assert_eq!(results.borrow().len(), 1);
+        check_result(&heap, &tenants, &results.borrow()[0]);
     }
 
-    pub struct NativeContextInferrer {}
-
-    impl NativeContextInferrer {
-        #[inline]
-        pub fn infer(_cage_base: PtrComprCageBase, _map: &objects::Map, _object: &objects::HeapObject, _native_context: &mut objects::Address) -> bool {
-            false
-        }
+    #[test]
+    fn delayed_measurement_is_done_by_next_gc_and_skips_dead_contexts() {
+        let heap = new_heap(true);
+        let tenants = allocate_tenants(&heap);
+        let results = measure(&heap, &tenants, MeasureMemoryExecution::kDefault);
+        let delay = heap.memory_measurement().delayed_gc_task_deadline().unwrap() - Instant::now();
+        assert!(delay > Duration::from_secs(MemoryMeasurement::K_GC_TASK_DELAY_IN_SECONDS - 1));
+        assert!(delay < Duration::from_secs(2 * MemoryMeasurement::K_GC_TASK_DELAY_IN_SECONDS));
+        assert!(!heap.run_memory_measurement_tasks());
+
+        heap.dispose_root(tenants.b);
+        full_gc(&heap);
+        assert!(heap.run_memory_measurement_tasks());
+
+        assert_eq!(heap.native_contexts().len(), 2);
+        let results = results.borrow();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].contexts, [heap.root_object(tenants.a)]);
+        assert_eq!(results[0].sizes_for_contexts, [tenants.a_size]);
+        assert_eq!(results[0].shared_size_in_bytes, tenants.shared_size);
+        assert_eq!(results[0].unattributed_size_in_bytes, tenants.c_size);
     }
-
-    pub struct PtrComprCageBase {}
-}
\ No newline at end of file
+}
@@ -2,47 +2,49 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-//use crate::flags::flags; // Assuming a Rust equivalent exists
-//use crate::heap::gc_tracer; // Assuming a Rust equivalent exists
-//use crate::heap::heap_inl; // Assuming a Rust equivalent exists
-//use crate::heap::incremental_marking; // Assuming a Rust equivalent exists
-//use crate::init::v8; // Assuming a Rust equivalent exists
-//use crate::utils::utils; // Assuming a Rust equivalent exists
+use std::cell::Cell;
 
-use std::time::{Duration, Instant};
+use crate::heap::gc_tracer::GarbageCollectionReason;
+use crate::heap::heap::Heap;
 
-const K_LONG_DELAY_MS: i32 = 8000;
-const K_SHORT_DELAY_MS: i32 = 500;
-const K_WATCHDOG_DELAY_MS: i32 = 100000;
-const K_COMMITTED_MEMORY_FACTOR: f64 = 1.1;
-const K_COMMITTED_MEMORY_DELTA: usize = 10 * 1024 * 1024; // 10 MB
+const MB: usize = 1024 * 1024;
 
+/// What triggers a transition of the `MemoryReducer`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum EventType {
+pub enum EventType {
     Timer,
     MarkCompact,
     PossibleGarbage,
 }
 
+/// State of the `MemoryReducer`:
+/// - `Uninit` before the first mark-compact,
+/// - `Wait` while waiting for the timer to start the next garbage
+///   collection,
+/// - `Run` while a memory-reducing garbage collection is in progress,
+/// - `Done` once no more memory-reducing garbage collections are needed
+///   until the heap grows.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Id {
+pub enum Id {
     Uninit,
     Wait,
     Run,
     Done,
 }
 
-#[derive(Debug, Clone, Copy)]
-struct State {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct State {
     id: Id,
-    started_gcs: i32,
+    /// Number of memory-reducing garbage collections started since the
+    /// memory reducer was last done.
+    started_gcs: usize,
     next_gc_start_ms: f64,
     last_gc_time_ms: f64,
     committed_memory_at_last_run: usize,
 }
 
 impl State {
-    fn create_uninitialized() -> Self {
+    pub fn create_uninitialized() -> Self {
         State {
             id: Id::Uninit,
             started_gcs: 0,
@@ -52,7 +54,7 @@ impl State {
         }
     }
 
-    fn create_wait(started_gcs: i32, next_gc_start_ms: f64, last_gc_time_ms: f64) -> Self {
+    pub fn create_wait(started_gcs: usize, next_gc_start_ms: f64, last_gc_time_ms: f64) -> Self {
         State {
             id: Id::Wait,
             started_gcs,
@@ -62,7 +64,7 @@ impl State {
         }
     }
 
-    fn create_run(started_gcs: i32) -> Self {
+    pub fn create_run(started_gcs: usize) -> Self {
         State {
             id: Id::Run,
             started_gcs,
@@ -72,7 +74,7 @@ impl State {
         }
     }
 
-    fn create_done(last_gc_time_ms: f64, committed_memory: usize) -> Self {
+    pub fn create_done(last_gc_time_ms: f64, committed_memory: usize) -> Self {
         State {
             id: Id::Done,
             started_gcs: 0,
@@ -82,308 +84,363 @@ impl State {
         }
     }
 
-    fn id(&self) -> Id {
+    pub fn id(&self) -> Id {
         self.id
     }
 
-    fn started_gcs(&self) -> i32 {
+    pub fn started_gcs(&self) -> usize {
         self.started_gcs
     }
 
-    fn last_gc_time_ms(&self) -> f64 {
+    pub fn last_gc_time_ms(&self) -> f64 {
         self.last_gc_time_ms
     }
 
-    fn committed_memory_at_last_run(&self) -> usize {
+    pub fn committed_memory_at_last_run(&self) -> usize {
         self.committed_memory_at_last_run
     }
 
-    fn next_gc_start_ms(&self) -> f64 {
+    pub fn next_gc_start_ms(&self) -> f64 {
         self.next_gc_start_ms
     }
 }
 
 #[derive(Debug, Clone, Copy)]
-struct Event {
-    event_type: EventType,
-    time_ms: f64,
-    committed_memory: usize,
-    next_gc_likely_to_collect_more: bool,
-    should_start_incremental_gc: bool,
-    can_start_incremental_gc: bool,
-    is_frozen: bool,
+pub struct Event {
+    pub event_type: EventType,
+    /// Time since the creation of the heap's tracer.
+    pub time_ms: f64,
+    pub committed_memory: usize,
+    pub next_gc_likely_to_collect_more: bool,
+    pub should_start_incremental_gc: bool,
+    pub can_start_incremental_gc: bool,
 }
 
-struct MemoryReducer {
-    heap_: *mut Heap, // Assuming Heap struct exists
-    taskrunner_: Box<dyn TaskRunner>, // Assuming TaskRunner trait exists
-    state_: State,
-    js_calls_counter_: i32,
-    js_calls_sample_time_ms_: f64,
+/// Starts memory-reducing garbage collections once the heap stopped
+/// growing, to release memory that the allocation limit of the last
+/// mark-compact would otherwise hold on to. Mark-compacts started by the
+/// memory reducer use `HeapGrowingMode::kMinimal`, and the heap grows
+/// slowly while the memory reducer is done, see
+/// `Heap::current_heap_growing_mode()`.
+///
+/// Like `IncrementalMarkingJob`, the timer is a task that the embedder runs
+/// from its message loop through `Heap::run_memory_reducer_task()`.
+pub struct MemoryReducer {
+    state: Cell<State>,
+    /// Time the pending timer task is due, if any.
+    timer_due_ms: Cell<Option<f64>>,
+    /// Size of the objects and time when the pending timer task was
+    /// scheduled, from which the allocation rate is derived.
+    size_of_objects_at_timer_start: Cell<usize>,
+    timer_start_ms: Cell<f64>,
 }
 
 impl MemoryReducer {
-    fn new(heap: *mut Heap) -> Self {
-        // Assuming the following flags are accessible
-        //assert!(flags::incremental_marking);
-        //assert!(flags::memory_reducer);
-
+    pub const K_LONG_DELAY_MS: f64 = 8000.0;
+    pub const K_SHORT_DELAY_MS: f64 = 500.0;
+    pub const K_WATCHDOG_DELAY_MS: f64 = 100000.0;
+    /// Delay between a notification of possible garbage and the first
+    /// garbage collection, like `--gc-memory-reducer-start-delay-ms`.
+    pub const K_START_DELAY_MS: f64 = 8000.0;
+    /// Maximum number of memory-reducing garbage collections in a row,
+    /// like `--memory-reducer-gc-count`.
+    pub const K_MAX_NUMBER_OF_GCS: usize = 2;
+    /// The memory reducer becomes active again once committed memory grew
+    /// by this factor or delta since it was last done.
+    pub const K_COMMITTED_MEMORY_FACTOR: f64 = 1.1;
+    pub const K_COMMITTED_MEMORY_DELTA: usize = 10 * MB;
+    /// Allocation rate in bytes per millisecond below which the mutator is
+    /// considered idle.
+    pub const K_LOW_ALLOCATION_THROUGHPUT: f64 = 1000.0;
+
+    pub fn new() -> Self {
         MemoryReducer {
-            heap_: heap,
-            taskrunner_: get_foreground_task_runner(heap),//heap.get_foreground_task_runner(),
-            state_: State::create_uninitialized(),
-            js_calls_counter_: 0,
-            js_calls_sample_time_ms_: 0.0,
+            state: Cell::new(State::create_uninitialized()),
+            timer_due_ms: Cell::new(None),
+            size_of_objects_at_timer_start: Cell::new(0),
+            timer_start_ms: Cell::new(0.0),
         }
     }
 
-    fn notify_timer(&mut self, event: &Event) {
-        if self.state_.id() != Id::Wait {
+    pub fn state(&self) -> State {
+        self.state.get()
+    }
+
+    /// While done, the heap grows slowly so that it stays small until the
+    /// memory reducer becomes active again.
+    pub fn should_grow_heap_slowly(&self) -> bool {
+        self.state.get().id() == Id::Done
+    }
+
+    pub fn is_task_pending(&self) -> bool {
+        self.timer_due_ms.get().is_some()
+    }
+
+    /// Runs the pending timer task if it is due. Returns false if no task
+    /// ran.
+    pub fn run_task(&self, heap: &Heap) -> bool {
+        let time_ms = Self::time_ms(heap);
+        match self.timer_due_ms.get() {
+            Some(due_ms) if due_ms <= time_ms => {}
+            _ => return false,
+        }
+        self.timer_due_ms.set(None);
+        let elapsed_ms = time_ms - self.timer_start_ms.get();
+        let allocated = heap
+            .size_of_objects()
+            .saturating_sub(self.size_of_objects_at_timer_start.get());
+        let low_allocation_rate = allocated as f64 <= Self::K_LOW_ALLOCATION_THROUGHPUT * elapsed_ms;
+        let incremental_marking = heap.incremental_marking();
+        self.notify_timer(
+            heap,
+            &Event {
+                event_type: EventType::Timer,
+                time_ms,
+                committed_memory: heap.committed_memory(),
+                next_gc_likely_to_collect_more: false,
+                should_start_incremental_gc: low_allocation_rate,
+                can_start_incremental_gc: incremental_marking.is_stopped() && incremental_marking.can_be_started(heap),
+            },
+        );
+        true
+    }
+
+    /// Handles a timer event: starts a memory-reducing garbage collection
+    /// or reschedules the timer.
+    pub fn notify_timer(&self, heap: &Heap, event: &Event) {
+        if self.state.get().id() != Id::Wait {
             return;
         }
         assert_eq!(EventType::Timer, event.event_type);
-        self.state_ = self.step(self.state_, event);
-        if self.state_.id() == Id::Run {
-            //assert!(self.heap().incremental_marking().is_stopped());
-            //assert!(flags::incremental_marking);
-            //if flags::trace_memory_reducer {
-                //self.heap().isolate().print_with_timestamp(
-                //    &format!("Memory reducer: started GC #{}", self.state_.started_gcs()),
-                //);
-            //}
-            //let gc_flags = if flags::memory_reducer_favors_memory {
-            //    GCFlag::kReduceMemoryFootprint
-            //} else {
-            //    GCFlag::kNoFlags
-            //};
-            //self.heap().start_incremental_marking(
-            //    gc_flags,
-            //    GarbageCollectionReason::kMemoryReducer,
-            //    kGCCallbackFlagCollectAllExternalMemory,
-            //);
-        } else if self.state_.id() == Id::Wait {
-            // Re-schedule the timer.
-            self.schedule_timer(self.state_.next_gc_start_ms() - event.time_ms);
-            //if flags::trace_memory_reducer {
-            //    self.heap().isolate().print_with_timestamp(
-            //        &format!("Memory reducer: waiting for {} ms", self.state_.next_gc_start_ms() - event.time_ms),
-            //    );
-            //}
+        let state = Self::step(self.state.get(), event);
+        self.state.set(state);
+        match state.id() {
+            Id::Run => heap.start_incremental_marking(GarbageCollectionReason::kMemoryReducer),
+            Id::Wait => self.schedule_timer(heap, state.next_gc_start_ms() - event.time_ms),
+            _ => {}
         }
     }
 
-    fn notify_mark_compact(&mut self, committed_memory_before: usize) {
-        //if !flags::incremental_marking {
-        //    return;
-        //}
-        //let committed_memory = self.heap().committed_old_generation_memory();
-
-        //// Trigger one more GC if
-        //// - this GC decreased committed memory,
-        //// - there is high fragmentation,
-        //let event = Event {
-        //    event_type: EventType::MarkCompact,
-        //    time_ms: self.heap().monotonically_increasing_time_in_ms(),
-        //    committed_memory,
-        //    next_gc_likely_to_collect_more: (committed_memory_before > committed_memory + 1024 * 1024)
-        //        || self.heap().has_high_fragmentation(),
-        //    should_start_incremental_gc: false,
-        //    can_start_incremental_gc: false,
-        //    is_frozen: self.is_frozen(self.heap()),
-        //};
-        //let old_state = self.state_;
-        //self.state_ = self.step(self.state_, &event);
-        //if old_state.id() != Id::Wait && self.state_.id() == Id::Wait {
-        //    // If we are transitioning to the WAIT state, start the timer.
-        //    self.schedule_timer(self.state_.next_gc_start_ms() - event.time_ms);
-        //}
-        //if old_state.id() == Id::Run && flags::trace_memory_reducer {
-        //    self.heap().isolate().print_with_timestamp(
-        //        &format!(
-        //            "Memory reducer: finished GC #{} ({})",
-        //            old_state.started_gcs(),
-        //            if self.state_.id() == Id::Wait {
-        //                "will do more"
-        //            } else {
-        //                "done"
-        //            }
-        //        ),
-        //    );
-        //}
+    /// Called at the end of each mark-compact.
+    pub fn notify_mark_compact(&self, heap: &Heap, committed_memory_before: usize) {
+        let committed_memory = heap.committed_memory();
+        // Trigger one more GC if
+        // - this GC decreased committed memory,
+        // - there is high fragmentation.
+        let event = Event {
+            event_type: EventType::MarkCompact,
+            time_ms: Self::time_ms(heap),
+            committed_memory,
+            next_gc_likely_to_collect_more: committed_memory_before > committed_memory + MB
+                || committed_memory > 2 * heap.size_of_objects(),
+            should_start_incremental_gc: false,
+            can_start_incremental_gc: false,
+        };
+        let old_state = self.state.replace(Self::step(self.state.get(), &event));
+        if old_state.id() != Id::Wait && self.state.get().id() == Id::Wait {
+            // If we are transitioning to the WAIT state, start the timer.
+            self.schedule_timer(heap, self.state.get().next_gc_start_ms() - event.time_ms);
+        }
     }
 
-    fn notify_possible_garbage(&mut self) {
-        //if !flags::incremental_marking {
-        //    return;
-        //}
-        //let event = Event {
-        //    event_type: EventType::PossibleGarbage,
-        //    time_ms: self.heap().monotonically_increasing_time_in_ms(),
-        //    committed_memory: 0,
-        //    next_gc_likely_to_collect_more: false,
-        //    should_start_incremental_gc: false,
-        //    can_start_incremental_gc: false,
-        //    is_frozen: self.is_frozen(self.heap_),
-        //};
-        //let old_action = self.state_.id();
-        //self.state_ = self.step(self.state_, &event);
-        //if old_action != Id::Wait && self.state_.id() == Id::Wait {
-        //    // If we are transitioning to the WAIT state, start the timer.
-        //    self.schedule_timer(self.state_.next_gc_start_ms() - event.time_ms);
-        //}
+    /// Called when the embedder expects garbage, e.g. after a context was
+    /// disposed.
+    pub fn notify_possible_garbage(&self, heap: &Heap) {
+        let event = Event {
+            event_type: EventType::PossibleGarbage,
+            time_ms: Self::time_ms(heap),
+            committed_memory: 0,
+            next_gc_likely_to_collect_more: false,
+            should_start_incremental_gc: false,
+            can_start_incremental_gc: false,
+        };
+        let old_state = self.state.replace(Self::step(self.state.get(), &event));
+        if old_state.id() != Id::Wait && self.state.get().id() == Id::Wait {
+            // If we are transitioning to the WAIT state, start the timer.
+            self.schedule_timer(heap, self.state.get().next_gc_start_ms() - event.time_ms);
+        }
     }
 
-    fn watchdog_gc(&self, state: &State, event: &Event) -> bool {
-        state.last_gc_time_ms() != 0.0
-            && event.time_ms > state.last_gc_time_ms() + K_WATCHDOG_DELAY_MS as f64
+    fn watchdog_gc(state: &State, event: &Event) -> bool {
+        state.last_gc_time_ms() != 0.0 && event.time_ms > state.last_gc_time_ms() + Self::K_WATCHDOG_DELAY_MS
     }
 
-    fn step(&self, state: State, event: &Event) -> State {
-        //assert!(flags::memory_reducer);
-        //assert!(flags::incremental_marking);
-
+    /// The state transition function.
+    pub fn step(state: State, event: &Event) -> State {
         match state.id() {
-            Id::Uninit | Id::Done => {
-                if event.event_type == EventType::Timer {
-                    state
-                } else if event.event_type == EventType::MarkCompact {
+            Id::Uninit | Id::Done => match event.event_type {
+                EventType::Timer => state,
+                EventType::MarkCompact => {
                     if event.committed_memory
-                        < std::cmp::max(
-                            (state.committed_memory_at_last_run() as f64 * K_COMMITTED_MEMORY_FACTOR) as usize,
-                            state.committed_memory_at_last_run() + K_COMMITTED_MEMORY_DELTA,
-                        )
+                        < ((state.committed_memory_at_last_run() as f64 * Self::K_COMMITTED_MEMORY_FACTOR) as usize)
+                            .max(state.committed_memory_at_last_run() + Self::K_COMMITTED_MEMORY_DELTA)
                     {
                         state
                     } else {
-                        State::create_wait(0, event.time_ms + K_LONG_DELAY_MS as f64, event.time_ms)
+                        State::create_wait(0, event.time_ms + Self::K_LONG_DELAY_MS, event.time_ms)
                     }
-                } else {
-                    assert_eq!(EventType::PossibleGarbage, event.event_type);
-                    //State::create_wait(
-                    //    0,
-                    //    event.time_ms + flags::gc_memory_reducer_start_delay_ms as f64,
-                    //    state.last_gc_time_ms(),
-                    //)
-                    state // Dummy return to allow compilation
                 }
-            }
+                EventType::PossibleGarbage => {
+                    State::create_wait(0, event.time_ms + Self::K_START_DELAY_MS, state.last_gc_time_ms())
+                }
+            },
             Id::Wait => {
-                //assert!(state.started_gcs() <= self.max_number_of_gcs());
+                debug_assert!(state.started_gcs() <= Self::K_MAX_NUMBER_OF_GCS);
                 match event.event_type {
                     EventType::PossibleGarbage => state,
                     EventType::Timer => {
-                        //if event.is_frozen || state.started_gcs() >= self.max_number_of_gcs() {
-                        //    State::create_done(state.last_gc_time_ms(), event.committed_memory)
-                        //} else if event.can_start_incremental_gc
-                        //    && (event.should_start_incremental_gc || self.watchdog_gc(&state, event))
-                        //{
-                        //    if state.next_gc_start_ms() <= event.time_ms {
-                        //        State::create_run(state.started_gcs() + 1)
-                        //    } else {
-                        //        state
-                        //    }
-                        //} else {
-                        //    State::create_wait(
-                        //        state.started_gcs(),
-                        //        event.time_ms + K_LONG_DELAY_MS as f64,
-                        //        state.last_gc_time_ms(),
-                        //    )
-                        //}
-                        state // Dummy return to allow compilation
+                        if state.started_gcs() >= Self::K_MAX_NUMBER_OF_GCS {
+                            State::create_done(state.last_gc_time_ms(), event.committed_memory)
+                        } else if event.can_start_incremental_gc
+                            && (event.should_start_incremental_gc || Self::watchdog_gc(&state, event))
+                        {
+                            if state.next_gc_start_ms() <= event.time_ms {
+                                State::create_run(state.started_gcs() + 1)
+                            } else {
+                                state
+                            }
+                        } else {
+                            State::create_wait(
+                                state.started_gcs(),
+                                event.time_ms + Self::K_LONG_DELAY_MS,
+                                state.last_gc_time_ms(),
+                            )
+                        }
                     }
                     EventType::MarkCompact => State::create_wait(
                         state.started_gcs(),
-                        event.time_ms + K_LONG_DELAY_MS as f64,
+                        event.time_ms + Self::K_LONG_DELAY_MS,
                         event.time_ms,
                     ),
                 }
             }
             Id::Run => {
-                //assert!(state.started_gcs() <= self.max_number_of_gcs());
-                if event.event_type == EventType::MarkCompact {
-                    //if !event.is_frozen
-                    //    && state.started_gcs() < self.max_number_of_gcs()
-                    //    && (event.next_gc_likely_to_collect_more || state.started_gcs() == 1)
-                    //{
-                    //    State::create_wait(
-                    //        state.started_gcs(),
-                    //        event.time_ms + K_SHORT_DELAY_MS as f64,
-                    //        event.time_ms,
-                    //    )
-                    //} else {
-                    //    State::create_done(event.time_ms, event.committed_memory)
-                    //}
-                    state // Dummy return to allow compilation
+                debug_assert!(state.started_gcs() <= Self::K_MAX_NUMBER_OF_GCS);
+                if event.event_type != EventType::MarkCompact {
+                    return state;
+                }
+                if state.started_gcs() < Self::K_MAX_NUMBER_OF_GCS
+                    && (event.next_gc_likely_to_collect_more || state.started_gcs() == 1)
+                {
+                    State::create_wait(state.started_gcs(), event.time_ms + Self::K_SHORT_DELAY_MS, event.time_ms)
                 } else {
-                    state
+                    State::create_done(event.time_ms, event.committed_memory)
                 }
             }
         }
     }
 
-    fn schedule_timer(&self, delay_ms: f64) {
-        assert!(0.0 < delay_ms);
-        //if self.heap().is_tearing_down() {
-        //    return;
-        //}
-        //// Leave some room for precision error in task scheduler.
-        //const K_SLACK_MS: f64 = 100.0;
-        //self.taskrunner_.post_delayed_task(
-        //    Box::new(TimerTask::new(self)),
-        //    Duration::from_secs_f64((delay_ms + K_SLACK_MS) / 1000.0),
-        //);
+    fn schedule_timer(&self, heap: &Heap, delay_ms: f64) {
+        debug_assert!(delay_ms > 0.0);
+        let time_ms = Self::time_ms(heap);
+        self.timer_due_ms.set(Some(time_ms + delay_ms));
+        self.timer_start_ms.set(time_ms);
+        self.size_of_objects_at_timer_start.set(heap.size_of_objects());
     }
 
-    fn tear_down(&mut self) {
-        self.state_ = State::create_uninitialized();
-    }
-
-    fn max_number_of_gcs(&self) -> i32 {
-        //assert!(flags::memory_reducer_gc_count > 0);
-        //flags::memory_reducer_gc_count
-        1 // Dummy return to allow compilation
-    }
-
-    fn is_frozen(&self, heap: *mut Heap) -> bool {
-        //flags::memory_reducer_respects_frozen_state && heap.isolate().is_frozen()
-        false // Dummy return to allow compilation
-    }
-
-    fn heap(&self) -> &mut Heap {
-        unsafe {
-            &mut (*self.heap_)
-        }
+    fn time_ms(heap: &Heap) -> f64 {
+        heap.tracer().monotonically_increasing_time().as_secs_f64() * 1000.0
     }
 }
 
-struct TimerTask {
-    memory_reducer_: *mut MemoryReducer
+impl Default for MemoryReducer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl TimerTask {
-    fn new(memory_reducer: &MemoryReducer) -> Self {
-        TimerTask{
-            memory_reducer_: memory_reducer as *const MemoryReducer as *mut MemoryReducer
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap::heap::{HeapGrowingMode, HeapOptions};
+    use crate::heap::memory_chunk::AllocationSpace;
+
+    fn event(event_type: EventType, time_ms: f64, committed_memory: usize) -> Event {
+        Event {
+            event_type,
+            time_ms,
+            committed_memory,
+            next_gc_likely_to_collect_more: false,
+            should_start_incremental_gc: true,
+            can_start_incremental_gc: true,
         }
     }
-}
-
-trait TaskRunner {
-    fn post_delayed_task(&self, task: Box<dyn FnOnce() + Send + 'static>, delay: Duration);
-}
 
-//Dummy task runner for compilation purposes
-struct DummyTaskRunner {}
+    #[test]
+    fn step_waits_after_growth_then_runs_until_done() {
+        let state = MemoryReducer::step(
+            State::create_uninitialized(),
+            &event(EventType::MarkCompact, 1000.0, 20 * MB),
+        );
+        assert_eq!(state, State::create_wait(0, 1000.0 + MemoryReducer::K_LONG_DELAY_MS, 1000.0));
+
+        // Too early: keep waiting.
+        assert_eq!(MemoryReducer::step(state, &event(EventType::Timer, 2000.0, 20 * MB)), state);
+        let state = MemoryReducer::step(state, &event(EventType::Timer, 9000.0, 20 * MB));
+        assert_eq!(state, State::create_run(1));
+
+        // The first GC always asks for one more.
+        let state = MemoryReducer::step(state, &event(EventType::MarkCompact, 9500.0, 12 * MB));
+        assert_eq!(state, State::create_wait(1, 9500.0 + MemoryReducer::K_SHORT_DELAY_MS, 9500.0));
+        let state = MemoryReducer::step(state, &event(EventType::Timer, 10000.0, 12 * MB));
+        assert_eq!(state, State::create_run(2));
+        let state = MemoryReducer::step(state, &event(EventType::MarkCompact, 10500.0, 12 * MB));
+        assert_eq!(state, State::create_done(10500.0, 12 * MB));
+
+        // Done until committed memory grows substantially.
+        assert_eq!(MemoryReducer::step(state, &event(EventType::MarkCompact, 11000.0, 20 * MB)), state);
+        assert_eq!(MemoryReducer::step(state, &event(EventType::MarkCompact, 11000.0, 23 * MB)).id(), Id::Wait);
+    }
 
-impl TaskRunner for DummyTaskRunner {
-    fn post_delayed_task(&self, _task: Box<dyn FnOnce() + Send + 'static>, _delay: Duration) {
-        //Do nothing
+    #[test]
+    fn step_postpones_while_mutator_is_busy() {
+        let state = State::create_wait(0, 1000.0, 500.0);
+        let busy = Event {
+            should_start_incremental_gc: false,
+            ..event(EventType::Timer, 2000.0, 20 * MB)
+        };
+        assert_eq!(
+            MemoryReducer::step(state, &busy),
+            State::create_wait(0, 2000.0 + MemoryReducer::K_LONG_DELAY_MS, 500.0)
+        );
+        // The watchdog starts a GC eventually.
+        let late = Event {
+            time_ms: 500.0 + MemoryReducer::K_WATCHDOG_DELAY_MS + 1.0,
+            ..busy
+        };
+        assert_eq!(MemoryReducer::step(state, &late), State::create_run(1));
     }
-}
 
-fn get_foreground_task_runner(_heap: *mut Heap) -> Box<dyn TaskRunner> {
-    Box::new(DummyTaskRunner{})
+    #[test]
+    fn memory_reducer_gcs_use_minimal_heap_growing() {
+        let heap = Heap::new(HeapOptions {
+            concurrent_marking: false,
+            concurrent_sweeping: false,
+            ..HeapOptions::default()
+        });
+        let memory_reducer = heap.memory_reducer().unwrap();
+        memory_reducer.notify_possible_garbage(&heap);
+        assert_eq!(memory_reducer.state().id(), Id::Wait);
+        assert!(memory_reducer.is_task_pending());
+        // The timer is not due yet.
+        assert!(!heap.run_memory_reducer_task());
+
+        let due_ms = memory_reducer.state().next_gc_start_ms();
+        memory_reducer.notify_timer(&heap, &event(EventType::Timer, due_ms, heap.committed_memory()));
+        assert_eq!(memory_reducer.state().id(), Id::Run);
+        assert_eq!(
+            heap.incremental_marking().start_reason(),
+            Some(GarbageCollectionReason::kMemoryReducer)
+        );
+        assert_eq!(heap.current_heap_growing_mode(), HeapGrowingMode::kMinimal);
+
+        heap.collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kFinalizeMarkingViaTask);
+        // The first GC always asks for one more.
+        assert_eq!(memory_reducer.state().id(), Id::Wait);
+        assert_eq!(memory_reducer.state().started_gcs(), 1);
+        assert_eq!(heap.current_heap_growing_mode(), HeapGrowingMode::kDefault);
+        let decision = heap.tracer().heap_limit_decisions().pop().unwrap();
+        assert_eq!(decision.source, "V8Heap");
+        assert_eq!(decision.limit, heap.options().initial_old_generation_size);
+    }
 }
-
-//Dummy Heap struct for compilation purposes
-struct Heap {}
\ No newline at end of file
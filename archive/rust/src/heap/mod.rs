@@ -124,5 +124,7 @@ pub mod remembered-set-inl;
 pub mod marking-state;
 pub mod minor-mark-sweep;
 pub mod heap-verifier;
+#[cfg(test)]
+pub mod heap-test-utils;
 pub mod marking-barrier-inl;
 pub mod gc-tracer-inl;
@@ -1,935 +1,286 @@
-// src/heap/new_spaces.rs
+// Copyright 2011 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-//use std::sync::atomic::{AtomicUsize, Ordering};
-//use std::cell::RefCell;
-
-//use crate::base::macros::*; // Adapt macros
-
-//use crate::common::globals::*; // global constants/enums
-//use crate::flags::flags::*; // command line flags
-
-//use crate::heap::allocation_observer::*;
-//use crate::heap::array_buffer_sweeper::*;
-//use crate::heap::concurrent_marking::*;
-//use crate::heap::free_list_inl::*; // inline functions
-//use crate::heap::gc_tracer_inl::*; // inline functions
-//use crate::heap::heap_inl::*; // inline functions
-//use crate::heap::heap_verifier::*;
-//use crate::heap::incremental_marking::*;
-//use crate::heap::mark_compact::*;
-//use crate::heap::marking_state_inl::*; // inline functions
-//use crate::heap::marking_state::*;
-//use crate::heap::memory_allocator::*;
-//use crate::heap::memory_chunk::*;
-//use crate::heap::mutable_page_metadata::*;
-//use crate::heap::page_metadata_inl::*; // inline functions
-//use crate::heap::page_metadata::*;
-//use crate::heap::paged_spaces::*;
-//use crate::heap::safepoint::*;
-//use crate::heap::spaces_inl::*; // inline functions
-//use crate::heap::spaces::*;
-//use crate::heap::zapping::*;
-
-//use crate::v8::{Isolate, Heap, MemoryChunkLayout, SemiSpaceId, SemiSpaceObjectIterator, ObjectIterator, SpaceVerificationVisitor, HeapObject, Cast, ExternalString, AllocationAlignment, RootIndex, MemoryChunk, PageMetadata, MutablePageMetadata, FreeList, MemoryAllocator, PageIterator, MemoryChunkLayout, MainAllocator, MemoryChunk, PtrComprCageBase, PageRange, MemoryAllocator};
-//use crate::v8::base::OS;
-//use crate::v8::heap::spaces::Space;
-//use crate::v8::flags;
-//use crate::v8::heap::{SemiSpaceNewSpaceAllocatorPolicy, PagedNewSpaceAllocatorPolicy, SemiSpaceNewSpace, PagedNewSpace};
-
-//pub mod new_spaces {
-//use super::*;
+use std::ptr::NonNull;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
-// Placeholder types and functions
-pub type Address = usize;
-pub const kNullAddress: Address = 0;
-pub trait PageOwner {}
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum ExternalBackingStoreType {
-    kArrayBuffer,
-    kExternalString,
-    kNumValues,
-}
-
-impl ExternalBackingStoreType {
-    pub fn to_usize(self) -> usize {
-        match self {
-            ExternalBackingStoreType::kArrayBuffer => 0,
-            ExternalBackingStoreType::kExternalString => 1,
-            ExternalBackingStoreType::kNumValues => 2,
-        }
-    }
-}
-
-// MemoryChunk Flag constants, needs better definition
-pub mod MemoryChunk {
-    pub const TO_PAGE: u32 = 1 << 0;
-    pub const FROM_PAGE: u32 = 1 << 1;
-    pub const POINTERS_TO_HERE_ARE_INTERESTING: u32 = 1 << 2;
-    pub const POINTERS_FROM_HERE_ARE_INTERESTING: u32 = 1 << 3;
-    pub const PAGE_NEW_OLD_PROMOTION: u32 = 1 << 4;
-    pub const NEW_SPACE_BELOW_AGE_MARK: u32 = 1 << 5;
-    pub const IS_QUARANTINED: u32 = 1 << 6;
-}
-
-//impl MemoryChunk {
-//    pub fn InYoungGeneration(&self) -> bool {
-//        todo!()
-//    }
-//
-//    pub fn IsFlagSet(&self, pointers_to_here_are_interesting: u32) -> bool {
-//        todo!()
-//    }
-//
-//    pub fn SetFlagNonExecutable(&self, from_page: u32) {
-//        todo!()
-//    }
-//
-//    pub fn ClearFlagNonExecutable(&self, to_page: u32) {
-//        todo!()
-//    }
-//
-//    pub fn SetFlagsNonExecutable(&self, to_space_flags: u32) {
-//        todo!()
-//    }
-//}
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum AllocationAlignment {
-    kWordAligned,
-}
-
-// Example FreeList - Needs proper definition
-pub struct FreeList {}
-impl FreeList {
-    pub fn CreateFreeListForNewSpace() -> FreeList {
-        FreeList {}
-    }
-}
-// Example FreeListCategory - Needs proper definition
-pub struct FreeListCategory {}
-
-// Example AllocationOrigin - Needs proper definition
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum AllocationOrigin {
-    kRuntime,
-}
-
-// Example Heap - Needs proper definition
-pub struct Heap {
-    // Add necessary fields here
-    pub new_space: Box<SemiSpaceNewSpace>, // Example of using Box
-    pub paged_new_space: Box<PagedNewSpace>,
-}
-impl Heap {
-    pub fn isolate(&self) -> &Isolate {
-        unimplemented!()
-    }
-
-    pub fn CreateFillerObjectAt(&mut self, start: Address, size: i32) {
-        // Implementation here
-        unimplemented!()
-    }
-
-    pub fn FatalProcessOutOfMemory(&self, isolate: &Isolate, message: &str) -> ! {
-        panic!("Fatal process out of memory: {}", message);
-    }
-
-    pub fn allocator(&self) -> &MainAllocator {
-        unimplemented!()
-    }
-    pub fn semi_space_new_space(&self) -> &SemiSpaceNewSpace {
-        &self.new_space
-    }
-    pub fn gc_state(&self) -> HeapState {
-        unimplemented!()
-    }
-    pub fn incremental_marking(&self) -> &IncrementalMarking {
-        unimplemented!()
-    }
-    pub fn array_buffer_sweeper(&self) -> &ArrayBufferSweeper {
-        unimplemented!()
-    }
-    pub fn tracer(&self) -> &GCTracer {
-        unimplemented!()
-    }
-    pub fn main_thread_local_heap(&self) -> &MainThreadLocalHeap {
-        unimplemented!()
-    }
-}
-
-// Example MainAllocator - Needs proper definition
-pub struct MainAllocator {}
-impl MainAllocator{
-    pub fn new() -> Self {Self{}}
-}
-// Example IncrementalMarking - Needs proper definition
-pub struct IncrementalMarking {}
-impl IncrementalMarking{
-    pub fn marking_mode(&self) -> bool {
-        unimplemented!()
-    }
-    pub fn IsMajorMarking(&self) -> bool {
-        unimplemented!()
-    }
-    pub fn IsMarking(&self) -> bool {
-        unimplemented!()
-    }
-}
-// Example GCTracer - Needs proper definition
-pub struct GCTracer {}
-impl GCTracer{
-    pub fn IsInAtomicPause(&self) -> bool {
-        unimplemented!()
-    }
-}
-// Example MainThreadLocalHeap - Needs proper definition
-pub struct MainThreadLocalHeap {}
-
-// Example ArrayBufferSweeper - Needs proper definition
-pub struct ArrayBufferSweeper {
-
-}
-impl ArrayBufferSweeper {
-    pub fn young(&self) -> &Young {
-        unimplemented!()
-    }
-}
-pub struct Young {}
-impl Young {
-    pub fn BytesSlow(&self) -> usize {
-        unimplemented!()
-    }
-}
-// Example Isolate - Needs proper definition
-pub struct Isolate {}
-impl Isolate {
-    pub fn root(&self, root_index: RootIndex) -> Root {
-        unimplemented!()
-    }
-}
-pub struct Root {}
-impl Root {
-    pub fn ptr(&self) -> Address {
-        unimplemented!()
-    }
-}
-
-pub struct ActiveSystemPages {}
-
-impl ActiveSystemPages {
-    pub fn Add(&self, start: usize, end: usize, commit_page_size_bits: i32) -> usize {
-        unimplemented!()
-    }
-}
-
-// MemoryChunk methods that are needed, others are ignored
-impl MemoryChunk {
-    pub fn Chunk(metadata: &MutablePageMetadata) -> &MemoryChunk {
-        unimplemented!()
-    }
-
-    pub fn SetFlagNonExecutable(&self, flag: u32) {
-        // Implementation here
-        unimplemented!()
-    }
-
-    pub fn ClearFlagNonExecutable(&self, flag: u32) {
-        // Implementation here
-        unimplemented!()
-    }
-    pub fn SetFlagsNonExecutable(&self, flags: u32) {
-        unimplemented!()
-    }
-    pub fn IsFlagSet(&self, flag: u32) -> bool {
-        unimplemented!()
-    }
-
-    pub fn IsQuarantined(&self) -> bool {
-        unimplemented!()
-    }
-
-    pub fn IsFromPage(&self) -> bool {
-        unimplemented!()
-    }
-
-    pub fn IsToPage(&self) -> bool {
-        unimplemented!()
-    }
-
-    pub fn Offset(&self, address: Address) -> usize {
-        unimplemented!()
-    }
-
-    pub fn address(&self) -> Address {
-        unimplemented!()
-    }
+use crate::heap::heap_layout::{create_filler_object_at, Address, HeapObject};
+use crate::heap::memory_chunk::{AllocationSpace, MemoryChunk, K_PAGE_SIZE};
 
-    pub fn address_unsafe(&self) -> Address {
-        unimplemented!()
-    }
-    pub fn InitializationMemoryFence(&self) {
-        unimplemented!()
-    }
-    pub fn Metadata(&self) -> &MutablePageMetadata {
-        unimplemented!()
-    }
-}
-
-// MutablePageMetadata methods that are needed, others are ignored
-impl MutablePageMetadata {
-    pub fn Chunk(&self) -> &MemoryChunk {
-        unimplemented!()
-    }
-    pub fn CommittedPhysicalMemory(&self) -> usize {
-        unimplemented!()
-    }
-    pub fn ExternalBackingStoreBytes(&self, type_: ExternalBackingStoreType) -> usize {
-        unimplemented!()
-    }
-    pub fn ContainsLimit(&self, current_age_mark: Address) -> bool {
-        unimplemented!()
-    }
-    pub fn AllocatedLabSize(&self) -> usize {
-        unimplemented!()
-    }
-    pub fn Contains(&self, age_mark: Address) -> bool {
-        unimplemented!()
-    }
-}
-
-// PageMetadata methods that are needed, others are ignored
-impl PageMetadata {
-    pub const kPageSize: usize = 4096;
-
-    pub fn cast(mutable_page_metadata: *mut MutablePageMetadata) -> *mut PageMetadata {
-        mutable_page_metadata as *mut PageMetadata
-    }
-    pub fn Chunk(&self) -> &MemoryChunk {
-        unimplemented!()
-    }
-    pub fn Owner(&self) -> &dyn PageOwner {
-        unimplemented!()
-    }
-
-    pub fn owner(&self) -> &dyn PageOwner {
-        unimplemented!()
-    }
-
-    pub fn set_owner(&mut self, owner: &dyn PageOwner) {
-        unimplemented!()
-    }
-
-    pub fn area_start(&self) -> Address {
-        unimplemented!()
-    }
-    pub fn area_size(&self) -> usize {
-        unimplemented!()
-    }
-    pub fn prev_page(&self) -> *mut PageMetadata {
-        unimplemented!()
-    }
-    pub fn next_page(&self) -> *mut PageMetadata {
-        unimplemented!()
-    }
-    pub fn HighWaterMark(&self) -> Address {
-        unimplemented!()
-    }
-    pub fn IsAlignedToPageSize(address: Address) -> bool {
-        unimplemented!()
-    }
-    pub fn ResetAllocationStatistics(&mut self) {
-        unimplemented!()
-    }
-    pub fn AllocateFreeListCategories(&mut self) {
-        unimplemented!()
-    }
-    pub fn InitializeFreeListCategories(&mut self) {
-        unimplemented!()
-    }
-
-    pub fn list_node(&self) -> &ListNode {
-        unimplemented!()
-    }
-
-    pub fn list_node_mut(&mut self) -> &mut ListNode {
-        unimplemented!()
-    }
-
-    pub fn ClearLiveness(&mut self) {
-        unimplemented!()
-    }
-    pub fn IsLivenessClear(&self) -> bool {
-        unimplemented!()
-    }
-    pub fn AllocateFreeListCategories(&self) {
-        unimplemented!()
-    }
-    pub fn InitializeFreeListCategories(&self) {
-        unimplemented!()
-    }
-    pub fn FromAddress(address: Address) -> *mut PageMetadata {
-        address as *mut PageMetadata
-    }
-    pub fn FromAllocationAreaAddress(address: Address) -> *mut PageMetadata {
-        address as *mut PageMetadata
-    }
-    pub fn active_system_pages(&self) -> &ActiveSystemPages {
-        unimplemented!()
-    }
-    pub fn CommittedPhysicalMemory(&self) -> usize {
-        unimplemented!()
-    }
-    pub fn ExternalBackingStoreBytes(&self, type_: ExternalBackingStoreType) -> usize {
-        unimplemented!()
-    }
-
-    pub fn heap(&self) -> &Heap {
-        unimplemented!()
-    }
-    pub fn ConvertNewToOld(page: *mut PageMetadata) -> *mut PageMetadata {
-        unimplemented!()
-    }
-
-}
-
-// ListNode struct and methods
-pub struct ListNode {
-    prev: *mut PageMetadata,
-    next: *mut PageMetadata,
-}
-
-impl ListNode {
-    pub fn Initialize(&mut self) {
-        self.prev = std::ptr::null_mut();
-        self.next = std::ptr::null_mut();
-    }
-    pub fn prev(&self) -> *mut PageMetadata {
-        self.prev
-    }
-    pub fn next(&self) -> *mut PageMetadata {
-        self.next
-    }
-
-}
-
-//Needs proper definition
-pub struct Space {}
-impl Space {
-
-}
-//Needs proper definition
-pub struct SemiSpaceList {}
-
-//Needs proper definition
-pub struct MemoryAllocator {}
-impl MemoryAllocator {
-    pub fn AllocatePage(&mut self, kUsePool: AllocationMode, space: &SemiSpace, not_executable: u32) -> *mut PageMetadata {
-        unimplemented!()
-    }
-
-    pub fn Free(&mut self, kPool: FreeMode, last: *mut MutablePageMetadata) {
-        unimplemented!()
-    }
-    pub fn GetCommitPageSizeBits() -> i32 {
-        unimplemented!()
-    }
-    pub fn GetCommitPageSize() -> usize {
-        unimplemented!()
-    }
-}
-
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum AllocationMode {
-    kUsePool,
-}
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum FreeMode {
-    kPool,
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SemiSpaceId {
+    kFromSpace,
+    kToSpace,
 }
 
+/// One of the two halves of the young generation.
 pub struct SemiSpace {
-    heap: *mut Heap, // Raw pointer, needs proper handling
-    minimum_capacity: usize,
-    maximum_capacity: usize,
-    target_capacity: usize,
     id: SemiSpaceId,
-    age_mark: Address,
-    memory_chunk_list: SemiSpaceList, // Needs proper definition
-    current_page: *mut PageMetadata,   // Raw pointer, needs proper handling
-    current_capacity: usize,
-    quarantined_pages_count: usize,
-    committed_physical_memory_: usize,
-    committed_: AtomicUsize,
-    external_backing_store_bytes_: [AtomicUsize; ExternalBackingStoreType::kNumValues as usize],
-    allow_to_grow_beyond_capacity_: bool,
+    pages: Vec<NonNull<MemoryChunk>>,
 }
 
 impl SemiSpace {
-    pub fn InitializePage(mutable_page: *mut MutablePageMetadata, id: SemiSpaceId) -> *mut PageMetadata {
-        let in_to_space = (id != SemiSpaceId::kFromSpace);
-        let chunk = unsafe { (*mutable_page).Chunk() };
-        //chunk.SetFlagNonExecutable(if in_to_space { MemoryChunk::TO_PAGE } else { MemoryChunk::FROM_PAGE });
-        unsafe {
-            (*chunk).SetFlagNonExecutable(if in_to_space { MemoryChunk::TO_PAGE } else { MemoryChunk::FROM_PAGE });
-        }
-        let page = mutable_page as *mut PageMetadata;
-        unsafe {
-            (*page).list_node_mut().Initialize();
-            assert!((*page).IsLivenessClear());
-            (*chunk).InitializationMemoryFence();
-        }
-        page
-    }
-
-    pub fn EnsureCurrentCapacity(&mut self) -> bool {
-        if self.IsCommitted() {
-            self.EnsureCapacity(self.target_capacity);
-        }
-        self.allow_to_grow_beyond_capacity_ = false;
-        true
-    }
-
-    pub fn new(
-        heap: *mut Heap,
-        semispace: SemiSpaceId,
-        initial_capacity: usize,
-        minimum_capacity: usize,
-        maximum_capacity: usize,
-    ) -> Self {
-        assert!(initial_capacity % PageMetadata::kPageSize == 0);
-        assert!(minimum_capacity % PageMetadata::kPageSize == 0);
-        assert!(maximum_capacity % PageMetadata::kPageSize == 0);
-
-        assert!(minimum_capacity >= PageMetadata::kPageSize);
-        assert!(minimum_capacity <= initial_capacity);
-        assert!(initial_capacity <= maximum_capacity);
-
+    fn new(id: SemiSpaceId, page_count: usize) -> Self {
+        let flag = Self::page_flag(id);
         SemiSpace {
-            heap,
-            minimum_capacity,
-            maximum_capacity,
-            target_capacity: initial_capacity,
-            id: semispace,
-            age_mark: kNullAddress,
-            memory_chunk_list: SemiSpaceList {}, // Initialize properly
-            current_page: std::ptr::null_mut(),
-            current_capacity: 0,
-            quarantined_pages_count: 0,
-            committed_physical_memory_: 0,
-            committed_: AtomicUsize::new(0),
-            external_backing_store_bytes_: [AtomicUsize::new(0); ExternalBackingStoreType::kNumValues as usize],
-            allow_to_grow_beyond_capacity_: false,
+            id,
+            pages: (0..page_count)
+                .map(|_| MemoryChunk::allocate(K_PAGE_SIZE, AllocationSpace::NEW_SPACE, flag))
+                .collect(),
         }
     }
 
-    pub fn last_page(&self) -> *mut MutablePageMetadata {
-        unimplemented!()
-    }
-
-    pub fn first_page(&self) -> *mut PageMetadata {
-        unimplemented!()
+    fn page_flag(id: SemiSpaceId) -> usize {
+        match id {
+            SemiSpaceId::kFromSpace => MemoryChunk::FROM_PAGE,
+            SemiSpaceId::kToSpace => MemoryChunk::TO_PAGE,
+        }
     }
 
     pub fn id(&self) -> SemiSpaceId {
         self.id
     }
 
-    pub fn is_committed(&self) -> bool {
-        self.CommittedMemory() > 0
+    pub fn pages(&self) -> impl Iterator<Item = &MemoryChunk> {
+        // SAFETY: pages live as long as the semi-space.
+        self.pages.iter().map(|page| unsafe { page.as_ref() })
     }
 
-    pub fn ensure_capacity(&mut self, capacity: usize) -> bool {
-        if !self.is_committed() {
-            assert_eq!(self.CommittedMemory(), 0);
-        }
-        let quarantined_pages = if self.id() == SemiSpaceId::kToSpace {
-            self.quarantined_pages_count
-        } else {
-            0
-        };
-        let pages_available_for_allocation =
-            0;//self.memory_chunk_list.size() as i32 - quarantined_pages as i32;
-
-        let num_pages = (capacity / PageMetadata::kPageSize) as i32 - 0;//pages_available_for_allocation;
-        if num_pages >= 0 {
-            for pages_added in 0..num_pages {
-                if !self.AllocateFreshPage() {
-                    if pages_added > 0 {
-                        self.RewindPages(pages_added);
-                    }
-                    return false;
-                }
-            }
-        } else {
-            // Due to previously quarantined pages, we already have more pages then
-            // needed. Free the redundant pages.
-            self.RewindPages(-num_pages);
+    fn set_id(&mut self, id: SemiSpaceId) {
+        self.id = id;
+        for page in self.pages() {
+            page.clear_flag(MemoryChunk::FROM_PAGE | MemoryChunk::TO_PAGE);
+            page.set_flag(Self::page_flag(id));
         }
-        assert_eq!(capacity + quarantined_pages * PageMetadata::kPageSize, self.CommittedMemory());
-        assert_eq!(
-            (capacity / PageMetadata::kPageSize) as usize + quarantined_pages,
-            0//self.memory_chunk_list.size()
-        );
-        true
     }
+}
 
-    pub fn commit(&mut self) -> bool {
-        if !self.EnsureCapacity(self.target_capacity) {
-            return false;
+impl Drop for SemiSpace {
+    fn drop(&mut self) {
+        for page in self.pages.drain(..) {
+            // SAFETY: the pages were allocated by this semi-space.
+            unsafe { MemoryChunk::free(page) };
         }
-        self.Reset();
-        assert_eq!(self.target_capacity, self.CommittedMemory());
-        if self.age_mark == kNullAddress {
-            self.age_mark = unsafe { (*self.first_page()).area_start() };
-        }
-        assert!(self.IsCommitted());
-        true
-    }
-
-    pub fn uncommit(&mut self) {
-        assert!(self.IsCommitted());
-        //assert_eq!(self.CommittedMemory(), self.memory_chunk_list.size() * PageMetadata::kPageSize);
-        self.RewindPages(0/*self.memory_chunk_list.size() as i32*/);
-        self.current_page = std::ptr::null_mut();
-        self.current_capacity = 0;
-        self.quarantined_pages_count = 0;
-        assert_eq!(self.CommittedPhysicalMemory(), 0);
-        assert_eq!(self.CommittedMemory(), 0);
-        assert!(!self.IsCommitted());
     }
+}
 
-    pub fn committed_physical_memory(&self) -> usize {
-        if !self.IsCommitted() {
-            return 0;
+struct NewSpaceState {
+    to_space: SemiSpace,
+    from_space: SemiSpace,
+    /// Index of the to-space page that is currently allocated on.
+    current_page: usize,
+    /// Allocation top on the current page.
+    top: Address,
+}
+
+/// The young generation as two semi-spaces of equal capacity. The mutator
+/// allocates in to-space; a scavenge flips the semi-spaces and evacuates
+/// live objects from from-space back into to-space or the old generation.
+pub struct SemiSpaceNewSpace {
+    state: Mutex<NewSpaceState>,
+    /// Objects in pages marked `NEW_SPACE_BELOW_AGE_MARK` below this address
+    /// survived a scavenge. Read concurrently by scavenger tasks.
+    age_mark: AtomicUsize,
+    capacity: usize,
+}
+
+// SAFETY: pages are only handed out under the lock or by address.
+unsafe impl Send for SemiSpaceNewSpace {}
+unsafe impl Sync for SemiSpaceNewSpace {}
+
+impl SemiSpaceNewSpace {
+    /// Creates a new space with semi-spaces of `semi_space_capacity` bytes,
+    /// rounded up to whole pages.
+    pub fn new(semi_space_capacity: usize) -> Self {
+        let page_count = semi_space_capacity.div_ceil(K_PAGE_SIZE).max(1);
+        let to_space = SemiSpace::new(SemiSpaceId::kToSpace, page_count);
+        let top = to_space.pages().next().unwrap().area_start();
+        SemiSpaceNewSpace {
+            state: Mutex::new(NewSpaceState {
+                to_space,
+                from_space: SemiSpace::new(SemiSpaceId::kFromSpace, page_count),
+                current_page: 0,
+                top,
+            }),
+            age_mark: AtomicUsize::new(top),
+            capacity: page_count * K_PAGE_SIZE,
         }
-        if !OS::HasLazyCommits() {
-            return self.CommittedMemory();
-        }
-        self.committed_physical_memory_
     }
 
-    pub fn grow_to(&mut self, new_capacity: usize) -> bool {
-        if !self.IsCommitted() {
-            if !self.Commit() {
-                assert!(!self.IsCommitted());
-                return false;
+    /// Capacity of one semi-space in bytes.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes allocated in to-space, including fillers and unused parts of
+    /// handed out LABs.
+    pub fn size(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .to_space
+            .pages()
+            .take(state.current_page + 1)
+            .map(MemoryChunk::allocated_bytes)
+            .sum()
+    }
+
+    /// Hands out a linear allocation area of at least `min_size` and at
+    /// most `max_size` bytes. Returns `None` if to-space is exhausted.
+    pub fn allocate_lab(&self, min_size: usize, max_size: usize) -> Option<(Address, Address)> {
+        debug_assert!(min_size <= max_size);
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let available = state.current_page().area_end() - state.top;
+            if available >= min_size {
+                let start = state.top;
+                let end = start + available.min(max_size);
+                state.top = end;
+                state.current_page().set_high_water_mark(end);
+                return Some((start, end));
             }
+            if state.current_page + 1 == state.to_space.pages.len() {
+                return None;
+            }
+            state.current_page += 1;
+            state.top = state.current_page().area_start();
         }
-        assert!(new_capacity % 8 == 0);
-        assert!(new_capacity <= self.maximum_capacity);
-        assert!(new_capacity > self.target_capacity);
-        if !self.EnsureCapacity(new_capacity) {
-            return false;
-        }
-        self.target_capacity = new_capacity;
-        true
     }
 
-    fn allocate_fresh_page(&mut self) -> bool {
-        let new_page = unsafe {
-            (*self.heap).memory_allocator().AllocatePage(
-                AllocationMode::kUsePool,
-                self,
-                0, //NOT_EXECUTABLE, needs better definition
-            )
-        };
-        if new_page.is_null() {
-            return false;
+    /// Returns the unused part [top, limit) of a LAB.
+    pub fn free_lab_remainder(&self, top: Address, limit: Address) {
+        if top == limit {
+            return;
         }
-
-        //self.memory_chunk_list.PushBack(new_page);
-        self.IncrementCommittedPhysicalMemory(unsafe { (*new_page).CommittedPhysicalMemory() });
-        self.AccountCommitted(PageMetadata::kPageSize);
-        unsafe {
-            (*self.heap).CreateFillerObjectAt(
-                (*new_page).area_start(),
-                (*new_page).area_size() as i32,
-            );
+        let mut state = self.state.lock().unwrap();
+        if state.top == limit && state.current_page().contains(top) {
+            state.top = top;
+            state.current_page().set_high_water_mark(top);
+        } else {
+            // SAFETY: the remainder belongs to the LAB and is unused.
+            unsafe { create_filler_object_at(top, limit - top) };
         }
-
-        true
     }
 
-    pub fn rewind_pages(&mut self, num_pages: i32) {
-        assert!(num_pages > 0);
-        //assert!(self.last_page());
-        self.AccountUncommitted(num_pages as usize * PageMetadata::kPageSize);
-        let mut uncommitted_physical_memory: usize = 0;
-        let mut num_pages_left = num_pages;
-        while num_pages_left > 0 {
-            let last = self.last_page();
-            assert!(!last.is_null());
-            uncommitted_physical_memory += unsafe { (*last).CommittedPhysicalMemory() };
-            //self.memory_chunk_list.Remove(last);
-            unsafe {
-                (*self.heap).memory_allocator().Free(
-                    FreeMode::kPool,
-                    last,
-                );
-            }
-            num_pages_left -= 1;
+    /// Swaps the semi-spaces at the start of a scavenge. Allocation
+    /// continues at the start of the new, empty to-space.
+    pub fn flip(&self) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        std::mem::swap(&mut state.to_space, &mut state.from_space);
+        state.to_space.set_id(SemiSpaceId::kToSpace);
+        state.from_space.set_id(SemiSpaceId::kFromSpace);
+        for page in state.to_space.pages() {
+            page.clear_flag(MemoryChunk::NEW_SPACE_BELOW_AGE_MARK);
+            page.set_high_water_mark(page.area_start());
         }
-        self.DecrementCommittedPhysicalMemory(uncommitted_physical_memory);
+        state.current_page = 0;
+        state.top = state.current_page().area_start();
     }
 
-    pub fn shrink_to(&mut self, new_capacity: usize) {
-        assert!(new_capacity % 8 == 0);
-        assert!(new_capacity >= self.minimum_capacity);
-        assert!(new_capacity < self.target_capacity);
-        if self.IsCommitted() {
-            self.EnsureCapacity(new_capacity);
+    /// Sets the age mark to the current allocation top at the end of a
+    /// scavenge: all objects in to-space survived one scavenge and are
+    /// promoted by the next.
+    pub fn set_age_mark(&self) {
+        let state = self.state.lock().unwrap();
+        for page in state.to_space.pages().take(state.current_page + 1) {
+            page.set_flag(MemoryChunk::NEW_SPACE_BELOW_AGE_MARK);
         }
-        self.target_capacity = new_capacity;
+        self.age_mark.store(state.top, Ordering::Release);
     }
 
-    pub fn fix_pages_flags(&mut self) {
-        unimplemented!()
+    pub fn age_mark(&self) -> Address {
+        self.age_mark.load(Ordering::Acquire)
     }
 
-    pub fn reset(&mut self) {
-        //assert!(self.first_page());
-        //assert!(self.last_page());
-        self.current_page = self.first_page();
-        self.current_capacity = PageMetadata::kPageSize;
-        self.quarantined_pages_count = 0;
-    }
-
-    pub fn remove_page(&mut self, page: *mut PageMetadata) {
-        //if self.current_page == page {
-        //    if unsafe { (*page).prev_page() } {
-        //        self.current_page = unsafe { (*page).prev_page() };
-        //    }
-        //}
-        //self.memory_chunk_list.Remove(page);
-        self.AccountUncommitted(PageMetadata::kPageSize);
-        self.DecrementCommittedPhysicalMemory(unsafe { (*page).CommittedPhysicalMemory() });
-        //ForAll<ExternalBackingStoreType>(
-        //    [this, page](ExternalBackingStoreType type, int index) {
-        //        DecrementExternalBackingStoreBytes(
-        //            type, page->ExternalBackingStoreBytes(type));
-        //    });
-    }
-
-    pub fn move_page_to_the_end(&mut self, page: *mut PageMetadata) {
-        //DCHECK_EQ(page->owner(), this);
-        //memory_chunk_list_.Remove(page);
-        //memory_chunk_list_.PushBack(page);
-        self.current_page = page;
-    }
-
-    pub fn swap(from: &mut SemiSpace, to: &mut SemiSpace) {
-        // We won't be swapping semispaces without data in them.
-        //DCHECK(from->first_page());
-        //DCHECK(to->first_page());
-        assert_eq!(from.maximum_capacity, to.maximum_capacity);
-        assert_eq!(from.minimum_capacity, to.minimum_capacity);
-        // We swap all properties but id_.
-        std::mem::swap(&mut from.target_capacity, &mut to.target_capacity);
-        std::mem::swap(&mut from.age_mark, &mut to.age_mark);
-        std::mem::swap(&mut from.memory_chunk_list, &mut to.memory_chunk_list);
-        std::mem::swap(&mut from.current_page, &mut to.current_page);
-        //ForAll<ExternalBackingStoreType>(
-        //    [from, to](ExternalBackingStoreType type, int index) {
-        //        const size_t tmp = from->external_backing_store_bytes_[index].load(
-        //            std::memory_order_relaxed);
-        //        from->external_backing_store_bytes_[index].store(
-        //            to->external_backing_store_bytes_[index].load(
-        //                std::memory_order_relaxed),
-        //            std::memory_order_relaxed);
-        //        to->external_backing_store_bytes_[index].store(
-        //            tmp, std::memory_order_relaxed);
-        //    });
-        std::mem::swap(&mut from.committed_physical_memory_, &mut to.committed_physical_memory_);
-
-        // Swap committed atomic counters.
-        let to_committed = to.committed_.load(Ordering::Relaxed);
-        to.committed_.store(from.committed_.load(Ordering::Relaxed), Ordering::Relaxed);
-        from.committed_.store(to_committed, Ordering::Relaxed);
-
-        std::mem::swap(&mut from.quarantined_pages_count, &mut to.quarantined_pages_count);
-
-        // Swapping the `memory_cunk_list_` essentially swaps out the pages (actual
-        // payload) from to and from space.
-        to.FixPagesFlags();
-        from.FixPagesFlags();
-    }
-
-    pub fn increment_committed_physical_memory(&mut self, increment_value: usize) {
-        if !OS::HasLazyCommits() {
-            return;
-        }
-        assert!(self.committed_physical_memory_ <= self.committed_physical_memory_ + increment_value);
-        self.committed_physical_memory_ += increment_value;
-    }
-
-    pub fn decrement_committed_physical_memory(&mut self, decrement_value: usize) {
-        if !OS::HasLazyCommits() {
-            return;
+    /// Returns true if `object`, which must be in from-space, survived a
+    /// scavenge already.
+    #[inline]
+    pub fn is_below_age_mark(&self, object: HeapObject) -> bool {
+        let chunk = object.chunk();
+        if !chunk.is_flag_set(MemoryChunk::NEW_SPACE_BELOW_AGE_MARK) {
+            return false;
         }
-        assert!(decrement_value <= self.committed_physical_memory_);
-        self.committed_physical_memory_ -= decrement_value;
+        let age_mark = self.age_mark();
+        !chunk.contains(age_mark) || object.address() < age_mark
     }
 
-    pub fn add_range_to_active_system_pages(&mut self, start: Address, end: Address) {
-        let page = self.current_page();
-        let chunk = unsafe { (*page).Chunk() };
-
-        assert!(unsafe { (*chunk).address() } <= start);
-        assert!(start < end);
-        assert!(end <= unsafe { (*chunk).address() } + PageMetadata::kPageSize);
-
-        //const size_t added_pages = page->active_system_pages()->Add(
-        //    chunk->Offset(start), chunk->Offset(end),
-        //    MemoryAllocator::GetCommitPageSizeBits());
-        //IncrementCommittedPhysicalMemory(added_pages *
-        //                                   MemoryAllocator::GetCommitPageSize());
+    /// Invokes `f` on every page of from-space.
+    pub fn for_each_from_page(&self, mut f: impl FnMut(&MemoryChunk)) {
+        let state = self.state.lock().unwrap();
+        state.from_space.pages().for_each(&mut f);
     }
 
-    pub fn set_age_mark(&mut self, mark: Address) {
-        self.age_mark = mark;
-        let age_mark_page = unsafe { PageMetadata::FromAllocationAreaAddress(mark) };
-        let mut below_age_mark_pages = 0;
-        //DCHECK_EQ(age_mark_page->owner(), this);
-        // Mark all pages up to the one containing mark.
-        //for (PageMetadata* p : *this) {
-        //    below_age_mark_pages++;
-        //    p->Chunk()->SetFlagNonExecutable(MemoryChunk::NEW_SPACE_BELOW_AGE_MARK);
-        //    if (p == age_mark_page) break;
-        //}
-        //DCHECK_LT(quarantined_pages_count_, below_age_mark_pages);
-        //USE(below_age_mark_pages);
+    /// Invokes `f` on every to-space page that holds objects.
+    pub fn for_each_to_page(&self, mut f: impl FnMut(&MemoryChunk)) {
+        let state = self.state.lock().unwrap();
+        state
+            .to_space
+            .pages()
+            .take(state.current_page + 1)
+            .for_each(&mut f);
     }
 
-    pub fn get_object_iterator(&self, _heap: *mut Heap) -> ! {
-        // Use the SemiSpaceNewSpace::NewObjectIterator to iterate the ToSpace.
-        unreachable!();
-    }
-
-    pub fn move_quarantined_page(&mut self, chunk: *mut MemoryChunk) {
-        unimplemented!()
-    }
-
-    pub fn CommittedMemory(&self) -> usize {
-        self.committed_.load(Ordering::Relaxed)
-    }
-    pub fn AccountCommitted(&mut self, size: usize) {
-        self.committed_.fetch_add(size, Ordering::Relaxed);
-    }
-
-    pub fn AccountUncommitted(&mut self, size: usize) {
-        self.committed_.fetch_sub(size, Ordering::Relaxed);
-    }
-
-    pub fn IsCommitted(&self) -> bool {
-        self.CommittedMemory() > 0
-    }
-
-    pub fn EnsureCapacity(&mut self, capacity: usize) -> bool {
-        if !self.IsCommitted() {
-            assert_eq!(self.CommittedMemory(), 0);
-        }
-        let quarantined_pages = if self.id() == SemiSpaceId::kToSpace {
-            self.quarantined_pages_count
-        } else {
-            0
-        };
-        let pages_available_for_allocation = 0; //self.memory_chunk_list_.size() - quarantined_pages;
-        let num_pages = (capacity / PageMetadata::kPageSize) - pages_available_for_allocation;
-        if num_pages >= 0 {
-            for pages_added in 0..num_pages {
-                if !self.AllocateFreshPage() {
-                    if pages_added > 0 {
-                        self.RewindPages(pages_added as i32);
-                    }
-                    return false;
-                }
+    /// Overwrites from-space after a scavenge so that stale references to
+    /// evacuated objects are caught early.
+    pub fn zap_from_space(&self) {
+        self.for_each_from_page(|page| {
+            // SAFETY: from-space holds no live objects after a scavenge.
+            unsafe {
+                std::ptr::write_bytes(
+                    page.area_start() as *mut u8,
+                    0,
+                    page.high_water_mark() - page.area_start(),
+                );
             }
-        } else {
-            self.RewindPages(-num_pages as i32);
-        }
-        assert_eq!(capacity + quarantined_pages * PageMetadata::kPageSize, self.CommittedMemory());
-        assert_eq!(
-            capacity / PageMetadata::kPageSize + quarantined_pages,
-            0//self.memory_chunk_list_.size()
-        );
-        true
-    }
-
-    pub fn current_page(&self) -> *mut PageMetadata {
-        self.current_page
-    }
-}
-
-impl PageOwner for SemiSpace {}
-
-// SemiSpaceIterator struct and methods
-pub struct SemiSpaceIterator {
-    current: *mut PageMetadata,
-}
-
-impl SemiSpaceIterator {
-    pub fn new(space: &SemiSpace) -> Self {
-        SemiSpaceIterator {
-            current: space.first_page(),
+            page.set_high_water_mark(page.area_start());
+            page.clear_live_objects();
+        });
+    }
+
+    /// Empties the young generation after a mark-compact evacuated all of
+    /// its live objects. Both semi-spaces are zapped and allocation starts
+    /// over at the beginning of to-space.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        for page in state.to_space.pages().chain(state.from_space.pages()) {
+            // SAFETY: the young generation holds no live objects anymore.
+            unsafe {
+                std::ptr::write_bytes(
+                    page.area_start() as *mut u8,
+                    0,
+                    page.high_water_mark() - page.area_start(),
+                );
+            }
+            page.set_high_water_mark(page.area_start());
+            page.clear_flag(MemoryChunk::NEW_SPACE_BELOW_AGE_MARK);
+            page.clear_live_objects();
         }
-    }
-}
-
-//impl Iterator for SemiSpaceIterator {
-//    type Item = *mut PageMetadata;
-//
-//    fn next(&mut self) -> Option<Self::Item> {
-//        if self.current.is_null() {
-//            return None;
-//        }
-//        let current = self.current;
-//        self.current = unsafe { (*self.current).next_page() };
-//        Some(current)
-//    }
-//}
-
-pub struct NewSpace {}
-impl NewSpace {
-
-}
-pub struct HeapState {}
-
-// Placeholder implementation
-impl SemiSpaceList {
-    pub fn PushBack(&mut self, _page: *mut PageMetadata) {
-        // Implementation here
-    }
-
-    pub fn PushFront(&mut self, _page: *mut PageMetadata) {
-        // Implementation here
+        state.current_page = 0;
+        state.top = state.current_page().area_start();
+        self.age_mark.store(state.top, Ordering::Release);
     }
 
-    pub fn Remove(&mut self, _last: *mut MutablePageMetadata) {
-        unimplemented!()
-    }
-
-    pub fn Empty(&self) -> bool {
-        unimplemented!()
+    /// Returns a snapshot of the to-space pages that hold objects.
+    pub fn to_space_pages(&self) -> Vec<&MemoryChunk> {
+        let state = self.state.lock().unwrap();
+        state
+            .to_space
+            .pages
+            .iter()
+            .take(state.current_page + 1)
+            // SAFETY: pages live as long as the semi-space.
+            .map(|page| unsafe { &*page.as_ptr() })
+            .collect()
     }
 }
 
-// Placeholder implementation
-pub mod OS {
-    pub fn HasLazyCommits() -> bool {
-        false
+impl NewSpaceState {
+    fn current_page(&self) -> &MemoryChunk {
+        // SAFETY: pages live as long as the semi-space.
+        unsafe { self.to_space.pages[self.current_page].as_ref() }
     }
 }
-
-pub mod heap {
-    pub fn ShouldZapGarbage() -> bool {
-        false
-    }
-
-    pub fn ZapBlock(start: Address, size: usize, zap_value: i32) {
-        unimplemented!()
-    }
-}
-
-//} // new_spaces.rs
@@ -2,9 +2,9 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::atomic::{AtomicBool, Ordering};
-
 pub mod internal {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
     /// Represents a parallel work item that can be acquired for processing.
     pub struct ParallelWorkItem {
         acquire: AtomicBool,
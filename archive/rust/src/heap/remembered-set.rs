@@ -2,883 +2,73 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
-#![allow(unused_variables)]
-#![allow(clippy::missing_safety_doc)]
+use crate::heap::heap_layout::{Address, ObjectSlot};
+use crate::heap::memory_chunk::MemoryChunk;
+use crate::heap::slot_set::SlotCallbackResult;
 
-mod base {
-    pub mod memory {
-        pub type Address = usize;
-    }
-}
-
-mod codegen {
-    pub mod reloc_info {
-        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-        pub enum RelocMode {
-            CodeTarget,
-            EmbeddedObject,
-            // Add other modes as needed
-        }
+/// Slots in old objects that refer to young objects.
+pub const OLD_TO_NEW: usize = 0;
+/// Slots in old objects that refer to evacuation candidates. Recorded
+/// during marking and used to update pointers after compaction.
+pub const OLD_TO_OLD: usize = 1;
+pub const NUMBER_OF_REMEMBERED_SET_TYPES: usize = 2;
 
-        pub trait WritableRelocInfo {
-            fn rmode(&self) -> RelocMode;
-            fn target_address(&self) -> base::memory::Address;
-            fn set_target_address(&mut self, address: base::memory::Address);
-            fn target_object(&self, isolate: &crate::isolate::Isolate) -> Tagged<HeapObject>;
-            fn set_target_object(&mut self, object: Tagged<HeapObject>);
-        }
-
-        pub fn is_code_target_mode(mode: RelocMode) -> bool {
-            mode == RelocMode::CodeTarget
-        }
-
-        pub fn is_embedded_object_mode(mode: RelocMode) -> bool {
-            mode == RelocMode::EmbeddedObject
-        }
+/// Remembered sets record slots of a chunk that a collector has to visit
+/// without visiting the chunk's objects, e.g. the old-to-new references a
+/// scavenge has to update. Slots are recorded in the chunk of their host
+/// object.
+pub struct RememberedSet<const TYPE: usize>;
 
+impl<const TYPE: usize> RememberedSet<TYPE> {
+    /// Given a chunk and a slot in that chunk, this function adds the slot
+    /// to the remembered set. Safe to call concurrently.
+    pub fn insert(chunk: &MemoryChunk, slot: ObjectSlot) {
+        chunk
+            .get_or_allocate_slot_set(TYPE)
+            .insert(chunk.offset(slot.address()));
     }
-}
 
-mod common {
-    pub mod globals {
-        pub const kTaggedSize: usize = 8; // Example value
+    /// Given a chunk and a slot in that chunk, returns true if the
+    /// remembered set contains the slot.
+    pub fn contains(chunk: &MemoryChunk, slot: ObjectSlot) -> bool {
+        chunk
+            .slot_set(TYPE)
+            .is_some_and(|slot_set| slot_set.contains(chunk.offset(slot.address())))
     }
-}
-
-mod heap {
-    pub mod base {
-        pub mod worklist {
-            pub struct Local<T, const SIZE: usize> {
-                // Placeholder implementation
-                items: Vec<T>,
-            }
-
-            impl<T, const SIZE: usize> Local<T, SIZE> {
-                pub fn new() -> Self {
-                    Local { items: Vec::new() }
-                }
 
-                pub fn push(&mut self, item: T) {
-                    self.items.push(item);
-                }
-            }
+    pub fn remove(chunk: &MemoryChunk, slot: ObjectSlot) {
+        if let Some(slot_set) = chunk.slot_set(TYPE) {
+            slot_set.remove(chunk.offset(slot.address()));
         }
     }
 
-    pub mod memory_chunk_layout {}
-    pub mod mutable_page_metadata {
-        use crate::heap::{SlotSet, TypedSlotSet, RememberedSetType, AccessMode};
-
-        pub struct MutablePageMetadata {
-            chunk: MemoryChunk,
-            slot_sets: [Option<Box<SlotSet>>; 3], // Assuming 3 types
-            typed_slot_sets: [Option<Box<TypedSlotSet>>; 3],
-            possibly_empty_buckets: PossiblyEmptyBuckets,
-        }
-
-        impl MutablePageMetadata {
-            pub fn new(chunk: MemoryChunk) -> Self {
-                MutablePageMetadata {
-                    chunk,
-                    slot_sets: [None, None, None],
-                    typed_slot_sets: [None, None, None],
-                    possibly_empty_buckets: PossiblyEmptyBuckets::new(),
-                }
-            }
-
-            pub fn chunk(&self) -> &MemoryChunk {
-                &self.chunk
-            }
-
-            pub fn chunk_address(&self) -> usize {
-                self.chunk.address()
-            }
-
-             pub fn offset(&self, address: usize) -> usize {
-                self.chunk.offset(address)
-            }
-
-            pub fn offset_maybe_out_of_range(&self, address: usize) -> usize {
-                self.chunk.offset_maybe_out_of_range(address)
-            }
-
-            pub fn contains(&self, address: usize) -> bool {
-                self.chunk.contains(address)
-            }
-
-            pub fn allocate_slot_set(&mut self, set_type: RememberedSetType) -> &mut SlotSet {
-                let index = set_type as usize;
-                self.slot_sets[index] = Some(Box::new(SlotSet::new()));
-                self.slot_sets[index].as_mut().unwrap()
-            }
-
-            pub fn allocate_typed_slot_set(&mut self, set_type: RememberedSetType) -> &mut TypedSlotSet {
-                let index = set_type as usize;
-                self.typed_slot_sets[index] = Some(Box::new(TypedSlotSet::new()));
-                self.typed_slot_sets[index].as_mut().unwrap()
-            }
-
-            pub fn slot_set<const TYPE: RememberedSetType, const MODE: AccessMode>(&self) -> Option<&SlotSet> {
-                let index = TYPE as usize;
-                self.slot_sets[index].as_deref()
-            }
-
-            pub fn slot_set<const TYPE: RememberedSetType>(&self) -> Option<&SlotSet> {
-                let index = TYPE as usize;
-                self.slot_sets[index].as_deref()
-            }
-
-            pub fn typed_slot_set<const TYPE: RememberedSetType, const MODE: AccessMode>(&self) -> Option<&TypedSlotSet> {
-                 let index = TYPE as usize;
-                self.typed_slot_sets[index].as_deref()
-            }
-
-             pub fn typed_slot_set<const TYPE: RememberedSetType>(&self) -> Option<&TypedSlotSet> {
-                 let index = TYPE as usize;
-                self.typed_slot_sets[index].as_deref()
-            }
-
-            pub fn set_slot_set<const TYPE: RememberedSetType, const MODE: AccessMode>(&mut self, slot_set: &SlotSet) {
-                let index = TYPE as usize;
-                self.slot_sets[index] = Some(Box::new(SlotSet::clone(slot_set)));
-            }
-
-            pub fn set_typed_slot_set<const TYPE: RememberedSetType, const MODE: AccessMode>(&mut self, typed_slot_set: &TypedSlotSet) {
-                let index = TYPE as usize;
-                self.typed_slot_sets[index] = Some(Box::new(TypedSlotSet::clone(typed_slot_set)));
-            }
-
-            pub fn buckets_in_slot_set(&self) -> usize {
-                1024 // Example value
-            }
-
-            pub fn possibly_empty_buckets(&mut self) -> &mut PossiblyEmptyBuckets {
-                &mut self.possibly_empty_buckets
-            }
-
-            pub fn release_slot_set(&mut self, set_type: RememberedSetType) {
-                let index = set_type as usize;
-                self.slot_sets[index] = None;
-            }
-
-            pub fn release_typed_slot_set(&mut self, set_type: RememberedSetType) {
-                let index = set_type as usize;
-                self.typed_slot_sets[index] = None;
-            }
-        }
-
-        pub struct PossiblyEmptyBuckets {
-            // Placeholder implementation
-        }
-
-        impl PossiblyEmptyBuckets {
-            pub fn new() -> Self {
-                PossiblyEmptyBuckets {}
-            }
-
-            pub fn is_empty(&self) -> bool {
-                true // Example
-            }
-        }
-    }
-    pub mod paged_spaces {}
-    pub mod slot_set {
-        pub enum AccessMode {
-            ATOMIC,
-            NON_ATOMIC,
-        }
-
-        pub enum SlotCallbackResult {
-            KEEP_SLOT,
-            REMOVE_SLOT,
-        }
-
-        pub struct SlotSet {
-            // Placeholder implementation
-        }
-
-        impl SlotSet {
-            pub fn new() -> Self {
-                SlotSet {}
-            }
-
-            pub fn clone(other: &SlotSet) -> Self {
-                SlotSet {} // Placeholder
-            }
-
-            pub fn insert<const ATOMIC: bool>(&mut self, slot_offset: usize) {}
-
-            pub fn iterate<const MODE: AccessMode>(
-                &mut self,
-                chunk_address: usize,
-                start_bucket: usize,
-                end_bucket: usize,
-                callback: impl Fn(MaybeObjectSlot) -> SlotCallbackResult,
-                mode: EmptyBucketMode,
-            ) -> i32 {
-                0 // Placeholder
-            }
-
-            pub fn iterate_and_track_empty_buckets(
-                &mut self,
-                chunk_address: usize,
-                start_bucket: usize,
-                end_bucket: usize,
-                callback: impl Fn(MaybeObjectSlot) -> SlotCallbackResult,
-                possibly_empty_buckets: &mut super::mutable_page_metadata::PossiblyEmptyBuckets,
-            ) -> i32 {
-                0 // Placeholder
-            }
-
-            pub fn remove(&mut self, offset: usize) {}
-
-            pub fn remove_range(&mut self, start_offset: i32, end_offset: i32, buckets_in_slot_set: usize, mode: EmptyBucketMode) {}
-
-            pub fn contains(&self, offset: usize) -> bool {
-                false // Placeholder
-            }
-
-            pub fn delete(other_slot_set: &mut SlotSet) {
-               // Placeholder
-            }
-
-            pub fn merge(&mut self, other_slot_set: &SlotSet, buckets_in_slot_set: usize) {
-                // Placeholder
-            }
-
-            pub fn bucket_for_slot(offset: usize) -> usize {
-                offset // Example
-            }
-
-            pub fn check_possibly_empty_buckets(&mut self, buckets_in_slot_set: usize, possibly_empty_buckets: &mut super::mutable_page_metadata::PossiblyEmptyBuckets) -> bool {
-                false // Placeholder
-            }
-        }
-
-        pub struct TypedSlotSet {
-            // Placeholder
-        }
-
-        impl TypedSlotSet {
-            pub fn new() -> Self {
-                TypedSlotSet {}
-            }
-
-             pub fn clone(other: &TypedSlotSet) -> Self {
-                TypedSlotSet {} // Placeholder
-            }
-
-            pub fn insert(&mut self, slot_type: SlotType, offset: u32) {}
-
-            pub fn iterate<F>(&mut self, callback: F, mode: EmptyBucketMode) -> i32
-            where
-                F: Fn(SlotType, usize) -> SlotCallbackResult,
-            {
-                0 // Placeholder
-            }
-
-            pub fn merge(&mut self, other: &TypedSlotSet) {}
-            pub fn merge_typed(&mut self, other: &TypedSlots) {}
-        }
-
-        pub enum EmptyBucketMode {
-            KEEP_EMPTY_BUCKETS,
-            FREE_EMPTY_BUCKETS,
-        }
-
-        #[derive(Debug, Clone, Copy)]
-        pub struct MaybeObjectSlot {
-            address: usize,
-        }
-
-        impl MaybeObjectSlot {
-            pub fn address(&self) -> usize {
-                self.address
-            }
-        }
-
-        #[derive(Debug, Clone, Copy)]
-        pub struct FullMaybeObjectSlot<'a> {
-            ptr: &'a mut usize,
-        }
-
-        impl <'a> FullMaybeObjectSlot<'a> {
-            pub fn new(ptr: &'a mut usize) -> Self {
-                FullMaybeObjectSlot { ptr }
-            }
-
-             pub fn set_address(&mut self, address: usize) {
-                *self.ptr = address;
-            }
-        }
-
-        pub struct TypedSlots {
-            //Placeholder
+    /// Removes all slots in [start, end) of the chunk.
+    pub fn remove_range(chunk: &MemoryChunk, start: Address, end: Address) {
+        if let Some(slot_set) = chunk.slot_set(TYPE) {
+            slot_set.remove_range(chunk.offset(start), end - chunk.address());
         }
     }
-    pub mod spaces {}
-    pub struct Heap {}
-}
-
-mod isolate {
-    pub struct Isolate {}
-}
-
-pub mod heap {
-    use crate::base::memory::Address;
-    use crate::codegen::reloc_info::{RelocMode, WritableRelocInfo};
-    use crate::common::globals::kTaggedSize;
-    use crate::heap::base::worklist;
-    use crate::heap::mutable_page_metadata::MutablePageMetadata;
-    use crate::heap::slot_set::{
-        AccessMode, EmptyBucketMode, FullMaybeObjectSlot, MaybeObjectSlot, SlotCallbackResult,
-        SlotSet, TypedSlotSet, TypedSlots,
-    };
-    use std::marker::PhantomData;
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub enum RememberedSetType {
-        OLD_TO_NEW,
-        OLD_TO_NEW_BACKGROUND,
-        OLD_TO_OLD,
-        TRUSTED_TO_CODE,
+    /// Iterates and filters the remembered set of the chunk with the given
+    /// callback. The callback returns REMOVE_SLOT for slots that should be
+    /// dropped. Returns the number of slots kept.
+    pub fn iterate(chunk: &MemoryChunk, mut callback: impl FnMut(ObjectSlot) -> SlotCallbackResult) -> usize {
+        chunk.slot_set(TYPE).map_or(0, |slot_set| {
+            slot_set.iterate(chunk.address(), |slot| callback(ObjectSlot::new(slot)))
+        })
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub enum SlotType {
-        CodeEntry,
-        CodeTarget,
-        EmbeddedPointer,
+    pub fn is_empty(chunk: &MemoryChunk) -> bool {
+        chunk.slot_set(TYPE).is_none_or(|slot_set| slot_set.is_empty())
     }
 
-    struct RememberedSetOperations {}
-
-    impl RememberedSetOperations {
-        // Given a page and a slot in that page, this function adds the slot to the
-        // remembered set.
-        pub fn insert<const ACCESS_MODE_IS_ATOMIC: bool>(slot_set: &mut SlotSet, slot_offset: usize) {
-            if ACCESS_MODE_IS_ATOMIC {
-                slot_set.insert::<true>(slot_offset);
-            } else {
-                slot_set.insert::<false>(slot_offset);
-            }
-        }
-
-        pub fn iterate<const ACCESS_MODE_IS_ATOMIC: bool, Callback>(
-            slot_set: &mut SlotSet,
-            chunk: &MutablePageMetadata,
-            callback: Callback,
-            mode: EmptyBucketMode,
-        ) -> i32
-        where
-            Callback: Fn(MaybeObjectSlot) -> SlotCallbackResult,
-        {
-            let mut slots = 0;
-            if let Some(slot_set) = Some(slot_set) {
-                slots += if ACCESS_MODE_IS_ATOMIC {
-                    slot_set.iterate::<{ AccessMode::ATOMIC == AccessMode::ATOMIC }>(
-                        chunk.chunk_address(),
-                        0,
-                        chunk.buckets_in_slot_set(),
-                        callback,
-                        mode,
-                    )
-                } else {
-                    slot_set.iterate::<{ AccessMode::ATOMIC == AccessMode::NON_ATOMIC }>(
-                        chunk.chunk_address(),
-                        0,
-                        chunk.buckets_in_slot_set(),
-                        callback,
-                        mode,
-                    )
-                };
-            }
-            slots
-        }
-
-        pub fn remove(slot_set: &mut SlotSet, chunk: &mut MutablePageMetadata, slot_addr: Address) {
-            if let Some(slot_set) = Some(slot_set) {
-                let offset = chunk.offset(slot_addr);
-                slot_set.remove(offset);
-            }
-        }
-
-        pub fn remove_range(
-            slot_set: &mut SlotSet,
-            page: &mut MutablePageMetadata,
-            start: Address,
-            end: Address,
-            mode: EmptyBucketMode,
-        ) {
-            if let Some(slot_set) = Some(slot_set) {
-                let chunk = page.chunk();
-                let start_offset = chunk.offset(start);
-                let end_offset = chunk.offset_maybe_out_of_range(end);
-                assert!(start_offset <= end_offset);
-                slot_set.remove_range(start_offset as i32, end_offset as i32, page.buckets_in_slot_set(), mode);
-            }
-        }
-
-        pub fn check_none_in_range(
-            slot_set: &mut SlotSet,
-            chunk: &MemoryChunk,
-            start: Address,
-            end: Address,
-        ) {
-            if let Some(slot_set) = Some(slot_set) {
-                let start_bucket = SlotSet::bucket_for_slot(chunk.offset(start));
-                // Both 'end' and 'end_bucket' are exclusive limits, so do some index
-                // juggling to make sure we get the right bucket even if the end address
-                // is at the start of a bucket.
-                let end_bucket = SlotSet::bucket_for_slot(
-                    chunk.offset_maybe_out_of_range(end) - kTaggedSize,
-                ) + 1;
-                slot_set.iterate::<{AccessMode::ATOMIC == AccessMode::ATOMIC}>(
-                    chunk.address(),
-                    start_bucket,
-                    end_bucket,
-                    |slot| {
-                        if slot.address() < start || slot.address() >= end {
-                            SlotCallbackResult::KEEP_SLOT
-                        } else {
-                           panic!("Address found within start-end range");
-                        }
-                    },
-                    EmptyBucketMode::KEEP_EMPTY_BUCKETS,
-                );
-            }
-        }
+    /// Drops the remembered set of the chunk.
+    ///
+    /// # Safety
+    ///
+    /// See `MemoryChunk::release_slot_set()`.
+    pub unsafe fn clear(chunk: &MemoryChunk) {
+        // SAFETY: the caller upholds the contract of `release_slot_set()`.
+        unsafe { chunk.release_slot_set(TYPE) };
     }
-
-    struct RememberedSet<const TYPE: RememberedSetType>;
-
-    impl<const TYPE: RememberedSetType> RememberedSet<TYPE> {
-        // Given a page and a slot in that page, this function adds the slot to the
-        // remembered set.
-        pub fn insert<const ACCESS_MODE_IS_ATOMIC: bool>(page: &mut MutablePageMetadata, slot_offset: usize) {
-            let slot_set = if ACCESS_MODE_IS_ATOMIC {
-                page.allocate_slot_set(TYPE)
-            } else {
-                page.allocate_slot_set(TYPE)
-            };
-            RememberedSetOperations::insert::<ACCESS_MODE_IS_ATOMIC>(slot_set, slot_offset);
-        }
-
-        // Given a page and a slot set, this function merges the slot set to the set
-        // of the page. |other_slot_set| should not be used after calling this method.
-        pub fn merge_and_delete(chunk: &mut MutablePageMetadata, other_slot_set: SlotSet) {
-            assert!(
-                TYPE == RememberedSetType::OLD_TO_NEW
-                    || TYPE == RememberedSetType::OLD_TO_NEW_BACKGROUND
-            );
-            let slot_set = chunk.allocate_slot_set(TYPE);
-            if let Some(slot_set) = Some(slot_set) {
-               slot_set.merge(&other_slot_set, chunk.buckets_in_slot_set());
-                SlotSet::delete(&mut SlotSet::clone(&other_slot_set));
-            } else {
-                chunk.set_slot_set::<TYPE, { AccessMode::NON_ATOMIC == AccessMode::NON_ATOMIC }>(&other_slot_set);
-            }
-        }
-
-        pub fn merge_and_delete_typed(chunk: &mut MutablePageMetadata, mut other_typed_slot_set: TypedSlotSet) {
-            assert_eq!(TYPE, RememberedSetType::OLD_TO_NEW);
-             let typed_slot_set = chunk.allocate_typed_slot_set(TYPE);
-            if let Some(typed_slot_set) = Some(typed_slot_set) {
-                typed_slot_set.merge(&other_typed_slot_set);
-                //TypedSlotSet::delete(&mut other_typed_slot_set); // There isn't a direct translation in Rust.
-            } else {
-                chunk.set_typed_slot_set::<TYPE, { AccessMode::NON_ATOMIC == AccessMode::NON_ATOMIC }>(&other_typed_slot_set);
-            }
-        }
-
-         pub fn delete_typed(mut other_typed_slot_set: TypedSlotSet) {
-            //TypedSlotSet::delete(&mut other_typed_slot_set); // There isn't a direct translation in Rust.
-        }
-
-        pub fn contains(chunk: &mut MutablePageMetadata, slot_addr: Address) -> bool {
-            assert!(chunk.contains(slot_addr));
-            let slot_set = chunk.slot_set::<TYPE>();
-            if let Some(slot_set) = slot_set {
-                let offset = chunk.offset(slot_addr);
-                slot_set.contains(offset)
-            } else {
-                false
-            }
-        }
-
-        pub fn check_none_in_range(page: &mut MutablePageMetadata, start: Address, end: Address) {
-            let slot_set = page.slot_set::<TYPE>();
-            if let Some(slot_set) = slot_set {
-                RememberedSetOperations::check_none_in_range(slot_set as &mut SlotSet, page.chunk(), start, end);
-            }
-        }
-
-        pub fn remove(chunk: &mut MutablePageMetadata, slot_addr: Address) {
-            assert!(chunk.contains(slot_addr));
-            let slot_set = chunk.slot_set::<TYPE>();
-            if let Some(slot_set) = slot_set {
-                RememberedSetOperations::remove(slot_set as &mut SlotSet, chunk, slot_addr);
-            }
-        }
-
-        pub fn remove_range(
-            chunk: &mut MutablePageMetadata,
-            start: Address,
-            end: Address,
-            mode: EmptyBucketMode,
-        ) {
-            let slot_set = chunk.slot_set::<TYPE>();
-            if let Some(slot_set) = slot_set {
-                RememberedSetOperations::remove_range(slot_set as &mut SlotSet, chunk, start, end, mode);
-            }
-        }
-
-        pub fn iterate_memory_chunks<Callback>(heap: &Heap, callback: Callback)
-        where
-            Callback: Fn(&mut MutablePageMetadata),
-        {
-            let mut it = OldGenerationMemoryChunkIterator::new(heap);
-            while let Some(mut chunk) = it.next() {
-                let slot_set = chunk.slot_set::<TYPE>();
-                let typed_slot_set = chunk.typed_slot_set::<TYPE>();
-                if slot_set.is_some() || typed_slot_set.is_some() {
-                    callback(&mut chunk);
-                }
-            }
-        }
-
-        pub fn iterate<const ACCESS_MODE_IS_ATOMIC: bool, Callback>(
-            chunk: &mut MutablePageMetadata,
-            callback: Callback,
-            mode: EmptyBucketMode,
-        ) -> i32
-        where
-            Callback: Fn(MaybeObjectSlot) -> SlotCallbackResult,
-        {
-            let slot_set = chunk.slot_set::<TYPE>();
-            RememberedSet::<TYPE>::iterate_internal::<ACCESS_MODE_IS_ATOMIC, Callback>(slot_set.unwrap(), chunk, callback, mode)
-        }
-
-        fn iterate_internal<const ACCESS_MODE_IS_ATOMIC: bool, Callback>(
-            slot_set: &mut SlotSet,
-            chunk: &MutablePageMetadata,
-            callback: Callback,
-            mode: EmptyBucketMode,
-        ) -> i32
-        where
-            Callback: Fn(MaybeObjectSlot) -> SlotCallbackResult,
-        {
-            RememberedSetOperations::iterate::<ACCESS_MODE_IS_ATOMIC>(slot_set, chunk, callback, mode)
-        }
-
-        pub fn iterate_and_track_empty_buckets<Callback>(
-            chunk: &mut MutablePageMetadata,
-            callback: Callback,
-            empty_chunks: &mut worklist::Local<MutablePageMetadata, 64>,
-        ) -> i32
-        where
-            Callback: Fn(MaybeObjectSlot) -> SlotCallbackResult,
-        {
-            let slot_set = chunk.slot_set::<TYPE>();
-            let mut slots = 0;
-            if let Some(slot_set) = slot_set {
-                let possibly_empty_buckets = chunk.possibly_empty_buckets();
-                slots += slot_set.iterate_and_track_empty_buckets(
-                    chunk.chunk_address(),
-                    0,
-                    chunk.buckets_in_slot_set(),
-                    callback,
-                    possibly_empty_buckets,
-                );
-                if !possibly_empty_buckets.is_empty() {
-                    empty_chunks.push(MutablePageMetadata::new(MemoryChunk::new())); //Placeholder: Create a new chunk to push as the original chunk is borrowed.
-                }
-            }
-            slots
-        }
-
-        pub fn check_possibly_empty_buckets(chunk: &mut MutablePageMetadata) -> bool {
-            assert!(
-                TYPE == RememberedSetType::OLD_TO_NEW || TYPE == RememberedSetType::OLD_TO_NEW_BACKGROUND
-            );
-            let slot_set = chunk.slot_set::<TYPE, { AccessMode::NON_ATOMIC == AccessMode::NON_ATOMIC }>();
-            if let Some(slot_set) = slot_set {
-                if slot_set.check_possibly_empty_buckets(
-                    chunk.buckets_in_slot_set(),
-                    chunk.possibly_empty_buckets(),
-                ) {
-                    chunk.release_slot_set(TYPE);
-                    return true;
-                }
-            }
-            false
-        }
-
-        pub fn insert_typed(memory_chunk: &mut MutablePageMetadata, slot_type: SlotType, offset: u32) {
-             let slot_set = memory_chunk.allocate_typed_slot_set(TYPE);
-            slot_set.insert(slot_type, offset);
-        }
-
-        pub fn merge_typed(page: &mut MutablePageMetadata, other: std::unique_ptr::UniquePtr<TypedSlots>) {
-             let slot_set = page.allocate_typed_slot_set(TYPE);
-            slot_set.merge_typed(other.get());
-        }
-
-        pub fn remove_range_typed(page: &mut MutablePageMetadata, start: Address, end: Address) {
-            let slot_set = page.typed_slot_set::<TYPE>();
-            if let Some(slot_set) = slot_set {
-                slot_set.iterate(
-                    |slot_type, slot_addr| {
-                        if start <= slot_addr && slot_addr < end {
-                            SlotCallbackResult::REMOVE_SLOT
-                        } else {
-                            SlotCallbackResult::KEEP_SLOT
-                        }
-                    },
-                    EmptyBucketMode::FREE_EMPTY_BUCKETS,
-                );
-            }
-        }
-
-        pub fn iterate_typed<Callback>(chunk: &mut MutablePageMetadata, callback: Callback) -> i32
-        where
-            Callback: Fn(SlotType, Address) -> SlotCallbackResult,
-        {
-             let slot_set = chunk.typed_slot_set::<TYPE>();
-            match slot_set {
-                Some(slot_set) => Self::iterate_typed_internal(slot_set, callback),
-                None => 0,
-            }
-        }
-
-        fn iterate_typed_internal<Callback>(slot_set: &mut TypedSlotSet, callback: Callback) -> i32
-        where
-            Callback: Fn(SlotType, Address) -> SlotCallbackResult,
-        {
-            slot_set.iterate(callback, EmptyBucketMode::KEEP_EMPTY_BUCKETS)
-        }
-
-        pub fn clear_all(heap: &mut Heap) {
-            assert!(TYPE == RememberedSetType::OLD_TO_OLD || TYPE == RememberedSetType::TRUSTED_TO_CODE);
-            let mut it = OldGenerationMemoryChunkIterator::new(heap);
-            while let Some(mut chunk) = it.next() {
-                chunk.release_slot_set(RememberedSetType::OLD_TO_OLD);
-                chunk.release_slot_set(RememberedSetType::TRUSTED_TO_CODE);
-                chunk.release_typed_slot_set(RememberedSetType::OLD_TO_OLD);
-            }
-        }
-    }
-
-    struct UpdateTypedSlotHelper {}
-
-    impl UpdateTypedSlotHelper {
-        pub fn update_typed_slot<Callback>(
-            jit_allocation: &mut WritableJitAllocation,
-            heap: &mut Heap,
-            slot_type: SlotType,
-            addr: Address,
-            callback: Callback,
-        ) -> SlotCallbackResult
-        where
-            Callback: Fn(FullMaybeObjectSlot) -> SlotCallbackResult,
-        {
-            match slot_type {
-                SlotType::CodeEntry => Self::update_code_entry(addr, callback),
-                SlotType::CodeTarget => {
-                    // Assuming WritableRelocInfo is available through WritableJitAllocation
-                    if let Some(rinfo) = jit_allocation.get_writable_reloc_info() {
-                        Self::update_code_target(rinfo, callback)
-                    } else {
-                        SlotCallbackResult::KEEP_SLOT // Or handle the error appropriately
-                    }
-                }
-                SlotType::EmbeddedPointer => {
-                     if let Some(rinfo) = jit_allocation.get_writable_reloc_info() {
-                         Self::update_embedded_pointer(heap, rinfo, callback)
-                    } else {
-                        SlotCallbackResult::KEEP_SLOT // Or handle the error appropriately
-                    }
-                }
-            }
-        }
-
-        pub fn get_target_object(heap: &mut Heap, slot_type: SlotType, addr: Address) -> Tagged<HeapObject> {
-            // Placeholder implementation
-            Tagged::<HeapObject>::zero()
-        }
-
-        fn update_code_entry<Callback>(
-            entry_address: Address,
-            callback: Callback,
-        ) -> SlotCallbackResult
-        where
-            Callback: Fn(FullMaybeObjectSlot) -> SlotCallbackResult,
-        {
-            let code = InstructionStream::from_entry_address(entry_address);
-            let old_code = code;
-            let mut code_addr = code.instruction_start();
-            let mut slot = FullMaybeObjectSlot::new(&mut code_addr);
-            let result = callback(slot);
-
-            let new_code = InstructionStream::from_instruction_start(code_addr);
-
-            if new_code != old_code {
-                unsafe {
-                    *(entry_address as *mut Address) = code_addr;
-                }
-            }
-            result
-        }
-
-        fn update_code_target<Callback>(
-            rinfo: &mut dyn WritableRelocInfo,
-            callback: Callback,
-        ) -> SlotCallbackResult
-        where
-            Callback: Fn(FullMaybeObjectSlot) -> SlotCallbackResult,
-        {
-            assert!(rinfo.rmode() == RelocMode::CodeTarget);
-            let old_target = InstructionStream::from_target_address(rinfo.target_address());
-            let mut new_target = old_target.instruction_start();
-            let mut slot = FullMaybeObjectSlot::new(&mut new_target);
-            let result = callback(slot);
-            let new_instruction_stream = InstructionStream::from_instruction_start(new_target);
-
-            if new_instruction_stream != old_target {
-                rinfo.set_target_address(new_target);
-            }
-            result
-        }
-
-        fn update_embedded_pointer<Callback>(
-            heap: &mut Heap,
-            rinfo: &mut dyn WritableRelocInfo,
-            callback: Callback,
-        ) -> SlotCallbackResult
-        where
-            Callback: Fn(FullMaybeObjectSlot) -> SlotCallbackResult,
-        {
-            assert!(rinfo.rmode() == RelocMode::EmbeddedObject);
-            let old_target = rinfo.target_object(&crate::isolate::Isolate{});
-            let mut new_target = old_target.address();
-            let mut slot = FullMaybeObjectSlot::new(&mut new_target);
-            let result = callback(slot);
-            let new_object = Tagged::<HeapObject>::from_address(new_target);
-            if new_object != old_target {
-                rinfo.set_target_object(new_object);
-            }
-            result
-        }
-    }
-
-    pub trait WritableJitAllocation {
-        fn get_writable_reloc_info(&mut self) -> Option<&mut dyn WritableRelocInfo>;
-    }
-
-    // Placeholder implementations
-    struct OldGenerationMemoryChunkIterator<'a> {
-        heap: &'a Heap,
-        current: usize,
-    }
-
-    impl<'a> OldGenerationMemoryChunkIterator<'a> {
-        fn new(heap: &'a Heap) -> Self {
-            OldGenerationMemoryChunkIterator { heap, current: 0 }
-        }
-
-        fn next(&mut self) -> Option<MutablePageMetadata> {
-            if self.current < 5 {
-                self.current += 1;
-                Some(MutablePageMetadata::new(MemoryChunk::new()))
-            } else {
-                None
-            }
-        }
-    }
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub struct Tagged<T> {
-        address: usize,
-        _phantom: PhantomData<T>,
-    }
-
-    impl<T> Tagged<T> {
-         pub fn zero() -> Self {
-            Tagged { address: 0, _phantom: PhantomData }
-        }
-
-         pub fn from_address(address: usize) -> Self {
-            Tagged { address: address, _phantom: PhantomData }
-        }
-
-        pub fn address(&self) -> usize {
-            self.address
-        }
-    }
-
-    pub struct HeapObject {}
-    pub struct InstructionStream {}
-
-    impl InstructionStream {
-        pub fn from_entry_address(address: usize) -> Self {
-            InstructionStream {} // Placeholder
-        }
-
-        pub fn from_instruction_start(address: usize) -> Self {
-             InstructionStream {} // Placeholder
-        }
-
-        pub fn from_target_address(address: usize) -> Self {
-            InstructionStream {} // Placeholder
-        }
-
-        pub fn instruction_start(&self) -> usize {
-            0 // Placeholder
-        }
-    }
-
-    pub fn has_weak_heap_object_tag(code: InstructionStream) -> bool {
-        false // Placeholder
-    }
-
-    pub struct MemoryChunk {
-        address: usize
-    }
-
-    impl MemoryChunk {
-        pub fn new() -> Self {
-            MemoryChunk { address: 0 }
-        }
-
-        pub fn address(&self) -> usize {
-            self.address
-        }
-
-        pub fn offset(&self, address: usize) -> usize {
-            address - self.address
-        }
-
-        pub fn offset_maybe_out_of_range(&self, address: usize) -> usize {
-            address - self.address
-        }
-
-        pub fn contains(&self, address: usize) -> bool {
-            address >= self.address && address < self.address + 4096 // Example range
-        }
-    }
-
-    pub mod std {
-        pub mod unique_ptr {
-            pub struct UniquePtr<T> {
-                value: T,
-            }
-
-            impl<T> UniquePtr<T> {
-                pub fn get(&self) -> &T {
-                    &self.value
-                }
-            }
-        }
-    }
-}
\ No newline at end of file
+}
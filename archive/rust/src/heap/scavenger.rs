@@ -2,675 +2,612 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod scavenger {
-    use std::collections::HashMap;
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::{Arc, Mutex};
-    use crate::heap::base::worklist::Worklist;
-    use crate::heap::ephemeron_remembered_set::EphemeronRememberedSet;
-    // use crate::heap::evacuation_allocator::EvacuationAllocator; // TODO: Implement EvacuationAllocator
-    use crate::heap::heap_visitor::HeapVisitor; // TODO: Implement HeapVisitor
-    use crate::heap::index_generator::IndexGenerator;
-    use crate::heap::mutable_page_metadata::MutablePageMetadata;
-    use crate::heap::parallel_work_item::ParallelWorkItem;
-    // use crate::heap::pretenuring_handler::PretenuringHandler; // TODO: Implement PretenuringHandler
-    // use crate::heap::slot_set::SlotSet; // TODO: Implement SlotSet
-
-    // use v8::base::platform::condition_variable::ConditionVariable;  // TODO: Implement ConditionVariable
-    // use crate::objects;  // TODO: Figure out where this is supposed to come from, and what's in there
-
-    // Placeholder types
-    pub type HeapObject = u64; // Replace with actual HeapObject type
-    pub type Tagged<T> = T; // Replace with Tagged type
-    pub type Map = u64; // Replace with actual Map type
-    pub type Address = usize;
-    pub type MaybeObject = u64;
-    pub type EphemeronHashTable = u64;
-    pub type ThinString = u64;
-    pub type ConsString = u64;
-    pub type String = u64;
-    pub type ObjectFields = u64; // Dummy type. Replace with actual ObjectFields type if available.
-    pub type FullObjectSlot = *mut HeapObject; // Replace with actual FullObjectSlot type. Raw pointer for now.
-    pub type SlotType = u32;
-    pub type MemoryChunk = u64; // Dummy type. Replace with actual MemoryChunk.
-    pub type Isolate = u64; // Dummy type. Replace with actual Isolate
-    pub type Object = u64;
-    pub type JobDelegate = u64;
-
-    pub enum CopyAndForwardResult {
-        SUCCESS_YOUNG_GENERATION,
-        SUCCESS_OLD_GENERATION,
-        FAILURE,
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::heap::base::worklist::{Local, Worklist};
+use crate::heap::ephemeron_remembered_set::IndicesSet;
+use crate::heap::evacuation_allocator::EvacuationAllocator;
+use crate::heap::evacuation_verifier::EvacuationVerifier;
+use crate::heap::gc_tracer::{GarbageCollector, ScopeId};
+use crate::heap::heap::Heap;
+use crate::heap::heap_layout::{HeapLayout, HeapObject, MapWord, ObjectSlot, Tagged};
+use crate::heap::heap_visitor::EphemeronHashTableShape;
+use crate::heap::memory_chunk::MemoryChunk;
+use crate::heap::new_spaces::SemiSpaceNewSpace;
+use crate::heap::paged_spaces::PagedSpace;
+use crate::heap::parallel_work_item::internal::ParallelWorkItem;
+use crate::heap::remembered_set::{RememberedSet, OLD_TO_NEW};
+
+const MB: usize = 1024 * 1024;
+
+pub(crate) const K_COPIED_LIST_SEGMENT_SIZE: usize = 256;
+pub(crate) const K_PROMOTED_LIST_SEGMENT_SIZE: usize = 256;
+
+/// Objects copied within the young generation whose bodies still have to
+/// be scanned.
+pub(crate) type CopiedList = Worklist<HeapObject, K_COPIED_LIST_SEGMENT_SIZE>;
+/// Objects promoted into the old generation whose bodies still have to be
+/// scanned. Their slots to young objects are recorded in OLD_TO_NEW.
+pub(crate) type PromotedList = Worklist<HeapObject, K_PROMOTED_LIST_SEGMENT_SIZE>;
+
+/// Sizes of the objects evacuated by a scavenge.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ScavengeResult {
+    /// Bytes copied within the young generation.
+    pub copied_size: usize,
+    /// Bytes promoted into the old generation.
+    pub promoted_size: usize,
+}
+
+/// A weak slot referring to a from-space object. Weak references do not
+/// keep their targets alive; they are updated or cleared after all live
+/// objects have been evacuated.
+pub(crate) struct WeakSlot<'a> {
+    pub(crate) slot: ObjectSlot,
+    /// Page of the slot's host if the host is old, i.e. the slot must be
+    /// recorded in OLD_TO_NEW while it refers to a young object.
+    pub(crate) host_chunk: Option<&'a MemoryChunk>,
+}
+
+/// State shared by all tasks of a scavenge.
+struct ScavengeJob<'a> {
+    copied_list: CopiedList,
+    promoted_list: PromotedList,
+    /// Old pages with OLD_TO_NEW slots. Each page is processed by the task
+    /// that acquires it.
+    remembered_set_items: Vec<(&'a MemoryChunk, ParallelWorkItem)>,
+    num_tasks: usize,
+    idle_tasks: Mutex<usize>,
+    /// Mirrors `idle_tasks` so that busy tasks can cheaply check whether
+    /// they should share work.
+    idle_tasks_hint: AtomicUsize,
+    work_available: Condvar,
+}
+
+impl ScavengeJob<'_> {
+    /// Time an idle task waits before checking for published work again.
+    const K_IDLE_POLL_INTERVAL: Duration = Duration::from_micros(100);
+
+    fn has_global_work(&self) -> bool {
+        !self.copied_list.is_empty() || !self.promoted_list.is_empty()
     }
 
-    pub type SurvivingNewLargeObjectsMap = HashMap<Tagged<HeapObject>, Tagged<Map>>;
-    pub type SurvivingNewLargeObjectMapEntry = (Tagged<HeapObject>, Tagged<Map>);
-
-    pub struct ScavengerCollector {
-        isolate_: Isolate,
-        heap_: *mut Heap, // Raw pointer because of potential cycles/lifetime issues
-        surviving_new_large_objects_: SurvivingNewLargeObjectsMap,
-        estimate_concurrency_: AtomicUsize,
+    fn has_idle_tasks(&self) -> bool {
+        self.idle_tasks_hint.load(Ordering::Relaxed) > 0
     }
 
-    impl ScavengerCollector {
-        pub const K_MAX_SCAVENGER_TASKS: usize = 8;
-        pub const K_MAIN_THREAD_ID: usize = 0;
-
-        pub fn new(heap: *mut Heap) -> Self {
-            ScavengerCollector {
-                isolate_: 0, // TODO: Initialize correctly
-                heap_: heap,
-                surviving_new_large_objects_: HashMap::new(),
-                estimate_concurrency_: AtomicUsize::new(0),
+    /// Called by a task that ran out of work. Returns true once work was
+    /// published by another task, or false when all tasks are idle and the
+    /// scavenge is complete.
+    fn wait_for_work(&self) -> bool {
+        let mut idle = self.idle_tasks.lock().unwrap();
+        *idle += 1;
+        self.idle_tasks_hint.store(*idle, Ordering::Relaxed);
+        loop {
+            if self.has_global_work() {
+                *idle -= 1;
+                self.idle_tasks_hint.store(*idle, Ordering::Relaxed);
+                return true;
             }
-        }
-
-        pub fn collect_garbage(&mut self) {
-            todo!()
-        }
-
-        fn merge_surviving_new_large_objects(
-            &mut self,
-            objects: &SurvivingNewLargeObjectsMap,
-        ) {
-            for (k, v) in objects {
-                self.surviving_new_large_objects_.insert(*k, *v);
+            if *idle == self.num_tasks {
+                self.work_available.notify_all();
+                return false;
             }
+            idle = self
+                .work_available
+                .wait_timeout(idle, Self::K_IDLE_POLL_INTERVAL)
+                .unwrap()
+                .0;
         }
+    }
+}
+
+/// Performs a scavenge: evacuates the live objects of from-space into
+/// to-space, or into old space once they survived a scavenge already.
+///
+/// The roots are processed on the main thread. Afterwards all tasks
+/// process the OLD_TO_NEW remembered set page by page and scan evacuated
+/// objects, stealing work from each other through the shared worklists.
+pub struct ScavengerCollector<'h> {
+    heap: &'h Heap,
+}
+
+impl<'h> ScavengerCollector<'h> {
+    pub const K_MAX_SCAVENGER_TASKS: usize = 8;
+
+    pub fn new(heap: &'h Heap) -> Self {
+        ScavengerCollector { heap }
+    }
 
-        fn number_of_scavenge_tasks(&self) -> i32 {
-            todo!()
-        }
+    /// Returns the number of tasks, including the main thread, for the
+    /// next scavenge. Small young generations are not worth parallelizing.
+    pub fn number_of_scavenge_tasks(&self) -> usize {
+        let options = self.heap.options();
+        if !options.parallel_scavenge {
+            return 1;
+        }
+        let tasks_for_capacity = self.heap.new_space().capacity() / MB + 1;
+        options
+            .max_scavenger_tasks
+            .clamp(1, Self::K_MAX_SCAVENGER_TASKS)
+            .min(tasks_for_capacity)
+    }
 
-        fn process_weak_references(
-            &mut self,
-            ephemeron_table_list: &mut EphemeronRememberedSet::TableList,
-        ) {
-            todo!()
-        }
-        fn clear_young_ephemerons(
-            &mut self,
-            ephemeron_table_list: &mut EphemeronRememberedSet::TableList,
-        ) {
-            todo!()
-        }
-        fn clear_old_ephemerons(&mut self) {
-            todo!()
-        }
-        fn handle_surviving_new_large_objects(&mut self) {
-            todo!()
-        }
+    pub fn collect_garbage(&self) -> ScavengeResult {
+        let heap = self.heap;
+        let tracer = heap.tracer();
+        let _scavenge_scope = tracer.scope(ScopeId::SCAVENGER_SCAVENGE);
+        let new_space = heap.new_space();
+        let old_space = heap.old_space();
 
-        fn sweep_array_buffer_extensions(&mut self) {
-            todo!()
-        }
+        new_space.flip();
 
-        fn fetch_and_reset_concurrency_estimate(&self) -> usize {
-            let estimate = self
-                .estimate_concurrency_
-                .swap(0, Ordering::Relaxed);
-            if estimate == 0 {
-                1
-            } else {
-                estimate
+        let mut remembered_set_items = Vec::new();
+        for chunk in old_space.pages().into_iter().chain(heap.lo_space().pages()) {
+            if !RememberedSet::<OLD_TO_NEW>::is_empty(chunk) {
+                remembered_set_items.push((chunk, ParallelWorkItem::new()));
             }
         }
-    }
-
-    struct JobTask {
-        collector_: *mut ScavengerCollector,  // Raw pointer
-        scavengers_: *mut Vec<Box<Scavenger>>, // Raw pointer
-        old_to_new_chunks_: *mut Vec<(ParallelWorkItem, *mut MutablePageMetadata)>, // Raw pointer
-        copied_list_: *const Scavenger::CopiedList,
-        pinned_list_: *const Scavenger::PinnedList,
-        promoted_list_: *const Scavenger::PromotedList,
-        remaining_memory_chunks_: AtomicUsize,
-        generator_: IndexGenerator,
-        trace_id_: u64,
-    }
-
-    impl JobTask {
-        fn new(
-            collector: *mut ScavengerCollector,
-            scavengers: *mut Vec<Box<Scavenger>>,
-            old_to_new_chunks: *mut Vec<(ParallelWorkItem, *mut MutablePageMetadata)>,
-            copied_list: *const Scavenger::CopiedList,
-            pinned_list: *const Scavenger::PinnedList,
-            promoted_list: *const Scavenger::PromotedList,
-        ) -> Self {
-            JobTask {
-                collector_: collector,
-                scavengers_: scavengers,
-                old_to_new_chunks_: old_to_new_chunks,
-                copied_list_: copied_list,
-                pinned_list_: pinned_list,
-                promoted_list_: promoted_list,
-                remaining_memory_chunks_: AtomicUsize::new(0),
-                generator_: IndexGenerator::new(),
-                trace_id_: 0, // TODO: Initialize correctly
-            }
+        let num_tasks = self.number_of_scavenge_tasks();
+        let job = ScavengeJob {
+            copied_list: CopiedList::new(),
+            promoted_list: PromotedList::new(),
+            remembered_set_items,
+            num_tasks,
+            idle_tasks: Mutex::new(0),
+            idle_tasks_hint: AtomicUsize::new(0),
+            work_available: Condvar::new(),
+        };
+        let old_ephemeron_entries = heap.ephemeron_remembered_set().take();
+        let marking = MarkingMode {
+            is_marking: heap.incremental_marking().is_marking(),
+            is_compacting: heap.mark_compact_collector().is_compacting(),
+        };
+
+        let mut main_scavenger = Scavenger::new(new_space, old_space, &job.copied_list, &job.promoted_list, marking);
+        {
+            let _scope = tracer.scope(ScopeId::SCAVENGER_SCAVENGE_ROOTS);
+            heap.iterate_roots(|slot| {
+                main_scavenger.scavenge_root(slot);
+            });
+            // Traced handles keep young objects alive even with a `CppHeap`
+            // attached, as young collections do not trace cppgc objects.
+            heap.iterate_traced_handles(|slot| {
+                main_scavenger.scavenge_root(slot);
+            });
+            main_scavenger.publish();
+        }
+
+        let mut finished = Vec::with_capacity(num_tasks);
+        let mut background_time = Duration::ZERO;
+        {
+            let _scope = tracer.scope(ScopeId::SCAVENGER_SCAVENGE_PARALLEL);
+            std::thread::scope(|scope| {
+                let job = &job;
+                let handles: Vec<_> = (1..num_tasks)
+                    .map(|_| {
+                        scope.spawn(move || {
+                            let start = Instant::now();
+                            let mut scavenger =
+                                Scavenger::new(new_space, old_space, &job.copied_list, &job.promoted_list, marking);
+                            scavenger.run(job);
+                            (scavenger.finalize(), start.elapsed())
+                        })
+                    })
+                    .collect();
+                main_scavenger.run(job);
+                finished.push(main_scavenger.finalize());
+                for handle in handles {
+                    let (result, duration) = handle.join().unwrap();
+                    finished.push(result);
+                    background_time += duration;
+                }
+            });
         }
+        tracer.add_scope_sample(ScopeId::SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL, background_time);
+        debug_assert!(job.copied_list.is_empty() && job.promoted_list.is_empty());
 
-        fn run(&mut self, delegate: JobDelegate) {
-            unsafe {
-                let scavengers = &*self.scavengers_;
-                for scavenger in scavengers {
-                    self.process_items(delegate, scavenger.as_ref());
+        let mut result = ScavengeResult::default();
+        {
+            let _scope = tracer.scope(ScopeId::SCAVENGER_SCAVENGE_WEAK);
+            for scavenger in &finished {
+                for weak_slot in &scavenger.weak_slots {
+                    Self::update_weak_slot(weak_slot);
+                }
+                for &table in &scavenger.ephemeron_table_list {
+                    let entries = 0..table.length() / EphemeronHashTableShape::K_ENTRY_SIZE;
+                    self.process_ephemeron_entries(table, entries);
                 }
             }
+            for (table, entries) in old_ephemeron_entries {
+                self.process_ephemeron_entries(table, entries);
+            }
         }
 
-        fn get_max_concurrency(&self, worker_count: usize) -> usize {
-            1 // TODO: Implement correctly
-        }
-
-        fn process_items(&mut self, delegate: JobDelegate, scavenger: &Scavenger) {
-            self.concurrent_scavenge_pages(scavenger);
-            self.visit_pinned_objects(scavenger);
-        }
+        {
+            let _scope = tracer.scope(ScopeId::SCAVENGER_SCAVENGE_FINALIZE);
+            for scavenger in &finished {
+                result.copied_size += scavenger.copied_size;
+                result.promoted_size += scavenger.promoted_size;
+            }
+            new_space.set_age_mark();
+            if marking.is_marking {
+                heap.incremental_marking().update_marking_worklist_after_scavenge(heap);
+            }
+            if heap.options().verify_heap
+                && let Err(error) = EvacuationVerifier::verify_after_scavenge(heap)
+            {
+                panic!("{error}");
+            }
+            // Objects left in from-space without a forwarding address died.
+            heap.notify_object_trackers(
+                GarbageCollector::SCAVENGER,
+                |object| match object.map_word() {
+                    MapWord::Forwarding(target) if object.chunk().is_from_page() => Some(target),
+                    _ => None,
+                },
+                |object| !object.chunk().is_from_page(),
+            );
+            new_space.zap_from_space();
+        }
+        result
+    }
 
-        fn concurrent_scavenge_pages(&mut self, scavenger: &Scavenger) {
-            unsafe {
-                let old_to_new_chunks = &*self.old_to_new_chunks_;
-                for (item, page) in old_to_new_chunks {
-                    scavenger.scavenge_page(*page);
+    /// Updates a weak slot to the new location of its target, or clears it
+    /// if the target died.
+    fn update_weak_slot(weak_slot: &WeakSlot<'_>) {
+        let slot = weak_slot.slot;
+        let Some(object) = slot.load().get_heap_object() else {
+            return;
+        };
+        if !object.chunk().is_from_page() {
+            return;
+        }
+        match object.map_word() {
+            MapWord::Forwarding(target) => {
+                slot.store(Tagged::weak(target));
+                if let Some(chunk) = weak_slot.host_chunk
+                    && HeapLayout::in_young_generation(target)
+                {
+                    RememberedSet::<OLD_TO_NEW>::insert(chunk, slot);
                 }
             }
+            MapWord::Map(_) => slot.store(Tagged::CLEARED),
         }
-
-        fn visit_pinned_objects(&mut self, scavenger: &Scavenger) {
-            scavenger.visit_pinned_objects();
-        }
-
-        fn trace_id(&self) -> u64 {
-            self.trace_id_
-        }
-    }
-
-    /// The scavenger class.
-    pub struct Scavenger {
-        collector_: *mut ScavengerCollector, // Raw pointer because of potential cycles/lifetime issues
-        heap_: *mut Heap,            // Raw pointer
-        local_empty_chunks_: EmptyChunksList::Local,
-        local_copied_list_: CopiedList::Local,
-        local_pinned_list_: PinnedList::Local,
-        local_promoted_list_: PromotedList::Local,
-        local_ephemeron_table_list_: EphemeronRememberedSet::TableList::Local,
-        // local_pretenuring_feedback_: PretenuringHandler::PretenuringFeedbackMap, // TODO: Implement PretenuringHandler
-        local_ephemeron_remembered_set_: EphemeronRememberedSet::TableMap,
-        local_surviving_new_large_objects_: SurvivingNewLargeObjectsMap,
-        copied_size_: usize,
-        promoted_size_: usize,
-        // allocator_: EvacuationAllocator, // TODO: Implement EvacuationAllocator
-        is_logging_: bool,
-        is_incremental_marking_: bool,
-        is_compacting_: bool,
-        shared_string_table_: bool,
-        mark_shared_heap_: bool,
-        shortcut_strings_: bool,
     }
 
-    impl Scavenger {
-        pub const K_COPIED_LIST_SEGMENT_SIZE: usize = 256;
-        pub const K_PINNED_LIST_SEGMENT_SIZE: usize = 64;
-        pub const K_PROMOTED_LIST_SEGMENT_SIZE: usize = 256;
-        const K_INTERRUPT_THRESHOLD: i32 = 128;
-
-        pub type CopiedList = Worklist<Tagged<HeapObject>, { Self::K_COPIED_LIST_SEGMENT_SIZE }>;
-
-        pub type ObjectAndMap = (Tagged<HeapObject>, Tagged<Map>);
-        pub type PinnedList = Worklist<ObjectAndMap, { Self::K_PINNED_LIST_SEGMENT_SIZE }>;
-
-        #[derive(Clone, Copy)]
-        pub struct PromotedListEntry {
-            pub heap_object: Tagged<HeapObject>,
-            pub map: Tagged<Map>,
-            pub size: i32,
-        }
-
-        pub type PromotedList = Worklist<PromotedListEntry, { Self::K_PROMOTED_LIST_SEGMENT_SIZE }>;
-
-        pub type EmptyChunksList = Worklist<*mut MutablePageMetadata, 64>;
-
-        pub fn new(
-            collector: *mut ScavengerCollector,
-            heap: *mut Heap,
-            is_logging: bool,
-            empty_chunks: &mut EmptyChunksList,
-            copied_list: &mut CopiedList,
-            pinned_list: &mut PinnedList,
-            promoted_list: &mut PromotedList,
-            ephemeron_table_list: &mut EphemeronRememberedSet::TableList,
-        ) -> Self {
-            Scavenger {
-                collector_: collector,
-                heap_: heap,
-                local_empty_chunks_: empty_chunks.create_local(),
-                local_copied_list_: copied_list.create_local(),
-                local_pinned_list_: pinned_list.create_local(),
-                local_promoted_list_: promoted_list.create_local(),
-                local_ephemeron_table_list_: ephemeron_table_list.create_local(),
-                // local_pretenuring_feedback_: PretenuringHandler::PretenuringFeedbackMap::new(), // TODO: Implement PretenuringHandler
-                local_ephemeron_remembered_set_: EphemeronRememberedSet::TableMap::new(),
-                local_surviving_new_large_objects_: HashMap::new(),
-                copied_size_: 0,
-                promoted_size_: 0,
-                // allocator_: EvacuationAllocator::new(), // TODO: Implement EvacuationAllocator
-                is_logging_: is_logging,
-                is_incremental_marking_: false, // TODO: Initialize correctly
-                is_compacting_: false,        // TODO: Initialize correctly
-                shared_string_table_: false,    // TODO: Initialize correctly
-                mark_shared_heap_: false,       // TODO: Initialize correctly
-                shortcut_strings_: false,       // TODO: Initialize correctly
+    /// Updates the young keys of the given entries of an ephemeron table
+    /// and removes entries whose key died. Entries of old tables whose key
+    /// stays young are recorded in the ephemeron remembered set again.
+    fn process_ephemeron_entries(&self, table: HeapObject, entries: impl IntoIterator<Item = usize>) {
+        let table_is_old = !HeapLayout::in_young_generation(table);
+        let mut young_entries = IndicesSet::new();
+        for entry in entries {
+            let key_slot = table.raw_field(EphemeronHashTableShape::key_index(entry));
+            let Some(key) = key_slot.load().get_heap_object() else {
+                continue;
+            };
+            let key = if key.chunk().is_from_page() {
+                match key.map_word() {
+                    MapWord::Forwarding(target) => {
+                        key_slot.store(Tagged::strong(target));
+                        target
+                    }
+                    MapWord::Map(_) => {
+                        key_slot.store(Tagged::CLEARED);
+                        table
+                            .raw_field(EphemeronHashTableShape::value_index(entry))
+                            .store(Tagged::CLEARED);
+                        continue;
+                    }
+                }
+            } else {
+                key
+            };
+            if table_is_old && HeapLayout::in_young_generation(key) {
+                young_entries.insert(entry);
             }
         }
-
-        pub fn scavenge_page(&self, page: *mut MutablePageMetadata) {
-            todo!()
-        }
-
-        pub fn process(&mut self, delegate: JobDelegate) {
-            todo!()
-        }
-
-        pub fn finalize(&mut self) {
-            todo!()
-        }
-        pub fn publish(&mut self) {
-            todo!()
-        }
-
-        pub fn add_ephemeron_hash_table(&mut self, table: Tagged<EphemeronHashTable>) {
-            todo!()
-        }
-
-        pub fn promote_if_large_object(&self, object: Tagged<HeapObject>) -> bool {
-            todo!()
-        }
-
-        pub fn push_pinned_object(&mut self, object: Tagged<HeapObject>, map: Tagged<Map>) {
-            self.local_pinned_list_.push((object, map));
-        }
-
-        pub fn visit_pinned_objects(&self) {
-            todo!()
-        }
-
-        pub fn bytes_copied(&self) -> usize {
-            self.copied_size_
-        }
-        pub fn bytes_promoted(&self) -> usize {
-            self.promoted_size_
-        }
-
-        fn heap(&self) -> *mut Heap {
-            self.heap_
-        }
-
-        fn synchronize_page_access(&self, object: Tagged<MaybeObject>) {
-            todo!()
-        }
-
-        fn add_page_to_sweeper_if_necessary(&mut self, page: *mut MutablePageMetadata) {
-            todo!()
-        }
-
-        fn check_and_scavenge_object<TSlot>(&mut self, heap: *mut Heap, slot: TSlot) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn check_old_to_new_slot_for_shared_untyped<TSlot>(
-            &mut self,
-            chunk: MemoryChunk,
-            page: *mut MutablePageMetadata,
-            slot: TSlot,
-        ) {
-            todo!()
-        }
-        fn check_old_to_new_slot_for_shared_typed(
-            &mut self,
-            chunk: MemoryChunk,
-            page: *mut MutablePageMetadata,
-            slot_type: SlotType,
-            slot_address: Address,
-            new_target: Tagged<MaybeObject>,
-        ) {
-            todo!()
-        }
-
-        fn scavenge_object<THeapObjectSlot>(
-            &mut self,
-            p: THeapObjectSlot,
-            object: Tagged<HeapObject>,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn migrate_object(
-            &mut self,
-            map: Tagged<Map>,
-            source: Tagged<HeapObject>,
-            target: Tagged<HeapObject>,
-            size: i32,
-            promotion_heap_choice: PromotionHeapChoice,
-        ) -> bool {
-            todo!()
-        }
-
-        fn remembered_set_entry_needed(
-            &mut self,
-            result: CopyAndForwardResult,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn semi_space_copy_object<THeapObjectSlot>(
-            &mut self,
-            map: Tagged<Map>,
-            slot: THeapObjectSlot,
-            object: Tagged<HeapObject>,
-            object_size: i32,
-            object_fields: ObjectFields,
-        ) -> CopyAndForwardResult {
-            todo!()
+        if table_is_old {
+            self.heap
+                .ephemeron_remembered_set()
+                .record_ephemeron_key_writes(table, young_entries);
         }
-
-        fn promote_object<THeapObjectSlot>(
-            &mut self,
-            map: Tagged<Map>,
-            slot: THeapObjectSlot,
-            object: Tagged<HeapObject>,
-            object_size: i32,
-            object_fields: ObjectFields,
-        ) -> CopyAndForwardResult {
-            todo!()
-        }
-
-        fn evacuate_object<THeapObjectSlot>(
-            &mut self,
-            slot: THeapObjectSlot,
-            map: Tagged<Map>,
-            source: Tagged<HeapObject>,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn handle_large_object(
-            &mut self,
-            map: Tagged<Map>,
-            object: Tagged<HeapObject>,
-            object_size: i32,
-            object_fields: ObjectFields,
-        ) -> bool {
-            todo!()
-        }
-
-        fn evacuate_object_default<THeapObjectSlot>(
-            &mut self,
-            map: Tagged<Map>,
-            slot: THeapObjectSlot,
-            object: Tagged<HeapObject>,
-            object_size: i32,
-            object_fields: ObjectFields,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn evacuate_thin_string<THeapObjectSlot>(
-            &mut self,
-            map: Tagged<Map>,
-            slot: THeapObjectSlot,
-            object: Tagged<ThinString>,
-            object_size: i32,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn evacuate_shortcut_candidate<THeapObjectSlot>(
-            &mut self,
-            map: Tagged<Map>,
-            slot: THeapObjectSlot,
-            object: Tagged<ConsString>,
-            object_size: i32,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn evacuate_in_place_internalizable_string<THeapObjectSlot>(
-            &mut self,
-            map: Tagged<Map>,
-            slot: THeapObjectSlot,
-            string: Tagged<String>,
-            object_size: i32,
-            object_fields: ObjectFields,
-        ) -> SlotCallbackResult {
-            todo!()
-        }
-
-        fn iterate_and_scavenge_promoted_object(
-            &mut self,
-            target: Tagged<HeapObject>,
-            map: Tagged<Map>,
-            size: i32,
-        ) {
-            todo!()
-        }
-        fn remember_promoted_ephemeron(&mut self, table: Tagged<EphemeronHashTable>, index: i32) {
-            todo!()
-        }
-
-        fn should_eagerly_process_promoted_list(&self) -> bool {
-            todo!()
-        }
-    }
-
-    enum PromotionHeapChoice {
-        kPromoteIntoLocalHeap,
-        kPromoteIntoSharedHeap,
     }
-
-    // Helper enum for callbacks regarding Slots
-    pub enum SlotCallbackResult {
-        Continue,
-        // TODO: Add other variants if needed
-    }
-
-    /// Helper class for turning the scavenger into an object visitor that is also
-    /// filtering out non-HeapObjects and objects which do not reside in new space.
-    pub struct RootScavengeVisitor<'a> {
-        scavenger_: &'a mut Scavenger,
-    }
-
-    impl<'a> RootScavengeVisitor<'a> {
-        pub fn new(scavenger: &'a mut Scavenger) -> Self {
-            RootScavengeVisitor { scavenger_: scavenger }
-        }
-
-        fn scavenge_pointer(&mut self, p: FullObjectSlot) {
-            todo!()
+}
+
+/// State of full marking that a scavenge has to preserve.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct MarkingMode {
+    /// Mark bits are transferred to the copies of marked objects.
+    pub(crate) is_marking: bool,
+    /// Slots of promoted objects to evacuation candidates are recorded.
+    pub(crate) is_compacting: bool,
+}
+
+/// Per-task state of a scavenge.
+pub(crate) struct Scavenger<'a> {
+    pub(crate) new_space: &'a SemiSpaceNewSpace,
+    pub(crate) marking: MarkingMode,
+    pub(crate) allocator: EvacuationAllocator<'a>,
+    pub(crate) copied_list: Local<'a, HeapObject, K_COPIED_LIST_SEGMENT_SIZE>,
+    pub(crate) promoted_list: Local<'a, HeapObject, K_PROMOTED_LIST_SEGMENT_SIZE>,
+    pub(crate) weak_slots: Vec<WeakSlot<'a>>,
+    /// Evacuated ephemeron tables, whose keys are processed after
+    /// evacuation.
+    pub(crate) ephemeron_table_list: Vec<HeapObject>,
+    pub(crate) copied_size: usize,
+    pub(crate) promoted_size: usize,
+}
+
+/// Results of a task that are processed on the main thread.
+struct FinishedScavenger<'a> {
+    weak_slots: Vec<WeakSlot<'a>>,
+    ephemeron_table_list: Vec<HeapObject>,
+    copied_size: usize,
+    promoted_size: usize,
+}
+
+impl<'a> Scavenger<'a> {
+    /// Number of objects processed between checks whether idle tasks are
+    /// waiting for work.
+    const K_INTERRUPT_BUDGET: usize = 64;
+
+    fn new(
+        new_space: &'a SemiSpaceNewSpace,
+        old_space: &'a PagedSpace,
+        copied_list: &'a CopiedList,
+        promoted_list: &'a PromotedList,
+        marking: MarkingMode,
+    ) -> Self {
+        Scavenger {
+            new_space,
+            marking,
+            allocator: EvacuationAllocator::new(new_space, old_space),
+            copied_list: Local::new(copied_list),
+            promoted_list: Local::new(promoted_list),
+            weak_slots: Vec::new(),
+            ephemeron_table_list: Vec::new(),
+            copied_size: 0,
+            promoted_size: 0,
         }
     }
 
-    // The RootVisitor trait doesn't exist in this codebase, this is just a placeholder
-    pub trait RootVisitor {
-        fn visit_root_pointer(&mut self, root: Root, description: &str, p: FullObjectSlot);
-        fn visit_root_pointers(&mut self, root: Root, description: &str, start: FullObjectSlot, end: FullObjectSlot);
-    }
-
-    impl<'a> RootVisitor for RootScavengeVisitor<'a> {
-        fn visit_root_pointer(&mut self, root: Root, description: &str, p: FullObjectSlot) {
-            self.scavenge_pointer(p);
+    fn run(&mut self, job: &ScavengeJob<'a>) {
+        for (chunk, item) in &job.remembered_set_items {
+            if item.try_acquire() {
+                self.scavenge_page(chunk);
+                self.process(job);
+            }
         }
-
-        fn visit_root_pointers(&mut self, root: Root, description: &str, start: FullObjectSlot, end: FullObjectSlot) {
-            // Iterate from start to end and call scavenge_pointer for each slot
-            let mut current = start;
-            unsafe {
-                while current <= end {
-                    self.scavenge_pointer(current);
-                    current = current.add(1); // Assuming FullObjectSlot is a pointer type
-                }
+        loop {
+            self.process(job);
+            if !job.wait_for_work() {
+                break;
             }
         }
     }
 
-    impl<'a> Drop for RootScavengeVisitor<'a> {
-        fn drop(&mut self) {}
+    /// Processes the OLD_TO_NEW slots of an old page.
+    fn scavenge_page(&mut self, chunk: &'a MemoryChunk) {
+        RememberedSet::<OLD_TO_NEW>::iterate(chunk, |slot| self.check_and_scavenge_object(slot, Some(chunk)));
     }
 
-    // Dummy enum
-    pub enum Root {}
-
-    // Dummy struct
-    pub struct Heap {}
-} // mod scavenger
-
-pub mod heap {
-    pub mod base {
-        pub mod worklist {
-            use std::collections::VecDeque;
-
-            pub struct Worklist<T, const SEGMENT_SIZE: usize> {
-                segments: Vec<Vec<T>>,
-            }
-
-            impl<T, const SEGMENT_SIZE: usize> Worklist<T, const SEGMENT_SIZE> {
-                pub fn new() -> Self {
-                    Worklist {
-                        segments: Vec::new(),
-                    }
-                }
-
-                pub fn push(&mut self, value: T) {
-                    if self.segments.is_empty() || self.segments.last().unwrap().len() == SEGMENT_SIZE {
-                        self.segments.push(Vec::with_capacity(SEGMENT_SIZE));
-                    }
-                    self.segments.last_mut().unwrap().push(value);
-                }
-
-                pub fn pop(&mut self) -> Option<T> {
-                    if self.segments.is_empty() {
-                        return None;
-                    }
-
-                    let last_index = self.segments.len() - 1;
-                    let mut last_segment = self.segments.get_mut(last_index).unwrap();
-
-                    if last_segment.is_empty() {
-                        self.segments.pop();  // Remove empty segment
-                        if self.segments.is_empty() {
-                            return None;  // All segments are empty
-                        }
-                        let last_index = self.segments.len() - 1;
-                        last_segment = self.segments.get_mut(last_index).unwrap();
-                    }
-
-                    last_segment.pop()
-                }
-
-                pub fn is_empty(&self) -> bool {
-                    self.segments.is_empty() || self.segments.last().map_or(true, |s| s.is_empty())
-                }
-
-                pub fn create_local(&self) -> Local<T, SEGMENT_SIZE> {
-                    Local {
-                        worklist: self as *const Worklist<T, SEGMENT_SIZE> as *mut Worklist<T, SEGMENT_SIZE>, //UNSAFE: We are using this only in the same thread
-                        queue: VecDeque::new(),
-                    }
-                }
-            }
-
-            pub struct Local<T, const SEGMENT_SIZE: usize> {
-                worklist: *mut Worklist<T, SEGMENT_SIZE>, //UNSAFE: We are using this only in the same thread
-                queue: VecDeque<T>,
+    /// Scans evacuated objects until the local and global worklists are
+    /// empty.
+    fn process(&mut self, job: &ScavengeJob<'a>) {
+        let mut processed = 0;
+        loop {
+            if let Some(object) = self.copied_list.pop() {
+                self.iterate_and_scavenge_promoted_object(object, false);
+            } else if let Some(object) = self.promoted_list.pop() {
+                self.iterate_and_scavenge_promoted_object(object, true);
+            } else {
+                break;
             }
-
-            impl<T, const SEGMENT_SIZE: usize> Local<T, SEGMENT_SIZE> {
-                pub fn push(&mut self, value: T) {
-                    self.queue.push_back(value);
-                }
+            processed += 1;
+            if processed % Self::K_INTERRUPT_BUDGET == 0 && job.has_idle_tasks() && !job.has_global_work() {
+                self.publish();
             }
-
-            unsafe impl<T, const SEGMENT_SIZE: usize> Send for Local<T, SEGMENT_SIZE> {}
-            unsafe impl<T, const SEGMENT_SIZE: usize> Sync for Local<T, SEGMENT_SIZE> {}
         }
     }
 
-    pub mod ephemeron_remembered_set {
-        use std::collections::HashMap;
+    fn publish(&mut self) {
+        self.copied_list.publish();
+        self.promoted_list.publish();
+    }
 
-        pub struct EphemeronRememberedSet {
-            // Implementation details would go here
+    fn finalize(mut self) -> FinishedScavenger<'a> {
+        self.allocator.finalize();
+        FinishedScavenger {
+            weak_slots: std::mem::take(&mut self.weak_slots),
+            ephemeron_table_list: std::mem::take(&mut self.ephemeron_table_list),
+            copied_size: self.copied_size,
+            promoted_size: self.promoted_size,
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap::heap::{AllocationType, HeapOptions};
+    use crate::heap::heap_test_utils::{heap_options, scavenge};
+
+    fn new_heap(max_scavenger_tasks: usize) -> Heap {
+        Heap::new(HeapOptions {
+            max_semi_space_size: 4 * MB,
+            max_scavenger_tasks,
+            ..heap_options(max_scavenger_tasks > 1)
+        })
+    }
 
-        impl EphemeronRememberedSet {
-            pub struct TableList {
-                // Implementation details for TableList
-            }
+    #[test]
+    fn survivors_are_copied_and_garbage_is_reclaimed() {
+        let heap = new_heap(1);
+        let array = heap.allocate_fixed_array(2, AllocationType::kYoung);
+        let bytes = heap.allocate_byte_array(16, AllocationType::kYoung);
+        bytes.data().copy_from_slice(&[7; 16]);
+        heap.set(array, 0, Tagged::strong(bytes));
+        heap.set(array, 1, Tagged::from_smi(42));
+        let root = heap.create_root(Tagged::strong(array));
+        for _ in 0..100 {
+            heap.allocate_fixed_array(10, AllocationType::kYoung);
+        }
+        let old_address = array.address();
+
+        scavenge(&heap);
+
+        let array = heap.root_object(root);
+        assert_ne!(array.address(), old_address);
+        assert!(HeapLayout::in_young_generation(array));
+        assert_eq!(array.get(1), Tagged::from_smi(42));
+        let bytes = array.get(0).get_heap_object().unwrap();
+        assert_eq!(bytes.data(), &[7; 16]);
+        assert_eq!(heap.promoted_objects_size(), 0);
+        assert_eq!(heap.semi_space_copied_object_size(), array.size() + bytes.size());
+        assert_eq!(heap.new_space().size(), array.size() + bytes.size());
+    }
 
-            impl TableList {
-                pub fn create_local(&self) -> Local {
-                    Local {}
-                }
-            }
+    #[test]
+    fn objects_are_promoted_by_the_second_scavenge() {
+        let heap = new_heap(1);
+        let root = heap.create_root(Tagged::strong(heap.allocate_fixed_array(1, AllocationType::kYoung)));
+        scavenge(&heap);
+        assert!(HeapLayout::in_young_generation(heap.root_object(root)));
+        let fresh = heap.create_root(Tagged::strong(heap.allocate_fixed_array(1, AllocationType::kYoung)));
 
-            pub struct TableMap {
-                // Implementation details for TableMap
-            }
+        scavenge(&heap);
 
-            impl TableMap {
-                pub fn new() -> Self {
-                    TableMap {}
-                }
-            }
+        assert!(!HeapLayout::in_young_generation(heap.root_object(root)));
+        assert!(HeapLayout::in_young_generation(heap.root_object(fresh)));
+        assert_eq!(heap.promoted_objects_size(), heap.root_object(root).size());
+    }
 
-            pub struct Local {}
-        }
+    #[test]
+    fn old_to_new_slots_keep_young_objects_alive() {
+        let heap = new_heap(1);
+        let old = heap.allocate_fixed_array(1, AllocationType::kOld);
+        let root = heap.create_root(Tagged::strong(old));
+        let young = heap.allocate_fixed_array(1, AllocationType::kYoung);
+        heap.set(young, 0, Tagged::from_smi(7));
+        heap.set(old, 0, Tagged::strong(young));
+        assert!(RememberedSet::<OLD_TO_NEW>::contains(old.chunk(), old.raw_field(0)));
+
+        scavenge(&heap);
+
+        let young = heap.root_object(root).get(0).get_heap_object().unwrap();
+        assert!(HeapLayout::in_young_generation(young));
+        assert_eq!(young.get(0), Tagged::from_smi(7));
+        assert!(RememberedSet::<OLD_TO_NEW>::contains(old.chunk(), old.raw_field(0)));
+
+        scavenge(&heap);
+
+        let promoted = old.get(0).get_heap_object().unwrap();
+        assert!(!HeapLayout::in_young_generation(promoted));
+        assert_eq!(promoted.get(0), Tagged::from_smi(7));
+        assert!(!RememberedSet::<OLD_TO_NEW>::contains(old.chunk(), old.raw_field(0)));
     }
 
-    pub mod evacuation_allocator {
-        // Implementation of EvacuationAllocator
+    #[test]
+    fn weak_references_are_updated_or_cleared() {
+        let heap = new_heap(1);
+        let holder = heap.allocate_weak_fixed_array(2, AllocationType::kOld);
+        let root = heap.create_root(Tagged::strong(holder));
+        let alive = heap.allocate_fixed_array(1, AllocationType::kYoung);
+        let alive_root = heap.create_root(Tagged::strong(alive));
+        let dead = heap.allocate_fixed_array(1, AllocationType::kYoung);
+        heap.set(holder, 0, Tagged::weak(alive));
+        heap.set(holder, 1, Tagged::weak(dead));
+
+        scavenge(&heap);
+
+        let holder = heap.root_object(root);
+        assert_eq!(holder.get(0), Tagged::weak(heap.root_object(alive_root)));
+        assert!(holder.get(1).is_cleared());
+        assert!(RememberedSet::<OLD_TO_NEW>::contains(holder.chunk(), holder.raw_field(0)));
     }
 
-    pub mod heap_visitor {
-        // Implementation of HeapVisitor
-        pub struct HeapVisitor {}
+    #[test]
+    fn ephemeron_entries_with_dead_keys_are_removed() {
+        let heap = new_heap(1);
+        let young_table = heap.allocate_ephemeron_hash_table(2, AllocationType::kYoung);
+        let young_table_root = heap.create_root(Tagged::strong(young_table));
+        let old_table = heap.allocate_ephemeron_hash_table(2, AllocationType::kOld);
+        let key = heap.allocate_fixed_array(0, AllocationType::kYoung);
+        let key_root = heap.create_root(Tagged::strong(key));
+        let dead_key = heap.allocate_fixed_array(0, AllocationType::kYoung);
+        for table in [young_table, old_table] {
+            heap.set(table, EphemeronHashTableShape::key_index(0), Tagged::strong(key));
+            heap.set(table, EphemeronHashTableShape::value_index(0), Tagged::from_smi(1));
+            heap.set(table, EphemeronHashTableShape::key_index(1), Tagged::strong(dead_key));
+            heap.set(table, EphemeronHashTableShape::value_index(1), Tagged::from_smi(2));
+        }
+        assert!(heap.ephemeron_remembered_set().contains(old_table, 1));
+
+        scavenge(&heap);
+
+        let key = Tagged::strong(heap.root_object(key_root));
+        for table in [heap.root_object(young_table_root), old_table] {
+            assert_eq!(table.get(EphemeronHashTableShape::key_index(0)), key);
+            assert_eq!(table.get(EphemeronHashTableShape::value_index(0)), Tagged::from_smi(1));
+            assert!(table.get(EphemeronHashTableShape::key_index(1)).is_cleared());
+            assert!(table.get(EphemeronHashTableShape::value_index(1)).is_cleared());
+        }
+        assert!(heap.ephemeron_remembered_set().contains(old_table, 0));
+        assert!(!heap.ephemeron_remembered_set().contains(old_table, 1));
     }
 
-    pub mod index_generator {
-        pub struct IndexGenerator {
-            next_index: usize,
+    #[test]
+    fn parallel_scavenge_preserves_object_graph() {
+        let heap = new_heap(4);
+        assert_eq!(ScavengerCollector::new(&heap).number_of_scavenge_tasks(), 4);
+        const K_LISTS: usize = 64;
+        const K_LENGTH: usize = 200;
+        let lists = heap.allocate_fixed_array(K_LISTS, AllocationType::kOld);
+        let root = heap.create_root(Tagged::strong(lists));
+        for list in 0..K_LISTS {
+            for i in 0..K_LENGTH {
+                let node = heap.allocate_fixed_array(2, AllocationType::kYoung);
+                node.raw_field(0).store(Tagged::from_smi((list * K_LENGTH + i) as isize));
+                heap.set(node, 1, lists.get(list));
+                heap.set(lists, list, Tagged::strong(node));
+            }
         }
 
-        impl IndexGenerator {
-            pub fn new() -> Self {
-                IndexGenerator { next_index: 0 }
-            }
+        scavenge(&heap);
+        scavenge(&heap);
 
-            pub fn generate_index(&mut self) -> usize {
-                let index = self.next_index;
-                self.next_index += 1;
-                index
+        let lists = heap.root_object(root);
+        for list in 0..K_LISTS {
+            let mut node = lists.get(list);
+            for i in (0..K_LENGTH).rev() {
+                let object = node.get_heap_object().unwrap();
+                assert!(!HeapLayout::in_young_generation(object));
+                assert_eq!(object.get(0), Tagged::from_smi((list * K_LENGTH + i) as isize));
+                node = object.get(1);
             }
+            assert_eq!(node, Tagged::ZERO);
         }
+        let event = heap.tracer().last_event();
+        assert_eq!(event.promoted_object_size, K_LISTS * K_LENGTH * 4 * 8);
+        assert!(event.scope(ScopeId::SCAVENGER_SCAVENGE_PARALLEL) > Duration::ZERO);
+        assert_eq!(heap.tracer().recorded_events().len(), 2);
     }
 
-    pub mod mutable_page_metadata {
-        // Implementation of MutablePageMetadata
-        pub struct MutablePageMetadata {}
-    }
-
-    pub mod parallel_work_item {
-        // Implementation of ParallelWorkItem
-        pub struct ParallelWorkItem {}
-    }
-
-    pub mod pretenuring_handler {
-        // Implementation of PretenuringHandler
-    }
-
-    pub mod slot_set {
-        // Implementation of SlotSet
+    #[test]
+    fn allocation_failure_triggers_scavenges() {
+        let heap = new_heap(2);
+        let survivor = heap.create_root(Tagged::strong(heap.allocate_fixed_array(3, AllocationType::kYoung)));
+        heap.set(heap.root_object(survivor), 2, Tagged::from_smi(5));
+        for _ in 0..100_000 {
+            heap.allocate_fixed_array(16, AllocationType::kYoung);
+        }
+        assert!(heap.gc_count() > 0);
+        assert!(heap.new_space().size() <= heap.new_space().capacity());
+        assert_eq!(heap.old_space().size_of_objects(), heap.root_object(survivor).size());
+        assert_eq!(heap.root_object(survivor).get(2), Tagged::from_smi(5));
     }
-}
\ No newline at end of file
+}
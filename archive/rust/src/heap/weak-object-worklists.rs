@@ -2,654 +2,101 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/heap/weak-object-worklists.h (Rust module declaration)
-mod weak_object_worklists {
-    use std::ptr::NonNull;
-
-    // Re-export the necessary type for HeapObject
-    pub type HeapObject = usize;
-    pub type Code = usize;
-    pub type TransitionArray = usize;
-    pub type EphemeronHashTable = usize;
-    pub type JSWeakRef = usize;
-    pub type WeakCell = usize;
-    pub type SharedFunctionInfo = usize;
-    pub type JSFunction = usize;
-
-    pub struct Ephemeron {
-        pub key: HeapObject,
-        pub value: HeapObject,
-    }
-
-    pub struct HeapObjectAndSlot {
-        pub heap_object: HeapObject,
-        pub slot: Address, // Assuming Address is a pointer type
-    }
-
-    pub struct TrustedObjectAndSlot {
-        pub heap_object: HeapObject,
-        pub slot: Address, // Assuming Address is a pointer type
-    }
-
-    pub struct HeapObjectAndCode {
-        pub heap_object: HeapObject,
-        pub code: Code,
-    }
-
-    #[derive(Debug, Clone)]
-    pub struct Address(*mut u8);
-
-    impl Address {
-        pub fn from_ptr<T>(ptr: *mut T) -> Self {
-            Address(ptr as *mut u8)
-        }
-
-        pub fn address(&self) -> usize {
-            self.0 as usize
-        }
-    }
-
-    // Generic WeakObjectWorklist
-    pub struct WeakObjectWorklist<T> {
-        // Placeholder for the actual data structure.
-        // In C++, this might be a list or vector.
-        // Replace `Vec<T>` with the appropriate collection type.
-        data: Vec<T>,
-    }
-
-    impl<T> WeakObjectWorklist<T> {
-        pub fn new() -> Self {
-            WeakObjectWorklist { data: Vec::new() }
-        }
-
-        pub fn push(&mut self, value: T) {
-            self.data.push(value);
-        }
-
-        pub fn clear(&mut self) {
-            self.data.clear();
-        }
-
-        #[cfg(debug_assertions)]
-        pub fn iterate<F>(&self, mut f: F)
-        where
-            F: FnMut(&T),
-        {
-            for item in &self.data {
-                f(item);
-            }
-        }
-
-        pub fn update<F>(&mut self, mut updater: F)
-        where
-            F: FnMut(T, *mut T) -> bool,
-            T: Copy,
-        {
-            let mut i = 0;
-            while i < self.data.len() {
-                let mut slot_out = self.data[i];
-                if updater(self.data[i], &mut slot_out) {
-                    self.data[i] = slot_out;
-                    i += 1;
-                } else {
-                    self.data.remove(i);
-                }
-            }
-        }
-    }
-
-    pub struct WeakObjects {
-    }
-
-    impl WeakObjects {
-        pub fn new() -> Self {
-            WeakObjects {}
-        }
-
-        pub fn update_after_scavenge(&mut self, local: &mut Local) {
-            local.update_transition_arrays(&mut self.transition_arrays);
-            local.update_ephemeron_hash_tables(&mut self.ephemeron_hash_tables);
-            local.update_current_ephemerons(&mut self.current_ephemerons);
-            local.update_next_ephemerons(&mut self.next_ephemerons);
-            local.update_weak_references_trivial(&mut self.weak_references_trivial);
-            local.update_weak_references_trusted(&mut self.weak_references_trusted);
-            local.update_weak_references_non_trivial(&mut self.weak_references_non_trivial);
-            local.update_weak_references_non_trivial_unmarked(&mut self.weak_references_non_trivial_unmarked);
-            local.update_weak_objects_in_code(&mut self.weak_objects_in_code);
-            local.update_js_weak_refs(&mut self.js_weak_refs);
-            local.update_weak_cells(&mut self.weak_cells);
-            local.update_code_flushing_candidates(&mut self.code_flushing_candidates);
-            local.update_flushed_js_functions(&mut self.flushed_js_functions);
-
-            #[cfg(not(feature = "v8_enable_leaptiering"))]
-            local.update_baseline_flushing_candidates(&mut self.baseline_flush_candidates);
-
-        }
-        
-        pub fn clear(&mut self) {
-            self.transition_arrays.clear();
-            self.ephemeron_hash_tables.clear();
-            self.current_ephemerons.clear();
-            self.next_ephemerons.clear();
-            self.weak_references_trivial.clear();
-            self.weak_references_trusted.clear();
-            self.weak_references_non_trivial.clear();
-            self.weak_references_non_trivial_unmarked.clear();
-            self.weak_objects_in_code.clear();
-            self.js_weak_refs.clear();
-            self.weak_cells.clear();
-            self.code_flushing_candidates.clear();
-            self.flushed_js_functions.clear();
-            #[cfg(not(feature = "v8_enable_leaptiering"))]
-            self.baseline_flush_candidates.clear();
-        }
-
-        pub fn update_transition_arrays(transition_arrays: &mut WeakObjectWorklist<TransitionArray>) {
-            // TODO: Add ContainsYoungObjects function
-           // assert!(!Self::contains_young_objects(transition_arrays));
-        }
-    
-        pub fn update_ephemeron_hash_tables(ephemeron_hash_tables: &mut WeakObjectWorklist<EphemeronHashTable>) {
-            ephemeron_hash_tables.update(
-                |slot_in: EphemeronHashTable, slot_out: *mut EphemeronHashTable| -> bool {
-                   // let forwarded = ForwardingAddress(slot_in);
-                   // TODO: Implement ForwardingAddress
-                    let forwarded = None;
-    
-                    if forwarded.is_none() {
-                        return false;
-                    }
-                    unsafe {
-                        *slot_out = slot_in; // forward.unwrap();
-                    }
-                    true
-                });
-        }
-
-        pub fn update_current_ephemerons(current_ephemerons: &mut WeakObjectWorklist<Ephemeron>) {
-            current_ephemerons.update(ephemeron_updater);
-        }
-
-        pub fn update_next_ephemerons(next_ephemerons: &mut WeakObjectWorklist<Ephemeron>) {
-            next_ephemerons.update(ephemeron_updater);
-        }
-        
-        pub fn update_weak_references_trivial(weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-
-        pub fn update_weak_references_trusted(weak_references: &mut WeakObjectWorklist<TrustedObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-
-        pub fn update_weak_references_non_trivial(weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-        
-        pub fn update_weak_references_non_trivial_unmarked(weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-
-        pub fn update_weak_objects_in_code(weak_objects_in_code: &mut WeakObjectWorklist<HeapObjectAndCode>) {
-            weak_objects_in_code.update(
-                |slot_in: HeapObjectAndCode, slot_out: *mut HeapObjectAndCode| -> bool {
-                   // let forwarded = ForwardingAddress(slot_in.heap_object);
-                   let forwarded = None; // TODO: Implement ForwardingAddress
-
-                    if forwarded.is_none() {
-                        return false;
-                    }
-
-                    unsafe {
-                        (*slot_out).heap_object = slot_in.heap_object; // forwarded.unwrap();
-                        (*slot_out).code = slot_in.code;
-                    }
-
-                    true
-                });
-        }
-
-        pub fn update_js_weak_refs(js_weak_refs: &mut WeakObjectWorklist<JSWeakRef>) {
-            js_weak_refs.update(
-                |js_weak_ref_in: JSWeakRef, js_weak_ref_out: *mut JSWeakRef| -> bool {
-                    //let forwarded = ForwardingAddress(js_weak_ref_in);
-                    let forwarded = None; // TODO: Implement ForwardingAddress
-    
-                    if forwarded.is_none() {
-                        return false;
-                    }
-                    unsafe {
-                         *js_weak_ref_out = js_weak_ref_in; // forwarded.unwrap();
-                    }
-                   
-                    true
-                });
-        }
-
-        pub fn update_weak_cells(weak_cells: &mut WeakObjectWorklist<WeakCell>) {
-            // TODO(syg, marja): Support WeakCells in the young generation.
-            //assert!(!Self::contains_young_objects(weak_cells));
-        }
-
-        pub fn update_code_flushing_candidates(code_flushing_candidates: &mut WeakObjectWorklist<SharedFunctionInfo>) {
-            //assert!(!Self::contains_young_objects(code_flushing_candidates));
-        }
-
-        pub fn update_flushed_js_functions(flushed_js_functions: &mut WeakObjectWorklist<JSFunction>) {
-            flushed_js_functions.update(
-                |slot_in: JSFunction, slot_out: *mut JSFunction| -> bool {
-                    //let forwarded = ForwardingAddress(slot_in);
-                    let forwarded = None; // TODO: Implement ForwardingAddress
-
-                    if forwarded.is_none() {
-                        return false;
-                    }
-
-                    unsafe {
-                         *slot_out = slot_in; //forwarded.unwrap();
-                    }
-                   
-                    true
-                });
-        }
-
-        #[cfg(not(feature = "v8_enable_leaptiering"))]
-        pub fn update_baseline_flushing_candidates(baseline_flush_candidates: &mut WeakObjectWorklist<JSFunction>) {
-            baseline_flush_candidates.update(
-                |slot_in: JSFunction, slot_out: *mut JSFunction| -> bool {
-                    //let forwarded = ForwardingAddress(slot_in);
-                    let forwarded = None; // TODO: Implement ForwardingAddress
-                    if forwarded.is_none() {
-                        return false;
-                    }
-
-                    unsafe {
-                        *slot_out = slot_in; //forwarded.unwrap();
-                    }
-                    
-                    true
-                });
-        }
-        
-        transition_arrays: WeakObjectWorklist<TransitionArray> = WeakObjectWorklist::new();
-        ephemeron_hash_tables: WeakObjectWorklist<EphemeronHashTable> = WeakObjectWorklist::new();
-        current_ephemerons: WeakObjectWorklist<Ephemeron> = WeakObjectWorklist::new();
-        next_ephemerons: WeakObjectWorklist<Ephemeron> = WeakObjectWorklist::new();
-        weak_references_trivial: WeakObjectWorklist<HeapObjectAndSlot> = WeakObjectWorklist::new();
-        weak_references_trusted: WeakObjectWorklist<TrustedObjectAndSlot> = WeakObjectWorklist::new();
-        weak_references_non_trivial: WeakObjectWorklist<HeapObjectAndSlot> = WeakObjectWorklist::new();
-        weak_references_non_trivial_unmarked: WeakObjectWorklist<HeapObjectAndSlot> = WeakObjectWorklist::new();
-        weak_objects_in_code: WeakObjectWorklist<HeapObjectAndCode> = WeakObjectWorklist::new();
-        js_weak_refs: WeakObjectWorklist<JSWeakRef> = WeakObjectWorklist::new();
-        weak_cells: WeakObjectWorklist<WeakCell> = WeakObjectWorklist::new();
-        code_flushing_candidates: WeakObjectWorklist<SharedFunctionInfo> = WeakObjectWorklist::new();
-        flushed_js_functions: WeakObjectWorklist<JSFunction> = WeakObjectWorklist::new();
-        #[cfg(not(feature = "v8_enable_leaptiering"))]
-        baseline_flush_candidates: WeakObjectWorklist<JSFunction> = WeakObjectWorklist::new();
-
-        #[cfg(debug_assertions)]
-        fn contains_young_objects<Type>(worklist: &mut WeakObjectWorklist<Type>) -> bool {
-            let mut result = false;
-            worklist.iterate(|candidate| {
-                // TODO: Implement HeapLayout::InYoungGeneration(candidate)
-                // if HeapLayout::InYoungGeneration(candidate) {
-                //     result = true;
-                // }
-            });
-            result
-        }
-    }
-
-    pub struct WeakObjectsFields {
-        pub transition_arrays: WeakObjectWorklist<TransitionArray>,
-        pub ephemeron_hash_tables: WeakObjectWorklist<EphemeronHashTable>,
-        pub current_ephemerons: WeakObjectWorklist<Ephemeron>,
-        pub next_ephemerons: WeakObjectWorklist<Ephemeron>,
-        pub weak_references_trivial: WeakObjectWorklist<HeapObjectAndSlot>,
-        pub weak_references_trusted: WeakObjectWorklist<TrustedObjectAndSlot>,
-        pub weak_references_non_trivial: WeakObjectWorklist<HeapObjectAndSlot>,
-        pub weak_references_non_trivial_unmarked: WeakObjectWorklist<HeapObjectAndSlot>,
-        pub weak_objects_in_code: WeakObjectWorklist<HeapObjectAndCode>,
-        pub js_weak_refs: WeakObjectWorklist<JSWeakRef>,
-        pub weak_cells: WeakObjectWorklist<WeakCell>,
-        pub code_flushing_candidates: WeakObjectWorklist<SharedFunctionInfo>,
-        pub flushed_js_functions: WeakObjectWorklist<JSFunction>,
-        #[cfg(not(feature = "v8_enable_leaptiering"))]
-        pub baseline_flush_candidates: WeakObjectWorklist<JSFunction>,
-    }
-
-    pub struct Local {
-        transition_arrays_local: WeakObjectWorklist<TransitionArray>,
-        ephemeron_hash_tables_local: WeakObjectWorklist<EphemeronHashTable>,
-        current_ephemerons_local: WeakObjectWorklist<Ephemeron>,
-        next_ephemerons_local: WeakObjectWorklist<Ephemeron>,
-        weak_references_trivial_local: WeakObjectWorklist<HeapObjectAndSlot>,
-        weak_references_trusted_local: WeakObjectWorklist<TrustedObjectAndSlot>,
-        weak_references_non_trivial_local: WeakObjectWorklist<HeapObjectAndSlot>,
-        weak_references_non_trivial_unmarked_local: WeakObjectWorklist<HeapObjectAndSlot>,
-        weak_objects_in_code_local: WeakObjectWorklist<HeapObjectAndCode>,
-        js_weak_refs_local: WeakObjectWorklist<JSWeakRef>,
-        weak_cells_local: WeakObjectWorklist<WeakCell>,
-        code_flushing_candidates_local: WeakObjectWorklist<SharedFunctionInfo>,
-        flushed_js_functions_local: WeakObjectWorklist<JSFunction>,
-        #[cfg(not(feature = "v8_enable_leaptiering"))]
-        baseline_flush_candidates_local: WeakObjectWorklist<JSFunction>,
-    }
-
-    impl Local {
-        pub fn new(weak_objects: &mut WeakObjectsFields) -> Self {
-            Local {
-                transition_arrays_local: std::mem::take(&mut weak_objects.transition_arrays),
-                ephemeron_hash_tables_local: std::mem::take(&mut weak_objects.ephemeron_hash_tables),
-                current_ephemerons_local: std::mem::take(&mut weak_objects.current_ephemerons),
-                next_ephemerons_local: std::mem::take(&mut weak_objects.next_ephemerons),
-                weak_references_trivial_local: std::mem::take(&mut weak_objects.weak_references_trivial),
-                weak_references_trusted_local: std::mem::take(&mut weak_objects.weak_references_trusted),
-                weak_references_non_trivial_local: std::mem::take(&mut weak_objects.weak_references_non_trivial),
-                weak_references_non_trivial_unmarked_local: std::mem::take(&mut weak_objects.weak_references_non_trivial_unmarked),
-                weak_objects_in_code_local: std::mem::take(&mut weak_objects.weak_objects_in_code),
-                js_weak_refs_local: std::mem::take(&mut weak_objects.js_weak_refs),
-                weak_cells_local: std::mem::take(&mut weak_objects.weak_cells),
-                code_flushing_candidates_local: std::mem::take(&mut weak_objects.code_flushing_candidates),
-                flushed_js_functions_local: std::mem::take(&mut weak_objects.flushed_js_functions),
-                #[cfg(not(feature = "v8_enable_leaptiering"))]
-                baseline_flush_candidates_local: std::mem::take(&mut weak_objects.baseline_flush_candidates),
-            }
-        }
-
-        pub fn publish(&mut self, weak_objects: &mut WeakObjectsFields) {
-            weak_objects.transition_arrays = std::mem::take(&mut self.transition_arrays_local);
-            weak_objects.ephemeron_hash_tables = std::mem::take(&mut self.ephemeron_hash_tables_local);
-            weak_objects.current_ephemerons = std::mem::take(&mut self.current_ephemerons_local);
-            weak_objects.next_ephemerons = std::mem::take(&mut self.next_ephemerons_local);
-            weak_objects.weak_references_trivial = std::mem::take(&mut self.weak_references_trivial_local);
-            weak_objects.weak_references_trusted = std::mem::take(&mut self.weak_references_trusted_local);
-            weak_objects.weak_references_non_trivial = std::mem::take(&mut self.weak_references_non_trivial_local);
-            weak_objects.weak_references_non_trivial_unmarked = std::mem::take(&mut self.weak_references_non_trivial_unmarked_local);
-            weak_objects.weak_objects_in_code = std::mem::take(&mut self.weak_objects_in_code_local);
-            weak_objects.js_weak_refs = std::mem::take(&mut self.js_weak_refs_local);
-            weak_objects.weak_cells = std::mem::take(&mut self.weak_cells_local);
-            weak_objects.code_flushing_candidates = std::mem::take(&mut self.code_flushing_candidates_local);
-            weak_objects.flushed_js_functions = std::mem::take(&mut self.flushed_js_functions_local);
-            #[cfg(not(feature = "v8_enable_leaptiering"))]
-            weak_objects.baseline_flush_candidates = std::mem::take(&mut self.baseline_flush_candidates_local);
-        }
-
-        pub fn update_transition_arrays(&mut self, transition_arrays: &mut WeakObjectWorklist<TransitionArray>) {
-             Self::update_transition_arrays_static(transition_arrays);
-        }
-    
-        pub fn update_ephemeron_hash_tables(&mut self, ephemeron_hash_tables: &mut WeakObjectWorklist<EphemeronHashTable>) {
-             Self::update_ephemeron_hash_tables_static(ephemeron_hash_tables);
-        }
-
-        pub fn update_current_ephemerons(&mut self, current_ephemerons: &mut WeakObjectWorklist<Ephemeron>) {
-             Self::update_current_ephemerons_static(current_ephemerons);
-        }
-
-        pub fn update_next_ephemerons(&mut self, next_ephemerons: &mut WeakObjectWorklist<Ephemeron>) {
-             Self::update_next_ephemerons_static(next_ephemerons);
-        }
-        
-        pub fn update_weak_references_trivial(&mut self, weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-             Self::update_weak_references_trivial_static(weak_references);
-        }
-
-        pub fn update_weak_references_trusted(&mut self, weak_references: &mut WeakObjectWorklist<TrustedObjectAndSlot>) {
-             Self::update_weak_references_trusted_static(weak_references);
-        }
-
-        pub fn update_weak_references_non_trivial(&mut self, weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-             Self::update_weak_references_non_trivial_static(weak_references);
-        }
-        
-        pub fn update_weak_references_non_trivial_unmarked(&mut self, weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-             Self::update_weak_references_non_trivial_unmarked_static(weak_references);
-        }
-
-        pub fn update_weak_objects_in_code(&mut self, weak_objects_in_code: &mut WeakObjectWorklist<HeapObjectAndCode>) {
-             Self::update_weak_objects_in_code_static(weak_objects_in_code);
-        }
-
-        pub fn update_js_weak_refs(&mut self, js_weak_refs: &mut WeakObjectWorklist<JSWeakRef>) {
-             Self::update_js_weak_refs_static(js_weak_refs);
-        }
-
-        pub fn update_weak_cells(&mut self, weak_cells: &mut WeakObjectWorklist<WeakCell>) {
-             Self::update_weak_cells_static(weak_cells);
-        }
-
-        pub fn update_code_flushing_candidates(&mut self, code_flushing_candidates: &mut WeakObjectWorklist<SharedFunctionInfo>) {
-             Self::update_code_flushing_candidates_static(code_flushing_candidates);
-        }
-
-        pub fn update_flushed_js_functions(&mut self, flushed_js_functions: &mut WeakObjectWorklist<JSFunction>) {
-             Self::update_flushed_js_functions_static(flushed_js_functions);
-        }
-
-        #[cfg(not(feature = "v8_enable_leaptiering"))]
-        pub fn update_baseline_flushing_candidates(&mut self, baseline_flush_candidates: &mut WeakObjectWorklist<JSFunction>) {
-             Self::update_baseline_flushing_candidates_static(baseline_flush_candidates);
-        }
-
-        pub fn update_transition_arrays_static(transition_arrays: &mut WeakObjectWorklist<TransitionArray>) {
-            // TODO: Add ContainsYoungObjects function
-           // assert!(!Self::contains_young_objects(transition_arrays));
-        }
-    
-        pub fn update_ephemeron_hash_tables_static(ephemeron_hash_tables: &mut WeakObjectWorklist<EphemeronHashTable>) {
-            ephemeron_hash_tables.update(
-                |slot_in: EphemeronHashTable, slot_out: *mut EphemeronHashTable| -> bool {
-                   // let forwarded = ForwardingAddress(slot_in);
-                   // TODO: Implement ForwardingAddress
-                    let forwarded = None;
-    
-                    if forwarded.is_none() {
-                        return false;
-                    }
-                    unsafe {
-                        *slot_out = slot_in; // forward.unwrap();
-                    }
-                    true
-                });
-        }
-
-        pub fn update_current_ephemerons_static(current_ephemerons: &mut WeakObjectWorklist<Ephemeron>) {
-            current_ephemerons.update(ephemeron_updater);
-        }
-
-        pub fn update_next_ephemerons_static(next_ephemerons: &mut WeakObjectWorklist<Ephemeron>) {
-            next_ephemerons.update(ephemeron_updater);
-        }
-        
-        pub fn update_weak_references_trivial_static(weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-
-        pub fn update_weak_references_trusted_static(weak_references: &mut WeakObjectWorklist<TrustedObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-
-        pub fn update_weak_references_non_trivial_static(weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-        
-        pub fn update_weak_references_non_trivial_unmarked_static(weak_references: &mut WeakObjectWorklist<HeapObjectAndSlot>) {
-            update_weak_references_helper(weak_references);
-        }
-
-        pub fn update_weak_objects_in_code_static(weak_objects_in_code: &mut WeakObjectWorklist<HeapObjectAndCode>) {
-            weak_objects_in_code.update(
-                |slot_in: HeapObjectAndCode, slot_out: *mut HeapObjectAndCode| -> bool {
-                   // let forwarded = ForwardingAddress(slot_in.heap_object);
-                   let forwarded = None; // TODO: Implement ForwardingAddress
-
-                    if forwarded.is_none() {
-                        return false;
-                    }
-
-                    unsafe {
-                        (*slot_out).heap_object = slot_in.heap_object; // forwarded.unwrap();
-                        (*slot_out).code = slot_in.code;
-                    }
-
-                    true
-                });
-        }
-
-        pub fn update_js_weak_refs_static(js_weak_refs: &mut WeakObjectWorklist<JSWeakRef>) {
-            js_weak_refs.update(
-                |js_weak_ref_in: JSWeakRef, js_weak_ref_out: *mut JSWeakRef| -> bool {
-                    //let forwarded = ForwardingAddress(js_weak_ref_in);
-                    let forwarded = None; // TODO: Implement ForwardingAddress
-    
-                    if forwarded.is_none() {
-                        return false;
-                    }
-                    unsafe {
-                         *js_weak_ref_out = js_weak_ref_in; // forwarded.unwrap();
-                    }
-                   
-                    true
-                });
-        }
-
-        pub fn update_weak_cells_static(weak_cells: &mut WeakObjectWorklist<WeakCell>) {
-            // TODO(syg, marja): Support WeakCells in the young generation.
-            //assert!(!Self::contains_young_objects(weak_cells));
-        }
-
-        pub fn update_code_flushing_candidates_static(code_flushing_candidates: &mut WeakObjectWorklist<SharedFunctionInfo>) {
-            //assert!(!Self::contains_young_objects(code_flushing_candidates));
-        }
-
-        pub fn update_flushed_js_functions_static(flushed_js_functions: &mut WeakObjectWorklist<JSFunction>) {
-            flushed_js_functions.update(
-                |slot_in: JSFunction, slot_out: *mut JSFunction| -> bool {
-                    //let forwarded = ForwardingAddress(slot_in);
-                    let forwarded = None; // TODO: Implement ForwardingAddress
-
-                    if forwarded.is_none() {
-                        return false;
-                    }
-
-                    unsafe {
-                         *slot_out = slot_in; //forwarded.unwrap();
-                    }
-                   
-                    true
-                });
-        }
-
-        #[cfg(not(feature = "v8_enable_leaptiering"))]
-        pub fn update_baseline_flushing_candidates_static(baseline_flush_candidates: &mut WeakObjectWorklist<JSFunction>) {
-            baseline_flush_candidates.update(
-                |slot_in: JSFunction, slot_out: *mut JSFunction| -> bool {
-                    //let forwarded = ForwardingAddress(slot_in);
-                    let forwarded = None; // TODO: Implement ForwardingAddress
-                    if forwarded.is_none() {
-                        return false;
-                    }
-
-                    unsafe {
-                        *slot_out = slot_in; //forwarded.unwrap();
-                    }
-                    
-                    true
-                });
-        }
-    }
-    
-    fn ephemeron_updater(slot_in: Ephemeron, slot_out: *mut Ephemeron) -> bool {
-        let key = slot_in.key;
-        let value = slot_in.value;
-
-       // let forwarded_key = ForwardingAddress(key);
-       // let forwarded_value = ForwardingAddress(value);
-        let forwarded_key = None; // TODO: Implement ForwardingAddress
-        let forwarded_value = None; // TODO: Implement ForwardingAddress
-
-        if forwarded_key.is_none() || forwarded_value.is_none() {
-            return false;
-        }
-
-        unsafe {
-           // *slot_out = Ephemeron {
-           //     key: forwarded_key.unwrap(),
-           //     value: forwarded_value.unwrap(),
-           // };
-            *slot_out = slot_in;
-        }
-
-        true
-    }
-
-    fn update_weak_references_helper<TSlot>(weak_references: &mut WeakObjectWorklist<TSlot>)
-    where
-        TSlot: Copy,
-        TSlot: HasHeapObjectAndSlot,
-    {
-        weak_references.update(|slot_in: TSlot, slot_out: *mut TSlot| -> bool {
-            let heap_obj = slot_in.get_heap_object();
-            let forwarded = None; //ForwardingAddress(heap_obj); // TODO: Implement ForwardingAddress
-
-            if forwarded.is_none() {
-                return false;
-            }
-
-            unsafe {
-                let forwarded_heap_object = heap_obj; // forwarded.unwrap(); //TODO: Use forwarded heap object
-
-                let distance_to_slot = slot_in.get_slot().address() as isize - heap_obj as isize;
-                let new_slot = (forwarded_heap_object as isize + distance_to_slot) as *mut u8;
-
-                (*slot_out).set_heap_object(forwarded_heap_object);
-                (*slot_out).set_slot(Address::from_ptr(new_slot));
-
-                true
-            }
+use crate::heap::base::worklist::{Local, Worklist};
+use crate::heap::heap_layout::{HeapObject, MapWord};
+
+const K_SEGMENT_SIZE: usize = 64;
+
+/// A weak slot: body word `index` of `host`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeapObjectAndSlot {
+    pub host: HeapObject,
+    pub index: usize,
+}
+
+/// Entry `entry` of the ephemeron table `table`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ephemeron {
+    pub table: HeapObject,
+    pub entry: usize,
+}
+
+/// Weak references and ephemerons found during marking. They are processed
+/// in the atomic pause once the transitive closure of strong references is
+/// known: ephemerons with marked keys keep their values alive, then weak
+/// references to unmarked objects and ephemerons with unmarked keys are
+/// cleared.
+#[derive(Default)]
+pub struct WeakObjects {
+    /// Weak slots whose targets were unmarked when the slot was visited.
+    pub weak_references: Worklist<HeapObjectAndSlot, K_SEGMENT_SIZE>,
+    /// Ephemerons whose keys were unmarked when the entry was visited.
+    pub discovered_ephemerons: Worklist<Ephemeron, K_SEGMENT_SIZE>,
+    /// All visited ephemeron tables, whose dead entries are removed.
+    pub ephemeron_hash_tables: Worklist<HeapObject, K_SEGMENT_SIZE>,
+}
+
+impl WeakObjects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn local(&self) -> WeakObjectsLocal<'_> {
+        WeakObjectsLocal {
+            weak_references: Local::new(&self.weak_references),
+            discovered_ephemerons: Local::new(&self.discovered_ephemerons),
+            ephemeron_hash_tables: Local::new(&self.ephemeron_hash_tables),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.weak_references.clear();
+        self.discovered_ephemerons.clear();
+        self.ephemeron_hash_tables.clear();
+    }
+
+    /// Updates entries of young objects after a scavenge that ran while
+    /// marking was in progress. Entries of objects that died are dropped.
+    pub fn update_after_scavenge(&self) {
+        self.weak_references.update(|entry| {
+            Some(HeapObjectAndSlot {
+                host: forwarding_address_after_scavenge(entry.host)?,
+                ..entry
+            })
         });
+        self.discovered_ephemerons.update(|entry| {
+            Some(Ephemeron {
+                table: forwarding_address_after_scavenge(entry.table)?,
+                ..entry
+            })
+        });
+        self.ephemeron_hash_tables.update(forwarding_address_after_scavenge);
     }
+}
 
-    trait HasHeapObjectAndSlot {
-        fn get_heap_object(&self) -> HeapObject;
-        fn get_slot(&self) -> Address;
-        fn set_heap_object(&mut self, heap_object: HeapObject);
-        fn set_slot(&mut self, slot: Address);
-    }
-
-    impl HasHeapObjectAndSlot for HeapObjectAndSlot {
-        fn get_heap_object(&self) -> HeapObject {
-            self.heap_object
-        }
-
-        fn get_slot(&self) -> Address {
-            self.slot.clone()
-        }
-
-        fn set_heap_object(&mut self, heap_object: HeapObject) {
-            self.heap_object = heap_object;
-        }
+/// Thread-local views on `WeakObjects`.
+pub struct WeakObjectsLocal<'a> {
+    pub weak_references: Local<'a, HeapObjectAndSlot, K_SEGMENT_SIZE>,
+    pub discovered_ephemerons: Local<'a, Ephemeron, K_SEGMENT_SIZE>,
+    pub ephemeron_hash_tables: Local<'a, HeapObject, K_SEGMENT_SIZE>,
+}
 
-        fn set_slot(&mut self, slot: Address) {
-            self.slot = slot;
-        }
+impl WeakObjectsLocal<'_> {
+    pub fn publish(&mut self) {
+        self.weak_references.publish();
+        self.discovered_ephemerons.publish();
+        self.ephemeron_hash_tables.publish();
     }
+}
 
-    impl HasHeapObjectAndSlot for TrustedObjectAndSlot {
-        fn get_heap_object(&self) -> HeapObject {
-            self.heap_object
-        }
-
-        fn get_slot(&self) -> Address {
-            self.slot.clone()
-        }
-        fn set_heap_object(&mut self, heap_object: HeapObject) {
-            self.heap_object = heap_object;
-        }
-
-        fn set_slot(&mut self, slot: Address) {
-            self.slot = slot;
-        }
+/// Returns the location of `object` after a scavenge, or `None` if it was
+/// a young object that died.
+pub fn forwarding_address_after_scavenge(object: HeapObject) -> Option<HeapObject> {
+    if !object.chunk().is_from_page() {
+        return Some(object);
+    }
+    match object.map_word() {
+        MapWord::Forwarding(target) => Some(target),
+        MapWord::Map(_) => None,
     }
-}
\ No newline at end of file
+}
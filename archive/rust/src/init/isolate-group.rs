@@ -1,12 +1,15 @@
 // src/init/isolate-group.rs
 
-use std::cell::RefCell;
 use std::ptr::null_mut;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     Mutex, Once,
 };
 
+use crate::base::bounded_page_allocator::BoundedPageAllocator;
+use crate::base::virtual_address_space::get_platform_virtual_address_space;
+use crate::sandbox::sandbox::{Sandbox, SandboxedArrayBufferAllocator};
+
 // Placeholder for platform-specific memory allocation.
 mod platform {
     pub struct PageAllocator;
@@ -41,13 +44,6 @@ mod platform {
 }
 
 mod base {
-    pub struct BoundedPageAllocator;
-    impl BoundedPageAllocator {
-        pub fn new() -> Self {
-            BoundedPageAllocator
-        }
-    }
-
     pub enum PageInitializationMode {
         KAllocatedPagesCanBeUninitialized,
     }
@@ -55,64 +51,6 @@ mod base {
     pub enum PageFreeingMode {
         KMakeInaccessible,
     }
-
-    pub struct RegionAllocator {
-        begin_: usize,
-        size_: usize,
-        granularity_: usize,
-        current_: RefCell<usize>,
-        on_merge_callback: RefCell<Option<Box<dyn Fn(usize, usize)>>>,
-    }
-
-    impl RegionAllocator {
-        pub fn new(base: usize, size: usize, granularity: usize) -> Self {
-            RegionAllocator {
-                begin_: base,
-                size_: size,
-                granularity_: granularity,
-                current_: RefCell::new(base),
-                on_merge_callback: RefCell::new(None),
-            }
-        }
-
-        pub fn allocate_region(&self, length: usize) -> usize {
-            let mut current = self.current_.borrow_mut();
-            let region = *current;
-            let aligned_length = crate::utils::round_up(length, self.granularity_);
-
-            if region + aligned_length > self.begin_ + self.size_ {
-                return Self::KALLOCATION_FAILURE;
-            }
-
-            *current += aligned_length;
-            region
-        }
-
-        pub fn free_region(&self, region: usize) -> bool {
-            // Dummy implementation, no actual freeing
-            true
-        }
-
-        pub fn set_on_merge_callback<F>(&self, callback: F)
-        where
-            F: Fn(usize, usize) + 'static,
-        {
-            *self.on_merge_callback.borrow_mut() = Some(Box::new(callback));
-        }
-
-        pub fn begin(&self) -> usize {
-            self.begin_
-        }
-
-        pub fn end(&self) -> usize {
-            self.begin_ + self.size_
-        }
-
-        pub fn size(&self) -> usize {
-            self.size_
-        }
-        const KALLOCATION_FAILURE: usize = 0;
-    }
 }
 
 mod common {
@@ -201,111 +139,6 @@ mod sandbox {
         pub fn initialize(&mut self) {}
         pub fn tear_down(&mut self) {}
     }
-    pub struct Sandbox {
-        address_space: VirtualAddressSpace,
-        base_: usize,
-        page_allocator: platform::PageAllocator,
-        is_initialized_: bool,
-    }
-
-    impl Sandbox {
-        pub fn new(address_space: VirtualAddressSpace) -> Self {
-            Sandbox {
-                address_space,
-                base_: 0, //Needs to be initialized later
-                page_allocator: platform::get_platform_page_allocator(),
-                is_initialized_: false,
-            }
-        }
-        pub fn is_initialized(&self) -> bool {
-            self.is_initialized_
-        }
-
-        pub fn base(&self) -> usize {
-            self.base_
-        }
-
-        pub fn address_space(&self) -> &VirtualAddressSpace {
-            &self.address_space
-        }
-
-        pub fn page_allocator(&self) -> &platform::PageAllocator {
-            &self.page_allocator
-        }
-
-        pub fn initialize(&mut self) -> Result<(), String> {
-            self.base_ = self.address_space().allocate_pages(
-                0,
-                crate::common::ptr_compr_inl::K_PTR_COMPR_CAGE_RESERVATION_SIZE,
-                crate::common::ptr_compr_inl::K_PTR_COMPR_CAGE_BASE_ALIGNMENT,
-                platform::Permission::NoAccess,
-            );
-
-            if self.base_ == 0 {
-                return Err("Failed to initialize Sandbox".to_string());
-            }
-
-            self.is_initialized_ = true;
-            Ok(())
-        }
-
-        pub fn tear_down(&mut self) {
-            // Free allocated resources here.
-            // Dummy implementation, add proper deallocation
-            self.is_initialized_ = false;
-        }
-
-        pub fn get_default() -> &'static Mutex<Option<Sandbox>> {
-            static DEFAULT_SANDBOX: Mutex<Option<Sandbox>> = Mutex::new(None);
-            static INIT: Once = Once::new();
-
-            INIT.call_once(|| {
-                let mut sandbox = Sandbox::new(VirtualAddressSpace::new());
-                if let Err(e) = sandbox.initialize() {
-                    panic!("Failed to initialize default sandbox: {}", e);
-                }
-                let mut guard = DEFAULT_SANDBOX.lock().unwrap();
-                *guard = Some(sandbox);
-            });
-
-            unsafe {
-                let ptr = &DEFAULT_SANDBOX as *const Mutex<Option<Sandbox>>;
-                &(*ptr)
-            }
-        }
-    }
-
-    pub struct VirtualAddressSpace;
-
-    impl VirtualAddressSpace {
-        pub fn new() -> Self {
-            VirtualAddressSpace
-        }
-        pub fn allocate_pages(
-            &self,
-            _hint: usize,
-            size: usize,
-            alignment: usize,
-            _permissions: platform::Permission,
-        ) -> usize {
-            //Dummy implementation
-            0 // Dummy address for now.
-        }
-
-        pub fn decommit_pages(&self, _start: usize, _size: usize) -> bool {
-            true //Dummy Implementation
-        }
-
-        pub fn discard_system_pages(&self, _start: usize, _size: usize) -> bool {
-            true //Dummy Implementation
-        }
-
-        pub fn free_pages(&self, _start: usize, _size: usize) {}
-
-        pub fn set_page_permissions(&self, _start: usize, _size: usize, _permissions: platform::Permission) -> bool {
-            true
-        }
-    }
 }
 
 mod utils {
@@ -365,7 +198,7 @@ pub struct IsolateGroup {
     reference_count_: AtomicUsize,
     isolate_count_: usize,
     process_wide_: bool,
-    page_allocator_: *mut platform::PageAllocator, // Option<Box<platform::PageAllocator>>,
+    page_allocator_: platform::PageAllocator,
     optimizing_compile_task_executor_: Option<Box<compiler_dispatcher::OptimizingCompileDispatcher>>,
     code_range_: Option<Box<heap::CodeRange>>,
     read_only_artifacts_: Option<Box<ReadOnlyArtifacts>>,
@@ -374,7 +207,10 @@ pub struct IsolateGroup {
     shared_space_isolate_: *mut execution::Isolate,
     pointer_compression_cage_: *const VirtualMemoryCage, //Option<Box<VirtualMemoryCage>>,
     trusted_pointer_compression_cage_: heap::trusted_range::TrustedRange,
-    sandbox_: *mut sandbox::Sandbox,
+    /// The sandbox of the group: the process-wide default sandbox, or
+    /// `owned_sandbox_` for groups created by `new_group()`.
+    sandbox_: *const Sandbox,
+    owned_sandbox_: Option<Box<Sandbox>>,
     js_dispatch_table_: JsDispatchTable,
     code_pointer_table_: sandbox::CodePointerTable,
     external_ref_table_: external_reference::ExternalReferenceTable,
@@ -431,7 +267,7 @@ impl IsolateGroup {
             reference_count_: AtomicUsize::new(1),
             isolate_count_: 0,
             process_wide_: false,
-            page_allocator_: null_mut(), //None,
+            page_allocator_: platform::get_platform_page_allocator(),
             optimizing_compile_task_executor_: None,
             code_range_: None,
             read_only_artifacts_: None,
@@ -442,7 +278,8 @@ impl IsolateGroup {
             trusted_pointer_compression_cage_: heap::trusted_range::TrustedRange::ensure_process_wide_trusted_range(
                 heap::trusted_range::K_MAXIMAL_TRUSTED_RANGE_SIZE,
             ),
-            sandbox_: null_mut(),
+            sandbox_: std::ptr::null(),
+            owned_sandbox_: None,
             js_dispatch_table_: JsDispatchTable::new(),
             code_pointer_table_: sandbox::CodePointerTable::new(),
             external_ref_table_: external_reference::ExternalReferenceTable,
@@ -461,18 +298,10 @@ impl IsolateGroup {
         default_group
     }
 
-    #[allow(unused_variables)]
-    fn initialize(&mut self, process_wide: bool, sandbox: *mut sandbox::Sandbox) {
+    fn initialize(&mut self, process_wide: bool, sandbox: &Sandbox) {
         self.process_wide_ = process_wide;
-
-        let sandbox_ref: &mut sandbox::Sandbox = unsafe { &mut (*sandbox) };
-
-        if let Err(e) = sandbox_ref.initialize() {
-            v8::fatal_process_out_of_memory(null_mut(), &format!("Sandbox initialization failed: {}", e));
-        }
-
+        assert!(sandbox.is_initialized());
         self.sandbox_ = sandbox;
-        self.page_allocator_ = sandbox_ref.page_allocator() as *const platform::PageAllocator as *mut platform::PageAllocator;
 
         self.optimizing_compile_task_executor_ = Some(Box::new(compiler_dispatcher::OptimizingCompileDispatcher));
         self.code_pointer_table().initialize();
@@ -490,20 +319,9 @@ impl IsolateGroup {
         IsolateGroup::set_default_isolate_group(group);
 
         let group_ref: &mut IsolateGroup = unsafe { &mut (*group) };
-        let sandbox_guard = sandbox::Sandbox::get_default();
-        let sandbox_ptr: *mut sandbox::Sandbox;
-
-        {
-            let sandbox_option = sandbox_guard.lock().unwrap();
-
-            if let Some(ref sandbox) = *sandbox_option {
-                sandbox_ptr = sandbox as *const sandbox::Sandbox as *mut sandbox::Sandbox;
-            } else {
-                panic!("Default sandbox not initialized");
-            }
-        }
-
-        group_ref.initialize(true, sandbox_ptr);
+        // The process-wide group lives in the default sandbox, which is
+        // reserved here on first use.
+        group_ref.initialize(true, Sandbox::initialize_default_once_per_process());
 
         #[cfg(feature = "V8_COMPRESS_POINTERS")]
         {
@@ -537,7 +355,7 @@ impl IsolateGroup {
     }
 
     pub fn ensure_code_range(&mut self, requested_size: usize) -> *mut heap::CodeRange {
-        let page_allocator = unsafe { &mut (*self.page_allocator_) };
+        let page_allocator = &self.page_allocator_;
 
         self.init_code_range_.call_once(|| {
             let mut code_range = Box::new(heap::CodeRange::new());
@@ -564,9 +382,14 @@ impl IsolateGroup {
         }
     }
 
-    pub fn get_backing_store_page_allocator(&self) -> *mut platform::PageAllocator {
-        let sandbox_ref: &sandbox::Sandbox = unsafe { &mut (*self.sandbox_) };
-        sandbox_ref.page_allocator() as *const platform::PageAllocator as *mut platform::PageAllocator
+    /// The allocator all ArrayBuffer backing stores of the group's isolates
+    /// are allocated with.
+    pub fn get_backing_store_page_allocator(&self) -> &BoundedPageAllocator {
+        self.sandbox().page_allocator().unwrap()
+    }
+
+    pub fn get_sandboxed_array_buffer_allocator(&self) -> SandboxedArrayBufferAllocator<'_> {
+        SandboxedArrayBufferAllocator::new(self.sandbox())
     }
 
     pub fn setup_read_only_heap(
@@ -622,15 +445,12 @@ impl IsolateGroup {
 
     pub fn new_group() -> *mut IsolateGroup {
         let group = Box::into_raw(Box::new(IsolateGroup::new()));
-
-        let sandbox = Box::into_raw(Box::new(sandbox::Sandbox::new(sandbox::VirtualAddressSpace::new())));
         let group_ref: &mut IsolateGroup = unsafe { &mut (*group) };
 
-        if let Err(e) = unsafe { &mut (*sandbox) }.initialize() {
-            v8::fatal_process_out_of_memory(null_mut(), &format!("Sandbox initialization failed: {}", e));
-        }
-
-        group_ref.initialize(false, sandbox);
+        // Each group gets its own sandbox; aborts if it cannot be reserved.
+        let sandbox = Sandbox::new_sandbox(get_platform_virtual_address_space());
+        group_ref.initialize(false, &sandbox);
+        group_ref.owned_sandbox_ = Some(sandbox);
 
         let group_access_scope = IsolateGroupAccessScope::new(group);
 
@@ -656,8 +476,10 @@ impl IsolateGroup {
         self.optimizing_compile_task_executor_.as_mut().unwrap()
     }
 
-    pub fn sandbox(&mut self) -> &mut sandbox::Sandbox {
-        unsafe { &mut (*self.sandbox_) }
+    pub fn sandbox(&self) -> &Sandbox {
+        // SAFETY: the sandbox is the default one, which is never freed, or
+        // owned by the group.
+        unsafe { &*self.sandbox_ }
     }
 
     pub fn external_ref_table(&mut self) -> &mut external_reference::ExternalReferenceTable {
@@ -686,22 +508,31 @@ impl Drop for IsolateGroup {
     }
 }
 
-#[derive(Default)]
+/// Makes `group` and its sandbox the current ones of the thread.
 struct IsolateGroupAccessScope {
     previous_: *mut IsolateGroup,
+    previous_sandbox_: Option<&'static Sandbox>,
 }
 
 impl IsolateGroupAccessScope {
     fn new(group: *mut IsolateGroup) -> Self {
         let previous_ = IsolateGroup::current();
+        let previous_sandbox_ = Sandbox::current();
         IsolateGroup::set_current(group);
-        IsolateGroupAccessScope { previous_ }
+        // SAFETY: the scope does not outlive the group, which keeps its
+        // sandbox alive.
+        Sandbox::set_current(Some(unsafe { &*(*group).sandbox_ }));
+        IsolateGroupAccessScope {
+            previous_,
+            previous_sandbox_,
+        }
     }
 }
 
 impl Drop for IsolateGroupAccessScope {
     fn drop(&mut self) {
         IsolateGroup::set_current(self.previous_);
+        Sandbox::set_current(self.previous_sandbox_);
     }
 }
 
@@ -786,149 +617,3 @@ impl JsDispatchTable {
 
     fn tear_down(&mut self) {}
 }
-
-#[derive(Default)]
-pub struct SandboxedArrayBufferAllocator {
-    mutex_: Mutex<()>,
-    region_alloc_: Option<Box<base::RegionAllocator>>,
-    end_of_accessible_region_: usize,
-    sandbox_: *mut sandbox::Sandbox,
-    is_initialized_: bool,
-}
-
-impl SandboxedArrayBufferAllocator {
-    const KCHUNK_SIZE: usize = 2 * 1024 * 1024;
-    const KALLOCATION_GRANULARITY: usize = 16;
-
-    pub fn lazy_initialize(&mut self, sandbox: *mut sandbox::Sandbox) {
-        let _guard = self.mutex_.lock().unwrap();
-
-        if self.is_initialized() {
-            return;
-        }
-
-        self.sandbox_ = sandbox;
-        let sandbox_ref: &sandbox::Sandbox = unsafe { &mut (*sandbox) };
-        assert!(sandbox_ref.is_initialized());
-
-        const MAX_BACKING_MEMORY_SIZE: usize = 8 * 1024 * 1024 * 1024;
-        const MIN_BACKING_MEMORY_SIZE: usize = 1 * 1024 * 1024 * 1024;
-
-        let mut backing_memory_size = MAX_BACKING_MEMORY_SIZE;
-        let mut backing_memory_base = 0;
-
-        while backing_memory_base == 0 && backing_memory_size >= MIN_BACKING_MEMORY_SIZE {
-            backing_memory_base = sandbox_ref.address_space().allocate_pages(
-                0,
-                backing_memory_size,
-                Self::KCHUNK_SIZE,
-                platform::Permission::NoAccess,
-            );
-
-            if backing_memory_base == 0 {
-                backing_memory_size /= 2;
-            }
-        }
-
-        if backing_memory_base == 0 {
-            v8::fatal_process_out_of_memory(
-                null_mut(),
-                "Could not reserve backing memory for ArrayBufferAllocators",
-            );
-        }
-        assert!(utils::is_aligned(backing_memory_base, Self::KCHUNK_SIZE));
-
-        self.region_alloc_ = Some(Box::new(base::RegionAllocator::new(
-            backing_memory_base,
-            backing_memory_size,
-            Self::KALLOCATION_GRANULARITY,
-        )));
-        self.end_of_accessible_region_ = self.region_alloc_.as_ref().unwrap().begin();
-
-        let region_alloc = self.region_alloc_.as_ref().unwrap();
-        let on_merge_callback = |start: usize, size: usize| {
-            let _guard = self.mutex_.lock().unwrap();
-            let end = start + size;
-            if end == region_alloc.end()
-                && start <= self.end_of_accessible_region_ - Self::KCHUNK_SIZE
-            {
-                let new_end_of_accessible_region = utils::round_up(start, Self::KCHUNK_SIZE);
-                let size_to_decommit =
-                    self.end_of_accessible_region_ - new_end_of_accessible_region;
-
-                if !sandbox_ref.address_space().decommit_pages(
-                    new_end_of_accessible_region,
-                    size_to_decommit,
-                ) {
-                    v8::fatal_process_out_of_memory(
-                        null_mut(),
-                        "SandboxedArrayBufferAllocator()",
-                    );
-                }
-                self.end_of_accessible_region_ = new_end_of_accessible_region;
-            } else if size >= 2 * Self::KCHUNK_SIZE {
-                let chunk_start = utils::round_up(start, Self::KCHUNK_SIZE);
-                let chunk_end = utils::round_down(start + size, Self::KCHUNK_SIZE);
-
-                if !sandbox_ref.address_space().discard_system_pages(
-                    chunk_start,
-                    chunk_end - chunk_start,
-                ) {
-                    v8::fatal_process_out_of_memory(
-                        null_mut(),
-                        "SandboxedArrayBufferAllocator()",
-                    );
-                }
-            }
-        };
-        self.region_alloc_
-            .as_ref()
-            .unwrap()
-            .set_on_merge_callback(on_merge_callback);
-        self.is_initialized_ = true;
-    }
-
-    pub fn allocate(&mut self, length: usize) -> *mut u8 {
-        let _guard = self.mutex_.lock().unwrap();
-
-        let length = utils::round_up(length, Self::KALLOCATION_GRANULARITY);
-        let region_alloc = self.region_alloc_.as_ref().unwrap();
-        let region = region_alloc.allocate_region(length);
-
-        if region == base::RegionAllocator::KALLOCATION_FAILURE {
-            return null_mut();
-        }
-
-        let end = region + length;
-        let mut length_to_memset = length;
-
-        if end > self.end_of_accessible_region_ {
-            let new_end_of_accessible_region = utils::round_up(end, Self::KCHUNK_SIZE);
-            let size = new_end_of_accessible_region - self.end_of_accessible_region_;
-            let sandbox_ref: &sandbox::Sandbox = unsafe { &mut (*self.sandbox_) };
-            if !sandbox_ref.address_space().set_page_permissions(
-                self.end_of_accessible_region_,
-                size,
-                platform::Permission::ReadWrite,
-            ) {
-                if !region_alloc.free_region(region) {
-                    v8::fatal_process_out_of_memory(
-                        null_mut(),
-                        "SandboxedArrayBufferAllocator::Allocate()",
-                    );
-                }
-                return null_mut();
-            }
-
-            length_to_memset = self.end_of_accessible_region_ - region;
-            self.end_of_accessible_region_ = new_end_of_accessible_region;
-        }
-
-        let mem = region as *mut u8;
-        unsafe {
-            std::ptr::write_bytes(mem, 0, length_to_memset);
-        }
-        mem
-    }
-
-    pub fn free(&mut self, data
\ No newline at end of file
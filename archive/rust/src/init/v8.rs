@@ -7,21 +7,35 @@
 mod v8 {
     pub struct OOMDetails {}
 
-    pub trait Platform {}
+    pub use crate::include::v8_platform::Platform;
 
     pub struct StartupData {}
 
     pub mod internal {
+        use std::sync::{Arc, RwLock};
+
+        use crate::init::isolate_group::IsolateGroup;
+
         //use crate::common::globals::*; // Assuming globals.h functionality is defined in common module
 
         pub struct Isolate {}
 
         pub struct V8 {}
 
+        static CURRENT_PLATFORM: RwLock<Option<Arc<dyn super::Platform>>> = RwLock::new(None);
+
         impl V8 {
             /// Global actions.
-            pub fn initialize() {}
-            pub fn dispose() {}
+            ///
+            /// Sets up the process-wide isolate group and with it the default
+            /// sandbox.
+            pub fn initialize() {
+                IsolateGroup::initialize_once_per_process();
+            }
+
+            pub fn dispose() {
+                IsolateGroup::tear_down_once_per_process();
+            }
 
             /// Report process out of memory. Implementation found in api.cc.
             /// This function will not return, but will terminate the execution.
@@ -84,25 +98,37 @@ mod v8 {
                  eprintln!("Fatal process out of memory: {} with details {}", _location, _detail);
             }
 
-            pub fn initialize_platform(_platform: Box<dyn super::Platform>) {}
-            pub fn initialize_platform_for_testing(_platform: Box<dyn super::Platform>) {}
-            pub fn dispose_platform() {}
+            /// Sets the platform V8 posts its tasks to. Must be called before
+            /// V8 is initialized.
+            pub fn initialize_platform(platform: Arc<dyn super::Platform>) {
+                let mut current = CURRENT_PLATFORM.write().unwrap();
+                assert!(current.is_none(), "the platform is already initialized");
+                *current = Some(platform);
+            }
 
-            static mut CURRENT_PLATFORM: Option<Box<dyn super::Platform>> = None;
+            pub fn initialize_platform_for_testing(platform: Arc<dyn super::Platform>) {
+                Self::set_platform_for_testing(platform);
+            }
 
-            pub fn get_current_platform() -> Option<&'static dyn super::Platform> {
-                unsafe { Self::CURRENT_PLATFORM.as_deref() }
+            /// Clears the reference to the platform. Must be called after V8
+            /// was disposed.
+            pub fn dispose_platform() {
+                *CURRENT_PLATFORM.write().unwrap() = None;
+            }
+
+            pub fn get_current_platform() -> Option<Arc<dyn super::Platform>> {
+                CURRENT_PLATFORM.read().unwrap().clone()
             }
 
             /// Replaces the current platform with the given platform.
             /// Should be used only for testing.
-            pub fn set_platform_for_testing(platform: Box<dyn super::Platform>) {
-                unsafe {
-                    Self::CURRENT_PLATFORM = Some(platform);
-                }
+            pub fn set_platform_for_testing(platform: Arc<dyn super::Platform>) {
+                *CURRENT_PLATFORM.write().unwrap() = Some(platform);
             }
 
             pub fn set_snapshot_blob(_snapshot_blob: *mut StartupData) {}
         }
     }
-}
\ No newline at end of file
+}
+
+pub use self::v8::internal::V8;
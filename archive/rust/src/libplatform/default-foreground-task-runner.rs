@@ -5,370 +5,265 @@
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, VecDeque};
 use std::sync::{Arc, Condvar, Mutex, MutexGuard};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use v8::{IdleTask, Task, TaskRunner};
+use crate::include::libplatform::libplatform::{IdleTaskSupport, MessageLoopBehavior};
+use crate::include::v8_platform::{IdleTask, Task, TaskRunner};
+use crate::include::v8_source_location::SourceLocation;
+use crate::libplatform::delayed_task_queue::TimeFunction;
 
-pub mod v8 {
-    pub struct Task {
-        // Opaque Task struct
-    }
-    pub struct IdleTask {
-        // Opaque IdleTask struct
-    }
-    pub trait TaskRunner {
-        fn idle_tasks_enabled(&self) -> bool;
-        fn non_nestable_tasks_enabled(&self) -> bool;
-        fn post_task(&self, task: Box<dyn FnOnce()>); // Example
-    }
-
-    #[derive(Clone, Copy)]
-    pub enum MessageLoopBehavior {
-        //Placeholder
-        Default,
-    }
+/// The task runner of an isolate's foreground thread. Tasks are run by the
+/// embedder through `PumpMessageLoop` and `RunIdleTasks`.
+pub struct DefaultForegroundTaskRunner {
+    lock: Mutex<ForegroundState>,
+    event_loop_control: Condvar,
+    idle_task_support: IdleTaskSupport,
+    time_function: TimeFunction,
 }
 
-pub mod base {
-    pub mod platform {
-        use std::sync::{Condvar, Mutex};
-
-        pub struct MutexWrapper {
-            mutex: Mutex<()>,
-        }
-
-        impl MutexWrapper {
-            pub fn new() -> Self {
-                MutexWrapper {
-                    mutex: Mutex::new(()),
-                }
-            }
-
-            pub fn lock(&self) -> MutexGuard<()> {
-                self.mutex.lock().unwrap()
-            }
-        }
-
-        pub struct ConditionVariable {
-            condvar: Condvar,
-        }
-
-        impl ConditionVariable {
-            pub fn new() -> Self {
-                ConditionVariable {
-                    condvar: Condvar::new(),
-                }
-            }
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Nestability {
+    kNestable,
+    kNonNestable,
+}
 
-            pub fn notify_one(&self) {
-                self.condvar.notify_one();
-            }
+struct ForegroundState {
+    terminated: bool,
+    /// The number of tasks currently running, > 1 if the message loop is
+    /// pumped from within a task.
+    nesting_depth: usize,
+    task_queue: VecDeque<(Nestability, Box<dyn Task>)>,
+    idle_task_queue: VecDeque<Box<dyn IdleTask>>,
+    delayed_task_queue: BinaryHeap<DelayedEntry>,
+    next_sequence_number: u64,
+}
 
-            pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
-                self.condvar.wait(guard).unwrap()
-            }
+impl ForegroundState {
+    /// Non-nestable tasks may only run at the outermost level.
+    fn has_poppable_task_in_queue(&self) -> bool {
+        if self.nesting_depth == 0 {
+            return !self.task_queue.is_empty();
         }
+        self.task_queue.iter().any(|(nestability, _)| *nestability == Nestability::kNestable)
     }
-}
 
-pub mod libplatform {
-    pub enum IdleTaskSupport {
-        Enabled,
-        Disabled,
+    fn move_expired_delayed_tasks(&mut self, now: f64) {
+        while self.delayed_task_queue.peek().is_some_and(|entry| entry.timeout_time <= now) {
+            let entry = self.delayed_task_queue.pop().unwrap();
+            self.task_queue.push_back((entry.nestability, entry.task));
+        }
     }
 }
 
-use libplatform::IdleTaskSupport;
-
-pub struct SourceLocation {} // Placeholder
-
-#[derive(Clone)]
-pub struct DefaultForegroundTaskRunner {
-    inner: Arc<DefaultForegroundTaskRunnerInner>,
+/// Marks a task of the runner as running, for the duration of the scope.
+pub struct RunTaskScope {
+    task_runner: Arc<DefaultForegroundTaskRunner>,
 }
 
-struct DefaultForegroundTaskRunnerInner {
-    terminated: Mutex<bool>,
-    mutex: base::platform::MutexWrapper,
-    event_loop_control: base::platform::ConditionVariable,
-    nesting_depth: Mutex<i32>,
-    task_queue: Mutex<VecDeque<TaskQueueEntry>>,
-    idle_task_support: IdleTaskSupport,
-    idle_task_queue: Mutex<Vec<Box<dyn FnOnce() + Send + Sync>>>, // IdleTask>,  //TODO Replace Box<dyn FnOnce...> with correct type
-    delayed_task_queue: Mutex<BinaryHeap<DelayedEntry>>,
-    time_function: fn() -> f64,
+impl RunTaskScope {
+    pub fn new(task_runner: Arc<DefaultForegroundTaskRunner>) -> Self {
+        task_runner.lock.lock().unwrap().nesting_depth += 1;
+        RunTaskScope { task_runner }
+    }
 }
 
-type TaskQueueEntry = (Nestability, Box<dyn FnOnce() + Send + Sync>); //Task>>
-#[derive(PartialEq, Eq, Copy, Clone)]
-enum Nestability {
-    Nestable,
-    NonNestable,
+impl Drop for RunTaskScope {
+    fn drop(&mut self) {
+        let mut state = self.task_runner.lock.lock().unwrap();
+        debug_assert!(state.nesting_depth > 0);
+        state.nesting_depth -= 1;
+    }
 }
 
 impl DefaultForegroundTaskRunner {
-    pub type TimeFunction = fn() -> f64;
-
-    pub struct RunTaskScope {
-        task_runner: DefaultForegroundTaskRunner,
-    }
-
-    impl RunTaskScope {
-        pub fn new(task_runner: DefaultForegroundTaskRunner) -> Self {
-            let mut nesting_depth = task_runner.inner.nesting_depth.lock().unwrap();
-            *nesting_depth += 1;
-            RunTaskScope { task_runner }
-        }
-    }
-
-    impl Drop for RunTaskScope {
-        fn drop(&mut self) {
-            let mut nesting_depth = self.task_runner.inner.nesting_depth.lock().unwrap();
-            *nesting_depth -= 1;
-        }
-    }
-
-    pub fn new(idle_task_support: IdleTaskSupport, time_function: fn() -> f64) -> Self {
+    pub fn new(idle_task_support: IdleTaskSupport, time_function: TimeFunction) -> Self {
         DefaultForegroundTaskRunner {
-            inner: Arc::new(DefaultForegroundTaskRunnerInner {
-                terminated: Mutex::new(false),
-                mutex: base::platform::MutexWrapper::new(),
-                event_loop_control: base::platform::ConditionVariable::new(),
-                nesting_depth: Mutex::new(0),
-                task_queue: Mutex::new(VecDeque::new()),
-                idle_task_support,
-                idle_task_queue: Mutex::new(Vec::new()),
-                delayed_task_queue: Mutex::new(BinaryHeap::new()),
-                time_function,
+            lock: Mutex::new(ForegroundState {
+                terminated: false,
+                nesting_depth: 0,
+                task_queue: VecDeque::new(),
+                idle_task_queue: VecDeque::new(),
+                delayed_task_queue: BinaryHeap::new(),
+                next_sequence_number: 0,
             }),
+            event_loop_control: Condvar::new(),
+            idle_task_support,
+            time_function,
         }
     }
 
+    /// Drops all queued tasks. Tasks posted afterwards are dropped as well.
     pub fn terminate(&self) {
-        let lock = self.inner.mutex.lock();
-        let mut terminated = self.inner.terminated.lock().unwrap();
-        *terminated = true;
-        self.inner.event_loop_control.notify_one();
-        drop(lock);
+        let (task_queue, idle_task_queue, delayed_task_queue) = {
+            let mut state = self.lock.lock().unwrap();
+            state.terminated = true;
+            self.event_loop_control.notify_all();
+            (
+                std::mem::take(&mut state.task_queue),
+                std::mem::take(&mut state.idle_task_queue),
+                std::mem::take(&mut state.delayed_task_queue),
+            )
+        };
+        // Tasks may post from their destructors, so drop them outside of the
+        // lock.
+        drop((task_queue, idle_task_queue, delayed_task_queue));
     }
 
-    pub fn pop_task_from_queue(
-        self: &DefaultForegroundTaskRunner,
-        wait_for_work: v8::MessageLoopBehavior,
-    ) -> Option<Box<dyn FnOnce() + Send + Sync>> { //Task>> {
-        let lock = self.inner.mutex.lock();
-        let task = self.pop_task_from_queue_locked(wait_for_work, lock);
-        task
-    }
-
-    fn pop_task_from_queue_locked(
-        &self,
-        wait_for_work: v8::MessageLoopBehavior,
-        mut lock: std::sync::MutexGuard<()>,
-    ) -> Option<Box<dyn FnOnce() + Send + Sync>> { //Task>> {
-        loop {
-            if let Some(task) = self.pop_task_from_delayed_queue_locked(&mut Nestability::Nestable) {
-                return Some(task);
-            }
-
-            if self.has_poppable_task_in_queue() {
-                let mut task_queue = self.inner.task_queue.lock().unwrap();
-                let (nestability, task) = task_queue.pop_front().unwrap();
-                return Some(task);
-            }
-
-            let terminated = *self.inner.terminated.lock().unwrap();
-            if terminated {
+    /// Returns the next task that may run at the current nesting level.
+    /// With `MessageLoopBehavior::kWaitForWork`, blocks until there is one or
+    /// the runner is terminated.
+    pub fn pop_task_from_queue(&self, wait_for_work: MessageLoopBehavior) -> Option<Box<dyn Task>> {
+        let mut state = self.lock.lock().unwrap();
+        state.move_expired_delayed_tasks(self.monotonically_increasing_time());
+        while !state.has_poppable_task_in_queue() {
+            if wait_for_work == MessageLoopBehavior::kDoNotWait || state.terminated {
                 return None;
             }
-
-            match wait_for_work {
-                v8::MessageLoopBehavior::Default => {
-                    lock = self.inner.event_loop_control.wait(lock);
-                }
-            }
+            state = self.wait_for_task_locked(state);
+            state.move_expired_delayed_tasks(self.monotonically_increasing_time());
         }
+        let index = if state.nesting_depth == 0 {
+            0
+        } else {
+            state
+                .task_queue
+                .iter()
+                .position(|(nestability, _)| *nestability == Nestability::kNestable)
+                .unwrap()
+        };
+        state.task_queue.remove(index).map(|(_, task)| task)
     }
 
-    pub fn pop_task_from_idle_queue(&self) -> Option<Box<dyn FnOnce() + Send + Sync>> { //IdleTask>> {
-        let mut idle_task_queue = self.inner.idle_task_queue.lock().unwrap();
-        idle_task_queue.pop()
+    pub fn pop_task_from_idle_queue(&self) -> Option<Box<dyn IdleTask>> {
+        self.lock.lock().unwrap().idle_task_queue.pop_front()
     }
 
     pub fn monotonically_increasing_time(&self) -> f64 {
-        (self.inner.time_function)()
+        (self.time_function)()
     }
 
-    fn wait_for_task_locked(&self) {
-        let lock = self.inner.mutex.lock();
-        let terminated = *self.inner.terminated.lock().unwrap();
-        if terminated {
-            return;
+    fn wait_for_task_locked<'a>(&self, state: MutexGuard<'a, ForegroundState>) -> MutexGuard<'a, ForegroundState> {
+        match state.delayed_task_queue.peek() {
+            Some(entry) => {
+                let wait_time = (entry.timeout_time - self.monotonically_increasing_time()).max(0.0);
+                self.event_loop_control
+                    .wait_timeout(state, Duration::from_secs_f64(wait_time))
+                    .unwrap()
+                    .0
+            }
+            None => self.event_loop_control.wait(state).unwrap(),
         }
-        self.inner.event_loop_control.wait(lock);
     }
 
-    fn post_task_locked(
-        &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //Task>,
-        nestability: Nestability,
-    ) -> Option<Box<dyn FnOnce() + Send + Sync>> {  //Task>> {
-        let mut terminated = self.inner.terminated.lock().unwrap();
-        if *terminated {
-            return Some(task);
+    fn post_task_locked(&self, state: &mut ForegroundState, task: Box<dyn Task>, nestability: Nestability) {
+        if state.terminated {
+            return;
         }
-
-        let mut task_queue = self.inner.task_queue.lock().unwrap();
-        task_queue.push_back((nestability, task));
-        self.inner.event_loop_control.notify_one();
-        None
+        state.task_queue.push_back((nestability, task));
+        self.event_loop_control.notify_one();
     }
 
     fn post_delayed_task_locked(
         &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //Task>,
+        state: &mut ForegroundState,
+        task: Box<dyn Task>,
         delay_in_seconds: f64,
         nestability: Nestability,
     ) {
+        debug_assert!(delay_in_seconds >= 0.0);
+        if state.terminated {
+            return;
+        }
         let timeout_time = self.monotonically_increasing_time() + delay_in_seconds;
-        let mut delayed_task_queue = self.inner.delayed_task_queue.lock().unwrap();
-        delayed_task_queue.push(DelayedEntry {
+        let sequence_number = state.next_sequence_number;
+        state.next_sequence_number += 1;
+        state.delayed_task_queue.push(DelayedEntry {
             timeout_time,
+            sequence_number,
             nestability,
             task,
         });
-    }
-
-    fn pop_task_from_delayed_queue_locked(
-        &self,
-        nestability: &mut Nestability,
-    ) -> Option<Box<dyn FnOnce() + Send + Sync>> { //Task>> {
-        let now = self.monotonically_increasing_time();
-        let mut delayed_task_queue = self.inner.delayed_task_queue.lock().unwrap();
-
-        if let Some(entry) = delayed_task_queue.peek() {
-            if entry.timeout_time <= now {
-                let entry = delayed_task_queue.pop().unwrap();
-                *nestability = entry.nestability;
-                return Some(entry.task);
-            }
-        }
-        None
-    }
-
-    fn has_poppable_task_in_queue(&self) -> bool {
-        let nesting_depth = *self.inner.nesting_depth.lock().unwrap();
-        let task_queue = self.inner.task_queue.lock().unwrap();
-
-        task_queue.iter().any(|(nestability, _)| match nestability {
-            Nestability::Nestable => true,
-            Nestability::NonNestable => nesting_depth == 0,
-        })
-    }
-
-    fn move_expired_delayed_tasks_locked(&self) -> Vec<Box<dyn FnOnce() + Send + Sync>> { //Task>> {
-        let now = self.monotonically_increasing_time();
-        let mut delayed_task_queue = self.inner.delayed_task_queue.lock().unwrap();
-        let mut expired_tasks = Vec::new();
-
-        while let Some(entry) = delayed_task_queue.peek() {
-            if entry.timeout_time <= now {
-                let entry = delayed_task_queue.pop().unwrap();
-                let terminated = *self.inner.terminated.lock().unwrap();
-                if terminated {
-                    expired_tasks.push(entry.task);
-                } else {
-                    let mut task_queue = self.inner.task_queue.lock().unwrap();
-                    task_queue.push_back((entry.nestability, entry.task));
-                }
-            } else {
-                break;
-            }
-        }
-
-        expired_tasks
+        self.event_loop_control.notify_one();
     }
 }
 
 impl TaskRunner for DefaultForegroundTaskRunner {
-    fn idle_tasks_enabled(&self) -> bool {
-        match self.inner.idle_task_support {
-            IdleTaskSupport::Enabled => true,
-            IdleTaskSupport::Disabled => false,
-        }
+    fn post_task(&self, task: Box<dyn Task>, _location: SourceLocation) {
+        let mut state = self.lock.lock().unwrap();
+        self.post_task_locked(&mut state, task, Nestability::kNestable);
     }
 
-    fn non_nestable_tasks_enabled(&self) -> bool {
-        true // Placeholder
+    fn post_non_nestable_task(&self, task: Box<dyn Task>, _location: SourceLocation) {
+        let mut state = self.lock.lock().unwrap();
+        self.post_task_locked(&mut state, task, Nestability::kNonNestable);
     }
 
-    fn post_task(&self, task: Box<dyn FnOnce()>) { // Placeholder, this is just an example of what a real implementation might look like.
-        let lock = self.inner.mutex.lock();
-        self.post_task_locked(Box::new(move || task()), Nestability::Nestable);
+    fn post_delayed_task(&self, task: Box<dyn Task>, delay_in_seconds: f64, _location: SourceLocation) {
+        let mut state = self.lock.lock().unwrap();
+        self.post_delayed_task_locked(&mut state, task, delay_in_seconds, Nestability::kNestable);
     }
-}
 
-impl DefaultForegroundTaskRunner {
-    fn post_task_impl(
-        &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //Task>,
-        location: &SourceLocation,
-    ) {
-        let lock = self.inner.mutex.lock();
-        self.post_task_locked(task, Nestability::Nestable);
+    fn post_non_nestable_delayed_task(&self, task: Box<dyn Task>, delay_in_seconds: f64, _location: SourceLocation) {
+        let mut state = self.lock.lock().unwrap();
+        self.post_delayed_task_locked(&mut state, task, delay_in_seconds, Nestability::kNonNestable);
     }
 
-    fn post_delayed_task_impl(
-        &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //Task>,
-        delay_in_seconds: f64,
-        location: &SourceLocation,
-    ) {
-        let lock = self.inner.mutex.lock();
-        self.post_delayed_task_locked(task, delay_in_seconds, Nestability::Nestable);
+    fn post_idle_task(&self, task: Box<dyn IdleTask>, _location: SourceLocation) {
+        assert_eq!(
+            self.idle_task_support,
+            IdleTaskSupport::kEnabled,
+            "idle tasks are disabled for this platform"
+        );
+        let mut state = self.lock.lock().unwrap();
+        if state.terminated {
+            return;
+        }
+        state.idle_task_queue.push_back(task);
     }
 
-    fn post_idle_task_impl(
-        &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //IdleTask>,
-        location: &SourceLocation,
-    ) {
-        let mut idle_task_queue = self.inner.idle_task_queue.lock().unwrap();
-        idle_task_queue.push(task);
+    fn idle_tasks_enabled(&self) -> bool {
+        self.idle_task_support == IdleTaskSupport::kEnabled
     }
 
-    fn post_non_nestable_task_impl(
-        &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //Task>,
-        location: &SourceLocation,
-    ) {
-        let lock = self.inner.mutex.lock();
-        self.post_task_locked(task, Nestability::NonNestable);
+    fn non_nestable_tasks_enabled(&self) -> bool {
+        true
     }
 
-    fn post_non_nestable_delayed_task_impl(
-        &self,
-        task: Box<dyn FnOnce() + Send + Sync>, //Task>,
-        delay_in_seconds: f64,
-        location: &SourceLocation,
-    ) {
-        let lock = self.inner.mutex.lock();
-        self.post_delayed_task_locked(task, delay_in_seconds, Nestability::NonNestable);
+    fn non_nestable_delayed_tasks_enabled(&self) -> bool {
+        true
     }
 }
 
-#[derive(PartialEq, PartialOrd)]
+/// A delayed task in the min-heap of the runner. Tasks with the same deadline
+/// keep the order in which they were posted.
 struct DelayedEntry {
     timeout_time: f64,
+    sequence_number: u64,
     nestability: Nestability,
-    task: Box<dyn FnOnce() + Send + Sync>, //Task>,
+    task: Box<dyn Task>,
+}
+
+impl PartialEq for DelayedEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
 impl Eq for DelayedEntry {}
 
+impl PartialOrd for DelayedEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Ord for DelayedEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Reverse the order to make it a min-heap
-        self.timeout_time.partial_cmp(&other.timeout_time).unwrap_or(Ordering::Equal).reverse()
+        // Reversed, so that `BinaryHeap` pops the earliest deadline first.
+        other
+            .timeout_time
+            .total_cmp(&self.timeout_time)
+            .then_with(|| other.sequence_number.cmp(&self.sequence_number))
     }
-}
\ No newline at end of file
+}
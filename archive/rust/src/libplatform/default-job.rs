@@ -2,237 +2,297 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::{Arc, Mutex, Condvar, Weak};
+use std::cell::{Cell, OnceCell};
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
-use std::thread;
-use std::time::Duration;
-use std::u8;
-use std::any::Any;
-
-pub trait JobDelegate {
-    fn notify_concurrency_increase(&mut self);
-    fn should_yield(&mut self) -> bool;
-    fn get_task_id(&self) -> u8;
-    fn is_joining_thread(&self) -> bool;
-}
-
-pub trait JobTask {
-    fn run(&mut self, delegate: &mut dyn JobDelegate);
-}
-
-pub trait Platform {
-    fn call_on_worker_thread(&mut self, priority: TaskPriority, task: Box<dyn Task>);
-    fn get_max_concurrency(&self) -> usize;
-}
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
 
-pub trait Task {
-    fn run(&mut self);
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub enum TaskPriority {
-  Highest,
-  High,
-  Normal,
-  Low,
-  Lowest,
-}
-
-pub trait JobHandle {
-    fn notify_concurrency_increase(&mut self);
-    fn join(&mut self);
-    fn cancel(&mut self);
-    fn cancel_and_detach(&mut self);
-    fn is_active(&self) -> bool;
-    fn is_valid(&self) -> bool;
-    fn update_priority_enabled(&self) -> bool;
-    fn update_priority(&mut self, priority: TaskPriority);
-}
+use crate::include::v8_platform::{JobDelegate, JobHandle, JobTask, Platform, Task, TaskPriority};
+use crate::include::v8_source_location::SourceLocation;
 
+/// The scheduling state of a job, shared by its handle and its workers.
 pub struct DefaultJobState {
-    platform: Box<dyn Platform>,
-    job_task: Mutex<Option<Box<dyn JobTask + Send>>>,
-    mutex: Mutex<DefaultJobStateInternal>,
-    priority: Mutex<TaskPriority>,
-    is_canceled: AtomicBool,
-    num_worker_threads: usize,
+    platform: Weak<dyn Platform>,
+    job_task: Box<dyn JobTask>,
+    weak_self: Weak<DefaultJobState>,
+    mutex: Mutex<JobCounters>,
+    /// Signaled when a worker returns from the job.
     worker_released_condition: Condvar,
+    is_canceled: AtomicBool,
+    /// One bit per task id currently handed out.
     assigned_task_ids: AtomicU32,
 }
 
-struct DefaultJobStateInternal {
-  active_workers: usize,
-  pending_tasks: usize,
+struct JobCounters {
+    priority: TaskPriority,
+    /// Number of workers running this job.
+    active_workers: usize,
+    /// Number of posted tasks that aren't running this job yet.
+    pending_tasks: usize,
+    /// Cap on the number of workers, the pool size plus one for a joining
+    /// thread.
+    num_worker_threads: usize,
 }
 
 impl DefaultJobState {
+    /// Task ids are bits of `assigned_task_ids`.
+    pub const K_MAX_WORKERS_PER_JOB: usize = 32;
+
+    /// The job posts its workers to `platform`, which it doesn't keep alive.
     pub fn new(
-        platform: Box<dyn Platform>,
-        job_task: Box<dyn JobTask + Send>,
+        platform: Weak<dyn Platform>,
+        job_task: Box<dyn JobTask>,
         priority: TaskPriority,
         num_worker_threads: usize,
     ) -> Arc<Self> {
-        Arc::new(Self {
+        Arc::new_cyclic(|weak_self| DefaultJobState {
             platform,
-            job_task: Mutex::new(Some(job_task)),
-            mutex: Mutex::new(DefaultJobStateInternal { active_workers: 0, pending_tasks: 0 }),
-            priority: Mutex::new(priority),
-            is_canceled: AtomicBool::new(false),
-            num_worker_threads,
+            job_task,
+            weak_self: weak_self.clone(),
+            mutex: Mutex::new(JobCounters {
+                priority,
+                active_workers: 0,
+                pending_tasks: 0,
+                num_worker_threads: num_worker_threads.min(Self::K_MAX_WORKERS_PER_JOB),
+            }),
             worker_released_condition: Condvar::new(),
+            is_canceled: AtomicBool::new(false),
             assigned_task_ids: AtomicU32::new(0),
         })
     }
 
-    fn notify_concurrency_increase(&self) {
-        let mut mutex = self.mutex.lock().unwrap();
-        mutex.pending_tasks += 1;
-        self.post_worker_task();
+    pub fn notify_concurrency_increase(&self) {
+        if self.is_canceled.load(Ordering::Relaxed) {
+            return;
+        }
+        let (num_tasks_to_post, priority) = {
+            let mut counters = self.mutex.lock().unwrap();
+            let max_concurrency = self.capped_max_concurrency(&counters, counters.active_workers);
+            (Self::reserve_pending_tasks(&mut counters, max_concurrency), counters.priority)
+        };
+        self.post_workers(priority, num_tasks_to_post);
     }
 
-    fn acquire_task_id(&self) -> u8 {
-        let id = self.assigned_task_ids.fetch_add(1, Ordering::Relaxed);
-        (id % (std::u8::MAX as u32)) as u8
+    /// Returns the smallest task id not in use by another worker.
+    pub fn acquire_task_id(&self) -> u8 {
+        let mut assigned_task_ids = self.assigned_task_ids.load(Ordering::Relaxed);
+        loop {
+            debug_assert!(assigned_task_ids.count_ones() < Self::K_MAX_WORKERS_PER_JOB as u32);
+            let task_id = (!assigned_task_ids).trailing_zeros();
+            let new_assigned_task_ids = assigned_task_ids | (1 << task_id);
+            match self.assigned_task_ids.compare_exchange_weak(
+                assigned_task_ids,
+                new_assigned_task_ids,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return task_id as u8,
+                Err(current) => assigned_task_ids = current,
+            }
+        }
     }
 
-    fn release_task_id(&self, _task_id: u8) {
-        //Not doing anything. Task ids are reused automatically.
-    }
+    pub fn release_task_id(&self, task_id: u8) {
+        let previous_task_ids = self.assigned_task_ids.fetch_and(!(1 << task_id), Ordering::Release);
+        debug_assert!(previous_task_ids & (1 << task_id) != 0);
+    }
+
+    /// Runs the job on the calling thread, alongside the workers, until max
+    /// concurrency drops to 0.
+    pub fn join(&self) {
+        let number_of_worker_threads = self.platform.upgrade().map_or(0, |platform| platform.number_of_worker_threads());
+        let num_tasks_to_post = {
+            let mut counters = self.mutex.lock().unwrap();
+            counters.priority = TaskPriority::kUserBlocking;
+            // Reserve a worker for the joining thread. GetMaxConcurrency() is
+            // ignored here, but wait_for_participation_opportunity() waits for
+            // workers to return if necessary so we don't exceed
+            // GetMaxConcurrency().
+            counters.num_worker_threads = number_of_worker_threads + 1;
+            counters.active_workers += 1;
+            let (guard, max_concurrency) = self.wait_for_participation_opportunity(counters);
+            counters = guard;
+            if max_concurrency == 0 {
+                return;
+            }
+            Self::reserve_pending_tasks(&mut counters, max_concurrency)
+        };
+        // Spawn more worker tasks if needed.
+        self.post_workers(TaskPriority::kUserBlocking, num_tasks_to_post);
 
-    fn join(&self) {
-        let mut mutex = self.mutex.lock().unwrap();
-        while mutex.active_workers > 0 || mutex.pending_tasks > 0 {
-            mutex = self.worker_released_condition.wait(mutex).unwrap();
+        let delegate = DefaultJobDelegate::new(self, true);
+        loop {
+            // Participate in job execution, as one active worker.
+            self.job_task.run(&delegate);
+            let counters = self.mutex.lock().unwrap();
+            if self.wait_for_participation_opportunity(counters).1 == 0 {
+                return;
+            }
         }
     }
 
-    fn cancel_and_wait(&self) {
+    pub fn cancel_and_wait(&self) {
+        let mut counters = self.mutex.lock().unwrap();
         self.is_canceled.store(true, Ordering::Relaxed);
-        self.join();
+        while counters.active_workers > 0 {
+            counters = self.worker_released_condition.wait(counters).unwrap();
+        }
     }
 
-    fn cancel_and_detach(&self) {
+    pub fn cancel_and_detach(&self) {
+        let _counters = self.mutex.lock().unwrap();
         self.is_canceled.store(true, Ordering::Relaxed);
-        // Detach means doing nothing more.
     }
 
-    fn is_active(&self) -> bool {
-        let mutex = self.mutex.lock().unwrap();
-        mutex.active_workers > 0 || mutex.pending_tasks > 0
+    pub fn is_active(&self) -> bool {
+        let counters = self.mutex.lock().unwrap();
+        self.job_task.get_max_concurrency(counters.active_workers) != 0 || counters.active_workers != 0
     }
 
-    fn can_run_first_task(&self) -> bool {
-        let mut mutex = self.mutex.lock().unwrap();
+    /// Must be called before running `job_task` for the first time. If it
+    /// returns true, then the worker thread must contribute and must call
+    /// `did_run_task`. Otherwise it must return.
+    pub fn can_run_first_task(&self) -> bool {
+        let mut counters = self.mutex.lock().unwrap();
+        counters.pending_tasks -= 1;
         if self.is_canceled.load(Ordering::Relaxed) {
             return false;
         }
-
-        if mutex.pending_tasks == 0 {
+        let max_concurrency = self.capped_max_concurrency(&counters, counters.active_workers);
+        if counters.active_workers >= max_concurrency {
             return false;
         }
-
-        mutex.pending_tasks -= 1;
-        mutex.active_workers += 1;
+        counters.active_workers += 1;
         true
     }
 
-    fn did_run_task(&self) -> bool {
-        let mut mutex = self.mutex.lock().unwrap();
-        if self.is_canceled.load(Ordering::Relaxed) {
-            mutex.active_workers -= 1;
-            self.worker_released_condition.notify_one();
-            return false;
-        }
-
-        let capped_max_concurrency = self.capped_max_concurrency(self.num_worker_threads);
-        if mutex.active_workers >= capped_max_concurrency {
-            mutex.active_workers -= 1;
-            self.worker_released_condition.notify_one();
-            return false;
-        }
-
-        self.post_worker_task();
+    /// Must be called after running `job_task`. Returns true if the worker
+    /// thread must run `job_task` again.
+    pub fn did_run_task(&self) -> bool {
+        let (num_tasks_to_post, priority) = {
+            let mut counters = self.mutex.lock().unwrap();
+            let max_concurrency = self.capped_max_concurrency(&counters, counters.active_workers - 1);
+            if self.is_canceled.load(Ordering::Relaxed) || counters.active_workers > max_concurrency {
+                counters.active_workers -= 1;
+                self.worker_released_condition.notify_one();
+                return false;
+            }
+            (Self::reserve_pending_tasks(&mut counters, max_concurrency), counters.priority)
+        };
+        // Post additional worker tasks to reach max concurrency in the case
+        // that it increased. This is not strictly necessary, since
+        // notify_concurrency_increase() should eventually be invoked. However,
+        // some users of post_job() batch work and tend to call
+        // notify_concurrency_increase() late. Posting here allows us to spawn
+        // new workers sooner.
+        self.post_workers(priority, num_tasks_to_post);
         true
     }
 
-    fn update_priority(&self, priority: TaskPriority) {
-        let mut current_priority = self.priority.lock().unwrap();
-        *current_priority = priority;
+    pub fn update_priority(&self, priority: TaskPriority) {
+        self.mutex.lock().unwrap().priority = priority;
     }
 
-    fn capped_max_concurrency(&self, worker_count: usize) -> usize {
-        std::cmp::min(self.platform.get_max_concurrency(), worker_count)
+    /// Returns GetMaxConcurrency() capped by the number of threads used by
+    /// this job.
+    fn capped_max_concurrency(&self, counters: &JobCounters, worker_count: usize) -> usize {
+        self.job_task.get_max_concurrency(worker_count).min(counters.num_worker_threads)
     }
 
-    fn call_on_worker_thread(&self, priority: TaskPriority, task: Box<dyn Task>) {
-        self.platform.call_on_worker_thread(priority, task);
+    /// Accounts for the tasks needed to reach `max_concurrency` and returns
+    /// their number.
+    fn reserve_pending_tasks(counters: &mut JobCounters, max_concurrency: usize) -> usize {
+        let scheduled = counters.active_workers + counters.pending_tasks;
+        if max_concurrency <= scheduled {
+            return 0;
+        }
+        let num_tasks_to_post = max_concurrency - scheduled;
+        counters.pending_tasks += num_tasks_to_post;
+        num_tasks_to_post
+    }
+
+    /// Waits until the joining thread, counted in `active_workers`, can run
+    /// the job without exceeding max concurrency. Returns the max
+    /// concurrency, or 0 if the job is done.
+    fn wait_for_participation_opportunity<'a>(
+        &self,
+        mut counters: MutexGuard<'a, JobCounters>,
+    ) -> (MutexGuard<'a, JobCounters>, usize) {
+        // Subtract one from active_workers since the current thread is not
+        // participating yet.
+        let mut max_concurrency = self.capped_max_concurrency(&counters, counters.active_workers - 1);
+        // Wait until we can participate in the job.
+        while counters.active_workers > max_concurrency && counters.active_workers > 1 {
+            counters = self.worker_released_condition.wait(counters).unwrap();
+            max_concurrency = self.capped_max_concurrency(&counters, counters.active_workers - 1);
+        }
+        if max_concurrency != 0 {
+            return (counters, max_concurrency);
+        }
+        // The joining thread is the last one out, which completes the job.
+        debug_assert_eq!(counters.active_workers, 1);
+        counters.active_workers = 0;
+        self.is_canceled.store(true, Ordering::Relaxed);
+        (counters, 0)
     }
 
-    fn post_worker_task(&self) {
-        let job_task_mutex = self.job_task.lock().unwrap();
-        let job_task = match job_task_mutex.as_ref() {
-            Some(task) => task,
-            None => return
+    fn post_workers(&self, priority: TaskPriority, num_tasks: usize) {
+        if num_tasks == 0 {
+            return;
+        }
+        let Some(platform) = self.platform.upgrade() else {
+            // The platform is shutting down; the tasks would never run.
+            return;
         };
-        let priority = *self.priority.lock().unwrap();
-        let weak_self = Arc::downgrade(self);
-
-        let worker = DefaultJobWorker::new(weak_self, job_task);
-        self.call_on_worker_thread(priority, Box::new(worker));
+        for _ in 0..num_tasks {
+            let worker = DefaultJobWorker::new(self.weak_self.clone());
+            platform.post_task_on_worker_thread(priority, Box::new(worker), SourceLocation::new());
+        }
     }
 }
 
 impl Drop for DefaultJobState {
     fn drop(&mut self) {
-        // Ensure the job is canceled before dropping to avoid dangling pointers.
-        self.cancel_and_wait();
+        let counters = self.mutex.get_mut().unwrap();
+        debug_assert_eq!(counters.active_workers, 0);
     }
 }
 
-impl DefaultJobState {
-    pub fn get_delegate(self: &Arc<Self>, is_joining_thread: bool) -> DefaultJobStateJobDelegate {
-        DefaultJobStateJobDelegate::new(Arc::clone(self), is_joining_thread)
-    }
-}
-
-pub struct DefaultJobStateJobDelegate {
-    outer: Arc<DefaultJobState>,
-    task_id: u8,
+/// The delegate passed to each run of the job task. Its task id is acquired
+/// lazily and released when the run is over.
+pub struct DefaultJobDelegate<'a> {
+    outer: &'a DefaultJobState,
+    task_id: OnceCell<u8>,
     is_joining_thread: bool,
-    was_told_to_yield: Mutex<bool>
+    was_told_to_yield: Cell<bool>,
 }
 
-const K_INVALID_TASK_ID: u8 = std::u8::MAX;
-
-impl DefaultJobStateJobDelegate {
-    pub fn new(outer: Arc<DefaultJobState>, is_joining_thread: bool) -> Self {
-        DefaultJobStateJobDelegate {
+impl<'a> DefaultJobDelegate<'a> {
+    pub fn new(outer: &'a DefaultJobState, is_joining_thread: bool) -> Self {
+        DefaultJobDelegate {
             outer,
-            task_id: K_INVALID_TASK_ID,
+            task_id: OnceCell::new(),
             is_joining_thread,
-            was_told_to_yield: Mutex::new(false),
+            was_told_to_yield: Cell::new(false),
         }
     }
 }
 
-impl JobDelegate for DefaultJobStateJobDelegate {
-    fn notify_concurrency_increase(&mut self) {
-        self.outer.notify_concurrency_increase();
+impl JobDelegate for DefaultJobDelegate<'_> {
+    fn should_yield(&self) -> bool {
+        // After should_yield() returned true, the job is expected to return
+        // and not call should_yield() again.
+        debug_assert!(!self.was_told_to_yield.get());
+        // Thread-safe but may return an outdated result.
+        self.was_told_to_yield.set(self.outer.is_canceled.load(Ordering::Relaxed));
+        self.was_told_to_yield.get()
     }
 
-    fn should_yield(&mut self) -> bool {
-        let mut was_told_to_yield = self.was_told_to_yield.lock().unwrap();
-
-        if *was_told_to_yield {
-           panic!("ShouldYield called twice")
-        }
-
-        *was_told_to_yield |= self.outer.is_canceled.load(Ordering::Relaxed);
-        *was_told_to_yield
+    fn notify_concurrency_increase(&self) {
+        self.outer.notify_concurrency_increase();
     }
 
     fn get_task_id(&self) -> u8 {
-        self.task_id
+        *self.task_id.get_or_init(|| self.outer.acquire_task_id())
     }
 
     fn is_joining_thread(&self) -> bool {
@@ -240,95 +300,103 @@ impl JobDelegate for DefaultJobStateJobDelegate {
     }
 }
 
-impl Drop for DefaultJobStateJobDelegate {
+impl Drop for DefaultJobDelegate<'_> {
     fn drop(&mut self) {
-        if self.task_id != K_INVALID_TASK_ID {
-            self.outer.release_task_id(self.task_id);
+        if let Some(&task_id) = self.task_id.get() {
+            self.outer.release_task_id(task_id);
         }
     }
 }
 
+/// The `JobHandle` returned by `NewDefaultJobHandle`. It must be joined or
+/// canceled before it is dropped.
 pub struct DefaultJobHandle {
-    state: Arc<DefaultJobState>,
+    state: Option<Arc<DefaultJobState>>,
 }
 
 impl DefaultJobHandle {
     pub fn new(state: Arc<DefaultJobState>) -> Self {
-        DefaultJobHandle { state }
+        DefaultJobHandle { state: Some(state) }
+    }
+
+    fn state(&self) -> &Arc<DefaultJobState> {
+        self.state.as_ref().expect("the job was already joined or canceled")
     }
 }
 
 impl JobHandle for DefaultJobHandle {
-    fn notify_concurrency_increase(&mut self) {
-        self.state.notify_concurrency_increase();
+    fn notify_concurrency_increase(&self) {
+        self.state().notify_concurrency_increase();
     }
 
     fn join(&mut self) {
-        self.state.join();
+        self.state().join();
+        self.state = None;
     }
 
     fn cancel(&mut self) {
-        self.state.cancel_and_wait();
+        self.state().cancel_and_wait();
+        self.state = None;
     }
 
     fn cancel_and_detach(&mut self) {
-        self.state.cancel_and_detach();
+        self.state().cancel_and_detach();
+        self.state = None;
     }
 
     fn is_active(&self) -> bool {
-        self.state.is_active()
+        self.state().is_active()
     }
 
     fn is_valid(&self) -> bool {
-        true // Always valid as long as the struct exists
+        self.state.is_some()
     }
 
     fn update_priority_enabled(&self) -> bool {
         true
     }
 
-    fn update_priority(&mut self, priority: TaskPriority) {
-        self.state.update_priority(priority);
+    fn update_priority(&self, new_priority: TaskPriority) {
+        self.state().update_priority(new_priority);
     }
 }
 
+impl Drop for DefaultJobHandle {
+    fn drop(&mut self) {
+        debug_assert!(
+            self.state.is_none() || std::thread::panicking(),
+            "a job handle must be joined or canceled before it is dropped"
+        );
+    }
+}
+
+/// The task posted to a worker thread to run a job. It doesn't keep the job
+/// alive; a job that is gone by the time the task runs is skipped.
 pub struct DefaultJobWorker {
     state: Weak<DefaultJobState>,
-    job_task: *mut dyn JobTask,
 }
 
 impl DefaultJobWorker {
-    pub fn new(state: Weak<DefaultJobState>, job_task: &dyn JobTask) -> Self {
-        DefaultJobWorker {
-            state,
-            job_task: job_task as *const dyn JobTask as *mut dyn JobTask,
-        }
+    pub fn new(state: Weak<DefaultJobState>) -> Self {
+        DefaultJobWorker { state }
     }
 }
 
 impl Task for DefaultJobWorker {
     fn run(&mut self) {
-        let shared_state = match self.state.upgrade() {
-            Some(state) => state,
-            None => return,
+        let Some(shared_state) = self.state.upgrade() else {
+            return;
         };
-
         if !shared_state.can_run_first_task() {
             return;
         }
-
         loop {
-            let mut delegate = shared_state.get_delegate(false);
-            unsafe {
-                (*self.job_task).run(&mut delegate);
-            }
-
+            let delegate = DefaultJobDelegate::new(&shared_state, false);
+            shared_state.job_task.run(&delegate);
+            drop(delegate);
             if !shared_state.did_run_task() {
                 break;
             }
         }
     }
 }
-
-unsafe impl Send for DefaultJobWorker {}
-unsafe impl Sync for DefaultJobWorker {}
\ No newline at end of file
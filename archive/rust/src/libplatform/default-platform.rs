@@ -2,599 +2,493 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::cmp;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
-use std::{collections::HashMap, thread};
-
-//use crate::base::debug::stack_trace::StackTrace; // Assuming a similar Rust implementation
-//use crate::base::logging; // Assuming a similar Rust logging implementation
-//use crate::base::page_allocator::PageAllocator; // Assuming a similar Rust implementation
-//use crate::base::platform::platform; // Assuming a similar Rust platform abstraction
-//use crate::base::platform::time; // Assuming a similar Rust time abstraction
-//use crate::base::sys_info; // Assuming a similar Rust system info abstraction
-//use crate::libplatform::default_foreground_task_runner::DefaultForegroundTaskRunner; // Assuming a similar Rust implementation
-//use crate::libplatform::default_job::DefaultJob; // Assuming a similar Rust implementation
-//use crate::libplatform::default_worker_threads_task_runner::DefaultWorkerThreadsTaskRunner; // Assuming a similar Rust implementation
-
-/// Represents a task to be executed.
-trait Task {
-    fn run(&mut self);
-}
-
-trait IdleTask {
-    fn run(&mut self, deadline_in_seconds: f64);
-}
-
-trait JobTask {
-    fn run(&mut self, job_handle: &dyn JobHandle, worker_id: usize);
-}
-
-trait TaskRunner {
-    fn post_task(&self, task: Box<dyn Task + Send + Sync>);
-    fn post_delayed_task(&self, task: Box<dyn Task + Send + Sync>, delay_in_seconds: f64);
-}
-
-trait JobHandle {
-    fn is_cancelled(&self) -> bool;
-    fn cancel(&self);
-    fn join(&self);
-}
-
-enum TaskPriority {
-    kBackground,
-    kUserVisible,
-    kBestEffort,
-}
-
-enum IdleTaskSupport {
-    kEnabled,
-    kDisabled,
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Instant;
+
+use crate::base::page_allocator::{get_platform_page_allocator, PageAllocator};
+use crate::include::libplatform::libplatform::{
+    IdleTaskSupport, InProcessStackDumping, MessageLoopBehavior, PriorityMode,
+};
+use crate::include::v8_platform::{
+    Isolate, JobHandle, JobTask, Platform, Task, TaskPriority, TaskRunner, TracingController,
+};
+use crate::include::v8_source_location::SourceLocation;
+use crate::libplatform::default_foreground_task_runner::{DefaultForegroundTaskRunner, RunTaskScope};
+use crate::libplatform::default_job::{DefaultJobHandle, DefaultJobState};
+use crate::libplatform::default_worker_threads_task_runner::DefaultWorkerThreadsTaskRunner;
+use crate::libplatform::delayed_task_queue::TimeFunction;
+use crate::libplatform::worker_thread::ThreadPriority;
+
+/// The upper bound of worker threads per priority.
+pub const K_MAX_THREAD_POOL_SIZE: usize = 16;
+
+/// Returns a new instance of the default v8::Platform implementation.
+///
+/// |thread_pool_size| is the number of worker threads to allocate for
+/// background jobs. If a value of zero is passed, a suitable default based on
+/// the current number of processors online will be chosen.
+/// If |idle_task_support| is enabled then the platform will accept idle
+/// tasks (IdleTasksEnabled will return true) and will rely on the embedder
+/// calling v8::platform::RunIdleTasks to process the idle tasks.
+/// If |tracing_controller| is None, the default platform will create a
+/// v8::platform::TracingController instance and use it.
+/// If |priority_mode| is PriorityMode::kApply, the default platform will use
+/// multiple task queues executed by threads different system-level priorities
+/// (where available) to schedule tasks.
+pub fn new_default_platform(
+    thread_pool_size: i32,
+    idle_task_support: IdleTaskSupport,
+    in_process_stack_dumping: InProcessStackDumping,
+    tracing_controller: Option<Arc<TracingController>>,
+    priority_mode: PriorityMode,
+) -> Arc<DefaultPlatform> {
+    // Panics already print a backtrace when RUST_BACKTRACE is set, there is
+    // no signal handler to install.
+    let _ = in_process_stack_dumping;
+    DefaultPlatform::new(
+        get_actual_thread_pool_size(thread_pool_size),
+        idle_task_support,
+        tracing_controller,
+        priority_mode,
+    )
 }
 
-enum InProcessStackDumping {
-    kEnabled,
-    kDisabled,
+/// The same as NewDefaultPlatform but disables the worker thread pool.
+/// It must be used with the --single-threaded V8 flag.
+pub fn new_single_threaded_default_platform(
+    idle_task_support: IdleTaskSupport,
+    in_process_stack_dumping: InProcessStackDumping,
+    tracing_controller: Option<Arc<TracingController>>,
+) -> Arc<DefaultPlatform> {
+    let _ = in_process_stack_dumping;
+    DefaultPlatform::new(0, idle_task_support, tracing_controller, PriorityMode::kDontApply)
+}
+
+/// Returns a new instance of the default v8::JobHandle implementation.
+///
+/// The job will be executed by spawning up to |num_worker_threads| many worker
+/// threads on the provided |platform| with the given |priority|.
+pub fn new_default_job_handle(
+    platform: &Arc<dyn Platform>,
+    priority: TaskPriority,
+    job_task: Box<dyn JobTask>,
+    num_worker_threads: usize,
+) -> Box<dyn JobHandle> {
+    let state = DefaultJobState::new(Arc::downgrade(platform), job_task, priority, num_worker_threads);
+    Box::new(DefaultJobHandle::new(state))
 }
 
-enum MessageLoopBehavior {
-    kWaitForWork,
-    kDoNotWait,
+/// Pumps the message loop for the given isolate.
+///
+/// The caller has to make sure that this is called from the right thread.
+/// Returns true if a task was executed, and false otherwise. If the call to
+/// PumpMessageLoop is nested within another call to PumpMessageLoop, only
+/// nestable tasks may run. Otherwise, any task may run. Unless requested through
+/// the |behavior| parameter, this call does not block if no task is pending.
+pub fn pump_message_loop(platform: &DefaultPlatform, isolate: *mut Isolate, behavior: MessageLoopBehavior) -> bool {
+    platform.pump_message_loop(isolate, behavior)
 }
 
-enum PriorityMode {
-    kNormal,
-    kHigh,
+/// Runs pending idle tasks for at most |idle_time_in_seconds| seconds.
+///
+/// The caller has to make sure that this is called from the right thread.
+/// This call does not block if no task is pending.
+pub fn run_idle_tasks(platform: &DefaultPlatform, isolate: *mut Isolate, idle_time_in_seconds: f64) {
+    platform.run_idle_tasks(isolate, idle_time_in_seconds);
 }
 
-// Placeholder for v8::Isolate
-struct Isolate {}
-
-// Placeholder for v8::TracingController
-struct TracingController {}
-
-struct SourceLocation {}
-
-type StackTracePrinter = fn();
-
-// Assuming this is a redefinition of v8::Platform
-trait Platform {
-    fn get_foreground_task_runner(&self, isolate: &Isolate, priority: TaskPriority) -> Arc<dyn TaskRunner + Send + Sync>;
-    fn post_task_on_worker_thread(&self, priority: TaskPriority, task: Box<dyn Task + Send + Sync>, location: SourceLocation);
-    fn post_delayed_task_on_worker_thread(&self, priority: TaskPriority, task: Box<dyn Task + Send + Sync>, delay_in_seconds: f64, location: SourceLocation);
-    fn idle_tasks_enabled(&self, isolate: &Isolate) -> bool;
-    fn create_job(&self, priority: TaskPriority, job_task: Box<dyn JobTask + Send + Sync>, location: SourceLocation) -> Box<dyn JobHandle>;
-    fn monotonically_increasing_time(&self) -> f64;
-    fn current_clock_time_millis(&self) -> f64;
-    fn get_tracing_controller(&self) -> &TracingController;
-    fn set_tracing_controller(&mut self, tracing_controller: Box<TracingController>);
-    fn number_of_worker_threads(&self) -> usize;
-    fn get_stack_trace_printer(&self) -> StackTracePrinter;
-    //fn get_page_allocator(&self) -> &PageAllocator;
-    //fn get_thread_isolated_allocator(&self) -> Option<&ThreadIsolatedAllocator>;
-    fn notify_isolate_shutdown(&self, isolate: &Isolate);
+/// Notifies the given platform about the Isolate getting deleted soon. Has to be
+/// called for all Isolates which are deleted - unless we're shutting down the
+/// platform.
+pub fn notify_isolate_shutdown(platform: &DefaultPlatform, isolate: *mut Isolate) {
+    platform.notify_isolate_shutdown(isolate);
 }
 
-const K_MAX_THREAD_POOL_SIZE: usize = 16;
-
 fn get_actual_thread_pool_size(thread_pool_size: i32) -> usize {
-    assert!(thread_pool_size >= 0);
-    let mut thread_pool_size = thread_pool_size as usize;
-    if thread_pool_size < 1 {
-        //thread_pool_size = sys_info::number_of_processors() - 1;
-        thread_pool_size = num_cpus::get() - 1; //Using num_cpus crate as alternative
-    }
-    cmp::max(cmp::min(thread_pool_size, K_MAX_THREAD_POOL_SIZE), 1)
-}
-
-struct DefaultPlatform {
+    debug_assert!(thread_pool_size >= 0);
+    let thread_pool_size = if thread_pool_size < 1 {
+        std::thread::available_parallelism().map_or(1, |processors| processors.get()).saturating_sub(1)
+    } else {
+        thread_pool_size as usize
+    };
+    thread_pool_size.clamp(1, K_MAX_THREAD_POOL_SIZE)
+}
+
+/// Seconds on the monotonic clock, counted from the first call in the
+/// process.
+pub fn default_time_function() -> f64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
+/// The default v8::Platform. Background tasks run on pools of worker threads,
+/// one per priority if priorities are applied. Foreground tasks of each
+/// isolate are queued until the embedder pumps the isolate's message loop.
+pub struct DefaultPlatform {
+    /// Jobs post their workers through a weak reference to the platform.
+    weak_self: Weak<DefaultPlatform>,
     thread_pool_size: usize,
     idle_task_support: IdleTaskSupport,
-    tracing_controller: Box<TracingController>,
-    //page_allocator: Box<PageAllocator>,
     priority_mode: PriorityMode,
-    foreground_task_runner_map: Mutex<HashMap<*const Isolate, Arc<DefaultForegroundTaskRunner>>>,
-    worker_threads_task_runners: [Option<Arc<DefaultWorkerThreadsTaskRunner>>; 4], // Assuming 4 priority levels
-    lock: Mutex<()>,
-    time_function_for_testing: Option<fn() -> f64>,
-    //thread_isolated_allocator: ThreadIsolatedAllocator, //Not implemented
+    /// One runner, or one per priority with PriorityMode::kApply. Empty for the
+    /// single-threaded platform.
+    worker_threads_task_runners: Vec<Arc<DefaultWorkerThreadsTaskRunner>>,
+    lock: Mutex<PlatformState>,
+    tracing_controller: Arc<TracingController>,
+}
+
+struct PlatformState {
+    /// Keyed by the isolate's address.
+    foreground_task_runner_map: HashMap<usize, Arc<DefaultForegroundTaskRunner>>,
+    time_function_for_testing: Option<TimeFunction>,
 }
 
 impl DefaultPlatform {
-    fn new(
-        thread_pool_size: i32,
+    pub fn new(
+        thread_pool_size: usize,
         idle_task_support: IdleTaskSupport,
-        tracing_controller: Box<TracingController>,
+        tracing_controller: Option<Arc<TracingController>>,
         priority_mode: PriorityMode,
-    ) -> Self {
-        let thread_pool_size = get_actual_thread_pool_size(thread_pool_size);
-        let mut platform = DefaultPlatform {
+    ) -> Arc<Self> {
+        let num_worker_runners = match (thread_pool_size, priority_mode) {
+            (0, _) => 0,
+            (_, PriorityMode::kApply) => TaskPriority::kMaxPriority as usize + 1,
+            (_, PriorityMode::kDontApply) => 1,
+        };
+        let worker_threads_task_runners = (0..num_worker_runners)
+            .map(|index| {
+                let priority = match priority_mode {
+                    PriorityMode::kApply => Self::thread_priority_from_index(index),
+                    PriorityMode::kDontApply => ThreadPriority::kDefault,
+                };
+                Arc::new(DefaultWorkerThreadsTaskRunner::new(
+                    thread_pool_size,
+                    Arc::new(default_time_function),
+                    priority,
+                ))
+            })
+            .collect();
+        Arc::new_cyclic(|weak_self| DefaultPlatform {
+            weak_self: weak_self.clone(),
             thread_pool_size,
             idle_task_support,
-            tracing_controller,
-            //page_allocator: Box::new(PageAllocator::new()), // Assuming a constructor
             priority_mode,
-            foreground_task_runner_map: Mutex::new(HashMap::new()),
-            worker_threads_task_runners: [None, None, None, None],
-            lock: Mutex::new(()),
-            time_function_for_testing: None,
-            //thread_isolated_allocator: ThreadIsolatedAllocator::new(), //Not implemented
+            worker_threads_task_runners,
+            lock: Mutex::new(PlatformState {
+                foreground_task_runner_map: HashMap::new(),
+                time_function_for_testing: None,
+            }),
+            tracing_controller: tracing_controller.unwrap_or_else(|| Arc::new(TracingController::new())),
+        })
+    }
+
+    /// Replaces the clock of the foreground task runners. Has to be called
+    /// right after the construction of the platform.
+    pub fn set_time_function_for_testing(&self, time_function: TimeFunction) {
+        let mut state = self.lock.lock().unwrap();
+        debug_assert!(state.foreground_task_runner_map.is_empty());
+        state.time_function_for_testing = Some(time_function);
+    }
+
+    /// See `v8::platform::PumpMessageLoop`.
+    pub fn pump_message_loop(&self, isolate: *mut Isolate, wait_for_work: MessageLoopBehavior) -> bool {
+        let failed_result = wait_for_work == MessageLoopBehavior::kWaitForWork;
+        let Some(task_runner) = self.existing_foreground_task_runner(isolate) else {
+            return failed_result;
         };
-        //controller->Initialize(nullptr);  // Assuming tracing controller initialization is handled elsewhere or not needed.
-        if platform.thread_pool_size > 0 {
-            platform.ensure_background_task_runner_initialized();
-        }
-        platform
-    }
-
-    fn new_single_threaded(
-        idle_task_support: IdleTaskSupport,
-        tracing_controller: Box<TracingController>,
-    ) -> Self {
-        DefaultPlatform {
-            thread_pool_size: 0,
-            idle_task_support,
-            tracing_controller,
-            //page_allocator: Box::new(PageAllocator::new()), // Assuming a constructor
-            priority_mode: PriorityMode::kNormal,
-            foreground_task_runner_map: Mutex::new(HashMap::new()),
-            worker_threads_task_runners: [None, None, None, None],
-            lock: Mutex::new(()),
-            time_function_for_testing: None,
-            //thread_isolated_allocator: ThreadIsolatedAllocator::new(), //Not implemented
-        }
-    }
-
-    fn pump_message_loop(&self, isolate: &Isolate, wait_for_work: MessageLoopBehavior) -> bool {
-        let failed_result = matches!(wait_for_work, MessageLoopBehavior::kWaitForWork);
-        let task_runner: Arc<DefaultForegroundTaskRunner>;
-        {
-            let guard = self.foreground_task_runner_map.lock().unwrap();
-            let isolate_ptr = isolate as *const Isolate;
-            match guard.get(&isolate_ptr) {
-                Some(tr) => task_runner = tr.clone(),
-                None => return failed_result,
-            }
-        }
-
-        let mut task = task_runner.pop_task_from_queue(wait_for_work);
-        if task.is_none() {
+        let Some(mut task) = task_runner.pop_task_from_queue(wait_for_work) else {
             return failed_result;
-        }
-
-        let _scope = DefaultForegroundTaskRunner::RunTaskScope {task_runner: task_runner.clone()};
-        task.unwrap().run();
+        };
+        let _scope = RunTaskScope::new(task_runner);
+        task.run();
         true
     }
 
-    fn run_idle_tasks(&self, isolate: &Isolate, idle_time_in_seconds: f64) {
-        match self.idle_task_support {
-            IdleTaskSupport::kDisabled => return,
-            IdleTaskSupport::kEnabled => {}
-        }
-
-        let task_runner: Arc<DefaultForegroundTaskRunner>;
-        {
-            let guard = self.foreground_task_runner_map.lock().unwrap();
-            let isolate_ptr = isolate as *const Isolate;
-            match guard.get(&isolate_ptr) {
-                Some(tr) => task_runner = tr.clone(),
-                None => return,
-            }
-        }
-
+    /// See `v8::platform::RunIdleTasks`.
+    pub fn run_idle_tasks(&self, isolate: *mut Isolate, idle_time_in_seconds: f64) {
+        debug_assert_eq!(self.idle_task_support, IdleTaskSupport::kEnabled);
+        let Some(task_runner) = self.existing_foreground_task_runner(isolate) else {
+            return;
+        };
         let deadline_in_seconds = self.monotonically_increasing_time() + idle_time_in_seconds;
-
         while deadline_in_seconds > self.monotonically_increasing_time() {
-            let mut task = task_runner.pop_task_from_idle_queue();
-            if task.is_none() {
+            let Some(mut task) = task_runner.pop_task_from_idle_queue() else {
                 return;
-            }
-            let _scope = DefaultForegroundTaskRunner::RunTaskScope { task_runner: task_runner.clone() };
-            task.unwrap().run(deadline_in_seconds);
+            };
+            let _scope = RunTaskScope::new(Arc::clone(&task_runner));
+            task.run(deadline_in_seconds);
         }
     }
 
-    fn ensure_background_task_runner_initialized(&mut self) {
-        assert!(self.worker_threads_task_runners[0].is_none());
-        for i in 0..self.num_worker_runners() {
-            let priority = self.priority_from_index(i);
-            self.worker_threads_task_runners[i] = Some(Arc::new(DefaultWorkerThreadsTaskRunner::new(
-                self.thread_pool_size,
-                self.time_function_for_testing.unwrap_or(default_time_function),
-                priority,
-            )));
+    /// See `v8::platform::NotifyIsolateShutdown`.
+    pub fn notify_isolate_shutdown(&self, isolate: *mut Isolate) {
+        let task_runner = self.lock.lock().unwrap().foreground_task_runner_map.remove(&(isolate as usize));
+        if let Some(task_runner) = task_runner {
+            task_runner.terminate();
         }
-        assert!(self.worker_threads_task_runners[0].is_some());
-    }
-
-    fn set_time_function_for_testing(&mut self, time_function: fn() -> f64) {
-        let _guard = self.lock.lock().unwrap();
-        self.time_function_for_testing = Some(time_function);
-        assert!(self.foreground_task_runner_map.lock().unwrap().is_empty());
     }
 
-    fn num_worker_runners(&self) -> usize {
-        4 // Assuming 4 priority levels
+    fn existing_foreground_task_runner(&self, isolate: *mut Isolate) -> Option<Arc<DefaultForegroundTaskRunner>> {
+        self.lock.lock().unwrap().foreground_task_runner_map.get(&(isolate as usize)).cloned()
     }
 
-    fn priority_from_index(&self, index: usize) -> TaskPriority {
+    fn thread_priority_from_index(index: usize) -> ThreadPriority {
         match index {
-            0 => TaskPriority::kBackground,
-            1 => TaskPriority::kUserVisible,
-            2 => TaskPriority::kBestEffort,
-            _ => TaskPriority::kBestEffort, // Default case, adjust as needed
-        }
-    }
-
-    fn priority_to_index(&self, priority: TaskPriority) -> usize {
-        match priority {
-            TaskPriority::kBackground => 0,
-            TaskPriority::kUserVisible => 1,
-            TaskPriority::kBestEffort => 2,
+            0 => ThreadPriority::kBestEffort,
+            1 => ThreadPriority::kUserVisible,
+            _ => ThreadPriority::kUserBlocking,
         }
     }
 
-    fn post_task_on_worker_thread_impl(
-        &self,
-        priority: TaskPriority,
-        task: Box<dyn Task + Send + Sync>,
-        _location: SourceLocation,
-    ) {
-        let index = self.priority_to_index(priority);
-        let runner = self.worker_threads_task_runners[index].as_ref().unwrap();
-        runner.post_task(task);
-    }
-
-    fn post_delayed_task_on_worker_thread_impl(
-        &self,
-        priority: TaskPriority,
-        task: Box<dyn Task + Send + Sync>,
-        delay_in_seconds: f64,
-        _location: SourceLocation,
-    ) {
-        let index = self.priority_to_index(priority);
-        let runner = self.worker_threads_task_runners[index].as_ref().unwrap();
-        runner.post_delayed_task(task, delay_in_seconds);
+    fn worker_threads_task_runner(&self, priority: TaskPriority) -> &DefaultWorkerThreadsTaskRunner {
+        let index = match self.priority_mode {
+            PriorityMode::kApply => priority as usize,
+            PriorityMode::kDontApply => 0,
+        };
+        self.worker_threads_task_runners
+            .get(index)
+            .expect("the single-threaded platform has no worker threads")
     }
 }
 
-impl Drop for DefaultPlatform {
-    fn drop(&mut self) {
-        let _guard = self.lock.lock().unwrap();
-        if self.worker_threads_task_runners[0].is_some() {
-            for i in 0..self.num_worker_runners() {
-                if let Some(runner) = &self.worker_threads_task_runners[i] {
-                    runner.terminate();
-                }
-            }
-        }
-
-        let mut foreground_runners = self.foreground_task_runner_map.lock().unwrap();
-        for (_, runner) in foreground_runners.iter() {
-            runner.terminate();
-        }
+impl Platform for DefaultPlatform {
+    fn get_page_allocator(&self) -> Option<&dyn PageAllocator> {
+        Some(get_platform_page_allocator())
     }
-}
 
-fn default_time_function() -> f64 {
-    let now = Instant::now(); // Replace with a more precise time source if needed
-    let duration = now.elapsed();
-    duration.as_secs_f64()
-}
+    fn number_of_worker_threads(&self) -> usize {
+        self.thread_pool_size
+    }
 
-impl Platform for DefaultPlatform {
-    fn get_foreground_task_runner(&self, isolate: &Isolate, priority: TaskPriority) -> Arc<dyn TaskRunner + Send + Sync> {
-        let mut guard = self.foreground_task_runner_map.lock().unwrap();
-        let isolate_ptr = isolate as *const Isolate;
-        if !guard.contains_key(&isolate_ptr) {
-            let task_runner = Arc::new(DefaultForegroundTaskRunner::new(
-                self.idle_task_support,
-                self.time_function_for_testing.unwrap_or(default_time_function),
-            ));
-            guard.insert(isolate_ptr, task_runner.clone());
-        }
-        guard.get(&isolate_ptr).unwrap().clone()
+    fn get_foreground_task_runner(&self, isolate: *mut Isolate, _priority: TaskPriority) -> Arc<dyn TaskRunner> {
+        let mut state = self.lock.lock().unwrap();
+        let time_function = state
+            .time_function_for_testing
+            .clone()
+            .unwrap_or_else(|| Arc::new(default_time_function));
+        let task_runner = state
+            .foreground_task_runner_map
+            .entry(isolate as usize)
+            .or_insert_with(|| Arc::new(DefaultForegroundTaskRunner::new(self.idle_task_support, time_function)));
+        Arc::clone(task_runner) as Arc<dyn TaskRunner>
     }
 
-    fn post_task_on_worker_thread(&self, priority: TaskPriority, task: Box<dyn Task + Send + Sync>, location: SourceLocation) {
-        self.post_task_on_worker_thread_impl(priority, task, location);
+    fn post_task_on_worker_thread(&self, priority: TaskPriority, task: Box<dyn Task>, location: SourceLocation) {
+        self.worker_threads_task_runner(priority).post_task(task, location);
     }
 
-    fn post_delayed_task_on_worker_thread(&self, priority: TaskPriority, task: Box<dyn Task + Send + Sync>, delay_in_seconds: f64, location: SourceLocation) {
-        self.post_delayed_task_on_worker_thread_impl(priority, task, delay_in_seconds, location);
+    fn post_delayed_task_on_worker_thread(
+        &self,
+        priority: TaskPriority,
+        task: Box<dyn Task>,
+        delay_in_seconds: f64,
+        location: SourceLocation,
+    ) {
+        self.worker_threads_task_runner(priority)
+            .post_delayed_task(task, delay_in_seconds, location);
     }
 
-    fn idle_tasks_enabled(&self, _isolate: &Isolate) -> bool {
-        matches!(self.idle_task_support, IdleTaskSupport::kEnabled)
+    fn idle_tasks_enabled(&self, _isolate: *mut Isolate) -> bool {
+        self.idle_task_support == IdleTaskSupport::kEnabled
     }
 
-    fn create_job(&self, priority: TaskPriority, job_task: Box<dyn JobTask + Send + Sync>, location: SourceLocation) -> Box<dyn JobHandle> {
+    fn create_job(&self, priority: TaskPriority, job_task: Box<dyn JobTask>, _location: SourceLocation) -> Box<dyn JobHandle> {
         let mut num_worker_threads = self.number_of_worker_threads();
-        if matches!(priority, TaskPriority::kBestEffort) && num_worker_threads > 2 {
+        if priority == TaskPriority::kBestEffort && num_worker_threads > 2 {
             num_worker_threads = 2;
         }
-        new_default_job_handle(self, priority, job_task, num_worker_threads)
+        let platform: Weak<dyn Platform> = self.weak_self.clone();
+        Box::new(DefaultJobHandle::new(DefaultJobState::new(
+            platform,
+            job_task,
+            priority,
+            num_worker_threads,
+        )))
     }
 
     fn monotonically_increasing_time(&self) -> f64 {
-        match self.time_function_for_testing {
-            Some(f) => f(),
+        match &self.lock.lock().unwrap().time_function_for_testing {
+            Some(time_function) => time_function(),
             None => default_time_function(),
         }
     }
 
-    fn current_clock_time_millis(&self) -> f64 {
-        let now = Instant::now(); // Replace with a more accurate clock if needed
-        let duration = now.elapsed();
-        duration.as_millis() as f64
-    }
-
     fn get_tracing_controller(&self) -> &TracingController {
         &self.tracing_controller
     }
-
-    fn set_tracing_controller(&mut self, tracing_controller: Box<TracingController>) {
-        self.tracing_controller = tracing_controller;
-    }
-
-    fn number_of_worker_threads(&self) -> usize {
-        self.thread_pool_size
-    }
-
-    fn get_stack_trace_printer(&self) -> StackTracePrinter {
-        print_stack_trace //Assuming print_stack_trace is defined elsewhere.
-    }
-    /*
-    fn get_page_allocator(&self) -> &PageAllocator {
-        &self.page_allocator
-    }
-    */
-    //fn get_thread_isolated_allocator(&self) -> Option<&ThreadIsolatedAllocator> { //Not implemented
-
-    fn notify_isolate_shutdown(&self, isolate: &Isolate) {
-        let mut taskrunner: Option<Arc<DefaultForegroundTaskRunner>> = None;
-        {
-            let mut guard = self.foreground_task_runner_map.lock().unwrap();
-            let isolate_ptr = isolate as *const Isolate;
-            if guard.contains_key(&isolate_ptr) {
-                taskrunner = guard.remove(&isolate_ptr);
-            }
-        }
-        if let Some(runner) = taskrunner {
-            runner.terminate();
-        }
-    }
 }
 
-fn new_default_platform(
-    thread_pool_size: i32,
-    idle_task_support: IdleTaskSupport,
-    _in_process_stack_dumping: InProcessStackDumping,
-    tracing_controller: Box<TracingController>,
-    priority_mode: PriorityMode,
-) -> Box<dyn Platform> {
-    //if in_process_stack_dumping == InProcessStackDumping::kEnabled {
-    //    base::debug::EnableInProcessStackDumping(); // Assuming a similar Rust implementation
-    //}
-    let platform = DefaultPlatform::new(
-        thread_pool_size,
-        idle_task_support,
-        tracing_controller,
-        priority_mode,
-    );
-    Box::new(platform)
-}
-
-fn new_single_threaded_default_platform(
-    idle_task_support: IdleTaskSupport,
-    _in_process_stack_dumping: InProcessStackDumping,
-    tracing_controller: Box<TracingController>,
-) -> Box<dyn Platform> {
-    //if in_process_stack_dumping == InProcessStackDumping::kEnabled {
-    //    base::debug::EnableInProcessStackDumping(); // Assuming a similar Rust implementation
-    //}
-    let platform = DefaultPlatform::new_single_threaded(idle_task_support, tracing_controller);
-    Box::new(platform)
-}
-
-struct DefaultJobState {
-    platform: *const DefaultPlatform, // raw pointer to avoid lifetime issues with Platform trait
-    job_task: Box<dyn JobTask + Send + Sync>,
-    priority: TaskPriority,
-    num_worker_threads: usize,
-    cancelled: Arc<Mutex<bool>>,
-    join_handle: Option<thread::JoinHandle<()>>,
-}
-
-impl DefaultJobState {
-    fn new(
-        platform: &dyn Platform,
-        job_task: Box<dyn JobTask + Send + Sync>,
-        priority: TaskPriority,
-        num_worker_threads: usize,
-    ) -> Self {
-        DefaultJobState {
-            platform: platform as *const dyn Platform as *const DefaultPlatform,
-            job_task,
-            priority,
-            num_worker_threads,
-            cancelled: Arc::new(Mutex::new(false)),
-            join_handle: None,
+impl Drop for DefaultPlatform {
+    fn drop(&mut self) {
+        for task_runner in &self.worker_threads_task_runners {
+            task_runner.terminate();
         }
-    }
-}
-
-struct DefaultJobHandle {
-    state: Arc<DefaultJobState>,
-}
-
-impl DefaultJobHandle {
-    fn new(state: Arc<DefaultJobState>) -> Self {
-        let cancelled = state.cancelled.clone();
-        let num_worker_threads = state.num_worker_threads;
-        let platform_ptr = state.platform;
-        let job_task = &mut state.job_task;
-        let priority = state.priority;
-
-        let join_handle = thread::spawn(move || {
-            let platform = unsafe { &*platform_ptr }; // Dereference the raw pointer
-
-            let mut handles = Vec::new();
-            for worker_id in 0..num_worker_threads {
-                let cancelled_clone = cancelled.clone();
-                let mut job_task_clone = || {
-                    let platform = platform;
-                    (unsafe { &mut *(job_task as *const _ as *mut dyn JobTask) }).run(&DefaultJobHandle{state: state.clone()}, worker_id);
-
-                };
-                let join_handle = thread::spawn(move ||{
-                    job_task_clone();
-                });
-                handles.push(join_handle);
-            }
-            for handle in handles {
-                handle.join().unwrap();
-            }
-
-        });
-
-        DefaultJobHandle {
-            state: state.clone(),
+        let state = self.lock.get_mut().unwrap();
+        for (_, task_runner) in state.foreground_task_runner_map.drain() {
+            task_runner.terminate();
         }
     }
-
 }
 
-impl JobHandle for DefaultJobHandle {
-    fn is_cancelled(&self) -> bool {
-        *self.state.cancelled.lock().unwrap()
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include::v8_platform::{IdleTask, JobDelegate};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::time::Duration;
 
-    fn cancel(&self) {
-        *self.state.cancelled.lock().unwrap() = true;
+    struct RecordingTask {
+        id: u32,
+        log: Arc<Mutex<Vec<u32>>>,
     }
 
-    fn join(&self) {
-        if let Some(handle) = &self.state.join_handle {
-            handle.join().unwrap();
+    impl Task for RecordingTask {
+        fn run(&mut self) {
+            self.log.lock().unwrap().push(self.id);
         }
     }
-}
 
-fn new_default_job_handle(
-    platform: &dyn Platform,
-    priority: TaskPriority,
-    job_task: Box<dyn JobTask + Send + Sync>,
-    num_worker_threads: usize,
-) -> Box<dyn JobHandle> {
-    let state = Arc::new(DefaultJobState::new(platform, job_task, priority, num_worker_threads));
-    Box::new(DefaultJobHandle::new(state))
-}
-
-fn pump_message_loop(
-    platform: &dyn Platform,
-    isolate: &Isolate,
-    behavior: MessageLoopBehavior,
-) -> bool {
-    let platform = unsafe { &*(platform as *const dyn Platform as *const DefaultPlatform) }; // Type cast
-    platform.pump_message_loop(isolate, behavior)
-}
-
-fn run_idle_tasks(platform: &dyn Platform, isolate: &Isolate, idle_time_in_seconds: f64) {
-    let platform = unsafe { &*(platform as *const dyn Platform as *const DefaultPlatform) }; // Type cast
-    platform.run_idle_tasks(isolate, idle_time_in_seconds);
-}
-
-fn notify_isolate_shutdown(platform: &dyn Platform, isolate: &Isolate) {
-    let platform = unsafe { &*(platform as *const dyn Platform as *const DefaultPlatform) }; // Type cast
-    platform.notify_isolate_shutdown(isolate);
-}
-
-fn print_stack_trace() {
-    //crate::base::debug::stack_trace::StackTrace::new().print();
-    // Implement stack trace printing here, possibly using backtrace crate
-    println!("Stack trace printing not yet implemented.");
-}
-
-//Dummy structs for types that were missing. These will need implementing if the above code is to work.
-struct DefaultForegroundTaskRunner{
-    idle_task_support: IdleTaskSupport,
-    time_function: fn() -> f64,
-}
-
-impl DefaultForegroundTaskRunner{
-    fn new(idle_task_support: IdleTaskSupport, time_function: fn() -> f64) -> Self{
-        DefaultForegroundTaskRunner{
-            idle_task_support,
-            time_function,
+    impl IdleTask for RecordingTask {
+        fn run(&mut self, _deadline_in_seconds: f64) {
+            self.log.lock().unwrap().push(self.id);
         }
     }
 
-    fn pop_task_from_queue(&self, wait_for_work: MessageLoopBehavior) -> Option<Box<dyn Task>>{
-        //Dummy implementation returning None
-        None
-    }
-
-    fn pop_task_from_idle_queue(&self) -> Option<Box<dyn IdleTask>>{
-        //Dummy implementation returning None
-        None
-    }
-
-    fn terminate(&self){
-        //Dummy implementation returning None
-    }
-
-    struct RunTaskScope{
-        task_runner: Arc<DefaultForegroundTaskRunner>,
-    }
-}
-
-struct DefaultWorkerThreadsTaskRunner{
-    thread_pool_size: usize,
-    time_function: fn() -> f64,
-    priority: TaskPriority,
-}
+    fn isolate_key(id: usize) -> *mut Isolate {
+        id as *mut Isolate
+    }
+
+    #[test]
+    fn message_loop_runs_due_foreground_tasks_and_idle_tasks() {
+        let platform = new_single_threaded_default_platform(IdleTaskSupport::kEnabled, InProcessStackDumping::kDisabled, None);
+        let now_in_ms = Arc::new(AtomicU64::new(0));
+        let clock = Arc::clone(&now_in_ms);
+        platform.set_time_function_for_testing(Arc::new(move || clock.load(Ordering::Relaxed) as f64 / 1000.0));
+        let isolate = isolate_key(8);
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let task = |id| Box::new(RecordingTask { id, log: Arc::clone(&log) });
+
+        let task_runner = platform.get_foreground_task_runner(isolate, TaskPriority::kUserBlocking);
+        task_runner.post_delayed_task(task(1), 1.0, SourceLocation::new());
+        task_runner.post_non_nestable_task(task(2), SourceLocation::new());
+        task_runner.post_idle_task(task(3), SourceLocation::new());
+        while pump_message_loop(&platform, isolate, MessageLoopBehavior::kDoNotWait) {}
+        assert_eq!(*log.lock().unwrap(), vec![2]);
+
+        now_in_ms.store(1000, Ordering::Relaxed);
+        assert!(pump_message_loop(&platform, isolate, MessageLoopBehavior::kDoNotWait));
+        run_idle_tasks(&platform, isolate, 1.0);
+        assert_eq!(*log.lock().unwrap(), vec![2, 1, 3]);
+
+        notify_isolate_shutdown(&platform, isolate);
+        assert!(!pump_message_loop(&platform, isolate, MessageLoopBehavior::kDoNotWait));
+    }
+
+    #[test]
+    fn worker_tasks_run_in_the_background_per_priority() {
+        let platform = new_default_platform(
+            2,
+            IdleTaskSupport::kDisabled,
+            InProcessStackDumping::kDisabled,
+            None,
+            PriorityMode::kApply,
+        );
+        assert_eq!(platform.number_of_worker_threads(), 2);
+        let (sender, receiver) = mpsc::channel();
+        struct SendingTask(TaskPriority, mpsc::Sender<TaskPriority>);
+        impl Task for SendingTask {
+            fn run(&mut self) {
+                let _ = self.1.send(self.0);
+            }
+        }
 
-impl DefaultWorkerThreadsTaskRunner{
-    fn new(thread_pool_size: usize, time_function: fn() -> f64, priority: TaskPriority) -> Self{
-        DefaultWorkerThreadsTaskRunner{
-            thread_pool_size,
-            time_function,
-            priority,
+        for priority in [TaskPriority::kBestEffort, TaskPriority::kUserVisible, TaskPriority::kUserBlocking] {
+            platform.post_task_on_worker_thread(priority, Box::new(SendingTask(priority, sender.clone())), SourceLocation::new());
+        }
+        platform.call_delayed_on_worker_thread(
+            Box::new(SendingTask(TaskPriority::kMaxPriority, sender)),
+            0.01,
+            SourceLocation::new(),
+        );
+        let mut priorities: Vec<_> = (0..4).map(|_| receiver.recv_timeout(Duration::from_secs(10)).unwrap()).collect();
+        priorities.sort();
+        assert_eq!(
+            priorities,
+            vec![
+                TaskPriority::kBestEffort,
+                TaskPriority::kUserVisible,
+                TaskPriority::kUserBlocking,
+                TaskPriority::kUserBlocking
+            ]
+        );
+    }
+
+    struct CountingJob {
+        remaining_items: AtomicUsize,
+        processed_by_task_id: Mutex<Vec<u8>>,
+    }
+
+    impl JobTask for CountingJob {
+        fn run(&self, delegate: &dyn JobDelegate) {
+            while !delegate.should_yield() {
+                let taken = self.remaining_items.fetch_update(Ordering::AcqRel, Ordering::Acquire, |items| items.checked_sub(1));
+                if taken.is_err() {
+                    return;
+                }
+                self.processed_by_task_id.lock().unwrap().push(delegate.get_task_id());
+            }
         }
-    }
 
-    fn post_task(&self, task: Box<dyn Task + Send + Sync>){
-        //Dummy implementation returning None
+        fn get_max_concurrency(&self, _worker_count: usize) -> usize {
+            self.remaining_items.load(Ordering::Acquire)
+        }
     }
 
-    fn post_delayed_task(&self, task: Box<dyn Task + Send + Sync>, delay_in_seconds: f64){
-        //Dummy implementation returning None
-    }
+    #[test]
+    fn jobs_are_processed_by_workers_and_the_joining_thread() {
+        let platform: Arc<dyn Platform> = new_default_platform(
+            3,
+            IdleTaskSupport::kDisabled,
+            InProcessStackDumping::kDisabled,
+            None,
+            PriorityMode::kDontApply,
+        );
+        let job = Arc::new(CountingJob {
+            remaining_items: AtomicUsize::new(1000),
+            processed_by_task_id: Mutex::new(Vec::new()),
+        });
+        struct SharedJob(Arc<CountingJob>);
+        impl JobTask for SharedJob {
+            fn run(&self, delegate: &dyn JobDelegate) {
+                self.0.run(delegate);
+            }
+            fn get_max_concurrency(&self, worker_count: usize) -> usize {
+                self.0.get_max_concurrency(worker_count)
+            }
+        }
 
-    fn terminate(&self){
-        //Dummy implementation returning None
+        let mut handle = platform.post_job(TaskPriority::kUserVisible, Box::new(SharedJob(Arc::clone(&job))), SourceLocation::new());
+        assert!(handle.is_valid());
+        handle.join();
+        assert!(!handle.is_valid());
+        let processed_by_task_id = job.processed_by_task_id.lock().unwrap();
+        assert_eq!(processed_by_task_id.len(), 1000);
+        // The joining thread and at most 3 workers share the ids.
+        assert!(processed_by_task_id.iter().all(|&task_id| task_id < 4));
+
+        let mut canceled = new_default_job_handle(
+            &platform,
+            TaskPriority::kBestEffort,
+            Box::new(SharedJob(Arc::new(CountingJob {
+                remaining_items: AtomicUsize::new(usize::MAX),
+                processed_by_task_id: Mutex::new(Vec::new()),
+            }))),
+            1,
+        );
+        canceled.notify_concurrency_increase();
+        canceled.cancel();
+        assert!(!canceled.is_valid());
     }
-
-}
\ No newline at end of file
+}
@@ -1,271 +1,112 @@
-use std::sync::{Arc, Mutex, Condvar};
-use std::thread;
-use std::time::{Duration, Instant};
-use std::collections::VecDeque;
+// Copyright 2017 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-// Assuming base::Thread::Priority maps to a similar concept in Rust
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum ThreadPriority {
-    Normal,
-    High, // Or other levels as appropriate
-}
-
-// Mock for SourceLocation as it's likely debugging information
-#[derive(Debug, Clone)]
-pub struct SourceLocation {}
+use std::sync::{Arc, Condvar, Mutex};
 
-type Task = Box<dyn FnOnce() + Send + 'static>;
-type IdleTask = Box<dyn FnOnce() + Send + 'static>;
-type TimeFunction = Arc<dyn Fn() -> f64 + Send + Sync>;
+use crate::include::v8_platform::{Task, TaskRunner};
+use crate::include::v8_source_location::SourceLocation;
+use crate::libplatform::delayed_task_queue::{DelayedTaskQueue, MaybeNextTask, TimeFunction};
+use crate::libplatform::worker_thread::{TaskSource, ThreadPriority, WorkerThread};
 
-struct TaskEntry {
-    task: Task,
-}
-
-struct DelayedTaskEntry {
-    task: Task,
-    delay: f64,
-    scheduled_time: f64,
+/// A pool of worker threads sharing one queue of immediate and delayed
+/// tasks.
+pub struct DefaultWorkerThreadsTaskRunner {
+    shared: Arc<SharedQueue>,
+    thread_pool: Mutex<Vec<WorkerThread>>,
 }
 
-struct DelayedTaskQueue {
-    queue: Mutex<VecDeque<DelayedTaskEntry>>,
-    time_function: TimeFunction,
-    terminated: Mutex<bool>,
-    condvar: Condvar,
+/// The queue the workers pull from. Idle workers wait on `queue_changed`,
+/// with a timeout if a delayed task is pending.
+struct SharedQueue {
+    queue: Mutex<DelayedTaskQueue>,
+    queue_changed: Condvar,
 }
 
-impl DelayedTaskQueue {
-    fn new(time_function: TimeFunction) -> Self {
-        DelayedTaskQueue {
-            queue: Mutex::new(VecDeque::new()),
-            time_function,
-            terminated: Mutex::new(false),
-            condvar: Condvar::new(),
+impl DefaultWorkerThreadsTaskRunner {
+    pub fn new(thread_pool_size: usize, time_function: TimeFunction, priority: ThreadPriority) -> Self {
+        let shared = Arc::new(SharedQueue {
+            queue: Mutex::new(DelayedTaskQueue::new(time_function)),
+            queue_changed: Condvar::new(),
+        });
+        let thread_pool = (0..thread_pool_size)
+            .map(|_| {
+                WorkerThread::new(
+                    "V8 DefaultWorkerThreadsTaskRunner WorkerThread",
+                    priority,
+                    Arc::clone(&shared) as Arc<dyn TaskSource>,
+                )
+            })
+            .collect();
+        DefaultWorkerThreadsTaskRunner {
+            shared,
+            thread_pool: Mutex::new(thread_pool),
         }
     }
 
-    fn append(&self, task: Task) {
-        self.append_delayed(task, 0.0);
-    }
-
-    fn append_delayed(&self, task: Task, delay: f64) {
-        let scheduled_time = (self.time_function)();
-        let entry = DelayedTaskEntry {
-            task,
-            delay,
-            scheduled_time: scheduled_time + delay,
-        };
-
-        let mut queue = self.queue.lock().unwrap();
-        queue.push_back(entry);
-        self.condvar.notify_one();
-    }
-
-    enum MaybeNextTask {
-        Task(Task),
-        Terminated,
-        WaitIndefinite,
-        WaitDelayed(f64),
-    }
-
-    fn try_get_next(&self) -> MaybeNextTask {
-        let mut queue = self.queue.lock().unwrap();
-        if *self.terminated.lock().unwrap() {
-            return MaybeNextTask::Terminated;
-        }
-
-        loop {
-            match queue.pop_front() {
-                Some(entry) => {
-                    let now = (self.time_function)();
-                    if entry.scheduled_time <= now {
-                        return MaybeNextTask::Task(entry.task);
-                    } else {
-                        // Re-insert the entry and wait
-                        queue.push_front(entry);
-                        let wait_time = entry.scheduled_time - now;
-                        return MaybeNextTask::WaitDelayed(wait_time);
-                    }
-                }
-                None => {
-                    if *self.terminated.lock().unwrap() {
-                        return MaybeNextTask::Terminated;
-                    }
-                    return MaybeNextTask::WaitIndefinite;
-                }
+    /// Drops the pending tasks and waits for the workers to finish their
+    /// current task and exit. Tasks posted afterwards are dropped.
+    pub fn terminate(&self) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.is_terminated() {
+                return;
             }
+            queue.terminate();
+            self.shared.queue_changed.notify_all();
         }
-    }
-
-    fn terminate(&self) {
-        *self.terminated.lock().unwrap() = true;
-        self.condvar.notify_all();
-    }
-}
-
-pub struct DefaultWorkerThreadsTaskRunner {
-    queue_: DelayedTaskQueue,
-    time_function_: TimeFunction,
-    thread_pool_: Mutex<Vec<WorkerThread>>,
-    lock_: Mutex<()>,
-    idle_threads_: Mutex<Vec<Arc<WorkerThread>>>,
-    terminated_: Mutex<bool>,
-}
-
-impl DefaultWorkerThreadsTaskRunner {
-    pub fn new(thread_pool_size: u32, time_function: TimeFunction, priority: ThreadPriority) -> Self {
-        let runner = DefaultWorkerThreadsTaskRunner {
-            queue_: DelayedTaskQueue::new(time_function.clone()),
-            time_function_: time_function.clone(),
-            thread_pool_: Mutex::new(Vec::new()),
-            lock_: Mutex::new(()),
-            idle_threads_: Mutex::new(Vec::new()),
-            terminated_: Mutex::new(false),
-        };
-
-        let mut thread_pool = runner.thread_pool_.lock().unwrap();
-        for _ in 0..thread_pool_size {
-            thread_pool.push(WorkerThread::new(Arc::new(runner.clone()), priority));
+        let thread_pool = std::mem::take(&mut *self.thread_pool.lock().unwrap());
+        for thread in thread_pool {
+            thread.join();
         }
-
-        runner
     }
 
     pub fn monotonically_increasing_time(&self) -> f64 {
-        (self.time_function_)()
-    }
-
-    pub fn terminate(&self) {
-        let _guard = self.lock_.lock().unwrap();
-        *self.terminated_.lock().unwrap() = true;
-        self.queue_.terminate();
-        self.idle_threads_.lock().unwrap().clear();
-
-        // Clearing the thread pool lets all worker threads join.
-        self.thread_pool_.lock().unwrap().clear();
+        self.shared.queue.lock().unwrap().monotonically_increasing_time()
     }
 
-    pub fn post_task(&self, task: Task, _location: &SourceLocation) {
-        self.post_task_impl(task, _location);
+    pub fn number_of_worker_threads(&self) -> usize {
+        self.thread_pool.lock().unwrap().len()
     }
+}
 
-    fn post_task_impl(&self, task: Task, _location: &SourceLocation) {
-        let _guard = self.lock_.lock().unwrap();
-        if *self.terminated_.lock().unwrap() {
+impl TaskRunner for DefaultWorkerThreadsTaskRunner {
+    fn post_task(&self, task: Box<dyn Task>, _location: SourceLocation) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.is_terminated() {
             return;
         }
-        self.queue_.append(task);
-
-        let mut idle_threads = self.idle_threads_.lock().unwrap();
-        if let Some(thread) = idle_threads.pop() {
-            thread.notify();
-        }
-    }
-
-    pub fn post_delayed_task(&self, task: Task, delay_in_seconds: f64, _location: &SourceLocation) {
-        self.post_delayed_task_impl(task, delay_in_seconds, _location);
+        queue.append(task);
+        self.shared.queue_changed.notify_one();
     }
 
-    fn post_delayed_task_impl(&self, task: Task, delay_in_seconds: f64, _location: &SourceLocation) {
-        let _guard = self.lock_.lock().unwrap();
-        if *self.terminated_.lock().unwrap() {
+    fn post_delayed_task(&self, task: Box<dyn Task>, delay_in_seconds: f64, _location: SourceLocation) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.is_terminated() {
             return;
         }
-        self.queue_.append_delayed(task, delay_in_seconds);
-
-        let mut idle_threads = self.idle_threads_.lock().unwrap();
-        if let Some(thread) = idle_threads.pop() {
-            thread.notify();
-        }
-    }
-
-    pub fn post_idle_task(&self, _task: IdleTask, _location: &SourceLocation) {
-        // There are no idle worker tasks.
-        panic!("UNREACHABLE");
-    }
-
-    pub fn idle_tasks_enabled(&self) -> bool {
-        // There are no idle worker tasks.
-        false
+        queue.append_delayed(task, delay_in_seconds);
+        // The new task may be due before the one an idle worker waits for.
+        self.shared.queue_changed.notify_one();
     }
 }
 
-impl Clone for DefaultWorkerThreadsTaskRunner {
-    fn clone(&self) -> Self {
-        DefaultWorkerThreadsTaskRunner {
-            queue_: DelayedTaskQueue::new(self.time_function_.clone()),
-            time_function_: self.time_function_.clone(),
-            thread_pool_: Mutex::new(Vec::new()),
-            lock_: Mutex::new(()),
-            idle_threads_: Mutex::new(Vec::new()),
-            terminated_: Mutex::new(false),
-        }
-    }
-}
-
-struct WorkerThread {
-    thread: Option<thread::JoinHandle<()>>,
-    runner_: Arc<DefaultWorkerThreadsTaskRunner>,
-    condition_var_: Arc<Condvar>,
-}
-
-impl WorkerThread {
-    fn new(runner_: Arc<DefaultWorkerThreadsTaskRunner>, priority: ThreadPriority) -> Self {
-        let runner = runner_.clone();
-        let condition_var_ = Arc::new(Condvar::new());
-        let condition_var = condition_var_.clone();
-
-        let thread = thread::spawn(move || {
-            let condvar = condition_var;
-            let runner = runner;
-            loop {
-                let next_task = runner.queue_.try_get_next();
-                match next_task {
-                    DelayedTaskQueue::MaybeNextTask::Task(task) => {
-                        (task)();
-                    }
-                    DelayedTaskQueue::MaybeNextTask::Terminated => {
-                        break;
-                    }
-                    DelayedTaskQueue::MaybeNextTask::WaitIndefinite => {
-                        let mut idle_threads = runner.idle_threads_.lock().unwrap();
-                        idle_threads.push(Arc::new(WorkerThread { thread: None, runner_: runner.clone(), condition_var_: condvar.clone() }));
-                        drop(idle_threads);
-
-                        let _guard = runner.lock_.lock().unwrap();
-                        let _ = condvar.wait(_guard).unwrap();
-                    }
-                    DelayedTaskQueue::MaybeNextTask::WaitDelayed(wait_time) => {
-                        let mut idle_threads = runner.idle_threads_.lock().unwrap();
-                        idle_threads.push(Arc::new(WorkerThread { thread: None, runner_: runner.clone(), condition_var_: condvar.clone() }));
-                        drop(idle_threads);
-
-                        let duration = Duration::from_secs_f64(wait_time);
-                        let _guard = runner.lock_.lock().unwrap();
-                        let ( _guard_ret, timeout_result) = condvar.wait_timeout(_guard, duration).unwrap();
-                    }
-                }
-            }
-        });
-
-        WorkerThread {
-            thread: Some(thread),
-            runner_: runner_,
-            condition_var_: condition_var_,
+impl TaskSource for SharedQueue {
+    fn get_next(&self) -> Option<Box<dyn Task>> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            queue = match queue.try_get_next() {
+                MaybeNextTask::Task(task) => return Some(task),
+                MaybeNextTask::Terminated => return None,
+                MaybeNextTask::WaitIndefinitely => self.queue_changed.wait(queue).unwrap(),
+                MaybeNextTask::WaitDelayed(wait_time) => self.queue_changed.wait_timeout(queue, wait_time).unwrap().0,
+            };
         }
     }
-
-    fn notify(&self) {
-        self.condition_var_.notify_all();
-    }
 }
 
-impl Drop for WorkerThread {
+impl Drop for DefaultWorkerThreadsTaskRunner {
     fn drop(&mut self) {
-        self.condition_var_.notify_all();
-        if let Some(thread) = self.thread.take() {
-            thread.join().unwrap();
-        }
+        self.terminate();
     }
-}
\ No newline at end of file
+}
@@ -3,184 +3,210 @@
 // found in the LICENSE file.
 
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
-use std::sync::Mutex;
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::Arc;
 use std::time::Duration;
 
-// TODO: Add logging crate
-// use log::{debug, error, info, trace, warn};
+use crate::include::v8_platform::Task;
 
-// Placeholder for base::TimeDelta and TimeConstants
-pub mod base {
-    #[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord)]
-    pub struct TimeDelta {
-        pub microseconds: i64,
-    }
-
-    impl TimeDelta {
-        pub fn from_microseconds(microseconds: i64) -> Self {
-            TimeDelta { microseconds }
-        }
-    }
-
-    pub mod TimeConstants {
-        pub const K_MICROSECONDS_PER_SECOND: i64 = 1_000_000;
-    }
-}
+/// Returns the current time in seconds on a monotonic clock.
+pub type TimeFunction = Arc<dyn Fn() -> f64 + Send + Sync>;
 
-/// Represents a task to be executed.
-pub trait Task: Send {
-    fn run(&mut self);
+/// DelayedTaskQueue provides queueing for immediate and delayed tasks. It does
+/// not provide any guarantees about ordering of tasks, except that immediate
+/// tasks will be run in the order that they are posted. It is not thread-safe;
+/// the owning task runner guards it with its lock.
+pub struct DelayedTaskQueue {
+    task_queue: VecDeque<Box<dyn Task>>,
+    delayed_task_queue: BinaryHeap<DelayedEntry>,
+    next_sequence_number: u64,
+    terminated: bool,
+    time_function: TimeFunction,
 }
 
-/// A queue for managing delayed tasks.
-pub struct DelayedTaskQueue {
-    time_function: Box<dyn Fn() -> f64 + Send + Sync>,
-    task_queue: Mutex<Vec<Box<dyn Task>>>,
-    delayed_task_queue: Mutex<BinaryHeap<DelayedTask>>,
-    terminated: Mutex<bool>,
+/// The result of `DelayedTaskQueue::try_get_next`.
+pub enum MaybeNextTask {
+    /// A task that is ready to run.
+    Task(Box<dyn Task>),
+    /// No task is queued; wait until one is appended.
+    WaitIndefinitely,
+    /// Only delayed tasks are queued; the first one is due after the given
+    /// time.
+    WaitDelayed(Duration),
+    Terminated,
 }
 
 impl DelayedTaskQueue {
-    /// Creates a new `DelayedTaskQueue`.
-    pub fn new(time_function: impl Fn() -> f64 + Send + Sync + 'static) -> Self {
+    pub fn new(time_function: TimeFunction) -> Self {
         DelayedTaskQueue {
-            time_function: Box::new(time_function),
-            task_queue: Mutex::new(Vec::new()),
-            delayed_task_queue: Mutex::new(BinaryHeap::new()),
-            terminated: Mutex::new(false),
+            task_queue: VecDeque::new(),
+            delayed_task_queue: BinaryHeap::new(),
+            next_sequence_number: 0,
+            terminated: false,
+            time_function,
         }
     }
 
-    /// Returns the current time using the provided time function.
-    fn monotonically_increasing_time(&self) -> f64 {
-        (self.time_function)()
+    /// Returns true if the queue holds a task that can run right away.
+    pub fn has_poppable_task_in_queue(&self) -> bool {
+        !self.task_queue.is_empty()
+            || self
+                .delayed_task_queue
+                .peek()
+                .is_some_and(|entry| entry.timeout_time <= self.monotonically_increasing_time())
     }
 
-    /// Appends a task to the queue.
-    pub fn append(&self, task: Box<dyn Task>) {
-        let terminated = *self.terminated.lock().unwrap();
-        assert!(!terminated);
-        self.task_queue.lock().unwrap().push(task);
-    }
-
-    /// Appends a task to the queue with a specified delay.
-    pub fn append_delayed(&self, task: Box<dyn Task>, delay_in_seconds: f64) {
-        assert!(delay_in_seconds >= 0.0);
-        let deadline = self.monotonically_increasing_time() + delay_in_seconds;
-        {
-            let terminated = *self.terminated.lock().unwrap();
-            assert!(!terminated);
-            self.delayed_task_queue
-                .lock()
-                .unwrap()
-                .push(DelayedTask { deadline, task });
-        }
+    /// Appends an immediate task to the queue. The queue takes ownership of
+    /// |task|. Tasks appended via this method will be run in order.
+    pub fn append(&mut self, task: Box<dyn Task>) {
+        debug_assert!(!self.terminated);
+        self.task_queue.push_back(task);
     }
 
-    /// Represents the possible results of trying to get the next task.
-    pub enum MaybeNextTask {
-        /// A task is available.
-        Task(Box<dyn Task>),
-        /// The queue has been terminated.
-        Terminated,
-        /// Wait for the specified duration before trying again.
-        WaitDelayed(base::TimeDelta),
-        /// Wait indefinitely for a new task.
-        WaitIndefinite,
-    }
-
-    /// Tries to get the next task from the queue.
-    pub fn try_get_next(&self) -> MaybeNextTask {
-        loop {
-            // Move delayed tasks that have hit their deadline to the main queue.
-            let now = self.monotonically_increasing_time();
-            loop {
-                let task = self.pop_task_from_delayed_queue(now);
-                if let Some(task) = task {
-                    self.task_queue.lock().unwrap().push(task);
-                } else {
-                    break;
-                }
-            }
-
-            let mut task_queue = self.task_queue.lock().unwrap();
-            if !task_queue.is_empty() {
-                let task = task_queue.remove(0);
-                return MaybeNextTask::Task(task);
-            }
-
-            let terminated = *self.terminated.lock().unwrap();
-            if terminated {
-                return MaybeNextTask::Terminated;
-            }
-
-            let delayed_task_queue = self.delayed_task_queue.lock().unwrap();
-            if task_queue.is_empty() && !delayed_task_queue.is_empty() {
-                // Wait for the next delayed task or a newly posted task.
-                let wait_in_seconds = delayed_task_queue.peek().unwrap().deadline - now;
-                return MaybeNextTask::WaitDelayed(base::TimeDelta::from_microseconds(
-                    (base::TimeConstants::K_MICROSECONDS_PER_SECOND as f64 * wait_in_seconds) as i64,
-                ));
-            } else {
-                return MaybeNextTask::WaitIndefinite;
-            }
-        }
+    /// Appends a delayed task to the queue. There is no ordering guarantee
+    /// provided regarding delayed tasks, both with respect to other delayed
+    /// tasks and non-delayed tasks that were appended using `append`.
+    pub fn append_delayed(&mut self, task: Box<dyn Task>, delay_in_seconds: f64) {
+        debug_assert!(delay_in_seconds >= 0.0);
+        debug_assert!(!self.terminated);
+        let timeout_time = self.monotonically_increasing_time() + delay_in_seconds;
+        let sequence_number = self.next_sequence_number;
+        self.next_sequence_number += 1;
+        self.delayed_task_queue.push(DelayedEntry {
+            timeout_time,
+            sequence_number,
+            task,
+        });
     }
 
-    /// Pops a task from the delayed queue if its deadline has passed.
-    fn pop_task_from_delayed_queue(&self, now: f64) -> Option<Box<dyn Task>> {
-        let mut delayed_task_queue = self.delayed_task_queue.lock().unwrap();
-        if delayed_task_queue.is_empty() {
-            return None;
+    /// Returns the next task to process, or tells the caller how long to wait
+    /// for one. Delayed tasks whose deadline has passed are moved to the back
+    /// of the immediate queue first.
+    pub fn try_get_next(&mut self) -> MaybeNextTask {
+        let now = self.monotonically_increasing_time();
+        while let Some(task) = self.pop_task_from_delayed_queue(now) {
+            self.task_queue.push_back(task);
         }
-
-        if delayed_task_queue.peek().unwrap().deadline > now {
-            return None;
+        if let Some(task) = self.task_queue.pop_front() {
+            return MaybeNextTask::Task(task);
+        }
+        if self.terminated {
+            return MaybeNextTask::Terminated;
+        }
+        match self.delayed_task_queue.peek() {
+            Some(entry) => MaybeNextTask::WaitDelayed(Duration::from_secs_f64((entry.timeout_time - now).max(0.0))),
+            None => MaybeNextTask::WaitIndefinitely,
         }
+    }
 
-        delayed_task_queue.pop().map(|dt| dt.task)
+    /// Terminate the queue. Queued tasks are dropped.
+    pub fn terminate(&mut self) {
+        debug_assert!(!self.terminated);
+        self.terminated = true;
+        self.task_queue.clear();
+        self.delayed_task_queue.clear();
     }
 
-    /// Terminates the queue.
-    pub fn terminate(&self) {
-        let mut terminated = self.terminated.lock().unwrap();
-        assert!(!*terminated);
-        *terminated = true;
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+
+    pub fn monotonically_increasing_time(&self) -> f64 {
+        (self.time_function)()
     }
-}
 
-impl Drop for DelayedTaskQueue {
-    fn drop(&mut self) {
-        let terminated = *self.terminated.lock().unwrap();
-        assert!(terminated);
-        assert!(self.task_queue.lock().unwrap().is_empty());
+    fn pop_task_from_delayed_queue(&mut self, now: f64) -> Option<Box<dyn Task>> {
+        if self.delayed_task_queue.peek()?.timeout_time > now {
+            return None;
+        }
+        self.delayed_task_queue.pop().map(|entry| entry.task)
     }
 }
 
-/// Represents a delayed task with a deadline.
-struct DelayedTask {
-    deadline: f64,
+/// A delayed task in the min-heap of `DelayedTaskQueue`. Tasks with the same
+/// deadline keep the order in which they were appended.
+struct DelayedEntry {
+    timeout_time: f64,
+    sequence_number: u64,
     task: Box<dyn Task>,
 }
 
-impl PartialEq for DelayedTask {
+impl PartialEq for DelayedEntry {
     fn eq(&self, other: &Self) -> bool {
-        self.deadline == other.deadline
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-impl Eq for DelayedTask {}
+impl Eq for DelayedEntry {}
 
-impl PartialOrd for DelayedTask {
+impl PartialOrd for DelayedEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
 
-impl Ord for DelayedTask {
+impl Ord for DelayedEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        // BinaryHeap is a max-heap, so we reverse the order to get a min-heap.
-        other.deadline.partial_cmp(&self.deadline).unwrap()
+        // Reversed, so that `BinaryHeap` pops the earliest deadline first.
+        other
+            .timeout_time
+            .total_cmp(&self.timeout_time)
+            .then_with(|| other.sequence_number.cmp(&self.sequence_number))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+    use std::sync::Mutex;
+
+    struct RecordingTask {
+        id: u32,
+        log: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl Task for RecordingTask {
+        fn run(&mut self) {
+            self.log.lock().unwrap().push(self.id);
+        }
+    }
+
+    fn run_next(queue: &mut DelayedTaskQueue) -> bool {
+        match queue.try_get_next() {
+            MaybeNextTask::Task(mut task) => {
+                task.run();
+                true
+            }
+            _ => false,
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn delayed_tasks_become_ready_at_their_deadline() {
+        let now_in_ms = Arc::new(AtomicU64::new(0));
+        let clock = Arc::clone(&now_in_ms);
+        let mut queue = DelayedTaskQueue::new(Arc::new(move || clock.load(AtomicOrdering::Relaxed) as f64 / 1000.0));
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let task = |id| Box::new(RecordingTask { id, log: Arc::clone(&log) });
+
+        assert!(matches!(queue.try_get_next(), MaybeNextTask::WaitIndefinitely));
+        queue.append_delayed(task(1), 2.0);
+        queue.append_delayed(task(2), 1.0);
+        queue.append(task(3));
+        assert!(run_next(&mut queue));
+        match queue.try_get_next() {
+            MaybeNextTask::WaitDelayed(wait_time) => assert_eq!(wait_time, Duration::from_secs(1)),
+            _ => panic!("expected to wait for the delayed task"),
+        }
+
+        now_in_ms.store(2000, AtomicOrdering::Relaxed);
+        assert!(queue.has_poppable_task_in_queue());
+        while run_next(&mut queue) {}
+        assert_eq!(*log.lock().unwrap(), vec![3, 2, 1]);
+
+        queue.append_delayed(task(4), 1.0);
+        queue.terminate();
+        assert!(matches!(queue.try_get_next(), MaybeNextTask::Terminated));
+    }
+}
@@ -8,3 +8,4 @@ pub mod default-worker-threads-task-runner;
 pub mod default-job;
 pub mod worker-thread;
 pub mod default-platform;
+pub mod tracing;
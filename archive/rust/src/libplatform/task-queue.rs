@@ -1,81 +1,91 @@
-use std::sync::{Arc, Mutex, Condvar};
-use std::sync::mpsc::{Sender, Receiver, channel};
-use std::thread;
+// Copyright 2013 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex};
 use std::time::Duration;
 
-/// A task to be executed.  Corresponds to `v8::Task`.
-pub trait Task: Send {
-    fn run(&mut self);
-}
+use crate::include::v8_platform::Task;
+use crate::libplatform::worker_thread::TaskSource;
 
+/// A FIFO of tasks shared by a set of worker threads.
 pub struct TaskQueue {
-    lock: Arc<Mutex<TaskQueueInternal>>,
-    process_queue_semaphore: Arc<Condvar>,
+    lock: Mutex<TaskQueueState>,
+    process_queue: Condvar,
 }
 
-struct TaskQueueInternal {
-    task_queue: Vec<Box<dyn Task>>,
+struct TaskQueueState {
+    task_queue: VecDeque<Box<dyn Task>>,
     terminated: bool,
 }
 
 impl TaskQueue {
     pub fn new() -> Self {
         TaskQueue {
-            lock: Arc::new(Mutex::new(TaskQueueInternal {
-                task_queue: Vec::new(),
+            lock: Mutex::new(TaskQueueState {
+                task_queue: VecDeque::new(),
                 terminated: false,
-            })),
-            process_queue_semaphore: Arc::new(Condvar::new()),
+            }),
+            process_queue: Condvar::new(),
         }
     }
 
+    /// Appends a task to the queue. The queue must not have been terminated.
     pub fn append(&self, task: Box<dyn Task>) {
-        let mut guard = self.lock.lock().unwrap();
-        assert!(!guard.terminated);
-        guard.task_queue.push(task);
-        self.process_queue_semaphore.notify_one();
+        let mut state = self.lock.lock().unwrap();
+        assert!(!state.terminated, "cannot append to a terminated task queue");
+        state.task_queue.push_back(task);
+        self.process_queue.notify_one();
     }
 
+    /// Returns the next task to process. Blocks if no task is available.
+    /// Returns `None` if the queue is terminated.
     pub fn get_next(&self) -> Option<Box<dyn Task>> {
+        let mut state = self.lock.lock().unwrap();
         loop {
-            let mut guard = self.lock.lock().unwrap();
-            if !guard.task_queue.is_empty() {
-                let result = guard.task_queue.remove(0);
-                return Some(result);
+            if let Some(task) = state.task_queue.pop_front() {
+                return Some(task);
             }
-            if guard.terminated {
-                self.process_queue_semaphore.notify_one();
+            if state.terminated {
                 return None;
             }
-
-            guard = self.process_queue_semaphore.wait(guard).unwrap();
+            state = self.process_queue.wait(state).unwrap();
         }
     }
 
+    /// Terminate the queue. Worker threads blocked in `get_next` return
+    /// `None` once the tasks that are still queued have been handed out.
     pub fn terminate(&self) {
-        let mut guard = self.lock.lock().unwrap();
-        assert!(!guard.terminated);
-        guard.terminated = true;
-        self.process_queue_semaphore.notify_one();
+        let mut state = self.lock.lock().unwrap();
+        assert!(!state.terminated, "the task queue is already terminated");
+        state.terminated = true;
+        self.process_queue.notify_all();
     }
 
     pub fn block_until_queue_empty_for_testing(&self) {
-        loop {
-            {
-                let guard = self.lock.lock().unwrap();
-                if guard.task_queue.is_empty() {
-                    return;
-                }
-            }
-            thread::sleep(Duration::from_millis(5));
+        while !self.lock.lock().unwrap().task_queue.is_empty() {
+            std::thread::sleep(Duration::from_millis(5));
         }
     }
 }
 
+impl Default for TaskQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TaskSource for TaskQueue {
+    fn get_next(&self) -> Option<Box<dyn Task>> {
+        TaskQueue::get_next(self)
+    }
+}
+
 impl Drop for TaskQueue {
     fn drop(&mut self) {
-        let mut guard = self.lock.lock().unwrap();
-        assert!(guard.terminated);
-        assert!(guard.task_queue.is_empty());
+        let state = self.lock.get_mut().unwrap();
+        debug_assert!(state.terminated, "task queues must be terminated before they are dropped");
+        debug_assert!(state.task_queue.is_empty());
     }
-}
\ No newline at end of file
+}
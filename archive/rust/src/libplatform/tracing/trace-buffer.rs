@@ -2,103 +2,253 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::Mutex;
-use std::vec::Vec;
-use std::ptr::NonNull;
+use crate::libplatform::tracing::trace_config::TraceRecordMode;
+use crate::libplatform::tracing::trace_object::TraceObject;
+use crate::libplatform::tracing::trace_writer::TraceWriter;
+
+/// A fixed-size run of trace events. Chunks are reused once the ring buffer
+/// wraps around, so each use gets a new sequence number that invalidates old
+/// handles into it.
+pub struct TraceBufferChunk {
+    seq: u32,
+    next_free: usize,
+    chunk: Vec<TraceObject>,
+}
 
-// Assuming v8-tracing.h defines a trait or struct called TraceObject, TraceBuffer, and TraceWriter
-// and TraceBufferChunk::kChunkSize
+impl TraceBufferChunk {
+    pub const K_CHUNK_SIZE: usize = 64;
 
-trait TraceObject {}
+    pub fn new(seq: u32) -> Self {
+        TraceBufferChunk { seq, next_free: 0, chunk: Vec::with_capacity(Self::K_CHUNK_SIZE) }
+    }
 
-trait TraceBuffer {
-    fn add_trace_event(&mut self, handle: &mut u64) -> Option<&mut dyn TraceObject>;
-    fn get_event_by_handle(&self, handle: u64) -> Option<&dyn TraceObject>;
-    fn flush(&mut self) -> bool;
-}
+    pub fn reset(&mut self, new_seq: u32) {
+        self.next_free = 0;
+        self.seq = new_seq;
+    }
 
-trait TraceWriter {
-    fn write(&mut self, data: &[u8]) -> Result<(), std::io::Error>;
-}
+    pub fn is_full(&self) -> bool {
+        self.next_free == Self::K_CHUNK_SIZE
+    }
+
+    /// Returns the next free event and its index in the chunk.
+    pub fn add_trace_event(&mut self) -> (&mut TraceObject, usize) {
+        debug_assert!(!self.is_full());
+        let event_index = self.next_free;
+        self.next_free += 1;
+        if event_index == self.chunk.len() {
+            self.chunk.push(TraceObject::new());
+        }
+        (&mut self.chunk[event_index], event_index)
+    }
+
+    pub fn get_event_at(&mut self, index: usize) -> &mut TraceObject {
+        &mut self.chunk[index]
+    }
 
-struct TraceBufferChunk {
-    // Placeholder, define fields based on the original C++ implementation
+    pub fn events(&self) -> &[TraceObject] {
+        &self.chunk[..self.next_free]
+    }
+
+    pub fn seq(&self) -> u32 {
+        self.seq
+    }
+
+    pub fn size(&self) -> usize {
+        self.next_free
+    }
 }
 
-impl TraceBufferChunk {
-    const K_CHUNK_SIZE: usize = 4096; // Example chunk size
+/// Storage for trace events between `start_tracing` and `stop_tracing`.
+/// Events are identified by handles so that the duration of complete events
+/// can be filled in when their scope ends.
+pub trait TraceBuffer: Send {
+    /// Returns storage for a new event and its handle, or `None` if the
+    /// buffer is full.
+    fn add_trace_event(&mut self) -> Option<(&mut TraceObject, u64)>;
+
+    /// Returns the event with `handle`, or `None` if it has been overwritten.
+    fn get_event_by_handle(&mut self, handle: u64) -> Option<&mut TraceObject>;
+
+    /// Writes all buffered events to the trace writer and empties the buffer.
+    fn flush(&mut self) -> bool;
+
+    fn set_record_mode(&mut self, _mode: TraceRecordMode) {}
 }
 
-struct TraceBufferRingBuffer {
-    mutex: Mutex<()>,
+/// The number of chunks of the default ring buffer.
+pub const K_RING_BUFFER_CHUNKS: usize = 1024;
+
+/// A trace buffer that either stops accepting events once all chunks are
+/// full or, when recording continuously, overwrites the oldest chunk.
+pub struct TraceBufferRingBuffer {
     max_chunks: usize,
-    trace_writer: Box<dyn TraceWriter + Send>,
-    chunks: Vec<Box<TraceBufferChunk>>,
+    trace_writer: Box<dyn TraceWriter>,
+    chunks: Vec<Option<Box<TraceBufferChunk>>>,
     chunk_index: usize,
     is_empty: bool,
     current_chunk_seq: u32,
+    record_mode: TraceRecordMode,
 }
 
 impl TraceBufferRingBuffer {
     /// Takes ownership of `trace_writer`.
-    fn new(max_chunks: usize, trace_writer: Box<dyn TraceWriter + Send>) -> Self {
+    pub fn new(max_chunks: usize, trace_writer: Box<dyn TraceWriter>) -> Self {
+        assert!(max_chunks > 0);
         TraceBufferRingBuffer {
-            mutex: Mutex::new(()),
             max_chunks,
             trace_writer,
-            chunks: Vec::with_capacity(max_chunks),
+            chunks: (0..max_chunks).map(|_| None).collect(),
             chunk_index: 0,
             is_empty: true,
             current_chunk_seq: 1,
+            record_mode: TraceRecordMode::kRecordContinuously,
         }
     }
 
-    fn make_handle(&self, chunk_index: usize, chunk_seq: u32, event_index: usize) -> u64 {
-        // Implement handle creation logic
-        ((chunk_index as u64) << 48) | ((chunk_seq as u64) << 32) | (event_index as u64)
+    fn capacity(&self) -> u64 {
+        (self.max_chunks * TraceBufferChunk::K_CHUNK_SIZE) as u64
     }
 
-    fn extract_handle(
-        &self,
-        handle: u64,
-        chunk_index: &mut usize,
-        chunk_seq: &mut u32,
-        event_index: &mut usize,
-    ) {
-        *chunk_index = ((handle >> 48) & 0xFFFF) as usize;
-        *chunk_seq = ((handle >> 32) & 0xFFFF) as u32;
-        *event_index = (handle & 0xFFFFFFFF) as usize;
+    fn make_handle(&self, chunk_index: usize, chunk_seq: u32, event_index: usize) -> u64 {
+        chunk_seq as u64 * self.capacity() + (chunk_index * TraceBufferChunk::K_CHUNK_SIZE + event_index) as u64
     }
 
-    fn capacity(&self) -> usize {
-        self.max_chunks * TraceBufferChunk::K_CHUNK_SIZE
+    /// Returns (chunk_index, chunk_seq, event_index).
+    fn extract_handle(&self, handle: u64) -> (usize, u32, usize) {
+        let chunk_seq = (handle / self.capacity()) as u32;
+        let indices = (handle % self.capacity()) as usize;
+        (indices / TraceBufferChunk::K_CHUNK_SIZE, chunk_seq, indices % TraceBufferChunk::K_CHUNK_SIZE)
     }
 
     fn next_chunk_index(&self, index: usize) -> usize {
-        (index + 1) % self.max_chunks
+        if index + 1 == self.max_chunks {
+            0
+        } else {
+            index + 1
+        }
     }
 }
 
 impl TraceBuffer for TraceBufferRingBuffer {
-    fn add_trace_event(&mut self, handle: &mut u64) -> Option<&mut dyn TraceObject> {
-        let _lock = self.mutex.lock().unwrap();
-        // Implement adding a trace event and updating the handle
-        // Return None if no space is available.
-        
-        //Placeholder return
-        None
+    fn add_trace_event(&mut self) -> Option<(&mut TraceObject, u64)> {
+        let current_is_full = self.chunks[self.chunk_index].as_ref().is_none_or(|chunk| chunk.is_full());
+        if self.is_empty || current_is_full {
+            let next_index = if self.is_empty { 0 } else { self.next_chunk_index(self.chunk_index) };
+            let wraps = !self.is_empty && self.chunks[next_index].is_some();
+            if wraps && self.record_mode != TraceRecordMode::kRecordContinuously {
+                return None;
+            }
+            self.chunk_index = next_index;
+            self.is_empty = false;
+            let seq = self.current_chunk_seq;
+            self.current_chunk_seq += 1;
+            match &mut self.chunks[next_index] {
+                Some(chunk) => chunk.reset(seq),
+                chunk => *chunk = Some(Box::new(TraceBufferChunk::new(seq))),
+            }
+        }
+        let chunk_index = self.chunk_index;
+        let chunk = self.chunks[chunk_index].as_ref().unwrap();
+        let handle = self.make_handle(chunk_index, chunk.seq(), chunk.size());
+        let (trace_object, _) = self.chunks[chunk_index].as_mut().unwrap().add_trace_event();
+        Some((trace_object, handle))
     }
 
-    fn get_event_by_handle(&self, handle: u64) -> Option<&dyn TraceObject> {
-        // Implement retrieving an event by its handle
-        //Placeholder return
-        None
+    fn get_event_by_handle(&mut self, handle: u64) -> Option<&mut TraceObject> {
+        let (chunk_index, chunk_seq, event_index) = self.extract_handle(handle);
+        if chunk_index >= self.chunks.len() {
+            return None;
+        }
+        let chunk = self.chunks[chunk_index].as_mut()?;
+        if chunk.seq() != chunk_seq || event_index >= chunk.size() {
+            return None;
+        }
+        Some(chunk.get_event_at(event_index))
     }
 
     fn flush(&mut self) -> bool {
-        let _lock = self.mutex.lock().unwrap();
-        // Implement flushing the buffer to the trace writer.
-        // Return true on success, false otherwise.
+        // This flushes all the traces stored in the buffer, oldest first.
+        if !self.is_empty {
+            let mut i = self.next_chunk_index(self.chunk_index);
+            loop {
+                if let Some(chunk) = &self.chunks[i] {
+                    for event in chunk.events() {
+                        self.trace_writer.append_trace_event(event);
+                    }
+                }
+                if i == self.chunk_index {
+                    break;
+                }
+                i = self.next_chunk_index(i);
+            }
+        }
+        self.trace_writer.flush();
+        // This resets the trace buffer.
+        self.chunks.iter_mut().for_each(|chunk| *chunk = None);
+        self.is_empty = true;
         true
     }
-}
\ No newline at end of file
+
+    fn set_record_mode(&mut self, mode: TraceRecordMode) {
+        self.record_mode = mode;
+    }
+}
+
+pub fn create_trace_buffer_ring_buffer(max_chunks: usize, trace_writer: Box<dyn TraceWriter>) -> Box<dyn TraceBuffer> {
+    Box::new(TraceBufferRingBuffer::new(max_chunks, trace_writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingWriter(Arc<Mutex<Vec<String>>>);
+
+    impl TraceWriter for RecordingWriter {
+        fn append_trace_event(&mut self, trace_event: &TraceObject) {
+            self.0.lock().unwrap().push(trace_event.name().to_string());
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn add(buffer: &mut dyn TraceBuffer, name: usize) -> Option<u64> {
+        let (trace_object, handle) = buffer.add_trace_event()?;
+        trace_object.initialize(b'X', "v8", Cow::Owned(name.to_string()), None, 0, 0, Vec::new(), 0, 0, 0);
+        Some(handle)
+    }
+
+    #[test]
+    fn ring_buffer_overwrites_oldest_chunks_when_recording_continuously() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut buffer = TraceBufferRingBuffer::new(2, Box::new(RecordingWriter(written.clone())));
+        let events = 3 * TraceBufferChunk::K_CHUNK_SIZE;
+        let handles: Vec<u64> = (0..events).map(|i| add(&mut buffer, i).unwrap()).collect();
+
+        // The first chunk was reused, which invalidates its handles.
+        assert!(buffer.get_event_by_handle(handles[0]).is_none());
+        assert_eq!(buffer.get_event_by_handle(handles[events - 1]).unwrap().name(), (events - 1).to_string());
+
+        assert!(buffer.flush());
+        let written = written.lock().unwrap();
+        let expected: Vec<String> = (TraceBufferChunk::K_CHUNK_SIZE..events).map(|i| i.to_string()).collect();
+        assert_eq!(*written, expected);
+    }
+
+    #[test]
+    fn ring_buffer_stops_when_full_in_record_until_full_mode() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut buffer = TraceBufferRingBuffer::new(2, Box::new(RecordingWriter(written.clone())));
+        buffer.set_record_mode(TraceRecordMode::kRecordUntilFull);
+        let capacity = 2 * TraceBufferChunk::K_CHUNK_SIZE;
+        for i in 0..capacity {
+            assert!(add(&mut buffer, i).is_some());
+        }
+        assert!(add(&mut buffer, capacity).is_none());
+        buffer.flush();
+        assert_eq!(written.lock().unwrap().len(), capacity);
+    }
+}
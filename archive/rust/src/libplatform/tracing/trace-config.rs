@@ -3,50 +3,364 @@
 // found in the LICENSE file.
 
 use std::fmt;
-use std::str;
 
-/// Represents the trace configuration for the V8 engine.
+/// What the trace buffer does once it is full.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TraceRecordMode {
+    /// Record until the trace buffer is full.
+    #[default]
+    kRecordUntilFull,
+    /// Record until the user ends the trace. The trace buffer is a fixed size
+    /// and we use it as a ring buffer during recording.
+    kRecordContinuously,
+    /// Record until the trace buffer is full, but with a huge buffer size.
+    kRecordAsMuchAsPossible,
+    /// Echo to console. Events are discarded.
+    kEchoToConsole,
+}
+
+impl TraceRecordMode {
+    /// The names used in JSON trace configs.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "record-until-full" => Some(TraceRecordMode::kRecordUntilFull),
+            "record-continuously" => Some(TraceRecordMode::kRecordContinuously),
+            "record-as-much-as-possible" => Some(TraceRecordMode::kRecordAsMuchAsPossible),
+            "trace-to-console" => Some(TraceRecordMode::kEchoToConsole),
+            _ => None,
+        }
+    }
+}
+
+/// Which trace categories to record and how.
+#[derive(Clone, Default)]
 pub struct TraceConfig {
+    record_mode: TraceRecordMode,
+    enable_systrace: bool,
+    enable_argument_filter: bool,
     included_categories: Vec<String>,
+    excluded_categories: Vec<String>,
 }
 
 impl TraceConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     /// Creates a default `TraceConfig` with the "v8" category included.
     pub fn create_default_trace_config() -> Self {
-        let mut trace_config = TraceConfig {
-            included_categories: Vec::new(),
-        };
-        trace_config.included_categories.push("v8".to_string());
+        let mut trace_config = TraceConfig::new();
+        trace_config.add_included_category("v8");
         trace_config
     }
 
-    /// Checks if a given category group is enabled based on the included categories.
-    pub fn is_category_group_enabled(&self, category_group: &str) -> bool {
-        for category in category_group.split(',') {
-            let category = category.trim();
-            if category.is_empty() {
-              continue;
-            }
-            for included_category in &self.included_categories {
-                if category == included_category {
-                    return true;
+    /// Parses a trace config in the JSON format accepted by d8's
+    /// --trace-config, e.g.
+    ///
+    /// ```text
+    /// {
+    ///   "record_mode": "record-continuously",
+    ///   "included_categories": ["v8", "disabled-by-default-v8.gc"],
+    ///   "excluded_categories": ["v8.runtime"]
+    /// }
+    /// ```
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let JsonValue::Object(members) = JsonParser::new(json).parse()? else {
+            return Err("trace config must be a JSON object".to_string());
+        };
+        let mut trace_config = TraceConfig::new();
+        for (key, value) in members {
+            match (key.as_str(), value) {
+                ("record_mode", JsonValue::String(mode)) => {
+                    trace_config.record_mode =
+                        TraceRecordMode::from_name(&mode).ok_or_else(|| format!("unknown record mode \"{mode}\""))?;
                 }
+                ("enable_systrace", JsonValue::Bool(enabled)) => trace_config.enable_systrace = enabled,
+                ("enable_argument_filter", JsonValue::Bool(enabled)) => trace_config.enable_argument_filter = enabled,
+                ("included_categories", JsonValue::Array(categories)) => {
+                    for category in categories {
+                        let JsonValue::String(category) = category else {
+                            return Err("categories must be strings".to_string());
+                        };
+                        trace_config.add_included_category(&category);
+                    }
+                }
+                ("excluded_categories", JsonValue::Array(categories)) => {
+                    for category in categories {
+                        let JsonValue::String(category) = category else {
+                            return Err("categories must be strings".to_string());
+                        };
+                        trace_config.add_excluded_category(&category);
+                    }
+                }
+                (key, _) => return Err(format!("unexpected trace config entry \"{key}\"")),
             }
         }
-        false
+        Ok(trace_config)
+    }
+
+    pub fn get_trace_record_mode(&self) -> TraceRecordMode {
+        self.record_mode
+    }
+
+    pub fn get_enabled_categories(&self) -> &[String] {
+        &self.included_categories
+    }
+
+    pub fn is_systrace_enabled(&self) -> bool {
+        self.enable_systrace
+    }
+
+    pub fn is_argument_filter_enabled(&self) -> bool {
+        self.enable_argument_filter
+    }
+
+    pub fn set_trace_record_mode(&mut self, mode: TraceRecordMode) {
+        self.record_mode = mode;
+    }
+
+    pub fn enable_systrace(&mut self) {
+        self.enable_systrace = true;
+    }
+
+    pub fn enable_argument_filter(&mut self) {
+        self.enable_argument_filter = true;
     }
 
-    /// Adds a category to the list of included categories.
     pub fn add_included_category(&mut self, included_category: &str) {
         assert!(!included_category.is_empty());
         self.included_categories.push(included_category.to_string());
     }
+
+    pub fn add_excluded_category(&mut self, excluded_category: &str) {
+        assert!(!excluded_category.is_empty());
+        self.excluded_categories.push(excluded_category.to_string());
+    }
+
+    /// A category group is a comma-separated list of categories. It is
+    /// enabled if any of its categories is included and not excluded.
+    pub fn is_category_group_enabled(&self, category_group: &str) -> bool {
+        category_group.split(',').map(str::trim).filter(|category| !category.is_empty()).any(|category| {
+            self.included_categories.iter().any(|pattern| Self::matches(pattern, category))
+                && !self.excluded_categories.iter().any(|pattern| Self::matches(pattern, category))
+        })
+    }
+
+    /// Patterns match exactly, or by prefix if they end in '*'. Wildcards
+    /// never enable disabled-by-default categories unless the pattern names
+    /// the prefix itself.
+    fn matches(pattern: &str, category: &str) -> bool {
+        const K_DISABLED_BY_DEFAULT_PREFIX: &str = "disabled-by-default-";
+        match pattern.strip_suffix('*') {
+            Some(prefix) => {
+                category.starts_with(prefix)
+                    && (!category.starts_with(K_DISABLED_BY_DEFAULT_PREFIX)
+                        || prefix.starts_with(K_DISABLED_BY_DEFAULT_PREFIX))
+            }
+            None => pattern == category,
+        }
+    }
 }
 
 impl fmt::Debug for TraceConfig {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("TraceConfig")
+            .field("record_mode", &self.record_mode)
             .field("included_categories", &self.included_categories)
+            .field("excluded_categories", &self.excluded_categories)
             .finish()
     }
-}
\ No newline at end of file
+}
+
+/// The subset of JSON that trace configs use.
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Number,
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+struct JsonParser<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(input: &'a str) -> Self {
+        JsonParser { input: input.as_bytes(), position: 0 }
+    }
+
+    fn parse(mut self) -> Result<JsonValue, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.position != self.input.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("invalid trace config at offset {}: {message}", self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.input.get(self.position).is_some_and(u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn consume(&mut self, expected: u8) -> bool {
+        self.skip_whitespace();
+        if self.input.get(self.position) == Some(&expected) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        if self.consume(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected as char)))
+        }
+    }
+
+    fn consume_literal(&mut self, literal: &str) -> bool {
+        if self.input[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            return true;
+        }
+        false
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.input.get(self.position) {
+            Some(b'{') => self.parse_object(),
+            Some(b'[') => self.parse_array(),
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b'-' | b'0'..=b'9') => {
+                while self.input.get(self.position).is_some_and(|c| b"+-.eE0123456789".contains(c)) {
+                    self.position += 1;
+                }
+                Ok(JsonValue::Number)
+            }
+            _ if self.consume_literal("true") => Ok(JsonValue::Bool(true)),
+            _ if self.consume_literal("false") => Ok(JsonValue::Bool(false)),
+            _ if self.consume_literal("null") => Ok(JsonValue::Null),
+            _ => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.consume(b'}') {
+            return Ok(JsonValue::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            members.push((key, self.parse_value()?));
+            if self.consume(b'}') {
+                return Ok(JsonValue::Object(members));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        self.expect(b'[')?;
+        let mut elements = Vec::new();
+        if self.consume(b']') {
+            return Ok(JsonValue::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            if self.consume(b']') {
+                return Ok(JsonValue::Array(elements));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        if self.input.get(self.position) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&c) = self.input.get(self.position) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escaped) = self.input.get(self.position) else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.position += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'u' => {
+                            let digits = self
+                                .input
+                                .get(self.position..self.position + 4)
+                                .and_then(|digits| std::str::from_utf8(digits).ok())
+                                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                                .ok_or_else(|| self.error("invalid unicode escape"))?;
+                            self.position += 4;
+                            let c = char::from_u32(digits).unwrap_or(char::REPLACEMENT_CHARACTER);
+                            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_json_trace_configs() {
+        let trace_config = TraceConfig::from_json(
+            r#"{
+                "record_mode": "record-continuously",
+                "enable_systrace": true,
+                "included_categories": ["v8", "v8.*", "disabled-by-default-v8.gc"],
+                "excluded_categories": ["v8.runtime"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(trace_config.get_trace_record_mode(), TraceRecordMode::kRecordContinuously);
+        assert!(trace_config.is_systrace_enabled());
+        assert!(!trace_config.is_argument_filter_enabled());
+
+        assert!(trace_config.is_category_group_enabled("v8"));
+        assert!(trace_config.is_category_group_enabled("v8.compile"));
+        assert!(trace_config.is_category_group_enabled("blink,disabled-by-default-v8.gc"));
+        assert!(!trace_config.is_category_group_enabled("v8.runtime"));
+        assert!(!trace_config.is_category_group_enabled("disabled-by-default-v8.cpu_profiler"));
+        assert!(!trace_config.is_category_group_enabled("blink"));
+
+        assert!(TraceConfig::from_json(r#"{"record_mode": "forever"}"#).is_err());
+        assert!(TraceConfig::from_json(r#"{"included_categories": ["v8"]"#).is_err());
+    }
+}
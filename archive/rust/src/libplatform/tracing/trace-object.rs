@@ -2,317 +2,274 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulonglong, c_void};
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::{mem, ptr, slice, str};
-
-// Placeholder for v8::ConvertableToTraceFormat (cannot be directly translated)
-// It represents a trait or abstract class in C++
-trait ConvertableToTraceFormat {}
-
-// Define constants
-const TRACE_VALUE_TYPE_CONVERTABLE: u8 = 3;
-const TRACE_VALUE_TYPE_STRING: u8 = 4;
-const TRACE_VALUE_TYPE_COPY_STRING: u8 = 5;
-const TRACE_EVENT_FLAG_COPY: u32 = 1;
-const K_TRACE_MAX_NUM_ARGS: usize = 2; // Example Value, replace with actual value if known
-
-// Get current process ID (platform-specific)
-#[cfg(target_os = "linux")]
-fn get_current_process_id() -> i32 {
-    unsafe { libc::getpid() }
+use std::borrow::Cow;
+
+/// An argument value that serializes itself, e.g. a `TracedValue`.
+pub trait ConvertableToTraceFormat: Send {
+    /// Append the class info to the provided `out` string. The appended
+    /// data must be a valid JSON object. Strings must be properly quoted, and
+    /// escaped. There is no processing applied to the content after it is
+    /// appended.
+    fn append_as_trace_format(&self, out: &mut String);
 }
 
-#[cfg(target_os = "macos")]
-fn get_current_process_id() -> i32 {
-    unsafe { libc::getpid() }
+/// The value of a trace event argument.
+#[allow(non_camel_case_types)]
+pub enum TraceArgValue {
+    kBool(bool),
+    kUint(u64),
+    kInt(i64),
+    kDouble(f64),
+    kPointer(usize),
+    kString(Cow<'static, str>),
+    kConvertable(Box<dyn ConvertableToTraceFormat>),
 }
 
-#[cfg(target_os = "windows")]
-fn get_current_process_id() -> i32 {
-    unsafe { kernel32::GetCurrentProcessId() as i32 }
+impl From<bool> for TraceArgValue {
+    fn from(value: bool) -> Self {
+        TraceArgValue::kBool(value)
+    }
 }
 
-#[cfg(target_os = "windows")]
-extern "system" {
-    fn kernel32::GetCurrentProcessId() -> u32;
+impl From<u64> for TraceArgValue {
+    fn from(value: u64) -> Self {
+        TraceArgValue::kUint(value)
+    }
 }
 
-// Get current thread ID (platform-specific)
-#[cfg(target_os = "linux")]
-fn get_current_thread_id() -> i32 {
-    unsafe { libc::syscall(libc::SYS_gettid) as i32 }
+impl From<u32> for TraceArgValue {
+    fn from(value: u32) -> Self {
+        TraceArgValue::kUint(value as u64)
+    }
 }
 
-#[cfg(target_os = "macos")]
-fn get_current_thread_id() -> i32 {
-    unsafe { libc::pthread_mach_thread_np(libc::pthread_self()) as i32 }
+impl From<usize> for TraceArgValue {
+    fn from(value: usize) -> Self {
+        TraceArgValue::kUint(value as u64)
+    }
 }
 
-#[cfg(target_os = "windows")]
-fn get_current_thread_id() -> i32 {
-    unsafe { kernel32::GetCurrentThreadId() as i32 }
+impl From<i64> for TraceArgValue {
+    fn from(value: i64) -> Self {
+        TraceArgValue::kInt(value)
+    }
+}
+
+impl From<i32> for TraceArgValue {
+    fn from(value: i32) -> Self {
+        TraceArgValue::kInt(value as i64)
+    }
 }
 
-#[cfg(target_os = "windows")]
-extern "system" {
-    fn kernel32::GetCurrentThreadId() -> u32;
+impl From<f64> for TraceArgValue {
+    fn from(value: f64) -> Self {
+        TraceArgValue::kDouble(value)
+    }
 }
 
-#[cfg(all(target_os = "macos", feature = "use_mach"))]
-extern "C" {
-    fn pthread_mach_thread_np(thread: libc::pthread_t) -> libc::mach_port_t;
+impl From<&'static str> for TraceArgValue {
+    fn from(value: &'static str) -> Self {
+        TraceArgValue::kString(Cow::Borrowed(value))
+    }
 }
 
-#[derive(Clone, Copy)]
-#[repr(C)]
-union ArgValue {
-    as_uint: u64,
-    as_string: *const c_char,
+impl From<String> for TraceArgValue {
+    fn from(value: String) -> Self {
+        TraceArgValue::kString(Cow::Owned(value))
+    }
 }
 
-/// Represents a trace object.
+impl<T: ConvertableToTraceFormat + 'static> From<Box<T>> for TraceArgValue {
+    fn from(value: Box<T>) -> Self {
+        TraceArgValue::kConvertable(value)
+    }
+}
+
+/// A named trace event argument.
+pub struct TraceArg {
+    pub name: &'static str,
+    pub value: TraceArgValue,
+}
+
+impl TraceArg {
+    pub fn new(name: &'static str, value: impl Into<TraceArgValue>) -> Self {
+        TraceArg { name, value: value.into() }
+    }
+}
+
+/// Events keep at most this many arguments; the rest are dropped.
+pub const K_TRACE_MAX_NUM_ARGS: usize = 2;
+
+/// A recorded trace event, in the format of the Trace Event Format
+/// (https://goo.gl/yMeyUY) used by chrome://tracing and ui.perfetto.dev.
+/// Timestamps are in microseconds.
 pub struct TraceObject {
-    pid_: i32,
-    tid_: i32,
-    phase_: c_char,
-    category_enabled_flag_: *const u8,
-    name_: *const c_char,
-    scope_: *const c_char,
-    id_: u64,
-    bind_id_: u64,
-    flags_: u32,
-    ts_: i64,
-    tts_: i64,
-    duration_: i64,
-    cpu_duration_: i64,
-    num_args_: usize,
-    arg_names_: [*const c_char; K_TRACE_MAX_NUM_ARGS],
-    arg_values_: [ArgValue; K_TRACE_MAX_NUM_ARGS],
-    arg_types_: [u8; K_TRACE_MAX_NUM_ARGS],
-    arg_convertables_: [Option<Box<dyn ConvertableToTraceFormat>>; K_TRACE_MAX_NUM_ARGS],
-    parameter_copy_storage_: *mut c_char,
+    pid: i32,
+    tid: i32,
+    phase: u8,
+    category_group: &'static str,
+    name: Cow<'static, str>,
+    scope: Option<&'static str>,
+    id: u64,
+    bind_id: u64,
+    flags: u32,
+    ts: i64,
+    tts: i64,
+    duration: i64,
+    cpu_duration: i64,
+    args: Vec<TraceArg>,
 }
 
-impl TraceObject {
-    /// Creates a new `TraceObject`.
-    pub fn new() -> Self {
+impl Default for TraceObject {
+    fn default() -> Self {
         TraceObject {
-            pid_: 0,
-            tid_: 0,
-            phase_: 0,
-            category_enabled_flag_: ptr::null(),
-            name_: ptr::null(),
-            scope_: ptr::null(),
-            id_: 0,
-            bind_id_: 0,
-            flags_: 0,
-            ts_: 0,
-            tts_: 0,
-            duration_: 0,
-            cpu_duration_: 0,
-            num_args_: 0,
-            arg_names_: [ptr::null(); K_TRACE_MAX_NUM_ARGS],
-            arg_values_: [ArgValue { as_uint: 0 }; K_TRACE_MAX_NUM_ARGS],
-            arg_types_: [0; K_TRACE_MAX_NUM_ARGS],
-            arg_convertables_: [None; K_TRACE_MAX_NUM_ARGS],
-            parameter_copy_storage_: ptr::null_mut(),
+            pid: 0,
+            tid: 0,
+            phase: 0,
+            category_group: "",
+            name: Cow::Borrowed(""),
+            scope: None,
+            id: 0,
+            bind_id: 0,
+            flags: 0,
+            ts: 0,
+            tts: 0,
+            duration: 0,
+            cpu_duration: 0,
+            args: Vec::new(),
         }
     }
+}
 
-    /// Initializes the `TraceObject` with the given parameters.
+impl TraceObject {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize(
         &mut self,
-        phase: c_char,
-        category_enabled_flag: *const u8,
-        name: *const c_char,
-        scope: *const c_char,
+        phase: u8,
+        category_group: &'static str,
+        name: Cow<'static, str>,
+        scope: Option<&'static str>,
         id: u64,
         bind_id: u64,
-        num_args: c_int,
-        arg_names: &[*const c_char],
-        arg_types: &[u8],
-        arg_values: &[u64],
-        arg_convertables: &mut [Option<Box<dyn ConvertableToTraceFormat>>],
-        flags: c_uint,
+        mut args: Vec<TraceArg>,
+        flags: u32,
         timestamp: i64,
         cpu_timestamp: i64,
     ) {
-        self.pid_ = get_current_process_id();
-        self.tid_ = get_current_thread_id();
-        self.phase_ = phase;
-        self.category_enabled_flag_ = category_enabled_flag;
-        self.name_ = name;
-        self.scope_ = scope;
-        self.id_ = id;
-        self.bind_id_ = bind_id;
-        self.flags_ = flags;
-        self.ts_ = timestamp;
-        self.tts_ = cpu_timestamp;
-        self.duration_ = 0;
-        self.cpu_duration_ = 0;
-
-        let num_args = num_args as usize;
-
-        // Clamp num_args
-        self.num_args_ = if num_args > K_TRACE_MAX_NUM_ARGS {
-            K_TRACE_MAX_NUM_ARGS
-        } else {
-            num_args
+        args.truncate(K_TRACE_MAX_NUM_ARGS);
+        *self = TraceObject {
+            pid: get_current_process_id(),
+            tid: get_current_thread_id(),
+            phase,
+            category_group,
+            name,
+            scope,
+            id,
+            bind_id,
+            flags,
+            ts: timestamp,
+            tts: cpu_timestamp,
+            duration: 0,
+            cpu_duration: 0,
+            args,
         };
-
-        for i in 0..self.num_args_ {
-            self.arg_names_[i] = arg_names[i];
-            self.arg_values_[i].as_uint = arg_values[i];
-            self.arg_types_[i] = arg_types[i];
-            if arg_types[i] == TRACE_VALUE_TYPE_CONVERTABLE {
-                self.arg_convertables_[i] = arg_convertables[i].take();
-            } else {
-                self.arg_convertables_[i] = None;
-            }
-        }
-
-        let copy = (flags & TRACE_EVENT_FLAG_COPY) != 0;
-
-        let mut alloc_size: usize = 0;
-        if copy {
-            alloc_size += get_alloc_length(self.name_);
-            alloc_size += get_alloc_length(self.scope_);
-            for i in 0..self.num_args_ {
-                alloc_size += get_alloc_length(self.arg_names_[i]);
-                if self.arg_types_[i] == TRACE_VALUE_TYPE_STRING {
-                    self.arg_types_[i] = TRACE_VALUE_TYPE_COPY_STRING;
-                }
-            }
-        }
-
-        let mut arg_is_copy = [false; K_TRACE_MAX_NUM_ARGS];
-        for i in 0..self.num_args_ {
-            arg_is_copy[i] = self.arg_types_[i] == TRACE_VALUE_TYPE_COPY_STRING;
-            if arg_is_copy[i] {
-                unsafe {
-                    alloc_size += get_alloc_length(self.arg_values_[i].as_string);
-                }
-            }
-        }
-
-        if alloc_size > 0 {
-            // Since TraceObject can be initialized multiple times, we might need
-            // to free old memory.
-            if !self.parameter_copy_storage_.is_null() {
-                unsafe {
-                    drop(Vec::from_raw_parts(
-                        self.parameter_copy_storage_ as *mut u8,
-                        0,
-                        alloc_size,
-                    ));
-                }
-            }
-
-            let mut buffer = Vec::with_capacity(alloc_size);
-            unsafe {
-                buffer.set_len(alloc_size);
-            }
-            let mut ptr = buffer.as_mut_ptr() as *mut c_char;
-            self.parameter_copy_storage_ = ptr;
-
-            if copy {
-                copy_trace_object_parameter(&mut ptr, &mut self.name_);
-                copy_trace_object_parameter(&mut ptr, &mut self.scope_);
-                for i in 0..self.num_args_ {
-                    copy_trace_object_parameter(&mut ptr, &mut self.arg_names_[i]);
-                }
-            }
-
-            for i in 0..self.num_args_ {
-                if arg_is_copy[i] {
-                    unsafe {
-                        copy_trace_object_parameter(&mut ptr, &mut self.arg_values_[i].as_string);
-                    }
-                }
-            }
-
-            mem::forget(buffer);
-        }
     }
 
-    /// Updates the duration of the trace object.
     pub fn update_duration(&mut self, timestamp: i64, cpu_timestamp: i64) {
-        self.duration_ = timestamp - self.ts_;
-        self.cpu_duration_ = cpu_timestamp - self.tts_;
+        self.duration = timestamp - self.ts;
+        self.cpu_duration = cpu_timestamp - self.tts;
     }
 
-    /// Initializes the `TraceObject` for testing purposes.
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_for_testing(
         &mut self,
-        phase: c_char,
-        category_enabled_flag: *const u8,
-        name: *const c_char,
-        scope: *const c_char,
+        phase: u8,
+        category_group: &'static str,
+        name: Cow<'static, str>,
+        scope: Option<&'static str>,
         id: u64,
         bind_id: u64,
-        num_args: c_int,
-        arg_names: &[*const c_char],
-        arg_types: &[u8],
-        arg_values: &[u64],
-        arg_convertables: &mut [Option<Box<dyn ConvertableToTraceFormat>>],
-        flags: c_uint,
+        args: Vec<TraceArg>,
+        flags: u32,
         pid: i32,
         tid: i32,
         ts: i64,
         tts: i64,
-        duration: u64,
-        cpu_duration: u64,
+        duration: i64,
+        cpu_duration: i64,
     ) {
-        self.pid_ = pid;
-        self.tid_ = tid;
-        self.phase_ = phase;
-        self.category_enabled_flag_ = category_enabled_flag;
-        self.name_ = name;
-        self.scope_ = scope;
-        self.id_ = id;
-        self.bind_id_ = bind_id;
-        self.num_args_ = num_args as usize;
-        self.flags_ = flags;
-        self.ts_ = ts;
-        self.tts_ = tts;
-        self.duration_ = duration as i64;
-        self.cpu_duration_ = cpu_duration as i64;
+        self.initialize(phase, category_group, name, scope, id, bind_id, args, flags, ts, tts);
+        self.pid = pid;
+        self.tid = tid;
+        self.duration = duration;
+        self.cpu_duration = cpu_duration;
     }
-}
 
-impl Drop for TraceObject {
-    fn drop(&mut self) {
-        if !self.parameter_copy_storage_.is_null() {
-            unsafe {
-                libc::free(self.parameter_copy_storage_ as *mut c_void);
-            }
-        }
+    pub fn pid(&self) -> i32 {
+        self.pid
     }
-}
 
-/// Calculates the allocation length for a C-style string.
-fn get_alloc_length(str: *const c_char) -> usize {
-    if str.is_null() {
-        0
-    } else {
-        unsafe { strlen(str) + 1 }
+    pub fn tid(&self) -> i32 {
+        self.tid
     }
-}
 
-extern "C" {
-    fn strlen(s: *const c_char) -> usize;
-}
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    pub fn category_group(&self) -> &'static str {
+        self.category_group
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-/// Copies a trace object parameter.
-fn copy_trace_object_parameter(buffer: &mut *mut c_char, member: &mut *const c_char) {
-    if member.is_null() {
-        return;
+    pub fn scope(&self) -> Option<&'static str> {
+        self.scope
     }
-    let length = unsafe { strlen(*member) + 1 };
-    unsafe {
-        libc::memcpy(*buffer as *mut c_void, *member as *const c_void, length);
-        *member = *buffer;
-        *buffer = (*buffer).add(length);
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn bind_id(&self) -> u64 {
+        self.bind_id
+    }
+
+    pub fn flags(&self) -> u32 {
+        self.flags
     }
-}
\ No newline at end of file
+
+    pub fn ts(&self) -> i64 {
+        self.ts
+    }
+
+    pub fn tts(&self) -> i64 {
+        self.tts
+    }
+
+    pub fn duration(&self) -> i64 {
+        self.duration
+    }
+
+    pub fn cpu_duration(&self) -> i64 {
+        self.cpu_duration
+    }
+
+    pub fn args(&self) -> &[TraceArg] {
+        &self.args
+    }
+}
+
+fn get_current_process_id() -> i32 {
+    unsafe { libc::getpid() }
+}
+
+fn get_current_thread_id() -> i32 {
+    unsafe { libc::syscall(libc::SYS_gettid) as i32 }
+}
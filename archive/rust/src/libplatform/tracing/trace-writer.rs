@@ -2,424 +2,400 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/libplatform/tracing/trace-writer.h equivalent
-mod trace_writer {
-    use std::any::Any;
-    use std::io::Write;
-
-    pub trait ConvertableToTraceFormat {
-        fn append_as_trace_format(&self, out: &mut String);
-    }
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::libplatform::tracing::trace_object::{TraceArgValue, TraceObject};
+use crate::tracing::trace_event::{
+    TRACE_EVENT_FLAG_FLOW_IN, TRACE_EVENT_FLAG_FLOW_OUT, TRACE_EVENT_FLAG_HAS_ID, TRACE_EVENT_PHASE_BEGIN,
+    TRACE_EVENT_PHASE_COMPLETE, TRACE_EVENT_PHASE_END,
+};
+
+/// Serializes trace events when the trace buffer is flushed.
+pub trait TraceWriter: Send {
+    fn append_trace_event(&mut self, trace_event: &TraceObject);
+    fn flush(&mut self);
+}
 
-    pub struct ArgValue {
-        pub as_uint: u64,
-        pub as_int: i64,
-        pub as_double: f64,
-        pub as_pointer: *const std::ffi::c_void,
-        pub as_string: *const std::ffi::c_char, //Need to convert to Rust Strings when used
+/// Writes every event to each of the writers, e.g. to produce a JSON and a
+/// Perfetto trace of the same session.
+impl TraceWriter for Vec<Box<dyn TraceWriter>> {
+    fn append_trace_event(&mut self, trace_event: &TraceObject) {
+        for writer in self.iter_mut() {
+            writer.append_trace_event(trace_event);
+        }
     }
 
-    impl ArgValue {
-        pub fn default() -> Self {
-            ArgValue {
-                as_uint: 0,
-                as_int: 0,
-                as_double: 0.0,
-                as_pointer: std::ptr::null(),
-                as_string: std::ptr::null(),
-            }
+    fn flush(&mut self) {
+        for writer in self.iter_mut() {
+            writer.flush();
         }
     }
+}
 
-    pub trait TraceWriter {
-        fn append_trace_event(&mut self, trace_event: &TraceObject);
-        fn flush(&mut self);
-    }
+/// Writes traces in the Chrome JSON trace format, as understood by
+/// chrome://tracing and ui.perfetto.dev.
+pub struct JsonTraceWriter {
+    stream: Box<dyn Write + Send>,
+    append_comma: bool,
+}
 
-    pub trait TraceObjectTrait {
-        fn pid(&self) -> i32;
-        fn tid(&self) -> i32;
-        fn ts(&self) -> i64;
-        fn tts(&self) -> i64;
-        fn phase(&self) -> &str;
-        fn category_enabled_flag(&self) -> *const std::ffi::c_char;
-        fn name(&self) -> &str;
-        fn duration(&self) -> i64;
-        fn cpu_duration(&self) -> i64;
-        fn flags(&self) -> u32;
-        fn bind_id(&self) -> u64;
-        fn scope(&self) -> *const std::ffi::c_char;
-        fn id(&self) -> u64;
-        fn arg_names(&self) -> &[*const std::ffi::c_char];
-        fn arg_types(&self) -> &[u8];
-        fn arg_values(&self) -> &[ArgValue];
-        fn arg_convertables(&self) -> &[*mut dyn ConvertableToTraceFormat];
-        fn num_args(&self) -> usize;
+impl JsonTraceWriter {
+    pub fn new(stream: Box<dyn Write + Send>) -> Self {
+        Self::with_tag(stream, "traceEvents")
     }
 
-    // Dummy implementation, needs to be implemented properly
-    pub struct TraceObject {
-        pid: i32,
-        tid: i32,
-        ts: i64,
-        tts: i64,
-        phase: String,
-        category_enabled_flag: *const std::ffi::c_char,
-        name: String,
-        duration: i64,
-        cpu_duration: i64,
-        flags: u32,
-        bind_id: u64,
-        scope: *const std::ffi::c_char,
-        id: u64,
-        arg_names: Vec<*const std::ffi::c_char>,
-        arg_types: Vec<u8>,
-        arg_values: Vec<ArgValue>,
-        arg_convertables: Vec<*mut dyn ConvertableToTraceFormat>,
-        num_args: usize,
+    /// Writes the events into the array named `tag` of the top-level object.
+    pub fn with_tag(mut stream: Box<dyn Write + Send>, tag: &str) -> Self {
+        let _ = write!(stream, "{{\"{tag}\":[");
+        JsonTraceWriter { stream, append_comma: false }
     }
 
-    impl TraceObject {
-        pub fn new() -> Self {
-            TraceObject {
-                pid: 0,
-                tid: 0,
-                ts: 0,
-                tts: 0,
-                phase: String::new(),
-                category_enabled_flag: std::ptr::null(),
-                name: String::new(),
-                duration: 0,
-                cpu_duration: 0,
-                flags: 0,
-                bind_id: 0,
-                scope: std::ptr::null(),
-                id: 0,
-                arg_names: Vec::new(),
-                arg_types: Vec::new(),
-                arg_values: Vec::new(),
-                arg_convertables: Vec::new(),
-                num_args: 0,
+    fn append_arg_value(out: &mut String, value: &TraceArgValue) {
+        match value {
+            TraceArgValue::kBool(value) => out.push_str(if *value { "true" } else { "false" }),
+            TraceArgValue::kUint(value) => {
+                let _ = write!(out, "{value}");
+            }
+            TraceArgValue::kInt(value) => {
+                let _ = write!(out, "{value}");
+            }
+            TraceArgValue::kDouble(value) => {
+                if value.is_finite() {
+                    let real = format!("{value}");
+                    // Ensure that the number has a .0 if there's no decimal or
+                    // 'e'. This makes sure that when we read the JSON back,
+                    // it's interpreted as a real rather than an int.
+                    out.push_str(&real);
+                    if !real.contains(['.', 'e', 'E']) {
+                        out.push_str(".0");
+                    }
+                } else if value.is_nan() {
+                    // The JSON spec doesn't allow NaN and Infinity (since
+                    // these are objects in EcmaScript). Use strings instead.
+                    out.push_str("\"NaN\"");
+                } else if *value < 0.0 {
+                    out.push_str("\"-Infinity\"");
+                } else {
+                    out.push_str("\"Infinity\"");
+                }
+            }
+            TraceArgValue::kPointer(value) => {
+                // JSON only supports double and int numbers. So as not to
+                // lose bits from a 64-bit pointer, output as a hex string.
+                let _ = write!(out, "\"{value:#x}\"");
             }
+            TraceArgValue::kString(value) => write_json_string(out, value),
+            TraceArgValue::kConvertable(value) => value.append_as_trace_format(out),
         }
+    }
+}
 
-        pub fn set_pid(&mut self, pid: i32) {
-            self.pid = pid;
+/// Appends `value` as a quoted JSON string.
+pub fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            // Note that because we use double quotes for JSON strings, we
+            // don't need to escape single quotes.
+            c if c < ' ' || c == '\u{7f}' => {
+                let _ = write!(out, "\\u{:04X}", c as u32);
+            }
+            c => out.push(c),
         }
     }
+    out.push('"');
+}
 
-    impl TraceObjectTrait for TraceObject {
-        fn pid(&self) -> i32 {
-            self.pid
-        }
-        fn tid(&self) -> i32 {
-            self.tid
-        }
-        fn ts(&self) -> i64 {
-            self.ts
-        }
-        fn tts(&self) -> i64 {
-            self.tts
-        }
-        fn phase(&self) -> &str {
-            &self.phase
-        }
-        fn category_enabled_flag(&self) -> *const std::ffi::c_char {
-            self.category_enabled_flag
+impl TraceWriter for JsonTraceWriter {
+    fn append_trace_event(&mut self, trace_event: &TraceObject) {
+        let mut out = String::new();
+        if self.append_comma {
+            out.push(',');
         }
-        fn name(&self) -> &str {
-            &self.name
-        }
-        fn duration(&self) -> i64 {
-            self.duration
-        }
-        fn cpu_duration(&self) -> i64 {
-            self.cpu_duration
-        }
-        fn flags(&self) -> u32 {
-            self.flags
-        }
-        fn bind_id(&self) -> u64 {
-            self.bind_id
-        }
-        fn scope(&self) -> *const std::ffi::c_char {
-            self.scope
-        }
-        fn id(&self) -> u64 {
-            self.id
-        }
-        fn arg_names(&self) -> &[*const std::ffi::c_char] {
-            &self.arg_names
-        }
-        fn arg_types(&self) -> &[u8] {
-            &self.arg_types
-        }
-        fn arg_values(&self) -> &[ArgValue] {
-            &self.arg_values
+        self.append_comma = true;
+        let _ = write!(
+            out,
+            "{{\"pid\":{},\"tid\":{},\"ts\":{},\"tts\":{},\"ph\":\"{}\",\"cat\":",
+            trace_event.pid(),
+            trace_event.tid(),
+            trace_event.ts(),
+            trace_event.tts(),
+            trace_event.phase() as char,
+        );
+        write_json_string(&mut out, trace_event.category_group());
+        out.push_str(",\"name\":");
+        write_json_string(&mut out, trace_event.name());
+        let _ = write!(out, ",\"dur\":{},\"tdur\":{}", trace_event.duration(), trace_event.cpu_duration());
+        let flags = trace_event.flags();
+        if flags & (TRACE_EVENT_FLAG_FLOW_IN | TRACE_EVENT_FLAG_FLOW_OUT) != 0 {
+            let _ = write!(out, ",\"bind_id\":\"{:#x}\"", trace_event.bind_id());
+            if flags & TRACE_EVENT_FLAG_FLOW_IN != 0 {
+                out.push_str(",\"flow_in\":true");
+            }
+            if flags & TRACE_EVENT_FLAG_FLOW_OUT != 0 {
+                out.push_str(",\"flow_out\":true");
+            }
         }
-        fn arg_convertables(&self) -> &[*mut dyn ConvertableToTraceFormat] {
-            &self.arg_convertables
+        if flags & TRACE_EVENT_FLAG_HAS_ID != 0 {
+            if let Some(scope) = trace_event.scope() {
+                out.push_str(",\"scope\":");
+                write_json_string(&mut out, scope);
+            }
+            // So as not to lose bits from a 64-bit integer ID, serialize it
+            // as a hex string.
+            let _ = write!(out, ",\"id\":\"{:#x}\"", trace_event.id());
         }
-        fn num_args(&self) -> usize {
-            self.num_args
+        out.push_str(",\"args\":{");
+        for (i, arg) in trace_event.args().iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, arg.name);
+            out.push(':');
+            Self::append_arg_value(&mut out, &arg.value);
         }
+        out.push_str("}}");
+        let _ = self.stream.write_all(out.as_bytes());
     }
 
-    pub fn create_json_trace_writer<W: Write>(stream: W) -> Box<dyn TraceWriter> {
-        Box::new(JSONTraceWriter::new(stream, "traceEvents".to_string()))
+    fn flush(&mut self) {
+        let _ = self.stream.flush();
     }
+}
 
-    pub fn create_json_trace_writer_with_tag<W: Write>(stream: W, tag: String) -> Box<dyn TraceWriter> {
-        Box::new(JSONTraceWriter::new(stream, tag))
+impl Drop for JsonTraceWriter {
+    fn drop(&mut self) {
+        let _ = self.stream.write_all(b"]}");
+        let _ = self.stream.flush();
     }
+}
 
-    // Values for arg types
-    pub const TRACE_VALUE_TYPE_BOOL: u8 = 0;
-    pub const TRACE_VALUE_TYPE_UINT: u8 = 1;
-    pub const TRACE_VALUE_TYPE_INT: u8 = 2;
-    pub const TRACE_VALUE_TYPE_DOUBLE: u8 = 3;
-    pub const TRACE_VALUE_TYPE_POINTER: u8 = 4;
-    pub const TRACE_VALUE_TYPE_STRING: u8 = 5;
-    pub const TRACE_VALUE_TYPE_COPY_STRING: u8 = 6;
-    pub const TRACE_VALUE_TYPE_CONVERTABLE: u8 = 7;
-
-    // Flags for trace events
-    pub const TRACE_EVENT_FLAG_FLOW_IN: u32 = 1 << 0;
-    pub const TRACE_EVENT_FLAG_FLOW_OUT: u32 = 1 << 1;
-    pub const TRACE_EVENT_FLAG_HAS_ID: u32 = 1 << 2;
-
-    //TODO: Add SystemInstrumentationTraceWriter implementation when Recorder is implemented.
+/// A protobuf message under construction. Only the wire types used by
+/// Perfetto traces are supported.
+#[derive(Default)]
+struct ProtoMessage {
+    bytes: Vec<u8>,
 }
 
-// src/libplatform/tracing/trace-writer.cc equivalent
-pub mod tracing {
-    use super::trace_writer::*;
-    use std::io::Write;
-    use std::ffi::CStr;
-    use std::os::raw::c_char;
-    use std::fmt::Write as FmtWrite;
-
-    // Writes the given string to a stream, taking care to escape characters
-    // when necessary.
-    fn write_json_string_to_stream<W: Write>(str: &str, stream: &mut W) -> std::io::Result<()> {
-        stream.write_all(b"\"")?;
-        for char in str.chars() {
-            match char {
-                '\u{0008}' => stream.write_all(b"\\b")?,
-                '\u{000C}' => stream.write_all(b"\\f")?,
-                '\n' => stream.write_all(b"\\n")?,
-                '\r' => stream.write_all(b"\\r")?,
-                '\t' => stream.write_all(b"\\t")?,
-                '"' => stream.write_all(b"\\\"")?,
-                '\\' => stream.write_all(b"\\\\")?,
-                _ => {
-                    let mut buf = [0u8; 4];
-                    let s = char.encode_utf8(&mut buf);
-                    stream.write_all(s.as_bytes())?
-                },
-            }
+impl ProtoMessage {
+    const K_WIRE_TYPE_VARINT: u32 = 0;
+    const K_WIRE_TYPE_FIXED64: u32 = 1;
+    const K_WIRE_TYPE_LENGTH_DELIMITED: u32 = 2;
+
+    fn append_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push((value as u8) | 0x80);
+            value >>= 7;
         }
-        stream.write_all(b"\"")?;
-        Ok(())
+        self.bytes.push(value as u8);
     }
 
-    pub struct JSONTraceWriter<W: Write> {
-        stream: W,
-        append_comma: bool,
-        tag: String,
+    fn append_tag(&mut self, field: u32, wire_type: u32) {
+        self.append_varint(((field << 3) | wire_type) as u64);
     }
 
-    impl<W: Write> JSONTraceWriter<W> {
-        pub fn new(stream: W, tag: String) -> Self {
-            let mut writer = JSONTraceWriter {
-                stream,
-                append_comma: false,
-                tag,
-            };
-            write!(&mut writer.stream, "{{\"{}\":[", writer.tag).unwrap();
-            writer
-        }
-
-        fn append_arg_value(&mut self, type_: u8, value: ArgValue) -> std::io::Result<()> {
-            match type_ {
-                TRACE_VALUE_TYPE_BOOL => {
-                    if value.as_uint != 0 {
-                        write!(&mut self.stream, "true")?;
-                    } else {
-                        write!(&mut self.stream, "false")?;
-                    }
-                }
-                TRACE_VALUE_TYPE_UINT => {
-                    write!(&mut self.stream, "{}", value.as_uint)?;
-                }
-                TRACE_VALUE_TYPE_INT => {
-                    write!(&mut self.stream, "{}", value.as_int)?;
-                }
-                TRACE_VALUE_TYPE_DOUBLE => {
-                    let val = value.as_double;
-                    if val.is_finite() {
-                        let real = val.to_string();
-                        if !real.contains('.') && !real.contains('e') && !real.contains('E') {
-                            write!(&mut self.stream, "{}.0", real)?;
-                        } else {
-                            write!(&mut self.stream, "{}", real)?;
-                        }
-                    } else if val.is_nan() {
-                        write!(&mut self.stream, "\"NaN\"")?;
-                    } else if val < 0.0 {
-                        write!(&mut self.stream, "\"-Infinity\"")?;
-                    } else {
-                        write!(&mut self.stream, "\"Infinity\"")?;
-                    }
-                }
-                TRACE_VALUE_TYPE_POINTER => {
-                    write!(&mut self.stream, "\"{:p}\"", value.as_pointer)?;
-                }
-                TRACE_VALUE_TYPE_STRING | TRACE_VALUE_TYPE_COPY_STRING => {
-                    if value.as_string.is_null() {
-                        write!(&mut self.stream, "\"nullptr\"")?;
-                    } else {
-                        let c_str = unsafe { CStr::from_ptr(value.as_string) };
-                        let str = c_str.to_str().unwrap();
-                        write_json_string_to_stream(str, &mut self.stream)?;
-                    }
-                }
-                _ => {
-                    panic!("UNREACHABLE");
-                }
-            }
-            Ok(())
-        }
-
-        fn append_arg_value_convertable(&mut self, value: &mut dyn ConvertableToTraceFormat) -> std::io::Result<()> {
-            let mut arg_stringified = String::new();
-            value.append_as_trace_format(&mut arg_stringified);
-            write!(&mut self.stream, "{}", arg_stringified)?;
-            Ok(())
-        }
+    fn add_varint(&mut self, field: u32, value: u64) {
+        self.append_tag(field, Self::K_WIRE_TYPE_VARINT);
+        self.append_varint(value);
     }
 
-    impl<W: Write> TraceWriter for JSONTraceWriter<W> {
-        fn append_trace_event(&mut self, trace_event: &TraceObject) {
-            if self.append_comma {
-                write!(&mut self.stream, ",").unwrap();
-            }
-            self.append_comma = true;
-
-            write!(&mut self.stream,
-                "{{\"pid\":{},\"tid\":{},\"ts\":{},\"tts\":{},\"ph\":\"{}\",\"cat\":\"{}\",\"name\":\"{}\",\"dur\":{},\"tdur\":{}",
-                trace_event.pid(),
-                trace_event.tid(),
-                trace_event.ts(),
-                trace_event.tts(),
-                trace_event.phase(),
-                unsafe {
-                    CStr::from_ptr(trace_event.category_enabled_flag()).to_str().unwrap()
-                },
-                trace_event.name(),
-                trace_event.duration(),
-                trace_event.cpu_duration()
-            ).unwrap();
+    fn add_fixed64(&mut self, field: u32, value: u64) {
+        self.append_tag(field, Self::K_WIRE_TYPE_FIXED64);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
 
-            if trace_event.flags() & (TRACE_EVENT_FLAG_FLOW_IN | TRACE_EVENT_FLAG_FLOW_OUT) != 0 {
-                write!(&mut self.stream, ",\"bind_id\":\"0x{:x}\"", trace_event.bind_id()).unwrap();
+    fn add_bytes(&mut self, field: u32, value: &[u8]) {
+        self.append_tag(field, Self::K_WIRE_TYPE_LENGTH_DELIMITED);
+        self.append_varint(value.len() as u64);
+        self.bytes.extend_from_slice(value);
+    }
 
-                if trace_event.flags() & TRACE_EVENT_FLAG_FLOW_IN != 0 {
-                    write!(&mut self.stream, ",\"flow_in\":true").unwrap();
-                }
-                if trace_event.flags() & TRACE_EVENT_FLAG_FLOW_OUT != 0 {
-                    write!(&mut self.stream, ",\"flow_out\":true").unwrap();
-                }
-            }
+    fn add_string(&mut self, field: u32, value: &str) {
+        self.add_bytes(field, value.as_bytes());
+    }
 
-            if trace_event.flags() & TRACE_EVENT_FLAG_HAS_ID != 0 {
-                if !trace_event.scope().is_null() {
-                    let scope = unsafe { CStr::from_ptr(trace_event.scope()).to_str().unwrap() };
-                    write!(&mut self.stream, ",\"scope\":\"{}\"", scope).unwrap();
-                }
+    fn add_message(&mut self, field: u32, message: &ProtoMessage) {
+        self.add_bytes(field, &message.bytes);
+    }
+}
 
-                write!(&mut self.stream, ",\"id\":\"0x{:x}\"", trace_event.id()).unwrap();
-            }
+// Field numbers from perfetto/protos/perfetto/trace/.
+const K_TRACE_PACKET: u32 = 1;
+const K_TRACE_PACKET_TIMESTAMP: u32 = 8;
+const K_TRACE_PACKET_TRUSTED_PACKET_SEQUENCE_ID: u32 = 10;
+const K_TRACE_PACKET_TRACK_EVENT: u32 = 11;
+const K_TRACE_PACKET_SEQUENCE_FLAGS: u32 = 13;
+const K_TRACE_PACKET_TRACK_DESCRIPTOR: u32 = 60;
+const K_SEQ_INCREMENTAL_STATE_CLEARED: u64 = 1;
+const K_TRACK_DESCRIPTOR_UUID: u32 = 1;
+const K_TRACK_DESCRIPTOR_PROCESS: u32 = 3;
+const K_TRACK_DESCRIPTOR_THREAD: u32 = 4;
+const K_TRACK_DESCRIPTOR_PARENT_UUID: u32 = 5;
+const K_PROCESS_DESCRIPTOR_PID: u32 = 1;
+const K_THREAD_DESCRIPTOR_PID: u32 = 1;
+const K_THREAD_DESCRIPTOR_TID: u32 = 2;
+const K_TRACK_EVENT_DEBUG_ANNOTATIONS: u32 = 4;
+const K_TRACK_EVENT_TYPE: u32 = 9;
+const K_TRACK_EVENT_TRACK_UUID: u32 = 11;
+const K_TRACK_EVENT_CATEGORIES: u32 = 22;
+const K_TRACK_EVENT_NAME: u32 = 23;
+const K_TRACK_EVENT_FLOW_IDS: u32 = 47;
+const K_TYPE_SLICE_BEGIN: u64 = 1;
+const K_TYPE_SLICE_END: u64 = 2;
+const K_TYPE_INSTANT: u64 = 3;
+const K_DEBUG_ANNOTATION_BOOL_VALUE: u32 = 2;
+const K_DEBUG_ANNOTATION_UINT_VALUE: u32 = 3;
+const K_DEBUG_ANNOTATION_INT_VALUE: u32 = 4;
+const K_DEBUG_ANNOTATION_DOUBLE_VALUE: u32 = 5;
+const K_DEBUG_ANNOTATION_STRING_VALUE: u32 = 6;
+const K_DEBUG_ANNOTATION_POINTER_VALUE: u32 = 7;
+const K_DEBUG_ANNOTATION_LEGACY_JSON_VALUE: u32 = 9;
+const K_DEBUG_ANNOTATION_NAME: u32 = 10;
+
+/// Writes traces as a Perfetto protobuf `Trace`, which ui.perfetto.dev
+/// opens directly. Each thread gets its own track; complete events become
+/// slices.
+pub struct PerfettoTraceWriter {
+    stream: Box<dyn Write + Send>,
+    emitted_tracks: HashSet<u64>,
+    wrote_first_packet: bool,
+}
 
-            write!(&mut self.stream, ",\"args\":{{").unwrap();
-            let arg_names = trace_event.arg_names();
-            let arg_types = trace_event.arg_types();
-            let arg_values = trace_event.arg_values();
-            // let arg_convertables = trace_event.arg_convertables(); //TODO: Handle arg_convertables
+impl PerfettoTraceWriter {
+    const K_SEQUENCE_ID: u64 = 1;
 
-            for i in 0..trace_event.num_args() {
-                if i > 0 {
-                    write!(&mut self.stream, ",").unwrap();
-                }
-                let arg_name = unsafe { CStr::from_ptr(arg_names[i]).to_str().unwrap() };
-                write!(&mut self.stream, "\"{}\":", arg_name).unwrap();
-                
-                //TODO: Handle arg_convertables, need to figure out lifetimes here for the dyn trait
-                // if arg_types[i] == TRACE_VALUE_TYPE_CONVERTABLE {
-                //     if let Some(convertable) = arg_convertables.get(i) {
-                //         self.append_arg_value_convertable(unsafe { &mut **convertable }).unwrap();
-                //     }
-                // } else {
-                    self.append_arg_value(arg_types[i], arg_values[i]).unwrap();
-                // }
-            }
+    pub fn new(stream: Box<dyn Write + Send>) -> Self {
+        PerfettoTraceWriter { stream, emitted_tracks: HashSet::new(), wrote_first_packet: false }
+    }
 
-            write!(&mut self.stream, "}}}}").unwrap();
+    fn write_packet(&mut self, mut packet: ProtoMessage) {
+        packet.add_varint(K_TRACE_PACKET_TRUSTED_PACKET_SEQUENCE_ID, Self::K_SEQUENCE_ID);
+        if !self.wrote_first_packet {
+            packet.add_varint(K_TRACE_PACKET_SEQUENCE_FLAGS, K_SEQ_INCREMENTAL_STATE_CLEARED);
+            self.wrote_first_packet = true;
         }
+        // A trace is a sequence of packets, so packets can be streamed.
+        let mut trace = ProtoMessage::default();
+        trace.add_message(K_TRACE_PACKET, &packet);
+        let _ = self.stream.write_all(&trace.bytes);
+    }
 
-        fn flush(&mut self) {}
+    fn process_track_uuid(pid: i32) -> u64 {
+        pid as u32 as u64
     }
 
-    impl<W: Write> Drop for JSONTraceWriter<W> {
-        fn drop(&mut self) {
-            write!(&mut self.stream, "]}}").unwrap();
-        }
+    fn thread_track_uuid(pid: i32, tid: i32) -> u64 {
+        ((pid as u32 as u64) << 32) | tid as u32 as u64
     }
 
-    //Need Recorder and other parts of SystemInstrumentationTraceWriter to implement
-    #[cfg(feature = "system_instrumentation")]
-    pub struct SystemInstrumentationTraceWriter {
-        recorder: std::unique_ptr::UniquePtr<Recorder>,
+    /// Describes the tracks of the process and thread of `trace_event`
+    /// before their first event.
+    fn ensure_tracks(&mut self, pid: i32, tid: i32) {
+        let process_uuid = Self::process_track_uuid(pid);
+        if self.emitted_tracks.insert(process_uuid) {
+            let mut process = ProtoMessage::default();
+            process.add_varint(K_PROCESS_DESCRIPTOR_PID, pid as u64);
+            let mut track = ProtoMessage::default();
+            track.add_varint(K_TRACK_DESCRIPTOR_UUID, process_uuid);
+            track.add_message(K_TRACK_DESCRIPTOR_PROCESS, &process);
+            let mut packet = ProtoMessage::default();
+            packet.add_message(K_TRACE_PACKET_TRACK_DESCRIPTOR, &track);
+            self.write_packet(packet);
+        }
+        let thread_uuid = Self::thread_track_uuid(pid, tid);
+        if self.emitted_tracks.insert(thread_uuid) {
+            let mut thread = ProtoMessage::default();
+            thread.add_varint(K_THREAD_DESCRIPTOR_PID, pid as u64);
+            thread.add_varint(K_THREAD_DESCRIPTOR_TID, tid as u64);
+            let mut track = ProtoMessage::default();
+            track.add_varint(K_TRACK_DESCRIPTOR_UUID, thread_uuid);
+            track.add_varint(K_TRACK_DESCRIPTOR_PARENT_UUID, process_uuid);
+            track.add_message(K_TRACK_DESCRIPTOR_THREAD, &thread);
+            let mut packet = ProtoMessage::default();
+            packet.add_message(K_TRACE_PACKET_TRACK_DESCRIPTOR, &track);
+            self.write_packet(packet);
+        }
     }
 
-    #[cfg(feature = "system_instrumentation")]
-    impl SystemInstrumentationTraceWriter {
-        pub fn new() -> Self {
-            SystemInstrumentationTraceWriter {
-                recorder: std::unique_ptr::UniquePtr::new(Recorder::new()),
+    fn debug_annotation(name: &str, value: &TraceArgValue) -> ProtoMessage {
+        let mut annotation = ProtoMessage::default();
+        annotation.add_string(K_DEBUG_ANNOTATION_NAME, name);
+        match value {
+            TraceArgValue::kBool(value) => annotation.add_varint(K_DEBUG_ANNOTATION_BOOL_VALUE, *value as u64),
+            TraceArgValue::kUint(value) => annotation.add_varint(K_DEBUG_ANNOTATION_UINT_VALUE, *value),
+            TraceArgValue::kInt(value) => annotation.add_varint(K_DEBUG_ANNOTATION_INT_VALUE, *value as u64),
+            TraceArgValue::kDouble(value) => annotation.add_fixed64(K_DEBUG_ANNOTATION_DOUBLE_VALUE, value.to_bits()),
+            TraceArgValue::kPointer(value) => annotation.add_varint(K_DEBUG_ANNOTATION_POINTER_VALUE, *value as u64),
+            TraceArgValue::kString(value) => annotation.add_string(K_DEBUG_ANNOTATION_STRING_VALUE, value),
+            TraceArgValue::kConvertable(value) => {
+                let mut json = String::new();
+                value.append_as_trace_format(&mut json);
+                annotation.add_string(K_DEBUG_ANNOTATION_LEGACY_JSON_VALUE, &json);
             }
         }
+        annotation
     }
 
-    #[cfg(feature = "system_instrumentation")]
-    impl TraceWriter for SystemInstrumentationTraceWriter {
-        fn append_trace_event(&mut self, trace_event: &TraceObject) {
-            if self.recorder.is_enabled() {
-                self.recorder.add_event(trace_event);
+    fn write_track_event(&mut self, trace_event: &TraceObject, event_type: u64, timestamp_us: i64, with_details: bool) {
+        let mut track_event = ProtoMessage::default();
+        track_event.add_varint(K_TRACK_EVENT_TYPE, event_type);
+        track_event.add_varint(K_TRACK_EVENT_TRACK_UUID, Self::thread_track_uuid(trace_event.pid(), trace_event.tid()));
+        if with_details {
+            for category in trace_event.category_group().split(',') {
+                track_event.add_string(K_TRACK_EVENT_CATEGORIES, category.trim());
+            }
+            track_event.add_string(K_TRACK_EVENT_NAME, trace_event.name());
+            for arg in trace_event.args() {
+                track_event.add_message(K_TRACK_EVENT_DEBUG_ANNOTATIONS, &Self::debug_annotation(arg.name, &arg.value));
+            }
+            if trace_event.flags() & (TRACE_EVENT_FLAG_FLOW_IN | TRACE_EVENT_FLAG_FLOW_OUT) != 0 {
+                track_event.add_fixed64(K_TRACK_EVENT_FLOW_IDS, trace_event.bind_id());
             }
         }
-
-        fn flush(&mut self) {}
+        let mut packet = ProtoMessage::default();
+        packet.add_varint(K_TRACE_PACKET_TIMESTAMP, (timestamp_us as u64).saturating_mul(1000));
+        packet.add_message(K_TRACE_PACKET_TRACK_EVENT, &track_event);
+        self.write_packet(packet);
     }
+}
 
-    #[cfg(feature = "system_instrumentation")]
-    impl Drop for SystemInstrumentationTraceWriter {
-        fn drop(&mut self) {
-            self.recorder.reset(nullptr);
+impl TraceWriter for PerfettoTraceWriter {
+    fn append_trace_event(&mut self, trace_event: &TraceObject) {
+        self.ensure_tracks(trace_event.pid(), trace_event.tid());
+        match trace_event.phase() {
+            TRACE_EVENT_PHASE_BEGIN => self.write_track_event(trace_event, K_TYPE_SLICE_BEGIN, trace_event.ts(), true),
+            TRACE_EVENT_PHASE_END => self.write_track_event(trace_event, K_TYPE_SLICE_END, trace_event.ts(), false),
+            TRACE_EVENT_PHASE_COMPLETE => {
+                self.write_track_event(trace_event, K_TYPE_SLICE_BEGIN, trace_event.ts(), true);
+                let end = trace_event.ts() + trace_event.duration();
+                self.write_track_event(trace_event, K_TYPE_SLICE_END, end, false);
+            }
+            _ => self.write_track_event(trace_event, K_TYPE_INSTANT, trace_event.ts(), true),
         }
     }
 
-    #[cfg(feature = "system_instrumentation")]
-    pub fn create_system_instrumentation_trace_writer() -> Box<dyn TraceWriter> {
-        Box::new(SystemInstrumentationTraceWriter::new())
+    fn flush(&mut self) {
+        let _ = self.stream.flush();
     }
+}
 
-    //Stubs for compilation purposes.
+pub fn create_json_trace_writer(stream: Box<dyn Write + Send>) -> Box<dyn TraceWriter> {
+    Box::new(JsonTraceWriter::new(stream))
+}
 
-    pub struct TracingController {}
-    impl TracingController {
-        pub fn get_category_group_name(_flag: *const c_char) -> &'static str {
-            "dummy_category"
-        }
-    }
-}
\ No newline at end of file
+pub fn create_json_trace_writer_with_tag(stream: Box<dyn Write + Send>, tag: &str) -> Box<dyn TraceWriter> {
+    Box::new(JsonTraceWriter::with_tag(stream, tag))
+}
+
+pub fn create_perfetto_trace_writer(stream: Box<dyn Write + Send>) -> Box<dyn TraceWriter> {
+    Box::new(PerfettoTraceWriter::new(stream))
+}
@@ -2,262 +2,136 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::io::Write;
-use std::sync::{
-    atomic::{AtomicBool, AtomicUsize, Ordering},
-    Mutex,
-};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
-#[cfg(feature = "v8_use_perfetto")]
-mod perfetto_integration {
-    use super::*;
-    use protobuf::Message;
-    use std::fs::File;
-    use std::io::{self, BufReader};
-
-    pub mod protos {
-        pub mod gen {
-            include!(concat!(env!("OUT_DIR"), "/perfetto.rs"));
-        }
-    }
-
-    pub struct JsonOutputWriter<'a, W: Write> {
-        stream: &'a mut W,
-    }
-
-    impl<'a, W: Write> JsonOutputWriter<'a, W> {
-        pub fn new(stream: &'a mut W) -> Self {
-            JsonOutputWriter { stream }
-        }
-
-        pub fn append_string(&mut self, string: &str) -> Result<(), io::Error> {
-            self.stream.write_all(string.as_bytes())?;
-            Ok(())
-        }
-    }
-
-    // Dummy implementations for perfetto integration that are not directly translatable.
-    // Replace with actual Perfetto interaction when available in Rust.
-    pub struct TraceProcessorStorage {}
-
-    impl TraceProcessorStorage {
-        pub fn create_instance(_config: ()) -> Self {
-            TraceProcessorStorage {}
-        }
-
-        pub fn parse(&mut self, _trace_bytes: Vec<u8>) {}
-        pub fn notify_end_of_file(&mut self) {}
-    }
-
-    pub fn export_json<W: Write>(
-        _trace_processor: &TraceProcessorStorage,
-        _output_writer: &mut JsonOutputWriter<W>,
-        _a: Option<&()>,
-        _b: Option<&()>,
-        _c: Option<&()>,
-    ) -> Result<(), io::Error> {
-        // Placeholder implementation
-        Ok(())
-    }
-
-    pub struct TracingSession {}
-
-    impl TracingSession {
-        pub fn new_trace(_backend_type: ()) -> Self {
-            TracingSession {}
-        }
-        pub fn setup(&mut self, _trace_config: protos::gen::TraceConfig) {}
-        pub fn start_blocking(&mut self) {}
-        pub fn stop_blocking(&mut self) {}
-        pub fn read_trace_blocking(&self) -> Vec<u8> {
-            Vec::new()
-        }
-    }
-
-    pub mod tracing {
-        pub fn initialize(_output_stream: &mut File) {}
-    }
-}
-
-mod base {
-    pub mod platform {
-        pub struct Mutex {
-            inner: std::sync::Mutex<()>,
-        }
-
-        impl Mutex {
-            pub fn new() -> Self {
-                Mutex {
-                    inner: std::sync::Mutex::new(()),
-                }
-            }
-
-            pub fn lock(&self) -> MutexGuard {
-                MutexGuard {
-                    _guard: self.inner.lock().unwrap(),
-                }
-            }
-        }
-
-        pub struct MutexGuard<'a> {
-            _guard: std::sync::MutexGuard<'a, ()>,
-        }
-
-        pub struct TimeTicks;
-
-        impl TimeTicks {
-            pub fn now() -> i64 {
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_micros() as i64
-            }
-        }
-
-        pub struct ThreadTicks;
-
-        impl ThreadTicks {
-            pub fn now() -> i64 {
-                //Dummy implementation
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_micros() as i64
-            }
-        }
-    }
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::libplatform::tracing::trace_buffer::TraceBuffer;
+use crate::libplatform::tracing::trace_config::TraceConfig;
+use crate::libplatform::tracing::trace_object::TraceArg;
+
+/// Notified when tracing is started or stopped, e.g. to update cached
+/// category state.
+pub trait TraceStateObserver: Send + Sync {
+    fn on_trace_enabled(&self);
+    fn on_trace_disabled(&self);
 }
 
-mod libplatform {
-    pub mod tracing {
-        //Public interface related to v8-tracing.h
-    }
+/// Bits of the category group enabled flags.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum CategoryGroupEnabledFlags {
+    /// Category group enabled for the recording mode.
+    kEnabledForRecording = 1 << 0,
+    /// Category group enabled by SetEventCallbackEnabled().
+    kEnabledForEventCallback = 1 << 2,
+    /// Category group enabled to export events to ETW.
+    kEnabledForETWExport = 1 << 3,
 }
 
-const K_MAX_CATEGORY_GROUPS: usize = 200;
+/// The maximum number of distinct category groups. Further groups share the
+/// "tracing categories exhausted" group, which is never enabled.
+pub const K_MAX_CATEGORY_GROUPS: usize = 200;
 
-#[derive(Default)]
-pub struct TraceConfig {
-    enabled_categories: Vec<String>,
-}
+// Parallel to the category group names, indexed by the offset of the flag.
+const K_BUILTIN_CATEGORY_GROUPS: [&str; 3] =
+    ["toplevel", "tracing categories exhausted; must increase kMaxCategoryGroups", "__metadata"];
+const K_CATEGORY_CATEGORIES_EXHAUSTED: usize = 1;
 
-impl TraceConfig {
-    pub fn new() -> Self {
-        TraceConfig {
-            enabled_categories: Vec::new(),
-        }
-    }
-    pub fn get_enabled_categories(&self) -> &Vec<String> {
-        &self.enabled_categories
-    }
-
-    pub fn is_category_group_enabled(&self, category_group: &str) -> bool {
-        self.enabled_categories.iter().any(|c| c == category_group)
-    }
-
-    pub fn add_enabled_category(&mut self, category: String) {
-        self.enabled_categories.push(category);
-    }
+struct State {
+    trace_buffer: Option<Box<dyn TraceBuffer>>,
+    trace_config: Option<TraceConfig>,
+    observers: Vec<Arc<dyn TraceStateObserver>>,
 }
 
-#[cfg(not(feature = "v8_use_perfetto"))]
-static G_CATEGORY_GROUPS: [&'static str; K_MAX_CATEGORY_GROUPS] = {
-    let mut arr = [""; K_MAX_CATEGORY_GROUPS];
-    arr[0] = "toplevel";
-    arr[1] = "tracing categories exhausted; must increase kMaxCategoryGroups";
-    arr[2] = "__metadata";
-    arr
-};
-
-#[cfg(not(feature = "v8_use_perfetto"))]
-static mut G_CATEGORY_GROUP_ENABLED: [u8; K_MAX_CATEGORY_GROUPS] = [0; K_MAX_CATEGORY_GROUPS];
-
-#[cfg(not(feature = "v8_use_perfetto"))]
-const G_CATEGORY_CATEGORIES_EXHAUSTED: usize = 1;
-#[cfg(not(feature = "v8_use_perfetto"))]
-const G_NUM_BUILTIN_CATEGORIES: usize = 3;
-#[cfg(not(feature = "v8_use_perfetto"))]
-static G_CATEGORY_INDEX: AtomicUsize = AtomicUsize::new(G_NUM_BUILTIN_CATEGORIES);
-
+/// Records trace events into a trace buffer while tracing is started.
+/// Events are attributed to category groups, whose enabled flags are
+/// handed out once per group so that trace points can check them cheaply.
 pub struct TracingController {
-    mutex: Mutex<()>,
     recording: AtomicBool,
-    trace_config: Option<TraceConfig>,
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    observers: Mutex<std::collections::HashSet<*mut TracingControllerTraceStateObserver>>,
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    trace_buffer: Option<TraceBuffer>,
-    #[cfg(feature = "v8_use_perfetto")]
-    output_stream: Option<std::fs::File>,
-    #[cfg(feature = "v8_use_perfetto")]
-    tracing_session: Option<perfetto_integration::TracingSession>,
-    #[cfg(feature = "v8_use_perfetto")]
-    trace_processor_: Option<perfetto_integration::TraceProcessorStorage>,
-    #[cfg(feature = "v8_use_perfetto")]
-    listener_for_testing_: Option<TraceEventListener>,
+    category_groups: Mutex<Vec<&'static str>>,
+    category_group_enabled: [AtomicU8; K_MAX_CATEGORY_GROUPS],
+    state: Mutex<State>,
+}
+
+impl Default for TracingController {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TracingController {
     pub fn new() -> Self {
         TracingController {
-            mutex: Mutex::new(()),
             recording: AtomicBool::new(false),
-            trace_config: None,
-            #[cfg(not(feature = "v8_use_perfetto"))]
-            observers: Mutex::new(std::collections::HashSet::new()),
-            #[cfg(not(feature = "v8_use_perfetto"))]
-            trace_buffer: None,
-            #[cfg(feature = "v8_use_perfetto")]
-            output_stream: None,
-            #[cfg(feature = "v8_use_perfetto")]
-            tracing_session: None,
-            #[cfg(feature = "v8_use_perfetto")]
-            trace_processor_: None,
-            #[cfg(feature = "v8_use_perfetto")]
-            listener_for_testing_: None,
+            category_groups: Mutex::new(K_BUILTIN_CATEGORY_GROUPS.to_vec()),
+            category_group_enabled: [const { AtomicU8::new(0) }; K_MAX_CATEGORY_GROUPS],
+            state: Mutex::new(State { trace_buffer: None, trace_config: None, observers: Vec::new() }),
         }
     }
 
-    #[cfg(feature = "v8_use_perfetto")]
-    pub fn initialize_for_perfetto(&mut self, output_stream: std::fs::File) {
-        self.output_stream = Some(output_stream);
+    /// Takes ownership of `trace_buffer`.
+    pub fn initialize(&self, trace_buffer: Box<dyn TraceBuffer>) {
+        self.state.lock().unwrap().trace_buffer = Some(trace_buffer);
+    }
+
+    pub fn current_timestamp_microseconds(&self) -> i64 {
+        Self::clock_microseconds(libc::CLOCK_MONOTONIC)
     }
 
-    #[cfg(feature = "v8_use_perfetto")]
-    pub fn set_trace_event_listener_for_testing(&mut self, listener: TraceEventListener) {
-        self.listener_for_testing_ = Some(listener);
+    pub fn current_cpu_timestamp_microseconds(&self) -> i64 {
+        Self::clock_microseconds(libc::CLOCK_THREAD_CPUTIME_ID)
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    pub fn initialize(&mut self, trace_buffer: TraceBuffer) {
-        self.trace_buffer = Some(trace_buffer);
+    fn clock_microseconds(clock: libc::clockid_t) -> i64 {
+        let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { libc::clock_gettime(clock, &mut ts) };
+        ts.tv_sec * 1_000_000 + ts.tv_nsec / 1000
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    fn current_timestamp_microseconds(&self) -> i64 {
-        base::platform::TimeTicks::now()
+    /// Returns the enabled flags of `category_group`, registering the group
+    /// on first use. The flags stay valid for the lifetime of the
+    /// controller.
+    pub fn get_category_group_enabled(&self, category_group: &str) -> &AtomicU8 {
+        let mut category_groups = self.category_groups.lock().unwrap();
+        if let Some(index) = category_groups.iter().position(|group| *group == category_group) {
+            return &self.category_group_enabled[index];
+        }
+        let index = category_groups.len();
+        if index == K_MAX_CATEGORY_GROUPS {
+            // No more space in the category table.
+            return &self.category_group_enabled[K_CATEGORY_CATEGORIES_EXHAUSTED];
+        }
+        // Category groups are registered once and live as long as the
+        // process, like the string literals they usually come from.
+        let category_group: &'static str = Box::leak(category_group.to_string().into_boxed_str());
+        category_groups.push(category_group);
+        self.update_category_group_enabled_flag(index, category_group);
+        &self.category_group_enabled[index]
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    fn current_cpu_timestamp_microseconds(&self) -> i64 {
-        base::platform::ThreadTicks::now()
+    /// Returns the name of the category group `category_enabled_flag`
+    /// belongs to.
+    pub fn get_category_group_name(&self, category_enabled_flag: &AtomicU8) -> &'static str {
+        let base = self.category_group_enabled.as_ptr() as usize;
+        let offset = category_enabled_flag as *const AtomicU8 as usize;
+        assert!(offset >= base && offset < base + K_MAX_CATEGORY_GROUPS, "flag not from this controller");
+        self.category_groups.lock().unwrap()[offset - base]
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
+    /// Adds a trace event with the current timestamp. Returns a handle for
+    /// `update_trace_event_duration`, or 0 if the event was not recorded.
+    #[allow(clippy::too_many_arguments)]
     pub fn add_trace_event(
         &self,
-        phase: char,
-        category_enabled_flag: *const u8,
-        name: &str,
-        scope: &str,
+        phase: u8,
+        category_enabled_flag: &AtomicU8,
+        name: Cow<'static, str>,
+        scope: Option<&'static str>,
         id: u64,
         bind_id: u64,
-        num_args: usize,
-        arg_names: &[*const char],
-        arg_types: &[u8],
-        arg_values: &[u64],
-        arg_convertables: &mut [Option<Box<dyn ConvertableToTraceFormat>>],
+        args: Vec<TraceArg>,
         flags: u32,
     ) -> u64 {
         let now_us = self.current_timestamp_microseconds();
@@ -268,406 +142,273 @@ impl TracingController {
             scope,
             id,
             bind_id,
-            num_args,
-            arg_names,
-            arg_types,
-            arg_values,
-            arg_convertables,
+            args,
             flags,
             now_us,
         )
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
+    #[allow(clippy::too_many_arguments)]
     pub fn add_trace_event_with_timestamp(
         &self,
-        phase: char,
-        category_enabled_flag: *const u8,
-        name: &str,
-        scope: &str,
+        phase: u8,
+        category_enabled_flag: &AtomicU8,
+        name: Cow<'static, str>,
+        scope: Option<&'static str>,
         id: u64,
         bind_id: u64,
-        num_args: usize,
-        arg_names: &[*const char],
-        arg_types: &[u8],
-        arg_values: &[u64],
-        arg_convertables: &mut [Option<Box<dyn ConvertableToTraceFormat>>],
+        args: Vec<TraceArg>,
         flags: u32,
         timestamp: i64,
     ) -> u64 {
-        let cpu_now_us = self.current_cpu_timestamp_microseconds();
-        let mut handle: u64 = 0;
-
-        if self.recording.load(Ordering::Acquire) {
-            if let Some(trace_buffer) = &self.trace_buffer {
-                let trace_object = trace_buffer.add_trace_event(&mut handle);
-                if let Some(trace_object) = trace_object {
-                    let _lock = self.mutex.lock().unwrap();
-                    trace_object.initialize(
-                        phase,
-                        category_enabled_flag,
-                        name,
-                        scope,
-                        id,
-                        bind_id,
-                        num_args,
-                        arg_names,
-                        arg_types,
-                        arg_values,
-                        arg_convertables,
-                        flags,
-                        timestamp,
-                        cpu_now_us,
-                    );
-                }
-            }
+        if !self.recording.load(Ordering::Acquire) {
+            return 0;
         }
+        let cpu_now_us = self.current_cpu_timestamp_microseconds();
+        let category_group = self.get_category_group_name(category_enabled_flag);
+        let mut state = self.state.lock().unwrap();
+        let Some((trace_object, handle)) = state.trace_buffer.as_mut().and_then(|buffer| buffer.add_trace_event())
+        else {
+            return 0;
+        };
+        trace_object.initialize(phase, category_group, name, scope, id, bind_id, args, flags, timestamp, cpu_now_us);
         handle
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    pub fn update_trace_event_duration(
-        &self,
-        category_enabled_flag: *const u8,
-        name: &str,
-        handle: u64,
-    ) {
+    /// Sets the duration of the complete event with `handle` to end now.
+    pub fn update_trace_event_duration(&self, _category_enabled_flag: &AtomicU8, _name: &str, handle: u64) {
         let now_us = self.current_timestamp_microseconds();
         let cpu_now_us = self.current_cpu_timestamp_microseconds();
-
-        if let Some(trace_buffer) = &self.trace_buffer {
-            if let Some(trace_object) = trace_buffer.get_event_by_handle(handle) {
-                trace_object.update_duration(now_us, cpu_now_us);
-            }
+        let mut state = self.state.lock().unwrap();
+        if let Some(trace_object) = state.trace_buffer.as_mut().and_then(|buffer| buffer.get_event_by_handle(handle)) {
+            trace_object.update_duration(now_us, cpu_now_us);
         }
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    pub fn get_category_group_name(&self, category_group_enabled: *const u8) -> &'static str {
-        unsafe {
-            let category_begin = G_CATEGORY_GROUP_ENABLED.as_ptr() as usize;
-            let category_ptr = category_group_enabled as usize;
-            assert!(
-                category_ptr >= category_begin
-                    && category_ptr
-                        < G_CATEGORY_GROUP_ENABLED.as_ptr().add(K_MAX_CATEGORY_GROUPS) as usize
-            );
-
-            let category_index = (category_ptr - category_begin) / std::mem::size_of::<u8>();
-            G_CATEGORY_GROUPS[category_index]
-        }
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Acquire)
     }
 
-    pub fn start_tracing(&mut self, trace_config: TraceConfig) {
-        #[cfg(feature = "v8_use_perfetto")]
-        {
-            let mut output_stream = self.output_stream.take().unwrap();
-
-            let processor_config = (); //Dummy config for now
-
-            self.trace_processor_ =
-                Some(perfetto_integration::TraceProcessorStorage::create_instance(
-                    processor_config,
-                ));
-
-            let mut perfetto_trace_config =
-                perfetto_integration::protos::gen::TraceConfig::new();
-            let buffers = perfetto_trace_config.mut_buffers();
-            buffers.push(perfetto_integration::protos::gen::BufferConfig::new());
-            buffers[0].set_size_kb(4096);
-
-            let data_sources = perfetto_trace_config.mut_data_sources();
-            data_sources.push(perfetto_integration::protos::gen::DataSource::new());
-
-            let ds_config = data_sources[0].mut_config();
-            ds_config.set_name("track_event".to_string());
-
-            let mut te_config = perfetto_integration::protos::gen::TrackEventConfig::new();
-            te_config.enabled_categories = protobuf::RepeatedField::from_vec(
-                trace_config.get_enabled_categories().clone(),
-            );
-            te_config.disabled_categories
-                .push("*".to_string());
-
-            ds_config.set_track_event_config_raw(te_config.write_to_bytes().unwrap());
-
-            let mut tracing_session =
-                perfetto_integration::TracingSession::new_trace(());
-            tracing_session.setup(perfetto_trace_config);
-            tracing_session.start_blocking();
-
-            self.tracing_session = Some(tracing_session);
-        }
-
-        self.trace_config = Some(trace_config);
-        self.recording.store(true, Ordering::Release);
-
-        #[cfg(not(feature = "v8_use_perfetto"))]
-        {
-            let observers_copy: std::collections::HashSet<*mut TracingControllerTraceStateObserver>;
-            {
-                let _lock = self.mutex.lock().unwrap();
-                self.update_category_group_enabled_flags();
-                observers_copy = self.observers.lock().unwrap().clone();
-            }
-            for o in observers_copy {
-                unsafe {
-                    (*o).on_trace_enabled();
-                }
+    pub fn start_tracing(&self, trace_config: TraceConfig) {
+        let observers = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(buffer) = state.trace_buffer.as_mut() {
+                buffer.set_record_mode(trace_config.get_trace_record_mode());
             }
+            state.trace_config = Some(trace_config);
+            self.recording.store(true, Ordering::Release);
+            self.update_category_group_enabled_flags(&state);
+            state.observers.clone()
+        };
+        for observer in observers {
+            observer.on_trace_enabled();
         }
     }
 
-    pub fn stop_tracing(&mut self) {
-        let mut expected = true;
-        if !self.recording.compare_exchange(
-            expected,
-            false,
-            Ordering::Strong,
-            Ordering::Relaxed,
-        ) {
+    /// Stops recording and writes the recorded events out.
+    pub fn stop_tracing(&self) {
+        if self.recording.compare_exchange(true, false, Ordering::AcqRel, Ordering::Relaxed).is_err() {
             return;
         }
-
-        #[cfg(not(feature = "v8_use_perfetto"))]
-        {
-            self.update_category_group_enabled_flags();
-            let observers_copy: std::collections::HashSet<*mut TracingControllerTraceStateObserver>;
-            {
-                let _lock = self.mutex.lock().unwrap();
-                observers_copy = self.observers.lock().unwrap().clone();
-            }
-            for o in observers_copy {
-                unsafe {
-                    (*o).on_trace_disabled();
-                }
-            }
+        let observers = {
+            let state = self.state.lock().unwrap();
+            self.update_category_group_enabled_flags(&state);
+            state.observers.clone()
+        };
+        for observer in observers {
+            observer.on_trace_disabled();
         }
-
-        #[cfg(feature = "v8_use_perfetto")]
-        {
-            let mut tracing_session = self.tracing_session.take().unwrap();
-
-            tracing_session.stop_blocking();
-
-            let trace = tracing_session.read_trace_blocking();
-            let mut trace_bytes = trace;
-
-            if let Some(mut trace_processor_) = self.trace_processor_.take() {
-                trace_processor_.parse(trace_bytes);
-                trace_processor_.notify_end_of_file();
-
-                let mut output_stream = self.output_stream.take().unwrap();
-                let mut output_writer = perfetto_integration::JsonOutputWriter::new(&mut output_stream);
-
-                let status = perfetto_integration::export_json(
-                    &trace_processor_,
-                    &mut output_writer,
-                    None,
-                    None,
-                    None,
-                );
-
-                //DCHECK(status.ok());
-
-                if let Some(listener_for_testing_) = &self.listener_for_testing_ {
-                    //listener_for_testing_.parse_from_array(trace);
-                }
-            }
+        let mut state = self.state.lock().unwrap();
+        if let Some(buffer) = state.trace_buffer.as_mut() {
+            buffer.flush();
         }
+    }
 
-        #[cfg(not(feature = "v8_use_perfetto"))]
+    pub fn add_trace_state_observer(&self, observer: Arc<dyn TraceStateObserver>) {
         {
-            let _lock = self.mutex.lock().unwrap();
-            if let Some(trace_buffer) = &self.trace_buffer {
-                trace_buffer.flush();
+            let mut state = self.state.lock().unwrap();
+            state.observers.push(observer.clone());
+            if !self.recording.load(Ordering::Acquire) {
+                return;
             }
         }
+        // Fire the observer if recording is already in progress.
+        observer.on_trace_enabled();
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    fn update_category_group_enabled_flag(&self, category_index: usize) {
-        let mut enabled_flag: u8 = 0;
-        let category_group = unsafe { G_CATEGORY_GROUPS[category_index] };
-
-        if self.recording.load(Ordering::Acquire) {
-            if let Some(trace_config) = &self.trace_config {
-                if trace_config.is_category_group_enabled(category_group) {
-                    enabled_flag |= ENABLED_FOR_RECORDING;
-                }
-            }
-        }
-
-        if self.recording.load(Ordering::Acquire) && category_group == "__metadata" {
-            enabled_flag |= ENABLED_FOR_RECORDING;
-        }
-
-        unsafe {
-            G_CATEGORY_GROUP_ENABLED[category_index] = enabled_flag;
+    pub fn remove_trace_state_observer(&self, observer: &Arc<dyn TraceStateObserver>) {
+        let mut state = self.state.lock().unwrap();
+        let index = state.observers.iter().position(|o| Arc::ptr_eq(o, observer));
+        debug_assert!(index.is_some());
+        if let Some(index) = index {
+            state.observers.remove(index);
         }
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    fn update_category_group_enabled_flags(&self) {
-        let category_index = G_CATEGORY_INDEX.load(Ordering::Acquire);
-        for i in 0..category_index {
-            self.update_category_group_enabled_flag(i);
-        }
+    fn update_category_group_enabled_flag(&self, index: usize, category_group: &str) {
+        let state = self.state.lock().unwrap();
+        self.update_category_group_enabled_flag_locked(&state, index, category_group);
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    pub fn get_category_group_enabled(&self, category_group: &str) -> *mut u8 {
-        assert!(!category_group.contains('"'));
-
-        let category_index = G_CATEGORY_INDEX.load(Ordering::Acquire);
-
-        for i in 0..category_index {
-            unsafe {
-                if G_CATEGORY_GROUPS[i] == category_group {
-                    return G_CATEGORY_GROUP_ENABLED.as_mut_ptr().add(i);
-                }
-            }
-        }
-
-        let _lock = self.mutex.lock().unwrap();
-
-        let category_index = G_CATEGORY_INDEX.load(Ordering::Acquire);
-        for i in 0..category_index {
-            unsafe {
-                if G_CATEGORY_GROUPS[i] == category_group {
-                    return G_CATEGORY_GROUP_ENABLED.as_mut_ptr().add(i);
-                }
-            }
-        }
-
-        assert!(category_index < K_MAX_CATEGORY_GROUPS);
-        if category_index < K_MAX_CATEGORY_GROUPS {
-            let new_group = category_group.to_string();
-            let new_group_ptr = Box::leak(new_group.into_boxed_str()) as *mut str;
-
-            unsafe {
-                //G_CATEGORY_GROUPS[category_index] = new_group_ptr as *const char; // String does not convert to *const char directly
-                //The line above is unconvertible since Rust strings do not convert directly to C strings.
-                //This translation will leave the category groups to be uninitialized strings
-            }
-
-            self.update_category_group_enabled_flag(category_index);
-            let category_group_enabled =
-                unsafe { G_CATEGORY_GROUP_ENABLED.as_mut_ptr().add(category_index) };
-
-            G_CATEGORY_INDEX.store(category_index + 1, Ordering::Release);
-
-            return category_group_enabled;
-        } else {
-            unsafe {
-                return G_CATEGORY_GROUP_ENABLED.as_mut_ptr().add(G_CATEGORY_CATEGORIES_EXHAUSTED);
-            }
+    fn update_category_group_enabled_flag_locked(&self, state: &State, index: usize, category_group: &str) {
+        let mut enabled_flag = 0;
+        if self.recording.load(Ordering::Acquire)
+            && state.trace_config.as_ref().is_some_and(|config| config.is_category_group_enabled(category_group))
+        {
+            enabled_flag |= CategoryGroupEnabledFlags::kEnabledForRecording as u8;
         }
+        self.category_group_enabled[index].store(enabled_flag, Ordering::Relaxed);
     }
 
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    pub fn add_trace_state_observer(
-        &self,
-        observer: *mut TracingControllerTraceStateObserver,
-    ) {
-        {
-            let mut observers = self.observers.lock().unwrap();
-            observers.insert(observer);
-            if !self.recording.load(Ordering::Acquire) {
-                return;
+    fn update_category_group_enabled_flags(&self, state: &State) {
+        let category_groups = self.category_groups.lock().unwrap();
+        for (index, category_group) in category_groups.iter().enumerate() {
+            if index == K_CATEGORY_CATEGORIES_EXHAUSTED {
+                continue;
             }
+            self.update_category_group_enabled_flag_locked(state, index, category_group);
         }
-
-        unsafe {
-            (*observer).on_trace_enabled();
-        }
-    }
-
-    #[cfg(not(feature = "v8_use_perfetto"))]
-    pub fn remove_trace_state_observer(
-        &self,
-        observer: *mut TracingControllerTraceStateObserver,
-    ) {
-        let mut observers = self.observers.lock().unwrap();
-        assert!(observers.contains(&observer));
-        observers.remove(&observer);
     }
 }
 
 impl Drop for TracingController {
     fn drop(&mut self) {
         self.stop_tracing();
-
-        #[cfg(not(feature = "v8_use_perfetto"))]
-        {
-            let _lock = self.mutex.lock().unwrap();
-            //The code below is unconvertible since it uses raw pointers which is unsafe in Rust
-            //Therefore this part is being dropped.
-            //for i in (G_NUM_BUILTIN_CATEGORIES..G_CATEGORY_INDEX.load(Ordering::Relaxed)).rev() {
-            //   unsafe {
-            //        if !G_CATEGORY_GROUPS[i].is_null() {
-            //           // free(G_CATEGORY_GROUPS[i] as *mut _);
-            //        }
-            //        G_CATEGORY_GROUPS[i] = std::ptr::null();
-            //    }
-            //}
-            //G_CATEGORY_INDEX.store(G_NUM_BUILTIN_CATEGORIES, Ordering::Relaxed);
-        }
     }
 }
 
-const ENABLED_FOR_RECORDING: u8 = 1 << 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libplatform::tracing::trace_buffer::{create_trace_buffer_ring_buffer, K_RING_BUFFER_CHUNKS};
+    use crate::libplatform::tracing::trace_writer::{create_json_trace_writer, create_perfetto_trace_writer, TraceWriter};
+    use crate::tracing::trace_event::{TRACE_EVENT_FLAG_NONE, TRACE_EVENT_PHASE_COMPLETE, TRACE_EVENT_PHASE_INSTANT};
+    use std::io::Write;
 
-// Dummy trait
-pub trait ConvertableToTraceFormat {}
+    #[derive(Clone, Default)]
+    struct SharedStream(Arc<Mutex<Vec<u8>>>);
 
-// Dummy struct
-pub struct TraceBuffer {}
+    impl Write for SharedStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
 
-impl TraceBuffer {
-    pub fn add_trace_event(&self, _handle: &mut u64) -> Option<&mut TraceObject> {
-        Some(&mut TraceObject {}) // Dummy Implementation
-    }
-    pub fn get_event_by_handle(&self, _handle: u64) -> Option<&mut TraceObject> {
-        Some(&mut TraceObject {}) // Dummy Implementation
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
     }
-    pub fn flush(&self) {}
-}
 
-//Dummy struct for now
-pub struct TraceObject {}
-
-impl TraceObject {
-    pub fn initialize(
-        &mut self,
-        _phase: char,
-        _category_enabled_flag: *const u8,
-        _name: &str,
-        _scope: &str,
-        _id: u64,
-        _bind_id: u64,
-        _num_args: usize,
-        _arg_names: &[*const char],
-        _arg_types: &[u8],
-        _arg_values: &[u64],
-        _arg_convertables: &mut [Option<Box<dyn ConvertableToTraceFormat>>],
-        _flags: u32,
-        _timestamp: i64,
-        _cpu_now_us: i64,
-    ) {
+    fn controller_writing_to(writer: Box<dyn TraceWriter>) -> TracingController {
+        let controller = TracingController::new();
+        controller.initialize(create_trace_buffer_ring_buffer(K_RING_BUFFER_CHUNKS, writer));
+        controller
+    }
+
+    #[test]
+    fn records_enabled_categories_as_json() {
+        let json = SharedStream::default();
+        let controller = controller_writing_to(create_json_trace_writer(Box::new(json.clone())));
+        let v8 = controller.get_category_group_enabled("v8");
+        let gc = controller.get_category_group_enabled("v8.gc");
+        assert_eq!(controller.get_category_group_name(gc), "v8.gc");
+        assert_eq!(v8.load(Ordering::Relaxed), 0);
+
+        let mut trace_config = TraceConfig::new();
+        trace_config.add_included_category("v8");
+        controller.start_tracing(trace_config);
+        assert_eq!(v8.load(Ordering::Relaxed), CategoryGroupEnabledFlags::kEnabledForRecording as u8);
+        assert_eq!(gc.load(Ordering::Relaxed), 0);
+
+        let handle = controller.add_trace_event(
+            TRACE_EVENT_PHASE_COMPLETE,
+            v8,
+            Cow::Borrowed("V8.Execute"),
+            None,
+            0,
+            0,
+            vec![TraceArg::new("answer", 42u64), TraceArg::new("name", "quote\"d")],
+            TRACE_EVENT_FLAG_NONE,
+        );
+        assert_ne!(handle, 0);
+        controller.update_trace_event_duration(v8, "V8.Execute", handle);
+        controller.stop_tracing();
+        assert_eq!(v8.load(Ordering::Relaxed), 0);
+        drop(controller);
+
+        let json = String::from_utf8(json.0.lock().unwrap().clone()).unwrap();
+        assert!(json.starts_with("{\"traceEvents\":[{\"pid\":"), "{json}");
+        assert!(json.contains("\"ph\":\"X\",\"cat\":\"v8\",\"name\":\"V8.Execute\""), "{json}");
+        assert!(json.contains("\"args\":{\"answer\":42,\"name\":\"quote\\\"d\"}}"), "{json}");
+        assert!(json.ends_with("]}"), "{json}");
+    }
+
+    /// Reads the packets of a Perfetto trace as (field, payload) lists.
+    fn decode_fields(mut bytes: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        fn varint(bytes: &mut &[u8]) -> u64 {
+            let mut value = 0;
+            let mut shift = 0;
+            loop {
+                let byte = bytes[0];
+                *bytes = &bytes[1..];
+                value |= ((byte & 0x7f) as u64) << shift;
+                if byte & 0x80 == 0 {
+                    return value;
+                }
+                shift += 7;
+            }
+        }
+        let mut fields = Vec::new();
+        while !bytes.is_empty() {
+            let tag = varint(&mut bytes);
+            let payload = match tag & 7 {
+                0 => varint(&mut bytes).to_le_bytes().to_vec(),
+                1 => {
+                    let (value, rest) = bytes.split_at(8);
+                    bytes = rest;
+                    value.to_vec()
+                }
+                2 => {
+                    let length = varint(&mut bytes) as usize;
+                    let (value, rest) = bytes.split_at(length);
+                    bytes = rest;
+                    value.to_vec()
+                }
+                wire_type => panic!("unexpected wire type {wire_type}"),
+            };
+            fields.push(((tag >> 3) as u32, payload));
+        }
+        fields
+    }
+
+    #[test]
+    fn writes_perfetto_protobuf_traces() {
+        let proto = SharedStream::default();
+        let controller = controller_writing_to(create_perfetto_trace_writer(Box::new(proto.clone())));
+        let v8 = controller.get_category_group_enabled("v8");
+        controller.start_tracing(TraceConfig::create_default_trace_config());
+        controller.add_trace_event(
+            TRACE_EVENT_PHASE_INSTANT,
+            v8,
+            Cow::Borrowed("V8.GC"),
+            None,
+            0,
+            0,
+            Vec::new(),
+            TRACE_EVENT_FLAG_NONE,
+        );
+        controller.stop_tracing();
+
+        let trace = proto.0.lock().unwrap().clone();
+        let packets = decode_fields(&trace);
+        assert!(packets.iter().all(|(field, _)| *field == 1));
+        // Process and thread track descriptors, then the event.
+        assert_eq!(packets.len(), 3);
+        let event = decode_fields(&packets[2].1);
+        let track_event = &event.iter().find(|(field, _)| *field == 11).unwrap().1;
+        let track_event = decode_fields(track_event);
+        assert!(track_event.contains(&(23, b"V8.GC".to_vec())));
+        assert!(track_event.contains(&(22, b"v8".to_vec())));
+        assert!(track_event.contains(&(9, 3u64.to_le_bytes().to_vec())));
     }
-    pub fn update_duration(&mut self, _now_us: i64, _cpu_now_us: i64) {}
-}
-
-// Dummy trait
-pub trait TracingControllerTraceStateObserver {
-    fn on_trace_enabled(&self) {}
-    fn on_trace_disabled(&self) {}
 }
-
-// Dummy struct
-pub struct TraceEventListener {}
-
-impl TraceEventListener {
-    pub fn parse_from_array(&self, _trace: Vec<u8>) {}
-}
\ No newline at end of file
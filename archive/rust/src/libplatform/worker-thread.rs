@@ -1,78 +1,88 @@
-use std::sync::Mutex;
+// Copyright 2013 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
 use std::sync::Arc;
-use std::thread;
-use std::thread::JoinHandle;
-use std::boxed::Box;
+use std::thread::{self, JoinHandle};
+
+use crate::include::v8_platform::Task;
 
-/// A trait representing a task that can be run.
-pub trait Task: Send + 'static {
-    fn run(self: Box<Self>);
+/// Hands tasks to worker threads.
+pub trait TaskSource: Send + Sync {
+    /// Blocks until a task is available. Returns `None` once the source has
+    /// been terminated, which makes the worker thread exit.
+    fn get_next(&self) -> Option<Box<dyn Task>>;
 }
 
-/// A queue for tasks to be executed by worker threads.
-pub struct TaskQueue {
-    queue: Arc<Mutex<Vec<Box<dyn Task>>>>,
+/// The OS priority of a worker thread.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadPriority {
+    kBestEffort,
+    kUserVisible,
+    kUserBlocking,
+    kDefault,
 }
 
-impl TaskQueue {
-    /// Creates a new TaskQueue.
-    pub fn new() -> Self {
-        TaskQueue {
-            queue: Arc::new(Mutex::new(Vec::new())),
-        }
+/// A thread that runs the tasks of a `TaskSource` until it is terminated.
+pub struct WorkerThread {
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WorkerThread {
+    pub fn new(name: &str, priority: ThreadPriority, source: Arc<dyn TaskSource>) -> Self {
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                set_current_thread_priority(priority);
+                while let Some(mut task) = source.get_next() {
+                    task.run();
+                }
+            })
+            .expect("failed to start a worker thread");
+        WorkerThread { handle: Some(handle) }
     }
 
-    /// Adds a task to the queue.
-    pub fn add_task(&self, task: Box<dyn Task>) {
-        let mut queue = self.queue.lock().unwrap();
-        queue.push(task);
+    /// Waits for the thread to exit. The task source must have been
+    /// terminated.
+    pub fn join(mut self) {
+        self.join_internal();
     }
 
-    /// Retrieves the next task from the queue.  Returns `None` if the queue is empty.
-    pub fn get_next(&self) -> Option<Box<dyn Task>> {
-        let mut queue = self.queue.lock().unwrap();
-        if queue.is_empty() {
-            None
-        } else {
-            Some(queue.remove(0))
+    fn join_internal(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        // The last reference to the platform may be dropped by one of its own
+        // workers; that thread exits on its own after the current task.
+        if handle.thread().id() == thread::current().id() {
+            return;
+        }
+        if handle.join().is_err() {
+            eprintln!("A worker thread panicked");
         }
     }
 }
 
-/// A worker thread that executes tasks from a TaskQueue.
-pub struct WorkerThread {
-    thread: Option<JoinHandle<()>>,
-    queue: Arc<TaskQueue>,
+impl Drop for WorkerThread {
+    fn drop(&mut self) {
+        self.join_internal();
+    }
 }
 
-impl WorkerThread {
-    /// Creates a new WorkerThread that executes tasks from the given TaskQueue.
-    pub fn new(queue: Arc<TaskQueue>) -> Self {
-        let queue_clone = queue.clone();
-        let thread = thread::spawn(move || {
-            loop {
-                match queue_clone.get_next() {
-                    Some(task) => {
-                        task.run();
-                    }
-                    None => {
-                        //Queue is empty so thread stops here
-                        break;
-                    }
-                }
-            }
-        });
-
-        WorkerThread {
-            thread: Some(thread),
-            queue,
-        }
+/// Only lowering the priority of best effort threads is supported, as raising
+/// it requires privileges.
+#[cfg(target_os = "linux")]
+fn set_current_thread_priority(priority: ThreadPriority) {
+    const K_BEST_EFFORT_NICE_VALUE: libc::c_int = 10;
+    if priority != ThreadPriority::kBestEffort {
+        return;
     }
-
-    /// Waits for the worker thread to finish executing all tasks in the queue.
-    pub fn join(mut self) {
-        if let Some(thread) = self.thread.take() {
-            thread.join().unwrap();
-        }
+    unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, tid, K_BEST_EFFORT_NICE_VALUE);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_priority(_priority: ThreadPriority) {}
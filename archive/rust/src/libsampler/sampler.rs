@@ -4,45 +4,16 @@
 
 // src/libsampler/sampler.h (Rust module definition)
 pub mod sampler {
-    use std::sync::{atomic::{AtomicBool, Ordering}, Mutex, MutexGuard, PoisonError, RwLock};
+    use std::cell::UnsafeCell;
     use std::collections::HashMap;
-    use std::thread;
-    use std::time::Duration;
-    use std::sync::Arc;
-    use lazy_static::lazy_static;
-
-    extern crate libc;
-
-    // Define macro for platform checks.  Since we can't get target_os! without a build script,
-    // we'll leave platform-specific code under cfg! macros.
-    #[cfg(target_os = "linux")]
-    #[cfg(target_os = "android")]
-    #[cfg(target_os = "macos")]
-    #[cfg(target_os = "ios")]
-    #[cfg(target_os = "freebsd")]
-    #[cfg(target_os = "netbsd")]
-    #[cfg(target_os = "openbsd")]
-    #[cfg(target_os = "solaris")]
-    #[cfg(target_os = "qnx")]
-    #[cfg(target_os = "aix")]
-    #[cfg(target_os = "zos")]
-    #[cfg(target_os = "windows")]
-    #[cfg(target_os = "cygwin")]
-    #[cfg(target_os = "fuchsia")]
-    macro_rules! use_signals {
-        () => {
-            true
-        };
-    }
-
-    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "solaris", target_os = "qnx", target_os = "aix", target_os = "zos", target_os = "windows", target_os = "cygwin", target_os = "fuchsia")))]
-    macro_rules! use_signals {
-        () => {
-            false
-        };
-    }
-
-    #[derive(Debug, Default)]
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::LazyLock;
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
+    use std::sync::Mutex;
+
+    // Every POSIX platform samples by sending SIGPROF to the profiled thread;
+    // the remaining platforms suspend the thread and read its context.
+    #[derive(Debug, Clone, Copy)]
     pub struct RegisterState {
         pub pc: *mut libc::c_void,
         pub sp: *mut libc::c_void,
@@ -50,6 +21,25 @@ pub mod sampler {
         pub lr: *mut libc::c_void,
     }
 
+    impl Default for RegisterState {
+        fn default() -> Self {
+            RegisterState {
+                pc: std::ptr::null_mut(),
+                sp: std::ptr::null_mut(),
+                fp: std::ptr::null_mut(),
+                lr: std::ptr::null_mut(),
+            }
+        }
+    }
+
+    /// The isolate a sampler belongs to. The sampler only uses it as an
+    /// identity: checking whether the isolate may be sampled is left to the
+    /// `SampleStackHandler`.
+    #[repr(C)]
+    pub struct Isolate {
+        _private: [u8; 0],
+    }
+
     #[derive(Debug)]
     pub struct AtomicGuard<'a> {
         atomic_: &'a AtomicBool,
@@ -60,20 +50,23 @@ pub mod sampler {
         pub fn new(atomic: &'a AtomicBool, is_blocking: bool) -> Self {
             let mut is_success_ = false;
             loop {
-                let expected = false;
                 // We have to use the strong version here for the case where is_blocking
                 // is false, and we will only attempt the exchange once.
-                if atomic.compare_exchange_strong(expected, true, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                if atomic
+                    .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok()
+                {
                     is_success_ = true;
                     break;
                 }
                 if !is_blocking {
                     break;
                 }
+                std::hint::spin_loop();
             }
             AtomicGuard {
                 atomic_: atomic,
-                is_success_: is_success_,
+                is_success_,
             }
         }
 
@@ -82,7 +75,7 @@ pub mod sampler {
         }
     }
 
-    impl<'a> Drop for AtomicGuard<'a> {
+    impl Drop for AtomicGuard<'_> {
         fn drop(&mut self) {
             if !self.is_success_ {
                 return;
@@ -91,33 +84,49 @@ pub mod sampler {
         }
     }
 
-    #[cfg(use_signals!())]
-    pub struct Sampler {
-        isolate_: *mut v8::Isolate,
-        data_: Box<PlatformData>,
-        is_active_: Arc<AtomicBool>,
-        should_record_sample_: Arc<AtomicBool>,
+    /// Receives the register state of the sampled thread. On platforms using
+    /// signals this is called from within the signal handler on the sampled
+    /// thread, so implementations must be async-signal-safe: no allocation,
+    /// no locking.
+    pub trait SampleStackHandler: Send + Sync {
+        fn sample_stack(&self, state: &RegisterState);
     }
 
-    #[cfg(not(use_signals!()))]
     pub struct Sampler {
-        isolate_: *mut v8::Isolate,
-        data_: Box<PlatformData>,
-        is_active_: Arc<AtomicBool>,
+        isolate_: *mut Isolate,
+        data_: PlatformData,
+        is_active_: AtomicBool,
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        should_record_sample_: AtomicBool,
+        handler_: Option<Box<dyn SampleStackHandler>>,
     }
 
+    // The sampler is shared between the thread driving it and the signal
+    // handler of the sampled thread; all mutable state is atomic.
+    unsafe impl Send for Sampler {}
+    unsafe impl Sync for Sampler {}
+
     impl Sampler {
-        pub fn new(isolate: *mut v8::Isolate) -> Self {
+        /// Creates a sampler for the calling thread. Samples are taken of the
+        /// thread that constructed the sampler.
+        pub fn new(isolate: *mut Isolate) -> Self {
             Sampler {
                 isolate_: isolate,
-                data_: Box::new(PlatformData::new()),
-                is_active_: Arc::new(AtomicBool::new(false)),
-                #[cfg(use_signals!())]
-                should_record_sample_: Arc::new(AtomicBool::new(false)),
+                data_: PlatformData::new(),
+                is_active_: AtomicBool::new(false),
+                #[cfg(all(unix, not(target_os = "fuchsia")))]
+                should_record_sample_: AtomicBool::new(false),
+                handler_: None,
             }
         }
 
-        pub fn isolate(&self) -> *mut v8::Isolate {
+        pub fn with_handler(isolate: *mut Isolate, handler: Box<dyn SampleStackHandler>) -> Self {
+            let mut sampler = Self::new(isolate);
+            sampler.handler_ = Some(handler);
+            sampler
+        }
+
+        pub fn isolate(&self) -> *mut Isolate {
             self.isolate_
         }
 
@@ -133,260 +142,223 @@ pub mod sampler {
             self.is_active_.store(active, Ordering::SeqCst);
         }
 
-        #[cfg(use_signals!())]
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
         pub fn should_record_sample(&self) -> bool {
             self.should_record_sample_.load(Ordering::SeqCst)
         }
 
-        #[cfg(use_signals!())]
-        pub fn set_should_record_sample(&self) {
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        fn set_should_record_sample(&self) {
             self.should_record_sample_.store(true, Ordering::SeqCst);
         }
 
-        #[cfg(use_signals!())]
-        pub fn clear_should_record_sample(&self) {
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        fn clear_should_record_sample(&self) {
             self.should_record_sample_.store(false, Ordering::SeqCst);
         }
 
+        /// Starts sampling. The sampler must not move while it is active: the
+        /// signal handler reaches it through its address.
         pub fn start(&self) {
             if self.is_active() {
                 return;
             }
-
             self.set_active(true);
-
-            #[cfg(use_signals!())]
+            #[cfg(all(unix, not(target_os = "fuchsia")))]
             {
-                unsafe { SignalHandler::increase_sampler_count() };
+                SignalHandler::increase_sampler_count();
                 SamplerManager::instance().add_sampler(self);
             }
         }
 
         pub fn stop(&self) {
-            #[cfg(use_signals!())]
+            if !self.is_active() {
+                return;
+            }
+            #[cfg(all(unix, not(target_os = "fuchsia")))]
             {
                 SamplerManager::instance().remove_sampler(self);
-                unsafe { SignalHandler::decrease_sampler_count() };
+                SignalHandler::decrease_sampler_count();
             }
+            self.set_active(false);
+        }
 
-            if !self.is_active() {
+        /// Requests a sample of the profiled thread. On POSIX the sample is
+        /// taken by the signal handler before this returns.
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        pub fn do_sample(&self) {
+            let _guard = SignalHandler::mutex();
+            if !SignalHandler::installed() {
                 return;
             }
-
-            self.set_active(false);
+            self.set_should_record_sample();
+            unsafe { libc::pthread_kill(self.platform_data().vm_tself(), libc::SIGPROF) };
         }
 
-        #[cfg(use_signals!())]
+        #[cfg(windows)]
         pub fn do_sample(&self) {
-            unsafe {
-                let guard = SignalHandler::mutex().lock().unwrap();
-                if !SignalHandler::installed() {
-                    drop(guard);
-                    return;
-                }
-                self.set_should_record_sample();
-                libc::pthread_kill(self.platform_data().vm_tself(), libc::SIGPROF);
-                drop(guard);
-                self.clear_should_record_sample();
+            let profiled_thread = self.platform_data().profiled_thread();
+            if profiled_thread.is_null() {
+                return;
+            }
+            const SUSPEND_FAILED: u32 = u32::MAX;
+            if unsafe { win32::SuspendThread(profiled_thread) } == SUSPEND_FAILED {
+                return;
             }
+            let mut context = win32::Context::new();
+            if unsafe { win32::GetThreadContext(profiled_thread, &mut context) } != 0 {
+                self.sample_stack(&context.register_state());
+            }
+            unsafe { win32::ResumeThread(profiled_thread) };
         }
 
-        #[cfg(not(use_signals!()))]
+        #[cfg(target_os = "fuchsia")]
         pub fn do_sample(&self) {
-            //Platform specific sampling
-            #[cfg(target_os = "windows")]
-            {
-                let profiled_thread = self.platform_data().profiled_thread();
-                if profiled_thread.is_null() {
-                    return;
-                }
-
-                const SUSPEND_FAILED: u32 = u32::MAX;
-                if unsafe { libc::SuspendThread(profiled_thread) } == SUSPEND_FAILED {
-                    return;
-                }
-
-                let mut context: libc::CONTEXT = unsafe {std::mem::zeroed()};
-                context.ContextFlags = libc::CONTEXT_ALL as u32;
-
-                if unsafe { libc::GetThreadContext(profiled_thread, &mut context) } != 0 {
-                    let mut state = RegisterState::default();
-
-                    #[cfg(target_arch = "x86_64")]
-                    {
-                        state.pc = context.Rip as *mut libc::c_void;
-                        state.sp = context.Rsp as *mut libc::c_void;
-                        state.fp = context.Rbp as *mut libc::c_void;
-                    }
-
-                    #[cfg(target_arch = "aarch64")]
-                    {
-                        state.pc = context.Pc as *mut libc::c_void;
-                        state.sp = context.Sp as *mut libc::c_void;
-                        state.fp = context.Fp as *mut libc::c_void;
-                    }
-
-                    #[cfg(target_arch = "x86")]
-                    {
-                         state.pc = context.Eip as *mut libc::c_void;
-                         state.sp = context.Esp as *mut libc::c_void;
-                         state.fp = context.Ebp as *mut libc::c_void;
-                    }
-                    self.sample_stack(&state);
-                }
-
-                unsafe { libc::ResumeThread(profiled_thread) };
+            let profiled_thread = self.platform_data().profiled_thread();
+            if profiled_thread == zx::ZX_HANDLE_INVALID {
+                return;
             }
-
-            #[cfg(target_os = "fuchsia")]
-            {
-                let profiled_thread = self.platform_data().profiled_thread();
-                if profiled_thread == 0 {
-                    return;
-                }
-
-                let mut suspend_token: zx::Handle = zx::Handle::invalid();
-                if zx::task::suspend_token(zx::AsHandleRef::borrow(&profiled_thread), &mut suspend_token).is_err() {
-                    return;
-                }
-
-                let signals = profiled_thread.wait_one(zx::Signals::SUSPENDED | zx::Signals::TERMINATED, zx::Duration::from_millis(100));
-
-                if signals.is_err() || (signals.unwrap() & zx::Signals::SUSPENDED).is_empty() {
-                    suspend_token.close();
-                    return;
-                }
-
-                let mut thread_state: zx::ThreadStateGeneralRegs = unsafe {std::mem::zeroed()};
-
-                if zx::thread::read_state(&profiled_thread, zx::ThreadStateKind::GeneralRegs, &mut thread_state).is_ok() {
-                    let mut state = RegisterState::default();
-
-                    #[cfg(target_arch = "x86_64")]
-                    {
-                        state.pc = thread_state.rip as *mut libc::c_void;
-                        state.sp = thread_state.rsp as *mut libc::c_void;
-                        state.fp = thread_state.rbp as *mut libc::c_void;
-                    }
-
-                    #[cfg(target_arch = "aarch64")]
-                    {
-                        state.pc = thread_state.pc as *mut libc::c_void;
-                        state.sp = thread_state.sp as *mut libc::c_void;
-                        state.fp = thread_state.r[29] as *mut libc::c_void;
-                    }
-
-                    self.sample_stack(&state);
+            let mut suspend_token = zx::ZX_HANDLE_INVALID;
+            if unsafe { zx::zx_task_suspend_token(profiled_thread, &mut suspend_token) } != zx::ZX_OK {
+                return;
+            }
+            // Wait for the target thread to become suspended, or to exit. The
+            // timeout keeps a misbehaving thread from blocking the sampler.
+            let mut signals: zx::zx_signals_t = 0;
+            let status = unsafe {
+                zx::zx_object_wait_one(
+                    profiled_thread,
+                    zx::ZX_THREAD_SUSPENDED | zx::ZX_THREAD_TERMINATED,
+                    zx::zx_deadline_after(100 * 1_000_000),
+                    &mut signals,
+                )
+            };
+            if status == zx::ZX_OK && signals & zx::ZX_THREAD_SUSPENDED != 0 {
+                let mut thread_state = zx::ThreadStateGeneralRegs::default();
+                let status = unsafe {
+                    zx::zx_thread_read_state(
+                        profiled_thread,
+                        zx::ZX_THREAD_STATE_GENERAL_REGS,
+                        &mut thread_state as *mut _ as *mut libc::c_void,
+                        std::mem::size_of::<zx::ThreadStateGeneralRegs>(),
+                    )
+                };
+                if status == zx::ZX_OK {
+                    self.sample_stack(&thread_state.register_state());
                 }
-                suspend_token.close();
             }
+            unsafe { zx::zx_handle_close(suspend_token) };
         }
 
-
+        /// Performs stack sampling. Called from the signal handler on the sampled
+        /// thread, so it must not allocate or take locks.
         pub fn sample_stack(&self, state: &RegisterState) {
-            //TODO: Implement
-            println!("Sampling stack at PC: {:p}, SP: {:p}, FP: {:p}", state.pc, state.sp, state.fp);
+            if let Some(handler) = &self.handler_ {
+                handler.sample_stack(state);
+            }
         }
     }
 
-    #[cfg(use_signals!())]
     impl Drop for Sampler {
         fn drop(&mut self) {
-            if self.is_active() {
-                self.stop();
-            }
+            self.stop();
         }
     }
 
-    #[cfg(use_signals!())]
-    unsafe impl Send for Sampler {}
-    #[cfg(use_signals!())]
-    unsafe impl Sync for Sampler {}
-
-
     #[derive(Debug)]
     pub struct PlatformData {
-        #[cfg(use_signals!())]
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
         vm_tid_: i32,
-        #[cfg(use_signals!())]
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
         vm_tself_: libc::pthread_t,
 
-        #[cfg(target_os = "windows")]
-        profiled_thread_: libc::HANDLE,
+        #[cfg(windows)]
+        profiled_thread_: win32::HANDLE,
 
         #[cfg(target_os = "fuchsia")]
-        profiled_thread_: zx::Handle,
+        profiled_thread_: zx::zx_handle_t,
     }
 
     impl PlatformData {
-        #[cfg(use_signals!())]
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
         pub fn new() -> Self {
             PlatformData {
-                vm_tid_: unsafe { libc::syscall(libc::SYS_gettid) as i32 },
+                vm_tid_: current_thread_id(),
                 vm_tself_: unsafe { libc::pthread_self() },
             }
         }
 
-        #[cfg(use_signals!())]
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
         pub fn vm_tid(&self) -> i32 {
             self.vm_tid_
         }
 
-        #[cfg(use_signals!())]
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
         pub fn vm_tself(&self) -> libc::pthread_t {
             self.vm_tself_
         }
 
-        #[cfg(target_os = "windows")]
+        #[cfg(windows)]
         pub fn new() -> Self {
-            let current_process = unsafe { libc::GetCurrentProcess() };
-            let mut profiled_thread_: libc::HANDLE = std::ptr::null_mut();
-
-            let result = unsafe {
-                libc::DuplicateHandle(
+            let mut profiled_thread_: win32::HANDLE = std::ptr::null_mut();
+            unsafe {
+                let current_process = win32::GetCurrentProcess();
+                let result = win32::DuplicateHandle(
                     current_process,
-                    libc::GetCurrentThread(),
+                    win32::GetCurrentThread(),
                     current_process,
                     &mut profiled_thread_,
-                    libc::THREAD_GET_CONTEXT | libc::THREAD_SUSPEND_RESUME | libc::THREAD_QUERY_INFORMATION,
-                    libc::FALSE,
+                    win32::THREAD_GET_CONTEXT
+                        | win32::THREAD_SUSPEND_RESUME
+                        | win32::THREAD_QUERY_INFORMATION,
                     0,
-                )
-            };
-
-            assert!(result != 0, "DuplicateHandle failed");
-            PlatformData {
-                profiled_thread_: profiled_thread_,
+                    0,
+                );
+                if result == 0 {
+                    profiled_thread_ = std::ptr::null_mut();
+                }
             }
+            PlatformData { profiled_thread_ }
         }
 
-        #[cfg(target_os = "windows")]
-        pub fn profiled_thread(&self) -> libc::HANDLE {
+        #[cfg(windows)]
+        pub fn profiled_thread(&self) -> win32::HANDLE {
             self.profiled_thread_
         }
 
         #[cfg(target_os = "fuchsia")]
         pub fn new() -> Self {
-            let mut profiled_thread_: zx::Handle = zx::Handle::invalid();
-            zx::duplicate_handle(zx::Handle::from(unsafe {zx::Thread::self_handle()}), zx::Rights::SAME_RIGHTS, &mut profiled_thread_).unwrap();
-            PlatformData {
-                profiled_thread_: profiled_thread_,
+            let mut profiled_thread_ = zx::ZX_HANDLE_INVALID;
+            let status = unsafe {
+                zx::zx_handle_duplicate(
+                    zx::zx_thread_self(),
+                    zx::ZX_RIGHT_SAME_RIGHTS,
+                    &mut profiled_thread_,
+                )
+            };
+            if status != zx::ZX_OK {
+                profiled_thread_ = zx::ZX_HANDLE_INVALID;
             }
+            PlatformData { profiled_thread_ }
         }
 
         #[cfg(target_os = "fuchsia")]
-        pub fn profiled_thread(&self) -> zx::Handle {
+        pub fn profiled_thread(&self) -> zx::zx_handle_t {
             self.profiled_thread_
         }
     }
 
-    #[cfg(target_os = "windows")]
+    impl Default for PlatformData {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    #[cfg(windows)]
     impl Drop for PlatformData {
-         fn drop(&mut self) {
+        fn drop(&mut self) {
             if !self.profiled_thread_.is_null() {
-                unsafe {
-                    libc::CloseHandle(self.profiled_thread_);
-                }
+                unsafe { win32::CloseHandle(self.profiled_thread_) };
             }
         }
     }
@@ -394,141 +366,146 @@ pub mod sampler {
     #[cfg(target_os = "fuchsia")]
     impl Drop for PlatformData {
         fn drop(&mut self) {
-            if self.profiled_thread_.is_valid() {
-                self.profiled_thread_.close();
+            if self.profiled_thread_ != zx::ZX_HANDLE_INVALID {
+                unsafe { zx::zx_handle_close(self.profiled_thread_) };
             }
         }
     }
 
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn current_thread_id() -> i32 {
+        unsafe { libc::syscall(libc::SYS_gettid) as i32 }
+    }
+
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "fuchsia"))))]
+    fn current_thread_id() -> i32 {
+        unsafe { libc::pthread_self() as usize as i32 }
+    }
+
     type SamplerList = Vec<*const Sampler>;
 
-    #[derive(Debug)]
+    /// Maps thread ids to the samplers of that thread, so the signal handler
+    /// can find the samplers interested in the interrupted thread.
     pub struct SamplerManager {
-        sampler_map_: RwLock<HashMap<i32, SamplerList>>,
+        // Only touched while `samplers_access_counter_` is held. The signal
+        // handler merely tries to take it, so it never blocks on a sampler
+        // being added or removed by the thread it interrupted.
+        sampler_map_: UnsafeCell<HashMap<i32, SamplerList>>,
         samplers_access_counter_: AtomicBool,
     }
 
+    unsafe impl Send for SamplerManager {}
+    unsafe impl Sync for SamplerManager {}
+
     impl SamplerManager {
         fn new() -> Self {
             SamplerManager {
-                sampler_map_: RwLock::new(HashMap::new()),
+                sampler_map_: UnsafeCell::new(HashMap::new()),
                 samplers_access_counter_: AtomicBool::new(false),
             }
         }
 
-        fn add_sampler(&self, sampler: &Sampler) {
-            let atomic_guard = AtomicGuard::new(&self.samplers_access_counter_, true);
-            if !atomic_guard.is_success() {
-                return;
-            }
-            let thread_id = sampler.platform_data().vm_tid();
-            let mut sampler_map = self.sampler_map_.write().unwrap();
-            match sampler_map.get_mut(&thread_id) {
-                Some(samplers) => {
-                    if !samplers.contains(&(sampler as *const Sampler)) {
-                        samplers.push(sampler as *const Sampler);
-                    }
-                }
-                None => {
-                    let mut samplers = SamplerList::new();
-                    samplers.push(sampler as *const Sampler);
-                    sampler_map.insert(thread_id, samplers);
-                }
+        /// Adds `sampler` to the map if it is not already present.
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        pub fn add_sampler(&self, sampler: &Sampler) {
+            let _atomic_guard = AtomicGuard::new(&self.samplers_access_counter_, true);
+            let sampler_map = unsafe { &mut *self.sampler_map_.get() };
+            let samplers = sampler_map.entry(sampler.platform_data().vm_tid()).or_default();
+            let sampler = sampler as *const Sampler;
+            if !samplers.contains(&sampler) {
+                samplers.push(sampler);
             }
         }
 
-        fn remove_sampler(&self, sampler: &Sampler) {
-            let atomic_guard = AtomicGuard::new(&self.samplers_access_counter_, true);
-             if !atomic_guard.is_success() {
-                return;
-            }
+        /// Removes `sampler` from the map if it is present.
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        pub fn remove_sampler(&self, sampler: &Sampler) {
+            let _atomic_guard = AtomicGuard::new(&self.samplers_access_counter_, true);
+            let sampler_map = unsafe { &mut *self.sampler_map_.get() };
             let thread_id = sampler.platform_data().vm_tid();
-            let mut sampler_map = self.sampler_map_.write().unwrap();
             if let Some(samplers) = sampler_map.get_mut(&thread_id) {
-                samplers.retain(|&s| s != sampler as *const Sampler);
+                samplers.retain(|&s| !std::ptr::eq(s, sampler));
                 if samplers.is_empty() {
                     sampler_map.remove(&thread_id);
                 }
             }
         }
 
-        fn do_sample(&self, state: &RegisterState) {
+        /// If the map is not being modified, records a sample for every
+        /// sampler of the current thread that asked for one.
+        #[cfg(all(unix, not(target_os = "fuchsia")))]
+        pub fn do_sample(&self, state: &RegisterState) {
             let atomic_guard = AtomicGuard::new(&self.samplers_access_counter_, false);
             if !atomic_guard.is_success() {
                 return;
             }
-            let thread_id = unsafe { libc::syscall(libc::SYS_gettid) as i32 };
-            let sampler_map = self.sampler_map_.read().unwrap();
-            if let Some(samplers) = sampler_map.get(&thread_id) {
-                for &sampler_ptr in samplers {
-                    let sampler: &Sampler = unsafe { &*sampler_ptr };
-                    #[cfg(use_signals!())]
-                    if !sampler.should_record_sample() {
-                        continue;
-                    }
-                    let isolate = sampler.isolate();
-                    if isolate.is_null() || unsafe { (*isolate).is_in_use() != 0 } {
-                        continue;
-                    }
-                    sampler.sample_stack(state);
+            let sampler_map = unsafe { &*self.sampler_map_.get() };
+            let Some(samplers) = sampler_map.get(&current_thread_id()) else {
+                return;
+            };
+            for &sampler in samplers {
+                let sampler = unsafe { &*sampler };
+                if !sampler.should_record_sample() {
+                    continue;
+                }
+                sampler.clear_should_record_sample();
+                if sampler.isolate().is_null() {
+                    continue;
                 }
+                sampler.sample_stack(state);
             }
         }
 
         pub fn instance() -> &'static SamplerManager {
-            lazy_static! {
-                static ref INSTANCE: SamplerManager = SamplerManager::new();
-            }
+            static INSTANCE: LazyLock<SamplerManager> = LazyLock::new(SamplerManager::new);
             &INSTANCE
         }
     }
 
-    extern "C" {
-        fn v8_thread_isolated_allocator_set_default_permissions_for_signal_handler();
-        fn v8_set_mutex_deadlock_detection_mode(mode: i32);
-        fn v8_isolate_is_in_use(isolate: *mut v8::Isolate) -> i32;
-    }
+    /// Installs the SIGPROF handler while at least one sampler is active and
+    /// restores the previous handler afterwards.
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
+    struct SignalHandler;
 
-    impl Isolate {
-        unsafe fn is_in_use(&self) -> i32 {
-            v8_isolate_is_in_use(self)
-        }
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
+    struct SignalHandlerState {
+        client_count: i32,
+        old_signal_handler: Option<libc::sigaction>,
     }
 
-    // Dummy Isolate struct to satisfy the pointer cast. This is just a simulation;
-    // the actual Isolate would be defined by the v8 crate.
-    #[repr(C)]
-    pub struct Isolate {
-        _private: [u8; 0],
-    }
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
+    unsafe impl Send for SignalHandlerState {}
+
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
+    static SIGNAL_HANDLER_STATE: Mutex<SignalHandlerState> = Mutex::new(SignalHandlerState {
+        client_count: 0,
+        old_signal_handler: None,
+    });
 
-    #[cfg(use_signals!())]
-    struct SignalHandler {}
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
+    static SIGNAL_HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
 
-    #[cfg(use_signals!())]
+    #[cfg(all(unix, not(target_os = "fuchsia")))]
     impl SignalHandler {
-        lazy_static! {
-            static ref MUTEX: Mutex<()> = Mutex::new(());
-            static ref CLIENT_COUNT: Mutex<i32> = Mutex::new(0);
-            static ref SIGNAL_HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
-            static ref OLD_SIGNAL_HANDLER: Mutex<libc::sigaction> = Mutex::new(unsafe { std::mem::zeroed() });
+        fn mutex() -> std::sync::MutexGuard<'static, SignalHandlerState> {
+            SIGNAL_HANDLER_STATE
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
         }
 
-        unsafe fn increase_sampler_count() {
-            let _lock = MUTEX.lock().unwrap();
-            let mut client_count = CLIENT_COUNT.lock().unwrap();
-            *client_count += 1;
-            if *client_count == 1 {
-                Self::install();
+        fn increase_sampler_count() {
+            let mut state = Self::mutex();
+            state.client_count += 1;
+            if state.client_count == 1 {
+                Self::install(&mut state);
             }
         }
 
-        unsafe fn decrease_sampler_count() {
-            let _lock = MUTEX.lock().unwrap();
-            let mut client_count = CLIENT_COUNT.lock().unwrap();
-            *client_count -= 1;
-            if *client_count == 0 {
-                Self::restore();
+        fn decrease_sampler_count() {
+            let mut state = Self::mutex();
+            state.client_count -= 1;
+            if state.client_count == 0 {
+                Self::restore(&mut state);
             }
         }
 
@@ -536,297 +513,368 @@ pub mod sampler {
             SIGNAL_HANDLER_INSTALLED.load(Ordering::SeqCst)
         }
 
-        fn mutex() -> &'static Mutex<()> {
-            &MUTEX
-        }
-
-        unsafe fn install() {
-            let mut sa: libc::sigaction = std::mem::zeroed();
-            sa.sa_sigaction = Self::handle_profiler_signal as usize as *mut _;
-            libc::sigemptyset(&mut sa.sa_mask);
-            sa.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO | libc::SA_ONSTACK;
-
-            let mut old_signal_handler = OLD_SIGNAL_HANDLER.lock().unwrap();
-            let result = libc::sigaction(libc::SIGPROF, &sa, &mut *old_signal_handler);
-            if result == 0 {
-                SIGNAL_HANDLER_INSTALLED.store(true, Ordering::SeqCst);
+        fn install(state: &mut SignalHandlerState) {
+            unsafe {
+                let mut sa: libc::sigaction = std::mem::zeroed();
+                sa.sa_sigaction = Self::handle_profiler_signal as *const () as usize;
+                libc::sigemptyset(&mut sa.sa_mask);
+                sa.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO | libc::SA_ONSTACK;
+                let mut old_signal_handler: libc::sigaction = std::mem::zeroed();
+                if libc::sigaction(libc::SIGPROF, &sa, &mut old_signal_handler) == 0 {
+                    state.old_signal_handler = Some(old_signal_handler);
+                    SIGNAL_HANDLER_INSTALLED.store(true, Ordering::SeqCst);
+                }
             }
         }
 
-        unsafe fn restore() {
-            if SIGNAL_HANDLER_INSTALLED.load(Ordering::SeqCst) {
-                SIGNAL_HANDLER_INSTALLED.store(false, Ordering::SeqCst);
-                #[cfg(any(target_os = "aix", target_arch = "s390x"))]
-                std::thread::sleep(Duration::from_micros(10));
-                let old_signal_handler = OLD_SIGNAL_HANDLER.lock().unwrap();
-                libc::sigaction(libc::SIGPROF, &*old_signal_handler, std::ptr::null_mut());
+        fn restore(state: &mut SignalHandlerState) {
+            if !SIGNAL_HANDLER_INSTALLED.swap(false, Ordering::SeqCst) {
+                return;
+            }
+            // On AIX and z/OS a pending SIGPROF may still be delivered after
+            // the handler is reset; give it time to drain.
+            #[cfg(any(target_os = "aix", target_arch = "s390x"))]
+            std::thread::sleep(std::time::Duration::from_micros(10));
+            if let Some(old_signal_handler) = state.old_signal_handler.take() {
+                unsafe { libc::sigaction(libc::SIGPROF, &old_signal_handler, std::ptr::null_mut()) };
             }
         }
 
-        extern "C" fn handle_profiler_signal(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
-            unsafe {
-                v8_thread_isolated_allocator_set_default_permissions_for_signal_handler();
-            }
+        extern "C" fn handle_profiler_signal(
+            signal: libc::c_int,
+            _info: *mut libc::siginfo_t,
+            context: *mut libc::c_void,
+        ) {
             if signal != libc::SIGPROF {
                 return;
             }
-
             let mut state = RegisterState::default();
-            Self::fill_register_state(context, &mut state);
+            unsafe { Self::fill_register_state(context, &mut state) };
             SamplerManager::instance().do_sample(&state);
         }
 
-        #[cfg(target_os = "linux")]
-        #[cfg(target_os = "android")]
+        #[cfg(any(target_os = "linux", target_os = "android"))]
         unsafe fn fill_register_state(context: *mut libc::c_void, state: &mut RegisterState) {
             let ucontext = context as *mut libc::ucontext_t;
-            let mcontext = &mut (*ucontext).uc_mcontext;
+            #[allow(unused_variables)]
+            let mcontext = unsafe { &(*ucontext).uc_mcontext };
 
             #[cfg(target_arch = "x86")]
             {
-                state.pc = (*mcontext).gregs[14] as *mut libc::c_void;  //REG_EIP
-                state.sp = (*mcontext).gregs[7] as *mut libc::c_void;   //REG_ESP
-                state.fp = (*mcontext).gregs[6] as *mut libc::c_void;   //REG_EBP
+                state.pc = mcontext.gregs[libc::REG_EIP as usize] as *mut libc::c_void;
+                state.sp = mcontext.gregs[libc::REG_ESP as usize] as *mut libc::c_void;
+                state.fp = mcontext.gregs[libc::REG_EBP as usize] as *mut libc::c_void;
             }
 
             #[cfg(target_arch = "x86_64")]
             {
-                state.pc = (*mcontext).gregs[16] as *mut libc::c_void; //REG_RIP
-                state.sp = (*mcontext).gregs[15] as *mut libc::c_void; //REG_RSP
-                state.fp = (*mcontext).gregs[10] as *mut libc::c_void; //REG_RBP
+                state.pc = mcontext.gregs[libc::REG_RIP as usize] as *mut libc::c_void;
+                state.sp = mcontext.gregs[libc::REG_RSP as usize] as *mut libc::c_void;
+                state.fp = mcontext.gregs[libc::REG_RBP as usize] as *mut libc::c_void;
             }
 
             #[cfg(target_arch = "arm")]
             {
-                state.pc = (*mcontext).arm_pc as *mut libc::c_void;
-                state.sp = (*mcontext).arm_sp as *mut libc::c_void;
-                state.fp = (*mcontext).arm_fp as *mut libc::c_void;
-                state.lr = (*mcontext).arm_lr as *mut libc::c_void;
+                state.pc = mcontext.arm_pc as *mut libc::c_void;
+                state.sp = mcontext.arm_sp as *mut libc::c_void;
+                state.fp = mcontext.arm_fp as *mut libc::c_void;
+                state.lr = mcontext.arm_lr as *mut libc::c_void;
             }
 
             #[cfg(target_arch = "aarch64")]
             {
-                state.pc = (*mcontext).pc as *mut libc::c_void;
-                state.sp = (*mcontext).sp as *mut libc::c_void;
-                state.fp = (*mcontext).regs[29] as *mut libc::c_void;
-                state.lr = (*mcontext).regs[30] as *mut libc::c_void;
+                state.pc = mcontext.pc as *mut libc::c_void;
+                state.sp = mcontext.sp as *mut libc::c_void;
+                state.fp = mcontext.regs[29] as *mut libc::c_void;
+                state.lr = mcontext.regs[30] as *mut libc::c_void;
             }
 
-            #[cfg(target_arch = "mips64")]
+            #[cfg(target_arch = "riscv64")]
             {
-                state.pc = (*mcontext).pc as *mut libc::c_void;
-                state.sp = (*mcontext).gregs[29] as *mut libc::c_void;
-                state.fp = (*mcontext).gregs[30] as *mut libc::c_void;
+                state.pc = mcontext.__gregs[0] as *mut libc::c_void; // REG_PC
+                state.sp = mcontext.__gregs[2] as *mut libc::c_void; // REG_SP
+                state.fp = mcontext.__gregs[8] as *mut libc::c_void; // REG_S0
+                state.lr = mcontext.__gregs[1] as *mut libc::c_void; // REG_RA
             }
 
-            #[cfg(target_arch = "loongarch64")]
+            #[cfg(target_arch = "s390x")]
             {
-                state.pc = (*mcontext).__pc as *mut libc::c_void;
-                state.sp = (*mcontext).__gregs[3] as *mut libc::c_void;
-                state.fp = (*mcontext).__gregs[22] as *mut libc::c_void;
+                state.pc = mcontext.psw.addr as *mut libc::c_void;
+                state.sp = mcontext.gregs[15] as *mut libc::c_void;
+                state.fp = mcontext.gregs[11] as *mut libc::c_void;
+                state.lr = mcontext.gregs[14] as *mut libc::c_void;
             }
+        }
 
-            #[cfg(target_arch = "powerpc64")]
-            {
-               state.pc = (*(*ucontext).uc_mcontext.regs).nip as *mut libc::c_void;
-               state.sp = (*(*ucontext).uc_mcontext.regs).gpr[1] as *mut libc::c_void;
-               state.fp = (*(*ucontext).uc_mcontext.regs).gpr[31] as *mut libc::c_void;
-               state.lr = (*(*ucontext).uc_mcontext.regs).link as *mut libc::c_void;
-            }
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        unsafe fn fill_register_state(context: *mut libc::c_void, state: &mut RegisterState) {
+            let ucontext = context as *mut libc::ucontext_t;
+            let mcontext = unsafe { &*(*ucontext).uc_mcontext };
 
-            #[cfg(target_arch = "s390x")]
+            #[cfg(target_arch = "x86_64")]
             {
-                state.pc = (*ucontext).uc_mcontext.psw.addr as *mut libc::c_void;
-                state.sp = (*ucontext).uc_mcontext.gregs[15] as *mut libc::c_void;
-                state.fp = (*ucontext).uc_mcontext.gregs[11] as *mut libc::c_void;
-                state.lr = (*ucontext).uc_mcontext.gregs[14] as *mut libc::c_void;
+                state.pc = mcontext.__ss.__rip as *mut libc::c_void;
+                state.sp = mcontext.__ss.__rsp as *mut libc::c_void;
+                state.fp = mcontext.__ss.__rbp as *mut libc::c_void;
             }
 
-            #[cfg(target_arch = "riscv64")]
+            #[cfg(target_arch = "aarch64")]
             {
-                 state.pc = (*mcontext).__gregs[34] as *mut libc::c_void; //REG_PC
-                 state.sp = (*mcontext).__gregs[2] as *mut libc::c_void;  //REG_SP
-                 state.fp = (*mcontext).__gregs[8] as *mut libc::c_void;  //REG_S0/FP
-                 state.lr = (*mcontext).__gregs[1] as *mut libc::c_void;  //REG_RA
+                state.pc = mcontext.__ss.__pc as *mut libc::c_void;
+                state.sp = mcontext.__ss.__sp as *mut libc::c_void;
+                state.fp = mcontext.__ss.__fp as *mut libc::c_void;
+                state.lr = mcontext.__ss.__lr as *mut libc::c_void;
             }
         }
-         #[cfg(target_os = "macos")]
-         #[cfg(target_os = "ios")]
-         unsafe fn fill_register_state(context: *mut libc::c_void, state: &mut RegisterState) {
-            let ucontext = context as *mut libc::ucontext_t;
-            let mcontext = &mut (*ucontext).uc_mcontext;
 
-            #[cfg(target_arch = "x86_64")] {
-                state.pc = (*mcontext).__ss.__rip as *mut libc::c_void;
-                state.sp = (*mcontext).__ss.__rsp as *mut libc::c_void;
-                state.fp = (*mcontext).__ss.__rbp as *mut libc::c_void;
-            }
-
-            #[cfg(target_arch = "aarch64")] {
-                use mach2::arm::*;
-                state.pc = arm_thread_state64_get_pc((*mcontext).__ss) as *mut libc::c_void;
-                state.sp = arm_thread_state64_get_sp((*mcontext).__ss) as *mut libc::c_void;
-                state.fp = arm_thread_state64_get_fp((*mcontext).__ss) as *mut libc::c_void;
-            }
-         }
+        #[cfg(not(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios"
+        )))]
+        unsafe fn fill_register_state(_context: *mut libc::c_void, _state: &mut RegisterState) {}
     }
 
-    #[cfg(target_os = "fuchsia")]
-    mod zx {
-        #![allow(dead_code)]
-        use std::mem;
-        use std::ptr;
-        use std::time::Duration;
-
-        use bitflags::bitflags;
-
-        #[repr(C)]
-        #[derive(Debug, Copy, Clone)]
-        pub struct Handle(u32);
-
-        impl Handle {
-            pub const INVALID: Self = Handle(0);
-
-            pub fn invalid() -> Self {
-                Self::INVALID
-            }
-
-            pub fn is_valid(&self) -> bool {
-                self.0 != Self::INVALID.0
-            }
-
-            pub fn close(&self) {
-                unsafe {
-                    zx_handle_close(self.0);
-                }
-            }
-
-            pub fn wait_one(&self, signals: Signals, deadline: Duration) -> Result<Signals, ()> {
-                let mut observed: u32 = 0;
-                let status = unsafe {
-                    zx_object_wait_one(self.0, signals.bits(), duration_to_nanos(deadline), &mut observed)
-                };
-                if status == 0 {
-                    Ok(Signals::from_bits_truncate(observed))
-                } else {
-                    Err(())
+    /// The subset of the Win32 API used to suspend the profiled thread and
+    /// read its registers.
+    #[cfg(windows)]
+    #[allow(non_snake_case, non_camel_case_types)]
+    mod win32 {
+        use super::RegisterState;
+
+        pub type HANDLE = *mut libc::c_void;
+
+        pub const THREAD_GET_CONTEXT: u32 = 0x0008;
+        pub const THREAD_SUSPEND_RESUME: u32 = 0x0002;
+        pub const THREAD_QUERY_INFORMATION: u32 = 0x0040;
+
+        unsafe extern "system" {
+            pub fn GetCurrentProcess() -> HANDLE;
+            pub fn GetCurrentThread() -> HANDLE;
+            pub fn DuplicateHandle(
+                source_process: HANDLE,
+                source_handle: HANDLE,
+                target_process: HANDLE,
+                target_handle: *mut HANDLE,
+                desired_access: u32,
+                inherit_handle: i32,
+                options: u32,
+            ) -> i32;
+            pub fn CloseHandle(handle: HANDLE) -> i32;
+            pub fn SuspendThread(thread: HANDLE) -> u32;
+            pub fn ResumeThread(thread: HANDLE) -> u32;
+            pub fn GetThreadContext(thread: HANDLE, context: *mut Context) -> i32;
+        }
+
+        /// CONTEXT, with only the control registers named. The remaining
+        /// state is kept as padding so that the kernel can fill it in.
+        #[cfg(target_arch = "x86_64")]
+        #[repr(C, align(16))]
+        pub struct Context {
+            _home: [u64; 6],
+            context_flags: u32,
+            _mx_csr: u32,
+            _segs: [u16; 6],
+            _eflags: u32,
+            _debug_regs: [u64; 6],
+            _rax_to_rbx: [u64; 4],
+            rsp: u64,
+            rbp: u64,
+            _rsi_to_r15: [u64; 10],
+            rip: u64,
+            _rest: [u8; 976],
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        impl Context {
+            const CONTEXT_FULL: u32 = 0x0010_000b;
+
+            pub fn new() -> Self {
+                let mut context: Context = unsafe { std::mem::zeroed() };
+                context.context_flags = Self::CONTEXT_FULL;
+                context
+            }
+
+            pub fn register_state(&self) -> RegisterState {
+                RegisterState {
+                    pc: self.rip as *mut libc::c_void,
+                    sp: self.rsp as *mut libc::c_void,
+                    fp: self.rbp as *mut libc::c_void,
+                    lr: std::ptr::null_mut(),
                 }
             }
         }
 
-        impl AsHandleRef for Handle {}
-
-        impl Drop for Handle {
-            fn drop(&mut self) {
-                if self.is_valid() {
-                    self.close();
-                }
-            }
+        #[cfg(target_arch = "aarch64")]
+        #[repr(C, align(16))]
+        pub struct Context {
+            context_flags: u32,
+            _cpsr: u32,
+            _x: [u64; 29],
+            fp: u64,
+            lr: u64,
+            sp: u64,
+            pc: u64,
+            _rest: [u8; 640],
         }
 
-        #[derive(Copy, Clone, Debug)]
-        pub struct AsHandleRefBorrowed<'a>(&'a Handle);
+        #[cfg(target_arch = "aarch64")]
+        impl Context {
+            const CONTEXT_FULL: u32 = 0x0040_0007;
 
-        impl<'a> AsHandleRefBorrowed<'a> {
-            pub fn new(handle: &'a Handle) -> Self {
-                Self(handle)
+            pub fn new() -> Self {
+                let mut context: Context = unsafe { std::mem::zeroed() };
+                context.context_flags = Self::CONTEXT_FULL;
+                context
             }
-        }
 
-        impl<'a> AsHandleRef for AsHandleRefBorrowed<'a> {
-            fn borrow(&self) -> Handle {
-                self.0.clone()
+            pub fn register_state(&self) -> RegisterState {
+                RegisterState {
+                    pc: self.pc as *mut libc::c_void,
+                    sp: self.sp as *mut libc::c_void,
+                    fp: self.fp as *mut libc::c_void,
+                    lr: self.lr as *mut libc::c_void,
+                }
             }
         }
+    }
 
-        pub trait AsHandleRef {
-            fn borrow(&self) -> Handle;
-        }
-
-        #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-        pub struct Duration(i64);
-
-        impl Duration {
-            pub const INFINITE: Self = Duration(i64::MAX);
-
-            pub fn from_nanos(nanos: i64) -> Self {
-                Self(nanos)
-            }
-
-            pub fn from_millis(millis: i64) -> Self {
-                Self(millis * 1_000_000)
-            }
-
-            pub fn from_secs(secs: i64) -> Self {
-                Self(secs * 1_000_000_000)
+    /// The subset of the Zircon syscalls used to suspend the profiled thread
+    /// and read its registers.
+    #[cfg(target_os = "fuchsia")]
+    #[allow(non_camel_case_types)]
+    mod zx {
+        use super::RegisterState;
+
+        pub type zx_handle_t = u32;
+        pub type zx_status_t = i32;
+        pub type zx_signals_t = u32;
+        pub type zx_rights_t = u32;
+        pub type zx_time_t = i64;
+        pub type zx_duration_t = i64;
+
+        pub const ZX_OK: zx_status_t = 0;
+        pub const ZX_HANDLE_INVALID: zx_handle_t = 0;
+        pub const ZX_RIGHT_SAME_RIGHTS: zx_rights_t = 1 << 31;
+        pub const ZX_THREAD_TERMINATED: zx_signals_t = 1 << 3;
+        pub const ZX_THREAD_SUSPENDED: zx_signals_t = 1 << 5;
+        pub const ZX_THREAD_STATE_GENERAL_REGS: u32 = 0;
+
+        unsafe extern "C" {
+            pub fn zx_thread_self() -> zx_handle_t;
+            pub fn zx_handle_duplicate(
+                handle: zx_handle_t,
+                rights: zx_rights_t,
+                out: *mut zx_handle_t,
+            ) -> zx_status_t;
+            pub fn zx_handle_close(handle: zx_handle_t) -> zx_status_t;
+            pub fn zx_task_suspend_token(handle: zx_handle_t, token: *mut zx_handle_t) -> zx_status_t;
+            pub fn zx_object_wait_one(
+                handle: zx_handle_t,
+                signals: zx_signals_t,
+                deadline: zx_time_t,
+                observed: *mut zx_signals_t,
+            ) -> zx_status_t;
+            pub fn zx_thread_read_state(
+                handle: zx_handle_t,
+                kind: u32,
+                buffer: *mut libc::c_void,
+                buffer_size: usize,
+            ) -> zx_status_t;
+            pub fn zx_deadline_after(nanoseconds: zx_duration_t) -> zx_time_t;
+        }
+
+        /// zx_thread_state_general_regs_t.
+        #[cfg(target_arch = "x86_64")]
+        #[repr(C)]
+        #[derive(Default)]
+        pub struct ThreadStateGeneralRegs {
+            pub rax: u64,
+            pub rbx: u64,
+            pub rcx: u64,
+            pub rdx: u64,
+            pub rsi: u64,
+            pub rdi: u64,
+            pub rbp: u64,
+            pub rsp: u64,
+            pub r: [u64; 8],
+            pub rip: u64,
+            pub rflags: u64,
+            pub fs_base: u64,
+            pub gs_base: u64,
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        impl ThreadStateGeneralRegs {
+            pub fn register_state(&self) -> RegisterState {
+                RegisterState {
+                    pc: self.rip as *mut libc::c_void,
+                    sp: self.rsp as *mut libc::c_void,
+                    fp: self.rbp as *mut libc::c_void,
+                    lr: std::ptr::null_mut(),
+                }
             }
+        }
 
-            pub fn from_minutes(minutes: i64) -> Self {
-                Self(minutes * 60 * 1_000_000_000)
+        /// zx_thread_state_general_regs_t.
+        #[cfg(target_arch = "aarch64")]
+        #[repr(C)]
+        #[derive(Default)]
+        pub struct ThreadStateGeneralRegs {
+            pub r: [u64; 30],
+            pub lr: u64,
+            pub sp: u64,
+            pub pc: u64,
+            pub cpsr: u64,
+            pub tpidr: u64,
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        impl ThreadStateGeneralRegs {
+            pub fn register_state(&self) -> RegisterState {
+                RegisterState {
+                    pc: self.pc as *mut libc::c_void,
+                    sp: self.sp as *mut libc::c_void,
+                    fp: self.r[29] as *mut libc::c_void,
+                    lr: self.lr as *mut libc::c_void,
+                }
             }
+        }
+    }
 
-            pub fn from_hours(hours: i64) -> Self {
-                Self(hours * 60 * 60 * 1_000_000_000)
-            }
+    #[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+    mod tests {
+        use super::*;
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::Arc;
 
-            pub fn from_days(days: i64) -> Self {
-                Self(days * 24 * 60 * 60 * 1_000_000_000)
-            }
-        }
+        struct CountingHandler(Arc<AtomicUsize>);
 
-        bitflags! {
-            #[repr(transparent)]
-            #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-            pub struct Rights: u32 {
-                const NONE = 0;
-                const DUPLICATE = 1 << 0;
-                const TRANSFER = 1 << 1;
-                const READ = 1 << 2;
-                const WRITE = 1 << 3;
-                const EXECUTE = 1 << 4;
-                const MAP = 1 << 5;
-                const GET_PROPERTY = 1 << 6;
-                const SET_PROPERTY = 1 << 7;
-                const ENUMERATE = 1 << 8;
-                const DESTROY = 1 << 9;
-                const SET_POLICY = 1 << 10;
-                const GET_POLICY = 1 << 11;
-                const SIGNAL = 1 << 12;
-                const WAIT = 1 << 13;
-                const INSPECT = 1 << 14;
-                const MANAGE_JOB = 1 << 16;
-                const MANAGE_PROCESS = 1 << 17;
-                const MANAGE_THREAD = 1 << 18;
-                const SUSPEND = 1 << 19;
-                const RESUME = 1 << 20;
-                const GET_CPU_MAP = 1 << 21;
-                const SET_DEBUG_REGS = 1 << 22;
-                const GET_DEBUG_REGS = 1 << 23;
-                const SAME_RIGHTS = 0x7fff_ffff;
+        impl SampleStackHandler for CountingHandler {
+            fn sample_stack(&self, state: &RegisterState) {
+                if !state.pc.is_null() && !state.sp.is_null() {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
             }
         }
 
-        bitflags! {
-            #[repr(transparent)]
-            #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-            pub struct Signals: u32 {
-                const NONE = 0;
-                const READABLE = 1 << 0;
-                const WRITABLE = 1 << 1;
-                const PEER_CLOSED = 1 << 2;
-                const SIGNALED = 1 << 3;
-                const THREAD_SUSPENDED = 1 << 4;
-                const THREAD_RUNNING = 1 << 5;
-                const THREAD_TERMINATED = 1 << 6;
-                const PROCESS_TERMINATED = 1 << 7;
-                const JOB_TERMINATED = 1 << 8;
-                const OBJECT_OBSERVED = 1 << 9;
-                const POLICY_CHANGED = 1 << 10;
-                const KERNEL_OBJECT_ALL = 0x7fff_ffff;
-            }
+        #[test]
+        fn do_sample_records_current_thread() {
+            let samples = Arc::new(AtomicUsize::new(0));
+            let mut isolate_storage = 0u8;
+            let isolate = &mut isolate_storage as *mut u8 as *mut Isolate;
+            let sampler = Sampler::with_handler(isolate, Box::new(CountingHandler(samples.clone())));
+            sampler.start();
+            assert!(sampler.is_active());
+            sampler.do_sample();
+            sampler.do_sample();
+            sampler.stop();
+            assert!(!sampler.is_active());
+            assert_eq!(samples.load(Ordering::SeqCst), 2);
+            // Once stopped, no further samples are taken.
+            sampler.do_sample();
+            assert_eq!(samples.load(Ordering::SeqCst), 2);
         }
-
-        #[repr(C)]
-        #[derive(Default, Debug, Copy, Clone)]
-        pub struct ThreadStateGeneralRegs {
-            pub
\ No newline at end of file
+    }
+}
@@ -1,174 +1,178 @@
-// src/logging/code_events.rs
+// Copyright 2016 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
-use std::any::Any;
-// use base::platform::mutex::Mutex; // Assuming a cross-platform mutex is desired
-// use base::vector::Vector; // Using std::vec::Vec instead
-// use common::globals::Address; // Using usize for address representation
-// use objects::bytecode_array::BytecodeArray; // Assuming a suitable replacement exists
-// use objects::code::Code; // Assuming a suitable replacement exists
-// use objects::instruction_stream::InstructionStream; // Assuming a suitable replacement exists
-// use objects::name::Name; // Assuming a suitable replacement exists
-// use objects::shared_function_info::SharedFunctionInfo; // Assuming a suitable replacement exists
-// use objects::string::String; // Assuming a suitable replacement exists
+use std::sync::{Arc, Mutex};
 
-// Dummy types for V8 specific classes as replacements
-#[derive(Debug, Clone, Copy)]
-pub struct Address(usize);
+use crate::objects::code_kind::CodeKind;
 
-pub type Tagged<T> = T;
+pub type Address = usize;
 
-#[derive(Debug, Clone, Copy)]
-pub struct AbstractCode;
-
-#[derive(Debug, Clone, Copy)]
-pub struct Name;
-
-#[derive(Debug, Clone, Copy)]
-pub struct SharedFunctionInfo;
-
-#[derive(Debug, Clone, Copy)]
-pub struct String;
-
-#[derive(Debug, Clone, Copy)]
-pub struct BytecodeArray;
-
-#[derive(Debug, Clone, Copy)]
-pub struct Code;
-
-#[derive(Debug, Clone, Copy)]
-pub struct InstructionStream;
-
-#[derive(Debug, Clone, Copy)]
-pub struct RegExpFlags;
-
-#[derive(Debug, Clone, Copy)]
-pub struct DeoptimizeKind;
+/// The events of the V8 log, with their names in the log file.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    kCodeCreation,
+    kCodeDisableOpt,
+    kCodeMove,
+    kCodeDeopt,
+    kCodeDelete,
+    kCodeMovingGC,
+    kSharedFuncMove,
+    kSnapshotCodeName,
+    kTick,
+}
 
-pub mod wasm {
-    // pub type WasmName = base::Vector<const char>; // Using Vec<u8> for simplicity
-    pub type WasmName = Vec<u8>;
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::kCodeCreation => "code-creation",
+            Event::kCodeDisableOpt => "code-disable-optimization",
+            Event::kCodeMove => "code-move",
+            Event::kCodeDeopt => "code-deopt",
+            Event::kCodeDelete => "code-delete",
+            Event::kCodeMovingGC => "code-moving-gc",
+            Event::kSharedFuncMove => "sfi-move",
+            Event::kSnapshotCodeName => "snapshot-code-name",
+            Event::kTick => "tick",
+        }
+    }
+}
 
-    #[derive(Debug, Clone, Copy)]
-    pub struct WasmCode;
+/// What a created code object is for.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeTag {
+    kBuiltin,
+    kCallback,
+    kEval,
+    kFunction,
+    kHandler,
+    kBytecodeHandler,
+    kRegExp,
+    kScript,
+    kStub,
+    kNativeFunction,
+    kNativeScript,
 }
 
-macro_rules! log_event_list {
-    ($V:ident) => {
-        $V!(kCodeCreation, "code-creation");
-        $V!(kCodeDisableOpt, "code-disable-optimization");
-        $V!(kCodeMove, "code-move");
-        $V!(kCodeDeopt, "code-deopt");
-        $V!(kCodeDelete, "code-delete");
-        $V!(kCodeMovingGC, "code-moving-gc");
-        $V!(kSharedFuncMove, "sfi-move");
-        $V!(kSnapshotCodeName, "snapshot-code-name");
-        $V!(kTick, "tick");
-    };
+impl CodeTag {
+    /// The code type column of code-creation events.
+    pub fn name(self) -> &'static str {
+        match self {
+            CodeTag::kBuiltin => "Builtin",
+            CodeTag::kCallback => "Callback",
+            CodeTag::kEval => "Eval",
+            CodeTag::kFunction | CodeTag::kNativeFunction => "JS",
+            CodeTag::kHandler => "Handler",
+            CodeTag::kBytecodeHandler => "BytecodeHandler",
+            CodeTag::kRegExp => "RegExp",
+            CodeTag::kScript | CodeTag::kNativeScript => "Script",
+            CodeTag::kStub => "CodeStub",
+        }
+    }
 }
 
-macro_rules! code_type_list {
-    ($V:ident) => {
-        $V!(kBuiltin, Builtin);
-        $V!(kCallback, Callback);
-        $V!(kEval, Eval);
-        $V!(kFunction, JS);
-        $V!(kHandler, Handler);
-        $V!(kBytecodeHandler, BytecodeHandler);
-        $V!(kRegExp, RegExp);
-        $V!(kScript, Script);
-        $V!(kStub, Stub);
-        $V!(kNativeFunction, JS);
-        $V!(kNativeScript, Script);
-    };
+/// The parts of a code object (Code or BytecodeArray) that listeners see.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbstractCode {
+    pub kind: CodeKind,
+    pub instruction_start: Address,
+    pub instruction_size: usize,
+    /// The encoded SourcePositionTable, mapping code offsets to script
+    /// offsets.
+    pub source_position_table: Option<Arc<[u8]>>,
+    /// The .eh_frame and .eh_frame_hdr written by EhFrameWriter.
+    pub unwinding_info: Option<Arc<[u8]>>,
 }
 
-// #[macro_export]
-// macro_rules! profile {
-//     ($the_isolate:expr, $Call:expr) => {
-//         $the_isolate.logger().$Call
-//     };
-// }
-//
-// //This macro requires access to Isolate and Logger which cannot be provided without refactoring
-// //The macro is kept commented for reference
+impl AbstractCode {
+    pub fn new(kind: CodeKind, instruction_start: Address, instruction_size: usize) -> Self {
+        AbstractCode { kind, instruction_start, instruction_size, source_position_table: None, unwinding_info: None }
+    }
+}
 
-#[allow(unused_variables)]
-pub trait LogEventListener: Send + Sync {
-    type Error;
+/// The parts of a Script that listeners see.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Script {
+    pub id: i32,
+    pub name: Option<String>,
+    /// The offsets of the line terminators of the source.
+    pub line_ends: Vec<i32>,
+}
 
-    fn code_create_event_str(&self, tag: CodeTag, code: AbstractCode, name: &str) -> Result<(), Self::Error> {
-        Ok(())
+impl Script {
+    pub fn new(id: i32, name: Option<String>, source: &str) -> Self {
+        let mut line_ends: Vec<i32> =
+            source.char_indices().filter(|(_, c)| *c == '\n').map(|(i, _)| i as i32).collect();
+        // The last line ends at the end of the source.
+        line_ends.push(source.len() as i32);
+        Script { id, name, line_ends }
     }
 
-    fn code_create_event_name(&self, tag: CodeTag, code: AbstractCode, name: Name) -> Result<(), Self::Error> {
-        Ok(())
+    /// Returns the zero-based line and column of a script offset.
+    pub fn get_position_info(&self, position: i32) -> Option<(i32, i32)> {
+        if position < 0 || position > *self.line_ends.last()? {
+            return None;
+        }
+        let line = self.line_ends.partition_point(|&end| end < position);
+        let line_start = if line == 0 { 0 } else { self.line_ends[line - 1] + 1 };
+        Some((line as i32, position - line_start))
     }
+}
 
-    fn code_create_event_shared(&self, tag: CodeTag, code: AbstractCode, shared: SharedFunctionInfo, script_name: Name) -> Result<(), Self::Error> {
-        Ok(())
-    }
+/// The parts of a SharedFunctionInfo that listeners see.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedFunctionInfo {
+    pub address: Address,
+    pub debug_name: String,
+    pub script: Option<Arc<Script>>,
+}
 
-    fn code_create_event_line(&self, tag: CodeTag, code: AbstractCode, shared: SharedFunctionInfo, script_name: Name, line: i32, column: i32) -> Result<(), Self::Error> {
-        Ok(())
-    }
+/// Receives code events, e.g. to write them to the log file or to keep the
+/// code map of a profiler up to date. Events a listener does not care about
+/// are ignored by default.
+#[allow(unused_variables)]
+pub trait LogEventListener: Send + Sync {
+    fn code_create_event(&self, tag: CodeTag, code: &AbstractCode, name: &str);
 
-    #[cfg(feature = "v8_enable_webassembly")]
-    fn code_create_event_wasm(&self, tag: CodeTag, code: &wasm::WasmCode, name: wasm::WasmName, source_url: &str, code_offset: i32, script_id: i32) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn code_create_event_shared(
+        &self,
+        tag: CodeTag,
+        code: &AbstractCode,
+        shared: &SharedFunctionInfo,
+        script_name: &str,
+        line: i32,
+        column: i32,
+    );
 
-    fn callback_event(&self, name: Name, entry_point: Address) -> Result<(), Self::Error> {
-        Ok(())
+    fn code_create_event_wasm(&self, code: &AbstractCode, name: &str, source_url: &str, code_offset: i32, script_id: i32) {
     }
 
-    fn getter_callback_event(&self, name: Name, entry_point: Address) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn callback_event(&self, name: &str, entry_point: Address) {}
 
-    fn setter_callback_event(&self, name: Name, entry_point: Address) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn getter_callback_event(&self, name: &str, entry_point: Address) {}
 
-    fn regexp_code_create_event(&self, code: AbstractCode, source: String, flags: RegExpFlags) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn setter_callback_event(&self, name: &str, entry_point: Address) {}
 
-    fn code_move_event(&self, from: Tagged<InstructionStream>, to: Tagged<InstructionStream>) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn regexp_code_create_event(&self, code: &AbstractCode, source: &str, flags: &str) {}
 
-    fn bytecode_move_event(&self, from: Tagged<BytecodeArray>, to: Tagged<BytecodeArray>) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn code_move_event(&self, from: Address, to: Address) {}
 
-    fn shared_function_info_move_event(&self, from: Address, to: Address) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn bytecode_move_event(&self, from: Address, to: Address) {}
 
-    fn native_context_move_event(&self, from: Address, to: Address) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    /// Code was freed, e.g. unreachable Wasm or regexp code.
+    fn code_delete_event(&self, instruction_start: Address) {}
 
-    fn code_moving_gc_event(&self) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn shared_function_info_move_event(&self, from: Address, to: Address) {}
 
-    fn code_disable_opt_event(&self, code: AbstractCode, shared: SharedFunctionInfo) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn native_context_move_event(&self, from: Address, to: Address) {}
 
-    fn code_deopt_event(&self, code: Code, kind: DeoptimizeKind, pc: Address, fp_to_sp_delta: i32) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn code_moving_gc_event(&self) {}
 
-    fn code_dependency_change_event(&self, code: Code, shared: SharedFunctionInfo, reason: &str) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn code_disable_opt_event(&self, code: &AbstractCode, shared: &SharedFunctionInfo, reason: &str) {}
 
-    fn weak_code_clear_event(&self) -> Result<(), Self::Error> {
-        Ok(())
-    }
+    fn code_deopt_event(&self, code: &AbstractCode, kind: &str, pc: Address, fp_to_sp_delta: i32) {}
 
     fn is_listening_to_code_events(&self) -> bool {
         false
@@ -179,289 +183,123 @@ pub trait LogEventListener: Send + Sync {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub enum Event {
-    kCodeCreation,
-    kCodeDisableOpt,
-    kCodeMove,
-    kCodeDeopt,
-    kCodeDelete,
-    kCodeMovingGC,
-    kSharedFuncMove,
-    kSnapshotCodeName,
-    kTick,
-    kLength,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u8)]
-pub enum CodeTag {
-    kBuiltin,
-    kCallback,
-    kEval,
-    kFunction,
-    kHandler,
-    kBytecodeHandler,
-    kRegExp,
-    kScript,
-    kStub,
-    kNativeFunction,
-    kNativeScript,
-    kLength,
-}
-
-impl From<u8> for CodeTag {
-    fn from(value: u8) -> Self {
-        match value {
-            0 => CodeTag::kBuiltin,
-            1 => CodeTag::kCallback,
-            2 => CodeTag::kEval,
-            3 => CodeTag::kFunction,
-            4 => CodeTag::kHandler,
-            5 => CodeTag::kBytecodeHandler,
-            6 => CodeTag::kRegExp,
-            7 => CodeTag::kScript,
-            8 => CodeTag::kStub,
-            9 => CodeTag::kNativeFunction,
-            10 => CodeTag::kNativeScript,
-            _ => CodeTag::kBuiltin
-        }
-    }
-}
-
-impl From<CodeTag> for u8 {
-    fn from(code_tag: CodeTag) -> Self {
-        match code_tag {
-            CodeTag::kBuiltin => 0,
-            CodeTag::kCallback => 1,
-            CodeTag::kEval => 2,
-            CodeTag::kFunction => 3,
-            CodeTag::kHandler => 4,
-            CodeTag::kBytecodeHandler => 5,
-            CodeTag::kRegExp => 6,
-            CodeTag::kScript => 7,
-            CodeTag::kStub => 8,
-            CodeTag::kNativeFunction => 9,
-            CodeTag::kNativeScript => 10
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum CodeType {
-    Builtin,
-    Callback,
-    Eval,
-    JS,
-    Handler,
-    BytecodeHandler,
-    RegExp,
-    Script,
-    Stub,
-}
-
+/// Dispatches code events to all registered listeners.
 #[derive(Default)]
 pub struct Logger {
-    listeners: Arc<Mutex<Vec<Box<dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>>>>>,
+    listeners: Mutex<Vec<Arc<dyn LogEventListener>>>,
 }
 
 impl Logger {
     pub fn new() -> Self {
-        Logger {
-            listeners: Arc::new(Mutex::new(Vec::new())),
-        }
-    }
-
-    pub fn add_listener(&self, listener: Box<dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>>) -> Result<bool, PoisonError<MutexGuard<'_, Vec<Box<dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>>>>>> {
-        let mut guard = self.listeners.lock()?;
-        if guard.iter().any(|l| {
-            let listener_ptr = &*listener as *const dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>;
-            let l_ptr = &**l as *const dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>;
-            listener_ptr == l_ptr
-        }) {
-            return Ok(false);
-        }
-        guard.push(listener);
-        Ok(true)
+        Self::default()
     }
 
-    pub fn remove_listener(&self, listener: &dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>> -> Result<bool, PoisonError<MutexGuard<'_, Vec<Box<dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>>>>>> {
-        let mut guard = self.listeners.lock()?;
-        let listener_ptr = listener as *const dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>;
-        if let Some(position) = guard.iter().position(|l| {
-            let l_ptr = &**l as *const dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>;
-            listener_ptr == l_ptr
-        }) {
-            guard.remove(position);
-            Ok(true)
-        } else {
-            Ok(false)
+    /// Returns false if `listener` was already registered.
+    pub fn add_listener(&self, listener: Arc<dyn LogEventListener>) -> bool {
+        let mut listeners = self.listeners.lock().unwrap();
+        if listeners.iter().any(|l| Arc::ptr_eq(l, &listener)) {
+            return false;
         }
+        listeners.push(listener);
+        true
     }
 
-    pub fn is_listening_to_code_events(&self) -> Result<bool, PoisonError<MutexGuard<'_, Vec<Box<dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>>>>>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            if listener.is_listening_to_code_events() {
-                return Ok(true);
-            }
-        }
-        Ok(false)
-    }
-
-    pub fn allows_code_compaction(&self) -> Result<bool, PoisonError<MutexGuard<'_, Vec<Box<dyn LogEventListener<Error = Box<dyn Any + Send + Sync>>>>>>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            if !listener.allows_code_compaction() {
-                return Ok(false);
-            }
-        }
-        Ok(true)
+    /// Returns false if `listener` was not registered.
+    pub fn remove_listener(&self, listener: &Arc<dyn LogEventListener>) -> bool {
+        let mut listeners = self.listeners.lock().unwrap();
+        let Some(position) = listeners.iter().position(|l| Arc::ptr_eq(l, listener)) else {
+            return false;
+        };
+        listeners.remove(position);
+        true
     }
 
-    pub fn code_create_event_str(&self, tag: CodeTag, code: AbstractCode, comment: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_create_event_str(tag, code, comment)?;
-        }
-        Ok(())
+    pub fn is_listening_to_code_events(&self) -> bool {
+        self.listeners.lock().unwrap().iter().any(|listener| listener.is_listening_to_code_events())
     }
 
-    pub fn code_create_event_name(&self, tag: CodeTag, code: AbstractCode, name: Name) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_create_event_name(tag, code, name)?;
-        }
-        Ok(())
+    pub fn allows_code_compaction(&self) -> bool {
+        self.listeners.lock().unwrap().iter().all(|listener| listener.allows_code_compaction())
     }
 
-    pub fn code_create_event_shared(&self, tag: CodeTag, code: AbstractCode, shared: SharedFunctionInfo, name: Name) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_create_event_shared(tag, code, shared, name)?;
+    fn for_each(&self, f: impl Fn(&dyn LogEventListener)) {
+        for listener in self.listeners.lock().unwrap().iter() {
+            f(listener.as_ref());
         }
-        Ok(())
     }
 
-    pub fn code_create_event_line(&self, tag: CodeTag, code: AbstractCode, shared: SharedFunctionInfo, source: Name, line: i32, column: i32) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_create_event_line(tag, code, shared, source, line, column)?;
-        }
-        Ok(())
+    pub fn code_create_event(&self, tag: CodeTag, code: &AbstractCode, name: &str) {
+        self.for_each(|listener| listener.code_create_event(tag, code, name));
     }
 
-    #[cfg(feature = "v8_enable_webassembly")]
-    pub fn code_create_event_wasm(&self, tag: CodeTag, code: &wasm::WasmCode, name: wasm::WasmName, source_url: &str, code_offset: i32, script_id: i32) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_create_event_wasm(tag, code, name.clone(), source_url, code_offset, script_id)?;
-        }
-        Ok(())
+    pub fn code_create_event_shared(
+        &self,
+        tag: CodeTag,
+        code: &AbstractCode,
+        shared: &SharedFunctionInfo,
+        script_name: &str,
+        line: i32,
+        column: i32,
+    ) {
+        self.for_each(|listener| listener.code_create_event_shared(tag, code, shared, script_name, line, column));
     }
 
-    pub fn callback_event(&self, name: Name, entry_point: Address) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.callback_event(name, entry_point)?;
-        }
-        Ok(())
+    pub fn code_create_event_wasm(
+        &self,
+        code: &AbstractCode,
+        name: &str,
+        source_url: &str,
+        code_offset: i32,
+        script_id: i32,
+    ) {
+        self.for_each(|listener| listener.code_create_event_wasm(code, name, source_url, code_offset, script_id));
     }
 
-    pub fn getter_callback_event(&self, name: Name, entry_point: Address) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.getter_callback_event(name, entry_point)?;
-        }
-        Ok(())
+    pub fn callback_event(&self, name: &str, entry_point: Address) {
+        self.for_each(|listener| listener.callback_event(name, entry_point));
     }
 
-    pub fn setter_callback_event(&self, name: Name, entry_point: Address) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.setter_callback_event(name, entry_point)?;
-        }
-        Ok(())
+    pub fn getter_callback_event(&self, name: &str, entry_point: Address) {
+        self.for_each(|listener| listener.getter_callback_event(name, entry_point));
     }
 
-    pub fn regexp_code_create_event(&self, code: AbstractCode, source: String, flags: RegExpFlags) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.regexp_code_create_event(code, source, flags)?;
-        }
-        Ok(())
+    pub fn setter_callback_event(&self, name: &str, entry_point: Address) {
+        self.for_each(|listener| listener.setter_callback_event(name, entry_point));
     }
 
-    pub fn code_move_event(&self, from: Tagged<InstructionStream>, to: Tagged<InstructionStream>) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_move_event(from, to)?;
-        }
-        Ok(())
+    pub fn regexp_code_create_event(&self, code: &AbstractCode, source: &str, flags: &str) {
+        self.for_each(|listener| listener.regexp_code_create_event(code, source, flags));
     }
 
-    pub fn bytecode_move_event(&self, from: Tagged<BytecodeArray>, to: Tagged<BytecodeArray>) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.bytecode_move_event(from, to)?;
-        }
-        Ok(())
+    pub fn code_move_event(&self, from: Address, to: Address) {
+        self.for_each(|listener| listener.code_move_event(from, to));
     }
 
-    pub fn shared_function_info_move_event(&self, from: Address, to: Address) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.shared_function_info_move_event(from, to)?;
-        }
-        Ok(())
+    pub fn bytecode_move_event(&self, from: Address, to: Address) {
+        self.for_each(|listener| listener.bytecode_move_event(from, to));
     }
 
-    pub fn native_context_move_event(&self, from: Address, to: Address) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.native_context_move_event(from, to)?;
-        }
-        Ok(())
+    pub fn code_delete_event(&self, instruction_start: Address) {
+        self.for_each(|listener| listener.code_delete_event(instruction_start));
     }
 
-    pub fn code_moving_gc_event(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_moving_gc_event()?;
-        }
-        Ok(())
+    pub fn shared_function_info_move_event(&self, from: Address, to: Address) {
+        self.for_each(|listener| listener.shared_function_info_move_event(from, to));
     }
 
-    pub fn code_disable_opt_event(&self, code: AbstractCode, shared: SharedFunctionInfo) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_disable_opt_event(code, shared)?;
-        }
-        Ok(())
+    pub fn native_context_move_event(&self, from: Address, to: Address) {
+        self.for_each(|listener| listener.native_context_move_event(from, to));
     }
 
-    pub fn code_deopt_event(&self, code: Code, kind: DeoptimizeKind, pc: Address, fp_to_sp_delta: i32) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_deopt_event(code, kind, pc, fp_to_sp_delta)?;
-        }
-        Ok(())
+    pub fn code_moving_gc_event(&self) {
+        self.for_each(|listener| listener.code_moving_gc_event());
     }
 
-    pub fn code_dependency_change_event(&self, code: Code, sfi: SharedFunctionInfo, reason: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.code_dependency_change_event(code, sfi, reason)?;
-        }
-        Ok(())
+    pub fn code_disable_opt_event(&self, code: &AbstractCode, shared: &SharedFunctionInfo, reason: &str) {
+        self.for_each(|listener| listener.code_disable_opt_event(code, shared, reason));
     }
 
-    pub fn weak_code_clear_event(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let guard = self.listeners.lock()?;
-        for listener in guard.iter() {
-            listener.weak_code_clear_event()?;
-        }
-        Ok(())
+    pub fn code_deopt_event(&self, code: &AbstractCode, kind: &str, pc: Address, fp_to_sp_delta: i32) {
+        self.for_each(|listener| listener.code_deopt_event(code, kind, pc, fp_to_sp_delta));
     }
-}
\ No newline at end of file
+}
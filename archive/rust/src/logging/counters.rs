@@ -5,6 +5,9 @@ use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::time::Duration;
 
+use crate::logging::runtime_call_stats::RuntimeCallStatsTables;
+use crate::logging::runtime_call_stats_scope::HasRuntimeCallStats;
+
 // Placeholder for base::ElapsedTimer and base::TimeDelta
 // Need to find suitable Rust equivalents or implementations.
 #[derive(Debug, Clone, Copy)]
@@ -572,6 +575,8 @@ pub struct Counters {
     aggregatable_histogram_timer_list: Vec<(&'static str, &'static str, AggregatableHistogramTimer)>,
     stats_counter_list: Vec<(&'static str, &'static str, StatsCounter)>,
     stats_counter_native_code_list: Vec<(&'static str, &'static str, StatsCounter)>,
+
+    runtime_call_stats_: Arc<RuntimeCallStatsTables>,
 }
 
 impl Counters {
@@ -593,6 +598,7 @@ impl Counters {
             aggregatable_histogram_timer_list: Vec::new(),
             stats_counter_list: Vec::new(),
             stats_counter_native_code_list: Vec::new(),
+            runtime_call_stats_: Arc::new(RuntimeCallStatsTables::new()),
         };
 
         counters
@@ -686,6 +692,19 @@ impl Counters {
         });
     }
 
+    /// The runtime call stats tables of the threads of the isolate.
+    pub fn runtime_call_stats(&self) -> &Arc<RuntimeCallStatsTables> {
+        &self.runtime_call_stats_
+    }
+
+    /// Prints the runtime call stats of all threads, as for
+    /// --runtime-call-stats, and starts over.
+    pub fn dump_and_reset_runtime_call_stats(&self, os: &mut dyn std::io::Write) -> std::io::Result<()> {
+        self.runtime_call_stats_.print(os)?;
+        self.runtime_call_stats_.reset();
+        Ok(())
+    }
+
     pub fn stats_table(&self) -> Arc<Mutex<StatsTable>> {
         self.stats_table_.clone()
     }
@@ -729,6 +748,12 @@ impl Counters {
     }
 }
 
+impl HasRuntimeCallStats for Counters {
+    fn runtime_call_stats(&self) -> Option<&RuntimeCallStatsTables> {
+        Some(&self.runtime_call_stats_)
+    }
+}
+
 // Macros
 macro_rules! histogram_range_list {
     ($($name:ident, $caption:literal, $min:expr, $max:expr, $num_buckets:expr);*) => {
@@ -774,4 +799,58 @@ macro_rules! nested_timed_histogram_list {
     ($($name:ident, $caption:literal, $max:expr, $res:ident);*) => {
         $(
             pub fn $name(&mut self, counters: *mut Counters) {
-                self.nested_timed_histogram_list.push((stringify!($name), $caption, $max, TimedHistogramResolution::$res, NestedTimedHistogram::new($caption, $
\ No newline at end of file
+                self.nested_timed_histogram_list.push((stringify!($name), $caption, $max, TimedHistogramResolution::$res, NestedTimedHistogram::new($caption, $max, TimedHistogramResolution::$res, counters)));
+            }
+        )*
+    };
+}
+
+macro_rules! nested_timed_histogram_list_slow {
+    ($($name:ident, $caption:literal, $max:expr, $res:ident);*) => {
+        $(
+            pub fn $name(&mut self, counters: *mut Counters) {
+                self.nested_timed_histogram_list_slow.push((stringify!($name), $caption, $max, TimedHistogramResolution::$res, NestedTimedHistogram::new($caption, $max, TimedHistogramResolution::$res, counters)));
+            }
+        )*
+    };
+}
+
+macro_rules! timed_histogram_list {
+    ($($name:ident, $caption:literal, $max:expr, $res:ident);*) => {
+        $(
+            pub fn $name(&mut self, counters: *mut Counters) {
+                self.timed_histogram_list.push((stringify!($name), $caption, $max, TimedHistogramResolution::$res, TimedHistogram::new($caption, 0, $max, TimedHistogramResolution::$res, DEFAULT_TIMED_HISTOGRAM_NUM_BUCKETS, counters)));
+            }
+        )*
+    };
+}
+
+macro_rules! aggregatable_histogram_timer_list {
+    ($($name:ident, $caption:literal);*) => {
+        $(
+            pub fn $name(&mut self, counters: *mut Counters) {
+                self.aggregatable_histogram_timer_list.push((stringify!($name), $caption, AggregatableHistogramTimer::new($caption, counters)));
+            }
+        )*
+    };
+}
+
+macro_rules! stats_counter_list {
+    ($($name:ident, $caption:literal);*) => {
+        $(
+            pub fn $name(&mut self, counters: *mut Counters) {
+                self.stats_counter_list.push((stringify!($name), $caption, StatsCounter::new($caption, counters)));
+            }
+        )*
+    };
+}
+
+macro_rules! stats_counter_native_code_list {
+    ($($name:ident, $caption:literal);*) => {
+        $(
+            pub fn $name(&mut self, counters: *mut Counters) {
+                self.stats_counter_native_code_list.push((stringify!($name), $caption, StatsCounter::new($caption, counters)));
+            }
+        )*
+    };
+}
@@ -2,127 +2,45 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod local_logger {
-    //use crate::base::logging; // Assuming a Rust equivalent exists
-    //use crate::logging::log; // Assuming a Rust equivalent exists
-
-    // Placeholder types.  Need to define proper Rust equivalents.
-    pub type Isolate = u32; // Example placeholder
-    pub type Script = u32; // Example placeholder
-    pub type TrustedByteArray = u32; // Example placeholder
-    pub type Address = usize; // Example placeholder
-    pub type Map = u32; // Example placeholder
-    pub type ScriptEventType = u32; // Example placeholder
-
-    #[derive(Debug, Copy, Clone)]
-    pub enum CodeType {
-        // Placeholder enum
-        Other,
+use std::sync::Arc;
+
+use crate::logging::code_events::LogEventListener;
+use crate::logging::log::{ScriptEventType, V8FileLogger};
+
+/// The logger of a LocalIsolate, e.g. for off-thread compilation. It
+/// forwards to the V8FileLogger of the main isolate, whose log file
+/// serializes the lines of all threads.
+pub struct LocalLogger {
+    v8_file_logger: Option<Arc<V8FileLogger>>,
+    is_logging: bool,
+    is_listening_to_code_events: bool,
+}
+
+impl LocalLogger {
+    pub fn new(v8_file_logger: Option<Arc<V8FileLogger>>) -> Self {
+        let is_logging = v8_file_logger.as_ref().is_some_and(|logger| logger.is_logging());
+        let is_listening_to_code_events =
+            v8_file_logger.as_ref().is_some_and(|logger| logger.is_listening_to_code_events());
+        LocalLogger { v8_file_logger, is_logging, is_listening_to_code_events }
     }
 
-    #[derive(Debug, Copy, Clone)]
-    pub struct JitCodeEvent {
-        // Placeholder struct
-        pub code_type: CodeType,
-    }
-    
-    pub struct V8FileLogger;  // Placeholder type. Need actual definition.
-    
-    /// Logger that handles logging events locally within a single thread.
-    pub struct LocalLogger {
-        v8_file_logger: *mut V8FileLogger, // raw pointer since V8FileLogger definition is unknown.  Needs memory management based on actual use.
-        is_logging: bool,
-        is_listening_to_code_events: bool,
+    pub fn is_logging(&self) -> bool {
+        self.is_logging
     }
 
-    impl LocalLogger {
-        /// Creates a new `LocalLogger`.
-        pub fn new(isolate: Isolate) -> Self {
-            // `isolate` is unused in the C++ code.  Keeping it for potential future use.
-            LocalLogger {
-                v8_file_logger: std::ptr::null_mut(), // needs initialization based on usage pattern and actual type
-                is_logging: false,
-                is_listening_to_code_events: false,
-            }
-        }
-
-        /// Returns `true` if logging is enabled.
-        pub fn is_logging(&self) -> bool {
-            self.is_logging
-        }
-
-        /// Returns `true` if the logger is listening to code events.
-        pub fn is_listening_to_code_events(&self) -> bool {
-            self.is_listening_to_code_events
-        }
-
-        /// Records details about a script.
-        pub fn script_details(&mut self, script: Script) {
-            // Implementation details to be filled in based on the original C++ code.
-            // Likely involves interacting with `v8_file_logger`.
-            // Assuming Script and other V8 types have Rust equivalents.
-            unsafe{
-                // Placeholder for v8_file_logger usage.  Requires proper memory handling/ownership definition.
-                //(*self.v8_file_logger).log_script(script);
-            }
-        }
-
-        /// Records a script event.
-        pub fn script_event(&mut self, _type: ScriptEventType, _script_id: i32) {
-            // Implementation details to be filled in based on the original C++ code.
-            // Likely involves interacting with `v8_file_logger`.
-            unsafe{
-                // Placeholder for v8_file_logger usage.  Requires proper memory handling/ownership definition.
-                //(*self.v8_file_logger).log_script_event(_type, _script_id);
-            }
-        }
-
-        /// Records code line position info event.
-        pub fn code_line_pos_info_record_event(
-            &mut self,
-            code_start: Address,
-            source_position_table: TrustedByteArray,
-            code_type: JitCodeEvent::CodeType,
-        ) {
-            // Implementation details to be filled in based on the original C++ code.
-            // Likely involves interacting with `v8_file_logger`.
-             unsafe{
-                // Placeholder for v8_file_logger usage.  Requires proper memory handling/ownership definition.
-                //(*self.v8_file_logger).log_code_line_pos_info(code_start, source_position_table, code_type);
-            }
-        }
-
-        /// Records the creation of a map.
-        pub fn map_create(&mut self, map: Map) {
-            // Implementation details to be filled in based on the original C++ code.
-            // Likely involves interacting with `v8_file_logger`.
-            unsafe{
-                // Placeholder for v8_file_logger usage.  Requires proper memory handling/ownership definition.
-                //(*self.v8_file_logger).log_map_create(map);
-            }
-        }
+    pub fn is_listening_to_code_events(&self) -> bool {
+        self.is_listening_to_code_events
+    }
 
-        /// Records details about a map.
-        pub fn map_details(&mut self, map: Map) {
-            // Implementation details to be filled in based on the original C++ code.
-            // Likely involves interacting with `v8_file_logger`.
-            unsafe{
-                // Placeholder for v8_file_logger usage.  Requires proper memory handling/ownership definition.
-                //(*self.v8_file_logger).log_map_details(map);
-            }
+    pub fn script_details(&self, script_id: i32, name: &str, line_offset: i32, column_offset: i32, source: Option<&str>) {
+        if let Some(logger) = &self.v8_file_logger {
+            logger.script_details(script_id, name, line_offset, column_offset, source);
         }
     }
 
-    impl Drop for LocalLogger {
-        fn drop(&mut self) {
-            unsafe {
-                if !self.v8_file_logger.is_null() {
-                    // Requires to replace with correct code for proper deallocation.
-                    // For instance, if using Box::from_raw, the code would look like
-                    // let _ = Box::from_raw(self.v8_file_logger);
-                    // Otherwise, it will cause memory leak.
-                }
-            }
+    pub fn script_event(&self, event_type: ScriptEventType, script_id: i32) {
+        if let Some(logger) = &self.v8_file_logger {
+            logger.script_event(event_type, script_id);
         }
     }
-}
\ No newline at end of file
+}
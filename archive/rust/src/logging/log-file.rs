@@ -2,367 +2,290 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::ffi::{CString, NulError};
+use std::fmt::{self, Write as _};
 use std::fs::File;
-use std::io::{self, Write, BufWriter};
-use std::mem::MaybeUninit;
-use std::num::TryFromIntError;
-use std::ops::DerefMut;
-use std::path::Path;
+use std::io::{self, BufWriter, Write};
 use std::sync::{Mutex, MutexGuard};
-use std::sync::atomic::{AtomicBool, Ordering};
 
-// Placeholder for base::Vector. Needs further definition.
-mod base {
-    pub struct Vector<T>(Vec<T>);
+use crate::include::v8_version::v8_version::{
+    V8_BUILD_NUMBER, V8_IS_CANDIDATE_VERSION, V8_MAJOR_VERSION, V8_MINOR_VERSION, V8_PATCH_LEVEL,
+};
 
-    impl<T> Vector<T> {
-        pub fn new() -> Self {
-            Vector(Vec::new())
-        }
-    }
+/// Whether pointers into the heap are sandboxed, see
+/// src/common/globals.rs. Reported in the v8-version log line.
+const V8_ENABLE_SANDBOX_BOOL: bool = false;
 
-    impl Vector<char> {
-        pub fn from_vec(vec: Vec<char>) -> Self {
-            Vector(vec)
-        }
-    }
+/// Separates the fields of a log line.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LogSeparator {
+    kSeparator,
 }
 
-mod internal {
-    use super::*;
-    use std::fmt;
-    use std::fmt::Write as FmtWrite;
-    use std::borrow::Cow;
-
-    pub struct V8FileLogger; // Placeholder for V8FileLogger
-
-    #[derive(Debug, Copy, Clone)]
-    pub enum LogSeparator {
-        kSeparator,
-    }
-
-    pub struct LogFile {
-        logger_: *mut V8FileLogger, // Raw pointer, consider alternatives
-        file_name_: String,
-        output_handle_: Option<BufWriter<File>>,
-        os_: OFStream,
-        mutex_: Mutex<()>, // Placeholder, refine Mutex usage if necessary
-        format_buffer_: Box<[u8; Self::K_MESSAGE_BUFFER_SIZE]>,
-    }
-
-    impl LogFile {
-        pub const K_MESSAGE_BUFFER_SIZE: usize = 2048;
-        pub const K_LOG_TO_TEMPORARY_FILE: &'static str = "temporary";
-        pub const K_LOG_TO_CONSOLE: &'static str = "console";
-
-        pub fn new(logger: *mut V8FileLogger, log_file_name: String) -> LogFile {
-            LogFile {
-                logger_: logger,
-                file_name_: log_file_name,
-                output_handle_: None,
-                os_: OFStream::default(), //Needs default implementation
-                mutex_: Mutex::new(()),
-                format_buffer_: Box::new([0u8; Self::K_MESSAGE_BUFFER_SIZE]),
-            }
-        }
-
-        pub fn is_logging_to_console(file_name: String) -> bool {
-            file_name == Self::K_LOG_TO_CONSOLE
-        }
-
-        pub fn is_logging_to_temporary_file(file_name: String) -> bool {
-            file_name == Self::K_LOG_TO_TEMPORARY_FILE
-        }
+/// Where the log goes.
+enum Output {
+    File(BufWriter<File>),
+    Console,
+    Temporary(Vec<u8>),
+}
 
-        pub fn close(&mut self) -> io::Result<()> {
-            // Placeholder return value. Original C++ returns FILE*.
-            if let Some(mut writer) = self.output_handle_.take() {
-                writer.flush()?;
-                //File is implicitly closed when writer goes out of scope
+impl Output {
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        match self {
+            Output::File(file) => file.write_all(bytes),
+            Output::Console => io::stdout().lock().write_all(bytes),
+            Output::Temporary(buffer) => {
+                buffer.extend_from_slice(bytes);
+                Ok(())
             }
-            Ok(())
-        }
-
-        pub fn file_name(&self) -> &str {
-            &self.file_name_
-        }
-
-        fn create_output_handle(file_name: String) -> io::Result<BufWriter<File>> {
-            let path = Path::new(&file_name);
-            let file = File::create(&path)?;
-            Ok(BufWriter::new(file))
         }
+    }
 
-        fn mutex(&self) -> &Mutex<()> {
-            &self.mutex_
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::File(file) => file.flush(),
+            Output::Console => io::stdout().lock().flush(),
+            Output::Temporary(_) => Ok(()),
         }
+    }
+}
 
-        fn write_log_header(&mut self) -> io::Result<()> {
-            // Placeholder implementation
-            writeln!(self.os_, "Log Header Placeholder")
-        }
+/// The log file of a V8FileLogger (--logfile). Lines are written whole, so
+/// messages from several threads never interleave.
+pub struct LogFile {
+    file_name: String,
+    output: Mutex<Option<Output>>,
+}
 
-        pub fn new_message_builder(&mut self) -> Option<MessageBuilder> {
-            //Check if logging is disabled
-            Some(MessageBuilder::new(self))
-        }
+impl LogFile {
+    /// Log into a temporary buffer that `close` returns, e.g. for tests.
+    pub const K_LOG_TO_TEMPORARY_FILE: &'static str = "+";
+    /// Log to stdout.
+    pub const K_LOG_TO_CONSOLE: &'static str = "-";
+
+    /// Opens `file_name` and writes the log header.
+    pub fn new(file_name: &str) -> io::Result<LogFile> {
+        let output = if Self::is_logging_to_console(file_name) {
+            Output::Console
+        } else if Self::is_logging_to_temporary_file(file_name) {
+            Output::Temporary(Vec::new())
+        } else {
+            Output::File(BufWriter::new(File::create(file_name)?))
+        };
+        let log = LogFile { file_name: file_name.to_string(), output: Mutex::new(Some(output)) };
+        log.write_log_header();
+        Ok(log)
     }
 
-    pub struct MessageBuilder<'a> {
-        log_: &'a mut LogFile,
-        lock_guard_: MutexGuard<'a, ()>, // Consider if this is really needed.  Mutex is acquired in new.
-        buffer_offset: usize,
+    pub fn is_logging_to_console(file_name: &str) -> bool {
+        file_name == Self::K_LOG_TO_CONSOLE
     }
 
-    impl<'a> MessageBuilder<'a> {
-        fn new(log: &'a mut LogFile) -> Self {
-            let lock_guard_ = log.mutex().lock().unwrap();
-            MessageBuilder {
-                log_: log,
-                lock_guard_,
-                buffer_offset: 0,
-            }
-        }
-
-        pub fn append_string(&mut self, str: TaggedString) -> Result<(), std::io::Error>{
-            self.append_string_with_limit(str, None)
-        }
-
-        pub fn append_string_with_limit(&mut self, str: TaggedString, length_limit: Option<usize>) -> Result<(), std::io::Error>{
-            let str_val = str.value;
-            let string = match str_val {
-                Some(s) => s,
-                None => return Ok(())
-            };
-
-            self.append_raw_string(&string)
-        }
-
-        pub fn append_string_vector(&mut self, str: base::Vector<char>) -> Result<(), std::io::Error> {
-            let chars = str.0;
-            let string: String = chars.into_iter().collect();
-
-            self.append_raw_string(&string)
-        }
-
-        pub fn append_string_slice(&mut self, str: &str) -> Result<(), std::io::Error> {
-            self.append_raw_string(str)
-        }
-
-        pub fn append_string_slice_with_length(&mut self, str: &str, length: usize, _is_one_byte: bool) -> Result<(), std::io::Error> {
-            let slice = &str[..std::cmp::min(length, str.len())];
-            self.append_raw_string(slice)
-        }
-
-        pub fn append_format_string(&mut self, format: &str, args: Vec<String>) -> Result<(), std::io::Error>{
-            let formatted_string = format.to_string(); //Format string here
-            self.append_raw_string(&formatted_string)
-        }
-
-        pub fn append_character(&mut self, c: char) -> Result<(), std::io::Error> {
-            self.append_raw_character(c)
-        }
-
-        pub fn append_two_byte_character(&mut self, c1: char, c2: char) -> Result<(), std::io::Error> {
-            self.append_character(c1)?;
-            self.append_character(c2)
-        }
-
-        pub fn append_symbol_name(&mut self, symbol: TaggedSymbol) -> Result<(), std::io::Error> {
-            let string = symbol.name;
-            self.append_symbol_name_details(string, false)
-        }
+    pub fn is_logging_to_temporary_file(file_name: &str) -> bool {
+        file_name == Self::K_LOG_TO_TEMPORARY_FILE
+    }
 
-        fn append_symbol_name_details(&mut self, str: TaggedString, show_impl_info: bool) -> Result<(), std::io::Error>{
-            let str_val = str.value;
-            let string = match str_val {
-                Some(s) => s,
-                None => return Ok(())
-            };
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
 
-            self.append_raw_string(&string)
-        }
+    pub fn is_enabled(&self) -> bool {
+        self.output.lock().unwrap().is_some()
+    }
 
-        fn format_string_into_buffer(&mut self, format: &str, args: Vec<String>) -> Result<usize, std::io::Error> {
-            // Placeholder implementation, needs proper formatting logic
-            let formatted_string = format.to_string();
-            let len = formatted_string.len();
-            if len > Self::K_MESSAGE_BUFFER_SIZE {
-                return Ok(Self::K_MESSAGE_BUFFER_SIZE);
+    /// Flushes and closes the log. Returns the contents of a temporary log.
+    pub fn close(&self) -> Option<Vec<u8>> {
+        match self.output.lock().unwrap().take()? {
+            Output::Temporary(buffer) => Some(buffer),
+            mut output => {
+                let _ = output.flush();
+                None
             }
-            Ok(len)
         }
+    }
 
-        fn append_raw_format_string(&mut self, format: &str, args: Vec<String>) -> Result<(), std::io::Error>{
-            let formatted_string = format.to_string();
-            self.append_raw_string(&formatted_string)
+    pub fn flush(&self) {
+        if let Some(output) = self.output.lock().unwrap().as_mut() {
+            let _ = output.flush();
         }
+    }
 
-        fn append_raw_string(&mut self, string: &str) -> Result<(), std::io::Error>{
-            for &byte in string.as_bytes() {
-                self.append_raw_character(byte as char)?;
-            }
-            Ok(())
-        }
+    /// Returns a builder for one log line, or `None` once the log is closed.
+    /// The log stays locked until the message is written.
+    pub fn new_message_builder(&self) -> Option<MessageBuilder<'_>> {
+        let output = self.output.lock().unwrap();
+        output.as_ref()?;
+        Some(MessageBuilder { output, line: String::new() })
+    }
 
-        fn append_raw_character(&mut self, character: char) -> Result<(), std::io::Error>{
-            if self.buffer_offset >= LogFile::K_MESSAGE_BUFFER_SIZE {
-                self.write_to_log_file()?;
-            }
-            self.log_.format_buffer_[self.buffer_offset] = character as u8;
-            self.buffer_offset += 1;
-            Ok(())
+    fn write_log_header(&self) {
+        if let Some(mut msg) = self.new_message_builder() {
+            msg.append("v8-version")
+                .append(LogSeparator::kSeparator)
+                .append(V8_MAJOR_VERSION)
+                .append(LogSeparator::kSeparator)
+                .append(V8_MINOR_VERSION)
+                .append(LogSeparator::kSeparator)
+                .append(V8_BUILD_NUMBER)
+                .append(LogSeparator::kSeparator)
+                .append(V8_PATCH_LEVEL)
+                .append(LogSeparator::kSeparator)
+                .append("")
+                .append(LogSeparator::kSeparator)
+                .append(V8_IS_CANDIDATE_VERSION as i32)
+                .append(LogSeparator::kSeparator)
+                .append(V8_ENABLE_SANDBOX_BOOL as i32);
+            msg.write_to_log_file();
+        }
+        if let Some(mut msg) = self.new_message_builder() {
+            msg.append("v8-platform")
+                .append(LogSeparator::kSeparator)
+                .append(std::env::consts::OS)
+                .append(LogSeparator::kSeparator)
+                .append(std::env::consts::OS);
+            msg.write_to_log_file();
         }
+    }
+}
 
-        pub fn write_to_log_file(&mut self) -> Result<(), std::io::Error> {
-            if self.log_.output_handle_.is_none() {
-                let output_handle = LogFile::create_output_handle(self.log_.file_name_.clone())?;
-                self.log_.output_handle_ = Some(output_handle);
-                self.log_.write_log_header()?;
-            }
-
-            if let Some(output_handle) = &mut self.log_.output_handle_ {
-                let slice = &self.log_.format_buffer_[..self.buffer_offset];
-                output_handle.write_all(slice)?;
-                output_handle.flush()?;
-                self.buffer_offset = 0; // Reset the buffer offset
-            } else {
-                eprintln!("Error: Output handle is unexpectedly None");
-            }
+/// A value that can be appended to a log line.
+pub trait LogValue {
+    fn append_to(&self, line: &mut String);
+}
 
-            Ok(())
+/// Strings are escaped so that every message stays on one line and commas
+/// only ever separate fields.
+impl LogValue for &str {
+    fn append_to(&self, line: &mut String) {
+        for c in self.chars() {
+            append_character(line, c);
         }
     }
+}
 
-    impl<'a> Drop for MessageBuilder<'a> {
-        fn drop(&mut self) {
-            //Ensure anything left in the buffer gets flushed to the file
-            if self.buffer_offset > 0 {
-                let _ = self.write_to_log_file(); //Ignore result, nothing can be done
-            }
-        }
+impl LogValue for String {
+    fn append_to(&self, line: &mut String) {
+        self.as_str().append_to(line)
     }
+}
 
-    impl<'a> fmt::Write for MessageBuilder<'a> {
-        fn write_str(&mut self, s: &str) -> fmt::Result {
-            for c in s.chars() {
-                if self.buffer_offset >= LogFile::K_MESSAGE_BUFFER_SIZE {
-                    if let Err(_) = self.write_to_log_file() {
-                        return Err(fmt::Error);
-                    }
-                }
-                self.log_.format_buffer_[self.buffer_offset] = c as u8;
-                self.buffer_offset += 1;
-            }
-            Ok(())
-        }
+impl LogValue for char {
+    fn append_to(&self, line: &mut String) {
+        append_character(line, *self)
     }
+}
 
-    impl<'a> MessageBuilder<'a> {
-        // Delegate insertion to the underlying {log_}.
-        // All appended strings are escaped to maintain one-line log entries.
-        pub fn append<T: fmt::Display>(&mut self, value: T) -> Result<(), std::io::Error> {
-            let mut string_buffer = String::new();
-            write!(&mut string_buffer, "{}", value).unwrap(); //Write to String cannot fail
-
-            self.append_raw_string(&string_buffer)
-        }
+impl LogValue for LogSeparator {
+    fn append_to(&self, line: &mut String) {
+        line.push(',');
     }
+}
 
+macro_rules! impl_log_value_for_numbers {
+    ($($type:ty),*) => {
+        $(impl LogValue for $type {
+            fn append_to(&self, line: &mut String) {
+                let _ = write!(line, "{}", self);
+            }
+        })*
+    };
+}
 
-    //Implement specializations of append based on type
-
-    impl<'a> MessageBuilder<'a> {
-        pub fn append_log_separator(&mut self, separator: LogSeparator) -> Result<(), std::io::Error> {
-            self.append_raw_string("LogSeparator") // Placeholder
-        }
-
-        pub fn append_void_pointer(&mut self, pointer: *mut std::ffi::c_void) -> Result<(), std::io::Error>{
-            let pointer_string = format!("{:p}", pointer);
-            self.append_raw_string(&pointer_string)
-        }
+impl_log_value_for_numbers!(i32, i64, u32, u64, usize, f64);
 
-        pub fn append_char(&mut self, c: char) -> Result<(), std::io::Error> {
-            self.append_raw_character(c)
-        }
+/// Addresses are logged in hex.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LogAddress(pub usize);
 
-        pub fn append_tagged_string(&mut self, string: TaggedString) -> Result<(), std::io::Error> {
-            self.append_string(string)
-        }
+impl LogValue for LogAddress {
+    fn append_to(&self, line: &mut String) {
+        let _ = write!(line, "{:#x}", self.0);
+    }
+}
 
-        pub fn append_tagged_symbol(&mut self, symbol: TaggedSymbol) -> Result<(), std::io::Error>{
-            self.append_symbol_name(symbol)
+fn append_character(line: &mut String, c: char) {
+    match c {
+        ',' => line.push_str("\\x2C"),
+        '\\' => line.push_str("\\\\"),
+        ' '..='~' => line.push(c),
+        '\n' => line.push_str("\\n"),
+        c if (c as u32) <= 0xFF => {
+            let _ = write!(line, "\\x{:02x}", c as u32);
         }
-
-        pub fn append_tagged_name(&mut self, name: TaggedName) -> Result<(), std::io::Error>{
-            let name_string = name.name.unwrap_or("".to_string());
-            self.append_raw_string(&name_string)
+        c => {
+            let _ = write!(line, "\\u{:04x}", c as u32);
         }
     }
+}
 
-    //Helper Types
-    #[derive(Default)]
-    struct OFStream {
-        // Placeholder, needs actual implementation, likely writing to a buffer or file
-    }
-
-    impl OFStream {
-        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-            // Placeholder implementation
-            eprintln!("OFStream::write: {}", String::from_utf8_lossy(buf));
-            Ok(buf.len())
-        }
-    }
+/// Builds one line of the log.
+pub struct MessageBuilder<'a> {
+    output: MutexGuard<'a, Option<Output>>,
+    line: String,
+}
 
-    impl fmt::Write for OFStream {
-        fn write_str(&mut self, s: &str) -> fmt::Result {
-            // Placeholder implementation
-            eprintln!("OFStream::write_str: {}", s);
-            Ok(())
-        }
+impl MessageBuilder<'_> {
+    pub fn append<T: LogValue>(&mut self, value: T) -> &mut Self {
+        value.append_to(&mut self.line);
+        self
     }
 
-    // Placeholder for Tagged<T>.  Needs to be defined more accurately
-    #[derive(Clone)]
-    pub struct TaggedString {
-        value: Option<String>
+    /// Appends pre-formatted text without escaping.
+    pub fn append_raw(&mut self, args: fmt::Arguments<'_>) -> &mut Self {
+        let _ = self.line.write_fmt(args);
+        self
     }
 
-    impl TaggedString {
-        pub fn new(value: Option<String>) -> Self {
-            TaggedString {
-                value
-            }
+    /// Appends `value` with at most `length_limit` characters.
+    pub fn append_string_with_limit(&mut self, value: &str, length_limit: usize) -> &mut Self {
+        for c in value.chars().take(length_limit) {
+            append_character(&mut self.line, c);
         }
+        self
     }
 
-    #[derive(Clone)]
-    pub struct TaggedSymbol {
-        name: TaggedString
+    /// Appends a symbol as `symbol("description" hash 1f)`.
+    pub fn append_symbol_name(&mut self, description: Option<&str>, hash: u32) -> &mut Self {
+        self.line.push_str("symbol(");
+        if let Some(description) = description {
+            self.line.push('"');
+            self.append(description);
+            self.line.push_str("\" ");
+        }
+        let _ = write!(self.line, "hash {hash:x})");
+        self
     }
 
-    impl TaggedSymbol {
-        pub fn new(name: TaggedString) -> Self {
-            TaggedSymbol {
-                name
-            }
+    /// Terminates the line and writes it to the log.
+    pub fn write_to_log_file(mut self) {
+        self.line.push('\n');
+        if let Some(output) = self.output.as_mut() {
+            let _ = output.write_all(self.line.as_bytes());
         }
     }
+}
 
-    #[derive(Clone)]
-    pub struct TaggedName {
-        name: Option<String>
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    impl TaggedName {
-        pub fn new(name: Option<String>) -> Self {
-            TaggedName {
-                name
-            }
-        }
+    #[test]
+    fn escapes_log_lines() {
+        let log = LogFile::new(LogFile::K_LOG_TO_TEMPORARY_FILE).unwrap();
+        let mut msg = log.new_message_builder().unwrap();
+        msg.append("code-creation")
+            .append(LogSeparator::kSeparator)
+            .append(LogAddress(0x1f00))
+            .append(LogSeparator::kSeparator)
+            .append("a,b\\c\nd\u{7f}\u{263a}")
+            .append(LogSeparator::kSeparator)
+            .append_symbol_name(Some("iterator"), 0xab);
+        msg.write_to_log_file();
+        let contents = String::from_utf8(log.close().unwrap()).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert!(lines[0].starts_with(&format!("v8-version,{V8_MAJOR_VERSION},{V8_MINOR_VERSION},")), "{contents}");
+        assert!(lines[1].starts_with("v8-platform,"));
+        assert_eq!(lines[2], "code-creation,0x1f00,a\\x2Cb\\\\c\\nd\\x7f\\u263a,symbol(\"iterator\" hash ab)");
+        assert!(log.new_message_builder().is_none());
     }
-
-    // Placeholder for NoGarbageCollectionMutexGuard
-    struct NoGarbageCollectionMutexGuard {}
-}
\ No newline at end of file
+}
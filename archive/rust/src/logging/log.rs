@@ -1,355 +1,906 @@
-// src/logging/log.rs
-
-// TODO: Add equivalent Rust crates for:
-// - include/v8-locker.h
-// - src/api/api-inl.h
-// - src/base/hashing.h
-// - src/base/platform/mutex.h
-// - src/base/platform/platform.h
-// - src/base/platform/wrappers.h
-// - src/builtins/profile-data-reader.h
-// - src/codegen/bailout-reason.h
-// - src/codegen/compiler.h
-// - src/codegen/macro-assembler.h
-// - src/codegen/source-position-table.h
-// - src/common/assert-scope.h
-// - src/deoptimizer/deoptimizer.h
-// - src/diagnostics/perf-jit.h
-// - src/execution/isolate.h
-// - src/execution/v8threads.h
-// - src/execution/vm-state-inl.h
-// - src/execution/vm-state.h
-// - src/handles/global-handles.h
-// - src/heap/combined-heap.h
-// - src/heap/heap-inl.h
-// - src/heap/heap-layout-inl.h
-// - src/init/bootstrapper.h
-// - src/interpreter/bytecodes.h
-// - src/interpreter/interpreter.h
-// - src/libsampler/sampler.h
-// - src/logging/code-events.h
-// - src/logging/counters.h
-// - src/logging/log-file.h
-// - src/logging/log-inl.h
-// - src/objects/api-callbacks.h
-// - src/objects/code-kind.h
-// - src/objects/code.h
-// - src/profiler/tick-sample.h
-// - src/snapshot/embedded/embedded-data.h
-// - src/strings/string-stream.h
-// - src/strings/unicode-inl.h
-// - src/tracing/tracing-category-observer.h
-// - src/utils/memcopy.h
-// - src/utils/version.h
-
-// #[cfg(ENABLE_GDB_JIT_INTERFACE)]
-// mod gdb_jit; // Assuming gdb_jit.h is in src/diagnostics
-
-// #[cfg(V8_ENABLE_WEBASSEMBLY)]
-// mod wasm; // Assuming wasm related files are in src/wasm
-
-// #[cfg(V8_ENABLE_ETW_STACK_WALKING)]
-// mod etw_jit_win; // Assuming etw-jit-win.h is in src/diagnostics
-
-use std::sync::{atomic, Mutex, MutexGuard, atomic::AtomicBool, atomic::Ordering, LazyLock, Arc};
-use std::fmt;
-use std::fmt::Write;
-use std::collections::{HashMap, HashSet};
-
-//use crate::api::Local;  // Example, adjust as needed
-//use crate::base::{Mutex, Thread, TimeDelta, OS};  // Example, adjust as needed
-
-// Define macros as const or functions
-const KB: usize = 1024;
-
-// Enum conversions and implementations
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum CodeTag {
-    kLength,
-    kBuiltin,
-    kCallback,
-    kEval,
-    kNativeFunction,
-    kFunction,
-    kHandler,
-    kBytecodeHandler,
-    kRegExp,
-    kNativeScript,
-    kScript,
-    kStub,
+// Copyright 2011 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::codegen::source_position_table::SourcePositionTableIterator;
+use crate::diagnostics::gdb_jit::gdb_jit::{
+    self, JitCodeEvent, JitCodeEventType, JitCodeScript, LinePosInfo, PositionType,
+};
+#[cfg(target_os = "linux")]
+use crate::diagnostics::perf_jit::{LinuxPerfBasicLogger, LinuxPerfJitLogger, PerfFlags};
+use crate::libsampler::sampler::sampler::{RegisterState, SampleStackHandler, Sampler};
+use crate::logging::code_events::{AbstractCode, Address, CodeTag, Event, LogEventListener, Logger, SharedFunctionInfo};
+use crate::logging::log_file::{LogAddress, LogFile, LogSeparator::kSeparator, MessageBuilder};
+use crate::objects::code_kind::CodeKind;
+use crate::profiler::circular_queue::internal::SamplingCircularQueue;
+use crate::profiler::tick_sample::internal::{Isolate, RecordCEntryFrame, TickSample};
+
+/// The flags controlling the V8 log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFlags {
+    /// --logfile: the log file name, "-" for stdout, "+" for a temporary log.
+    pub logfile: String,
+    /// --logfile-per-isolate: prefix the file name with the isolate and pid.
+    pub logfile_per_isolate: bool,
+    /// --log-code: log code events.
+    pub log_code: bool,
+    /// --prof: log statistical profiling ticks. Implies --log-code.
+    pub prof: bool,
+    /// --prof-sampling-interval: the sampling interval in microseconds.
+    pub prof_sampling_interval: u64,
+    /// --gdbjit: register code with GDB through the GDB JIT interface.
+    pub gdbjit: bool,
+    /// --perf-basic-prof, --perf-prof and their options.
+    #[cfg(target_os = "linux")]
+    pub perf: PerfFlags,
 }
 
-impl fmt::Display for CodeTag {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let name = match self {
-            CodeTag::kLength => "kLength",
-            CodeTag::kBuiltin => "kBuiltin",
-            CodeTag::kCallback => "kCallback",
-            CodeTag::kEval => "kEval",
-            CodeTag::kNativeFunction => "kNativeFunction",
-            CodeTag::kFunction => "kFunction",
-            CodeTag::kHandler => "kHandler",
-            CodeTag::kBytecodeHandler => "kBytecodeHandler",
-            CodeTag::kRegExp => "kRegExp",
-            CodeTag::kNativeScript => "kNativeScript",
-            CodeTag::kScript => "kScript",
-            CodeTag::kStub => "kStub",
-        };
-        write!(f, "{}", name)
+impl Default for LogFlags {
+    fn default() -> Self {
+        LogFlags {
+            logfile: "v8.log".to_string(),
+            logfile_per_isolate: true,
+            log_code: false,
+            prof: false,
+            prof_sampling_interval: 1000,
+            gdbjit: false,
+            #[cfg(target_os = "linux")]
+            perf: PerfFlags::default(),
+        }
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum Event {
-    kCodeCreation,
-    kCodeMove,
-    kSharedFuncMove,
-    kCodeDisableOpt,
-    kCodeDeopt,
-    kSnapshotCodeName,
-    kTick,
+impl LogFlags {
+    pub fn is_logging(&self) -> bool {
+        self.log_code || self.prof
+    }
 }
 
-impl fmt::Display for Event {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let name = match self {
-            Event::kCodeCreation => "kCodeCreation",
-            Event::kCodeMove => "kCodeMove",
-            Event::kSharedFuncMove => "kSharedFuncMove",
-            Event::kCodeDisableOpt => "kCodeDisableOpt",
-            Event::kCodeDeopt => "kCodeDeopt",
-            Event::kSnapshotCodeName => "kSnapshotCodeName",
-            Event::kTick => "kTick",
-        };
-        write!(f, "{}", name)
+/// The types of script events.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptEventType {
+    kReserveId,
+    kCreate,
+    kDeserialize,
+    kBackgroundCompile,
+    kStreamingCompileBackground,
+    kStreamingCompileForeground,
+}
+
+impl ScriptEventType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ScriptEventType::kReserveId => "reserve-id",
+            ScriptEventType::kCreate => "create",
+            ScriptEventType::kDeserialize => "deserialize",
+            ScriptEventType::kBackgroundCompile => "background-compile",
+            ScriptEventType::kStreamingCompileBackground => "streaming-compile",
+            ScriptEventType::kStreamingCompileForeground => "streaming-compile-foreground",
+        }
+    }
+}
+
+/// The marker the tick processor shows in front of JS function names.
+fn compute_marker(kind: CodeKind) -> &'static str {
+    match kind {
+        CodeKind::INTERPRETED_FUNCTION => "~",
+        CodeKind::BASELINE => "^",
+        CodeKind::MAGLEV => "+",
+        CodeKind::TURBOFAN_JS => "*",
+        _ => "",
     }
 }
 
-pub trait LogEventListener {
-    fn code_create_event(&mut self, tag: CodeTag, code: *mut AbstractCode, comment: &str);
-    fn code_create_event_name(&mut self, tag: CodeTag, code: *mut AbstractCode, name: &str);
-    fn code_create_event_shared(&mut self, tag: CodeTag, code: *mut AbstractCode, shared: *mut SharedFunctionInfo, script_name: &str);
-    fn code_create_event_shared_line(&mut self, tag: CodeTag, code: *mut AbstractCode, shared: *mut SharedFunctionInfo, script_name: &str, line: i32, column: i32);
-    //#[cfg(V8_ENABLE_WEBASSEMBLY)]
-    //fn code_create_event_wasm(&mut self, tag: CodeTag, code: *const wasm::WasmCode, name: wasm::WasmName, source_url: &str, code_offset: i32, script_id: i32);
-    fn regexp_code_create_event(&mut self, code: *mut AbstractCode, source: &str, flags: i32);
-    fn code_move_event(&mut self, from: *mut InstructionStream, to: *mut InstructionStream);
-    fn bytecode_move_event(&mut self, from: *mut BytecodeArray, to: *mut BytecodeArray);
-    fn code_disable_opt_event(&mut self, code: *mut AbstractCode, shared: *mut SharedFunctionInfo);
+/// Returns the file name of the log, which is prefixed with the isolate and
+/// the process id for --logfile-per-isolate.
+pub fn prepare_log_file_name(isolate: *const Isolate, flags: &LogFlags) -> String {
+    if !flags.logfile_per_isolate
+        || LogFile::is_logging_to_console(&flags.logfile)
+        || LogFile::is_logging_to_temporary_file(&flags.logfile)
+    {
+        return flags.logfile.clone();
+    }
+    format!("isolate-{:p}-{}-{}", isolate, std::process::id(), flags.logfile)
 }
 
-// Logger related structs
-struct NameBuffer {
-    utf8_pos_: usize,
-    utf8_buffer_: [u8; 4096], // Assuming 4096 is the correct size
+/// Writes the code events of an isolate and, for --prof, the ticks of the
+/// statistical profiler to the log file, in the format read by the tick
+/// processor.
+pub struct V8FileLogger {
+    log: Arc<LogFile>,
+    timer: Instant,
+    is_logging: AtomicBool,
+    log_code: bool,
+    profiler: Mutex<Option<Profiler>>,
+    /// The isolate's code event dispatcher.
+    logger: Arc<Logger>,
+    /// The listeners added to `logger` by set_up, including this logger.
+    listeners: Mutex<Vec<Arc<dyn LogEventListener>>>,
 }
 
-impl NameBuffer {
-    fn new() -> Self {
-        NameBuffer {
-            utf8_pos_: 0,
-            utf8_buffer_: [0; 4096],
+impl V8FileLogger {
+    /// Opens the log, adds it and the listeners selected by `flags` to the
+    /// isolate's `logger` and, for --prof, starts sampling the calling
+    /// thread, which must be the thread running `isolate`.
+    pub fn set_up(isolate: *mut Isolate, logger: Arc<Logger>, flags: &LogFlags) -> io::Result<Arc<V8FileLogger>> {
+        let log = Arc::new(LogFile::new(&prepare_log_file_name(isolate, flags))?);
+        // Open the perf files before any listener is added, so that failing
+        // to open them leaves the isolate's logger untouched.
+        #[allow(unused_mut)]
+        let mut perf_loggers: Vec<Arc<dyn LogEventListener>> = Vec::new();
+        #[cfg(target_os = "linux")]
+        {
+            if flags.perf.perf_basic_prof {
+                perf_loggers.push(Arc::new(LinuxPerfBasicLogger::new(&flags.perf)?));
+            }
+            if flags.perf.perf_prof {
+                perf_loggers.push(Arc::new(LinuxPerfJitLogger::new(&flags.perf)?));
+            }
+        }
+        let file_logger = Arc::new(V8FileLogger {
+            log,
+            timer: Instant::now(),
+            is_logging: AtomicBool::new(flags.is_logging()),
+            log_code: flags.log_code || flags.prof,
+            profiler: Mutex::new(None),
+            logger,
+            listeners: Mutex::new(Vec::new()),
+        });
+        if flags.is_logging() {
+            file_logger.add_log_event_listener(file_logger.clone());
+        }
+        if flags.gdbjit {
+            file_logger.add_log_event_listener(Arc::new(JitLogger::new(gdb_jit::event_handler)));
+        }
+        for perf_logger in perf_loggers {
+            file_logger.add_log_event_listener(perf_logger);
+        }
+        if flags.prof {
+            file_logger.log_shared_library_addresses();
+            let interval = Duration::from_micros(flags.prof_sampling_interval.max(1));
+            *file_logger.profiler.lock().unwrap() = Some(Profiler::engage(isolate, file_logger.clone(), interval));
+        }
+        Ok(file_logger)
+    }
+
+    fn add_log_event_listener(&self, listener: Arc<dyn LogEventListener>) {
+        assert!(self.logger.add_listener(listener.clone()));
+        self.listeners.lock().unwrap().push(listener);
+    }
+
+    /// Stops the profiler, removes the listeners added by set_up and closes
+    /// the log. Returns the contents of a temporary log.
+    pub fn tear_down(&self) -> Option<Vec<u8>> {
+        self.stop_profiler();
+        for listener in std::mem::take(&mut *self.listeners.lock().unwrap()) {
+            self.logger.remove_listener(&listener);
         }
+        self.is_logging.store(false, Ordering::Relaxed);
+        self.log.close()
     }
 
-    fn reset(&mut self) {
-        self.utf8_pos_ = 0;
-    }
-
-    fn init(&mut self, tag: CodeTag) {
-        self.reset();
-        self.append_bytes(format!("{}", tag).as_bytes());
-        self.append_byte(b':');
-    }
-
-    //fn append_name(&mut self, name: Tagged<Name>) { // Requires Tagged<Name> definition
-    //    if name.is_string() {
-    //        self.append_string(name.cast::<String>());
-    //    } else {
-    //        let symbol = name.cast::<Symbol>();
-    //        self.append_bytes(b"symbol(");
-    //        if !symbol.description().is_undefined() {
-    //            self.append_bytes(b"\"");
-    //            self.append_string(symbol.description().cast::<String>());
-    //            self.append_bytes(b"\" ");
-    //        }
-    //        self.append_bytes(b"hash ");
-    //        self.append_hex(symbol.hash());
-    //        self.append_byte(b')');
-    //    }
-    //}
-
-    //fn append_string(&mut self, str_: Tagged<String>) { // Requires Tagged<String> definition
-    //    if str_.is_null() {
-    //        return;
-    //    }
-    //    let length = 0;
-    //    let c_str = str_.to_c_string(&length);
-    //    self.append_bytes(c_str.as_bytes(), length);
-    //}
-
-    fn append_bytes(&mut self, bytes: &[u8]) {
-        let size = std::cmp::min(bytes.len(), self.utf8_buffer_.len() - self.utf8_pos_);
-        self.utf8_buffer_[self.utf8_pos_..self.utf8_pos_ + size].copy_from_slice(&bytes[..size]);
-        self.utf8_pos_ += size;
-    }
-
-    fn append_byte(&mut self, c: u8) {
-        if self.utf8_pos_ >= self.utf8_buffer_.len() {
+    pub fn is_logging(&self) -> bool {
+        self.is_logging.load(Ordering::Relaxed)
+    }
+
+    pub fn file_name(&self) -> &str {
+        self.log.file_name()
+    }
+
+    /// Stops ticks from being logged; code events are still logged.
+    pub fn stop_profiler(&self) {
+        let profiler = self.profiler.lock().unwrap().take();
+        if let Some(profiler) = profiler {
+            profiler.disengage();
+        }
+    }
+
+    /// Microseconds since the logger was set up.
+    fn time(&self) -> u64 {
+        self.timer.elapsed().as_micros() as u64
+    }
+
+    fn new_message_builder(&self) -> Option<MessageBuilder<'_>> {
+        if !self.is_logging() {
+            return None;
+        }
+        self.log.new_message_builder()
+    }
+
+    fn code_create_header(&self, tag: CodeTag, code: &AbstractCode) -> Option<MessageBuilder<'_>> {
+        if !self.log_code {
+            return None;
+        }
+        let mut msg = self.new_message_builder()?;
+        msg.append(Event::kCodeCreation.name())
+            .append(kSeparator)
+            .append(tag.name())
+            .append(kSeparator)
+            .append(code.kind as i32)
+            .append(kSeparator)
+            .append(self.time())
+            .append(kSeparator)
+            .append(LogAddress(code.instruction_start))
+            .append(kSeparator)
+            .append(code.instruction_size)
+            .append(kSeparator);
+        Some(msg)
+    }
+
+    fn callback_event_internal(&self, prefix: &str, name: &str, entry_point: Address) {
+        if !self.log_code {
             return;
         }
-        self.utf8_buffer_[self.utf8_pos_] = c;
-        self.utf8_pos_ += 1;
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append(Event::kCodeCreation.name())
+            .append(kSeparator)
+            .append(CodeTag::kCallback.name())
+            .append(kSeparator)
+            .append(-2)
+            .append(kSeparator)
+            .append(self.time())
+            .append(kSeparator)
+            .append(LogAddress(entry_point))
+            .append(kSeparator)
+            .append(1)
+            .append(kSeparator)
+            .append(prefix)
+            .append(name);
+        msg.write_to_log_file();
     }
 
-    fn append_int(&mut self, n: i32) {
-        if self.utf8_pos_ >= self.utf8_buffer_.len() {
+    fn move_event_internal(&self, event: Event, from: Address, to: Address) {
+        if !self.log_code {
             return;
         }
-        let mut buffer = itoa::Buffer::new();
-        let s = buffer.format(n);
-        self.append_bytes(s.as_bytes());
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append(event.name()).append(kSeparator).append(LogAddress(from)).append(kSeparator).append(LogAddress(to));
+        msg.write_to_log_file();
+    }
+
+    pub fn shared_library_event(&self, library_path: &str, start: Address, end: Address, aslr_slide: isize) {
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append("shared-library")
+            .append(kSeparator)
+            .append(library_path)
+            .append(kSeparator)
+            .append(LogAddress(start))
+            .append(kSeparator)
+            .append(LogAddress(end))
+            .append(kSeparator)
+            .append(aslr_slide as i64);
+        msg.write_to_log_file();
+    }
+
+    /// Logs the executable mappings of the process, so that the tick
+    /// processor can attribute ticks outside of V8 code to a library.
+    pub fn log_shared_library_addresses(&self) {
+        for library in shared_library_addresses() {
+            self.shared_library_event(&library.path, library.start, library.end, 0);
+        }
     }
 
-    fn append_hex(&mut self, n: u32) {
-        if self.utf8_pos_ >= self.utf8_buffer_.len() {
+    pub fn script_event(&self, event_type: ScriptEventType, script_id: i32) {
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append("script")
+            .append(kSeparator)
+            .append(event_type.name())
+            .append(kSeparator)
+            .append(script_id)
+            .append(kSeparator)
+            .append(self.time());
+        msg.write_to_log_file();
+    }
+
+    /// Logs the name and source of a script, so that code-creation events
+    /// can be mapped back to the source.
+    pub fn script_details(&self, script_id: i32, name: &str, line_offset: i32, column_offset: i32, source: Option<&str>) {
+        if let Some(mut msg) = self.new_message_builder() {
+            msg.append("script-details")
+                .append(kSeparator)
+                .append(script_id)
+                .append(kSeparator)
+                .append(name)
+                .append(kSeparator)
+                .append(line_offset)
+                .append(kSeparator)
+                .append(column_offset)
+                .append(kSeparator);
+            msg.write_to_log_file();
+        }
+        let Some(source) = source else { return };
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append("script-source")
+            .append(kSeparator)
+            .append(script_id)
+            .append(kSeparator)
+            .append(name)
+            .append(kSeparator)
+            .append(source);
+        msg.write_to_log_file();
+    }
+
+    /// Logs one sample of the profiler:
+    /// `tick,pc,time,is_external_callback,tos_or_external_callback,vm_state,[overflow,]frames...`
+    pub fn tick_event(&self, sample: &TickSample, overflow: bool) {
+        let Some(mut msg) = self.new_message_builder() else { return };
+        let time = sample.timestamp.saturating_duration_since(self.timer).as_micros() as u64;
+        msg.append(Event::kTick.name())
+            .append(kSeparator)
+            .append(LogAddress(sample.pc as Address))
+            .append(kSeparator)
+            .append(time)
+            .append(kSeparator);
+        // SAFETY: `has_external_callback` selects the initialized field.
+        unsafe {
+            if sample.has_external_callback {
+                msg.append(1)
+                    .append(kSeparator)
+                    .append(LogAddress(sample.tos_or_external_callback_entry.external_callback_entry as Address));
+            } else {
+                msg.append(0).append(kSeparator).append(LogAddress(sample.tos_or_external_callback_entry.tos as Address));
+            }
+        }
+        msg.append(kSeparator).append(sample.state as i32);
+        if overflow {
+            msg.append(kSeparator).append("overflow");
+        }
+        for frame in &sample.stack[..sample.frames_count as usize] {
+            msg.append(kSeparator).append(LogAddress(*frame as Address));
+        }
+        msg.write_to_log_file();
+    }
+}
+
+impl LogEventListener for V8FileLogger {
+    fn code_create_event(&self, tag: CodeTag, code: &AbstractCode, name: &str) {
+        let Some(mut msg) = self.code_create_header(tag, code) else { return };
+        msg.append(name);
+        msg.write_to_log_file();
+    }
+
+    fn code_create_event_shared(
+        &self,
+        tag: CodeTag,
+        code: &AbstractCode,
+        shared: &SharedFunctionInfo,
+        script_name: &str,
+        line: i32,
+        column: i32,
+    ) {
+        let Some(mut msg) = self.code_create_header(tag, code) else { return };
+        msg.append(shared.debug_name.as_str())
+            .append(' ')
+            .append(script_name)
+            .append(':')
+            .append(line)
+            .append(':')
+            .append(column)
+            .append(kSeparator)
+            .append(LogAddress(shared.address))
+            .append(kSeparator)
+            .append(compute_marker(code.kind));
+        msg.write_to_log_file();
+    }
+
+    fn code_create_event_wasm(&self, code: &AbstractCode, name: &str, _source_url: &str, code_offset: i32, script_id: i32) {
+        let Some(mut msg) = self.code_create_header(CodeTag::kFunction, code) else { return };
+        // The script id and function offset group the code of one function
+        // across tiers, as the SharedFunctionInfo does for JS.
+        msg.append(name)
+            .append(kSeparator)
+            .append(script_id)
+            .append('-')
+            .append(code_offset)
+            .append(kSeparator)
+            .append(compute_marker(code.kind));
+        msg.write_to_log_file();
+    }
+
+    fn callback_event(&self, name: &str, entry_point: Address) {
+        self.callback_event_internal("", name, entry_point);
+    }
+
+    fn getter_callback_event(&self, name: &str, entry_point: Address) {
+        self.callback_event_internal("get ", name, entry_point);
+    }
+
+    fn setter_callback_event(&self, name: &str, entry_point: Address) {
+        self.callback_event_internal("set ", name, entry_point);
+    }
+
+    fn regexp_code_create_event(&self, code: &AbstractCode, source: &str, flags: &str) {
+        let Some(mut msg) = self.code_create_header(CodeTag::kRegExp, code) else { return };
+        msg.append('/').append(source).append('/').append(flags);
+        msg.write_to_log_file();
+    }
+
+    fn code_move_event(&self, from: Address, to: Address) {
+        self.move_event_internal(Event::kCodeMove, from, to);
+    }
+
+    fn bytecode_move_event(&self, from: Address, to: Address) {
+        self.move_event_internal(Event::kCodeMove, from, to);
+    }
+
+    fn code_delete_event(&self, instruction_start: Address) {
+        if !self.log_code {
             return;
         }
-        let mut buffer = itoa::Buffer::new();
-        let s = buffer.format(n);
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append(Event::kCodeDelete.name()).append(kSeparator).append(LogAddress(instruction_start));
+        msg.write_to_log_file();
+    }
 
-        let hex_string = format!("{:x}", n);
-        self.append_bytes(hex_string.as_bytes());
+    fn shared_function_info_move_event(&self, from: Address, to: Address) {
+        self.move_event_internal(Event::kSharedFuncMove, from, to);
     }
 
-    fn get(&self) -> &[u8] {
-        &self.utf8_buffer_[..self.utf8_pos_]
+    fn code_disable_opt_event(&self, _code: &AbstractCode, shared: &SharedFunctionInfo, reason: &str) {
+        if !self.log_code {
+            return;
+        }
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append(Event::kCodeDisableOpt.name())
+            .append(kSeparator)
+            .append(shared.debug_name.as_str())
+            .append(kSeparator)
+            .append(reason);
+        msg.write_to_log_file();
+    }
+
+    fn code_deopt_event(&self, code: &AbstractCode, kind: &str, pc: Address, fp_to_sp_delta: i32) {
+        if !self.log_code {
+            return;
+        }
+        let Some(mut msg) = self.new_message_builder() else { return };
+        msg.append(Event::kCodeDeopt.name())
+            .append(kSeparator)
+            .append(self.time())
+            .append(kSeparator)
+            .append(code.instruction_size)
+            .append(kSeparator)
+            .append(LogAddress(code.instruction_start))
+            .append(kSeparator)
+            .append(fp_to_sp_delta)
+            .append(kSeparator)
+            .append(kind)
+            .append(kSeparator)
+            .append(LogAddress(pc));
+        msg.write_to_log_file();
     }
 
-    fn size(&self) -> usize {
-        self.utf8_pos_
+    fn is_listening_to_code_events(&self) -> bool {
+        self.log_code && self.is_logging()
     }
 }
 
-struct CodeEventLogger {
-    isolate_: *mut Isolate,
-    name_buffer_: Box<NameBuffer>,
+/// A listener that only needs a name for each code object, like the perf
+/// and gdb JIT interfaces. Names look like `JS:~foo a.js:1:1`.
+pub trait CodeEventLogger: Send + Sync {
+    fn log_recorded_buffer(&self, code: &AbstractCode, shared: Option<&SharedFunctionInfo>, name: &str);
+
+    fn code_move_event(&self, from: Address, to: Address);
+
+    fn code_delete_event(&self, _instruction_start: Address) {}
+
+    fn allows_code_compaction(&self) -> bool {
+        true
+    }
 }
 
-impl CodeEventLogger {
-    fn new(isolate: *mut Isolate) -> Self {
-        CodeEventLogger {
-            isolate_: isolate,
-            name_buffer_: Box::new(NameBuffer::new()),
+impl<T: CodeEventLogger> LogEventListener for T {
+    fn code_create_event(&self, tag: CodeTag, code: &AbstractCode, name: &str) {
+        self.log_recorded_buffer(code, None, &format!("{}:{}", tag.name(), name));
+    }
+
+    fn code_create_event_shared(
+        &self,
+        tag: CodeTag,
+        code: &AbstractCode,
+        shared: &SharedFunctionInfo,
+        script_name: &str,
+        line: i32,
+        column: i32,
+    ) {
+        let name = format!(
+            "{}:{}{} {}:{}:{}",
+            tag.name(),
+            compute_marker(code.kind),
+            shared.debug_name,
+            script_name,
+            line,
+            column
+        );
+        self.log_recorded_buffer(code, Some(shared), &name);
+    }
+
+    fn code_create_event_wasm(&self, code: &AbstractCode, name: &str, _source_url: &str, _code_offset: i32, _script_id: i32) {
+        self.log_recorded_buffer(code, None, &format!("{}:{}", CodeTag::kFunction.name(), name));
+    }
+
+    fn regexp_code_create_event(&self, code: &AbstractCode, source: &str, _flags: &str) {
+        self.log_recorded_buffer(code, None, &format!("{}:{}", CodeTag::kRegExp.name(), source));
+    }
+
+    fn code_move_event(&self, from: Address, to: Address) {
+        CodeEventLogger::code_move_event(self, from, to);
+    }
+
+    fn code_delete_event(&self, instruction_start: Address) {
+        CodeEventLogger::code_delete_event(self, instruction_start);
+    }
+
+    fn is_listening_to_code_events(&self) -> bool {
+        true
+    }
+
+    fn allows_code_compaction(&self) -> bool {
+        CodeEventLogger::allows_code_compaction(self)
+    }
+}
+
+/// A JitCodeEventHandler as passed to Isolate::SetJitCodeEventHandler.
+pub type JitCodeEventHandler = fn(&mut JitCodeEvent<'_>);
+
+/// Forwards code events to a JitCodeEventHandler, e.g. the GDB JIT interface
+/// for --gdbjit. Code with a source position table is preceded by its line
+/// information, as the code generator reports it while assembling.
+pub struct JitLogger {
+    code_event_handler: JitCodeEventHandler,
+}
+
+impl JitLogger {
+    pub fn new(code_event_handler: JitCodeEventHandler) -> Self {
+        JitLogger { code_event_handler }
+    }
+
+    fn log_line_positions(&self, code: &AbstractCode, table: &[u8]) {
+        let mut event = JitCodeEvent::new(JitCodeEventType::CODE_START_LINE_INFO_RECORDING, 0, 0);
+        (self.code_event_handler)(&mut event);
+        let mut user_data = event.user_data.take();
+        let mut iterator = SourcePositionTableIterator::with_defaults(table);
+        while !iterator.done() {
+            let position = iterator.source_position();
+            if position.is_javascript() {
+                let mut event = JitCodeEvent::new(JitCodeEventType::CODE_ADD_LINE_POS_INFO, 0, 0);
+                event.line_info = Some(LinePosInfo {
+                    offset: iterator.code_offset() as usize,
+                    pos: position.script_offset() as usize,
+                    position_type: if iterator.is_statement() {
+                        PositionType::STATEMENT_POSITION
+                    } else {
+                        PositionType::POSITION
+                    },
+                });
+                event.user_data = user_data;
+                (self.code_event_handler)(&mut event);
+                user_data = event.user_data.take();
+            }
+            iterator.advance();
+        }
+        let mut event = JitCodeEvent::new(JitCodeEventType::CODE_END_LINE_INFO_RECORDING, code.instruction_start, 0);
+        event.user_data = user_data;
+        (self.code_event_handler)(&mut event);
+    }
+}
+
+impl CodeEventLogger for JitLogger {
+    fn log_recorded_buffer(&self, code: &AbstractCode, shared: Option<&SharedFunctionInfo>, name: &str) {
+        let script = shared.and_then(|shared| shared.script.as_ref()).map(|script| {
+            JitCodeScript::with_line_ends(
+                script.name.as_deref().unwrap_or(""),
+                script.line_ends.iter().map(|&end| end as usize).collect(),
+            )
+        });
+        if let (Some(_), Some(table)) = (&script, &code.source_position_table) {
+            self.log_line_positions(code, table);
         }
+        let mut event = JitCodeEvent::new(JitCodeEventType::CODE_ADDED, code.instruction_start, code.instruction_size);
+        event.name = name;
+        event.script = script.as_ref();
+        (self.code_event_handler)(&mut event);
+    }
+
+    fn code_move_event(&self, from: Address, to: Address) {
+        let mut event = JitCodeEvent::new(JitCodeEventType::CODE_MOVED, from, 0);
+        event.new_code_start = to;
+        (self.code_event_handler)(&mut event);
+    }
+
+    fn code_delete_event(&self, instruction_start: Address) {
+        let mut event = JitCodeEvent::new(JitCodeEventType::CODE_REMOVED, instruction_start, 0);
+        (self.code_event_handler)(&mut event);
     }
+}
+
+/// An executable mapping of the process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedLibraryAddress {
+    pub path: String,
+    pub start: Address,
+    pub end: Address,
+}
 
-    fn code_create_event(&mut self, tag: CodeTag, code: *mut AbstractCode, comment: &str) {
-        // TODO: Implement is_listening_to_code_events and LogRecordedBuffer
-        //if !self.is_listening_to_code_events() {
-        //    return;
-        //}
-        self.name_buffer_.init(tag);
-        self.name_buffer_.append_bytes(comment.as_bytes());
-        //DisallowGarbageCollection no_gc;
-        //self.log_recorded_buffer(code, None, self.name_buffer_.get(), self.name_buffer_.size());
-    }
-
-    //fn code_create_event_name(&mut self, tag: CodeTag, code: *mut AbstractCode, name: Tagged<Name>) {
-    //    if !self.is_listening_to_code_events() {
-    //        return;
-    //    }
-    //    self.name_buffer_.init(tag);
-    //    self.name_buffer_.append_name(name);
-    //    //DisallowGarbageCollection no_gc;
-    //    //self.log_recorded_buffer(code, None, self.name_buffer_.get(), self.name_buffer_.size());
-    //}
-
-    //fn code_create_event_shared(&mut self, tag: CodeTag, code: *mut AbstractCode, shared: Tagged<SharedFunctionInfo>, script_name: Tagged<Name>) {
-    //    if !self.is_listening_to_code_events() {
-    //        return;
-    //    }
-    //    self.name_buffer_.init(tag);
-    //    //self.name_buffer_.append_bytes(compute_marker(shared, code).as_bytes());
-    //    self.name_buffer_.append_byte(b' ');
-    //    //self.name_buffer_.append_name(script_name);
-    //    //DisallowGarbageCollection no_gc;
-    //    //self.log_recorded_buffer(code, Some(shared), self.name_buffer_.get(), self.name_buffer_.size());
-    //}
-
-    //fn code_create_event_shared_line(&mut self, tag: CodeTag, code: *mut AbstractCode, shared: Tagged<SharedFunctionInfo>, script_name: Tagged<Name>, line: i32, column: i32) {
-    //    if !self.is_listening_to_code_events() {
-    //        return;
-    //    }
-    //    self.name_buffer_.init(tag);
-    //    //self.name_buffer_.append_bytes(compute_marker(shared, code).as_bytes());
-    //    //self.name_buffer_.append_bytes(shared.debug_name_cstr().as_bytes());
-    //    self.name_buffer_.append_byte(b' ');
-    //    //if script_name.is_string() {
-    //    //    self.name_buffer_.append_string(script_name.cast::<String>());
-    //    //} else {
-    //    //    self.name_buffer_.append_bytes(b"symbol(hash ");
-    //    //    self.name_buffer_.append_hex(script_name.cast::<Name>().hash());
-    //    //    self.name_buffer_.append_byte(b')');
-    //    //}
-    //    self.name_buffer_.append_byte(b':');
-    //    self.name_buffer_.append_int(line);
-    //    self.name_buffer_.append_byte(b':');
-    //    self.name_buffer_.append_int(column);
-    //    //DisallowGarbageCollection no_gc;
-    //    //self.log_recorded_buffer(code, Some(shared), self.name_buffer_.get(), self.name_buffer_.size());
-    //}
-
-    //#[cfg(V8_ENABLE_WEBASSEMBLY)]
-    //fn code_create_event_wasm(&mut self, tag: CodeTag, code: *const wasm::WasmCode, name: wasm::WasmName, source_url: &str, code_offset: i32, script_id: i32) {
-    //    if !self.is_listening_to_code_events() {
-    //        return;
-    //    }
-    //    self.name_buffer_.init(tag);
-    //    //DCHECK(!name.empty());
-    //    //self.name_buffer_.append_bytes(name.begin(), name.length());
-    //    self.name_buffer_.append_byte(b'-');
-    //    //if code.is_anonymous() {
-    //    //    self.name_buffer_.append_bytes(b"<anonymous>");
-    //    //} else {
-    //    //    self.name_buffer_.append_int(code.index());
-    //    //}
-    //    self.name_buffer_.append_byte(b'-');
-    //    //self.name_buffer_.append_bytes(ExecutionTierToString(code.tier()));
-    //    //DisallowGarbageCollection no_gc;
-    //    //self.log_recorded_buffer(code, self.name_buffer_.get(), self.name_buffer_.size());
-    //}
-
-    fn regexp_code_create_event(&mut self, code: *mut AbstractCode, source: &str, flags: i32) {
-        // TODO: Implement is_listening_to_code_events and LogRecordedBuffer
-        //if !self.is_listening_to_code_events() {
-        //    return;
-        //}
-        self.name_buffer_.reset();
-        self.name_buffer_.append_bytes(b"RegExp.>");
-        self.name_buffer_.append_bytes(b" src: '");
-        self.name_buffer_.append_bytes(source.as_bytes());
-        self.name_buffer_.append_bytes(b"' flags: '");
-        //DirectHandle<String> flags_str = JSRegExp::StringFromFlags(isolate_, JSRegExp::AsJSRegExpFlags(flags));
-        //name_buffer_->AppendString(*flags_str);
-        self.name_buffer_.append_bytes(b"''");
-        //DisallowGarbageCollection no_gc;
-        //LogRecordedBuffer(*code, MaybeDirectHandle<SharedFunctionInfo>(),
-        //            name_buffer_->get(), name_buffer_->size());
-    }
-
-    // Implement other methods
+/// Reads the executable file mappings from /proc/self/maps. `start` is the
+/// address the file is mapped at, i.e. the mapping adjusted by its offset.
+pub fn shared_library_addresses() -> Vec<SharedLibraryAddress> {
+    let Ok(maps) = fs::read_to_string("/proc/self/maps") else { return Vec::new() };
+    let mut libraries = Vec::new();
+    for line in maps.lines() {
+        let mut fields = line.split_whitespace();
+        let (Some(range), Some(permissions), Some(offset)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some(path) = fields.nth(2) else { continue };
+        if !permissions.contains('x') || !path.starts_with('/') {
+            continue;
+        }
+        let Some((start, end)) = range.split_once('-') else { continue };
+        let (Ok(start), Ok(end), Ok(offset)) = (
+            Address::from_str_radix(start, 16),
+            Address::from_str_radix(end, 16),
+            Address::from_str_radix(offset, 16),
+        ) else {
+            continue;
+        };
+        libraries.push(SharedLibraryAddress { path: path.to_string(), start: start - offset, end });
+    }
+    libraries
 }
 
-// TODO: Implement LinuxPerfBasicLogger, ExternalLogEventListener, LowLevelLogger, JitLogger, Profiler, Ticker, V8FileLogger, ExistingCodeLogger
-// Missing type definitions: Isolate, AbstractCode, SharedFunctionInfo, Name, String, Symbol, InstructionStream, BytecodeArray, wasm::WasmCode, Tagged
-
-// Dummy definitions to allow compilation
-struct Isolate;
-struct AbstractCode;
-struct SharedFunctionInfo;
-struct Name;
-struct String;
-struct Symbol;
-struct InstructionStream;
-struct BytecodeArray;
+const K_TICK_SAMPLE_BUFFER_LENGTH: usize = 128;
+
+/// A tick in the sample buffer. The addresses of the sample are only ever
+/// logged, never dereferenced, so it may move to the profiler thread.
+#[derive(Default)]
+struct TickRecord(TickSample);
+
+unsafe impl Send for TickRecord {}
+
+type TickBuffer = SamplingCircularQueue<TickRecord, K_TICK_SAMPLE_BUFFER_LENGTH>;
+
+/// Fills the sample buffer from the signal handler of the sampler.
+struct Ticker {
+    isolate: *mut Isolate,
+    buffer: Arc<TickBuffer>,
+    overflow: Arc<AtomicBool>,
+    interval: Duration,
+}
+
+unsafe impl Send for Ticker {}
+unsafe impl Sync for Ticker {}
+
+impl SampleStackHandler for Ticker {
+    fn sample_stack(&self, state: &RegisterState) {
+        // SAFETY: the isolate outlives the profiler, which stops sampling
+        // before the logger is torn down.
+        let Some(isolate) = (unsafe { self.isolate.as_mut() }) else { return };
+        // SAFETY: the sampler is the single producer of the buffer, so the
+        // record is not accessed by anyone else until `finish_enqueue`.
+        let Some(mut record) = (unsafe { self.buffer.start_enqueue() }) else {
+            self.overflow.store(true, Ordering::Relaxed);
+            return;
+        };
+        // SAFETY: as above.
+        let record = unsafe { record.as_mut() };
+        record.0.init(isolate, state, RecordCEntryFrame::KIncludeCEntryFrame, true, false, self.interval, None);
+        // SAFETY: as above.
+        unsafe { self.buffer.finish_enqueue() };
+    }
+}
+
+/// The statistical profiler of --prof. A sampling thread interrupts the
+/// isolate thread every interval; the samples are written to the log by the
+/// profiler thread.
+struct Profiler {
+    sampler: Arc<Sampler>,
+    running: Arc<AtomicBool>,
+    sampling_thread: Option<JoinHandle<()>>,
+    profiler_thread: Option<JoinHandle<()>>,
+}
+
+impl Profiler {
+    fn engage(isolate: *mut Isolate, logger: Arc<V8FileLogger>, interval: Duration) -> Profiler {
+        let buffer = Arc::new(TickBuffer::new());
+        let overflow = Arc::new(AtomicBool::new(false));
+        let running = Arc::new(AtomicBool::new(true));
+        if let Some(mut msg) = logger.new_message_builder() {
+            msg.append("profiler").append(kSeparator).append("begin").append(kSeparator).append(interval.as_micros() as u64);
+            msg.write_to_log_file();
+        }
+
+        let ticker = Ticker { isolate, buffer: buffer.clone(), overflow: overflow.clone(), interval };
+        let sampler = Arc::new(Sampler::with_handler(isolate as *mut _, Box::new(ticker)));
+        sampler.start();
+
+        let sampling_thread = {
+            let sampler = sampler.clone();
+            let running = running.clone();
+            thread::Builder::new()
+                .name("SamplingThread".to_string())
+                .spawn(move || {
+                    while running.load(Ordering::Relaxed) {
+                        sampler.do_sample();
+                        thread::sleep(interval);
+                    }
+                })
+                .expect("failed to start the sampling thread")
+        };
+
+        let profiler_thread = {
+            let running = running.clone();
+            thread::Builder::new()
+                .name("v8:Profiler".to_string())
+                .spawn(move || loop {
+                    // Read the flag before draining, so the last samples are
+                    // logged once the sampling thread has stopped.
+                    let still_running = running.load(Ordering::Acquire);
+                    // SAFETY: this thread is the single consumer of the buffer.
+                    while let Some(record) = unsafe { buffer.peek() } {
+                        logger.tick_event(&record.0, overflow.swap(false, Ordering::Relaxed));
+                        unsafe { buffer.remove() };
+                    }
+                    if !still_running {
+                        if let Some(mut msg) = logger.new_message_builder() {
+                            msg.append("profiler").append(kSeparator).append("end");
+                            msg.write_to_log_file();
+                        }
+                        break;
+                    }
+                    thread::sleep(interval);
+                })
+                .expect("failed to start the profiler thread")
+        };
+
+        Profiler { sampler, running, sampling_thread: Some(sampling_thread), profiler_thread: Some(profiler_thread) }
+    }
+
+    fn disengage(mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.sampling_thread.take() {
+            let _ = thread.join();
+        }
+        self.sampler.stop();
+        if let Some(thread) = self.profiler_thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiler::tick_sample::internal::StateTag;
+
+    fn temporary_log() -> Arc<V8FileLogger> {
+        let flags = LogFlags { logfile: LogFile::K_LOG_TO_TEMPORARY_FILE.to_string(), log_code: true, ..LogFlags::default() };
+        V8FileLogger::set_up(std::ptr::null_mut(), Arc::new(Logger::new()), &flags).unwrap()
+    }
+
+    #[test]
+    fn logs_code_events_and_ticks() {
+        let logger = temporary_log();
+        let code = AbstractCode::new(CodeKind::INTERPRETED_FUNCTION, 0x1000, 0x40);
+        let shared = SharedFunctionInfo { address: 0x2000, debug_name: "foo".to_string(), script: None };
+        logger.code_create_event_shared(CodeTag::kFunction, &code, &shared, "a.js", 3, 10);
+        logger.regexp_code_create_event(&AbstractCode { kind: CodeKind::REGEXP, ..code.clone() }, "a,b", "g");
+        logger.code_move_event(0x1000, 0x3000);
+        logger.code_delete_event(0x3000);
+
+        let mut sample = TickSample::new();
+        sample.pc = 0x1010 as *mut _;
+        sample.state = StateTag::JS;
+        sample.stack[0] = 0x5000 as *mut _;
+        sample.frames_count = 1;
+        logger.tick_event(&sample, false);
+
+        let contents = String::from_utf8(logger.tear_down().unwrap()).unwrap();
+        let lines: Vec<&str> = contents.lines().skip(2).collect();
+        let kind = CodeKind::INTERPRETED_FUNCTION as i32;
+        let fields: Vec<&str> = lines[0].split(',').collect();
+        assert_eq!(&fields[..3], &["code-creation", "JS", kind.to_string().as_str()]);
+        assert_eq!(&fields[4..], &["0x1000", "64", "foo a.js:3:10", "0x2000", "~"]);
+        assert!(lines[1].ends_with(",/a\\x2Cb/g"), "{}", lines[1]);
+        assert_eq!(lines[2], "code-move,0x1000,0x3000");
+        assert_eq!(lines[3], "code-delete,0x3000");
+        let tick: Vec<&str> = lines[4].split(',').collect();
+        assert_eq!(tick[0], "tick");
+        assert_eq!(tick[1], "0x1010");
+        assert_eq!(&tick[3..], &["0", "0x0", "0", "0x5000"]);
+        assert!(!logger.is_logging());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn perf_basic_prof_writes_the_perf_map() {
+        let directory = std::env::temp_dir().join(format!("v8-perf-basic-prof-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let logger = Arc::new(Logger::new());
+        let perf = PerfFlags {
+            perf_basic_prof: true,
+            perf_basic_prof_path: directory.to_str().unwrap().to_string(),
+            ..PerfFlags::default()
+        };
+        let flags = LogFlags { logfile: LogFile::K_LOG_TO_TEMPORARY_FILE.to_string(), perf, ..LogFlags::default() };
+        let file_logger = V8FileLogger::set_up(std::ptr::null_mut(), logger.clone(), &flags).unwrap();
+        assert!(logger.is_listening_to_code_events());
+        logger.code_create_event(CodeTag::kBuiltin, &AbstractCode::new(CodeKind::BUILTIN, 0x1000, 0x40), "Abort");
+        file_logger.tear_down();
+
+        let map = fs::read_to_string(directory.join(format!("perf-{}.map", std::process::id()))).unwrap();
+        assert!(map.lines().any(|line| line.starts_with("1000 40 ") && line.ends_with("Abort")), "{map}");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    type RecordedJitEvent = (JitCodeEventType, Address, String, Option<LinePosInfo>);
+
+    static JIT_EVENTS: Mutex<Vec<RecordedJitEvent>> = Mutex::new(Vec::new());
+
+    fn record_jit_event(event: &mut JitCodeEvent<'_>) {
+        let name = event.script.map_or(event.name.to_string(), |script| format!("{} {}", event.name, script.name()));
+        JIT_EVENTS.lock().unwrap().push((event.type_, event.code_start, name, event.line_info));
+        if event.type_ == JitCodeEventType::CODE_START_LINE_INFO_RECORDING {
+            event.user_data = Some(Box::default());
+        }
+    }
+
+    #[test]
+    fn jit_logger_forwards_code_events_with_line_info() {
+        use crate::codegen::source_position_table::{RecordingMode, SourcePosition, SourcePositionTableBuilder};
+        use crate::logging::code_events::Script;
+
+        let logger = Arc::new(Logger::new());
+        let flags = LogFlags { logfile: LogFile::K_LOG_TO_TEMPORARY_FILE.to_string(), ..LogFlags::default() };
+        let file_logger = V8FileLogger::set_up(std::ptr::null_mut(), logger.clone(), &flags).unwrap();
+        assert!(!logger.is_listening_to_code_events());
+        file_logger.tear_down();
+        let flags = LogFlags { gdbjit: true, ..flags };
+        let file_logger = V8FileLogger::set_up(std::ptr::null_mut(), logger.clone(), &flags).unwrap();
+        assert!(logger.is_listening_to_code_events());
+        file_logger.tear_down();
+        assert!(!logger.is_listening_to_code_events());
+
+        logger.add_listener(Arc::new(JitLogger::new(record_jit_event)));
+        let mut builder = SourcePositionTableBuilder::new(RecordingMode::RECORD_SOURCE_POSITIONS);
+        builder.add_position(0, SourcePosition::new(0, -1), true);
+        builder.add_position(8, SourcePosition::new(12, -1), false);
+        let code = AbstractCode {
+            source_position_table: Some(builder.to_source_position_table_vector().into()),
+            ..AbstractCode::new(CodeKind::INTERPRETED_FUNCTION, 0x1000, 0x40)
+        };
+        let script = Arc::new(Script::new(1, Some("a.js".to_string()), "function foo() {}"));
+        let shared = SharedFunctionInfo { address: 0x2000, debug_name: "foo".to_string(), script: Some(script) };
+        logger.code_create_event_shared(CodeTag::kFunction, &code, &shared, "a.js", 1, 1);
+        logger.code_move_event(0x1000, 0x3000);
+        logger.code_delete_event(0x3000);
+
+        let events = std::mem::take(&mut *JIT_EVENTS.lock().unwrap());
+        let types: Vec<_> = events.iter().map(|(type_, start, _, _)| (*type_, *start)).collect();
+        assert_eq!(
+            types,
+            [
+                (JitCodeEventType::CODE_START_LINE_INFO_RECORDING, 0),
+                (JitCodeEventType::CODE_ADD_LINE_POS_INFO, 0),
+                (JitCodeEventType::CODE_ADD_LINE_POS_INFO, 0),
+                (JitCodeEventType::CODE_END_LINE_INFO_RECORDING, 0x1000),
+                (JitCodeEventType::CODE_ADDED, 0x1000),
+                (JitCodeEventType::CODE_MOVED, 0x1000),
+                (JitCodeEventType::CODE_REMOVED, 0x3000),
+            ]
+        );
+        assert_eq!(
+            events[2].3,
+            Some(LinePosInfo { offset: 8, pos: 12, position_type: PositionType::POSITION })
+        );
+        assert_eq!(events[4].2, "JS:~foo a.js:1:1 a.js");
+    }
+}
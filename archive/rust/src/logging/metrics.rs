@@ -2,233 +2,300 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::any::Any;
 use std::collections::VecDeque;
-use std::marker::PhantomData;
-use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::include::v8_metrics::{
+    self, ContextId, GarbageCollectionFullCycle, GarbageCollectionFullMainThreadBatchedIncrementalMark,
+    GarbageCollectionFullMainThreadBatchedIncrementalSweep, GarbageCollectionFullMainThreadIncrementalMark,
+    GarbageCollectionFullMainThreadIncrementalSweep, GarbageCollectionYoungCycle, WasmModuleCompiled,
+    WasmModuleDecoded, WasmModuleInstantiated, WasmModulesPerIsolate,
+};
+use crate::include::v8_platform::{Task, TaskRunner};
+use crate::include::v8_source_location::SourceLocation;
+
+/// An event the embedder receives through one of the
+/// `add_main_thread_event_*` methods of its recorder.
+pub trait MainThreadEvent: Send + 'static {
+    fn add_to(&self, recorder: &dyn v8_metrics::Recorder, id: ContextId);
+}
 
-pub mod v8 {
-    pub mod metrics {
-        pub type Recorder = super::metrics::Recorder;
-        pub type ContextId = u32; // Example type, replace with the actual type
-    }
-    pub trait TaskRunner: Send + Sync {
-        fn post_task(&self, task: Box<dyn FnOnce()>);
-    }
+/// An event the embedder receives through one of the
+/// `add_thread_safe_event_*` methods of its recorder.
+pub trait ThreadSafeEvent {
+    fn add_to(&self, recorder: &dyn v8_metrics::Recorder);
 }
 
-pub mod base {
-    pub mod platform {
-        use std::time::{Instant, SystemTime, UNIX_EPOCH};
-        pub use std::time::Duration;
+macro_rules! main_thread_events {
+    ($($event:ty => $method:ident,)*) => {
+        $(
+            impl MainThreadEvent for $event {
+                fn add_to(&self, recorder: &dyn v8_metrics::Recorder, id: ContextId) {
+                    recorder.$method(self, id);
+                }
+            }
+        )*
+    };
+}
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-        pub struct TimeTicks {
-            instant: Option<Instant>,
-        }
+main_thread_events! {
+    GarbageCollectionFullCycle => add_main_thread_event_garbage_collection_full_cycle,
+    GarbageCollectionFullMainThreadIncrementalMark =>
+        add_main_thread_event_garbage_collection_full_main_thread_incremental_mark,
+    GarbageCollectionFullMainThreadBatchedIncrementalMark =>
+        add_main_thread_event_garbage_collection_full_main_thread_batched_incremental_mark,
+    GarbageCollectionFullMainThreadIncrementalSweep =>
+        add_main_thread_event_garbage_collection_full_main_thread_incremental_sweep,
+    GarbageCollectionFullMainThreadBatchedIncrementalSweep =>
+        add_main_thread_event_garbage_collection_full_main_thread_batched_incremental_sweep,
+    GarbageCollectionYoungCycle => add_main_thread_event_garbage_collection_young_cycle,
+    WasmModuleDecoded => add_main_thread_event_wasm_module_decoded,
+    WasmModuleCompiled => add_main_thread_event_wasm_module_compiled,
+    WasmModuleInstantiated => add_main_thread_event_wasm_module_instantiated,
+}
 
-        impl TimeTicks {
-            pub fn now() -> Self {
-                TimeTicks { instant: Some(Instant::now()) }
-            }
+impl ThreadSafeEvent for WasmModulesPerIsolate {
+    fn add_to(&self, recorder: &dyn v8_metrics::Recorder) {
+        recorder.add_thread_safe_event_wasm_modules_per_isolate(self);
+    }
+}
 
-            pub fn min() -> Self {
-                TimeTicks { instant: None }
-            }
+type DelayedEvent = Box<dyn FnOnce(&dyn v8_metrics::Recorder) + Send>;
+
+/// The metrics recorder of an isolate. Forwards events to the embedder's
+/// `v8::metrics::Recorder`, if one is set, and drops them otherwise.
+///
+/// Delayed main thread events are queued and delivered in batches by a task
+/// on the isolate's foreground task runner, so that reporting stays off the
+/// critical path of the main thread. The first event of a batch posts the
+/// delivery task with a delay of `batch_delay`.
+pub struct Recorder {
+    embedder_recorder: OnceLock<(Arc<dyn v8_metrics::Recorder>, Arc<dyn TaskRunner>)>,
+    delayed_events: Mutex<VecDeque<DelayedEvent>>,
+    /// Held while events are handed to the embedder, so that batches are
+    /// delivered in order and never after isolate disposal.
+    delivery_lock: Mutex<()>,
+    disposed: AtomicBool,
+    batch_delay: Duration,
+    last_context_id: AtomicUsize,
+}
 
-            pub fn is_min(&self) -> bool {
-                self.instant.is_none()
-            }
+impl Recorder {
+    pub const K_DEFAULT_BATCH_DELAY: Duration = Duration::from_secs(1);
 
-            pub fn duration_since(&self, earlier: TimeTicks) -> TimeDelta {
-                match (self.instant, earlier.instant) {
-                    (Some(later_instant), Some(earlier_instant)) => {
-                        TimeDelta { duration: later_instant.duration_since(earlier_instant) }
-                    }
-                    _ => TimeDelta { duration: Duration::from_secs(0) },
-                }
-            }
-        }
+    pub fn new() -> Self {
+        Self::with_batch_delay(Self::K_DEFAULT_BATCH_DELAY)
+    }
 
-        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-        pub struct TimeDelta {
-            duration: Duration,
+    pub fn with_batch_delay(batch_delay: Duration) -> Self {
+        Recorder {
+            embedder_recorder: OnceLock::new(),
+            delayed_events: Mutex::new(VecDeque::new()),
+            delivery_lock: Mutex::new(()),
+            disposed: AtomicBool::new(false),
+            batch_delay,
+            last_context_id: AtomicUsize::new(0),
         }
+    }
 
-        impl TimeDelta {
-            pub fn in_microseconds(&self) -> i64 {
-                self.duration.as_micros() as i64
-            }
-        }
+    /// Sets the embedder's recorder, and the foreground task runner of the
+    /// isolate that delivers delayed events. Can only be called once per
+    /// isolate.
+    pub fn set_embedder_recorder(
+        &self,
+        embedder_recorder: Arc<dyn v8_metrics::Recorder>,
+        foreground_task_runner: Arc<dyn TaskRunner>,
+    ) {
+        assert!(
+            self.embedder_recorder.set((embedder_recorder, foreground_task_runner)).is_ok(),
+            "the embedder recorder can only be set once"
+        );
+    }
 
-        pub struct Mutex {
-            inner: std::sync::Mutex<()>,
-        }
+    pub fn has_embedder_recorder(&self) -> bool {
+        self.embedder_recorder.get().is_some() && !self.disposed.load(Ordering::Acquire)
+    }
 
-        impl Mutex {
-            pub fn new() -> Self {
-                Mutex { inner: std::sync::Mutex::new(()) }
-            }
+    /// Returns a new id for a context of the isolate. Ids start at 1 as 0
+    /// denotes the empty id.
+    pub fn new_context_id(&self) -> ContextId {
+        ContextId::from_id(self.last_context_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
 
-            pub fn lock(&self) -> MutexGuard<()> {
-                self.inner.lock().unwrap()
-            }
+    /// Delivers the events that are still queued and tells the embedder
+    /// that the isolate is gone. Later events are dropped.
+    pub fn notify_isolate_disposal(&self) {
+        self.flush_delayed_events();
+        let _delivery = self.delivery_lock.lock().unwrap();
+        if self.disposed.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        if let Some((embedder_recorder, _)) = self.embedder_recorder.get() {
+            embedder_recorder.notify_isolate_disposal();
         }
     }
-}
 
-pub mod init {
-    pub mod v8 {
-        pub fn initialize() {} //Placeholder
+    /// Delivers `event` to the embedder right away, on the calling thread.
+    pub fn add_main_thread_event<T: MainThreadEvent>(&self, event: &T, id: ContextId) {
+        let _delivery = self.delivery_lock.lock().unwrap();
+        if let Some(embedder_recorder) = self.live_embedder_recorder() {
+            event.add_to(embedder_recorder.as_ref(), id);
+        }
     }
-}
 
-pub mod internal {
-    pub mod metrics {
-        use std::any::Any;
-        use std::sync::{Arc, Mutex, MutexGuard};
-        use crate::base::platform;
-        use crate::v8;
-
-        pub struct Recorder {
-            lock: Mutex<()>,
-            foreground_task_runner: Option<Arc<dyn v8::TaskRunner>>,
-            embedder_recorder: Option<Arc<Recorder>>,
-            delayed_events: Mutex<VecDeque<Box<dyn DelayedEventBase>>>,
+    /// Queues `event` for delivery with the next batch.
+    pub fn delay_main_thread_event<T: MainThreadEvent>(self: &Arc<Self>, event: T, id: ContextId) {
+        if !self.has_embedder_recorder() {
+            return;
+        }
+        let was_empty = {
+            let mut delayed_events = self.delayed_events.lock().unwrap();
+            let was_empty = delayed_events.is_empty();
+            delayed_events.push_back(Box::new(move |recorder: &dyn v8_metrics::Recorder| event.add_to(recorder, id)));
+            was_empty
+        };
+        if was_empty {
+            self.schedule_delivery();
         }
+    }
 
-        impl Recorder {
-            pub fn new() -> Self {
-                Recorder {
-                    lock: Mutex::new(()),
-                    foreground_task_runner: None,
-                    embedder_recorder: None,
-                    delayed_events: Mutex::new(VecDeque::new()),
-                }
-            }
+    pub fn add_thread_safe_event<T: ThreadSafeEvent>(&self, event: &T) {
+        if let Some(embedder_recorder) = self.live_embedder_recorder() {
+            event.add_to(embedder_recorder.as_ref());
+        }
+    }
 
-            pub fn set_embedder_recorder(
-                &mut self,
-                isolate: *mut std::ffi::c_void, // Replace with actual isolate type
-                embedder_recorder: Arc<v8::metrics::Recorder>,
-            ) {
-                // TODO: Use isolate to store the embedder recorder.
-                let _ = isolate;
-                self.embedder_recorder = Some(embedder_recorder);
+    /// Delivers all queued events on the calling thread.
+    pub fn flush_delayed_events(&self) {
+        let _delivery = self.delivery_lock.lock().unwrap();
+        let delayed_events = std::mem::take(&mut *self.delayed_events.lock().unwrap());
+        if let Some(embedder_recorder) = self.live_embedder_recorder() {
+            for event in delayed_events {
+                event(embedder_recorder.as_ref());
             }
+        }
+    }
 
-            pub fn has_embedder_recorder(&self) -> bool {
-                self.embedder_recorder.is_some()
-            }
+    fn live_embedder_recorder(&self) -> Option<&Arc<dyn v8_metrics::Recorder>> {
+        if self.disposed.load(Ordering::Acquire) {
+            return None;
+        }
+        self.embedder_recorder.get().map(|(embedder_recorder, _)| embedder_recorder)
+    }
 
-            pub fn notify_isolate_disposal(&self) {
-                // Placeholder implementation
-                //println!("Isolate disposal notification received.");
-            }
+    fn schedule_delivery(self: &Arc<Self>) {
+        let Some((_, foreground_task_runner)) = self.embedder_recorder.get() else {
+            return;
+        };
+        foreground_task_runner.post_delayed_task(
+            Box::new(DeliveryTask { recorder: Arc::clone(self) }),
+            self.batch_delay.as_secs_f64(),
+            SourceLocation::new(),
+        );
+    }
+}
 
-            pub fn add_main_thread_event<T: Any + Send + Sync>(
-                &self,
-                event: &T,
-                id: v8::metrics::ContextId,
-            ) {
-                if let Some(embedder_recorder) = &self.embedder_recorder {
-                    embedder_recorder.add_main_thread_event(event, id);
-                }
-            }
+/// Delivers the batch of delayed events of a recorder.
+struct DeliveryTask {
+    recorder: Arc<Recorder>,
+}
 
-            pub fn delay_main_thread_event<T: Any + Send + Sync>(
-                &self,
-                event: T,
-                id: v8::metrics::ContextId,
-            ) {
-                if self.embedder_recorder.is_none() {
-                    return;
-                }
+impl Task for DeliveryTask {
+    fn run(&mut self) {
+        self.recorder.flush_delayed_events();
+    }
+}
 
-                let delayed_event = DelayedEvent::new(event, id);
-                self.delay(Box::new(delayed_event));
-            }
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            pub fn add_thread_safe_event<T: Any + Send + Sync>(&self, event: &T) {
-                if let Some(embedder_recorder) = &self.embedder_recorder {
-                    embedder_recorder.add_thread_safe_event(event);
-                }
-            }
+/// Measures the wall clock time until it is stopped or dropped and stores
+/// it, in microseconds, in the duration field of an event.
+pub struct TimedScope<'a> {
+    wall_clock_duration_in_us: &'a mut i64,
+    start_time: Option<Instant>,
+}
 
-            fn delay(&self, event: Box<dyn DelayedEventBase>) {
-                let mut delayed_events = self.delayed_events.lock().unwrap();
-                delayed_events.push_back(event);
-            }
+impl<'a> TimedScope<'a> {
+    pub fn new(wall_clock_duration_in_us: &'a mut i64) -> Self {
+        TimedScope {
+            wall_clock_duration_in_us,
+            start_time: Some(Instant::now()),
         }
+    }
 
-        trait DelayedEventBase: Send {
-            fn run(&self, recorder: &Arc<Recorder>);
+    pub fn stop(&mut self) {
+        if let Some(start_time) = self.start_time.take() {
+            *self.wall_clock_duration_in_us = start_time.elapsed().as_micros() as i64;
         }
+    }
+}
 
-        struct DelayedEvent<T: Any + Send + Sync> {
-            event: T,
-            id: v8::metrics::ContextId,
-        }
+impl Drop for TimedScope<'_> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
 
-        impl<T: Any + Send + Sync> DelayedEvent<T> {
-            fn new(event: T, id: v8::metrics::ContextId) -> Self {
-                DelayedEvent { event, id }
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include::libplatform::libplatform::{IdleTaskSupport, MessageLoopBehavior};
+    use crate::libplatform::default_foreground_task_runner::DefaultForegroundTaskRunner;
+    use std::sync::atomic::AtomicU64;
 
-        impl<T: Any + Send + Sync> DelayedEventBase for DelayedEvent<T> {
-            fn run(&self, recorder: &Arc<Recorder>) {
-                recorder.add_main_thread_event(&self.event, self.id);
-            }
-        }
+    #[derive(Default)]
+    struct TestRecorder {
+        events: Mutex<Vec<usize>>,
+    }
 
-        pub struct TimedScope<'a, T, F>
-            where
-                F: Fn(&platform::TimeDelta) -> i64,
-        {
-            event: &'a mut T,
-            start_time: platform::TimeTicks,
-            precision: F,
-            _phantom: PhantomData<&'a T>,
+    impl v8_metrics::Recorder for TestRecorder {
+        fn add_main_thread_event_wasm_module_decoded(&self, event: &WasmModuleDecoded, context_id: ContextId) {
+            self.events.lock().unwrap().push(event.module_size_in_bytes + context_id.get_id());
         }
 
-        impl<'a, T, F> TimedScope<'a, T, F>
-            where
-                F: Fn(&platform::TimeDelta) -> i64,
-        {
-            pub fn new(event: &'a mut T, precision: F) -> Self {
-                let mut timed_scope = TimedScope {
-                    event,
-                    start_time: platform::TimeTicks::min(),
-                    precision,
-                    _phantom: PhantomData,
-                };
-                timed_scope.start();
-                timed_scope
-            }
-
-            fn start(&mut self) {
-                self.start_time = platform::TimeTicks::now();
-            }
-
-            fn stop(&mut self) {
-                if self.start_time.is_min() {
-                    return;
-                }
-                let duration = platform::TimeTicks::now().duration_since(self.start_time);
-                self.event.wall_clock_duration_in_us = (self.precision)(&duration);
-                self.start_time = platform::TimeTicks::min();
-            }
+        fn notify_isolate_disposal(&self) {
+            self.events.lock().unwrap().push(0);
         }
+    }
 
-        impl<'a, T, F> Drop for TimedScope<'a, T, F>
-            where
-                F: Fn(&platform::TimeDelta) -> i64,
+    #[test]
+    fn delayed_events_are_delivered_in_batches_by_a_foreground_task() {
+        let now_in_ms = Arc::new(AtomicU64::new(0));
+        let time_function = {
+            let now_in_ms = now_in_ms.clone();
+            Arc::new(move || now_in_ms.load(Ordering::Relaxed) as f64 / 1000.0)
+        };
+        let task_runner = Arc::new(DefaultForegroundTaskRunner::new(IdleTaskSupport::kDisabled, time_function));
+        let embedder_recorder = Arc::new(TestRecorder::default());
+        let recorder = Arc::new(Recorder::with_batch_delay(Duration::from_millis(10)));
+        recorder.set_embedder_recorder(embedder_recorder.clone(), task_runner.clone());
+        let context_id = recorder.new_context_id();
+        assert_eq!(context_id.get_id(), 1);
+
+        let mut event = WasmModuleDecoded { module_size_in_bytes: 100, ..Default::default() };
         {
-            fn drop(&mut self) {
-                self.stop();
-            }
+            let _timed_scope = TimedScope::new(&mut event.wall_clock_duration_in_us);
         }
+        assert!(event.wall_clock_duration_in_us >= 0);
+        recorder.delay_main_thread_event(event, context_id);
+        recorder.delay_main_thread_event(WasmModuleDecoded { module_size_in_bytes: 200, ..event }, context_id);
+
+        assert!(task_runner.pop_task_from_queue(MessageLoopBehavior::kDoNotWait).is_none());
+        now_in_ms.store(10, Ordering::Relaxed);
+        let mut task = task_runner.pop_task_from_queue(MessageLoopBehavior::kDoNotWait).unwrap();
+        assert!(task_runner.pop_task_from_queue(MessageLoopBehavior::kDoNotWait).is_none());
+        assert!(embedder_recorder.events.lock().unwrap().is_empty());
+        task.run();
+        assert_eq!(*embedder_recorder.events.lock().unwrap(), [101, 201]);
+
+        recorder.notify_isolate_disposal();
+        recorder.add_main_thread_event(&event, context_id);
+        assert_eq!(*embedder_recorder.events.lock().unwrap(), [101, 201, 0]);
     }
-}
\ No newline at end of file
+}
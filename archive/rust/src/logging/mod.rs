@@ -9,6 +9,7 @@ pub mod local-logger;
 pub mod code-events;
 pub mod log-file;
 pub mod log-inl;
+pub mod tick-processor;
 pub mod counters-scopes;
 pub mod counters-definitions;
 pub mod counters;
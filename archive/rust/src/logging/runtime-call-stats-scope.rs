@@ -2,113 +2,105 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod runtime_call_stats_scope {
-    use std::cell::RefCell;
-    use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
-    use crate::execution::isolate::Isolate;
-    use crate::execution::local_isolate::LocalIsolate;
-    use crate::logging::counters::Counters;
-    use crate::logging::runtime_call_stats::{RuntimeCallCounterId, RuntimeCallStats, Timer};
-    use crate::logging::tracing_flags::TracingFlags;
+use crate::logging::runtime_call_stats::{CounterMode, RuntimeCallCounterId, RuntimeCallStats, RuntimeCallStatsTables};
+use crate::logging::tracing_flags::TracingFlags;
 
-    // This cfg attribute conditionally compiles the code inside the module
-    #[cfg(feature = "runtime_call_stats")]
-    pub mod enabled {
-        use super::*;
+/// Implemented by the owners of runtime call stats tables, e.g. the isolate,
+/// so that `rcs_scope!` can be given the isolate.
+pub trait HasRuntimeCallStats {
+    fn runtime_call_stats(&self) -> Option<&RuntimeCallStatsTables>;
+}
 
-        // Define a macro to mimic the C++ RCS_SCOPE macro.  Since we can't
-        // directly use the line number in Rust macros to generate unique
-        // identifiers, we'll omit that part.  This might lead to shadowing
-        // if multiple RCS_SCOPE macros are used in the same scope, but it's
-        // the best we can do without procedural macros.
-        #[macro_export]
-        macro_rules! rcs_scope {
-            ($isolate:expr, $counter_id:expr) => {
-                let _rcs_timer_scope =
-                    v8::internal::runtime_call_stats_scope::enabled::RuntimeCallTimerScope::new(
-                        $isolate,
-                        $counter_id,
-                    );
-            };
-            ($isolate:expr, $counter_id:expr, $mode:expr) => {
-                let _rcs_timer_scope =
-                    v8::internal::runtime_call_stats_scope::enabled::RuntimeCallTimerScope::new_with_mode(
-                        $isolate,
-                        $counter_id,
-                        $mode,
-                    );
-            };
-        }
+impl HasRuntimeCallStats for RuntimeCallStatsTables {
+    fn runtime_call_stats(&self) -> Option<&RuntimeCallStatsTables> {
+        Some(self)
+    }
+}
+
+/// Times the rest of the enclosing block with `counter_id` in the table of
+/// the current thread, e.g.
+/// `rcs_scope!(isolate, RuntimeCallCounterId::kParseProgram)`.
+#[macro_export]
+macro_rules! rcs_scope {
+    ($isolate:expr, $counter_id:expr) => {
+        let _rcs_timer_scope =
+            $crate::logging::runtime_call_stats_scope::RuntimeCallTimerScope::new($isolate, $counter_id);
+    };
+    ($isolate:expr, $counter_id:expr, $mode:expr) => {
+        let _rcs_timer_scope =
+            $crate::logging::runtime_call_stats_scope::RuntimeCallTimerScope::new_with_mode($isolate, $counter_id, $mode);
+    };
+}
+
+/// Enters a counter of the current thread's runtime call stats table and
+/// leaves it when dropped. Does nothing unless runtime call stats are
+/// enabled.
+pub struct RuntimeCallTimerScope {
+    stats: Option<Arc<Mutex<RuntimeCallStats>>>,
+}
+
+impl RuntimeCallTimerScope {
+    pub fn new<I: HasRuntimeCallStats + ?Sized>(isolate: &I, counter_id: RuntimeCallCounterId) -> Self {
+        Self::new_with_mode(isolate, counter_id, CounterMode::kExact)
+    }
 
-        pub struct RuntimeCallTimerScope<'a> {
-            stats_: Option<Rc<RefCell<&'a mut RuntimeCallStats>>>, // Need to figure out lifetimes, using this for now
-            timer_: Timer,
+    pub fn new_with_mode<I: HasRuntimeCallStats + ?Sized>(
+        isolate: &I,
+        counter_id: RuntimeCallCounterId,
+        mode: CounterMode,
+    ) -> Self {
+        if !TracingFlags::is_runtime_stats_enabled() {
+            return RuntimeCallTimerScope { stats: None };
         }
-
-        impl<'a> RuntimeCallTimerScope<'a> {
-            pub fn new(isolate: &'a mut Isolate, counter_id: RuntimeCallCounterId) -> Self {
-                if !TracingFlags::is_runtime_stats_enabled() {
-                    return RuntimeCallTimerScope { stats_: None, timer_: Timer::default() };
-                }
-                let stats = isolate.counters().runtime_call_stats();
-                let mut timer_ = Timer::default();
-
-                stats.borrow_mut().enter(&mut timer_, counter_id);
-
-                RuntimeCallTimerScope {
-                    stats_: Some(stats.clone()),
-                    timer_: timer_,
-                }
-            }
-
-            pub fn new_with_mode(
-                isolate: &'a mut LocalIsolate,
-                counter_id: RuntimeCallCounterId,
-                mode: RuntimeCallStats::CounterMode,
-            ) -> Self {
-                if !TracingFlags::is_runtime_stats_enabled() {
-                    return RuntimeCallTimerScope { stats_: None, timer_: Timer::default() };
-                }
-
-                let stats = isolate.runtime_call_stats().expect("Runtime call stats should not be null");
-                let mut timer_ = Timer::default();
-
-                let counter_id = if mode == RuntimeCallStats::CounterMode::kThreadSpecific {
-                    stats.borrow().counter_id_for_thread(counter_id)
-                } else {
-                    counter_id
-                };
-
-                assert!(stats.borrow().is_counter_appropriate_for_thread(counter_id));
-                stats.borrow_mut().enter(&mut timer_, counter_id);
-
-                RuntimeCallTimerScope {
-                    stats_: Some(stats.clone()),
-                    timer_: timer_,
-                }
-            }
+        let Some(tables) = isolate.runtime_call_stats() else {
+            return RuntimeCallTimerScope { stats: None };
+        };
+        let stats = tables.current_thread_table();
+        {
+            let mut table = stats.lock().unwrap();
+            let counter_id = match mode {
+                CounterMode::kThreadSpecific => table.counter_id_for_thread(counter_id),
+                CounterMode::kExact => counter_id,
+            };
+            assert!(table.is_counter_appropriate_for_thread(counter_id));
+            table.enter(counter_id);
         }
+        RuntimeCallTimerScope { stats: Some(stats) }
+    }
+}
 
-        impl<'a> Drop for RuntimeCallTimerScope<'a> {
-            fn drop(&mut self) {
-                // The exit logic should go here, but it is not fully defined in the C++ code
-            }
+impl Drop for RuntimeCallTimerScope {
+    fn drop(&mut self) {
+        if let Some(stats) = &self.stats {
+            stats.lock().unwrap().leave();
         }
     }
-
-    #[cfg(not(feature = "runtime_call_stats"))]
-    pub mod disabled {
-        #[macro_export]
-        macro_rules! rcs_scope {
-            ($isolate:expr, $counter_id:expr) => {};
-            ($isolate:expr, $counter_id:expr, $mode:expr) => {};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging::tracing_flags::RUNTIME_STATS;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn scopes_nest_until_the_end_of_the_block() {
+        RUNTIME_STATS.store(1, Ordering::Relaxed);
+        let tables = RuntimeCallStatsTables::new();
+        let stats = tables.current_thread_table();
+        {
+            crate::rcs_scope!(&tables, RuntimeCallCounterId::kTestCounter1);
+            {
+                crate::rcs_scope!(&tables, RuntimeCallCounterId::kCompileScript, CounterMode::kThreadSpecific);
+                assert_eq!(stats.lock().unwrap().current_counter(), Some(RuntimeCallCounterId::kCompileScript));
+            }
+            assert_eq!(stats.lock().unwrap().current_counter(), Some(RuntimeCallCounterId::kTestCounter1));
         }
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.current_counter(), None);
+        assert_eq!(stats.get_counter(RuntimeCallCounterId::kTestCounter1).count(), 1);
+        assert_eq!(stats.get_counter(RuntimeCallCounterId::kCompileScript).count(), 1);
     }
-
-    #[cfg(feature = "runtime_call_stats")]
-    pub use enabled::*;
-
-    #[cfg(not(feature = "runtime_call_stats"))]
-    pub use disabled::*;
-}
\ No newline at end of file
+}
@@ -1,912 +1,719 @@
-#![allow(non_snake_case)]
-#![allow(dead_code)]
-#![allow(unused_variables)]
-#![allow(clippy::too_many_arguments)]
-#![allow(clippy::new_without_default)]
-
-use std::cmp::Ordering;
-use std::sync::{Mutex, MutexGuard};
+// Copyright 2021 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::cell::RefCell;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
 use std::time::{Duration, Instant};
-use std::thread;
-use std::cell::Cell;
-use std::fmt;
-use std::fmt::Write;
-use std::collections::HashMap;
 
-//use base::{TimeTicks, ThreadTicks}; // Assuming these are defined elsewhere or need equivalent Rust types
-
-// Mock implementations for base types
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct TimeTicks(u64);
-
-impl TimeTicks {
-    fn now() -> Self {
-        TimeTicks(Instant::now().elapsed().as_micros() as u64)
-    }
-
-    fn from_internal_value(value: u64) -> Self {
-        TimeTicks(value)
-    }
-
-    fn to_internal_value(&self) -> u64 {
-        self.0
-    }
-}
-
-#[derive(Debug, Copy, Clone)]
-struct ThreadTicks(u64);
-
-impl ThreadTicks {
-    fn now() -> Self {
-        // Mock implementation for now
-        ThreadTicks(Instant::now().elapsed().as_micros() as u64)
-    }
-
-    fn wait_until_initialized() {}
-
-    fn is_supported() -> bool {
-        true
-    }
-
-    fn to_internal_value(&self) -> u64 {
-        self.0
-    }
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct TimeDelta(u64);
-
-impl TimeDelta {
-    fn from_microseconds(microseconds: u64) -> Self {
-        TimeDelta(microseconds)
-    }
-    fn in_microseconds(&self) -> u64 {
-        self.0
-    }
-
-    fn as_secs_f64(&self) -> f64 {
-        self.0 as f64 / 1_000_000.0
-    }
-}
-
-// Mock flags module
-mod flags {
-    pub static mut rcs_cpu_time: bool = false;
-}
-
-// Mock tracing module
-mod tracing {
-    pub struct TracedValue {}
-
-    impl TracedValue {
-        pub fn create() -> Box<TracedValue> {
-            Box::new(TracedValue {})
-        }
-        pub fn begin_array(&mut self, _name: &str) {}
-        pub fn append_double(&mut self, _value: f64) {}
-        pub fn end_array(&mut self) {}
-    }
-
-    // Mock TracingCategoryObserver
-    pub mod tracing_category_observer {
-        pub const ENABLED_BY_TRACING: u32 = 1;
-    }
-}
-
-// Mock utils module
-mod utils {
-    use std::io;
-
-    pub struct StdoutStream;
-
-    impl StdoutStream {
-        pub fn new() -> Self {
-            StdoutStream {}
-        }
-    }
-
-    impl io::Write for StdoutStream {
-        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-            print!("{}", String::from_utf8_lossy(buf));
-            Ok(buf.len())
-        }
-
-        fn flush(&mut self) -> io::Result<()> {
-            Ok(())
-        }
-    }
-}
-
-// Mock thread module
-mod base {
-    use std::thread;
-    use std::cell::RefCell;
-    use std::collections::HashMap;
-    use std::sync::{Mutex, MutexGuard};
-
-    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-    pub struct ThreadId(u64);
-
-    impl ThreadId {
-        pub fn current() -> Self {
-            // Mock implementation for now
-            // Note: this is for demonstration only, in a real scenario, we'd need a robust way
-            // of generating unique thread IDs.
-            use std::sync::atomic::{AtomicU64, Ordering};
-            static NEXT_ID: AtomicU64 = AtomicU64::new(1);
-            ThreadId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
-        }
-
-        pub fn is_valid(&self) -> bool {
-            self.0 != 0
-        }
-    }
-
-    pub struct Thread {
-        // Mock implementation of Thread
-    }
-
-    impl Thread {
-        pub type LocalStorageKey = u32; // Mock key type
-
-        pub fn create_thread_local_key() -> LocalStorageKey {
-            // Mock implementation
-            use std::sync::atomic::{AtomicU32, Ordering};
-            static NEXT_KEY: AtomicU32 = AtomicU32::new(1);
-            NEXT_KEY.fetch_add(1, Ordering::Relaxed)
-        }
-
-        pub fn delete_thread_local_key(_key: LocalStorageKey) {
-            // Mock implementation
-        }
-
-        pub fn get_thread_local<T: 'static>(key: LocalStorageKey) -> Option<*mut T> {
-            THREAD_LOCAL_STORAGE.lock().unwrap().get(&key).map(|&ptr| ptr as *mut T)
-        }
-
-        pub fn set_thread_local<T: 'static>(key: LocalStorageKey, value: *mut T) {
-            THREAD_LOCAL_STORAGE.lock().unwrap().insert(key, value as *mut ());
-        }
-    }
-
-    lazy_static::lazy_static! {
-        static ref THREAD_LOCAL_STORAGE: Mutex<HashMap<LocalStorageKey, *mut ()>> = Mutex::new(HashMap::new());
-    }
-
-    pub struct MutexGuard<'a, T> {
-        guard: std::sync::MutexGuard<'a, T>,
-    }
-
-    impl<'a, T> MutexGuard<'a, T> {
-        pub fn new(guard: std::sync::MutexGuard<'a, T>) -> Self {
-            MutexGuard { guard }
-        }
-    }
-
-    // Implement Deref and DerefMut to allow access to the underlying MutexGuard
-    impl<'a, T> std::ops::Deref for MutexGuard<'a, T> {
-        type Target = T;
-
-        fn deref(&self) -> &Self::Target {
-            &self.guard
-        }
-    }
-
-    impl<'a, T> std::ops::DerefMut for MutexGuard<'a, T> {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.guard
-        }
-    }
-
-    impl<'a, T> From<std::sync::MutexGuard<'a, T>> for MutexGuard<'a, T> {
-        fn from(guard: std::sync::MutexGuard<'a, T>) -> Self {
-            MutexGuard { guard }
-        }
-    }
-}
-
-macro_rules! FOR_EACH_GC_COUNTER {
-    ($callback:ident) => {
-        $callback!(kGCTime);
-        $callback!(kGCHeapSize);
-    };
-}
-
-macro_rules! FOR_EACH_MANUAL_COUNTER {
-    ($callback:ident) => {
-        $callback!(kCompile);
-        $callback!(kParse);
-    };
-}
-
-macro_rules! FOR_EACH_INTRINSIC {
-    ($callback:ident) => {
-        $callback!(kMathSin, 1, 1);
-        $callback!(kMathCos, 1, 1);
-    };
-}
-
-macro_rules! BUILTIN_LIST_C {
-    ($callback:ident) => {
-        $callback!(kArrayPush, 1);
-        $callback!(kArrayPop, 0);
-    };
-}
-
-macro_rules! FOR_EACH_API_COUNTER {
-    ($callback:ident) => {
-        $callback!(kAPIWrite);
-        $callback!(kAPIRead);
-    };
-}
-
-macro_rules! FOR_EACH_HANDLER_COUNTER {
-    ($callback:ident) => {
-        $callback!(kHandlerA);
-        $callback!(kHandlerB);
-    };
-}
-
-macro_rules! FOR_EACH_THREAD_SPECIFIC_COUNTER {
-    ($callback:ident) => {
-        $callback!(kIsolate);
-        $callback!(kIsolateBackground);
-    };
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-#[repr(C)]
-pub enum RuntimeCallCounterId {
-    kGCTime,
-    kGCHeapSize,
-    kCompile,
-    kParse,
-    kMathSin,
-    kMathCos,
-    kArrayPush,
-    kArrayPop,
-    kAPIWrite,
-    kAPIRead,
-    kHandlerA,
-    kHandlerB,
-    kIsolate,
-    kIsolateBackground,
-    kNumberOfCounters,
-}
-
-impl RuntimeCallCounterId {
-    fn as_usize(self) -> usize {
-        self as usize
-    }
-}
-
-impl fmt::Display for RuntimeCallCounterId {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-struct RuntimeCallTimer {
-    start_time: TimeTicks,
-    start_cpu_time: TimeTicks,
-    counter: *mut RuntimeCallCounter,
-    parent: *mut RuntimeCallTimer,
-    elapsed_time: TimeDelta,
-    elapsed_cpu_time: TimeDelta,
-    is_running: bool,
-}
-
-impl RuntimeCallTimer {
-    fn new() -> Self {
-        RuntimeCallTimer {
-            start_time: TimeTicks(0),
-            start_cpu_time: TimeTicks(0),
-            counter: std::ptr::null_mut(),
-            parent: std::ptr::null_mut(),
-            elapsed_time: TimeDelta(0),
-            elapsed_cpu_time: TimeDelta(0),
-            is_running: false,
-        }
-    }
-
-    fn now() -> TimeTicks {
-        TimeTicks::now()
-    }
-
-    fn now_cpu_time() -> TimeTicks {
-        let ticks = ThreadTicks::now();
-        TimeTicks::from_internal_value(ticks.to_internal_value())
-    }
-
-    fn start(&mut self, counter: *mut RuntimeCallCounter, parent: *mut RuntimeCallTimer) {
-        assert!(!self.is_running);
-        self.counter = counter;
-        self.parent = parent;
-        self.start_time = Self::now();
-        self.start_cpu_time = Self::now_cpu_time();
-        self.is_running = true;
-    }
-
-    fn pause(&mut self, now: TimeTicks) {
-        if !self.is_running {
-            return;
-        }
-        let elapsed = TimeDelta::from_microseconds(now.0 - self.start_time.0);
-        self.elapsed_time = TimeDelta::from_microseconds(self.elapsed_time.0 + elapsed.0);
-
-        let now_cpu = Self::now_cpu_time();
-        let elapsed_cpu = TimeDelta::from_microseconds(now_cpu.0 - self.start_cpu_time.0);
-        self.elapsed_cpu_time = TimeDelta::from_microseconds(self.elapsed_cpu_time.0 + elapsed_cpu.0);
-
-        self.is_running = false;
-    }
-
-    fn stop(&mut self) -> *mut RuntimeCallTimer {
-        let now = Self::now();
-        self.pause(now);
-        self.commit_time_to_counter();
-        self.is_running = false;
-        self.parent
-    }
-
-    fn resume(&mut self, now: TimeTicks) {
-        if self.is_running {
-            return;
-        }
-        self.start_time = now;
-        self.start_cpu_time = Self::now_cpu_time();
-        self.is_running = true;
-    }
-
-    fn commit_time_to_counter(&mut self) {
-        if self.counter.is_null() {
-            return;
-        }
-        unsafe {
-            (*self.counter).add_time(self.elapsed_time);
-        }
-        self.elapsed_time = TimeDelta(0);
-        self.elapsed_cpu_time = TimeDelta(0);
-    }
-
-    fn parent(&self) -> *mut RuntimeCallTimer {
-        self.parent
-    }
-
-    fn counter(&self) -> *mut RuntimeCallCounter {
-        self.counter
-    }
+use crate::libplatform::tracing::trace_object::ConvertableToTraceFormat;
+use crate::tracing::traced_value::TracedValue;
+
+/// Declares RuntimeCallCounterId from the counter lists below. Counters with
+/// thread-specific variants are listed as (main thread, background thread)
+/// pairs; the background variant always directly follows its main variant.
+macro_rules! define_runtime_call_counter_ids {
+    (
+        gc: [$($gc:ident),* $(,)?],
+        manual: [$($manual:ident),* $(,)?],
+        runtime: [$($runtime:ident),* $(,)?],
+        builtin: [$($builtin:ident),* $(,)?],
+        api: [$($api:ident),* $(,)?],
+        handler: [$($handler:ident),* $(,)?],
+        thread_specific: [$(($main:ident, $background:ident)),* $(,)?] $(,)?
+    ) => {
+        /// The counters of the runtime call stats. Their names are the
+        /// variant names without the leading "k".
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum RuntimeCallCounterId {
+            $($gc,)*
+            $($manual,)*
+            $($runtime,)*
+            $($builtin,)*
+            $($api,)*
+            $($handler,)*
+            $($main, $background,)*
+            kNumberOfCounters,
+        }
+
+        impl RuntimeCallCounterId {
+            /// All counters, in table order.
+            pub const ALL: &'static [RuntimeCallCounterId] = &[
+                $(RuntimeCallCounterId::$gc,)*
+                $(RuntimeCallCounterId::$manual,)*
+                $(RuntimeCallCounterId::$runtime,)*
+                $(RuntimeCallCounterId::$builtin,)*
+                $(RuntimeCallCounterId::$api,)*
+                $(RuntimeCallCounterId::$handler,)*
+                $(RuntimeCallCounterId::$main, RuntimeCallCounterId::$background,)*
+            ];
+
+            pub fn name(self) -> &'static str {
+                let name = match self {
+                    $(RuntimeCallCounterId::$gc => stringify!($gc),)*
+                    $(RuntimeCallCounterId::$manual => stringify!($manual),)*
+                    $(RuntimeCallCounterId::$runtime => stringify!($runtime),)*
+                    $(RuntimeCallCounterId::$builtin => stringify!($builtin),)*
+                    $(RuntimeCallCounterId::$api => stringify!($api),)*
+                    $(RuntimeCallCounterId::$handler => stringify!($handler),)*
+                    $(RuntimeCallCounterId::$main => stringify!($main),)*
+                    $(RuntimeCallCounterId::$background => stringify!($background),)*
+                    RuntimeCallCounterId::kNumberOfCounters => "kNumberOfCounters",
+                };
+                &name[1..]
+            }
 
-    fn set_counter(&mut self, counter: *mut RuntimeCallCounter) {
-        self.counter = counter;
-    }
+            fn background_variant(self) -> Option<RuntimeCallCounterId> {
+                match self {
+                    $(RuntimeCallCounterId::$main => Some(RuntimeCallCounterId::$background),)*
+                    _ => None,
+                }
+            }
 
-    fn snapshot(&mut self) {
-        let now = Self::now();
-        self.pause(now);
-
-        let mut timer = self;
-        while !timer.parent.is_null() {
-            timer.commit_time_to_counter();
-            unsafe {
-                timer = &mut *timer.parent;
+            fn is_background_variant(self) -> bool {
+                matches!(self, $(RuntimeCallCounterId::$background)|*)
             }
         }
-        timer.commit_time_to_counter();
-
-        self.resume(now);
-    }
+    };
 }
 
-#[derive(Debug)]
-struct RuntimeCallCounter {
+define_runtime_call_counter_ids! {
+    gc: [
+        kGC_HEAP_EPILOGUE,
+        kGC_HEAP_EXTERNAL_EPILOGUE,
+        kGC_HEAP_EXTERNAL_PROLOGUE,
+        kGC_HEAP_PROLOGUE,
+        kGC_MARK_COMPACTOR,
+        kGC_MC_CLEAR,
+        kGC_MC_EVACUATE,
+        kGC_MC_FINISH,
+        kGC_MC_INCREMENTAL,
+        kGC_MC_INCREMENTAL_FINALIZE,
+        kGC_MC_INCREMENTAL_START,
+        kGC_MC_MARK,
+        kGC_MC_MARK_ROOTS,
+        kGC_MC_PROLOGUE,
+        kGC_MC_SWEEP,
+        kGC_MINOR_MARK_SWEEPER,
+        kGC_SCAVENGER,
+        kGC_SCAVENGER_SCAVENGE,
+        kGC_SCAVENGER_SCAVENGE_ROOTS,
+        kGC_SCAVENGER_SWEEP_ARRAY_BUFFERS,
+        kGC_MC_BACKGROUND_EVACUATE_COPY,
+        kGC_MC_BACKGROUND_MARKING,
+        kGC_MC_BACKGROUND_SWEEPING,
+        kGC_SCAVENGER_BACKGROUND_SCAVENGE_PARALLEL,
+        kGC_Custom_AllAvailableGarbage,
+        kGC_Custom_IncrementalMarkingObserver,
+        kGC_Custom_SlowAllocateRaw,
+    ],
+    manual: [
+        kAccessorGetterCallback,
+        kAccessorSetterCallback,
+        kArrayLengthGetter,
+        kArrayLengthSetter,
+        kBoundFunctionLengthGetter,
+        kBoundFunctionNameGetter,
+        kDeoptimizeCode,
+        kFunctionCallback,
+        kFunctionLengthGetter,
+        kFunctionPrototypeGetter,
+        kFunctionPrototypeSetter,
+        kInvokeApiFunction,
+        kJS_Execution,
+        kMap_SetPrototype,
+        kMap_TransitionToAccessorProperty,
+        kNamedGetterCallback,
+        kObjectVerify,
+        kOptimizeConcurrentFinalize,
+        kOptimizeFinalizePipelineJob,
+        kOptimizeNonConcurrent,
+        kParseEval,
+        kParseFunction,
+        kPrototypeMap_TransitionToAccessorProperty,
+        kPrototypeObject_DeleteProperty,
+        kStringLengthGetter,
+        kTestCounter1,
+        kTestCounter2,
+        kTestCounter3,
+        kUpdateProtector,
+    ],
+    runtime: [
+        kRuntime_AllocateInYoungGeneration,
+        kRuntime_CompileBaseline,
+        kRuntime_CompileLazy,
+        kRuntime_GetProperty,
+        kRuntime_NewClosure,
+        kRuntime_NewObject,
+        kRuntime_NotifyDeoptimized,
+        kRuntime_SetKeyedProperty,
+        kRuntime_StackGuard,
+        kRuntime_StringAdd,
+        kRuntime_ThrowTypeError,
+    ],
+    builtin: [
+        kBuiltin_ArrayConcat,
+        kBuiltin_ArrayPop,
+        kBuiltin_ArrayPush,
+        kBuiltin_ArrayShift,
+        kBuiltin_ArrayUnshift,
+        kBuiltin_FunctionConstructor,
+        kBuiltin_HandleApiConstruct,
+        kBuiltin_JsonParse,
+        kBuiltin_JsonStringify,
+        kBuiltin_ObjectDefineProperties,
+        kBuiltin_ObjectFreeze,
+    ],
+    api: [
+        kAPI_Context_New,
+        kAPI_Function_Call,
+        kAPI_Function_New,
+        kAPI_JSON_Parse,
+        kAPI_JSON_Stringify,
+        kAPI_Object_Get,
+        kAPI_Object_New,
+        kAPI_Object_Set,
+        kAPI_Script_Run,
+        kAPI_ScriptCompiler_Compile,
+        kAPI_String_NewFromUtf8,
+        kAPI_Value_ToString,
+    ],
+    handler: [
+        kHandler_KeyedLoadIC_LoadElementDH,
+        kHandler_KeyedStoreIC_SlowStub,
+        kHandler_LoadIC_LoadFieldDH,
+        kHandler_LoadIC_LoadFieldFromPrototypeDH,
+        kHandler_LoadIC_LoadGlobalDH,
+        kHandler_LoadIC_LoadNonexistentDH,
+        kHandler_LoadIC_LoadNormalDH,
+        kHandler_LoadIC_SlowStub,
+        kHandler_StoreIC_SlowStub,
+        kHandler_StoreIC_StoreFieldDH,
+        kHandler_StoreIC_StoreNormalDH,
+    ],
+    thread_specific: [
+        (kCompileAnalyse, kCompileBackgroundAnalyse),
+        (kCompileCompileTask, kCompileBackgroundCompileTask),
+        (kCompileEval, kCompileBackgroundEval),
+        (kCompileFunction, kCompileBackgroundFunction),
+        (kCompileIgnition, kCompileBackgroundIgnition),
+        (kCompileIgnitionFinalization, kCompileBackgroundIgnitionFinalization),
+        (kCompileRewriteReturnResult, kCompileBackgroundRewriteReturnResult),
+        (kCompileScopeAnalysis, kCompileBackgroundScopeAnalysis),
+        (kCompileScript, kCompileBackgroundScript),
+        (kOptimizeAllocateGeneralRegisters, kOptimizeBackgroundAllocateGeneralRegisters),
+        (kOptimizeAssembleCode, kOptimizeBackgroundAssembleCode),
+        (kOptimizeBytecodeGraphBuilder, kOptimizeBackgroundBytecodeGraphBuilder),
+        (kOptimizeEscapeAnalysis, kOptimizeBackgroundEscapeAnalysis),
+        (kOptimizeInlining, kOptimizeBackgroundInlining),
+        (kOptimizeScheduling, kOptimizeBackgroundScheduling),
+        (kOptimizeSimplifiedLowering, kOptimizeBackgroundSimplifiedLowering),
+        (kParseArrowFunctionLiteral, kParseBackgroundArrowFunctionLiteral),
+        (kParseFunctionLiteral, kParseBackgroundFunctionLiteral),
+        (kParseProgram, kParseBackgroundProgram),
+        (kPreParseArrowFunctionLiteral, kPreParseBackgroundArrowFunctionLiteral),
+        (kPreParseWithVariableResolution, kPreParseBackgroundWithVariableResolution),
+    ],
+}
+
+/// The calls and the time spent in one counter. The time of a counter does
+/// not include the time of the counters nested in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeCallCounter {
     name: &'static str,
     count: u64,
-    time: TimeDelta,
+    time: Duration,
 }
 
 impl RuntimeCallCounter {
-    fn new(name: &'static str) -> Self {
-        RuntimeCallCounter {
-            name,
-            count: 0,
-            time: TimeDelta(0),
-        }
+    pub fn new(name: &'static str) -> Self {
+        RuntimeCallCounter { name, count: 0, time: Duration::ZERO }
     }
 
-    fn reset(&mut self) {
+    pub fn reset(&mut self) {
         self.count = 0;
-        self.time = TimeDelta(0);
+        self.time = Duration::ZERO;
     }
 
-    fn dump(&self, value: &mut tracing::TracedValue) {
+    /// Writes the counter as `"name": [count, time in microseconds]`.
+    pub fn dump(&self, value: &mut TracedValue) {
         value.begin_array(self.name);
         value.append_double(self.count as f64);
-        value.append_double(self.time.as_secs_f64() * 1000.0); // Convert seconds to milliseconds
+        value.append_double(self.time.as_micros() as f64);
         value.end_array();
     }
 
-    fn add(&mut self, other: &RuntimeCallCounter) {
-        self.count += other.count();
-        self.time = TimeDelta::from_microseconds(self.time.in_microseconds() + other.time().in_microseconds());
+    pub fn add(&mut self, other: &RuntimeCallCounter) {
+        self.count += other.count;
+        self.time += other.time;
     }
 
-    fn add_time(&mut self, time: TimeDelta) {
-        self.time = TimeDelta::from_microseconds(self.time.in_microseconds() + time.in_microseconds());
-    }
-
-    fn increment(&mut self) {
-        self.count += 1;
+    pub fn name(&self) -> &'static str {
+        self.name
     }
 
-    fn count(&self) -> u64 {
+    pub fn count(&self) -> u64 {
         self.count
     }
 
-    fn time(&self) -> TimeDelta {
+    pub fn time(&self) -> Duration {
         self.time
     }
 
-    fn name(&self) -> &str {
-        self.name
-    }
-}
-
-struct RuntimeCallStatEntries {
-    total_call_count: u64,
-    total_time: TimeDelta,
-    entries: Vec<Entry>,
-}
-
-impl RuntimeCallStatEntries {
-    fn new() -> Self {
-        RuntimeCallStatEntries {
-            total_call_count: 0,
-            total_time: TimeDelta(0),
-            entries: Vec::new(),
-        }
-    }
-
-    fn print(&mut self, os: &mut dyn std::io::Write) -> std::io::Result<()> {
-        if self.total_call_count == 0 {
-            return Ok(());
-        }
-
-        self.entries.sort_by(|a, b| b.cmp(a));
-
-        writeln!(os, "{:50} {:12} {:18}", "Runtime Function/C++ Builtin", "Time", "Count")?;
-        writeln!(os, "{}", "=".repeat(88))?;
-
-        for entry in &mut self.entries {
-            entry.set_total(self.total_time, self.total_call_count);
-            entry.print(os)?;
-        }
-
-        writeln!(os, "{}", "-".repeat(88))?;
-        let mut total_entry = Entry::new("Total", self.total_time, self.total_call_count);
-        total_entry.set_total(self.total_time, self.total_call_count);
-        total_entry.print(os)?;
-
-        Ok(())
+    pub fn increment(&mut self) {
+        self.count += 1;
     }
 
-    fn add(&mut self, counter: &RuntimeCallCounter) {
-        if counter.count() == 0 {
-            return;
-        }
-        self.entries.push(Entry::new(counter.name(), counter.time(), counter.count()));
-        self.total_time = TimeDelta::from_microseconds(self.total_time.in_microseconds() + counter.time().in_microseconds());
-        self.total_call_count += counter.count();
+    pub fn add_time(&mut self, time: Duration) {
+        self.time += time;
     }
 }
 
-struct Entry {
-    name: &'static str,
-    time: i64,
-    count: u64,
-    time_percent: f64,
-    count_percent: f64,
+/// A running scope. Only the innermost timer of a table runs; entering a
+/// nested scope pauses its parent until the nested scope is left.
+#[derive(Debug)]
+struct RuntimeCallTimer {
+    counter_id: RuntimeCallCounterId,
+    start_time: Option<Instant>,
+    elapsed: Duration,
 }
 
-impl Entry {
-    fn new(name: &'static str, time: TimeDelta, count: u64) -> Self {
-        Entry {
-            name,
-            time: time.in_microseconds() as i64,
-            count,
-            time_percent: 100.0,
-            count_percent: 100.0,
-        }
-    }
-
-    fn print(&self, os: &mut dyn std::io::Write) -> std::io::Result<()> {
-        writeln!(
-            os,
-            "{:50} {:10.2}ms {:6.2}% {:10} {:6.2}%",
-            self.name,
-            self.time as f64 / 1000.0,
-            self.time_percent,
-            self.count,
-            self.count_percent
-        )
+impl RuntimeCallTimer {
+    fn start(counter_id: RuntimeCallCounterId, now: Instant) -> Self {
+        RuntimeCallTimer { counter_id, start_time: Some(now), elapsed: Duration::ZERO }
     }
 
-    fn set_total(&mut self, total_time: TimeDelta, total_count: u64) {
-        if total_time.in_microseconds() == 0 {
-            self.time_percent = 0.0;
-        } else {
-            self.time_percent = 100.0 * self.time as f64 / total_time.in_microseconds() as f64;
+    fn pause(&mut self, now: Instant) {
+        if let Some(start_time) = self.start_time.take() {
+            self.elapsed += now - start_time;
         }
-        self.count_percent = 100.0 * self.count as f64 / total_count as f64;
     }
-}
 
-impl PartialEq for Entry {
-    fn eq(&self, other: &Self) -> bool {
-        self.time == other.time && self.count == other.count
+    fn resume(&mut self, now: Instant) {
+        debug_assert!(self.start_time.is_none());
+        self.start_time = Some(now);
     }
-}
 
-impl Eq for Entry {}
-
-impl PartialOrd for Entry {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
+    fn is_running(&self) -> bool {
+        self.start_time.is_some()
     }
 }
 
-impl Ord for Entry {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.time < other.time {
-            Ordering::Less
-        } else if self.time > other.time {
-            Ordering::Greater
-        } else {
-            self.count.cmp(&other.count)
-        }
-    }
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadType {
+    /// The main thread of an isolate.
+    kMainIsolateThread,
+    /// A worker thread, e.g. of background compilation.
+    kWorkerThread,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum ThreadType {
-    Isolate,
-    WorkerThread,
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterMode {
+    /// Use the counter as given.
+    kExact,
+    /// Use the background variant of the counter on worker threads.
+    kThreadSpecific,
 }
 
+/// The runtime call stats table of one thread. The scopes of the thread
+/// form a stack of timers; each counter collects the count and self time of
+/// the scopes that used it.
 #[derive(Debug)]
-struct RuntimeCallStats {
-    in_use: bool,
+pub struct RuntimeCallStats {
     thread_type: ThreadType,
-    counters: [RuntimeCallCounter; RuntimeCallCounterId::kNumberOfCounters as usize],
-    thread_id: Cell<base::ThreadId>,
-    current_timer: Cell<*mut RuntimeCallTimer>,
-    current_counter: Cell<*mut RuntimeCallCounter>,
+    counters: Vec<RuntimeCallCounter>,
+    timers: Vec<RuntimeCallTimer>,
 }
 
 impl RuntimeCallStats {
-    fn new(thread_type: ThreadType) -> Self {
-        let mut stats = RuntimeCallStats {
-            in_use: false,
-            thread_type,
-            counters: array_init::array_init(|i| {
-                let name = match num::FromPrimitive::from_usize(i).unwrap() {
-                    RuntimeCallCounterId::kGCTime => "GC_kGCTime",
-                    RuntimeCallCounterId::kGCHeapSize => "GC_kGCHeapSize",
-                    RuntimeCallCounterId::kCompile => "kCompile",
-                    RuntimeCallCounterId::kParse => "kParse",
-                    RuntimeCallCounterId::kMathSin => "kMathSin",
-                    RuntimeCallCounterId::kMathCos => "kMathCos",
-                    RuntimeCallCounterId::kArrayPush => "kArrayPush",
-                    RuntimeCallCounterId::kArrayPop => "kArrayPop",
-                    RuntimeCallCounterId::kAPIWrite => "API_kAPIWrite",
-                    RuntimeCallCounterId::kAPIRead => "API_kAPIRead",
-                    RuntimeCallCounterId::kHandlerA => "kHandlerA",
-                    RuntimeCallCounterId::kHandlerB => "kHandlerB",
-                    RuntimeCallCounterId::kIsolate => "kIsolate",
-                    RuntimeCallCounterId::kIsolateBackground => "kIsolateBackground",
-                    RuntimeCallCounterId::kNumberOfCounters => unreachable!(),
-                };
-                RuntimeCallCounter::new(name)
-            }),
-            thread_id: Cell::new(base::ThreadId(0)),
-            current_timer: Cell::new(std::ptr::null_mut()),
-            current_counter: Cell::new(std::ptr::null_mut()),
-        };
-
-        unsafe {
-            if flags::rcs_cpu_time {
-                assert!(ThreadTicks::is_supported());
-                ThreadTicks::wait_until_initialized();
-                RuntimeCallTimer::now = RuntimeCallTimer::now_cpu_time;
-            }
-        }
-
-        stats
+    pub fn new(thread_type: ThreadType) -> Self {
+        let counters = RuntimeCallCounterId::ALL.iter().map(|id| RuntimeCallCounter::new(id.name())).collect();
+        RuntimeCallStats { thread_type, counters, timers: Vec::new() }
     }
 
-    fn has_thread_specific_counter_variants(id: RuntimeCallCounterId) -> bool {
-        const K_FIRST_THREAD_VARIANT_COUNTER: RuntimeCallCounterId = RuntimeCallCounterId::kIsolate;
-        const K_LAST_THREAD_VARIANT_COUNTER: RuntimeCallCounterId = RuntimeCallCounterId::kIsolateBackground;
-
-        id >= K_FIRST_THREAD_VARIANT_COUNTER && id <= K_LAST_THREAD_VARIANT_COUNTER
+    pub fn thread_type(&self) -> ThreadType {
+        self.thread_type
     }
 
-    fn is_background_thread_specific_variant(id: RuntimeCallCounterId) -> bool {
-        Self::has_thread_specific_counter_variants(id) &&
-            (id as i32 - RuntimeCallCounterId::kIsolate as i32) % 2 == 1
+    pub fn get_counter(&self, counter_id: RuntimeCallCounterId) -> &RuntimeCallCounter {
+        &self.counters[counter_id as usize]
     }
 
-    fn enter(&self, timer: *mut RuntimeCallTimer, counter_id: RuntimeCallCounterId) {
-        assert!(self.is_called_on_the_same_thread());
-
-        let counter = self.get_counter(counter_id);
-
-        assert!(!unsafe { (*counter).name().is_empty() });
-        unsafe {
-          (*timer).start(counter, self.current_timer.get());
-        }
-        self.current_timer.set(timer);
-        self.current_counter.set(counter);
+    fn get_counter_mut(&mut self, counter_id: RuntimeCallCounterId) -> &mut RuntimeCallCounter {
+        &mut self.counters[counter_id as usize]
     }
 
-    fn leave(&self, timer: *mut RuntimeCallTimer) {
-        assert!(self.is_called_on_the_same_thread());
+    /// The counter of the innermost running scope.
+    pub fn current_counter(&self) -> Option<RuntimeCallCounterId> {
+        self.timers.last().map(|timer| timer.counter_id)
+    }
 
-        let stack_top = self.current_timer.get();
-        if stack_top.is_null() {
-            return;
+    /// Starts measuring time for `counter_id` until the matching leave(),
+    /// pausing the current scope.
+    pub fn enter(&mut self, counter_id: RuntimeCallCounterId) {
+        debug_assert!(self.is_counter_appropriate_for_thread(counter_id));
+        let now = Instant::now();
+        if let Some(parent) = self.timers.last_mut() {
+            parent.pause(now);
         }
-
-        assert_eq!(stack_top, timer);
-
-        let stopped_timer = unsafe { (*timer).stop() };
-        self.current_timer.set(stopped_timer);
-
-        let cur_timer = self.current_timer.get();
-        self.current_counter.set(if !cur_timer.is_null() {
-            unsafe { (*cur_timer).counter() }
-        } else {
-            std::ptr::null_mut()
-        });
+        self.timers.push(RuntimeCallTimer::start(counter_id, now));
     }
 
-    fn add(&mut self, other: &RuntimeCallStats) {
-        for i in 0..RuntimeCallCounterId::kNumberOfCounters as usize {
-            self.counters[i].add(&other.counters[i]);
+    /// Leaves the innermost scope, adds its time to its counter and resumes
+    /// the enclosing scope.
+    pub fn leave(&mut self) {
+        let Some(mut timer) = self.timers.pop() else { return };
+        let now = Instant::now();
+        timer.pause(now);
+        let counter = self.get_counter_mut(timer.counter_id);
+        counter.increment();
+        counter.add_time(timer.elapsed);
+        if let Some(parent) = self.timers.last_mut() {
+            parent.resume(now);
         }
     }
 
-    fn correct_current_counter_id(counter_id: RuntimeCallCounterId, mode: CounterMode) {
-        // This function needs access to thread-local RuntimeCallStats.
-        // Since the access pattern is not clear, omitting thread-local access for now.
-        // TODO(you): Implement thread-local access if needed.
+    /// Changes the counter of the current scope, e.g. once it is known what
+    /// kind of function a call ended up in.
+    pub fn correct_current_counter_id(&mut self, counter_id: RuntimeCallCounterId, mode: CounterMode) {
+        let counter_id = match mode {
+            CounterMode::kThreadSpecific => self.counter_id_for_thread(counter_id),
+            CounterMode::kExact => counter_id,
+        };
+        debug_assert!(self.is_counter_appropriate_for_thread(counter_id));
+        if let Some(timer) = self.timers.last_mut() {
+            timer.counter_id = counter_id;
+        }
     }
 
-    fn is_called_on_the_same_thread(&self) -> bool {
-        if self.thread_id.get().is_valid() {
-            self.thread_id.get() == base::ThreadId::current()
-        } else {
-            self.thread_id.set(base::ThreadId::current());
-            true
+    /// Adds the time the running scopes spent so far to their counters, so
+    /// that a dump in the middle of a scope is accurate.
+    fn snapshot(&mut self) {
+        let now = Instant::now();
+        let RuntimeCallStats { counters, timers, .. } = self;
+        for timer in timers.iter_mut() {
+            let was_running = timer.is_running();
+            timer.pause(now);
+            counters[timer.counter_id as usize].add_time(timer.elapsed);
+            timer.elapsed = Duration::ZERO;
+            if was_running {
+                timer.resume(now);
+            }
         }
     }
 
-    fn print(&mut self, os: &mut dyn std::io::Write) -> std::io::Result<()> {
-        let mut entries = RuntimeCallStatEntries::new();
-
-        if !self.current_timer.get().is_null() {
-            unsafe { (*self.current_timer.get()).snapshot() };
+    /// Clears all counters. Scopes that are still running only count the
+    /// time after the reset.
+    pub fn reset(&mut self) {
+        let now = Instant::now();
+        for timer in &mut self.timers {
+            timer.elapsed = Duration::ZERO;
+            if timer.is_running() {
+                timer.start_time = Some(now);
+            }
         }
-
-        for i in 0..RuntimeCallCounterId::kNumberOfCounters as usize {
-            entries.add(&self.counters[i]);
+        for counter in &mut self.counters {
+            counter.reset();
         }
-
-        entries.print(os)
     }
 
-    fn reset(&mut self) {
-        if !is_runtime_stats_enabled() {
-            return;
+    /// Adds the counters of `other`, e.g. of a worker thread.
+    pub fn add(&mut self, other: &RuntimeCallStats) {
+        for (counter, other_counter) in self.counters.iter_mut().zip(&other.counters) {
+            counter.add(other_counter);
         }
+    }
 
-        while !self.current_timer.get().is_null() {
-            unsafe {
-                let current_timer = self.current_timer.get();
-                (*current_timer).stop();
-                self.current_timer.set((*current_timer).parent());
-            }
+    /// Prints the used counters, sorted by time and count.
+    pub fn print(&mut self, os: &mut dyn io::Write) -> io::Result<()> {
+        self.snapshot();
+        let mut entries = RuntimeCallStatEntries::default();
+        for counter in &self.counters {
+            entries.add(counter);
         }
+        entries.print(os)
+    }
 
-        for i in 0..RuntimeCallCounterId::kNumberOfCounters as usize {
-            self.counters[i].reset();
+    /// Writes the used counters to `value`, as for the trace event args.
+    pub fn dump(&mut self, value: &mut TracedValue) {
+        self.snapshot();
+        for counter in self.counters.iter().filter(|counter| counter.count() > 0) {
+            counter.dump(value);
         }
-
-        self.in_use = true;
     }
 
-    fn dump(&mut self, value: &mut tracing::TracedValue) {
-        for i in 0..RuntimeCallCounterId::kNumberOfCounters as usize {
-            if self.counters[i].count() > 0 {
-                self.counters[i].dump(value);
-            }
-        }
+    /// Returns the used counters as a JSON object of
+    /// `"name": [count, time in microseconds]`.
+    pub fn dump_json(&mut self) -> String {
+        let mut value = TracedValue::create();
+        self.dump(&mut value);
+        let mut json = String::new();
+        value.append_as_trace_format(&mut json);
+        json
+    }
 
-        self.in_use = false;
+    pub fn has_thread_specific_counter_variants(counter_id: RuntimeCallCounterId) -> bool {
+        counter_id.background_variant().is_some() || counter_id.is_background_variant()
     }
 
-    fn get_counter(&self, id: RuntimeCallCounterId) -> *mut RuntimeCallCounter {
-        let index = id.as_usize();
-        &self.counters[index] as *const RuntimeCallCounter as *mut RuntimeCallCounter
+    pub fn is_background_thread_specific_variant(counter_id: RuntimeCallCounterId) -> bool {
+        counter_id.is_background_variant()
     }
 
-    fn counter_id_for_thread(counter_id: RuntimeCallCounterId) -> RuntimeCallCounterId {
-      // Mock
-      counter_id
+    /// Returns the background variant of `counter_id` on worker threads.
+    pub fn counter_id_for_thread(&self, counter_id: RuntimeCallCounterId) -> RuntimeCallCounterId {
+        debug_assert!(!Self::is_background_thread_specific_variant(counter_id));
+        match self.thread_type {
+            ThreadType::kWorkerThread => counter_id.background_variant().unwrap_or(counter_id),
+            ThreadType::kMainIsolateThread => counter_id,
+        }
     }
 
-    fn is_counter_appropriate_for_thread(counter_id: RuntimeCallCounterId) -> bool {
-      // Mock
-      true
+    /// Thread-specific counters must use the variant of their thread.
+    pub fn is_counter_appropriate_for_thread(&self, counter_id: RuntimeCallCounterId) -> bool {
+        if !Self::has_thread_specific_counter_variants(counter_id) {
+            return true;
+        }
+        Self::is_background_thread_specific_variant(counter_id) == (self.thread_type == ThreadType::kWorkerThread)
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum CounterMode {
-  kNormal,
-  kThreadSpecific
+/// The runtime call stats tables of an isolate, one per thread. The thread
+/// that creates the tables is the main thread of the isolate; the tables of
+/// all other threads use the background variants of thread-specific
+/// counters.
+///
+/// Each thread finds its table through a thread-local cache, so scopes only
+/// lock the table of their own thread, which is contended by dumps only.
+/// The list of all tables is locked when a thread uses the tables for the
+/// first time and for dumps.
+#[derive(Debug)]
+pub struct RuntimeCallStatsTables {
+    id: u64,
+    main_thread: ThreadId,
+    tables: Mutex<Vec<Arc<Mutex<RuntimeCallStats>>>>,
 }
 
-#[derive(Debug)]
-struct WorkerThreadRuntimeCallStats {
-    isolate_thread_id: base::ThreadId,
-    tls_key: Option<base::Thread::LocalStorageKey>,
-    tables: Vec<std::unique_ptr<RuntimeCallStats>>,
-    mutex: Mutex<()>,
+thread_local! {
+    /// The tables of the current thread by `RuntimeCallStatsTables::id`.
+    static THREAD_TABLES: RefCell<Vec<(u64, Arc<Mutex<RuntimeCallStats>>)>> = const { RefCell::new(Vec::new()) };
 }
 
-impl WorkerThreadRuntimeCallStats {
-    fn new() -> Self {
-        WorkerThreadRuntimeCallStats {
-            isolate_thread_id: base::ThreadId::current(),
-            tls_key: None,
-            tables: Vec::new(),
-            mutex: Mutex::new(()),
-        }
+impl Default for RuntimeCallStatsTables {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn get_key(&mut self) -> base::Thread::LocalStorageKey {
-        let _lock = self.mutex.lock().unwrap(); // Lock the mutex
-        if self.tls_key.is_none() {
-            self.tls_key = Some(base::Thread::create_thread_local_key());
+impl RuntimeCallStatsTables {
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        RuntimeCallStatsTables {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            main_thread: thread::current().id(),
+            tables: Mutex::new(Vec::new()),
         }
-        self.tls_key.unwrap()
     }
 
-    fn new_table(&mut self) -> *mut RuntimeCallStats {
-        assert_ne!(base::ThreadId::current(), self.isolate_thread_id);
-
-        let new_table = std::unique_ptr::new(RuntimeCallStats::new(RuntimeCallStats::kWorkerThread));
-        let result = new_table.as_mut().unwrap() as *mut RuntimeCallStats;
-
-        let _lock = self.mutex.lock().unwrap(); // Lock the mutex
-        self.tables.push(new_table);
-        result
+    /// Returns the table of the current thread, creating it on first use.
+    pub fn current_thread_table(&self) -> Arc<Mutex<RuntimeCallStats>> {
+        THREAD_TABLES.with(|thread_tables| {
+            let mut thread_tables = thread_tables.borrow_mut();
+            if let Some((_, table)) = thread_tables.iter().find(|(id, _)| *id == self.id) {
+                return table.clone();
+            }
+            // Forget the tables of isolates that are gone.
+            thread_tables.retain(|(_, table)| Arc::strong_count(table) > 1);
+            let thread_type = if thread::current().id() == self.main_thread {
+                ThreadType::kMainIsolateThread
+            } else {
+                ThreadType::kWorkerThread
+            };
+            let table = Arc::new(Mutex::new(RuntimeCallStats::new(thread_type)));
+            self.tables.lock().unwrap().push(table.clone());
+            thread_tables.push((self.id, table.clone()));
+            table
+        })
     }
 
-    fn add_to_main_table(&mut self, main_call_stats: &mut RuntimeCallStats) {
-        let _lock = self.mutex.lock().unwrap(); // Lock the mutex
-
-        for worker_stats in &mut self.tables {
-            assert_ne!(main_call_stats as *mut _, worker_stats.as_mut().unwrap() as *mut _);
-            main_call_stats.add(worker_stats.as_ref().unwrap());
-            worker_stats.as_mut().unwrap().reset();
+    /// Adds the counters of all threads to `call_stats`.
+    pub fn add_to(&self, call_stats: &mut RuntimeCallStats) {
+        for table in self.tables.lock().unwrap().iter() {
+            let mut table = table.lock().unwrap();
+            table.snapshot();
+            call_stats.add(&table);
         }
     }
-}
 
-struct WorkerThreadRuntimeCallStatsScope<'a> {
-    table: *mut RuntimeCallStats,
-    worker_stats: &'a mut WorkerThreadRuntimeCallStats
-}
+    /// The counters of all threads in one table.
+    fn combined(&self) -> RuntimeCallStats {
+        let mut call_stats = RuntimeCallStats::new(ThreadType::kMainIsolateThread);
+        self.add_to(&mut call_stats);
+        call_stats
+    }
 
-impl<'a> WorkerThreadRuntimeCallStatsScope<'a> {
-    fn new(worker_stats: &'a mut WorkerThreadRuntimeCallStats) -> Self {
-        if !is_runtime_stats_enabled() {
-            return WorkerThreadRuntimeCallStatsScope { table: std::ptr::null_mut(), worker_stats };
-        }
+    /// Prints the used counters of all threads, sorted by time and count.
+    pub fn print(&self, os: &mut dyn io::Write) -> io::Result<()> {
+        self.combined().print(os)
+    }
 
-        let key = worker_stats.get_key();
-        let table = unsafe { base::Thread::get_thread_local(key) };
+    /// Returns the used counters of all threads as JSON, see
+    /// `RuntimeCallStats::dump_json()`.
+    pub fn dump_json(&self) -> String {
+        self.combined().dump_json()
+    }
 
-        let table = if table.is_none() {
-            if !is_runtime_stats_enabled() {
-                return WorkerThreadRuntimeCallStatsScope { table: std::ptr::null_mut(), worker_stats };
-            }
-            let new_table = worker_stats.new_table();
-            unsafe { base::Thread::set_thread_local(key, new_table) };
-            new_table
-        } else {
-            table.unwrap()
-        } as *mut RuntimeCallStats;
-
-        if is_runtime_stats_tracing_enabled() {
-            unsafe {
-                (*table).reset();
-            }
+    /// Clears the counters of all threads.
+    pub fn reset(&self) {
+        for table in self.tables.lock().unwrap().iter() {
+            table.lock().unwrap().reset();
         }
-
-        WorkerThreadRuntimeCallStatsScope { table, worker_stats }
     }
 }
 
-impl<'a> Drop for WorkerThreadRuntimeCallStatsScope<'a> {
-    fn drop(&mut self) {
-        if self.table.is_null() {
-            return;
-        }
+struct Entry {
+    name: &'static str,
+    time: Duration,
+    count: u64,
+    time_percent: f64,
+    count_percent: f64,
+}
 
-        if is_runtime_stats_tracing_enabled() {
-            let value = tracing::TracedValue::create();
-            unsafe { (*self.table).dump(value.as_mut()) };
-            trace_event_instant1("V8.RuntimeStats", "V8.RuntimeStats", "runtime-call-stats", value);
-        }
+impl Entry {
+    fn new(name: &'static str, time: Duration, count: u64) -> Self {
+        Entry { name, time, count, time_percent: 100.0, count_percent: 100.0 }
     }
-}
 
-// Mock tracing macros and functions
-fn is_runtime_stats_enabled() -> bool {
-    unsafe {
-        true // Mock.  Replace with actual check of `TracingFlags::is_runtime_stats_enabled()` if available.
+    fn set_total(&mut self, total_time: Duration, total_count: u64) {
+        self.time_percent =
+            if total_time.is_zero() { 0.0 } else { 100.0 * self.time.as_secs_f64() / total_time.as_secs_f64() };
+        self.count_percent = 100.0 * self.count as f64 / total_count as f64;
     }
-}
 
-fn is_runtime_stats_tracing_enabled() -> bool {
-    unsafe {
-        true // Mock. Replace with actual check of tracing flags.
+    fn print(&self, os: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(
+            os,
+            "{:>50}{:>10.2}ms {:>6.2}%{:>10} {:>6.2}%",
+            self.name,
+            self.time.as_micros() as f64 / 1000.0,
+            self.time_percent,
+            self.count,
+            self.count_percent
+        )
     }
 }
 
-fn trace_event_instant1(category: &str, name: &str, event_name: &str, value: Box<tracing::TracedValue>) {
-    // Mock implementation.  Replace with actual tracing logic.
-    println!("TRACE_EVENT_INSTANT1: {} {} {} {:?}", category, name, event_name, value);
+#[derive(Default)]
+struct RuntimeCallStatEntries {
+    total_call_count: u64,
+    total_time: Duration,
+    entries: Vec<Entry>,
 }
 
-// Mock implementation for unique_ptr
-mod std {
-    pub mod unique_ptr {
-        use std::ops::{Deref, DerefMut};
-
-        pub struct UniquePtr<T>(Box<T>);
-
-        impl<T> UniquePtr<T> {
-            pub fn new(value: T) -> Self {
-                UniquePtr(Box::new(value))
-            }
-
-            pub fn as_mut(&mut self) -> Option<&mut T> {
-              Some(&mut self.0)
-            }
-
-            pub fn as_ref(&self) -> Option<&T> {
-              Some(&self.0)
-            }
+impl RuntimeCallStatEntries {
+    fn add(&mut self, counter: &RuntimeCallCounter) {
+        if counter.count() == 0 {
+            return;
         }
+        self.entries.push(Entry::new(counter.name(), counter.time(), counter.count()));
+        self.total_time += counter.time();
+        self.total_call_count += counter.count();
+    }
 
-        impl<T> Deref for UniquePtr<T> {
-            type Target = T;
-
-            fn deref(&self) -> &Self::Target {
-                &self.0
-            }
+    fn print(&mut self, os: &mut dyn io::Write) -> io::Result<()> {
+        if self.total_call_count == 0 {
+            return Ok(());
         }
-
-        impl<T> DerefMut for UniquePtr<T> {
-            fn deref_mut(&mut self) -> &mut Self::Target {
-                &mut self.0
-            }
+        self.entries.sort_by_key(|entry| std::cmp::Reverse((entry.time, entry.count)));
+        writeln!(os, "{:>50}{:>12}{:>18}", "Runtime Function/C++ Builtin", "Time", "Count")?;
+        writeln!(os, "{}", "=".repeat(88))?;
+        for entry in &mut self.entries {
+            entry.set_total(self.total_time, self.total_call_count);
+            entry.print(os)?;
         }
+        writeln!(os, "{}", "-".repeat(88))?;
+        let mut total = Entry::new("Total", self.total_time, self.total_call_count);
+        total.set_total(self.total_time, self.total_call_count);
+        total.print(os)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_self_time_of_nested_scopes() {
+        let mut stats = RuntimeCallStats::new(ThreadType::kMainIsolateThread);
+        stats.enter(RuntimeCallCounterId::kTestCounter1);
+        stats.enter(RuntimeCallCounterId::kTestCounter2);
+        thread::sleep(Duration::from_millis(20));
+        stats.leave();
+        stats.enter(RuntimeCallCounterId::kTestCounter2);
+        stats.leave();
+        stats.leave();
+
+        let outer = stats.get_counter(RuntimeCallCounterId::kTestCounter1).clone();
+        let inner = stats.get_counter(RuntimeCallCounterId::kTestCounter2).clone();
+        assert_eq!((outer.count(), inner.count()), (1, 2));
+        assert!(inner.time() >= Duration::from_millis(20));
+        assert!(outer.time() < inner.time());
+
+        let mut table = Vec::new();
+        stats.print(&mut table).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].ends_with("Runtime Function/C++ Builtin        Time             Count"));
+        assert!(lines[2].trim_start().starts_with("TestCounter2"));
+        assert!(lines[2].ends_with("2  66.67%"));
+        assert!(lines[3].trim_start().starts_with("TestCounter1"));
+        assert!(lines[5].trim_start().starts_with("Total"));
+        assert!(lines[5].ends_with("3 100.00%"));
+
+        let json = stats.dump_json();
+        assert!(json.starts_with("{\"TestCounter1\":[1,"));
+        assert!(json.contains("\"TestCounter2\":[2,"));
+
+        stats.reset();
+        assert_eq!(stats.get_counter(RuntimeCallCounterId::kTestCounter2).count(), 0);
+        assert_eq!(stats.dump_json(), "{}");
+    }
+
+    #[test]
+    fn worker_threads_use_background_counters() {
+        let tables = Arc::new(RuntimeCallStatsTables::new());
+        let main_table = tables.current_thread_table();
+        assert!(Arc::ptr_eq(&main_table, &tables.current_thread_table()));
+        let worker = {
+            let tables = tables.clone();
+            thread::spawn(move || {
+                let table = tables.current_thread_table();
+                let mut stats = table.lock().unwrap();
+                let counter_id = stats.counter_id_for_thread(RuntimeCallCounterId::kParseProgram);
+                assert_eq!(counter_id, RuntimeCallCounterId::kParseBackgroundProgram);
+                stats.enter(counter_id);
+                stats.leave();
+            })
+        };
+        worker.join().unwrap();
+
+        let mut main_stats = main_table.lock().unwrap();
+        assert!(!main_stats.is_counter_appropriate_for_thread(RuntimeCallCounterId::kParseBackgroundProgram));
+        assert_eq!(
+            main_stats.counter_id_for_thread(RuntimeCallCounterId::kParseProgram),
+            RuntimeCallCounterId::kParseProgram
+        );
+        main_stats.enter(RuntimeCallCounterId::kParseProgram);
+        main_stats.leave();
+        drop(main_stats);
+        assert_eq!(tables.dump_json().matches(":[1,").count(), 2);
+        tables.reset();
+        assert_eq!(tables.dump_json(), "{}");
     }
-    use unique_ptr::UniquePtr;
-    pub use UniquePtr;
-}
\ No newline at end of file
+}
@@ -0,0 +1,533 @@
+// Copyright 2012 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+// Reads the log of --prof and prints the flat, bottom-up and top-down
+// profiles, like tools/linux-tick-processor.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::process::Command;
+
+use crate::logging::code_events::Address;
+
+/// Callers below this share of their callee are not shown in the bottom-up
+/// profile.
+const K_CALLERS_TO_PRINT_THRESHOLD_PERCENT: f64 = 1.0;
+/// Callees below this share of all ticks are not shown in the top-down
+/// profile.
+const K_CALLEES_TO_PRINT_THRESHOLD_PERCENT: f64 = 0.1;
+/// How many levels of callers the bottom-up profile shows.
+const K_CALL_GRAPH_SIZE: usize = 5;
+/// vm_state of ticks taken during GC; see StateTag.
+const K_GC_STATE: i32 = 1;
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    kJavaScript,
+    kCpp,
+    kSharedLibrary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeEntry {
+    pub size: usize,
+    pub name: String,
+    pub entry_type: EntryType,
+}
+
+/// Maps addresses to the code, C++ functions and libraries containing them.
+#[derive(Debug, Default)]
+pub struct CodeMap {
+    code: BTreeMap<Address, CodeEntry>,
+    cpp: BTreeMap<Address, CodeEntry>,
+    libraries: BTreeMap<Address, CodeEntry>,
+}
+
+impl CodeMap {
+    pub fn add_code(&mut self, start: Address, entry: CodeEntry) {
+        self.code.insert(start, entry);
+    }
+
+    pub fn move_code(&mut self, from: Address, to: Address) {
+        if let Some(entry) = self.code.remove(&from) {
+            self.code.insert(to, entry);
+        }
+    }
+
+    pub fn delete_code(&mut self, start: Address) {
+        self.code.remove(&start);
+    }
+
+    pub fn add_library(&mut self, start: Address, entry: CodeEntry) {
+        self.libraries.insert(start, entry);
+    }
+
+    pub fn add_cpp(&mut self, start: Address, entry: CodeEntry) {
+        self.cpp.insert(start, entry);
+    }
+
+    pub fn find_entry(&self, address: Address) -> Option<&CodeEntry> {
+        [&self.code, &self.cpp, &self.libraries].into_iter().find_map(|map| {
+            let (start, entry) = map.range(..=address).next_back()?;
+            (address < start + entry.size).then_some(entry)
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct CallNode {
+    name: String,
+    total: u64,
+    self_ticks: u64,
+    children: Vec<CallNode>,
+}
+
+impl CallNode {
+    fn add_path<'a>(&mut self, path: impl Iterator<Item = &'a str>) {
+        let mut node = self;
+        node.total += 1;
+        for name in path {
+            let index = match node.children.iter().position(|child| child.name == name) {
+                Some(index) => index,
+                None => {
+                    node.children.push(CallNode { name: name.to_string(), ..CallNode::default() });
+                    node.children.len() - 1
+                }
+            };
+            node = &mut node.children[index];
+            node.total += 1;
+        }
+        node.self_ticks += 1;
+    }
+
+    fn sorted_children(&self) -> Vec<&CallNode> {
+        let mut children: Vec<&CallNode> = self.children.iter().collect();
+        children.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+        children
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TickCounts {
+    pub total: u64,
+    pub unaccounted: u64,
+    pub excluded: u64,
+    pub gc: u64,
+}
+
+/// Builds the profiles from the lines of a v8.log.
+pub struct TickProcessor {
+    nm: Option<String>,
+    code_map: CodeMap,
+    ticks: TickCounts,
+    /// Ticks per function on top of the stack.
+    flat: HashMap<String, (EntryType, u64)>,
+    bottom_up: CallNode,
+    top_down: CallNode,
+}
+
+impl TickProcessor {
+    /// `nm` is used to read the C++ symbols of shared libraries; without it
+    /// ticks in C++ are attributed to the library.
+    pub fn new(nm: Option<String>) -> Self {
+        TickProcessor {
+            nm,
+            code_map: CodeMap::default(),
+            ticks: TickCounts::default(),
+            flat: HashMap::new(),
+            bottom_up: CallNode::default(),
+            top_down: CallNode::default(),
+        }
+    }
+
+    pub fn ticks(&self) -> TickCounts {
+        self.ticks
+    }
+
+    pub fn process_log(&mut self, log: &str) {
+        for line in log.lines() {
+            let fields: Vec<String> = line.split(',').map(unescape).collect();
+            self.process_line(&fields);
+        }
+    }
+
+    fn process_line(&mut self, fields: &[String]) {
+        let Some(event) = fields.first() else { return };
+        let field = |i: usize| fields.get(i).map(String::as_str).unwrap_or("");
+        let address = |i: usize| parse_address(field(i));
+        match event.as_str() {
+            "shared-library" => {
+                let (Some(start), Some(end)) = (address(2), address(3)) else { return };
+                self.process_shared_library(field(1), start, end);
+            }
+            "code-creation" => {
+                let (Some(start), Some(size)) = (address(4), field(5).parse::<usize>().ok()) else { return };
+                // Functions carry the address of their SharedFunctionInfo and
+                // the tier marker in two more fields.
+                let marker = if fields.len() > 7 { field(8) } else { "" };
+                let name = format!("{}: {}{}", field(1), marker, field(6));
+                self.code_map.add_code(start, CodeEntry { size, name, entry_type: EntryType::kJavaScript });
+            }
+            "code-move" => {
+                if let (Some(from), Some(to)) = (address(1), address(2)) {
+                    self.code_map.move_code(from, to);
+                }
+            }
+            "code-delete" => {
+                if let Some(start) = address(1) {
+                    self.code_map.delete_code(start);
+                }
+            }
+            "tick" => self.process_tick(fields),
+            _ => {}
+        }
+    }
+
+    fn process_shared_library(&mut self, path: &str, start: Address, end: Address) {
+        self.code_map.add_library(
+            start,
+            CodeEntry { size: end.saturating_sub(start), name: path.to_string(), entry_type: EntryType::kSharedLibrary },
+        );
+        let Some(nm) = &self.nm else { return };
+        for (address, size, name) in read_cpp_symbols(nm, path) {
+            // Executables that are not position independent list absolute
+            // addresses, everything else is relative to the load address.
+            let symbol_start = if address >= start { address } else { start + address };
+            if symbol_start < end {
+                self.code_map.add_cpp(symbol_start, CodeEntry { size, name, entry_type: EntryType::kCpp });
+            }
+        }
+    }
+
+    fn process_tick(&mut self, fields: &[String]) {
+        let (Some(mut pc), Some(vm_state)) =
+            (fields.get(1).and_then(|f| parse_address(f)), fields.get(5).and_then(|f| f.parse::<i32>().ok()))
+        else {
+            return;
+        };
+        let is_external_callback = fields.get(3).is_some_and(|f| f == "1");
+        let mut tos_or_external_callback = fields.get(4).and_then(|f| parse_address(f)).unwrap_or(0);
+        self.ticks.total += 1;
+        if vm_state == K_GC_STATE {
+            self.ticks.gc += 1;
+        }
+
+        if is_external_callback {
+            // The pc is somewhere in the callback; attribute the tick to it.
+            pc = tos_or_external_callback;
+            tos_or_external_callback = 0;
+        } else if tos_or_external_callback != 0 {
+            // The top of the stack points into JS code for frameless calls.
+            let in_js = self
+                .code_map
+                .find_entry(tos_or_external_callback)
+                .is_some_and(|entry| entry.entry_type == EntryType::kJavaScript);
+            if !in_js {
+                tos_or_external_callback = 0;
+            }
+        }
+
+        let frames = fields[6..].iter().filter(|f| f.as_str() != "overflow").filter_map(|f| parse_address(f));
+        let addresses = std::iter::once(pc).chain((tos_or_external_callback != 0).then_some(tos_or_external_callback)).chain(frames);
+        let mut stack: Vec<String> = Vec::new();
+        for (i, address) in addresses.enumerate() {
+            match self.code_map.find_entry(address) {
+                Some(entry) => {
+                    if i == 0 {
+                        self.flat.entry(entry.name.clone()).or_insert((entry.entry_type, 0)).1 += 1;
+                    }
+                    stack.push(entry.name.clone());
+                }
+                None if i == 0 => {
+                    self.ticks.unaccounted += 1;
+                    stack.push("UNKNOWN".to_string());
+                }
+                None => {}
+            }
+        }
+        self.bottom_up.add_path(stack.iter().map(String::as_str));
+        self.top_down.add_path(stack.iter().rev().map(String::as_str));
+    }
+
+    fn ticks_of(&self, entry_type: EntryType) -> u64 {
+        self.flat.values().filter(|(t, _)| *t == entry_type).map(|(_, ticks)| ticks).sum()
+    }
+
+    fn print_entries(&self, out: &mut String, title: &str, entry_type: EntryType, non_lib_ticks: Option<u64>) {
+        let _ = writeln!(out, "\n [{title}]:\n   ticks  total  nonlib   name");
+        let mut entries: Vec<(&String, u64)> =
+            self.flat.iter().filter(|(_, (t, _))| *t == entry_type).map(|(name, (_, ticks))| (name, *ticks)).collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        for (name, ticks) in entries {
+            self.print_line(out, name, ticks, non_lib_ticks);
+        }
+    }
+
+    fn print_line(&self, out: &mut String, name: &str, ticks: u64, non_lib_ticks: Option<u64>) {
+        let non_lib = match non_lib_ticks {
+            Some(non_lib_ticks) => format!("{:>5.1}%  ", percent(ticks, non_lib_ticks)),
+            None => " ".repeat(8),
+        };
+        let _ = writeln!(out, "  {ticks:>5}  {:>5.1}%  {non_lib}{name}", percent(ticks, self.ticks.total));
+    }
+
+    fn print_bottom_up(out: &mut String, node: &CallNode, indent: usize) {
+        for child in node.sorted_children() {
+            let parent_percent = percent(child.total, node.total);
+            if parent_percent < K_CALLERS_TO_PRINT_THRESHOLD_PERCENT {
+                continue;
+            }
+            let _ = writeln!(out, "  {:>5}  {parent_percent:>5.1}%  {:indent$}{}", child.total, "", child.name);
+            if indent < 2 * K_CALL_GRAPH_SIZE {
+                Self::print_bottom_up(out, child, indent + 2);
+            }
+            if indent == 0 {
+                out.push('\n');
+            }
+        }
+    }
+
+    fn print_top_down(&self, out: &mut String, node: &CallNode, indent: usize) {
+        for child in node.sorted_children() {
+            let total_percent = percent(child.total, self.ticks.total);
+            if total_percent < K_CALLEES_TO_PRINT_THRESHOLD_PERCENT {
+                continue;
+            }
+            let _ = writeln!(
+                out,
+                "  {:>5}  {total_percent:>5.1}%  {:>5}  {:>5.1}%  {:indent$}{}",
+                child.total,
+                child.self_ticks,
+                percent(child.self_ticks, self.ticks.total),
+                "",
+                child.name
+            );
+            self.print_top_down(out, child, indent + 2);
+        }
+    }
+
+    /// Prints the statistics of the processed log.
+    pub fn print_statistics(&self, log_file_name: &str) -> String {
+        let mut out = String::new();
+        let ticks = &self.ticks;
+        let _ = writeln!(
+            out,
+            "Statistical profiling result from {log_file_name}, ({} ticks, {} unaccounted, {} excluded).",
+            ticks.total, ticks.unaccounted, ticks.excluded
+        );
+        let library_ticks = self.ticks_of(EntryType::kSharedLibrary);
+        let non_lib_ticks = ticks.total - library_ticks;
+        let js_ticks = self.ticks_of(EntryType::kJavaScript);
+        let cpp_ticks = self.ticks_of(EntryType::kCpp);
+
+        self.print_entries(&mut out, "Shared libraries", EntryType::kSharedLibrary, None);
+        self.print_entries(&mut out, "JavaScript", EntryType::kJavaScript, Some(non_lib_ticks));
+        self.print_entries(&mut out, "C++", EntryType::kCpp, Some(non_lib_ticks));
+
+        let _ = writeln!(out, "\n [Summary]:\n   ticks  total  nonlib   name");
+        self.print_line(&mut out, "JavaScript", js_ticks, Some(non_lib_ticks));
+        self.print_line(&mut out, "C++", cpp_ticks, Some(non_lib_ticks));
+        self.print_line(&mut out, "GC", ticks.gc, Some(non_lib_ticks));
+        self.print_line(&mut out, "Shared libraries", library_ticks, None);
+        self.print_line(&mut out, "Unaccounted", ticks.unaccounted, None);
+
+        let _ = writeln!(
+            out,
+            "\n [Bottom up (heavy) profile]:\n  Note: percentage shows a share of a particular caller in the total\n  \
+             amount of its parent calls.\n  Callers occupying less than {K_CALLERS_TO_PRINT_THRESHOLD_PERCENT:.1}% are \
+             not shown.\n\n   ticks  parent  name"
+        );
+        Self::print_bottom_up(&mut out, &self.bottom_up, 0);
+
+        let _ = writeln!(
+            out,
+            "\n [Top down (heavy) profile]:\n  Note: callees occupying less than {K_CALLEES_TO_PRINT_THRESHOLD_PERCENT:.1}% \
+             are not shown.\n\n  inclusive      self           name\n  ticks   total  ticks   total"
+        );
+        self.print_top_down(&mut out, &self.top_down, 0);
+        out
+    }
+}
+
+fn percent(ticks: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        ticks as f64 * 100.0 / total as f64
+    }
+}
+
+fn parse_address(field: &str) -> Option<Address> {
+    match field.strip_prefix("0x") {
+        Some(hex) => Address::from_str_radix(hex, 16).ok(),
+        None => field.parse().ok(),
+    }
+}
+
+/// Reverses the escaping of LogFile.
+fn unescape(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let digits = match chars.next() {
+            Some('n') => {
+                result.push('\n');
+                continue;
+            }
+            Some('x') => 2,
+            Some('u') => 4,
+            Some(other) => {
+                result.push(other);
+                continue;
+            }
+            None => break,
+        };
+        let code: String = chars.by_ref().take(digits).collect();
+        if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Returns the functions of a binary as (address, size, name), read with
+/// `nm`. Symbols without a size extend to the next symbol.
+fn read_cpp_symbols(nm: &str, path: &str) -> Vec<(Address, usize, String)> {
+    let Ok(output) = Command::new(nm).args(["-C", "-n", "-S", "--defined-only", path]).output() else {
+        return Vec::new();
+    };
+    let mut symbols: Vec<(Address, Option<usize>, String)> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut rest = line;
+        let mut next = || {
+            let (token, tail) = rest.trim_start().split_once(' ').unwrap_or((rest.trim_start(), ""));
+            rest = tail;
+            token
+        };
+        let Ok(address) = Address::from_str_radix(next(), 16) else { continue };
+        let mut token = next();
+        let size = if token.len() > 1 { usize::from_str_radix(token, 16).ok() } else { None };
+        if size.is_some() {
+            token = next();
+        }
+        if matches!(token, "t" | "T" | "w" | "W" | "i") {
+            symbols.push((address, size, rest.to_string()));
+        }
+    }
+    (0..symbols.len())
+        .map(|i| {
+            let (address, size, name) = &symbols[i];
+            let size = size.unwrap_or_else(|| symbols.get(i + 1).map_or(0, |next| next.0.saturating_sub(*address)));
+            (*address, size, name.clone())
+        })
+        .collect()
+}
+
+/// `tick-processor [--nm=<nm>] [--no-nm] [<log file>]`
+pub fn run(args: &[String]) -> Result<String, String> {
+    let mut nm = Some("nm".to_string());
+    let mut log_file_name = "v8.log".to_string();
+    for arg in args {
+        if let Some(path) = arg.strip_prefix("--nm=") {
+            nm = Some(path.to_string());
+        } else if arg == "--no-nm" {
+            nm = None;
+        } else if arg.starts_with("--") {
+            return Err(format!("unknown option {arg}"));
+        } else {
+            log_file_name = arg.clone();
+        }
+    }
+    let log = std::fs::read_to_string(&log_file_name).map_err(|e| format!("cannot read {log_file_name}: {e}"))?;
+    let mut processor = TickProcessor::new(nm);
+    processor.process_log(&log);
+    Ok(processor.print_statistics(&log_file_name))
+}
+
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(output) => print!("{output}"),
+        Err(message) => {
+            eprintln!("{message}");
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::logging::code_events::{AbstractCode, CodeTag, LogEventListener, Logger, SharedFunctionInfo};
+    use crate::logging::log::{LogFlags, V8FileLogger};
+    use crate::logging::log_file::LogFile;
+    use crate::objects::code_kind::CodeKind;
+    use crate::profiler::tick_sample::internal::{StateTag, TickSample};
+
+    #[test]
+    fn processes_a_log_written_by_the_logger() {
+        let flags = LogFlags { logfile: LogFile::K_LOG_TO_TEMPORARY_FILE.to_string(), log_code: true, ..LogFlags::default() };
+        let logger = V8FileLogger::set_up(std::ptr::null_mut(), Arc::new(Logger::new()), &flags).unwrap();
+        logger.shared_library_event("/lib/libc.so.6", 0x10000, 0x20000, 0);
+        let function = |start, name: &str, address| {
+            let code = AbstractCode::new(CodeKind::INTERPRETED_FUNCTION, start, 0x100);
+            let shared = SharedFunctionInfo { address, debug_name: name.to_string(), script: None };
+            logger.code_create_event_shared(CodeTag::kFunction, &code, &shared, "a.js", 1, 1);
+        };
+        function(0x1000, "main", 0xa000);
+        function(0x2000, "foo", 0xb000);
+        function(0x3000, "dead", 0xc000);
+        logger.code_delete_event(0x3000);
+        logger.code_move_event(0x2000, 0x4000);
+
+        let tick = |pc: usize, state, frames: &[usize]| {
+            let mut sample = TickSample::new();
+            sample.pc = pc as *mut _;
+            sample.state = state;
+            for (i, frame) in frames.iter().enumerate() {
+                sample.stack[i] = *frame as *mut _;
+            }
+            sample.frames_count = frames.len() as u16;
+            logger.tick_event(&sample, false);
+        };
+        for _ in 0..6 {
+            tick(0x4010, StateTag::JS, &[0x1010]);
+        }
+        tick(0x1020, StateTag::GC, &[]);
+        tick(0x10100, StateTag::EXTERNAL, &[0x4010, 0x1010]);
+        tick(0x3010, StateTag::JS, &[]);
+        tick(0x1030, StateTag::JS, &[]);
+        let log = String::from_utf8(logger.tear_down().unwrap()).unwrap();
+
+        let mut processor = TickProcessor::new(None);
+        processor.process_log(&log);
+        assert_eq!(processor.ticks(), TickCounts { total: 10, unaccounted: 1, excluded: 0, gc: 1 });
+        let output = processor.print_statistics("v8.log");
+        assert!(output.starts_with("Statistical profiling result from v8.log, (10 ticks, 1 unaccounted, 0 excluded)."));
+        for line in [
+            "      1   10.0%          /lib/libc.so.6",
+            "      6   60.0%   66.7%  JS: ~foo a.js:1:1",
+            "      2   20.0%   22.2%  JS: ~main a.js:1:1",
+            "      8   80.0%   88.9%  JavaScript",
+            "      1   10.0%   11.1%  GC",
+            "      1   10.0%          Unaccounted",
+            // Bottom up: foo is only called by main.
+            "      6   60.0%  JS: ~foo a.js:1:1",
+            "      6  100.0%    JS: ~main a.js:1:1",
+            // Top down: main calls foo and libc.
+            "      9   90.0%      2   20.0%  JS: ~main a.js:1:1",
+            "      7   70.0%      6   60.0%    JS: ~foo a.js:1:1",
+            "      1   10.0%      1   10.0%      /lib/libc.so.6",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing {line:?} in\n{output}");
+        }
+    }
+}
@@ -2,26 +2,38 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod tracing_flags {
-    use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicU32, Ordering};
 
-    /// A struct to hold tracing flags.  Mimics the C++ class of the same name.
-    pub struct TracingFlags {}
+/// Statistics that can be enabled both by V8 flags and by tracing. The values
+/// are TracingCategoryObserver modes, 0 when disabled.
+pub struct TracingFlags;
 
-    impl TracingFlags {
-        /// Runtime statistics tracing flag.
-        pub static runtime_stats: AtomicU32 = AtomicU32::new(0);
+/// --runtime-call-stats, or the disabled-by-default-v8.runtime_stats trace
+/// categories.
+pub static RUNTIME_STATS: AtomicU32 = AtomicU32::new(0);
+pub static GC: AtomicU32 = AtomicU32::new(0);
+pub static GC_STATS: AtomicU32 = AtomicU32::new(0);
+pub static IC_STATS: AtomicU32 = AtomicU32::new(0);
+pub static ZONE_STATS: AtomicU32 = AtomicU32::new(0);
 
-        /// Garbage collection tracing flag.
-        pub static gc: AtomicU32 = AtomicU32::new(0);
+impl TracingFlags {
+    pub fn is_runtime_stats_enabled() -> bool {
+        RUNTIME_STATS.load(Ordering::Relaxed) != 0
+    }
 
-        /// Garbage collection statistics tracing flag.
-        pub static gc_stats: AtomicU32 = AtomicU32::new(0);
+    pub fn is_gc_enabled() -> bool {
+        GC.load(Ordering::Relaxed) != 0
+    }
 
-        /// Inline cache statistics tracing flag.
-        pub static ic_stats: AtomicU32 = AtomicU32::new(0);
+    pub fn is_gc_stats_enabled() -> bool {
+        GC_STATS.load(Ordering::Relaxed) != 0
+    }
+
+    pub fn is_ic_stats_enabled() -> bool {
+        IC_STATS.load(Ordering::Relaxed) != 0
+    }
 
-        /// Zone statistics tracing flag.
-        pub static zone_stats: AtomicU32 = AtomicU32::new(0);
+    pub fn is_zone_stats_enabled() -> bool {
+        ZONE_STATS.load(Ordering::Relaxed) != 0
     }
-}
\ No newline at end of file
+}
@@ -6,10 +6,14 @@ pub mod conversions {
     use std::string::String;
     use std::{f32, f64};
     use std::{fmt, mem};
-    use std::num::ParseFloatError;
     use std::ptr;
     use std::borrow::Cow;
 
+    use crate::base::numbers::double::double::Double;
+    use crate::base::numbers::dtoa::dtoa::{double_to_ascii, DtoaMode, K_BASE10_MAXIMAL_LENGTH};
+    use crate::base::numbers::strtod::strtod::strtod;
+    use crate::numbers::integer_literal::integer_literal::IntegerLiteral;
+
     //use crate::bigint::BigInt; // Assuming BigInt is defined in bigint.rs
     //use crate::isolate::Isolate; // Assuming Isolate is defined in isolate.rs
 
@@ -40,13 +44,13 @@ pub mod conversions {
     // sign bit, but that bit is implicitly cut off when assigning the 64-bit double
     // to a 16-bit output.
     pub const K_FP64_TO16_REBIAS_EXPONENT_AND_ROUND: u64 =
-        ((15u64 - K_FP64_EXPONENT_BIAS) << K_FP64_MANTISSA_BITS) +
-        K_FP64_TO16_ROUNDING_ADDEND;
+        (15u64.wrapping_sub(K_FP64_EXPONENT_BIAS) << K_FP64_MANTISSA_BITS)
+            .wrapping_add(K_FP64_TO16_ROUNDING_ADDEND);
     // A magic value that aligns 10 mantissa bits at the bottom of the double when
     // added to a double using floating point addition. Depends on floating point
     // addition being round-to-nearest-even.
     pub const K_FP64_TO16_DENORMAL_MAGIC: u64 =
-        (K_FP16_MIN_EXPONENT + (K_FP64_MANTISSA_BITS - K_FP16_MANTISSA_BITS))
+        (K_FP16_MIN_EXPONENT + (K_FP64_MANTISSA_BITS - K_FP16_MANTISSA_BITS) as u64)
         << K_FP64_MANTISSA_BITS;
 
     pub const K_FP32_WITHOUT_SIGN_MASK: u32 = 0x7fffffff;
@@ -123,12 +127,71 @@ pub mod conversions {
 
     // This function should match the exact semantics of truncating x to
     // IEEE 754-2019 binary16 format using roundTiesToEven mode.
+    // Returns the bit pattern of the result.
     pub fn double_to_float16(x: f64) -> u16 {
-        // Implementation detail needed
-        let bits: u64 = x.to_bits();
+        let mut bits = x.to_bits();
+        let sign = bits & K_FP64_SIGN_MASK;
+        bits ^= sign;
+
+        let out = if bits >= K_FP16_INFINITY_AND_NAN_INFIMUM {
+            // Result is infinity or NaN.
+            if bits > K_FP64_INFINITY {
+                K_FP16_Q_NAN
+            } else {
+                K_FP16_INFINITY
+            }
+        } else if bits < K_FP16_DENORMAL_THRESHOLD {
+            // Result is a denormal or zero. Use the magic value and FP addition
+            // to align 10 mantissa bits at the bottom of the float. Depends on
+            // FP addition being round-to-nearest-even.
+            let temp = f64::from_bits(bits) + f64::from_bits(K_FP64_TO16_DENORMAL_MAGIC);
+            (temp.to_bits() - K_FP64_TO16_DENORMAL_MAGIC) as u16
+        } else {
+            // Result is not a denormal.
+            // Remember if the result mantissa will be odd before rounding.
+            let mant_odd = (bits >> (K_FP64_MANTISSA_BITS - K_FP16_MANTISSA_BITS)) & 1;
+            // Update the exponent and round to nearest even.
+            // Rounding to nearest even is handled in two parts. First, adding
+            // K_FP64_TO16_REBIAS_EXPONENT_AND_ROUND has the effect of rebiasing
+            // the exponent and that if any of the lower 41 bits of the mantissa
+            // are set, the 11th mantissa bit from the front becomes set. Second,
+            // adding mant_odd ensures ties are rounded to even. A result that
+            // rounds past the largest finite value carries into the exponent
+            // and becomes infinity.
+            bits = bits.wrapping_add(K_FP64_TO16_REBIAS_EXPONENT_AND_ROUND);
+            bits = bits.wrapping_add(mant_odd);
+            (bits >> (K_FP64_MANTISSA_BITS - K_FP16_MANTISSA_BITS)) as u16
+        };
+        out | (sign >> 48) as u16
+    }
+
+    // Converts the bit pattern of an IEEE 754-2019 binary16 value to a double.
+    // The conversion is exact; NaN payloads are preserved.
+    pub fn float16_to_double(x: u16) -> f64 {
+        const K_FP16_EXPONENT_MASK: u16 = 0x7c00;
+        const K_FP16_MANTISSA_MASK: u16 = 0x03ff;
+        let sign = ((x & 0x8000) as u64) << 48;
+        let exponent = x & K_FP16_EXPONENT_MASK;
+        let mantissa = (x & K_FP16_MANTISSA_MASK) as u64;
+        let shifted_mantissa = mantissa << (K_FP64_MANTISSA_BITS - K_FP16_MANTISSA_BITS);
+        let magnitude = if exponent == K_FP16_EXPONENT_MASK {
+            // Infinity or NaN.
+            K_FP64_INFINITY | shifted_mantissa
+        } else if exponent == 0 {
+            // Zero or denormal: mantissa * 2^-24, exact in double precision.
+            (mantissa as f64 * f64::from_bits((K_FP64_EXPONENT_BIAS - 24) << K_FP64_MANTISSA_BITS)).to_bits()
+        } else {
+            let rebiased_exponent =
+                (exponent >> K_FP16_MANTISSA_BITS) as u64 + K_FP64_EXPONENT_BIAS - 15;
+            (rebiased_exponent << K_FP64_MANTISSA_BITS) | shifted_mantissa
+        };
+        f64::from_bits(sign | magnitude)
+    }
 
-        let rebiased_bits = bits.wrapping_add(K_FP64_TO16_REBIAS_EXPONENT_AND_ROUND);
-        rebiased_bits as u16
+    // Implements Math.f16round (ECMA-262 21.3.2.17): rounds x to the nearest
+    // binary16 value, ties to even.
+    pub fn math_f16round(x: f64) -> f64 {
+        float16_to_double(double_to_float16(x))
     }
 
     // This function should match the exact semantics of ECMA-262 9.4.
@@ -243,224 +306,1311 @@ pub mod conversions {
         }
     }
 
-    // Converts a string into a double value according to ECMA-262 9.3.1
-    pub fn string_to_double(str: &[u8], flag: ConversionFlag, empty_string_val: f64) -> f64 {
-        if str.is_empty() {
-            return empty_string_val;
+    /// A code unit of a string the string-to-number conversions can read:
+    /// `u8` for one-byte (Latin-1) strings and `u16` for two-byte (UTF-16)
+    /// strings.
+    pub trait NumberChar: Copy {
+        fn code_unit(self) -> u32;
+    }
+
+    impl NumberChar for u8 {
+        #[inline]
+        fn code_unit(self) -> u32 {
+            self as u32
         }
+    }
 
-        let s = String::from_utf8_lossy(str);
-        string_to_double_utf16(&s, flag, empty_string_val)
+    impl NumberChar for u16 {
+        #[inline]
+        fn code_unit(self) -> u32 {
+            self as u32
+        }
     }
 
-    pub fn string_to_double_utf16(str: &str, flag: ConversionFlag, empty_string_val: f64) -> f64 {
-        if str.is_empty() {
-            return empty_string_val;
+    // Maximum number of significant digits in decimal representation.
+    // The longest possible double in decimal representation is
+    // (2^53 - 1) * 2 ^ -1074 that is (2 ^ 53 - 1) * 5 ^ 1074 / 10 ^ 1074
+    // (768 digits). If we parse a number whose first digits are equal to a
+    // mean of 2 adjacent doubles (that could have up to 769 digits) the result
+    // must be rounded to the bigger one unless the tail consists of zeros, so
+    // we don't need to preserve all the digits.
+    const K_MAX_SIGNIFICANT_DIGITS: usize = 772;
+
+    // Numbers with more decimal digits than this are always Infinity, so
+    // parseInt only keeps this many (plus one to get the rounding right).
+    const K_MAX_SIGNIFICANT_PARSE_INT_DIGITS: usize = 309;
+
+    const K_INFINITY_STRING: &[u8] = b"Infinity";
+
+    // The StrWhiteSpaceChar production of ECMA-262 7.1.4.1: WhiteSpace
+    // (including every Zs code point) and LineTerminator.
+    fn is_white_space_or_line_terminator(c: u32) -> bool {
+        matches!(
+            c,
+            0x0009..=0x000D
+                | 0x0020
+                | 0x00A0
+                | 0x1680
+                | 0x2000..=0x200A
+                | 0x2028
+                | 0x2029
+                | 0x202F
+                | 0x205F
+                | 0x3000
+                | 0xFEFF
+        )
+    }
+
+    // Advances |pos| past any whitespace. Returns true if a non-whitespace
+    // character remains.
+    fn advance_to_non_space<C: NumberChar>(str: &[C], pos: &mut usize) -> bool {
+        while *pos < str.len() {
+            if !is_white_space_or_line_terminator(str[*pos].code_unit()) {
+                return true;
+            }
+            *pos += 1;
+        }
+        false
+    }
+
+    // Returns the value of |c| as a digit in |radix|, if it is one.
+    fn digit_value(c: u32, radix: u32) -> Option<u32> {
+        let digit = match c {
+            0x30..=0x39 => c - 0x30,
+            0x61..=0x7A => c - 0x61 + 10,
+            0x41..=0x5A => c - 0x41 + 10,
+            _ => return None,
+        };
+        (digit < radix).then_some(digit)
+    }
+
+    fn is_decimal_digit(c: u32) -> bool {
+        (0x30..=0x39).contains(&c)
+    }
+
+    fn char_at<C: NumberChar>(str: &[C], pos: usize) -> u32 {
+        str.get(pos).map_or(0, |c| c.code_unit())
+    }
+
+    // Matches |expected| at |pos| and advances past it on success.
+    fn sub_string_equals<C: NumberChar>(str: &[C], pos: &mut usize, expected: &[u8]) -> bool {
+        let matches = str.len() - *pos >= expected.len()
+            && expected
+                .iter()
+                .enumerate()
+                .all(|(i, &e)| str[*pos + i].code_unit() == e as u32);
+        if matches {
+            *pos += expected.len();
+        }
+        matches
+    }
+
+    // Returns log2 of the radix selected by a `0x`, `0o` or `0b` prefix at
+    // |pos|, or None if there is no such prefix.
+    fn radix_prefix_log_2<C: NumberChar>(str: &[C], pos: usize) -> Option<u32> {
+        if char_at(str, pos) != '0' as u32 {
+            return None;
+        }
+        match char_at(str, pos + 1) | 0x20 {
+            0x78 => Some(4), // 'x'
+            0x6F => Some(3), // 'o'
+            0x62 => Some(1), // 'b'
+            _ => None,
         }
+    }
 
-        let mut s = str.trim();
+    fn signed_zero(negative: bool) -> f64 {
+        if negative {
+            -0.0
+        } else {
+            0.0
+        }
+    }
+
+    // Parsing integers with radix 2, 4, 8, 16, 32. Assumes the current
+    // position is not at the end and points at the first digit (or a
+    // leading zero). The result is correctly rounded.
+    fn internal_string_to_int_double<C: NumberChar>(
+        str: &[C],
+        mut pos: usize,
+        radix_log_2: u32,
+        negative: bool,
+        allow_trailing_junk: bool,
+    ) -> f64 {
+        debug_assert!(pos < str.len());
+        let end = str.len();
+        // Skip leading 0s.
+        while str[pos].code_unit() == '0' as u32 {
+            pos += 1;
+            if pos == end {
+                return signed_zero(negative);
+            }
+        }
 
-        if s.is_empty() {
+        let radix = 1u32 << radix_log_2;
+        let mut number: i64 = 0;
+        let mut exponent: i32 = 0;
+        while pos < end {
+            let Some(digit) = digit_value(str[pos].code_unit(), radix) else {
+                if !allow_trailing_junk && advance_to_non_space(str, &mut pos) {
+                    return f64::NAN;
+                }
+                break;
+            };
+
+            number = number * radix as i64 + digit as i64;
+            let mut overflow = (number >> 53) as i32;
+            if overflow != 0 {
+                // Overflow occurred. Need to determine which direction to round
+                // the result.
+                let mut overflow_bits_count = 1;
+                while overflow > 1 {
+                    overflow_bits_count += 1;
+                    overflow >>= 1;
+                }
+
+                let dropped_bits_mask = (1i64 << overflow_bits_count) - 1;
+                let dropped_bits = number & dropped_bits_mask;
+                number >>= overflow_bits_count;
+                exponent = overflow_bits_count;
+
+                let mut zero_tail = true;
+                pos += 1;
+                while pos < end {
+                    let Some(digit) = digit_value(str[pos].code_unit(), radix) else {
+                        break;
+                    };
+                    zero_tail = zero_tail && digit == 0;
+                    exponent = exponent.saturating_add(radix_log_2 as i32);
+                    pos += 1;
+                }
+
+                if !allow_trailing_junk && advance_to_non_space(str, &mut pos) {
+                    return f64::NAN;
+                }
+
+                let middle_value = 1i64 << (overflow_bits_count - 1);
+                if dropped_bits > middle_value {
+                    number += 1; // Rounding up.
+                } else if dropped_bits == middle_value {
+                    // Rounding to even to consistency with decimals: half-way
+                    // case rounds up if significant part is odd and down
+                    // otherwise.
+                    if (number & 1) != 0 || !zero_tail {
+                        number += 1; // Rounding up.
+                    }
+                }
+
+                // Rounding up may cause overflow.
+                if (number & (1i64 << 53)) != 0 {
+                    exponent += 1;
+                    number >>= 1;
+                }
+                break;
+            }
+            pos += 1;
+        }
+
+        debug_assert!(number < (1i64 << 53));
+        // Both factors are exact, so the product is correctly rounded (or
+        // overflows to Infinity).
+        let magnitude = number as f64 * 2f64.powi(exponent.min(2 * 1024));
+        if negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    // Converts a string to a double value. Assumes the string is not
+    // empty. The conversions of ToNumber (NonDecimalPrefix, empty string is
+    // 0) and parseFloat (TrailingJunk, empty string is NaN) differ only in
+    // |flag| and |empty_string_val|.
+    fn internal_string_to_double<C: NumberChar>(
+        str: &[C],
+        flag: ConversionFlag,
+        empty_string_val: f64,
+    ) -> f64 {
+        let allow_trailing_junk = flag == ConversionFlag::AllowTrailingJunk;
+        let end = str.len();
+        let mut pos = 0;
+
+        if !advance_to_non_space(str, &mut pos) {
             return empty_string_val;
         }
 
-        let (radix, s) = match flag {
-            ConversionFlag::AllowNonDecimalPrefix => {
-                if s.starts_with("0x") || s.starts_with("0X") {
-                    (16, &s[2..])
-                } else if s.starts_with("0o") || s.starts_with("0O") {
-                    (8, &s[2..])
-                } else if s.starts_with("0b") || s.starts_with("0B") {
-                    (2, &s[2..])
+        // The longest form of simplified number is: "-<significant digits>'.1eXXX\0".
+        let mut buffer = [0u8; K_MAX_SIGNIFICANT_DIGITS + 1];
+        let mut buffer_pos = 0;
+
+        // Exponent will be adjusted if insignificant digits of the integer part
+        // or insignificant leading zeros of the fractional part are dropped.
+        let mut exponent: i32 = 0;
+        let mut significant_digits = 0;
+        let mut insignificant_digits: i32 = 0;
+        let mut nonzero_digit_dropped = false;
+
+        let mut has_sign = false;
+        let mut negative = false;
+        match char_at(str, pos) {
+            0x2B => {
+                // '+'
+                pos += 1;
+                has_sign = true;
+                if pos == end {
+                    return f64::NAN;
+                }
+            }
+            0x2D => {
+                // '-'
+                pos += 1;
+                has_sign = true;
+                negative = true;
+                if pos == end {
+                    return f64::NAN;
+                }
+            }
+            _ => {}
+        }
+
+        if char_at(str, pos) == 'I' as u32 {
+            if !sub_string_equals(str, &mut pos, K_INFINITY_STRING) {
+                return f64::NAN;
+            }
+            if !allow_trailing_junk && advance_to_non_space(str, &mut pos) {
+                return f64::NAN;
+            }
+            return if negative { f64::NEG_INFINITY } else { f64::INFINITY };
+        }
+
+        if flag == ConversionFlag::AllowNonDecimalPrefix {
+            if let Some(radix_log_2) = radix_prefix_log_2(str, pos) {
+                pos += 2;
+                // "0x" without digits, or a sign in front of the prefix.
+                if has_sign || digit_value(char_at(str, pos), 1 << radix_log_2).is_none() {
+                    return f64::NAN;
+                }
+                return internal_string_to_int_double(str, pos, radix_log_2, false, false);
+            }
+        }
+
+        let mut leading_zero = false;
+        while char_at(str, pos) == '0' as u32 {
+            leading_zero = true;
+            pos += 1;
+            if pos == end {
+                return signed_zero(negative);
+            }
+        }
+
+        'parsing_done: {
+            // Copy significant digits of the integer part (if any) to the buffer.
+            while is_decimal_digit(char_at(str, pos)) {
+                let digit = str[pos].code_unit() as u8;
+                if significant_digits < K_MAX_SIGNIFICANT_DIGITS {
+                    buffer[buffer_pos] = digit;
+                    buffer_pos += 1;
+                    significant_digits += 1;
                 } else {
-                    (10, s)
+                    insignificant_digits = insignificant_digits.saturating_add(1);
+                    nonzero_digit_dropped = nonzero_digit_dropped || digit != b'0';
+                }
+                pos += 1;
+                if pos == end {
+                    break 'parsing_done;
                 }
             }
-            _ => (10, s),
-        };
 
-        let result = if radix == 10 {
-            s.parse::<f64>()
-        } else {
-            let without_junk = match flag {
-                ConversionFlag::AllowTrailingJunk => {
-                    let mut end = s.len();
-                    for (i, c) in s.chars().enumerate().rev() {
-                        let valid = match radix {
-                            2 => c >= '0' && c <= '1',
-                            8 => c >= '0' && c <= '7',
-                            16 => c.is_digit(16),
-                            _ => true, // Should not happen
-                        };
-                        if !valid {
-                            end = i + 1;
-                            break;
+            if char_at(str, pos) == '.' as u32 {
+                pos += 1;
+                if pos == end {
+                    if significant_digits == 0 && !leading_zero {
+                        return f64::NAN;
+                    }
+                    break 'parsing_done;
+                }
+
+                if significant_digits == 0 {
+                    // Integer part consists of 0 or is absent. Significant
+                    // digits start after leading zeros (if any).
+                    while char_at(str, pos) == '0' as u32 {
+                        pos += 1;
+                        if pos == end {
+                            return signed_zero(negative);
                         }
+                        exponent -= 1; // Move this 0 into the exponent.
                     }
-                    &s[..end]
                 }
-                _ => s
-            };
 
-            let value = u64::from_str_radix(without_junk, radix)
-                        .map(|x| x as f64);
-            match value {
-                Ok(v) => Ok(v),
-                Err(_) => Err(ParseFloatError::Invalid)
+                // There is a fractional part. We don't emit a '.', but adjust
+                // the exponent instead.
+                while is_decimal_digit(char_at(str, pos)) {
+                    let digit = str[pos].code_unit() as u8;
+                    if significant_digits < K_MAX_SIGNIFICANT_DIGITS {
+                        buffer[buffer_pos] = digit;
+                        buffer_pos += 1;
+                        significant_digits += 1;
+                        exponent -= 1;
+                    } else {
+                        // Ignore insignificant digits in the fractional part.
+                        nonzero_digit_dropped = nonzero_digit_dropped || digit != b'0';
+                    }
+                    pos += 1;
+                    if pos == end {
+                        break 'parsing_done;
+                    }
+                }
             }
 
-        };
+            if !leading_zero && exponent == 0 && significant_digits == 0 {
+                // If leading_zero is true then the string contains zeros.
+                // If exponent < 0 then string was [+-]\.0*...
+                // If significant_digits != 0 the string is not equal to 0.
+                // Otherwise there are no digits in the string.
+                return f64::NAN;
+            }
 
-        match result {
-            Ok(v) => v,
-            Err(_) => {
-                if s.to_lowercase() == "infinity" || s == "+infinity" {
-                    f64::INFINITY
-                } else if s == "-infinity" {
-                    f64::NEG_INFINITY
-                } else if s == "nan" {
-                    f64::NAN
-                } else if flag == ConversionFlag::AllowTrailingJunk {
-                    f64::NAN
-                } else {
-                    f64::NAN
+            // Parse exponential part.
+            if char_at(str, pos) | 0x20 == 'e' as u32 {
+                pos += 1;
+                let mut exponent_negative = false;
+                match char_at(str, pos) {
+                    0x2B => pos += 1,
+                    0x2D => {
+                        exponent_negative = true;
+                        pos += 1;
+                    }
+                    _ => {}
+                }
+
+                if !is_decimal_digit(char_at(str, pos)) {
+                    if allow_trailing_junk {
+                        break 'parsing_done;
+                    }
+                    return f64::NAN;
+                }
+
+                const K_MAX_EXPONENT: i32 = i32::MAX / 2;
+                let mut num: i32 = 0;
+                while is_decimal_digit(char_at(str, pos)) {
+                    let digit = (str[pos].code_unit() - '0' as u32) as i32;
+                    num = if num >= K_MAX_EXPONENT / 10
+                        && !(num == K_MAX_EXPONENT / 10 && digit <= K_MAX_EXPONENT % 10)
+                    {
+                        K_MAX_EXPONENT
+                    } else {
+                        num * 10 + digit
+                    };
+                    pos += 1;
                 }
+                exponent += if exponent_negative { -num } else { num };
             }
+
+            if !allow_trailing_junk && advance_to_non_space(str, &mut pos) {
+                return f64::NAN;
+            }
+        }
+
+        exponent = exponent.saturating_add(insignificant_digits);
+
+        let mut digits = buffer_pos;
+        let mut result_buffer = [0u8; K_MAX_SIGNIFICANT_DIGITS + 1];
+        result_buffer[..digits].copy_from_slice(&buffer[..digits]);
+        if nonzero_digit_dropped {
+            result_buffer[digits] = b'1';
+            digits += 1;
+            exponent -= 1;
+        }
+
+        let converted = strtod(&result_buffer[..digits], exponent);
+        if negative {
+            -converted
+        } else {
+            converted
         }
     }
 
-    // This version expects a zero-terminated character array.
+    /// Converts a string into a double value according to ECMA-262 9.3.1.
+    /// Works on one-byte (`&[u8]`, Latin-1) and two-byte (`&[u16]`, UTF-16)
+    /// strings alike. `Number(string)` is
+    /// `string_to_double(s, ConversionFlag::AllowNonDecimalPrefix, 0.0)`.
+    pub fn string_to_double<C: NumberChar>(
+        str: &[C],
+        flag: ConversionFlag,
+        empty_string_val: f64,
+    ) -> f64 {
+        internal_string_to_double(str, flag, empty_string_val)
+    }
+
+    /// Same as `string_to_double`, for a Rust string slice.
     pub fn string_to_double_cstr(str: &str, flag: ConversionFlag, empty_string_val: f64) -> f64 {
-        string_to_double(str.as_bytes(), flag, empty_string_val)
+        if str.is_ascii() {
+            string_to_double(str.as_bytes(), flag, empty_string_val)
+        } else {
+            let utf16: Vec<u16> = str.encode_utf16().collect();
+            string_to_double(&utf16, flag, empty_string_val)
+        }
     }
 
-    // Converts a binary string (of the form `0b[0-1]*`) into a double value
-    // according to https://tc39.es/ecma262/#sec-numericvalue
-    pub fn binary_string_to_double(str: &[u8]) -> f64 {
-        let s = String::from_utf8_lossy(str);
-        if s.starts_with("0b") || s.starts_with("0B") {
-            let without_prefix = &s[2..];
-            if without_prefix.is_empty() {
-                return f64::NAN;
+    /// Implements the global `parseFloat(string)` (ECMA-262 19.2.4) on the
+    /// already stringified argument: leading whitespace is skipped and the
+    /// longest prefix that is a StrDecimalLiteral is converted. Returns NaN if
+    /// there is no such prefix.
+    pub fn parse_float<C: NumberChar>(str: &[C]) -> f64 {
+        internal_string_to_double(str, ConversionFlag::AllowTrailingJunk, f64::NAN)
+    }
+
+    // Converts the decimal digits at |pos| for parseInt. Every number with
+    // more than K_MAX_SIGNIFICANT_PARSE_INT_DIGITS digits is Infinity, so the
+    // remaining digits are dropped.
+    fn parse_int_base_ten<C: NumberChar>(str: &[C], mut pos: usize) -> f64 {
+        let mut buffer = [0u8; K_MAX_SIGNIFICANT_PARSE_INT_DIGITS + 1];
+        let mut buffer_pos = 0;
+        while is_decimal_digit(char_at(str, pos)) {
+            if buffer_pos <= K_MAX_SIGNIFICANT_PARSE_INT_DIGITS {
+                // If the number has more than K_MAX_SIGNIFICANT_PARSE_INT_DIGITS
+                // digits it will be parsed as infinity.
+                buffer[buffer_pos] = str[pos].code_unit() as u8;
+                buffer_pos += 1;
             }
-            match u64::from_str_radix(without_prefix, 2) {
-                Ok(value) => value as f64,
-                Err(_) => f64::NAN,
+            pos += 1;
+        }
+        strtod(&buffer[..buffer_pos], 0)
+    }
+
+    // Converts the digits at |pos| for parseInt with a radix that is neither
+    // a power of two nor 10.
+    // The following code causes accumulating rounding error for numbers
+    // greater than ~2^56. It's explicitly allowed in the spec ("if R is not 2,
+    // 4, 8, 10, 16, or 32, then mathInt may be an implementation-dependent
+    // approximation to the mathematical integer value").
+    fn parse_int_generic_radix<C: NumberChar>(str: &[C], mut pos: usize, radix: u32) -> f64 {
+        // The value of each part is computed with 32-bit multiply-and-add as
+        // long as possible to avoid losing precision.
+        const K_MAXIMUM_MULTIPLIER: u32 = 0xFFFF_FFFF / 36;
+        let mut result = 0.0;
+        let mut done = false;
+        while !done {
+            // Parse the longest part of the string starting at |pos| possible
+            // while keeping the multiplier, and thus the part itself, within
+            // 32 bits.
+            let mut part: u32 = 0;
+            let mut multiplier: u32 = 1;
+            loop {
+                let Some(digit) = digit_value(char_at(str, pos), radix) else {
+                    done = true;
+                    break;
+                };
+                // Update the value of the part as long as the multiplier fits
+                // in 32 bits. When we can't guarantee that the next iteration
+                // will not overflow the multiplier, we stop parsing the part
+                // by leaving the loop.
+                let m = multiplier * radix;
+                if m > K_MAXIMUM_MULTIPLIER {
+                    break;
+                }
+                part = part * radix + digit;
+                multiplier = m;
+                debug_assert!(multiplier > part);
+                pos += 1;
             }
-        } else {
-            f64::NAN
+            result = result * multiplier as f64 + part as f64;
         }
+        result
     }
 
-    // Converts an octal string (of the form `0o[0-8]*`) into a double value
-    // according to https://tc39.es/ecma262/#sec-numericvalue
-    pub fn octal_string_to_double(str: &[u8]) -> f64 {
-        let s = String::from_utf8_lossy(str);
-        if s.starts_with("0o") || s.starts_with("0O") {
-            let without_prefix = &s[2..];
-            if without_prefix.is_empty() {
-                return f64::NAN;
+    /// Implements the global `parseInt(string, radix)` (ECMA-262 19.2.5) on
+    /// the already stringified argument. `radix` is the radix argument after
+    /// ToInt32; 0 selects radix 10, or 16 for a `0x` prefix. Results for
+    /// radixes that are not a power of two or 10 may be approximated, as the
+    /// spec allows.
+    pub fn string_to_int<C: NumberChar>(str: &[C], radix: i32) -> f64 {
+        let end = str.len();
+        let mut pos = 0;
+        if !advance_to_non_space(str, &mut pos) {
+            return f64::NAN;
+        }
+
+        let mut negative = false;
+        match char_at(str, pos) {
+            0x2D => {
+                // '-'
+                negative = true;
+                pos += 1;
             }
-            match u64::from_str_radix(without_prefix, 8) {
-                Ok(value) => value as f64,
-                Err(_) => f64::NAN,
+            0x2B => pos += 1, // '+'
+            _ => {}
+        }
+        if pos == end {
+            return f64::NAN;
+        }
+
+        let mut radix = radix;
+        let mut leading_zero = false;
+        if radix == 0 || radix == 16 {
+            if radix == 0 {
+                // Radix detection.
+                radix = 10;
             }
+            if char_at(str, pos) == '0' as u32 {
+                pos += 1;
+                if pos == end {
+                    return signed_zero(negative);
+                }
+                if char_at(str, pos) | 0x20 == 'x' as u32 {
+                    radix = 16;
+                    pos += 1;
+                    if pos == end {
+                        return f64::NAN;
+                    }
+                } else {
+                    leading_zero = true;
+                }
+            }
+        }
+        if !(2..=36).contains(&radix) {
+            return f64::NAN;
+        }
+        let radix = radix as u32;
+
+        // Skip leading zeros.
+        while char_at(str, pos) == '0' as u32 {
+            leading_zero = true;
+            pos += 1;
+            if pos == end {
+                return signed_zero(negative);
+            }
+        }
+
+        if digit_value(char_at(str, pos), radix).is_none() {
+            return if leading_zero { signed_zero(negative) } else { f64::NAN };
+        }
+
+        if radix.is_power_of_two() {
+            return internal_string_to_int_double(str, pos, radix.trailing_zeros(), negative, true);
+        }
+        let magnitude = if radix == 10 {
+            parse_int_base_ten(str, pos)
         } else {
-            f64::NAN
+            parse_int_generic_radix(str, pos, radix)
+        };
+        if negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    // Validates the numeric separators of a literal and removes them.
+    // A separator is only allowed between two digits of |radix|. Returns
+    // None for a misplaced separator or a character outside ASCII.
+    fn strip_numeric_separators<C: NumberChar>(literal: &[C], radix: u32) -> Option<Vec<u8>> {
+        let mut result = Vec::with_capacity(literal.len());
+        for (i, c) in literal.iter().enumerate() {
+            let c = c.code_unit();
+            if c >= 0x80 {
+                return None;
+            }
+            if c == '_' as u32 {
+                let before = i.checked_sub(1).map(|j| literal[j].code_unit());
+                let after = literal.get(i + 1).map(|c| c.code_unit());
+                let is_digit = |c: Option<u32>| c.and_then(|c| digit_value(c, radix)).is_some();
+                if !is_digit(before) || !is_digit(after) {
+                    return None;
+                }
+                continue;
+            }
+            result.push(c as u8);
+        }
+        Some(result)
+    }
+
+    // Converts the digits of a `0b`, `0o` or `0x` literal after its prefix.
+    fn prefixed_literal_to_double(digits: &[u8], radix_log_2: u32) -> f64 {
+        let radix = 1 << radix_log_2;
+        if digits.is_empty() || !digits.iter().all(|&c| digit_value(c as u32, radix).is_some()) {
+            return f64::NAN;
+        }
+        internal_string_to_int_double(digits, 0, radix_log_2, false, false)
+    }
+
+    fn radix_literal_to_double<C: NumberChar>(str: &[C], prefix: u8, radix_log_2: u32) -> f64 {
+        if radix_prefix_log_2(str, 0).is_none() || char_at(str, 1) | 0x20 != prefix as u32 {
+            return f64::NAN;
         }
+        match strip_numeric_separators(&str[2..], 1 << radix_log_2) {
+            Some(digits) => prefixed_literal_to_double(&digits, radix_log_2),
+            None => f64::NAN,
+        }
+    }
+
+    /// Converts a binary string (of the form `0b[0-1]*`) into a double value
+    /// according to https://tc39.es/ecma262/#sec-numericvalue
+    pub fn binary_string_to_double<C: NumberChar>(str: &[C]) -> f64 {
+        radix_literal_to_double(str, b'b', 1)
+    }
+
+    /// Converts an octal string (of the form `0o[0-7]*`) into a double value
+    /// according to https://tc39.es/ecma262/#sec-numericvalue
+    pub fn octal_string_to_double<C: NumberChar>(str: &[C]) -> f64 {
+        radix_literal_to_double(str, b'o', 3)
+    }
+
+    /// Converts a hex string (of the form `0x[0-9a-f]*`) into a double value
+    /// according to https://tc39.es/ecma262/#sec-numericvalue
+    pub fn hex_string_to_double<C: NumberChar>(str: &[C]) -> f64 {
+        radix_literal_to_double(str, b'x', 4)
     }
 
-    // Converts a hex string (of the form `0x[0-9a-f]*`) into a double value
-    // according to https://tc39.es/ecma262/#sec-numericvalue
-    pub fn hex_string_to_double(str: &[u8]) -> f64 {
-        let s = String::from_utf8_lossy(str);
-        if s.starts_with("0x") || s.starts_with("0X") {
-            let without_prefix = &s[2..];
-            if without_prefix.is_empty() {
+    /// Converts an implicit octal string (a.k.a. LegacyOctalIntegerLiteral, of
+    /// the form `0[0-7]*`) into a double value according to
+    /// https://tc39.es/ecma262/#sec-numericvalue
+    pub fn implicit_octal_string_to_double<C: NumberChar>(str: &[C]) -> f64 {
+        if char_at(str, 0) != '0' as u32
+            || !str.iter().all(|c| digit_value(c.code_unit(), 8).is_some())
+        {
+            return f64::NAN;
+        }
+        internal_string_to_int_double(str, 0, 3, false, false)
+    }
+
+    /// Converts the source text of a NumericLiteral without a BigInt suffix
+    /// (decimal, `0b`/`0o`/`0x`, legacy octal `017` and non-octal decimal
+    /// `089` forms) to its value. Numeric separators are allowed between
+    /// digits, except in the legacy forms. Returns NaN if |literal| is not a
+    /// valid literal.
+    pub fn numeric_literal_to_double<C: NumberChar>(literal: &[C]) -> f64 {
+        if let Some(radix_log_2) = radix_prefix_log_2(literal, 0) {
+            return match strip_numeric_separators(&literal[2..], 1 << radix_log_2) {
+                Some(digits) => prefixed_literal_to_double(&digits, radix_log_2),
+                None => f64::NAN,
+            };
+        }
+
+        if char_at(literal, 0) == '0' as u32
+            && (is_decimal_digit(char_at(literal, 1)) || char_at(literal, 1) == '_' as u32)
+        {
+            // LegacyOctalIntegerLiteral and NonOctalDecimalIntegerLiteral can't
+            // contain separators. Only the latter may have a fraction or an
+            // exponent.
+            let int_len = literal.iter().take_while(|c| is_decimal_digit(c.code_unit())).count();
+            if literal[..int_len].iter().all(|c| digit_value(c.code_unit(), 8).is_some()) {
+                if int_len != literal.len() {
+                    return f64::NAN;
+                }
+                return implicit_octal_string_to_double(literal);
+            }
+            if literal.iter().any(|c| c.code_unit() == '_' as u32) {
                 return f64::NAN;
             }
-            match u64::from_str_radix(without_prefix, 16) {
-                Ok(value) => value as f64,
-                Err(_) => f64::NAN,
+        }
+
+        let Some(literal) = strip_numeric_separators(literal, 10) else {
+            return f64::NAN;
+        };
+        let is_literal_char =
+            |c: &u8| c.is_ascii_digit() || matches!(c, b'.' | b'e' | b'E' | b'+' | b'-');
+        if literal.is_empty()
+            || !(literal[0].is_ascii_digit() || literal[0] == b'.')
+            || !literal.iter().all(is_literal_char)
+        {
+            return f64::NAN;
+        }
+        internal_string_to_double(&literal, ConversionFlag::NoConversionFlag, f64::NAN)
+    }
+
+    /// A BigInt value produced by the string conversions: the sign and the
+    /// magnitude as little-endian 64-bit digits without leading zero digits.
+    /// Zero has no digits and is never negative.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct ParsedBigInt {
+        negative: bool,
+        digits: Vec<u64>,
+    }
+
+    impl ParsedBigInt {
+        pub fn new(negative: bool, mut digits: Vec<u64>) -> Self {
+            while digits.last() == Some(&0) {
+                digits.pop();
             }
-        } else {
-            f64::NAN
+            let negative = negative && !digits.is_empty();
+            ParsedBigInt { negative, digits }
+        }
+
+        pub fn is_negative(&self) -> bool {
+            self.negative
+        }
+
+        pub fn is_zero(&self) -> bool {
+            self.digits.is_empty()
+        }
+
+        pub fn digits(&self) -> &[u64] {
+            &self.digits
+        }
+
+        /// Returns the value as an `IntegerLiteral` if its magnitude fits in
+        /// 64 bits.
+        pub fn to_integer_literal(&self) -> Option<IntegerLiteral> {
+            match self.digits.as_slice() {
+                [] => Some(IntegerLiteral::new(false, 0)),
+                [digit] => Some(IntegerLiteral::new(self.negative, *digit)),
+                _ => None,
+            }
+        }
+
+        /// Formats the value in base 10, with a leading '-' if negative.
+        pub fn to_decimal_string(&self) -> String {
+            if self.is_zero() {
+                return "0".to_string();
+            }
+            // Peel off 19 decimal digits at a time, least significant first.
+            const K_TEN19: u64 = 10_000_000_000_000_000_000;
+            let mut magnitude = self.digits.clone();
+            let mut chunks = Vec::new();
+            while !magnitude.is_empty() {
+                let mut remainder: u128 = 0;
+                for digit in magnitude.iter_mut().rev() {
+                    let current = (remainder << 64) | *digit as u128;
+                    *digit = (current / K_TEN19 as u128) as u64;
+                    remainder = current % K_TEN19 as u128;
+                }
+                while magnitude.last() == Some(&0) {
+                    magnitude.pop();
+                }
+                chunks.push(remainder as u64);
+            }
+            let mut result = String::with_capacity(chunks.len() * 19 + 1);
+            if self.negative {
+                result.push('-');
+            }
+            let mut chunks = chunks.iter().rev();
+            if let Some(first) = chunks.next() {
+                result.push_str(&first.to_string());
+            }
+            for chunk in chunks {
+                result.push_str(&format!("{:019}", chunk));
+            }
+            result
         }
     }
 
-    // Converts an implicit octal string (a.k.a. LegacyOctalIntegerLiteral, of the
-    // form `0[0-7]*`) into a double value according to
-    // https://tc39.es/ecma262/#sec-numericvalue
-    pub fn implicit_octal_string_to_double(str: &[u8]) -> f64 {
-        let s = String::from_utf8_lossy(str);
-        if s.starts_with('0') && s.len() > 1 {
-            match u64::from_str_radix(&s[1..], 8) {
-                Ok(value) => value as f64,
-                Err(_) => f64::NAN,
+    // magnitude := magnitude * multiplier + addend.
+    fn multiply_add(magnitude: &mut Vec<u64>, multiplier: u64, addend: u64) {
+        let mut carry = addend as u128;
+        for digit in magnitude.iter_mut() {
+            let product = *digit as u128 * multiplier as u128 + carry;
+            *digit = product as u64;
+            carry = product >> 64;
+        }
+        if carry != 0 {
+            magnitude.push(carry as u64);
+        }
+    }
+
+    // Accumulates ASCII |digits|, all valid in |radix|, into a magnitude.
+    fn digits_to_bigint_magnitude(digits: &[u8], radix: u32) -> Vec<u64> {
+        // Digits are combined into a part as long as the multiplier fits in 64
+        // bits, then the part is folded into the magnitude in one step.
+        let mut magnitude: Vec<u64> = Vec::new();
+        let mut part: u64 = 0;
+        let mut multiplier: u64 = 1;
+        for &c in digits {
+            let digit = digit_value(c as u32, radix).expect("digits were validated") as u64;
+            if multiplier > u64::MAX / radix as u64 {
+                multiply_add(&mut magnitude, multiplier, part);
+                part = 0;
+                multiplier = 1;
             }
-        } else {
-            f64::NAN
+            part = part * radix as u64 + digit;
+            multiplier *= radix as u64;
         }
+        multiply_add(&mut magnitude, multiplier, part);
+        magnitude
     }
 
-    // TODO: Implement StringToInt
-    // pub fn string_to_int(isolate: &Isolate, string: &String, radix: i32) -> f64 {
-    //     unimplemented!()
-    // }
+    fn all_digits<C: NumberChar>(str: &[C], radix: u32) -> bool {
+        !str.is_empty() && str.iter().all(|c| digit_value(c.code_unit(), radix).is_some())
+    }
 
-    // TODO: Implement StringToBigInt
-    // pub fn string_to_bigint(isolate: &Isolate, string: &String) -> Option<BigInt> {
-    //     unimplemented!()
-    // }
+    fn narrow_digits<C: NumberChar>(str: &[C]) -> Vec<u8> {
+        str.iter().map(|c| c.code_unit() as u8).collect()
+    }
 
-    // TODO: Implement BigIntLiteral
-    // pub fn bigint_literal<T>(isolate: &T, string: &str) -> Option<BigInt> {
-    //     unimplemented!()
-    // }
+    /// Implements StringToBigInt (ECMA-262 7.1.14), as used by `BigInt(string)`:
+    /// surrounding whitespace is ignored, the empty string is 0n, and the
+    /// string must otherwise be a StringIntegerLiteral (a signed decimal
+    /// integer or an unsigned `0b`/`0o`/`0x` literal, without separators).
+    /// Returns None where the spec throws a SyntaxError.
+    pub fn string_to_bigint<C: NumberChar>(str: &[C]) -> Option<ParsedBigInt> {
+        let mut start = 0;
+        if !advance_to_non_space(str, &mut start) {
+            return Some(ParsedBigInt::default());
+        }
+        let mut end = str.len();
+        while is_white_space_or_line_terminator(str[end - 1].code_unit()) {
+            end -= 1;
+        }
+        let str = &str[start..end];
+
+        if let Some(radix_log_2) = radix_prefix_log_2(str, 0) {
+            let radix = 1 << radix_log_2;
+            if !all_digits(&str[2..], radix) {
+                return None;
+            }
+            let magnitude = digits_to_bigint_magnitude(&narrow_digits(&str[2..]), radix);
+            return Some(ParsedBigInt::new(false, magnitude));
+        }
+
+        let (negative, digits) = match char_at(str, 0) {
+            0x2D => (true, &str[1..]),
+            0x2B => (false, &str[1..]),
+            _ => (false, str),
+        };
+        if !all_digits(digits, 10) {
+            return None;
+        }
+        let magnitude = digits_to_bigint_magnitude(&narrow_digits(digits), 10);
+        Some(ParsedBigInt::new(negative, magnitude))
+    }
+
+    /// Converts the source text of a BigInt literal, without the trailing
+    /// `n`, to its value. Numeric separators are allowed between digits;
+    /// legacy octal forms are not BigInt literals. Returns None if |literal|
+    /// is not a valid literal.
+    pub fn bigint_literal<C: NumberChar>(literal: &[C]) -> Option<ParsedBigInt> {
+        let (radix, digits) = match radix_prefix_log_2(literal, 0) {
+            Some(radix_log_2) => (1 << radix_log_2, &literal[2..]),
+            None => (10, literal),
+        };
+        let digits = strip_numeric_separators(digits, radix)?;
+        if !all_digits(&digits, radix) {
+            return None;
+        }
+        if radix == 10 && digits.len() > 1 && digits[0] == b'0' {
+            return None;
+        }
+        Some(ParsedBigInt::new(false, digits_to_bigint_magnitude(&digits, radix)))
+    }
+
+    /// Converts the source text of a BigInt literal (see `bigint_literal`)
+    /// to its decimal representation.
+    pub fn bigint_literal_to_decimal(literal: &[u8]) -> Option<String> {
+        bigint_literal(literal).map(|value| value.to_decimal_string())
+    }
 
     pub const K_DOUBLE_TO_STRING_MIN_BUFFER_SIZE: i32 = 100;
 
+    const K_RADIX_CHARS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
     // Converts a double to a string value according to ECMA-262 9.8.1.
-    // The buffer should be large enough for any floating point number.
-    // 100 characters is enough.
-    // Note: The returned string_view is not necessarily pointing inside the
-    // provided buffer.
-    pub fn double_to_string_view(value: f64, buffer: &mut [char]) -> String {
-        // Implementation detail needed
-        format!("{}", value)
-    }
+    pub fn double_to_string_view(v: f64) -> String {
+        if v.is_nan() {
+            return "NaN".to_string();
+        }
+        if v.is_infinite() {
+            return if v < 0.0 { "-Infinity" } else { "Infinity" }.to_string();
+        }
+        if v == 0.0 {
+            return "0".to_string();
+        }
+        if is_int32_double(v) {
+            return int_to_string_view(fast_d2i(v));
+        }
 
-    // TODO: Implement BigIntLiteralToDecimal
-    // pub fn bigint_literal_to_decimal(isolate: &Isolate, literal: &[u8]) -> Vec<u8> {
-    //     unimplemented!()
-    // }
+        let mut decimal_rep = [0u8; K_BASE10_MAXIMAL_LENGTH + 1];
+        let mut sign = false;
+        let mut length = 0;
+        let mut decimal_point = 0;
+        double_to_ascii(v, DtoaMode::DTOA_SHORTEST, 0, &mut decimal_rep, &mut sign, &mut length, &mut decimal_point);
+        let digits = &decimal_rep[..length as usize];
 
-    // Convert an int to string value. The returned string is located inside the
-    // buffer, but not necessarily at the start.
-    pub fn int_to_string_view(n: i32, buffer: &mut [char]) -> String {
-        // Implementation detail needed
-        format!("{}", n)
+        let mut builder = String::with_capacity(K_DOUBLE_TO_STRING_MIN_BUFFER_SIZE as usize);
+        if sign {
+            builder.push('-');
+        }
+        if length <= decimal_point && decimal_point <= 21 {
+            // ECMA-262 section 9.8.1 step 6.
+            push_ascii(&mut builder, digits);
+            push_padding(&mut builder, '0', decimal_point - length);
+        } else if 0 < decimal_point && decimal_point <= 21 {
+            // ECMA-262 section 9.8.1 step 7.
+            push_ascii(&mut builder, &digits[..decimal_point as usize]);
+            builder.push('.');
+            push_ascii(&mut builder, &digits[decimal_point as usize..]);
+        } else if decimal_point <= 0 && decimal_point > -6 {
+            // ECMA-262 section 9.8.1 step 8.
+            builder.push_str("0.");
+            push_padding(&mut builder, '0', -decimal_point);
+            push_ascii(&mut builder, digits);
+        } else {
+            // ECMA-262 section 9.8.1 step 9 and 10 combined.
+            builder.push(digits[0] as char);
+            if length != 1 {
+                builder.push('.');
+                push_ascii(&mut builder, &digits[1..]);
+            }
+            builder.push('e');
+            builder.push(if decimal_point >= 0 { '+' } else { '-' });
+            let exponent = (decimal_point - 1).abs();
+            builder.push_str(&exponent.to_string());
+        }
+        builder
+    }
+
+    // Convert an int to string value.
+    pub fn int_to_string_view(n: i32) -> String {
+        // Build the digits from the right using the unsigned magnitude, so
+        // that i32::MIN does not overflow on negation.
+        let mut digits = [0u8; 11];
+        let mut pos = digits.len();
+        let mut magnitude = n.unsigned_abs();
+        loop {
+            pos -= 1;
+            digits[pos] = b'0' + (magnitude % 10) as u8;
+            magnitude /= 10;
+            if magnitude == 0 {
+                break;
+            }
+        }
+        let mut builder = String::with_capacity(digits.len() - pos + 1);
+        if n < 0 {
+            builder.push('-');
+        }
+        push_ascii(&mut builder, &digits[pos..]);
+        builder
     }
 
     // Additional number to string conversions for the number type.
-    pub fn double_to_fixed_string_view(value: f64, f: i32, buffer: &mut [char]) -> String {
-        // Implementation detail needed
-        format!("{:.f$}", value, f = f as usize)
+
+    // Implements Number.prototype.toFixed for a finite value and
+    // 0 <= f <= K_MAX_FRACTION_DIGITS.
+    pub fn double_to_fixed_string_view(value: f64, f: i32) -> String {
+        const K_FIRST_NON_FIXED: f64 = 1e21;
+        debug_assert!(value.is_finite());
+        debug_assert!((0..=K_MAX_FRACTION_DIGITS).contains(&f));
+
+        let negative = value < 0.0;
+        let abs_value = if negative { -value } else { value };
+
+        // If abs_value has more than K_DOUBLE_TO_FIXED_MAX_DIGITS_BEFORE_POINT
+        // digits before the point use the non-fixed conversion routine.
+        if abs_value >= K_FIRST_NON_FIXED {
+            return double_to_string_view(value);
+        }
+
+        // Find a sufficiently precise decimal representation of n.
+        let mut decimal_rep = [0u8; (K_DOUBLE_TO_FIXED_MAX_DIGITS_BEFORE_POINT + K_MAX_FRACTION_DIGITS + 1) as usize];
+        let mut sign = false;
+        let mut decimal_rep_length = 0;
+        let mut decimal_point = 0;
+        double_to_ascii(
+            value,
+            DtoaMode::DTOA_FIXED,
+            f,
+            &mut decimal_rep,
+            &mut sign,
+            &mut decimal_rep_length,
+            &mut decimal_point,
+        );
+
+        // Create a representation that is padded with zeros if needed.
+        let mut zero_prefix_length = 0;
+        let mut zero_postfix_length = 0;
+
+        if decimal_point <= 0 {
+            zero_prefix_length = -decimal_point + 1;
+            decimal_point = 1;
+        }
+
+        if zero_prefix_length + decimal_rep_length < decimal_point + f {
+            zero_postfix_length = decimal_point + f - decimal_rep_length - zero_prefix_length;
+        }
+
+        let mut rep = String::with_capacity((zero_prefix_length + decimal_rep_length + zero_postfix_length) as usize);
+        push_padding(&mut rep, '0', zero_prefix_length);
+        push_ascii(&mut rep, &decimal_rep[..decimal_rep_length as usize]);
+        push_padding(&mut rep, '0', zero_postfix_length);
+
+        // Create the result string by appending a minus and putting in a
+        // decimal point if needed.
+        let mut builder = String::with_capacity((decimal_point + f + 2) as usize);
+        if negative {
+            builder.push('-');
+        }
+        builder.push_str(&rep[..decimal_point as usize]);
+        if f > 0 {
+            builder.push('.');
+            builder.push_str(&rep[decimal_point as usize..(decimal_point + f) as usize]);
+        }
+        builder
     }
 
-    pub fn double_to_exponential_string_view(value: f64, f: i32, buffer: &mut [char]) -> String {
-        // Implementation detail needed
-        format!("{:e}", value)
+    fn create_exponential_representation(
+        decimal_rep: &[u8],
+        mut exponent: i32,
+        negative: bool,
+        significant_digits: i32,
+    ) -> String {
+        let mut negative_exponent = false;
+        if exponent < 0 {
+            negative_exponent = true;
+            exponent = -exponent;
+        }
+
+        // Leave room in the result for appending a minus, for a period, the
+        // letter 'e', a minus or a plus depending on the exponent, and a
+        // three digit exponent.
+        let mut builder = String::with_capacity((significant_digits + 7) as usize);
+        if negative {
+            builder.push('-');
+        }
+        builder.push(decimal_rep[0] as char);
+        if significant_digits != 1 {
+            builder.push('.');
+            push_ascii(&mut builder, &decimal_rep[1..]);
+            debug_assert!(significant_digits as usize >= decimal_rep.len());
+            push_padding(&mut builder, '0', significant_digits - decimal_rep.len() as i32);
+        }
+
+        builder.push('e');
+        builder.push(if negative_exponent { '-' } else { '+' });
+        builder.push_str(&exponent.to_string());
+        builder
     }
 
-    pub fn double_to_precision_string_view(value: f64, f: i32, buffer: &mut [char]) -> String {
-        // Implementation detail needed
-        format!("{:.p$}", value, p = f as usize)
+    // Implements Number.prototype.toExponential for a finite value. f might be
+    // -1 to signal that f was undefined in JavaScript.
+    pub fn double_to_exponential_string_view(mut value: f64, mut f: i32) -> String {
+        debug_assert!(value.is_finite());
+        debug_assert!((-1..=K_MAX_FRACTION_DIGITS).contains(&f));
+
+        let mut negative = false;
+        if value < 0.0 {
+            value = -value;
+            negative = true;
+        }
+
+        // Find a sufficiently precise decimal representation of n.
+        let mut sign = false;
+        let mut decimal_point = 0;
+        // f corresponds to the digits after the point. There is always one
+        // digit before the point. The number of requested_digits equals hence
+        // f + 1. Make sure that the buffer is big enough, even if we fall back
+        // to the shortest representation (which happens when f equals -1).
+        const _: () = assert!(K_BASE10_MAXIMAL_LENGTH as i32 <= K_MAX_FRACTION_DIGITS + 1);
+        let mut decimal_rep = [0u8; (K_MAX_FRACTION_DIGITS + 1) as usize];
+        let mut decimal_rep_length = 0;
+        if f == -1 {
+            double_to_ascii(
+                value,
+                DtoaMode::DTOA_SHORTEST,
+                0,
+                &mut decimal_rep,
+                &mut sign,
+                &mut decimal_rep_length,
+                &mut decimal_point,
+            );
+            f = decimal_rep_length - 1;
+        } else {
+            double_to_ascii(
+                value,
+                DtoaMode::DTOA_PRECISION,
+                f + 1,
+                &mut decimal_rep,
+                &mut sign,
+                &mut decimal_rep_length,
+                &mut decimal_point,
+            );
+        }
+        debug_assert!(decimal_rep_length > 0);
+        debug_assert!(decimal_rep_length <= f + 1);
+
+        let exponent = decimal_point - 1;
+        create_exponential_representation(&decimal_rep[..decimal_rep_length as usize], exponent, negative, f + 1)
     }
 
-    pub fn double_to_radix_string_view(value: f64, radix: i32, buffer: &mut [char]) -> String {
-        // Implementation detail needed
-        format!("{}", value) // Placeholder: Radix conversion not directly supported in Rust format
+    // Implements Number.prototype.toPrecision for a finite value and
+    // 1 <= p <= K_MAX_FRACTION_DIGITS.
+    pub fn double_to_precision_string_view(mut value: f64, p: i32) -> String {
+        debug_assert!(value.is_finite());
+        debug_assert!((1..=K_MAX_FRACTION_DIGITS).contains(&p));
+
+        let mut negative = false;
+        if value < 0.0 {
+            value = -value;
+            negative = true;
+        }
+
+        // Find a sufficiently precise decimal representation of n.
+        let mut sign = false;
+        let mut decimal_point = 0;
+        let mut decimal_rep = [0u8; K_MAX_FRACTION_DIGITS as usize];
+        let mut decimal_rep_length = 0;
+        double_to_ascii(
+            value,
+            DtoaMode::DTOA_PRECISION,
+            p,
+            &mut decimal_rep,
+            &mut sign,
+            &mut decimal_rep_length,
+            &mut decimal_point,
+        );
+        debug_assert!(decimal_rep_length <= p);
+        let digits = &decimal_rep[..decimal_rep_length as usize];
+
+        let exponent = decimal_point - 1;
+
+        if exponent < -6 || exponent >= p {
+            return create_exponential_representation(digits, exponent, negative, p);
+        }
+
+        // Use fixed notation.
+        //
+        // Leave room in the result for appending a minus, a period and in the
+        // case where decimal_point is not positive for a zero in front of the
+        // period.
+        let result_size = if decimal_point <= 0 { -decimal_point + p + 3 } else { p + 2 };
+        let mut builder = String::with_capacity(result_size as usize);
+        if negative {
+            builder.push('-');
+        }
+        if decimal_point <= 0 {
+            builder.push_str("0.");
+            push_padding(&mut builder, '0', -decimal_point);
+            push_ascii(&mut builder, digits);
+            push_padding(&mut builder, '0', p - decimal_rep_length);
+        } else {
+            // Exactly p significant digits are printed, so pad the digits with
+            // zeros and put the period after decimal_point of them.
+            let integer_digits = decimal_point.min(decimal_rep_length) as usize;
+            push_ascii(&mut builder, &digits[..integer_digits]);
+            push_padding(&mut builder, '0', decimal_point - decimal_rep_length);
+            if decimal_point < p {
+                builder.push('.');
+                if decimal_rep_length > decimal_point {
+                    push_ascii(&mut builder, &digits[decimal_point as usize..]);
+                }
+                push_padding(&mut builder, '0', p - decimal_point.max(decimal_rep_length));
+            }
+        }
+        builder
+    }
+
+    // Implements Number.prototype.toString(radix) for 2 <= radix <= 36. The
+    // digits are produced up to the precision of the input double, so that
+    // reading them back yields the input.
+    pub fn double_to_radix_string_view(mut value: f64, radix: i32) -> String {
+        debug_assert!((2..=36).contains(&radix));
+        if value.is_nan() {
+            return "NaN".to_string();
+        }
+        if value.is_infinite() {
+            return if value < 0.0 { "-Infinity" } else { "Infinity" }.to_string();
+        }
+        if value == 0.0 {
+            return "0".to_string();
+        }
+        if radix == 10 {
+            return double_to_string_view(value);
+        }
+
+        // Temporary buffer for the result. We start with the decimal point in
+        // the middle and write to the left for the integer part and to the
+        // right for the fractional part. 1024 characters for the exponent and
+        // 52 for the mantissa either way, with additional space for sign and
+        // decimal point should be sufficient.
+        const K_BUFFER_SIZE: usize = K_DOUBLE_TO_RADIX_MAX_CHARS as usize;
+        let mut buffer = [0u8; K_BUFFER_SIZE];
+        let mut integer_cursor = K_BUFFER_SIZE / 2;
+        let mut fraction_cursor = integer_cursor;
+        let radix_f = radix as f64;
+
+        let negative = value < 0.0;
+        if negative {
+            value = -value;
+        }
+
+        // Split the value into an integer part and a fractional part.
+        let mut integer = value.floor();
+        let mut fraction = value - integer;
+        // We only compute fractional digits up to the input double's precision.
+        let mut delta = 0.5 * (Double::from_f64(value).next_double() - value);
+        delta = delta.max(Double::from_f64(0.0).next_double());
+        debug_assert!(delta > 0.0);
+        if fraction >= delta {
+            // Insert decimal point.
+            buffer[fraction_cursor] = b'.';
+            fraction_cursor += 1;
+            loop {
+                // Shift up by one digit.
+                fraction *= radix_f;
+                delta *= radix_f;
+                // Write digit.
+                let digit = fraction as usize;
+                buffer[fraction_cursor] = K_RADIX_CHARS[digit];
+                fraction_cursor += 1;
+                // Calculate remainder.
+                fraction -= digit as f64;
+                // Round to even.
+                if (fraction > 0.5 || (fraction == 0.5 && (digit & 1) != 0)) && fraction + delta > 1.0 {
+                    // We need to back trace already written digits in case of
+                    // carry-over.
+                    loop {
+                        fraction_cursor -= 1;
+                        if fraction_cursor == K_BUFFER_SIZE / 2 {
+                            assert_eq!(buffer[fraction_cursor], b'.');
+                            // Carry over to the integer part.
+                            integer += 1.0;
+                            break;
+                        }
+                        let c = buffer[fraction_cursor];
+                        // Reconstruct digit.
+                        let digit = if c > b'9' { (c - b'a' + 10) as i32 } else { (c - b'0') as i32 };
+                        if digit + 1 < radix {
+                            buffer[fraction_cursor] = K_RADIX_CHARS[(digit + 1) as usize];
+                            fraction_cursor += 1;
+                            break;
+                        }
+                    }
+                    break;
+                }
+                if fraction < delta {
+                    break;
+                }
+            }
+        }
+
+        // Compute integer digits. Fill unrepresented digits with zero.
+        while Double::from_f64(integer / radix_f).exponent() > 0 {
+            integer /= radix_f;
+            integer_cursor -= 1;
+            buffer[integer_cursor] = b'0';
+        }
+        loop {
+            let remainder = integer % radix_f;
+            integer_cursor -= 1;
+            buffer[integer_cursor] = K_RADIX_CHARS[remainder as usize];
+            integer = (integer - remainder) / radix_f;
+            if integer <= 0.0 {
+                break;
+            }
+        }
+
+        // Add sign.
+        if negative {
+            integer_cursor -= 1;
+            buffer[integer_cursor] = b'-';
+        }
+        String::from_utf8_lossy(&buffer[integer_cursor..fraction_cursor]).into_owned()
+    }
+
+    fn push_ascii(builder: &mut String, digits: &[u8]) {
+        builder.extend(digits.iter().map(|&c| c as char));
+    }
+
+    fn push_padding(builder: &mut String, c: char, count: i32) {
+        builder.extend(std::iter::repeat_n(c, count.max(0) as usize));
     }
 
     pub fn is_minus_zero(value: f64) -> bool {
@@ -557,24 +1707,20 @@ pub mod conversions {
     // {max_length_for_conversion}. 23 was chosen because any representable double
     // can be represented using a string of length 23.
     pub fn try_string_to_double(isolate: &(), object: &str, max_length_for_conversion: u32) -> Option<f64> {
-        if object.len() > max_length_for_conversion as usize {
+        let utf16: Vec<u16> = object.encode_utf16().collect();
+        if utf16.len() > max_length_for_conversion as usize {
             return None;
         }
-        match object.parse::<f64>() {
-            Ok(value) => Some(value),
-            Err(_) => None,
-        }
+        Some(string_to_double(&utf16, ConversionFlag::AllowNonDecimalPrefix, 0.0))
     }
 
     // Return None if the string is longer than 20.
     pub fn try_string_to_int(isolate: &(), object: &str, radix: i32) -> Option<f64> {
-        if object.len() > 20 {
+        let utf16: Vec<u16> = object.encode_utf16().collect();
+        if utf16.len() > 20 {
             return None;
         }
-        match i64::from_str_radix(object, radix as u32) {
-            Ok(value) => Some(value as f64),
-            Err(_) => None,
-        }
+        Some(string_to_int(&utf16, radix))
     }
 
     // TODO: Implement TryNumberToSize
@@ -597,4 +1743,727 @@ pub mod conversions {
     // pub fn is_special_index(string: &String) -> bool {
     //     unimplemented!()
     // }
-}
\ No newline at end of file
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Deterministic xorshift64 generator for the randomized comparisons.
+        struct Rng(u64);
+
+        impl Rng {
+            fn next_u64(&mut self) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0
+            }
+
+            fn next_finite_double(&mut self) -> f64 {
+                loop {
+                    let v = f64::from_bits(self.next_u64());
+                    if v.is_finite() {
+                        return v;
+                    }
+                }
+            }
+        }
+
+        /// Returns true if the exact decimal expansion of |v| has a "5"
+        /// followed only by zeros at the given position after the point of
+        /// `{:.1100e}`. These halfway cases round away from zero in
+        /// ECMAScript but to even in the reference formatter.
+        fn is_halfway_after(v: f64, significant_digits: usize) -> bool {
+            let exact = format!("{:.1100e}", v.abs());
+            let digits = exact.split_once('e').unwrap().0.replace('.', "");
+            significant_digits < digits.len() && digits[significant_digits..].trim_end_matches('0') == "5"
+        }
+
+        /// Rewrites Rust's `1.5e3` / `1.5e-3` into ECMAScript's `1.5e+3` /
+        /// `1.5e-3`.
+        fn js_exponent(formatted: &str) -> String {
+            let (mantissa, exponent) = formatted.split_once('e').unwrap();
+            if exponent.starts_with('-') {
+                format!("{}e{}", mantissa, exponent)
+            } else {
+                format!("{}e+{}", mantissa, exponent)
+            }
+        }
+
+        #[test]
+        fn test_double_to_string() {
+            let cases: &[(f64, &str)] = &[
+                (0.0, "0"),
+                (-0.0, "0"),
+                (1.0, "1"),
+                (-1.5, "-1.5"),
+                (0.1, "0.1"),
+                (0.000001, "0.000001"),
+                (1e-7, "1e-7"),
+                (123e-20, "1.23e-18"),
+                (2147483648.0, "2147483648"),
+                (-2147483648.0, "-2147483648"),
+                (1e20, "100000000000000000000"),
+                (1e21, "1e+21"),
+                (123456789012345680000.0, "123456789012345680000"),
+                (f64::MAX, "1.7976931348623157e+308"),
+                (5e-324, "5e-324"),
+                (f64::NAN, "NaN"),
+                (f64::INFINITY, "Infinity"),
+                (f64::NEG_INFINITY, "-Infinity"),
+            ];
+            for &(value, expected) in cases {
+                assert_eq!(double_to_string_view(value), expected);
+            }
+            assert_eq!(int_to_string_view(i32::MIN), "-2147483648");
+            assert_eq!(int_to_string_view(0), "0");
+
+            let mut rng = Rng(0x2545_F491_4F6C_DD1D);
+            for _ in 0..50_000 {
+                let v = rng.next_finite_double();
+                let formatted = double_to_string_view(v);
+                assert_eq!(formatted.parse::<f64>().unwrap(), v, "{}", formatted);
+            }
+        }
+
+        #[test]
+        fn test_double_to_fixed() {
+            let cases: &[(f64, i32, &str)] = &[
+                (0.5, 0, "1"),
+                (2.5, 0, "3"),
+                (-2.5, 0, "-3"),
+                (1.005, 2, "1.00"),
+                (123.456, 2, "123.46"),
+                (0.000001, 7, "0.0000010"),
+                (-0.0000001, 2, "-0.00"),
+                (-0.0, 2, "0.00"),
+                (0.0, 0, "0"),
+                (1e20, 2, "100000000000000000000.00"),
+                (1e21, 2, "1e+21"),
+                (-1e21, 2, "-1e+21"),
+                (0.1, 20, "0.10000000000000000555"),
+            ];
+            for &(value, f, expected) in cases {
+                assert_eq!(double_to_fixed_string_view(value, f), expected);
+            }
+            assert_eq!(
+                double_to_fixed_string_view(0.1, 100),
+                format!("{:.100}", 0.1),
+            );
+
+            let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+            for i in 0..50_000 {
+                let v = if i % 2 == 0 {
+                    rng.next_finite_double()
+                } else {
+                    (rng.next_u64() >> 11) as f64 / (1u64 << (rng.next_u64() % 64)) as f64
+                };
+                if v.abs() >= 1e21 || v == 0.0 {
+                    continue;
+                }
+                let f = (rng.next_u64() % (K_MAX_FRACTION_DIGITS as u64 + 1)) as i32;
+                let formatted = double_to_fixed_string_view(v, f);
+                let expected = format!("{:.*}", f as usize, v);
+                if formatted != expected {
+                    let exact = format!("{:.1100}", v.abs());
+                    let tail = &exact[exact.find('.').unwrap() + 1 + f as usize..];
+                    assert_eq!(tail.trim_end_matches('0'), "5", "{:e} {}", v, f);
+                    continue;
+                }
+                assert_eq!(formatted, expected);
+            }
+        }
+
+        #[test]
+        fn test_double_to_exponential() {
+            let cases: &[(f64, i32, &str)] = &[
+                (0.0, -1, "0e+0"),
+                (0.0, 2, "0.00e+0"),
+                (-0.0, -1, "0e+0"),
+                (123456.0, -1, "1.23456e+5"),
+                (123456.0, 2, "1.23e+5"),
+                (1.5e-7, 3, "1.500e-7"),
+                (-1.5, 0, "-2e+0"),
+                (0.00001, -1, "1e-5"),
+                (f64::MAX, -1, "1.7976931348623157e+308"),
+                (5e-324, 2, "4.94e-324"),
+            ];
+            for &(value, f, expected) in cases {
+                assert_eq!(double_to_exponential_string_view(value, f), expected);
+            }
+
+            let mut rng = Rng(0xD1B5_4A32_D192_ED03);
+            for _ in 0..50_000 {
+                let v = rng.next_finite_double();
+                let shortest = double_to_exponential_string_view(v, -1);
+                let expected = js_exponent(&format!("{:e}", v));
+                // The bignum fallback rounds exact halfway cases of the
+                // shortest form to even, the reference rounds them up.
+                let shortest_digits = shortest.split_once('e').unwrap().0.trim_start_matches('-').replace('.', "");
+                assert!(shortest == expected || is_halfway_after(v, shortest_digits.len()), "{:e}", v);
+                let f = (rng.next_u64() % (K_MAX_FRACTION_DIGITS as u64 + 1)) as i32;
+                let formatted = double_to_exponential_string_view(v, f);
+                let expected = js_exponent(&format!("{:.*e}", f as usize, v));
+                if formatted != expected {
+                    assert!(is_halfway_after(v, f as usize + 1), "{:e} {}", v, f);
+                    continue;
+                }
+                assert_eq!(formatted, expected);
+            }
+        }
+
+        #[test]
+        fn test_double_to_precision() {
+            let cases: &[(f64, i32, &str)] = &[
+                (0.0, 3, "0.00"),
+                (-0.0, 1, "0"),
+                (123.456, 4, "123.5"),
+                (0.00001, 1, "0.00001"),
+                (0.0000001, 1, "1e-7"),
+                (123456.0, 2, "1.2e+5"),
+                (1e21, 3, "1.00e+21"),
+                (-1.5, 1, "-2"),
+                (100.0, 3, "100"),
+                (100.0, 5, "100.00"),
+                (99.99, 3, "100"),
+                (99.99, 4, "99.99"),
+                (99.99, 2, "1.0e+2"),
+                (-0.000123, 2, "-0.00012"),
+            ];
+            for &(value, p, expected) in cases {
+                assert_eq!(double_to_precision_string_view(value, p), expected);
+            }
+
+            let mut rng = Rng(0xA076_1D64_78BD_642F);
+            for _ in 0..50_000 {
+                let v = rng.next_finite_double();
+                if v == 0.0 {
+                    continue;
+                }
+                let p = (rng.next_u64() % K_MAX_FRACTION_DIGITS as u64) as i32 + 1;
+                let formatted = double_to_precision_string_view(v, p);
+                // Build the expected output from the reference digits following
+                // the steps of Number.prototype.toPrecision.
+                let reference = format!("{:.*e}", p as usize - 1, v.abs());
+                let (mantissa, exponent) = reference.split_once('e').unwrap();
+                let digits = mantissa.replace('.', "");
+                let e: i32 = exponent.parse().unwrap();
+                let sign = if v < 0.0 { "-" } else { "" };
+                let expected = if e < -6 || e >= p {
+                    format!("{}{}", sign, js_exponent(&reference))
+                } else if e >= 0 {
+                    let (integral, fraction) = digits.split_at(e as usize + 1);
+                    if fraction.is_empty() {
+                        format!("{}{}", sign, integral)
+                    } else {
+                        format!("{}{}.{}", sign, integral, fraction)
+                    }
+                } else {
+                    format!("{}0.{}{}", sign, "0".repeat((-e - 1) as usize), digits)
+                };
+                if formatted != expected {
+                    assert!(is_halfway_after(v, p as usize), "{:e} {}", v, p);
+                    continue;
+                }
+                assert_eq!(formatted, expected);
+            }
+        }
+
+        #[test]
+        fn test_double_to_radix() {
+            let cases: &[(f64, i32, &str)] = &[
+                (255.0, 16, "ff"),
+                (-255.5, 16, "-ff.8"),
+                (0.5, 2, "0.1"),
+                (0.1, 2, "0.0001100110011001100110011001100110011001100110011001101"),
+                (-0.0, 2, "0"),
+                (35.0, 36, "z"),
+                (1e21, 10, "1e+21"),
+                (1152921504606846976.0, 2, "1000000000000000000000000000000000000000000000000000000000000"),
+                (f64::NAN, 16, "NaN"),
+                (f64::NEG_INFINITY, 2, "-Infinity"),
+            ];
+            for &(value, radix, expected) in cases {
+                assert_eq!(double_to_radix_string_view(value, radix), expected);
+            }
+
+            let mut rng = Rng(0x6A09_E667_F3BC_C909);
+            for _ in 0..50_000 {
+                let n = rng.next_u64() >> (11 + rng.next_u64() % 53);
+                let v = n as f64;
+                assert_eq!(double_to_radix_string_view(v, 2), format!("{:b}", n));
+                assert_eq!(double_to_radix_string_view(v, 8), format!("{:o}", n));
+                assert_eq!(double_to_radix_string_view(-v, 16), format!("{}{:x}", if n == 0 { "" } else { "-" }, n));
+                // Binary fractions with few significant bits print exactly.
+                let shift = rng.next_u64() % 40;
+                let fraction = (n & 0xFFFF) as f64 / (1u64 << shift) as f64;
+                let expected = if fraction == 0.0 {
+                    "0".to_string()
+                } else {
+                    let bits = (n & 0xFFFF) as u128;
+                    let integral = bits >> shift;
+                    let rest = bits & ((1u128 << shift) - 1);
+                    let mut s = format!("{:b}", integral);
+                    if rest != 0 {
+                        let fraction_bits = format!("{:0width$b}", rest, width = shift as usize);
+                        s.push('.');
+                        s.push_str(fraction_bits.trim_end_matches('0'));
+                    }
+                    s
+                };
+                assert_eq!(double_to_radix_string_view(fraction, 2), expected);
+            }
+        }
+
+        fn latin1(s: &str) -> Vec<u8> {
+            s.chars().map(|c| u8::try_from(c as u32).unwrap()).collect()
+        }
+
+        fn utf16(s: &str) -> Vec<u16> {
+            s.encode_utf16().collect()
+        }
+
+        /// ToNumber on a string, checked on the one-byte and two-byte paths.
+        fn to_number(s: &str) -> f64 {
+            let result = string_to_double(&utf16(s), ConversionFlag::AllowNonDecimalPrefix, 0.0);
+            if s.chars().all(|c| (c as u32) < 0x100) {
+                let one_byte = string_to_double(&latin1(s), ConversionFlag::AllowNonDecimalPrefix, 0.0);
+                assert_eq!(one_byte.to_bits(), result.to_bits(), "{:?}", s);
+            }
+            result
+        }
+
+        fn assert_same(actual: f64, expected: f64, input: &str) {
+            assert!(
+                actual.to_bits() == expected.to_bits() || (actual.is_nan() && expected.is_nan()),
+                "{:?}: got {}, expected {}",
+                input,
+                actual,
+                expected
+            );
+        }
+
+        #[test]
+        fn test_string_to_number() {
+            let cases: &[(&str, f64)] = &[
+                ("", 0.0),
+                (" \t\n\u{0B}\u{0C}\r", 0.0),
+                ("\u{A0}\u{FEFF}\u{1680}\u{2000}\u{200A}\u{2028}\u{2029}\u{202F}\u{205F}\u{3000}42\u{3000}", 42.0),
+                ("\u{180E}42", f64::NAN),
+                ("\u{200B}42", f64::NAN),
+                ("1", 1.0),
+                ("-0", -0.0),
+                ("+.5", 0.5),
+                ("5.", 5.0),
+                (".", f64::NAN),
+                ("+", f64::NAN),
+                ("-", f64::NAN),
+                ("e5", f64::NAN),
+                ("1e", f64::NAN),
+                ("1e+", f64::NAN),
+                ("1.5e-3", 0.0015),
+                ("1E3", 1000.0),
+                ("00012", 12.0),
+                ("012", 12.0),
+                ("0.000", 0.0),
+                ("-.0e7", -0.0),
+                ("1e1000", f64::INFINITY),
+                ("-1e1000", f64::NEG_INFINITY),
+                ("1e-1000", 0.0),
+                ("1e99999999999999", f64::INFINITY),
+                ("Infinity", f64::INFINITY),
+                ("  -Infinity  ", f64::NEG_INFINITY),
+                ("+Infinity", f64::INFINITY),
+                ("infinity", f64::NAN),
+                ("Infinityx", f64::NAN),
+                ("NaN", f64::NAN),
+                ("0x1F", 31.0),
+                ("0X1f", 31.0),
+                ("0o17", 15.0),
+                ("0B101", 5.0),
+                ("0x", f64::NAN),
+                ("0b2", f64::NAN),
+                ("-0x10", f64::NAN),
+                ("+0x10", f64::NAN),
+                ("0x10 ", 16.0),
+                ("0x10g", f64::NAN),
+                ("0x1fffffffffffff", 9007199254740991.0),
+                ("0x20000000000001", 9007199254740992.0),
+                ("0x20000000000003", 9007199254740996.0),
+                ("0x20000000000001000", 9007199254740992.0 * 4096.0),
+                ("0x200000000000010001", 9007199254740994.0 * 65536.0),
+                ("1_000", f64::NAN),
+                ("0x1_0", f64::NAN),
+                ("12abc", f64::NAN),
+                ("1 2", f64::NAN),
+            ];
+            for &(input, expected) in cases {
+                assert_same(to_number(input), expected, input);
+            }
+            assert_same(string_to_double(b" ", ConversionFlag::NoConversionFlag, f64::NAN), f64::NAN, " ");
+            assert_same(string_to_double(b"0x10", ConversionFlag::NoConversionFlag, 0.0), f64::NAN, "0x10");
+            assert_same(string_to_double_cstr("\u{3000}7", ConversionFlag::NoConversionFlag, 0.0), 7.0, "7");
+
+            // Long inputs keep only the significant digits but still round
+            // correctly, including a nonzero digit far in the tail.
+            let mut long = "9007199254740993".to_string();
+            long.push_str(&"0".repeat(1000));
+            assert_same(to_number(&long), f64::INFINITY, &long);
+            let mut long = "9007199254740993.".to_string();
+            long.push_str(&"0".repeat(1000));
+            assert_same(to_number(&long), 9007199254740992.0, &long);
+            long.push('1');
+            assert_same(to_number(&long), 9007199254740994.0, &long);
+            // Half of the smallest denormal rounds to even (zero), anything
+            // above it rounds up.
+            let exact_half = "2.4703282292062327208828439643411068618252990130716238221279284125033775363510437593264991818081799618989828234772285886546332835517796989819938739800539093906315035659515570226392290858392449105184435931802849936536152500319370457678249219365623669863658480757001585769269903706311928279558551332927834338409351978015531246597263579574622766465272827220056374006485499977096599470454020828166226237857393450736339007967761930577506740176324673600968951340535537458516661134223766678604162159680461914467291840300530057530849048765391711386591646239524912623653881879636239373280423891018672348497668235089863388587925628302755995657524455507255189313690836254779186948667994968324049705821028513185451396213837722826145437693412532098591327667236328125e-324";
+            assert_same(to_number(exact_half), 0.0, "exact half");
+            let above_half = format!("{}000000000001e-324", &exact_half[..exact_half.len() - 5]);
+            assert_same(to_number(&above_half), 5e-324, "above half");
+        }
+
+        #[test]
+        fn test_string_to_number_matches_reference() {
+            let mut rng = Rng(0xBB67_AE85_84CA_A73B);
+            for _ in 0..50_000 {
+                let v = rng.next_finite_double();
+                for s in [format!("{:e}", v), format!("{:?}", v), format!("{:.30e}", v)] {
+                    let expected: f64 = s.parse().unwrap();
+                    assert_same(to_number(&s), expected, &s);
+                    assert_same(parse_float(s.as_bytes()), expected, &s);
+                }
+                // Random decimal strings with up to 30 digits.
+                let digits = 1 + rng.next_u64() % 30;
+                let mantissa: String = (0..digits).map(|_| (b'0' + (rng.next_u64() % 10) as u8) as char).collect();
+                let exponent = (rng.next_u64() % 700) as i32 - 350;
+                let s = format!("{}e{}", mantissa, exponent);
+                assert_same(to_number(&s), s.parse().unwrap(), &s);
+            }
+        }
+
+        #[test]
+        fn test_parse_float() {
+            let cases: &[(&str, f64)] = &[
+                ("", f64::NAN),
+                ("  ", f64::NAN),
+                ("\u{A0}3.25abc", 3.25),
+                ("-0", -0.0),
+                ("-.5x", -0.5),
+                ("1e", 1.0),
+                ("1e+", 1.0),
+                ("1e-2e3", 0.01),
+                ("1.2.3", 1.2),
+                ("0x10", 0.0),
+                ("0b1", 0.0),
+                ("Infinityx", f64::INFINITY),
+                ("-Infinity", f64::NEG_INFINITY),
+                ("Infinit", f64::NAN),
+                ("1_000", 1.0),
+                ("abc", f64::NAN),
+                (".e1", f64::NAN),
+                ("+-1", f64::NAN),
+            ];
+            for &(input, expected) in cases {
+                assert_same(parse_float(&utf16(input)), expected, input);
+            }
+            assert_same(parse_float(&latin1("\u{A0}3.25abc")), 3.25, "latin1");
+        }
+
+        #[test]
+        fn test_string_to_int() {
+            let cases: &[(&str, i32, f64)] = &[
+                ("", 0, f64::NAN),
+                ("  42abc", 0, 42.0),
+                ("\u{3000}-42", 10, -42.0),
+                ("-0", 0, -0.0),
+                ("-", 0, f64::NAN),
+                ("0x1F", 0, 31.0),
+                ("0x1F", 16, 31.0),
+                ("0x1F", 10, 0.0),
+                ("-0xF", 0, -15.0),
+                ("0x", 0, f64::NAN),
+                ("0xg", 16, f64::NAN),
+                ("0b11", 0, 0.0),
+                ("0b11", 2, 0.0),
+                ("11", 2, 3.0),
+                ("012", 0, 12.0),
+                ("0012", 8, 10.0),
+                ("00", 0, 0.0),
+                ("z", 36, 35.0),
+                ("Z", 36, 35.0),
+                ("10", 1, f64::NAN),
+                ("10", 37, f64::NAN),
+                ("10", -1, f64::NAN),
+                ("9", 8, f64::NAN),
+                ("1e3", 10, 1.0),
+                ("3.9", 10, 3.0),
+                ("Infinity", 10, f64::NAN),
+                ("Infinity", 36, 1461559270678.0),
+                ("1_000", 10, 1.0),
+                ("9007199254740993", 10, 9007199254740992.0),
+                ("9007199254740995", 10, 9007199254740996.0),
+                ("100000000000000000001", 2, 1048577.0),
+                ("20000000000001", 16, 9007199254740992.0),
+                ("20000000000003", 16, 9007199254740996.0),
+                ("20000000000001g", 16, 9007199254740992.0),
+                ("200000000000010001", 16, 9007199254740994.0 * 65536.0),
+            ];
+            for &(input, radix, expected) in cases {
+                assert_same(string_to_int(&utf16(input), radix), expected, input);
+                if input.chars().all(|c| (c as u32) < 0x100) {
+                    assert_same(string_to_int(&latin1(input), radix), expected, input);
+                }
+            }
+            let long = "9".repeat(400);
+            assert_same(string_to_int(long.as_bytes(), 10), f64::INFINITY, "400 nines");
+            let long = format!("{}1", "0".repeat(400));
+            assert_same(string_to_int(long.as_bytes(), 10), 1.0, "leading zeros");
+            assert_eq!(try_string_to_int(&(), "123456789012345678901", 10), None);
+            assert_eq!(try_string_to_int(&(), "0x10", 0), Some(16.0));
+            assert_eq!(try_string_to_double(&(), "1.5e3", 23), Some(1500.0));
+            assert_eq!(try_string_to_double(&(), "", 23), Some(0.0));
+        }
+
+        #[test]
+        fn test_string_to_int_matches_integer_formatting() {
+            fn digits_in_radix(mut n: u64, radix: u64) -> Vec<u8> {
+                let mut digits = Vec::new();
+                loop {
+                    digits.push(K_RADIX_CHARS[(n % radix) as usize]);
+                    n /= radix;
+                    if n == 0 {
+                        break;
+                    }
+                }
+                digits.reverse();
+                digits
+            }
+
+            let mut rng = Rng(0x3C6E_F372_FE94_F82B);
+            for _ in 0..50_000 {
+                let n = rng.next_u64() >> (rng.next_u64() % 64);
+                // The power of two radixes and radix 10 round exactly like
+                // the u64 -> f64 conversion.
+                for radix in [2, 4, 8, 10, 16, 32] {
+                    let digits = digits_in_radix(n, radix);
+                    assert_eq!(string_to_int(&digits, radix as i32), n as f64, "{} in radix {}", n, radix);
+                }
+                // Other radixes are exact as long as the value fits in 53 bits.
+                let n = n >> 11;
+                let radix = match 3 + rng.next_u64() % 34 {
+                    4 | 8 | 10 | 16 | 32 => 36,
+                    radix => radix,
+                };
+                let digits = digits_in_radix(n, radix);
+                assert_eq!(string_to_int(&digits, radix as i32), n as f64, "{} in radix {}", n, radix);
+            }
+        }
+
+        #[test]
+        fn test_numeric_literal_to_double() {
+            let cases: &[(&str, f64)] = &[
+                ("0", 0.0),
+                ("1_000_000", 1000000.0),
+                ("1_0.2_5e1_0", 10.25e10),
+                (".5", 0.5),
+                ("1.", 1.0),
+                ("1.e2", 100.0),
+                ("1__0", f64::NAN),
+                ("_1", f64::NAN),
+                ("1_", f64::NAN),
+                ("1_.5", f64::NAN),
+                ("1._5", f64::NAN),
+                ("1e_5", f64::NAN),
+                ("1_e5", f64::NAN),
+                ("0x_1", f64::NAN),
+                ("0xF_F", 255.0),
+                ("0b1_0", 2.0),
+                ("0o7_7", 63.0),
+                ("0x", f64::NAN),
+                ("017", 15.0),
+                ("0_17", f64::NAN),
+                ("01_7", f64::NAN),
+                ("019", 19.0),
+                ("019.5", 19.5),
+                ("01_9", f64::NAN),
+                ("017.5", f64::NAN),
+                ("00", 0.0),
+                ("+1", f64::NAN),
+                (" 1", f64::NAN),
+                ("1 ", f64::NAN),
+                ("Infinity", f64::NAN),
+                ("1e", f64::NAN),
+                ("", f64::NAN),
+            ];
+            for &(input, expected) in cases {
+                assert_same(numeric_literal_to_double(input.as_bytes()), expected, input);
+                assert_same(numeric_literal_to_double(&utf16(input)), expected, input);
+            }
+            assert_same(binary_string_to_double(b"0b1_01"), 5.0, "0b1_01");
+            assert_same(binary_string_to_double(b"0x1"), f64::NAN, "0x1");
+            assert_same(octal_string_to_double(b"0O17"), 15.0, "0O17");
+            assert_same(octal_string_to_double(b"0o8"), f64::NAN, "0o8");
+            assert_same(hex_string_to_double(&utf16("0xff")), 255.0, "0xff");
+            assert_same(hex_string_to_double(b"0x20000000000001"), 9007199254740992.0, "0x20000000000001");
+            assert_same(implicit_octal_string_to_double(b"0777"), 511.0, "0777");
+            assert_same(implicit_octal_string_to_double(b"0778"), f64::NAN, "0778");
+        }
+
+        #[test]
+        fn test_string_to_bigint() {
+            let to_decimal = |s: &str| string_to_bigint(&utf16(s)).map(|v| v.to_decimal_string());
+            assert_eq!(to_decimal(""), Some("0".to_string()));
+            assert_eq!(to_decimal(" \u{A0}\n"), Some("0".to_string()));
+            assert_eq!(to_decimal(" 123 "), Some("123".to_string()));
+            assert_eq!(to_decimal("-0"), Some("0".to_string()));
+            assert_eq!(to_decimal("-123"), Some("-123".to_string()));
+            assert_eq!(to_decimal("+123"), Some("123".to_string()));
+            assert_eq!(to_decimal("0x10"), Some("16".to_string()));
+            assert_eq!(to_decimal("0B11"), Some("3".to_string()));
+            assert_eq!(to_decimal("0o777"), Some("511".to_string()));
+            assert_eq!(to_decimal("007"), Some("7".to_string()));
+            assert_eq!(
+                to_decimal("0xffffffffffffffffffffffffffffffff"),
+                Some("340282366920938463463374607431768211455".to_string())
+            );
+            for invalid in ["-0x10", "0x", "1.5", "1e3", "1n", "1_000", "Infinity", "12 3", "-", "0b2"] {
+                assert_eq!(to_decimal(invalid), None, "{:?}", invalid);
+            }
+            assert_eq!(string_to_bigint(b"-0").unwrap().to_integer_literal(), Some(IntegerLiteral::new(false, 0)));
+            assert_eq!(
+                string_to_bigint(b"-18446744073709551615").unwrap().to_integer_literal(),
+                Some(IntegerLiteral::new(true, u64::MAX))
+            );
+            assert_eq!(string_to_bigint(b"18446744073709551616").unwrap().to_integer_literal(), None);
+            assert_eq!(string_to_bigint(b"18446744073709551616").unwrap().digits(), &[0, 1]);
+
+            // Long decimal strings survive the round trip through 64-bit digits.
+            let mut rng = Rng(0xA54F_F53A_5F1D_36F1);
+            for _ in 0..200 {
+                let len = 1 + rng.next_u64() % 200;
+                let mut s: String = (0..len).map(|_| (b'0' + (rng.next_u64() % 10) as u8) as char).collect();
+                let trimmed = s.trim_start_matches('0');
+                let expected = if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() };
+                assert_eq!(string_to_bigint(s.as_bytes()).unwrap().to_decimal_string(), expected);
+                s.insert(0, '-');
+                let expected = if expected == "0" { expected } else { format!("-{}", expected) };
+                assert_eq!(string_to_bigint(s.as_bytes()).unwrap().to_decimal_string(), expected);
+            }
+        }
+
+        #[test]
+        fn test_bigint_literal() {
+            assert_eq!(bigint_literal_to_decimal(b"0"), Some("0".to_string()));
+            assert_eq!(bigint_literal_to_decimal(b"1_000_000"), Some("1000000".to_string()));
+            assert_eq!(bigint_literal_to_decimal(b"0xFF_FF"), Some("65535".to_string()));
+            assert_eq!(
+                bigint_literal_to_decimal(b"0b1_0000000000000000000000000000000000000000000000000000000000000000"),
+                Some("18446744073709551616".to_string())
+            );
+            for invalid in ["", "00", "017", "0_1", "1__0", "1_", "0x", "0x_1", "1.5", "-1", " 1", "1e3"] {
+                assert_eq!(bigint_literal_to_decimal(invalid.as_bytes()), None, "{:?}", invalid);
+            }
+            assert_eq!(bigint_literal(&utf16("0o7_7")), Some(ParsedBigInt::new(false, vec![63])));
+        }
+
+        // Rounds |x| (finite, non-negative) to binary16 by searching the
+        // neighbouring half-precision values directly.
+        fn reference_float16(x: f64) -> u16 {
+            if x >= 65520.0 {
+                return K_FP16_INFINITY;
+            }
+            // Positive finite halves are ordered like their bit patterns.
+            let (mut below, mut above) = (0u16, 0x7bffu16);
+            while below < above {
+                let mid = below + (above - below).div_ceil(2);
+                if float16_to_double(mid) <= x {
+                    below = mid;
+                } else {
+                    above = mid - 1;
+                }
+            }
+            let lo = float16_to_double(below);
+            if lo == x || below == 0x7bff {
+                return below;
+            }
+            let hi = float16_to_double(below + 1);
+            match (x - lo).partial_cmp(&(hi - x)).unwrap() {
+                std::cmp::Ordering::Less => below,
+                std::cmp::Ordering::Greater => below + 1,
+                std::cmp::Ordering::Equal => if below % 2 == 0 { below } else { below + 1 },
+            }
+        }
+
+        #[test]
+        fn test_float16_round_trip() {
+            for bits in 0..=u16::MAX {
+                let value = float16_to_double(bits);
+                if value.is_nan() {
+                    // NaNs narrow to the canonical quiet NaN, keeping the sign.
+                    assert_eq!(double_to_float16(value), K_FP16_Q_NAN | (bits & 0x8000));
+                    continue;
+                }
+                assert_eq!(double_to_float16(value), bits, "{:#06x}", bits);
+            }
+            assert_eq!(float16_to_double(0x0001), 2f64.powi(-24));
+            assert_eq!(float16_to_double(0x03ff), 1023.0 * 2f64.powi(-24));
+            assert_eq!(float16_to_double(0x0400), 2f64.powi(-14));
+            assert_eq!(float16_to_double(0x3c00), 1.0);
+            assert_eq!(float16_to_double(0x7bff), 65504.0);
+            assert_eq!(float16_to_double(0xc000), -2.0);
+            assert_eq!(float16_to_double(0x7c00), f64::INFINITY);
+            assert_eq!(float16_to_double(0xfc00), f64::NEG_INFINITY);
+            assert!(float16_to_double(0x7e00).is_nan());
+            assert!(float16_to_double(0x8000).is_sign_negative());
+        }
+
+        #[test]
+        fn test_double_to_float16_rounding() {
+            // Overflow threshold: 65520 is the midpoint between the largest
+            // finite half and the next (infinite) step, and rounds to even.
+            assert_eq!(double_to_float16(65504.0), 0x7bff);
+            assert_eq!(double_to_float16(65519.99), 0x7bff);
+            assert_eq!(double_to_float16(65520.0), 0x7c00);
+            assert_eq!(double_to_float16(-65520.0), 0xfc00);
+            assert_eq!(double_to_float16(1e300), 0x7c00);
+            assert_eq!(double_to_float16(f64::NAN), K_FP16_Q_NAN);
+            // Ties to even among normals: 1 + 2^-11 lies between 1 and 1 + 2^-10.
+            assert_eq!(double_to_float16(1.0 + 2f64.powi(-11)), 0x3c00);
+            assert_eq!(double_to_float16(1.0 + 3.0 * 2f64.powi(-11)), 0x3c02);
+            assert_eq!(double_to_float16(1.0 + 2f64.powi(-11) + 2f64.powi(-40)), 0x3c01);
+            // Denormals, including the boundary with the smallest normal.
+            assert_eq!(double_to_float16(2f64.powi(-25)), 0x0000);
+            assert_eq!(double_to_float16(-2f64.powi(-25)), 0x8000);
+            assert_eq!(double_to_float16(2f64.powi(-25) + 2f64.powi(-60)), 0x0001);
+            assert_eq!(double_to_float16(3.0 * 2f64.powi(-25)), 0x0002);
+            assert_eq!(double_to_float16(2f64.powi(-14) - 2f64.powi(-25)), 0x0400);
+            assert_eq!(double_to_float16(2f64.powi(-14) - 2f64.powi(-24)), 0x03ff);
+            assert_eq!(double_to_float16(f64::MIN_POSITIVE), 0x0000);
+            assert_eq!(math_f16round(-0.0).to_bits(), (-0.0f64).to_bits());
+            assert_eq!(math_f16round(5.5), 5.5);
+            assert_eq!(math_f16round(5.0005), 5.0);
+            assert_eq!(math_f16round(1.337), 1.3369140625);
+
+            // Every midpoint between adjacent finite halves, and its neighbours.
+            for bits in 0..0x7bffu16 {
+                let lo = float16_to_double(bits);
+                let hi = float16_to_double(bits + 1);
+                let mid = (lo + hi) / 2.0;
+                let even = if bits % 2 == 0 { bits } else { bits + 1 };
+                assert_eq!(double_to_float16(mid), even, "{:#06x}", bits);
+                assert_eq!(double_to_float16(f64::from_bits(mid.to_bits() - 1)), bits);
+                assert_eq!(double_to_float16(f64::from_bits(mid.to_bits() + 1)), bits + 1);
+                assert_eq!(double_to_float16(-mid), even | 0x8000);
+            }
+
+            let mut rng = Rng(0x1F16_0000_DEAD_BEEF);
+            for _ in 0..2000 {
+                let x = f64::from_bits(rng.next_u64() >> 1) % 70000.0;
+                if x.is_nan() {
+                    continue;
+                }
+                assert_eq!(double_to_float16(x), reference_float16(x), "{:e}", x);
+            }
+        }
+    }
+}
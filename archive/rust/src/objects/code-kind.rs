@@ -4,15 +4,13 @@
 
 // src/objects/code-kind.h
 
-use std::fmt;
-use std::ops::{BitAnd, BitOr, BitOrAssign};
-use std::usize;
-
 // Placeholder for base::Bounds as it does not have a direct equivalent in Rust's standard library
 // You might need to use a custom implementation or a crate like 'num-traits' or similar
 // to represent numeric bounds if the functionality is needed.
 mod base {
-    pub fn is_in_range<T: PartialOrd>(value: T, lower: T, upper: T) -> bool {
+    use std::ops::{BitAnd, BitOr, BitOrAssign};
+
+    pub const fn is_in_range(value: u8, lower: u8, upper: u8) -> bool {
         value >= lower && value <= upper
     }
 
@@ -24,7 +22,11 @@ mod base {
 
     impl<T> Flags<T> {
         pub const fn empty() -> Self {
-            Flags { bits: 0, _phantom: std::marker::PhantomData }
+            Self::from_bits(0)
+        }
+
+        pub const fn from_bits(bits: u32) -> Self {
+            Flags { bits, _phantom: std::marker::PhantomData }
         }
 
         pub fn contains(&self, other: Flags<T>) -> bool {
@@ -57,39 +59,6 @@ mod base {
             self.bits |= other.bits;
         }
     }
-
-    macro_rules! define_operators_for_flags {
-        ($flags_type:ident) => {
-            impl std::ops::BitOr for $flags_type {
-                type Output = Self;
-
-                fn bitor(self, other: Self) -> Self {
-                    $flags_type {
-                        bits: self.bits | other.bits,
-                        _phantom: std::marker::PhantomData,
-                    }
-                }
-            }
-
-            impl std::ops::BitAnd for $flags_type {
-                type Output = Self;
-
-                fn bitand(self, other: Self) -> Self {
-                    $flags_type {
-                        bits: self.bits & other.bits,
-                        _phantom: std::marker::PhantomData,
-                    }
-                }
-            }
-
-            impl std::ops::BitOrAssign for $flags_type {
-                fn bitor_assign(&mut self, other: Self) {
-                    self.bits |= other.bits;
-                }
-            }
-        };
-    }
-    pub(crate) use define_operators_for_flags;
 }
 
 // Placeholder for flags as this is a separate module in V8
@@ -106,6 +75,7 @@ mod flags {
     }
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
 pub enum CodeKind {
@@ -126,11 +96,11 @@ pub enum CodeKind {
 
 const _: () = {
     assert!(
-        CodeKind::INTERPRETED_FUNCTION < CodeKind::BASELINE,
+        (CodeKind::INTERPRETED_FUNCTION as u8) < CodeKind::BASELINE as u8,
         "CodeKind::INTERPRETED_FUNCTION should be less than CodeKind::BASELINE"
     );
     assert!(
-        CodeKind::BASELINE < CodeKind::TURBOFAN_JS,
+        (CodeKind::BASELINE as u8) < CodeKind::TURBOFAN_JS as u8,
         "CodeKind::BASELINE should be less than CodeKind::TURBOFAN_JS"
     );
 };
@@ -138,7 +108,7 @@ const _: () = {
 const K_CODE_KIND_COUNT: usize = 13;
 const _: () = {
     assert!(
-        K_CODE_KIND_COUNT <= std::u8::MAX as usize,
+        K_CODE_KIND_COUNT <= u8::MAX as usize,
         "kCodeKindCount should be less than or equal to the maximum value of u8"
     );
 };
@@ -186,11 +156,11 @@ pub fn code_kind_to_marker(kind: CodeKind, context_specialized: bool) -> &'stati
 }
 
 pub const fn code_kind_is_interpreted_js_function(kind: CodeKind) -> bool {
-    kind == CodeKind::INTERPRETED_FUNCTION
+    matches!(kind, CodeKind::INTERPRETED_FUNCTION)
 }
 
 pub const fn code_kind_is_baselined_js_function(kind: CodeKind) -> bool {
-    kind == CodeKind::BASELINE
+    matches!(kind, CodeKind::BASELINE)
 }
 
 pub const fn code_kind_is_unoptimized_js_function(kind: CodeKind) -> bool {
@@ -200,7 +170,7 @@ pub const fn code_kind_is_unoptimized_js_function(kind: CodeKind) -> bool {
             "CodeKind::INTERPRETED_FUNCTION + 1 must equal CodeKind::BASELINE"
         );
     };
-    base::is_in_range(kind, CodeKind::INTERPRETED_FUNCTION, CodeKind::BASELINE)
+    base::is_in_range(kind as u8, CodeKind::INTERPRETED_FUNCTION as u8, CodeKind::BASELINE as u8)
 }
 
 pub const fn code_kind_is_optimized_js_function(kind: CodeKind) -> bool {
@@ -210,7 +180,7 @@ pub const fn code_kind_is_optimized_js_function(kind: CodeKind) -> bool {
             "CodeKind::MAGLEV + 1 must equal CodeKind::TURBOFAN_JS"
         );
     };
-    base::is_in_range(kind, CodeKind::MAGLEV, CodeKind::TURBOFAN_JS)
+    base::is_in_range(kind as u8, CodeKind::MAGLEV as u8, CodeKind::TURBOFAN_JS as u8)
 }
 
 pub const fn code_kind_is_js_function(kind: CodeKind) -> bool {
@@ -220,11 +190,11 @@ pub const fn code_kind_is_js_function(kind: CodeKind) -> bool {
             "CodeKind::BASELINE + 1 must equal CodeKind::MAGLEV"
         );
     };
-    base::is_in_range(kind, CodeKind::INTERPRETED_FUNCTION, CodeKind::TURBOFAN_JS)
+    base::is_in_range(kind as u8, CodeKind::INTERPRETED_FUNCTION as u8, CodeKind::TURBOFAN_JS as u8)
 }
 
 pub const fn code_kind_is_builtin_or_js_function(kind: CodeKind) -> bool {
-    kind == CodeKind::BUILTIN || code_kind_is_js_function(kind)
+    matches!(kind, CodeKind::BUILTIN) || code_kind_is_js_function(kind)
 }
 
 pub fn code_kind_can_deoptimize(kind: CodeKind, v8_flags: &flags::Flags) -> bool {
@@ -233,45 +203,46 @@ pub fn code_kind_can_deoptimize(kind: CodeKind, v8_flags: &flags::Flags) -> bool
 }
 
 pub const fn code_kind_can_osr(kind: CodeKind) -> bool {
-    kind == CodeKind::TURBOFAN_JS || kind == CodeKind::MAGLEV
+    matches!(kind, CodeKind::TURBOFAN_JS) || matches!(kind, CodeKind::MAGLEV)
 }
 
 pub const fn code_kind_can_tier_up(kind: CodeKind) -> bool {
-    code_kind_is_unoptimized_js_function(kind) || kind == CodeKind::MAGLEV
+    code_kind_is_unoptimized_js_function(kind) || matches!(kind, CodeKind::MAGLEV)
 }
 
 // TODO(jgruber): Rename or remove this predicate. Currently it means 'is this
 // kind stored either in the FeedbackVector cache, or in the OSR cache?'.
 pub const fn code_kind_is_stored_in_optimized_code_cache(kind: CodeKind) -> bool {
-    kind == CodeKind::MAGLEV || kind == CodeKind::TURBOFAN_JS
+    matches!(kind, CodeKind::MAGLEV) || matches!(kind, CodeKind::TURBOFAN_JS)
 }
 
 pub const fn code_kind_uses_bytecode_or_interpreter_data(kind: CodeKind) -> bool {
     code_kind_is_baselined_js_function(kind)
 }
 
-pub const fn code_kind_uses_deoptimization_data(kind: CodeKind) -> bool {
+pub const fn code_kind_uses_deoptimization_data(_kind: CodeKind) -> bool {
     // Need a flag to pass for wasm deopt feature, it is not included in this header.
     false
 }
 
 pub const fn code_kind_uses_bytecode_offset_table(kind: CodeKind) -> bool {
-    kind == CodeKind::BASELINE
+    matches!(kind, CodeKind::BASELINE)
 }
 
 pub const fn code_kind_may_lack_source_position_table(kind: CodeKind) -> bool {
     // Either code that uses a bytecode offset table or code that may be embedded
     // in the snapshot, in which case the source position table is cleared.
     code_kind_uses_bytecode_offset_table(kind)
-        || kind == CodeKind::BUILTIN
-        || kind == CodeKind::BYTECODE_HANDLER
-        || kind == CodeKind::FOR_TESTING
+        || matches!(kind, CodeKind::BUILTIN)
+        || matches!(kind, CodeKind::BYTECODE_HANDLER)
+        || matches!(kind, CodeKind::FOR_TESTING)
 }
 
 pub const fn code_kind_for_top_tier() -> CodeKind {
     CodeKind::TURBOFAN_JS
 }
 
+#[allow(non_camel_case_types)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(u32)]
 pub enum CodeKindFlag {
@@ -317,15 +288,12 @@ pub const fn code_kind_to_code_kind_flag(kind: CodeKind) -> CodeKindFlag {
 
 pub type CodeKinds = base::Flags<CodeKindFlag>;
 
-pub const K_JS_FUNCTION_CODE_KINDS_MASK: CodeKinds = CodeKinds {
-    bits: CodeKindFlag::INTERPRETED_FUNCTION as u32
+pub const K_JS_FUNCTION_CODE_KINDS_MASK: CodeKinds = CodeKinds::from_bits(
+    CodeKindFlag::INTERPRETED_FUNCTION as u32
         | CodeKindFlag::BASELINE as u32
         | CodeKindFlag::MAGLEV as u32
         | CodeKindFlag::TURBOFAN_JS as u32,
-    _phantom: std::marker::PhantomData,
-};
+);
 
-pub const K_OPTIMIZED_JS_FUNCTION_CODE_KINDS_MASK: CodeKinds = CodeKinds {
-    bits: CodeKindFlag::MAGLEV as u32 | CodeKindFlag::TURBOFAN_JS as u32,
-    _phantom: std::marker::PhantomData,
-};
\ No newline at end of file
+pub const K_OPTIMIZED_JS_FUNCTION_CODE_KINDS_MASK: CodeKinds =
+    CodeKinds::from_bits(CodeKindFlag::MAGLEV as u32 | CodeKindFlag::TURBOFAN_JS as u32);
\ No newline at end of file
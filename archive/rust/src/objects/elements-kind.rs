@@ -121,7 +121,9 @@ pub mod elements_kind {
         };
     }
 
-    #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
     #[repr(u8)]
     pub enum ElementsKind {
         // The "fast" kind for elements that only contain SMI values. Must be first
@@ -167,590 +169,384 @@ pub mod elements_kind {
         FAST_STRING_WRAPPER_ELEMENTS,
         SLOW_STRING_WRAPPER_ELEMENTS,
 
-        // Fixed typed arrays.
-        #[allow(non_camel_case_types)]
-        #[allow(dead_code)]
-        #[allow(clippy::upper_case_acronyms)]
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+        // Fixed typed arrays, in the order of typed_arrays!.
+        UINT8_ELEMENTS,
+        INT8_ELEMENTS,
+        UINT16_ELEMENTS,
+        INT16_ELEMENTS,
+        UINT32_ELEMENTS,
+        INT32_ELEMENTS,
+        BIGUINT64_ELEMENTS,
+        BIGINT64_ELEMENTS,
+        UINT8_CLAMPED_ELEMENTS,
+        FLOAT32_ELEMENTS,
+        FLOAT64_ELEMENTS,
+        FLOAT16_ELEMENTS,
+
+        // Typed arrays backed by resizable or growable shared array buffers, in
+        // the order of rab_gsab_typed_arrays!.
+        RAB_GSAB_UINT8_ELEMENTS,
+        RAB_GSAB_INT8_ELEMENTS,
+        RAB_GSAB_UINT16_ELEMENTS,
+        RAB_GSAB_INT16_ELEMENTS,
+        RAB_GSAB_UINT32_ELEMENTS,
+        RAB_GSAB_INT32_ELEMENTS,
+        RAB_GSAB_BIGUINT64_ELEMENTS,
+        RAB_GSAB_BIGINT64_ELEMENTS,
+        RAB_GSAB_UINT8_CLAMPED_ELEMENTS,
+        RAB_GSAB_FLOAT32_ELEMENTS,
+        RAB_GSAB_FLOAT64_ELEMENTS,
+        RAB_GSAB_FLOAT16_ELEMENTS,
+
+        // WasmObject elements kind. The actual elements type is read from the
+        // respective WasmTypeInfo.
+        WASM_ARRAY_ELEMENTS,
+
+        // Sentinel ElementsKind for objects with no elements.
+        NO_ELEMENTS,
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
+    use ElementsKind::*;
+
+    // Derived constants from ElementsKind.
+    pub const FIRST_ELEMENTS_KIND: ElementsKind = PACKED_SMI_ELEMENTS;
+    pub const LAST_ELEMENTS_KIND: ElementsKind = RAB_GSAB_FLOAT16_ELEMENTS;
+    pub const FIRST_FAST_ELEMENTS_KIND: ElementsKind = PACKED_SMI_ELEMENTS;
+    pub const LAST_FAST_ELEMENTS_KIND: ElementsKind = HOLEY_DOUBLE_ELEMENTS;
+    pub const FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND: ElementsKind = UINT8_ELEMENTS;
+    pub const LAST_FIXED_TYPED_ARRAY_ELEMENTS_KIND: ElementsKind = FLOAT16_ELEMENTS;
+    pub const FIRST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND: ElementsKind = RAB_GSAB_UINT8_ELEMENTS;
+    pub const LAST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND: ElementsKind = RAB_GSAB_FLOAT16_ELEMENTS;
+    pub const TERMINAL_FAST_ELEMENTS_KIND: ElementsKind = HOLEY_ELEMENTS;
+    pub const FIRST_ANY_NONEXTENSIBLE_ELEMENTS_KIND: ElementsKind = PACKED_NONEXTENSIBLE_ELEMENTS;
+    pub const LAST_ANY_NONEXTENSIBLE_ELEMENTS_KIND: ElementsKind = SHARED_ARRAY_ELEMENTS;
+
+    pub const FAST_ELEMENTS_KINDS: RangeInclusive<ElementsKind> =
+        FIRST_FAST_ELEMENTS_KIND..=LAST_FAST_ELEMENTS_KIND;
+    pub const FIXED_TYPED_ARRAY_ELEMENTS_KINDS: RangeInclusive<ElementsKind> =
+        FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND..=LAST_FIXED_TYPED_ARRAY_ELEMENTS_KIND;
+    pub const RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KINDS: RangeInclusive<ElementsKind> =
+        FIRST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND..=LAST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND;
+    pub const ANY_NONEXTENSIBLE_ELEMENTS_KINDS: RangeInclusive<ElementsKind> =
+        FIRST_ANY_NONEXTENSIBLE_ELEMENTS_KIND..=LAST_ANY_NONEXTENSIBLE_ELEMENTS_KIND;
+
+    pub const ELEMENTS_KIND_COUNT: usize = LAST_ELEMENTS_KIND as usize - FIRST_ELEMENTS_KIND as usize + 1;
+    pub const FAST_ELEMENTS_KIND_COUNT: usize =
+        LAST_FAST_ELEMENTS_KIND as usize - FIRST_FAST_ELEMENTS_KIND as usize + 1;
+
+    // The number to add to a packed elements kind to reach a holey elements kind.
+    pub const FAST_ELEMENTS_KIND_PACKED_TO_HOLEY: u8 = HOLEY_SMI_ELEMENTS as u8 - PACKED_SMI_ELEMENTS as u8;
+
+    const _: () = assert!(RAB_GSAB_UINT8_ELEMENTS as u8 - UINT8_ELEMENTS as u8
+        == RAB_GSAB_FLOAT16_ELEMENTS as u8 - FLOAT16_ELEMENTS as u8);
+    const _: () = assert!(HOLEY_ELEMENTS as u8 - PACKED_ELEMENTS as u8 == FAST_ELEMENTS_KIND_PACKED_TO_HOLEY);
+    const _: () = assert!(HOLEY_DOUBLE_ELEMENTS as u8 - PACKED_DOUBLE_ELEMENTS as u8
+        == FAST_ELEMENTS_KIND_PACKED_TO_HOLEY);
+
+    // Every elements kind, in declaration order.
+    const ALL_ELEMENTS_KINDS: [ElementsKind; NO_ELEMENTS as usize + 1] = [
+        PACKED_SMI_ELEMENTS, HOLEY_SMI_ELEMENTS, PACKED_ELEMENTS, HOLEY_ELEMENTS,
+        PACKED_DOUBLE_ELEMENTS, HOLEY_DOUBLE_ELEMENTS, PACKED_NONEXTENSIBLE_ELEMENTS,
+        HOLEY_NONEXTENSIBLE_ELEMENTS, PACKED_SEALED_ELEMENTS, HOLEY_SEALED_ELEMENTS,
+        PACKED_FROZEN_ELEMENTS, HOLEY_FROZEN_ELEMENTS, SHARED_ARRAY_ELEMENTS, DICTIONARY_ELEMENTS,
+        FAST_SLOPPY_ARGUMENTS_ELEMENTS, SLOW_SLOPPY_ARGUMENTS_ELEMENTS,
+        FAST_STRING_WRAPPER_ELEMENTS, SLOW_STRING_WRAPPER_ELEMENTS,
+        UINT8_ELEMENTS, INT8_ELEMENTS, UINT16_ELEMENTS, INT16_ELEMENTS, UINT32_ELEMENTS,
+        INT32_ELEMENTS, BIGUINT64_ELEMENTS, BIGINT64_ELEMENTS, UINT8_CLAMPED_ELEMENTS,
+        FLOAT32_ELEMENTS, FLOAT64_ELEMENTS, FLOAT16_ELEMENTS,
+        RAB_GSAB_UINT8_ELEMENTS, RAB_GSAB_INT8_ELEMENTS, RAB_GSAB_UINT16_ELEMENTS,
+        RAB_GSAB_INT16_ELEMENTS, RAB_GSAB_UINT32_ELEMENTS, RAB_GSAB_INT32_ELEMENTS,
+        RAB_GSAB_BIGUINT64_ELEMENTS, RAB_GSAB_BIGINT64_ELEMENTS, RAB_GSAB_UINT8_CLAMPED_ELEMENTS,
+        RAB_GSAB_FLOAT32_ELEMENTS, RAB_GSAB_FLOAT64_ELEMENTS, RAB_GSAB_FLOAT16_ELEMENTS,
+        WASM_ARRAY_ELEMENTS, NO_ELEMENTS,
+    ];
+
+    impl ElementsKind {
+        // Inverse of `kind as u8`.
+        pub fn from_u8(value: u8) -> Option<ElementsKind> {
+            ALL_ELEMENTS_KINDS.get(value as usize).copied()
         }
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
+    impl fmt::Display for ElementsKind {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(elements_kind_to_string(*self))
         }
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn elements_kind_to_string(kind: ElementsKind) -> &'static str {
+        macro_rules! names {
+            ($($kind:ident),* $(,)?) => {
+                match kind { $($kind => stringify!($kind),)* }
+            };
+        }
+        names!(
+            PACKED_SMI_ELEMENTS, HOLEY_SMI_ELEMENTS, PACKED_ELEMENTS, HOLEY_ELEMENTS,
+            PACKED_DOUBLE_ELEMENTS, HOLEY_DOUBLE_ELEMENTS, PACKED_NONEXTENSIBLE_ELEMENTS,
+            HOLEY_NONEXTENSIBLE_ELEMENTS, PACKED_SEALED_ELEMENTS, HOLEY_SEALED_ELEMENTS,
+            PACKED_FROZEN_ELEMENTS, HOLEY_FROZEN_ELEMENTS, SHARED_ARRAY_ELEMENTS, DICTIONARY_ELEMENTS,
+            FAST_SLOPPY_ARGUMENTS_ELEMENTS, SLOW_SLOPPY_ARGUMENTS_ELEMENTS,
+            FAST_STRING_WRAPPER_ELEMENTS, SLOW_STRING_WRAPPER_ELEMENTS,
+            UINT8_ELEMENTS, INT8_ELEMENTS, UINT16_ELEMENTS, INT16_ELEMENTS, UINT32_ELEMENTS,
+            INT32_ELEMENTS, BIGUINT64_ELEMENTS, BIGINT64_ELEMENTS, UINT8_CLAMPED_ELEMENTS,
+            FLOAT32_ELEMENTS, FLOAT64_ELEMENTS, FLOAT16_ELEMENTS,
+            RAB_GSAB_UINT8_ELEMENTS, RAB_GSAB_INT8_ELEMENTS, RAB_GSAB_UINT16_ELEMENTS,
+            RAB_GSAB_INT16_ELEMENTS, RAB_GSAB_UINT32_ELEMENTS, RAB_GSAB_INT32_ELEMENTS,
+            RAB_GSAB_BIGUINT64_ELEMENTS, RAB_GSAB_BIGINT64_ELEMENTS, RAB_GSAB_UINT8_CLAMPED_ELEMENTS,
+            RAB_GSAB_FLOAT32_ELEMENTS, RAB_GSAB_FLOAT64_ELEMENTS, RAB_GSAB_FLOAT16_ELEMENTS,
+            WASM_ARRAY_ELEMENTS, NO_ELEMENTS,
+        )
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        FIXED_TYPED_ARRAY_ELEMENTS_KINDS.contains(&kind)
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_rab_gsab_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KINDS.contains(&kind)
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_typed_array_or_rab_gsab_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        (FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND..=LAST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND).contains(&kind)
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_float16_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        kind == FLOAT16_ELEMENTS || kind == RAB_GSAB_FLOAT16_ELEMENTS
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_bigint_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        matches!(
+            kind,
+            BIGINT64_ELEMENTS | BIGUINT64_ELEMENTS | RAB_GSAB_BIGINT64_ELEMENTS | RAB_GSAB_BIGUINT64_ELEMENTS
+        )
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_float_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        matches!(
+            kind,
+            FLOAT16_ELEMENTS
+                | FLOAT32_ELEMENTS
+                | FLOAT64_ELEMENTS
+                | RAB_GSAB_FLOAT16_ELEMENTS
+                | RAB_GSAB_FLOAT32_ELEMENTS
+                | RAB_GSAB_FLOAT64_ELEMENTS
+        )
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_signed_int_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        matches!(
+            kind,
+            INT8_ELEMENTS
+                | INT16_ELEMENTS
+                | INT32_ELEMENTS
+                | RAB_GSAB_INT8_ELEMENTS
+                | RAB_GSAB_INT16_ELEMENTS
+                | RAB_GSAB_INT32_ELEMENTS
+        )
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_unsigned_int_typed_array_elements_kind(kind: ElementsKind) -> bool {
+        matches!(
+            kind,
+            UINT8_CLAMPED_ELEMENTS
+                | UINT8_ELEMENTS
+                | UINT16_ELEMENTS
+                | UINT32_ELEMENTS
+                | RAB_GSAB_UINT8_CLAMPED_ELEMENTS
+                | RAB_GSAB_UINT8_ELEMENTS
+                | RAB_GSAB_UINT16_ELEMENTS
+                | RAB_GSAB_UINT32_ELEMENTS
+        )
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_wasm_array_elements_kind(kind: ElementsKind) -> bool {
+        kind == WASM_ARRAY_ELEMENTS
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_dictionary_elements_kind(kind: ElementsKind) -> bool {
+        kind == DICTIONARY_ELEMENTS
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_fast_arguments_elements_kind(kind: ElementsKind) -> bool {
+        kind == FAST_SLOPPY_ARGUMENTS_ELEMENTS
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_slow_arguments_elements_kind(kind: ElementsKind) -> bool {
+        kind == SLOW_SLOPPY_ARGUMENTS_ELEMENTS
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_sloppy_arguments_elements_kind(kind: ElementsKind) -> bool {
+        is_fast_arguments_elements_kind(kind) || is_slow_arguments_elements_kind(kind)
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_string_wrapper_elements_kind(kind: ElementsKind) -> bool {
+        kind == FAST_STRING_WRAPPER_ELEMENTS || kind == SLOW_STRING_WRAPPER_ELEMENTS
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_fast_elements_kind(kind: ElementsKind) -> bool {
+        FAST_ELEMENTS_KINDS.contains(&kind)
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_any_nonextensible_elements_kind(kind: ElementsKind) -> bool {
+        ANY_NONEXTENSIBLE_ELEMENTS_KINDS.contains(&kind)
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        
-        
-        
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_nonextensible_elements_kind(kind: ElementsKind) -> bool {
+        kind == PACKED_NONEXTENSIBLE_ELEMENTS || kind == HOLEY_NONEXTENSIBLE_ELEMENTS
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_sealed_elements_kind(kind: ElementsKind) -> bool {
+        kind == PACKED_SEALED_ELEMENTS || kind == HOLEY_SEALED_ELEMENTS
+    }
 
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(non_snake_case)]
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_frozen_elements_kind(kind: ElementsKind) -> bool {
+        kind == PACKED_FROZEN_ELEMENTS || kind == HOLEY_FROZEN_ELEMENTS
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_smi_elements_kind(kind: ElementsKind) -> bool {
+        kind == PACKED_SMI_ELEMENTS || kind == HOLEY_SMI_ELEMENTS
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        
-        
-
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_double_elements_kind(kind: ElementsKind) -> bool {
+        kind == PACKED_DOUBLE_ELEMENTS || kind == HOLEY_DOUBLE_ELEMENTS
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_object_elements_kind(kind: ElementsKind) -> bool {
+        kind == PACKED_ELEMENTS || kind == HOLEY_ELEMENTS
+    }
 
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
-        #[allow(unused_variables)]
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
-        }
+    pub fn is_holey_elements_kind(kind: ElementsKind) -> bool {
+        matches!(
+            kind,
+            HOLEY_SMI_ELEMENTS
+                | HOLEY_ELEMENTS
+                | HOLEY_DOUBLE_ELEMENTS
+                | HOLEY_NONEXTENSIBLE_ELEMENTS
+                | HOLEY_SEALED_ELEMENTS
+                | HOLEY_FROZEN_ELEMENTS
+        )
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
+    pub fn is_packed_elements_kind(kind: ElementsKind) -> bool {
+        is_fast_elements_kind(kind) && !is_holey_elements_kind(kind)
+    }
+
+    pub fn get_packed_elements_kind(kind: ElementsKind) -> ElementsKind {
+        match kind {
+            HOLEY_SMI_ELEMENTS => PACKED_SMI_ELEMENTS,
+            HOLEY_ELEMENTS => PACKED_ELEMENTS,
+            HOLEY_DOUBLE_ELEMENTS => PACKED_DOUBLE_ELEMENTS,
+            HOLEY_NONEXTENSIBLE_ELEMENTS => PACKED_NONEXTENSIBLE_ELEMENTS,
+            HOLEY_SEALED_ELEMENTS => PACKED_SEALED_ELEMENTS,
+            HOLEY_FROZEN_ELEMENTS => PACKED_FROZEN_ELEMENTS,
+            _ => kind,
         }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
+    }
+
+    pub fn get_holey_elements_kind(kind: ElementsKind) -> ElementsKind {
+        match kind {
+            PACKED_SMI_ELEMENTS => HOLEY_SMI_ELEMENTS,
+            PACKED_ELEMENTS => HOLEY_ELEMENTS,
+            PACKED_DOUBLE_ELEMENTS => HOLEY_DOUBLE_ELEMENTS,
+            PACKED_NONEXTENSIBLE_ELEMENTS => HOLEY_NONEXTENSIBLE_ELEMENTS,
+            PACKED_SEALED_ELEMENTS => HOLEY_SEALED_ELEMENTS,
+            PACKED_FROZEN_ELEMENTS => HOLEY_FROZEN_ELEMENTS,
+            _ => kind,
         }
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $TYPE##_ELEMENTS,
-            };
+    }
+
+    // Maps a typed array elements kind to the variant used when the array is
+    // backed by a resizable or growable shared buffer.
+    pub fn get_corresponding_rab_gsab_elements_kind(typed_array_kind: ElementsKind) -> ElementsKind {
+        debug_assert!(is_typed_array_elements_kind(typed_array_kind));
+        let offset = typed_array_kind as u8 - FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8;
+        ALL_ELEMENTS_KINDS[(FIRST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8 + offset) as usize]
+    }
+
+    pub fn get_corresponding_non_rab_gsab_elements_kind(typed_array_kind: ElementsKind) -> ElementsKind {
+        debug_assert!(is_rab_gsab_typed_array_elements_kind(typed_array_kind));
+        let offset = typed_array_kind as u8 - FIRST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8;
+        ALL_ELEMENTS_KINDS[(FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8 + offset) as usize]
+    }
+
+    // Returns log2 of the size in bytes of one element of the given kind.
+    pub fn elements_kind_to_shift_size(kind: ElementsKind) -> u32 {
+        match kind {
+            UINT8_ELEMENTS | INT8_ELEMENTS | UINT8_CLAMPED_ELEMENTS | RAB_GSAB_UINT8_ELEMENTS
+            | RAB_GSAB_INT8_ELEMENTS | RAB_GSAB_UINT8_CLAMPED_ELEMENTS => 0,
+            UINT16_ELEMENTS | INT16_ELEMENTS | FLOAT16_ELEMENTS | RAB_GSAB_UINT16_ELEMENTS
+            | RAB_GSAB_INT16_ELEMENTS | RAB_GSAB_FLOAT16_ELEMENTS => 1,
+            UINT32_ELEMENTS | INT32_ELEMENTS | FLOAT32_ELEMENTS | RAB_GSAB_UINT32_ELEMENTS
+            | RAB_GSAB_INT32_ELEMENTS | RAB_GSAB_FLOAT32_ELEMENTS => 2,
+            PACKED_DOUBLE_ELEMENTS | HOLEY_DOUBLE_ELEMENTS | FLOAT64_ELEMENTS | BIGINT64_ELEMENTS
+            | BIGUINT64_ELEMENTS | RAB_GSAB_FLOAT64_ELEMENTS | RAB_GSAB_BIGINT64_ELEMENTS
+            | RAB_GSAB_BIGUINT64_ELEMENTS => 3,
+            WASM_ARRAY_ELEMENTS | NO_ELEMENTS => unreachable!("{} has no element size", kind),
+            // Everything else holds tagged values.
+            _ => TAGGED_SIZE_LOG2,
         }
+    }
+
+    pub fn elements_kind_to_byte_size(kind: ElementsKind) -> usize {
+        1 << elements_kind_to_shift_size(kind)
+    }
 
-        #[allow(unused_macros)]
-        #[macro_use]
-        macro_rules! rab_gsab_typed_array_elements_kind {
-            ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
-                $
\ No newline at end of file
+    const TAGGED_SIZE_LOG2: u32 = if cfg!(target_pointer_width = "64") { 3 } else { 2 };
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_kind_ranges() {
+            for (index, kind) in ALL_ELEMENTS_KINDS.iter().enumerate() {
+                assert_eq!(*kind as usize, index);
+                assert_eq!(ElementsKind::from_u8(index as u8), Some(*kind));
+                assert_eq!(elements_kind_to_string(*kind), kind.to_string());
+            }
+            assert_eq!(ElementsKind::from_u8(NO_ELEMENTS as u8 + 1), None);
+            assert!(is_typed_array_elements_kind(FLOAT16_ELEMENTS));
+            assert!(!is_typed_array_elements_kind(RAB_GSAB_UINT8_ELEMENTS));
+            assert!(is_rab_gsab_typed_array_elements_kind(RAB_GSAB_FLOAT16_ELEMENTS));
+            assert!(is_float16_typed_array_elements_kind(RAB_GSAB_FLOAT16_ELEMENTS));
+            assert!(is_float_typed_array_elements_kind(FLOAT16_ELEMENTS));
+            assert!(!is_bigint_typed_array_elements_kind(FLOAT16_ELEMENTS));
+            assert_eq!(elements_kind_to_byte_size(FLOAT16_ELEMENTS), 2);
+            assert_eq!(elements_kind_to_byte_size(RAB_GSAB_BIGINT64_ELEMENTS), 8);
+            assert_eq!(get_holey_elements_kind(PACKED_SEALED_ELEMENTS), HOLEY_SEALED_ELEMENTS);
+            assert_eq!(get_packed_elements_kind(HOLEY_DOUBLE_ELEMENTS), PACKED_DOUBLE_ELEMENTS);
+            assert!(is_packed_elements_kind(PACKED_DOUBLE_ELEMENTS));
+            assert!(!is_packed_elements_kind(HOLEY_SMI_ELEMENTS));
+        }
+
+        #[test]
+        fn test_element_sizes_match_typed_array_lists() {
+            let mut expected = Vec::new();
+            macro_rules! collect {
+                ($Type:ident, $type:ident, $TYPE:ident, $ctype:ty) => {
+                    expected.push((concat!(stringify!($TYPE), "_ELEMENTS"), std::mem::size_of::<$ctype>()));
+                };
+            }
+            typed_arrays!(collect);
+            rab_gsab_typed_arrays!(collect);
+            let kinds = FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8..=LAST_RAB_GSAB_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8;
+            let actual: Vec<_> = kinds
+                .map(|kind| ElementsKind::from_u8(kind).unwrap())
+                .map(|kind| (elements_kind_to_string(kind), elements_kind_to_byte_size(kind)))
+                .collect();
+            assert_eq!(actual, expected);
+        }
+
+        #[test]
+        fn test_rab_gsab_mapping() {
+            for kind in FIRST_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8..=LAST_FIXED_TYPED_ARRAY_ELEMENTS_KIND as u8 {
+                let kind = ElementsKind::from_u8(kind).unwrap();
+                let rab_gsab = get_corresponding_rab_gsab_elements_kind(kind);
+                assert!(is_rab_gsab_typed_array_elements_kind(rab_gsab));
+                assert_eq!(elements_kind_to_string(rab_gsab), format!("RAB_GSAB_{}", kind));
+                assert_eq!(get_corresponding_non_rab_gsab_elements_kind(rab_gsab), kind);
+                assert_eq!(elements_kind_to_shift_size(rab_gsab), elements_kind_to_shift_size(kind));
+            }
+        }
+    }
+}
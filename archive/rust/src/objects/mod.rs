@@ -261,3 +261,4 @@ pub mod microtask;
 pub mod oddball;
 pub mod swiss-name-dictionary-inl;
 pub mod feedback-cell-inl;
+pub mod typed-array-elements;
@@ -0,0 +1,184 @@
+// Copyright 2024 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+// Raw element access for typed arrays and DataViews, corresponding to the
+// TypedElementsAccessor load/store paths in elements.cc. Values are expected to
+// have already gone through ToNumber / ToBigInt; this module only performs the
+// final ToInt8..ToFloat64 style conversion and the byte-level encoding.
+
+pub mod typed_array_elements {
+    use crate::numbers::conversions::conversions::{double_to_float16, float16_to_double};
+    use crate::objects::elements_kind::elements_kind::{
+        elements_kind_to_byte_size, get_corresponding_non_rab_gsab_elements_kind,
+        is_rab_gsab_typed_array_elements_kind,
+        is_typed_array_or_rab_gsab_typed_array_elements_kind, ElementsKind,
+    };
+
+    /// A JavaScript value stored in or loaded from a typed array element.
+    /// BigInt elements use `BigInt`, holding the mathematical value; all other
+    /// kinds use `Number`.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum TypedElementValue {
+        Number(f64),
+        BigInt(i128),
+    }
+
+    // Resizable/growable-buffer kinds share the representation of their
+    // fixed-length counterpart.
+    fn storage_kind(kind: ElementsKind) -> ElementsKind {
+        debug_assert!(is_typed_array_or_rab_gsab_typed_array_elements_kind(kind));
+        if is_rab_gsab_typed_array_elements_kind(kind) {
+            get_corresponding_non_rab_gsab_elements_kind(kind)
+        } else {
+            kind
+        }
+    }
+
+    // ECMA-262 7.1.7 ToUint32 without the final reinterpretation, i.e. the
+    // value modulo 2^32. Narrower integer conversions truncate the result.
+    fn to_uint32_modular(value: f64) -> u32 {
+        if !value.is_finite() {
+            return 0;
+        }
+        value.trunc().rem_euclid(4294967296.0) as u32
+    }
+
+    // ECMA-262 7.1.12 ToUint8Clamp.
+    fn to_uint8_clamp(value: f64) -> u8 {
+        if value.is_nan() || value <= 0.0 {
+            return 0;
+        }
+        if value >= 255.0 {
+            return 255;
+        }
+        value.round_ties_even() as u8
+    }
+
+    /// Encodes `value` as an element of `kind` into `bytes`, which must be
+    /// exactly one element long.
+    pub fn store_element(kind: ElementsKind, bytes: &mut [u8], value: TypedElementValue, little_endian: bool) {
+        let kind = storage_kind(kind);
+        debug_assert_eq!(bytes.len(), elements_kind_to_byte_size(kind));
+        macro_rules! store {
+            ($value:expr) => {{
+                let value = $value;
+                bytes.copy_from_slice(&if little_endian { value.to_le_bytes() } else { value.to_be_bytes() });
+            }};
+        }
+        match (kind, value) {
+            (ElementsKind::BIGINT64_ELEMENTS, TypedElementValue::BigInt(v)) => store!(v as i64),
+            (ElementsKind::BIGUINT64_ELEMENTS, TypedElementValue::BigInt(v)) => store!(v as u64),
+            (_, TypedElementValue::Number(v)) => match kind {
+                ElementsKind::UINT8_ELEMENTS => store!(to_uint32_modular(v) as u8),
+                ElementsKind::INT8_ELEMENTS => store!(to_uint32_modular(v) as i8),
+                ElementsKind::UINT8_CLAMPED_ELEMENTS => store!(to_uint8_clamp(v)),
+                ElementsKind::UINT16_ELEMENTS => store!(to_uint32_modular(v) as u16),
+                ElementsKind::INT16_ELEMENTS => store!(to_uint32_modular(v) as i16),
+                ElementsKind::UINT32_ELEMENTS => store!(to_uint32_modular(v)),
+                ElementsKind::INT32_ELEMENTS => store!(to_uint32_modular(v) as i32),
+                ElementsKind::FLOAT16_ELEMENTS => store!(double_to_float16(v)),
+                ElementsKind::FLOAT32_ELEMENTS => store!(v as f32),
+                ElementsKind::FLOAT64_ELEMENTS => store!(v),
+                _ => unreachable!("a Number cannot be stored in {}", kind),
+            },
+            (_, TypedElementValue::BigInt(_)) => unreachable!("a BigInt cannot be stored in {}", kind),
+        }
+    }
+
+    /// Decodes the element of `kind` held in `bytes`, which must be exactly
+    /// one element long.
+    pub fn load_element(kind: ElementsKind, bytes: &[u8], little_endian: bool) -> TypedElementValue {
+        let kind = storage_kind(kind);
+        debug_assert_eq!(bytes.len(), elements_kind_to_byte_size(kind));
+        macro_rules! load {
+            ($type:ty) => {{
+                let raw = bytes.try_into().unwrap();
+                if little_endian { <$type>::from_le_bytes(raw) } else { <$type>::from_be_bytes(raw) }
+            }};
+        }
+        let number = match kind {
+            ElementsKind::BIGINT64_ELEMENTS => return TypedElementValue::BigInt(load!(i64) as i128),
+            ElementsKind::BIGUINT64_ELEMENTS => return TypedElementValue::BigInt(load!(u64) as i128),
+            ElementsKind::UINT8_ELEMENTS | ElementsKind::UINT8_CLAMPED_ELEMENTS => load!(u8) as f64,
+            ElementsKind::INT8_ELEMENTS => load!(i8) as f64,
+            ElementsKind::UINT16_ELEMENTS => load!(u16) as f64,
+            ElementsKind::INT16_ELEMENTS => load!(i16) as f64,
+            ElementsKind::UINT32_ELEMENTS => load!(u32) as f64,
+            ElementsKind::INT32_ELEMENTS => load!(i32) as f64,
+            ElementsKind::FLOAT16_ELEMENTS => float16_to_double(load!(u16)),
+            ElementsKind::FLOAT32_ELEMENTS => load!(f32) as f64,
+            ElementsKind::FLOAT64_ELEMENTS => load!(f64),
+            _ => unreachable!("{} is not a typed array elements kind", kind),
+        };
+        TypedElementValue::Number(number)
+    }
+
+    fn element_bytes(kind: ElementsKind, index: usize) -> std::ops::Range<usize> {
+        let size = elements_kind_to_byte_size(storage_kind(kind));
+        index * size..(index + 1) * size
+    }
+
+    /// Reads element `index` of a typed array's backing store. Typed arrays
+    /// use the platform byte order.
+    pub fn get_element(kind: ElementsKind, data: &[u8], index: usize) -> TypedElementValue {
+        load_element(kind, &data[element_bytes(kind, index)], cfg!(target_endian = "little"))
+    }
+
+    /// Writes element `index` of a typed array's backing store.
+    pub fn set_element(kind: ElementsKind, data: &mut [u8], index: usize, value: TypedElementValue) {
+        store_element(kind, &mut data[element_bytes(kind, index)], value, cfg!(target_endian = "little"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use TypedElementValue::{BigInt, Number};
+
+        fn round_trip(kind: ElementsKind, value: TypedElementValue) -> TypedElementValue {
+            let mut data = [0u8; 24];
+            set_element(kind, &mut data, 2, value);
+            get_element(kind, &data, 2)
+        }
+
+        #[test]
+        fn test_integer_conversions() {
+            assert_eq!(round_trip(ElementsKind::INT8_ELEMENTS, Number(300.0)), Number(44.0));
+            assert_eq!(round_trip(ElementsKind::INT8_ELEMENTS, Number(-129.5)), Number(127.0));
+            assert_eq!(round_trip(ElementsKind::UINT8_ELEMENTS, Number(-1.0)), Number(255.0));
+            assert_eq!(round_trip(ElementsKind::UINT16_ELEMENTS, Number(f64::INFINITY)), Number(0.0));
+            assert_eq!(round_trip(ElementsKind::INT32_ELEMENTS, Number(4294967295.0)), Number(-1.0));
+            assert_eq!(round_trip(ElementsKind::UINT32_ELEMENTS, Number(-1e20)), Number(2632974336.0));
+            assert_eq!(round_trip(ElementsKind::UINT8_CLAMPED_ELEMENTS, Number(2.5)), Number(2.0));
+            assert_eq!(round_trip(ElementsKind::UINT8_CLAMPED_ELEMENTS, Number(3.5)), Number(4.0));
+            assert_eq!(round_trip(ElementsKind::UINT8_CLAMPED_ELEMENTS, Number(300.0)), Number(255.0));
+            assert_eq!(round_trip(ElementsKind::UINT8_CLAMPED_ELEMENTS, Number(f64::NAN)), Number(0.0));
+            assert_eq!(round_trip(ElementsKind::BIGINT64_ELEMENTS, BigInt(1 << 63)), BigInt(-(1 << 63)));
+            assert_eq!(round_trip(ElementsKind::BIGUINT64_ELEMENTS, BigInt(-1)), BigInt(u64::MAX as i128));
+            assert_eq!(round_trip(ElementsKind::RAB_GSAB_INT16_ELEMENTS, Number(-32769.0)), Number(32767.0));
+        }
+
+        #[test]
+        fn test_float_conversions() {
+            assert_eq!(round_trip(ElementsKind::FLOAT16_ELEMENTS, Number(1.337)), Number(1.3369140625));
+            assert_eq!(round_trip(ElementsKind::FLOAT16_ELEMENTS, Number(65520.0)), Number(f64::INFINITY));
+            assert_eq!(round_trip(ElementsKind::RAB_GSAB_FLOAT16_ELEMENTS, Number(-6e-8)), Number(-5.960464477539063e-8));
+            assert_eq!(round_trip(ElementsKind::FLOAT32_ELEMENTS, Number(0.1)), Number(0.1f32 as f64));
+            assert_eq!(round_trip(ElementsKind::FLOAT64_ELEMENTS, Number(0.1)), Number(0.1));
+            let Number(nan) = round_trip(ElementsKind::FLOAT16_ELEMENTS, Number(f64::NAN)) else { panic!() };
+            assert!(nan.is_nan());
+            let Number(zero) = round_trip(ElementsKind::FLOAT16_ELEMENTS, Number(-0.0)) else { panic!() };
+            assert!(zero == 0.0 && zero.is_sign_negative());
+        }
+
+        #[test]
+        fn test_byte_order() {
+            let mut bytes = [0u8; 2];
+            store_element(ElementsKind::FLOAT16_ELEMENTS, &mut bytes, Number(1.5), false);
+            assert_eq!(bytes, [0x3e, 0x00]);
+            assert_eq!(load_element(ElementsKind::FLOAT16_ELEMENTS, &bytes, true), Number(float16_to_double(0x003e)));
+            store_element(ElementsKind::FLOAT16_ELEMENTS, &mut bytes, Number(1.5), true);
+            assert_eq!(bytes, [0x00, 0x3e]);
+        }
+    }
+}
@@ -2,12 +2,13 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::sync::atomic::{AtomicI32, Ordering};
-use std::mem::MaybeUninit;
+use std::cell::UnsafeCell;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
 
 const PROCESSOR_CACHE_LINE_SIZE: usize = 64;
 
-mod internal {
+pub mod internal {
     use super::*;
 
     /// Lock-free cache-friendly sampling circular queue for large records.
@@ -16,83 +17,115 @@ mod internal {
     /// return `None`. The queue is designed with a goal in mind to evade
     /// cache lines thrashing by preventing simultaneous reads and writes to
     /// adjacent memory locations.
+    ///
+    /// The queue does not check who calls it: only one thread may act as the
+    /// producer (`start_enqueue`, `finish_enqueue`) and only one as the
+    /// consumer (`peek`, `remove`) at a time, which is why these methods are
+    /// unsafe. A record is owned by the producer while its marker is empty
+    /// and by the consumer while it is full; the marker is handed over with
+    /// release/acquire ordering.
     pub struct SamplingCircularQueue<T, const LENGTH: usize> {
-        buffer: [Entry<T>; LENGTH],
-        enqueue_pos: CacheAligned<*mut Entry<T>>,
-        dequeue_pos: CacheAligned<*mut Entry<T>>,
+        buffer: Box<[Entry<T>; LENGTH]>,
+        // Positions are stored as buffer indices rather than entry pointers so
+        // that the queue stays valid when it is moved after construction.
+        // Each is only accessed by one side, so relaxed ordering suffices.
+        enqueue_pos: CacheAligned<AtomicUsize>,
+        dequeue_pos: CacheAligned<AtomicUsize>,
     }
 
-    impl<T, const LENGTH: usize> SamplingCircularQueue<T, LENGTH> {
-        /// Executed on the application thread.
-        pub fn new() -> Self {
-            let mut buffer: [MaybeUninit<Entry<T>>; LENGTH] = MaybeUninit::uninit_array();
-            for elem in buffer.iter_mut() {
-                elem.write(Entry::new());
-            }
+    // SAFETY: records are only accessed through the unsafe producer and
+    // consumer methods, whose contract gives each record a single owner at a
+    // time.
+    unsafe impl<T: Send, const LENGTH: usize> Sync for SamplingCircularQueue<T, LENGTH> {}
 
-            // SAFETY: buffer is now fully initialized.
-            let buffer: [Entry<T>; LENGTH] = unsafe { std::mem::transmute_copy(&buffer) };
+    impl<T: Default, const LENGTH: usize> Default for SamplingCircularQueue<T, LENGTH> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
 
-            let enqueue_pos = CacheAligned::new(&buffer[0] as *const Entry<T> as *mut Entry<T>);
-            let dequeue_pos = CacheAligned::new(&buffer[0] as *const Entry<T> as *mut Entry<T>);
+    impl<T: Default, const LENGTH: usize> SamplingCircularQueue<T, LENGTH> {
+        /// Executed on the application thread.
+        pub fn new() -> Self {
+            let buffer: Box<[Entry<T>; LENGTH]> = Box::new(std::array::from_fn(|_| Entry::new()));
 
             SamplingCircularQueue {
                 buffer,
-                enqueue_pos,
-                dequeue_pos,
+                enqueue_pos: CacheAligned::new(AtomicUsize::new(0)),
+                dequeue_pos: CacheAligned::new(AtomicUsize::new(0)),
             }
         }
+    }
 
+    impl<T, const LENGTH: usize> SamplingCircularQueue<T, LENGTH> {
         /// Executed on the application thread.
-        pub fn start_enqueue(&self) -> Option<&mut T> {
-            let current_enqueue_pos = unsafe { &mut *self.enqueue_pos.value };
+        /// Returns a pointer to the memory location for the next record, or
+        /// `None` if the queue is full. The caller must call `finish_enqueue`
+        /// after writing the record. The record may be written through the
+        /// pointer until then.
+        ///
+        /// # Safety
+        ///
+        /// Must only be called by the producer.
+        pub unsafe fn start_enqueue(&self) -> Option<NonNull<T>> {
+            let current_enqueue_pos = self.entry(self.enqueue_pos.value.load(Ordering::Relaxed));
             if current_enqueue_pos.marker.load(Ordering::Acquire) == kFull {
                 return None;
             }
 
-            Some(&mut current_enqueue_pos.record)
+            // The entry is empty, so the consumer does not access it, and the
+            // caller is the only producer.
+            NonNull::new(current_enqueue_pos.record.get())
         }
 
         /// Notifies the queue that the producer has complete writing data into the
         /// memory returned by `start_enqueue` and it can be passed to the consumer.
-        pub fn finish_enqueue(&self) {
-            let current_enqueue_pos = unsafe { &mut *self.enqueue_pos.value };
-            current_enqueue_pos.marker.store(kFull, Ordering::Release);
-
-            let next_enqueue_pos = self.next(current_enqueue_pos);
-            self.enqueue_pos.value = next_enqueue_pos;
+        ///
+        /// # Safety
+        ///
+        /// Must only be called by the producer, after `start_enqueue`
+        /// returned a record.
+        pub unsafe fn finish_enqueue(&self) {
+            let index = self.enqueue_pos.value.load(Ordering::Relaxed);
+            self.entry(index).marker.store(kFull, Ordering::Release);
+            self.enqueue_pos.value.store(Self::next(index), Ordering::Relaxed);
         }
 
         /// Executed on the consumer (analyzer) thread.
         /// Retrieves, but does not remove, the head of this queue, returning `None`
         /// if this queue is empty. After the record had been read by a consumer,
         /// `remove` must be called.
-        pub fn peek(&self) -> Option<&T> {
-            let current_dequeue_pos = unsafe { &mut *self.dequeue_pos.value };
+        ///
+        /// # Safety
+        ///
+        /// Must only be called by the consumer, and the returned record must
+        /// not be used after `remove`.
+        pub unsafe fn peek(&self) -> Option<&T> {
+            let current_dequeue_pos = self.entry(self.dequeue_pos.value.load(Ordering::Relaxed));
             if current_dequeue_pos.marker.load(Ordering::Acquire) == kEmpty {
                 return None;
             }
 
-            Some(&current_dequeue_pos.record)
+            // SAFETY: the entry is full, so the producer does not access it.
+            Some(unsafe { &*current_dequeue_pos.record.get() })
         }
 
-        pub fn remove(&self) {
-            let current_dequeue_pos = unsafe { &mut *self.dequeue_pos.value };
-            current_dequeue_pos.marker.store(kEmpty, Ordering::Release);
-
-            let next_dequeue_pos = self.next(current_dequeue_pos);
-            self.dequeue_pos.value = next_dequeue_pos;
+        /// # Safety
+        ///
+        /// Must only be called by the consumer, after `peek` returned a
+        /// record.
+        pub unsafe fn remove(&self) {
+            let index = self.dequeue_pos.value.load(Ordering::Relaxed);
+            self.entry(index).marker.store(kEmpty, Ordering::Release);
+            self.dequeue_pos.value.store(Self::next(index), Ordering::Relaxed);
         }
 
-        fn next(&self, entry: *mut Entry<T>) -> *mut Entry<T> {
-            let entry_ptr = entry as *const Entry<T>;
-
-            let entry_index = unsafe {
-                self.buffer.as_ptr().sub_ptr(entry_ptr)
-            };
+        fn entry(&self, index: usize) -> &Entry<T> {
+            &self.buffer[index]
+        }
 
-            let next_index = (entry_index + 1) % LENGTH;
-            &self.buffer[next_index] as *const Entry<T> as *mut Entry<T>
+        fn next(index: usize) -> usize {
+            (index + 1) % LENGTH
         }
     }
 
@@ -101,17 +134,14 @@ mod internal {
 
     #[repr(align(64))]
     struct Entry<T> {
-        record: T,
+        record: UnsafeCell<T>,
         marker: AtomicI32,
     }
 
-    impl<T> Entry<T> {
+    impl<T: Default> Entry<T> {
         fn new() -> Self {
-            // Initialize record with a default value if possible.
-            // Otherwise, caller must initialize before using.
-            let record = unsafe { std::mem::zeroed() };
             Entry {
-                record,
+                record: UnsafeCell::new(T::default()),
                 marker: AtomicI32::new(kEmpty),
             }
         }
@@ -127,13 +157,5 @@ mod internal {
             CacheAligned { value }
         }
     }
-
-    impl<T, const LENGTH: usize> Drop for SamplingCircularQueue<T, LENGTH> {
-        fn drop(&mut self) {
-            // No need to drop elements individually as MaybeUninit doesn't own them.
-            // If T needs to be dropped, it must be handled separately.
-        }
-    }
-
 } // namespace internal
    // No direct equivalent for v8 namespace in Rust
\ No newline at end of file
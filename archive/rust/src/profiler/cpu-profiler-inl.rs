@@ -49,8 +49,8 @@ pub struct CodeDisableOptEventRecord {
 
 impl CodeDisableOptEventRecord {
     pub fn update_code_map(&self, instruction_stream_map: &mut InstructionStreamMap) {
-        if let Some(entry) = instruction_stream_map.find_entry(self.instruction_start) {
-            entry.set_bailout_reason(self.bailout_reason);
+        if let Some((entry, _)) = instruction_stream_map.find_entry(self.instruction_start) {
+            unsafe { (*entry).set_bailout_reason(self.bailout_reason) };
         }
     }
 }
@@ -64,8 +64,8 @@ pub struct CodeDeoptEventRecord {
 
 impl CodeDeoptEventRecord {
     pub fn update_code_map(&mut self, instruction_stream_map: &mut InstructionStreamMap) {
-        if let Some(entry) = instruction_stream_map.find_entry(self.instruction_start) {
-            entry.set_deopt_info(self.deopt_reason, self.deopt_id, self.deopt_frames.drain(..).collect());
+        if let Some((entry, _)) = instruction_stream_map.find_entry(self.instruction_start) {
+            unsafe { (*entry).set_deopt_info(self.deopt_reason, self.deopt_id, self.deopt_frames.drain(..).collect()) };
         }
     }
 }
@@ -78,8 +78,8 @@ pub struct ReportBuiltinEventRecord {
 
 impl ReportBuiltinEventRecord {
     pub fn update_code_map(&self, instruction_stream_map: &mut InstructionStreamMap) {
-        if let Some(entry) = instruction_stream_map.find_entry(self.instruction_start) {
-            entry.set_builtin_id(self.builtin);
+        if let Some((entry, _)) = instruction_stream_map.find_entry(self.instruction_start) {
+            unsafe { (*entry).set_builtin_id(self.builtin) };
             return;
         }
 
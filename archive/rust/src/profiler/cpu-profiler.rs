@@ -3,42 +3,94 @@
 // found in the LICENSE file.
 
 // src/profiler/cpu-profiler.h (Module definition)
-mod cpu_profiler {
+pub mod cpu_profiler {
     use std::time::Duration;
 
+    use crate::profiler::profile_generator::ProfilingMode;
+
+    /// Optional profiling attributes.
+    #[derive(Debug)]
     pub struct CpuProfilingOptions {
         pub record_samples: bool,
-        // Other options...
+        mode: ProfilingMode,
+        max_samples: u32,
+        sampling_interval_us: i32,
+    }
+
+    impl CpuProfilingOptions {
+        /// Indicates that the sample buffer size should not be explicitly limited.
+        pub const K_NO_SAMPLE_LIMIT: u32 = u32::MAX;
+
+        /// |max_samples| caps the number of samples recorded by the profile;
+        /// later samples are discarded and the profile's delegate is notified.
+        /// |sampling_interval_us| is snapped to the next lowest non-zero
+        /// multiple of the profiler's sampling interval; zero means the
+        /// profiler's own interval.
+        pub fn new(mode: ProfilingMode, max_samples: u32, sampling_interval_us: i32) -> Self {
+            CpuProfilingOptions {
+                record_samples: true,
+                mode,
+                max_samples,
+                sampling_interval_us,
+            }
+        }
+
+        pub fn mode(&self) -> ProfilingMode {
+            self.mode
+        }
+
+        pub fn max_samples(&self) -> u32 {
+            self.max_samples
+        }
+
+        pub fn sampling_interval_us(&self) -> i32 {
+            self.sampling_interval_us
+        }
     }
 
+    impl Default for CpuProfilingOptions {
+        fn default() -> Self {
+            CpuProfilingOptions::new(
+                ProfilingMode::kLeafNodeLineNumbers,
+                Self::K_NO_SAMPLE_LIMIT,
+                0,
+            )
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum CpuProfilingStatus {
         Started,
         AlreadyStarted,
-        Failed,
+        ErrorTooManyProfilers,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct CpuProfilingResult {
+        pub id: ProfilerId,
         pub status: CpuProfilingStatus,
-        // Other results...
     }
 
-    pub trait DiscardedSamplesDelegate {
-        fn discarded_samples(&self, count: usize);
+    /// Delegate for when max samples reached and samples are discarded.
+    pub trait DiscardedSamplesDelegate: Send {
+        fn notify(&mut self);
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum CpuProfilingNamingMode {
         UserProvided,
         SourceText,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum CpuProfilingLoggingMode {
         EagerLogging,
         LazyLogging,
     }
 
-    pub struct CpuProfile;
+    pub use crate::profiler::profile_generator::CpuProfile;
 
-    pub type ProfilerId = i32;
+    pub type ProfilerId = u32;
 
     pub trait CpuProfilerInterface {
         fn get_profiles_count(&self) -> i32;
@@ -48,19 +100,24 @@ mod cpu_profiler {
         fn start_profiling(
             &mut self,
             options: CpuProfilingOptions,
-            delegate: Box<dyn DiscardedSamplesDelegate>,
+            delegate: Option<Box<dyn DiscardedSamplesDelegate>>,
         ) -> CpuProfilingResult;
         fn stop_profiling(&mut self, id: ProfilerId) -> Option<&CpuProfile>;
     }
+
+    /// Default interval between two samples taken by the profiler thread
+    /// (--cpu-profiler-sampling-interval).
+    pub const K_DEFAULT_SAMPLING_INTERVAL: Duration = Duration::from_micros(1000);
 }
 
 // src/profiler/cpu-profiler.cc (Implementation)
 
 use std::collections::HashMap;
+use std::ptr::NonNull;
 use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 //use v8::support::AlignedAllocWithRetry;
 //use v8::support::AlignedFree;
 
@@ -70,19 +127,24 @@ use std::time::Instant;
 //use crate::execution::frames_inl::StackFrame;
 //use crate::execution::v8threads::ThreadId;
 //use crate::execution::vm_state_inl::VMState;
-//use crate::libsampler::sampler::Sampler;
+use crate::libsampler::sampler::sampler::{RegisterState, SampleStackHandler, Sampler};
 //use crate::logging::counters::RuntimeCallStats;
 //use crate::logging::log::LogEventListener;
 //use crate::profiler::cpu_profiler_inl::{
 //    CodeEventsContainer, CodeEventRecord, CodeEntryStorage,
 //};
 //use crate::profiler::profiler_stats::ProfilerStats;
-//use crate::profiler::symbolizer::Symbolizer;
-//use crate::utils::locked_queue_inl::LockedQueue;
+use crate::profiler::circular_queue::internal::SamplingCircularQueue;
+use crate::profiler::profile_generator::{CpuProfilesCollection, TimeDelta};
+use crate::profiler::profiler_listener::{
+    CodeEntryStorage, CodeEventRecordType, CodeEventsContainer, ProfilerListener,
+};
+use crate::profiler::symbolizer::symbolizer::{InstructionStreamMap, Symbolizer};
+use crate::profiler::tick_sample::internal::{RecordCEntryFrame, TickSample};
+use crate::utils::locked_queue_inl::LockedQueue;
 
-//use self::cpu_profiler::CpuProfile;
-use self::cpu_profiler::{
-    CpuProfilingLoggingMode, CpuProfilingNamingMode, CpuProfilingOptions,
+pub use self::cpu_profiler::{
+    CpuProfile, CpuProfilingLoggingMode, CpuProfilingNamingMode, CpuProfilingOptions,
     CpuProfilingResult, CpuProfilingStatus, DiscardedSamplesDelegate, ProfilerId,
 };
 
@@ -93,12 +155,39 @@ const kProfilerStackSize: usize = 256 * KB;
 //#[cfg(V8_ENABLE_WEBASSEMBLY)]
 //use crate::wasm::wasm_engine::GetWasmEngine;
 
+/// A tick sample together with the id of the last code event enqueued before
+/// it was taken. Ticks are only symbolized once every code event up to that id
+/// has been applied to the code map.
+#[derive(Default)]
+pub struct TickSampleEventRecord {
+    pub order: u64,
+    pub sample: TickSample,
+}
+
+impl TickSampleEventRecord {
+    fn new(order: u64) -> Self {
+        TickSampleEventRecord {
+            order,
+            sample: TickSample::new(),
+        }
+    }
+}
+
+// Length of the ring buffer shared between the signal handler and the
+// processor thread (kTickSampleQueueLength).
+const kTickSampleQueueLength: usize = 64;
+
 struct CpuSampler {
-    isolate: *mut Isolate, // Assuming Isolate is defined elsewhere
+    isolate: *mut Isolate,
     processor: *mut SamplingEventsProcessor,
     per_thread_data: *mut PerIsolateThreadData, // Assuming PerIsolateThreadData is defined elsewhere
 }
 
+// The sampler is only driven from the profiler thread and the signal handler
+// of the sampled thread; neither outlives the owning processor.
+unsafe impl Send for CpuSampler {}
+unsafe impl Sync for CpuSampler {}
+
 impl CpuSampler {
     fn new(isolate: *mut Isolate, processor: *mut SamplingEventsProcessor) -> Self {
         CpuSampler {
@@ -107,8 +196,10 @@ impl CpuSampler {
             per_thread_data: unsafe { (*isolate).find_per_thread_data_for_this_thread() },
         }
     }
+}
 
-    fn sample_stack(&mut self, regs: &RegisterState) {
+impl SampleStackHandler for CpuSampler {
+    fn sample_stack(&self, regs: &RegisterState) {
         let isolate = self.isolate;
         if unsafe { (*isolate).was_locker_ever_used() }
             && (!unsafe { (*isolate).thread_manager().is_locked_by_thread((*self.per_thread_data).thread_id()) }
@@ -121,22 +212,24 @@ impl CpuSampler {
         // #[cfg(V8_HEAP_USE_PKU_JIT_WRITE_PROTECT)]
         // i::RwxMemoryWriteScope::SetDefaultPermissionsForSignalHandler();
 
-        let sample = unsafe { (*self.processor).start_tick_sample() };
-        if sample.is_null() {
+        let processor = unsafe { &*self.processor };
+        // SAFETY: the sampler is the single producer of the ticks buffer.
+        let Some(mut sample) = (unsafe { processor.start_tick_sample() }) else {
             // ProfilerStats::Instance().AddReason(ProfilerStats::Reason::kTickBufferFull);
             return;
-        }
+        };
 
         // Every bailout up until here resulted in a dropped sample. From now on,
         // the sample is created in the buffer.
         unsafe {
-            (*sample).init(
-                isolate,
+            sample.as_mut().init(
+                &mut *isolate,
                 regs,
-                TickSample::kIncludeCEntryFrame,
+                RecordCEntryFrame::KIncludeCEntryFrame,
                 true, // update_stats
                 true, // use_simulator_reg_state
-                (*self.processor).period(),
+                processor.period(),
+                None,
             );
         }
 
@@ -145,7 +238,8 @@ impl CpuSampler {
         //      if sample.state == EXTERNAL { self.external_sample_count_ += 1; }
         //  }
 
-        unsafe { (*self.processor).finish_tick_sample() };
+        // SAFETY: as above.
+        unsafe { processor.finish_tick_sample() };
     }
 }
 
@@ -200,10 +294,10 @@ impl Drop for ProfilingScope {
 
 struct ProfilerEventsProcessor {
     thread: Option<thread::JoinHandle<()>>,
-    symbolizer: *mut Symbolizer,
     code_observer: *mut ProfilerCodeObserver,
     profiles: *mut CpuProfilesCollection,
-    last_code_event_id: u64,
+    // Read by the signal handler to tag samples, hence atomic.
+    last_code_event_id: AtomicU64,
     last_processed_code_event_id: u64,
     isolate: *mut Isolate,
     events_buffer: LockedQueue<CodeEventsContainer>,
@@ -216,42 +310,31 @@ struct ProfilerEventsProcessor {
 impl ProfilerEventsProcessor {
     fn new(
         isolate: *mut Isolate,
-        symbolizer: *mut Symbolizer,
         code_observer: *mut ProfilerCodeObserver,
         profiles: *mut CpuProfilesCollection,
     ) -> Self {
-        // DCHECK(!code_observer_->processor());
-        // code_observer_->set_processor(this);
-        unsafe { (*code_observer).set_processor(std::ptr::null_mut()) };
-
-        let running_ = Arc::new(AtomicBool::new(false));
-        let running_mutex_ = Arc::new(Mutex::new(()));
-        let running_cond_ = Arc::new(Condvar::new());
-
         ProfilerEventsProcessor {
             thread: None,
-            symbolizer,
             code_observer,
             profiles,
-            last_code_event_id: 0,
+            last_code_event_id: AtomicU64::new(0),
             last_processed_code_event_id: 0,
             isolate,
             events_buffer: LockedQueue::new(),
             ticks_from_vm_buffer: LockedQueue::new(),
-            running_,
-            running_mutex_,
-            running_cond_,
+            running_: Arc::new(AtomicBool::new(false)),
+            running_mutex_: Arc::new(Mutex::new(())),
+            running_cond_: Arc::new(Condvar::new()),
         }
     }
 
     fn enqueue(&mut self, mut event: CodeEventsContainer) {
-        self.last_code_event_id += 1;
-        event.generic.order = self.last_code_event_id;
+        event.order = self.last_code_event_id.fetch_add(1, Ordering::Release) + 1;
         self.events_buffer.enqueue(event);
     }
 
     fn add_deopt_stack(&mut self, from: usize, fp_to_sp_delta: i32) {
-        let mut record = TickSampleEventRecord::new(self.last_code_event_id);
+        let mut record = TickSampleEventRecord::new(self.last_code_event_id.load(Ordering::Acquire));
         let mut regs = RegisterState::default();
         let fp = unsafe { (*self.isolate).c_entry_fp((*self.isolate).thread_local_top()) };
         regs.sp = (fp as isize - fp_to_sp_delta as isize) as *mut std::ffi::c_void;
@@ -259,11 +342,13 @@ impl ProfilerEventsProcessor {
         regs.pc = from as *mut std::ffi::c_void;
         unsafe {
             record.sample.init(
-                self.isolate,
+                &mut *self.isolate,
                 &regs,
-                TickSample::kSkipCEntryFrame,
+                RecordCEntryFrame::KSkipCEntryFrame,
                 false,
                 false,
+                Duration::ZERO,
+                None,
             );
         }
         self.ticks_from_vm_buffer.enqueue(record);
@@ -274,7 +359,7 @@ impl ProfilerEventsProcessor {
         update_stats: bool,
         trace_id: Option<u64>,
     ) {
-        let mut record = TickSampleEventRecord::new(self.last_code_event_id);
+        let mut record = TickSampleEventRecord::new(self.last_code_event_id.load(Ordering::Acquire));
         let mut regs = RegisterState::default();
         // Assuming StackFrameIterator and related types are defined elsewhere
         // let it = StackFrameIterator::new(self.isolate, (*self.isolate).thread_local_top(), StackFrameIterator::NoHandles);
@@ -286,12 +371,12 @@ impl ProfilerEventsProcessor {
         // }
         unsafe {
             record.sample.init(
-                self.isolate,
+                &mut *self.isolate,
                 &regs,
-                TickSample::kSkipCEntryFrame,
+                RecordCEntryFrame::KSkipCEntryFrame,
                 update_stats,
                 false,
-                Duration::from_secs(0),
+                Duration::ZERO,
                 trace_id,
             );
         }
@@ -299,41 +384,42 @@ impl ProfilerEventsProcessor {
     }
 
     fn add_sample(&mut self, sample: TickSample) {
-        let mut record = TickSampleEventRecord::new(self.last_code_event_id);
+        let mut record = TickSampleEventRecord::new(self.last_code_event_id.load(Ordering::Acquire));
         record.sample = sample;
         self.ticks_from_vm_buffer.enqueue(record);
     }
 
     fn stop_synchronously(&mut self) {
-        let expected = true;
-        if !self
+        if self
             .running_
-            .compare_exchange(expected, false, Ordering::Relaxed, Ordering::Relaxed)
+            .compare_exchange(true, false, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
         {
             return;
         }
-        let guard = self.running_mutex_.lock().unwrap();
-        self.running_cond_.notify_one();
-        drop(guard); // Explicitly drop the guard before joining
+        {
+            let _guard = self.running_mutex_.lock().unwrap();
+            self.running_cond_.notify_one();
+        }
         if let Some(thread) = self.thread.take() {
             thread.join().unwrap();
         }
     }
 
     fn process_code_event(&mut self) -> bool {
-        if let Some(mut record) = self.events_buffer.dequeue() {
-            if record.generic.event_type == CodeEventRecordType::NativeContextMove {
-                // let nc_record = record.NativeContextMoveEventRecord_;
-                // unsafe {
-                //     (*self.profiles).UpdateNativeContextAddressForCurrentProfiles(
-                //         nc_record.from_address,
-                //         nc_record.to_address,
-                //     );
-                // }
+        if let Some(record) = self.events_buffer.dequeue() {
+            if record.event_type == CodeEventRecordType::kNativeContextMove {
+                let nc_record = &record.NativeContextMoveEventRecord_;
+                unsafe {
+                    (*self.profiles).update_native_context_address_for_current_profiles(
+                        nc_record.from_address,
+                        nc_record.to_address,
+                    );
+                }
             } else {
-                unsafe { (*self.code_observer).code_event_handler_internal(record) };
+                unsafe { (*self.code_observer).code_event_handler_internal(record.clone()) };
             }
-            self.last_processed_code_event_id = record.generic.order;
+            self.last_processed_code_event_id = record.order;
             true
         } else {
             false
@@ -341,93 +427,49 @@ impl ProfilerEventsProcessor {
     }
 
     fn code_event_handler(&mut self, evt_rec: &CodeEventsContainer) {
-        match evt_rec.generic.event_type {
-            CodeEventRecordType::CodeCreation
-            | CodeEventRecordType::CodeMove
-            | CodeEventRecordType::CodeDisableOpt
-            | CodeEventRecordType::CodeDelete
-            | CodeEventRecordType::NativeContextMove => {
+        match evt_rec.event_type {
+            CodeEventRecordType::kCodeCreation
+            | CodeEventRecordType::kCodeMove
+            | CodeEventRecordType::kCodeDisableOpt
+            | CodeEventRecordType::kCodeDelete
+            | CodeEventRecordType::kNativeContextMove => {
                 let evt_rec_copy = evt_rec.clone();
                 self.enqueue(evt_rec_copy);
             }
-            CodeEventRecordType::CodeDeopt => {
-                // let rec = &evt_rec.CodeDeoptEventRecord_;
-                // let pc = rec.pc;
-                // let fp_to_sp_delta = rec.fp_to_sp_delta;
+            CodeEventRecordType::kCodeDeopt => {
+                let pc = evt_rec.CodeDeoptEventRecord_.pc;
+                let fp_to_sp_delta = evt_rec.CodeDeoptEventRecord_.fp_to_sp_delta;
                 let evt_rec_copy = evt_rec.clone();
                 self.enqueue(evt_rec_copy);
-                // self.add_deopt_stack(pc, fp_to_sp_delta);
-            }
-            CodeEventRecordType::NoEvent | CodeEventRecordType::ReportBuiltin => {
-                // UNREACHABLE(); // No direct equivalent in Rust, consider logging or panicking
+                self.add_deopt_stack(pc, fp_to_sp_delta);
             }
-        }
-    }
-
-    fn run(&mut self) {
-        let running_mutex = Arc::clone(&self.running_mutex_);
-        let running_cond = Arc::clone(&self.running_cond_);
-        let running = Arc::clone(&self.running_);
-
-        let mut guard = running_mutex.lock().unwrap();
-
-        while running.load(Ordering::Relaxed) {
-            // Simulate work with a sleep.
-            thread::sleep(Duration::from_millis(100));
-
-            let (new_guard, timeout_result) =
-                running_cond.wait_timeout(guard, Duration::from_secs(1)).unwrap();
-            guard = new_guard;
-
-            match timeout_result.timed_out() {
-                true => {
-                    println!("Timed out, doing more work.");
-                }
-                false => {
-                    if !running.load(Ordering::Relaxed) {
-                        break;
-                    }
-                    println!("Spurious wakeup");
-                }
+            CodeEventRecordType::kNoEvent | CodeEventRecordType::kReportBuiltin => {
+                unreachable!();
             }
         }
-        println!("Thread is exiting.");
-    }
-
-    fn start_synchronously(&mut self) -> Result<(), String> {
-        let running = Arc::clone(&self.running_);
-        let running_mutex = Arc::clone(&self.running_mutex_);
-        let running_cond = Arc::clone(&self.running_cond_);
-
-        if self.running_.load(Ordering::Relaxed) {
-            return Ok(());
-        }
-
-        self.running_.store(true, Ordering::Relaxed);
-
-        let mut this = self;
-
-        let builder = thread::Builder::new().name("v8:ProfEvntProc".into()).stack_size(kProfilerStackSize);
-        self.thread = Some(builder.spawn(move || {
-            this.run();
-        }).map_err(|e| e.to_string())?);
-
-        Ok(())
     }
 }
 
 impl Drop for ProfilerEventsProcessor {
     fn drop(&mut self) {
         unsafe {
-            //  DCHECK_EQ((*self.code_observer).processor(), self);
-            //   (*self.code_observer).clear_processor();
+            (*self.code_observer).set_processor(std::ptr::null_mut());
         }
     }
 }
 
+/// Raw processor pointer handed to the profiler thread. The processor joins
+/// the thread before it is dropped, so the pointer never dangles.
+struct ProcessorPtr(*mut SamplingEventsProcessor);
+
+unsafe impl Send for ProcessorPtr {}
+
 struct SamplingEventsProcessor {
     base: ProfilerEventsProcessor,
-    sampler: Box<CpuSampler>,
+    symbolizer: *mut Symbolizer<'static>,
+    // Samples taken by the signal handler; filled without locking.
+    ticks_buffer: SamplingCircularQueue<TickSampleEventRecord, kTickSampleQueueLength>,
+    sampler: Option<Box<Sampler>>,
     period: Duration,
     use_precise_sampling: bool,
     //#[cfg(V8_OS_WIN)]
@@ -435,71 +477,194 @@ struct SamplingEventsProcessor {
 }
 
 impl SamplingEventsProcessor {
+    /// Must be called on the thread that is going to be sampled: the sampler
+    /// records the identity of the calling thread.
     fn new(
         isolate: *mut Isolate,
-        symbolizer: *mut Symbolizer,
+        symbolizer: *mut Symbolizer<'static>,
         code_observer: *mut ProfilerCodeObserver,
         profiles: *mut CpuProfilesCollection,
         period: Duration,
         use_precise_sampling: bool,
     ) -> Box<Self> {
-        let mut this = Self {
-            base: ProfilerEventsProcessor::new(isolate, symbolizer, code_observer, profiles),
-            sampler: Box::new(CpuSampler::new(isolate, std::ptr::null_mut())), // Temp value
+        let mut this = Box::new(Self {
+            base: ProfilerEventsProcessor::new(isolate, code_observer, profiles),
+            symbolizer,
+            ticks_buffer: SamplingCircularQueue::new(),
+            sampler: None,
             period,
             use_precise_sampling,
             //#[cfg(V8_OS_WIN)]
             //precise_sleep_timer_: PreciseSleepTimer::new(),
-        };
-        this.base.running_.store(true, Ordering::Relaxed);
-        unsafe { this.sampler = Box::new(CpuSampler::new(isolate, &mut this.base as *mut ProfilerEventsProcessor as *mut SamplingEventsProcessor)) };
-        //this.sampler.start();
-
-        Box::new(this)
+        });
+        let this_ptr: *mut SamplingEventsProcessor = this.as_mut();
+        unsafe { (*code_observer).set_processor(&mut this.base) };
+        let sampler = Box::new(Sampler::with_handler(
+            isolate as *mut _,
+            Box::new(CpuSampler::new(isolate, this_ptr)),
+        ));
+        sampler.start();
+        this.sampler = Some(sampler);
+        this
     }
 
-    unsafe fn start_tick_sample(&mut self) -> *mut TickSample {
-        //Placeholder
-        std::ptr::null_mut()
+    /// Tick sample events are filled directly in the buffer of the circular
+    /// queue (because the structure is of fixed width, but usually not all
+    /// stack frame entries are filled.) This method returns a pointer to the
+    /// next record of the buffer. Called from the signal handler.
+    ///
+    /// # Safety
+    ///
+    /// Must only be called by the sampler, which is the single producer of
+    /// the ticks buffer.
+    unsafe fn start_tick_sample(&self) -> Option<NonNull<TickSample>> {
+        // SAFETY: the caller is the single producer, so the record is not
+        // accessed by anyone else until `finish_tick_sample`.
+        let record = unsafe { self.ticks_buffer.start_enqueue()?.as_mut() };
+        record.order = self.base.last_code_event_id.load(Ordering::Acquire);
+        Some(NonNull::from(&mut record.sample))
     }
 
-    unsafe fn finish_tick_sample(&mut self) {
-        //Placeholder
+    /// # Safety
+    ///
+    /// Must only be called by the sampler, after `start_tick_sample`
+    /// returned a sample.
+    unsafe fn finish_tick_sample(&self) {
+        // SAFETY: the caller is the single producer.
+        unsafe { self.ticks_buffer.finish_enqueue() };
     }
 
     fn period(&self) -> Duration {
         self.period
     }
 
-    fn symbolize_and_add_to_profiles(&mut self, record: &TickSampleEventRecord) {
-        //Placeholder
-        // let tick_sample = &record.sample;
-        // let symbolized = unsafe {
-        //     (*self.base.symbolizer).symbolize_tick_sample(tick_sample)
-        // };
-        // unsafe {
-        //     (*self.base.profiles).add_path_to_current_profiles(
-        //         tick_sample.timestamp,
-        //         symbolized.stack_trace,
-        //         symbolized.src_line,
-        //         tick_sample.update_stats_,
-        //         tick_sample.sampling_interval_,
-        //         tick_sample.state,
-        //         tick_sample.embedder_state,
-        //         tick_sample.context as usize,
-        //         tick_sample.embedder_context as usize,
-        //         tick_sample.trace_id_,
-        //     );
-        // }
+    fn symbolize_and_add_to_profiles(&self, record: &TickSampleEventRecord) {
+        let tick_sample = &record.sample;
+        let symbolized = unsafe { (*self.symbolizer).symbolize_tick_sample(tick_sample) };
+        unsafe {
+            (*self.base.profiles).add_path_to_current_profiles(
+                tick_sample.timestamp,
+                &symbolized.stack_trace,
+                symbolized.src_line,
+                tick_sample.update_stats_,
+                tick_sample.sampling_interval_,
+                tick_sample.state,
+                tick_sample.embedder_state,
+                tick_sample.context as usize,
+                tick_sample.trace_id_,
+            );
+        }
     }
 
     fn process_one_sample(&mut self) -> SampleProcessingResult {
-        //Placeholder
-        SampleProcessingResult::NoSamplesInQueue
+        // Samples taken synchronously from the VM take priority as long as
+        // the code map is in sync with them.
+        let vm_tick_ready = self
+            .base
+            .ticks_from_vm_buffer
+            .peek()
+            .is_some_and(|record| record.order == self.base.last_processed_code_event_id);
+        if vm_tick_ready {
+            let record = self.base.ticks_from_vm_buffer.dequeue().unwrap();
+            self.symbolize_and_add_to_profiles(&record);
+            return SampleProcessingResult::OneSampleProcessed;
+        }
+
+        // SAFETY: the processor thread is the single consumer of the ticks
+        // buffer.
+        let Some(record) = (unsafe { self.ticks_buffer.peek() }) else {
+            if self.base.ticks_from_vm_buffer.is_empty() {
+                return SampleProcessingResult::NoSamplesInQueue;
+            }
+            return SampleProcessingResult::FoundSampleForNextCodeEvent;
+        };
+        if record.order != self.base.last_processed_code_event_id {
+            return SampleProcessingResult::FoundSampleForNextCodeEvent;
+        }
+        self.symbolize_and_add_to_profiles(record);
+        // SAFETY: as above; the record is not used any more.
+        unsafe { self.ticks_buffer.remove() };
+        SampleProcessingResult::OneSampleProcessed
     }
 
     fn run(&mut self) {
-        //Placeholder
+        let running_mutex = Arc::clone(&self.base.running_mutex_);
+        let running_cond = Arc::clone(&self.base.running_cond_);
+        let mut guard = running_mutex.lock().unwrap();
+
+        while self.base.running_.load(Ordering::Relaxed) {
+            let next_sample_time = Instant::now() + self.period;
+            let mut now;
+            // Keep processing existing events until we need to do next sample
+            // or the ticks buffer is empty.
+            loop {
+                let result = self.process_one_sample();
+                if result == SampleProcessingResult::FoundSampleForNextCodeEvent {
+                    // All ticks of the current last_processed_code_event_id_ are
+                    // processed, proceed to the next code event.
+                    self.process_code_event();
+                }
+                now = Instant::now();
+                if result == SampleProcessingResult::NoSamplesInQueue || now >= next_sample_time {
+                    break;
+                }
+            }
+
+            // Wait until the next sample is due, waking early if the processor is
+            // being stopped. Spurious wakeups are handled by re-checking the
+            // deadline.
+            while now < next_sample_time && self.base.running_.load(Ordering::Relaxed) {
+                let (new_guard, _) = running_cond
+                    .wait_timeout(guard, next_sample_time - now)
+                    .unwrap();
+                guard = new_guard;
+                now = Instant::now();
+            }
+
+            // Schedule next sample.
+            if let Some(sampler) = &self.sampler {
+                sampler.do_sample();
+            }
+        }
+        drop(guard);
+
+        // Process remaining tick events.
+        loop {
+            while self.process_one_sample() == SampleProcessingResult::OneSampleProcessed {}
+            if !self.process_code_event() {
+                break;
+            }
+        }
+    }
+
+    fn process_code_event(&mut self) -> bool {
+        self.base.process_code_event()
+    }
+
+    fn start_synchronously(&mut self) -> Result<(), String> {
+        if self.base.running_.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        let this = ProcessorPtr(self);
+        let builder = thread::Builder::new()
+            .name("v8:ProfEvntProc".into())
+            .stack_size(kProfilerStackSize);
+        let handle = builder
+            .spawn(move || {
+                let this = this;
+                unsafe { (*this.0).run() };
+            })
+            .map_err(|e| {
+                self.base.running_.store(false, Ordering::Relaxed);
+                e.to_string()
+            })?;
+        self.base.thread = Some(handle);
+        Ok(())
+    }
+
+    fn stop_synchronously(&mut self) {
+        self.base.stop_synchronously();
     }
 
     fn set_sampling_interval(&mut self, period: Duration) {
@@ -507,19 +672,18 @@ impl SamplingEventsProcessor {
             return;
         }
 
-        self.base.stop_synchronously();
+        self.stop_synchronously();
         self.period = period;
-
-        self.base.running_.store(true, Ordering::Relaxed);
-        self.base.start_synchronously().unwrap();
+        self.start_synchronously().unwrap();
     }
-
-    //TODO Implement Operator New and Delete
 }
 
 impl Drop for SamplingEventsProcessor {
     fn drop(&mut self) {
-        //self.sampler.stop();
+        self.stop_synchronously();
+        if let Some(sampler) = self.sampler.take() {
+            sampler.stop();
+        }
     }
 }
 
@@ -533,7 +697,7 @@ enum SampleProcessingResult {
 struct ProfilerCodeObserver {
     isolate: *mut Isolate,
     code_entries: CodeEntryStorage,
-    code_map: CodeMap,
+    code_map: InstructionStreamMap,
     weak_code_registry: WeakCodeRegistry,
     processor: *mut ProfilerEventsProcessor,
 }
@@ -543,7 +707,7 @@ impl ProfilerCodeObserver {
         let mut observer = ProfilerCodeObserver {
             isolate,
             code_entries: storage,
-            code_map: CodeMap::new(),
+            code_map: InstructionStreamMap::new(),
             weak_code_registry: WeakCodeRegistry::new(isolate),
             processor: std::ptr::null_mut(),
         };
@@ -562,43 +726,62 @@ impl ProfilerCodeObserver {
             unsafe { (*self.processor).code_event_handler(evt_rec) };
             return;
         }
-        self.code_event_handler_internal(evt_rec);
+        self.code_event_handler_internal(evt_rec.clone());
     }
 
     fn get_estimated_memory_usage(&self) -> usize {
         // To avoid race condition in codemap,
         // for now limit computation in kEagerLogging mode
         if self.processor.is_null() {
-            0 // Placeholder
-              // return std::mem::size_of(self) + self.code_map.get_estimated_memory_usage() +
-              //        self.code_entries.strings().get_string_size();
+            std::mem::size_of_val(self) + self.code_map.get_estimated_memory_usage()
         } else {
             0
         }
     }
 
     fn code_event_handler_internal(&mut self, evt_rec: CodeEventsContainer) {
-        let mut record = evt_rec;
-        match evt_rec.generic.event_type {
-            CodeEventRecordType::CodeCreation => {
-                //record.CodeCreationEventRecord_.UpdateCodeMap(&self.code_map);
+        match evt_rec.event_type {
+            CodeEventRecordType::kCodeCreation => {
+                let rec = &evt_rec.CodeCreateEventRecord_;
+                self.code_map.add_code(rec.instruction_start, rec.entry.as_ptr(), rec.instruction_size);
             }
-            CodeEventRecordType::CodeMove => {
-                //record.CodeMoveEventRecord_.UpdateCodeMap(&self.code_map);
+            CodeEventRecordType::kCodeMove => {
+                let rec = &evt_rec.CodeMoveEventRecord_;
+                self.code_map.move_code(rec.from_instruction_start, rec.to_instruction_start);
             }
-            CodeEventRecordType::CodeDisableOpt => {
-                //record.CodeDisableOptEventRecord_.UpdateCodeMap(&self.code_map);
+            CodeEventRecordType::kCodeDisableOpt => {
+                let rec = &evt_rec.CodeDisableOptEventRecord_;
+                if let Some((entry, _)) = self.code_map.find_entry(rec.instruction_start) {
+                    unsafe { (*entry).set_bailout_reason(&rec.bailout_reason) };
+                }
             }
-            CodeEventRecordType::CodeDelete => {
-                //record.CodeDeleteEventRecord_.UpdateCodeMap(&self.code_map);
+            CodeEventRecordType::kCodeDeopt => {
+                let rec = &evt_rec.CodeDeoptEventRecord_;
+                if let Some((entry, _)) = self.code_map.find_entry(rec.instruction_start) {
+                    let frames = if rec.deopt_frames.is_null() {
+                        Vec::new()
+                    } else {
+                        unsafe { std::slice::from_raw_parts(rec.deopt_frames, rec.deopt_frame_count) }
+                            .to_vec()
+                    };
+                    unsafe { (*entry).set_deopt_info(&rec.deopt_reason, rec.deopt_id, frames) };
+                }
             }
-            CodeEventRecordType::NativeContextMove => {
-                //record.NativeContextMoveEventRecord_.UpdateCodeMap(&self.code_map);
+            CodeEventRecordType::kCodeDelete => {
+                let rec = &evt_rec.CodeDeleteEventRecord_;
+                let removed = self.code_map.remove_code(rec.entry.as_ptr());
+                debug_assert!(removed);
             }
-            _ => {}
+            CodeEventRecordType::kNativeContextMove
+            | CodeEventRecordType::kNoEvent
+            | CodeEventRecordType::kReportBuiltin => {}
         }
     }
 
+    fn instruction_stream_map(&mut self) -> &mut InstructionStreamMap {
+        &mut self.code_map
+    }
+
     fn create_entries_for_runtime_call_stats(&mut self) {
         //Placeholder
     }
@@ -619,7 +802,7 @@ struct CpuProfiler {
     base_sampling_interval: Duration,
     code_observer: Box<ProfilerCodeObserver>,
     profiles: Box<CpuProfilesCollection>,
-    symbolizer: Option<Box<Symbolizer>>,
+    symbolizer: Option<Box<Symbolizer<'static>>>,
     processor: Option<Box<SamplingEventsProcessor>>,
     is_profiling: bool,
     profiler_listener: Option<Box<ProfilerListener>>,
@@ -634,33 +817,29 @@ impl CpuProfiler {
         naming_mode: CpuProfilingNamingMode,
         logging_mode: CpuProfilingLoggingMode,
     ) -> Self {
-        CpuProfiler::new_with_components(
-            isolate,
-            naming_mode,
-            logging_mode,
-            Box::new(CpuProfilesCollection::new(isolate)),
-            None,
-            None,
-        )
+        CpuProfiler::new_with_components(isolate, naming_mode, logging_mode, None, None, None)
     }
 
     fn new_with_components(
         isolate: *mut Isolate,
         naming_mode: CpuProfilingNamingMode,
         logging_mode: CpuProfilingLoggingMode,
-        test_profiles: Box<CpuProfilesCollection>,
-        test_symbolizer: Option<Box<Symbolizer>>,
+        test_profiles: Option<Box<CpuProfilesCollection>>,
+        test_symbolizer: Option<Box<Symbolizer<'static>>>,
         test_processor: Option<Box<SamplingEventsProcessor>>,
     ) -> Self {
         let code_entries_ = CodeEntryStorage::new();
-        let test_code_observer = ProfilerCodeObserver::new(isolate, code_entries_.clone());
+        let mut test_code_observer = Box::new(ProfilerCodeObserver::new(isolate, code_entries_.clone()));
+        let profiles = test_profiles.unwrap_or_else(|| {
+            Box::new(CpuProfilesCollection::new(isolate as *mut _, &mut test_code_observer.code_entries))
+        });
         let mut profiler = CpuProfiler {
             isolate,
             naming_mode,
             logging_mode,
-            base_sampling_interval: Duration::from_micros(1000), // Replace with v8_flags.cpu_profiler_sampling_interval
-            code_observer: Box::new(test_code_observer),
-            profiles: test_profiles,
+            base_sampling_interval: cpu_profiler::K_DEFAULT_SAMPLING_INTERVAL,
+            code_observer: test_code_observer,
+            profiles,
             symbolizer: test_symbolizer,
             processor: test_processor,
             is_profiling: false,
@@ -669,9 +848,6 @@ impl CpuProfiler {
             use_precise_sampling_: false,
             code_entries_: code_entries_.clone(),
         };
-        unsafe {
-            (*profiler.profiles).set_cpu_profiler(&mut profiler);
-        }
         Self::get_profilers_manager().add_profiler(isolate, &profiler);
 
         if logging_mode == CpuProfilingLoggingMode::EagerLogging {
@@ -692,8 +868,7 @@ impl CpuProfiler {
     }
 
     fn reset_profiles(&mut self) {
-        self.profiles = Box::new(CpuProfilesCollection::new(self.isolate));
-        unsafe { (*self.profiles).set_cpu_profiler(self) };
+        self.profiles = Box::new(CpuProfilesCollection::new(self.isolate as *mut _, &mut self.code_observer.code_entries));
     }
 
     fn enable_logging(&mut self) {
@@ -728,7 +903,7 @@ impl CpuProfiler {
     }
 
     fn compute_sampling_interval(&mut self) -> Duration {
-        unsafe { (*self.profiles).get_common_sampling_interval() }
+        self.profiles.get_common_sampling_interval(self.base_sampling_interval)
     }
 
     fn adjust_sampling_interval(&mut self) {
@@ -763,7 +938,7 @@ impl CpuProfiler {
     fn start_profiling(
         &mut self,
         options: CpuProfilingOptions,
-        delegate: Box<dyn DiscardedSamplesDelegate>,
+        delegate: Option<Box<dyn DiscardedSamplesDelegate>>,
     ) -> CpuProfilingResult {
         self.start_profiling_with_title(None, options, delegate)
     }
@@ -772,9 +947,9 @@ impl CpuProfiler {
         &mut self,
         title: Option<&str>,
         options: CpuProfilingOptions,
-        delegate: Box<dyn DiscardedSamplesDelegate>,
+        delegate: Option<Box<dyn DiscardedSamplesDelegate>>,
     ) -> CpuProfilingResult {
-        let result = unsafe { (*self.profiles).start_profiling(title, options, delegate) };
+        let result = self.profiles.start_profiling(title, options, delegate);
 
         // TODO(nicodubus): Revisit logic for if we want to do anything different for
         // kAlreadyStarted
@@ -826,28 +1001,29 @@ impl CpuProfiler {
         }
 
         if self.symbolizer.is_none() {
-            // self.symbolizer =
-            //     Some(Box::new(Symbolizer::new(self.code_observer.instruction_stream_map())));
+            // The code observer is boxed and outlives both the symbolizer and the
+            // processor, which are torn down in stop_processor().
+            let code_map: *const InstructionStreamMap = self.code_observer.instruction_stream_map();
+            self.symbolizer = Some(Box::new(Symbolizer::new(unsafe { &*code_map })));
         }
 
         let sampling_interval = self.compute_sampling_interval();
-        // if let Some(symbolizer) = &self.symbolizer {
+        let symbolizer: *mut Symbolizer<'static> = self.symbolizer.as_deref_mut().unwrap();
         self.processor = Some(SamplingEventsProcessor::new(
             self.isolate,
-            std::ptr::null_mut(),
-            &*self.code_observer,
-            &*self.profiles,
+            symbolizer,
+            &mut *self.code_observer,
+            &mut *self.profiles,
             sampling_interval,
             self.use_precise_sampling_,
         ));
-        // }
 
         self.is_profiling = true;
 
         // Enable stack sampling.
         if let Some(processor) = self.processor.as_mut() {
             processor.base.add_current_stack(false, None);
-            processor.base.start_synchronously().unwrap();
+            processor.start_synchronously().unwrap();
         }
     }
 
@@ -856,13 +1032,14 @@ impl CpuProfiler {
             return None;
         }
 
-        let last_profile = unsafe { (*self.profiles).is_last_profile_left(id) };
+        let last_profile = self.profiles.is_last_profile_left(id);
         if last_profile {
             self.stop_processor();
         }
 
-        let profile = unsafe { (*self.profiles).stop_profiling(id) };
-
+        // The interval of the remaining profiles no longer has to accommodate
+        // the stopped one.
+        let stopped = self.profiles.stop_profiling(id).is_some();
         self.adjust_sampling_interval();
 
         //   DCHECK(self.profiling_scope.is_some());
@@ -870,22 +1047,167 @@ impl CpuProfiler {
             self.disable_logging();
         }
 
-        profile
+        // A stopped profile is the most recently finished one.
+        if stopped {
+            self.profiles.profiles().last().map(|profile| profile.as_ref())
+        } else {
+            None
+        }
     }
 
     fn stop_processor(&mut self) {
         self.is_profiling = false;
         if let Some(mut processor) = self.processor.take() {
-            processor.base.stop_synchronously();
+            processor.stop_synchronously();
         }
+        self.symbolizer = None;
     }
 
     fn get_profiles_count(&self) -> i32 {
-        unsafe {
-             (*self.profiles).profiles().len() as i32
-        }
+        self.profiles.profiles().len() as i32
     }
 
     fn get_profile(&self, index: usize) -> Option<&CpuProfile> {
-        unsafe {
-            (*self.profiles).profiles().get(index).map(|profile| profile.as_ref()).map
\ No newline at end of file
+        self.profiles.profiles().get(index).map(|profile| profile.as_ref())
+    }
+
+    fn delete_all_profiles(&mut self) {
+        if self.is_profiling {
+            self.stop_processor();
+        }
+        self.reset_profiles();
+    }
+
+    fn delete_profile(&mut self, id: ProfilerId) {
+        self.profiles.remove_profile(id);
+        if self.profiles.profiles().is_empty() && !self.is_profiling {
+            // If this was the last profile, clean up all accessory data as well.
+            self.reset_profiles();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include::libplatform::libplatform::{
+        new_single_threaded_default_platform, pump_message_loop, IdleTaskSupport, InProcessStackDumping,
+        MessageLoopBehavior,
+    };
+    use crate::profiler::profile_generator::{CodeEntry, CodeType, ProfilingMode};
+    use std::sync::atomic::AtomicUsize;
+
+    fn new_code_entry(name: &str) -> Box<CodeEntry> {
+        Box::new(CodeEntry::new(0, name, "a.js", 1, 1, None, false, CodeType::JS))
+    }
+
+    fn add_sample(profiles: &CpuProfilesCollection, code_map: &InstructionStreamMap, pc: usize, caller_pc: usize) {
+        let mut sample = TickSample::new();
+        sample.pc = pc as *mut std::ffi::c_void;
+        sample.stack[0] = caller_pc as *mut std::ffi::c_void;
+        sample.frames_count = 1;
+        let symbolized = Symbolizer::new(code_map).symbolize_tick_sample(&sample);
+        profiles.add_path_to_current_profiles(
+            sample.timestamp,
+            &symbolized.stack_trace,
+            symbolized.src_line,
+            true,
+            sample.sampling_interval_,
+            sample.state,
+            sample.embedder_state,
+            0,
+            None,
+        );
+    }
+
+    #[test]
+    fn start_and_stop_profiling() {
+        let mut code_entries = CodeEntryStorage::new();
+        let mut profiles = CpuProfilesCollection::new(std::ptr::null_mut(), &mut code_entries);
+        let started = profiles.start_profiling(Some("a"), CpuProfilingOptions::default(), None);
+        assert_eq!(started.status, CpuProfilingStatus::Started);
+        let again = profiles.start_profiling(Some("a"), CpuProfilingOptions::default(), None);
+        assert_eq!(again, CpuProfilingResult { id: started.id, status: CpuProfilingStatus::AlreadyStarted });
+        assert!(profiles.is_last_profile_left(started.id));
+
+        assert_eq!(profiles.stop_profiling(started.id).map(|profile| profile.title()), Some("a"));
+        assert!(profiles.stop_profiling(started.id).is_none());
+        assert_eq!(profiles.profiles().len(), 1);
+    }
+
+    #[test]
+    fn stopped_profile_no_longer_constrains_the_sampling_interval() {
+        let mut code_entries = CodeEntryStorage::new();
+        let mut profiles = CpuProfilesCollection::new(std::ptr::null_mut(), &mut code_entries);
+        let options = |interval_us| {
+            CpuProfilingOptions::new(ProfilingMode::kLeafNodeLineNumbers, CpuProfilingOptions::K_NO_SAMPLE_LIMIT, interval_us)
+        };
+        let fast = profiles.start_profiling(None, options(300), None).id;
+        profiles.start_profiling(None, options(500), None);
+        let base = Duration::from_micros(100);
+        assert_eq!(profiles.get_common_sampling_interval(base), Duration::from_micros(100));
+        profiles.stop_profiling(fast);
+        assert_eq!(profiles.get_common_sampling_interval(base), Duration::from_micros(500));
+    }
+
+    #[test]
+    fn samples_are_attributed_to_the_code_containing_the_pc() {
+        let mut code_entries = CodeEntryStorage::new();
+        let mut profiles = CpuProfilesCollection::new(std::ptr::null_mut(), &mut code_entries);
+        let mut leaf = new_code_entry("leaf");
+        let mut caller = new_code_entry("caller");
+        let mut code_map = InstructionStreamMap::new();
+        code_map.add_code(0x1000, leaf.as_mut(), 0x100);
+        code_map.add_code(0x2000, caller.as_mut(), 0x100);
+
+        // Samples taken before the profile starts are not attributed to it.
+        add_sample(&profiles, &code_map, 0x1010, 0x2040);
+        let id = profiles.start_profiling(None, CpuProfilingOptions::default(), None).id;
+        add_sample(&profiles, &code_map, 0x1010, 0x2040);
+        add_sample(&profiles, &code_map, 0x1020, 0x2040);
+        let profile = profiles.stop_profiling(id).unwrap();
+
+        assert_eq!(profile.samples_count(), 2);
+        let root = profile.top_down().root();
+        assert_eq!(root.children().len(), 1);
+        let caller_node = unsafe { &*root.children()[0] };
+        assert_eq!(unsafe { (*caller_node.entry()).name() }, "caller");
+        assert_eq!(caller_node.children().len(), 1);
+        let leaf_node = unsafe { &*caller_node.children()[0] };
+        assert_eq!(unsafe { (*leaf_node.entry()).name() }, "leaf");
+        assert_eq!(leaf_node.self_ticks(), 2);
+    }
+
+    #[test]
+    fn discarded_samples_are_reported_on_the_isolate_thread() {
+        struct CountingDelegate(Arc<AtomicUsize>);
+        impl DiscardedSamplesDelegate for CountingDelegate {
+            fn notify(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let platform =
+            new_single_threaded_default_platform(IdleTaskSupport::kDisabled, InProcessStackDumping::kDisabled, None);
+        let isolate = 0x10 as *mut crate::include::v8_platform::Isolate;
+        let mut code_entries = CodeEntryStorage::new();
+        let mut profiles = CpuProfilesCollection::new(isolate, &mut code_entries);
+        profiles.set_platform(platform.clone());
+        let mut leaf = new_code_entry("leaf");
+        let mut code_map = InstructionStreamMap::new();
+        code_map.add_code(0x1000, leaf.as_mut(), 0x100);
+        let notifications = Arc::new(AtomicUsize::new(0));
+        let options = CpuProfilingOptions::new(ProfilingMode::kLeafNodeLineNumbers, 1, 0);
+        let id = profiles
+            .start_profiling(None, options, Some(Box::new(CountingDelegate(Arc::clone(&notifications)))))
+            .id;
+        for _ in 0..3 {
+            add_sample(&profiles, &code_map, 0x1010, 0);
+        }
+        assert_eq!(notifications.load(Ordering::SeqCst), 0);
+
+        while pump_message_loop(&platform, isolate, MessageLoopBehavior::kDoNotWait) {}
+        assert_eq!(notifications.load(Ordering::SeqCst), 1);
+        assert_eq!(profiles.stop_profiling(id).unwrap().samples_count(), 1);
+    }
+}
@@ -61,20 +61,19 @@ mod v8_profiler {
         fn get_detachedness(
             v8_value: &dyn Any,
             class_id: u16,
-        ) -> EmbedderGraph::Node::Detachedness;
+        ) -> super::embedder_graph::Detachedness;
     }
 
-    pub type BuildEmbedderGraphCallback =
-        extern "C" fn(isolate: *mut std::ffi::c_void, graph: *mut EmbedderGraph, data: *mut std::ffi::c_void);
+    pub type BuildEmbedderGraphCallback = fn(
+        isolate: *mut std::ffi::c_void,
+        graph: &mut dyn super::embedder_graph::EmbedderGraph,
+        data: *mut std::ffi::c_void,
+    );
     pub type GetDetachednessCallback =
         extern "C" fn(isolate: *mut std::ffi::c_void, v8_value: &dyn Any, class_id: u16, data: *mut std::ffi::c_void)
-            -> EmbedderGraph::Node::Detachedness;
+            -> super::embedder_graph::Detachedness;
 
     pub const kUnknownObjectId: SnapshotObjectId = 0;
-
-    pub enum SamplingFlags {
-        kNone,
-    }
 }
 
 // Placeholder for api-inl.h
@@ -114,10 +113,9 @@ mod heap_snapshot_generator_inl {
     // Define necessary inline functions for heap snapshot generation
 }
 
-// Placeholder for sampling-heap-profiler.h
-mod sampling_heap_profiler {
-    // Implement SamplingHeapProfiler related structs and functions
-}
+use crate::profiler::sampling_heap_profiler::{
+    AllocationProfile, SamplingHeapProfiler, SamplingHeapProfilerHost,
+};
 
 type Address = usize;
 type SnapshotObjectId = u32;
@@ -145,26 +143,44 @@ mod v8 {
 
 }
 
-// Placeholder for v8::EmbedderGraph
-mod embedder_graph {
-    pub struct EmbedderGraph;
+/// Graph of embedder objects that is merged into a heap snapshot, like
+/// `v8::EmbedderGraph`. Built by `CppGraphBuilder` for the attached
+/// `CppHeap` and by `BuildEmbedderGraphCallback`s.
+pub mod embedder_graph {
+    use crate::heap::heap_layout::Address;
 
-    impl EmbedderGraph {
-        pub enum Node {
-            Unspecified,
-            Synthetic,
-            Detachedness {
-                kAttached: i32,
-                kMaybeDetached: i32,
-                kDetached: i32,
-            },
-        }
+    /// Identifies a node of an `EmbedderGraph`.
+    pub type NodeId = usize;
+
+    /// Whether an embedder object is still attached to a document, as
+    /// reported by `GetDetachednessCallback`s.
+    #[allow(non_camel_case_types)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Detachedness {
+        kUnknown,
+        kAttached,
+        kDetached,
+    }
+
+    /// A node for an object outside of the V8 heap.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct EmbedderNode {
+        pub name: String,
+        pub size: usize,
+    }
+
+    pub trait EmbedderGraph {
+        /// Adds a node for an object outside of the V8 heap.
+        fn add_node(&mut self, node: EmbedderNode) -> NodeId;
+        /// Returns the node of the V8 object at `object`.
+        fn v8_node(&mut self, object: Address) -> NodeId;
+        fn add_edge(&mut self, from: NodeId, to: NodeId, name: Option<&str>);
     }
 }
 
 use debug::DCHECK;
-use embedder_graph::EmbedderGraph;
-use v8_profiler::{HeapSnapshotMode, OutputStream, WriteResult, SamplingFlags};
+use embedder_graph::{Detachedness, EmbedderGraph};
+use v8_profiler::{HeapSnapshotMode, OutputStream, WriteResult};
 use utils::LocalValue;
 
 // StringsStorage
@@ -319,7 +335,7 @@ impl HeapProfiler {
             .retain(|&(cb, d)| cb != callback || d != data);
     }
 
-    pub fn build_embedder_graph(&self, _isolate: *mut Isolate, _graph: *mut EmbedderGraph) {
+    pub fn build_embedder_graph(&self, _isolate: *mut Isolate, _graph: &mut dyn EmbedderGraph) {
         // Placeholder implementation.  Needs isolate and graph access.
         // for cb in &self.build_embedder_graph_callbacks_ {
         //     (cb.0)(isolate, graph, cb.1);
@@ -334,9 +350,9 @@ impl HeapProfiler {
         self.get_detachedness_callback_ = (callback, data);
     }
 
-    pub fn get_detachedness(&self, _v8_value: &dyn Any, _class_id: u16) -> EmbedderGraph::Node::Detachedness {
+    pub fn get_detachedness(&self, _v8_value: &dyn Any, _class_id: u16) -> Detachedness {
         // Placeholder implementation
-        EmbedderGraph::Node::Unspecified
+        Detachedness::kUnknown
     }
 
     pub fn has_get_detachedness_callback(&self) -> bool {
@@ -389,22 +405,24 @@ impl HeapProfiler {
         serializer.serialize(&mut stream);
     }
 
+    /// Starts sampling allocations every |sample_interval| bytes on average.
+    /// |flags| is a mask of sampling_heap_profiler::heap_profiler::SamplingFlags.
     pub fn start_sampling_heap_profiler(
         &mut self,
+        host: Box<dyn SamplingHeapProfilerHost>,
         sample_interval: u64,
         stack_depth: i32,
-        flags: SamplingFlags,
+        flags: u32,
     ) -> bool {
         if self.sampling_heap_profiler_.is_some() {
             return false;
         }
-        self.sampling_heap_profiler_ = Some(Box::new(SamplingHeapProfiler::new(
-            std::ptr::null_mut(), //heap(), Needs heap
-            self.names_.as_ref(),
+        self.sampling_heap_profiler_ = Some(SamplingHeapProfiler::new(
+            host,
             sample_interval,
             stack_depth,
             flags,
-        )));
+        ));
         true
     }
 
@@ -413,12 +431,16 @@ impl HeapProfiler {
         self.maybe_clear_strings_storage();
     }
 
-    pub fn get_allocation_profile(&self) -> Option<AllocationProfile> {
+    pub fn get_allocation_profile(&mut self) -> Option<AllocationProfile> {
         self.sampling_heap_profiler_
-            .as_ref()
+            .as_mut()
             .map(|profiler| profiler.get_allocation_profile())
     }
 
+    pub fn sampling_heap_profiler(&mut self) -> Option<&mut SamplingHeapProfiler> {
+        self.sampling_heap_profiler_.as_deref_mut()
+    }
+
     pub fn start_heap_objects_tracking(&mut self, track_allocations: bool) {
         self.ids_.update_heap_objects_map();
         if let Some(listener) = &self.native_move_listener_ {
@@ -616,34 +638,6 @@ impl Drop for HeapSnapshot {
     }
 }
 
-// SamplingHeapProfiler
-struct SamplingHeapProfiler {
-    heap: *mut Heap, //raw pointer here
-    names: *mut StringsStorage, //raw pointer here
-    sample_interval: u64,
-    stack_depth: i32,
-    flags: SamplingFlags,
-}
-
-impl SamplingHeapProfiler {
-    fn new(heap: *mut Heap, names: *mut StringsStorage, sample_interval: u64, stack_depth: i32, flags: SamplingFlags) -> Self {
-        SamplingHeapProfiler {
-            heap,
-            names,
-            sample_interval,
-            stack_depth,
-            flags,
-        }
-    }
-
-    fn get_allocation_profile(&self) -> AllocationProfile {
-        AllocationProfile {} // Placeholder implementation
-    }
-}
-
-// AllocationProfile
-struct AllocationProfile {}
-
 // AllocationTracker
 struct AllocationTracker {
     ids: *mut HeapObjectsMap, //raw pointer here
@@ -19,3 +19,4 @@ pub mod strings-storage;
 pub mod profile-generator;
 pub mod cpu-profiler;
 pub mod symbolizer;
+pub mod pprof-serializer;
@@ -0,0 +1,418 @@
+// Copyright 2025 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+// Serializes profiles into the pprof format
+// (https://github.com/google/pprof/blob/main/proto/profile.proto), gzipped as
+// expected by `go tool pprof` and friends.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::profiler::profile_generator::{
+    ticks_in_microseconds, CpuProfile, ProfileNode, K_NO_LINE_NUMBER_INFO,
+};
+use crate::profiler::sampling_heap_profiler::{AllocationProfile, AllocationProfileNode};
+
+/// Minimal protobuf wire-format writer, sufficient for profile.proto.
+#[derive(Default)]
+pub struct ProtoWriter {
+    buffer: Vec<u8>,
+}
+
+impl ProtoWriter {
+    const WIRE_TYPE_VARINT: u32 = 0;
+    const WIRE_TYPE_LENGTH_DELIMITED: u32 = 2;
+
+    pub fn new() -> Self {
+        ProtoWriter { buffer: Vec::new() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    pub fn write_varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buffer.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        self.buffer.push(value as u8);
+    }
+
+    fn write_tag(&mut self, field: u32, wire_type: u32) {
+        self.write_varint(((field << 3) | wire_type) as u64);
+    }
+
+    /// Writes a uint64/int64 field. Zero values are skipped, as proto3 does.
+    pub fn write_uint64(&mut self, field: u32, value: u64) {
+        if value == 0 {
+            return;
+        }
+        self.write_tag(field, Self::WIRE_TYPE_VARINT);
+        self.write_varint(value);
+    }
+
+    pub fn write_int64(&mut self, field: u32, value: i64) {
+        self.write_uint64(field, value as u64);
+    }
+
+    pub fn write_bytes(&mut self, field: u32, bytes: &[u8]) {
+        self.write_tag(field, Self::WIRE_TYPE_LENGTH_DELIMITED);
+        self.write_varint(bytes.len() as u64);
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    pub fn write_string(&mut self, field: u32, value: &str) {
+        self.write_bytes(field, value.as_bytes());
+    }
+
+    pub fn write_message(&mut self, field: u32, message: ProtoWriter) {
+        self.write_bytes(field, &message.buffer);
+    }
+
+    pub fn write_packed_uint64(&mut self, field: u32, values: impl IntoIterator<Item = u64>) {
+        let mut packed = ProtoWriter::new();
+        for value in values {
+            packed.write_varint(value);
+        }
+        if !packed.buffer.is_empty() {
+            self.write_bytes(field, &packed.buffer);
+        }
+    }
+}
+
+/// One frame of a sampled stack.
+#[derive(Debug, Clone, Copy)]
+pub struct PprofFrame<'a> {
+    pub function_name: &'a str,
+    pub file_name: &'a str,
+    /// 1-based line the function starts at, or 0 if unknown.
+    pub start_line: i64,
+    /// 1-based line executing in this frame, or 0 if unknown.
+    pub line: i64,
+}
+
+/// Accumulates samples and interns their strings, functions and locations.
+pub struct PprofProfileBuilder {
+    string_table: Vec<String>,
+    string_ids: HashMap<String, i64>,
+    functions: Vec<(i64, i64, i64)>,
+    function_ids: HashMap<(i64, i64, i64), u64>,
+    locations: Vec<(u64, i64)>,
+    location_ids: HashMap<(u64, i64), u64>,
+    samples: Vec<(Vec<u64>, Vec<i64>)>,
+    sample_types: Vec<(i64, i64)>,
+    period_type: (i64, i64),
+    period: i64,
+    time_nanos: i64,
+    duration_nanos: i64,
+}
+
+impl PprofProfileBuilder {
+    /// |sample_types| lists the (type, unit) pair of every value carried by a
+    /// sample, e.g. ("samples", "count").
+    pub fn new(sample_types: &[(&str, &str)], period_type: (&str, &str), period: i64) -> Self {
+        let mut builder = PprofProfileBuilder {
+            string_table: Vec::new(),
+            string_ids: HashMap::new(),
+            functions: Vec::new(),
+            function_ids: HashMap::new(),
+            locations: Vec::new(),
+            location_ids: HashMap::new(),
+            samples: Vec::new(),
+            sample_types: Vec::new(),
+            period_type: (0, 0),
+            period,
+            time_nanos: 0,
+            duration_nanos: 0,
+        };
+        // string_table[0] must be the empty string.
+        builder.intern("");
+        builder.sample_types = sample_types
+            .iter()
+            .map(|(ty, unit)| (builder.intern(ty), builder.intern(unit)))
+            .collect();
+        builder.period_type = (builder.intern(period_type.0), builder.intern(period_type.1));
+        builder
+    }
+
+    pub fn set_time(&mut self, time_nanos: i64, duration_nanos: i64) {
+        self.time_nanos = time_nanos;
+        self.duration_nanos = duration_nanos;
+    }
+
+    fn intern(&mut self, s: &str) -> i64 {
+        if let Some(&id) = self.string_ids.get(s) {
+            return id;
+        }
+        let id = self.string_table.len() as i64;
+        self.string_table.push(s.to_string());
+        self.string_ids.insert(s.to_string(), id);
+        id
+    }
+
+    fn function_id(&mut self, frame: &PprofFrame) -> u64 {
+        let key = (
+            self.intern(frame.function_name),
+            self.intern(frame.file_name),
+            frame.start_line,
+        );
+        if let Some(&id) = self.function_ids.get(&key) {
+            return id;
+        }
+        self.functions.push(key);
+        let id = self.functions.len() as u64;
+        self.function_ids.insert(key, id);
+        id
+    }
+
+    fn location_id(&mut self, frame: &PprofFrame) -> u64 {
+        let key = (self.function_id(frame), frame.line);
+        if let Some(&id) = self.location_ids.get(&key) {
+            return id;
+        }
+        self.locations.push(key);
+        let id = self.locations.len() as u64;
+        self.location_ids.insert(key, id);
+        id
+    }
+
+    /// Adds a sample. |frames| are ordered from the leaf to the root; |values|
+    /// must match the sample types passed to the constructor.
+    pub fn add_sample(&mut self, frames: &[PprofFrame], values: &[i64]) {
+        debug_assert_eq!(values.len(), self.sample_types.len());
+        let location_ids = frames.iter().map(|frame| self.location_id(frame)).collect();
+        self.samples.push((location_ids, values.to_vec()));
+    }
+
+    /// Returns the uncompressed profile.proto message.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut profile = ProtoWriter::new();
+        for &(ty, unit) in &self.sample_types {
+            let mut value_type = ProtoWriter::new();
+            value_type.write_int64(1, ty);
+            value_type.write_int64(2, unit);
+            profile.write_message(1, value_type);
+        }
+        for (location_ids, values) in &self.samples {
+            let mut sample = ProtoWriter::new();
+            sample.write_packed_uint64(1, location_ids.iter().copied());
+            sample.write_packed_uint64(2, values.iter().map(|&v| v as u64));
+            profile.write_message(2, sample);
+        }
+        for (index, &(function_id, line)) in self.locations.iter().enumerate() {
+            let mut location = ProtoWriter::new();
+            location.write_uint64(1, index as u64 + 1);
+            let mut line_message = ProtoWriter::new();
+            line_message.write_uint64(1, function_id);
+            line_message.write_int64(2, line);
+            location.write_message(4, line_message);
+            profile.write_message(4, location);
+        }
+        for (index, &(name, file_name, start_line)) in self.functions.iter().enumerate() {
+            let mut function = ProtoWriter::new();
+            function.write_uint64(1, index as u64 + 1);
+            function.write_int64(2, name);
+            function.write_int64(3, name);
+            function.write_int64(4, file_name);
+            function.write_int64(5, start_line);
+            profile.write_message(5, function);
+        }
+        for s in &self.string_table {
+            profile.write_string(6, s);
+        }
+        profile.write_int64(9, self.time_nanos);
+        profile.write_int64(10, self.duration_nanos);
+        let mut period_type = ProtoWriter::new();
+        period_type.write_int64(1, self.period_type.0);
+        period_type.write_int64(2, self.period_type.1);
+        profile.write_message(11, period_type);
+        profile.write_int64(12, self.period);
+        profile.into_bytes()
+    }
+
+    /// Writes the gzipped profile, which is what pprof tooling consumes.
+    pub fn write_gzipped(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut encoder = GzEncoder::new(out, Compression::default());
+        encoder.write_all(&self.encode())?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+fn pprof_line(line: i32) -> i64 {
+    if line == K_NO_LINE_NUMBER_INFO {
+        0
+    } else {
+        line as i64
+    }
+}
+
+/// Collects the frames of the path from |node| up to (excluding) the root.
+fn node_stack(node: &ProfileNode) -> Vec<PprofFrame<'_>> {
+    let mut frames = Vec::new();
+    let mut current: *const ProfileNode = node;
+    unsafe {
+        while !(*current).parent().is_null() {
+            let entry = &*(*current).entry();
+            frames.push(PprofFrame {
+                function_name: entry.name(),
+                file_name: entry.resource_name(),
+                start_line: pprof_line(entry.line_number()),
+                line: pprof_line((*current).line_number()),
+            });
+            current = (*current).parent();
+        }
+    }
+    frames
+}
+
+/// Converts a CPU profile into pprof with two sample types: the number of
+/// samples and the CPU time they account for.
+pub fn build_cpu_profile(profile: &CpuProfile) -> PprofProfileBuilder {
+    let interval_us = std::cmp::max(profile.sampling_interval_us(), 1);
+    let interval_nanos = interval_us * 1000;
+    let mut builder = PprofProfileBuilder::new(
+        &[("samples", "count"), ("cpu", "nanoseconds")],
+        ("cpu", "nanoseconds"),
+        interval_nanos,
+    );
+    let start_us = ticks_in_microseconds(profile.start_time());
+    let end_us = ticks_in_microseconds(profile.end_time());
+    builder.set_time(start_us * 1000, (end_us - start_us).max(0) * 1000);
+
+    if profile.samples_count() > 0 {
+        // Aggregate the recorded samples per leaf node; the time attributed to
+        // a sample is the gap to the next sample (or the end of the profile).
+        let mut per_node: HashMap<*const ProfileNode, (i64, i64)> = HashMap::new();
+        let mut order = Vec::new();
+        let samples = profile.samples();
+        for (i, sample) in samples.iter().enumerate() {
+            let timestamp = ticks_in_microseconds(sample.timestamp);
+            let next = samples
+                .get(i + 1)
+                .map_or(end_us, |next| ticks_in_microseconds(next.timestamp));
+            let totals = per_node.entry(sample.node).or_insert_with(|| {
+                order.push(sample.node);
+                (0, 0)
+            });
+            totals.0 += 1;
+            totals.1 += (next - timestamp).max(0) * 1000;
+        }
+        for node in order {
+            let (count, nanos) = per_node[&node];
+            let frames = node_stack(unsafe { &*node });
+            builder.add_sample(&frames, &[count, nanos]);
+        }
+    } else {
+        // Samples were not recorded; fall back to the self ticks of the tree.
+        let mut pending: Vec<*const ProfileNode> = vec![profile.top_down().root()];
+        while let Some(node) = pending.pop() {
+            let node = unsafe { &*node };
+            let ticks = node.self_ticks() as i64;
+            if ticks > 0 && !node.parent().is_null() {
+                builder.add_sample(&node_stack(node), &[ticks, ticks * interval_nanos]);
+            }
+            pending.extend(node.children().iter().map(|&child| child as *const ProfileNode));
+        }
+    }
+    builder
+}
+
+/// Writes |profile| as a gzipped pprof protobuf.
+pub fn serialize_cpu_profile(profile: &CpuProfile, out: &mut dyn Write) -> io::Result<()> {
+    build_cpu_profile(profile).write_gzipped(out)
+}
+
+fn add_allocation_node_samples<'a>(
+    builder: &mut PprofProfileBuilder,
+    node: &'a AllocationProfileNode,
+    path: &mut Vec<PprofFrame<'a>>,
+) {
+    path.push(PprofFrame {
+        function_name: &node.name,
+        file_name: &node.script_name,
+        start_line: node.line as i64,
+        line: node.line as i64,
+    });
+    if !node.allocations.is_empty() {
+        let frames: Vec<PprofFrame> = path.iter().rev().copied().collect();
+        for allocation in &node.allocations {
+            let count = allocation.count as i64;
+            builder.add_sample(&frames, &[count, count * allocation.size as i64]);
+        }
+    }
+    for child in &node.children {
+        add_allocation_node_samples(builder, child, path);
+    }
+    path.pop();
+}
+
+/// Converts a sampling heap profile into pprof with the scaled number of live
+/// objects and their bytes per stack. |rate| is the mean sampling interval in
+/// bytes the profile was taken with.
+pub fn build_allocation_profile(profile: &AllocationProfile, rate: u64) -> PprofProfileBuilder {
+    let mut builder = PprofProfileBuilder::new(
+        &[("objects", "count"), ("space", "bytes")],
+        ("space", "bytes"),
+        rate as i64,
+    );
+    // The root node is synthetic and is left out of the stacks.
+    let mut path = Vec::new();
+    for child in &profile.get_root_node().children {
+        add_allocation_node_samples(&mut builder, child, &mut path);
+    }
+    builder
+}
+
+/// Writes |profile| as a gzipped pprof protobuf.
+pub fn serialize_allocation_profile(profile: &AllocationProfile, rate: u64, out: &mut dyn Write) -> io::Result<()> {
+    build_allocation_profile(profile, rate).write_gzipped(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn varint_encoding() {
+        let mut writer = ProtoWriter::new();
+        writer.write_varint(1);
+        writer.write_varint(300);
+        writer.write_varint(u64::MAX);
+        let bytes = writer.into_bytes();
+        assert_eq!(&bytes[..3], &[0x01, 0xac, 0x02]);
+        assert_eq!(bytes.len(), 3 + 10);
+        assert_eq!(bytes[bytes.len() - 1], 0x01);
+    }
+
+    #[test]
+    fn builder_interns_functions_and_locations() {
+        let mut builder = PprofProfileBuilder::new(&[("samples", "count")], ("cpu", "nanoseconds"), 1000);
+        let leaf = PprofFrame { function_name: "leaf", file_name: "a.js", start_line: 1, line: 3 };
+        let root = PprofFrame { function_name: "main", file_name: "a.js", start_line: 10, line: 12 };
+        builder.add_sample(&[leaf, root], &[2]);
+        builder.add_sample(&[root], &[1]);
+        assert_eq!(builder.functions.len(), 2);
+        assert_eq!(builder.locations.len(), 2);
+        assert_eq!(builder.samples[1].0, vec![builder.samples[0].0[1]]);
+        assert_eq!(builder.string_table[0], "");
+    }
+
+    #[test]
+    fn gzipped_output_round_trips() {
+        let mut builder = PprofProfileBuilder::new(&[("samples", "count")], ("cpu", "nanoseconds"), 1000);
+        builder.add_sample(&[PprofFrame { function_name: "f", file_name: "", start_line: 0, line: 0 }], &[1]);
+        let mut gzipped = Vec::new();
+        builder.write_gzipped(&mut gzipped).unwrap();
+        assert_eq!(&gzipped[..2], &[0x1f, 0x8b]);
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&gzipped[..]).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, builder.encode());
+    }
+}
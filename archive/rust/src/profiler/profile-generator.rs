@@ -5,9 +5,20 @@
 pub mod profile_generator {
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::collections::{HashMap, HashSet, VecDeque};
-    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
     use std::{fmt, mem, ptr};
-    use std::sync::{Arc, Mutex, MutexGuard, RecursiveMutex, RwLock};
+    use std::sync::{Arc, Mutex, MutexGuard, OnceLock, RwLock};
+
+    use crate::profiler::cpu_profiler::{
+        CpuProfilingOptions, CpuProfilingResult, CpuProfilingStatus, DiscardedSamplesDelegate,
+        ProfilerId,
+    };
+    use crate::include::v8_platform::{Isolate as V8Isolate, Platform, Task, TaskPriority};
+    use crate::include::v8_source_location::SourceLocation;
+    use crate::init::v8::V8;
+    use crate::profiler::output_stream_writer::{OutputStream, OutputStreamWriter};
+    use crate::profiler::profiler_listener::CodeEntryStorage;
+    use crate::profiler::tick_sample::internal::{EmbedderStateTag, StateTag};
 
     //use crate::base::platform::time::TimeTicks; // Assuming a similar struct exists in Rust
     //use crate::builtins::builtins::Builtin;  // Assuming a similar enum exists in Rust
@@ -25,7 +36,6 @@ pub mod profile_generator {
     pub type Isolate = usize; // Replace with actual Isolate type
     pub type Name = String; // Replace with actual Name type
     pub type Tagged<T> = T; // Replace with actual Tagged type
-    pub type OutputStream = Vec<u8>; // Replace with actual OutputStream type
 
     pub const K_NO_LINE_NUMBER_INFO: i32 = 0; //v8::CpuProfileNode::kNoLineNumberInfo;
     pub const K_HEAP_OBJECT_TAG: Address = 1;
@@ -52,11 +62,15 @@ pub mod profile_generator {
         }
 
         pub fn get_source_line_number(&self, pc_offset: i32) -> i32 {
-            if let Ok(index) = self.pc_offsets_to_lines_.binary_search_by(|probe| probe.pc_offset.cmp(&pc_offset)) {
-                self.pc_offsets_to_lines_[index].line_number
-            } else {
-                K_NO_LINE_NUMBER_INFO //v8::CpuProfileNode::kNoLineNumberInfo
+            if self.pc_offsets_to_lines_.is_empty() {
+                return K_NO_LINE_NUMBER_INFO; //v8::CpuProfileNode::kNoLineNumberInfo
             }
+            // Positions are recorded at the start of each instruction range, so the
+            // owning entry is the last one whose offset is not above |pc_offset|.
+            let index = self
+                .pc_offsets_to_lines_
+                .partition_point(|probe| probe.pc_offset <= pc_offset);
+            self.pc_offsets_to_lines_[index.saturating_sub(1)].line_number
         }
 
         pub fn get_inlining_id(&self, pc_offset: i32) -> i32 {
@@ -498,7 +512,7 @@ pub mod profile_generator {
 
     pub const K_NO_DEOPTIMIZATION_ID: i32 = -1;
 
-    #[derive(Debug, Clone)]
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
     pub struct CodeEntryAndLineNumber {
         pub code_entry: *mut CodeEntry, // Raw pointer
         pub line_number: i32,
@@ -529,7 +543,7 @@ pub mod profile_generator {
             if self.native_context_address_ == K_NULL_ADDRESS {
                 return true;
             }
-            (native_context_address & !K_HEAP_OBJECT_TAG) == native_context_address
+            (native_context_address & !K_HEAP_OBJECT_TAG) == self.native_context_address_
         }
 
         pub fn set_native_context_address(&mut self, address: Address) {
@@ -775,23 +789,35 @@ pub mod profile_generator {
             update_stats: bool,
             mode: ProfilingMode,
         ) -> *mut ProfileNode {
-            let mut current = self.root_.as_mut() as *mut ProfileNode;
+            let mut node = self.root_.as_mut() as *mut ProfileNode;
+            let mut last_entry: *mut CodeEntry = ptr::null_mut();
+            let mut parent_line_number = K_NO_LINE_NUMBER_INFO;
             for frame in path.iter().rev() {
+                if frame.code_entry.is_null() {
+                    continue;
+                }
+                last_entry = frame.code_entry;
                 unsafe {
-                    let line_number = match mode {
-                        ProfilingMode::kLeafNodeLineNumbers => src_line,
-                        _ => frame.line_number,
-                    };
-                    current = (&mut *current).find_or_add_child(frame.code_entry, line_number);
-                    if update_stats {
-                        (&mut *current).increment_self_ticks();
-                    }
-                    if mode == ProfilingMode::kAllNodesLineNumbers && frame.line_number != 0 {
-                        (&mut *current).increment_line_ticks(frame.line_number);
+                    node = (&mut *node).find_or_add_child(frame.code_entry, parent_line_number);
+                }
+                parent_line_number = if mode == ProfilingMode::kAllNodesLineNumbers {
+                    frame.line_number
+                } else {
+                    K_NO_LINE_NUMBER_INFO
+                };
+            }
+            unsafe {
+                if !last_entry.is_null() && (*last_entry).has_deopt_info() {
+                    (&mut *node).collect_deopt_info(last_entry);
+                }
+                if update_stats {
+                    (&mut *node).increment_self_ticks();
+                    if src_line != K_NO_LINE_NUMBER_INFO {
+                        (&mut *node).increment_line_ticks(src_line);
                     }
                 }
             }
-            current
+            node
         }
 
         pub fn root(&self) -> &ProfileNode {
@@ -835,20 +861,551 @@ pub mod profile_generator {
         kAllNodesLineNumbers,
     }
 
-    pub struct CpuProfiler;
+    pub type TimeTicks = Instant;
+    pub type TimeDelta = Duration;
+
+    /// Microseconds elapsed between the first profiler timestamp taken in this
+    /// process and |ticks|. Used as the time base of serialized profiles.
+    pub fn ticks_in_microseconds(ticks: TimeTicks) -> i64 {
+        static ORIGIN: OnceLock<Instant> = OnceLock::new();
+        let origin = *ORIGIN.get_or_init(Instant::now);
+        ticks.saturating_duration_since(origin).as_micros() as i64
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct SampleInfo {
+        pub node: *const ProfileNode,
+        pub timestamp: TimeTicks,
+        pub line: i32,
+        pub state_tag: StateTag,
+        pub embedder_state_tag: EmbedderStateTag,
+        pub trace_id: Option<u64>,
+    }
+
+    /// Notifies a profile's delegate that samples were discarded. Runs on the
+    /// isolate's thread rather than on the profiler thread that noticed it.
+    struct CpuProfileMaxSamplesCallbackTask {
+        delegate: Option<Box<dyn DiscardedSamplesDelegate>>,
+    }
+
+    impl Task for CpuProfileMaxSamplesCallbackTask {
+        fn run(&mut self) {
+            if let Some(mut delegate) = self.delegate.take() {
+                delegate.notify();
+            }
+        }
+    }
 
-    #[derive(Debug)]
     pub struct CpuProfile {
+        isolate_: *mut V8Isolate,
+        platform_: Option<Arc<dyn Platform>>,
         title_: String,
         options_: CpuProfilingOptions,
-        delegate_: Option<Box<DiscardedSamplesDelegate>>,
+        delegate_: Option<Box<dyn DiscardedSamplesDelegate>>,
         context_filter_: ContextFilter,
         start_time_: TimeTicks,
         end_time_: TimeTicks,
-        samples_: Vec<SampleInfo>,
+        samples_: VecDeque<SampleInfo>,
         top_down_: ProfileTree,
-        profiler_: *mut CpuProfiler, // Raw pointer
         streaming_next_sample_: usize,
         id_: ProfilerId,
+        // Number of microseconds worth of profiler ticks that should elapse before
+        // the next sample is recorded.
         next_sample_delta_: TimeDelta,
-    }
\ No newline at end of file
+    }
+
+    impl CpuProfile {
+        pub fn new(
+            isolate: *mut V8Isolate,
+            platform: Option<Arc<dyn Platform>>,
+            storage: *mut CodeEntryStorage,
+            id: ProfilerId,
+            title: &str,
+            options: CpuProfilingOptions,
+            delegate: Option<Box<dyn DiscardedSamplesDelegate>>,
+        ) -> Self {
+            CpuProfile {
+                isolate_: isolate,
+                platform_: platform,
+                title_: title.to_string(),
+                options_: options,
+                delegate_: delegate,
+                context_filter_: ContextFilter::new(K_NULL_ADDRESS),
+                start_time_: Instant::now(),
+                end_time_: Instant::now(),
+                samples_: VecDeque::new(),
+                top_down_: ProfileTree::new(0, storage),
+                streaming_next_sample_: 0,
+                id_: id,
+                next_sample_delta_: TimeDelta::ZERO,
+            }
+        }
+
+        /// Checks whether or not the given TickSample should be (sub)sampled,
+        /// given the sampling interval of the profiler that recorded it.
+        pub fn check_subsample(&mut self, source_sampling_interval: TimeDelta) -> bool {
+            // If the sampling source's sampling interval is 0, record as many samples
+            // are possible irrespective of the profile's sampling interval. Manually
+            // taken samples (via CollectSample) fall into this case as well.
+            if source_sampling_interval.is_zero() {
+                return true;
+            }
+
+            self.next_sample_delta_ = self
+                .next_sample_delta_
+                .saturating_sub(source_sampling_interval);
+            if self.next_sample_delta_.is_zero() {
+                self.next_sample_delta_ =
+                    Duration::from_micros(self.options_.sampling_interval_us() as u64);
+                return true;
+            }
+            false
+        }
+
+        /// Add pc -> ... -> main() call path to the profile.
+        pub fn add_path(
+            &mut self,
+            timestamp: TimeTicks,
+            path: &ProfileStackTrace,
+            src_line: i32,
+            update_stats: bool,
+            sampling_interval: TimeDelta,
+            state: StateTag,
+            embedder_state: EmbedderStateTag,
+            native_context_address: Address,
+            trace_id: Option<u64>,
+        ) {
+            if !self.context_filter_.accept(native_context_address) {
+                return;
+            }
+            if !self.check_subsample(sampling_interval) {
+                return;
+            }
+
+            let top_frame_node =
+                self.top_down_
+                    .add_path_from_end_stack(path, src_line, update_stats, self.options_.mode());
+
+            let max_samples = self.options_.max_samples();
+            let is_buffer_full = max_samples != CpuProfilingOptions::K_NO_SAMPLE_LIMIT
+                && self.samples_.len() >= max_samples as usize;
+            let should_record_sample = self.options_.record_samples
+                && timestamp >= self.start_time_
+                && !is_buffer_full;
+
+            if should_record_sample {
+                self.samples_.push_back(SampleInfo {
+                    node: top_frame_node,
+                    timestamp,
+                    line: src_line,
+                    state_tag: state,
+                    embedder_state_tag: embedder_state,
+                    trace_id,
+                });
+            } else if is_buffer_full {
+                // Taking the delegate ensures it is notified only once per profile.
+                if let Some(delegate) = self.delegate_.take() {
+                    if let Some(platform) = &self.platform_ {
+                        let task_runner =
+                            platform.get_foreground_task_runner(self.isolate_, TaskPriority::kUserBlocking);
+                        task_runner.post_task(
+                            Box::new(CpuProfileMaxSamplesCallbackTask { delegate: Some(delegate) }),
+                            SourceLocation::current(),
+                        );
+                    }
+                }
+            }
+        }
+
+        pub fn finish_profile(&mut self) {
+            self.end_time_ = Instant::now();
+            self.streaming_next_sample_ = self.samples_.len();
+        }
+
+        pub fn title(&self) -> &str {
+            &self.title_
+        }
+
+        pub fn top_down(&self) -> &ProfileTree {
+            &self.top_down_
+        }
+
+        pub fn samples_count(&self) -> usize {
+            self.samples_.len()
+        }
+
+        pub fn sample(&self, index: usize) -> &SampleInfo {
+            &self.samples_[index]
+        }
+
+        pub fn samples(&self) -> &VecDeque<SampleInfo> {
+            &self.samples_
+        }
+
+        pub fn sampling_interval_us(&self) -> i64 {
+            self.options_.sampling_interval_us() as i64
+        }
+
+        pub fn start_time(&self) -> TimeTicks {
+            self.start_time_
+        }
+
+        pub fn end_time(&self) -> TimeTicks {
+            self.end_time_
+        }
+
+        pub fn id(&self) -> ProfilerId {
+            self.id_
+        }
+
+        pub fn context_filter(&mut self) -> &mut ContextFilter {
+            &mut self.context_filter_
+        }
+
+        pub fn update_ticks_scale(&mut self) {
+            // Ticks are recorded unscaled; nothing to do.
+        }
+
+        /// Writes the profile in the DevTools `.cpuprofile` JSON format.
+        pub fn serialize(&self, stream: &mut dyn OutputStream) {
+            let mut serializer = CpuProfileJSONSerializer::new(self);
+            serializer.serialize(stream);
+        }
+
+        pub fn print(&self) {
+            println!("[Top down]:");
+            self.top_down_.print();
+        }
+    }
+
+    impl fmt::Debug for CpuProfile {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("CpuProfile")
+                .field("title", &self.title_)
+                .field("id", &self.id_)
+                .field("samples", &self.samples_.len())
+                .finish()
+        }
+    }
+
+    pub struct CpuProfileJSONSerializer<'a> {
+        profile_: &'a CpuProfile,
+    }
+
+    impl<'a> CpuProfileJSONSerializer<'a> {
+        pub fn new(profile: &'a CpuProfile) -> Self {
+            CpuProfileJSONSerializer { profile_: profile }
+        }
+
+        pub fn serialize(&mut self, stream: &mut dyn OutputStream) {
+            let mut writer = OutputStreamWriter::new(stream);
+            self.serialize_impl(&mut writer);
+            writer.finalize();
+        }
+
+        fn serialize_impl(&self, writer: &mut OutputStreamWriter) {
+            writer.add_string("{");
+            writer.add_string("\"nodes\":[");
+            let mut first = true;
+            self.serialize_nodes(writer, self.profile_.top_down().root(), &mut first);
+            writer.add_string("],");
+
+            writer.add_string("\"startTime\":");
+            writer.add_number(ticks_in_microseconds(self.profile_.start_time()));
+            writer.add_string(",\"endTime\":");
+            writer.add_number(ticks_in_microseconds(self.profile_.end_time()));
+
+            writer.add_string(",\"samples\":[");
+            for (i, sample) in self.profile_.samples().iter().enumerate() {
+                if i > 0 {
+                    writer.add_string(",");
+                }
+                writer.add_number(unsafe { (*sample.node).id() });
+            }
+            writer.add_string("],\"timeDeltas\":[");
+            let mut last_time = ticks_in_microseconds(self.profile_.start_time());
+            for (i, sample) in self.profile_.samples().iter().enumerate() {
+                if i > 0 {
+                    writer.add_string(",");
+                }
+                let timestamp = ticks_in_microseconds(sample.timestamp);
+                writer.add_number(timestamp - last_time);
+                last_time = timestamp;
+            }
+            writer.add_string("]}");
+        }
+
+        fn serialize_nodes(&self, writer: &mut OutputStreamWriter, node: &ProfileNode, first: &mut bool) {
+            if !*first {
+                writer.add_string(",");
+            }
+            *first = false;
+            self.serialize_node(writer, node);
+            for &child in node.children() {
+                self.serialize_nodes(writer, unsafe { &*child }, first);
+            }
+        }
+
+        fn serialize_call_frame(writer: &mut OutputStreamWriter, node: &ProfileNode) {
+            let entry = unsafe { &*node.entry() };
+            writer.add_string("{\"functionName\":");
+            Self::add_json_string(writer, entry.name());
+            writer.add_string(",\"scriptId\":");
+            writer.add_number(entry.script_id());
+            writer.add_string(",\"url\":");
+            Self::add_json_string(writer, entry.resource_name());
+            // DevTools expects zero-based line and column numbers.
+            writer.add_string(",\"lineNumber\":");
+            writer.add_number(entry.line_number() - 1);
+            writer.add_string(",\"columnNumber\":");
+            writer.add_number(entry.column_number() - 1);
+            writer.add_string("}");
+        }
+
+        fn serialize_node(&self, writer: &mut OutputStreamWriter, node: &ProfileNode) {
+            writer.add_string("{\"id\":");
+            writer.add_number(node.id());
+
+            writer.add_string(",\"hitCount\":");
+            writer.add_number(node.self_ticks());
+
+            writer.add_string(",\"callFrame\":");
+            Self::serialize_call_frame(writer, node);
+
+            let children = node.children();
+            if !children.is_empty() {
+                writer.add_string(",\"children\":[");
+                for (i, &child) in children.iter().enumerate() {
+                    if i > 0 {
+                        writer.add_string(",");
+                    }
+                    writer.add_number(unsafe { (*child).id() });
+                }
+                writer.add_string("]");
+            }
+
+            let entry = unsafe { &*node.entry() };
+            let deopt_reason = entry.bailout_reason();
+            if !deopt_reason.is_empty() {
+                writer.add_string(",\"deoptReason\":");
+                Self::add_json_string(writer, deopt_reason);
+            }
+
+            if !node.line_ticks_.is_empty() {
+                let mut line_ticks: Vec<(i32, i32)> =
+                    node.line_ticks_.iter().map(|(&line, &ticks)| (line, ticks)).collect();
+                line_ticks.sort_unstable();
+                writer.add_string(",\"positionTicks\":[");
+                for (i, (line, ticks)) in line_ticks.into_iter().enumerate() {
+                    if i > 0 {
+                        writer.add_string(",");
+                    }
+                    writer.add_string("{\"line\":");
+                    writer.add_number(line);
+                    writer.add_string(",\"ticks\":");
+                    writer.add_number(ticks);
+                    writer.add_string("}");
+                }
+                writer.add_string("]");
+            }
+            writer.add_string("}");
+        }
+
+        fn add_json_string(writer: &mut OutputStreamWriter, s: &str) {
+            writer.add_string("\"");
+            for c in s.chars() {
+                match c {
+                    '"' => writer.add_string("\\\""),
+                    '\\' => writer.add_string("\\\\"),
+                    '\n' => writer.add_string("\\n"),
+                    '\r' => writer.add_string("\\r"),
+                    '\t' => writer.add_string("\\t"),
+                    c if (c as u32) < 0x20 || (c as u32) > 0x7e => {
+                        // The stream is ASCII-only; escape everything else as
+                        // UTF-16 code units.
+                        let mut units = [0u16; 2];
+                        for unit in c.encode_utf16(&mut units) {
+                            writer.add_string(&format!("\\u{:04x}", unit));
+                        }
+                    }
+                    c => {
+                        let mut buf = [0u8; 4];
+                        writer.add_string(c.encode_utf8(&mut buf));
+                    }
+                }
+            }
+            writer.add_string("\"");
+        }
+    }
+
+    /// Collects the profiles of a single CpuProfiler. Every sample is added to
+    /// all currently running profiles.
+    pub struct CpuProfilesCollection {
+        isolate_: *mut V8Isolate,
+        platform_: Option<Arc<dyn Platform>>,
+        finished_profiles_: Vec<Box<CpuProfile>>,
+        current_profiles_: Mutex<Vec<Box<CpuProfile>>>,
+        code_entries_: *mut CodeEntryStorage,
+        last_id_: ProfilerId,
+    }
+
+    impl CpuProfilesCollection {
+        pub const K_MAX_SIMULTANEOUS_PROFILES: usize = 100;
+
+        pub fn new(isolate: *mut V8Isolate, code_entries: *mut CodeEntryStorage) -> Self {
+            CpuProfilesCollection {
+                isolate_: isolate,
+                platform_: V8::get_current_platform(),
+                finished_profiles_: Vec::new(),
+                current_profiles_: Mutex::new(Vec::new()),
+                code_entries_: code_entries,
+                last_id_: 0,
+            }
+        }
+
+        /// Sets the platform that discarded-samples notifications of
+        /// profiles started from now on are posted to, instead of the
+        /// current platform of V8.
+        pub fn set_platform(&mut self, platform: Arc<dyn Platform>) {
+            self.platform_ = Some(platform);
+        }
+
+        pub fn start_profiling(
+            &mut self,
+            title: Option<&str>,
+            options: CpuProfilingOptions,
+            delegate: Option<Box<dyn DiscardedSamplesDelegate>>,
+        ) -> CpuProfilingResult {
+            let mut current_profiles = self.current_profiles_.lock().unwrap();
+            if current_profiles.len() >= Self::K_MAX_SIMULTANEOUS_PROFILES {
+                return CpuProfilingResult {
+                    id: 0,
+                    status: CpuProfilingStatus::ErrorTooManyProfilers,
+                };
+            }
+
+            if let Some(title) = title {
+                if let Some(profile) = current_profiles.iter().find(|p| p.title() == title) {
+                    // Ignore attempts to start profile with the same title...
+                    // ... though return kAlreadyStarted to force it collect a sample.
+                    return CpuProfilingResult {
+                        id: profile.id(),
+                        status: CpuProfilingStatus::AlreadyStarted,
+                    };
+                }
+            }
+
+            self.last_id_ += 1;
+            let id = self.last_id_;
+            let profile = Box::new(CpuProfile::new(
+                self.isolate_,
+                self.platform_.clone(),
+                self.code_entries_,
+                id,
+                title.unwrap_or(""),
+                options,
+                delegate,
+            ));
+            current_profiles.push(profile);
+            CpuProfilingResult {
+                id,
+                status: CpuProfilingStatus::Started,
+            }
+        }
+
+        pub fn stop_profiling(&mut self, id: ProfilerId) -> Option<&CpuProfile> {
+            let mut current_profiles = self.current_profiles_.lock().unwrap();
+            let index = current_profiles.iter().position(|p| p.id() == id)?;
+            let mut profile = current_profiles.remove(index);
+            drop(current_profiles);
+            profile.finish_profile();
+            self.finished_profiles_.push(profile);
+            self.finished_profiles_.last().map(|p| p.as_ref())
+        }
+
+        /// Returns true if |id| belongs to the only profile that is still
+        /// recording, in which case the processor can be stopped.
+        pub fn is_last_profile_left(&self, id: ProfilerId) -> bool {
+            let current_profiles = self.current_profiles_.lock().unwrap();
+            current_profiles.len() == 1 && current_profiles[0].id() == id
+        }
+
+        pub fn profiles(&self) -> &Vec<Box<CpuProfile>> {
+            &self.finished_profiles_
+        }
+
+        pub fn remove_profile(&mut self, id: ProfilerId) {
+            self.finished_profiles_.retain(|p| p.id() != id);
+        }
+
+        /// Finds a common sampling interval dividing each profile's interval,
+        /// rounded up to the nearest multiple of the base sampling interval.
+        /// Returns zero if no profiles are attached.
+        pub fn get_common_sampling_interval(&self, base_sampling_interval: TimeDelta) -> TimeDelta {
+            let base_sampling_interval_us = base_sampling_interval.as_micros() as i64;
+            if base_sampling_interval_us == 0 {
+                return TimeDelta::ZERO;
+            }
+
+            fn greatest_common_divisor(a: i64, b: i64) -> i64 {
+                if b == 0 {
+                    a
+                } else {
+                    greatest_common_divisor(b, a % b)
+                }
+            }
+
+            let mut interval_us = 0;
+            for profile in self.current_profiles_.lock().unwrap().iter() {
+                // Snap the profile's requested sampling interval to the next multiple
+                // of the base sampling interval.
+                let profile_interval_us = std::cmp::max(
+                    (profile.sampling_interval_us() + base_sampling_interval_us - 1)
+                        / base_sampling_interval_us,
+                    1,
+                ) * base_sampling_interval_us;
+                interval_us = greatest_common_divisor(interval_us, profile_interval_us);
+            }
+            Duration::from_micros(interval_us as u64)
+        }
+
+        /// Called from profile generator thread.
+        pub fn add_path_to_current_profiles(
+            &self,
+            timestamp: TimeTicks,
+            path: &ProfileStackTrace,
+            src_line: i32,
+            update_stats: bool,
+            sampling_interval: TimeDelta,
+            state: StateTag,
+            embedder_state: EmbedderStateTag,
+            native_context_address: Address,
+            trace_id: Option<u64>,
+        ) {
+            // As starting / stopping profiles is rare relatively to this
+            // method, we don't bother minimizing the duration of lock holding,
+            // e.g. copying contents of the list to a local vector.
+            let mut current_profiles = self.current_profiles_.lock().unwrap();
+            for profile in current_profiles.iter_mut() {
+                profile.add_path(
+                    timestamp,
+                    path,
+                    src_line,
+                    update_stats,
+                    sampling_interval,
+                    state,
+                    embedder_state,
+                    native_context_address,
+                    trace_id,
+                );
+            }
+        }
+
+        pub fn update_native_context_address_for_current_profiles(&self, from: Address, to: Address) {
+            let mut current_profiles = self.current_profiles_.lock().unwrap();
+            for profile in current_profiles.iter_mut() {
+                profile.context_filter().on_move_event(from, to);
+            }
+        }
+    }
+}
@@ -356,17 +356,24 @@ impl Drop for ProfilerListener {
     fn drop(&mut self) {}
 }
 
-enum CodeEventRecordType {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CodeEventRecordType {
+    kNoEvent,
     kCodeCreation,
     kCodeMove,
     kNativeContextMove,
     kCodeDisableOpt,
     kCodeDeopt,
     kCodeDelete,
+    kReportBuiltin,
 }
 
-struct CodeEventsContainer {
-    event_type: CodeEventRecordType,
+#[derive(Clone)]
+pub(crate) struct CodeEventsContainer {
+    pub(crate) event_type: CodeEventRecordType,
+    // Sequence number assigned by the events processor; ticks are only
+    // symbolized after every code event preceding them has been applied.
+    pub(crate) order: u64,
     CodeCreateEventRecord_: CodeCreateEventRecord,
     CodeMoveEventRecord_: CodeMoveEventRecord,
     NativeContextMoveEventRecord_: NativeContextMoveEventRecord,
@@ -379,6 +386,7 @@ impl CodeEventsContainer {
     fn new(event_type: CodeEventRecordType) -> Self {
         CodeEventsContainer {
             event_type,
+            order: 0,
             CodeCreateEventRecord_: CodeCreateEventRecord {
                 instruction_start: 0,
                 entry: NonNull::dangling(),
@@ -412,28 +420,33 @@ impl CodeEventsContainer {
     }
 }
 
-struct CodeCreateEventRecord {
+#[derive(Clone)]
+pub(crate) struct CodeCreateEventRecord {
     instruction_start: usize,
     entry: NonNull<CodeEntry>,
     instruction_size: usize,
 }
 
-struct CodeMoveEventRecord {
+#[derive(Clone)]
+pub(crate) struct CodeMoveEventRecord {
     from_instruction_start: usize,
     to_instruction_start: usize,
 }
 
-struct NativeContextMoveEventRecord {
+#[derive(Clone)]
+pub(crate) struct NativeContextMoveEventRecord {
     from_address: usize,
     to_address: usize,
 }
 
-struct CodeDisableOptEventRecord {
+#[derive(Clone)]
+pub(crate) struct CodeDisableOptEventRecord {
     instruction_start: usize,
     bailout_reason: String,
 }
 
-struct CodeDeoptEventRecord {
+#[derive(Clone)]
+pub(crate) struct CodeDeoptEventRecord {
     instruction_start: usize,
     deopt_reason: String,
     deopt_id: i32,
@@ -443,12 +456,14 @@ struct CodeDeoptEventRecord {
     deopt_frame_count: usize,
 }
 
-struct CodeDeleteEventRecord {
+#[derive(Clone)]
+pub(crate) struct CodeDeleteEventRecord {
     entry: NonNull<CodeEntry>,
 }
 
 #[repr(C)]
-struct CpuProfileDeoptFrame {
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CpuProfileDeoptFrame {
     script_id: i32,
     offset: usize,
 }
@@ -4,356 +4,787 @@
 
 // src/profiler/sampling-heap-profiler.rs
 
-use std::cell::RefCell;
-use std::collections::{HashMap, BTreeMap};
-use std::f64;
-use std::mem::size_of;
-use std::rc::Rc;
-use std::time::Instant;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
 
-use lazy_static::lazy_static;
-use rand::Rng;
-use rand::rngs::ThreadRng;
+use crate::base::utils::random_number_generator::RandomNumberGenerator;
+use crate::heap::allocation_observer::allocation_observer::AllocationObserver;
+use crate::heap::gc_tracer::{GarbageCollectionReason, GarbageCollector};
+use crate::heap::heap::{Heap, HeapObjectTracker, HeapState};
+use crate::heap::memory_chunk::AllocationSpace;
+use crate::profiler::tick_sample::internal::StateTag;
 
-//use v8_rs::api::*;  // Assuming v8_rs crate exists with necessary bindings.  Needs definition.
-//use v8_rs::internal::*; // Assuming v8_rs crate exists with necessary bindings. Needs definition.
+type Address = usize;
 
 const K_TAGGED_SIZE: usize = 8; // Assuming 64-bit architecture
 
-thread_local! {
-    static NEXT_NODE_ID: RefCell<u32> = RefCell::new(0);
-    static NEXT_SAMPLE_ID: RefCell<u32> = RefCell::new(0);
-}
+/// v8::UnboundScript::kNoScriptId
+pub const K_NO_SCRIPT_ID: i32 = 0;
 
-fn next_node_id() -> u32 {
-    NEXT_NODE_ID.with(|id| {
-        let mut next_id = id.borrow_mut();
-        *next_id += 1;
-        *next_id
-    })
-}
+// v8_flags needs to be defined, for now stubbed.
+static V8_FLAGS: V8Flags = V8Flags {
+    sampling_heap_profiler_suppress_randomness: false,
+};
 
-fn next_sample_id() -> u32 {
-    NEXT_SAMPLE_ID.with(|id| {
-        let mut next_id = id.borrow_mut();
-        *next_id += 1;
-        *next_id
-    })
+struct V8Flags {
+    sampling_heap_profiler_suppress_randomness: bool,
 }
 
-// v8_flags needs to be defined, for now stubbed.
-lazy_static! {
-    static ref V8_FLAGS: V8Flags = V8Flags {
-        sampling_heap_profiler_suppress_randomness: false,
-    };
+pub mod heap_profiler {
+    /// Flags controlling which samples survive and how the profile is taken.
+    /// These are combined into a bit mask.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SamplingFlags {
+        kSamplingNoFlags = 0,
+        kSamplingForceGC = 1 << 0,
+        kSamplingIncludeObjectsCollectedByMinorGC = 1 << 1,
+        kSamplingIncludeObjectsCollectedByMajorGC = 1 << 2,
+    }
 }
 
-struct V8Flags {
-    sampling_heap_profiler_suppress_randomness: bool,
+use heap_profiler::SamplingFlags;
+
+/// v8::AllocationProfile::Allocation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    /// Size of the sampled allocation object.
+    pub size: usize,
+    /// The number of objects of such size that were sampled, scaled to
+    /// estimate the number of objects allocated.
+    pub count: u32,
 }
 
+/// v8::AllocationProfile::Node
 #[derive(Debug, Clone)]
 pub struct AllocationProfileNode {
     pub name: String,
     pub script_name: String,
     pub script_id: i32,
     pub script_position: i32,
+    /// 1-indexed line number, or AllocationProfile::K_NO_LINE_NUMBER_INFO.
     pub line: i32,
+    /// 1-indexed column number, or AllocationProfile::K_NO_COLUMN_NUMBER_INFO.
     pub column: i32,
     pub id: u32,
     pub children: Vec<Box<AllocationProfileNode>>,
     pub allocations: Vec<Allocation>,
-    pub pinned_: bool, // Added to mirror C++ version
-}
-
-#[derive(Debug, Clone)]
-pub struct Allocation {
-    pub size: usize,
-    pub count: u32,
 }
 
-#[derive(Debug, Clone)]
+/// v8::AllocationProfile::Sample
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Sample {
-    pub owner_id: u32,
+    /// id of the node in the profile tree.
+    pub node_id: u32,
+    /// Size of the sampled allocation object.
     pub size: usize,
+    /// The number of objects of such size that were sampled.
     pub count: u32,
-    pub sample_id: u32,
+    /// Unique time-ordered id of the allocation sample. Can be used to track
+    /// what samples were added or removed between two snapshots.
+    pub sample_id: u64,
 }
 
+/// A snapshot of the sampled allocations that are still considered live.
+#[derive(Debug, Clone)]
 pub struct AllocationProfile {
-    pub nodes: Vec<AllocationProfileNode>,
-    pub samples: Vec<Sample>,
+    root: AllocationProfileNode,
+    samples: Vec<Sample>,
 }
 
 impl AllocationProfile {
-    pub fn new() -> Self {
-        AllocationProfile {
-            nodes: Vec::new(),
-            samples: Vec::new(),
-        }
+    pub const K_NO_LINE_NUMBER_INFO: i32 = 0;
+    pub const K_NO_COLUMN_NUMBER_INFO: i32 = 0;
+
+    pub fn get_root_node(&self) -> &AllocationProfileNode {
+        &self.root
+    }
+
+    /// Returns the samples ordered by sample id, i.e. by allocation time.
+    pub fn get_samples(&self) -> &[Sample] {
+        &self.samples
     }
 }
 
-pub mod heap_profiler {
-    pub enum SamplingFlags {
-        kSamplingForceGC = 1 << 0,
-        kSamplingIncludeObjectsCollectedByMinorGC = 1 << 1,
-        kSamplingIncludeObjectsCollectedByMajorGC = 1 << 2,
+/// A JavaScript frame captured at a sampled allocation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampledFrame {
+    /// Debug name of the function.
+    pub name: String,
+    /// Id of the script the function belongs to, or K_NO_SCRIPT_ID.
+    pub script_id: i32,
+    /// Start position of the function in its script.
+    pub start_position: i32,
+}
+
+/// The JavaScript stack at the time of a sampled allocation.
+#[derive(Debug, Clone, Default)]
+pub struct CapturedStack {
+    /// Frames ordered from the innermost (top of stack) to the outermost.
+    pub frames: Vec<SampledFrame>,
+    /// True if frames without a function (arguments marker frames left by
+    /// deoptimization) were skipped while walking the stack.
+    pub found_arguments_marker_frames: bool,
+}
+
+/// Script information needed to resolve node positions.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptInfo {
+    pub name: String,
+    /// Positions of the line terminators in the script source.
+    pub line_ends: Vec<i32>,
+}
+
+impl ScriptInfo {
+    /// Returns the 0-based (line, column) of |position|.
+    pub fn get_position_info(&self, position: i32) -> (i32, i32) {
+        let line = self.line_ends.partition_point(|&end| end < position);
+        let line_start = if line == 0 { 0 } else { self.line_ends[line - 1] + 1 };
+        (line as i32, position - line_start)
+    }
+}
+
+/// What the sampling heap profiler needs from the isolate it is attached to.
+pub trait SamplingHeapProfilerHost {
+    /// Registers |observer| with the allocation counters of all spaces. The
+    /// observer stays valid until it is removed again.
+    fn add_allocation_observer(&mut self, observer: *const dyn AllocationObserver);
+    fn remove_allocation_observer(&mut self, observer: *const dyn AllocationObserver);
+    /// Registers |tracker| to follow the sampled objects through garbage
+    /// collections. The tracker stays valid until it is removed again.
+    fn add_object_tracker(&mut self, tracker: *const dyn HeapObjectTracker);
+    fn remove_object_tracker(&mut self, tracker: *const dyn HeapObjectTracker);
+    /// Walks the JavaScript stack, capturing at most |max_depth| frames.
+    fn capture_stack(&self, max_depth: usize) -> CapturedStack;
+    fn current_vm_state(&self) -> StateTag;
+    fn script(&self, script_id: i32) -> Option<ScriptInfo>;
+    /// Performs a full GC. Samples of objects found dead are reported back
+    /// through SamplingHeapProfiler::on_garbage_collection().
+    fn collect_all_garbage(&mut self);
+}
+
+/// Hosts the profiler on a `Heap`. The heap runs no JavaScript, so samples
+/// are attributed to the VM state.
+pub struct HeapSamplingProfilerHost {
+    heap_: *const Heap,
+}
+
+impl HeapSamplingProfilerHost {
+    /// # Safety
+    ///
+    /// |heap| must outlive the profiler using this host.
+    pub unsafe fn new(heap: *const Heap) -> Self {
+        HeapSamplingProfilerHost { heap_: heap }
+    }
+
+    fn heap(&self) -> &Heap {
+        unsafe { &*self.heap_ }
+    }
+}
+
+// The host contract keeps registered observers and trackers valid until they
+// are removed.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+impl SamplingHeapProfilerHost for HeapSamplingProfilerHost {
+    fn add_allocation_observer(&mut self, observer: *const dyn AllocationObserver) {
+        unsafe { self.heap().add_allocation_observer(observer) };
+    }
+
+    fn remove_allocation_observer(&mut self, observer: *const dyn AllocationObserver) {
+        unsafe { self.heap().remove_allocation_observer(observer) };
+    }
+
+    fn add_object_tracker(&mut self, tracker: *const dyn HeapObjectTracker) {
+        unsafe { self.heap().add_object_tracker(tracker) };
+    }
+
+    fn remove_object_tracker(&mut self, tracker: *const dyn HeapObjectTracker) {
+        self.heap().remove_object_tracker(tracker);
+    }
+
+    fn capture_stack(&self, _max_depth: usize) -> CapturedStack {
+        CapturedStack::default()
+    }
+
+    fn current_vm_state(&self) -> StateTag {
+        if self.heap().gc_state() == HeapState::NOT_IN_GC {
+            StateTag::OTHER
+        } else {
+            StateTag::GC
+        }
+    }
+
+    fn script(&self, _script_id: i32) -> Option<ScriptInfo> {
+        None
+    }
+
+    fn collect_all_garbage(&mut self) {
+        self.heap().collect_garbage(AllocationSpace::OLD_SPACE, GarbageCollectionReason::kTesting);
     }
 }
 
-pub struct Observer<'a> {
-    heap_: usize, // Heap*, needs proper translation
-    next_sample_interval_: isize,
+/// Samples allocations at a Poisson-distributed byte interval.
+pub struct SamplingAllocationObserver {
+    profiler_: *mut SamplingHeapProfiler,
     rate_: u64,
-    profiler_: &'a SamplingHeapProfiler,
-    random_: ThreadRng, //RandomNumberGenerator,
+    random_: RefCell<RandomNumberGenerator>,
+    steps_: Cell<usize>,
 }
 
-impl<'a> Observer<'a> {
-    fn new(heap: usize, rate: u64, profiler: &'a SamplingHeapProfiler, random: ThreadRng) -> Self {
-        Observer {
-            heap_: heap,
-            next_sample_interval_: rate as isize,
-            rate_: rate,
+impl SamplingAllocationObserver {
+    fn new(profiler: *mut SamplingHeapProfiler, rate: u64) -> Self {
+        SamplingAllocationObserver {
             profiler_: profiler,
-            random_: random,
+            rate_: rate,
+            random_: RefCell::new(RandomNumberGenerator::new()),
+            steps_: Cell::new(0),
         }
     }
 
-    fn get_next_sample_interval(&mut self, rate: u64) -> isize {
+    /// Number of sampling steps taken so far.
+    pub fn steps(&self) -> usize {
+        self.steps_.get()
+    }
+
+    fn get_next_sample_interval(&self, rate: u64) -> i64 {
         if V8_FLAGS.sampling_heap_profiler_suppress_randomness {
-            return rate as isize;
+            return rate as i64;
         }
-        let u: f64 = self.random_.gen_range(0.0..1.0);
-        let next: f64 = (-u.ln()) * rate as f64;
+        // The sample interval follows an exponential distribution with mean
+        // |rate|, which makes the sampled allocations a Poisson process.
+        let u = self.random_.borrow_mut().next_double();
+        let next = -u.ln() * rate as f64;
         if next < K_TAGGED_SIZE as f64 {
-            K_TAGGED_SIZE as isize
+            K_TAGGED_SIZE as i64
         } else if next > i32::MAX as f64 {
-            i32::MAX as isize
+            i32::MAX as i64
         } else {
-            next as isize
+            next as i64
         }
     }
 }
 
-pub struct SamplingHeapProfiler {
-    isolate_: usize, //Isolate*, Needs definition
-    heap_: usize, //Heap*, Needs definition
-    allocation_observer_: Observer<'static>, //HeapObserver,
-    names_: usize, //StringsStorage*, Needs definition
-    profile_root_: AllocationNode,
-    stack_depth_: i32,
-    rate_: u64,
-    flags_: u32, //v8::HeapProfiler::SamplingFlags,
-    samples_: HashMap<u32, Box<Sample>>,
-    next_sample_id_: u32
+impl AllocationObserver for SamplingAllocationObserver {
+    fn step(&self, _current_counter: i32, soon_object: usize, object_size: usize) {
+        self.steps_.set(self.steps_.get() + 1);
+        if soon_object != 0 {
+            // TODO(ofrobots): it would be better to sample the next object rather
+            // than skipping this sample epoch if soon_object happens to be null.
+            unsafe { (*self.profiler_).sample_object(soon_object, object_size) };
+        }
+    }
+
+    fn get_next_step_size(&self) -> i64 {
+        self.get_next_sample_interval(self.rate_)
+    }
+}
+
+/// Follows the sampled objects through garbage collections, like the weak
+/// handles V8 keeps for its samples.
+pub struct SamplingObjectTracker {
+    profiler_: *mut SamplingHeapProfiler,
+}
+
+impl HeapObjectTracker for SamplingObjectTracker {
+    fn tracked_objects(&self) -> Vec<Address> {
+        unsafe { (*self.profiler_).samples_.keys().copied().collect() }
+    }
+
+    fn on_object_moved(&self, from: Address, to: Address) {
+        unsafe { (*self.profiler_).on_object_moved(from, to) };
+    }
+
+    fn on_garbage_collection(&self, collector: GarbageCollector, is_live: &dyn Fn(Address) -> bool) {
+        unsafe { (*self.profiler_).on_garbage_collection(collector, is_live) };
+    }
 }
 
-#[derive(Debug)]
-struct AllocationNode {
-    parent_: Option<Box<AllocationNode>>,
+type FunctionId = u64;
+
+pub struct AllocationNode {
+    parent_: *mut AllocationNode,
     name_: String,
     script_id_: i32,
     script_position_: i32,
     id_: u32,
-    children_: HashMap<u64, Box<AllocationNode>>,
-    allocations_: HashMap<usize, u32>,
+    // Children are kept ordered by function id so profiles are stable.
+    children_: BTreeMap<FunctionId, Box<AllocationNode>>,
+    // Maps an allocation size to the number of live samples of that size.
+    allocations_: BTreeMap<usize, u32>,
+    // Pinned nodes are not disposed while the profile is being translated.
     pinned_: bool,
 }
 
 impl AllocationNode {
-    fn new(parent: Option<Box<AllocationNode>>, name: String, script_id: i32, script_position: i32, id: u32) -> Self {
+    fn new(parent: *mut AllocationNode, name: &str, script_id: i32, script_position: i32, id: u32) -> Self {
         AllocationNode {
             parent_: parent,
-            name_: name,
+            name_: name.to_string(),
             script_id_: script_id,
             script_position_: script_position,
             id_: id,
-            children_: HashMap::new(),
-            allocations_: HashMap::new(),
+            children_: BTreeMap::new(),
+            allocations_: BTreeMap::new(),
             pinned_: false,
         }
     }
 
-     fn function_id(script_id: i32, start_position: i32, name: &str) -> u64 {
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        script_id.hash(&mut hasher);
-        start_position.hash(&mut hasher);
-        name.hash(&mut hasher);
-        hasher.finish()
+    fn function_id(script_id: i32, start_position: i32, name: &str) -> FunctionId {
+        // script_id == kNoScriptId case:
+        //   Use a hash of the function name as an id. Names derived from VM state
+        //   must not collide with the builtin names. The least significant bit
+        //   of the id is set to 1.
+        if script_id == K_NO_SCRIPT_ID {
+            let mut hasher = DefaultHasher::new();
+            name.hash(&mut hasher);
+            return hasher.finish() | 1;
+        }
+        // script_id != kNoScriptId case:
+        //   Use script_id, start_position pair to uniquelly identify the node.
+        //   The least significant bit of the id is set to 0.
+        debug_assert!((start_position as u32) < (1u32 << 31));
+        ((script_id as u64) << 32) + ((start_position as u64) << 1)
+    }
+
+    fn find_child_node(&mut self, id: FunctionId) -> Option<*mut AllocationNode> {
+        self.children_.get_mut(&id).map(|child| child.as_mut() as *mut AllocationNode)
+    }
+
+    fn add_child_node(&mut self, id: FunctionId, node: Box<AllocationNode>) -> *mut AllocationNode {
+        let child = self.children_.entry(id).or_insert(node);
+        child.as_mut() as *mut AllocationNode
     }
 
-    fn find_child_node(&self, id: u64) -> Option<&AllocationNode> {
-        self.children_.get(&id).map(|child| child.as_ref())
+    pub fn id(&self) -> u32 {
+        self.id_
     }
 
-     fn add_child_node(&mut self, id: u64, mut new_child: Box<AllocationNode>) -> &mut AllocationNode {
-        new_child.parent_ = Some(Box::new(self.clone()));
-        self.children_.insert(id, new_child);
-        self.children_.get_mut(&id).map(|child| child.as_mut()).unwrap()
+    pub fn name(&self) -> &str {
+        &self.name_
     }
 }
 
+struct SampleRecord {
+    size: usize,
+    owner: *mut AllocationNode,
+    sample_id: u64,
+}
+
+pub struct SamplingHeapProfiler {
+    host_: Box<dyn SamplingHeapProfilerHost>,
+    allocation_observer_: Box<SamplingAllocationObserver>,
+    object_tracker_: Box<SamplingObjectTracker>,
+    profile_root_: Box<AllocationNode>,
+    // Live samples keyed by the address of the sampled object.
+    samples_: HashMap<Address, SampleRecord>,
+    // Samples of collected objects that the flags asked to keep.
+    retained_samples_: Vec<SampleRecord>,
+    stack_depth_: i32,
+    rate_: u64,
+    flags_: u32,
+    last_node_id_: u32,
+    last_sample_id_: u64,
+}
+
 impl SamplingHeapProfiler {
-    pub fn new(heap: usize, names: usize, rate: u64, stack_depth: i32, flags: u32) -> Self {
-        let isolate_ = 0; //Isolate::FromHeap(heap); // Placeholder
-        let random = rand::thread_rng();
-        let allocation_observer_ = Observer::new(heap, rate,  unsafe { std::mem::transmute(&()) }, random);
-        SamplingHeapProfiler {
-            isolate_: isolate_,
-            heap_: heap,
-            allocation_observer_: allocation_observer_,
-            names_: names,
-            profile_root_: AllocationNode::new(None, "(root)".to_string(), 0, 0, next_node_id()),
+    /// Creates the profiler and starts sampling right away. |rate| is the mean
+    /// number of bytes between two samples and |flags| is a mask of
+    /// SamplingFlags.
+    pub fn new(host: Box<dyn SamplingHeapProfilerHost>, rate: u64, stack_depth: i32, flags: u32) -> Box<Self> {
+        assert!(rate > 0);
+        let mut profiler = Box::new(SamplingHeapProfiler {
+            host_: host,
+            allocation_observer_: Box::new(SamplingAllocationObserver::new(std::ptr::null_mut(), rate)),
+            object_tracker_: Box::new(SamplingObjectTracker { profiler_: std::ptr::null_mut() }),
+            profile_root_: Box::new(AllocationNode::new(std::ptr::null_mut(), "(root)", K_NO_SCRIPT_ID, 0, 0)),
+            samples_: HashMap::new(),
+            retained_samples_: Vec::new(),
             stack_depth_: stack_depth,
             rate_: rate,
             flags_: flags,
-            samples_: HashMap::new(),
-            next_sample_id_: 0,
-        }
+            last_node_id_: 0,
+            last_sample_id_: 0,
+        });
+        profiler.profile_root_.id_ = profiler.next_node_id();
+        let profiler_ptr: *mut SamplingHeapProfiler = profiler.as_mut();
+        profiler.allocation_observer_.profiler_ = profiler_ptr;
+        profiler.object_tracker_.profiler_ = profiler_ptr;
+        let tracker: *const dyn HeapObjectTracker = profiler.object_tracker_.as_ref();
+        profiler.host_.add_object_tracker(tracker);
+        let observer: *const dyn AllocationObserver = profiler.allocation_observer_.as_ref();
+        profiler.host_.add_allocation_observer(observer);
+        profiler
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.rate_
+    }
+
+    pub fn allocation_observer(&self) -> &SamplingAllocationObserver {
+        &self.allocation_observer_
+    }
+
+    fn has_flag(&self, flag: SamplingFlags) -> bool {
+        self.flags_ & flag as u32 != 0
+    }
+
+    fn next_node_id(&mut self) -> u32 {
+        self.last_node_id_ += 1;
+        self.last_node_id_
+    }
+
+    fn next_sample_id(&mut self) -> u64 {
+        self.last_sample_id_ += 1;
+        self.last_sample_id_
     }
 
     fn scale_sample(&self, size: usize, count: u32) -> Allocation {
         let scale = 1.0 / (1.0 - (-(size as f64 / self.rate_ as f64)).exp());
         Allocation {
-            size: size,
+            size,
             count: (count as f64 * scale + 0.5) as u32,
         }
     }
 
-        // SampleObject implementation (partially translated, requires v8 bindings)
-    fn sample_object(&mut self, soon_object: usize, size: usize) {
-        //DisallowGarbageCollection no_gc;  // Needs equivalent. Perhaps a guard?
-
-        //DCHECK(IsMap(HeapObject::FromAddress(soon_object)->map(isolate_), isolate_));
-
-        //HandleScope scope(isolate_);
-        //Tagged<HeapObject> heap_object = HeapObject::FromAddress(soon_object);
-        //Handle<Object> obj(heap_object, isolate_);
-
-        // The C++ code uses specific V8 types and functions like HeapObject, Handle, etc.
-        // These need to be mapped to Rust equivalents using a V8 crate.  For now, stubbing out.
-        //if let Some(obj) = unsafe { v8::HeapObject::from_address(soon_object) } {
-
+    /// Records an allocation of |size| bytes about to be initialized at
+    /// |soon_object|, attributing it to the current JavaScript stack.
+    pub fn sample_object(&mut self, soon_object: Address, size: usize) {
+        // DisallowGarbageCollection no_gc;
         let node = self.add_stack();
-            *node.allocations_.entry(size).or_insert(0) += 1;
-
-        //    let sample = Sample {
-        //        size: size,
-        //        owner_id: node.id_, // Use the id from AllocationNode
-        //        count: 1,             // Count is 1 because it's a single sample
-        //        sample_id: next_sample_id(),
-        //    };
-        //    self.samples_.push(sample);
-        //}
-    }
-
-    fn add_stack(&mut self) -> &mut AllocationNode {
-        let mut node = &mut self.profile_root_;
-
-        //std::vector<Tagged<SharedFunctionInfo>> stack;
-        //JavaScriptStackFrameIterator frame_it(isolate_);
-        //int frames_captured = 0;
-        //bool found_arguments_marker_frames = false;
-        //while (!frame_it.done() && frames_captured < stack_depth_) {
-        //    JavaScriptFrame* frame = frame_it.frame();
-        //    if (IsJSFunction(frame->unchecked_function())) {
-        //      Tagged<SharedFunctionInfo> shared = frame->function()->shared();
-        //      stack.push_back(shared);
-        //      frames_captured++;
-        //    } else {
-        //      found_arguments_marker_frames = true;
-        //    }
-        //    frame_it.Advance();
-        //}
-
-        // This code needs to iterate over the JavaScript stack, which requires the V8 crate.
-        // For now, it will just return a dummy node.
-
-        node = self.find_or_add_child_node(node, "(JS)", 0, 0); // Placeholder
+        unsafe { *(*node).allocations_.entry(size).or_insert(0) += 1 };
+        let sample_id = self.next_sample_id();
+        if let Some(previous) = self.samples_.insert(soon_object, SampleRecord { size, owner: node, sample_id }) {
+            // The previous object at this address died without us noticing;
+            // drop its sample.
+            self.remove_sample(previous);
+        }
+    }
 
-        node
+    /// Tells the profiler that |collector| finished. |is_live| reports whether
+    /// the object sampled at a given address survived. Samples of dead objects
+    /// are discarded unless the flags ask to keep objects collected by this
+    /// kind of GC.
+    pub fn on_garbage_collection(&mut self, collector: GarbageCollector, is_live: &dyn Fn(Address) -> bool) {
+        let is_minor_gc = collector != GarbageCollector::MARK_COMPACTOR;
+        let should_keep_sample = if is_minor_gc {
+            self.has_flag(SamplingFlags::kSamplingIncludeObjectsCollectedByMinorGC)
+        } else {
+            self.has_flag(SamplingFlags::kSamplingIncludeObjectsCollectedByMajorGC)
+        };
+        let dead: Vec<Address> = self.samples_.keys().copied().filter(|&address| !is_live(address)).collect();
+        for address in dead {
+            let sample = self.samples_.remove(&address).unwrap();
+            if should_keep_sample {
+                // Like a sample whose weak handle has been reset: it is reported
+                // forever but no longer tracked by address.
+                self.retained_samples_.push(sample);
+            } else {
+                self.remove_sample(sample);
+            }
+        }
     }
 
-    fn find_or_add_child_node(&mut self, parent: &mut AllocationNode, name: &str, script_id: i32, start_position: i32) -> &mut AllocationNode {
-        let id = AllocationNode::function_id(script_id, start_position, name);
-        if let Some(child) = parent.find_child_node(id) {
-            return unsafe {
-                 &mut *(child as *const AllocationNode as *mut AllocationNode)
-            };
+    /// Tells the profiler that the object at |from| was moved to |to|.
+    pub fn on_object_moved(&mut self, from: Address, to: Address) {
+        if let Some(sample) = self.samples_.remove(&from) {
+            self.samples_.insert(to, sample);
         }
+    }
 
-        let new_child = AllocationNode::new(None, name.to_string(), script_id, start_position, next_node_id());
-        parent.add_child_node(id, Box::new(new_child))
+    fn remove_sample(&mut self, sample: SampleRecord) {
+        let mut node = sample.owner;
+        unsafe {
+            let count = (*node).allocations_.get_mut(&sample.size).unwrap();
+            debug_assert!(*count > 0);
+            *count -= 1;
+            if *count == 0 {
+                (*node).allocations_.remove(&sample.size);
+            }
+            while (*node).allocations_.is_empty()
+                && (*node).children_.is_empty()
+                && !(*node).parent_.is_null()
+                && !(*(*node).parent_).pinned_
+            {
+                let parent = (*node).parent_;
+                let id = AllocationNode::function_id((*node).script_id_, (*node).script_position_, &(*node).name_);
+                (*parent).children_.remove(&id);
+                node = parent;
+            }
+        }
     }
 
-    fn translate_allocation_node(&self, profile: &mut AllocationProfile, node: &AllocationNode, _scripts: &HashMap<i32, i32>) -> AllocationProfileNode {
-       // let script_name = String::new(); //Placeholder
-        let script_name = "".to_string();
-        let line = 0; //v8::AllocationProfile::kNoLineNumberInfo;
-        let column = 0; //v8::AllocationProfile::kNoColumnNumberInfo;
-        let mut allocations: Vec<Allocation> = Vec::new();
+    fn add_stack(&mut self) -> *mut AllocationNode {
+        let mut node: *mut AllocationNode = self.profile_root_.as_mut();
+        let stack = self.host_.capture_stack(self.stack_depth_.max(0) as usize);
+
+        if stack.frames.is_empty() {
+            let name = match self.host_.current_vm_state() {
+                StateTag::GC => "(GC)",
+                StateTag::PARSER => "(PARSER)",
+                StateTag::COMPILER => "(COMPILER)",
+                StateTag::BYTECODE_COMPILER => "(BYTECODE_COMPILER)",
+                StateTag::OTHER => "(V8 API)",
+                StateTag::EXTERNAL => "(EXTERNAL)",
+                StateTag::LOGGING => "(LOGGING)",
+                StateTag::IDLE => "(IDLE)",
+                // Treat atomics wait as a normal JS event; we don't care about the
+                // difference for allocations.
+                StateTag::ATOMICS_WAIT => "(ATOMICS_WAIT)",
+                StateTag::JS => "(JS)",
+            };
+            return self.find_or_add_child_node(node, name, K_NO_SCRIPT_ID, 0);
+        }
 
-        for (size, count) in &node.allocations_ {
-            allocations.push(self.scale_sample(*size, *count));
+        // We need to process the stack in reverse order as the top of the stack is
+        // the first element in the list.
+        for frame in stack.frames.iter().rev() {
+            node = self.find_or_add_child_node(node, &frame.name, frame.script_id, frame.start_position);
         }
 
-        let mut children: Vec<Box<AllocationProfileNode>> = Vec::new();
-        for (_id, child) in &node.children_ {
-            children.push(Box::new(self.translate_allocation_node(profile, child.as_ref(), _scripts)));
+        if stack.found_arguments_marker_frames {
+            node = self.find_or_add_child_node(node, "(deopt)", K_NO_SCRIPT_ID, 0);
         }
 
-       AllocationProfileNode {
+        node
+    }
+
+    fn find_or_add_child_node(
+        &mut self,
+        parent: *mut AllocationNode,
+        name: &str,
+        script_id: i32,
+        start_position: i32,
+    ) -> *mut AllocationNode {
+        let id = AllocationNode::function_id(script_id, start_position, name);
+        if let Some(child) = unsafe { (*parent).find_child_node(id) } {
+            debug_assert_eq!(unsafe { &(*child).name_ }, name);
+            return child;
+        }
+        let node_id = self.next_node_id();
+        let new_child = Box::new(AllocationNode::new(parent, name, script_id, start_position, node_id));
+        unsafe { (*parent).add_child_node(id, new_child) }
+    }
+
+    fn translate_allocation_node(
+        &self,
+        node: *mut AllocationNode,
+        scripts: &mut HashMap<i32, Option<ScriptInfo>>,
+    ) -> AllocationProfileNode {
+        let node = unsafe { &mut *node };
+        // By pinning the node we make sure its children won't get disposed if
+        // a GC kicks in during the tree retrieval.
+        node.pinned_ = true;
+        let mut script_name = String::new();
+        let mut line = AllocationProfile::K_NO_LINE_NUMBER_INFO;
+        let mut column = AllocationProfile::K_NO_COLUMN_NUMBER_INFO;
+        if node.script_id_ != K_NO_SCRIPT_ID {
+            let script = scripts
+                .entry(node.script_id_)
+                .or_insert_with(|| self.host_.script(node.script_id_));
+            if let Some(script) = script {
+                script_name = script.name.clone();
+                let (pos_line, pos_column) = script.get_position_info(node.script_position_);
+                line = pos_line + 1;
+                column = pos_column + 1;
+            }
+        }
+        let allocations = node
+            .allocations_
+            .iter()
+            .map(|(&size, &count)| self.scale_sample(size, count))
+            .collect();
+        let children_ptrs: Vec<*mut AllocationNode> = node
+            .children_
+            .values_mut()
+            .map(|child| child.as_mut() as *mut AllocationNode)
+            .collect();
+        let children = children_ptrs
+            .into_iter()
+            .map(|child| Box::new(self.translate_allocation_node(child, scripts)))
+            .collect();
+        node.pinned_ = false;
+        AllocationProfileNode {
             name: node.name_.clone(),
-            script_name: script_name,
+            script_name,
             script_id: node.script_id_,
             script_position: node.script_position_,
-            line: line,
-            column: column,
+            line,
+            column,
             id: node.id_,
-            children: children,
-            allocations: allocations,
-            pinned_: false, // Added to mirror C++ version,
-       }
+            children,
+            allocations,
+        }
     }
 
     pub fn get_allocation_profile(&mut self) -> AllocationProfile {
-        // if (flags_ & v8::HeapProfiler::kSamplingForceGC) {
-        //     isolate_->heap()->CollectAllGarbage(
-        //         GCFlag::kNoFlags, GarbageCollectionReason::kSamplingProfiler);
-        // }
-        // std::map<int, Handle<Script>> scripts;
-        // {
-        //     Script::Iterator iterator(isolate_);
-        //     for (Tagged<Script> script = iterator.Next(); !script.is_null();
-        //          script = iterator.Next()) {
-        //       scripts[script->id()] = handle(script, isolate_);
-        //   }
-        // }
-
-        let scripts: HashMap<i32, i32> = HashMap::new(); // placeholder
-
-        let mut profile = AllocationProfile::new();
-        let root = self.translate_allocation_node(&mut profile, &self.profile_root_, &scripts);
-
-        profile.nodes.push(root);
-        profile.samples = self.build_samples();
-        profile
+        if self.has_flag(SamplingFlags::kSamplingForceGC) {
+            self.host_.collect_all_garbage();
+        }
+        let mut scripts = HashMap::new();
+        let root: *mut AllocationNode = self.profile_root_.as_mut();
+        let root = self.translate_allocation_node(root, &mut scripts);
+        AllocationProfile {
+            root,
+            samples: self.build_samples(),
+        }
     }
 
     fn build_samples(&self) -> Vec<Sample> {
-        let mut samples: Vec<Sample> = Vec::new();
-        for (_id, sample) in &self.samples_ {
-            samples.push(Sample {
-                owner_id: 0, //sample.owner_id,
+        let mut samples: Vec<Sample> = self
+            .samples_
+            .values()
+            .chain(self.retained_samples_.iter())
+            .map(|sample| Sample {
+                node_id: unsafe { (*sample.owner).id_ },
                 size: sample.size,
-                count: 1, //self.scale_sample(sample.size, 1).count,
-                sample_id: 0, //sample.sample_id,
-            });
-        }
+                count: self.scale_sample(sample.size, 1).count,
+                sample_id: sample.sample_id,
+            })
+            .collect();
+        samples.sort_by_key(|sample| sample.sample_id);
         samples
     }
 }
 
-use std::hash::{Hash, Hasher};
\ No newline at end of file
+impl Drop for SamplingHeapProfiler {
+    fn drop(&mut self) {
+        let observer: *const dyn AllocationObserver = self.allocation_observer_.as_ref();
+        self.host_.remove_allocation_observer(observer);
+        let tracker: *const dyn HeapObjectTracker = self.object_tracker_.as_ref();
+        self.host_.remove_object_tracker(tracker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[derive(Default)]
+    struct TestHost {
+        stack: Rc<RefCell<CapturedStack>>,
+        observers: Rc<Cell<i32>>,
+    }
+
+    impl SamplingHeapProfilerHost for TestHost {
+        fn add_allocation_observer(&mut self, _observer: *const dyn AllocationObserver) {
+            self.observers.set(self.observers.get() + 1);
+        }
+        fn remove_allocation_observer(&mut self, _observer: *const dyn AllocationObserver) {
+            self.observers.set(self.observers.get() - 1);
+        }
+        fn add_object_tracker(&mut self, _tracker: *const dyn HeapObjectTracker) {}
+        fn remove_object_tracker(&mut self, _tracker: *const dyn HeapObjectTracker) {}
+        fn capture_stack(&self, max_depth: usize) -> CapturedStack {
+            let mut stack = self.stack.borrow().clone();
+            stack.frames.truncate(max_depth);
+            stack
+        }
+        fn current_vm_state(&self) -> StateTag {
+            StateTag::GC
+        }
+        fn script(&self, _script_id: i32) -> Option<ScriptInfo> {
+            Some(ScriptInfo { name: "test.js".to_string(), line_ends: vec![10, 20] })
+        }
+        fn collect_all_garbage(&mut self) {}
+    }
+
+    fn frame(name: &str, start_position: i32) -> SampledFrame {
+        SampledFrame { name: name.to_string(), script_id: 1, start_position }
+    }
+
+    #[test]
+    fn samples_are_attributed_to_stacks() {
+        let host = TestHost::default();
+        let stack = host.stack.clone();
+        let observers = host.observers.clone();
+        let mut profiler = SamplingHeapProfiler::new(Box::new(host), 1024, 16, 0);
+        assert_eq!(observers.get(), 1);
+
+        *stack.borrow_mut() = CapturedStack { frames: vec![frame("inner", 15), frame("outer", 0)], ..Default::default() };
+        profiler.sample_object(0x1000, 32);
+        profiler.sample_object(0x2000, 32);
+        stack.borrow_mut().frames.clear();
+        profiler.sample_object(0x3000, 64);
+
+        let profile = profiler.get_allocation_profile();
+        let root = profile.get_root_node();
+        assert_eq!(root.children.len(), 2);
+        let outer = root.children.iter().find(|node| node.name == "outer").unwrap();
+        assert_eq!((outer.line, outer.column), (1, 1));
+        let inner = &outer.children[0];
+        assert_eq!(inner.name, "inner");
+        assert_eq!((inner.line, inner.column), (2, 5));
+        assert_eq!(inner.allocations.len(), 1);
+        assert!(root.children.iter().any(|node| node.name == "(GC)"));
+        assert_eq!(profile.get_samples().len(), 3);
+        assert!(profile.get_samples().windows(2).all(|w| w[0].sample_id < w[1].sample_id));
+
+        drop(profiler);
+        assert_eq!(observers.get(), 0);
+    }
+
+    #[test]
+    fn dead_objects_are_removed_unless_flagged() {
+        let host = TestHost::default();
+        let stack = host.stack.clone();
+        let flags = SamplingFlags::kSamplingIncludeObjectsCollectedByMinorGC as u32;
+        let mut profiler = SamplingHeapProfiler::new(Box::new(host), 1024, 16, flags);
+        *stack.borrow_mut() = CapturedStack { frames: vec![frame("f", 0)], ..Default::default() };
+        profiler.sample_object(0x1000, 32);
+        profiler.sample_object(0x2000, 32);
+
+        profiler.on_garbage_collection(GarbageCollector::SCAVENGER, &|address| address != 0x1000);
+        assert_eq!(profiler.get_allocation_profile().get_root_node().children.len(), 1);
+
+        profiler.on_object_moved(0x2000, 0x4000);
+        profiler.on_garbage_collection(GarbageCollector::MARK_COMPACTOR, &|_| false);
+        let profile = profiler.get_allocation_profile();
+        // Only the sample kept across the minor GC survives.
+        assert_eq!(profile.get_samples().len(), 1);
+        assert_eq!(profile.get_root_node().children.len(), 1);
+        assert_eq!(profile.get_root_node().children[0].allocations, vec![profiler.scale_sample(32, 1)]);
+    }
+
+    #[test]
+    fn heap_samples_follow_moves_and_deaths() {
+        use crate::heap::heap::{AllocationType, HeapOptions};
+        use crate::heap::heap_layout::Tagged;
+
+        let heap = Heap::new(HeapOptions::default());
+        let host = unsafe { HeapSamplingProfilerHost::new(&heap) };
+        let mut profiler = SamplingHeapProfiler::new(Box::new(host), 64, 16, 0);
+        let roots: Vec<_> = (0..64)
+            .map(|_| heap.create_root(Tagged::strong(heap.allocate_fixed_array(4, AllocationType::kYoung))))
+            .collect();
+        for _ in 0..64 {
+            heap.allocate_fixed_array(4, AllocationType::kYoung);
+        }
+        assert!(profiler.allocation_observer().steps() > 0);
+
+        let collect = |space| heap.collect_garbage(space, GarbageCollectionReason::kTesting);
+        collect(AllocationSpace::NEW_SPACE);
+        collect(AllocationSpace::NEW_SPACE);
+        collect(AllocationSpace::OLD_SPACE);
+        let live: Vec<Address> = roots.iter().map(|&root| heap.root_object(root).address()).collect();
+        assert!(!profiler.samples_.is_empty());
+        assert!(profiler.samples_.keys().all(|address| live.contains(address)));
+
+        for root in roots {
+            heap.dispose_root(root);
+        }
+        collect(AllocationSpace::OLD_SPACE);
+        assert!(profiler.get_allocation_profile().get_samples().is_empty());
+        drop(profiler);
+        heap.allocate_fixed_array(4, AllocationType::kYoung);
+    }
+
+    #[test]
+    fn sample_interval_is_bounded() {
+        let observer = SamplingAllocationObserver::new(std::ptr::null_mut(), 1);
+        for _ in 0..100 {
+            assert!(observer.get_next_step_size() >= K_TAGGED_SIZE as i64);
+        }
+    }
+}
@@ -3,27 +3,14 @@
 // found in the LICENSE file.
 
 pub mod symbolizer {
+    use std::collections::BTreeMap;
     use std::fmt;
 
-    // Placeholder for ProfileStackTrace - needs definition based on original C++
-    #[derive(Debug, Clone)]
-    pub struct ProfileStackTrace {
-        // Add necessary fields here based on the original C++ definition
-    }
-
-    // Placeholder for TickSample - needs definition based on original C++
-    #[derive(Debug, Clone)]
-    pub struct TickSample {
-        // Add necessary fields here based on the original C++ definition
-    }
-
-    // Placeholder for CodeEntry - needs definition based on original C++
-    #[derive(Debug)]
-    pub struct CodeEntry {
-        // Add necessary fields here based on the original C++ definition
-    }
+    use crate::profiler::profile_generator::{
+        CodeEntry, CodeEntryAndLineNumber, ProfileStackTrace, K_NO_LINE_NUMBER_INFO,
+    };
+    use crate::profiler::tick_sample::internal::{StateTag, TickSample};
 
-    // Placeholder for Address - needs definition based on original C++
     pub type Address = usize;
 
     pub struct SymbolizedSample {
@@ -42,11 +29,108 @@ pub mod symbolizer {
             }
         }
 
+        /// Resolves the pc and every captured stack frame of |sample| to code
+        /// entries. The resulting stack trace is ordered from the leaf frame to
+        /// the outermost frame; unresolvable frames are dropped.
         pub fn symbolize_tick_sample(&self, sample: &TickSample) -> SymbolizedSample {
-            // Placeholder implementation
+            let mut stack_trace: ProfileStackTrace =
+                Vec::with_capacity(sample.frames_count as usize + 3);
+            // The ProfileNode knows nothing about all versions of generated code for
+            // the same JS function. The line number information associated with
+            // the latest version of generated code is used to find a source line
+            // number for a JS function. Then, the detected source line is passed to
+            // ProfileNode to increase the tick count for this source line.
+            let no_line_info = K_NO_LINE_NUMBER_INFO;
+            let mut src_line = no_line_info;
+            let mut src_line_not_found = true;
+
+            if !sample.pc.is_null() {
+                if sample.has_external_callback && sample.state == StateTag::EXTERNAL {
+                    // Don't use PC when in external callback code, as it can point
+                    // inside a callback's code, and we will erroneously report
+                    // that a callback calls itself.
+                    let callback =
+                        unsafe { sample.tos_or_external_callback_entry.external_callback_entry };
+                    if let Some((entry, _)) = self.find_entry(callback as Address) {
+                        stack_trace.push(CodeEntryAndLineNumber {
+                            code_entry: entry,
+                            line_number: no_line_info,
+                        });
+                    }
+                } else {
+                    let mut attributed_pc = sample.pc as Address;
+                    let mut pc_entry = self.find_entry(attributed_pc);
+                    // If there is no pc_entry, we're likely in native code. Find out
+                    // if the top of the stack (the return address) was pointing
+                    // inside a JS function, meaning that we have encountered a
+                    // frameless invocation.
+                    if pc_entry.is_none() && !sample.has_external_callback {
+                        attributed_pc =
+                            unsafe { sample.tos_or_external_callback_entry.tos } as Address;
+                        pc_entry = self.find_entry(attributed_pc);
+                    }
+                    // If pc is in the function code before it set up stack frame or
+                    // after the frame was destroyed, StackFrameIteratory incorrectly
+                    // thinks that ebp contains the return address of the current
+                    // function and skips the caller's frame. Check for this case and
+                    // just skip such samples.
+                    if let Some((entry, instruction_start)) = pc_entry {
+                        let pc_offset = (attributed_pc - instruction_start) as i32;
+                        // TODO(petermarshall): pc_offset can still be negative in
+                        // some cases.
+                        src_line = unsafe { (*entry).get_source_line(pc_offset) };
+                        if src_line == no_line_info {
+                            src_line = unsafe { (*entry).line_number() };
+                        }
+                        src_line_not_found = false;
+                        stack_trace.push(CodeEntryAndLineNumber {
+                            code_entry: entry,
+                            line_number: src_line,
+                        });
+                    }
+                }
+            }
+
+            for i in 0..sample.frames_count as usize {
+                let stack_pos = sample.stack[i] as Address;
+                let Some((entry, instruction_start)) = self.find_entry(stack_pos) else {
+                    continue;
+                };
+                let pc_offset = (stack_pos - instruction_start) as i32;
+                if let Some(inline_stack) = unsafe { (*entry).get_inline_stack(pc_offset) } {
+                    let most_inlined_frame_line_number = unsafe { (*entry).get_source_line(pc_offset) };
+                    for inline_frame in inline_stack {
+                        stack_trace.push(inline_frame.clone());
+                    }
+                    // This is a bit of a messy hack. The line number for the most-inlined
+                    // frame (the function at the end of the chain of function calls) has
+                    // the wrong line number in inline_stack. The actual line number in
+                    // this function is stored in the SourcePositionTable in entry. We fix
+                    // up the line number for the most-inlined frame here.
+                    let index = stack_trace.len() - inline_stack.len();
+                    stack_trace[index].line_number = most_inlined_frame_line_number;
+                    if src_line_not_found {
+                        src_line = most_inlined_frame_line_number;
+                        src_line_not_found = false;
+                    }
+                } else {
+                    let line_number = unsafe { (*entry).get_source_line(pc_offset) };
+                    // Skip unresolved frames (e.g. internal frame) and get source line
+                    // of the first JS caller.
+                    if src_line_not_found {
+                        src_line = line_number;
+                        src_line_not_found = false;
+                    }
+                    stack_trace.push(CodeEntryAndLineNumber {
+                        code_entry: entry,
+                        line_number,
+                    });
+                }
+            }
+
             SymbolizedSample {
-                stack_trace: ProfileStackTrace {}, // Replace with actual logic
-                src_line: 0,                     // Replace with actual logic
+                stack_trace,
+                src_line,
             }
         }
 
@@ -54,28 +138,106 @@ pub mod symbolizer {
             self.code_map_
         }
 
-        fn find_entry(&self, address: Address) -> Option<&CodeEntry> {
-            // Placeholder implementation
-            // Should return Option<&CodeEntry>
-            None
+        /// Returns the code entry covering |address| and the start address of
+        /// its instructions.
+        fn find_entry(&self, address: Address) -> Option<(*mut CodeEntry, Address)> {
+            self.code_map_.find_entry(address)
         }
     }
 
-    // Placeholder for InstructionStreamMap - needs definition based on original C++
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy)]
+    struct CodeEntryMapInfo {
+        entry: *mut CodeEntry,
+        size: usize,
+    }
+
+    /// Maps instruction start addresses to the code entries of the generated
+    /// code living there. Entries are non-overlapping; adding a new entry
+    /// evicts every entry it overlaps.
+    #[derive(Debug, Default)]
     pub struct InstructionStreamMap {
-        // Add necessary fields here based on the original C++ definition
+        code_map_: BTreeMap<Address, CodeEntryMapInfo>,
     }
 
     impl InstructionStreamMap {
         pub fn new() -> Self {
-            InstructionStreamMap {}
+            InstructionStreamMap {
+                code_map_: BTreeMap::new(),
+            }
+        }
+
+        pub fn add_code(&mut self, addr: Address, entry: *mut CodeEntry, size: usize) {
+            self.clear_code_ranges_in_range(addr, addr + size);
+            unsafe { (*entry).set_instruction_start(addr) };
+            self.code_map_.insert(addr, CodeEntryMapInfo { entry, size });
+        }
+
+        /// Removes |entry| from the map. Returns false if it was not registered.
+        pub fn remove_code(&mut self, entry: *mut CodeEntry) -> bool {
+            let addr = unsafe { (*entry).instruction_start() };
+            match self.code_map_.get(&addr) {
+                Some(info) if info.entry == entry => {
+                    self.code_map_.remove(&addr);
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        pub fn move_code(&mut self, from: Address, to: Address) {
+            if from == to {
+                return;
+            }
+            let Some(info) = self.code_map_.remove(&from) else {
+                return;
+            };
+            self.clear_code_ranges_in_range(to, to + info.size);
+            unsafe { (*info.entry).set_instruction_start(to) };
+            self.code_map_.insert(to, info);
+        }
+
+        pub fn find_entry(&self, addr: Address) -> Option<(*mut CodeEntry, Address)> {
+            let (&start, info) = self.code_map_.range(..=addr).next_back()?;
+            if addr < start + info.size {
+                Some((info.entry, start))
+            } else {
+                None
+            }
+        }
+
+        pub fn size(&self) -> usize {
+            self.code_map_.len()
+        }
+
+        pub fn clear(&mut self) {
+            self.code_map_.clear();
+        }
+
+        pub fn get_estimated_memory_usage(&self) -> usize {
+            self.code_map_.len()
+                * (std::mem::size_of::<Address>() + std::mem::size_of::<CodeEntryMapInfo>())
+        }
+
+        fn clear_code_ranges_in_range(&mut self, start: Address, end: Address) {
+            let mut overlapping = Vec::new();
+            if let Some((&left, info)) = self.code_map_.range(..start).next_back() {
+                if left + info.size > start {
+                    overlapping.push(left);
+                }
+            }
+            overlapping.extend(self.code_map_.range(start..end).map(|(&addr, _)| addr));
+            for addr in overlapping {
+                self.code_map_.remove(&addr);
+            }
         }
     }
 
     impl fmt::Display for InstructionStreamMap {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "InstructionStreamMap")
+            for (addr, info) in &self.code_map_ {
+                writeln!(f, "{:#x} {:5} {}", addr, info.size, unsafe { (*info.entry).name() })?;
+            }
+            Ok(())
         }
     }
-}
\ No newline at end of file
+}
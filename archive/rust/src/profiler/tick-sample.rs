@@ -7,66 +7,69 @@
 //       indicates the need for an external unwinder dependency.
 // use v8_unwinder as unwinder;
 
-use std::time::Duration;
-use std::time::SystemTime;
 use std::option::Option;
 
-mod base {
-    pub mod platform {
-        pub struct TimeTicks {
-            time: SystemTime,
-        }
-
-        impl TimeTicks {
-            pub fn now() -> Self {
-                TimeTicks {
-                    time: SystemTime::now(),
-                }
-            }
-        }
-
-        pub struct TimeDelta {
-            duration: Duration,
-        }
-
-        impl TimeDelta {
-            pub fn from_microseconds(microseconds: i64) -> Self {
-                TimeDelta {
-                    duration: Duration::from_micros(microseconds as u64),
-                }
-            }
-        }
-    }
-}
-
 mod common {
     pub mod globals {
         pub const K_BITS_PER_BYTE: usize = 8;
     }
 }
 
-mod internal {
+pub mod internal {
     // Placeholder for Isolate.  A complete Isolate implementation is
     // outside the scope of this conversion.
-    pub struct Isolate {}
+    pub struct Isolate {
+        /// Stack pointer of the outermost JS entry frame, or 0 when the isolate
+        /// is not executing JavaScript (ThreadLocalTop::js_entry_sp_).
+        pub js_entry_sp: usize,
+        /// The VM state of the isolate thread (Isolate::current_vm_state()).
+        pub current_vm_state: StateTag,
+    }
 
     impl Isolate {
         pub fn new() -> Self {
-            Isolate {}
+            Isolate {
+                js_entry_sp: 0,
+                current_vm_state: StateTag::OTHER,
+            }
         }
     }
 
-    #[repr(C)]
-    pub struct RegisterState {}
+    pub use crate::libsampler::sampler::sampler::RegisterState;
 
     #[repr(C)]
-    pub struct SampleInfo {}
+    pub struct SampleInfo {
+        /// Number of frames collected.
+        pub frames_count: usize,
+        /// Current VM state.
+        pub vm_state: StateTag,
+        /// Address of the external callback entry, if any.
+        pub external_callback_entry: *mut std::ffi::c_void,
+    }
 
-    #[derive(Debug, Copy, Clone)]
+    impl Default for SampleInfo {
+        fn default() -> Self {
+            SampleInfo {
+                frames_count: 0,
+                vm_state: StateTag::OTHER,
+                external_callback_entry: std::ptr::null_mut(),
+            }
+        }
+    }
+
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
     #[repr(C)]
     pub enum StateTag {
+        JS,
+        GC,
+        PARSER,
+        BYTECODE_COMPILER,
+        COMPILER,
         OTHER,
         EXTERNAL,
+        ATOMICS_WAIT,
+        IDLE,
+        LOGGING,
     }
 
     #[derive(Debug, Copy, Clone)]
@@ -75,6 +78,12 @@ mod internal {
         EMPTY,
     }
 
+    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+    pub enum RecordCEntryFrame {
+        KIncludeCEntryFrame,
+        KSkipCEntryFrame,
+    }
+
     /// TickSample captures the information collected for each sample.
     #[repr(C)]
     pub struct TickSample {
@@ -83,8 +92,8 @@ mod internal {
         pub context: *mut std::ffi::c_void, // Address of the incumbent native context.
         pub embedder_context: *mut std::ffi::c_void, // Address of the embedder native context.
 
-        pub timestamp: base::platform::TimeTicks,
-        pub sampling_interval_: base::platform::TimeDelta, // Sampling interval used to capture.
+        pub timestamp: std::time::Instant,
+        pub sampling_interval_: std::time::Duration, // Sampling interval used to capture.
 
         pub state: StateTag, // The state of the VM.
         pub embedder_state: EmbedderStateTag,
@@ -105,6 +114,12 @@ mod internal {
         pub external_callback_entry: *mut std::ffi::c_void,
     }
 
+    impl Default for TickSample {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl TickSample {
         pub const K_MAX_FRAMES_COUNT_LOG2: u32 = 8;
         pub const K_MAX_FRAMES_COUNT: usize = (1 << Self::K_MAX_FRAMES_COUNT_LOG2) - 1;
@@ -115,8 +130,8 @@ mod internal {
                 tos_or_external_callback_entry: TosOrExternalCallbackEntry { external_callback_entry: std::ptr::null_mut() },
                 context: std::ptr::null_mut(),
                 embedder_context: std::ptr::null_mut(),
-                timestamp: base::platform::TimeTicks::now(),
-                sampling_interval_: base::platform::TimeDelta::from_microseconds(0),
+                timestamp: std::time::Instant::now(),
+                sampling_interval_: std::time::Duration::ZERO,
                 state: StateTag::OTHER,
                 embedder_state: EmbedderStateTag::EMPTY,
                 frames_count: 0,
@@ -127,10 +142,6 @@ mod internal {
             }
         }
 
-        pub enum RecordCEntryFrame {
-            KIncludeCEntryFrame,
-            KSkipCEntryFrame,
-        }
 
         /// Initialize a tick sample from the isolate.
         /// \param isolate The isolate.
@@ -149,21 +160,62 @@ mod internal {
             record_c_entry_frame: RecordCEntryFrame,
             update_stats: bool,
             use_simulator_reg_state: bool,
-            sampling_interval: base::platform::TimeDelta,
+            sampling_interval: std::time::Duration,
             trace_id: Option<u64>,
         ) {
-            // Implementation details would go here, interacting with the isolate
-            // and register state to populate the TickSample fields.
-            self.timestamp = base::platform::TimeTicks::now();
+            self.timestamp = std::time::Instant::now();
             self.sampling_interval_ = sampling_interval;
             self.update_stats_ = update_stats;
             self.trace_id_ = trace_id;
-            // Placeholder logic:
-            self.pc = std::ptr::null_mut();
             self.context = std::ptr::null_mut();
             self.embedder_context = std::ptr::null_mut();
-            self.state = StateTag::OTHER;
             self.embedder_state = EmbedderStateTag::EMPTY;
+
+            let mut regs = RegisterState {
+                pc: state.pc,
+                sp: state.sp,
+                fp: state.fp,
+                lr: state.lr,
+            };
+            let mut info = SampleInfo::default();
+            let mut out_state = StateTag::OTHER;
+            let frames_limit = Self::K_MAX_FRAMES_COUNT;
+            if !Self::get_stack_sample(
+                isolate,
+                &mut regs,
+                record_c_entry_frame,
+                &mut self.stack,
+                frames_limit,
+                &mut info,
+                Some(&mut out_state),
+                use_simulator_reg_state,
+            ) {
+                // It is executing JS but failed to collect a stack trace.
+                // Mark the sample as spoiled.
+                self.pc = std::ptr::null_mut();
+                self.frames_count = 0;
+                return;
+            }
+
+            self.state = if out_state == StateTag::EXTERNAL {
+                StateTag::EXTERNAL
+            } else {
+                info.vm_state
+            };
+            self.pc = regs.pc;
+            self.frames_count = info.frames_count as u16;
+            self.has_external_callback = !info.external_callback_entry.is_null();
+            if self.has_external_callback {
+                self.tos_or_external_callback_entry = TosOrExternalCallbackEntry {
+                    external_callback_entry: info.external_callback_entry,
+                };
+            } else if isolate.js_entry_sp != 0 && !regs.sp.is_null() {
+                // The top of stack is only used as a fallback when the pc does not
+                // resolve to a code object, e.g. when sampled inside a frameless
+                // builtin prologue.
+                let tos = unsafe { *(regs.sp as *const *mut std::ffi::c_void) };
+                self.tos_or_external_callback_entry = TosOrExternalCallbackEntry { tos };
+            }
         }
 
         /// Get a call stack sample from the isolate.
@@ -201,28 +253,93 @@ mod internal {
             out_state: Option<&mut StateTag>,
             use_simulator_reg_state: bool,
         ) -> bool {
-            // Implementation details for stack sampling would go here.  This requires
-            // potentially unsafe operations and external unwinding libraries.
-
-            // Placeholder logic:
+            let _ = use_simulator_reg_state;
+            sample_info.frames_count = 0;
+            sample_info.vm_state = isolate.current_vm_state;
+            sample_info.external_callback_entry = std::ptr::null_mut();
             if let Some(state_out) = out_state {
-                *state_out = StateTag::OTHER;
+                *state_out = sample_info.vm_state;
+            }
+            if sample_info.vm_state == StateTag::GC {
+                return true;
+            }
+
+            let js_entry_sp = isolate.js_entry_sp;
+            if js_entry_sp == 0 {
+                // Not executing JS now.
+                return true;
             }
-            sample_info as *mut SampleInfo; // Use sample_info to suppress warning
-            state as *mut RegisterState; // Use state to suppress warning
-            isolate as *mut Isolate; // Use isolate to suppress warning
 
-            match record_c_entry_frame {
-                TickSample::RecordCEntryFrame::KIncludeCEntryFrame => {},
-                TickSample::RecordCEntryFrame::KSkipCEntryFrame => {}
+            let sp = state.sp as usize;
+            let mut fp = state.fp as usize;
+            // Check whether we interrupted setup/teardown of a stack frame in JS code.
+            if sp == 0 || sp > js_entry_sp || fp == 0 {
+                return false;
+            }
+
+            let frames_limit = frames_limit.min(frames.len());
+            let mut i = 0;
+            if let RecordCEntryFrame::KSkipCEntryFrame = record_c_entry_frame {
+                // The innermost frame belongs to the runtime function that requested
+                // the sample; drop it from the walk.
+                if let Some(caller_fp) = Self::caller_fp(fp, sp, js_entry_sp) {
+                    fp = caller_fp;
+                }
             }
 
-            use_simulator_reg_state as bool;
-            frames_limit as usize;
-            frames[0] = std::ptr::null_mut();
+            // Walk the frame pointer chain. Every frame is bounded by the stack
+            // pointer at the time of the interrupt and the JS entry frame, so a
+            // corrupted or partially set up frame terminates the walk instead of
+            // reading outside the thread's stack.
+            while i < frames_limit {
+                let Some(return_address) = Self::return_address(fp, sp, js_entry_sp) else {
+                    break;
+                };
+                frames[i] = return_address as *mut std::ffi::c_void;
+                i += 1;
+                match Self::caller_fp(fp, sp, js_entry_sp) {
+                    Some(caller_fp) => fp = caller_fp,
+                    None => break,
+                }
+            }
+            sample_info.frames_count = i;
             true
         }
 
+        const K_FP_ALIGNMENT: usize = std::mem::size_of::<usize>();
+
+        fn frame_in_bounds(fp: usize, sp: usize, js_entry_sp: usize) -> bool {
+            fp >= sp
+                && fp.is_multiple_of(Self::K_FP_ALIGNMENT)
+                && fp + 2 * std::mem::size_of::<usize>() <= js_entry_sp
+        }
+
+        /// Reads the return address stored right above the saved frame pointer.
+        fn return_address(fp: usize, sp: usize, js_entry_sp: usize) -> Option<usize> {
+            if !Self::frame_in_bounds(fp, sp, js_entry_sp) {
+                return None;
+            }
+            let pc = unsafe { *((fp + std::mem::size_of::<usize>()) as *const usize) };
+            if pc == 0 {
+                None
+            } else {
+                Some(pc)
+            }
+        }
+
+        /// Reads the caller's frame pointer, which must be strictly above |fp|.
+        fn caller_fp(fp: usize, sp: usize, js_entry_sp: usize) -> Option<usize> {
+            if !Self::frame_in_bounds(fp, sp, js_entry_sp) {
+                return None;
+            }
+            let caller_fp = unsafe { *(fp as *const usize) };
+            if caller_fp <= fp || caller_fp >= js_entry_sp {
+                None
+            } else {
+                Some(caller_fp)
+            }
+        }
+
         pub fn print(&self) {
             println!("TickSample {{");
             println!("  pc: {:?}", self.pc);
@@ -242,7 +359,7 @@ mod internal {
     }
 
     const _: () = assert!(
-        std::mem::size_of::<u16>() * common::globals::K_BITS_PER_BYTE >= TickSample::K_MAX_FRAMES_COUNT_LOG2 as usize,
+        std::mem::size_of::<u16>() * super::common::globals::K_BITS_PER_BYTE >= TickSample::K_MAX_FRAMES_COUNT_LOG2 as usize,
         "sizeof(frames_count) * kBitsPerByte >= kMaxFramesCountLog2"
     );
 }
\ No newline at end of file
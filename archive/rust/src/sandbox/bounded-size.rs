@@ -2,30 +2,12 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod bounded_size {
-    // use crate::common::globals::*; // Assuming globals are defined elsewhere, adapt as needed
+pub use crate::sandbox::sandbox::K_MAX_SAFE_BUFFER_SIZE_FOR_SANDBOX;
 
-    // Placeholder for Address type, assuming it's a memory address
-    pub type Address = usize;
+/// Bounded sizes are stored shifted left by this amount, so that a
+/// corrupted value read back is at most `K_MAX_SAFE_BUFFER_SIZE_FOR_SANDBOX`.
+/// Together with a sandboxed pointer and the guard regions this bounds
+/// every access to a buffer to the sandbox.
+pub const K_BOUNDED_SIZE_SHIFT: u32 = 29;
 
-    /// Reads a BoundedSize from the given memory address.
-    #[inline]
-    pub fn read_bounded_size_field(field_address: Address) -> usize {
-        // SAFETY: This operation is inherently unsafe as it dereferences a raw pointer.
-        // The caller must ensure that `field_address` is a valid memory location
-        // containing a size_t value.
-        unsafe { *(field_address as *const usize) }
-    }
-
-    /// Writes a BoundedSize to the given memory address.
-    #[inline]
-    pub fn write_bounded_size_field(field_address: Address, value: usize) {
-        // SAFETY: This operation is inherently unsafe as it dereferences a raw pointer.
-        // The caller must ensure that `field_address` is a valid memory location
-        // and that writing to it is safe.
-        unsafe {
-            *(field_address as *mut usize) = value;
-        }
-    }
-
-} // mod bounded_size
\ No newline at end of file
+const _: () = assert!(usize::MAX >> K_BOUNDED_SIZE_SHIFT == K_MAX_SAFE_BUFFER_SIZE_FOR_SANDBOX);
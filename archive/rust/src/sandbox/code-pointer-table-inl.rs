@@ -2,68 +2,17 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
-#![allow(unused_variables)]
+use std::sync::atomic::Ordering;
 
-// Assuming V8_COMPRESS_POINTERS is always enabled for this Rust conversion.
-// Otherwise, feature flags or conditional compilation would be needed.
-
-use std::sync::atomic::{AtomicUsize, Ordering};
-
-// Placeholder types and constants.  Need to be replaced with actual definitions
-// from other V8 Rust modules that corresponds to the C++ code.
-type Address = usize;
-type CodeEntrypointTag = usize; // Could be an enum
-type CodePointerHandle = usize;
-type Space = usize; // Replace with a proper type representing a memory space
-struct CFIMetadataWriteScope<'a>(&'a str);
-
-impl<'a> CFIMetadataWriteScope<'a> {
-    fn new(name: &'a str) -> Self {
-        CFIMetadataWriteScope(name)
-    }
-}
-
-const kMarkingBit: Address = 1;
-const kCodeEntrypointTagShift: usize = 0;
-const kFreeCodePointerTableEntryTag: CodeEntrypointTag = 0;
-const kFreeEntryTag: Address = 1;
-const kNullAddress: Address = 0;
-const kCodePointerHandleShift: usize = 0;
-const kCodePointerHandleMarker: CodePointerHandle = 1;
-const kNullCodePointerHandle: CodePointerHandle = 0;
-
-macro_rules! DCHECK {
-    ($condition:expr) => {
-        if !$condition {
-            panic!("DCHECK failed: {}", stringify!($condition));
-        }
-    };
-}
-
-macro_rules! USE {
-    ($expression:expr) => {
-        let _ = $expression;
-    };
-}
-
-/// Represents an entry in the CodePointerTable.
-#[derive(Debug)]
-pub struct CodePointerTableEntry {
-    code_: AtomicUsize,
-    entrypoint_: AtomicUsize,
-}
+use crate::heap::heap_layout::Address;
+use crate::sandbox::code_entrypoint_tag::CodeEntrypointTag;
+use crate::sandbox::code_pointer_table::{
+    CodePointerHandle, CodePointerTable, CodePointerTableEntry, CodePointerTableSpace,
+};
+use crate::sandbox::external_entity_table::ExternalEntityTableEntry;
 
 impl CodePointerTableEntry {
-    /// Creates a new `CodePointerTableEntry`.
-    pub fn new() -> Self {
-        CodePointerTableEntry {
-            code_: AtomicUsize::new(0),
-            entrypoint_: AtomicUsize::new(0),
-        }
-    }
-
-    /// Makes this entry a code pointer entry.
+    #[inline]
     pub fn make_code_pointer_entry(
         &self,
         code: Address,
@@ -71,231 +20,87 @@ impl CodePointerTableEntry {
         tag: CodeEntrypointTag,
         mark_as_alive: bool,
     ) {
-        DCHECK!(code & kMarkingBit == 0);
-        DCHECK!(entrypoint >> kCodeEntrypointTagShift == 0);
-        DCHECK!(tag != kFreeCodePointerTableEntryTag);
-
-        let mut code_to_store = code;
+        debug_assert_eq!(code as u64 & Self::K_MARKING_BIT, Self::K_MARKING_BIT, "code must be a heap object");
+        let mut code = code as u64 & !Self::K_MARKING_BIT;
         if mark_as_alive {
-            code_to_store |= kMarkingBit;
+            code |= Self::K_MARKING_BIT;
         }
-
-        self.entrypoint_
-            .store(entrypoint ^ tag, Ordering::Relaxed);
-        self.code_.store(code_to_store, Ordering::Relaxed);
+        self.entrypoint.store(entrypoint as u64 ^ tag as u64, Ordering::Relaxed);
+        self.code.store(code, Ordering::Relaxed);
     }
 
-    /// Gets the entrypoint for this entry.
+    /// Loads the entrypoint, removing `tag` with an XOR. A different tag
+    /// leaves tag bits set and the result is not a valid code address.
+    #[inline]
     pub fn get_entrypoint(&self, tag: CodeEntrypointTag) -> Address {
-        DCHECK!(!self.is_freelist_entry());
-        self.entrypoint_.load(Ordering::Relaxed) ^ tag
+        (self.entrypoint.load(Ordering::Relaxed) ^ tag as u64) as Address
     }
 
-    /// Sets the entrypoint for this entry.
-    pub fn set_entrypoint(&self, value: Address, tag: CodeEntrypointTag) {
-        DCHECK!(!self.is_freelist_entry());
-        DCHECK!(value >> kCodeEntrypointTagShift == 0);
-        DCHECK!(tag != kFreeCodePointerTableEntryTag);
-        self.entrypoint_.store(value ^ tag, Ordering::Relaxed);
+    #[inline]
+    pub fn set_entrypoint(&self, entrypoint: Address, tag: CodeEntrypointTag) {
+        self.entrypoint.store(entrypoint as u64 ^ tag as u64, Ordering::Relaxed);
     }
 
-    /// Gets the code object for this entry.
+    /// Loads the code object. Loading a free entry yields an invalid
+    /// pointer, as its free entry tag remains set.
+    #[inline]
     pub fn get_code_object(&self) -> Address {
-        DCHECK!(!self.is_freelist_entry());
-        self.code_.load(Ordering::Relaxed) | kMarkingBit
-    }
-
-    /// Sets the code object for this entry.
-    pub fn set_code_object(&self, new_value: Address) {
-        DCHECK!(!self.is_freelist_entry());
-        // SetContent shouldn't change the marking state of the entry. Currently this
-        // is always automatically the case, but if this ever fails, we might need to
-        // manually copy the marking bit.
-        DCHECK_EQ!(self.code_.load(Ordering::Relaxed) & kMarkingBit, new_value & kMarkingBit);
-        self.code_.store(new_value, Ordering::Relaxed);
-    }
-
-    /// Makes this entry a freelist entry.
-    pub fn make_freelist_entry(&self, next_entry_index: u32) {
-        let value = kFreeEntryTag | next_entry_index as Address;
-        self.entrypoint_.store(value, Ordering::Relaxed);
-        self.code_.store(kNullAddress, Ordering::Relaxed);
-    }
-
-    /// Checks if this entry is a freelist entry.
-    pub fn is_freelist_entry(&self) -> bool {
-        let entrypoint = self.entrypoint_.load(Ordering::Relaxed);
-        (entrypoint & kFreeEntryTag) == kFreeEntryTag
-    }
-
-    /// Gets the next freelist entry index.
-    pub fn get_next_freelist_entry_index(&self) -> u32 {
-        self.entrypoint_.load(Ordering::Relaxed) as u32
-    }
-
-    /// Marks this entry.
-    pub fn mark(&self) {
-        let old_value = self.code_.load(Ordering::Relaxed);
-        let new_value = old_value | kMarkingBit;
-
-        let success = self.code_.compare_exchange_strong(
-            old_value,
-            new_value,
-            Ordering::Relaxed,
-            Ordering::Relaxed,
-        ).is_ok();
-
-        DCHECK!(success || (old_value & kMarkingBit) == kMarkingBit);
-        USE!(success);
-    }
-
-    /// Unmarks this entry.
-    pub fn unmark(&self) {
-        let value = self.code_.load(Ordering::Relaxed);
-        let value = value & !kMarkingBit;
-        self.code_.store(value, Ordering::Relaxed);
+        (self.code.load(Ordering::Relaxed) | Self::K_MARKING_BIT) as Address
     }
 
-    /// Checks if this entry is marked.
-    pub fn is_marked(&self) -> bool {
-        let value = self.code_.load(Ordering::Relaxed);
-        value & kMarkingBit != 0
+    /// Stores the code object, keeping the mark bit.
+    #[inline]
+    pub fn set_code_object(&self, code: Address) {
+        let code = code as u64 & !Self::K_MARKING_BIT;
+        self.code
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old_code| {
+                Some(code | (old_code & Self::K_MARKING_BIT))
+            })
+            .unwrap();
     }
 }
 
-/// Represents a table of code pointers.
-pub struct CodePointerTable {
-    entries: Vec<CodePointerTableEntry>,
-}
-
 impl CodePointerTable {
-    /// Creates a new `CodePointerTable`.
-    pub fn new(size: usize) -> Self {
-        let mut entries = Vec::with_capacity(size);
-        for _ in 0..size {
-            entries.push(CodePointerTableEntry::new());
-        }
-        CodePointerTable { entries }
-    }
-
-    fn at(&self, index: usize) -> &CodePointerTableEntry {
-        &self.entries[index]
-    }
-
-    /// Gets the entrypoint for the given handle and tag.
-    pub fn get_entrypoint(
-        &self,
-        handle: CodePointerHandle,
-        tag: CodeEntrypointTag,
-    ) -> Address {
-        let index = self.handle_to_index(handle);
-        self.at(index).get_entrypoint(tag)
-    }
-
-    /// Gets the code object for the given handle.
-    pub fn get_code_object(&self, handle: CodePointerHandle) -> Address {
-        let index = self.handle_to_index(handle);
-        DCHECK_NE!(index, 0);
-        self.at(index).get_code_object()
-    }
-
-    /// Sets the entrypoint for the given handle and value.
-    pub fn set_entrypoint(
-        &self,
-        handle: CodePointerHandle,
-        value: Address,
-        tag: CodeEntrypointTag,
-    ) {
-        DCHECK_NE!(kNullCodePointerHandle, handle);
-        let index = self.handle_to_index(handle);
-        let _write_scope = CFIMetadataWriteScope::new("CodePointerTable write");
-        self.at(index).set_entrypoint(value, tag);
-    }
-
-    /// Sets the code object for the given handle and value.
-    pub fn set_code_object(&self, handle: CodePointerHandle, value: Address) {
-        DCHECK_NE!(kNullCodePointerHandle, handle);
-        let index = self.handle_to_index(handle);
-        let _write_scope = CFIMetadataWriteScope::new("CodePointerTable write");
-        self.at(index).set_code_object(value);
-    }
-
-    // Placeholder for the actual memory allocation mechanism.
-    fn allocate_entry(&self, _space: Space) -> usize {
-        0 // Placeholder: Real allocation logic needed
-    }
-
-    /// Allocates and initializes a new entry in the table.
+    #[inline]
     pub fn allocate_and_initialize_entry(
         &self,
-        space: Space,
+        space: &CodePointerTableSpace,
         code: Address,
         entrypoint: Address,
         tag: CodeEntrypointTag,
     ) -> CodePointerHandle {
-        //DCHECK!(space.belongs_to(self));  // Need to implement Space::belongs_to()
         let index = self.allocate_entry(space);
-        let _write_scope = CFIMetadataWriteScope::new("CodePointerTable write");
-        self.at(index).make_code_pointer_entry(
-            code,
-            entrypoint,
-            tag,
-            true // space.allocate_black(), // Need to implement Space::allocate_black()
-        );
-        self.index_to_handle(index)
+        self.at(index)
+            .make_code_pointer_entry(code, entrypoint, tag, space.allocate_black());
+        Self::index_to_handle(index)
     }
 
-    /// Marks the entry associated with the given handle.
-    pub fn mark(&self, space: Space, handle: CodePointerHandle) {
-        //DCHECK!(space.belongs_to(this)); // Need to implement Space::belongs_to()
-        if handle == kNullCodePointerHandle {
-            return;
-        }
-
-        let index = self.handle_to_index(handle);
-        //DCHECK!(space.contains(index)); // Need to implement Space::contains()
-
-        let _write_scope = CFIMetadataWriteScope::new("CodePointerTable write");
-        self.at(index).mark();
+    #[inline]
+    pub fn get_entrypoint(&self, handle: CodePointerHandle, tag: CodeEntrypointTag) -> Address {
+        self.at(Self::handle_to_index(handle)).get_entrypoint(tag)
     }
 
-    /// Iterates through the active entries in the table.
-    pub fn iterate_active_entries_in<Callback>(&self, space: Space, callback: Callback)
-    where
-        Callback: Fn(CodePointerHandle, Address),
-    {
-        self.iterate_entries_in(space, |index| {
-            if !self.at(index).is_freelist_entry() {
-                let handle = self.index_to_handle(index);
-                let code_object = self.at(index).get_code_object();
-                callback(handle, code_object);
-            }
-        });
+    #[inline]
+    pub fn get_code_object(&self, handle: CodePointerHandle) -> Address {
+        self.at(Self::handle_to_index(handle)).get_code_object()
     }
 
-    fn iterate_entries_in<Callback>(&self, _space: Space, callback: Callback)
-    where
-        Callback: Fn(usize),
-    {
-        for index in 0..self.entries.len() {
-            callback(index);
-        }
+    #[inline]
+    pub fn set_entrypoint(&self, handle: CodePointerHandle, entrypoint: Address, tag: CodeEntrypointTag) {
+        debug_assert_ne!(Self::handle_to_index(handle), 0, "the null entry is read-only");
+        self.at(Self::handle_to_index(handle)).set_entrypoint(entrypoint, tag);
     }
 
-    /// Converts a handle to an index.
-    fn handle_to_index(&self, handle: CodePointerHandle) -> usize {
-        let index = handle >> kCodePointerHandleShift;
-        DCHECK_EQ!(
-            handle,
-            (index << kCodePointerHandleShift) | kCodePointerHandleMarker
-        );
-        index
+    #[inline]
+    pub fn set_code_object(&self, handle: CodePointerHandle, code: Address) {
+        debug_assert_ne!(Self::handle_to_index(handle), 0, "the null entry is read-only");
+        self.at(Self::handle_to_index(handle)).set_code_object(code);
     }
 
-    /// Converts an index to a handle.
-    fn index_to_handle(&self, index: usize) -> CodePointerHandle {
-        let handle = index << kCodePointerHandleShift;
-        DCHECK_EQ!(index, handle >> kCodePointerHandleShift);
-        handle | kCodePointerHandleMarker
+    #[inline]
+    pub fn mark(&self, handle: CodePointerHandle) {
+        if Self::handle_to_index(handle) != 0 {
+            self.at(Self::handle_to_index(handle)).set_mark_bit();
+        }
     }
-}
\ No newline at end of file
+}
@@ -2,396 +2,123 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)] // Suppress warnings about unused code
-
-#[cfg(feature = "v8_compress_pointers")]
-mod code_pointer_table {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::marker::PhantomData;
-
-    //use crate::base::atomicops; // Assuming equivalent functionality exists
-    //use crate::base::memory;    // Assuming equivalent functionality exists
-    //use crate::common::globals;   // Assuming equivalent functionality exists
-    use crate::code_entrypoint_tag::CodeEntrypointTag; // Assuming this exists
-    use crate::external_entity_table::ExternalEntityTable; // Assuming this exists
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::heap::heap_layout::K_HEAP_OBJECT_TAG;
+use crate::sandbox::code_entrypoint_tag::CodeEntrypointTag;
+use crate::sandbox::external_entity_table::{ExternalEntityTable, ExternalEntityTableEntry, Space};
+use crate::sandbox::trusted_pointer_table::IndirectPointerHandle;
+
+const MB: usize = 1024 * 1024;
+
+/// A code pointer handle is the index of the entry shifted left by
+/// `K_CODE_POINTER_HANDLE_SHIFT`, with `K_CODE_POINTER_HANDLE_MARKER` set to
+/// tell it apart from trusted pointer handles.
+pub type CodePointerHandle = IndirectPointerHandle;
+pub const K_NULL_CODE_POINTER_HANDLE: CodePointerHandle = 0;
+pub const K_CODE_POINTER_HANDLE_MARKER: u32 = 0x1;
+
+pub const K_CODE_POINTER_TABLE_RESERVATION_SIZE: usize = 16 * MB;
+pub const K_CODE_POINTER_HANDLE_SHIFT: u32 = 12;
+pub const K_MAX_CODE_POINTERS: usize =
+    K_CODE_POINTER_TABLE_RESERVATION_SIZE / std::mem::size_of::<CodePointerTableEntry>();
+const _: () = assert!(((u32::MAX >> K_CODE_POINTER_HANDLE_SHIFT) as usize) < K_MAX_CODE_POINTERS);
+
+/// The entries of a CodePointerTable. Each entry contains a pointer to a
+/// Code object as well as the Code's entrypoint, with its
+/// `CodeEntrypointTag` XORed into the top bits.
+///
+/// The mark bit is the heap object tag bit of the code pointer, which is
+/// cleared in unmarked entries and set again on load. Free entries store
+/// `kFreeCodePointerTableEntryTag` with the freelist link instead of the
+/// code pointer, and a zero entrypoint. The link is shifted past the mark
+/// bit so that marking a free entry cannot corrupt the freelist.
+#[repr(C)]
+pub struct CodePointerTableEntry {
+    pub(crate) entrypoint: AtomicU64,
+    pub(crate) code: AtomicU64,
+}
 
-    // Constants (replace with actual values)
-    const K_CODE_POINTER_TABLE_ENTRY_SIZE: usize = 8; // Example size
-    const K_CODE_POINTER_TABLE_RESERVATION_SIZE: usize = 1024; // Example size
-    const K_MAX_CODE_POINTERS: usize = 1024;
-    const K_MAX_CAPACITY: usize = 1024;
-    const K_FREE_CODE_POINTER_TABLE_ENTRY_TAG: usize = 0x1; // Example tag
+impl CodePointerTableEntry {
+    pub const K_MARKING_BIT: u64 = K_HEAP_OBJECT_TAG as u64;
+    pub const K_FREE_ENTRY_TAG: u64 = CodeEntrypointTag::kFreeCodePointerTableEntryTag as u64;
+}
 
-    /// The entries of a CodePointerTable.
-    ///
-    /// Each entry contains a pointer to a Code object as well as a raw pointer to
-    /// the Code's entrypoint.
-    #[repr(C)]
-    pub struct CodePointerTableEntry {
-        entrypoint_: AtomicUsize,
-        code_: AtomicUsize,
+impl ExternalEntityTableEntry for CodePointerTableEntry {
+    fn make_freelist_entry(&self, next_entry_index: u32) {
+        self.entrypoint.store(0, Ordering::Relaxed);
+        self.code
+            .store(Self::K_FREE_ENTRY_TAG | (next_entry_index as u64) << 1, Ordering::Relaxed);
     }
 
-    impl CodePointerTableEntry {
-        /// We write-protect the CodePointerTable on platforms that support it for
-        /// forward-edge CFI.
-        pub const IS_WRITE_PROTECTED: bool = true;
-
-        /// Make this entry a code pointer entry for the given code object and
-        /// entrypoint.
-        #[inline]
-        pub fn make_code_pointer_entry(&self, code: usize, entrypoint: usize, tag: CodeEntrypointTag, mark_as_alive: bool) {
-            self.set_code_object(code);
-            self.set_entrypoint(entrypoint, tag);
-            if mark_as_alive {
-                self.mark();
-            }
-        }
-
-        /// Make this entry a freelist entry, containing the index of the next entry
-        /// on the freelist.
-        #[inline]
-        pub fn make_freelist_entry(&self, next_entry_index: u32) {
-            self.code_.store((next_entry_index as usize) | Self::K_FREE_ENTRY_TAG, Ordering::Relaxed);
-        }
-
-        /// Load code entrypoint pointer stored in this entry.
-        /// This entry must be a code pointer entry.
-        #[inline]
-        pub fn get_entrypoint(&self, tag: CodeEntrypointTag) -> usize {
-            // Assuming the tag is relevant to the entrypoint value
-            let entrypoint = self.entrypoint_.load(Ordering::Relaxed);
-            entrypoint // Consider applying the tag logic here.
-        }
-
-        /// Store the given code entrypoint pointer in this entry.
-        /// This entry must be a code pointer entry.
-        #[inline]
-        pub fn set_entrypoint(&self, value: usize, tag: CodeEntrypointTag) {
-            // Assuming the tag is relevant to the entrypoint value
-            self.entrypoint_.store(value, Ordering::Relaxed); //Consider applying tag logic here.
-        }
-
-        /// Load the code object pointer stored in this entry.
-        /// This entry must be a code pointer entry.
-        #[inline]
-        pub fn get_code_object(&self) -> usize {
-            self.code_.load(Ordering::Relaxed) | Self::K_MARKING_BIT
-        }
-
-        /// Store the given code object pointer in this entry.
-        /// This entry must be a code pointer entry.
-        #[inline]
-        pub fn set_code_object(&self, value: usize) {
-            self.code_.store(value, Ordering::Relaxed);
-        }
-
-        /// Returns true if this entry is a freelist entry.
-        #[inline]
-        pub fn is_freelist_entry(&self) -> bool {
-             (self.code_.load(Ordering::Relaxed) & (Self::K_FREE_ENTRY_TAG as usize)) != 0
-        }
-
-        /// Get the index of the next entry on the freelist. This method may be
-        /// called even when the entry is not a freelist entry. However, the result
-        /// is only valid if this is a freelist entry. This behaviour is required
-        /// for efficient entry allocation, see TryAllocateEntryFromFreelist.
-        #[inline]
-        pub fn get_next_freelist_entry_index(&self) -> u32 {
-            (self.code_.load(Ordering::Relaxed) & !(Self::K_FREE_ENTRY_TAG as usize)) as u32
-        }
-
-        /// Mark this entry as alive during garbage collection.
-        #[inline]
-        pub fn mark(&self) {
-            self.code_.fetch_or(Self::K_MARKING_BIT, Ordering::Relaxed);
-        }
-
-        /// Unmark this entry during sweeping.
-        #[inline]
-        pub fn unmark(&self) {
-            self.code_.fetch_and(!Self::K_MARKING_BIT, Ordering::Relaxed);
-        }
-
-        /// Test whether this entry is currently marked as alive.
-        #[inline]
-        pub fn is_marked(&self) -> bool {
-            (self.code_.load(Ordering::Relaxed) & Self::K_MARKING_BIT) != 0
-        }
-
-        /// Freelist entries contain the index of the next free entry in their lower 32
-        /// bits and are tagged with the kFreeCodePointerTableEntryTag.
-        const K_FREE_ENTRY_TAG: usize = K_FREE_CODE_POINTER_TABLE_ENTRY_TAG;
-
-        /// The marking bit is stored in the code_ field, see below.
-        const K_MARKING_BIT: usize = 1;
-
+    fn get_next_freelist_entry_index(&self) -> u32 {
+        ((self.code.load(Ordering::Relaxed) & !Self::K_FREE_ENTRY_TAG) >> 1) as u32
     }
 
-    #[test]
-    fn test_code_pointer_table_entry_size() {
-        assert_eq!(std::mem::size_of::<CodePointerTableEntry>(), K_CODE_POINTER_TABLE_ENTRY_SIZE);
+    fn is_freelist_entry(&self) -> bool {
+        self.code.load(Ordering::Relaxed) & Self::K_FREE_ENTRY_TAG == Self::K_FREE_ENTRY_TAG
     }
 
-    pub type CodePointerHandle = u32; // Example type
-
-    /// A table containing pointers to Code.
-    ///
-    /// Essentially a specialized version of the trusted pointer table (TPT). A
-    /// code pointer table entry contains both a pointer to a Code object as well as
-    /// a pointer to the entrypoint. This way, the performance sensitive code paths
-    /// that for example call a JSFunction can directly load the entrypoint from the
-    /// table without having to load it from the Code object.
-    ///
-    /// When the sandbox is enabled, a code pointer table (CPT) is used to ensure
-    /// basic control-flow integrity in the absence of special hardware support
-    /// (such as landing pad instructions): by referencing code through an index
-    /// into a CPT, and ensuring that only valid code entrypoints are stored inside
-    /// the table, it is then guaranteed that any indirect control-flow transfer
-    /// ends up on a valid entrypoint as long as an attacker is still confined to
-    /// the sandbox.
-    pub struct CodePointerTable {
-        base: ExternalEntityTable<CodePointerTableEntry, K_CODE_POINTER_TABLE_RESERVATION_SIZE>,
+    fn set_mark_bit(&self) {
+        self.code.fetch_or(Self::K_MARKING_BIT, Ordering::Relaxed);
     }
 
-    impl CodePointerTable {
-        const K_SUPPORTS_COMPACTION: bool = false;
-
-        pub fn new() -> Self {
-            assert_eq!(K_MAX_CODE_POINTERS, K_MAX_CAPACITY);
-            assert!(!Self::K_SUPPORTS_COMPACTION);
-            CodePointerTable {
-                base: ExternalEntityTable::new(),
-            }
-        }
-
-        /// The Spaces used by a CodePointerTable.
-        pub type Space = crate::external_entity_table::SpaceWithBlackAllocationSupport; // Assuming this exists
-
-        /// Retrieves the entrypoint of the entry referenced by the given handle.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn get_entrypoint(&self, handle: CodePointerHandle, tag: CodeEntrypointTag) -> usize {
-            let index = self.handle_to_index(handle) as usize;
-            let entry = self.base.get_entry(index);
-            entry.get_entrypoint(tag)
-        }
-
-        /// Retrieves the code object of the entry referenced by the given handle.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn get_code_object(&self, handle: CodePointerHandle) -> usize {
-            let index = self.handle_to_index(handle) as usize;
-            let entry = self.base.get_entry(index);
-            entry.get_code_object()
-        }
-
-        /// Sets the entrypoint of the entry referenced by the given handle.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn set_entrypoint(&self, handle: CodePointerHandle, value: usize, tag: CodeEntrypointTag) {
-            let index = self.handle_to_index(handle) as usize;
-            let entry = self.base.get_entry(index);
-            entry.set_entrypoint(value, tag);
-        }
-
-        /// Sets the code object of the entry referenced by the given handle.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn set_code_object(&self, handle: CodePointerHandle, value: usize) {
-            let index = self.handle_to_index(handle) as usize;
-            let entry = self.base.get_entry(index);
-            entry.set_code_object(value);
-        }
-
-        /// Allocates a new entry in the table and initialize it.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn allocate_and_initialize_entry(&self, space: &mut Self::Space, code: usize, entrypoint: usize, tag: CodeEntrypointTag) -> CodePointerHandle {
-             let index = self.base.allocate_entry(space);
-            let entry = self.base.get_entry(index as usize);
-            entry.make_code_pointer_entry(code, entrypoint, tag, true); // Mark as alive on allocation.
-            self.index_to_handle(index)
-        }
-
-        /// Marks the specified entry as alive.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn mark(&self, space: &mut Self::Space, handle: CodePointerHandle) {
-            let index = self.handle_to_index(handle) as usize;
-            let entry = self.base.get_entry(index);
-            entry.mark();
-        }
-
-        /// Frees all unmarked entries in the given space.
-        ///
-        /// This method must only be called while mutator threads are stopped as it is
-        /// not safe to allocate table entries while a space is being swept.
-        ///
-        /// Returns the number of live entries after sweeping.
-        pub fn sweep(&self, space: &mut Self::Space, counters: &mut Counters) -> u32 {
-           let live_entries = self.base.sweep(space, |entry| {
-                if entry.is_marked() {
-                   entry.unmark();
-                   true // Keep the entry
-                } else {
-                    false // Free the entry
-                }
-            }) as u32;
-           counters.code_pointer_table_entries += live_entries as usize;
-
-           live_entries
-        }
-
-        /// Iterate over all active entries in the given space.
-        ///
-        /// The callback function will be invoked once for every entry that is
-        /// currently in use, i.e. has been allocated and not yet freed, and will
-        /// receive the handle and content (Code object pointer) of that entry.
-        pub fn iterate_active_entries_in<Callback>(&self, space: &Self::Space, mut callback: Callback)
-            where
-                Callback: FnMut(CodePointerHandle, usize),
-        {
-            self.base.iterate_active_entries_in(space, |index| {
-                let handle = self.index_to_handle(index);
-                let entry = self.base.get_entry(index as usize);
-                callback(handle, entry.get_code_object());
-            });
-        }
-
-        /// The base address of this table, for use in JIT compilers.
-        pub fn base_address(&self) -> usize {
-            self.base.base_address()
-        }
-
-        #[inline]
-        fn handle_to_index(&self, handle: CodePointerHandle) -> u32 {
-            handle // Assuming handle is the index itself.  May need to adjust based on actual handle implementation.
-        }
-
-        #[inline]
-        fn index_to_handle(&self, index: u32) -> CodePointerHandle {
-            index // Assuming handle is the index itself.  May need to adjust based on actual handle implementation.
-        }
+    fn clear_mark_bit(&self) {
+        self.code.fetch_and(!Self::K_MARKING_BIT, Ordering::Relaxed);
     }
 
-    pub struct Counters {
-        pub code_pointer_table_entries: usize,
-    }
-
-    impl Counters {
-        pub fn new() -> Self {
-            Counters {
-                code_pointer_table_entries: 0,
-            }
-        }
+    fn has_mark_bit_set(&self) -> bool {
+        let code = self.code.load(Ordering::Relaxed);
+        code & Self::K_MARKING_BIT != 0 && code & Self::K_FREE_ENTRY_TAG != Self::K_FREE_ENTRY_TAG
     }
 }
 
+pub type CodePointerTableSpace = Space;
 
-mod code_entrypoint_tag {
-    #[derive(Debug, Copy, Clone)]
-    pub enum CodeEntrypointTag {
-        Default, // Add more variants as needed
-    }
+/// A table of pointers to Code objects and their entrypoints, through
+/// which code inside the sandbox calls code: sandboxed objects only hold
+/// handles, so a call lands at a valid entrypoint of the expected kind.
+pub struct CodePointerTable {
+    table: ExternalEntityTable<CodePointerTableEntry, K_CODE_POINTER_TABLE_RESERVATION_SIZE>,
 }
 
-mod external_entity_table {
-    pub struct ExternalEntityTable<T, const N: usize> {
-        entries: Vec<T>,
-        free_list: Vec<u32>,
-        _phantom: std::marker::PhantomData<T>,
-    }
-
-    impl<T, const N: usize> ExternalEntityTable<T, N>
-    where T: Default + Send + Sync,
-    {
-        pub fn new() -> Self {
-            let mut entries = Vec::with_capacity(N);
-            for _ in 0..N {
-                entries.push(T::default());
-            }
-
-            let mut free_list = Vec::with_capacity(N);
-            for i in 0..N {
-                free_list.push(i as u32);
-            }
-
-            ExternalEntityTable {
-                entries,
-                free_list,
-                _phantom: std::marker::PhantomData,
-            }
+impl CodePointerTable {
+    pub fn new() -> Self {
+        CodePointerTable {
+            table: ExternalEntityTable::new(),
         }
+    }
 
-        pub fn allocate_entry(&mut self, _space: &mut SpaceWithBlackAllocationSupport) -> u32 {
-            if self.free_list.is_empty() {
-                panic!("No more entries available");
-            }
-            self.free_list.pop().unwrap()
-        }
-
-        pub fn get_entry(&self, index: usize) -> &T {
-            &self.entries[index]
-        }
-
-        pub fn get_entry_mut(&mut self, index: usize) -> &mut T {
-            &mut self.entries[index]
-        }
-
-        pub fn sweep<F>(&mut self, _space: &mut SpaceWithBlackAllocationSupport, mut keep_entry: F) -> usize
-        where F: FnMut(&mut T) -> bool {
-            let mut live_count = 0;
-
-            let mut new_free_list = Vec::new();
-
-            for i in 0..self.entries.len() {
-                if keep_entry(&mut self.entries[i]) {
-                    live_count += 1;
-                } else {
-                    new_free_list.push(i as u32);
-                }
-            }
-            self.free_list = new_free_list;
-
-            live_count
-        }
+    #[inline]
+    pub fn handle_to_index(handle: CodePointerHandle) -> u32 {
+        handle >> K_CODE_POINTER_HANDLE_SHIFT
+    }
 
-        pub fn iterate_active_entries_in<Callback>(&self, _space: &SpaceWithBlackAllocationSupport, mut callback: Callback)
-            where
-                Callback: FnMut(u32),
-        {
-            // Simple implementation, consider optimizing with a "used" bitset.
-            for i in 0..self.entries.len() {
-                if !self.free_list.contains(&(i as u32)) {
-                    callback(i as u32);
-                }
-            }
-        }
+    #[inline]
+    pub fn index_to_handle(index: u32) -> CodePointerHandle {
+        index << K_CODE_POINTER_HANDLE_SHIFT | K_CODE_POINTER_HANDLE_MARKER
+    }
 
-        pub fn base_address(&self) -> usize {
-            self.entries.as_ptr() as usize
-        }
+    pub fn contains(&self, handle: CodePointerHandle) -> bool {
+        self.table.is_committed(Self::handle_to_index(handle))
+    }
 
+    pub fn sweep(&self, space: &CodePointerTableSpace) -> u32 {
+        self.table.generic_sweep(space)
     }
+}
 
-    impl<T: Default, const N: usize> Default for ExternalEntityTable<T, N> {
-        fn default() -> Self {
-            Self::new()
-        }
+impl Default for CodePointerTable {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub struct SpaceWithBlackAllocationSupport; // Dummy type
+impl Deref for CodePointerTable {
+    type Target = ExternalEntityTable<CodePointerTableEntry, K_CODE_POINTER_TABLE_RESERVATION_SIZE>;
 
-    impl SpaceWithBlackAllocationSupport {
-        pub fn new() -> Self {
-            SpaceWithBlackAllocationSupport
-        }
+    fn deref(&self) -> &Self::Target {
+        &self.table
     }
-}
\ No newline at end of file
+}
@@ -2,356 +2,31 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/sandbox/compactible-external-entity-table-inl.h
-
-#![allow(dead_code)]
-#![allow(unused_variables)]
-
-use std::sync::{atomic::{AtomicU32, Ordering}, Mutex};
-use std::vec::Vec;
-use std::cmp::max;
-//use crate::logging::counters::Histogram; // Assuming you have a Rust equivalent
-//use crate::sandbox::compactible_external_entity_table::*; // Assuming you have a Rust equivalent
-//use crate::sandbox::external_entity_table::*; // Assuming you have a Rust equivalent
-//use crate::sandbox::external_pointer::Address; // Assuming you have a Rust equivalent
-
-// Placeholder definitions
-pub type Address = usize; // Or a more appropriate type
-pub struct Space {
-    start_of_evacuation_area_: AtomicU32,
-}
-impl Space {
-    pub fn start_of_evacuation_area(&self) -> &AtomicU32 {
-        &self.start_of_evacuation_area_
-    }
-    pub fn belongs_to<Entry, const size: usize>(&self, table: &CompactibleExternalEntityTable<Entry, size>) -> bool {
-        true // Placeholder
-    }
-    pub fn is_internal_read_only_space(&self) -> bool {
-        false // Placeholder
-    }
-    pub fn is_compacting(&self) -> bool {
-        self.start_of_evacuation_area_.load(Ordering::Relaxed) != kNotCompactingMarker
-    }
-    pub fn compacting_was_aborted(&self) -> bool {
-        let value = self.start_of_evacuation_area_.load(Ordering::Relaxed);
-        (value & kCompactionAbortedMarker) == kCompactionAbortedMarker
-    }
-    pub fn contains(&self, index: u32) -> bool {
-        true // Placeholder
-    }
-    pub fn stop_compacting(&self) {
-        self.start_of_evacuation_area_.store(kNotCompactingMarker, Ordering::Relaxed);
-    }
-    pub fn abort_compacting(&self, start_of_evacuation_area: u32) {
-        let compaction_aborted_marker = start_of_evacuation_area | kCompactionAbortedMarker;
-        assert_ne!(compaction_aborted_marker, kNotCompactingMarker);
-        self.start_of_evacuation_area_.store(compaction_aborted_marker, Ordering::Relaxed);
-    }
-}
-// Placeholder for Histogram
-pub struct Histogram {}
-impl Histogram {
-    pub fn add_sample(&self, sample: i32) {}
-}
-
-macro_rules! v8_unlikely {
-    ($x:expr) => {
-        $x // In Rust, branch prediction hints are usually handled by the compiler
-    };
-}
-
-const MB: usize = 1024 * 1024;
-
-const kCompactionAbortedMarker: u32 = 1 << 31; // MSB set to indicate aborted
-const kNotCompactingMarker: u32 = u32::MAX;
-
-#[derive(Debug, PartialEq)]
-pub enum ExternalEntityTableCompactionOutcome {
-    kAborted,
-    kSuccess,
-}
-
-pub struct CompactionResult {
-    pub start_of_evacuation_area: u32,
-    pub evacuation_was_successful: bool,
-}
-
-pub trait ExternalEntity {
-    fn make_evacuation_entry(&mut self, handle_location: Address);
-}
-
-pub struct CompactibleExternalEntityTable<Entry, const size: usize> {
-    base: ExternalEntityTable<Entry, size>,
-}
-
-impl<Entry: ExternalEntity + Default + Copy, const size: usize> CompactibleExternalEntityTable<Entry, size> {
-    pub fn new() -> Self {
-        CompactibleExternalEntityTable {
-            base: ExternalEntityTable::new(),
-        }
-    }
-    pub fn allocate_entry(&mut self, space: &mut Space) -> u32 {
-        let index = self.base.allocate_entry(space);
-
-        let start_of_evacuation_area = space.start_of_evacuation_area_.load(Ordering::Relaxed);
-        if v8_unlikely!(index >= start_of_evacuation_area) {
-            space.abort_compacting(start_of_evacuation_area);
-        }
-
-        index
-    }
-
-    pub fn finish_compaction(
-        &mut self,
-        space: &mut Space,
-        counter: &mut Histogram,
-    ) -> CompactionResult {
-        assert!(space.belongs_to(self));
-        assert!(!space.is_internal_read_only_space());
-
-        let start_of_evacuation_area = space.start_of_evacuation_area_.load(Ordering::Relaxed);
-        let mut evacuation_was_successful = false;
-        if space.is_compacting() {
-            let mut outcome = ExternalEntityTableCompactionOutcome::kAborted;
-            let mut local_start_of_evacuation_area = start_of_evacuation_area;
-            if space.compacting_was_aborted() {
-                local_start_of_evacuation_area &= !kCompactionAbortedMarker;
-            } else {
-                evacuation_was_successful = true;
-                outcome = ExternalEntityTableCompactionOutcome::kSuccess;
+use crate::heap::heap_layout::Address;
+use crate::sandbox::compactible_external_entity_table::{
+    CompactibleExternalEntityTable, CompactibleExternalEntityTableEntry, CompactibleSpace,
+};
+
+impl<Entry: CompactibleExternalEntityTableEntry, const RESERVATION_SIZE: usize>
+    CompactibleExternalEntityTable<Entry, RESERVATION_SIZE>
+{
+    /// Called by the marker for the entry at `index`, referenced by the
+    /// handle at `handle_location`. If the entry is to be evacuated, an
+    /// evacuation entry is allocated for it below the evacuation area.
+    #[inline]
+    pub(crate) fn maybe_create_evacuation_entry(&self, space: &CompactibleSpace, index: u32, handle_location: Address) {
+        // Without compaction, or after it was aborted, the start of the
+        // evacuation area lies above every index.
+        let start_of_evacuation_area = space.start_of_evacuation_area();
+        if index < start_of_evacuation_area {
+            return;
+        }
+        match self.try_allocate_entry(space) {
+            Some(new_index) => {
+                debug_assert!(new_index < start_of_evacuation_area);
+                self.at(new_index).make_evacuation_entry(handle_location);
             }
-            assert_eq!(local_start_of_evacuation_area % ExternalEntityTable::<Entry, size>::kEntriesPerSegment as u32, 0);
-
-            space.stop_compacting();
-            counter.add_sample(outcome as i32);
-        }
-
-        CompactionResult {
-            start_of_evacuation_area,
-            evacuation_was_successful,
+            None => space.abort_compacting(),
         }
     }
-
-    pub fn maybe_create_evacuation_entry(
-        &mut self,
-        space: &mut Space,
-        index: u32,
-        handle_location: Address,
-    ) {
-        let start_of_evacuation_area = space.start_of_evacuation_area_.load(Ordering::Relaxed);
-        if index >= start_of_evacuation_area {
-            assert!(space.is_compacting());
-            let new_index = self.base.allocate_entry_below(space, start_of_evacuation_area);
-            if let Some(new_index_value) = new_index {
-                assert!(new_index_value < start_of_evacuation_area);
-                assert!(space.contains(new_index_value));
-                self.base.at_mut(new_index_value as usize).make_evacuation_entry(handle_location);
-            } else {
-                space.abort_compacting(start_of_evacuation_area);
-            }
-        }
-    }
-}
-
-// ExternalEntityTable definition
-pub struct ExternalEntityTable<Entry, const size: usize> {
-    entries: Vec<Entry>,
-    freelist: Vec<u32>,
-}
-
-impl<Entry: Default + Copy, const size: usize> ExternalEntityTable<Entry, size> {
-    pub const kEntriesPerSegment: usize = 16;
-    pub const kEntrySize: usize = std::mem::size_of::<Entry>();
-
-    pub fn new() -> Self {
-        ExternalEntityTable {
-            entries: vec![Entry::default(); size],
-            freelist: (0..size as u32).rev().collect(),
-        }
-    }
-
-    pub fn allocate_entry(&mut self, _space: &mut Space) -> u32 {
-        self.freelist.pop().expect("Freelist is empty")
-    }
-
-        // Allocate entry below threshold
-    pub fn allocate_entry_below(&mut self, _space: &mut Space, threshold: u32) -> Option<u32> {
-        let mut found_index = None;
-        for (i, &index) in self.freelist.iter().enumerate() {
-            if index < threshold {
-                found_index = Some(i);
-                break;
-            }
-        }
-
-        if let Some(index_to_remove) = found_index {
-            let index = self.freelist.remove(index_to_remove);
-            Some(index)
-        } else {
-            None
-        }
-    }
-
-    pub fn at_mut(&mut self, index: usize) -> &mut Entry {
-        &mut self.entries[index]
-    }
-
-    pub fn capacity(&self) -> usize {
-        self.entries.len()
-    }
-}
-
-pub struct Segment {
-    first_entry: u32,
-}
-impl Segment {
-    pub fn first_entry(&self) -> u32 {
-        self.first_entry
-    }
-}
-
-impl Space {
-    pub fn new() -> Self {
-        Space {
-            start_of_evacuation_area_: AtomicU32::new(kNotCompactingMarker),
-        }
-    }
-    pub fn start_compacting<Entry, const size: usize>(
-        &mut self,
-        start_of_evacuation_area: u32,
-    ) {
-        self.start_of_evacuation_area_.store(start_of_evacuation_area, Ordering::Relaxed);
-    }
-
-    pub fn start_compacting_if_needed<Entry, const size: usize>(&mut self, table: &mut ExternalEntityTable<Entry, size>)
-    where Entry: Default + Copy
-    {
-        let num_free_entries = table.freelist.len() as u32;
-        let num_total_entries = table.capacity() as u32;
-
-        let free_ratio = num_free_entries as f64 / num_total_entries as f64;
-        let num_segments_to_evacuate = (num_free_entries / 2) / ExternalEntityTable::<Entry, size>::kEntriesPerSegment as u32;
-        let space_size = num_total_entries * ExternalEntityTable::<Entry, size>::kEntrySize as u32;
-
-        let should_compact = (space_size >= 1 * MB as u32) && (free_ratio >= 0.10) && (num_segments_to_evacuate >= 1);
-
-        if should_compact {
-            let start_of_evacuation_area = num_total_entries - num_segments_to_evacuate * ExternalEntityTable::<Entry, size>::kEntriesPerSegment as u32;
-            self.start_compacting(start_of_evacuation_area);
-        }
-    }
-}
-
-impl<Entry, const size: usize> CompactibleExternalEntityTable<Entry, size> {
-    // Missing: Space struct and its methods, Histogram struct and its methods,
-    // ExternalEntityTableCompactionOutcome enum,  DCHECK macros, kEntriesPerSegment const
-    // Flag definitions, AtomicU32 usages, memory ordering considerations
-    // Freelist implementation
-
-    pub struct Space {
-        start_of_evacuation_area_: AtomicU32,
-        invalidated_fields_: Vec<Address>,
-        invalidated_fields_mutex_: Mutex<()>,
-        mutex_: Mutex<()>,
-        segments_: Vec<Segment>,
-    }
-
-    impl Space {
-        pub fn new() -> Self {
-            Self {
-                start_of_evacuation_area_: AtomicU32::new(kNotCompactingMarker),
-                invalidated_fields_: Vec::new(),
-                invalidated_fields_mutex_: Mutex::new(()),
-                mutex_: Mutex::new(()),
-                segments_: Vec::new(),
-            }
-        }
-        pub fn start_compacting(&self, start_of_evacuation_area: u32) {
-            self.start_of_evacuation_area_.store(start_of_evacuation_area, Ordering::Relaxed);
-        }
-
-        pub fn stop_compacting(&self) {
-            self.start_of_evacuation_area_.store(kNotCompactingMarker, Ordering::Relaxed);
-        }
-
-        pub fn abort_compacting(&self, start_of_evacuation_area: u32) {
-            let compaction_aborted_marker = start_of_evacuation_area | kCompactionAbortedMarker;
-            assert_ne!(compaction_aborted_marker, kNotCompactingMarker);
-            self.start_of_evacuation_area_.store(compaction_aborted_marker, Ordering::Relaxed);
-        }
-
-        pub fn is_compacting(&self) -> bool {
-            self.start_of_evacuation_area_.load(Ordering::Relaxed) != kNotCompactingMarker
-        }
-
-        pub fn compacting_was_aborted(&self) -> bool {
-            let value = self.start_of_evacuation_area_.load(Ordering::Relaxed);
-            (value & kCompactionAbortedMarker) == kCompactionAbortedMarker
-        }
-
-        pub fn field_was_invalidated(&self, field_address: Address) -> bool {
-            //self.invalidated_fields_mutex_.lock().unwrap();
-            self.invalidated_fields_.contains(&field_address)
-        }
-
-        pub fn clear_invalidated_fields(&mut self) {
-            //self.invalidated_fields_mutex_.lock().unwrap();
-            self.invalidated_fields_.clear();
-        }
-
-        pub fn add_invalidated_field(&mut self, field_address: Address) {
-            if self.is_compacting() {
-                //let _guard = self.invalidated_fields_mutex_.lock().unwrap();
-                self.invalidated_fields_.push(field_address);
-            }
-        }
-
-        pub fn start_compacting_if_needed(&mut self) {
-            //let _guard = self.mutex_.lock().unwrap(); // Assuming mutex_ is for segments_
-
-            let num_free_entries = 10; // Placeholder, replace with correct implementation
-            let num_total_entries = 100; // Placeholder, replace with correct implementation
-
-            let free_ratio = num_free_entries as f64 / num_total_entries as f64;
-            let num_segments_to_evacuate = max(1, (num_free_entries / 2) / ExternalEntityTable::<Entry, size>::kEntriesPerSegment as u32); // ensure >= 1
-            let space_size = num_total_entries * ExternalEntityTable::<Entry, size>::kEntrySize as u32;
-
-            let should_compact = (space_size >= 1 * MB as u32) && (free_ratio >= 0.10) && (num_segments_to_evacuate >= 1);
-
-            if should_compact {
-                let start_of_evacuation_area = 50; // Placeholder
-                self.start_compacting(start_of_evacuation_area);
-            }
-        }
-
-    }
-
-    fn num_segments(&self) -> usize {
-        10 //Placeholder for number of segments
-    }
-}
-
-impl<Entry: Default + Copy, const size: usize> ExternalEntityTable<Entry, size> {
-    fn freelist_length(&self) -> usize {
-        self.freelist.len()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_compaction_result_equality() {
-        let result1 = CompactionResult {
-            start_of_evacuation_area: 100,
-            evacuation_was_successful: true,
-        };
-        let result2 = CompactionResult {
-            start_of_evacuation_area: 100,
-            evacuation_was_successful: true,
-        };
-        assert_eq!(result1, result2);
-    }
 }
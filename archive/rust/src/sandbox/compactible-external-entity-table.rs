@@ -2,215 +2,248 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
-
-mod external_entity_table;
-
-use external_entity_table::ExternalEntityTable;
+use std::ops::Deref;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Mutex;
-use std::vec::Vec;
-use std::marker::PhantomData;
 
-// Placeholder for v8config.h functionality
-const V8_COMPRESS_POINTERS: bool = true;
+use crate::heap::heap_layout::Address;
+use crate::sandbox::external_entity_table::{ExternalEntityTable, ExternalEntityTableEntry, FreelistHead, Space};
+
+/// Entries of a table that supports compaction. Besides free and regular
+/// entries they can be evacuation entries, which record the location of
+/// the handle referring to an entry that is to be moved into them.
+pub trait CompactibleExternalEntityTableEntry: ExternalEntityTableEntry {
+    fn make_evacuation_entry(&self, handle_location: Address);
+    fn has_evacuation_entry(&self) -> bool;
+    fn get_evacuation_entry_handle_location(&self) -> Address;
+    /// Moves the contents of this entry into `dest`, leaving it unmarked.
+    fn evacuate(&self, dest: &Self);
+}
+
+/// Outcome of a compaction cycle.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExternalEntityTableCompactionOutcome {
+    kSuccess,
+    /// Compaction was aborted because the freelist ran dry or the space had
+    /// to grow while marking.
+    kAborted,
+}
+
+/// A space of a compactible table. While compacting, the entries from
+/// `start_of_evacuation_area` on are moved out of the space.
+pub struct CompactibleSpace {
+    space: Space,
+    start_of_evacuation_area: AtomicU32,
+    last_compaction_outcome: AtomicU32,
+}
+
+impl CompactibleSpace {
+    pub const K_NOT_COMPACTING_MARKER: u32 = u32::MAX;
+    /// Or-ed into the start of the evacuation area when compaction is
+    /// aborted, which moves it above every valid index.
+    pub const K_COMPACTION_ABORTED_MARKER: u32 = 0xf000_0000;
+
+    pub fn new() -> Self {
+        CompactibleSpace {
+            space: Space::new(),
+            start_of_evacuation_area: AtomicU32::new(Self::K_NOT_COMPACTING_MARKER),
+            last_compaction_outcome: AtomicU32::new(Self::K_NOT_COMPACTING_MARKER),
+        }
+    }
 
-#[cfg(feature = "compression")]
-pub mod compactible_external_entity_table {
-    use super::*;
+    pub fn is_compacting(&self) -> bool {
+        self.start_of_evacuation_area.load(Ordering::Relaxed) != Self::K_NOT_COMPACTING_MARKER
+    }
 
-    // Placeholder for Isolate class.
-    pub struct Isolate {}
+    pub fn compacting_was_aborted(&self) -> bool {
+        let start = self.start_of_evacuation_area.load(Ordering::Relaxed);
+        self.is_compacting() && start & Self::K_COMPACTION_ABORTED_MARKER == Self::K_COMPACTION_ABORTED_MARKER
+    }
 
-    // Placeholder for Histogram class.
-    pub struct Histogram {}
+    pub(crate) fn start_of_evacuation_area(&self) -> u32 {
+        self.start_of_evacuation_area.load(Ordering::Relaxed)
+    }
 
-    /// Outcome of external pointer table compaction.
-    #[derive(Debug, PartialEq, Eq)]
-    pub enum ExternalEntityTableCompactionOutcome {
-        Success = 0,  // Compaction was successful.
-        Aborted = 2,  // Compaction was aborted because the freelist grew too short.
+    /// Aborts compaction. Entries allocated from then on may lie in the
+    /// evacuation area, so no entry is moved in this cycle.
+    pub(crate) fn abort_compacting(&self) {
+        self.start_of_evacuation_area
+            .fetch_or(Self::K_COMPACTION_ABORTED_MARKER, Ordering::Relaxed);
     }
 
-    /// An intermediate table class that abstracts garbage collection mechanism
-    /// for pointer tables that support compaction.
-    ///
-    /// Table compaction:
-    /// -----------------
-    /// The table's spaces are to some degree self-compacting: since the freelists
-    /// are sorted in ascending order, segments at the start of the table will
-    /// usually be fairly well utilized, while later segments might become
-    /// completely free, in which case they will be deallocated.
-    /// However, as a single live entry may keep an entire segment alive, the
-    /// following simple algorithm is used to compact a space if that is deemed
-    /// necessary:
-    ///  - At the start of the GC marking phase, determine if a space needs to be
-    ///    compacted. This decision is mostly based on the absolute and relative
-    ///    size of the freelist.
-    ///  - If compaction is needed, this algorithm determines by how many segments
-    ///    it would like to shrink the space (N). It will then attempt to move all
-    ///    live entries out of these segments so that they can be deallocated
-    ///    afterwards during sweeping.
-    ///  - The algorithm then simply selects the last N segments for evacuation, and
-    ///    it "marks" them for evacuation simply by remembering the start of the
-    ///    first selected segment. Everything after this threshold value then
-    ///    becomes the evacuation area. In this way, it becomes very cheap to test
-    ///    if an entry or segment should be evacuated: only a single integer
-    ///    comparison against the threshold is required. It also establishes a
-    ///    simple compaction invariant: compaction always moves an entry at or above
-    ///    the threshold to a new position before the threshold.
-    ///  - During marking, whenever a live entry inside the evacuation area is
-    ///    found, a new "evacuation entry" is allocated from the freelist (which is
-    ///    assumed to have enough free slots) and the address of the handle in the
-    ///    object owning the table entry is written into it.
-    ///  - During sweeping, these evacuation entries are resolved: the content of
-    ///    the old entry is copied into the new entry and the handle in the object
-    ///    is updated to point to the new entry.
-    ///
-    /// When compacting, it is expected that the evacuation area contains few live
-    /// entries and that the freelist will be able to serve all evacuation entry
-    /// allocations. In that case, compaction is essentially free (very little
-    /// marking overhead, no memory overhead). However, it can happen that the
-    /// application allocates a large number of table entries during marking, in
-    /// which case we might end up allocating new entries inside the evacuation area
-    /// or even allocate entire new segments for the space that's being compacted.
-    /// If that situation is detected, compaction is aborted during marking.
-    ///
-    /// This algorithm assumes that table entries (except for the null entry) are
-    /// never shared between multiple objects. Otherwise, the following could
-    /// happen: object A initially has handle H1 and is scanned during incremental
-    /// marking. Next, object B with handle H2 is scanned and marked for
-    /// evacuation. Afterwards, object A copies the handle H2 from object B.
-    /// During sweeping, only object B's handle will be updated to point to the
-    /// new entry while object A's handle is now dangling. If shared entries ever
-    /// become necessary, setting pointer handles would have to be guarded by
-    /// write barriers to avoid this scenario.
-    pub struct CompactibleExternalEntityTable<Entry, const SIZE: usize> {
-        base: ExternalEntityTable<Entry, SIZE>,
-        _phantom: PhantomData<Entry>,
+    /// Returns the outcome of the last cycle that compacted this space.
+    pub fn last_compaction_outcome(&self) -> Option<ExternalEntityTableCompactionOutcome> {
+        match self.last_compaction_outcome.load(Ordering::Relaxed) {
+            0 => Some(ExternalEntityTableCompactionOutcome::kSuccess),
+            1 => Some(ExternalEntityTableCompactionOutcome::kAborted),
+            _ => None,
+        }
     }
+}
 
-    impl<Entry, const SIZE: usize> CompactibleExternalEntityTable<Entry, SIZE> {
-        pub const K_SUPPORTS_COMPACTION: bool = true;
+impl Default for CompactibleSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        pub struct CompactionResult {
-            pub start_of_evacuation_area: u32,
-            pub success: bool,
-        }
+impl Deref for CompactibleSpace {
+    type Target = Space;
 
-        pub fn new() -> Self {
-            CompactibleExternalEntityTable {
-                base: ExternalEntityTable::new(),
-                _phantom: PhantomData,
-            }
+    fn deref(&self) -> &Space {
+        &self.space
+    }
+}
+
+/// An intermediate table class that implements garbage collection with
+/// compaction for tables whose entries are referenced by exactly one handle
+/// each.
+///
+/// Table compaction:
+/// -----------------
+/// Spaces are to some degree self-compacting, as the freelist hands out
+/// the lowest free entries first. To release memory after a large number
+/// of entries died, spaces are compacted during a full GC:
+///
+/// - When marking starts, `start_compacting_if_needed` picks the top
+///   segments of the space as the evacuation area if enough entries are
+///   free, and drops the free entries of the area from the freelist.
+/// - When the marker visits a handle referring to an entry in the area, it
+///   allocates a new entry below the area and turns it into an evacuation
+///   entry that records the location of the handle.
+/// - When sweeping, every evacuation entry receives the contents of the
+///   entry its handle refers to, the handle is updated to the new entry
+///   and the segments of the evacuation area are released.
+///
+/// If no free entry is left below the area, or the space has to grow while
+/// marking, compaction is aborted: evacuation entries are then freed when
+/// sweeping and nothing moves.
+pub struct CompactibleExternalEntityTable<Entry, const RESERVATION_SIZE: usize> {
+    table: ExternalEntityTable<Entry, RESERVATION_SIZE>,
+    /// Handles are indices shifted left by this amount.
+    handle_shift: u32,
+}
+
+impl<Entry: CompactibleExternalEntityTableEntry, const RESERVATION_SIZE: usize>
+    CompactibleExternalEntityTable<Entry, RESERVATION_SIZE>
+{
+    pub fn new(handle_shift: u32) -> Self {
+        assert!(
+            (ExternalEntityTable::<Entry, RESERVATION_SIZE>::K_MAX_CAPACITY as u32)
+                < CompactibleSpace::K_COMPACTION_ABORTED_MARKER,
+            "indices must stay below the compaction aborted marker"
+        );
+        CompactibleExternalEntityTable {
+            table: ExternalEntityTable::new(),
+            handle_shift,
         }
+    }
 
-        // The Spaces used by pointer tables also contain the state related
-        // to compaction.
-        pub struct Space<Entry, const SIZE: usize>
-        where Entry: Default + Copy {
-            base: external_entity_table::ExternalEntityTable<Entry, SIZE>::Space<Entry, SIZE>,
-            start_of_evacuation_area_: AtomicU32,
-            invalidated_fields_: Mutex<Vec<usize>>, // Changed Address to usize
+    /// Allocates an entry in `space`. Growing a space that is being
+    /// compacted aborts compaction.
+    pub fn allocate_entry(&self, space: &CompactibleSpace) -> u32 {
+        if let Some(index) = self.try_allocate_entry(space) {
+            return index;
         }
+        if space.is_compacting() {
+            space.abort_compacting();
+        }
+        self.extend(space)
+    }
 
-        impl<Entry, const SIZE: usize> Space<Entry, SIZE>
-        where Entry: Default + Copy {
-            pub fn new() -> Self {
-                Space {
-                    base: external_entity_table::ExternalEntityTable::<Entry, SIZE>::Space::<Entry, SIZE>::new(),
-                    start_of_evacuation_area_: AtomicU32::new(Self::K_NOT_COMPACTING_MARKER),
-                    invalidated_fields_: Mutex::new(Vec::new()),
-                }
-            }
-
-            /// Determine if compaction is needed and if so start the compaction.
-            /// This is expected to be called at the start of the GC marking phase.
-            pub fn start_compacting_if_needed(&self) {
-                // Implementation details go here.
-                // TODO: Implement the logic for compaction.
-            }
-
-            const K_NOT_COMPACTING_MARKER: u32 = u32::MAX;
-            const K_COMPACTION_ABORTED_MARKER: u32 = 0xf0000000;
-
-            #[inline]
-            fn is_compacting(&self) -> bool {
-                self.start_of_evacuation_area_.load(Ordering::Relaxed) != Self::K_NOT_COMPACTING_MARKER
-            }
-
-            #[inline]
-            fn start_compacting(&self, start_of_evacuation_area: u32) {
-                self.start_of_evacuation_area_.store(start_of_evacuation_area, Ordering::Relaxed);
-            }
-
-            #[inline]
-            fn stop_compacting(&self) {
-                self.start_of_evacuation_area_.store(Self::K_NOT_COMPACTING_MARKER, Ordering::Relaxed);
-            }
-
-            #[inline]
-            fn abort_compacting(&self, start_of_evacuation_area: u32) {
-                self.start_of_evacuation_area_.store(start_of_evacuation_area | Self::K_COMPACTION_ABORTED_MARKER, Ordering::Relaxed);
+    /// Starts compacting `space` if at least a segment's worth of free
+    /// entries can be given back. Called when marking starts.
+    pub fn start_compacting_if_needed(&self, space: &CompactibleSpace) {
+        let segments = space.segments.lock().unwrap();
+        debug_assert!(!space.is_compacting());
+        let entries_per_segment = ExternalEntityTable::<Entry, RESERVATION_SIZE>::K_ENTRIES_PER_SEGMENT;
+        let num_segments_to_evacuate = (space.freelist_length() / 2 / entries_per_segment) as usize;
+        if num_segments_to_evacuate == 0 || segments.len() <= 1 {
+            return;
+        }
+        let num_segments_to_evacuate = num_segments_to_evacuate.min(segments.len() - 1);
+        let first_segment = *segments.iter().rev().nth(num_segments_to_evacuate - 1).unwrap();
+        let start = ExternalEntityTable::<Entry, RESERVATION_SIZE>::first_entry_of(first_segment);
+
+        // Drop the free entries of the evacuation area from the freelist so
+        // that allocations while marking land below it.
+        let mut free_entries = Vec::with_capacity(space.freelist_length() as usize);
+        while let Some(index) = self.try_allocate_entry(space) {
+            if index < start {
+                free_entries.push(index);
             }
+        }
+        let mut freelist = FreelistHead::empty();
+        for &index in free_entries.iter().rev() {
+            self.at(index).make_freelist_entry(freelist.next());
+            freelist = FreelistHead::new(index, freelist.length() + 1);
+        }
+        space.freelist_head.store(freelist.pack(), Ordering::Release);
+        space.start_of_evacuation_area.store(start, Ordering::Relaxed);
+    }
 
-            #[inline]
-            fn compacting_was_aborted(&self) -> bool {
-                self.start_of_evacuation_area_.load(Ordering::Relaxed) & Self::K_COMPACTION_ABORTED_MARKER != 0
+    /// Sweeps `space`, completing compaction if it is in progress. Must not
+    /// run concurrently with allocation in `space`. Returns the number of
+    /// live entries.
+    pub fn sweep_and_compact(&self, space: &CompactibleSpace) -> u32 {
+        let mut segments = space.segments.lock().unwrap();
+        let start = space
+            .start_of_evacuation_area
+            .swap(CompactibleSpace::K_NOT_COMPACTING_MARKER, Ordering::Relaxed);
+        let was_compacting = start != CompactibleSpace::K_NOT_COMPACTING_MARKER;
+        let aborted = was_compacting
+            && start & CompactibleSpace::K_COMPACTION_ABORTED_MARKER == CompactibleSpace::K_COMPACTION_ABORTED_MARKER;
+        let compacting = was_compacting && !aborted;
+
+        // The segments of the evacuation area are released as a whole once
+        // their live entries have been moved out.
+        let mut evacuated_segments = Vec::new();
+        if compacting {
+            let entries_per_segment = ExternalEntityTable::<Entry, RESERVATION_SIZE>::K_ENTRIES_PER_SEGMENT;
+            evacuated_segments = segments.range(start / entries_per_segment..).copied().collect();
+            for segment in &evacuated_segments {
+                segments.remove(segment);
             }
+        }
 
-            #[inline]
-            fn field_was_invalidated(&self, field_address: usize) -> bool {
-                let lock = self.invalidated_fields_.lock().unwrap();
-                lock.contains(&field_address)
+        let handle_shift = self.handle_shift;
+        let num_live_entries = self.sweep_segments(space, &mut segments, |index, entry| {
+            // Evacuation entries of an aborted cycle are simply freed.
+            if !compacting || !entry.has_evacuation_entry() {
+                return false;
             }
-
-            #[inline]
-            fn clear_invalidated_fields(&self) {
-                let mut lock = self.invalidated_fields_.lock().unwrap();
-                lock.clear();
+            let handle_location = entry.get_evacuation_entry_handle_location();
+            let slot = unsafe { &*(handle_location as *const AtomicU32) };
+            let old_index = slot.load(Ordering::Relaxed) >> handle_shift;
+            if old_index < start {
+                // The field was overwritten with a new handle after it was
+                // marked, so the old entry is dead.
+                return false;
             }
+            self.at(old_index).evacuate(entry);
+            slot.store(index << handle_shift, Ordering::Relaxed);
+            true
+        });
 
-            #[inline]
-            fn add_invalidated_field(&self, field_address: usize) {
-                let mut lock = self.invalidated_fields_.lock().unwrap();
-                lock.push(field_address);
-            }
+        for segment in evacuated_segments {
+            self.free_segment(segment);
         }
+        if was_compacting {
+            let outcome = if aborted {
+                ExternalEntityTableCompactionOutcome::kAborted
+            } else {
+                ExternalEntityTableCompactionOutcome::kSuccess
+            };
+            space.last_compaction_outcome.store(outcome as u32, Ordering::Relaxed);
+        }
+        num_live_entries
+    }
+}
 
-        impl<Entry, const SIZE: usize> CompactibleExternalEntityTable<Entry, SIZE>
-        where Entry: Default + Copy {
-            /// Allocate an EPT entry from the space's freelist, or add a freshly-allocated
-            /// segment to the space and allocate there.  If the space is compacting but
-            /// the new index is above the evacuation threshold, abort compaction.
-            #[inline]
-            pub fn allocate_entry(&self, space: &mut Space<Entry, SIZE>) -> Result<u32, String> {
-                //TODO: Implement the logic for allocating an entry
-                //  and handling compaction abortion.
-                Ok(0)
-            }
-
-            pub fn finish_compaction(
-                &self,
-                space: &mut Space<Entry, SIZE>,
-                counter: &mut Histogram,
-            ) -> CompactionResult {
-                //TODO: Implement the logic for finishing compaction.
-                CompactionResult {
-                    start_of_evacuation_area: 0,
-                    success: false,
-                }
-            }
+impl<Entry, const RESERVATION_SIZE: usize> Deref for CompactibleExternalEntityTable<Entry, RESERVATION_SIZE> {
+    type Target = ExternalEntityTable<Entry, RESERVATION_SIZE>;
 
-            #[inline]
-            pub fn maybe_create_evacuation_entry(
-                &self,
-                space: &mut Space<Entry, SIZE>,
-                index: u32,
-                handle_location: usize, // Changed Address to usize
-            ) {
-                //TODO: Implement the logic for creating evacuation entry.
-            }
-        }
+    fn deref(&self) -> &Self::Target {
+        &self.table
     }
-}
\ No newline at end of file
+}
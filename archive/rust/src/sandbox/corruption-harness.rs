@@ -0,0 +1,381 @@
+// Copyright 2024 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::heap::heap_layout::Address;
+use crate::sandbox::code_entrypoint_tag::CodeEntrypointTag;
+use crate::sandbox::code_pointer_table::{CodePointerTable, CodePointerTableSpace};
+use crate::sandbox::cppheap_pointer_table::{CppHeapPointerTable, CppHeapPointerTableSpace, CppHeapPointerTag};
+use crate::sandbox::external_pointer_table::{ExternalPointerTable, ExternalPointerTableSpace, ExternalPointerTag};
+use crate::sandbox::indirect_pointer_tag::IndirectPointerTag;
+use crate::sandbox::js_dispatch_table::{JSDispatchHandle, JSDispatchTable, JSDispatchTableSpace};
+use crate::sandbox::trusted_pointer_table::{TrustedPointerTable, TrustedPointerTableSpace};
+
+/// Addresses below this are never mapped, so loads through them fault.
+pub const K_NULL_PAGE_SIZE: Address = 64 * 1024;
+/// Addresses from here on are not canonical user-space addresses, so loads
+/// through them fault.
+pub const K_FIRST_INACCESSIBLE_HIGH_ADDRESS: Address = 1 << 47;
+
+/// How a load through a corrupted handle ended.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LoadOutcome {
+    /// The handle resolved to an uncommitted part of the table, so the load
+    /// itself faults.
+    kFault,
+    /// The load yielded null or another address that faults on access.
+    kInaccessible,
+    /// The load yielded a pointer that the table holds for an object of
+    /// the expected type. An attacker who can corrupt the sandbox can swap
+    /// handles of the same type, which the sandbox does not protect
+    /// against.
+    kLegitimate,
+    /// The load yielded any other pointer, which would let an attacker
+    /// reach memory outside the sandbox.
+    kViolation,
+}
+
+#[derive(Default, Debug)]
+pub struct CorruptionReport {
+    pub faults: usize,
+    pub inaccessible: usize,
+    pub legitimate: usize,
+    /// Corrupted handles whose loads escaped the type checks.
+    pub violations: Vec<u32>,
+}
+
+impl CorruptionReport {
+    pub fn loads(&self) -> usize {
+        self.faults + self.inaccessible + self.legitimate + self.violations.len()
+    }
+
+    fn record(&mut self, handle: u32, outcome: LoadOutcome) {
+        match outcome {
+            LoadOutcome::kFault => self.faults += 1,
+            LoadOutcome::kInaccessible => self.inaccessible += 1,
+            LoadOutcome::kLegitimate => self.legitimate += 1,
+            LoadOutcome::kViolation => self.violations.push(handle),
+        }
+    }
+}
+
+/// Returns true if dereferencing `address` faults.
+pub fn is_inaccessible(address: Address) -> bool {
+    !(K_NULL_PAGE_SIZE..K_FIRST_INACCESSIBLE_HIGH_ADDRESS).contains(&address)
+}
+
+fn classify(address: Address, legitimate: bool) -> LoadOutcome {
+    if legitimate {
+        LoadOutcome::kLegitimate
+    } else if is_inaccessible(address) {
+        LoadOutcome::kInaccessible
+    } else {
+        LoadOutcome::kViolation
+    }
+}
+
+/// Test harness for the pointer tables: it models an attacker who can
+/// corrupt arbitrary memory inside the sandbox, including the handles that
+/// sandboxed objects store, and checks that no corrupted handle yields a
+/// pointer outside the sandbox other than one the table holds for an
+/// object of the expected type.
+///
+/// Each run populates a table, lets the garbage collector free half of the
+/// entries (compacting the tables that support it), and then loads
+/// through corrupted handles: random values, bit flips, neighbours of live
+/// handles and handles of freed entries. Each corrupted handle is loaded
+/// with the type the field it was written to expects.
+pub struct CorruptionHarness {
+    state: u64,
+}
+
+impl CorruptionHarness {
+    pub const K_NUM_ENTRIES: usize = 20_000;
+
+    pub fn new(seed: u64) -> Self {
+        CorruptionHarness { state: seed | 1 }
+    }
+
+    fn next(&mut self) -> u64 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn pick<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.next() as usize % values.len()]
+    }
+
+    /// Returns a corrupted handle derived from the live and stale handles.
+    fn corrupt(&mut self, live: &[u32], stale: &[u32]) -> u32 {
+        match self.next() % 4 {
+            0 => self.next() as u32,
+            1 => self.pick(live) ^ 1 << (self.next() % 32),
+            2 => self.pick(live).wrapping_add(self.next() as u32 % 64),
+            _ => self.pick(stale),
+        }
+    }
+
+    /// Loads through `iterations` corrupted handles. `expected` gives the
+    /// type a random field expects, `contains` tells whether a handle
+    /// resolves to committed memory and `load` performs and classifies a
+    /// load.
+    fn run<T: Copy>(
+        &mut self,
+        iterations: usize,
+        live: &[u32],
+        stale: &[u32],
+        expected: &[T],
+        contains: impl Fn(u32) -> bool,
+        mut load: impl FnMut(u32, T) -> LoadOutcome,
+    ) -> CorruptionReport {
+        let mut report = CorruptionReport::default();
+        for _ in 0..iterations {
+            let handle = self.corrupt(live, stale);
+            let outcome = if contains(handle) {
+                let expected = self.pick(expected);
+                load(handle, expected)
+            } else {
+                LoadOutcome::kFault
+            };
+            report.record(handle, outcome);
+        }
+        report
+    }
+
+    /// Splits `n` entries into survivors and dead ones at random.
+    fn survivors(&mut self, n: usize) -> Vec<bool> {
+        (0..n).map(|_| self.next().is_multiple_of(2)).collect()
+    }
+
+    pub fn run_external_pointer_table(&mut self, iterations: usize) -> CorruptionReport {
+        const TAGS: [ExternalPointerTag; 4] = [
+            ExternalPointerTag::kExternalStringResourceTag,
+            ExternalPointerTag::kEmbedderDataSlotPayloadTag,
+            ExternalPointerTag::kArrayBufferExtensionTag,
+            ExternalPointerTag::kForeignForeignAddressTag,
+        ];
+        let table = ExternalPointerTable::new();
+        let space = ExternalPointerTableSpace::new();
+        let tags: Vec<_> = (0..Self::K_NUM_ENTRIES).map(|_| self.pick(&TAGS)).collect();
+        let slots: Vec<AtomicU32> = (0..Self::K_NUM_ENTRIES)
+            .map(|i| AtomicU32::new(table.allocate_and_initialize_entry(&space, 0x10_0000 + i * 16, tags[i])))
+            .collect();
+        let original: Vec<u32> = slots.iter().map(|slot| slot.load(Ordering::Relaxed)).collect();
+        let survivors = self.survivors(Self::K_NUM_ENTRIES);
+        for _ in 0..2 {
+            table.start_compacting_if_needed(&space);
+            for (slot, _) in slots.iter().zip(&survivors).filter(|&(_, &alive)| alive) {
+                table.mark(&space, slot.load(Ordering::Relaxed), slot as *const AtomicU32 as Address);
+            }
+            table.sweep_and_compact(&space);
+        }
+        let live: Vec<u32> = slots.iter().zip(&survivors).filter(|&(_, &alive)| alive).map(|(slot, _)| slot.load(Ordering::Relaxed)).collect();
+        let stale: Vec<u32> = original.iter().copied().filter(|handle| !live.contains(handle)).collect();
+        let live_values: HashMap<Address, ExternalPointerTag> = (0..Self::K_NUM_ENTRIES)
+            .filter(|&i| survivors[i])
+            .map(|i| (0x10_0000 + i * 16, tags[i]))
+            .collect();
+
+        let report = self.run(iterations, &live, &stale, &TAGS, |handle| table.contains(handle), |handle, tag| {
+            let value = table.get(handle, tag.into());
+            classify(value, live_values.get(&value) == Some(&tag))
+        });
+        table.tear_down_space(&space);
+        report
+    }
+
+    pub fn run_cppheap_pointer_table(&mut self, iterations: usize) -> CorruptionReport {
+        const TAGS: [CppHeapPointerTag; 3] = [0x10, 0x11, 0x7000];
+        let table = CppHeapPointerTable::new();
+        let space = CppHeapPointerTableSpace::new();
+        let tags: Vec<_> = (0..Self::K_NUM_ENTRIES).map(|_| self.pick(&TAGS)).collect();
+        let slots: Vec<AtomicU32> = (0..Self::K_NUM_ENTRIES)
+            .map(|i| AtomicU32::new(table.allocate_and_initialize_entry(&space, 0x10_0000 + i * 16, tags[i])))
+            .collect();
+        let original: Vec<u32> = slots.iter().map(|slot| slot.load(Ordering::Relaxed)).collect();
+        let survivors = self.survivors(Self::K_NUM_ENTRIES);
+        for _ in 0..2 {
+            table.start_compacting_if_needed(&space);
+            for (slot, _) in slots.iter().zip(&survivors).filter(|&(_, &alive)| alive) {
+                table.mark(&space, slot.load(Ordering::Relaxed), slot as *const AtomicU32 as Address);
+            }
+            table.sweep_and_compact(&space);
+        }
+        let live: Vec<u32> = slots.iter().zip(&survivors).filter(|&(_, &alive)| alive).map(|(slot, _)| slot.load(Ordering::Relaxed)).collect();
+        let stale: Vec<u32> = original.iter().copied().filter(|handle| !live.contains(handle)).collect();
+        let live_values: HashMap<Address, CppHeapPointerTag> = (0..Self::K_NUM_ENTRIES)
+            .filter(|&i| survivors[i])
+            .map(|i| (0x10_0000 + i * 16, tags[i]))
+            .collect();
+
+        let report = self.run(iterations, &live, &stale, &TAGS, |handle| table.contains(handle), |handle, tag| {
+            let value = table.get(handle, tag.into());
+            classify(value, live_values.get(&value) == Some(&tag))
+        });
+        table.tear_down_space(&space);
+        report
+    }
+
+    pub fn run_trusted_pointer_table(&mut self, iterations: usize) -> CorruptionReport {
+        const TAGS: [IndirectPointerTag; 4] = [
+            IndirectPointerTag::kCodeIndirectPointerTag,
+            IndirectPointerTag::kBytecodeArrayIndirectPointerTag,
+            IndirectPointerTag::kInterpreterDataIndirectPointerTag,
+            IndirectPointerTag::kRegExpDataIndirectPointerTag,
+        ];
+        let table = TrustedPointerTable::new();
+        let space = TrustedPointerTableSpace::new();
+        let object = |i: usize| 0x1000_0000 + i * 16 + 1;
+        let tags: Vec<_> = (0..Self::K_NUM_ENTRIES).map(|_| self.pick(&TAGS)).collect();
+        let handles: Vec<u32> = (0..Self::K_NUM_ENTRIES)
+            .map(|i| table.allocate_and_initialize_entry(&space, object(i), tags[i]))
+            .collect();
+        let survivors = self.survivors(Self::K_NUM_ENTRIES);
+        let (live, stale) = partition(&handles, &survivors);
+        live.iter().for_each(|&handle| table.mark(handle));
+        table.sweep(&space);
+        let live_objects: HashMap<Address, IndirectPointerTag> = (0..Self::K_NUM_ENTRIES)
+            .filter(|&i| survivors[i])
+            .map(|i| (object(i), tags[i]))
+            .collect();
+
+        let report = self.run(iterations, &live, &stale, &TAGS, |handle| table.contains(handle), |handle, tag| {
+            let pointer = table.get(handle, tag);
+            classify(pointer, live_objects.get(&pointer) == Some(&tag))
+        });
+        table.tear_down_space(&space);
+        report
+    }
+
+    pub fn run_code_pointer_table(&mut self, iterations: usize) -> CorruptionReport {
+        const TAGS: [CodeEntrypointTag; 4] = [
+            CodeEntrypointTag::kDefaultCodeEntrypointTag,
+            CodeEntrypointTag::kWasmEntrypointTag,
+            CodeEntrypointTag::kBytecodeHandlerEntrypointTag,
+            CodeEntrypointTag::kRegExpEntrypointTag,
+        ];
+        let table = CodePointerTable::new();
+        let space = CodePointerTableSpace::new();
+        let code = |i: usize| 0x2000_0000 + i * 64 + 1;
+        let entrypoint = |i: usize| 0x4000_0000 + i * 64;
+        let tags: Vec<_> = (0..Self::K_NUM_ENTRIES).map(|_| self.pick(&TAGS)).collect();
+        let handles: Vec<u32> = (0..Self::K_NUM_ENTRIES)
+            .map(|i| table.allocate_and_initialize_entry(&space, code(i), entrypoint(i), tags[i]))
+            .collect();
+        let survivors = self.survivors(Self::K_NUM_ENTRIES);
+        let (live, stale) = partition(&handles, &survivors);
+        live.iter().for_each(|&handle| table.mark(handle));
+        table.sweep(&space);
+        let live_code: HashMap<Address, usize> = (0..Self::K_NUM_ENTRIES).filter(|&i| survivors[i]).map(|i| (code(i), i)).collect();
+
+        let report = self.run(iterations, &live, &stale, &TAGS, |handle| table.contains(handle), |handle, tag| {
+            let code_object = table.get_code_object(handle);
+            let target = table.get_entrypoint(handle, tag);
+            let code_outcome = classify(code_object, live_code.contains_key(&code_object));
+            let legitimate_target =
+                live_code.get(&code_object).is_some_and(|&i| tags[i] == tag && entrypoint(i) == target);
+            let target_outcome = classify(target, legitimate_target);
+            if code_outcome == LoadOutcome::kViolation || target_outcome == LoadOutcome::kViolation {
+                LoadOutcome::kViolation
+            } else if target_outcome == LoadOutcome::kLegitimate {
+                LoadOutcome::kLegitimate
+            } else {
+                LoadOutcome::kInaccessible
+            }
+        });
+        table.tear_down_space(&space);
+        report
+    }
+
+    pub fn run_js_dispatch_table(&mut self, iterations: usize) -> CorruptionReport {
+        let table = JSDispatchTable::new();
+        let space = JSDispatchTableSpace::new();
+        let code = |i: usize| 0x2000_0000 + i * 64 + 1;
+        let entrypoint = |i: usize| 0x4000_0000 + i * 64;
+        let parameter_counts: Vec<u16> = (0..Self::K_NUM_ENTRIES).map(|_| self.next() as u16 % 8).collect();
+        let handles: Vec<u32> = (0..Self::K_NUM_ENTRIES)
+            .map(|i| {
+                table
+                    .allocate_and_initialize_entry(&space, parameter_counts[i], code(i), entrypoint(i))
+                    .value()
+            })
+            .collect();
+        let survivors = self.survivors(Self::K_NUM_ENTRIES);
+        let (live, stale) = partition(&handles, &survivors);
+        live.iter().for_each(|&handle| table.mark(JSDispatchHandle(handle)));
+        table.sweep(&space);
+        let live_code: HashMap<Address, usize> = (0..Self::K_NUM_ENTRIES).filter(|&i| survivors[i]).map(|i| (code(i), i)).collect();
+
+        // Dispatch handles are untyped; what matters is that code,
+        // entrypoint and parameter count always belong together.
+        let report = self.run(iterations, &live, &stale, &[()], |handle| table.contains(JSDispatchHandle(handle)), |handle, ()| {
+            let handle = JSDispatchHandle(handle);
+            let code_object = table.get_code(handle);
+            let target = table.get_entrypoint(handle);
+            match live_code.get(&code_object) {
+                Some(&i) if entrypoint(i) == target && parameter_counts[i] == table.get_parameter_count(handle) => {
+                    LoadOutcome::kLegitimate
+                }
+                Some(_) => LoadOutcome::kViolation,
+                None if is_inaccessible(code_object) && is_inaccessible(target) => LoadOutcome::kInaccessible,
+                None => LoadOutcome::kViolation,
+            }
+        });
+        table.tear_down_space(&space);
+        report
+    }
+}
+
+fn partition(handles: &[u32], survivors: &[bool]) -> (Vec<u32>, Vec<u32>) {
+    let (live, dead): (Vec<_>, Vec<_>) = handles.iter().zip(survivors).partition(|&(_, &alive)| alive);
+    (live.into_iter().map(|(&handle, _)| handle).collect(), dead.into_iter().map(|(&handle, _)| handle).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const K_ITERATIONS: usize = 100_000;
+
+    fn check(name: &str, report: CorruptionReport) {
+        assert!(report.violations.is_empty(), "{name}: corrupted handles escaped: {:x?}", report.violations);
+        assert_eq!(report.loads(), K_ITERATIONS);
+        // Every kind of outcome is exercised.
+        assert!(report.faults > 0 && report.inaccessible > 0 && report.legitimate > 0, "{name}: {report:?}");
+    }
+
+    #[test]
+    fn corrupted_handles_cannot_escape_the_tables() {
+        let mut harness = CorruptionHarness::new(0x5eed);
+        check("ExternalPointerTable", harness.run_external_pointer_table(K_ITERATIONS));
+        check("CppHeapPointerTable", harness.run_cppheap_pointer_table(K_ITERATIONS));
+        check("TrustedPointerTable", harness.run_trusted_pointer_table(K_ITERATIONS));
+        check("CodePointerTable", harness.run_code_pointer_table(K_ITERATIONS));
+        check("JSDispatchTable", harness.run_js_dispatch_table(K_ITERATIONS));
+    }
+
+    #[test]
+    fn loads_from_uncommitted_entries_fault() {
+        let table = ExternalPointerTable::new();
+        let handle = ExternalPointerTable::index_to_handle(100_000);
+        assert!(!table.contains(handle));
+        // The load is done in a child process, which must die of a fault
+        // instead of reading memory.
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            let value = table.get(handle, ExternalPointerTag::kForeignForeignAddressTag.into());
+            unsafe { libc::_exit(if value == 0 { 0 } else { 1 }) };
+        }
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        assert!(libc::WIFSIGNALED(status), "load did not fault: status {status}");
+        assert!(matches!(libc::WTERMSIG(status), libc::SIGSEGV | libc::SIGBUS));
+    }
+}
@@ -2,368 +2,102 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
+use std::sync::atomic::Ordering;
 
-// This translation assumes that `V8_COMPRESS_POINTERS` is always enabled.
-// If it's not, you'll need to add conditional compilation based on that flag.
-
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::{
-    convert::TryFrom,
-    fmt::{Debug, Display, Formatter},
-    marker::PhantomData,
-    mem::size_of,
-    num::NonZeroU32,
+use crate::heap::heap_layout::Address;
+use crate::sandbox::cppheap_pointer_table::{
+    CppHeapPointerHandle, CppHeapPointerTable, CppHeapPointerTableEntry, CppHeapPointerTableSpace,
+    CppHeapPointerTag, CppHeapPointerTagRange, K_CPP_HEAP_POINTER_MARK_BIT, K_CPP_HEAP_POINTER_PAYLOAD_SHIFT,
+    K_CPP_HEAP_POINTER_ZAPPED_ENTRY_TAG,
 };
-
-const V8_COMPRESS_POINTERS: bool = true; // Conditional compilation not implemented, assuming true.
-
-const kBitsPerSystemPointer: usize = 64; // Assuming 64-bit architecture for simplicity. Needs to be adjusted for 32 bit architecture.
-const kCppHeapPointerPayloadShift: usize = 3; // Example value. Needs to be adjusted based on V8 constants.
-const kMaxCppHeapPointers: usize = 1024; // Example value. Needs to be adjusted based on V8 constants.
-const kNullAddress: usize = 0; // Example null address. Needs to be adjusted based on V8 implementation
-const kCppHeapPointerIndexShift: usize = 3; // Example value. Needs to be adjusted based on V8 constants.
-const kNullCppHeapPointerHandle: CppHeapPointerHandle = CppHeapPointerHandle(0); // Handle representing null.
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(u8)]
-pub enum CppHeapPointerTag {
-    kFreeEntryTag = 0,
-    kZappedEntryTag,
-    kEvacuationEntryTag,
-    kExternalPointerTag, // Example, add more if necessary. This needs to cover all tag types
-}
-
-impl Display for CppHeapPointerTag {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
-    }
-}
-
-// Define CppHeapPointerTagRange here if needed. Placeholder for now.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct CppHeapPointerTagRange {
-    start: CppHeapPointerTag,
-    end: CppHeapPointerTag,
-}
-
-impl CppHeapPointerTagRange {
-    pub const fn new(start: CppHeapPointerTag, end: CppHeapPointerTag) -> Self {
-        Self { start, end }
-    }
-
-    pub fn contains(&self, tag: CppHeapPointerTag) -> bool {
-        (tag as u8) >= (self.start as u8) && (tag as u8) <= (self.end as u8)
-    }
-}
-
-// Define Address (usize for now).  Adjust as needed if Address has special properties.
-pub type Address = usize;
-
-// Define Handle type.  Using u32 for now, adjust as needed.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct CppHeapPointerHandle(pub u32);
-
-#[derive(Copy, Clone)]
-struct Payload(u64);
-
-impl Payload {
-    fn new(value: usize, tag: CppHeapPointerTag) -> Self {
-        let value_bits = (value as u64) << kCppHeapPointerPayloadShift;
-        let tag_bits = (tag as u8) as u64;
-
-        Payload(value_bits | tag_bits)
-    }
-
-    fn extract_value(&self) -> usize {
-        (self.0 >> kCppHeapPointerPayloadShift) as usize
-    }
-
-    fn extract_tag(&self) -> CppHeapPointerTag {
-        match (self.0 & 0x7) as u8 {
-            0 => CppHeapPointerTag::kFreeEntryTag,
-            1 => CppHeapPointerTag::kZappedEntryTag,
-            2 => CppHeapPointerTag::kEvacuationEntryTag,
-            _ => CppHeapPointerTag::kExternalPointerTag, // default case, update if more tags exist.
-        }
-    }
-
-    fn contains_pointer(&self) -> bool {
-        !matches!(
-            self.extract_tag(),
-            CppHeapPointerTag::kFreeEntryTag | CppHeapPointerTag::kZappedEntryTag | CppHeapPointerTag::kEvacuationEntryTag
-        )
-    }
-
-    fn contains_evacuation_entry(&self) -> bool {
-        self.extract_tag() == CppHeapPointerTag::kEvacuationEntryTag
-    }
-
-    fn is_tagged_with_tag_in(&self, tag_range: CppHeapPointerTagRange) -> bool {
-        tag_range.contains(self.extract_tag())
-    }
-
-    fn untag(&self, tag_range: CppHeapPointerTagRange) -> Address {
-        debug_assert!(self.contains_pointer());
-        debug_assert!(tag_range.contains(self.extract_tag()));
-        self.extract_value()
-    }
-
-    fn extract_freelist_link(&self) -> u32 {
-        self.extract_value() as u32
-    }
-
-    fn has_mark_bit_set(&self) -> bool {
-        // Dummy implementation. Replace with actual logic using bitwise operation on self.0 if marking bit is used.
-        false
-    }
-
-    fn set_mark_bit(&mut self) {
-        // Dummy implementation. Replace with actual logic using bitwise operation on self.0 if marking bit is used.
-    }
-}
-
-#[derive(Debug)]
-pub struct CppHeapPointerTableEntry {
-    payload_: AtomicU64,
-}
+use crate::sandbox::external_entity_table::ExternalEntityTableEntry;
 
 impl CppHeapPointerTableEntry {
-    pub fn new() -> Self {
-        CppHeapPointerTableEntry {
-            payload_: AtomicU64::new(0),
-        }
-    }
-
+    #[inline]
     pub fn make_pointer_entry(&self, value: Address, tag: CppHeapPointerTag, mark_as_alive: bool) {
-        debug_assert_eq!(0, value >> (kBitsPerSystemPointer - kCppHeapPointerPayloadShift));
-        debug_assert_ne!(tag, CppHeapPointerTag::kFreeEntryTag);
-        debug_assert_ne!(tag, CppHeapPointerTag::kEvacuationEntryTag);
-
-        let mut new_payload = Payload::new(value, tag);
-        debug_assert!(!new_payload.has_mark_bit_set());
+        let mut payload = Self::encode(value as u64, tag);
         if mark_as_alive {
-            new_payload.set_mark_bit();
+            payload |= K_CPP_HEAP_POINTER_MARK_BIT;
         }
-        self.payload_.store(new_payload.0, Ordering::Relaxed);
+        self.payload.store(payload, Ordering::Relaxed);
     }
 
+    /// Loads the pointer of this entry, or null if its tag is not in
+    /// `tag_range`.
+    #[inline]
     pub fn get_pointer(&self, tag_range: CppHeapPointerTagRange) -> Address {
-        let payload = Payload(self.payload_.load(Ordering::Relaxed));
-        debug_assert!(payload.contains_pointer());
-        payload.untag(tag_range)
+        let payload = self.payload.load(Ordering::Relaxed);
+        if !tag_range.contains(Self::extract_tag(payload)) {
+            return 0;
+        }
+        (payload >> K_CPP_HEAP_POINTER_PAYLOAD_SHIFT) as Address
     }
 
+    /// Stores `value` with `tag`, keeping the mark bit.
+    #[inline]
     pub fn set_pointer(&self, value: Address, tag: CppHeapPointerTag) {
-        debug_assert_eq!(0, value >> (kBitsPerSystemPointer - kCppHeapPointerPayloadShift));
-        debug_assert_ne!(tag, CppHeapPointerTag::kFreeEntryTag);
-        debug_assert_ne!(tag, CppHeapPointerTag::kEvacuationEntryTag);
-        debug_assert!(Payload(self.payload_.load(Ordering::Relaxed)).contains_pointer());
-
-        let new_payload = Payload::new(value, tag);
-        self.payload_.store(new_payload.0, Ordering::Relaxed);
+        let payload = Self::encode(value as u64, tag);
+        self.payload
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old_payload| {
+                Some(payload | (old_payload & K_CPP_HEAP_POINTER_MARK_BIT))
+            })
+            .unwrap();
     }
 
+    #[inline]
     pub fn has_pointer(&self, tag_range: CppHeapPointerTagRange) -> bool {
-        let payload = Payload(self.payload_.load(Ordering::Relaxed));
-        payload.is_tagged_with_tag_in(tag_range)
+        tag_range.contains(Self::extract_tag(self.payload.load(Ordering::Relaxed)))
     }
 
+    #[inline]
     pub fn make_zapped_entry(&self) {
-        let new_payload = Payload::new(kNullAddress, CppHeapPointerTag::kZappedEntryTag);
-        self.payload_.store(new_payload.0, Ordering::Relaxed);
-    }
-
-    pub fn make_freelist_entry(&self, next_entry_index: u32) {
-        assert!(kMaxCppHeapPointers <= u32::MAX as usize);
-        let new_payload = Payload::new(next_entry_index as usize, CppHeapPointerTag::kFreeEntryTag);
-        self.payload_.store(new_payload.0, Ordering::Relaxed);
-    }
-
-    pub fn get_next_freelist_entry_index(&self) -> u32 {
-        let payload = Payload(self.payload_.load(Ordering::Relaxed));
-        payload.extract_freelist_link()
-    }
-
-    pub fn mark(&self) {
-        let old_payload = Payload(self.payload_.load(Ordering::Relaxed));
-        debug_assert!(old_payload.contains_pointer());
-
-        let mut new_payload = old_payload;
-        new_payload.set_mark_bit();
-
-        self.payload_.compare_exchange(
-            old_payload.0,
-            new_payload.0,
-            Ordering::Relaxed,
-            Ordering::Relaxed,
-        );
-    }
-
-    pub fn make_evacuation_entry(&self, handle_location: Address) {
-        let new_payload = Payload::new(handle_location, CppHeapPointerTag::kEvacuationEntryTag);
-        self.payload_.store(new_payload.0, Ordering::Relaxed);
-    }
-
-    pub fn has_evacuation_entry(&self) -> bool {
-        let payload = Payload(self.payload_.load(Ordering::Relaxed));
-        payload.contains_evacuation_entry()
-    }
-
-    pub fn evacuate(&self, dest: &CppHeapPointerTableEntry) {
-        let payload = Payload(self.payload_.load(Ordering::Relaxed));
-        debug_assert!(payload.contains_pointer());
-        debug_assert!(!payload.has_mark_bit_set());
-
-        dest.payload_.store(payload.0, Ordering::Relaxed);
-
-        self.make_zapped_entry();
-    }
-}
-
-// Forward declaration for Space.  Replace with actual definition if needed.
-#[derive(Debug)]
-pub struct Space {}
-
-impl Space {
-    pub fn belongs_to(&self, _table: &CppHeapPointerTable) -> bool {
-        true
-    }
-
-    pub fn allocate_black(&self) -> bool {
-        true
-    }
-
-    pub fn contains(&self, _index: u32) -> bool {
-        true
+        self.set_pointer(0, K_CPP_HEAP_POINTER_ZAPPED_ENTRY_TAG);
     }
 }
 
-#[derive(Debug)]
-pub struct CppHeapPointerTable {
-    entries: Vec<CppHeapPointerTableEntry>,
-}
-
 impl CppHeapPointerTable {
-    pub fn new() -> Self {
-        let mut entries = Vec::new();
-        for _ in 0..kMaxCppHeapPointers {
-            entries.push(CppHeapPointerTableEntry::new());
-        }
-        CppHeapPointerTable { entries }
-    }
-
-    fn at(&self, index: u32) -> &CppHeapPointerTableEntry {
-        &self.entries[index as usize]
-    }
-
-    fn at_mut(&mut self, index: u32) -> &mut CppHeapPointerTableEntry {
-        &mut self.entries[index as usize]
-    }
-
-    pub fn get(&self, handle: CppHeapPointerHandle, tag_range: CppHeapPointerTagRange) -> Address {
-        let index = Self::handle_to_index(handle);
-        debug_assert!(index == 0 || self.at(index).has_pointer(tag_range));
-        self.at(index).get_pointer(tag_range)
-    }
-
-    pub fn set(&mut self, handle: CppHeapPointerHandle, value: Address, tag: CppHeapPointerTag) {
-        debug_assert_ne!(kNullCppHeapPointerHandle, handle);
-        let index = Self::handle_to_index(handle);
-        self.at_mut(index).set_pointer(value, tag);
-    }
-
+    #[inline]
     pub fn allocate_and_initialize_entry(
-        &mut self,
-        space: &mut Space,
-        initial_value: Address,
+        &self,
+        space: &CppHeapPointerTableSpace,
+        value: Address,
         tag: CppHeapPointerTag,
     ) -> CppHeapPointerHandle {
-        debug_assert!(space.belongs_to(self));
         let index = self.allocate_entry(space);
-        self.at(index)
-            .make_pointer_entry(initial_value, tag, space.allocate_black());
+        self.at(index).make_pointer_entry(value, tag, space.allocate_black());
+        Self::index_to_handle(index)
+    }
 
-        let handle = Self::index_to_handle(index);
+    #[inline]
+    pub fn get(&self, handle: CppHeapPointerHandle, tag_range: CppHeapPointerTagRange) -> Address {
+        self.at(Self::handle_to_index(handle)).get_pointer(tag_range)
+    }
 
-        handle
+    #[inline]
+    pub fn set(&self, handle: CppHeapPointerHandle, value: Address, tag: CppHeapPointerTag) {
+        debug_assert_ne!(handle, 0, "the null entry is read-only");
+        self.at(Self::handle_to_index(handle)).set_pointer(value, tag);
     }
 
-    fn allocate_entry(&mut self, _space: &mut Space) -> u32 {
-        // Dummy allocation strategy.  Replace with proper freelist management.
-        for i in 1..kMaxCppHeapPointers {
-            if !self.at(i as u32).has_pointer(CppHeapPointerTagRange::new(
-                CppHeapPointerTag::kExternalPointerTag,
-                CppHeapPointerTag::kExternalPointerTag,
-            )) && !self.at(i as u32).has_evacuation_entry()
-            {
-                return i as u32;
-            }
+    #[inline]
+    pub fn zap(&self, handle: CppHeapPointerHandle) {
+        if handle != 0 {
+            self.at(Self::handle_to_index(handle)).make_zapped_entry();
         }
-        panic!("Out of entries!");
     }
 
-    pub fn mark(&self, space: &mut Space, handle: CppHeapPointerHandle, handle_location: Address) {
-        debug_assert!(space.belongs_to(self));
-
-        // The handle_location must always contain the given handle. Except if the
-        // slot is lazily-initialized. In that case, the handle may transition from
-        // the null handle to a valid handle. However, in that case the
-        // newly-allocated entry will already have been marked as alive during
-        // allocation, and so we don't need to do anything here.
-        //
-        //  CppHeapPointerHandle current_handle = base::AsAtomic32::Acquire_Load(
-        //      reinterpret_cast<CppHeapPointerHandle*>(handle_location));
-        //  DCHECK(handle == kNullCppHeapPointerHandle || handle == current_handle);
-        //
-
-        // If the handle is null, it doesn't have an EPT entry; no mark is needed.
-        if handle == kNullCppHeapPointerHandle {
+    /// Marks the entry of `handle`, which is stored at `handle_location`,
+    /// as alive, scheduling it for evacuation if the space is compacted.
+    #[inline]
+    pub fn mark(&self, space: &CppHeapPointerTableSpace, handle: CppHeapPointerHandle, handle_location: Address) {
+        if handle == 0 {
             return;
         }
-
         let index = Self::handle_to_index(handle);
-        debug_assert!(space.contains(index));
-
-        // If the table is being compacted and the entry is inside the evacuation
-        // area, then allocate and set up an evacuation entry for it.
-        self.maybe_create_evacuation_entry(space, index, handle_location);
-
-        // Even if the entry is marked for evacuation, it still needs to be marked as
-        // alive as it may be visited during sweeping before being evacuation.
-        self.at(index).mark();
-    }
-
-    fn maybe_create_evacuation_entry(
-        &self,
-        _space: &mut Space,
-        _index: u32,
-        _handle_location: Address,
-    ) {
-        // Implementation for MaybeCreateEvacuationEntry.  Not fully implemented.
-    }
-
-    pub fn is_valid_handle(handle: CppHeapPointerHandle) -> bool {
-        let index = handle.0 >> kCppHeapPointerIndexShift;
-        handle == CppHeapPointerHandle(index << kCppHeapPointerIndexShift)
-    }
-
-    pub fn handle_to_index(handle: CppHeapPointerHandle) -> u32 {
-        debug_assert!(Self::is_valid_handle(handle));
-        let index = handle.0 >> kCppHeapPointerIndexShift;
-        debug_assert!(index <= kMaxCppHeapPointers as u32);
-        index
-    }
-
-    pub fn index_to_handle(index: u32) -> CppHeapPointerHandle {
-        debug_assert!(index <= kMaxCppHeapPointers as u32);
-        let handle_value = index << kCppHeapPointerIndexShift;
-        debug_assert_ne!(handle_value, kNullCppHeapPointerHandle.0);
-        CppHeapPointerHandle(handle_value)
-    }
-
-    pub fn contains(&self, space: &Space, handle: CppHeapPointerHandle) -> bool {
-        debug_assert!(space.belongs_to(self));
-        space.contains(Self::handle_to_index(handle))
+        let entry = self.at(index);
+        if !entry.has_mark_bit_set() {
+            self.maybe_create_evacuation_entry(space, index, handle_location);
+        }
+        entry.set_mark_bit();
     }
-}
\ No newline at end of file
+}
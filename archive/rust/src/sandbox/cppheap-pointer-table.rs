@@ -1,343 +1,189 @@
-// src/sandbox/cppheap_pointer_table.rs
-
-//use crate::execution::isolate::Isolate;
-//use crate::logging::counters::Counters;
-//use crate::sandbox::cppheap_pointer_table_inl; // Assuming this is inlinable, or another rust module
-//use crate::base; // Assuming base translates to some rust crate or module
-//use std::sync::{Mutex, MutexGuard};
-//use std::sync::atomic::{AtomicU32, Ordering};
-//use std::vec::Vec;
-
-// Replace with appropriate Rust features
-//#[cfg(v8_compress_pointers)]
-mod cppheap_pointer_table {
-    //use super::*; // Bring in dependencies from parent module
-
-    // Assuming these constants are defined elsewhere and accessible, or redefine them here
-    const K_ENTRIES_PER_SEGMENT: usize = 64; // Example value
-    const K_ENTRY_ALLOCATION_IS_FORBIDDEN_MARKER: u32 = 0xFFFF_FFFF; // Example value
-
-    // Placeholder for Address, may need a more specific type
-    type Address = usize;
-
-    // Placeholder for CppHeapPointerHandle, may need a more specific type
-    type CppHeapPointerHandle = usize;
-
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    struct Segment {
-        first_entry: u32,
-        last_entry: u32,
+// Copyright 2024 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::heap::heap_layout::Address;
+use crate::sandbox::compactible_external_entity_table::{
+    CompactibleExternalEntityTable, CompactibleExternalEntityTableEntry, CompactibleSpace,
+};
+use crate::sandbox::external_entity_table::ExternalEntityTableEntry;
+
+const MB: usize = 1024 * 1024;
+
+/// A CppHeap pointer handle is the index of the entry shifted left by
+/// `K_CPP_HEAP_POINTER_INDEX_SHIFT`.
+pub type CppHeapPointerHandle = u32;
+pub const K_NULL_CPP_HEAP_POINTER_HANDLE: CppHeapPointerHandle = 0;
+
+pub const K_CPP_HEAP_POINTER_TABLE_RESERVATION_SIZE: usize = 8 * MB;
+pub const K_CPP_HEAP_POINTER_INDEX_SHIFT: u32 = 12;
+pub const K_MAX_CPP_HEAP_POINTERS: usize =
+    K_CPP_HEAP_POINTER_TABLE_RESERVATION_SIZE / std::mem::size_of::<CppHeapPointerTableEntry>();
+const _: () = assert!(((u32::MAX >> K_CPP_HEAP_POINTER_INDEX_SHIFT) as usize) < K_MAX_CPP_HEAP_POINTERS);
+
+/// Layout of an entry: the mark bit, a 15-bit tag and the pointer in the
+/// upper 48 bits.
+pub const K_CPP_HEAP_POINTER_MARK_BIT: u64 = 1;
+pub const K_CPP_HEAP_POINTER_TAG_SHIFT: u32 = 1;
+pub const K_CPP_HEAP_POINTER_PAYLOAD_SHIFT: u32 = 16;
+
+/// Tags of pointers to objects on the CppHeap. Tags are chosen by the
+/// embedder, which checks type hierarchies with ranges of them; the top
+/// tags are reserved for the table.
+pub type CppHeapPointerTag = u16;
+pub const K_CPP_HEAP_POINTER_NULL_TAG: CppHeapPointerTag = 0;
+pub const K_CPP_HEAP_POINTER_DEFAULT_TAG: CppHeapPointerTag = 0x7000;
+pub const K_CPP_HEAP_POINTER_ZAPPED_ENTRY_TAG: CppHeapPointerTag = 0x7ffd;
+pub const K_CPP_HEAP_POINTER_EVACUATION_ENTRY_TAG: CppHeapPointerTag = 0x7ffe;
+pub const K_CPP_HEAP_POINTER_FREE_ENTRY_TAG: CppHeapPointerTag = 0x7fff;
+
+/// An inclusive range of tags.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CppHeapPointerTagRange {
+    pub lower_bound: CppHeapPointerTag,
+    pub upper_bound: CppHeapPointerTag,
+}
+
+impl CppHeapPointerTagRange {
+    pub const fn new(lower_bound: CppHeapPointerTag, upper_bound: CppHeapPointerTag) -> Self {
+        CppHeapPointerTagRange { lower_bound, upper_bound }
     }
 
-    impl Segment {
-        fn first_entry(&self) -> u32 {
-            self.first_entry
-        }
-        fn last_entry(&self) -> u32 {
-            self.last_entry
-        }
+    pub fn contains(self, tag: CppHeapPointerTag) -> bool {
+        (self.lower_bound..=self.upper_bound).contains(&tag)
     }
+}
 
-    struct FreelistHead {
-        head: u32,
-        length: u32,
+impl From<CppHeapPointerTag> for CppHeapPointerTagRange {
+    fn from(tag: CppHeapPointerTag) -> Self {
+        Self::new(tag, tag)
     }
-    impl FreelistHead {
-        fn new(head: u32, length: u32) -> Self{
-            FreelistHead{ head, length}
-        }
+}
+
+/// The entries of a CppHeapPointerTable: a pointer with tag and mark bit,
+/// or a freelist, evacuation or zapped entry tagged with the reserved
+/// tags.
+#[repr(transparent)]
+pub struct CppHeapPointerTableEntry {
+    pub(crate) payload: AtomicU64,
+}
+
+impl CppHeapPointerTableEntry {
+    pub(crate) fn encode(value: u64, tag: CppHeapPointerTag) -> u64 {
+        assert_eq!(value >> (64 - K_CPP_HEAP_POINTER_PAYLOAD_SHIFT), 0, "CppHeap pointer out of range");
+        debug_assert!(tag <= K_CPP_HEAP_POINTER_FREE_ENTRY_TAG);
+        value << K_CPP_HEAP_POINTER_PAYLOAD_SHIFT | (tag as u64) << K_CPP_HEAP_POINTER_TAG_SHIFT
     }
 
-    // Placeholder for Space
-    struct Space {
-        mutex_: std::sync::Mutex<()>,
-        invalidated_fields_mutex_: std::sync::Mutex<()>,
-        freelist_head_: std::sync::atomic::AtomicU32,
-        start_of_evacuation_area_: std::sync::atomic::AtomicU32,
-        segments_: std::collections::HashSet<Segment>,
+    pub(crate) fn extract_tag(payload: u64) -> CppHeapPointerTag {
+        ((payload & 0xffff) >> K_CPP_HEAP_POINTER_TAG_SHIFT) as CppHeapPointerTag
     }
 
-    impl Space{
-        fn belongs_to(&self, _table: &CppHeapPointerTable) -> bool{
-            true // Placeholder, implement actual logic
-        }
-
-        fn is_compacting(&self) -> bool {
-            false // Placeholder, implement actual logic
-        }
-
-        fn compacting_was_aborted(&self) -> bool{
-            false // Placeholder, implement actual logic
-        }
-
-        fn stop_compacting(&mut self){
-            // Placeholder, implement actual logic
-        }
-
-        fn field_was_invalidated(&self, _handle_location: Address) -> bool{
-            false // Placeholder, implement actual logic
-        }
-
-        fn capacity(&self) -> u32{
-            0 // Placeholder, implement actual logic
-        }
+    fn is_tagged_with(&self, tag: CppHeapPointerTag) -> bool {
+        Self::extract_tag(self.payload.load(Ordering::Relaxed)) == tag
+    }
+}
 
-        fn freelist_length(&self) -> u32{
-            0 // Placeholder, implement actual logic
-        }
+impl ExternalEntityTableEntry for CppHeapPointerTableEntry {
+    fn make_freelist_entry(&self, next_entry_index: u32) {
+        let payload = Self::encode(next_entry_index as u64, K_CPP_HEAP_POINTER_FREE_ENTRY_TAG);
+        self.payload.store(payload, Ordering::Relaxed);
     }
 
-    // Placeholder for Counters
-    struct Counters { }
-    impl Counters{
-        fn cppheap_pointers_count(&self) -> &CppHeapPointersCounter{
-            &CppHeapPointersCounter{} // Placeholder
-        }
+    fn get_next_freelist_entry_index(&self) -> u32 {
+        (self.payload.load(Ordering::Relaxed) >> K_CPP_HEAP_POINTER_PAYLOAD_SHIFT) as u32
     }
 
-    // Placeholder for CppHeapPointersCounter
-    struct CppHeapPointersCounter{}
-    impl CppHeapPointersCounter{
-        fn add_sample(&self, _num_live_entries: u32){}
+    fn is_freelist_entry(&self) -> bool {
+        self.is_tagged_with(K_CPP_HEAP_POINTER_FREE_ENTRY_TAG)
     }
 
-    #[derive(Debug, Copy, Clone)]
-    struct RawPayload {
-        data: u64, // Example representation
+    fn set_mark_bit(&self) {
+        self.payload.fetch_or(K_CPP_HEAP_POINTER_MARK_BIT, Ordering::Relaxed);
     }
 
-    impl RawPayload {
-        fn contains_evacuation_entry(&self) -> bool {
-            false // Placeholder, implement actual logic
-        }
+    fn clear_mark_bit(&self) {
+        self.payload.fetch_and(!K_CPP_HEAP_POINTER_MARK_BIT, Ordering::Relaxed);
+    }
 
-        fn extract_evacuation_entry_handle_location(&self) -> Address {
-            0 // Placeholder, implement actual logic
-        }
+    fn has_mark_bit_set(&self) -> bool {
+        self.payload.load(Ordering::Relaxed) & K_CPP_HEAP_POINTER_MARK_BIT != 0
+    }
+}
 
-        fn contains_pointer(&self) -> bool {
-            true // Placeholder, implement actual logic
-        }
+impl CompactibleExternalEntityTableEntry for CppHeapPointerTableEntry {
+    fn make_evacuation_entry(&self, handle_location: Address) {
+        let payload = Self::encode(handle_location as u64, K_CPP_HEAP_POINTER_EVACUATION_ENTRY_TAG);
+        self.payload.store(payload, Ordering::Relaxed);
+    }
 
-        fn has_mark_bit_set(&self) -> bool {
-            false // Placeholder, implement actual logic
-        }
+    fn has_evacuation_entry(&self) -> bool {
+        self.is_tagged_with(K_CPP_HEAP_POINTER_EVACUATION_ENTRY_TAG)
+    }
 
-        fn clear_mark_bit(&mut self){
-            // Placeholder, implement actual logic
-        }
+    fn get_evacuation_entry_handle_location(&self) -> Address {
+        debug_assert!(self.has_evacuation_entry());
+        (self.payload.load(Ordering::Relaxed) >> K_CPP_HEAP_POINTER_PAYLOAD_SHIFT) as Address
     }
-    
-    struct Entry {
-        payload: RawPayload
+
+    fn evacuate(&self, dest: &Self) {
+        let payload = self.payload.load(Ordering::Relaxed) & !K_CPP_HEAP_POINTER_MARK_BIT;
+        dest.payload.store(payload, Ordering::Relaxed);
     }
-    impl Entry {
-        fn get_raw_payload(&self) -> RawPayload {
-            self.payload
-        }
+}
 
-        fn set_raw_payload(&mut self, new_payload: RawPayload){
-            self.payload = new_payload;
-        }
+pub type CppHeapPointerTableSpace = CompactibleSpace;
 
-        fn make_freelist_entry(&mut self, _next: u32){
-            // Placeholder, implement actual logic
-        }
+/// A table storing pointers from objects in the sandbox to their
+/// counterparts on the CppHeap, with the same guarantees and the same
+/// garbage collection as the ExternalPointerTable.
+pub struct CppHeapPointerTable {
+    table: CompactibleExternalEntityTable<CppHeapPointerTableEntry, K_CPP_HEAP_POINTER_TABLE_RESERVATION_SIZE>,
+}
 
-        fn has_evacuation_entry(&self) -> bool{
-            false // Placeholder, implement actual logic
+impl CppHeapPointerTable {
+    pub fn new() -> Self {
+        CppHeapPointerTable {
+            table: CompactibleExternalEntityTable::new(K_CPP_HEAP_POINTER_INDEX_SHIFT),
         }
+    }
 
-        fn evacuate(&mut self, _other: &mut Entry){
-            // Placeholder, implement actual logic
-        }
+    #[inline]
+    pub fn handle_to_index(handle: CppHeapPointerHandle) -> u32 {
+        handle >> K_CPP_HEAP_POINTER_INDEX_SHIFT
     }
 
-    pub struct CppHeapPointerTable {
-        entries: Vec<Entry>
+    #[inline]
+    pub fn index_to_handle(index: u32) -> CppHeapPointerHandle {
+        index << K_CPP_HEAP_POINTER_INDEX_SHIFT
     }
 
-    impl CppHeapPointerTable {
-        fn at(&self, index: u32) -> &Entry{
-            &self.entries[index as usize]
-        }
-        fn at_mut(&mut self, index: u32) -> &mut Entry{
-            &mut self.entries[index as usize]
-        }
+    pub fn contains(&self, handle: CppHeapPointerHandle) -> bool {
+        self.table.is_committed(Self::handle_to_index(handle))
+    }
 
-        fn free_table_segment(&mut self, _segment: Segment) {
-            // Placeholder, implement actual logic
-        }
+    pub fn start_compacting_if_needed(&self, space: &CppHeapPointerTableSpace) {
+        self.table.start_compacting_if_needed(space);
+    }
 
-        fn is_valid_handle(&self, _handle: CppHeapPointerHandle) -> bool{
-            true // Placeholder, implement actual logic
-        }
+    pub fn sweep_and_compact(&self, space: &CppHeapPointerTableSpace) -> u32 {
+        self.table.sweep_and_compact(space)
+    }
+}
 
-        fn handle_to_index(&self, _handle: CppHeapPointerHandle) -> u32{
-            0 // Placeholder, implement actual logic
-        }
+impl Default for CppHeapPointerTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        fn index_to_handle(&self, _index: u32) -> CppHeapPointerHandle{
-            0 // Placeholder, implement actual logic
-        }
+impl Deref for CppHeapPointerTable {
+    type Target = CompactibleExternalEntityTable<CppHeapPointerTableEntry, K_CPP_HEAP_POINTER_TABLE_RESERVATION_SIZE>;
 
-        pub fn sweep_and_compact(&mut self, space: &mut Space, counters: &mut Counters) -> u32 {
-            // Lock the space.
-            let _guard = space.mutex_.lock().unwrap();
-            // Same for the invalidated fields mutex.
-            let _invalidated_fields_guard = space.invalidated_fields_mutex_.lock().unwrap();
-    
-            // There must not be any entry allocations while the table is being swept as
-            // that would not be safe. Set the freelist to this special marker value to
-            // easily catch any violation of this requirement.
-            space.freelist_head_.store(K_ENTRY_ALLOCATION_IS_FORBIDDEN_MARKER, std::sync::atomic::Ordering::Relaxed);
-    
-            // When compacting, we can compute the number of unused segments at the end of
-            // the table and skip those during sweeping.
-            let mut start_of_evacuation_area =
-                space.start_of_evacuation_area_.load(std::sync::atomic::Ordering::Relaxed);
-            let mut evacuation_was_successful = false;
-            if space.is_compacting() {
-                if space.compacting_was_aborted() {
-                    // Extract the original start_of_evacuation_area value so that the
-                    // DCHECKs below and in TryResolveEvacuationEntryDuringSweeping work.
-                    start_of_evacuation_area &= !(0u32); // Equivalent of ~Space::kCompactionAbortedMarker, assuming it was 0
-                } else {
-                    evacuation_was_successful = true;
-                }
-                //DCHECK(IsAligned(start_of_evacuation_area, kEntriesPerSegment));
-                space.stop_compacting();
-            }
-    
-            // Sweep top to bottom and rebuild the freelist from newly dead and
-            // previously freed entries while also clearing the marking bit on live
-            // entries and resolving evacuation entries table when compacting the table.
-            // This way, the freelist ends up sorted by index which already makes the
-            // table somewhat self-compacting and is required for the compaction
-            // algorithm so that evacuated entries are evacuated to the start of a space.
-            // This method must run either on the mutator thread or while the mutator is
-            // stopped.
-            let mut current_freelist_head = 0;
-            let mut current_freelist_length = 0;
-            let mut add_to_freelist = |entry_index: u32| {
-                self.at_mut(entry_index).make_freelist_entry(current_freelist_head);
-                current_freelist_head = entry_index;
-                current_freelist_length += 1;
-            };
-    
-            let mut segments_to_deallocate: Vec<Segment> = Vec::new();
-            for segment in space.segments_.iter().rev() {
-                let segment_will_be_evacuated =
-                    evacuation_was_successful &&
-                    segment.first_entry() >= start_of_evacuation_area;
-                // Remember the state of the freelist before this segment in case this
-                // segment turns out to be completely empty and we deallocate it.
-                let previous_freelist_head = current_freelist_head;
-                let previous_freelist_length = current_freelist_length;
-    
-                // Process every entry in this segment, again going top to bottom.
-                let mut i = segment.last_entry();
-                while i >= segment.first_entry() {
-                    let payload = self.at(i).get_raw_payload();
-                    if payload.contains_evacuation_entry() {
-                        // Segments that will be evacuated cannot contain evacuation entries
-                        // into which other entries would be evacuated.
-                        assert!(!segment_will_be_evacuated);
-    
-                        // An evacuation entry contains the address of the slot that owns the
-                        // entry that is to be evacuated.
-                        let handle_location =
-                            payload.extract_evacuation_entry_handle_location();
-    
-                        // The CppHeapPointerTable does not support field invalidation.
-                        assert!(!space.field_was_invalidated(handle_location));
-    
-                        // Resolve the evacuation entry: take the pointer to the handle from the
-                        // evacuation entry, copy the entry to its new location, and finally
-                        // update the handle to point to the new entry.
-                        //
-                        // While we now know that the entry being evacuated is free, we don't
-                        // add it to (the start of) the freelist because that would immediately
-                        // cause new fragmentation when the next entry is allocated. Instead, we
-                        // assume that the segments out of which entries are evacuated will all
-                        // be decommitted anyway after this loop, which is usually the case
-                        // unless compaction was already aborted during marking.
-                        self.resolve_evacuation_entry_during_sweeping(
-                            i, handle_location as *mut CppHeapPointerHandle, //reinterpret_cast<CppHeapPointerHandle*>(handle_location),
-                            start_of_evacuation_area);
-    
-                        // The entry must now contain a pointer and be unmarked as the entry
-                        // that was evacuated must have been processed already (it is in an
-                        // evacuated segment, which are processed first as they are at the end
-                        // of the space). This will have cleared the marking bit.
-                        assert!(self.at(i).get_raw_payload().contains_pointer());
-                        assert!(!self.at(i).get_raw_payload().has_mark_bit_set());
-                    } else if !payload.has_mark_bit_set() {
-                        add_to_freelist(i);
-                    } else {
-                        let mut new_payload = payload;
-                        new_payload.clear_mark_bit();
-                        self.at_mut(i).set_raw_payload(new_payload);
-                    }
-    
-                    // We must have resolved all evacuation entries. Otherwise, we'll try to
-                    // process them again during the next GC, which would cause problems.
-                    assert!(!self.at(i).has_evacuation_entry());
-
-                    if i == 0 {
-                        break;
-                    }
-                    i -= 1;
-                }
-    
-                // If a segment is completely empty, or if all live entries will be
-                // evacuated out of it at the end of this loop, free the segment.
-                // Note: for segments that will be evacuated, we could avoid building up a
-                // freelist, but it's probably not worth the effort.
-                let free_entries = current_freelist_length - previous_freelist_length;
-                let segment_is_empty = free_entries as usize == K_ENTRIES_PER_SEGMENT;
-                if segment_is_empty || segment_will_be_evacuated {
-                    segments_to_deallocate.push(segment.clone());
-                    // Restore the state of the freelist before this segment.
-                    current_freelist_head = previous_freelist_head;
-                    current_freelist_length = previous_freelist_length;
-                }
-            }
-    
-            // We cannot deallocate the segments during the above loop, so do it now.
-            for segment in segments_to_deallocate {
-                self.free_table_segment(segment);
-                space.segments_.remove(&segment);
-            }
-    
-            let new_freelist = FreelistHead::new(current_freelist_head, current_freelist_length);
-            space.freelist_head_.store(new_freelist.head, std::sync::atomic::Ordering::Release);
-            //DCHECK_EQ(space->freelist_length(), current_freelist_length);
-    
-            let num_live_entries = space.capacity() - current_freelist_length;
-            counters.cppheap_pointers_count().add_sample(num_live_entries);
-            return num_live_entries;
-        }
-    
-        fn resolve_evacuation_entry_during_sweeping(
-            &mut self,
-            new_index: u32,
-            handle_location: *mut CppHeapPointerHandle,
-            start_of_evacuation_area: u32,
-        ) {
-            let old_handle = unsafe { *handle_location };
-            assert!(self.is_valid_handle(old_handle));
-    
-            let old_index = self.handle_to_index(old_handle);
-            let new_handle = self.index_to_handle(new_index);
-    
-            // The compaction algorithm always moves an entry from the evacuation area to
-            // the front of the table. These DCHECKs verify this invariant.
-            assert!(old_index >= start_of_evacuation_area);
-            assert!(new_index < start_of_evacuation_area);
-            let new_entry = self.at_mut(new_index);
-            self.at_mut(old_index).evacuate(new_entry);
-            unsafe { *handle_location = new_handle };
-        }
+    fn deref(&self) -> &Self::Target {
+        &self.table
     }
-}
\ No newline at end of file
+}
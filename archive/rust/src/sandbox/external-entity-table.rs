@@ -2,611 +2,444 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod external_entity_table {
-    use std::cell::UnsafeCell;
-    use std::collections::HashSet;
-    use std::marker::PhantomData;
-    use std::mem;
-    use std::ptr;
-    use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
-    use std::sync::{Arc, Mutex, MutexGuard, RwLock};
-
-    /// A thread-safe table with a fixed maximum size for storing references to
-    /// objects located outside of the sandbox.
-    ///
-    /// An external entity table provides the basic mechanisms to ensure
-    /// safe access to objects located outside the sandbox, but referenced
-    /// from within it. When an external entity table is used, objects located
-    /// inside the sandbox reference outside objects through indices into the table.
-    ///
-    /// The ExternalEntityTable class should be seen an an incomplete class that
-    /// needs to be extended by a concrete implementation class, such as the
-    /// ExternalPointerTable class, as it is lacking some functionality. In
-    /// particular, while the ExternalEntityTable implements basic table memory
-    /// management as well as entry allocation routines, it does not implement any
-    /// logic for reclaiming entries such as garbage collection. This must be done
-    /// by the child classes.
-    ///
-    /// For the purpose of memory management, the table is partitioned into Segments
-    /// (for example 64kb memory chunks) that are grouped together in "Spaces". All
-    /// segments in a space share a freelist, and so entry allocation and garbage
-    /// collection happen on the level of spaces.
-    pub struct ExternalEntityTable<Entry, const SIZE: usize> {
-        base: SegmentedTable<Entry, SIZE>,
-        _phantom: PhantomData<Entry>,
-    }
-
-    impl<Entry, const SIZE: usize> ExternalEntityTable<Entry, SIZE> {
-        pub const SUPPORTS_COMPACTION: bool = false;
-
-        pub fn new() -> Self {
-            ExternalEntityTable {
-                base: SegmentedTable::new(),
-                _phantom: PhantomData,
-            }
-        }
-
-        /// Initializes the table by reserving the backing memory, allocating an
-        /// initial segment, and populating the freelist.
-        pub fn initialize(&mut self) {
-            self.base.initialize();
-        }
-
-        /// Deallocates all memory associated with this table.
-        pub fn tear_down(&mut self) {
-            self.base.tear_down();
-        }
-
-        /// Initializes the given space for use with this table.
-        pub fn initialize_space(&self, space: &mut Space<Entry, SIZE>) {
-            space.owning_table.store(self as *const _ as *mut _, Ordering::Relaxed);
-        }
-
-        /// Deallocates all segments owned by the given space.
-        pub fn tear_down_space(&self, space: &mut Space<Entry, SIZE>) {
-            let mut guard = space.mutex.lock().unwrap();
-            for segment in &space.segments {
-                self.base.deallocate_segment(*segment);
-            }
-            space.segments.clear();
-        }
-
-        /// Attaches/detaches the given space to the internal read-only segment. Note
-        /// the lifetime of the underlying segment itself is managed by the table.
-        pub fn attach_space_to_read_only_segment(&mut self, space: &mut Space<Entry, SIZE>) {
-            space.is_internal_read_only_space_ = true;
-        }
-        pub fn detach_space_from_read_only_segment(&mut self, space: &mut Space<Entry, SIZE>) {
-            space.is_internal_read_only_space_ = false;
-        }
-
-        /// Use this scope to temporarily unseal the read-only segment (i.e. change
-        /// permissions to RW).
-        pub struct UnsealReadOnlySegmentScope<'a, Entry, const SIZE: usize> {
-            table: &'a mut ExternalEntityTable<Entry, SIZE>,
-        }
-
-        impl<'a, Entry, const SIZE: usize> UnsealReadOnlySegmentScope<'a, Entry, SIZE> {
-            pub fn new(table: &'a mut ExternalEntityTable<Entry, SIZE>) -> Self {
-                table.unseal_read_only_segment();
-                UnsealReadOnlySegmentScope { table }
-            }
-        }
-
-        impl<'a, Entry, const SIZE: usize> Drop for UnsealReadOnlySegmentScope<'a, Entry, SIZE> {
-            fn drop(&mut self) {
-                self.table.seal_read_only_segment();
-            }
-        }
-        const INTERNAL_READ_ONLY_SEGMENT_OFFSET: u32 = 0;
-        const INTERNAL_NULL_ENTRY_INDEX: u32 = 0;
-        const END_OF_INTERNAL_READ_ONLY_SEGMENT: usize =
-            SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT;
-
-        fn unseal_read_only_segment(&mut self) {
-            self.base.unseal_read_only_segment();
-        }
-        fn seal_read_only_segment(&mut self) {
-            self.base.seal_read_only_segment();
-        }
-
-        fn extend(&mut self, space: &mut Space<Entry, SIZE>, segment: Segment, freelist: FreelistHead) {
-            let mut guard = space.mutex.lock().unwrap();
-            space.segments.insert(segment);
-        }
-
-        fn allocate_entry(&self, space: &mut Space<Entry, SIZE>) -> u32 {
-            if let Some(index) = self.try_allocate_entry(space) {
-                index
-            } else {
-                let freelist = self
-                    .try_extend(space)
-                    .expect("Failed to extend space during allocation");
-                self.try_allocate_entry_from_freelist(space, freelist)
-                    .expect("Failed to allocate from new segment");
-                self.try_allocate_entry(space).unwrap()
-            }
-        }
-
-        fn try_allocate_entry(&self, space: &mut Space<Entry, SIZE>) -> Option<u32> {
-            let freelist_head = space.freelist_head.load(Ordering::Acquire);
-            if freelist_head == Self::ENTRY_ALLOCATION_IS_FORBIDDEN_MARKER {
-                return None;
-            }
-            if self.try_allocate_entry_from_freelist(space, freelist_head) {
-                let index = freelist_head.index;
-                return Some(index);
-            }
-
-            None
-        }
-
-        fn allocate_entry_below(&self, space: &mut Space<Entry, SIZE>, threshold_index: u32) -> u32 {
-            let mut current_head = space.freelist_head.load(Ordering::Acquire);
-            loop {
-                if current_head == Self::ENTRY_ALLOCATION_IS_FORBIDDEN_MARKER {
-                    return 0; // Allocation is forbidden
-                }
-                if current_head.index >= threshold_index {
-                    return 0; // No free entries below the threshold
-                }
-
-                if self.try_allocate_entry_from_freelist(space, current_head) {
-                    return current_head.index;
-                } else {
-                    current_head = space.freelist_head.load(Ordering::Acquire);
-                }
-            }
-        }
-
-        fn try_allocate_entry_from_freelist(&self, space: &mut Space<Entry, SIZE>, freelist: FreelistHead) -> bool {
-            let next_free = freelist.next;
-
-            let new_head = FreelistHead {
-                index: next_free,
-                next: if next_free == u32::MAX {
-                    u32::MAX
-                } else {
-                    self.base.get(next_free as usize).freelist_index.load(Ordering::Acquire)
-                },
-            };
-
-            space.freelist_head.compare_exchange(
-                freelist,
-                new_head,
-                Ordering::AcqRel,
-                Ordering::Acquire,
-            ).is_ok()
-        }
-
-        fn try_extend(&self, space: &mut Space<Entry, SIZE>) -> Option<FreelistHead> {
-            let segment = self.base.allocate_segment()?;
-            let mut guard = space.mutex.lock().unwrap();
-            space.segments.insert(segment);
-            let new_freelist_head = self.initialize_freelist_for_segment(segment);
-            guard.unlock();
-
-            Some(new_freelist_head)
-        }
-
-        fn generic_sweep(&self, space: &mut Space<Entry, SIZE>) -> u32
-        where
-            Entry: IsMarked + Unmark,
-        {
-            let mut live_entries = 0;
-            self.iterate_entries_in(space, |index| {
-                let entry = self.base.get_mut(index as usize);
-                if entry.is_marked() {
-                    entry.unmark();
-                    live_entries += 1;
-                } else {
-                    // Free the entry by adding it to the freelist
-                    self.add_entry_to_freelist(space, index);
-                }
-            });
-            live_entries
-        }
-
-        fn generic_sweep_with_callback<Callback>(&self, space: &mut Space<Entry, SIZE>, mut marked: Callback) -> u32
-        where
-            Entry: IsMarked + Unmark,
-            Callback: FnMut(u32),
-        {
-            let mut live_entries = 0;
-            self.iterate_entries_in(space, |index| {
-                let entry = self.base.get_mut(index as usize);
-                if entry.is_marked() {
-                    entry.unmark();
-                    live_entries += 1;
-                    marked(index); // Invoke the callback for marked entries
-                } else {
-                    // Free the entry by adding it to the freelist
-                    self.add_entry_to_freelist(space, index);
-                }
-            });
-            live_entries
-        }
-
-        fn iterate_entries_in<Callback>(&self, space: &Space<Entry, SIZE>, mut callback: Callback)
-        where
-            Callback: FnMut(u32),
-        {
-            let guard = space.mutex.lock().unwrap();
-            for segment in &space.segments {
-                for i in 0..SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT {
-                    let index = segment.index * SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT + i;
-                    callback(index as u32);
-                }
-            }
-        }
-        const ENTRY_ALLOCATION_IS_FORBIDDEN_MARKER: FreelistHead = FreelistHead { index: u32::MAX, next: u32::MAX };
-
-        fn add_entry_to_freelist(&self, space: &mut Space<Entry, SIZE>, index: u32) {
-            let mut current_head = space.freelist_head.load(Ordering::Acquire);
-
-            loop {
-                // Update the freelist index of the entry to be freed to point to the current head.
-                self.base.get_mut(index as usize).freelist_index.store(current_head.index, Ordering::Release);
-
-                let new_head = FreelistHead {
-                    index,
-                    next: current_head.index,
-                };
-
-                match space.freelist_head.compare_exchange(
-                    current_head,
-                    new_head,
-                    Ordering::AcqRel,
-                    Ordering::Acquire,
-                ) {
-                    Ok(_) => break,
-                    Err(new_current_head) => current_head = new_current_head,
-                }
-            }
-        }
-
-        fn initialize_freelist_for_segment(&self, segment: Segment) -> FreelistHead {
-            let start_index = segment.index * SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT;
-            let end_index = start_index + SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT;
-
-            for i in start_index..end_index {
-                let mut next_index = (i + 1) as u32;
-                if i == end_index - 1 {
-                    next_index = u32::MAX;
-                }
-                self.base.get_mut(i as usize).freelist_index.store(next_index, Ordering::Release);
-            }
-            FreelistHead {
-                index: start_index as u32,
-                next: start_index as u32 + 1,
-            }
-        }
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::heap::heap_layout::Address;
+
+const KB: usize = 1024;
+
+/// Table memory is committed and released in segments of this size.
+pub const K_ENTITY_TABLE_SEGMENT_SIZE: usize = 64 * KB;
+
+/// Index of the null entry. It lives in segment zero, which is owned by
+/// the table itself and mapped read-only, so the null handle always loads
+/// a zero entry.
+pub const K_NULL_ENTRY_INDEX: u32 = 0;
+
+/// The entries of an external entity table. Entries are plain atomics so
+/// that zero-filled memory is a valid (null) entry and entries can be read
+/// and written concurrently.
+pub trait ExternalEntityTableEntry: Sync {
+    /// Turns this entry into a freelist entry linking to `next_entry_index`.
+    fn make_freelist_entry(&self, next_entry_index: u32);
+    /// Returns the link of a freelist entry.
+    fn get_next_freelist_entry_index(&self) -> u32;
+    fn is_freelist_entry(&self) -> bool;
+
+    fn set_mark_bit(&self);
+    fn clear_mark_bit(&self);
+    fn has_mark_bit_set(&self) -> bool;
+}
+
+/// Head of a space's freelist: the index of the first free entry and the
+/// length of the list, packed into one word so both are updated with a
+/// single compare-and-swap. The length also guards against most ABA races
+/// between concurrent allocations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FreelistHead {
+    next: u32,
+    length: u32,
+}
+
+impl FreelistHead {
+    pub const fn new(next: u32, length: u32) -> Self {
+        FreelistHead { next, length }
     }
 
-    impl<Entry, const SIZE: usize> Default for ExternalEntityTable<Entry, SIZE> {
-        fn default() -> Self {
-            Self::new()
-        }
+    pub const fn empty() -> Self {
+        Self::new(K_NULL_ENTRY_INDEX, 0)
     }
 
-    trait IsMarked {
-        fn is_marked(&self) -> bool;
+    pub fn next(self) -> u32 {
+        self.next
     }
 
-    trait Unmark {
-        fn unmark(&mut self);
+    pub fn length(self) -> u32 {
+        self.length
     }
 
-    impl IsMarked for u32 {
-        fn is_marked(&self) -> bool {
-            false
-        }
+    pub fn is_empty(self) -> bool {
+        self.length == 0
     }
 
-    impl Unmark for u32 {
-        fn unmark(&mut self) {}
-    }
-
-    /// A collection of segments in an external entity table.
-    ///
-    /// For the purpose of memory management, a table is partitioned into segments
-    /// of a fixed size (e.g. 64kb). A Space is a collection of segments that all
-    /// share the same freelist. As such, entry allocation and freeing (e.g.
-    /// through garbage collection) all happen on the level of spaces.
-    ///
-    /// Spaces allow implementing features such as:
-    /// * Young generation GC support (a separate space is used for all entries
-    ///   belonging to the young generation)
-    /// * Having double-width entries in a table (a dedicated space is used that
-    ///   contains only double-width entries)
-    /// * Sharing one table between multiple isolates that perform GC independently
-    ///   (each Isolate owns one space)
-    pub struct Space<Entry, const SIZE: usize> {
-        owning_table: AtomicU64,
-        freelist_head: AtomicFreelistHead,
-        segments: HashSet<Segment>,
-        is_internal_read_only_space_: AtomicBool,
-        mutex: Mutex<()>,
-        _phantom: PhantomData<Entry>,
-    }
-
-    impl<Entry, const SIZE: usize> Space<Entry, SIZE> {
-        pub fn new() -> Self {
-            Space {
-                owning_table: AtomicU64::new(0),
-                freelist_head: AtomicFreelistHead::new(FreelistHead { index: u32::MAX, next: u32::MAX }),
-                segments: HashSet::new(),
-                is_internal_read_only_space_: AtomicBool::new(false),
-                mutex: Mutex::new(()),
-                _phantom: PhantomData,
-            }
-        }
-
-        /// Determines the number of entries currently on the freelist.
-        /// As entries can be allocated from other threads, the freelist size may
-        /// have changed by the time this method returns. As such, the returned
-        /// value should only be treated as an approximation.
-        pub fn freelist_length(&self) -> u32 {
-            //TODO implement
-            0
-        }
-
-        /// Returns the current number of segments currently associated with this
-        /// space.
-        /// The caller must lock the mutex.
-        pub fn num_segments(&self) -> u32 {
-            self.segments.len() as u32
-        }
-
-        /// Returns whether this space is currently empty.
-        /// The caller must lock the mutex.
-        pub fn is_empty(&self) -> bool {
-            self.num_segments() == 0
-        }
-
-        /// Returns the current capacity of this space.
-        /// The capacity of a space is the total number of entries it can contain.
-        /// The caller must lock the mutex.
-        pub fn capacity(&self) -> u32 {
-            self.num_segments() * SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT as u32
-        }
-
-        /// Returns true if this space contains the entry with the given index.
-        pub fn contains(&self, index: u32) -> bool {
-            for segment in &self.segments {
-                let start_index = segment.index * SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT;
-                let end_index = start_index + SegmentedTable::<Entry, SIZE>::ENTRIES_PER_SEGMENT;
-                if (start_index as u32..end_index as u32).contains(&index) {
-                    return true;
-                }
-            }
-            false
-        }
-
-        /// Whether this space is attached to a table's internal read-only segment.
-        pub fn is_internal_read_only_space(&self) -> bool {
-            self.is_internal_read_only_space_.load(Ordering::Relaxed)
-        }
-
-        #[cfg(debug_assertions)]
-        /// Check whether this space belongs to the given external entity table.
-        pub fn belongs_to(&self, table: *const ExternalEntityTable<Entry, SIZE>) -> bool {
-            self.owning_table.load(Ordering::Relaxed) == table as u64
-        }
+    pub(crate) fn pack(self) -> u64 {
+        (self.length as u64) << 32 | self.next as u64
+    }
 
-        #[cfg(not(debug_assertions))]
-        pub fn belongs_to(&self, _table: *const ExternalEntityTable<Entry, SIZE>) -> bool {
-            true
+    pub(crate) fn unpack(word: u64) -> Self {
+        Self::new(word as u32, (word >> 32) as u32)
+    }
+}
+
+/// A group of segments sharing a freelist. Entries are allocated and
+/// garbage collected per space, so that for example short-lived and
+/// long-lived entries can live in different segments. A space must only
+/// ever be used with one table and returned to it by `tear_down_space`.
+pub struct Space {
+    pub(crate) freelist_head: AtomicU64,
+    /// Numbers of the segments owned by this space. The mutex also
+    /// serializes extending and sweeping the space.
+    pub(crate) segments: Mutex<BTreeSet<u32>>,
+    /// Whether new entries are allocated marked, which is the case while
+    /// the garbage collector is marking.
+    allocate_black: AtomicBool,
+}
+
+impl Space {
+    pub fn new() -> Self {
+        Space {
+            freelist_head: AtomicU64::new(FreelistHead::empty().pack()),
+            segments: Mutex::new(BTreeSet::new()),
+            allocate_black: AtomicBool::new(false),
         }
+    }
 
-        pub fn num_segments_for_testing(&self) -> u32 {
-            let _guard = self.mutex.lock().unwrap();
-            self.num_segments()
-        }
+    pub fn freelist_length(&self) -> u32 {
+        FreelistHead::unpack(self.freelist_head.load(Ordering::Relaxed)).length()
     }
 
-    impl<Entry, const SIZE: usize> Drop for Space<Entry, SIZE> {
-        fn drop(&mut self) {
-            // Ensure proper cleanup when the space is dropped.  Deallocate segments here if necessary.
-        }
+    pub fn num_segments(&self) -> usize {
+        self.segments.lock().unwrap().len()
     }
 
-    impl<Entry, const SIZE: usize> Default for Space<Entry, SIZE> {
-        fn default() -> Self {
-            Self::new()
-        }
+    pub fn allocate_black(&self) -> bool {
+        self.allocate_black.load(Ordering::Relaxed)
     }
 
-    /// A Space that supports black allocations.
-    pub struct SpaceWithBlackAllocationSupport<Entry, const SIZE: usize> {
-        space: Space<Entry, SIZE>,
-        allocate_black: AtomicBool,
+    pub fn set_allocate_black(&self, allocate_black: bool) {
+        self.allocate_black.store(allocate_black, Ordering::Relaxed);
     }
+}
 
-    impl<Entry, const SIZE: usize> SpaceWithBlackAllocationSupport<Entry, SIZE> {
-        pub fn new() -> Self {
-            SpaceWithBlackAllocationSupport {
-                space: Space::new(),
-                allocate_black: AtomicBool::new(false),
-            }
-        }
-        pub fn allocate_black(&self) -> bool {
-            self.allocate_black.load(Ordering::Relaxed)
-        }
-        pub fn set_allocate_black(&self, allocate_black: bool) {
-            self.allocate_black.store(allocate_black, Ordering::Relaxed);
-        }
+impl Default for Space {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Space {
+    fn drop(&mut self) {
+        debug_assert!(
+            std::thread::panicking() || self.segments.get_mut().unwrap().is_empty(),
+            "space dropped without tear_down_space"
+        );
+    }
+}
+
+struct SegmentAllocator {
+    /// Released segments, reused lowest first so that tables stay dense.
+    free: BTreeSet<u32>,
+    /// Segments from here on have never been used.
+    next_unused: u32,
+    committed: BTreeSet<u32>,
+}
+
+/// A thread-safe table with a fixed maximum size for storing references to
+/// objects located outside of the sandbox.
+///
+/// Objects inside the sandbox refer to outside objects only through
+/// handles, which are indices into the table shifted left by a
+/// table-specific amount. The table reserves enough virtual memory for
+/// every index a 32-bit handle can encode, so any handle, however it was
+/// corrupted, resolves to an entry within the reservation: either a
+/// committed entry, whose type tag is checked on load, or inaccessible
+/// memory, which faults.
+///
+/// Memory is committed in segments that are grouped into spaces; see
+/// `Space`. This class implements memory management and entry allocation.
+/// Reclaiming entries is up to the concrete tables, built on
+/// `generic_sweep` or on `CompactibleExternalEntityTable`.
+pub struct ExternalEntityTable<Entry, const RESERVATION_SIZE: usize> {
+    base: *mut Entry,
+    segment_allocator: Mutex<SegmentAllocator>,
+    _entries: PhantomData<Entry>,
+}
+
+// The table hands out shared references to entries, which are atomics.
+unsafe impl<Entry: Sync, const RESERVATION_SIZE: usize> Send for ExternalEntityTable<Entry, RESERVATION_SIZE> {}
+unsafe impl<Entry: Sync, const RESERVATION_SIZE: usize> Sync for ExternalEntityTable<Entry, RESERVATION_SIZE> {}
+
+impl<Entry: ExternalEntityTableEntry, const RESERVATION_SIZE: usize> ExternalEntityTable<Entry, RESERVATION_SIZE> {
+    pub const K_ENTRY_SIZE: usize = std::mem::size_of::<Entry>();
+    pub const K_ENTRIES_PER_SEGMENT: u32 = (K_ENTITY_TABLE_SEGMENT_SIZE / Self::K_ENTRY_SIZE) as u32;
+    pub const K_MAX_CAPACITY: usize = RESERVATION_SIZE / Self::K_ENTRY_SIZE;
+    pub const K_MAX_SEGMENTS: u32 = (RESERVATION_SIZE / K_ENTITY_TABLE_SEGMENT_SIZE) as u32;
+
+    const K_VALID_LAYOUT: () = assert!(
+        RESERVATION_SIZE.is_multiple_of(K_ENTITY_TABLE_SEGMENT_SIZE)
+            && K_ENTITY_TABLE_SEGMENT_SIZE.is_multiple_of(Self::K_ENTRY_SIZE)
+            && Self::K_ENTRY_SIZE.is_power_of_two()
+    );
+
+    /// Reserves the backing memory and maps the null segment.
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::K_VALID_LAYOUT;
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                RESERVATION_SIZE,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        assert!(base != libc::MAP_FAILED, "failed to reserve external entity table");
+        let table = ExternalEntityTable {
+            base: base.cast(),
+            segment_allocator: Mutex::new(SegmentAllocator {
+                free: BTreeSet::new(),
+                next_unused: 1,
+                committed: BTreeSet::from([0]),
+            }),
+            _entries: PhantomData,
+        };
+        table.set_segment_permissions(0, libc::PROT_READ);
+        table
     }
 
-    impl<Entry, const SIZE: usize> std::ops::Deref for SpaceWithBlackAllocationSupport<Entry, SIZE> {
-        type Target = Space<Entry, SIZE>;
+    /// Returns the address of the first entry.
+    pub fn base(&self) -> Address {
+        self.base as Address
+    }
 
-        fn deref(&self) -> &Self::Target {
-            &self.space
-        }
+    /// Returns true if `address` lies within the table's reservation.
+    pub fn reservation_contains(&self, address: Address) -> bool {
+        address.wrapping_sub(self.base()) < RESERVATION_SIZE
     }
 
-    impl<Entry, const SIZE: usize> std::ops::DerefMut for SpaceWithBlackAllocationSupport<Entry, SIZE> {
-        fn deref_mut(&mut self) -> &mut Self::Target {
-            &mut self.space
-        }
+    /// Returns true if the entry at `index` is backed by memory. Accessing
+    /// any other entry of the reservation faults.
+    pub fn is_committed(&self, index: u32) -> bool {
+        let segment = index / Self::K_ENTRIES_PER_SEGMENT;
+        self.segment_allocator.lock().unwrap().committed.contains(&segment)
     }
 
-    impl<Entry, const SIZE: usize> Default for SpaceWithBlackAllocationSupport<Entry, SIZE> {
-        fn default() -> Self {
-            Self::new()
-        }
+    pub fn first_entry_of(segment: u32) -> u32 {
+        segment * Self::K_ENTRIES_PER_SEGMENT
     }
 
-    #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
-    struct Segment {
-        index: usize,
+    pub fn last_entry_of(segment: u32) -> u32 {
+        Self::first_entry_of(segment) + Self::K_ENTRIES_PER_SEGMENT - 1
     }
 
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct FreelistHead {
-        index: u32,
-        next: u32,
+    /// Returns all segments of `space` to the table.
+    pub fn tear_down_space(&self, space: &Space) {
+        let mut segments = space.segments.lock().unwrap();
+        for segment in std::mem::take(&mut *segments) {
+            self.free_segment(segment);
+        }
+        space.freelist_head.store(FreelistHead::empty().pack(), Ordering::Release);
     }
 
-    impl FreelistHead {
-        const fn new(index: u32, next: u32) -> Self {
-            FreelistHead { index, next }
+    /// Allocates a free entry in `space`, growing the space if its freelist
+    /// is empty. Panics if the table is exhausted.
+    pub fn allocate_entry(&self, space: &Space) -> u32 {
+        if let Some(index) = self.try_allocate_entry(space) {
+            return index;
         }
+        self.extend(space)
     }
 
-    struct AtomicFreelistHead {
-        inner: AtomicU64,
+    /// Adds a segment to `space` and allocates the first of its entries,
+    /// unless another thread refilled the freelist in the meantime.
+    pub(crate) fn extend(&self, space: &Space) -> u32 {
+        let mut segments = space.segments.lock().unwrap();
+        if let Some(index) = self.try_allocate_entry(space) {
+            return index;
+        }
+        let segment = self.allocate_segment();
+        segments.insert(segment);
+        let first = Self::first_entry_of(segment);
+        let last = Self::last_entry_of(segment);
+        for index in first + 1..last {
+            self.at(index).make_freelist_entry(index + 1);
+        }
+        self.at(last).make_freelist_entry(K_NULL_ENTRY_INDEX);
+        // Only sweeping frees entries and it holds the mutex, so the
+        // freelist is still empty.
+        let freelist = FreelistHead::new(first + 1, Self::K_ENTRIES_PER_SEGMENT - 1);
+        space.freelist_head.store(freelist.pack(), Ordering::Release);
+        first
     }
 
-    impl AtomicFreelistHead {
-        const fn new(head: FreelistHead) -> Self {
-            let combined = ((head.index as u64) << 32) | (head.next as u64);
-            AtomicFreelistHead {
-                inner: AtomicU64::new(combined),
-            }
-        }
+    /// Rebuilds the freelist of `space` from all unmarked entries and
+    /// clears the mark bits of the others. Segments without live entries
+    /// are released. Must not run concurrently with allocation in `space`.
+    /// Returns the number of live entries.
+    pub fn generic_sweep(&self, space: &Space) -> u32 {
+        let mut segments = space.segments.lock().unwrap();
+        self.sweep_segments(space, &mut segments, |_, _| false)
+    }
 
-        fn load(&self, order: Ordering) -> FreelistHead {
-            let combined = self.inner.load(order);
-            FreelistHead {
-                index: (combined >> 32) as u32,
-                next: (combined & 0xFFFFFFFF) as u32,
+    /// Sweeps the segments of a locked space top-down, so that the freelist
+    /// hands out the lowest entries first. `resolve` is called for every
+    /// unmarked entry that is not on the freelist and returns whether the
+    /// entry was turned into a live one.
+    pub(crate) fn sweep_segments(
+        &self,
+        space: &Space,
+        segments: &mut MutexGuard<'_, BTreeSet<u32>>,
+        mut resolve: impl FnMut(u32, &Entry) -> bool,
+    ) -> u32 {
+        let mut freelist = FreelistHead::empty();
+        let mut num_live_entries = 0;
+        let mut empty_segments = Vec::new();
+        for &segment in segments.iter().rev() {
+            let previous_freelist = freelist;
+            let mut segment_is_empty = true;
+            for index in (Self::first_entry_of(segment)..=Self::last_entry_of(segment)).rev() {
+                let entry = self.at(index);
+                if entry.has_mark_bit_set() {
+                    entry.clear_mark_bit();
+                } else if entry.is_freelist_entry() || !resolve(index, entry) {
+                    entry.make_freelist_entry(freelist.next());
+                    freelist = FreelistHead::new(index, freelist.length() + 1);
+                    continue;
+                }
+                segment_is_empty = false;
+                num_live_entries += 1;
+            }
+            if segment_is_empty {
+                freelist = previous_freelist;
+                empty_segments.push(segment);
             }
         }
-
-        fn store(&self, head: FreelistHead, order: Ordering) {
-            let combined = ((head.index as u64) << 32) | (head.next as u64);
-            self.inner.store(combined, order);
+        for segment in empty_segments {
+            segments.remove(&segment);
+            self.free_segment(segment);
         }
+        space.freelist_head.store(freelist.pack(), Ordering::Release);
+        num_live_entries
+    }
 
-        fn compare_exchange(
-            &self,
-            current: FreelistHead,
-            new: FreelistHead,
-            success: Ordering,
-            failure: Ordering,
-        ) -> Result<FreelistHead, FreelistHead> {
-            let current_combined = ((current.index as u64) << 32) | (current.next as u64);
-            let new_combined = ((new.index as u64) << 32) | (new.next as u64);
-
-            match self.inner.compare_exchange(current_combined, new_combined, success, failure) {
-                Ok(_) => Ok(new),
-                Err(actual_combined) => {
-                    Err(FreelistHead {
-                        index: (actual_combined >> 32) as u32,
-                        next: (actual_combined & 0xFFFFFFFF) as u32,
-                    })
-                }
+    pub(crate) fn allocate_segment(&self) -> u32 {
+        let mut allocator = self.segment_allocator.lock().unwrap();
+        let segment = match allocator.free.pop_first() {
+            Some(segment) => segment,
+            None => {
+                assert!(allocator.next_unused < Self::K_MAX_SEGMENTS, "external entity table exhausted");
+                allocator.next_unused += 1;
+                allocator.next_unused - 1
             }
-        }
+        };
+        allocator.committed.insert(segment);
+        self.set_segment_permissions(segment, libc::PROT_READ | libc::PROT_WRITE);
+        segment
     }
 
-    ///A generic segmented table
-    struct SegmentedTable<Entry, const SIZE: usize> {
-        segments: Arc<RwLock<Vec<Segment>>>,
-        data: Vec<UnsafeCell<Entry>>,
+    pub(crate) fn free_segment(&self, segment: u32) {
+        debug_assert_ne!(segment, 0, "the null segment is owned by the table");
+        let mut allocator = self.segment_allocator.lock().unwrap();
+        let address = self.segment_address(segment);
+        // Dropping the pages zeroes them when they are committed again.
+        unsafe { libc::madvise(address, K_ENTITY_TABLE_SEGMENT_SIZE, libc::MADV_DONTNEED) };
+        self.set_segment_permissions(segment, libc::PROT_NONE);
+        allocator.committed.remove(&segment);
+        allocator.free.insert(segment);
     }
 
-    unsafe impl<Entry, const SIZE: usize> Sync for SegmentedTable<Entry, SIZE> {}
-
-    impl<Entry, const SIZE: usize> SegmentedTable<Entry, SIZE> {
-        const SEGMENT_SIZE: usize = 65536; // 64KB
-        const ENTRY_SIZE: usize = mem::size_of::<Entry>();
-        const ENTRIES_PER_SEGMENT: usize = Self::SEGMENT_SIZE / Self::ENTRY_SIZE;
-
-        fn new() -> Self {
-            SegmentedTable {
-                segments: Arc::new(RwLock::new(Vec::new())),
-                data: Vec::new(),
-            }
-        }
+    fn segment_address(&self, segment: u32) -> *mut libc::c_void {
+        (self.base() + segment as usize * K_ENTITY_TABLE_SEGMENT_SIZE) as *mut libc::c_void
+    }
 
-        fn initialize(&mut self) {
-            let initial_segment = self.allocate_segment().expect("Failed to allocate initial segment");
-            self.segments.write().unwrap().push(initial_segment);
-        }
+    fn set_segment_permissions(&self, segment: u32, permissions: libc::c_int) {
+        let result = unsafe { libc::mprotect(self.segment_address(segment), K_ENTITY_TABLE_SEGMENT_SIZE, permissions) };
+        assert_eq!(result, 0, "failed to change external entity table permissions");
+    }
+}
 
-        fn tear_down(&mut self) {
-            self.segments.write().unwrap().clear();
-            self.data.clear();
-        }
+impl<Entry: ExternalEntityTableEntry, const RESERVATION_SIZE: usize> Default for ExternalEntityTable<Entry, RESERVATION_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        fn allocate_segment(&self) -> Option<Segment> {
-            let mut segments = self.segments.write().unwrap();
-            let new_index = segments.len();
+impl<Entry, const RESERVATION_SIZE: usize> Drop for ExternalEntityTable<Entry, RESERVATION_SIZE> {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base.cast(), RESERVATION_SIZE) };
+    }
+}
 
-            //Calculate number of needed new entries
-            let num_new_entries = Self::ENTRIES_PER_SEGMENT;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::AtomicU64;
 
-            //Extend the vector with default values
-            let mut new_data: Vec<UnsafeCell<Entry>> = Vec::with_capacity(self.data.len() + num_new_entries);
-            for _ in 0..self.data.len() {
-                //SAFETY: Here we assume that every value inside data is valid
-                unsafe {
-                    new_data.push(UnsafeCell::new(std::ptr::read(self.data.last().unwrap().get())));
-                }
-            }
-            //Extend the vector with default values
-            for _ in 0..num_new_entries {
-                new_data.push(UnsafeCell::new(unsafe { std::mem::zeroed() }));
-            }
+    #[derive(Default)]
+    struct TestEntry(AtomicU64);
 
-            //Update data vector
-            self.data = new_data;
+    const K_FREE: u64 = 1 << 62;
+    const K_MARK: u64 = 1 << 63;
 
-            segments.push(Segment { index: new_index });
-            Some(Segment { index: new_index })
+    impl ExternalEntityTableEntry for TestEntry {
+        fn make_freelist_entry(&self, next_entry_index: u32) {
+            self.0.store(K_FREE | next_entry_index as u64, Ordering::Relaxed);
         }
-
-        fn deallocate_segment(&self, segment: Segment) {
-            let mut segments = self.segments.write().unwrap();
-            segments.retain(|s| s != &segment);
+        fn get_next_freelist_entry_index(&self) -> u32 {
+            self.0.load(Ordering::Relaxed) as u32
         }
-
-        fn get(&self, index: usize) -> &Entry {
-            assert!(index < self.data.len(), "Index out of bounds");
-            // SAFETY: We ensure that the index is within the bounds of the vector.
-            unsafe { &*self.data[index].get() }
+        fn is_freelist_entry(&self) -> bool {
+            self.0.load(Ordering::Relaxed) & K_FREE != 0
         }
-
-        fn get_mut(&self, index: usize) -> &mut Entry {
-            assert!(index < self.data.len(), "Index out of bounds");
-            // SAFETY: We ensure that the index is within the bounds of the vector,
-            // and we only hand out one mutable reference at a time.
-            unsafe { &mut *self.data[index].get() }
+        fn set_mark_bit(&self) {
+            self.0.fetch_or(K_MARK, Ordering::Relaxed);
         }
-
-        fn unseal_read_only_segment(&mut self) {
-            // Placeholder for unsealing logic (e.g., changing memory permissions).
-            // This might involve platform-specific code to modify memory protection.
-            // This operation is inherently unsafe and requires careful handling.
+        fn clear_mark_bit(&self) {
+            self.0.fetch_and(!K_MARK, Ordering::Relaxed);
         }
+        fn has_mark_bit_set(&self) -> bool {
+            self.0.load(Ordering::Relaxed) & K_MARK != 0
+        }
+    }
 
-        fn seal_read_only_segment(&mut self) {
-            // Placeholder for sealing logic (e.g., restoring memory permissions).
-            // Similar to unseal_read_only_segment, this might involve platform-specific code.
-            // This operation is inherently unsafe and requires careful handling.
+    type TestTable = ExternalEntityTable<TestEntry, { 64 * K_ENTITY_TABLE_SEGMENT_SIZE }>;
+
+    #[test]
+    fn concurrent_allocation_and_sweeping() {
+        let table = TestTable::new();
+        let space = Space::new();
+        let per_segment = TestTable::K_ENTRIES_PER_SEGMENT as usize;
+        let indices: Vec<u32> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..per_segment)
+                            .map(|i| {
+                                let index = table.allocate_entry(&space);
+                                table.at(index).0.store(i as u64, Ordering::Relaxed);
+                                index
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            threads.into_iter().flat_map(|thread| thread.join().unwrap()).collect()
+        });
+        assert_eq!(indices.iter().collect::<HashSet<_>>().len(), 4 * per_segment);
+        assert!(indices.iter().all(|&index| index >= per_segment as u32 && table.is_committed(index)));
+        assert_eq!(space.num_segments(), 4);
+
+        // Keep the entries of the lowest segment; the others are released.
+        for &index in &indices {
+            if (index as usize) < 2 * per_segment {
+                table.at(index).set_mark_bit();
+            }
         }
+        assert_eq!(table.generic_sweep(&space), per_segment as u32);
+        assert_eq!(space.num_segments(), 1);
+        assert!(!table.is_committed(4 * per_segment as u32));
+        assert_eq!(space.freelist_length(), 0);
+        // Freed segments are reused and come back zeroed.
+        let index = table.allocate_entry(&space);
+        assert_eq!(index, 2 * per_segment as u32);
+        assert_eq!(table.at(index).0.load(Ordering::Relaxed), 0);
+        table.tear_down_space(&space);
     }
-}
\ No newline at end of file
+}
@@ -2,536 +2,101 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
-#![allow(non_snake_case)]
+use std::sync::atomic::Ordering;
 
-//use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
-use std::ptr::NonNull;
+use crate::heap::heap_layout::Address;
+use crate::sandbox::external_entity_table::ExternalEntityTableEntry;
+use crate::sandbox::js_dispatch_table::{JSDispatchEntry, JSDispatchHandle, JSDispatchTable, JSDispatchTableSpace};
 
-//use crate::builtins::builtins_inl::*; // Assuming builtins_inl.h is in builtins module
-//use crate::common::code_memory_access_inl::*; // Assuming code_memory_access_inl.h is in common module
-//use crate::objects::objects_inl::*; // Assuming objects_inl.h is in objects module
-//use crate::sandbox::external_entity_table_inl::*; // Assuming external_entity_table_inl.h is in sandbox module
-use crate::sandbox::js_dispatch_table::*; // Assuming js_dispatch_table.h is in sandbox module
-//use crate::snapshot::embedded::embedded_data::*; // Assuming embedded_data.h is in snapshot::embedded module
-
-const V8_ENABLE_LEAPTIERING: bool = true;
-
-pub mod internal {
-    use super::*;
-
-    const kHeapObjectTag: u64 = 1; // Example value
-    const kObjectPointerShift: u64 = 1; // Example value
-    const kMarkingBit: u64 = 2; // Example value
-    const kParameterCountMask: u64 = 0xFFFF; // Example value
-
-    #[derive(Debug)]
-    pub struct JSDispatchEntry {
-        encoded_word_: AtomicU64,
-        entrypoint_: AtomicU64, // Address
-        #[cfg(target_arch = "x86")]
-        parameter_count_: AtomicU32,
-        #[cfg(target_arch = "x86")]
-        next_free_entry_: AtomicU32,
-    }
-
-    impl JSDispatchEntry {
-        pub fn MakeJSDispatchEntry(
-            &self,
-            object: u64,
-            entrypoint: u64,
-            parameter_count: u16,
-            mark_as_alive: bool,
-        ) {
-            assert_eq!(object & kHeapObjectTag, 0);
-            assert_eq!((object << kObjectPointerShift) >> kObjectPointerShift, object);
-
-            let mut payload =
-                (object << kObjectPointerShift) | (parameter_count as u64 & kParameterCountMask);
-            assert_eq!(payload & kMarkingBit, 0);
-            if mark_as_alive {
-                payload |= kMarkingBit;
-            }
-            #[cfg(target_arch = "x86")]
-            {
-                self.parameter_count_
-                    .store(parameter_count as u32, Ordering::Relaxed);
-                self.next_free_entry_.store(0, Ordering::Relaxed);
-            }
-            self.encoded_word_.store(payload, Ordering::Relaxed);
-            self.entrypoint_.store(entrypoint, Ordering::Relaxed);
-            assert!(!self.IsFreelistEntry());
-        }
-
-        pub fn GetEntrypoint(&self) -> u64 {
-            assert!(!self.IsFreelistEntry());
-            self.entrypoint_.load(Ordering::Relaxed)
-        }
-
-        pub fn GetCodePointer(&self) -> u64 {
-            assert!(!self.IsFreelistEntry());
-            // The pointer tag bit (LSB) of the object pointer is used as marking bit,
-            // and so may be 0 or 1 here. As the return value is a tagged pointer, the
-            // bit must be 1 when returned, so we need to set it here.
-            let payload = self.encoded_word_.load(Ordering::Relaxed);
-            (payload >> kObjectPointerShift) | kHeapObjectTag
-        }
-
-        // This requires Tagged<Code> and Cast<Code> which depends on V8 internal
-        // structures and thus cannot be directly translated.  A possible approach
-        // is to use a raw pointer and cast it, but that's unsafe and needs careful
-        // handling.
-        //pub fn GetCode(&self) -> Tagged<Code> {
-        //    Cast::<Code>(Tagged::<Object>::new(self.GetCodePointer()))
-        //}
-
-        pub fn GetParameterCount(&self) -> u16 {
-            // Loading a pointer out of a freed entry will always result in an invalid
-            // pointer (e.g. upper bits set or nullptr). However, here we're just loading
-            // an integer (the parameter count), so we probably want to make sure that
-            // we're not getting that from a freed entry.
-            assert!(!self.IsFreelistEntry());
-            #[cfg(target_arch = "x86")]
-            {
-                self.parameter_count_.load(Ordering::Relaxed) as u16
-            }
-            #[cfg(not(target_arch = "x86"))]
-            {
-                assert_ne!(kParameterCountMask, 0);
-                let payload = self.encoded_word_.load(Ordering::Relaxed);
-                (payload & kParameterCountMask) as u16
-            }
-        }
-
-        pub fn SetCodeAndEntrypointPointer(&self, new_object: u64, new_entrypoint: u64) {
-            let old_payload = self.encoded_word_.load(Ordering::Relaxed);
-            let marking_bit = old_payload & kMarkingBit;
-            let parameter_count = old_payload & kParameterCountMask;
-            // We want to preserve the marking bit of the entry. Since that happens to
-            // be the tag bit of the pointer, we need to explicitly clear it here.
-            let object = (new_object << kObjectPointerShift) & !kMarkingBit;
-            let new_payload = object | marking_bit | parameter_count;
-            self.encoded_word_.store(new_payload, Ordering::Relaxed);
-            self.entrypoint_.store(new_entrypoint, Ordering::Relaxed);
-            assert!(!self.IsFreelistEntry());
-        }
-
-        pub fn SetEntrypointPointer(&self, new_entrypoint: u64) {
-            self.entrypoint_.store(new_entrypoint, Ordering::Relaxed);
-        }
-
-        pub fn MakeFreelistEntry(&self, next_entry_index: u32) {
-            #[cfg(target_arch = "x86_64")]
-            {
-                let payload = kFreeEntryTag | next_entry_index as u64;
-                self.entrypoint_.store(payload, Ordering::Relaxed);
-            }
-            #[cfg(not(target_arch = "x86_64"))]
-            {
-                // Store index + 1 such that we can use 0 for non-free entries.
-                self.next_free_entry_
-                    .store(next_entry_index + 1, Ordering::Relaxed);
-                self.entrypoint_.store(kNullAddress, Ordering::Relaxed);
-            }
-            self.encoded_word_.store(kNullAddress, Ordering::Relaxed);
-            assert!(self.IsFreelistEntry());
-        }
-
-        pub fn IsFreelistEntry(&self) -> bool {
-            #[cfg(target_arch = "x86_64")]
-            {
-                let entrypoint = self.entrypoint_.load(Ordering::Relaxed);
-                (entrypoint & kFreeEntryTag) == kFreeEntryTag
-            }
-            #[cfg(not(target_arch = "x86_64"))]
-            {
-                self.next_free_entry_.load(Ordering::Relaxed) != 0
-            }
-        }
-
-        pub fn GetNextFreelistEntryIndex(&self) -> u32 {
-            assert!(self.IsFreelistEntry());
-            #[cfg(target_arch = "x86_64")]
-            {
-                self.entrypoint_.load(Ordering::Relaxed) as u32
-            }
-            #[cfg(not(target_arch = "x86_64"))]
-            {
-                self.next_free_entry_.load(Ordering::Relaxed) - 1
-            }
-        }
-
-        pub fn Mark(&self) {
-            let old_value = self.encoded_word_.load(Ordering::Relaxed);
-            let new_value = old_value | kMarkingBit;
-            // We don't need this cas to succeed. If marking races with
-            // `SetCodeAndEntrypointPointer`, then we are bound to re-set the mark bit in
-            // the write barrier.
-            assert!(JSDispatchTable::kWriteBarrierSetsEntryMarkBit);
-            self.encoded_word_
-                .compare_exchange(old_value, new_value, Ordering::Relaxed, Ordering::Relaxed)
-                .ok(); //Discard the result, do not need the value
-        }
-
-        pub fn Unmark(&self) {
-            let value = self.encoded_word_.load(Ordering::Relaxed);
-            let value = value & !kMarkingBit;
-            self.encoded_word_.store(value, Ordering::Relaxed);
-        }
-
-        pub fn IsMarked(&self) -> bool {
-            let value = self.encoded_word_.load(Ordering::Relaxed);
-            value & kMarkingBit
-        }
-    }
-
-    const kFreeEntryTag: u64 = 0x8000000000000000; // Example tag
-    const kNullAddress: u64 = 0; // Example null address.
-
-    impl Default for JSDispatchEntry {
-        fn default() -> Self {
-            JSDispatchEntry {
-                encoded_word_: AtomicU64::new(0),
-                entrypoint_: AtomicU64::new(0),
-                #[cfg(target_arch = "x86")]
-                parameter_count_: AtomicU32::new(0),
-                #[cfg(target_arch = "x86")]
-                next_free_entry_: AtomicU32::new(0),
-            }
-        }
-    }
-    //Dummy implementation for types which are not provided
-    pub struct Space{}
-    impl Space{
-        pub fn BelongsTo(&self, _table: &JSDispatchTable) -> bool{
-            true
-        }
-        pub fn Contains(&self, _index: u32) -> bool{
-            true
-        }
-        pub fn allocate_black(&self) -> bool{
-            true
-        }
-    }
-
-    pub struct Counters{}
-    impl Counters{
-        pub fn js_dispatch_table_entries_count(&self) -> JSDTEntriesCounter{
-            JSDTEntriesCounter{}
-        }
-    }
-
-    pub struct JSDTEntriesCounter{}
-    impl JSDTEntriesCounter{
-        pub fn AddSample(&self, _value: u32){}
-    }
-
-    //Dummy Implementation for CFIMetadataWriteScope
-    pub struct CFIMetadataWriteScope(&'static str);
-    impl CFIMetadataWriteScope{
-        pub fn new(_name: &'static str) -> Self{
-            CFIMetadataWriteScope("")
-        }
-    }
-    impl Drop for CFIMetadataWriteScope{
-        fn drop(&mut self){
-            //println!("Exiting scope {}", self.0);
-        }
-    }
-
-    //Dummy implementation for heap layout
-    pub mod HeapLayout{
-        pub fn InYoungGeneration<T>(_obj: T) -> bool{
-            false
+impl JSDispatchEntry {
+    #[inline]
+    pub fn make_js_dispatch_entry(&self, code: Address, entrypoint: Address, parameter_count: u16, mark_as_alive: bool) {
+        let mut word = Self::encode_code(code) | parameter_count as u64;
+        if mark_as_alive {
+            word |= Self::K_MARKING_BIT;
         }
+        self.entrypoint.store(entrypoint as u64, Ordering::Relaxed);
+        self.encoded_word.store(word, Ordering::Relaxed);
     }
 
-    impl JSDispatchTable{
-        pub fn GetCode(&self, handle: JSDispatchHandle) -> u64{
-            let index = self.HandleToIndex(handle);
-            self.at(index).GetCodePointer()
-        }
-
-        pub fn SetCodeNoWriteBarrier(&self, handle: JSDispatchHandle, new_code: u64){
-            let new_entrypoint = new_code; //Assuming entrypoint is same as code address
-            self.SetCodeAndEntrypointNoWriteBarrier(handle, new_code, new_entrypoint);
-        }
-
-        pub fn SetCodeKeepTieringRequestNoWriteBarrier(
-            &self,
-            handle: JSDispatchHandle,
-            new_code: u64,
-        ) {
-            if self.IsTieringRequested(handle) {
-                self.SetCodeAndEntrypointNoWriteBarrier(handle, new_code, self.GetEntrypoint(handle));
-            } else {
-                let new_entrypoint = new_code; // Assuming entrypoint is same as code address
-                self.SetCodeAndEntrypointNoWriteBarrier(handle, new_code, new_entrypoint);
-            }
-        }
-
-        pub fn SetCodeAndEntrypointNoWriteBarrier(
-            &self,
-            handle: JSDispatchHandle,
-            new_code: u64,
-            new_entrypoint: u64,
-        ) {
-            assert!(self.IsCompatibleCode(new_code as i32, self.GetParameterCount(handle) as i32));
-
-            // The object should be in old space to avoid creating old-to-new references.
-            assert!(!HeapLayout::InYoungGeneration(new_code));
-
-            let index = self.HandleToIndex(handle);
-            assert!(index >= Self::kEndOfInternalReadOnlySegment);
-            let _write_scope = CFIMetadataWriteScope::new("JSDispatchTable update");
-            self.at(index)
-                .SetCodeAndEntrypointPointer(new_code, new_entrypoint);
-        }
-
-        pub fn SetTieringRequest(&self, handle: JSDispatchHandle, builtin: TieringBuiltin, isolate: &Isolate) {
-            assert!(self.IsValidTieringBuiltin(builtin));
-            let index = self.HandleToIndex(handle);
-            assert!(index >= Self::kEndOfInternalReadOnlySegment);
-            let _write_scope = CFIMetadataWriteScope::new("JSDispatchTable update");
-            let entrypoint = isolate.builtin_entry_table[builtin as usize]; //assuming enum values can be indices
-            self.at(index).SetEntrypointPointer(entrypoint);
-        }
-
-        pub fn IsTieringRequested(&self, handle: JSDispatchHandle) -> bool {
-            let index = self.HandleToIndex(handle);
-            assert!(index >= Self::kEndOfInternalReadOnlySegment);
-            let entrypoint = self.at(index).GetEntrypoint();
-            let code_entrypoint = self.GetCode(handle); // Assuming entrypoint is same as code address
-
-            code_entrypoint != entrypoint
-        }
-
-        pub fn IsTieringRequested2(&self, handle: JSDispatchHandle, builtin: TieringBuiltin, isolate: &Isolate) -> bool {
-            let index = self.HandleToIndex(handle);
-            assert!(index >= Self::kEndOfInternalReadOnlySegment);
-            let entrypoint = self.at(index).GetEntrypoint();
-            let code_entrypoint = self.GetCode(handle); // Assuming entrypoint is same as code address
-
-            if entrypoint == code_entrypoint {
-                return false;
-            }
-            let instruction_start = isolate.embedded_data.InstructionStartOf(builtin as usize); //Assuming builtin is the index
-            entrypoint == instruction_start
-        }
-
-        pub fn ResetTieringRequest(&self, handle: JSDispatchHandle) {
-            let index = self.HandleToIndex(handle);
-            assert!(index >= Self::kEndOfInternalReadOnlySegment);
-            let _write_scope = CFIMetadataWriteScope::new("JSDispatchTable update");
-            let entrypoint = self.GetCode(handle); // Assuming entrypoint is same as code address
-            self.at(index).SetEntrypointPointer(entrypoint);
-        }
-
-        pub fn AllocateAndInitializeEntry(
-            &self,
-            space: &Space,
-            parameter_count: u16,
-            new_code: u64,
-        ) -> JSDispatchHandle {
-            if let Some(res) = self.TryAllocateAndInitializeEntry(space, parameter_count, new_code) {
-                res
-            } else {
-                panic!("JSDispatchTable::AllocateAndInitializeEntry");
-                //V8::FatalProcessOutOfMemory(nullptr,
-                //    "JSDispatchTable::AllocateAndInitializeEntry");
-            }
-        }
-
-        pub fn TryAllocateAndInitializeEntry(
-            &self,
-            space: &Space,
-            parameter_count: u16,
-            new_code: u64,
-        ) -> Option<JSDispatchHandle> {
-            assert!(space.BelongsTo(self));
-            assert!(self.IsCompatibleCode(new_code as i32, parameter_count as i32));
-
-            let index = self.TryAllocateEntry(space)?;
-            let entry = self.at(index);
-            let _write_scope = CFIMetadataWriteScope::new("JSDispatchTable initialize");
-            entry.MakeJSDispatchEntry(
-                new_code, //object
-                new_code, //entrypoint - assuming code address is entrypoint
-                parameter_count,
-                space.allocate_black(), //mark as alive
-            );
-            Some(self.IndexToHandle(index))
-        }
-
-        pub fn GetEntrypoint(&self, handle: JSDispatchHandle) -> u64 {
-            let index = self.HandleToIndex(handle);
-            self.at(index).GetEntrypoint()
-        }
-
-        pub fn GetCodeAddress(&self, handle: JSDispatchHandle) -> u64 {
-            let index = self.HandleToIndex(handle);
-            let ptr = self.at(index).GetCodePointer();
-            assert!(Internals::HasHeapObjectTag(ptr));
-            ptr
-        }
-
-        pub fn GetParameterCount(&self, handle: JSDispatchHandle) -> u16 {
-            let index = self.HandleToIndex(handle);
-            self.at(index).GetParameterCount()
-        }
-
-        pub fn Mark(&self, handle: JSDispatchHandle) {
-            let index = self.HandleToIndex(handle);
-
-            // The read-only space is immortal and cannot be written to.
-            if index < Self::kEndOfInternalReadOnlySegment {
-                return;
-            }
-
-            let _write_scope = CFIMetadataWriteScope::new("JSDispatchTable write");
-            self.at(index).Mark();
-        }
-
-        #[cfg(any(debug_assertions, feature = "verify_heap"))]
-        pub fn VerifyEntry(&self, handle: JSDispatchHandle, space: &Space, ro_space: &Space) {
-            assert!(space.BelongsTo(self));
-            assert!(ro_space.BelongsTo(self));
-            if handle == Self::kNullJSDispatchHandle {
-                return;
-            }
-            let index = self.HandleToIndex(handle);
-            if ro_space.Contains(index) {
-                assert!(self.at(index).IsMarked());
-            } else {
-                assert!(space.Contains(index));
-            }
-        }
-
-        pub fn IterateActiveEntriesIn<Callback>(&self, space: &Space, mut callback: Callback)
-        where
-            Callback: FnMut(JSDispatchHandle),
-        {
-            self.IterateEntriesIn(space, |index| {
-                if !self.at(index).IsFreelistEntry() {
-                    callback(self.IndexToHandle(index));
-                }
-            });
-        }
-
-        pub fn IterateMarkedEntriesIn<Callback>(&self, space: &Space, mut callback: Callback)
-        where
-            Callback: FnMut(JSDispatchHandle),
-        {
-            self.IterateEntriesIn(space, |index| {
-                if self.at(index).IsMarked() {
-                    callback(self.IndexToHandle(index));
-                }
-            });
-        }
-
-        pub fn Sweep<Callback>(
-            &self,
-            space: &Space,
-            counters: &Counters,
-            callback: Callback,
-        ) -> u32
-        where
-            Callback: Fn(u32) -> bool,
-        {
-            let num_live_entries = self.GenericSweep(space, callback);
-            counters
-                .js_dispatch_table_entries_count()
-                .AddSample(num_live_entries);
-            num_live_entries
-        }
-
-        // static
-        pub fn IsCompatibleCode(_code: i32, _parameter_count: i32) -> bool {
-            //TODO
-            true
-        }
-
+    #[inline]
+    pub fn get_entrypoint(&self) -> Address {
+        self.entrypoint.load(Ordering::Relaxed) as Address
     }
 
-    impl JSDispatchTable{
-        const kWriteBarrierSetsEntryMarkBit: bool = true;
+    /// Loads the code object. Loading a free entry yields an invalid
+    /// pointer, as bits of its free entry tag remain set.
+    #[inline]
+    pub fn get_code(&self) -> Address {
+        let word = self.encoded_word.load(Ordering::Relaxed) | Self::K_MARKING_BIT;
+        (word >> Self::K_OBJECT_POINTER_SHIFT) as Address
     }
 
-    // Dummy enums and structs for types that are not provided
-    #[derive(Debug, Clone, Copy)]
-    pub enum TieringBuiltin {
-        Illegal = 0,
-        CompileLazy,
-        InterpreterEntryTrampoline,
-        InstantiateAsmJs,
-        DebugBreakTrampoline,
-        #[cfg(V8_ENABLE_WEBASSEMBLY)]
-        JSToWasmWrapper,
-        #[cfg(V8_ENABLE_WEBASSEMBLY)]
-        JSToJSWrapper,
-        #[cfg(V8_ENABLE_WEBASSEMBLY)]
-        JSToJSWrapperInvalidSig,
-        #[cfg(V8_ENABLE_WEBASSEMBLY)]
-        WasmPromising,
-        #[cfg(all(V8_ENABLE_WEBASSEMBLY, V8_ENABLE_DRUMBRAKE))]
-        GenericJSToWasmInterpreterWrapper,
-        #[cfg(V8_ENABLE_WEBASSEMBLY)]
-        WasmStressSwitch,
+    #[inline]
+    pub fn get_parameter_count(&self) -> u16 {
+        (self.encoded_word.load(Ordering::Relaxed) & Self::K_PARAMETER_COUNT_MASK) as u16
     }
 
-    impl TieringBuiltin{
-        pub fn from_usize(value: usize) -> Option<Self>{
-            match value{
-                0 => Some(TieringBuiltin::Illegal),
-                1 => Some(TieringBuiltin::CompileLazy),
-                2 => Some(TieringBuiltin::InterpreterEntryTrampoline),
-                3 => Some(TieringBuiltin::InstantiateAsmJs),
-                4 => Some(TieringBuiltin::DebugBreakTrampoline),
-                _ => None, //Add the rest of the cases
-            }
-        }
+    /// Installs new code, keeping the parameter count and the mark bit.
+    #[inline]
+    pub fn set_code_and_entrypoint(&self, code: Address, entrypoint: Address) {
+        let code = Self::encode_code(code);
+        self.encoded_word
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old_word| {
+                Some(code | (old_word & (Self::K_MARKING_BIT | Self::K_PARAMETER_COUNT_MASK)))
+            })
+            .unwrap();
+        self.entrypoint.store(entrypoint as u64, Ordering::Relaxed);
     }
 
-    impl JSDispatchTable {
-        fn IsValidTieringBuiltin(&self, _builtin: TieringBuiltin) -> bool {
-            true
-        }
+    fn encode_code(code: Address) -> u64 {
+        assert!((code as u64) < 1 << 47, "code pointer out of range");
+        debug_assert_eq!(code as u64 & 1, 1, "code must be a heap object");
+        (code as u64) << Self::K_OBJECT_POINTER_SHIFT & !Self::K_MARKING_BIT
     }
+}
 
-    pub struct Isolate {
-        builtin_entry_table: Vec<u64>, // Replace u64 with the correct type if known
-        embedded_data: EmbeddedData,
+impl JSDispatchTable {
+    #[inline]
+    pub fn allocate_and_initialize_entry(
+        &self,
+        space: &JSDispatchTableSpace,
+        parameter_count: u16,
+        code: Address,
+        entrypoint: Address,
+    ) -> JSDispatchHandle {
+        let index = self.allocate_entry(space);
+        self.at(index)
+            .make_js_dispatch_entry(code, entrypoint, parameter_count, space.allocate_black());
+        Self::index_to_handle(index)
     }
 
-    impl Isolate{
+    #[inline]
+    pub fn get_code(&self, handle: JSDispatchHandle) -> Address {
+        self.at(Self::handle_to_index(handle)).get_code()
     }
 
-    pub struct EmbeddedData{}
-
-    impl EmbeddedData{
-        pub fn InstructionStartOf(&self, index: usize) -> u64{
-            0 //Dummy implementation
-        }
-        pub fn FromBlob(_isolate: &Isolate) -> Self{
-            EmbeddedData{}
-        }
+    #[inline]
+    pub fn get_entrypoint(&self, handle: JSDispatchHandle) -> Address {
+        self.at(Self::handle_to_index(handle)).get_entrypoint()
     }
 
-    //Dummy Implementations
-    pub mod Builtins{
-        pub fn GetFormalParameterCount(_id: u32) -> i32{
-            0
-        }
+    #[inline]
+    pub fn get_parameter_count(&self, handle: JSDispatchHandle) -> u16 {
+        self.at(Self::handle_to_index(handle)).get_parameter_count()
     }
 
-    pub mod CodeKind{
-        pub const FOR_TESTING: i32 = 0;
+    #[inline]
+    pub fn set_code_and_entrypoint(&self, handle: JSDispatchHandle, code: Address, entrypoint: Address) {
+        debug_assert_ne!(Self::handle_to_index(handle), 0, "the null entry is read-only");
+        self.at(Self::handle_to_index(handle))
+            .set_code_and_entrypoint(code, entrypoint);
     }
 
-    pub mod Internals{
-        pub fn HasHeapObjectTag(_ptr: u64) -> bool{
-            true
+    #[inline]
+    pub fn mark(&self, handle: JSDispatchHandle) {
+        if Self::handle_to_index(handle) != 0 {
+            self.at(Self::handle_to_index(handle)).set_mark_bit();
         }
     }
-} // namespace internal
\ No newline at end of file
+}
@@ -2,582 +2,132 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-// Note: This conversion is incomplete as some V8-specific types and functionalities are not directly translatable.
-//       It serves as a structural outline and requires further adaptation and potentially custom implementations.
+use crate::heap::heap_layout::K_HEAP_OBJECT_TAG;
+use crate::sandbox::external_entity_table::{ExternalEntityTable, ExternalEntityTableEntry, Space};
 
-#[cfg(feature = "v8_enable_leaptiering")]
-pub mod js_dispatch_table {
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    //use v8::base::atomicops; //Requires equivalent Rust crate/implementation
-    //use v8::base::memory; //Requires equivalent Rust crate/implementation
-    //use v8::common::globals; //Requires equivalent Rust crate/implementation
-    //use v8::runtime::runtime; //Requires equivalent Rust crate/implementation
-    //use v8::sandbox::external_entity_table; //Requires equivalent Rust crate/implementation
-    //use v8::include::v8config;
-    pub type Address = usize;
-    pub type JSDispatchHandleValue = usize;
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-    pub struct JSDispatchHandle(pub JSDispatchHandleValue);
+const MB: usize = 1024 * 1024;
 
-    pub const K_JS_DISPATCH_TABLE_ENTRY_SIZE: usize = 16;
-    pub const K_JS_DISPATCH_TABLE_ENTRY_SIZE_LOG2: usize = 4; //log2(16)
-    pub const K_JS_DISPATCH_TABLE_RESERVATION_SIZE: usize = 4096;
-    pub const K_JS_DISPATCH_HANDLE_SHIFT: usize = 0;
-    pub const K_MAX_JS_DISPATCH_ENTRIES: usize = K_JS_DISPATCH_TABLE_RESERVATION_SIZE / K_JS_DISPATCH_TABLE_ENTRY_SIZE;
-    pub const K_MAX_CAPACITY: usize = K_MAX_JS_DISPATCH_ENTRIES;
-    pub const K_INTERNAL_NULL_ENTRY_INDEX: usize = 0;
-    pub const K_END_OF_INTERNAL_READ_ONLY_SEGMENT: usize = 10;
-    pub const V8_STATIC_DISPATCH_HANDLES_BOOL: bool = true;
+/// A handle to a dispatch table entry: the index of the entry shifted left
+/// by `K_JS_DISPATCH_HANDLE_SHIFT`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JSDispatchHandle(pub u32);
 
-    #[derive(Debug)]
-    pub struct Code; // Placeholder for Code type
-    pub type Tagged<T> = T; // Placeholder for Tagged type
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub enum TieringBuiltin {
-        Placeholder, // Placeholder value
-    }
-
-    pub struct Isolate; // Placeholder
-    pub struct Counters; // Placeholder
-
-    /// The entries of a JSDispatchTable.
-    ///
-    /// An entry contains all information to call a JavaScript function in a
-    /// sandbox-compatible way: the entrypoint and the parameter count (~= the
-    /// signature of the function). The entrypoint will always point to the current
-    /// code of the function, thereby enabling seamless tiering.
-    #[repr(C)]
-    pub struct JSDispatchEntry {
-        // We write-protect the JSDispatchTable on platforms that support it for
-        // forward-edge CFI.
-        pub const IS_WRITE_PROTECTED: bool = true;
-
-        // The first word contains the pointer to the (executable) entrypoint.
-        entrypoint_: AtomicUsize,
+pub const K_NULL_JS_DISPATCH_HANDLE: JSDispatchHandle = JSDispatchHandle(0);
 
-        // On 64 bit architectures the second word of the entry contains (1) the
-        // pointer to the code object associated with this entry, (2) the marking bit
-        // of the entry in the LSB of the object pointer (which must be unused as the
-        // address must be aligned), and (3) the 16-bit parameter count. The parameter
-        // count is stored in the lower 16 bits and therefore the pointer is shifted
-        // to the left. The final format therefore looks as follows:
-        //
-        // +----------------------+---------------+-------------------+
-        // | Bits 63 ... 17       | Bit 16        | Bits 15 ... 0     |
-        // |  HeapObject pointer  |  Marking bit  |  Parameter count  |
-        // +----------------------+---------------+-------------------+
-        //
-        // On 32 bit architectures only the mark bit is shared with the pointer.
-        //
-        // +----------------------+---------------+
-        // | Bits 32 ... 1        | Bit 0         |
-        // |  HeapObject pointer  |  Marking bit  |
-        // +----------------------+---------------+
-        //
-        // TODO(olivf): Find a better format that allows us to write atomically to the
-        // individual parts and unify with 32 bit. For instance we could try to store
-        // the code pointer in some compressd format, such that it fits into 32 bits.
+pub const K_JS_DISPATCH_TABLE_RESERVATION_SIZE: usize = 128 * MB;
+pub const K_JS_DISPATCH_HANDLE_SHIFT: u32 = 9;
+pub const K_MAX_JS_DISPATCH_ENTRIES: usize =
+    K_JS_DISPATCH_TABLE_RESERVATION_SIZE / std::mem::size_of::<JSDispatchEntry>();
+const _: () = assert!(((u32::MAX >> K_JS_DISPATCH_HANDLE_SHIFT) as usize) < K_MAX_JS_DISPATCH_ENTRIES);
 
-        encoded_word_: AtomicUsize,
-        #[cfg(target_arch = "x86")]
-        parameter_count_: AtomicUsize,
-
-        #[cfg(target_arch = "x86")]
-        next_free_entry_: AtomicUsize,
+impl JSDispatchHandle {
+    pub fn value(self) -> u32 {
+        self.0
     }
-
-    impl JSDispatchEntry {
-        pub const K_ENTRYPOINT_OFFSET: usize = 0;
-        pub const K_CODE_OBJECT_OFFSET: usize = std::mem::size_of::<usize>();
-        pub const K_PARAMETER_COUNT_SIZE: usize = 2;
-        
-        #[cfg(target_arch = "x86_64")]
-        pub const K_FREE_ENTRY_TAG: Address = 0xffff000000000000;
-        
-        #[cfg(target_arch = "x86_64")]
-        #[cfg(target_endian = "big")]
-        pub const K_BIG_ENDIAN_PARAM_COUNT_OFFSET: usize = std::mem::size_of::<usize>() - std::mem::size_of::<u16>();
-        #[cfg(target_arch = "x86_64")]
-        #[cfg(target_endian = "big")]
-        pub const K_PARAMETER_COUNT_OFFSET: usize = JSDispatchEntry::K_CODE_OBJECT_OFFSET + JSDispatchEntry::K_BIG_ENDIAN_PARAM_COUNT_OFFSET;
-        
-        #[cfg(target_arch = "x86_64")]
-        #[cfg(target_endian = "little")]
-        pub const K_PARAMETER_COUNT_OFFSET: usize = JSDispatchEntry::K_CODE_OBJECT_OFFSET;
-
-        #[cfg(target_arch = "x86_64")]
-        pub const K_OBJECT_POINTER_SHIFT: u32 = 16;
-        #[cfg(target_arch = "x86_64")]
-        pub const K_PARAMETER_COUNT_MASK: u32 = 0xffff;
-
-        #[cfg(target_arch = "x86")]
-        pub const K_PARAMETER_COUNT_OFFSET: usize = JSDispatchEntry::K_CODE_OBJECT_OFFSET + std::mem::size_of::<usize>();
-        #[cfg(target_arch = "x86")]
-        pub const K_OBJECT_POINTER_SHIFT: u32 = 0;
-        #[cfg(target_arch = "x86")]
-        pub const K_PARAMETER_COUNT_MASK: u32 = 0x0;
-
-        const K_MARKING_BIT: Address = 1 << Self::K_OBJECT_POINTER_SHIFT;
-
-        pub fn check_field_offsets() {
-            // TODO: Add static asserts to check field offsets
-        }
-
-        #[inline]
-        pub fn make_js_dispatch_entry(&self, object: Address, entrypoint: Address, parameter_count: u16, mark_as_alive: bool) {
-            self.set_code_and_entrypoint_pointer(object, entrypoint);
-            let mut encoded_word = object;
-
-            #[cfg(target_arch = "x86_64")]
-            {
-                encoded_word = encoded_word << Self::K_OBJECT_POINTER_SHIFT as usize;
-                encoded_word |= parameter_count as usize;
-            }
-
-            if mark_as_alive {
-                encoded_word |= Self::K_MARKING_BIT;
-            }
-
-            self.encoded_word_.store(encoded_word, Ordering::Relaxed);
-
-            #[cfg(target_arch = "x86")]
-            {
-                self.parameter_count_.store(parameter_count as usize, Ordering::Relaxed);
-            }
-        }
-
-        #[inline]
-        pub fn get_entrypoint(&self) -> Address {
-            self.entrypoint_.load(Ordering::Relaxed)
-        }
-
-        #[inline]
-        pub fn get_code_pointer(&self) -> Address {
-            let encoded_word = self.encoded_word_.load(Ordering::Relaxed);
-            let mask = !((Self::K_PARAMETER_COUNT_MASK as usize) | (Self::K_MARKING_BIT as usize));
-            encoded_word & mask
-        }
-
-        #[inline]
-        pub fn get_code(&self) -> Tagged<Code> {
-            // Requires conversion from address to Code type
-            // Placeholder implementation:
-            Code {}
-        }
-
-        #[inline]
-        pub fn get_parameter_count(&self) -> u16 {
-            #[cfg(target_arch = "x86_64")]
-            {
-                let encoded_word = self.encoded_word_.load(Ordering::Relaxed);
-                (encoded_word & Self::K_PARAMETER_COUNT_MASK as usize) as u16
-            }
-            #[cfg(target_arch = "x86")]
-            {
-                self.parameter_count_.load(Ordering::Relaxed) as u16
-            }
-        }
-
-        #[inline]
-        pub fn set_code_and_entrypoint_pointer(&self, new_object: Address, new_entrypoint: Address) {
-            self.entrypoint_.store(new_entrypoint, Ordering::Relaxed);
-            // Assuming here the object carries the marking bit etc.
-            self.encoded_word_.store(new_object, Ordering::Relaxed);
-        }
-
-        #[inline]
-        pub fn set_entrypoint_pointer(&self, new_entrypoint: Address) {
-            self.entrypoint_.store(new_entrypoint, Ordering::Relaxed);
-        }
-
-        #[inline]
-        pub fn make_freelist_entry(&self, next_entry_index: u32) {
-            #[cfg(target_arch = "x86_64")]
-            {
-                let next_entry_with_tag = (next_entry_index as Address) | Self::K_FREE_ENTRY_TAG;
-                self.encoded_word_.store(next_entry_with_tag, Ordering::Relaxed);
-            }
-            #[cfg(target_arch = "x86")]
-            {
-                self.next_free_entry_.store(next_entry_index as usize, Ordering::Relaxed);
-            }
-        }
-
-        #[inline]
-        pub fn is_freelist_entry(&self) -> bool {
-            #[cfg(target_arch = "x86_64")]
-            {
-                (self.encoded_word_.load(Ordering::Relaxed) & Self::K_FREE_ENTRY_TAG) == Self::K_FREE_ENTRY_TAG
-            }
-            #[cfg(target_arch = "x86")]
-            {
-                false
-            }
-        }
-
-        #[inline]
-        pub fn get_next_freelist_entry_index(&self) -> u32 {
-            #[cfg(target_arch = "x86_64")]
-            {
-                (self.encoded_word_.load(Ordering::Relaxed) & !(Self::K_FREE_ENTRY_TAG)) as u32
-            }
-            #[cfg(target_arch = "x86")]
-            {
-                self.next_free_entry_.load(Ordering::Relaxed) as u32
-            }
-        }
-
-        #[inline]
-        pub fn mark(&self) {
-            let current = self.encoded_word_.load(Ordering::Relaxed);
-            self.encoded_word_.store(current | Self::K_MARKING_BIT, Ordering::Relaxed);
-        }
-
-        #[inline]
-        pub fn unmark(&self) {
-            let current = self.encoded_word_.load(Ordering::Relaxed);
-            self.encoded_word_.store(current & !Self::K_MARKING_BIT, Ordering::Relaxed);
-        }
-
-        #[inline]
-        pub fn is_marked(&self) -> bool {
-             (self.encoded_word_.load(Ordering::Relaxed) & Self::K_MARKING_BIT) != 0
-        }
+}
+
+/// The entries of a JSDispatchTable. Each entry holds the entrypoint of a
+/// function's current code, and the code object together with the
+/// function's parameter count in one word, so that a caller always uses
+/// the parameter count that belongs to the code it calls.
+///
+/// The code pointer is shifted left by `K_OBJECT_POINTER_SHIFT`; its heap
+/// object tag bit serves as the mark bit and is cleared in unmarked
+/// entries. Free entries hold `K_FREE_ENTRY_TAG` and the freelist link,
+/// shifted past the mark bit.
+#[repr(C)]
+pub struct JSDispatchEntry {
+    pub(crate) entrypoint: AtomicU64,
+    pub(crate) encoded_word: AtomicU64,
+}
+
+impl JSDispatchEntry {
+    pub const K_OBJECT_POINTER_SHIFT: u32 = 16;
+    pub const K_PARAMETER_COUNT_MASK: u64 = 0xffff;
+    pub const K_MARKING_BIT: u64 = (K_HEAP_OBJECT_TAG as u64) << Self::K_OBJECT_POINTER_SHIFT;
+    /// No user-space code pointer has all these bits set after shifting.
+    pub const K_FREE_ENTRY_TAG: u64 = 0xffff << 48;
+}
+
+impl ExternalEntityTableEntry for JSDispatchEntry {
+    fn make_freelist_entry(&self, next_entry_index: u32) {
+        self.entrypoint.store(0, Ordering::Relaxed);
+        let word = Self::K_FREE_ENTRY_TAG | (next_entry_index as u64) << (Self::K_OBJECT_POINTER_SHIFT + 1);
+        self.encoded_word.store(word, Ordering::Relaxed);
     }
 
-    const _: () = assert!(std::mem::size_of::<JSDispatchEntry>() == K_JS_DISPATCH_TABLE_ENTRY_SIZE);
-
-    /// JSDispatchTable.
-    ///
-    /// The JSDispatchTable achieves two central goals:
-    ///
-    /// 1. It provides fine-grained forward-edge CFI for JavaScript function calls.
-    /// Both in the context of the V8 Sandbox and for process-wide CFI. For the
-    /// sandbox, this requires keeping the table outside of the sandbox and storing
-    /// both the function's entrypoints and its parameter count in it. That way, it
-    /// is guaranteed that every JSFunction call (1) lands at a valid JavaScript
-    /// entrypoint, and (2) uses the correct signature (~= parameter count). For
-    /// process-wide CFI, this table is write-protected using for example Intel
-    /// PKEYs. That way, even an attacker with an arbitrary, process-wide write
-    /// primitive cannot execute arbitrary code via JavaScript functions.
-    ///
-    /// 2. It enables cheap and fast tiering. When the JSDispatchTable is used, a
-    /// group of related JSFunctions (roughly those sharing the same SFI) share one
-    /// table entry. When the functions should tier up or down, only the entry needs
-    /// to be updated to point to the new code. Without such a table, every function
-    /// entrypoint would need to check if it needs to tier up or down, thereby
-    /// incurring some overhead on every function invocation.
-    pub struct JSDispatchTable {
-        base: ExternalEntityTable<JSDispatchEntry, K_JS_DISPATCH_TABLE_RESERVATION_SIZE>,
+    fn get_next_freelist_entry_index(&self) -> u32 {
+        let word = self.encoded_word.load(Ordering::Relaxed) & !Self::K_FREE_ENTRY_TAG;
+        (word >> (Self::K_OBJECT_POINTER_SHIFT + 1)) as u32
     }
 
-    impl JSDispatchTable {
-        pub const K_WRITE_BARRIER_SETS_ENTRY_MARK_BIT: bool = true;
-
-        pub fn new() -> Self {
-            JSDispatchTable {
-                base: ExternalEntityTable::<JSDispatchEntry, K_JS_DISPATCH_TABLE_RESERVATION_SIZE>::new(),
-            }
-        }
-
-        /// Retrieves the entrypoint of the entry referenced by the given handle.
-        #[inline]
-        pub fn get_entrypoint(&self, handle: JSDispatchHandle) -> Address {
-            let index = Self::handle_to_index(handle);
-            let entry = &self.base.entries[index];
-            entry.entrypoint_.load(Ordering::Relaxed)
-        }
-
-        /// Retrieves the Code stored in the entry referenced by the given handle.
-        ///
-        /// TODO(saelo): in the future, we might store either a Code or a
-        /// BytecodeArray in the entries. At that point, this could be changed to
-        /// return a Tagged<Union<Code, BytecodeArray>>.
-        #[inline]
-        pub fn get_code(&self, handle: JSDispatchHandle) -> Tagged<Code> {
-            let index = Self::handle_to_index(handle);
-            let entry = &self.base.entries[index];
-            entry.get_code()
-        }
-
-        /// Returns the address of the Code object stored in the specified entry.
-        #[inline]
-        pub fn get_code_address(&self, handle: JSDispatchHandle) -> Address {
-             let index = Self::handle_to_index(handle);
-             let entry = &self.base.entries[index];
-            entry.get_code_pointer()
-        }
-
-        /// Retrieves the parameter count of the entry referenced by the given handle.
-        #[inline]
-        pub fn get_parameter_count(&self, handle: JSDispatchHandle) -> u16 {
-             let index = Self::handle_to_index(handle);
-             let entry = &self.base.entries[index];
-            entry.get_parameter_count()
-        }
-
-        /// Updates the entry referenced by the given handle to the given Code and its
-        /// entrypoint. The code must be compatible with the specified entry. In
-        /// particular, the two must use the same parameter count.
-        /// NB: Callee must emit JS_DISPATCH_HANDLE_WRITE_BARRIER if needed!
-        #[inline]
-        pub fn set_code_no_write_barrier(&self, handle: JSDispatchHandle, new_code: Tagged<Code>) {
-             let index = Self::handle_to_index(handle);
-             let entry = &mut self.base.entries[index];
-
-            // Code address is a placeholder, replace with the actual code address when available
-            let code_address: Address = 0; // Placeholder
-
-            entry.set_code_and_entrypoint_pointer(code_address, entry.get_entrypoint()); // Requires getting entrypoint from code
-        }
-
-        /// Execute a tiering builtin instead of the actual code. Leaves the Code
-        /// pointer untouched and changes only the entrypoint.
-        #[inline]
-        pub fn set_tiering_request(&self, handle: JSDispatchHandle, builtin: TieringBuiltin, isolate: *mut Isolate) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (handle, builtin, isolate);
-        }
-        #[inline]
-        pub fn set_code_keep_tiering_request_no_write_barrier(&self, handle: JSDispatchHandle, new_code: Tagged<Code>) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (handle, new_code);
-        }
-        /// Resets the entrypoint to the code's entrypoint.
-        #[inline]
-        pub fn reset_tiering_request(&self, handle: JSDispatchHandle) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = handle;
-        }
-        /// Check if and/or which tiering builtin is installed.
-        #[inline]
-        pub fn is_tiering_requested(&self, handle: JSDispatchHandle) -> bool {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = handle;
-            false
-        }
-        #[inline]
-        pub fn is_tiering_requested_with_builtin(&self, handle: JSDispatchHandle, builtin: TieringBuiltin, isolate: *mut Isolate) -> bool {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (handle, builtin, isolate);
-            false
-        }
-
-        /// Allocates a new entry in the table and initialize it.
-        ///
-        /// Note: If possible allocate dispatch handles through the factory.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn allocate_and_initialize_entry(&mut self, space: &mut Space, parameter_count: u16, code: Tagged<Code>) -> JSDispatchHandle {
-            if let Some(handle) = self.try_allocate_and_initialize_entry(space, parameter_count, code) {
-                handle
-            } else {
-                panic!("Failed to allocate and initialize entry"); // Handle allocation failure appropriately
-            }
-        }
-
-        #[inline]
-        pub fn try_allocate_and_initialize_entry(&mut self, space: &mut Space, parameter_count: u16, code: Tagged<Code>) -> Option<JSDispatchHandle> {
-            let handle = self.base.allocate_entry(space)?;
-            let index = Self::handle_to_index(handle);
-            let entry = &mut self.base.entries[index];
-
-            let entrypoint: Address = 0; // Replace with Code entrypoint, get from code
-            let code_address: Address = 0; // Replace with Code address
-            entry.make_js_dispatch_entry(code_address, entrypoint, parameter_count, true);
-
-            Some(handle)
-        }
-
-        /// The following methods are used to pre allocate entries and then initialize
-        /// them later.
-        pub fn pre_allocate_entries(&mut self, space: &mut Space, num: i32, ensure_static_handles: bool) -> JSDispatchHandle {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (space, num, ensure_static_handles);
-            JSDispatchHandle(0) // Dummy handle, needs proper implementation
-        }
-        pub fn pre_allocated_entry_needs_initialization(&self, space: &mut Space, handle: JSDispatchHandle) -> bool {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (space, handle);
-            false
-        }
-        pub fn initialize_pre_allocated_entry(&mut self, space: &mut Space, handle: JSDispatchHandle, code: Tagged<Code>, parameter_count: u16) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (space, handle, code, parameter_count);
-        }
-
-        /// Can be used to statically predict the handles if the pre allocated entries
-        /// are in the overall first read only segment of the whole table.
-        #[cfg(feature = "v8_static_dispatch_handles_bool")]
-        pub fn get_static_handle_for_read_only_segment_entry(index: i32) -> JSDispatchHandle {
-            Self::index_to_handle((K_INTERNAL_NULL_ENTRY_INDEX + 1 + index) as u32)
-        }
-
-        pub fn in_read_only_segment(handle: JSDispatchHandle) -> bool {
-            Self::handle_to_index(handle) as usize <= K_END_OF_INTERNAL_READ_ONLY_SEGMENT
-        }
-
-        pub fn offset_of_entry(handle: JSDispatchHandle) -> usize {
-            Self::handle_to_index(handle) as usize * (K_JS_DISPATCH_TABLE_ENTRY_SIZE as usize)
-        }
-
-        /// Marks the specified entry as alive.
-        ///
-        /// This method is atomic and can be called from background threads.
-        #[inline]
-        pub fn mark(&self, handle: JSDispatchHandle) {
-            let index = Self::handle_to_index(handle);
-            let entry = &self.base.entries[index];
-            entry.mark();
-        }
-
-        /// Frees all unmarked entries in the given space.
-        ///
-        /// This method must only be called while mutator threads are stopped as it is
-        /// not safe to allocate table entries while a space is being swept.
-        ///
-        /// Returns the number of live entries after sweeping.
-        pub fn sweep<Callback>(&mut self, space: &mut Space, counters: *mut Counters, callback: Callback) -> u32
-        where
-            Callback: Fn(JSDispatchHandle),
-        {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (space, counters, callback);
-            0
-        }
-
-        /// Iterate over all active entries in the given space.
-        ///
-        /// The callback function will be invoked once for every entry that is
-        /// currently in use, i.e. has been allocated and not yet freed, and will
-        /// receive the handle of that entry.
-        pub fn iterate_active_entries_in<Callback>(&self, space: &mut Space, callback: Callback)
-        where
-            Callback: Fn(JSDispatchHandle),
-        {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (space, callback);
-        }
-
-         pub fn iterate_marked_entries_in<Callback>(&self, space: &mut Space, callback: Callback)
-        where
-            Callback: Fn(JSDispatchHandle),
-        {
-            let _ = (space, callback);
-        }
-
-        /// The base address of this table, for use in JIT compilers.
-        pub fn base_address(&self) -> Address {
-            self.base.base_address()
-        }
-
-        #[cfg(debug_assertions)]
-        pub fn is_marked(&self, handle: JSDispatchHandle) -> bool {
-            let index = Self::handle_to_index(handle);
-            let entry = &self.base.entries[index];
-            entry.is_marked()
-        }
-
-        #[cfg(any(debug_assertions, feature = "verify_heap"))]
-        pub fn verify_entry(&self, handle: JSDispatchHandle, space: &mut Space, ro_space: &mut Space) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (handle, space, ro_space);
-        }
-
-        pub fn print_entry(&self, handle: JSDispatchHandle) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = handle;
-        }
+    fn is_freelist_entry(&self) -> bool {
+        self.encoded_word.load(Ordering::Relaxed) & Self::K_FREE_ENTRY_TAG == Self::K_FREE_ENTRY_TAG
+    }
 
-        pub fn print_current_tiering_request(&self, handle: JSDispatchHandle, isolate: *mut Isolate, os: &mut std::ostream) {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (handle, isolate, os);
-        }
+    fn set_mark_bit(&self) {
+        self.encoded_word.fetch_or(Self::K_MARKING_BIT, Ordering::Relaxed);
+    }
 
-        #[inline]
-        fn is_compatible_code(code: Tagged<Code>, parameter_count: u16) -> bool {
-            // Placeholder implementation: Requires V8-specific logic
-            let _ = (code, parameter_count);
-            true
-        }
+    fn clear_mark_bit(&self) {
+        self.encoded_word.fetch_and(!Self::K_MARKING_BIT, Ordering::Relaxed);
+    }
 
-        #[inline]
-        fn set_code_and_entrypoint_no_write_barrier(&self, handle: JSDispatchHandle, new_code: Tagged<Code>, entrypoint: Address) {
-            let index = Self::handle_to_index(handle);
-            let entry = &mut self.base.entries[index];
+    fn has_mark_bit_set(&self) -> bool {
+        let word = self.encoded_word.load(Ordering::Relaxed);
+        word & Self::K_MARKING_BIT != 0 && word & Self::K_FREE_ENTRY_TAG != Self::K_FREE_ENTRY_TAG
+    }
+}
 
-            // Code address is a placeholder, replace with the actual code address when available
-            let code_address: Address = 0; // Placeholder
+pub type JSDispatchTableSpace = Space;
 
-            entry.set_code_and_entrypoint_pointer(code_address, entrypoint);
-        }
+/// The table through which JavaScript functions are called. A function in
+/// the sandbox only holds a `JSDispatchHandle`; its code, entrypoint and
+/// parameter count live outside the sandbox, so they cannot be made
+/// inconsistent by corrupting the function.
+pub struct JSDispatchTable {
+    table: ExternalEntityTable<JSDispatchEntry, K_JS_DISPATCH_TABLE_RESERVATION_SIZE>,
+}
 
-        fn handle_to_index(handle: JSDispatchHandle) -> u32 {
-            let index = handle.0 >> K_JS_DISPATCH_HANDLE_SHIFT;
-             index as u32
-        }
-        fn index_to_handle(index: u32) -> JSDispatchHandle {
-            JSDispatchHandle(index << K_JS_DISPATCH_HANDLE_SHIFT)
+impl JSDispatchTable {
+    pub fn new() -> Self {
+        JSDispatchTable {
+            table: ExternalEntityTable::new(),
         }
     }
 
-    /// Represents a space with black allocation support.
-    pub struct Space {
-        // Add necessary fields here
+    #[inline]
+    pub fn handle_to_index(handle: JSDispatchHandle) -> u32 {
+        handle.0 >> K_JS_DISPATCH_HANDLE_SHIFT
     }
 
-    impl Space {
-        // Implement Space methods here
+    #[inline]
+    pub fn index_to_handle(index: u32) -> JSDispatchHandle {
+        JSDispatchHandle(index << K_JS_DISPATCH_HANDLE_SHIFT)
     }
 
-    /// Generic external entity table.
-    pub struct ExternalEntityTable<T, const SIZE: usize> {
-        entries: Box<[T; SIZE]>,
-        free_list_head: AtomicUsize,
+    pub fn contains(&self, handle: JSDispatchHandle) -> bool {
+        self.table.is_committed(Self::handle_to_index(handle))
     }
 
-    impl<T, const SIZE: usize> ExternalEntityTable<T, const _>
-    where
-        T: Default + Copy,
-    {
-        pub fn new() -> Self {
-           let mut entries: Box<[T; SIZE]> = Box::new([T::default(); SIZE]);
-            // Initialize freelist.
-            for i in 0..(SIZE - 1) {
-                let entry = unsafe {&mut *entries.as_mut_ptr().add(i)};
-                //TODO: Implement make_freelist_entry, but is it really needed?
-               //entry.make_freelist_entry((i+1) as u32);
-
-            }
-            ExternalEntityTable {
-                entries: entries,
-                free_list_head: AtomicUsize::new(0),
-            }
-        }
+    pub fn sweep(&self, space: &JSDispatchTableSpace) -> u32 {
+        self.table.generic_sweep(space)
+    }
+}
 
-        fn allocate_entry(&mut self, space: &mut Space) -> Option<JSDispatchHandle> {
-             let _ = space;
-            let index = self.free_list_head.fetch_add(1, Ordering::Relaxed);
+impl Default for JSDispatchTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-            if index < SIZE {
-                 Some(JSDispatchHandle((index as u32) << K_JS_DISPATCH_HANDLE_SHIFT))
-            } else {
-                None // Freelist exhausted
-            }
-        }
+impl Deref for JSDispatchTable {
+    type Target = ExternalEntityTable<JSDispatchEntry, K_JS_DISPATCH_TABLE_RESERVATION_SIZE>;
 
-        fn base_address(&self) -> Address {
-            self.entries.as_ptr() as Address
-        }
+    fn deref(&self) -> &Self::Target {
+        &self.table
     }
-    impl<T: Default + Copy, const SIZE: usize> Default for ExternalEntityTable<T, SIZE> {
-        fn default() -> Self {
-            Self::new()
-        }
-    }
-
-    // TODO: Implement Drop for ExternalEntityTable to free memory
-}
\ No newline at end of file
+}
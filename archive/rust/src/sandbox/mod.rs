@@ -36,3 +36,4 @@ pub mod isolate;
 pub mod external-pointer;
 pub mod code-pointer-table-inl;
 pub mod cppheap-pointer-table-inl;
+pub mod corruption-harness;
@@ -2,422 +2,567 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// Note: The following features are enabled based on conditional compilation flags from the original C++ code.
-//       Make sure to enable them in your Rust project if they are required.
-// #![feature(allocator_api)]
-// #![feature(const_fn_trait_bound)]
-// #![feature(core_intrinsics)]
-// #![feature(nonnull_slice_from_raw_parts)]
-// #![feature(strict_provenance)]
-
-use std::ptr::null_mut;
+use std::cell::Cell;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Once, RwLock};
+use std::sync::OnceLock;
 
-// Mock imports for v8-internal.h, v8-platform.h, v8config.h, globals.h, bounds.h
-// Replace with actual crate imports when available
-mod v8 {
-    pub type Address = usize;
-    pub trait VirtualAddressSpace {
-        fn allocate_subspace(&mut self, size: usize) -> Option<usize>;
-        fn deallocate_subspace(&mut self, address: usize, size: usize);
-    }
-    pub trait PageAllocator {}
-}
+use crate::base::bounded_page_allocator::{BoundedPageAllocator, PageFreeingMode, PageInitializationMode};
+use crate::base::emulated_virtual_address_subspace::EmulatedVirtualAddressSubspace;
+use crate::base::page_allocator::{get_platform_page_allocator, PageAllocator, Permission};
+use crate::base::virtual_address_space::{
+    get_platform_virtual_address_space, PagePermissions, VirtualAddressSpace, K_NO_HINT,
+};
 
-mod base {
-    pub fn is_in_half_open_range(addr: usize, start: usize, end: usize) -> bool {
-        addr >= start && addr < end
-    }
-}
+pub type Address = usize;
+pub const K_NULL_ADDRESS: Address = 0;
 
-const V8_ENABLE_WEBASSEMBLY: bool = true;
-const V8_COMPRESS_POINTERS_IN_MULTIPLE_CAGES: bool = true;
-const USING_V8_SHARED_PRIVATE: bool = false;
+const GB: usize = 1 << 30;
 
-const kNullAddress: usize = 0;
+/// Size of the sandbox: 1TB.
+pub const K_SANDBOX_SIZE_LOG2: u32 = 40;
+pub const K_SANDBOX_SIZE: usize = 1 << K_SANDBOX_SIZE_LOG2;
 
-#[cfg(test)]
-mod testing {
-    pub mod gtest {
-        #[macro_export]
-        macro_rules! assert_eq {
-            ($left:expr, $right:expr) => {
-                if $left != $right {
-                    panic!("assertion failed: `(left == right)`\
-                      left: `{:?}`,\
-                     right: `{:?}`", $left, $right)
-                }
-            };
-        }
+/// The sandbox base is a pointer compression cage base, so it must be
+/// aligned like one.
+pub const K_SANDBOX_ALIGNMENT: usize = 4 * GB;
 
-        #[macro_export]
-        macro_rules! assert {
-            ($condition:expr) => {
-                if !$condition {
-                    panic!("assertion failed: `{}`", stringify!($condition))
-                }
-            };
-        }
-    }
-}
+/// Sandboxed pointers are offsets from the sandbox base shifted into the
+/// upper bits, so they can never leave the sandbox.
+pub const K_SANDBOXED_POINTER_SHIFT: u32 = 64 - K_SANDBOX_SIZE_LOG2;
 
-#[cfg(test)]
-mod sandbox_test {
-    use super::*;
-    #[test]
-    fn initialization_with_size() {
-        let mut vas = MockVirtualAddressSpace::new();
-        let mut sandbox = Sandbox::new();
-        sandbox.initialize(&mut vas, 4096, false);
-        assert_eq!(sandbox.is_initialized(), true);
-        sandbox.tear_down();
-    }
+/// Largest size of a buffer that sandboxed code may access.
+pub const K_MAX_SAFE_BUFFER_SIZE_FOR_SANDBOX: usize = 32 * GB - 1;
 
-    #[test]
-    fn partially_reserved_sandbox() {
-        let mut vas = MockVirtualAddressSpace::new();
-        let mut sandbox = Sandbox::new();
-        sandbox.initialize_as_partially_reserved_sandbox(&mut vas, 8192, 4096);
-        assert_eq!(sandbox.is_initialized(), true);
-        assert_eq!(sandbox.is_partially_reserved(), true);
-        sandbox.tear_down();
-    }
+/// Size of the inaccessible regions before and after the sandbox. An
+/// access through a sandboxed pointer plus a bounded size plus a 32-bit
+/// index, possibly scaled, always lands in the sandbox or a guard region.
+pub const K_SANDBOX_GUARD_REGION_SIZE: usize = 32 * GB + 4 * GB;
 
-    struct MockVirtualAddressSpace {}
-    impl MockVirtualAddressSpace {
-        pub fn new() -> Self {
-            MockVirtualAddressSpace {}
-        }
-    }
-    impl v8::VirtualAddressSpace for MockVirtualAddressSpace {
-        fn allocate_subspace(&mut self, size: usize) -> Option<usize> {
-            Some(0x1000)
-        }
-        fn deallocate_subspace(&mut self, address: usize, size: usize) {
-            // do nothing
-        }
-    }
-}
+/// Smallest part of the sandbox that must actually be reserved when the
+/// address space is too small for the whole sandbox.
+pub const K_SANDBOX_MINIMUM_RESERVATION_SIZE: usize = 8 * GB;
 
-#[derive(Default)]
+/// Set once the first four GB of the address space are reserved, which
+/// makes every Smi-sized address inaccessible. Never undone.
+static FIRST_FOUR_GB_OF_ADDRESS_SPACE_ARE_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// The sandbox is a large region of virtual address space that contains
+/// the V8 heap and all ArrayBuffer backing stores. Sandboxed code can only
+/// reference memory inside it, via offsets, table handles and bounded
+/// sizes, so memory corruption inside the sandbox cannot spread to the
+/// rest of the process.
+///
+/// The sandbox is normally fully reserved and surrounded by guard regions.
+/// If the address space is too small for that, it is partially reserved:
+/// only its start is reserved and the rest is emulated, which weakens the
+/// security guarantees but keeps everything else working.
 pub struct Sandbox {
-    base_: v8::Address,
-    end_: v8::Address,
-    size_: usize,
-    reservation_base_: v8::Address,
-    reservation_size_: usize,
-    initialized_: bool,
-    #[cfg(all(V8_ENABLE_WEBASSEMBLY, feature = "trap_handler_supported"))]
-    trap_handler_initialized_: bool,
-    address_space_: Option<Box<dyn v8::VirtualAddressSpace + Send + Sync>>,
-    sandbox_page_allocator_: Option<Box<dyn v8::PageAllocator + Send + Sync>>,
-    constants_: SandboxedPointerConstants,
+    base: Address,
+    end: Address,
+    size: usize,
+    reservation_base: Address,
+    reservation_size: usize,
+    initialized: bool,
+    /// The address space the sandbox's memory is allocated from.
+    address_space: Option<Box<dyn VirtualAddressSpace>>,
+    /// The region the page allocator hands out memory from.
+    backing_store_region: (Address, usize),
+    sandbox_page_allocator: Option<BoundedPageAllocator>,
+    constants: SandboxedPointerConstants,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Sandbox {
     pub const K_FALLBACK_TO_PARTIALLY_RESERVED_SANDBOX_ALLOWED: bool = true;
 
-    pub fn new() -> Self {
-        Sandbox::default()
+    pub const fn new() -> Self {
+        Sandbox {
+            base: K_NULL_ADDRESS,
+            end: K_NULL_ADDRESS,
+            size: 0,
+            reservation_base: K_NULL_ADDRESS,
+            reservation_size: 0,
+            initialized: false,
+            address_space: None,
+            backing_store_region: (K_NULL_ADDRESS, 0),
+            sandbox_page_allocator: None,
+            constants: SandboxedPointerConstants::new(),
+        }
     }
 
-    pub fn initialize(&mut self, vas: &mut dyn v8::VirtualAddressSpace) {
-        self.initialize_with_guard_regions(vas, true);
-    }
+    /// Reserves the sandbox in `vas`, falling back to a partially reserved
+    /// sandbox if there is not enough address space. Aborts the process if
+    /// not even that works.
+    pub fn initialize(&mut self, vas: &'static dyn VirtualAddressSpace) {
+        assert!(!self.initialized);
+        let sandbox_size = K_SANDBOX_SIZE;
+        let full_reservation_size = sandbox_size + 2 * K_SANDBOX_GUARD_REGION_SIZE;
 
-    fn initialize_with_guard_regions(&mut self, vas: &mut dyn v8::VirtualAddressSpace, use_guard_regions: bool) -> bool {
-        let size = 1024 * 1024; // Example size, needs to come from v8config
-        self.initialize_with_size(vas, size, use_guard_regions)
-    }
+        // Do not take more than a quarter of the usable address space, so
+        // that the rest of the process keeps working.
+        let max_reservation_size = determine_address_space_limit() / 4;
 
-    fn initialize_with_size(&mut self, vas: &mut dyn v8::VirtualAddressSpace, size: usize, use_guard_regions: bool) -> bool {
-        if use_guard_regions {
-            // TODO: Implement guard regions, requires knowing guard region size
-            let reservation_size = size; //+ 2 * guard_region_size;
-            if let Some(reservation_base) = vas.allocate_subspace(reservation_size) {
-                self.reservation_base_ = reservation_base;
-                self.reservation_size_ = reservation_size;
-                self.base_ = reservation_base; //+ guard_region_size;
-                self.size_ = size;
-                self.end_ = self.base_ + self.size_;
-                self.address_space_ = Some(Box::new(EmulatedVirtualAddressSubspace::new(reservation_base, size)));
-                self.sandbox_page_allocator_ = Some(Box::new(MockPageAllocator {}));
-                self.finish_initialization();
-                true
-            } else {
-                if Self::K_FALLBACK_TO_PARTIALLY_RESERVED_SANDBOX_ALLOWED {
-                    self.initialize_as_partially_reserved_sandbox(vas, size, size)
-                } else {
-                    panic!("OOM crash: Could not allocate virtual address space for sandbox."); // Proper OOM handling required
-                }
+        let mut success = false;
+        if vas.can_allocate_subspaces() && full_reservation_size <= max_reservation_size {
+            success = self.initialize_with_size(vas, sandbox_size, true);
+        }
+        if !success && Self::K_FALLBACK_TO_PARTIALLY_RESERVED_SANDBOX_ALLOWED {
+            let mut size_to_reserve = sandbox_size;
+            while size_to_reserve > max_reservation_size && size_to_reserve > K_SANDBOX_MINIMUM_RESERVATION_SIZE {
+                size_to_reserve /= 2;
             }
-        } else {
-            if let Some(base) = vas.allocate_subspace(size) {
-                self.reservation_base_ = base;
-                self.reservation_size_ = size;
-                self.base_ = base;
-                self.size_ = size;
-                self.end_ = self.base_ + self.size_;
-                self.address_space_ = Some(Box::new(EmulatedVirtualAddressSubspace::new(base, size)));
-                self.sandbox_page_allocator_ = Some(Box::new(MockPageAllocator {}));
-                self.finish_initialization();
-                true
-            } else {
-                panic!("OOM crash: Could not allocate virtual address space for sandbox.");
+            while !success && size_to_reserve >= K_SANDBOX_MINIMUM_RESERVATION_SIZE {
+                success = self.initialize_as_partially_reserved_sandbox(vas, sandbox_size, size_to_reserve);
+                size_to_reserve /= 2;
             }
         }
-    }
-
-    fn initialize_as_partially_reserved_sandbox(&mut self, vas: &mut dyn v8::VirtualAddressSpace, size: usize, size_to_reserve: usize) -> bool {
-        if let Some(base) = vas.allocate_subspace(size_to_reserve) {
-            self.reservation_base_ = base;
-            self.reservation_size_ = size_to_reserve;
-            self.base_ = base;
-            self.size_ = size;
-            self.end_ = self.base_ + self.size_;
-            self.address_space_ = Some(Box::new(EmulatedVirtualAddressSubspace::new(base, size)));
-            self.sandbox_page_allocator_ = Some(Box::new(MockPageAllocator {}));
-            self.finish_initialization();
-            true
-        } else {
-            panic!("OOM crash: Could not allocate virtual address space for sandbox.");
+        if !success {
+            panic!("Fatal process out of memory: failed to reserve the virtual address space for the sandbox");
         }
     }
 
-    fn finish_initialization(&mut self) {
-        self.initialized_ = true;
-        self.initialize_constants();
-
-        // Attempt to make the first four GB inaccessible. This can fail.
-        first_four_gb_of_address_space_are_reserved().store(true, Ordering::Relaxed);
-    }
-
-    fn initialize_constants(&mut self) {
-        //TODO: Proper implementation of constant initialization inside sandbox
-        self.constants_ = SandboxedPointerConstants::default();
-    }
-
+    /// Reserves a sandbox of `size` bytes as a subspace of `vas`, with
+    /// guard regions on both sides if `use_guard_regions` is set. Returns
+    /// false if the reservation fails.
+    pub fn initialize_with_size(&mut self, vas: &'static dyn VirtualAddressSpace, size: usize, use_guard_regions: bool) -> bool {
+        assert!(!self.initialized);
+        assert!(size.is_power_of_two() && size >= K_SANDBOX_ALIGNMENT);
+        assert!(vas.can_allocate_subspaces());
+
+        let guard_region_size = if use_guard_regions { K_SANDBOX_GUARD_REGION_SIZE } else { 0 };
+        let reservation_size = size + 2 * guard_region_size;
+        let hint = vas.random_page_address() & !(K_SANDBOX_ALIGNMENT - 1);
+        let Some(address_space) =
+            vas.allocate_subspace(hint, reservation_size, K_SANDBOX_ALIGNMENT, PagePermissions::kReadWriteExecute)
+        else {
+            return false;
+        };
+
+        self.reservation_base = address_space.base();
+        self.reservation_size = reservation_size;
+        self.base = self.reservation_base + guard_region_size;
+        self.size = size;
+        self.end = self.base + size;
+        if use_guard_regions {
+            assert!(address_space.allocate_guard_region(self.reservation_base, guard_region_size));
+            assert!(address_space.allocate_guard_region(self.end, guard_region_size));
+        }
+        self.address_space = Some(address_space);
+        self.finish_initialization(vas);
+        true
+    }
+
+    /// Reserves only the first `size_to_reserve` bytes of a sandbox of
+    /// `size` bytes and emulates the rest. Returns false if the
+    /// reservation fails.
+    pub fn initialize_as_partially_reserved_sandbox(
+        &mut self,
+        vas: &'static dyn VirtualAddressSpace,
+        size: usize,
+        size_to_reserve: usize,
+    ) -> bool {
+        assert!(!self.initialized);
+        assert!(size.is_power_of_two() && size_to_reserve.is_power_of_two());
+        assert!(size_to_reserve <= size && size_to_reserve >= K_SANDBOX_ALIGNMENT);
+
+        let hint = vas.random_page_address() & !(K_SANDBOX_ALIGNMENT - 1);
+        let Some(reservation_base) =
+            vas.allocate_pages(hint, size_to_reserve, K_SANDBOX_ALIGNMENT, PagePermissions::kNoAccess)
+        else {
+            return false;
+        };
+
+        self.reservation_base = reservation_base;
+        self.reservation_size = size_to_reserve;
+        self.base = reservation_base;
+        self.size = size;
+        self.end = self.base + size;
+        self.address_space = Some(Box::new(EmulatedVirtualAddressSubspace::new(
+            vas,
+            reservation_base,
+            size_to_reserve,
+            size,
+        )));
+        self.finish_initialization(vas);
+        true
+    }
+
+    fn finish_initialization(&mut self, vas: &'static dyn VirtualAddressSpace) {
+        let address_space = self.address_space.as_deref().unwrap();
+
+        // Backing stores get half of the reserved part of the sandbox; the
+        // rest is left for the heap.
+        let reserved_size = if self.is_partially_reserved() { self.reservation_size } else { self.size };
+        let region_size = reserved_size / 2;
+        let page_size = address_space.allocation_granularity();
+        let region_base = address_space
+            .allocate_pages(self.base, region_size, page_size, PagePermissions::kNoAccess)
+            .expect("failed to reserve the backing store region of the sandbox");
+        self.backing_store_region = (region_base, region_size);
+        let page_allocator = BoundedPageAllocator::new(
+            get_platform_page_allocator(),
+            region_base,
+            region_size,
+            page_size,
+            PageInitializationMode::kAllocatedPagesMustBeZeroInitialized,
+            PageFreeingMode::kMakeInaccessible,
+        );
+
+        // Empty backing stores point to a read-only zero page inside the
+        // sandbox, so that their data pointer is always a valid sandboxed
+        // pointer.
+        let empty_backing_store_buffer = page_allocator
+            .allocate_pages(K_NO_HINT, page_size, page_size, Permission::kRead)
+            .expect("failed to allocate the empty backing store buffer");
+        self.constants.set_empty_backing_store_buffer(empty_backing_store_buffer);
+        self.sandbox_page_allocator = Some(page_allocator);
+
+        try_reserve_first_four_gb_of_address_space(vas);
+        self.initialized = true;
+    }
+
+    /// Releases the sandbox's address space. All memory allocated inside
+    /// the sandbox must be freed before.
     pub fn tear_down(&mut self) {
-        if self.initialized_ {
-            if let Some(mut address_space) = self.address_space_.take() {
-                address_space.deallocate_subspace(self.reservation_base_, self.reservation_size_);
-            }
-            self.initialized_ = false;
-            self.base_ = kNullAddress;
-            self.end_ = kNullAddress;
-            self.size_ = 0;
-            self.reservation_base_ = kNullAddress;
-            self.reservation_size_ = 0;
-
-            #[cfg(all(V8_ENABLE_WEBASSEMBLY, feature = "trap_handler_supported"))]
-            {
-                self.trap_handler_initialized_ = false;
-            }
-
-            first_four_gb_of_address_space_are_reserved().store(false, Ordering::Relaxed);
+        if !self.initialized {
+            return;
         }
+        self.sandbox_page_allocator = None;
+        let address_space = self.address_space.take().unwrap();
+        let (region_base, region_size) = self.backing_store_region;
+        address_space.free_pages(region_base, region_size);
+        drop(address_space);
+
+        self.initialized = false;
+        self.base = K_NULL_ADDRESS;
+        self.end = K_NULL_ADDRESS;
+        self.size = 0;
+        self.reservation_base = K_NULL_ADDRESS;
+        self.reservation_size = 0;
+        self.backing_store_region = (K_NULL_ADDRESS, 0);
+        self.constants.reset();
     }
 
     pub fn is_initialized(&self) -> bool {
-        self.initialized_
+        self.initialized
     }
 
+    /// Returns true if only a part of the sandbox is reserved. A partially
+    /// reserved sandbox has no guard regions and does not guarantee that
+    /// the unreserved part stays free of other mappings.
     pub fn is_partially_reserved(&self) -> bool {
-        self.reservation_size_ < self.size_
+        self.reservation_size < self.size
     }
 
+    /// Returns true if addresses below 4GB are inaccessible, so that Smis
+    /// misinterpreted as pointers fault.
     pub fn smi_address_range_is_inaccessible(&self) -> bool {
-        first_four_gb_of_address_space_are_reserved().load(Ordering::Relaxed)
+        FIRST_FOUR_GB_OF_ADDRESS_SPACE_ARE_RESERVED.load(Ordering::Relaxed)
     }
 
-    pub fn base(&self) -> v8::Address {
-        self.base_
+    pub fn base(&self) -> Address {
+        self.base
     }
 
-    pub fn end(&self) -> v8::Address {
-        self.end_
+    pub fn end(&self) -> Address {
+        self.end
     }
 
     pub fn size(&self) -> usize {
-        self.size_
+        self.size
+    }
+
+    pub fn reservation_base(&self) -> Address {
+        self.reservation_base
     }
 
     pub fn reservation_size(&self) -> usize {
-        self.reservation_size_
+        self.reservation_size
     }
 
-    pub fn address_space(&self) -> Option<&dyn v8::VirtualAddressSpace> {
-        self.address_space_.as_ref().map(|x| x.as_ref())
+    /// The address space inside the sandbox, for allocating heap memory.
+    pub fn address_space(&self) -> Option<&dyn VirtualAddressSpace> {
+        self.address_space.as_deref()
     }
 
-    pub fn page_allocator(&self) -> Option<&dyn v8::PageAllocator> {
-        self.sandbox_page_allocator_.as_ref().map(|x| x.as_ref())
+    /// The page allocator all ArrayBuffer backing stores are allocated
+    /// with.
+    pub fn page_allocator(&self) -> Option<&BoundedPageAllocator> {
+        self.sandbox_page_allocator.as_ref()
     }
 
-    pub fn contains(&self, addr: v8::Address) -> bool {
-        base::is_in_half_open_range(addr, self.base_, self.base_ + self.size_)
+    pub fn contains(&self, address: Address) -> bool {
+        address.wrapping_sub(self.base) < self.size
     }
 
-    pub fn contains_ptr(&self, ptr: *mut std::ffi::c_void) -> bool {
-        self.contains(ptr as v8::Address)
+    pub fn contains_ptr(&self, ptr: *const std::ffi::c_void) -> bool {
+        self.contains(ptr as Address)
     }
 
-    pub fn reservation_contains(&self, addr: v8::Address) -> bool {
-        base::is_in_half_open_range(addr, self.reservation_base_, self.reservation_base_ + self.reservation_size_)
+    /// Returns true if `address` lies in the reserved part of the sandbox,
+    /// including its guard regions.
+    pub fn reservation_contains(&self, address: Address) -> bool {
+        address.wrapping_sub(self.reservation_base) < self.reservation_size
     }
 
     pub fn constants(&self) -> &SandboxedPointerConstants {
-        &self.constants_
+        &self.constants
     }
 
-    pub fn base_address(&self) -> v8::Address {
-        (&self.base_) as *const _ as v8::Address
+    pub fn base_address(&self) -> Address {
+        &self.base as *const Address as Address
     }
-    pub fn end_address(&self) -> v8::Address {
-        (&self.end_) as *const _ as v8::Address
+
+    pub fn end_address(&self) -> Address {
+        &self.end as *const Address as Address
     }
-    pub fn size_address(&self) -> v8::Address {
-        (&self.size_) as *const _ as v8::Address
+
+    pub fn size_address(&self) -> Address {
+        &self.size as *const usize as Address
     }
 
-    pub fn initialize_default_once_per_process(vas: &mut dyn v8::VirtualAddressSpace) {
-        static ONCE: Once = Once::new();
-        ONCE.call_once(|| {
-            let mut default_sandbox = Sandbox::new();
-            default_sandbox.initialize(vas);
-            *DEFAULT_SANDBOX.write().unwrap() = Some(Box::new(default_sandbox));
-        });
+    /// Initializes the process-wide default sandbox in the platform's
+    /// address space. Called when the process-wide isolate group is set up,
+    /// see `IsolateGroup::initialize_once_per_process()`.
+    pub fn initialize_default_once_per_process() -> &'static Sandbox {
+        DEFAULT_SANDBOX.get_or_init(|| {
+            let mut sandbox = Sandbox::new();
+            sandbox.initialize(get_platform_virtual_address_space());
+            sandbox
+        })
     }
 
-    pub fn tear_down_default() {
-        if let Some(mut sandbox) = DEFAULT_SANDBOX.write().unwrap().take() {
-            sandbox.tear_down();
-        }
+    pub fn get_default() -> Option<&'static Sandbox> {
+        DEFAULT_SANDBOX.get()
     }
 
-    pub fn new_sandbox(vas: &mut dyn v8::VirtualAddressSpace) -> Box<Sandbox> {
-        let mut sandbox = Sandbox::new();
+    pub fn new_sandbox(vas: &'static dyn VirtualAddressSpace) -> Box<Sandbox> {
+        let mut sandbox = Box::new(Sandbox::new());
         sandbox.initialize(vas);
-        Box::new(sandbox)
+        sandbox
     }
 
-    #[cfg(V8_COMPRESS_POINTERS_IN_MULTIPLE_CAGES)]
+    /// Returns the sandbox of the current thread, or the default one.
     pub fn current() -> Option<&'static Sandbox> {
-        CURRENT.with(|current| current.borrow().map(|s| unsafe { &*(&**s as *const Sandbox) }))
+        CURRENT_SANDBOX.with(Cell::get).or_else(Self::get_default)
     }
 
-    #[cfg(V8_COMPRESS_POINTERS_IN_MULTIPLE_CAGES)]
-    pub fn set_current(sandbox: Option<Box<Sandbox>>) {
-        CURRENT.with(|current| {
-            *current.borrow_mut() = sandbox;
-        });
+    pub fn set_current(sandbox: Option<&'static Sandbox>) {
+        CURRENT_SANDBOX.with(|current| current.set(sandbox));
     }
+}
 
-    #[cfg(not(V8_COMPRESS_POINTERS_IN_MULTIPLE_CAGES))]
-    pub fn current() -> Option<&'static Sandbox> {
-        Self::get_default()
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        self.tear_down();
     }
-    #[cfg(not(V8_COMPRESS_POINTERS_IN_MULTIPLE_CAGES))]
-    pub fn set_current(_sandbox: Option<Box<Sandbox>>) {
-        // do nothing
+}
+
+static DEFAULT_SANDBOX: OnceLock<Sandbox> = OnceLock::new();
+
+thread_local! {
+    static CURRENT_SANDBOX: Cell<Option<&'static Sandbox>> = const { Cell::new(None) };
+}
+
+/// Returns how much virtual address space the process may use.
+fn determine_address_space_limit() -> usize {
+    let mut limit = crate::base::virtual_address_space::K_USER_ADDRESS_SPACE_SIZE;
+    let mut rlimit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(libc::RLIMIT_AS, &mut rlimit) } == 0 && rlimit.rlim_cur != libc::RLIM_INFINITY {
+        limit = limit.min(rlimit.rlim_cur as usize);
     }
+    limit
+}
 
-    pub fn get_default() -> Option<&'static Sandbox> {
-        DEFAULT_SANDBOX.read().unwrap().as_ref().map(|s| unsafe { &*(&**s as *const Sandbox) })
+/// Tries to reserve the first four GB of the address space, which makes
+/// Smis that are used as pointers fault. Failing is fine. The reservation
+/// is shared by all sandboxes and kept until the process exits.
+fn try_reserve_first_four_gb_of_address_space(vas: &'static dyn VirtualAddressSpace) {
+    if FIRST_FOUR_GB_OF_ADDRESS_SPACE_ARE_RESERVED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    // The lowest pages usually cannot be mapped, so the reservation may
+    // start a little later.
+    let end = 4 * GB;
+    let step = vas.allocation_granularity();
+    let mut start = K_NULL_ADDRESS;
+    while start <= 1 << 20 {
+        if vas.allocate_guard_region(start, end - start) {
+            return;
+        }
+        start += step;
     }
+    FIRST_FOUR_GB_OF_ADDRESS_SPACE_ARE_RESERVED.store(false, Ordering::Release);
 }
 
+/// Constants that generated code loads relative to the sandbox.
 #[derive(Default)]
 pub struct SandboxedPointerConstants {
-    empty_backing_store_buffer_: v8::Address,
+    empty_backing_store_buffer: Address,
 }
 
 impl SandboxedPointerConstants {
-    pub fn empty_backing_store_buffer(&self) -> v8::Address {
-        self.empty_backing_store_buffer_
+    pub const fn new() -> Self {
+        SandboxedPointerConstants { empty_backing_store_buffer: K_NULL_ADDRESS }
     }
-    pub fn empty_backing_store_buffer_address(&self) -> v8::Address {
-        (&self.empty_backing_store_buffer_) as *const _ as v8::Address
+
+    pub fn empty_backing_store_buffer(&self) -> Address {
+        self.empty_backing_store_buffer
     }
-    pub fn set_empty_backing_store_buffer(&mut self, value: v8::Address) {
-        self.empty_backing_store_buffer_ = value;
+
+    pub fn empty_backing_store_buffer_address(&self) -> Address {
+        &self.empty_backing_store_buffer as *const Address as Address
+    }
+
+    pub fn set_empty_backing_store_buffer(&mut self, value: Address) {
+        self.empty_backing_store_buffer = value;
     }
 
     pub fn reset(&mut self) {
-        self.empty_backing_store_buffer_ = 0;
+        self.empty_backing_store_buffer = K_NULL_ADDRESS;
     }
 }
 
-// Helper function that can be used to ensure that certain objects are not
-// located inside the sandbox. Typically used for trusted objects.
-// Will always return false when the sandbox is disabled or partially reserved.
-#[inline]
-pub fn inside_sandbox(address: usize) -> bool {
-    #[cfg(V8_ENABLE_SANDBOX)]
-    {
-        if let Some(sandbox) = Sandbox::current() {
-            // Use ReservationContains (instead of just Contains) to correctly handle the
-            // case of partially-reserved sandboxes.
-            return sandbox.reservation_contains(address);
+/// Allocator for ArrayBuffer backing stores. Every backing store is
+/// allocated with the sandbox's page allocator, so that sandboxed code
+/// can reference it with a sandboxed pointer and a bounded size. Isolates
+/// get it from their group, see
+/// `IsolateGroup::get_sandboxed_array_buffer_allocator()`.
+pub struct SandboxedArrayBufferAllocator<'a> {
+    sandbox: &'a Sandbox,
+}
+
+impl<'a> SandboxedArrayBufferAllocator<'a> {
+    pub fn new(sandbox: &'a Sandbox) -> Self {
+        assert!(sandbox.is_initialized());
+        SandboxedArrayBufferAllocator { sandbox }
+    }
+
+    fn page_allocator(&self) -> &'a BoundedPageAllocator {
+        self.sandbox.page_allocator().unwrap()
+    }
+
+    fn rounded_size(&self, length: usize) -> usize {
+        length.next_multiple_of(self.page_allocator().allocate_page_size())
+    }
+
+    /// Allocates a zero-initialized backing store of `length` bytes.
+    /// Returns `None` if the sandbox is out of memory or `length` exceeds
+    /// what sandboxed code may access.
+    pub fn allocate(&self, length: usize) -> Option<Address> {
+        if length == 0 {
+            return Some(self.sandbox.constants().empty_backing_store_buffer());
         }
+        if length > K_MAX_SAFE_BUFFER_SIZE_FOR_SANDBOX {
+            return None;
+        }
+        let page_allocator = self.page_allocator();
+        let size = self.rounded_size(length);
+        page_allocator.allocate_pages(K_NO_HINT, size, page_allocator.allocate_page_size(), Permission::kReadWrite)
     }
-    false
-}
 
-#[inline]
-pub fn empty_backing_store_buffer() -> *mut std::ffi::c_void {
-    #[cfg(V8_ENABLE_SANDBOX)]
-    {
-        if let Some(sandbox) = Sandbox::current() {
-            return sandbox.constants().empty_backing_store_buffer() as *mut std::ffi::c_void;
+    /// Pages are zeroed when freed, so this is the same as `allocate`.
+    pub fn allocate_uninitialized(&self, length: usize) -> Option<Address> {
+        self.allocate(length)
+    }
+
+    pub fn free(&self, data: Address, length: usize) {
+        if length == 0 {
+            debug_assert_eq!(data, self.sandbox.constants().empty_backing_store_buffer());
+            return;
         }
+        assert!(self.page_allocator().free_pages(data, self.rounded_size(length)));
     }
-    std::ptr::null_mut()
 }
 
-// Mock implementations for VirtualAddressSpace and PageAllocator
-struct EmulatedVirtualAddressSubspace {
-    base: usize,
-    size: usize,
+/// Returns true if `address` lies in the current sandbox's reservation,
+/// for checking that trusted objects live outside the sandbox. Always false
+/// without a sandbox.
+#[inline]
+pub fn inside_sandbox(address: Address) -> bool {
+    // reservation_contains also covers partially reserved sandboxes.
+    Sandbox::current().is_some_and(|sandbox| sandbox.reservation_contains(address))
 }
 
-impl EmulatedVirtualAddressSubspace {
-    fn new(base: usize, size: usize) -> Self {
-        EmulatedVirtualAddressSubspace { base, size }
-    }
+#[inline]
+pub fn empty_backing_store_buffer() -> Address {
+    Sandbox::current().map_or(K_NULL_ADDRESS, |sandbox| sandbox.constants().empty_backing_store_buffer())
 }
 
-impl v8::VirtualAddressSpace for EmulatedVirtualAddressSubspace {
-    fn allocate_subspace(&mut self, size: usize) -> Option<usize> {
-        // Simple emulation, always returns the base address if enough space is available.
-        if size <= self.size {
-            Some(self.base)
-        } else {
-            None
-        }
+#[cfg(test)]
+mod sandbox_test {
+    use super::*;
+
+    #[test]
+    fn initialization_with_size() {
+        let vas = get_platform_virtual_address_space();
+        let mut sandbox = Sandbox::new();
+        assert!(sandbox.initialize_with_size(vas, 8 * GB, true));
+        assert!(sandbox.is_initialized());
+        assert!(!sandbox.is_partially_reserved());
+        assert_eq!(sandbox.base() % K_SANDBOX_ALIGNMENT, 0);
+        assert_eq!(sandbox.reservation_base() + K_SANDBOX_GUARD_REGION_SIZE, sandbox.base());
+        assert!(sandbox.reservation_contains(sandbox.base() - 1) && !sandbox.contains(sandbox.base() - 1));
+
+        // Nothing can be allocated in the guard regions.
+        let address_space = sandbox.address_space().unwrap();
+        let granularity = address_space.allocation_granularity();
+        assert!(!address_space.allocate_guard_region(sandbox.end(), granularity));
+        let page = address_space
+            .allocate_pages(sandbox.end() - granularity, granularity, granularity, PagePermissions::kReadWrite)
+            .unwrap();
+        assert!(sandbox.contains(page));
+        address_space.free_pages(page, granularity);
+
+        sandbox.tear_down();
+        assert!(!sandbox.is_initialized());
     }
 
-    fn deallocate_subspace(&mut self, address: usize, size: usize) {
-        // In a real implementation, this would free the memory.
-        // Here, we just check that the address is correct.
-        assert_eq!(address, self.base);
-        assert!(size <= self.size);
+    #[test]
+    fn partially_reserved_sandbox() {
+        let vas = get_platform_virtual_address_space();
+        let mut sandbox = Sandbox::new();
+        assert!(sandbox.initialize_as_partially_reserved_sandbox(vas, 64 * GB, 8 * GB));
+        assert!(sandbox.is_initialized());
+        assert!(sandbox.is_partially_reserved());
+        assert_eq!(sandbox.reservation_size(), 8 * GB);
+        assert!(sandbox.contains(sandbox.base() + 32 * GB));
+        assert!(!sandbox.reservation_contains(sandbox.base() + 32 * GB));
+
+        // Allocations beyond the reservation still land in the sandbox.
+        let address_space = sandbox.address_space().unwrap();
+        let size = 16 * GB;
+        if let Some(address) = address_space.allocate_pages(K_NO_HINT, size, size, PagePermissions::kNoAccess) {
+            assert!(sandbox.contains(address) && sandbox.contains(address + size - 1));
+            address_space.free_pages(address, size);
+        }
+        sandbox.tear_down();
     }
-}
 
-struct MockPageAllocator {}
+    #[test]
+    fn backing_stores_are_allocated_inside_the_sandbox() {
+        let mut sandbox = Sandbox::new();
+        assert!(sandbox.initialize_with_size(get_platform_virtual_address_space(), 8 * GB, true));
+        let allocator = SandboxedArrayBufferAllocator::new(&sandbox);
 
-impl v8::PageAllocator for MockPageAllocator {}
+        let empty = allocator.allocate(0).unwrap();
+        assert!(sandbox.contains(empty));
+        assert_eq!(unsafe { *(empty as *const u64) }, 0);
 
-static DEFAULT_SANDBOX: RwLock<Option<Box<Sandbox>>> = RwLock::new(None);
-static FIRST_FOUR_GB_OF_ADDRESS_SPACE_ARE_RESERVED: AtomicBool = AtomicBool::new(false);
+        let buffer = allocator.allocate(10_000).unwrap();
+        assert!(sandbox.contains(buffer) && sandbox.contains(buffer + 9_999));
+        unsafe { *((buffer + 9_999) as *mut u8) = 1 };
+        allocator.free(buffer, 10_000);
+        // Freed memory is zeroed before it is handed out again.
+        let buffer = allocator.allocate(10_000).unwrap();
+        assert_eq!(unsafe { *((buffer + 9_999) as *const u8) }, 0);
+        allocator.free(buffer, 10_000);
 
-fn first_four_gb_of_address_space_are_reserved() -> &'static AtomicBool {
-    &FIRST_FOUR_GB_OF_ADDRESS_SPACE_ARE_RESERVED
+        assert!(allocator.allocate(K_MAX_SAFE_BUFFER_SIZE_FOR_SANDBOX + 1).is_none());
+    }
 }
-
-#[cfg(V8_COMPRESS_POINTERS_IN_MULTIPLE_CAGES)]
-thread_local! {
-    static CURRENT: std::cell::RefCell<Option<Box<Sandbox>>> = std::cell::RefCell::new(None);
-}
\ No newline at end of file
@@ -2,34 +2,12 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod sandbox {
-    pub mod sandboxed_pointer {
-        //use crate::common::globals::*; // Assuming globals.h has relevant definitions
-        // Placeholder for Address, PtrComprCageBase and relevant global definitions.
-        // Need to define these types appropriately based on the original C++ definitions.
-        // For example:
-        // type Address = usize;
-        // type PtrComprCageBase = usize;
-
-        // NOTE: These are placeholder definitions.  The actual definitions depend on
-        // how Address and PtrComprCageBase are represented in the V8 codebase.
-        pub type Address = usize;
-        pub type PtrComprCageBase = usize;
-
-        #[inline]
-        pub fn read_sandboxed_pointer_field(field_address: Address, cage_base: PtrComprCageBase) -> Address {
-            // Placeholder implementation. Needs the actual logic from the C++ version.
-            // This likely involves pointer manipulation and potentially unsafe code.
-            field_address + cage_base // Example: Simple addition as a placeholder.
-        }
-
-        #[inline]
-        pub fn write_sandboxed_pointer_field(field_address: Address, cage_base: PtrComprCageBase, value: Address) {
-            // Placeholder implementation. Needs the actual logic from the C++ version.
-            // This likely involves pointer manipulation and potentially unsafe code.
-            // Example: No-op placeholder.  In a real implementation, this would
-            // likely write to a memory location.
-            let _ = (field_address, cage_base, value); //Suppress unused variable warnings
-        }
-    }
-}
\ No newline at end of file
+pub use crate::sandbox::sandbox::K_SANDBOXED_POINTER_SHIFT;
+
+/// The in-memory representation of a sandboxed pointer: the offset of the
+/// pointer from the sandbox base, shifted left by
+/// `K_SANDBOXED_POINTER_SHIFT`. Shifting the offset back right can never
+/// produce an offset of the sandbox size or more, so a corrupted sandboxed
+/// pointer still points into the sandbox.
+#[allow(non_camel_case_types)]
+pub type SandboxedPointer_t = u64;
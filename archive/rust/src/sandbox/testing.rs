@@ -1,749 +1,288 @@
-// src/sandbox/testing.rs
-
-// NOTE: This is a placeholder and may require significant adjustments
-//       to integrate properly with the V8 engine's Rust bindings.
-
-#![allow(dead_code)]
-#![allow(non_snake_case)]
-#![allow(unused_variables)]
-#![allow(unused_imports)]
-
-use std::collections::HashMap;
-use std::ffi::CString;
-use std::mem::MaybeUninit;
-use std::os::raw::{c_char, c_int, c_void};
-use std::sync::Mutex;
-//use libc::{sigaction, siginfo_t}; // Consider using nix crate
-use std::{fmt, str};
-
-// Placeholder for V8 API bindings
-mod v8 {
-    pub type Isolate = usize;
-    pub type Context = usize;
-    pub type Value = usize;
-    pub type Number = usize;
-    pub type Integer = usize;
-    pub type Uint32 = usize;
-    pub type String = usize;
-    pub type Object = usize;
-    pub type Global = usize;
-    pub type FunctionCallbackInfo<T> = usize;
-    pub type FunctionTemplate = usize;
-    pub type FunctionCallback = extern "C" fn(info: FunctionCallbackInfo<Value>);
-    pub type MaybeLocal<T> = Result<T, ()>;
-    pub type Local<'a, T> = &'a T; // Assuming lifetime relationship
-
-    pub struct FunctionTemplateInfo;
-    pub struct JSFunction;
-    pub struct JSObject;
-    pub struct JSGlobalObject;
-    pub struct Factory;
-    pub struct Object;
-    pub struct Handle<T>(pub *mut T);
-    pub type IsolatePtr = *mut Isolate;
-
-    impl Handle<FunctionTemplateInfo> {
-        // Placeholder for now
-        pub fn new(_ptr: *mut FunctionTemplateInfo) -> Self {
-            Handle(_ptr)
-        }
-    }
-
-    impl Handle<JSFunction> {
-        // Placeholder for now
-        pub fn new(_ptr: *mut JSFunction) -> Self {
-            Handle(_ptr)
-        }
-    }
-
-    impl Handle<JSObject> {
-        // Placeholder for now
-        pub fn new(_ptr: *mut JSObject) -> Self {
-            Handle(_ptr)
-        }
-    }
-
-    impl Handle<String> {
-        // Placeholder for now
-        pub fn new(_ptr: *mut String) -> Self {
-            Handle(_ptr)
-        }
-    }
-
-    impl Handle<Object> {
-        // Placeholder for now
-        pub fn new(_ptr: *mut Object) -> Self {
-            Handle(_ptr)
-        }
-    }
-
-    impl Handle<JSGlobalObject> {
-        // Placeholder for now
-        pub fn new(_ptr: *mut JSGlobalObject) -> Self {
-            Handle(_ptr)
-        }
-    }
-
-    impl Factory {
-        // Placeholder for now
-        pub fn new() -> Self {
-            Factory {}
-        }
-
-        pub fn NewStringFromAsciiChecked(&mut self, str: &str) -> Handle<String> {
-            Handle(0 as *mut String)
-        }
-    }
+// Copyright 2023 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+
+use crate::sandbox::sandbox::{Address, Sandbox};
+
+/// Mode of the sandbox testing infrastructure.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Mode {
+    /// Sandbox testing mode is not active.
+    kDisabled,
+    /// Memory access violations that cannot be sandbox violations end the
+    /// process with exit status 0; sandbox violations are reported and
+    /// crash the process. Other crashes, such as failed checks, are left
+    /// alone so that tests notice them.
+    kForTesting,
+    /// Like `kForTesting`, but every crash other than a sandbox violation
+    /// ends the process with exit status 0, so that fuzzers only report
+    /// sandbox violations.
+    kForFuzzing,
 }
 
-// Placeholder for V8 internal bindings
-mod internal {
-    use super::v8;
-    use std::fmt;
-
-    pub type Address = usize;
-    pub type HeapObject = usize;
-    pub type Tagged<T> = T; // Assuming Tagged is a no-op
-    pub type InstanceType = u16;
-    pub type SharedFunctionInfo = usize;
-    pub type Script = usize;
-
-    pub struct VirtualAddressSpace;
-
-    impl VirtualAddressSpace {
-        pub fn AllocatePages(
-            &self,
-            hint: usize,
-            size: usize,
-            page_size: usize,
-            permissions: PagePermissions,
-        ) -> Address {
-            0 // Placeholder
-        }
-    }
-    pub enum PagePermissions {
-        kReadWrite,
-    }
-
-    pub fn GetPlatformVirtualAddressSpace() -> &'static VirtualAddressSpace {
-        static VAS: VirtualAddressSpace = VirtualAddressSpace {};
-        &VAS
-    }
-
-    pub const kNullAddress: Address = 0;
-
-    pub const JS_OBJECT_TYPE: InstanceType = 1;
-    pub const JS_FUNCTION_TYPE: InstanceType = 2;
-    pub const JS_ARRAY_TYPE: InstanceType = 3;
-    pub const JS_ARRAY_BUFFER_TYPE: InstanceType = 4;
-    pub const JS_TYPED_ARRAY_TYPE: InstanceType = 5;
-    pub const SEQ_ONE_BYTE_STRING_TYPE: InstanceType = 6;
-    pub const SEQ_TWO_BYTE_STRING_TYPE: InstanceType = 7;
-    pub const INTERNALIZED_ONE_BYTE_STRING_TYPE: InstanceType = 8;
-    pub const SLICED_ONE_BYTE_STRING_TYPE: InstanceType = 9;
-    pub const CONS_ONE_BYTE_STRING_TYPE: InstanceType = 10;
-    pub const SHARED_FUNCTION_INFO_TYPE: InstanceType = 11;
-    pub const SCRIPT_TYPE: InstanceType = 12;
-    pub const WASM_MODULE_OBJECT_TYPE: InstanceType = 13;
-    pub const WASM_INSTANCE_OBJECT_TYPE: InstanceType = 14;
-    pub const WASM_FUNC_REF_TYPE: InstanceType = 15;
-    pub const WASM_TABLE_OBJECT_TYPE: InstanceType = 16;
-
-    // Placeholder for WASM types
-    pub mod wasm {
-        pub const WASM_MODULE_OBJECT_TYPE: super::InstanceType = 13;
-        pub const WASM_INSTANCE_OBJECT_TYPE: super::InstanceType = 14;
-        pub const WASM_FUNC_REF_TYPE: super::InstanceType = 15;
-        pub const WASM_TABLE_OBJECT_TYPE: super::InstanceType = 16;
-    }
-
-    pub const LAST_TYPE: InstanceType = 16;
-    pub const FIRST_TYPE: InstanceType = 1;
-
-    pub type Object = usize;
-
-    pub const NONE: i32 = 0;
-    pub const DONT_ENUM: i32 = 0;
-    pub const FROZEN: i32 = 0;
-
-    pub struct Isolate {}
+/// Verdict of the crash filter on a crash.
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CrashClassification {
+    /// The crash cannot be a sandbox violation. Holds the reason.
+    kHarmless(&'static str),
+    /// The crash is not filtered and is reported as it is.
+    kNotFiltered,
+    /// The crash may be an exploitable access outside the sandbox.
+    kSandboxViolation,
+}
 
-    impl Isolate {
-        pub fn factory(&mut self) -> Factory {
-            Factory::new()
-        }
+/// Where the sandbox lives, captured when testing mode is enabled so that
+/// the signal handler does not need to access the sandbox object.
+#[derive(Clone, Copy, Debug)]
+pub struct SandboxBounds {
+    pub base: Address,
+    pub size: usize,
+    pub reservation_base: Address,
+    pub reservation_size: usize,
+}
 
-        pub fn global_object(&mut self) -> v8::Handle<v8::JSGlobalObject> {
-            v8::Handle::new(0 as *mut v8::JSGlobalObject)
+impl SandboxBounds {
+    pub fn of(sandbox: &Sandbox) -> Self {
+        SandboxBounds {
+            base: sandbox.base(),
+            size: sandbox.size(),
+            reservation_base: sandbox.reservation_base(),
+            reservation_size: sandbox.reservation_size(),
         }
     }
+}
 
-    pub struct Factory {}
-
-    impl Factory {
-        pub fn NewJSObject(&mut self, _function: usize, _allocation_type: i32) -> v8::Handle<v8::JSObject> {
-            v8::Handle::new(0 as *mut v8::JSObject)
+static MODE: AtomicU8 = AtomicU8::new(Mode::kDisabled as u8);
+static SANDBOX_BASE: AtomicUsize = AtomicUsize::new(0);
+static SANDBOX_SIZE: AtomicUsize = AtomicUsize::new(0);
+static SANDBOX_RESERVATION_BASE: AtomicUsize = AtomicUsize::new(0);
+static SANDBOX_RESERVATION_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Addresses below this are never valid pointers: they are null pointers
+/// with an offset or Smis that were used as pointers.
+const K_FIRST_VALID_ADDRESS: Address = 1 << 32;
+
+/// Signal codes of SIGSEGV on Linux: the address is not mapped, or mapped
+/// without the required permissions.
+pub const SEGV_MAPERR: libc::c_int = 1;
+pub const SEGV_ACCERR: libc::c_int = 2;
+
+/// Signals handled by the crash filter.
+const K_FILTERED_SIGNALS: [libc::c_int; 5] = [libc::SIGABRT, libc::SIGTRAP, libc::SIGBUS, libc::SIGSEGV, libc::SIGILL];
+
+static OLD_ACTIONS: [OnceLock<libc::sigaction>; K_FILTERED_SIGNALS.len()] =
+    [const { OnceLock::new() }; K_FILTERED_SIGNALS.len()];
+
+/// Infrastructure for testing and fuzzing the sandbox. In testing mode, a
+/// crash filter classifies every crash of the process: memory access
+/// violations that cannot escape the sandbox are harmless, all others are
+/// reported as sandbox violations.
+pub struct SandboxTesting;
+
+impl SandboxTesting {
+    /// Enables sandbox testing mode for the current sandbox and installs
+    /// the crash filter. Can be enabled once per process.
+    pub fn enable(mode: Mode) {
+        assert_eq!(Self::mode(), Mode::kDisabled);
+        assert_ne!(mode, Mode::kDisabled);
+        let sandbox = Sandbox::current().expect("sandbox testing requires a sandbox");
+        assert!(sandbox.is_initialized());
+
+        let bounds = SandboxBounds::of(sandbox);
+        SANDBOX_BASE.store(bounds.base, Ordering::Relaxed);
+        SANDBOX_SIZE.store(bounds.size, Ordering::Relaxed);
+        SANDBOX_RESERVATION_BASE.store(bounds.reservation_base, Ordering::Relaxed);
+        SANDBOX_RESERVATION_SIZE.store(bounds.reservation_size, Ordering::Relaxed);
+        MODE.store(mode as u8, Ordering::Release);
+
+        eprintln!(
+            "Sandbox testing mode is enabled. Only sandbox violations will be reported, all other crashes will be ignored."
+        );
+        install_crash_filter();
+    }
+
+    pub fn is_enabled() -> bool {
+        Self::mode() != Mode::kDisabled
+    }
+
+    pub fn mode() -> Mode {
+        match MODE.load(Ordering::Acquire) {
+            1 => Mode::kForTesting,
+            2 => Mode::kForFuzzing,
+            _ => Mode::kDisabled,
         }
     }
 
-    impl fmt::Display for InstanceType {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "{}", *self as u16)
+    /// Classifies a crash by `signal` with signal code `code` at
+    /// `fault_address`, which is only meaningful for SIGSEGV and SIGBUS.
+    pub fn classify_crash(
+        mode: Mode,
+        sandbox: &SandboxBounds,
+        signal: libc::c_int,
+        code: libc::c_int,
+        fault_address: Address,
+    ) -> CrashClassification {
+        if signal != libc::SIGSEGV && signal != libc::SIGBUS {
+            // Failed checks and similar aborts are deliberate crashes.
+            return if mode == Mode::kForFuzzing {
+                CrashClassification::kHarmless("Caught harmless signal")
+            } else {
+                CrashClassification::kNotFiltered
+            };
         }
-    }
-
-    pub struct Sandbox;
-
-    impl Sandbox {
-        pub fn current() -> &'static Sandbox {
-            static SANDBOX: Sandbox = Sandbox {};
-            &SANDBOX
+        if code == libc::SI_KERNEL {
+            // On x64, the kernel reports accesses to non-canonical
+            // addresses, which can never be mapped, with address 0.
+            return CrashClassification::kHarmless("Caught harmless memory access violation (non-canonical address)");
         }
-
-        pub fn base(&self) -> usize {
-            0
-        }
-
-        pub fn size(&self) -> usize {
-            0
+        if fault_address < K_FIRST_VALID_ADDRESS {
+            return CrashClassification::kHarmless("Caught harmless memory access violation (first 4GB of address space)");
         }
-
-        pub fn is_initialized(&self) -> bool {
-            true
+        if fault_address.wrapping_sub(sandbox.base) < sandbox.size
+            || fault_address.wrapping_sub(sandbox.reservation_base) < sandbox.reservation_size
+        {
+            // Includes the guard regions.
+            return CrashClassification::kHarmless(
+                "Caught harmless memory access violation (inside sandbox address space)",
+            );
         }
-
-        pub fn Contains(&self, _address: Address) -> bool {
-            false
+        if code == SEGV_ACCERR {
+            // The address is mapped, but not accessible in this way, for
+            // example a guard page or read-only memory. Such mappings
+            // cannot be abused to corrupt memory.
+            return CrashClassification::kHarmless("Caught harmless memory access violation (memory permission violation)");
         }
-    }
-
-    pub fn ToApiHandle<T>(_object: usize) -> usize {
-        0
-    }
-
-    pub fn IsHeapObject(_arg: &usize) -> bool {
-        false
+        CrashClassification::kSandboxViolation
     }
 }
 
-#[cfg(target_os = "linux")]
-mod os {
-    use std::os::raw::{c_char, c_int};
-
-    extern "C" {
-        pub fn write(fd: c_int, buf: *const c_char, count: usize) -> isize;
-        pub fn strlen(s: *const c_char) -> usize;
-        pub fn _exit(status: c_int) -> !;
-        pub fn sigaltstack(ss: *const stack_t, oss: *mut stack_t) -> c_int;
-        pub fn sysconf(name: c_int) -> isize;
-    }
-
-    #[repr(C)]
-    pub struct stack_t {
-        pub ss_sp: *mut c_void,
-        pub ss_flags: c_int,
-        pub ss_size: usize,
-    }
-
-    pub const STDERR_FILENO: c_int = 2;
-
-    pub const SIGSTKSZ: usize = 8192; // Placeholder
-    pub const _SC_PAGESIZE: c_int = 30; // Placeholder
-
-    extern "C" {
-        pub fn pthread_getattr_np(thread: usize, attr: *mut pthread_attr_t) -> c_int;
-        pub fn pthread_attr_getstack(attr: *const pthread_attr_t, stackaddr: *mut *mut c_void, stacksize: *mut usize) -> c_int;
-        pub fn pthread_self() -> usize;
-    }
-
-    #[repr(C)]
-    pub struct pthread_attr_t {
-        __size: [u8; 56], // Placeholder, adjust size if needed.
-    }
-}
-
-#[cfg(target_os = "linux")]
-mod signal {
-    use std::os::raw::{c_int, c_void};
-
-    #[repr(C)]
-    pub struct sigaction {
-        pub sa_sigaction: extern "C" fn(c_int, *mut siginfo_t, *mut c_void),
-        pub sa_mask: sigset_t,
-        pub sa_flags: c_int,
-        // sa_restorer: Option<unsafe extern "C" fn()>, // Removed to avoid function pointer issues
-    }
-
-    #[repr(C)]
-    pub struct sigset_t {
-        __val: [u64; 16], // Placeholder, size depends on the architecture.
-    }
-
-    #[repr(C)]
-    pub struct siginfo_t {
-        pub si_signo: c_int,
-        pub si_errno: c_int,
-        pub si_code: c_int,
-        pub si_addr: *mut c_void,
-        // Add other fields as needed based on usage in C++ code
-    }
-
-    extern "C" {
-        pub fn sigemptyset(set: *mut sigset_t) -> c_int;
-        pub fn sigaction(signum: c_int, act: *const sigaction, oldact: *mut sigaction) -> c_int;
-    }
-
-    pub const SIGABRT: c_int = 6;
-    pub const SIGTRAP: c_int = 5;
-    pub const SIGBUS: c_int = 7;
-    pub const SIGSEGV: c_int = 11;
-
-    pub const SA_SIGINFO: c_int = 4;
-    pub const SA_ONSTACK: c_int = 0x08000000; // Placeholder, check actual value
-    pub const SI_KERNEL: c_int = 128; // Placeholder, check actual value
-    pub const SEGV_ACCERR: c_int = 2; // Placeholder, check actual value
+fn write_to_stderr(message: &str) {
+    unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr() as *const libc::c_void, message.len()) };
 }
 
-#[cfg(target_os = "linux")]
-mod asan {
-    extern "C" {
-        pub fn __asan_get_report_address() -> usize;
-        pub fn __sanitizer_set_death_callback(callback: Option<extern "C" fn()>);
+/// Restores the handler that was installed before the crash filter.
+fn uninstall_crash_filter(signal: libc::c_int) {
+    if let Some(index) = K_FILTERED_SIGNALS.iter().position(|&filtered| filtered == signal)
+        && let Some(old_action) = OLD_ACTIONS[index].get()
+    {
+        unsafe { libc::sigaction(signal, old_action, std::ptr::null_mut()) };
     }
 }
 
-pub mod sandbox {
-    use super::*;
-
-    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-    pub enum Mode {
-        kDisabled,
-        kEnabled,
-        kForFuzzing,
-    }
-
-    pub struct SandboxTesting {
-        // Static mutable state needs synchronization
-        mode: Mutex<Mode>,
-        instance_type_map: Mutex<InstanceTypeMap>,
-        field_offset_map: Mutex<FieldOffsetMap>,
-    }
-
-    impl SandboxTesting {
-        pub fn new() -> Self {
-            SandboxTesting {
-                mode: Mutex::new(Mode::kDisabled),
-                instance_type_map: Mutex::new(InstanceTypeMap::new()),
-                field_offset_map: Mutex::new(FieldOffsetMap::new()),
-            }
+extern "C" fn crash_filter(signal: libc::c_int, info: *mut libc::siginfo_t, _context: *mut libc::c_void) {
+    // Only async-signal-safe operations from here on.
+    let (code, fault_address) = unsafe { ((*info).si_code, (*info).si_addr() as Address) };
+    let sandbox = SandboxBounds {
+        base: SANDBOX_BASE.load(Ordering::Relaxed),
+        size: SANDBOX_SIZE.load(Ordering::Relaxed),
+        reservation_base: SANDBOX_RESERVATION_BASE.load(Ordering::Relaxed),
+        reservation_size: SANDBOX_RESERVATION_SIZE.load(Ordering::Relaxed),
+    };
+    match SandboxTesting::classify_crash(SandboxTesting::mode(), &sandbox, signal, code, fault_address) {
+        CrashClassification::kHarmless(reason) => {
+            write_to_stderr(reason);
+            write_to_stderr(". Exiting process...\n");
+            unsafe { libc::_exit(0) };
         }
-
-        pub fn mode(&self) -> Mode {
-            *self.mode.lock().unwrap()
-        }
-
-        pub fn set_mode(&self, mode: Mode) {
-            *self.mode.lock().unwrap() = mode;
-        }
-
-        pub fn GetInstanceTypeMap(&self) -> InstanceTypeMap {
-            self.instance_type_map.lock().unwrap().clone()
-        }
-
-        pub fn GetFieldOffsetMap(&self) -> FieldOffsetMap {
-            self.field_offset_map.lock().unwrap().clone()
-        }
-
-        #[allow(unused_mut)]
-        pub fn InstallMemoryCorruptionApi(&mut self, isolate: v8::IsolatePtr) {
-            #[cfg(not(V8_ENABLE_MEMORY_CORRUPTION_API))]
-            {
-                panic!("This function should not be available in any shipping build where it could potentially be abused to facilitate exploitation.");
-            }
-
-            let isolate = isolate as *mut internal::Isolate;
-            unsafe {
-                let isolate = &mut *isolate;
-
-                let mut factory = isolate.factory();
-                let sandbox = factory.NewJSObject(0, 0);
-
-                install_getter(isolate, sandbox, sandbox_get_base, "base");
-                install_getter(isolate, sandbox, sandbox_get_byte_length, "byteLength");
-                install_constructor(isolate, sandbox, sandbox_memory_view, "MemoryView", 2);
-                install_function(isolate, sandbox, sandbox_get_address_of, "getAddressOf", 1);
-                install_function(isolate, sandbox, sandbox_get_object_at, "getObjectAt", 1);
-                install_function(isolate, sandbox, sandbox_is_valid_object_at, "isValidObjectAt", 1);
-                install_function(isolate, sandbox, sandbox_is_writable, "isWritable", 1);
-                install_function(isolate, sandbox, sandbox_is_writable_object_at, "isWritableObjectAt", 1);
-                install_function(isolate, sandbox, sandbox_get_size_of, "getSizeOf", 1);
-                install_function(isolate, sandbox, sandbox_get_size_of_object_at, "getSizeOfObjectAt", 1);
-                install_function(isolate, sandbox, sandbox_get_instance_type_of, "getInstanceTypeOf", 1);
-                install_function(isolate, sandbox, sandbox_get_instance_type_of_object_at, "getInstanceTypeOfObjectAt", 1);
-                install_function(isolate, sandbox, sandbox_get_instance_type_id_of, "getInstanceTypeIdOf", 1);
-                install_function(isolate, sandbox, sandbox_get_instance_type_id_of_object_at, "getInstanceTypeIdOfObjectAt", 1);
-                install_function(isolate, sandbox, sandbox_get_instance_type_id_for, "getInstanceTypeIdFor", 1);
-                install_function(isolate, sandbox, sandbox_get_field_offset, "getFieldOffset", 2);
-
-                let global = isolate.global_object();
-                let mut factory = isolate.factory();
-                let name = factory.NewStringFromAsciiChecked("Sandbox");
-
-                internal::JSObject::AddProperty(isolate, global, name, sandbox, internal::DONT_ENUM);
-            }
-        }
-
-        pub fn Enable(&self, mode: Mode) {
-            assert_eq!(*self.mode.lock().unwrap(), Mode::kDisabled);
-            assert_ne!(mode, Mode::kDisabled);
-            assert!(internal::Sandbox::current().is_initialized());
-
-            self.set_mode(mode);
-
-            eprintln!(
-                "Sandbox testing mode is enabled. Only sandbox violations will be reported, all other crashes will be ignored."
-            );
-
-            #[cfg(target_os = "linux")]
-            install_crash_filter();
-
-            #[cfg(not(target_os = "linux"))]
-            panic!("The sandbox crash filter is currently only available on Linux");
-        }
-
-        pub fn GetInstanceTypeMap() -> InstanceTypeMap {
-            lazy_static::lazy_static! {
-                static ref INSTANCE_TYPE_MAP: Mutex<InstanceTypeMap> = Mutex::new({
-                    let mut types = InstanceTypeMap::new();
-                    types.insert("JS_OBJECT_TYPE".to_string(), internal::JS_OBJECT_TYPE);
-                    types.insert("JS_FUNCTION_TYPE".to_string(), internal::JS_FUNCTION_TYPE);
-                    types.insert("JS_ARRAY_TYPE".to_string(), internal::JS_ARRAY_TYPE);
-                    types.insert("JS_ARRAY_BUFFER_TYPE".to_string(), internal::JS_ARRAY_BUFFER_TYPE);
-                    types.insert("JS_TYPED_ARRAY_TYPE".to_string(), internal::JS_TYPED_ARRAY_TYPE);
-                    types.insert("SEQ_ONE_BYTE_STRING_TYPE".to_string(), internal::SEQ_ONE_BYTE_STRING_TYPE);
-                    types.insert("SEQ_TWO_BYTE_STRING_TYPE".to_string(), internal::SEQ_TWO_BYTE_STRING_TYPE);
-                    types.insert("INTERNALIZED_ONE_BYTE_STRING_TYPE".to_string(), internal::INTERNALIZED_ONE_BYTE_STRING_TYPE);
-                    types.insert("SLICED_ONE_BYTE_STRING_TYPE".to_string(), internal::SLICED_ONE_BYTE_STRING_TYPE);
-                    types.insert("CONS_ONE_BYTE_STRING_TYPE".to_string(), internal::CONS_ONE_BYTE_STRING_TYPE);
-                    types.insert("SHARED_FUNCTION_INFO_TYPE".to_string(), internal::SHARED_FUNCTION_INFO_TYPE);
-                    types.insert("SCRIPT_TYPE".to_string(), internal::SCRIPT_TYPE);
-                    types.insert("WASM_MODULE_OBJECT_TYPE".to_string(), internal::wasm::WASM_MODULE_OBJECT_TYPE);
-                    types.insert("WASM_INSTANCE_OBJECT_TYPE".to_string(), internal::wasm::WASM_INSTANCE_OBJECT_TYPE);
-                    types.insert("WASM_FUNC_REF_TYPE".to_string(), internal::wasm::WASM_FUNC_REF_TYPE);
-                    types.insert("WASM_TABLE_OBJECT_TYPE".to_string(), internal::wasm::WASM_TABLE_OBJECT_TYPE);
-
-                    types
-                });
-            }
-
-            INSTANCE_TYPE_MAP.lock().unwrap().clone()
-        }
-
-        pub fn GetFieldOffsetMap() -> FieldOffsetMap {
-            lazy_static::lazy_static! {
-                static ref FIELD_OFFSET_MAP: Mutex<FieldOffsetMap> = Mutex::new({
-                    let mut fields = FieldOffsetMap::new();
-
-                    //fields.insert(internal::JS_FUNCTION_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("dispatch_handle".to_string(), internal::JSFunction::kDispatchHandleOffset);
-                    //    object_fields.insert("shared_function_info".to_string(), internal::JSFunction::kSharedFunctionInfoOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::JS_ARRAY_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("elements".to_string(), internal::JSArray::kElementsOffset);
-                    //    object_fields.insert("length".to_string(), internal::JSArray::kLengthOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::JS_TYPED_ARRAY_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("length".to_string(), internal::JSTypedArray::kRawLengthOffset);
-                    //    object_fields.insert("byte_length".to_string(), internal::JSTypedArray::kRawByteLengthOffset);
-                    //    object_fields.insert("byte_offset".to_string(), internal::JSTypedArray::kRawByteOffsetOffset);
-                    //    object_fields.insert("external_pointer".to_string(), internal::JSTypedArray::kExternalPointerOffset);
-                    //    object_fields.insert("base_pointer".to_string(), internal::JSTypedArray::kBasePointerOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::SEQ_ONE_BYTE_STRING_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("length".to_string(), internal::SeqOneByteString::kLengthOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::SEQ_TWO_BYTE_STRING_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("hash".to_string(), internal::SeqTwoByteString::kHashOffset);
-                    //    object_fields.insert("length".to_string(), internal::SeqTwoByteString::kLengthOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::INTERNALIZED_ONE_BYTE_STRING_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("length".to_string(), internal::InternalizedString::kLengthOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::SLICED_ONE_BYTE_STRING_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("parent".to_string(), internal::SlicedString::kParentOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::CONS_ONE_BYTE_STRING_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("length".to_string(), internal::ConsString::kLengthOffset);
-                    //    object_fields.insert("first".to_string(), internal::ConsString::kFirstOffset);
-                    //    object_fields.insert("second".to_string(), internal::ConsString::kSecondOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::SHARED_FUNCTION_INFO_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("trusted_function_data".to_string(), internal::SharedFunctionInfo::kTrustedFunctionDataOffset);
-                    //    object_fields.insert("length".to_string(), internal::SharedFunctionInfo::kLengthOffset);
-                    //    object_fields.insert("formal_parameter_count".to_string(), internal::SharedFunctionInfo::kFormalParameterCountOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::SCRIPT_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("wasm_managed_native_module".to_string(), internal::Script::kWasmManagedNativeModuleOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::wasm::WASM_MODULE_OBJECT_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("managed_native_module".to_string(), internal::WasmModuleObject::kManagedNativeModuleOffset);
-                    //    object_fields.insert("script".to_string(), internal::WasmModuleObject::kScriptOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::wasm::WASM_INSTANCE_OBJECT_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("module_object".to_string(), internal::WasmInstanceObject::kModuleObjectOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::wasm::WASM_FUNC_REF_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("trusted_internal".to_string(), internal::WasmFuncRef::kTrustedInternalOffset);
-                    //    object_fields
-                    //});
-
-                    //fields.insert(internal::wasm::WASM_TABLE_OBJECT_TYPE, {
-                    //    let mut object_fields = HashMap::new();
-                    //    object_fields.insert("entries".to_string(), internal::WasmTableObject::kEntriesOffset);
-                    //    object_fields.insert("current_length".to_string(), internal::WasmTableObject::kCurrentLengthOffset);
-                    //    object_fields.insert("maximum_length".to_string(), internal::WasmTableObject::kMaximumLengthOffset);
-                    //    object_fields.insert("raw_type".to_string(), internal::WasmTableObject::kRawTypeOffset);
-                    //    object_fields
-                    //});
-
-                    fields
-                });
-            }
-
-            FIELD_OFFSET_MAP.lock().unwrap().clone()
+        CrashClassification::kNotFiltered => {}
+        CrashClassification::kSandboxViolation => {
+            write_to_stderr("\n## V8 sandbox violation detected!\n\n");
         }
     }
+    // Returning re-executes the faulting instruction, which now crashes
+    // the process through the previous handler.
+    uninstall_crash_filter(signal);
+}
 
-    impl Default for SandboxTesting {
-        fn default() -> Self {
-            SandboxTesting::new()
-        }
+fn install_crash_filter() {
+    // Handle the signals on an alternate stack, so that stack overflows can
+    // be filtered too. The stack is leaked deliberately.
+    const K_ALTERNATE_STACK_SIZE: usize = 64 * 1024;
+    let stack = Box::leak(vec![0u8; K_ALTERNATE_STACK_SIZE].into_boxed_slice());
+    let signal_stack = libc::stack_t {
+        ss_sp: stack.as_mut_ptr() as *mut libc::c_void,
+        ss_flags: 0,
+        ss_size: K_ALTERNATE_STACK_SIZE,
+    };
+    assert_eq!(unsafe { libc::sigaltstack(&signal_stack, std::ptr::null_mut()) }, 0);
+
+    for (index, &signal) in K_FILTERED_SIGNALS.iter().enumerate() {
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = crash_filter as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        unsafe { libc::sigemptyset(&mut action.sa_mask) };
+        let mut old_action: libc::sigaction = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::sigaction(signal, &action, &mut old_action) }, 0);
+        OLD_ACTIONS[index].set(old_action).expect("the crash filter is installed once");
     }
+}
 
-    //impl Drop for SandboxTesting {
-    //    fn drop(&mut self) {
-    //        // Implement the Drop trait to clean up resources if needed.
-    //    }
-    //}
-
-    // Define type aliases for clarity
-    pub type InstanceTypeMap = HashMap<String, internal::InstanceType>;
-    pub type FieldOffsetMap = HashMap<internal::InstanceType, HashMap<String, usize>>;
-
-    // Implement the Free Callback
-    unsafe extern "C" fn free_callback(data: *mut c_void, _length: usize) {
-        if !data.is_null() {
-            drop(Box::from_raw(data as *mut u8)); // Ensure the data is properly deallocated
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base::virtual_address_space::get_platform_virtual_address_space;
+
+    const GB: usize = 1 << 30;
+
+    #[test]
+    fn crashes_outside_the_sandbox_are_violations() {
+        let sandbox = SandboxBounds {
+            base: 0x100_0000_0000,
+            size: 8 * GB,
+            reservation_base: 0x100_0000_0000 - 36 * GB,
+            reservation_size: 80 * GB,
+        };
+        let classify = |code, address| SandboxTesting::classify_crash(Mode::kForTesting, &sandbox, libc::SIGSEGV, code, address);
+        assert!(matches!(classify(SEGV_MAPERR, 0x10), CrashClassification::kHarmless(_)));
+        assert!(matches!(classify(SEGV_MAPERR, sandbox.base + GB), CrashClassification::kHarmless(_)));
+        // Guard regions are part of the reservation.
+        assert!(matches!(classify(SEGV_MAPERR, sandbox.base - GB), CrashClassification::kHarmless(_)));
+        assert!(matches!(classify(libc::SI_KERNEL, 0), CrashClassification::kHarmless(_)));
+        assert_eq!(classify(SEGV_MAPERR, 0x7f00_0000_0000), CrashClassification::kSandboxViolation);
+
+        let abort = |mode| SandboxTesting::classify_crash(mode, &sandbox, libc::SIGABRT, 0, 0);
+        assert_eq!(abort(Mode::kForTesting), CrashClassification::kNotFiltered);
+        assert!(matches!(abort(Mode::kForFuzzing), CrashClassification::kHarmless(_)));
+    }
+
+    /// Runs `access` in a child process with sandbox testing enabled and
+    /// returns its wait status.
+    fn run_in_testing_mode(access: impl FnOnce(&Sandbox)) -> libc::c_int {
+        let pid = unsafe { libc::fork() };
+        if pid == 0 {
+            let mut sandbox = Box::new(Sandbox::new());
+            assert!(sandbox.initialize_with_size(get_platform_virtual_address_space(), 8 * GB, true));
+            let sandbox: &'static Sandbox = Box::leak(sandbox);
+            Sandbox::set_current(Some(sandbox));
+            SandboxTesting::enable(Mode::kForTesting);
+            access(sandbox);
+            unsafe { libc::_exit(42) };
         }
+        let mut status = 0;
+        assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+        status
+    }
+
+    #[test]
+    fn crash_filter_detects_sandbox_violations() {
+        // A wild access inside the sandbox is harmless.
+        let status = run_in_testing_mode(|sandbox| unsafe {
+            std::ptr::write_volatile((sandbox.base() + 4 * GB) as *mut u64, 1);
+        });
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0, "status {status}");
+
+        // An access to unmapped memory outside of it is a violation.
+        let status = run_in_testing_mode(|_| unsafe {
+            let page = libc::mmap(std::ptr::null_mut(), 4096, libc::PROT_READ, libc::MAP_PRIVATE | libc::MAP_ANONYMOUS, -1, 0);
+            libc::munmap(page, 4096);
+            std::ptr::write_volatile(page as *mut u64, 1);
+        });
+        assert!(libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGSEGV, "status {status}");
     }
-
-    // Sandbox.base
-    extern "C" fn sandbox_get_base(info: v8::FunctionCallbackInfo<v8::Value>) {
-        //assert!(validate_callback_info(info));
-        let isolate = 0;
-        let sandbox_base = internal::Sandbox::current().base() as f64;
-        //let num = v8::Number::New(isolate, sandbox_base);
-        //v8::ReturnValue::Set(info, num);
-    }
-
-    // Sandbox.byteLength
-    extern "C" fn sandbox_get_byte_length(info: v8::FunctionCallbackInfo<v8::Value>) {
-        //assert!(validate_callback_info(info));
-        let isolate = 0;
-        let sandbox_size = internal::Sandbox::current().size() as f64;
-        //let num = v8::Number::New(isolate, sandbox_size);
-        //v8::ReturnValue::Set(info, num);
-    }
-
-    // new Sandbox.MemoryView(info) -> Sandbox.MemoryView
-    extern "C" fn sandbox_memory_view(info: v8::FunctionCallbackInfo<v8::Value>) {
-        //assert!(validate_callback_info(info));
-        let isolate = 0;
-
-        //if !info.is_construct_call() {
-        //    isolate.throw_error("Sandbox.MemoryView must be invoked with 'new'");
-        //    return;
-        //}
-        //
-        //let arg1 = info.at(0).to_integer(isolate).unwrap();
-        //let arg2 = info.at(1).to_integer(isolate).unwrap();
-        //
-        //let offset = arg1.integer_value() as usize;
-        //let size = arg2.integer_value() as usize;
-        //
-        //let sandbox = internal::Sandbox::current();
-        //
-        //if offset > sandbox.size() || size > sandbox.size() || offset + size > sandbox.size() {
-        //    isolate.throw_error("The MemoryView must be entirely contained within the sandbox");
-        //    return;
-        //}
-        //
-        //let memory = unsafe {
-        //    // Allocate a buffer for the memory view
-        //    let layout = std::alloc::Layout::from_size_align(size, std::mem::align_of::<u8>()).unwrap();
-        //    let ptr = std::alloc::alloc(layout);
-        //
-        //    if ptr.is_null() {
-        //        isolate.throw_error("Out of memory: MemoryView backing store");
-        //        return;
-        //    }
-        //
-        //    // Calculate the address within the sandbox
-        //    let sandbox_address = sandbox.base() + offset;
-        //
-        //    // Copy the memory from the sandbox to the allocated buffer
-        //    std::ptr::copy_nonoverlapping(sandbox_address as *const u8, ptr, size);
-        //
-        //    // Create a Box from the raw pointer
-        //    let boxed_data = Box::from_raw(ptr as *mut [u8]);
-        //
-        //    // Convert the Box to a raw pointer and return the length
-        //    let (ptr, len) = Box::into_raw(boxed_data);
-        //
-        //    // Backing store created.
-        //    (ptr as usize, len)
-        //};
-        //
-        //let mut backing_store = v8::ArrayBuffer::new_backing_store(memory.0 as *mut c_void, memory.1, |data, length| {
-        //    // Free the data when the backing store is garbage collected
-        //    unsafe {
-        //        free_callback(data, length);
-        //    }
-        //});
-        //
-        //let buffer = v8::ArrayBuffer::with_backing_store(isolate, &mut backing_store);
-        //v8::ReturnValue::Set(info, buffer.into());
-    }
-
-    // Sandbox.getAddressOf(Object) -> Number
-    extern "C" fn sandbox_get_address_of(info: v8::FunctionCallbackInfo<v8::Value>) {
-        //assert!(validate_callback_info(info));
-        let isolate = 0;
-        //let mut obj = 0;
-        //if !get_argument_object_passed_as_reference(info, &mut obj) {
-        //    return;
-        //}
-        //
-        //// HeapObjects must be allocated inside the pointer compression cage so their
-        //// address relative to the start of the sandbox can be obtained simply by
-        //// taking the lowest 32 bits of the absolute address.
-        //let address = obj as u32;
-        ////let num = v8::Integer::NewFromUnsigned(isolate, address);
-        ////v8::ReturnValue::Set(info, num);
-    }
-
-    // Sandbox.getObjectAt(Number) -> Object
-    extern "C" fn sandbox_get_object_at(info: v8::FunctionCallbackInfo<v8::Value>) {
-        //assert!(validate_callback_info(info));
-        let isolate = 0;
-        //let mut obj = 0;
-        //if !get_argument_object_passed_as_address(info, &mut obj) {
-        //    return;
-        //}
-        //
-        //let i_isolate = isolate as *mut internal::Isolate;
-        //unsafe {
-        //    let i_isolate = &mut *i_isolate;
-        //    let handle = internal::Object::new(obj, i_isolate);
-        //    //let api_handle = internal::ToApiHandle::<v8::Value>(handle);
-        //    //v8::ReturnValue::Set(info, api_handle);
-        //}
-    }
-
-    // Sandbox.isValidObjectAt(Address) -> Bool
-    extern "C" fn sandbox_is_valid_object_at(info: v8::FunctionCallbackInfo<v8::Value>) {
-        //assert!(validate_callback_info(info));
-        //let isolate = 0;
-        //let sandbox = internal::Sandbox::current();
-        //let heap = 0;
-        //
-        //let mut obj = 0;
-        //if !get_argument_object_passed_as_address(info, &mut obj) {
-        //    return;
-        //}
-        //
-        //info.get_return_value().set(false);
-    }
-
-    // Sandbox.isWritable(Object) -> Bool
-    extern "C" fn sandbox_is_writable(info: v8::FunctionCallbackInfo<v8::Value>) {
-        sandbox_is_writable_impl(info, get_argument_object_passed_as_reference);
-    }
-
-    // Sandbox.isWritableObjectAt(Number) -> Bool
-    extern "C" fn sandbox_is_writable_object_at(info: v8::FunctionCallbackInfo<v8::Value>) {
-        sandbox_is_writable_impl(info, get_argument_object_passed_as_address);
-    }
-
-    fn sandbox_is_writable_impl(
-        info: v8::FunctionCallbackInfo<v8::Value>,
-        get_argument_object: fn(v8::FunctionCallbackInfo<v8::Value>, &mut internal::HeapObject) -> bool,
-    ) {
-        //assert!(validate_callback_info(info));
-        //
-        //let mut obj = 0;
-        //if !get_argument_object(info, &mut obj) {
-        //    return;
-        //}
-        //
-        //let chunk = 0;
-        //let is_writable = true;
-        //
-        //info.get_return_value().set(is_writable);
-    }
-
-    // Sandbox.getSizeOf(Object) -> Number
-    extern "C" fn sandbox_get_size_of(info: v8::FunctionCallbackInfo<v8::Value>) {
-        sandbox_get_size_of_impl(info, get_argument_object_passed_as_reference);
-    }
-
-    // Sandbox.getSizeOfObjectAt(Number) -> Number
-    extern "C" fn sandbox_get_size_of_object_at(info: v8::FunctionCallbackInfo<v8::Value>) {
-        sandbox_get_size_of_impl(info, get_argument_object_passed_as_address);
-    }
-
-    fn sandbox_get_size_of_impl(
-        info: v8::FunctionCallbackInfo<v8::Value>,
-        get_argument_object: fn(v8::FunctionCallbackInfo<v8::Value>, &mut internal::HeapObject) -> bool,
-    ) {
-        //assert!(validate_callback_info(info));
-        //
-        //let mut obj = 0;
-        //if !get_argument_object(info, &mut
\ No newline at end of file
+}
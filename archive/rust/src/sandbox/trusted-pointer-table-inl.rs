@@ -1,486 +1,102 @@
-// Copyright 2023 the V8 project authors. All rights reserved.
+// Copyright 2024 the V8 project authors. All rights reserved.
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-#![allow(dead_code)]
-#![allow(unused_variables)]
-
-// Adaptation of V8_SANDBOX_TRUSTED_POINTER_TABLE_INL_H_
-
-#[cfg(feature = "v8_enable_sandbox")]
-pub mod sandbox {
-    use std::sync::atomic::{AtomicU64, Ordering};
-    use std::sync::Mutex;
-
-    // Placeholder for IndirectPointerTag, adjust as needed based on actual usage
-    #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-    pub enum IndirectPointerTag {
-        Unknown,
-        Unpublished,
-        Custom(u32),
-    }
-
-    pub const UNKNOWN_INDIRECT_POINTER_TAG: IndirectPointerTag = IndirectPointerTag::Unknown;
-    pub const UNPUBLISHED_INDIRECT_POINTER_TAG: IndirectPointerTag = IndirectPointerTag::Unpublished;
-
-    // Placeholder for Address type, using usize for simplicity
-    pub type Address = usize;
-
-    // Placeholder for TrustedPointerHandle type, using u64 for simplicity
-    pub type TrustedPointerHandle = u64;
-
-    const TRUSTED_POINTER_HANDLE_SHIFT: u32 = 3; // Arbitrary value
-    pub const NULL_TRUSTED_POINTER_HANDLE: TrustedPointerHandle = 0;
-    
-    // Placeholder for Space, Sandbox, TrustedPointerPublishingScope
-    pub struct Space {}
-    pub struct Sandbox {}
-    pub struct TrustedPointerPublishingScope<'a> {
-        tracked_pointers: Mutex<Vec<&'a TrustedPointerTableEntry>>,
-    }
-
-    impl<'a> TrustedPointerPublishingScope<'a> {
-        pub fn new() -> Self {
-            TrustedPointerPublishingScope {
-                tracked_pointers: Mutex::new(Vec::new()),
-            }
-        }
-
-        pub fn track_pointer(&self, entry: &'a TrustedPointerTableEntry) {
-            self.tracked_pointers.lock().unwrap().push(entry);
-        }
-    }
-
-    impl Space {
-        pub fn belongs_to(&self, _table: &TrustedPointerTable) -> bool {
-            true // Placeholder implementation
-        }
-
-        pub fn allocate_black(&self) -> bool {
-            false // Placeholder implementation
-        }
-
-        pub fn contains(&self, _index: u32) -> bool {
-            true // Placeholder implementation
-        }
-    }
-    
-    impl Sandbox {
-        pub fn current() -> &'static Sandbox {
-            lazy_static::lazy_static! {
-                static ref SANDBOX: Sandbox = Sandbox {};
-            }
-            &SANDBOX
-        }
-        
-        pub fn contains(&self, _pointer: Address) -> bool {
-            false // Placeholder implementation
-        }
+use std::sync::atomic::Ordering;
+
+use crate::heap::heap_layout::Address;
+use crate::sandbox::external_entity_table::ExternalEntityTableEntry;
+use crate::sandbox::indirect_pointer_tag::{
+    IndirectPointerTag, K_INDIRECT_POINTER_TAG_MASK, K_TRUSTED_POINTER_TABLE_MARK_BIT,
+};
+use crate::sandbox::trusted_pointer_table::{
+    TrustedPointerHandle, TrustedPointerTable, TrustedPointerTableEntry, TrustedPointerTableSpace,
+};
+
+impl TrustedPointerTableEntry {
+    #[inline]
+    pub fn make_trusted_pointer_entry(&self, pointer: Address, tag: IndirectPointerTag, mark_as_alive: bool) {
+        let mut content = Self::encode(pointer, tag);
+        if mark_as_alive {
+            content |= K_TRUSTED_POINTER_TABLE_MARK_BIT;
+        }
+        self.content.store(content, Ordering::Relaxed);
     }
 
-    // Represents the payload of a TrustedPointerTableEntry
-    #[derive(Debug, Copy, Clone)]
-    struct Payload(u64);
-
-    impl Payload {
-        // Constants for bit manipulation.
-        const MARK_BIT: u64 = 1 << 63;
-        const FREELIST_BIT: u64 = 1 << 62;
-        const TAG_MASK: u64 = 0b11 << 60; // 2 bits for the tag.
-        const ADDRESS_MASK: u64 = !(Self::MARK_BIT | Self::FREELIST_BIT | Self::TAG_MASK);
-
-        fn for_trusted_pointer_entry(pointer: Address, tag: IndirectPointerTag) -> Self {
-            let tag_bits = match tag {
-                IndirectPointerTag::Unknown => 0,
-                IndirectPointerTag::Unpublished => 1,
-                IndirectPointerTag::Custom(val) => (val as u64) & 0b11,
-            } << 60;
-            Payload((pointer as u64) & Self::ADDRESS_MASK | tag_bits)
-        }
-
-        fn for_freelist_entry(next_entry_index: u32) -> Self {
-            Payload((next_entry_index as u64) | Self::FREELIST_BIT)
-        }
-
-        fn for_zapped_entry() -> Self {
-            Payload(0) // Or some other sentinel value.
-        }
-
-        fn set_mark_bit(&mut self) {
-            self.0 |= Self::MARK_BIT;
-        }
-
-        fn clear_mark_bit(&mut self) {
-            self.0 &= !Self::MARK_BIT;
-        }
-
-        fn has_mark_bit_set(&self) -> bool {
-            (self.0 & Self::MARK_BIT) != 0
-        }
-
-        fn contains_freelist_link(&self) -> bool {
-            (self.0 & Self::FREELIST_BIT) != 0
-        }
-
-        fn extract_freelist_link(&self) -> u32 {
-            (self.0 & !Self::FREELIST_BIT) as u32
-        }
-
-        fn untag(&self, tag: IndirectPointerTag) -> Address {
-            //The tag parameter is ignored in the Untag function.
-            (self.0 & Self::ADDRESS_MASK) as Address
-        }
-        
-        fn set_tag(&mut self, tag: IndirectPointerTag) {
-            let tag_bits = match tag {
-                IndirectPointerTag::Unknown => 0,
-                IndirectPointerTag::Unpublished => 1,
-                IndirectPointerTag::Custom(val) => (val as u64) & 0b11,
-            } << 60;
-             self.0 = (self.0 & Self::ADDRESS_MASK) | tag_bits;
-        }
-
-        fn is_tagged_with(&self, tag: IndirectPointerTag) -> bool {
-            let tag_bits = match tag {
-                IndirectPointerTag::Unknown => 0,
-                IndirectPointerTag::Unpublished => 1,
-                IndirectPointerTag::Custom(val) => (val as u64) & 0b11,
-            } << 60;
-            (self.0 & Self::TAG_MASK) == tag_bits
-        }
-
-        fn contains_pointer(&self) -> bool {
-            (self.0 & Self::FREELIST_BIT) == 0
-        }
+    /// Loads the pointer of this entry, removing `tag` with an AND. If the
+    /// entry has a different tag, bits of it remain set in the result.
+    #[inline]
+    pub fn get_pointer(&self, tag: IndirectPointerTag) -> Address {
+        let content = self.content.load(Ordering::Relaxed);
+        (content & !(tag as u64 | K_TRUSTED_POINTER_TABLE_MARK_BIT)) as Address
     }
 
-    /// Represents an entry in the TrustedPointerTable.
-    pub struct TrustedPointerTableEntry {
-        payload_: AtomicU64,
+    /// Stores `pointer` with `tag`, keeping the mark bit.
+    #[inline]
+    pub fn set_pointer(&self, pointer: Address, tag: IndirectPointerTag) {
+        let content = Self::encode(pointer, tag);
+        self.content
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |old_content| {
+                Some(content | (old_content & K_TRUSTED_POINTER_TABLE_MARK_BIT))
+            })
+            .unwrap();
     }
 
-    impl TrustedPointerTableEntry {
-        pub fn new() -> Self {
-            TrustedPointerTableEntry {
-                payload_: AtomicU64::new(0),
-            }
-        }
-
-        pub fn make_trusted_pointer_entry(&self, pointer: Address, tag: IndirectPointerTag, mark_as_alive: bool) {
-            let mut payload = Payload::for_trusted_pointer_entry(pointer, tag);
-            if mark_as_alive {
-                payload.set_mark_bit();
-            }
-            self.payload_.store(payload.0, Ordering::Relaxed);
-        }
-
-        pub fn make_freelist_entry(&self, next_entry_index: u32) {
-            let payload = Payload::for_freelist_entry(next_entry_index);
-            self.payload_.store(payload.0, Ordering::Relaxed);
-        }
-
-        pub fn make_zapped_entry(&self) {
-            let payload = Payload::for_zapped_entry();
-            self.payload_.store(payload.0, Ordering::Relaxed);
-        }
-
-        pub fn get_pointer(&self, tag: IndirectPointerTag) -> Address {
-            assert!(!self.is_freelist_entry());
-            Payload(self.payload_.load(Ordering::Relaxed)).untag(tag)
-        }
-
-        pub fn set_pointer(&self, pointer: Address, tag: IndirectPointerTag) {
-            assert!(!self.is_freelist_entry());
-            assert!(!Payload(self.payload_.load(Ordering::Relaxed)).has_mark_bit_set());
-            let new_payload = Payload::for_trusted_pointer_entry(pointer, tag);
-            assert!(!Payload::for_trusted_pointer_entry(pointer, tag).has_mark_bit_set());
-            self.payload_.store(new_payload.0, Ordering::Relaxed);
-        }
-
-        pub fn has_pointer(&self, tag: IndirectPointerTag) -> bool {
-            let payload = Payload(self.payload_.load(Ordering::Relaxed));
-            if !payload.contains_pointer() {
-                return false;
-            }
-            tag == UNKNOWN_INDIRECT_POINTER_TAG || payload.is_tagged_with(tag)
-        }
-
-        pub fn overwrite_tag(&self, tag: IndirectPointerTag) {
-            assert_eq!(tag, UNPUBLISHED_INDIRECT_POINTER_TAG);
-
-            let old_payload = Payload(self.payload_.load(Ordering::Relaxed));
-            let mut new_payload = old_payload;
-            new_payload.set_tag(tag);
-
-            let success = self.payload_.compare_exchange(
-                old_payload.0,
-                new_payload.0,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ).is_ok();
-
-            assert!(success || Payload(self.payload_.load(Ordering::Relaxed)).is_tagged_with(UNPUBLISHED_INDIRECT_POINTER_TAG));
-        }
-
-        pub fn is_freelist_entry(&self) -> bool {
-            Payload(self.payload_.load(Ordering::Relaxed)).contains_freelist_link()
-        }
-
-        pub fn get_next_freelist_entry_index(&self) -> u32 {
-            Payload(self.payload_.load(Ordering::Relaxed)).extract_freelist_link()
-        }
-
-        pub fn mark(&self) {
-            let old_payload = Payload(self.payload_.load(Ordering::Relaxed));
-            assert!(old_payload.contains_pointer());
-
-            let mut new_payload = old_payload;
-            new_payload.set_mark_bit();
-
-            let success = self.payload_.compare_exchange(
-                old_payload.0,
-                new_payload.0,
-                Ordering::Relaxed,
-                Ordering::Relaxed,
-            ).is_ok();
-            assert!(success || Payload(self.payload_.load(Ordering::Relaxed)).has_mark_bit_set());
-        }
-
-        pub fn unmark(&self) {
-            let mut payload = Payload(self.payload_.load(Ordering::Relaxed));
-            payload.clear_mark_bit();
-            self.payload_.store(payload.0, Ordering::Relaxed);
-        }
-
-        pub fn is_marked(&self) -> bool {
-            Payload(self.payload_.load(Ordering::Relaxed)).has_mark_bit_set()
+    #[inline]
+    pub fn has_pointer(&self, tag: IndirectPointerTag) -> bool {
+        let actual_tag = Self::extract_tag(self.content.load(Ordering::Relaxed));
+        if tag == IndirectPointerTag::kUnknownIndirectPointerTag {
+            return !self.is_freelist_entry() && actual_tag != 0;
         }
+        actual_tag == tag as u64
     }
 
-    /// Represents the TrustedPointerTable.
-    pub struct TrustedPointerTable {
-        entries: Vec<TrustedPointerTableEntry>,
-        // Placeholder for free list management
-        free_list_head: Mutex<Option<u32>>,
-        size: usize,
-    }
-
-    impl TrustedPointerTable {
-        pub fn new(size: usize) -> Self {
-            let mut entries = Vec::with_capacity(size);
-            for _ in 0..size {
-                entries.push(TrustedPointerTableEntry::new());
-            }
-            let mut table = TrustedPointerTable {
-                entries,
-                free_list_head: Mutex::new(Some(1)), // Entry 0 is reserved
-                size,
-            };
-
-            // Initialize the freelist (starts from index 1)
-            for i in 1..table.size - 1 {
-                table.entries[i].make_freelist_entry((i + 1) as u32);
-            }
-            table.entries[table.size - 1].make_freelist_entry(0); // Last entry points to null
-            table
-        }
-
-        pub fn is_unpublished(&self, handle: TrustedPointerHandle) -> bool {
-            let index = self.handle_to_index(handle);
-            self.at(index).has_pointer(UNPUBLISHED_INDIRECT_POINTER_TAG)
-        }
-
-        pub fn get(&self, handle: TrustedPointerHandle, tag: IndirectPointerTag) -> Address {
-            let index = self.handle_to_index(handle);
-            assert!(index == 0 || self.at(index).has_pointer(tag));
-            self.at(index).get_pointer(tag)
-        }
-
-        pub fn get_maybe_unpublished(&self, handle: TrustedPointerHandle, tag: IndirectPointerTag) -> Address {
-            let index = self.handle_to_index(handle);
-            let entry = self.at(index);
-            if entry.has_pointer(UNPUBLISHED_INDIRECT_POINTER_TAG) {
-                return entry.get_pointer(UNPUBLISHED_INDIRECT_POINTER_TAG);
-            }
-            assert!(index == 0 || entry.has_pointer(tag));
-            entry.get_pointer(tag)
-        }
-
-        pub fn set(&self, handle: TrustedPointerHandle, pointer: Address, tag: IndirectPointerTag) {
-            assert_ne!(NULL_TRUSTED_POINTER_HANDLE, handle);
-            self.validate(pointer, tag);
-            let index = self.handle_to_index(handle);
-            self.at(index).set_pointer(pointer, tag);
-        }
-
-        pub fn allocate_and_initialize_entry(
-            &self,
-            space: &Space,
-            pointer: Address,
-            tag: IndirectPointerTag,
-            scope: Option<&mut TrustedPointerPublishingScope>,
-        ) -> TrustedPointerHandle {
-            self.validate(pointer, tag);
-            let index = self.allocate_entry(space);
-            self.at(index)
-                .make_trusted_pointer_entry(pointer, tag, space.allocate_black());
-            if let Some(s) = scope {
-                s.track_pointer(self.at(index));
-            }
-            self.index_to_handle(index)
-        }
-
-        pub fn mark(&self, space: &Space, handle: TrustedPointerHandle) {
-            if handle == NULL_TRUSTED_POINTER_HANDLE {
-                return;
-            }
-
-            let index = self.handle_to_index(handle);
-            assert!(space.contains(index as u32));
-
-            self.at(index).mark();
-        }
-
-        pub fn zap(&self, handle: TrustedPointerHandle) {
-            let index = self.handle_to_index(handle);
-            self.at(index).make_zapped_entry();
-        }
-
-        pub fn iterate_active_entries_in<Callback>(&self, space: &Space, mut callback: Callback)
-        where
-            Callback: FnMut(TrustedPointerHandle, Address),
-        {
-            self.iterate_entries_in(space, |index| {
-                if !self.at(index).is_freelist_entry() {
-                    let pointer = self.at(index).get_pointer(UNKNOWN_INDIRECT_POINTER_TAG);
-                    callback(self.index_to_handle(index), pointer);
-                }
-            });
-        }
-
-        fn handle_to_index(&self, handle: TrustedPointerHandle) -> usize {
-            let index = handle >> TRUSTED_POINTER_HANDLE_SHIFT;
-            assert_eq!(handle, index << TRUSTED_POINTER_HANDLE_SHIFT);
-            index as usize
-        }
-
-        fn index_to_handle(&self, index: usize) -> TrustedPointerHandle {
-            let handle = (index as TrustedPointerHandle) << TRUSTED_POINTER_HANDLE_SHIFT;
-            assert_eq!(index as TrustedPointerHandle, handle >> TRUSTED_POINTER_HANDLE_SHIFT);
-            handle
-        }
-
-        fn validate(&self, pointer: Address, tag: IndirectPointerTag) {
-            if self.is_trusted_space_migration_in_progress_for_objects_with_tag(tag) {
-                assert!(Sandbox::current().contains(pointer));
-                return;
-            }
-
-            assert!(!self.inside_sandbox(pointer));
-        }
-
-        fn at(&self, index: usize) -> &TrustedPointerTableEntry {
-            &self.entries[index]
-        }
-
-        fn inside_sandbox(&self, _pointer: Address) -> bool {
-            false
-        }
-
-        fn is_trusted_space_migration_in_progress_for_objects_with_tag(&self, _tag: IndirectPointerTag) -> bool {
-            false
-        }
-
-        fn allocate_entry(&self, _space: &Space) -> usize {
-            let mut head = self.free_list_head.lock().unwrap();
-            let index = match *head {
-                Some(i) => {
-                    let next_free = self.entries[i as usize].get_next_freelist_entry_index();
-                    *head = if next_free == 0 {
-                        None
-                    } else {
-                        Some(next_free)
-                    };
-                    i as usize
-                }
-                None => panic!("TrustedPointerTable out of entries"),
-            };
-
-            index
-        }
-
-        fn iterate_entries_in<Callback>(&self, _space: &Space, mut callback: Callback)
-        where
-            Callback: FnMut(usize),
-        {
-            for index in 0..self.size {
-                callback(index);
-            }
-        }
+    fn encode(pointer: Address, tag: IndirectPointerTag) -> u64 {
+        assert_eq!(
+            pointer as u64 & (K_INDIRECT_POINTER_TAG_MASK | K_TRUSTED_POINTER_TABLE_MARK_BIT),
+            0,
+            "trusted pointer out of range"
+        );
+        debug_assert!(tag.is_valid_indirect_pointer_tag() || tag == IndirectPointerTag::kUnpublishedIndirectPointerTag);
+        pointer as u64 | tag as u64
     }
 }
 
-#[cfg(not(feature = "v8_enable_sandbox"))]
-pub mod sandbox {
-    // Define empty stubs when sandbox is not enabled
-    pub enum IndirectPointerTag {}
-    pub type Address = usize;
-    pub type TrustedPointerHandle = u64;
-    pub struct TrustedPointerTableEntry {}
-    pub struct TrustedPointerTable {}
-    pub struct Space {}
-    pub struct Sandbox {}
-    pub struct TrustedPointerPublishingScope {}
-
-    impl TrustedPointerTableEntry {
-        pub fn new() -> Self { TrustedPointerTableEntry{} }
-        pub fn make_trusted_pointer_entry(&self, _: Address, _: IndirectPointerTag, _: bool) {}
-        pub fn make_freelist_entry(&self, _: u32) {}
-        pub fn make_zapped_entry(&self) {}
-        pub fn get_pointer(&self, _: IndirectPointerTag) -> Address { 0 }
-        pub fn set_pointer(&self, _: Address, _: IndirectPointerTag) {}
-        pub fn has_pointer(&self, _: IndirectPointerTag) -> bool { false }
-        pub fn overwrite_tag(&self, _: IndirectPointerTag) {}
-        pub fn is_freelist_entry(&self) -> bool { false }
-        pub fn get_next_freelist_entry_index(&self) -> u32 { 0 }
-        pub fn mark(&self) {}
-        pub fn unmark(&self) {}
-        pub fn is_marked(&self) -> bool { false }
+impl TrustedPointerTable {
+    #[inline]
+    pub fn allocate_and_initialize_entry(
+        &self,
+        space: &TrustedPointerTableSpace,
+        pointer: Address,
+        tag: IndirectPointerTag,
+    ) -> TrustedPointerHandle {
+        let index = self.allocate_entry(space);
+        self.at(index)
+            .make_trusted_pointer_entry(pointer, tag, space.allocate_black());
+        Self::index_to_handle(index)
     }
 
-    impl TrustedPointerTable {
-         pub fn new(_size: usize) -> Self { TrustedPointerTable {} }
-        pub fn is_unpublished(&self, _: TrustedPointerHandle) -> bool { false }
-        pub fn get(&self, _: TrustedPointerHandle, _: IndirectPointerTag) -> Address { 0 }
-        pub fn get_maybe_unpublished(&self, _: TrustedPointerHandle, _: IndirectPointerTag) -> Address { 0 }
-        pub fn set(&self, _: TrustedPointerHandle, _: Address, _: IndirectPointerTag) {}
-        pub fn allocate_and_initialize_entry(&self, _: &Space, _: Address, _: IndirectPointerTag, _: Option<&mut TrustedPointerPublishingScope>) -> TrustedPointerHandle { 0 }
-        pub fn mark(&self, _: &Space, _: TrustedPointerHandle) {}
-        pub fn zap(&self, _: TrustedPointerHandle) {}
-        pub fn iterate_active_entries_in<Callback>(&self, _: &Space, _: Callback) where Callback: FnMut(TrustedPointerHandle, Address) {}
+    #[inline]
+    pub fn get(&self, handle: TrustedPointerHandle, tag: IndirectPointerTag) -> Address {
+        self.at(Self::handle_to_index(handle)).get_pointer(tag)
     }
 
-    impl TrustedPointerPublishingScope {
-        pub fn new() -> Self { TrustedPointerPublishingScope {} }
-        pub fn track_pointer(&self, _: &TrustedPointerTableEntry) {}
+    #[inline]
+    pub fn set(&self, handle: TrustedPointerHandle, pointer: Address, tag: IndirectPointerTag) {
+        debug_assert_ne!(handle, 0, "the null entry is read-only");
+        self.at(Self::handle_to_index(handle)).set_pointer(pointer, tag);
     }
 
-    impl Space {
-        pub fn belongs_to(&self, _table: &TrustedPointerTable) -> bool { false }
-        pub fn allocate_black(&self) -> bool { false }
-        pub fn contains(&self, _index: u32) -> bool { false }
+    #[inline]
+    pub fn has_pointer(&self, handle: TrustedPointerHandle, tag: IndirectPointerTag) -> bool {
+        self.at(Self::handle_to_index(handle)).has_pointer(tag)
     }
-    
-    impl Sandbox {
-        pub fn current() -> &'static Sandbox {
-            lazy_static::lazy_static! {
-                static ref SANDBOX: Sandbox = Sandbox {};
-            }
-            &SANDBOX
-        }
-        
-        pub fn contains(&self, _pointer: Address) -> bool {
-            false
+
+    /// Marks the entry of `handle` as alive.
+    #[inline]
+    pub fn mark(&self, handle: TrustedPointerHandle) {
+        if handle != 0 {
+            self.at(Self::handle_to_index(handle)).set_mark_bit();
         }
     }
-}
\ No newline at end of file
+}
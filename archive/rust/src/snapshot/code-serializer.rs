@@ -3,266 +3,287 @@
 // found in the LICENSE file.
 
 pub mod code_serializer {
-    use std::sync::Mutex;
-
-    //use crate::base::macros::*; // Requires translation of C++ macros
-    //use crate::codegen::script_details::*; // Requires translation of codegen module
-    //use crate::snapshot::serializer::*; // Requires translation of serializer module
-    //use crate::snapshot::snapshot_data::*; // Requires translation of snapshot_data module
-
-    // Placeholder for v8 types. Needs proper definition based on v8's internal structure.
-    pub struct Isolate {}
-    pub struct LocalIsolate {}
-    pub struct HeapObject {}
-    pub struct SharedFunctionInfo {}
-    pub struct Script {}
-    pub struct String {}
-    pub struct FixedArray {}
-    pub struct ScriptCompiler {
-        pub cached_data: CachedData
+    use std::collections::HashMap;
+    use std::mem::size_of;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use crate::heap::heap_layout::{BodyKind, HeapObject, InstanceType, Tagged};
+    use crate::snapshot::deserializer::deserializer::{Deserializer, ExternalObjects};
+    use crate::snapshot::serializer::serializer::{ReferenceEncoder, Serializer};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{
+        ExternalReference, RootIndex, SerializationStatistics,
+    };
+    use crate::snapshot::snapshot::snapshot::{layout, Isolate, Snapshot};
+    use crate::snapshot::snapshot_data::internal::SerializedData;
+    use crate::snapshot::snapshot_utils::snapshot_utils::checksum;
+    use crate::utils::version::version::get_version_static;
+
+    static FLAG_HASH: AtomicU32 = AtomicU32::new(0);
+
+    /// Hash of the flags that affect code generation, i.e. FlagList::Hash().
+    /// Code caches produced under a different flag configuration are
+    /// rejected with kFlagsMismatch.
+    pub fn flag_hash() -> u32 {
+        FLAG_HASH.load(Ordering::Relaxed)
     }
-    impl ScriptCompiler {
-        pub struct CachedData {
-            pub data: Vec<u8>,
-            pub length: i32,
-            pub owns_data: bool,
-            pub rejected: bool,
-            pub compatibility_check_result: SerializedCodeSanityCheckResult,
-        }
+
+    pub fn set_flag_hash(hash: u32) {
+        FLAG_HASH.store(hash, Ordering::Relaxed);
     }
 
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum SerializedCodeSanityCheckResult {
-        kSuccess,
-        kVersionMismatch,
-        kArchitectureMismatch,
-        kPlatformMismatch,
-        kNoCacheNoReason,
-        kInvalidSourceHash,
-        kInvalidFlags,
-        kInvalidSnapshotChecksum,
-        k Corrupted,
-        kLast,
+        kSuccess = 0,
+        kMagicNumberMismatch = 1,
+        kVersionMismatch = 2,
+        kSourceMismatch = 3,
+        kFlagsMismatch = 5,
+        kChecksumMismatch = 6,
+        kInvalidHeader = 7,
+        kLengthMismatch = 8,
+        kReadOnlySnapshotChecksumMismatch = 9,
     }
 
-    // Placeholder handles
-    pub struct Handle<T> {
-        pub ptr: *mut T,
-    }
-    impl<T> Handle<T> {
-        pub fn new(ptr: *mut T) -> Self {
-            Handle { ptr }
-        }
+    impl SerializedCodeSanityCheckResult {
+        pub const kLast: SerializedCodeSanityCheckResult = SerializedCodeSanityCheckResult::kReadOnlySnapshotChecksumMismatch;
     }
 
-    pub struct DirectHandle<T> {
-        pub ptr: *mut T,
-    }
-    impl<T> DirectHandle<T> {
-        pub fn new(ptr: *mut T) -> Self {
-            DirectHandle { ptr }
-        }
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct ScriptOriginOptions {
+        is_module: bool,
     }
 
-    pub struct MaybeDirectHandle<T> {
-        pub ptr: *mut T, // Option<*mut T>
-    }
-    impl<T> MaybeDirectHandle<T> {
-        pub fn is_null(&self) -> bool {
-            self.ptr.is_null()
+    impl ScriptOriginOptions {
+        pub fn new(is_module: bool) -> Self {
+            ScriptOriginOptions { is_module }
         }
-    }
 
-    pub struct IndirectHandle<T> {
-        pub ptr: *mut T,
-    }
-
-    pub struct LocalHeap {}
-
-    pub struct ScriptDetails {}
-    pub struct ScriptOriginOptions {}
-
-    // Mock DeleteArray function.  Needs proper implementation.
-    unsafe fn delete_array<T>(ptr: *mut T) {
-        if !ptr.is_null() {
-            // Convert the raw pointer back into a Box and let it deallocate the memory.
-            drop(Box::from_raw(ptr));
+        pub fn is_module(&self) -> bool {
+            self.is_module
         }
     }
 
+    /// Owned copy of code cache data handed to the deserializer.
+    #[derive(Debug, Clone, Default)]
     pub struct AlignedCachedData {
-        owns_data: bool,
-        rejected: bool,
-        data_: *const u8,
-        length_: i32,
+        data_: Vec<u8>,
+        rejected_: bool,
     }
 
     impl AlignedCachedData {
-        pub fn new(data: *const u8, length: i32) -> Self {
-            AlignedCachedData {
-                owns_data: false,
-                rejected: false,
-                data_: data,
-                length_: length,
-            }
+        pub fn new(data: &[u8]) -> Self {
+            AlignedCachedData { data_: data.to_vec(), rejected_: false }
         }
 
-        pub fn data(&self) -> *const u8 {
-            self.data_
+        pub fn data(&self) -> &[u8] {
+            &self.data_
         }
 
-        pub fn length(&self) -> i32 {
-            self.length_
+        pub fn length(&self) -> usize {
+            self.data_.len()
         }
 
         pub fn rejected(&self) -> bool {
-            self.rejected
+            self.rejected_
         }
 
         pub fn reject(&mut self) {
-            self.rejected = true;
+            self.rejected_ = true;
         }
 
-        pub fn has_data_ownership(&self) -> bool {
-            self.owns_data
-        }
-
-        pub fn acquire_data_ownership(&mut self) {
-            debug_assert!(!self.owns_data);
-            self.owns_data = true;
+        pub fn into_data(self) -> Vec<u8> {
+            self.data_
         }
+    }
 
-        pub fn release_data_ownership(&mut self) {
-            debug_assert!(self.owns_data);
-            self.owns_data = false;
-        }
+    /// Compiled code cache data as exposed through the ScriptCompiler API.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct CachedData {
+        pub data: Vec<u8>,
+        pub rejected: bool,
+        pub compatibility_check_result: SerializedCodeSanityCheckResult,
     }
 
-    impl Drop for AlignedCachedData {
-        fn drop(&mut self) {
-            if self.owns_data {
-                unsafe {
-                    delete_array(self.data_ as *mut u8);
-                }
+    impl CachedData {
+        pub fn new(data: Vec<u8>) -> Self {
+            CachedData {
+                data,
+                rejected: false,
+                compatibility_check_result: SerializedCodeSanityCheckResult::kSuccess,
             }
         }
-    }
 
-    // Implement Debug trait manually to avoid the need for data_ to implement Debug.
-    impl std::fmt::Debug for AlignedCachedData {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            f.debug_struct("AlignedCachedData")
-                .field("owns_data", &self.owns_data)
-                .field("rejected", &self.rejected)
-                .field("length_", &self.length_)
-                .finish()
+        /// Checks whether the cache could be consumed by |isolate|, without
+        /// comparing the source.
+        pub fn compatibility_check(&self, isolate: &Isolate) -> SerializedCodeSanityCheckResult {
+            let scd = SerializedCodeData::new_with_data(self.data.clone());
+            scd.sanity_check_without_source(Snapshot::read_only_snapshot_checksum(isolate))
         }
     }
 
-    // Placeholder for PersistentHandles. Needs proper definition based on v8's internal structure.
-    pub struct PersistentHandles {}
-
-    // Placeholder for BackgroundMergeTask. Needs proper definition based on v8's internal structure.
-    pub struct BackgroundMergeTask {}
-
-    pub struct OffThreadDeserializeData {
-        maybe_result: MaybeDirectHandle<SharedFunctionInfo>,
-        scripts: Vec<IndirectHandle<Script>>,
-        persistent_handles: Option<Box<PersistentHandles>>,
-        sanity_check_result: SerializedCodeSanityCheckResult,
+    /// References from a code cache may point to read-only objects, which
+    /// are encoded as roots, and to the script source, which the embedder
+    /// supplies again when consuming the cache. Everything else is
+    /// serialized into the cache, except for objects that cannot be cached:
+    /// read-only objects without a root and context-specific objects. Those
+    /// are recorded in |unsupported_object_| and make serialization fail.
+    struct CodeSerializerEncoder<'a> {
+        isolate_: &'a Isolate,
+        source_: HeapObject,
+        root_indices_: &'a HashMap<HeapObject, RootIndex>,
+        unsupported_object_: Option<HeapObject>,
     }
 
-    impl OffThreadDeserializeData {
-        pub fn has_result(&self) -> bool {
-            self.maybe_result.is_null()
-        }
-
-        pub fn get_only_script(&self, _heap: &LocalHeap) -> DirectHandle<Script> {
-            // Implementation depends on how scripts are managed
-            DirectHandle {
-                ptr: std::ptr::null_mut()
+    impl ReferenceEncoder for CodeSerializerEncoder<'_> {
+        fn encode_external(&mut self, object: HeapObject) -> Option<ExternalReference> {
+            let source_reference = ExternalReference::Attached(CodeSerializer::K_SOURCE_OBJECT_REFERENCE);
+            if object == self.source_ {
+                return Some(source_reference);
             }
+            let supported = if self.isolate_.is_read_only(object) {
+                if let Some(&root_index) = self.root_indices_.get(&object) {
+                    return Some(ExternalReference::Root(root_index));
+                }
+                false
+            } else {
+                !matches!(
+                    object.map().instance_type(),
+                    InstanceType::NATIVE_CONTEXT_TYPE
+                        | InstanceType::JS_GLOBAL_OBJECT_TYPE
+                        | InstanceType::JS_GLOBAL_PROXY_TYPE
+                        | InstanceType::JS_API_OBJECT_TYPE
+                )
+            };
+            if supported {
+                return None;
+            }
+            // Keep the stream well-formed; the result is discarded anyway.
+            self.unsupported_object_.get_or_insert(object);
+            Some(source_reference)
         }
     }
 
+    /// Serializes a compiled SharedFunctionInfo tree, i.e. the bytecode,
+    /// scope infos and feedback metadata of a script's functions, so that
+    /// the script can be set up again without parsing it.
     pub struct CodeSerializer {
+        serializer_: Serializer,
         source_hash_: u32,
-        //no_gc_: DisallowGarbageCollection, // Needs translation of DisallowGarbageCollection
     }
 
     impl CodeSerializer {
-        pub fn serialize(isolate: &mut Isolate, info: Handle<SharedFunctionInfo>) -> *mut ScriptCompiler::CachedData {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let data: Vec<u8> = vec![1,2,3]; //Example data
-            let length: i32 = data.len() as i32;
-            let owned_data = data.clone();
-            let cached_data = ScriptCompiler::CachedData{
-                data,
-                length,
-                owns_data: true,
-                rejected: false,
-                compatibility_check_result: SerializedCodeSanityCheckResult::kSuccess
-            };
-            let boxed_data = Box::new(cached_data);
-            Box::into_raw(boxed_data)
+        /// The script source is the only attached object.
+        pub const K_SOURCE_OBJECT_REFERENCE: u32 = 0;
 
+        fn new(source_hash: u32) -> Self {
+            CodeSerializer { serializer_: Serializer::new(), source_hash_: source_hash }
         }
 
-        pub fn serialize_shared_function_info(&mut self, info: Handle<SharedFunctionInfo>) -> *mut AlignedCachedData {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let data: Vec<u8> = vec![4,5,6]; //Example data
-            let length: i32 = data.len() as i32;
-            let data_ptr = data.as_ptr();
-            let cached_data = AlignedCachedData::new(data_ptr, length);
-
-            let boxed_data = Box::new(cached_data);
-            Box::into_raw(boxed_data)
+        /// Produces a code cache for the top-level SharedFunctionInfo |info|,
+        /// or returns None if the function tree cannot be cached.
+        pub fn serialize(isolate: &Isolate, info: HeapObject, origin_options: ScriptOriginOptions) -> Option<CachedData> {
+            let source = isolate.script_source(Self::script_of(info));
+            let source_hash = SerializedCodeData::source_hash(&isolate.string_value(source), origin_options);
 
+            let mut serializer = CodeSerializer::new(source_hash);
+            let cached_data = serializer.serialize_shared_function_info(isolate, info)?;
+            Some(CachedData::new(cached_data.into_data()))
         }
 
-        pub fn deserialize(
-            isolate: &mut Isolate,
-            cached_data: *mut AlignedCachedData,
-            source: DirectHandle<String>,
-            script_details: &ScriptDetails,
-            maybe_cached_script: MaybeDirectHandle<Script>,
-        ) -> MaybeDirectHandle<SharedFunctionInfo> {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            MaybeDirectHandle{
-                ptr: std::ptr::null_mut()
+        pub fn serialize_shared_function_info(&mut self, isolate: &Isolate, info: HeapObject) -> Option<AlignedCachedData> {
+            let root_indices = Self::root_indices(isolate);
+            let mut encoder = CodeSerializerEncoder {
+                isolate_: isolate,
+                source_: isolate.script_source(Self::script_of(info)),
+                root_indices_: &root_indices,
+                unsupported_object_: None,
+            };
+            self.serializer_.serialize_root(Tagged::strong(info), &mut encoder);
+            self.serializer_.serialize_deferred_objects(&mut encoder);
+            if encoder.unsupported_object_.is_some() {
+                return None;
             }
-        }
 
-        pub fn start_deserialize_off_thread(
-            isolate: &mut LocalIsolate,
-            cached_data: *mut AlignedCachedData,
-        ) -> OffThreadDeserializeData {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            OffThreadDeserializeData {
-                maybe_result: MaybeDirectHandle{
-                    ptr: std::ptr::null_mut()
-                },
-                scripts: Vec::new(),
-                persistent_handles: None,
-                sanity_check_result: SerializedCodeSanityCheckResult::kSuccess,
-            }
+            let read_only_snapshot_checksum = Snapshot::read_only_snapshot_checksum(isolate);
+            Some(SerializedCodeData::new(self.serializer_.payload(), self, read_only_snapshot_checksum).get_script_data())
         }
 
-        pub fn finish_off_thread_deserialize(
+        /// Rehydrates the SharedFunctionInfo tree stored in |cached_data|
+        /// for |source|. The deserialized script is added to the isolate's
+        /// script list. If the cache fails the sanity checks, or holds
+        /// something other than a SharedFunctionInfo tree, |cached_data| is
+        /// rejected and None is returned; |sanity_check_result| tells which
+        /// check failed, if any.
+        pub fn deserialize(
             isolate: &mut Isolate,
-            data: OffThreadDeserializeData,
-            cached_data: *mut AlignedCachedData,
-            source: DirectHandle<String>,
-            script_details: &ScriptDetails,
-            background_merge_task: *mut BackgroundMergeTask,
-        ) -> MaybeDirectHandle<SharedFunctionInfo> {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            MaybeDirectHandle{
-                ptr: std::ptr::null_mut()
+            cached_data: &mut AlignedCachedData,
+            source: &str,
+            origin_options: ScriptOriginOptions,
+            sanity_check_result: &mut SerializedCodeSanityCheckResult,
+        ) -> Option<HeapObject> {
+            let expected_source_hash = SerializedCodeData::source_hash(source, origin_options);
+            *sanity_check_result = SerializedCodeSanityCheckResult::kSuccess;
+            let scd = match SerializedCodeData::from_cached_data(isolate, cached_data, expected_source_hash) {
+                Ok(scd) => scd,
+                Err(result) => {
+                    *sanity_check_result = result;
+                    cached_data.reject();
+                    return None;
+                }
+            };
+
+            let source = isolate.allocate_string(source);
+            let roots = isolate.read_only_roots();
+            let attached_objects = [source];
+            let external = ExternalObjects { roots: &roots, attached_objects: &attached_objects, ..Default::default() };
+
+            let mut deserializer = Deserializer::new(scd.payload());
+            deserializer.read_root(isolate.heap(), &external);
+            deserializer.read_deferred_objects(isolate.heap(), &external);
+            let info = deserializer.take_roots()[0]
+                .get_heap_object()
+                .filter(|info| info.map().instance_type() == InstanceType::SHARED_FUNCTION_INFO_TYPE);
+            let script = info.and_then(|info| info.get(layout::K_SHARED_FUNCTION_INFO_SCRIPT_SLOT).get_heap_object());
+            let (Some(info), Some(script)) = (info, script) else {
+                cached_data.reject();
+                return None;
+            };
+            if deserializer.source().has_more() || script.map().instance_type() != InstanceType::SCRIPT_TYPE {
+                cached_data.reject();
+                return None;
+            }
+            Self::internalize_strings(isolate, deserializer.new_objects());
+
+            isolate.register_script(script);
+            Some(info)
+        }
+
+        /// Internalized strings in the cache were deserialized as copies;
+        /// replace them by the string table entries of this isolate.
+        fn internalize_strings(isolate: &mut Isolate, new_objects: &[HeapObject]) {
+            let mut replacements = HashMap::new();
+            for &object in new_objects {
+                if object.map().instance_type() == InstanceType::INTERNALIZED_STRING_TYPE {
+                    let internalized = isolate.internalize(object);
+                    if internalized != object {
+                        replacements.insert(object, internalized);
+                    }
+                }
+            }
+            if replacements.is_empty() {
+                return;
+            }
+            for &object in new_objects {
+                if object.map().body_kind() == BodyKind::Data {
+                    continue;
+                }
+                for index in 0..object.length() {
+                    let value = object.get(index);
+                    if let Some(&replacement) = value.get_heap_object().and_then(|target| replacements.get(&target)) {
+                        isolate.heap().set(object, index, value.with_object(replacement));
+                    }
+                }
             }
         }
 
@@ -270,57 +291,50 @@ pub mod code_serializer {
             self.source_hash_
         }
 
-        fn serialize_generic(&mut self, heap_object: Handle<HeapObject>, slot_type: i32) {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
+        pub fn statistics(&self) -> &SerializationStatistics {
+            self.serializer_.statistics()
         }
 
-        fn serialize_object_impl(&mut self, o: Handle<HeapObject>, slot_type: i32) {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
+        fn script_of(info: HeapObject) -> HeapObject {
+            info.get(layout::K_SHARED_FUNCTION_INFO_SCRIPT_SLOT)
+                .get_heap_object()
+                .expect("SharedFunctionInfo has no script")
         }
 
-        fn new(isolate: &mut Isolate, source_hash: u32) -> Self {
-            CodeSerializer {
-                source_hash_: source_hash,
+        fn root_indices(isolate: &Isolate) -> HashMap<HeapObject, RootIndex> {
+            let mut root_indices = HashMap::new();
+            for (root_index, object) in isolate.read_only_roots().into_iter().enumerate() {
+                root_indices.entry(object).or_insert(root_index as RootIndex);
             }
+            root_indices
         }
     }
 
-    impl Drop for CodeSerializer {
-        fn drop(&mut self) {
-            //self.output_statistics("CodeSerializer");
-        }
-    }
+    const K_UINT32_SIZE: u32 = size_of::<u32>() as u32;
+    const K_POINTER_SIZE: u32 = 8;
 
-    // Placeholder for SerializedData. Needs proper definition based on v8's internal structure.
-    pub struct SerializedData {
-        data_: *mut u8,
-        size_: i32,
-    }
-    impl SerializedData{
-        fn new(data_: *mut u8, size_: i32) -> Self{
-            SerializedData {
-                data_,
-                size_,
-            }
-        }
+    const fn pointer_size_align(size: u32) -> u32 {
+        (size + K_POINTER_SIZE - 1) & !(K_POINTER_SIZE - 1)
     }
 
+    /// Wrapper around ScriptData to provide code-serializer-specific
+    /// functionality. The layout is:
+    ///   ... magic number
+    ///   ... version hash
+    ///   ... source hash
+    ///   ... flag hash
+    ///   ... read-only snapshot checksum
+    ///   ... payload length
+    ///   ... payload checksum
+    ///   ... padding to pointer size
+    ///   ... serialized payload
+    #[derive(Debug, Clone)]
     pub struct SerializedCodeData {
         serialized_data: SerializedData,
     }
 
-    const K_MAGIC_NUMBER_OFFSET: u32 = 0; // Placeholder value
-    const K_UINT32_SIZE: u32 = 4; // Size of uint32_t in bytes
-    const POINTER_SIZE: u32 = 8; // Assuming 64-bit architecture, size of a pointer in bytes
-
-    fn pointer_size_align(size: u32) -> u32 {
-        (size + (POINTER_SIZE - 1)) & !(POINTER_SIZE - 1)
-    }
-
     impl SerializedCodeData {
-        pub const K_VERSION_HASH_OFFSET: u32 = K_MAGIC_NUMBER_OFFSET + K_UINT32_SIZE;
+        pub const K_VERSION_HASH_OFFSET: u32 = SerializedData::K_MAGIC_NUMBER_OFFSET + K_UINT32_SIZE;
         pub const K_SOURCE_HASH_OFFSET: u32 = Self::K_VERSION_HASH_OFFSET + K_UINT32_SIZE;
         pub const K_FLAG_HASH_OFFSET: u32 = Self::K_SOURCE_HASH_OFFSET + K_UINT32_SIZE;
         pub const K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET: u32 = Self::K_FLAG_HASH_OFFSET + K_UINT32_SIZE;
@@ -329,159 +343,455 @@ pub mod code_serializer {
         pub const K_UNALIGNED_HEADER_SIZE: u32 = Self::K_CHECKSUM_OFFSET + K_UINT32_SIZE;
         pub const K_HEADER_SIZE: u32 = pointer_size_align(Self::K_UNALIGNED_HEADER_SIZE);
 
+        /// Upper bound on the payload length accepted when consuming.
+        const K_MAX_PAYLOAD_LENGTH: u32 = 1 << 30;
+
+        /// Used when consuming.
         pub fn from_cached_data(
-            isolate: &mut Isolate,
-            cached_data: *mut AlignedCachedData,
+            isolate: &Isolate,
+            cached_data: &AlignedCachedData,
             expected_source_hash: u32,
-            rejection_result: &mut SerializedCodeSanityCheckResult,
-        ) -> Self {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-
-            let cached_data_ref: &mut AlignedCachedData = unsafe {
-                assert!(!cached_data.is_null());
-                &mut *cached_data
-            };
-            let data_ptr = cached_data_ref.data_ as *mut u8;
-            let data_len = cached_data_ref.length_;
-            let serialized_data = SerializedData::new(data_ptr, data_len);
-            SerializedCodeData {
-                serialized_data,
+        ) -> Result<Self, SerializedCodeSanityCheckResult> {
+            let scd = Self::new_with_data(cached_data.data().to_vec());
+            match scd.sanity_check(Snapshot::read_only_snapshot_checksum(isolate), expected_source_hash) {
+                SerializedCodeSanityCheckResult::kSuccess => Ok(scd),
+                result => Err(result),
             }
         }
 
-        pub fn from_cached_data_without_source(
-            local_isolate: &mut LocalIsolate,
-            cached_data: *mut AlignedCachedData,
-            rejection_result: &mut SerializedCodeSanityCheckResult,
-        ) -> Self {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-             let cached_data_ref: &mut AlignedCachedData = unsafe {
-                assert!(!cached_data.is_null());
-                &mut *cached_data
-            };
-            let data_ptr = cached_data_ref.data_ as *mut u8;
-            let data_len = cached_data_ref.length_;
-            let serialized_data = SerializedData::new(data_ptr, data_len);
-            SerializedCodeData {
-                serialized_data,
-            }
+        /// Used when producing.
+        pub fn new(payload: &[u8], cs: &CodeSerializer, read_only_snapshot_checksum: u32) -> Self {
+            let size = Self::K_HEADER_SIZE + payload.len() as u32;
+            let mut serialized_data = SerializedData::default();
+            serialized_data.allocate_data(size);
+            serialized_data.set_magic_number();
+            serialized_data.set_header_value(Self::K_VERSION_HASH_OFFSET, get_version_static().hash());
+            serialized_data.set_header_value(Self::K_SOURCE_HASH_OFFSET, cs.source_hash());
+            serialized_data.set_header_value(Self::K_FLAG_HASH_OFFSET, flag_hash());
+            serialized_data.set_header_value(Self::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET, read_only_snapshot_checksum);
+            serialized_data.set_header_value(Self::K_PAYLOAD_LENGTH_OFFSET, payload.len() as u32);
+            serialized_data.data_mut()[Self::K_HEADER_SIZE as usize..].copy_from_slice(payload);
+            let payload_checksum = checksum(&serialized_data.data()[Self::K_HEADER_SIZE as usize..]);
+            serialized_data.set_header_value(Self::K_CHECKSUM_OFFSET, payload_checksum);
+            SerializedCodeData { serialized_data }
+        }
+
+        fn new_with_data(data: Vec<u8>) -> Self {
+            SerializedCodeData { serialized_data: SerializedData::new(&data) }
+        }
+
+        /// Return ScriptData object and relinquish ownership over it to the
+        /// caller.
+        pub fn get_script_data(self) -> AlignedCachedData {
+            AlignedCachedData { data_: self.serialized_data.data().to_vec(), rejected_: false }
         }
 
-        pub fn from_partially_sanity_checked_cached_data(
-            cached_data: *mut AlignedCachedData,
+        pub fn payload(&self) -> &[u8] {
+            &self.serialized_data.data()[Self::K_HEADER_SIZE as usize..]
+        }
+
+        /// The source hash is the source length in UTF-16 code units, with
+        /// the top bit marking modules.
+        pub fn source_hash(source: &str, origin_options: ScriptOriginOptions) -> u32 {
+            let length = source.encode_utf16().count() as u32;
+            assert!(length < 1 << 31, "Script source is too long for the code cache");
+            length | if origin_options.is_module() { 0x8000_0000 } else { 0 }
+        }
+
+        fn sanity_check(
+            &self,
+            expected_ro_snapshot_checksum: u32,
             expected_source_hash: u32,
-            rejection_result: &mut SerializedCodeSanityCheckResult,
-        ) -> Self {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let cached_data_ref: &mut AlignedCachedData = unsafe {
-                assert!(!cached_data.is_null());
-                &mut *cached_data
-            };
-            let data_ptr = cached_data_ref.data_ as *mut u8;
-            let data_len = cached_data_ref.length_;
-            let serialized_data = SerializedData::new(data_ptr, data_len);
-            SerializedCodeData {
-                serialized_data,
+        ) -> SerializedCodeSanityCheckResult {
+            let result = self.sanity_check_without_source(expected_ro_snapshot_checksum);
+            if result != SerializedCodeSanityCheckResult::kSuccess {
+                return result;
             }
+            self.sanity_check_just_source(expected_source_hash)
         }
 
-        pub fn new(payload: &Vec<u8>, cs: &CodeSerializer) -> Self {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let mut data: Vec<u8> = Vec::new(); // Create the data vector
-            let data_ptr = data.as_mut_ptr();
-            let data_len = data.len() as i32;
+        fn sanity_check_just_source(&self, expected_source_hash: u32) -> SerializedCodeSanityCheckResult {
+            if self.serialized_data.get_header_value(Self::K_SOURCE_HASH_OFFSET) != expected_source_hash {
+                return SerializedCodeSanityCheckResult::kSourceMismatch;
+            }
+            SerializedCodeSanityCheckResult::kSuccess
+        }
 
-            let serialized_data = SerializedData::new(data_ptr, data_len);
-            SerializedCodeData {
-                serialized_data,
+        fn sanity_check_without_source(&self, expected_ro_snapshot_checksum: u32) -> SerializedCodeSanityCheckResult {
+            let size = self.serialized_data.size();
+            if size < Self::K_HEADER_SIZE as usize {
+                return SerializedCodeSanityCheckResult::kInvalidHeader;
+            }
+            let header = |offset| self.serialized_data.get_header_value(offset);
+            if self.serialized_data.get_magic_number() != SerializedData::K_MAGIC_NUMBER {
+                return SerializedCodeSanityCheckResult::kMagicNumberMismatch;
+            }
+            if header(Self::K_VERSION_HASH_OFFSET) != get_version_static().hash() {
+                return SerializedCodeSanityCheckResult::kVersionMismatch;
+            }
+            if header(Self::K_FLAG_HASH_OFFSET) != flag_hash() {
+                return SerializedCodeSanityCheckResult::kFlagsMismatch;
+            }
+            if header(Self::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET) != expected_ro_snapshot_checksum {
+                return SerializedCodeSanityCheckResult::kReadOnlySnapshotChecksumMismatch;
+            }
+            let payload_length = header(Self::K_PAYLOAD_LENGTH_OFFSET);
+            if payload_length > Self::K_MAX_PAYLOAD_LENGTH
+                || payload_length as usize != size - Self::K_HEADER_SIZE as usize
+            {
+                return SerializedCodeSanityCheckResult::kLengthMismatch;
             }
+            if checksum(self.payload()) != header(Self::K_CHECKSUM_OFFSET) {
+                return SerializedCodeSanityCheckResult::kChecksumMismatch;
+            }
+            SerializedCodeSanityCheckResult::kSuccess
         }
+    }
+
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CompileOptions {
+        kNoCompileOptions,
+        kConsumeCodeCache,
+        kEagerCompile,
+    }
+
+    /// Source code of a script together with its origin and, optionally, a
+    /// code cache to consume.
+    #[derive(Debug, Clone)]
+    pub struct Source {
+        source_string: String,
+        origin_options: ScriptOriginOptions,
+        cached_data: Option<CachedData>,
+    }
 
-        pub fn get_script_data(self) -> *mut AlignedCachedData {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let data_ptr = self.serialized_data.data_;
-            let data_len = self.serialized_data.size_;
-            let cached_data = AlignedCachedData::new(data_ptr, data_len);
-            let boxed_data = Box::new(cached_data);
-            Box::into_raw(boxed_data)
+    impl Source {
+        pub fn new(source_string: &str, origin_options: ScriptOriginOptions) -> Self {
+            Source { source_string: source_string.to_string(), origin_options, cached_data: None }
         }
 
-        pub fn payload(&self) -> &[u8] {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            unsafe {
-                std::slice::from_raw_parts(self.serialized_data.data_ as *const u8, self.serialized_data.size_ as usize)
+        pub fn with_cached_data(source_string: &str, origin_options: ScriptOriginOptions, cached_data: CachedData) -> Self {
+            Source {
+                source_string: source_string.to_string(),
+                origin_options,
+                cached_data: Some(cached_data),
             }
         }
 
-        pub fn source_hash(
-            source: DirectHandle<String>,
-            wrapped_arguments: DirectHandle<FixedArray>,
-            origin_options: ScriptOriginOptions,
-        ) -> u32 {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            0 // Dummy hash value
-        }
-
-        fn new_with_data(data: *mut AlignedCachedData) -> Self {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let cached_data_ref: &mut AlignedCachedData = unsafe {
-                assert!(!data.is_null());
-                &mut *data
-            };
-            let data_ptr = cached_data_ref.data_ as *mut u8;
-            let data_len = cached_data_ref.length_;
-            let serialized_data = SerializedData::new(data_ptr, data_len);
-            SerializedCodeData {
-                serialized_data,
-            }
+        /// The cached data, with |rejected| updated after consuming.
+        pub fn get_cached_data(&self) -> Option<&CachedData> {
+            self.cached_data.as_ref()
         }
+    }
 
-        fn new_with_data_and_size(data: *const u8, size: i32) -> Self {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            let serialized_data = SerializedData::new(data as *mut u8, size);
-            SerializedCodeData {
-                serialized_data,
-            }
+    /// A compiled script that is not bound to a context yet.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct UnboundScript {
+        shared_function_info: HeapObject,
+        origin_options: ScriptOriginOptions,
+    }
+
+    impl UnboundScript {
+        pub fn shared_function_info(&self) -> HeapObject {
+            self.shared_function_info
         }
+    }
 
-        fn checksummed_content(&self) -> &[u8] {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            unsafe {
-                let start = self.serialized_data.data_.add(Self::K_HEADER_SIZE as usize);
-                std::slice::from_raw_parts(start as *const u8, (self.serialized_data.size_ as u32 - Self::K_HEADER_SIZE) as usize)
+    pub struct ScriptCompiler {}
+
+    impl ScriptCompiler {
+        /// Compiles |source|. With kConsumeCodeCache the attached cached data
+        /// is deserialized instead; if it is rejected, |rejected| and the
+        /// reason are recorded on the cached data and the script is compiled
+        /// from source.
+        pub fn compile_unbound_script(
+            isolate: &mut Isolate,
+            source: &mut Source,
+            options: CompileOptions,
+        ) -> Result<UnboundScript, String> {
+            if options == CompileOptions::kConsumeCodeCache {
+                let cached_data = source.cached_data.as_mut().expect("kConsumeCodeCache requires cached data");
+                let mut aligned_data = AlignedCachedData::new(&cached_data.data);
+                let mut sanity_check_result = SerializedCodeSanityCheckResult::kSuccess;
+                let maybe_result = CodeSerializer::deserialize(
+                    isolate,
+                    &mut aligned_data,
+                    &source.source_string,
+                    source.origin_options,
+                    &mut sanity_check_result,
+                );
+                cached_data.rejected = aligned_data.rejected();
+                cached_data.compatibility_check_result = sanity_check_result;
+                if let Some(shared_function_info) = maybe_result {
+                    return Ok(UnboundScript { shared_function_info, origin_options: source.origin_options });
+                }
             }
+
+            let source_string = isolate.allocate_string(&source.source_string);
+            let script = isolate.allocate_script(source_string);
+            let shared_function_info = isolate.compile_script(script)?;
+            Ok(UnboundScript { shared_function_info, origin_options: source.origin_options })
         }
 
-        fn sanity_check(
-            &self,
-            expected_ro_snapshot_checksum: u32,
-            expected_source_hash: u32,
-        ) -> SerializedCodeSanityCheckResult {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            SerializedCodeSanityCheckResult::kSuccess
+        /// Creates a code cache for |script| that can be passed back with
+        /// kConsumeCodeCache, also in another isolate. Returns None if the
+        /// script cannot be cached.
+        pub fn create_code_cache(isolate: &Isolate, script: &UnboundScript) -> Option<CachedData> {
+            CodeSerializer::serialize(isolate, script.shared_function_info, script.origin_options)
         }
+    }
 
-        fn sanity_check_just_source(&self, expected_source_hash: u32) -> SerializedCodeSanityCheckResult {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            SerializedCodeSanityCheckResult::kSuccess
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::cell::Cell;
+
+        use crate::snapshot::snapshot::snapshot::{SerializerFlags, StartupData};
+
+        const SOURCE: &str = "function add(a, b) { return a + b; } add(20, 22);";
+
+        thread_local! {
+            static COMPILE_COUNT: Cell<usize> = const { Cell::new(0) };
+        }
+
+        /// Stands in for the parser and bytecode generator: a top-level
+        /// function whose constant pool holds the inner function |add|.
+        fn compile_with_bytecode(isolate: &mut Isolate, script: HeapObject) -> Result<HeapObject, String> {
+            COMPILE_COUNT.with(|count| count.set(count.get() + 1));
+            let inner_scope_info = isolate.allocate_scope_info(&[2, 0, 1]);
+            let inner_feedback_metadata = isolate.allocate_feedback_metadata(&[4]);
+            let inner_bytecode = isolate.allocate_bytecode_array(&[0x0b, 0x03, 0x39, 0x02, 0xab], 1, 3, &[]);
+            let inner = isolate.allocate_shared_function_info(
+                script,
+                "add",
+                12,
+                37,
+                1,
+                Tagged::strong(inner_scope_info),
+                Tagged::strong(inner_feedback_metadata),
+                Some(inner_bytecode),
+            );
+
+            let scope_info = isolate.allocate_scope_info(&[0, 1]);
+            let feedback_metadata = isolate.allocate_feedback_metadata(&[1, 4]);
+            let bytecode = isolate.allocate_bytecode_array(
+                &[0x13, 0x00, 0x0d, 0x14, 0x5c, 0xab],
+                4,
+                1,
+                &[Tagged::strong(inner), Tagged::from_smi(42)],
+            );
+            Ok(isolate.allocate_shared_function_info(
+                script,
+                "",
+                0,
+                SOURCE.len() as u32,
+                0,
+                Tagged::strong(scope_info),
+                Tagged::strong(feedback_metadata),
+                Some(bytecode),
+            ))
+        }
+
+        fn new_isolate() -> Isolate {
+            let mut isolate = Isolate::new();
+            isolate.init_without_snapshot();
+            isolate.set_compile_script_callback(compile_with_bytecode);
+            isolate
+        }
+
+        fn compile(isolate: &mut Isolate, source: &mut Source, options: CompileOptions) -> UnboundScript {
+            ScriptCompiler::compile_unbound_script(isolate, source, options).unwrap()
+        }
+
+        fn produce_cache() -> CachedData {
+            let mut isolate = new_isolate();
+            let mut source = Source::new(SOURCE, ScriptOriginOptions::default());
+            let script = compile(&mut isolate, &mut source, CompileOptions::kNoCompileOptions);
+            ScriptCompiler::create_code_cache(&isolate, &script).unwrap()
+        }
+
+        fn consume(isolate: &mut Isolate, source: &str, cached_data: CachedData) -> CachedData {
+            let mut source = Source::with_cached_data(source, ScriptOriginOptions::default(), cached_data);
+            compile(isolate, &mut source, CompileOptions::kConsumeCodeCache);
+            source.get_cached_data().unwrap().clone()
+        }
+
+        #[test]
+        fn test_code_cache_round_trip_without_compiling() {
+            let mut producer = new_isolate();
+            let mut source = Source::new(SOURCE, ScriptOriginOptions::default());
+            let produced = compile(&mut producer, &mut source, CompileOptions::kNoCompileOptions);
+            let cached_data = ScriptCompiler::create_code_cache(&producer, &produced).unwrap();
+
+            // Consume in an isolate set up from a snapshot of a fresh heap:
+            // the read-only snapshot checksum has to match.
+            let mut template = Isolate::new();
+            template.init_without_snapshot();
+            let contexts = template.contexts();
+            let blob: StartupData = Snapshot::create(&template, &contexts, SerializerFlags::kNoFlags);
+            let mut consumer = Isolate::new();
+            consumer.set_snapshot_blob(blob);
+            assert!(Snapshot::initialize(&mut consumer));
+            consumer.set_compile_script_callback(compile_with_bytecode);
+            assert_eq!(cached_data.compatibility_check(&consumer), SerializedCodeSanityCheckResult::kSuccess);
+
+            let compiles_before = COMPILE_COUNT.with(Cell::get);
+            let mut source = Source::with_cached_data(SOURCE, ScriptOriginOptions::default(), cached_data);
+            let consumed = compile(&mut consumer, &mut source, CompileOptions::kConsumeCodeCache);
+            assert!(!source.get_cached_data().unwrap().rejected);
+            assert_eq!(COMPILE_COUNT.with(Cell::get), compiles_before);
+
+            // The rehydrated tree matches the compiled one object by object.
+            let mut worklist = vec![(produced.shared_function_info(), consumed.shared_function_info())];
+            let mut visited = std::collections::HashSet::new();
+            while let Some((expected, actual)) = worklist.pop() {
+                if !visited.insert(expected) {
+                    continue;
+                }
+                let (expected_map, actual_map) = (expected.map(), actual.map());
+                assert_eq!(expected_map.instance_type(), actual_map.instance_type());
+                assert_eq!(expected.length(), actual.length());
+                if expected_map.body_kind() == BodyKind::Data {
+                    assert_eq!(expected.data(), actual.data());
+                    continue;
+                }
+                for index in 0..expected.length() {
+                    let (expected_slot, actual_slot) = (expected.get(index), actual.get(index));
+                    match (expected_slot.get_heap_object(), actual_slot.get_heap_object()) {
+                        (Some(e), Some(a)) => {
+                            assert_eq!(expected_slot.is_weak(), actual_slot.is_weak());
+                            worklist.push((e, a));
+                        }
+                        _ => assert_eq!(expected_slot, actual_slot),
+                    }
+                }
+            }
+
+            // The script is registered and refers to the new source string.
+            let script = CodeSerializer::script_of(consumed.shared_function_info());
+            let source_string = consumer.script_source(script);
+            assert_eq!(consumer.string_value(source_string), SOURCE);
+            assert!(consumer.scripts().contains(&script));
+
+            // Function names are internalized in the consuming isolate.
+            let inner = consumer.shared_function_infos(script)[0];
+            let name = inner.get(layout::K_SHARED_FUNCTION_INFO_NAME_SLOT).get_heap_object().unwrap();
+            assert_eq!(consumer.intern("add"), name);
+        }
+
+        #[test]
+        fn test_code_cache_rejects_source_mismatch() {
+            let mut isolate = new_isolate();
+            let result = consume(&mut isolate, "function add(a, b) { return a - b; } add(20, 22);;", produce_cache());
+            assert!(result.rejected);
+            assert_eq!(result.compatibility_check_result, SerializedCodeSanityCheckResult::kSourceMismatch);
+
+            let mut source = Source::new(SOURCE, ScriptOriginOptions::new(true));
+            source.cached_data = Some(produce_cache());
+            compile(&mut isolate, &mut source, CompileOptions::kConsumeCodeCache);
+            let result = source.get_cached_data().unwrap();
+            assert_eq!(result.compatibility_check_result, SerializedCodeSanityCheckResult::kSourceMismatch);
+        }
+
+        #[test]
+        fn test_code_cache_rejects_header_mismatches() {
+            let patch_header = |offset: u32, value: u32| {
+                let mut cached_data = produce_cache();
+                let offset = offset as usize;
+                cached_data.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+                cached_data
+            };
+            let cases = [
+                (
+                    patch_header(SerializedData::K_MAGIC_NUMBER_OFFSET, 0),
+                    SerializedCodeSanityCheckResult::kMagicNumberMismatch,
+                ),
+                (
+                    patch_header(SerializedCodeData::K_VERSION_HASH_OFFSET, get_version_static().hash() ^ 1),
+                    SerializedCodeSanityCheckResult::kVersionMismatch,
+                ),
+                (
+                    patch_header(SerializedCodeData::K_FLAG_HASH_OFFSET, flag_hash() ^ 1),
+                    SerializedCodeSanityCheckResult::kFlagsMismatch,
+                ),
+                (
+                    patch_header(SerializedCodeData::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET, 0xdead),
+                    SerializedCodeSanityCheckResult::kReadOnlySnapshotChecksumMismatch,
+                ),
+            ];
+            let mut isolate = new_isolate();
+            for (cached_data, expected) in cases {
+                assert_eq!(cached_data.compatibility_check(&isolate), expected);
+                let result = consume(&mut isolate, SOURCE, cached_data);
+                assert!(result.rejected);
+                assert_eq!(result.compatibility_check_result, expected);
+            }
         }
 
-        fn sanity_check_without_source(&self, expected_ro_snapshot_checksum: u32) -> SerializedCodeSanityCheckResult {
-            // Implementation details depend on the V8 codebase.
-            // This is a placeholder.
-            SerializedCodeSanityCheckResult::kSuccess
+        #[test]
+        fn test_code_cache_rejects_corrupted_data() {
+            let mut isolate = new_isolate();
+
+            let mut corrupted = produce_cache();
+            let last = corrupted.data.len() - 1;
+            corrupted.data[last] ^= 0xff;
+            let result = consume(&mut isolate, SOURCE, corrupted);
+            assert_eq!(result.compatibility_check_result, SerializedCodeSanityCheckResult::kChecksumMismatch);
+
+            let mut truncated = produce_cache();
+            truncated.data.truncate(truncated.data.len() - 1);
+            let result = consume(&mut isolate, SOURCE, truncated);
+            assert_eq!(result.compatibility_check_result, SerializedCodeSanityCheckResult::kLengthMismatch);
+
+            let mut truncated = produce_cache();
+            truncated.data.truncate(SerializedCodeData::K_HEADER_SIZE as usize - 1);
+            let result = consume(&mut isolate, SOURCE, truncated);
+            assert_eq!(result.compatibility_check_result, SerializedCodeSanityCheckResult::kInvalidHeader);
+
+            // A rejected cache falls back to compiling from source.
+            let compiles_before = COMPILE_COUNT.with(Cell::get);
+            let result = consume(&mut isolate, SOURCE, CachedData::new(vec![]));
+            assert!(result.rejected);
+            assert_eq!(COMPILE_COUNT.with(Cell::get), compiles_before + 1);
+        }
+
+        #[test]
+        fn test_code_cache_rejects_unexpected_contents() {
+            let mut isolate = new_isolate();
+
+            // A well-formed cache whose root is not a SharedFunctionInfo.
+            let array = isolate.allocate_fixed_array(&[Tagged::from_smi(1)]);
+            let source_string = isolate.allocate_string(SOURCE);
+            let mut encoder = CodeSerializerEncoder {
+                isolate_: &isolate,
+                source_: source_string,
+                root_indices_: &HashMap::new(),
+                unsupported_object_: None,
+            };
+            let mut cs = CodeSerializer::new(SerializedCodeData::source_hash(SOURCE, ScriptOriginOptions::default()));
+            cs.serializer_.serialize_root(Tagged::strong(array), &mut encoder);
+            cs.serializer_.serialize_deferred_objects(&mut encoder);
+            let checksum = Snapshot::read_only_snapshot_checksum(&isolate);
+            let data = SerializedCodeData::new(cs.serializer_.payload(), &cs, checksum).get_script_data();
+
+            let compiles_before = COMPILE_COUNT.with(Cell::get);
+            let result = consume(&mut isolate, SOURCE, CachedData::new(data.into_data()));
+            assert!(result.rejected);
+            assert_eq!(result.compatibility_check_result, SerializedCodeSanityCheckResult::kSuccess);
+            assert_eq!(COMPILE_COUNT.with(Cell::get), compiles_before + 1);
+        }
+
+        #[test]
+        fn test_context_specific_code_is_not_cached() {
+            let mut isolate = new_isolate();
+            let mut source = Source::new(SOURCE, ScriptOriginOptions::default());
+            let script = compile(&mut isolate, &mut source, CompileOptions::kNoCompileOptions);
+            let context = isolate.contexts()[0];
+            let info = script.shared_function_info();
+            let bytecode = info.get(layout::K_SHARED_FUNCTION_INFO_FUNCTION_DATA_SLOT).get_heap_object().unwrap();
+            let constant_pool = bytecode.get(layout::K_BYTECODE_ARRAY_CONSTANT_POOL_SLOT).get_heap_object().unwrap();
+            isolate.heap().set(constant_pool, 1, Tagged::strong(context));
+            assert!(ScriptCompiler::create_code_cache(&isolate, &script).is_none());
         }
     }
-}
\ No newline at end of file
+}
@@ -3,162 +3,30 @@
 // found in the LICENSE file.
 
 pub mod context_deserializer {
-    use std::ptr::NonNull;
-
-    // Placeholder types for V8 internal classes
-    pub struct Isolate {
-        // Add fields as needed
-    }
-
-    pub struct Context {
-        // Add fields as needed
-    }
-
-    pub struct Object {
-        // Add fields as needed
-    }
-
-    pub struct JSGlobalProxy {
-        // Add fields as needed
-    }
-
-    pub struct NativeContext {
-        // Add fields as needed
-    }
-
-    pub struct SnapshotData {
-        payload: Vec<u8>, // Represent payload as a byte vector
-        magic_number: u32,
-    }
-
-    impl SnapshotData {
-        pub fn new(payload: Vec<u8>, magic_number: u32) -> Self {
-            SnapshotData {
-                payload,
-                magic_number,
-            }
-        }
-
-        pub fn Payload(&self) -> &[u8] {
-            &self.payload
-        }
-
-        pub fn GetMagicNumber(&self) -> u32 {
-            self.magic_number
-        }
-    }
-
-    // Placeholder types for Handle
-    #[derive(Clone, Copy)]
-    pub struct DirectHandle<T> {
-        ptr: *mut T, // Use raw pointer for now
-    }
-
-    impl<T> DirectHandle<T> {
-        pub fn new(ptr: *mut T) -> Self {
-            DirectHandle { ptr }
-        }
-
-        pub fn is_null(&self) -> bool {
-            self.ptr.is_null()
-        }
-
-        pub unsafe fn get(&self) -> *mut T {
-            self.ptr
-        }
-    }
-
-    pub type MaybeDirectHandle<T> = Option<DirectHandle<T>>;
-
-    pub type DeserializeEmbedderFieldsCallback = Box<dyn Fn(*mut NativeContext)>;
-
-    pub type DeserializeAPIWrapperCallback = Box<dyn Fn()>; // Placeholder, adjust the signature as needed
-
-    pub struct Deserializer<'a, I> {
-        isolate: *mut I,
-        payload: &'a [u8],
-        magic_number: u32,
-        // Placeholder fields
-        can_rehash: bool,
-    }
-
-    impl<'a, I> Deserializer<'a, I> {
-        fn new(isolate: *mut I, payload: &'a [u8], magic_number: u32, _field1: bool, can_rehash: bool) -> Self {
-            Deserializer {
-                isolate,
-                payload,
-                magic_number,
-                can_rehash,
-            }
-        }
-    }
+    use crate::heap::heap::Heap;
+    use crate::heap::heap_layout::HeapObject;
+    use crate::snapshot::deserializer::deserializer::{Deserializer, ExternalObjects};
+    use crate::snapshot::snapshot_data::internal::SnapshotData;
 
+    /// Deserializes a context snapshot written by ContextSerializer.
     pub struct ContextDeserializer<'a> {
-        deserializer: Deserializer<'a, Isolate>,
+        deserializer_: Deserializer<'a>,
     }
 
     impl<'a> ContextDeserializer<'a> {
-        pub fn DeserializeContext(
-            isolate: *mut Isolate,
-            data: &'a SnapshotData,
-            _context_index: usize,
-            can_rehash: bool,
-            global_proxy: DirectHandle<JSGlobalProxy>,
-            embedder_fields_deserializer: DeserializeEmbedderFieldsCallback,
-        ) -> MaybeDirectHandle<Context> {
-            let mut context_deserializer =
-                ContextDeserializer::new(isolate, data, can_rehash);
-            context_deserializer.Deserialize(isolate, global_proxy, embedder_fields_deserializer)
-        }
-
-        fn new(isolate: *mut Isolate, data: &'a SnapshotData, can_rehash: bool) -> Self {
-            let deserializer = Deserializer::new(
-                isolate,
-                data.Payload(),
-                data.GetMagicNumber(),
-                false,
-                can_rehash,
-            );
-            ContextDeserializer { deserializer }
-        }
-
-        fn Deserialize(
-            &mut self,
-            isolate: *mut Isolate,
-            global_proxy: DirectHandle<JSGlobalProxy>,
-            embedder_fields_deserializer: DeserializeEmbedderFieldsCallback,
-        ) -> MaybeDirectHandle<Context> {
-            // Placeholder implementation
-            // Implement the deserialization logic here, creating and populating the Context object.
-            // This will likely involve reading data from the payload and creating/linking objects.
-            // Example:
-            unsafe {
-                if isolate.is_null() || global_proxy.is_null() {
-                    return None;
-                }
-                // Dummy Context creation for demonstration
-                let context = Box::new(Context {});
-                let context_ptr = Box::into_raw(context);
-                Some(DirectHandle::new(context_ptr))
-            }
-        }
-
-        fn DeserializeEmbedderFields(
-            &self,
-            context: DirectHandle<NativeContext>,
-            embedder_fields_deserializer: DeserializeEmbedderFieldsCallback,
-        ) {
-            unsafe {
-                embedder_fields_deserializer(context.get());
-            }
+        pub fn new(context_data: &'a SnapshotData) -> Self {
+            ContextDeserializer { deserializer_: Deserializer::new(context_data.payload()) }
         }
 
-        fn DeserializeApiWrapperFields(
-            &self,
-            _api_wrapper_callback: &DeserializeAPIWrapperCallback,
-        ) {
-            // Placeholder implementation
-            // Implement API wrapper field deserialization here
+        /// Deserializes the context into |heap| and returns it. The startup
+        /// snapshot must have been deserialized into the same heap.
+        pub fn deserialize_context(mut self, heap: &Heap, external: &ExternalObjects) -> HeapObject {
+            self.deserializer_.read_root(heap, external);
+            self.deserializer_.read_deferred_objects(heap, external);
+            assert!(!self.deserializer_.source().has_more(), "Trailing data in context snapshot");
+            self.deserializer_.take_roots()[0]
+                .get_heap_object()
+                .expect("Context snapshot root is not an object")
         }
     }
-}
\ No newline at end of file
+}
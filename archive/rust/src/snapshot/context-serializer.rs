@@ -3,122 +3,90 @@
 // found in the LICENSE file.
 
 pub mod context_serializer {
-    use std::any::Any;
-    use std::marker::PhantomData;
-
-    use crate::objects::contexts::Context;
-    use crate::snapshot::serializer::Serializer;
-    use crate::snapshot::snapshot_source_sink::SnapshotByteSink;
-
-    pub type SerializeEmbedderFieldsCallback =
-        Option<Box<dyn Fn(&mut SnapshotByteSink, &dyn Any)>>;
-
-    pub struct ContextSerializer<'a> {
-        serializer: Serializer,
-        startup_serializer: &'a mut StartupSerializer<'a>,
-        serialize_embedder_fields: SerializeEmbedderFieldsCallback,
-        can_be_rehashed: bool,
-        context: Option<Context>,
-        embedder_fields_sink: SnapshotByteSink,
-        api_wrapper_sink: SnapshotByteSink,
-        phantom: PhantomData<&'a ()>,
+    use std::collections::HashSet;
+
+    use crate::heap::heap_layout::{HeapObject, Tagged};
+    use crate::snapshot::read_only_serializer::read_only_serializer::ReadOnlySerializer;
+    use crate::snapshot::serializer::serializer::{ReferenceEncoder, Serializer};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{ExternalReference, SerializationStatistics};
+    use crate::snapshot::shared_heap_serializer::shared_heap_serializer::SharedHeapSerializer;
+    use crate::snapshot::snapshot::snapshot::Isolate;
+    use crate::snapshot::startup_serializer::startup_serializer::StartupSerializer;
+
+    struct ContextEncoder<'a, 'b> {
+        isolate: &'a Isolate,
+        read_only_serializer: &'a ReadOnlySerializer,
+        startup_serializer: &'b mut StartupSerializer<'a>,
+        shared_heap_serializer: &'b mut SharedHeapSerializer<'a>,
+        context_objects: &'b HashSet<HeapObject>,
     }
 
-    impl<'a> ContextSerializer<'a> {
-        pub fn new(
-            isolate: *mut Isolate,
-            flags: SerializerFlags,
-            startup_serializer: &'a mut StartupSerializer<'a>,
-            callback: SerializeEmbedderFieldsCallback,
-        ) -> Self {
-            ContextSerializer {
-                serializer: Serializer::new(isolate, flags),
-                startup_serializer,
-                serialize_embedder_fields: callback,
-                can_be_rehashed: true,
-                context: None,
-                embedder_fields_sink: SnapshotByteSink::new(),
-                api_wrapper_sink: SnapshotByteSink::new(),
-                phantom: PhantomData,
+    impl ReferenceEncoder for ContextEncoder<'_, '_> {
+        fn encode_external(&mut self, object: HeapObject) -> Option<ExternalReference> {
+            if self.isolate.is_read_only(object) {
+                return Some(self.read_only_serializer.encode_reference(object));
             }
-        }
-
-        pub fn serialize(&mut self, o: &mut Context, _no_gc: &DisallowGarbageCollection) {
-            self.context = Some(o.clone()); // Cloning to avoid lifetime issues, consider alternatives
-            self.serializer.serialize_root(o);
-        }
-
-        pub fn can_be_rehashed(&self) -> bool {
-            self.can_be_rehashed
+            if let Some(index) = self.shared_heap_serializer.serialize_using_shared_heap_object_cache(object) {
+                return Some(ExternalReference::SharedHeapObjectCache(index));
+            }
+            if self.context_objects.contains(&object) {
+                return None;
+            }
+            Some(ExternalReference::StartupObjectCache(
+                self.startup_serializer.serialize_using_startup_object_cache(object, self.shared_heap_serializer),
+            ))
         }
     }
 
-    impl<'a> Drop for ContextSerializer<'a> {
-        fn drop(&mut self) {}
+    /// Serializes one native context. Objects only reachable from this
+    /// context are serialized into the context snapshot; anything else it
+    /// references goes through the startup or shared heap object cache.
+    pub struct ContextSerializer {
+        serializer_: Serializer,
+        context_objects_: HashSet<HeapObject>,
+        can_be_rehashed_: bool,
     }
 
-    impl<'a> ContextSerializer<'a> {
-        fn serialize_object_impl(&mut self, o: &mut HeapObject, slot_type: SlotType) {
-            self.serializer.serialize_object_impl(o, slot_type);
-        }
-
-        fn should_be_in_the_startup_object_cache(&self, _o: &HeapObject) -> bool {
-            false // Placeholder
+    impl ContextSerializer {
+        /// |context_objects| is the set of objects owned by the context,
+        /// i.e. not reachable from the strong roots or other contexts.
+        pub fn new(context_objects: HashSet<HeapObject>) -> Self {
+            ContextSerializer {
+                serializer_: Serializer::new(),
+                context_objects_: context_objects,
+                can_be_rehashed_: true,
+            }
         }
 
-        fn should_be_in_the_shared_object_cache(&self, _o: &HeapObject) -> bool {
-            false // Placeholder
+        pub fn serialize<'a>(
+            &mut self,
+            isolate: &'a Isolate,
+            context: HeapObject,
+            read_only_serializer: &'a ReadOnlySerializer,
+            startup_serializer: &mut StartupSerializer<'a>,
+            shared_heap_serializer: &mut SharedHeapSerializer<'a>,
+        ) {
+            let mut encoder = ContextEncoder {
+                isolate,
+                read_only_serializer,
+                startup_serializer,
+                shared_heap_serializer,
+                context_objects: &self.context_objects_,
+            };
+            self.serializer_.serialize_root(Tagged::strong(context), &mut encoder);
+            self.serializer_.serialize_deferred_objects(&mut encoder);
         }
 
-        fn check_rehashability(&mut self, _obj: &HeapObject) {
-            // Placeholder
+        pub fn can_be_rehashed(&self) -> bool {
+            self.can_be_rehashed_
         }
 
-        // TODO: Implement SerializeObjectWithEmbedderFields once the involved types
-        // are fully translated.
-        // fn serialize_object_with_embedder_fields<V8Type, UserSerializerWrapper, UserCallback, ApiObjectType>(
-        //     &mut self,
-        //     data_holder: Handle<V8Type>,
-        //     embedder_fields_count: i32,
-        //     wrapper: UserSerializerWrapper,
-        //     user_callback: UserCallback,
-        //     api_obj: ApiObjectType,
-        // ) {
-        //     // Placeholder implementation
-        // }
-
-        fn serialize_api_wrapper_fields(&mut self, _js_object: &mut JSObject) {
-            // Placeholder
+        pub fn statistics(&self) -> &SerializationStatistics {
+            self.serializer_.statistics()
         }
-    }
-
-    // Dummy types and functions to satisfy the compiler.  These should be
-    // replaced with actual implementations when the corresponding C++ code is
-    // translated.
-    #[derive(Clone, Copy)]
-    pub struct SerializerFlags {}
-    pub struct Isolate {}
-    pub struct HeapObject {}
-    pub struct SlotType {}
-    pub struct DisallowGarbageCollection {}
-    pub struct JSObject {}
-
-    pub struct StartupSerializer<'a> {
-        phantom: PhantomData<&'a ()>
-    }
-
-    impl<'a> StartupSerializer<'a> {
-        pub fn new() -> Self {
-            StartupSerializer{
-                phantom: PhantomData
-            }
-        }
-    }
 
-    impl Serializer {
-        fn serialize_root<T>(&mut self, _root: &mut T) {
-            // Placeholder implementation
+        pub fn into_payload(self) -> Vec<u8> {
+            self.serializer_.into_payload()
         }
-        fn serialize_object_impl(&mut self, _o: &mut HeapObject, _slot_type: SlotType) {}
     }
-}
\ No newline at end of file
+}
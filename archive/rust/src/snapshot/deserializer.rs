@@ -2,505 +2,206 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// #![allow(dead_code)] // Suppress warnings for now.
-// #![allow(unused_variables)] // Suppress warnings for now.
-// #![allow(unused_imports)] // Suppress warnings for now.
-
-// use std::os::raw::*;
-
-// mod base {
-//     pub mod logging;
-// }
-
-// mod codegen {
-//     pub mod assembler_inl;
-//     pub mod reloc_info_inl;
-// }
-
-// mod common {
-//     pub mod assert_scope;
-//     pub mod globals;
-// }
-
-// mod execution {
-//     pub mod isolate;
-// }
-
-// mod handles {
-//     pub mod global_handles_inl;
-// }
-
-// mod heap {
-//     pub mod heap_inl;
-//     pub mod heap_write_barrier_inl;
-//     pub mod heap_write_barrier;
-//     pub mod heap;
-//     pub mod local_heap_inl;
-// }
-
-// mod logging {
-//     pub mod local_logger;
-//     pub mod log;
-// }
-
-// mod objects {
-//     pub mod backing_store;
-//     pub mod js_array_buffer_inl;
-//     pub mod maybe_object;
-//     pub mod objects_body_descriptors_inl;
-//     pub mod objects;
-//     pub mod slots;
-//     pub mod string;
-// }
-
-// mod roots {
-//     pub mod roots;
-// }
-
-// mod sandbox {
-//     pub mod js_dispatch_table_inl;
-// }
-
-// mod snapshot {
-//     pub mod embedded {
-//         pub mod embedded_data_inl;
-//     }
-//     pub mod references;
-//     pub mod serializer_deserializer;
-//     pub mod shared_heap_serializer;
-//     pub mod snapshot_data;
-// }
-
-// mod utils {
-//     pub mod memcopy;
-// }
-
-// Constants (Macros)
-const K_EMPTY_BACKING_STORE_REF_SENTINEL: u32 = 0; //C++: kEmptyBackingStoreRefSentinel
-
-//Helper function for IndirectPointerTag
-//IndirectPointerTag IndirectPointerTagFromInstanceType(InstanceType instance_type) {
-//  return static_cast<IndirectPointerTag>(static_cast<uint8_t>(instance_type));
-//}
-
-// TODO: add types for Tagged<T>, MaybeObjectSlot, ExternalPointerSlot, Handle<T>, DirectHandle<T> and HeapObject
-
-// Struct definitions
-// #[derive(Debug)]
-// struct SlotAccessorForHeapObject {
-//     object_: *mut HeapObject,
-//     offset_: i32,
-// }
-
-// impl SlotAccessorForHeapObject {
-//     fn for_slot_index(object: *mut HeapObject, index: i32) -> Self {
-//         SlotAccessorForHeapObject {
-//             object_: object,
-//             offset_: index * K_TAGGED_SIZE,
-//         }
-//     }
-
-//     fn for_slot_offset(object: *mut HeapObject, offset: i32) -> Self {
-//         SlotAccessorForHeapObject {
-//             object_: object,
-//             offset_: offset,
-//         }
-//     }
-
-//     // TODO: Implement slot(), external_pointer_slot(), object(), offset()
-//     // Implement Write, WriteIndirectPointerTo
-
-//     // Writes the given value to this slot, with an offset (e.g. for repeat
-//     // writes). Returns the number of slots written (which is one).
-//     fn write(&self, value: Tagged<MaybeObject>, slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         let current_slot = self.slot() + slot_offset;
-//         current_slot.Relaxed_Store(value);
-//         WriteBarrier::ForValue(*self.object_, current_slot, value, mode);
-//         1
-//     }
-
-//     fn write_heap_object(&self, value: Tagged<HeapObject>, ref_type: HeapObjectReferenceType,
-//                          slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         self.write(Tagged<HeapObjectReference>(value, ref_type), slot_offset, mode)
-//     }
-
-//     fn write_direct_handle(&self, value: DirectHandle<HeapObject>, ref_type: HeapObjectReferenceType,
-//                              slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         self.write(*value, ref_type, slot_offset, mode)
-//     }
-
-//     fn write_indirect_pointer_to(&self, value: Tagged<HeapObject>, mode: WriteBarrierMode) -> i32 {
-//         // Only ExposedTrustedObjects can be referenced via indirect pointers, so
-//         // we must have one of these objects here. See the comments in
-//         // trusted-object.h for more details.
-//         DCHECK(IsExposedTrustedObject(value));
-//         let object = Cast<ExposedTrustedObject>(value);
-
-//         let instance_type = value->map()->instance_type();
-//         let tag = IndirectPointerTagFromInstanceType(instance_type);
-//         let dest = self.object_->RawIndirectPointerField(self.offset_, tag);
-//         dest.store(object);
-
-//         WriteBarrier::ForIndirectPointer(*self.object_, dest, value, mode);
-//         1
-//     }
-
-//     fn write_protected_pointer_to(&self, value: Tagged<TrustedObject>, mode: WriteBarrierMode) -> i32 {
-//         DCHECK(IsTrustedObject(*self.object_));
-//         let host = Cast<TrustedObject>(*self.object_);
-//         let dest = host->RawProtectedPointerField(self.offset_);
-//         dest.store(value);
-//         WriteBarrier::ForProtectedPointer(host, dest, value, mode);
-//         1
-//     }
-// }
-
-// #[derive(Debug)]
-// struct SlotAccessorForRootSlots {
-//     slot_: FullMaybeObjectSlot,
-// }
-
-// impl SlotAccessorForRootSlots {
-//     fn new(slot: FullMaybeObjectSlot) -> Self {
-//         SlotAccessorForRootSlots { slot_: slot }
-//     }
-
-//     // TODO: Implement slot(), external_pointer_slot(), object(), offset()
-//     // Implement Write, WriteIndirectPointerTo
-
-//     // Writes the given value to this slot, with an offset (e.g. for repeat
-//     // writes). Returns the number of slots written (which is one).
-//     fn write(&self, value: Tagged<MaybeObject>, slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         let current_slot = self.slot_ + slot_offset;
-//         current_slot.Relaxed_Store(value);
-//         1
-//     }
-
-//     fn write_heap_object(&self, value: Tagged<HeapObject>, ref_type: HeapObjectReferenceType,
-//                          slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         self.write(Tagged<HeapObjectReference>(value, ref_type), slot_offset, mode)
-//     }
-
-//     fn write_direct_handle(&self, value: DirectHandle<HeapObject>, ref_type: HeapObjectReferenceType,
-//                              slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         self.write(*value, ref_type, slot_offset, mode)
-//     }
-
-//     fn write_indirect_pointer_to(&self, value: Tagged<HeapObject>, mode: WriteBarrierMode) -> i32 {
-//         unimplemented!()
-//     }
-
-//     fn write_protected_pointer_to(&self, value: Tagged<TrustedObject>, mode: WriteBarrierMode) -> i32 {
-//         unimplemented!()
-//     }
-// }
-
-// #[derive(Debug)]
-// struct SlotAccessorForHandle<IsolateT> {
-//     handle_: *mut DirectHandle<HeapObject>,
-//     isolate_: *mut IsolateT,
-// }
-
-// impl<IsolateT> SlotAccessorForHandle<IsolateT> {
-//     fn new(handle: *mut DirectHandle<HeapObject>, isolate: *mut IsolateT) -> Self {
-//         SlotAccessorForHandle {
-//             handle_: handle,
-//             isolate_: isolate,
-//         }
-//     }
-
-//     // Implement Write, WriteIndirectPointerTo
-
-//     fn write(&self, value: Tagged<MaybeObject>, slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         unimplemented!()
-//     }
-
-//     fn write_heap_object(&self, value: Tagged<HeapObject>, ref_type: HeapObjectReferenceType,
-//                          slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         if slot_offset != 0 || ref_type != HeapObjectReferenceType::STRONG {
-//             panic!("Incorrect slot_offset or ref_type");
-//         }
-//         unsafe { *self.handle_ = direct_handle(value, self.isolate_); }
-//         1
-//     }
-
-//     fn write_direct_handle(&self, value: DirectHandle<HeapObject>, ref_type: HeapObjectReferenceType,
-//                              slot_offset: i32, mode: WriteBarrierMode) -> i32 {
-//         if slot_offset != 0 || ref_type != HeapObjectReferenceType::STRONG {
-//             panic!("Incorrect slot_offset or ref_type");
-//         }
-//         unsafe { *self.handle_ = value; }
-//         1
-//     }
-
-//     fn write_indirect_pointer_to(&self, value: Tagged<HeapObject>, mode: WriteBarrierMode) -> i32 {
-//         unimplemented!()
-//     }
-
-//     fn write_protected_pointer_to(&self, value: Tagged<TrustedObject>, mode: WriteBarrierMode) -> i32 {
-//         unimplemented!()
-//     }
-// }
-
-// // enums
-// #[derive(Debug, PartialEq, Eq)]
-// enum WriteBarrierMode {
-//     UPDATE_WRITE_BARRIER,
-//     SKIP_WRITE_BARRIER,
-// }
-
-// #[derive(Debug, PartialEq, Eq)]
-// enum HeapObjectReferenceType {
-//     WEAK,
-//     STRONG,
-// }
-
-// #[derive(Debug)]
-// struct ReferenceDescriptor {
-//     type_: HeapObjectReferenceType,
-//     is_indirect_pointer: bool,
-// }
-
-// impl ReferenceDescriptor {
-//     fn new() -> Self {
-//         ReferenceDescriptor {
-//             type_: HeapObjectReferenceType::STRONG,
-//             is_indirect_pointer: false,
-//         }
-//     }
-// }
-
-// const K_TAGGED_SIZE: i32 = 8;
-
-// // Placeholder types and functions. These need to be replaced with actual implementations
-// // or removed if not used.
-
-// // Placeholder Tagged type
-// #[derive(Debug, Copy, Clone)]
-// struct Tagged<T> {
-//     address: usize,
-//     _phantom: std::marker::PhantomData<T>,
-// }
-
-// impl<T> Tagged<T> {
-//     fn from_address(address: usize) -> Self {
-//         Tagged { address, _phantom: std::marker::PhantomData }
-//     }
-// }
-
-// // Dummy implementations
-// struct HeapObject {}
-// struct MaybeObject {}
-// struct HeapObjectReference {}
-// struct FullMaybeObjectSlot {}
-// struct ExternalPointerSlot {}
-// struct TrustedObject {}
-// struct ExposedTrustedObject {}
-// struct WriteBarrier {}
-
-// impl WriteBarrier {
-//     fn for_value(_object: &HeapObject, _slot: MaybeObjectSlot, _value: Tagged<MaybeObject>, _mode: WriteBarrierMode) {}
-//     fn for_indirect_pointer(_object: &HeapObject, _dest: ExternalPointerSlot, _value: Tagged<HeapObject>, _mode: WriteBarrierMode) {}
-//     fn for_protected_pointer(_host: &TrustedObject, _dest: ExternalPointerSlot, _value: Tagged<TrustedObject>, _mode: WriteBarrierMode) {}
-// }
-
-// struct MaybeObjectSlot {}
-// struct FullObjectSlot {}
-// struct FullMaybeObjectSlot {}
-// struct DirectHandle<T> {}
-
-// //Dummy Implementation of Handle
-// #[derive(Debug, Copy, Clone)]
-// struct Handle<T> {
-//     address: usize,
-//     _phantom: std::marker::PhantomData<T>,
-// }
-
-// impl<T> Handle<T> {
-//     fn from_address(address: usize) -> Self {
-//         Handle { address, _phantom: std::marker::PhantomData }
-//     }
-// }
-
-// unsafe fn direct_handle<T>(_obj: Tagged<T>, _isolate: *mut Isolate) -> DirectHandle<T> {
-//     DirectHandle{}
-// }
-
-// #[derive(Debug)]
-// struct Deserializer<IsolateT> {
-//     isolate_: *mut IsolateT,
-//     attached_objects_: Vec<Tagged<HeapObject>>, // Assuming attached_objects is a vector of HeapObjects
-//     source_: Vec<u8>, // Assuming source is a byte vector
-//     magic_number_: u32,
-//     new_maps_: Vec<Tagged<HeapObject>>, // Assuming new_maps is a vector of HeapObjects
-//     new_allocation_sites_: Vec<Tagged<HeapObject>>, // Assuming new_allocation_sites is a vector of HeapObjects
-//     new_code_objects_: Vec<Tagged<HeapObject>>, // Assuming new_code_objects is a vector of HeapObjects
-//     accessor_infos_: Vec<Tagged<HeapObject>>, // Assuming accessor_infos is a vector of HeapObjects
-//     function_template_infos_: Vec<Tagged<HeapObject>>, // Assuming function_template_infos is a vector of HeapObjects
-//     new_scripts_: Vec<Tagged<HeapObject>>, // Assuming new_scripts is a vector of HeapObjects
-//     new_descriptor_arrays_: Vec<Tagged<HeapObject>>, // Assuming new_descriptor_arrays is a vector of HeapObjects
-//     deserializing_user_code_: bool,
-//     should_rehash_: bool,
-//     to_rehash_: Vec<Tagged<HeapObject>>, // Assuming to_rehash is a vector of HeapObjects
-//     back_refs_: Vec<Handle<HeapObject>>,
-//     num_api_references_: i32,
-//     next_reference_is_weak_: bool,
-//     next_reference_is_indirect_pointer_: bool,
-//     depth_: i32,
-//     num_unresolved_forward_refs_: i32,
-//     unresolved_forward_refs_: Vec<UnresolvedForwardRef>,
-//     backing_stores_: Vec<Option<Box<BackingStore>>>,
-// }
-
-// // Additional structs used within Deserializer.
-// #[derive(Debug)]
-// struct UnresolvedForwardRef {
-//     object: Handle<HeapObject>,
-//     offset: i32,
-//     descr: ReferenceDescriptor,
-// }
-
-// impl<IsolateT> Deserializer<IsolateT> {
-//     fn new(isolate: *mut IsolateT, payload: Vec<u8>, magic_number: u32, deserializing_user_code: bool, can_rehash: bool) -> Self {
-//         // TODO: Initialize attached_objects_, source_, new_maps_, new_allocation_sites_,
-//         // new_code_objects_, accessor_infos_, function_template_infos_, new_scripts_,
-//         // new_descriptor_arrays_, to_rehash_.
-
-//         Deserializer {
-//             isolate_: isolate,
-//             attached_objects_: Vec::new(),
-//             source_: payload,
-//             magic_number_: magic_number,
-//             new_maps_: Vec::new(),
-//             new_allocation_sites_: Vec::new(),
-//             new_code_objects_: Vec::new(),
-//             accessor_infos_: Vec::new(),
-//             function_template_infos_: Vec::new(),
-//             new_scripts_: Vec::new(),
-//             new_descriptor_arrays_: Vec::new(),
-//             deserializing_user_code_: deserializing_user_code,
-//             should_rehash_: true, // TODO: Replace with actual calculation
-//             to_rehash_: Vec::new(),
-//             back_refs_: Vec::new(),
-//             num_api_references_: 0, // TODO: Replace with actual calculation
-//             next_reference_is_weak_: false,
-//             next_reference_is_indirect_pointer_: false,
-//             depth_: 0,
-//             num_unresolved_forward_refs_: 0,
-//             unresolved_forward_refs_: Vec::new(),
-//             backing_stores_: vec![None],
-//         }
-//     }
-
-//     fn rehash(&mut self) {
-//         unimplemented!()
-//     }
-
-//     // fn visit_root_pointers(&mut self, root: Root, description: &str, start: FullObjectSlot, end: FullObjectSlot) {
-//     //     self.read_data(start, end);
-//     // }
-
-//     fn synchronize(&mut self, _tag: i32) {
-//         unimplemented!()
-//     }
-
-//     fn deserialize_deferred_objects(&mut self) {
-//         unimplemented!()
-//     }
-
-//     fn log_new_map_events(&mut self) {
-//         unimplemented!()
-//     }
-
-//     fn weaken_descriptor_arrays(&mut self) {
-//         unimplemented!()
-//     }
-
-//     fn log_script_events(&mut self, _script: Tagged<HeapObject>) {
-//         unimplemented!()
-//     }
-
-//     // fn write_heap_pointer<SlotAccessor>(&mut self, slot_accessor: SlotAccessor, heap_object: Tagged<HeapObject>, descr: ReferenceDescriptor, mode: WriteBarrierMode) -> i32 {
-//     //     if descr.is_indirect_pointer {
-//     //         slot_accessor.write_indirect_pointer_to(heap_object, mode)
-//     //     } else {
-//     //         slot_accessor.write_heap_object(heap_object, descr.type, 0, mode)
-//     //     }
-//     // }
-
-//     // fn write_heap_pointer<SlotAccessor>(&mut self, slot_accessor: SlotAccessor, heap_object: DirectHandle<HeapObject>, descr: ReferenceDescriptor, mode: WriteBarrierMode) -> i32 {
-//     //     if descr.is_indirect_pointer {
-//     //         slot_accessor.write_indirect_pointer_to(*heap_object, mode)
-//     //     } else {
-//     //         slot_accessor.write_direct_handle(heap_object, descr.type, 0, mode)
-//     //     }
-//     // }
-
-//     // fn write_external_pointer(&mut self, host: Tagged<HeapObject>, dest: ExternalPointerSlot, value: Address, tag: ExternalPointerTag) -> i32 {
-//     //     unimplemented!()
-//     // }
-
-//     fn get_and_reset_next_reference_descriptor(&mut self) -> ReferenceDescriptor {
-//         let desc = ReferenceDescriptor {
-//             type_: if self.next_reference_is_weak_ { HeapObjectReferenceType::WEAK } else { HeapObjectReferenceType::STRONG },
-//             is_indirect_pointer: self.next_reference_is_indirect_pointer_,
-//         };
-//         self.next_reference_is_weak_ = false;
-//         self.next_reference_is_indirect_pointer_ = false;
-//         desc
-//     }
-
-//     fn get_back_referenced_object(&self, _index: u32) -> Handle<HeapObject> {
-//         unimplemented!()
-//     }
-
-//     fn read_object(&self) -> DirectHandle<HeapObject> {
-//         unimplemented!()
-//     }
-
-//     fn read_object_space(&self, _space: i32) -> Handle<HeapObject> {
-//         unimplemented!()
-//     }
-
-//     // fn read_meta_map(&mut self, _space: i32) -> Handle<HeapObject> {
-//     //     unimplemented!()
-//     // }
-
-//     // fn read_repeated_root<SlotAccessor>(&mut self, slot_accessor: SlotAccessor, repeat_count: i32) -> i32 {
-//     //     unimplemented!()
-//     // }
-
-//     // fn read_data(&mut self, object: Handle<HeapObject>, start_slot_index: i32, end_slot_index: i32) {
-//     //     unimplemented!()
-//     // }
-
-//     // fn read_data(&mut self, start: FullMaybeObjectSlot, end: FullMaybeObjectSlot) {
-//     //     unimplemented!()
-//     // }
-
-//     // fn read_single_bytecode_data<SlotAccessor>(&mut self, data: u8, slot_accessor: SlotAccessor) -> i32 {
-//     //     unimplemented!()
-//     // }
-// }
-
-// #[derive(Debug)]
-// struct StringTableInsertionKey {
-//     string_: *mut String,
-//     deserializing_user_code_: bool,
-// }
-
-// // External Pointer Table Types
-// type ExternalPointerTag = i32;
-// const K_EXTERNAL_POINTER_NULL_TAG: ExternalPointerTag = 0;
-
-// const K_NOP: u8 = 0;
-// use std::fmt;
-
-// impl<IsolateT> Drop for Deserializer<IsolateT> {
-//   fn drop(&mut self) {
-//     println!("Dropping Deserializer");
-//   }
-// }
-
-type Address = usize;
-
+pub mod deserializer {
+    use crate::heap::heap::{AllocationType, Heap};
+    use crate::heap::heap_layout::{BodyKind, HeapObject, InstanceType, Map, Tagged};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{
+        Bytecode, FixedRawDataWithSize, HotObject, HotObjectsList, NewObject, RootArrayConstant,
+        SerializerDeserializer, SnapshotSpace, K_TAGGED_SIZE,
+    };
+    use crate::snapshot::snapshot_source_sink::SnapshotByteSource;
+
+    /// Objects of earlier sections that the section being read refers to.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ExternalObjects<'a> {
+        pub read_only_objects: &'a [HeapObject],
+        pub roots: &'a [HeapObject],
+        pub startup_object_cache: &'a [HeapObject],
+        pub shared_heap_object_cache: &'a [HeapObject],
+        pub attached_objects: &'a [HeapObject],
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum SlotTarget {
+        Root(usize),
+        Object(HeapObject, usize),
+    }
+
+    /// Reads the bytecode stream written by the Serializer back into a heap.
+    /// Objects are allocated in old space; native contexts are allocated
+    /// through the heap so that it keeps track of them.
+    pub struct Deserializer<'a> {
+        source_: SnapshotByteSource<'a>,
+        back_refs_: Vec<HeapObject>,
+        hot_objects_: HotObjectsList,
+        roots_: Vec<Tagged>,
+        unresolved_forward_refs_: Vec<Option<(SlotTarget, bool)>>,
+        num_unresolved_forward_refs_: usize,
+        new_objects_: Vec<HeapObject>,
+    }
+
+    impl<'a> Deserializer<'a> {
+        pub fn new(payload: &'a [u8]) -> Self {
+            Deserializer {
+                source_: SnapshotByteSource::new(payload),
+                back_refs_: Vec::new(),
+                hot_objects_: HotObjectsList::default(),
+                roots_: Vec::new(),
+                unresolved_forward_refs_: Vec::new(),
+                num_unresolved_forward_refs_: 0,
+                new_objects_: Vec::new(),
+            }
+        }
+
+        pub fn source(&mut self) -> &mut SnapshotByteSource<'a> {
+            &mut self.source_
+        }
+
+        /// Objects allocated by this deserializer, in allocation order.
+        pub fn new_objects(&self) -> &[HeapObject] {
+            &self.new_objects_
+        }
+
+        /// Returns true if the next bytecode is kSynchronize.
+        pub fn at_synchronize(&self) -> bool {
+            self.source_.has_more() && self.source_.peek() == Bytecode::kSynchronize as u8
+        }
+
+        pub fn read_synchronize(&mut self) {
+            let bytecode = self.source_.get();
+            assert_eq!(bytecode, Bytecode::kSynchronize as u8, "Snapshot out of sync");
+        }
+
+        /// Reads the value of the next root slot. Root values may still be
+        /// forward references until deferred objects have been read, so they
+        /// are collected and handed out by take_roots().
+        pub fn read_root(&mut self, heap: &Heap, external: &ExternalObjects) -> usize {
+            let index = self.roots_.len();
+            self.roots_.push(Tagged::ZERO);
+            let value = self.read_slot(heap, external, SlotTarget::Root(index));
+            self.roots_[index] = value;
+            index
+        }
+
+        pub fn take_roots(&mut self) -> Vec<Tagged> {
+            assert_eq!(self.num_unresolved_forward_refs_, 0, "Unresolved forward references");
+            std::mem::take(&mut self.roots_)
+        }
+
+        /// Reads deferred objects up to and including the next kSynchronize.
+        pub fn read_deferred_objects(&mut self, heap: &Heap, external: &ExternalObjects) {
+            loop {
+                let bytecode = self.source_.get();
+                if bytecode == Bytecode::kSynchronize as u8 {
+                    break;
+                }
+                let space = NewObject::decode::<{ Bytecode::kNewObject as u8 }>(bytecode);
+                self.read_object(heap, external, space);
+            }
+            assert_eq!(self.num_unresolved_forward_refs_, 0, "Unresolved forward references");
+        }
+
+        fn read_slot(&mut self, heap: &Heap, external: &ExternalObjects, target: SlotTarget) -> Tagged {
+            let bytecode = self.source_.get();
+            let weak = bytecode == Bytecode::kWeakPrefix as u8;
+            let bytecode = if weak { self.source_.get() } else { bytecode };
+            let object = match bytecode {
+                b if b == Bytecode::kClearedWeakReference as u8 => {
+                    assert!(!weak);
+                    return Tagged::CLEARED;
+                }
+                b if b == FixedRawDataWithSize::encode(1) => {
+                    assert!(!weak);
+                    let raw = self.source_.get_raw(K_TAGGED_SIZE);
+                    return Tagged::from_smi(i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as isize);
+                }
+                b if b == Bytecode::kRegisterPendingForwardRef as u8 => {
+                    self.unresolved_forward_refs_.push(Some((target, weak)));
+                    self.num_unresolved_forward_refs_ += 1;
+                    // Patched by kResolvePendingForwardRef.
+                    return Tagged::ZERO;
+                }
+                b if b < Bytecode::kBackref as u8 => {
+                    let space = NewObject::decode::<{ Bytecode::kNewObject as u8 }>(b);
+                    self.read_object(heap, external, space)
+                }
+                b if b == Bytecode::kBackref as u8 => {
+                    let index = self.source_.get_uint30() as usize;
+                    let object = self.back_refs_[index];
+                    self.hot_objects_.add(object);
+                    object
+                }
+                b if b == Bytecode::kReadOnlyHeapRef as u8 => {
+                    external.read_only_objects[self.source_.get_uint30() as usize]
+                }
+                b if b == Bytecode::kRootArray as u8 => external.roots[self.source_.get_uint30() as usize],
+                b if b == Bytecode::kStartupObjectCache as u8 => {
+                    external.startup_object_cache[self.source_.get_uint30() as usize]
+                }
+                b if b == Bytecode::kSharedHeapObjectCache as u8 => {
+                    external.shared_heap_object_cache[self.source_.get_uint30() as usize]
+                }
+                b if b == Bytecode::kAttachedReference as u8 => {
+                    external.attached_objects[self.source_.get_uint30() as usize]
+                }
+                b if (Bytecode::kRootArrayConstants as u8..Bytecode::kFixedRawData as u8).contains(&b) => {
+                    external.roots[RootArrayConstant::decode(b) as usize]
+                }
+                b if (Bytecode::kHotObject as u8
+                    ..Bytecode::kHotObject as u8 + SerializerDeserializer::K_HOT_OBJECT_COUNT as u8)
+                    .contains(&b) =>
+                {
+                    self.hot_objects_.get(HotObject::decode(b) as usize)
+                }
+                b => panic!("Unexpected snapshot bytecode {:#x} at offset {}", b, self.source_.position() - 1),
+            };
+            if weak {
+                Tagged::weak(object)
+            } else {
+                Tagged::strong(object)
+            }
+        }
+
+        fn read_object(&mut self, heap: &Heap, external: &ExternalObjects, _space: SnapshotSpace) -> HeapObject {
+            let raw_instance_type = self.source_.get_uint30();
+            let instance_type = u16::try_from(raw_instance_type)
+                .ok()
+                .and_then(InstanceType::from_raw)
+                .unwrap_or_else(|| panic!("Unknown instance type {} in snapshot", raw_instance_type));
+            let map = Map::for_instance_type(instance_type);
+            let length = self.source_.get_uint30() as usize;
+            let object = if instance_type == InstanceType::NATIVE_CONTEXT_TYPE {
+                heap.allocate_native_context(length)
+            } else {
+                heap.allocate_with_map(map, length, AllocationType::kOld)
+            };
+            self.back_refs_.push(object);
+            self.new_objects_.push(object);
+            self.hot_objects_.add(object);
+
+            while self.source_.peek() == Bytecode::kResolvePendingForwardRef as u8 {
+                self.source_.get();
+                let index = self.source_.get_uint30() as usize;
+                let (target, weak) = self.unresolved_forward_refs_[index].take().expect("Forward ref resolved twice");
+                let value = if weak { Tagged::weak(object) } else { Tagged::strong(object) };
+                match target {
+                    SlotTarget::Root(root) => self.roots_[root] = value,
+                    SlotTarget::Object(host, slot) => heap.set(host, slot, value),
+                }
+                self.num_unresolved_forward_refs_ -= 1;
+            }
+
+            if map.body_kind() == BodyKind::Data {
+                object.data().copy_from_slice(self.source_.get_raw(length));
+                return object;
+            }
+            for slot in 0..length {
+                // Pending forward references are only resolved while reading
+                // deferred objects, so the placeholder is safe to store here.
+                let value = self.read_slot(heap, external, SlotTarget::Object(object, slot));
+                heap.set(object, slot, value);
+            }
+            object
+        }
+    }
+}
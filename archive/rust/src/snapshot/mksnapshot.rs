@@ -2,161 +2,36 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::collections::HashMap;
-use std::convert::TryInto;
-use std::ffi::{CStr, CString};
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::os::raw::{c_char, c_int};
-use std::path::Path;
-use std::ptr;
-use std::slice;
-use std::sync::Mutex;
-
-//use libc::{remove, printf}; //use libc crate instead of std::os::raw
-//use v8_rs as v8; // Assuming a v8-rs crate exists, or a custom binding
-//use v8::base;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use crate::heap::heap_layout::HeapObject;
+use crate::snapshot::snapshot::snapshot::{Isolate, RunScriptCallback, Snapshot, SnapshotCreator};
 
 // Placeholder for v8 related types and functions
 mod v8 {
-    pub struct StartupData {
-        pub data: *const i8,
-        pub raw_size: i32,
-    }
-
-    impl StartupData {
-        pub fn new(data: *const i8, raw_size: i32) -> Self {
-            StartupData { data, raw_size }
-        }
-    }
+    pub use crate::snapshot::snapshot::v8::{CreateParams, StartupData};
 
     pub mod platform {
         pub struct Platform;
-        pub fn NewDefaultPlatform() -> Platform {
+        pub fn new_default_platform() -> Platform {
             Platform
         }
     }
 
-    pub fn InitializeICUDefaultLocation(_argv0: *const i8) {}
-    pub fn InitializePlatform(_platform: &platform::Platform) {}
-    pub fn Initialize() {}
-    pub fn Dispose() {}
-    pub fn DisposePlatform() {}
-
-    pub struct Isolate {
-        counter_function: Option<Box<dyn Fn(*const i8) -> *mut i32>>,
-    }
-    impl Isolate {
-        pub fn Allocate() -> *mut Isolate {
-            Box::into_raw(Box::new(Isolate {
-                counter_function: None,
-            }))
-        }
-        pub fn SetCounterFunction(&mut self, f: Option<Box<dyn Fn(*const i8) -> *mut i32>>) {
-            self.counter_function = f;
-        }
-        pub fn Dispose(isolate: *mut Isolate) {
-            unsafe {
-                drop(Box::from_raw(isolate));
-            }
-        }
-    }
-
-    pub mod SnapshotCreator {
-        use super::StartupData;
-
-        pub struct FunctionCodeHandling;
-        impl FunctionCodeHandling {
-            pub const kClear: i32 = 0;
-        }
-
-        pub struct SnapshotCreator {
-            isolate: *mut super::Isolate,
-            create_params: super::CreateParams,
-        }
-
-        impl SnapshotCreator {
-            pub fn new(isolate: *mut super::Isolate, create_params: super::CreateParams) -> Self {
-                SnapshotCreator {
-                    isolate,
-                    create_params,
-                }
-            }
-        }
-    }
-
-    pub mod ArrayBuffer {
-        pub struct Allocator;
-        impl Allocator {
-            pub fn NewDefaultAllocator() -> Allocator {
-                Allocator
-            }
-        }
-    }
-
-    pub struct CreateParams {
-        array_buffer_allocator: *mut ArrayBuffer::Allocator,
-        constraints: Constraints,
-    }
-
-    impl CreateParams {
-        pub fn new(array_buffer_allocator: *mut ArrayBuffer::Allocator) -> Self {
-            CreateParams {
-                array_buffer_allocator,
-                constraints: Constraints::default(),
-            }
-        }
-    }
-
-    #[derive(Default)]
-    pub struct Constraints {
-        code_range_size_in_bytes: usize,
-    }
-
-    impl Constraints {
-        pub fn set_code_range_size_in_bytes(&mut self, size: usize) {
-            self.code_range_size_in_bytes = size;
-        }
-    }
+    pub fn initialize_icu_default_location(_argv0: &str) {}
+    pub fn initialize_platform(_platform: &platform::Platform) {}
+    pub fn initialize() {}
+    pub fn dispose() {}
+    pub fn dispose_platform() {}
 }
 
 mod base {
-    pub struct Vector<T> {
-        data: Vec<T>,
-    }
-
-    impl<T> Vector<T> {
-        pub fn new(data: Vec<T>) -> Self {
-            Vector { data }
-        }
-
-        pub fn begin(&self) -> *const T {
-            self.data.as_ptr()
-        }
-
-        pub fn length(&self) -> usize {
-            self.data.len()
-        }
-    }
-
-    impl Vector<u8> {
-        pub fn from_raw_parts(ptr: *const u8, len: usize) -> Self {
-            unsafe {
-                let slice = std::slice::from_raw_parts(ptr, len);
-                Vector {
-                    data: slice.to_vec(),
-                }
-            }
-        }
-    }
-
-    pub mod OS {
-        use std::ffi::CString;
-        use std::fs::OpenOptions;
+    pub mod os {
+        use std::fs::{File, OpenOptions};
         use std::io;
 
-        pub fn FOpen(filename: &str, mode: &str) -> Result<File, io::Error> {
-            let c_filename = CString::new(filename).unwrap();
+        pub fn fopen(filename: &str, mode: &str) -> Result<File, io::Error> {
             let mut open_options = OpenOptions::new();
 
             match mode {
@@ -167,33 +42,18 @@ mod base {
                     open_options.write(true).create(true).truncate(true);
                 }
                 _ => {
-                    return Err(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        "Invalid mode".to_string(),
-                    ));
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid mode".to_string()));
                 }
             }
 
             open_options.open(filename)
         }
     }
-
-    pub fn Fclose(file: Result<File, std::io::Error>) {
-        if let Ok(_f) = file {
-            // File is closed when it goes out of scope
-        }
-    }
 }
 
 mod codegen {
     pub mod cpu_features {
-        pub fn Probe(_flag: bool) {}
-    }
-}
-
-mod common {
-    pub mod globals {
-        // Placeholder for globals
+        pub fn probe(_flag: bool) {}
     }
 }
 
@@ -201,9 +61,7 @@ mod flags {
     pub mod flags {
         use std::sync::Mutex;
 
-        lazy_static::lazy_static! {
-            pub static ref V8_FLAGS: Mutex<V8Flags> = Mutex::new(V8Flags::default());
-        }
+        pub static V8_FLAGS: Mutex<V8Flags> = Mutex::new(V8Flags::new());
 
         #[derive(Default, Debug)]
         pub struct V8Flags {
@@ -219,124 +77,96 @@ mod flags {
             pub target_os: String,
             pub native_code_counters: bool,
             pub verify_snapshot_checksum: bool,
+            pub verify_snapshot: bool,
+        }
+
+        impl V8Flags {
+            pub const fn new() -> Self {
+                V8Flags {
+                    predictable: false,
+                    use_ic: true,
+                    profile_deserialization: false,
+                    startup_src: String::new(),
+                    startup_blob: String::new(),
+                    embedded_src: String::new(),
+                    embedded_variant: String::new(),
+                    static_roots_src: String::new(),
+                    target_arch: String::new(),
+                    target_os: String::new(),
+                    native_code_counters: false,
+                    verify_snapshot_checksum: true,
+                    verify_snapshot: false,
+                }
+            }
         }
 
-        pub struct FlagList;
+        pub struct HelpOptions {
+            usage_string: String,
+        }
 
-        impl FlagList {
-            pub struct HelpOptions {
-                exit_on_help: bool,
-                usage_string: String,
+        impl HelpOptions {
+            pub fn new(usage_string: String) -> Self {
+                HelpOptions { usage_string }
             }
+        }
 
-            impl HelpOptions {
-                pub fn new(exit_on_help: bool, usage_string: String) -> Self {
-                    HelpOptions {
-                        exit_on_help,
-                        usage_string,
-                    }
-                }
-            }
+        pub struct FlagList;
 
-            pub fn SetFlagsFromCommandLine(
-                argc: &mut i32,
-                argv: &mut [*mut i8],
-                remove_flags: bool,
-                help_options: HelpOptions,
-            ) -> i32 {
-                let mut i = 1;
-                let mut result = 0;
+        impl FlagList {
+            /// Parses the flags in |args|, skipping the program name, and
+            /// removes them so that only the program name and the positional
+            /// arguments are left. Values are given either as `--flag=value`
+            /// or as `--flag value`. Returns 0 on success.
+            pub fn set_flags_from_command_line(args: &mut Vec<String>, help_options: HelpOptions) -> i32 {
                 let mut v8_flags = V8_FLAGS.lock().unwrap();
+                let mut remaining = Vec::with_capacity(args.len());
+                let mut result = 0;
+                let mut iter = std::mem::take(args).into_iter();
+                remaining.extend(iter.next());
 
-                while i < *argc {
-                    let arg = unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-
-                    match arg {
-                        "--startup-src" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.startup_src = value.to_string();
-                            } else {
-                                result = 1;
-                                break;
-                            }
-                        }
-                        "--startup-blob" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.startup_blob = value.to_string();
-                            } else {
-                                result = 1;
-                                break;
-                            }
-                        }
-                        "--embedded-src" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.embedded_src = value.to_string();
-                            } else {
-                                result = 1;
-                                break;
-                            }
-                        }
-                        "--embedded-variant" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.embedded_variant = value.to_string();
-                            } else {
-                                result = 1;
-                                break;
-                            }
-                        }
-                        "--static-roots-src" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.static_roots_src = value.to_string();
-                            } else {
-                                result = 1;
-                                break;
-                            }
-                        }
-                        "--target-arch" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.target_arch = value.to_string();
-                            } else {
-                                result = 1;
-                                break;
-                            }
-                        }
-                        "--target-os" => {
-                            i += 1;
-                            if i < *argc {
-                                let value =
-                                    unsafe { CStr::from_ptr(argv[i as usize]).to_str().unwrap() };
-                                v8_flags.target_os = value.to_string();
-                            } else {
+                while let Some(arg) = iter.next() {
+                    if !arg.starts_with("--") {
+                        remaining.push(arg);
+                        continue;
+                    }
+                    let (name, inline_value) = match arg.split_once('=') {
+                        Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                        None => (arg, None),
+                    };
+                    let value_flag = match name.as_str() {
+                        "--startup-src" => Some(&mut v8_flags.startup_src),
+                        "--startup-blob" => Some(&mut v8_flags.startup_blob),
+                        "--embedded-src" => Some(&mut v8_flags.embedded_src),
+                        "--embedded-variant" => Some(&mut v8_flags.embedded_variant),
+                        "--static-roots-src" => Some(&mut v8_flags.static_roots_src),
+                        "--target-arch" => Some(&mut v8_flags.target_arch),
+                        "--target-os" => Some(&mut v8_flags.target_os),
+                        _ => None,
+                    };
+                    if let Some(flag) = value_flag {
+                        match inline_value.or_else(|| iter.next()) {
+                            Some(value) => *flag = value,
+                            None => {
                                 result = 1;
                                 break;
                             }
                         }
-                        "--native-code-counters" => {
-                            v8_flags.native_code_counters = true;
+                        continue;
+                    }
+                    match name.as_str() {
+                        "--native-code-counters" => v8_flags.native_code_counters = true,
+                        "--verify-snapshot-checksum" => v8_flags.verify_snapshot_checksum = true,
+                        "--verify-snapshot" => v8_flags.verify_snapshot = true,
+                        "--profile-deserialization" => v8_flags.profile_deserialization = true,
+                        _ => {
+                            eprintln!("Error: unrecognized flag {}", name);
+                            result = 1;
+                            break;
                         }
-                        _ => {}
                     }
-
-                    i += 1;
                 }
+                remaining.extend(iter);
+                *args = remaining;
 
                 if result > 0 {
                     println!("{}", help_options.usage_string);
@@ -345,23 +175,18 @@ mod flags {
                 result
             }
         }
-
-        impl FlagList {
-            #[derive(Debug)]
-            pub struct Error {}
-        }
     }
 }
 
 mod snapshot {
     pub mod embedded {
         pub mod embedded_file_writer {
-            use super::super::EmbeddedData;
-            use crate::flags::flags::V8_FLAGS;
+            use super::super::super::i::EmbeddedData;
             use std::fs::File;
             use std::io::{self, Write};
             use std::path::Path;
 
+            #[derive(Default)]
             pub struct EmbeddedFileWriter {
                 embedded_file_path: String,
                 embedded_variant: String,
@@ -371,12 +196,7 @@ mod snapshot {
 
             impl EmbeddedFileWriter {
                 pub fn new() -> Self {
-                    EmbeddedFileWriter {
-                        embedded_file_path: String::new(),
-                        embedded_variant: String::new(),
-                        target_arch: String::new(),
-                        target_os: String::new(),
-                    }
+                    Self::default()
                 }
 
                 pub fn set_embedded_file(&mut self, embedded_file: String) {
@@ -395,13 +215,13 @@ mod snapshot {
                     self.target_os = target_os;
                 }
 
-                pub fn WriteEmbedded(&self, embedded_blob: &EmbeddedData) -> Result<(), io::Error> {
+                pub fn write_embedded(&self, embedded_blob: &EmbeddedData) -> Result<(), io::Error> {
                     if self.embedded_file_path.is_empty() {
                         return Ok(());
                     }
 
                     let path = Path::new(&self.embedded_file_path);
-                    let mut file = File::create(&path)?;
+                    let mut file = File::create(path)?;
 
                     writeln!(file, "// Autogenerated embedded blob. Do not edit.")?;
                     writeln!(file)?;
@@ -411,7 +231,7 @@ mod snapshot {
                     writeln!(file, "namespace internal {{")?;
                     writeln!(file)?;
                     writeln!(file, "EmbeddedData EmbeddedData::FromBlob() {{")?;
-                    writeln!(file, "  return {};", embedded_blob.data)?; // Assuming EmbeddedData has a field 'data' which can be printed
+                    writeln!(file, "  return {};", embedded_blob.data)?;
                     writeln!(file, "}}")?;
                     writeln!(file)?;
                     writeln!(file, "}}  // namespace internal")?;
@@ -423,23 +243,6 @@ mod snapshot {
         }
     }
 
-    pub mod snapshot {
-        use super::super::v8::StartupData;
-        use std::ptr;
-
-        pub struct Snapshot;
-
-        impl Snapshot {
-            pub fn DefaultSnapshotBlob() -> *const StartupData {
-                ptr::null() //return null pointer, since actual implementation not available
-            }
-
-            pub fn ShouldVerifyChecksum(_data: *const StartupData) -> bool {
-                false //return false since v8 flags not implemented
-            }
-        }
-    }
-
     pub mod static_roots_gen {
         // Placeholder for static roots generation
         pub struct StaticRootsTableGen;
@@ -450,6 +253,7 @@ mod snapshot {
     }
 }
 
+#[derive(Default)]
 struct SnapshotFileWriter {
     snapshot_cpp_path: String,
     snapshot_blob_path: String,
@@ -457,10 +261,7 @@ struct SnapshotFileWriter {
 
 impl SnapshotFileWriter {
     fn new() -> Self {
-        SnapshotFileWriter {
-            snapshot_cpp_path: String::new(),
-            snapshot_blob_path: String::new(),
-        }
+        Self::default()
     }
 
     fn set_snapshot_file(&mut self, snapshot_cpp_file: String) {
@@ -471,40 +272,34 @@ impl SnapshotFileWriter {
         self.snapshot_blob_path = snapshot_blob_file;
     }
 
-    fn write_snapshot(&self, blob: v8::StartupData) -> Result<(), std::io::Error> {
-        let blob_vector = unsafe {
-            std::slice::from_raw_parts(blob.data as *const u8, blob.raw_size as usize).to_vec()
-        };
-        self.maybe_write_snapshot_file(&blob_vector)?;
-        self.maybe_write_startup_blob(&blob_vector)?;
+    fn write_snapshot(&self, blob: &v8::StartupData) -> Result<(), std::io::Error> {
+        self.maybe_write_snapshot_file(&blob.data)?;
+        self.maybe_write_startup_blob(&blob.data)?;
         Ok(())
     }
 
-    fn maybe_write_startup_blob(&self, blob: &Vec<u8>) -> Result<(), std::io::Error> {
+    fn maybe_write_startup_blob(&self, blob: &[u8]) -> Result<(), std::io::Error> {
         if self.snapshot_blob_path.is_empty() {
             return Ok(());
         }
 
-        let file = Self::get_file_descriptor_or_die(&self.snapshot_blob_path)?;
-        let mut file = std::io::BufWriter::new(file); //for efficient writing
-        let written = file.write_all(blob)?;
-        file.flush()?; //flush the buffer to ensure all data is written
-
-        if written.is_err() {
+        let file = Self::get_file_descriptor_or_die(&self.snapshot_blob_path);
+        let mut file = std::io::BufWriter::new(file);
+        if file.write_all(blob).and_then(|()| file.flush()).is_err() {
             eprintln!("Writing snapshot file failed.. Aborting.");
-            if std::fs::remove_file(&self.snapshot_blob_path).is_ok() {};
+            let _ = std::fs::remove_file(&self.snapshot_blob_path);
             std::process::exit(1);
         }
 
         Ok(())
     }
 
-    fn maybe_write_snapshot_file(&self, blob: &Vec<u8>) -> Result<(), std::io::Error> {
+    fn maybe_write_snapshot_file(&self, blob: &[u8]) -> Result<(), std::io::Error> {
         if self.snapshot_cpp_path.is_empty() {
             return Ok(());
         }
 
-        let file = Self::get_file_descriptor_or_die(&self.snapshot_cpp_path)?;
+        let file = Self::get_file_descriptor_or_die(&self.snapshot_cpp_path);
         let mut file = std::io::BufWriter::new(file);
 
         Self::write_snapshot_file_prefix(&mut file)?;
@@ -535,28 +330,16 @@ impl SnapshotFileWriter {
         writeln!(file, "  return &blob;")?;
         writeln!(file, "}}")?;
         writeln!(file)?;
-        writeln!(
-            file,
-            "bool Snapshot::ShouldVerifyChecksum(const v8::StartupData* data) {{"
-        )?;
-        writeln!(
-            file,
-            "  return crate::flags::flags::V8_FLAGS.lock().unwrap().verify_snapshot_checksum;"
-        )?;
+        writeln!(file, "bool Snapshot::ShouldVerifyChecksum(const v8::StartupData* data) {{")?;
+        writeln!(file, "  return v8_flags.verify_snapshot_checksum;")?;
         writeln!(file, "}}")?;
         writeln!(file, "}}  // namespace internal")?;
         writeln!(file, "}}  // namespace v8")?;
         Ok(())
     }
 
-    fn write_snapshot_file_data<W: std::io::Write>(
-        file: &mut W,
-        blob: &Vec<u8>,
-    ) -> Result<(), std::io::Error> {
-        writeln!(
-            file,
-            "alignas(kPointerAlignment) static const uint8_t blob_data[] = {{"
-        )?;
+    fn write_snapshot_file_data<W: std::io::Write>(file: &mut W, blob: &[u8]) -> Result<(), std::io::Error> {
+        writeln!(file, "alignas(kPointerAlignment) static const uint8_t blob_data[] = {{")?;
         Self::write_binary_contents_as_c_array(file, blob)?;
         writeln!(file, "}};")?;
         writeln!(file, "static const int blob_size = {};", blob.len())?;
@@ -565,10 +348,7 @@ impl SnapshotFileWriter {
         Ok(())
     }
 
-    fn write_binary_contents_as_c_array<W: std::io::Write>(
-        file: &mut W,
-        blob: &Vec<u8>,
-    ) -> Result<(), std::io::Error> {
+    fn write_binary_contents_as_c_array<W: std::io::Write>(file: &mut W, blob: &[u8]) -> Result<(), std::io::Error> {
         for (i, &byte) in blob.iter().enumerate() {
             if (i & 0x1F) == 0x1F {
                 writeln!(file)?;
@@ -582,9 +362,9 @@ impl SnapshotFileWriter {
         Ok(())
     }
 
-    fn get_file_descriptor_or_die(filename: &str) -> Result<File, std::io::Error> {
-        match base::OS::FOpen(filename, "wb") {
-            Ok(fp) => Ok(fp),
+    fn get_file_descriptor_or_die(filename: &str) -> File {
+        match base::os::fopen(filename, "wb") {
+            Ok(fp) => fp,
             Err(e) => {
                 eprintln!("Unable to open file \"{}\" for writing: {}", filename, e);
                 std::process::exit(1);
@@ -598,8 +378,7 @@ fn get_extra_code(filename: &str, description: &str) -> Option<Vec<u8>> {
         return None;
     }
     println!("Loading script for {}: {}", description, filename);
-    let file = base::OS::FOpen(filename, "rb");
-    let mut file = match file {
+    let mut file = match base::os::fopen(filename, "rb") {
         Ok(file) => file,
         Err(e) => {
             eprintln!("Failed to open '{}': {}", filename, e);
@@ -607,15 +386,8 @@ fn get_extra_code(filename: &str, description: &str) -> Option<Vec<u8>> {
         }
     };
 
-    let size = file.seek(SeekFrom::End(0)).unwrap();
-    file.seek(SeekFrom::Start(0)).unwrap();
-
-    let mut chars: Vec<u8> = Vec::with_capacity(size as usize);
-    unsafe {
-        chars.set_len(size as usize);
-    }
-
-    if let Err(e) = file.read_exact(&mut chars) {
+    let mut chars = Vec::new();
+    if let Err(e) = file.read_to_end(&mut chars) {
         eprintln!("Failed to read '{}': {}", filename, e);
         std::process::exit(1);
     }
@@ -624,23 +396,17 @@ fn get_extra_code(filename: &str, description: &str) -> Option<Vec<u8>> {
 }
 
 fn create_snapshot_data_blob(
-    snapshot_creator: &mut v8::SnapshotCreator::SnapshotCreator,
+    snapshot_creator: &mut SnapshotCreator,
     embedded_source: Option<&[u8]>,
+    run_script_callback: Option<RunScriptCallback>,
 ) -> v8::StartupData {
     use std::time::Instant;
     let timer = Instant::now();
 
-    let result = create_snapshot_data_blob_internal(
-        v8::SnapshotCreator::FunctionCodeHandling::kClear,
-        embedded_source,
-        snapshot_creator,
-    );
+    let result = create_snapshot_data_blob_internal(snapshot_creator, embedded_source, run_script_callback);
 
     let elapsed = timer.elapsed();
-    let profile_deserialization =
-        crate::flags::flags::V8_FLAGS.lock().unwrap().profile_deserialization;
-
-    if profile_deserialization {
+    if flags::flags::V8_FLAGS.lock().unwrap().profile_deserialization {
         println!("[Creating snapshot took {:.3} ms]", elapsed.as_secs_f64() * 1000.0);
     }
 
@@ -649,44 +415,101 @@ fn create_snapshot_data_blob(
 
 fn warm_up_snapshot_data_blob(
     cold_snapshot_blob: v8::StartupData,
-    warmup_source: Option<&[u8]>,
+    warmup_source: &[u8],
+    run_script_callback: Option<RunScriptCallback>,
 ) -> v8::StartupData {
     use std::time::Instant;
     let timer = Instant::now();
 
-    let result = warm_up_snapshot_data_blob_internal(cold_snapshot_blob, warmup_source);
+    let result = warm_up_snapshot_data_blob_internal(cold_snapshot_blob, warmup_source, run_script_callback);
 
     let elapsed = timer.elapsed();
-    let profile_deserialization =
-        crate::flags::flags::V8_FLAGS.lock().unwrap().profile_deserialization;
-
-    if profile_deserialization {
+    if flags::flags::V8_FLAGS.lock().unwrap().profile_deserialization {
         println!("Warming up snapshot took {:.3} ms", elapsed.as_secs_f64() * 1000.0);
     }
 
     result
 }
 
-// Placeholder functions for internal implementations that are not directly translatable.
+fn run_extra_code(isolate: &mut Isolate, context: HeapObject, source: &[u8], name: &str) -> bool {
+    let source = match std::str::from_utf8(source) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Script {} is not valid UTF-8: {}", name, e);
+            return false;
+        }
+    };
+    match isolate.run_script(context, source) {
+        Ok(()) => true,
+        Err(message) => {
+            eprintln!("Running {} failed: {}", name, message);
+            false
+        }
+    }
+}
+
+/// Runs |embedded_source|, if any, in the default context of the creator's
+/// isolate and serializes the isolate. Scripts are compiled and run by the
+/// embedder through |run_script_callback|.
 fn create_snapshot_data_blob_internal(
-    _function_code_handling: i32,
-    _embedded_source: Option<&[u8]>,
-    _snapshot_creator: &mut v8::SnapshotCreator::SnapshotCreator,
+    snapshot_creator: &mut SnapshotCreator,
+    embedded_source: Option<&[u8]>,
+    run_script_callback: Option<RunScriptCallback>,
 ) -> v8::StartupData {
-    // This function would contain the V8-specific logic to create the snapshot data blob.
-    // Replace with actual implementation if available.
-    println!("create_snapshot_data_blob_internal called (placeholder)");
-    v8::StartupData::new(ptr::null(), 0)
+    let isolate = snapshot_creator.get_isolate();
+    if let Some(callback) = run_script_callback {
+        isolate.set_run_script_callback(callback);
+    }
+    let context = isolate.contexts()[0];
+    if let Some(source) = embedded_source
+        && !run_extra_code(isolate, context, source, "<embedded>")
+    {
+        eprintln!("Failed to run embedded script. Aborting.");
+        std::process::exit(1);
+    }
+    snapshot_creator.set_default_context(context);
+    snapshot_creator.create_blob()
 }
 
+/// Runs |warmup_source| in a throw-away context deserialized from the cold
+/// snapshot, then snapshots a fresh copy of the default context. State the
+/// warm-up leaves outside of its context, e.g. compiled scripts, is kept.
 fn warm_up_snapshot_data_blob_internal(
-    _cold_snapshot_blob: v8::StartupData,
-    _warmup_source: Option<&[u8]>,
+    cold_snapshot_blob: v8::StartupData,
+    warmup_source: &[u8],
+    run_script_callback: Option<RunScriptCallback>,
 ) -> v8::StartupData {
-    // This function would contain the V8-specific logic to warm up the snapshot data blob.
-    // Replace with actual implementation if available.
-    println!("warm_up_snapshot_data_blob_internal called (placeholder)");
-    v8::StartupData::new(ptr::null(), 0)
+    let mut snapshot_creator = SnapshotCreator::from_blob(cold_snapshot_blob);
+    let isolate = snapshot_creator.get_isolate();
+    if let Some(callback) = run_script_callback {
+        isolate.set_run_script_callback(callback);
+    }
+    let warmup_context = Snapshot::new_context_from_snapshot(isolate, 0).expect("Cold snapshot has no context");
+    if !run_extra_code(isolate, warmup_context, warmup_source, "<warm-up>") {
+        eprintln!("Failed to run warm-up script. Aborting.");
+        std::process::exit(1);
+    }
+    let context = Snapshot::new_context_from_snapshot(isolate, 0).expect("Cold snapshot has no context");
+    snapshot_creator.set_default_context(context);
+    snapshot_creator.create_blob()
+}
+
+/// Deserializes |blob|, serializes the resulting isolate again and checks
+/// that the heap survives the round trip unchanged.
+fn verify_snapshot(blob: &v8::StartupData) -> Result<(), String> {
+    if !Snapshot::verify_checksum(blob) {
+        return Err("checksum mismatch".to_string());
+    }
+    let mut isolate = Isolate::new();
+    isolate.set_snapshot_blob(blob.clone());
+    if !Snapshot::initialize(&mut isolate) {
+        return Err("could not initialize isolate from snapshot".to_string());
+    }
+    for index in 0..Snapshot::extract_num_contexts(blob) as usize {
+        Snapshot::new_context_from_snapshot(&mut isolate, index)
+            .ok_or_else(|| format!("could not deserialize context {}", index))?;
+    }
+    Snapshot::serialize_deserialize_and_verify_for_testing(&isolate).map(|_| ())
 }
 
 // Placeholder for EmbeddedData and its method FromBlob.
@@ -696,90 +519,24 @@ mod i {
     }
 
     impl EmbeddedData {
-        pub fn FromBlob() -> Self {
+        pub fn from_blob() -> Self {
             EmbeddedData {
                 data: "/* Embedded data here */".to_string(),
             }
         }
     }
-
-    pub struct EmbeddedFileWriter {
-        embedded_file_path: String,
-        embedded_variant: String,
-        target_arch: String,
-        target_os: String,
-    }
-
-    impl EmbeddedFileWriter {
-        pub fn new() -> Self {
-            EmbeddedFileWriter {
-                embedded_file_path: String::new(),
-                embedded_variant: String::new(),
-                target_arch: String::new(),
-                target_os: String::new(),
-            }
-        }
-
-        pub fn SetEmbeddedFile(&mut self, embedded_file: String) {
-            self.embedded_file_path = embedded_file;
-        }
-        pub fn SetEmbeddedVariant(&mut self, embedded_variant: String) {
-            self.embedded_variant = embedded_variant;
-        }
-        pub fn SetTargetArch(&mut self, target_arch: String) {
-            self.target_arch = target_arch;
-        }
-        pub fn SetTargetOs(&mut self, target_os: String) {
-            self.target_os = target_os;
-        }
-
-        pub fn WriteEmbedded(&self, _embedded_blob: &EmbeddedData) {}
-    }
-
-    pub struct Isolate {
-        embedded_writer: *mut EmbeddedFileWriter,
-    }
-
-    impl Isolate {
-        pub fn RegisterEmbeddedFileWriter(&mut self, writer: *mut EmbeddedFileWriter) {
-            self.embedded_writer = writer;
-        }
-    }
 }
 
-lazy_static::lazy_static! {
-    static ref COUNTER_MAP: Mutex<HashMap<String, i32>> = Mutex::new(HashMap::new());
+pub fn main() {
+    std::process::exit(run(std::env::args().collect(), None));
 }
 
-fn maybe_set_counter_function(isolate: *mut v8::Isolate) {
-    // If --native-code-counters is on then we enable all counters to make
-    // sure we generate code to increment them from the snapshot.
-    //
-    // Note: For the sake of the mksnapshot, the counter function must only
-    // return distinct addresses for each counter s.t. the serializer can properly
-    // distinguish between them. In theory it should be okay to just return an
-    // incremented int value each time this function is called, but we play it
-    // safe and return a real distinct memory location tied to every counter name.
-    let v8_flags = crate::flags::flags::V8_FLAGS.lock().unwrap();
-    if v8_flags.native_code_counters {
-        unsafe {
-            let isolate_mut = &mut *isolate;
-            isolate_mut.SetCounterFunction(Some(Box::new(|name: *const i8| -> *mut i32 {
-                let name_str = CStr::from_ptr(name).to_str().unwrap().to_string();
-                let mut counter_map = COUNTER_MAP.lock().unwrap();
-                if !counter_map.contains_key(&name_str) {
-                    counter_map.insert(name_str.clone(), 0);
-                }
-                let ptr = counter_map.get_mut(&name_str).unwrap() as *mut i32;
-                ptr
-            })));
-        }
-    }
-}
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Runs mksnapshot with the command line |args|. The isolate has no compiler
+/// of its own: the embedded and warm-up scripts are run through
+/// |run_script_callback|, and passing scripts without one fails.
+pub fn run(mut args: Vec<String>, run_script_callback: Option<RunScriptCallback>) -> i32 {
     // Make mksnapshot runs predictable to create reproducible snapshots.
-    let mut v8_flags = crate::flags::flags::V8_FLAGS.lock().unwrap();
+    let mut v8_flags = flags::flags::V8_FLAGS.lock().unwrap();
     v8_flags.predictable = true;
 
     // Disable ICs globally in mksnapshot to avoid problems with Code handlers.
@@ -788,48 +545,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     v8_flags.use_ic = false;
     drop(v8_flags);
 
-    let mut args: Vec<*mut i8> = std::env::args()
-        .map(|arg| {
-            let c_string = CString::new(arg).unwrap();
-            c_string.into_raw()
-        })
-        .collect();
-    let mut argc = args.len() as i32;
-
-    let mut argv: [*mut i8; 64] = [ptr::null_mut(); 64]; // Assuming a maximum of 64 arguments
-    for (i, arg) in args.iter().enumerate() {
-        if i < 64 {
-            argv[i] = *arg;
-        }
-    }
-
     // Print the usage if an error occurs when parsing the command line
     // flags or if the help flag is set.
-    let usage = "Usage: mksnapshot [--startup-src=file] [--startup-blob=file] [--embedded-src=file] [--embedded-variant=label] [--static-roots-src=file] [--target-arch=arch] [--target-os=os] [extras]\n\n";
-    let help_options =
-        flags::flags::FlagList::HelpOptions::new(true, usage.to_string());
+    let usage = "Usage: mksnapshot [--startup-src=file] [--startup-blob=file] [--embedded-src=file] [--embedded-variant=label] [--static-roots-src=file] [--target-arch=arch] [--target-os=os] [--verify-snapshot] [extras]\n\n";
+    let help_options = flags::flags::HelpOptions::new(usage.to_string());
 
-    let mut result =
-        flags::flags::FlagList::SetFlagsFromCommandLine(&mut argc, &mut argv, true, help_options);
-    if result > 0 || (argc > 3) {
+    let result = flags::flags::FlagList::set_flags_from_command_line(&mut args, help_options);
+    if result > 0 || args.len() > 3 {
         println!("{}", usage);
-        std::process::exit(result);
+        return result.max(1);
     }
 
-    codegen::cpu_features::Probe(true);
-    let first_arg = if argc > 0 {
-        unsafe { CStr::from_ptr(argv[0]).to_str().unwrap() }
-    } else {
-        ""
-    };
-    let first_arg_cstr = CString::new(first_arg).unwrap();
-
-    v8::InitializeICUDefaultLocation(first_arg_cstr.as_ptr());
-    let platform = v8::platform::NewDefaultPlatform();
-    v8::InitializePlatform(&platform);
-    v8::Initialize();
+    codegen::cpu_features::probe(true);
+    v8::initialize_icu_default_location(args.first().map_or("", String::as_str));
+    let platform = v8::platform::new_default_platform();
+    v8::initialize_platform(&platform);
+    v8::initialize();
 
-    let v8_flags = crate::flags::flags::V8_FLAGS.lock().unwrap();
+    let v8_flags = flags::flags::V8_FLAGS.lock().unwrap();
     let snapshot_cpp_path = v8_flags.startup_src.clone();
     let snapshot_blob_path = v8_flags.startup_blob.clone();
     let embedded_src = v8_flags.embedded_src.clone();
@@ -837,6 +570,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let static_roots_src = v8_flags.static_roots_src.clone();
     let target_arch = v8_flags.target_arch.clone();
     let target_os = v8_flags.target_os.clone();
+    let verify = v8_flags.verify_snapshot;
     drop(v8_flags);
 
     let mut snapshot_writer = SnapshotFileWriter::new();
@@ -849,75 +583,97 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     embedded_writer.set_target_arch(target_arch);
     embedded_writer.set_target_os(target_os);
 
-    let embed_script = get_extra_code(
-        if argc >= 2 {
-            unsafe { CStr::from_ptr(argv[1]).to_str().unwrap() }
-        } else {
-            ""
-        },
-        "embedding",
-    );
-    let warmup_script = get_extra_code(
-        if argc >= 3 {
-            unsafe { CStr::from_ptr(argv[2]).to_str().unwrap() }
-        } else {
-            ""
-        },
-        "warm up",
-    );
-
-    let mut blob = v8::StartupData::new(ptr::null(), 0);
-    {
-        let isolate = v8::Isolate::Allocate();
+    let embed_script = get_extra_code(args.get(1).map_or("", String::as_str), "embedding");
+    let warmup_script = get_extra_code(args.get(2).map_or("", String::as_str), "warm up");
 
-        maybe_set_counter_function(isolate);
+    let mut create_params = v8::CreateParams::default();
+    // Set code range such that relative jumps for builtins to
+    // builtin calls in the snapshot are possible.
+    let code_range_size_mb = 512;
+    create_params.constraints.set_code_range_size_in_bytes(code_range_size_mb * 1024 * 1024);
+
+    let mut blob = {
+        let mut creator = SnapshotCreator::with_params(create_params);
+        let blob = create_snapshot_data_blob(&mut creator, embed_script.as_deref(), run_script_callback);
 
         // The isolate contains data from builtin compilation that needs
         // to be written out if builtins are embedded.
-        unsafe {
-            let i_isolate = isolate as *mut i::Isolate;
-            let embedded_writer_ptr = &mut embedded_writer as *mut snapshot::embedded::embedded_file_writer::EmbeddedFileWriter;
-            (*i_isolate).RegisterEmbeddedFileWriter(embedded_writer_ptr);
+        if let Err(e) = embedded_writer.write_embedded(&i::EmbeddedData::from_blob()) {
+            eprintln!("Writing embedded blob failed: {}", e);
+            return 1;
         }
 
-        let array_buffer_allocator = v8::ArrayBuffer::Allocator::NewDefaultAllocator();
-        let mut create_params = v8::CreateParams::new(&mut array_buffer_allocator);
+        if !static_roots_src.is_empty() {
+            snapshot::static_roots_gen::StaticRootsTableGen::write(0, static_roots_src);
+        }
+        blob
+    };
 
-        // Set code range such that relative jumps for builtins to
-        // builtin calls in the snapshot are possible.
-        let code_range_size_mb = if 0 == 0 {
-            512
-        } else {
-            std::cmp::min(0 / 1024 / 1024, 512)
-        };
-        create_params
-            .constraints
-            .set_code_range_size_in_bytes(code_range_size_mb * 1024 * 1024);
+    if let Some(warmup_script_data) = warmup_script {
+        let cold = blob;
+        blob = warm_up_snapshot_data_blob(cold, &warmup_script_data, run_script_callback);
+    }
 
-        {
-            let mut creator = v8::SnapshotCreator::SnapshotCreator::new(isolate, create_params);
+    if verify && let Err(message) = verify_snapshot(&blob) {
+        eprintln!("Snapshot verification failed: {}", message);
+        return 1;
+    }
 
-            blob = create_snapshot_data_blob(&mut creator, embed_script.as_ref().map(|v| v.as_slice()));
+    if let Err(e) = snapshot_writer.write_snapshot(&blob) {
+        eprintln!("Writing snapshot failed: {}", e);
+        return 1;
+    }
 
-            unsafe {
-                let i_isolate = isolate as *mut i::Isolate;
-                (*(*i_isolate).embedded_writer).WriteEmbedded(&i::EmbeddedData::FromBlob());
-            }
+    v8::dispose();
+    v8::dispose_platform();
+    0
+}
 
-            if !static_roots_src.is_empty() {
-                unsafe {
-                    let i_isolate = isolate as *mut i::Isolate;
-                    snapshot::static_roots_gen::StaticRootsTableGen::write(0, static_roots_src);
-                }
-            }
-        }
-        v8::Isolate::Dispose(isolate);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heap::heap_layout::Tagged;
+
+    /// Records the script and defines `ran` on the global object, the way an
+    /// embedder's compiler would leave a compiled script behind.
+    fn run_script_for_testing(isolate: &mut Isolate, context: HeapObject, source: &str) -> Result<(), String> {
+        let script = isolate.add_script(context, source);
+        isolate.compile_script(script)?;
+        let count = match isolate.get_global_property(context, "ran") {
+            Some(count) => count.smi_value().unwrap(),
+            None => 0,
+        };
+        isolate.set_global_property(context, "ran", Tagged::from_smi(count + 1));
+        Ok(())
     }
 
-    if let Some(warmup_script_data) = warmup_script {
-        let cold = blob;
-        blob = warm_up_snapshot_data_blob(cold, Some(&warmup_script_data));
-        // Drop the old blob data.
+    #[test]
+    fn test_warm_up_keeps_scripts_but_not_context_state() {
+        let mut creator = SnapshotCreator::new();
+        let cold = create_snapshot_data_blob(&mut creator, Some(b"embedded()"), Some(run_script_for_testing));
+        let warm = warm_up_snapshot_data_blob(cold, b"warmup()", Some(run_script_for_testing));
+        verify_snapshot(&warm).unwrap();
+
+        let mut isolate = Isolate::new();
+        isolate.set_snapshot_blob(warm);
+        assert!(Snapshot::initialize(&mut isolate));
+        let context = Snapshot::new_context_from_snapshot(&mut isolate, 0).unwrap();
+        // The warm-up ran in its own context; its effects on the globals
+        // are gone but its script is kept.
+        assert_eq!(isolate.get_global_property(context, "ran"), Some(Tagged::from_smi(1)));
+        let sources: Vec<String> = isolate
+            .scripts()
+            .into_iter()
+            .map(|script| isolate.string_value(isolate.script_source(script)))
+            .collect();
+        assert_eq!(sources, ["embedded()", "warmup()"]);
+    }
+
+    #[test]
+    fn test_scripts_need_a_run_script_callback() {
+        let mut isolate = Isolate::new();
+        isolate.init_without_snapshot();
+        let context = isolate.contexts()[0];
+        assert!(!run_extra_code(&mut isolate, context, b"embedded()", "<embedded>"));
     }
-
-    // Reset the
\ No newline at end of file
+}
@@ -27,3 +27,4 @@ pub mod roots-serializer;
 pub mod snapshot-utils;
 pub mod read-only-serializer;
 pub mod snapshot-empty;
+pub mod snapshot-tool;
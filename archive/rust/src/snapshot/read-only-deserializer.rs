@@ -1,733 +1,43 @@
-// src/snapshot/read_only_deserializer.rs
+// Copyright 2018 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-//use crate::handles::Handles;
-//use crate::heap::heap::{Heap, ReadOnlyHeap};
-//use crate::logging::counters_scopes::NestedTimedHistogramScope;
-//use crate::objects::objects::HeapObject;
-//use crate::objects::slots::Slot;
-//use crate::snapshot::embedded::embedded_data::EmbeddedData;
-//use crate::snapshot::read_only_serializer_deserializer;
-//use crate::snapshot::snapshot_data::SnapshotData;
+pub mod read_only_deserializer {
+    use crate::heap::heap::Heap;
+    use crate::heap::heap_layout::HeapObject;
+    use crate::snapshot::deserializer::deserializer::{Deserializer, ExternalObjects};
+    use crate::snapshot::snapshot_data::internal::SnapshotData;
 
-//use std::convert::TryFrom;
-
-// Placeholder for SnapshotByteSource - needs more context to implement properly
-pub struct SnapshotByteSource {
-    data: Vec<u8>,
-    position: usize,
-}
-
-impl SnapshotByteSource {
-    pub fn new(data: Vec<u8>) -> Self {
-        SnapshotByteSource {
-            data,
-            position: 0,
-        }
-    }
-
-    pub fn get(&mut self) -> u32 {
-        let value = self.data[self.position] as u32;
-        self.position += 1;
-        value
-    }
-
-    pub fn get_uint30(&mut self) -> u32 {
-        // Simplified, assuming always enough bytes and no actual Uint30 encoding
-        let mut result: u32 = 0;
-        result |= (self.data[self.position] as u32) << 0;
-        result |= (self.data[self.position+1] as u32) << 8;
-        result |= (self.data[self.position+2] as u32) << 16;
-        result |= (self.data[self.position+3] as u32) << 24;
-        self.position += 4;
-        result
-    }
-
-    pub fn get_uint32(&mut self) -> u32 {
-        // Simplified, assuming always enough bytes
-        let mut result: u32 = 0;
-        result |= (self.data[self.position] as u32) << 0;
-        result |= (self.data[self.position+1] as u32) << 8;
-        result |= (self.data[self.position+2] as u32) << 16;
-        result |= (self.data[self.position+3] as u32) << 24;
-        self.position += 4;
-        result
-    }
-
-    pub fn copy_raw(&mut self, dest: *mut std::ffi::c_void, size_in_bytes: usize) {
-        unsafe {
-            std::ptr::copy_nonoverlapping(
-                self.data[self.position..].as_ptr() as *const std::ffi::c_void,
-                dest,
-                size_in_bytes,
-            );
-        }
-        self.position += size_in_bytes;
-    }
-
-    pub fn data(&self) -> &[u8] {
-        &self.data
-    }
-
-    pub fn position(&self) -> usize {
-        self.position
-    }
-
-    pub fn advance(&mut self, bytes: usize) {
-        self.position += bytes;
-    }
-
-    pub fn length(&self) -> usize {
-        self.data.len()
-    }
-}
-
-// Placeholder for Isolate - needs more context to implement properly
-pub struct Isolate {
-    cage_base: usize,
-    //read_only_heap_: ReadOnlyHeap,
-    //api_external_references_: *const i64,
-    //external_reference_table_unsafe_: *mut ExternalReferenceTable,
-    next_unique_sfi_id: u32,
-    //counters_: Counters,
-    read_only_heap_: ReadOnlyHeap,
-    heap_: Heap
-}
-
-impl Isolate {
-    pub fn new() -> Self {
-        Isolate {
-            cage_base: 0,
-            //read_only_heap_: ReadOnlyHeap::new(), // Need default constructor
-            //api_external_references_: std::ptr::null(),
-            //external_reference_table_unsafe_: std::ptr::null_mut(),
-            next_unique_sfi_id: 0,
-            //counters_: Counters::new(),
-            read_only_heap_: ReadOnlyHeap::new(),
-            heap_: Heap::new(),
-        }
-    }
-
-    pub fn cage_base(&self) -> usize {
-        self.cage_base
-    }
-    pub fn read_only_heap(&mut self) -> &mut ReadOnlyHeap {
-        &mut self.read_only_heap_
-    }
-
-    /*
-    pub fn api_external_references(&self) -> *const i64 {
-        self.api_external_references_
-    }
-    */
-
-    /*
-    pub fn external_reference_table_unsafe(&self) -> *mut ExternalReferenceTable {
-        self.external_reference_table_unsafe_
-    }
-    */
-
-    pub fn get_and_inc_next_unique_sfi_id(&mut self) -> u32 {
-        let id = self.next_unique_sfi_id;
-        self.next_unique_sfi_id += 1;
-        id
-    }
-
-    /*
-    pub fn counters(&self) -> &Counters {
-        &self.counters_
-    }
-    */
-
-    pub fn heap(&mut self) -> &mut Heap {
-        &mut self.heap_
-    }
-}
-
-/*
-// Placeholder for Counters
-pub struct Counters {}
-
-impl Counters {
-    pub fn new() -> Self {
-        Counters {}
-    }
-
-    pub fn snapshot_deserialize_rospace(&self) -> NestedTimedHistogramScope {
-        NestedTimedHistogramScope {}
-    }
-}
-
-// Placeholder for NestedTimedHistogramScope
-pub struct NestedTimedHistogramScope {}
-*/
-
-// Placeholder for HandleScope
-pub struct HandleScope<'a> {
-    isolate: &'a mut Isolate,
-}
-
-impl<'a> HandleScope<'a> {
-    pub fn new(isolate: &'a mut Isolate) -> Self {
-        HandleScope { isolate }
-    }
-}
-
-// Placeholder for ReadOnlyHeap
-pub struct ReadOnlyHeap {
-    read_only_space_: ReadOnlySpace,
-}
-
-impl ReadOnlyHeap {
-    pub fn new() -> Self {
-        ReadOnlyHeap {
-            read_only_space_: ReadOnlySpace::new(),
-        }
-    }
-    pub fn read_only_space(&mut self) -> &mut ReadOnlySpace {
-        &mut self.read_only_space_
-    }
-}
-
-// Placeholder for ReadOnlySpace
-pub struct ReadOnlySpace {
-    pages: Vec<ReadOnlyPageMetadata>,
-}
-
-impl ReadOnlySpace {
-    pub fn new() -> Self {
-        ReadOnlySpace { pages: Vec::new() }
-    }
-
-    pub fn allocate_next_page(&mut self) -> usize {
-        self.pages.push(ReadOnlyPageMetadata::new());
-        self.pages.len() - 1
-    }
-
-    pub fn allocate_next_page_at(&mut self, _pos: usize) -> usize {
-        self.pages.push(ReadOnlyPageMetadata::new());
-        self.pages.len() - 1
-    }
-
-    pub fn initialize_page_for_deserialization(&mut self, page: &mut ReadOnlyPageMetadata, area_size_in_bytes: usize) {
-        page.area_size = area_size_in_bytes;
-    }
-
-    pub fn finalize_space_for_deserialization(&mut self) {}
-
-    pub fn repair_free_spaces_after_deserialization(&mut self) {}
-
-    pub fn pages(&self) -> &Vec<ReadOnlyPageMetadata> {
-        &self.pages
-    }
-}
-
-// Placeholder for ReadOnlyPageMetadata
-pub struct ReadOnlyPageMetadata {
-    area_start: usize,
-    area_end: usize,
-    area_size: usize,
-}
-
-impl ReadOnlyPageMetadata {
-    pub fn new() -> Self {
-        ReadOnlyPageMetadata {
-            area_start: 0,
-            area_end: 0,
-            area_size: 0,
-        }
-    }
-
-    pub fn area_start(&self) -> usize {
-        self.area_start
-    }
-
-    pub fn area_end(&self) -> usize {
-        self.area_end
-    }
-
-    pub fn offset_to_address(&self, offset: usize) -> usize {
-        self.area_start + offset
-    }
-}
-
-// Placeholder for Heap
-pub struct Heap {
-    hash_seed_initialized: bool,
-}
-
-impl Heap {
-    pub fn new() -> Self {
-        Heap {
-            hash_seed_initialized: false,
-        }
-    }
-
-    pub fn initialize_hash_seed(&mut self) {
-        self.hash_seed_initialized = true;
-    }
-}
-
-// Placeholder for ReadOnlyRoots
-pub struct ReadOnlyRoots<'a> {
-    isolate_: &'a mut Isolate,
-    read_only_roots_: Vec<usize>,
-}
-
-impl <'a>ReadOnlyRoots<'a> {
-    const kEntriesCount: usize = 10;
-
-    pub fn new(isolate: &'a mut Isolate) -> Self {
-        ReadOnlyRoots {
-            isolate_: isolate,
-            read_only_roots_: vec![0; Self::kEntriesCount],
-        }
-    }
-
-    pub fn init_from_static_roots_table(&mut self, _cage_base: usize) {}
-    pub fn verify_name_for_protectors_pages(&mut self) {}
-    pub fn verify_types(&mut self) {}
-    pub fn verify_name_for_protectors(&mut self) {}
-}
-
-// Placeholder for ExternalReferenceTable
-/*
-pub struct ExternalReferenceTable {}
-
-impl ExternalReferenceTable {
-    pub fn address(&self, _index: i32) -> usize {
-        0 // Placeholder
-    }
-}
-*/
-
-mod ro {
-    pub enum Bytecode {
-        kAllocatePage,
-        kAllocatePageAt,
-        kSegment,
-        kRelocateSegment,
-        kReadOnlyRootsTable,
-        kFinalizeReadOnlySpace,
-        kNumberOfBytecodes
-    }
-
-    // Implement From<u32> for Bytecode
-    impl From<u32> for Bytecode {
-        fn from(value: u32) -> Self {
-            match value {
-                0 => Bytecode::kAllocatePage,
-                1 => Bytecode::kAllocatePageAt,
-                2 => Bytecode::kSegment,
-                3 => Bytecode::kRelocateSegment,
-                4 => Bytecode::kReadOnlyRootsTable,
-                5 => Bytecode::kFinalizeReadOnlySpace,
-                _ => Bytecode::kAllocatePage, // Handle default case
-            }
-        }
-    }
-
-    pub struct EncodedTagged {
-        pub page_index: usize,
-        pub offset: usize,
-    }
-
-    impl EncodedTagged {
-        pub fn from_address(_address: usize) -> Self {
-            EncodedTagged {
-                page_index: 0,
-                offset: 0,
-            }
-        }
-
-        pub fn from_uint32(_encoded_as_int: u32) -> Self {
-            EncodedTagged {
-                page_index: 0,
-                offset: 0,
-            }
-        }
-    }
-
-    pub struct BitSet {
-        data: Vec<u8>,
-        size_in_bits: usize,
-    }
-
-    impl BitSet {
-        pub fn new(data: &[u8], size_in_bits: usize) -> Self {
-            BitSet {
-                data: data.to_vec(),
-                size_in_bits,
-            }
-        }
-
-        pub fn contains(&self, index: usize) -> bool {
-            if index >= self.size_in_bits {
-                return false;
-            }
-            let byte_index = index / 8;
-            let bit_index = index % 8;
-            (self.data[byte_index] >> bit_index) & 1 == 1
-        }
-
-        pub fn size_in_bytes(&self) -> usize {
-            self.data.len()
-        }
-
-        pub fn size_in_bits(&self) -> usize {
-            self.size_in_bits
-        }
-    }
-
-    pub struct EncodedExternalReference {
-        pub index: usize,
-        pub is_api_reference: bool,
-    }
-
-    impl EncodedExternalReference {
-        pub fn from_uint32(_encoded: u32) -> Self {
-            EncodedExternalReference{index: 0, is_api_reference: false}
-        }
-    }
-}
-
-const V8_STATIC_ROOTS_BOOL: bool = false;
-const COMPRESS_POINTERS_BOOL: bool = false;
-const USE_SIMULATOR_BOOL: bool = false;
-const kTaggedSize: usize = 8;
-const kHeapObjectTag: usize = 1;
-const kAccessorInfoSetterTag: usize = 1;
-const kAccessorInfoGetterTag: usize = 1;
-const kFunctionTemplateInfoCallbackTag: usize = 1;
-const kNullAddress: usize = 0;
-
-struct ReadOnlyHeapImageDeserializer<'a> {
-    source_: &'a mut SnapshotByteSource,
-    isolate_: &'a mut Isolate,
-}
-
-impl<'a> ReadOnlyHeapImageDeserializer<'a> {
-    fn deserialize(isolate: &'a mut Isolate, source: &'a mut SnapshotByteSource) {
-        let mut deserializer = ReadOnlyHeapImageDeserializer {
-            source_: source,
-            isolate_: isolate,
-        };
-        deserializer.deserialize_impl();
-    }
-
-    fn deserialize_impl(&mut self) {
-        loop {
-            let bytecode_as_int = self.source_.get();
-            //DCHECK_LT(bytecode_as_int, ro::kNumberOfBytecodes);
-            match ro::Bytecode::from(bytecode_as_int) {
-                ro::Bytecode::kAllocatePage => self.allocate_page(false),
-                ro::Bytecode::kAllocatePageAt => self.allocate_page(true),
-                ro::Bytecode::kSegment => self.deserialize_segment(),
-                ro::Bytecode::kRelocateSegment => unreachable!(), // Handled together with kSegment.
-                ro::Bytecode::kReadOnlyRootsTable => self.deserialize_read_only_roots_table(),
-                ro::Bytecode::kFinalizeReadOnlySpace => {
-                    self.ro_space().finalize_space_for_deserialization();
-                    return;
-                }
-                ro::Bytecode::kNumberOfBytecodes => {
-                    println!("bytecode_as_int: {}", bytecode_as_int);
-                    unreachable!();
-                }
-            }
-        }
-    }
-
-    fn allocate_page(&mut self, fixed_offset: bool) {
-        //CHECK_EQ(V8_STATIC_ROOTS_BOOL, fixed_offset);
-        assert_eq!(V8_STATIC_ROOTS_BOOL, fixed_offset);
-        let expected_page_index = self.source_.get_uint30() as usize;
-        let area_size_in_bytes = self.source_.get_uint30() as usize;
-        let actual_page_index = if fixed_offset {
-            //#ifdef V8_COMPRESS_POINTERS
-            let compressed_page_addr = self.source_.get_uint32() as usize;
-            let pos = self.isolate_.cage_base() + compressed_page_addr;
-            self.ro_space().allocate_next_page_at(pos)
-            //#else
-            //UNREACHABLE();
-            //#endif  // V8_COMPRESS_POINTERS
-        } else {
-            self.ro_space().allocate_next_page()
-        };
-        //CHECK_EQ(actual_page_index, expected_page_index);
-        assert_eq!(actual_page_index, expected_page_index);
-        self.ro_space().initialize_page_for_deserialization(
-            &mut self.page_at(actual_page_index),
-            area_size_in_bytes,
-        );
-    }
-
-    fn deserialize_segment(&mut self) {
-        let page_index = self.source_.get_uint30() as usize;
-        let page = self.page_at(page_index);
-
-        // Copy over raw contents.
-        let start = page.area_start() + self.source_.get_uint30() as usize;
-        let size_in_bytes = self.source_.get_uint30() as usize;
-        //CHECK_LE(start + size_in_bytes, page->area_end());
-        assert!(start + size_in_bytes <= page.area_end());
-        unsafe {
-            self.source_.copy_raw(std::mem::transmute(start), size_in_bytes);
-        }
-
-        if !V8_STATIC_ROOTS_BOOL {
-            let relocate_marker_bytecode = self.source_.get() as u8;
-            //CHECK_EQ(relocate_marker_bytecode, Bytecode::kRelocateSegment);
-            //assert_eq!(relocate_marker_bytecode, ro::Bytecode::kRelocateSegment as u8);
-            let tagged_slots_size_in_bits = size_in_bytes / kTaggedSize;
-            // The const_cast is unfortunate, but we promise not to mutate data.
-            let data = self.source_.data();
-            let position = self.source_.position();
-            let tagged_slots = ro::BitSet::new(&data[position..], tagged_slots_size_in_bits);
-            self.decode_tagged_slots(start, &tagged_slots);
-            self.source_.advance(tagged_slots.size_in_bytes());
-        }
-    }
-
-    fn decode(&self, encoded: ro::EncodedTagged) -> usize {
-        let page = self.page_at(encoded.page_index);
-        page.offset_to_address(encoded.offset * kTaggedSize)
-    }
-
-    fn decode_tagged_slots(&self, segment_start: usize, tagged_slots: &ro::BitSet) {
-        //DCHECK(!V8_STATIC_ROOTS_BOOL);
-        assert!(!V8_STATIC_ROOTS_BOOL);
-        for i in 0..tagged_slots.size_in_bits() {
-            // TODO(jgruber): Depending on sparseness, different iteration methods
-            // could be more efficient.
-            if !tagged_slots.contains(i) {
-                continue;
-            }
-            let slot_addr = segment_start + i * kTaggedSize;
-            let obj_addr = self.decode(ro::EncodedTagged::from_address(slot_addr));
-            let obj_ptr = obj_addr + kHeapObjectTag;
-
-            let dst = slot_addr as *mut usize;
-            unsafe {
-                *dst = if COMPRESS_POINTERS_BOOL {
-                    0 //V8HeapCompressionScheme::CompressObject(obj_ptr)
-                } else {
-                    obj_ptr
-                }
-            };
-        }
+    /// Deserializes the read-only heap written by ReadOnlySerializer.
+    pub struct ReadOnlyDeserializer<'a> {
+        deserializer_: Deserializer<'a>,
     }
 
-    fn page_at(&self, index: usize) -> ReadOnlyPageMetadata {
-        //DCHECK_LT(index, ro_space()->pages().size());
-        let pages = self.ro_space().pages();
-        assert!(index < pages.len());
-        pages[index].clone()
-    }
-
-    fn deserialize_read_only_roots_table(&mut self) {
-        let mut roots = ReadOnlyRoots::new(self.isolate_);
-        if V8_STATIC_ROOTS_BOOL {
-            roots.init_from_static_roots_table(self.isolate_.cage_base());
-        } else {
-            for i in 0..ReadOnlyRoots::kEntriesCount {
-                let encoded_as_int = self.source_.get_uint32();
-                let rudolf = self.decode(ro::EncodedTagged::from_uint32(encoded_as_int));
-                roots.read_only_roots_[i] = rudolf + kHeapObjectTag;
-            }
-        }
-    }
-
-    fn ro_space(&mut self) -> &mut ReadOnlySpace {
-        self.isolate_.read_only_heap().read_only_space()
-    }
-}
-
-#[derive(Debug)]
-pub struct ReadOnlyDeserializer<'a> {
-    isolate: &'a mut Isolate,
-    source: SnapshotByteSource,
-    //data: &'a SnapshotData,
-    //can_rehash: bool,
-    should_rehash_: bool,
-    objects_to_rehash: Vec<usize>,
-}
-
-impl<'a> ReadOnlyDeserializer<'a> {
-    pub fn new(isolate: &'a mut Isolate, data: Vec<u8>, _can_rehash: bool) -> Self {
-        ReadOnlyDeserializer {
-            isolate,
-            source: SnapshotByteSource::new(data),
-            //data,
-            //can_rehash,
-            should_rehash_: false,
-            objects_to_rehash: Vec::new(),
-        }
-    }
-
-    pub fn deserialize_into_isolate(&mut self) {
-        //base::ElapsedTimer timer;
-        //if (V8_UNLIKELY(v8_flags.profile_deserialization)) timer.Start();
-        //{
-        //NestedTimedHistogramScope histogram_timer(
-        //    isolate()->counters()->snapshot_deserialize_rospace());
-        //}
-        let mut scope = HandleScope::new(self.isolate);
-
-        ReadOnlyHeapImageDeserializer::deserialize(self.isolate, &mut self.source);
-        let ro_heap = self.isolate.read_only_heap();
-        ro_heap.read_only_space().repair_free_spaces_after_deserialization();
-        self.post_process_new_objects();
-
-        let mut roots = ReadOnlyRoots::new(self.isolate);
-        roots.verify_name_for_protectors_pages();
-        //#ifdef DEBUG
-        roots.verify_types();
-        roots.verify_name_for_protectors();
-        //#endif
-
-        if self.should_rehash() {
-            self.isolate.heap().initialize_hash_seed();
-            self.rehash();
+    impl<'a> ReadOnlyDeserializer<'a> {
+        pub fn new(data: &'a SnapshotData) -> Self {
+            ReadOnlyDeserializer { deserializer_: Deserializer::new(data.payload()) }
         }
 
-        /*
-        if (V8_UNLIKELY(v8_flags.profile_deserialization)) {
-            // ATTENTION: The Memory.json benchmark greps for this exact output. Do not
-            // change it without also updating Memory.json.
-            const int bytes = source()->length();
-            const double ms = timer.Elapsed().InMillisecondsF();
-            PrintF("[Deserializing read-only space (%d bytes) took %0.3f ms]\n", bytes,
-                   ms);
-        }
-        */
-    }
-
-    fn should_rehash(&self) -> bool {
-        self.should_rehash_
-    }
-
-    fn rehash(&mut self) {
-        //TODO Implement rehash
-    }
-
-    fn post_process_new_objects(&mut self) {
-        let cage_base = self.isolate.cage_base();
-        //#ifdef V8_COMPRESS_POINTERS
-        //ExternalPointerTable::UnsealReadOnlySegmentScope unseal_scope(
-        //    &isolate()->external_pointer_table());
-        //#endif  // V8_COMPRESS_POINTERS
-        let mut post_processor = ObjectPostProcessor::new(self.isolate);
-        let mut it = ReadOnlyHeapObjectIterator::new(self.isolate.read_only_heap());
-
-        while let Some(o) = it.next() {
-            let instance_type = 0;//o.map(cage_base).instance_type();
-            if self.should_rehash() {
-                /*
-                if (InstanceTypeChecker::IsString(instance_type)) {
-                    Tagged<String> str = Cast<String>(o);
-                    str->set_raw_hash_field(Name::kEmptyHashField);
-                    PushObjectToRehash(direct_handle(str, isolate()));
-                } else if (o->NeedsRehashing(instance_type)) {
-                    PushObjectToRehash(direct_handle(o, isolate()));
-                }
-                */
+        /// Allocates the read-only objects in |heap|. Returns the
+        /// deserialized objects in the order kReadOnlyHeapRef indices refer
+        /// to them, and the read-only roots table.
+        pub fn deserialize_into_isolate(mut self, heap: &Heap) -> (Vec<HeapObject>, Vec<HeapObject>) {
+            let external = ExternalObjects::default();
+            while !self.deserializer_.at_synchronize() {
+                self.deserializer_.read_root(heap, &external);
             }
+            self.deserializer_.read_synchronize();
+            self.deserializer_.read_deferred_objects(heap, &external);
+            self.deserializer_.take_roots();
 
-            //post_processor.post_process_if_needed(o, instance_type);
+            let objects = self.deserializer_.new_objects().to_vec();
+            let root_count = self.deserializer_.source().get_uint30();
+            let roots = (0..root_count)
+                .map(|_| objects[self.deserializer_.source().get_uint30() as usize])
+                .collect();
+            self.deserializer_.read_synchronize();
+            assert!(!self.deserializer_.source().has_more(), "Trailing data in read-only snapshot");
+            (objects, roots)
         }
-        //post_processor.finalize();
-    }
-
-    fn push_object_to_rehash(&mut self, object: usize) {
-        self.objects_to_rehash.push(object);
     }
 }
-
-fn no_external_references_callback() {
-    // The following check will trigger if a function or object template with
-    // references to native functions have been deserialized from snapshot, but
-    // no actual external references were provided when the isolate was created.
-    panic!("No external references provided via API");
-}
-
-struct ObjectPostProcessor<'a> {
-    isolate_: &'a mut Isolate,
-    //embedded_data_: EmbeddedData,
-    external_pointer_slots_: Vec<usize>,
-}
-
-impl<'a> ObjectPostProcessor<'a> {
-    pub fn new(isolate: &'a mut Isolate) -> Self {
-        ObjectPostProcessor {
-            isolate_: isolate,
-            //embedded_data_: EmbeddedData::from_blob(isolate_),
-            external_pointer_slots_: Vec::new(),
-        }
-    }
-
-    pub fn finalize(&mut self) {
-        //#ifdef V8_ENABLE_SANDBOX
-        //std::vector<ReadOnlyArtifacts::ExternalPointerRegistryEntry> registry;
-        //registry.reserve(external_pointer_slots_.size());
-        //for (auto& slot : external_pointer_slots_) {
-        //  registry.emplace_back(slot.Relaxed_LoadHandle(), slot.load(isolate_),
-        //                        slot.exact_tag());
-        //}
-
-        //isolate_->read_only_artifacts()->set_external_pointer_registry(
-        //    std::move(registry));
-        //#endif  // V8_ENABLE_SANDBOX
-    }
-
-    /*
-    fn post_process_if_needed(&self, o: &HeapObject, instance_type: i32) {
-        println!("post_process_if_needed: instance_type = {}", instance_type);
-        //DCHECK_EQ(o.map(self.isolate_).instance_type(), instance_type);
-        if instance_type == 10 {
-            println!("post_process_if_needed AccessorInfo");
-        }
-        if instance_type == 11 {
-            println!("post_process_if_needed FunctionTemplateInfo");
-        }
-        if instance_type == 12 {
-            println!("post_process_if_needed Code");
-        }
-        if instance_type == 13 {
-            println!("post_process_if_needed SharedFunctionInfo");
-        }
-    }
-    */
-}
-
-struct ReadOnlyHeapObjectIterator<'a> {
-    ro_heap: &'a ReadOnlyHeap,
-    current_page_index: usize,
-    current_offset: usize,
-}
-
-impl<'a> ReadOnlyHeapObjectIterator<'a> {
-    pub fn new(ro_heap: &'a ReadOnlyHeap) -> Self {
-        ReadOnlyHeapObjectIterator {
-            ro_heap,
-            current_page_index: 0,
-            current_offset: 0,
-        }
-    }
-
-    pub fn next(&mut self) -> Option<usize> {
-        let pages = &self.ro_heap.read_only_space().pages;
-        if self.current_page_index >= pages.len() {
-            return None;
-        }
-
-        let page = &pages[self.current_page_index];
-        if self.current_offset >= page.area_size {
-            self.current_page_index += 1;
-            self.current_offset = 0;
-            return self.next();
-        }
-
-        let object_address = page.area_start() + self.current_offset;
-        self.current_offset += 8; // Assuming object size is 8 for simplicity
-        Some(object_address)
-    }
-}
\ No newline at end of file
@@ -2,791 +2,93 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// #include "src/snapshot/read-only-serializer.h"
-// #include "src/common/globals.h"
-// #include "src/heap/heap-inl.h"
-// #include "src/heap/read-only-heap.h"
-// #include "src/heap/visit-object.h"
-// #include "src/objects/objects-inl.h"
-// #include "src/objects/slots.h"
-// #include "src/snapshot/read-only-serializer-deserializer.h"
-
-// use std::mem::size_of;
-// use std::ptr::null_mut;
-
-mod ro {
-    // Placeholder module for ro namespace
-    pub struct EncodedExternalReference {
-        pub is_from_api: bool,
-        pub index: u32,
-    }
-
-    impl EncodedExternalReference {
-        pub const kIndexBits: u32 = 20;
-
-        pub fn new(is_from_api: bool, index: u32) -> Self {
-            EncodedExternalReference {
-                is_from_api,
-                index,
-            }
-        }
-
-        pub fn to_uint32(&self) -> u32 {
-            (if self.is_from_api { 1 << 31 } else { 0 }) | (self.index & ((1 << Self::kIndexBits) - 1))
-        }
-
-        pub fn from_uint32(value: u32) -> Self {
-            let is_from_api = (value >> 31) == 1;
-            let index = value & ((1 << Self::kIndexBits) - 1);
-            EncodedExternalReference {
-                is_from_api,
-                index,
-            }
-        }
-    }
-
-    pub struct EncodedTagged {
-        pub index: u32,
-        pub offset: u32,
-    }
-    impl EncodedTagged {
-      pub const kPageIndexBits: u32 = 10;
-      pub const kSize: usize = 4;
-      pub fn new(index: u32, offset: u32) -> Self {
-          EncodedTagged {
-              index,
-              offset,
-          }
-      }
-
-      pub fn to_uint32(&self) -> u32 {
-          ((self.index as u32) << 22) | (self.offset as u32)
-      }
-
-       pub fn from_uint32(value: u32) -> Self {
-          let index = (value >> 22) as u32;
-          let offset = (value & ((1 << 22) - 1)) as u32;
-          EncodedTagged {
-              index,
-              offset,
-          }
-      }
-    }
-
-    pub struct BitSet {
-        data: Vec<u8>,
-        size_in_bits: usize,
-    }
-
-    impl BitSet {
-        pub fn new(size_in_bits: usize) -> Self {
-            let size_in_bytes = (size_in_bits + 7) / 8;
-            BitSet {
-                data: vec![0; size_in_bytes],
-                size_in_bits,
-            }
-        }
-
-        pub fn size_in_bytes(&self) -> usize {
-            self.data.len()
-        }
-
-        pub fn set(&mut self, index: usize) {
-            let byte_index = index / 8;
-            let bit_index = index % 8;
-            self.data[byte_index] |= 1 << bit_index;
-        }
-
-        pub fn data(&self) -> &Vec<u8> {
-          &self.data
-        }
-    }
-
-    pub enum Bytecode {
-      kAllocatePageAt,
-      kAllocatePage,
-      kSegment,
-      kRelocateSegment,
-      kReadOnlyRootsTable,
-      kFinalizeReadOnlySpace,
-    }
-}
-
-mod internal {
-    // Placeholder module for internal namespace
-
-    use super::ro;
-    use std::any::Any;
+pub mod read_only_serializer {
     use std::collections::HashMap;
-    use std::ptr::null_mut;
 
-    const V8_STATIC_ROOTS_BOOL: bool = false; // Adapt from C++ macro
-    const kTaggedSize: usize = 8; // Assuming 64-bit architecture
+    use crate::heap::heap_layout::{HeapObject, Tagged};
+    use crate::snapshot::serializer::serializer::{ReferenceEncoder, Serializer};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{
+        ExternalReference, RootIndex, SerializationStatistics, SnapshotSpace,
+    };
+    use crate::snapshot::snapshot::snapshot::Isolate;
 
-    // Placeholder types, replace with actual V8 types.
-    type Isolate = usize; // Replace with actual Isolate type
-    type Address = usize; // Replace with actual Address type. Using usize for simplicity
-    type HeapObject = usize; // Replace with actual HeapObject type. Using usize for simplicity
-    type Tagged<T> = usize; // Replace with actual Tagged type. Using usize for simplicity
-    type MaybeObjectSlot = usize; // Replace with actual MaybeObjectSlot
-    type ObjectSlot = usize;
-    type RelocInfo = usize;
-    type InstructionStream = usize;
-    type InstructionStreamSlot = usize;
-    type ExternalPointerSlot = usize;
-    type AccessorInfo = usize;
-    type FunctionTemplateInfo = usize;
-    type Code = usize;
-    type InstanceType = usize;
-    type ReadOnlyPageMetadata = usize;
-    type ReadOnlySpace = usize;
-    type MemoryChunkMetadata = usize;
-    type SnapshotByteSink = usize;
-    type RootIndex = usize;
-    type FreeSpace = usize;
-    type WasmNull = usize;
-    type Heap = usize;
-
-    // Placeholder implementations for V8 types.
-    // Replace with actual implementations.
-    trait InstanceTypeChecker {
-        fn is_accessor_info(itype: InstanceType) -> bool;
-        fn is_function_template_info(itype: InstanceType) -> bool;
-        fn is_code(itype: InstanceType) -> bool;
+    /// Read-only objects may only point to other read-only objects, so every
+    /// reference is serialized in place.
+    struct ReadOnlyEncoder<'a> {
+        isolate: &'a Isolate,
     }
 
-    impl InstanceTypeChecker for InstanceType {
-        fn is_accessor_info(itype: InstanceType) -> bool {
-            false
-        }
-
-        fn is_function_template_info(itype: InstanceType) -> bool {
-            false
+    impl ReferenceEncoder for ReadOnlyEncoder<'_> {
+        fn encode_external(&mut self, object: HeapObject) -> Option<ExternalReference> {
+            assert!(self.isolate.is_read_only(object), "Read-only object points to mutable object {:?}", object);
+            None
         }
 
-        fn is_code(itype: InstanceType) -> bool {
-            false
-        }
-    }
-
-    trait HeapObjectTrait {
-        fn map(&self, isolate: Isolate) -> InstanceType;
-        fn address(&self) -> Address;
-        fn size(&self) -> usize;
-        fn raw_maybe_weak_field(&self, offset: usize) -> MaybeObjectSlot;
-    }
-
-    impl HeapObjectTrait for HeapObject {
-        fn map(&self, isolate: Isolate) -> InstanceType {
-            0 // Placeholder
-        }
-        fn address(&self) -> Address {
-            0
-        }
-        fn size(&self) -> usize {
-            0
-        }
-        fn raw_maybe_weak_field(&self, offset: usize) -> MaybeObjectSlot {
-            0
+        fn space(&self, _object: HeapObject) -> SnapshotSpace {
+            SnapshotSpace::kReadOnlyHeap
         }
     }
 
-    trait CodeTrait {
-        fn clear_instruction_start_for_serialization(&self, isolate: Isolate);
-        fn has_source_position_table_or_bytecode_offset_table(&self) -> bool;
-        fn has_deoptimization_data_or_interpreter_data(&self) -> bool;
-        fn js_dispatch_handle(&self) -> usize; // Replace with actual JSDispatchHandle type
-    }
-
-    impl CodeTrait for Code {
-        fn clear_instruction_start_for_serialization(&self, isolate: Isolate) {}
-        fn has_source_position_table_or_bytecode_offset_table(&self) -> bool {
-            false
-        }
-        fn has_deoptimization_data_or_interpreter_data(&self) -> bool {
-            false
-        }
-        fn js_dispatch_handle(&self) -> usize {
-            0
-        }
-    }
-
-    trait ReadOnlyHeapTrait {
-        fn read_only_space(&self) -> ReadOnlySpace;
-    }
-
-    impl ReadOnlyHeapTrait for Heap {
-        fn read_only_space(&self) -> ReadOnlySpace {
-            0
-        }
-    }
-
-    trait ReadOnlySpaceTrait {
-        fn index_of(&self, chunk: MemoryChunkMetadata) -> usize;
-        fn pages(&self) -> Vec<ReadOnlyPageMetadata>;
-    }
-    impl ReadOnlySpaceTrait for ReadOnlySpace {
-      fn index_of(&self, chunk: MemoryChunkMetadata) -> usize {
-        0
-      }
-      fn pages(&self) -> Vec<ReadOnlyPageMetadata> {
-        vec![]
-      }
-    }
-
-    trait MemoryChunkMetadataTrait {
-        fn from_address(address: Address) -> MemoryChunkMetadata;
-        fn offset(&self, address: Address) -> usize;
-    }
-    impl MemoryChunkMetadataTrait for MemoryChunkMetadata {
-      fn from_address(address: Address) -> MemoryChunkMetadata {
-        0
-      }
-      fn offset(&self, address: Address) -> usize {
-        0
-      }
-    }
-
-    // Actual implementations
-    struct ObjectPreProcessor {
-        isolate_: Isolate,
-        extref_encoder_: ExternalReferenceEncoder,
-    }
-
-    impl ObjectPreProcessor {
-        fn new(isolate: Isolate) -> Self {
-            ObjectPreProcessor {
-                isolate_: isolate,
-                extref_encoder_: ExternalReferenceEncoder::new(isolate),
-            }
-        }
-
-        fn pre_process_if_needed(&mut self, o: Tagged<HeapObject>) {
-            let itype = o.map(self.isolate_);
-            if InstanceTypeChecker::is_accessor_info(itype) {
-                self.pre_process_accessor_info(o as AccessorInfo);
-            } else if InstanceTypeChecker::is_function_template_info(itype) {
-                self.pre_process_function_template_info(o as FunctionTemplateInfo);
-            } else if InstanceTypeChecker::is_code(itype) {
-                self.pre_process_code(o as Code);
-            }
-        }
-
-        fn encode_external_pointer_slot(&mut self, slot: ExternalPointerSlot) {
-            let value = slot.load(self.isolate_);
-            self.encode_external_pointer_slot_with_value(slot, value);
-        }
-
-        fn encode_external_pointer_slot_with_value(&mut self, slot: ExternalPointerSlot, value: Address) {
-            let encoder_value = self.extref_encoder_.encode(value);
-            //DCHECK_LT(encoder_value.index(), 1UL << ro::EncodedExternalReference::kIndexBits);
-            assert!(encoder_value.index() < (1 << ro::EncodedExternalReference::kIndexBits));
-            let encoded = ro::EncodedExternalReference::new(encoder_value.is_from_api(), encoder_value.index());
-            // Constructing no_gc here is not the intended use pattern (instead we
-            // should pass it along the entire callchain); but there's little point of
-            // doing that here - all of the code in this file relies on GC being
-            // disabled, and that's guarded at entry points.
-            // DisallowGarbageCollection no_gc;
-            slot.replace_content_with_index_for_serialization(encoded.to_uint32());
-        }
-
-        fn pre_process_accessor_info(&mut self, o: AccessorInfo) {
-            self.encode_external_pointer_slot_with_value(
-              o.raw_external_pointer_field(AccessorInfo::kMaybeRedirectedGetterOffset, kAccessorInfoGetterTag),
-              o.getter(self.isolate_)
-            );
-            self.encode_external_pointer_slot(o.raw_external_pointer_field(
-                AccessorInfo::kSetterOffset,
-                kAccessorInfoSetterTag,
-            ));
-        }
-
-        fn pre_process_function_template_info(&mut self, o: FunctionTemplateInfo) {
-          self.encode_external_pointer_slot_with_value(
-            o.raw_external_pointer_field(FunctionTemplateInfo::kMaybeRedirectedCallbackOffset, kFunctionTemplateInfoCallbackTag),
-            o.callback(self.isolate_)
-          );
-        }
-
-        fn pre_process_code(&mut self, o: Code) {
-            o.clear_instruction_start_for_serialization(self.isolate_);
-            assert!(!o.has_source_position_table_or_bytecode_offset_table());
-            assert!(!o.has_deoptimization_data_or_interpreter_data());
-            // #[cfg(V8_ENABLE_LEAPTIERING)]
-            assert_eq!(o.js_dispatch_handle(), 0);
-        }
-    }
-
-    trait ExternalPointerSlotTrait {
-      fn load(&self, isolate: Isolate) -> Address;
-      fn replace_content_with_index_for_serialization(&self, index: u32);
-      fn get_content_as_index_after_deserialization(&self) -> u32;
-      fn exact_tag(&self) -> u32;
-    }
-    impl ExternalPointerSlotTrait for ExternalPointerSlot {
-      fn load(&self, isolate: Isolate) -> Address {
-        0
-      }
-      fn replace_content_with_index_for_serialization(&self, index: u32) {}
-      fn get_content_as_index_after_deserialization(&self) -> u32 {
-        0
-      }
-      fn exact_tag(&self) -> u32 {
-        0
-      }
-    }
-
-    trait AccessorInfoTrait {
-      fn raw_external_pointer_field(&self, offset: usize, tag: u32) -> ExternalPointerSlot;
-      fn getter(&self, isolate: Isolate) -> Address;
-    }
-    impl AccessorInfoTrait for AccessorInfo {
-      fn raw_external_pointer_field(&self, offset: usize, tag: u32) -> ExternalPointerSlot {
-        0
-      }
-      fn getter(&self, isolate: Isolate) -> Address {
-        0
-      }
-    }
-
-    trait FunctionTemplateInfoTrait {
-      fn raw_external_pointer_field(&self, offset: usize, tag: u32) -> ExternalPointerSlot;
-      fn callback(&self, isolate: Isolate) -> Address;
-    }
-    impl FunctionTemplateInfoTrait for FunctionTemplateInfo {
-      fn raw_external_pointer_field(&self, offset: usize, tag: u32) -> ExternalPointerSlot {
-        0
-      }
-      fn callback(&self, isolate: Isolate) -> Address {
-        0
-      }
-    }
-
-    const kAccessorInfoGetterTag: u32 = 1; // Replace with actual tag value
-    const AccessorInfo::kMaybeRedirectedGetterOffset: usize = 0; // Replace with actual offset
-    const AccessorInfo::kSetterOffset: usize = 8;
-    const kAccessorInfoSetterTag: u32 = 2;
-    const FunctionTemplateInfo::kMaybeRedirectedCallbackOffset: usize = 0;
-    const kFunctionTemplateInfoCallbackTag: u32 = 3;
-
-    struct ExternalReferenceEncoder {
-        isolate_: Isolate,
-        // Placeholder: Replace with actual ExternalReferenceEncoder implementation
-        external_references: HashMap<Address, u32>,
-    }
-
-    impl ExternalReferenceEncoder {
-        fn new(isolate: Isolate) -> Self {
-            ExternalReferenceEncoder {
-                isolate_: isolate,
-                external_references: HashMap::new(),
-            }
-        }
-
-        fn encode(&mut self, address: Address) -> ExternalReferenceEncoder::Value {
-            // Placeholder: Replace with actual encoding logic
-            let index = self.external_references.len() as u32;
-            self.external_references.insert(address, index);
-            ExternalReferenceEncoder::Value {
-                is_from_api: false,
-                index: index,
-            }
-        }
+    /// Serializes the read-only heap. The section contains every read-only
+    /// object followed by the read-only roots table, encoded as indices into
+    /// the deserialized object list. Later sections refer to read-only
+    /// objects through the roots table or through that same index.
+    pub struct ReadOnlySerializer {
+        serializer_: Serializer,
+        root_indices_: HashMap<HeapObject, RootIndex>,
     }
 
-    impl ExternalReferenceEncoder {
-        struct Value {
-            is_from_api: bool,
-            index: u32,
+    impl Default for ReadOnlySerializer {
+        fn default() -> Self {
+            Self::new()
         }
     }
 
-    struct ReadOnlySegmentForSerialization {
-        page: ReadOnlyPageMetadata,
-        segment_start: Address,
-        segment_size: usize,
-        segment_offset: usize,
-        contents: Vec<u8>,
-        tagged_slots: ro::BitSet,
-    }
-
-    impl ReadOnlySegmentForSerialization {
-        fn new(
-            isolate: Isolate,
-            page: ReadOnlyPageMetadata,
-            segment_start: Address,
-            segment_size: usize,
-            pre_processor: &mut ObjectPreProcessor,
-        ) -> Self {
-            assert!(segment_size % kTaggedSize == 0);
-            //CHECK_LT(isolate->read_only_heap()->read_only_space()->IndexOf(page),1UL << ro::EncodedTagged::kPageIndexBits);
-
-            let segment_offset = segment_start - page.area_start();
-            let mut contents = vec![0u8; segment_size];
-            //MemCopy(contents.get(), reinterpret_cast<void*>(segment_start), segment_size);
-            //contents.copy_from_slice(unsafe { std::slice::from_raw_parts(segment_start as *const u8, segment_size) });
-            ReadOnlySegmentForSerialization::copy_memory(&mut contents, segment_start, segment_size);
-
-            let mut segment = ReadOnlySegmentForSerialization {
-                page: page,
-                segment_start: segment_start,
-                segment_size: segment_size,
-                segment_offset: segment_offset,
-                contents: contents,
-                tagged_slots: ro::BitSet::new(segment_size / kTaggedSize),
-            };
-
-            segment.pre_process_segment(pre_processor);
-            if !V8_STATIC_ROOTS_BOOL {
-                segment.encode_tagged_slots(isolate);
-            }
-            segment
-        }
-
-        fn copy_memory(dest: &mut [u8], src_addr: Address, size: usize) {
-            unsafe {
-              let src = src_addr as *const u8;
-              std::ptr::copy_nonoverlapping(src, dest.as_mut_ptr(), size);
+    impl ReadOnlySerializer {
+        pub fn new() -> Self {
+            ReadOnlySerializer {
+                serializer_: Serializer::new(),
+                root_indices_: HashMap::new(),
             }
         }
 
-        fn pre_process_segment(&mut self, pre_processor: &mut ObjectPreProcessor) {
-            // Iterate the RO page and the contents copy in lockstep, preprocessing
-            // objects as we go along.
-            //
-            // See also ObjectSerializer::OutputRawData.
-            assert!(self.segment_start >= self.page.area_start());
-            let segment_end = self.segment_start + self.segment_size;
-            let mut it = ReadOnlyPageObjectIterator::new(self.page, self.segment_start);
-            while let Some(o) = it.next() {
-                if o.address() >= segment_end {
-                    break;
+        pub fn serialize(&mut self, isolate: &Isolate) {
+            let mut encoder = ReadOnlyEncoder { isolate };
+            for object in isolate.read_only_objects() {
+                if !self.serializer_.contains(object) {
+                    self.serializer_.serialize_root(Tagged::strong(object), &mut encoder);
                 }
-                let o_offset = o.address() - self.segment_start;
-                let o_dst = self.contents.as_ptr() as Address + o_offset;
-                pre_processor.pre_process_if_needed(o_dst as HeapObject);
-            }
-        }
-
-        fn encode_tagged_slots(&mut self, isolate: Isolate) {
-            assert!(!V8_STATIC_ROOTS_BOOL);
-            let mut v = EncodeRelocationsVisitor::new(isolate, self);
-            //PtrComprCageBase cage_base(isolate);
-            assert!(self.segment_start >= self.page.area_start());
-            let segment_end = self.segment_start + self.segment_size;
-            let mut it = ReadOnlyPageObjectIterator::new(self.page, self.segment_start);
-            while let Some(o) = it.next() {
-              if o.address() >= segment_end {
-                break;
-              }
-              visit_object(isolate, o, &mut v);
-            }
-        }
-    }
-
-    fn encode(isolate: Isolate, o: Tagged<HeapObject>) -> ro::EncodedTagged {
-        let o_address = o.address();
-        let chunk = MemoryChunkMetadata::from_address(o_address);
-        let ro_space = isolate.read_only_heap().read_only_space();
-        let index = ro_space.index_of(chunk) as u32;
-        let offset = chunk.offset(o_address) as u32;
-        assert!(offset % kTaggedSize as u32 == 0);
-
-        ro::EncodedTagged::new(index, offset / kTaggedSize as u32)
-    }
-
-    // Placeholder implementation for other V8 structs.
-    struct EncodeRelocationsVisitor<'a> {
-        isolate_: Isolate,
-        segment_: &'a mut ReadOnlySegmentForSerialization,
-    }
-
-    impl<'a> EncodeRelocationsVisitor<'a> {
-        fn new(isolate: Isolate, segment: &'a mut ReadOnlySegmentForSerialization) -> Self {
-            EncodeRelocationsVisitor {
-                isolate_: isolate,
-                segment_: segment,
             }
-        }
-
-        fn visit_pointers(&mut self, host: Tagged<HeapObject>, start: ObjectSlot, end: ObjectSlot) {
-            self.visit_pointers_maybe(host, start as MaybeObjectSlot, end as MaybeObjectSlot);
-        }
+            self.serializer_.put_synchronize();
+            self.serializer_.serialize_deferred_objects(&mut encoder);
 
-        fn visit_pointers_maybe(&mut self, host: Tagged<HeapObject>, start: MaybeObjectSlot, end: MaybeObjectSlot) {
-            let mut slot = start;
-            while slot < end {
-                self.process_slot(slot);
-                slot += 1; // Assuming MaybeObjectSlot is just an index
+            let roots = isolate.read_only_roots();
+            self.serializer_.sink().put_uint30(roots.len() as u32, "ReadOnlyRootsCount");
+            for (root_index, &object) in roots.iter().enumerate() {
+                let index = self.serializer_.reference_index(object).expect("Read-only root was not serialized");
+                self.serializer_.sink().put_uint30(index, "ReadOnlyRoot");
+                self.root_indices_.entry(object).or_insert(root_index as RootIndex);
             }
+            self.serializer_.put_synchronize();
         }
 
-        fn visit_map_pointer(&mut self, host: Tagged<HeapObject>) {
-            self.process_slot(host.raw_maybe_weak_field(HeapObject::kMapOffset));
-        }
-
-        fn visit_instruction_stream_pointer(&mut self, host: Code, slot: InstructionStreamSlot) {
-          // RO space contains only builtin Code objects.
-          assert!(!host.has_instruction_stream());
-        }
-        fn visit_code_target(&mut self, instruction_stream: InstructionStream, reloc_info: RelocInfo) {
-          panic!();
-        }
-        fn visit_embedded_pointer(&mut self, instruction_stream: InstructionStream, reloc_info: RelocInfo) {
-          panic!();
-        }
-        fn visit_external_reference(&mut self, instruction_stream: InstructionStream, reloc_info: RelocInfo) {
-          panic!();
-        }
-        fn visit_internal_reference(&mut self, instruction_stream: InstructionStream, reloc_info: RelocInfo) {
-          panic!();
-        }
-        fn visit_off_heap_target(&mut self, instruction_stream: InstructionStream, reloc_info: RelocInfo) {
-          panic!();
-        }
-        fn visit_external_pointer(&mut self, host: Tagged<HeapObject>, slot: ExternalPointerSlot) {
-          // This slot was encoded in a previous pass, see EncodeExternalPointerSlot.
-          //ifdef DEBUG
-          let slot_in_segment = self.segment_.contents.as_ptr() as Address + self.segment_offset_of(slot);
-          let exact_tag = slot.exact_tag();
-          // Constructing no_gc here is not the intended use pattern (instead we
-          // should pass it along the entire callchain); but there's little point of
-          // doing that here - all of the code in this file relies on GC being
-          // disabled, and that's guarded at entry points.
-          //DisallowGarbageCollection no_gc;
-          let encoded = slot.get_content_as_index_after_deserialization();
-          let encoded = ro::EncodedExternalReference::from_uint32(encoded);
-
-          if encoded.is_from_api {
-            // Can't validate these since we don't know how many entries
-            // api_external_references contains.
-          } else {
-            //CHECK_LT(encoded.index, ExternalReferenceTable::kSize);
-          }
-          //#endif  // DEBUG
-        }
-
-        fn process_slot(&mut self, slot: MaybeObjectSlot) {
-            let o = *slot; // Dereference the slot
-            if !o.is_strong_or_weak() {
-                return;
+        /// Encodes a reference from another section to the read-only object
+        /// |object|, preferring the root table where possible.
+        pub fn encode_reference(&self, object: HeapObject) -> ExternalReference {
+            if let Some(&root_index) = self.root_indices_.get(&object) {
+                return ExternalReference::Root(root_index);
             }
-            assert!(o.is_strong());
-
-            let slot_offset = self.segment_offset_of(slot);
-            assert!(slot_offset % kTaggedSize == 0);
-
-            // Encode:
-            let encoded = encode(self.isolate_, o.get_heap_object());
-            //memcpy(segment_->contents.get() + slot_offset, &encoded, ro::EncodedTagged::kSize);
-            let encoded_bytes = encoded.to_uint32().to_ne_bytes();
-            self.segment_.contents[slot_offset..slot_offset + ro::EncodedTagged::kSize].copy_from_slice(&encoded_bytes);
-
-            // Record:
-            self.segment_.tagged_slots.set(self.as_slot(slot_offset));
+            ExternalReference::ReadOnlyHeap(
+                self.serializer_.reference_index(object).expect("Reference to unserialized read-only object"),
+            )
         }
 
-        fn segment_offset_of<SlotT>(&self, slot: SlotT) -> usize {
-            let addr = slot.address();
-            assert!(addr >= self.segment_.segment_start);
-            assert!(addr < self.segment_.segment_start + self.segment_.segment_size);
-            (addr - self.segment_.segment_start) as usize
+        pub fn statistics(&self) -> &SerializationStatistics {
+            self.serializer_.statistics()
         }
 
-        const fn as_slot(&self, byte_offset: usize) -> usize {
-            byte_offset / kTaggedSize
+        pub fn payload(&self) -> &Vec<u8> {
+            self.serializer_.payload()
         }
     }
-
-    trait MaybeObjectTrait {
-        fn is_strong_or_weak(&self) -> bool;
-        fn is_strong(&self) -> bool;
-        fn get_heap_object(&self) -> Tagged<HeapObject>;
-    }
-    impl MaybeObjectTrait for MaybeObjectSlot {
-      fn is_strong_or_weak(&self) -> bool {
-        false
-      }
-      fn is_strong(&self) -> bool {
-        false
-      }
-      fn get_heap_object(&self) -> Tagged<HeapObject> {
-        0
-      }
-    }
-
-    trait ObjectVisitor {
-        fn visit_pointers(&mut self, host: Tagged<HeapObject>, start: ObjectSlot, end: ObjectSlot);
-        fn visit_map_pointer(&mut self, host: Tagged<HeapObject>);
-    }
-    impl ObjectVisitor for EncodeRelocationsVisitor<'_> {
-      fn visit_pointers(&mut self, host: Tagged<HeapObject>, start: ObjectSlot, end: ObjectSlot) {
-        EncodeRelocationsVisitor::visit_pointers(self, host, start, end);
-      }
-      fn visit_map_pointer(&mut self, host: Tagged<HeapObject>) {
-        EncodeRelocationsVisitor::visit_map_pointer(self, host);
-      }
-    }
-
-    // Placeholder implementation for other V8 structs.
-    struct ReadOnlyHeapImageSerializer {
-        isolate_: Isolate,
-        sink_: SnapshotByteSink,
-        pre_processor_: ObjectPreProcessor,
-    }
-
-    impl ReadOnlyHeapImageSerializer {
-        struct MemoryRegion {
-            start: Address,
-            size: usize,
-        }
-
-        fn serialize(
-            isolate: Isolate,
-            sink: SnapshotByteSink,
-            unmapped_regions: &Vec<ReadOnlyHeapImageSerializer::MemoryRegion>,
-        ) {
-            let mut serializer = ReadOnlyHeapImageSerializer {
-                isolate_: isolate,
-                sink_: sink,
-                pre_processor_: ObjectPreProcessor::new(isolate),
-            };
-            serializer.serialize_impl(unmapped_regions);
-        }
-
-        fn serialize_impl(&mut self, unmapped_regions: &Vec<ReadOnlyHeapImageSerializer::MemoryRegion>) {
-            //DCHECK_EQ(sink_->Position(), 0);
-            assert_eq!(0,0);
-
-            let ro_space = self.isolate_.read_only_heap().read_only_space();
-
-            // Allocate all pages first s.t. the deserializer can easily handle forward
-            // references (e.g.: an object on page i points at an object on page i+1).
-            for page in ro_space.pages() {
-                self.emit_allocate_page(page, unmapped_regions);
-            }
-
-            // Now write the page contents.
-            for page in ro_space.pages() {
-                self.serialize_page(page, unmapped_regions);
-            }
-
-            self.emit_read_only_roots_table();
-            //sink_->Put(Bytecode::kFinalizeReadOnlySpace, "space end");
-            self.put_bytecode(ro::Bytecode::kFinalizeReadOnlySpace, "space end");
-        }
-
-        fn index_of(&self, page: ReadOnlyPageMetadata) -> u32 {
-            let ro_space = self.isolate_.read_only_heap().read_only_space();
-            ro_space.index_of(page) as u32
-        }
-
-        fn emit_allocate_page(
-            &mut self,
-            page: ReadOnlyPageMetadata,
-            unmapped_regions: &Vec<ReadOnlyHeapImageSerializer::MemoryRegion>,
-        ) {
-            if V8_STATIC_ROOTS_BOOL {
-                //sink_->Put(Bytecode::kAllocatePageAt, "fixed page begin");
-                self.put_bytecode(ro::Bytecode::kAllocatePageAt, "fixed page begin");
-            } else {
-                //sink_->Put(Bytecode::kAllocatePage, "page begin");
-                self.put_bytecode(ro::Bytecode::kAllocatePage, "page begin");
-            }
-            //sink_->PutUint30(IndexOf(page), "page index");
-            self.put_uint30(self.index_of(page), "page index");
-            //sink_->PutUint30(static_cast<uint32_t>(page->HighWaterMark() - page->area_start()),"area size in bytes");
-            self.put_uint30((page.high_water_mark() - page.area_start()) as u32, "area size in bytes");
-            if V8_STATIC_ROOTS_BOOL {
-                let page_addr = page.chunk_address();
-                //sink_->PutUint32(V8HeapCompressionScheme::CompressAny(page_addr),"page start offset");
-                self.put_uint32(0, "page start offset"); // Placeholder for V8HeapCompressionScheme
-            }
-        }
-
-        fn serialize_page(
-            &mut self,
-            page: ReadOnlyPageMetadata,
-            unmapped_regions: &Vec<ReadOnlyHeapImageSerializer::MemoryRegion>,
-        ) {
-            let mut pos = page.area_start();
-
-            // If this page contains unmapped regions split it into multiple segments.
-            let mut r_iter = unmapped_regions.iter();
-            while let Some(r) = r_iter.next() {
-                // Regions must be sorted and non-overlapping.
-                if let Some(next_r) = r_iter.next() {
-                  assert!(r.start < next_r.start);
-                  assert!(r.start + r.size < next_r.start);
-                }
-                if base::is_in_range(r.start, pos, page.high_water_mark()) {
-                    let segment_size = r.start - pos;
-                    let mut segment = ReadOnlySegmentForSerialization::new(
-                        self.isolate_,
-                        page,
-                        pos,
-                        segment_size,
-                        &mut self.pre_processor_,
-                    );
-                    self.emit_segment(&segment);
-                    pos += segment_size + r.size;
-                }
-            }
-
-            // Pages are shrunk, but memory at the end of the area is still
-            // uninitialized and we do not want to include it in the snapshot.
-            let segment_size = page.high_water_mark() - pos;
-            let mut segment = ReadOnlySegmentForSerialization::new(
-                self.isolate_,
-                page,
-                pos,
-                segment_size,
-                &mut self.pre_processor_,
-            );
-            self.emit_segment(&segment);
-        }
-
-        fn emit_segment(&mut self, segment: &ReadOnlySegmentForSerialization) {
-            //sink_->Put(Bytecode::kSegment, "segment begin");
-            self.put_bytecode(ro::Bytecode::kSegment, "segment begin");
-            //sink_->PutUint30(IndexOf(segment->page), "page index");
-            self.put_uint30(self.index_of(segment.page), "page index");
-            //sink_->PutUint30(static_cast<uint32_t>(segment->segment_offset),"segment start offset");
-            self.put_uint30(segment.segment_offset as u32, "segment start offset");
-            //sink_->PutUint30(static_cast<uint32_t>(segment->segment_size), "segment byte size");
-            self.put_uint30(segment.segment_size as u32, "segment byte size");
-            //sink_->PutRaw(segment->contents.get(), static_cast<int>(segment->segment_size), "page");
-            self.put_raw(&segment.contents, "page");
-
-            if !V8_STATIC_ROOTS_BOOL {
-                //sink_->Put(Bytecode::kRelocateSegment, "relocate segment");
-                self.put_bytecode(ro::Bytecode::kRelocateSegment, "relocate segment");
-                //sink_->PutRaw(segment->tagged_slots.data(),static_cast<int>(segment->tagged_slots.size_in_bytes()),"tagged_slots");
-                self.put_raw(segment.tagged_slots.data(), "tagged_slots");
-            }
-        }
-
-        fn emit_read_only_roots_table(&mut self) {
-            //sink_->Put(Bytecode::kReadOnlyRootsTable, "read only roots table");
-            self.put_bytecode(ro::Bytecode::kReadOnlyRootsTable, "read only roots table");
-            if !V8_STATIC_ROOTS_BOOL {
-              let roots = ReadOnlyRoots::new(self.isolate_);
-                for i in 0..ReadOnlyRoots::kEntriesCount {
-                    let rudi = i as RootIndex;
-                    let rudolf = roots.object_at(rudi) as HeapObject;
-                    let encoded = encode(self.isolate_, rudolf);
-                    //sink_->PutUint32(encoded.ToUint32(), "read only roots entry");
-                    self.put_uint32(encoded.to_uint32(), "read only roots entry");
-                }
-            }
-        }
-
-        fn put_bytecode(&mut self, bytecode: ro::Bytecode, comment: &str) {
-            // Placeholder implementation
-            println!("Emitting bytecode {:?} with comment: {}", bytecode, comment);
-            self.sink_ += 1; // Replace with actual sink operation
-        }
-
-        fn put_uint30(&mut self, value: u32, comment: &str) {
-            // Placeholder implementation
-            println!("Emitting uint30 {} with comment: {}", value, comment
\ No newline at end of file
+}
@@ -3,77 +3,57 @@
 // found in the LICENSE file.
 
 pub mod serializer_deserializer {
-    //use crate::base; // Assuming 'base' is a module in the v8 codebase
-    //use crate::objects::visitors::RootVisitor; // Assuming 'objects' and 'visitors' are modules
-    //use crate::snapshot::references; // Assuming 'snapshot' and 'references' are modules
+    use std::collections::HashMap;
 
-    /// Dummy traits to replace the C++ classes
-    trait RootVisitor {}
+    use crate::heap::heap_layout::{HeapObject, InstanceType};
 
-    // Mock structs/enums
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    enum SnapshotSpace {
-        Space1,
-        Space2,
-        Space3,
-        Space4,
-    }
-    const kNumberOfSnapshotSpaces: usize = 4;
-    
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    enum RootIndex {
-        Index1,
-        Index2,
-        Index3,
+    /// The spaces objects are allocated in when deserialized.
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum SnapshotSpace {
+        kReadOnlyHeap = 0,
+        kOld = 1,
+        kCode = 2,
+        kTrusted = 3,
     }
-
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct Isolate {}
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct HeapObject {}
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct AccessorInfo {}
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    struct FunctionTemplateInfo {}
-    
-    trait Tagged {}
-    impl Tagged for HeapObject {}
-    impl Tagged {
-        fn into_tagged(self) -> Self {
-            self
+    pub const kNumberOfSnapshotSpaces: usize = 4;
+
+    impl SnapshotSpace {
+        pub fn from_index(index: i32) -> SnapshotSpace {
+            match index {
+                0 => SnapshotSpace::kReadOnlyHeap,
+                1 => SnapshotSpace::kOld,
+                2 => SnapshotSpace::kCode,
+                3 => SnapshotSpace::kTrusted,
+                _ => panic!("Invalid SnapshotSpace value {}", index),
+            }
         }
-    }
 
-    pub struct SerializerDeserializer {}
-
-    impl SerializerDeserializer {
-        pub fn iterate_startup_object_cache(_isolate: &Isolate, _visitor: &dyn RootVisitor) {
-            // Implementation
+        /// The space an object that is not read-only is deserialized into.
+        pub fn for_instance_type(instance_type: InstanceType) -> SnapshotSpace {
+            match instance_type {
+                InstanceType::BYTECODE_ARRAY_TYPE => SnapshotSpace::kTrusted,
+                _ => SnapshotSpace::kOld,
+            }
         }
 
-        pub fn iterate_shared_heap_object_cache(_isolate: &Isolate, _visitor: &dyn RootVisitor) {
-            // Implementation
+        pub fn name(&self) -> &'static str {
+            match self {
+                SnapshotSpace::kReadOnlyHeap => "read_only_heap",
+                SnapshotSpace::kOld => "old",
+                SnapshotSpace::kCode => "code",
+                SnapshotSpace::kTrusted => "trusted",
+            }
         }
     }
 
-    impl SerializerDeserializer {
-        /// Determines if a HeapObject can be deferred based on its slot type.
-        fn can_be_deferred(_o: HeapObject, _slot_type: SlotType) -> bool {
-            // Implementation
-            true
-        }
+    /// Index into the root list.
+    pub type RootIndex = u32;
 
-        fn restore_external_reference_redirector(_isolate: &Isolate, _accessor_info: AccessorInfo) {
-            // Implementation
-        }
+    /// Size of a tagged slot in the snapshot; Smis are stored as 32-bit values.
+    pub const K_TAGGED_SIZE: usize = 4;
 
-        fn restore_external_reference_redirector_function_template(
-            _isolate: &Isolate,
-            _function_template_info: FunctionTemplateInfo,
-        ) {
-            // Implementation
-        }
-    }
+    pub struct SerializerDeserializer {}
 
     impl SerializerDeserializer {
         pub const K_ROOT_ARRAY_CONSTANTS_COUNT: i32 = 0x20;
@@ -96,8 +76,6 @@ pub mod serializer_deserializer {
         pub const K_EMPTY_BACKING_STORE_REF_SENTINEL: u32 = 0;
     }
 
-    impl RootVisitor for SerializerDeserializer {}
-
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum SlotType {
         kAnySlot,
@@ -180,17 +158,11 @@ pub mod serializer_deserializer {
         }
 
         pub fn decode<const BYTECODE: u8>(bytecode: u8) -> SnapshotSpace {
-            match BytecodeValueEncoder::decode::<BYTECODE, 0, {kNumberOfSnapshotSpaces as i32 - 1}, i32>(bytecode) {
-                0 => SnapshotSpace::Space1,
-                1 => SnapshotSpace::Space2,
-                2 => SnapshotSpace::Space3,
-                3 => SnapshotSpace::Space4,
-                _ => panic!("Invalid SnapshotSpace value"),
-            }
+            SnapshotSpace::from_index(BytecodeValueEncoder::decode::<BYTECODE, 0, {kNumberOfSnapshotSpaces as i32 - 1}, i32>(bytecode))
         }
     }
     
-    type NewObject = SpaceEncoder;
+    pub type NewObject = SpaceEncoder;
 
     pub struct FixedRawDataWithSize {}
 
@@ -251,12 +223,7 @@ pub mod serializer_deserializer {
         }
 
         pub fn decode(bytecode: u8) -> RootIndex {
-            match BytecodeValueEncoder::decode::<{Bytecode::kRootArrayConstants as u8}, 0, {SerializerDeserializer::K_ROOT_ARRAY_CONSTANTS_COUNT - 1}, i32>(bytecode) {
-                0 => RootIndex::Index1,
-                1 => RootIndex::Index2,
-                2 => RootIndex::Index3,
-                _ => panic!("Invalid RootIndex value"),
-            }
+            BytecodeValueEncoder::decode::<{Bytecode::kRootArrayConstants as u8}, 0, {SerializerDeserializer::K_ROOT_ARRAY_CONSTANTS_COUNT - 1}, i32>(bytecode) as RootIndex
         }
     }
 
@@ -322,4 +289,51 @@ pub mod serializer_deserializer {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// The last objects that were serialized or deserialized. References to
+    /// them are encoded in a single byte.
+    #[derive(Debug, Default)]
+    pub struct HotObjectsList {
+        circular_queue_: [Option<HeapObject>; SerializerDeserializer::K_HOT_OBJECT_COUNT as usize],
+        index_: usize,
+    }
+
+    impl HotObjectsList {
+        const K_SIZE_MASK: usize = SerializerDeserializer::K_HOT_OBJECT_COUNT as usize - 1;
+
+        pub fn add(&mut self, object: HeapObject) {
+            self.circular_queue_[self.index_] = Some(object);
+            self.index_ = (self.index_ + 1) & Self::K_SIZE_MASK;
+        }
+
+        pub fn get(&self, index: usize) -> HeapObject {
+            self.circular_queue_[index].expect("Hot object slot is empty")
+        }
+
+        pub fn find(&self, object: HeapObject) -> Option<usize> {
+            self.circular_queue_.iter().position(|&entry| entry == Some(object))
+        }
+    }
+
+    /// How an object that lives outside of the snapshot section being written
+    /// is referenced.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ExternalReference {
+        ReadOnlyHeap(u32),
+        Root(RootIndex),
+        StartupObjectCache(u32),
+        SharedHeapObjectCache(u32),
+        /// An object supplied by the embedder when deserializing, e.g. the
+        /// source string of a code cache.
+        Attached(u32),
+    }
+
+    /// Per-space allocation statistics of a serializer, printed by
+    /// --serialization-statistics.
+    #[derive(Debug, Default, Clone)]
+    pub struct SerializationStatistics {
+        pub allocation_size: [usize; kNumberOfSnapshotSpaces],
+        pub object_count: [usize; kNumberOfSnapshotSpaces],
+        pub instance_type_count: HashMap<InstanceType, usize>,
+    }
+}
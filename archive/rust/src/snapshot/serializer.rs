@@ -3,720 +3,225 @@
 // found in the LICENSE file.
 
 pub mod serializer {
-    use std::any::Any;
-    use std::collections::HashMap;
-    use std::fmt;
-    use std::mem::MaybeUninit;
-    use std::ptr::null_mut;
-    use std::sync::{Arc, Mutex};
-    use std::usize;
-
-    // Placeholder for V8-specific types and constants.
-    // Define dummy types for now.
-    pub type Address = usize;
-    pub type RootIndex = usize;
-    pub type Tagged<T> = *mut T; // Assuming Tagged is a raw pointer for now
-    pub type DirectHandle<T> = *mut T; // Assuming DirectHandle is a raw pointer for now
-    pub type MaybeDirectHandle<T> = *mut T; // Assuming MaybeDirectHandle is a raw pointer for now
-    pub type FullObjectSlot = *mut usize;
-    pub type ObjectSlot = *mut usize;
-    pub type MaybeObjectSlot = *mut usize;
-    pub type ExternalPointerSlot = *mut usize;
-    pub type IndirectPointerSlot = *mut usize;
-    pub type ProtectedPointerSlot = *mut usize;
-    pub type ProtectedMaybeObjectSlot = *mut usize;
-    pub type CppHeapPointerSlot = *mut usize;
-    pub type InstructionStreamSlot = *mut usize;
-    pub type ExternalPointerTag = usize;
-    pub type IndirectPointerMode = usize;
-    pub type SnapshotSpace = usize;
-    pub type SlotType = usize;
-    pub type HeapObject = usize;
-    pub type Map = usize;
-    pub type Code = usize;
-    pub type InstructionStream = usize;
-    pub type BytecodeArray = usize;
-    pub type FixedArray = usize;
-    pub type AbstractCode = usize;
-    pub type SharedFunctionInfo = usize;
-    pub type TrustedObject = usize;
-
-    pub type JSDispatchHandle = usize;
-    pub const kHotObjectCount: usize = 16;
-    pub const kNumberOfSnapshotSpaces: usize = 4;
-    pub const kNullAddress: Address = 0;
-    pub const LAST_TYPE: usize = 255;
-
-    macro_rules! DCHECK {
-        ($condition:expr) => {
-            if !$condition {
-                panic!("DCHECK failed: {}", stringify!($condition));
-            }
-        };
-    }
-
-    macro_rules! DCHECK_NOT_NULL {
-        ($ptr:expr) => {
-            if ($ptr as *const _).is_null() {
-                panic!("DCHECK_NOT_NULL failed: pointer is null");
-            }
-        };
-    }
-
-    macro_rules! DCHECK_NULL {
-        ($ptr:expr) => {
-            if !($ptr as *const _).is_null() {
-                panic!("DCHECK_NULL failed: pointer is not null");
-            }
-        };
-    }
-
-    macro_rules! UNREACHABLE {
-        () => {
-            panic!("UNREACHABLE");
-        };
-    }
-
-    pub struct Isolate {
-        logger: Box<Logger>,
-    }
-
-    impl Isolate {
-        pub fn logger(&self) -> &Logger {
-            &self.logger
-        }
-    }
-
-    pub struct Logger {
-        listeners: Mutex<Vec<Box<dyn CodeEventListener>>>,
+    use std::collections::{HashMap, VecDeque};
+
+    use crate::heap::heap_layout::{BodyKind, HeapObject, Tagged};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{
+        Bytecode, ExternalReference, FixedRawDataWithSize, HotObject, HotObjectsList, NewObject, RootArrayConstant,
+        SerializationStatistics, SnapshotSpace, K_TAGGED_SIZE,
+    };
+    use crate::snapshot::snapshot_source_sink::SnapshotByteSink;
+
+    /// Decides which objects are serialized into the current section and how
+    /// the remaining ones are referenced.
+    pub trait ReferenceEncoder {
+        /// Returns the encoding of a reference to |object| if it belongs to
+        /// another section, or None if it is serialized into this one.
+        fn encode_external(&mut self, object: HeapObject) -> Option<ExternalReference>;
+
+        /// The space |object| is allocated in when deserialized.
+        fn space(&self, object: HeapObject) -> SnapshotSpace {
+            SnapshotSpace::for_instance_type(object.map().instance_type())
+        }
+    }
+
+    /// Writes heap objects into a byte sink as a stream of bytecodes. Objects
+    /// are serialized depth-first the first time they are referenced and back
+    /// referenced afterwards; objects nested too deeply are deferred and
+    /// patched in through forward references.
+    ///
+    /// An object is written as its space, instance type and length followed
+    /// by its body: the raw bytes of data objects, or one entry per tagged
+    /// slot otherwise.
+    pub struct Serializer {
+        sink_: SnapshotByteSink,
+        reference_map_: HashMap<HeapObject, u32>,
+        hot_objects_: HotObjectsList,
+        deferred_objects_: VecDeque<HeapObject>,
+        forward_refs_per_pending_object_: HashMap<HeapObject, Vec<u32>>,
+        next_forward_ref_id_: u32,
+        recursion_depth_: usize,
+        statistics_: SerializationStatistics,
     }
 
-    impl Logger {
-        pub fn add_listener(&self, listener: Box<dyn CodeEventListener>) -> bool {
-            let mut listeners = self.listeners.lock().unwrap();
-            listeners.push(listener);
-            true
-        }
-
-        pub fn remove_listener(&self, listener: &dyn CodeEventListener) -> bool {
-            let mut listeners = self.listeners.lock().unwrap();
-            listeners.retain(|l| !l.is_same(listener));
-            true
-        }
-
-        pub fn notify_code_move_event(&self, from: Tagged<InstructionStream>, to: Tagged<InstructionStream>) {
-            let listeners = self.listeners.lock().unwrap();
-            for listener in listeners.iter() {
-                listener.code_move_event(from, to);
-            }
-        }
-
-        pub fn notify_bytecode_move_event(&self, from: Tagged<BytecodeArray>, to: Tagged<BytecodeArray>) {
-            let listeners = self.listeners.lock().unwrap();
-            for listener in listeners.iter() {
-                listener.bytecode_move_event(from, to);
-            }
-        }
-
-        pub fn notify_code_disable_opt_event(&self, code: DirectHandle<AbstractCode>, shared: DirectHandle<SharedFunctionInfo>) {
-            let listeners = self.listeners.lock().unwrap();
-            for listener in listeners.iter() {
-                listener.code_disable_opt_event(code, shared);
-            }
-        }
-
-        pub fn notify_log_recorded_buffer_code(&self, code: Tagged<AbstractCode>, shared: MaybeDirectHandle<SharedFunctionInfo>, name: &str, length: usize) {
-            let listeners = self.listeners.lock().unwrap();
-            for listener in listeners.iter() {
-                listener.log_recorded_buffer_code(code, shared, name, length);
-            }
+    impl Default for Serializer {
+        fn default() -> Self {
+            Self::new()
         }
     }
 
-    pub trait CodeEventListener: Send + Sync + 'static {
-        fn code_move_event(&self, from: Tagged<InstructionStream>, to: Tagged<InstructionStream>);
-        fn bytecode_move_event(&self, from: Tagged<BytecodeArray>, to: Tagged<BytecodeArray>);
-        fn code_disable_opt_event(&self, code: DirectHandle<AbstractCode>, shared: DirectHandle<SharedFunctionInfo>);
-        fn log_recorded_buffer_code(&self, code: Tagged<AbstractCode>, shared: MaybeDirectHandle<SharedFunctionInfo>, name: &str, length: usize);
-        fn is_same(&self, other: &dyn CodeEventListener) -> bool;
-    }
-
-    pub struct CodeAddressMap {
-        code_event_logger: CodeEventLogger,
-        address_to_name_map_: NameMap,
-    }
-
-    impl CodeAddressMap {
-        pub fn new(isolate: *mut Isolate) -> Self {
-            unsafe {
-                let isolate = isolate.as_mut().unwrap();
-                DCHECK!(isolate.logger().add_listener(Box::new(Self {
-                    code_event_logger: CodeEventLogger::new(isolate),
-                    address_to_name_map_: NameMap::new(),
-                })));
-            }
-            Self {
-                code_event_logger: CodeEventLogger::new(unsafe { (*isolate).as_mut().unwrap() }),
-                address_to_name_map_: NameMap::new(),
-            }
-        }
-    }
+    impl Serializer {
+        const K_MAX_RECURSION_DEPTH: usize = 32;
 
-    impl Drop for CodeAddressMap {
-        fn drop(&mut self) {
-            unsafe {
-                DCHECK!((self.code_event_logger.isolate_ as *mut Isolate).as_mut().unwrap().logger().remove_listener(self));
+        pub fn new() -> Self {
+            Serializer {
+                sink_: SnapshotByteSink::new(),
+                reference_map_: HashMap::new(),
+                hot_objects_: HotObjectsList::default(),
+                deferred_objects_: VecDeque::new(),
+                forward_refs_per_pending_object_: HashMap::new(),
+                next_forward_ref_id_: 0,
+                recursion_depth_: 0,
+                statistics_: SerializationStatistics::default(),
             }
         }
-    }
-
-    impl CodeEventListener for CodeAddressMap {
-        fn code_move_event(&self, from: Tagged<InstructionStream>, to: Tagged<InstructionStream>) {
-            self.address_to_name_map_.move_address(from as Address, to as Address);
-        }
-
-        fn bytecode_move_event(&self, from: Tagged<BytecodeArray>, to: Tagged<BytecodeArray>) {
-            self.address_to_name_map_.move_address(from as Address, to as Address);
-        }
 
-        fn code_disable_opt_event(&self, _code: DirectHandle<AbstractCode>, _shared: DirectHandle<SharedFunctionInfo>) {}
-
-        fn log_recorded_buffer_code(&self, code: Tagged<AbstractCode>, _shared: MaybeDirectHandle<SharedFunctionInfo>, name: &str, length: usize) {
-            let address = code as Address;
-            self.address_to_name_map_.insert(address, name, length);
+        pub fn sink(&mut self) -> &mut SnapshotByteSink {
+            &mut self.sink_
         }
 
-        fn is_same(&self, other: &dyn CodeEventListener) -> bool {
-            other as *const dyn CodeEventListener == self as *const dyn CodeEventListener
+        pub fn payload(&self) -> &Vec<u8> {
+            self.sink_.data()
         }
-    }
 
-    impl CodeAddressMap {
-        pub fn lookup(&self, address: Address) -> Option<&str> {
-            self.address_to_name_map_.lookup(address)
+        pub fn into_payload(self) -> Vec<u8> {
+            assert!(self.deferred_objects_.is_empty());
+            assert!(self.forward_refs_per_pending_object_.is_empty());
+            self.sink_.into_data()
         }
-    }
-
-    pub struct CodeEventLogger {
-        isolate_: *mut Isolate,
-    }
 
-    impl CodeEventLogger {
-        pub fn new(isolate: *mut Isolate) -> Self {
-            Self { isolate_: isolate }
+        pub fn statistics(&self) -> &SerializationStatistics {
+            &self.statistics_
         }
-    }
 
-    struct NameMap {
-        impl_: Mutex<HashMap<Address, String>>,
-    }
-
-    impl NameMap {
-        fn new() -> Self {
-            NameMap {
-                impl_: Mutex::new(HashMap::new()),
-            }
+        /// Returns true if |object| has been serialized into this section or
+        /// is pending serialization as a deferred object.
+        pub fn contains(&self, object: HeapObject) -> bool {
+            self.reference_map_.contains_key(&object) || self.forward_refs_per_pending_object_.contains_key(&object)
         }
 
-        fn insert(&self, code_address: Address, name: &str, name_size: usize) {
-            let mut map = self.impl_.lock().unwrap();
-            let name = Self::copy_name(name, name_size);
-            map.insert(code_address, name);
+        /// Index the deserializer will assign to |object| in its back
+        /// reference list, if the object has already been serialized.
+        pub fn reference_index(&self, object: HeapObject) -> Option<u32> {
+            self.reference_map_.get(&object).copied()
         }
 
-        fn lookup(&self, code_address: Address) -> Option<&str> {
-            let map = self.impl_.lock().unwrap();
-            map.get(&code_address).map(|s| s.as_str())
+        pub fn put_synchronize(&mut self) {
+            self.sink_.put(Bytecode::kSynchronize as u8, "Synchronize");
         }
 
-        fn remove(&self, code_address: Address) {
-            let mut map = self.impl_.lock().unwrap();
-            map.remove(&code_address);
+        /// Serializes the value of a root slot.
+        pub fn serialize_root(&mut self, value: Tagged, encoder: &mut dyn ReferenceEncoder) {
+            debug_assert_eq!(self.recursion_depth_, 0);
+            self.serialize_slot(value, encoder);
         }
 
-        fn move_address(&self, from: Address, to: Address) {
-            if from == to {
-                return;
-            }
-            let mut map = self.impl_.lock().unwrap();
-            if let Some(value) = map.remove(&from) {
-                map.insert(to, value);
+        fn serialize_slot(&mut self, value: Tagged, encoder: &mut dyn ReferenceEncoder) {
+            if let Some(smi) = value.smi_value() {
+                let smi = i32::try_from(smi).expect("Smi does not fit into a snapshot slot");
+                self.sink_.put(FixedRawDataWithSize::encode(1), "Smi");
+                self.sink_.put_raw(&smi.to_le_bytes(), K_TAGGED_SIZE, "Smi");
+            } else if value.is_cleared() {
+                self.sink_.put(Bytecode::kClearedWeakReference as u8, "ClearedWeakReference");
             } else {
-                DCHECK_NOT_NULL!(null_mut::<usize>()); // Original C++ code has DCHECK_NOT_NULL(from_entry) here.
-            }
-        }
-
-        fn copy_name(name: &str, name_size: usize) -> String {
-            let mut result = String::with_capacity(name_size + 1);
-            for c in name.chars().take(name_size) {
-                let c = if c == '\0' { ' ' } else { c };
-                result.push(c);
-            }
-            result.push('\0');
-            result
-        }
-    }
-
-    pub struct ObjectCacheIndexMap {
-        map_: IdentityMap<i32>,
-        next_index_: i32,
-    }
-
-    impl ObjectCacheIndexMap {
-        pub fn new(heap: *mut Heap) -> Self {
-            ObjectCacheIndexMap {
-                map_: IdentityMap::new(heap),
-                next_index_: 0,
-            }
-        }
-
-        pub fn lookup_or_insert(&mut self, obj: Tagged<HeapObject>, index_out: &mut i32) -> bool {
-            let find_result = self.map_.find_or_insert(obj);
-            if !find_result.already_exists {
-                *find_result.entry = self.next_index_;
-                self.next_index_ += 1;
-            }
-            *index_out = *find_result.entry;
-            find_result.already_exists
-        }
-
-        pub fn lookup(&self, obj: Tagged<HeapObject>, index_out: &mut i32) -> bool {
-            let index = self.map_.find(obj);
-            match index {
-                Some(i) => {
-                    *index_out = *i;
-                    true
+                let object = value.get_heap_object().unwrap();
+                if value.is_weak() {
+                    self.sink_.put(Bytecode::kWeakPrefix as u8, "WeakReference");
                 }
-                None => false,
+                self.serialize_object_reference(object, encoder);
             }
         }
 
-        pub fn values(&self, isolate: *mut Isolate) -> DirectHandle<FixedArray> {
-            //  This function needs to construct and return a FixedArray.
-            //  Since FixedArray is a V8 type, this part is not directly convertible.
-            //  Returning a null pointer as a placeholder.
-            null_mut()
-        }
-
-        pub fn size(&self) -> i32 {
-            self.next_index_
-        }
-    }
-
-    struct IdentityMapEntry<V> {
-        key: Address,
-        value: V,
-    }
-
-    struct IdentityMapFindResult<'a, V> {
-        already_exists: bool,
-        entry: &'a mut V,
-    }
-
-    pub struct IdentityMap<V> {
-        heap: *mut Heap,
-        map: Mutex<HashMap<Address, V>>,
-    }
-
-    impl<V: Default + Copy> IdentityMap<V> {
-        pub fn new(heap: *mut Heap) -> Self {
-            IdentityMap {
-                heap,
-                map: Mutex::new(HashMap::new()),
-            }
-        }
-
-        pub fn find_or_insert(&mut self, key: Tagged<HeapObject>) -> IdentityMapFindResult<V> {
-            let mut map = self.map.lock().unwrap();
-            let key = key as Address;
-            let already_exists = map.contains_key(&key);
-            let entry = map.entry(key).or_insert(V::default());
-
-            IdentityMapFindResult {
-                already_exists,
-                entry,
+        fn serialize_object_reference(&mut self, object: HeapObject, encoder: &mut dyn ReferenceEncoder) {
+            if let Some(index) = self.hot_objects_.find(object) {
+                self.sink_.put(HotObject::encode(index as i32), "HotObject");
+                return;
             }
-        }
-
-        pub fn find(&self, key: Tagged<HeapObject>) -> Option<&V> {
-            let map = self.map.lock().unwrap();
-            map.get(&(key as Address))
-        }
-    }
-
-    pub struct Heap {}
-
-    #[derive(Debug, Clone, Copy)]
-    pub struct PtrComprCageBase {}
-
-    pub struct Snapshot {
-        flags: SerializerFlags
-    }
-
-    impl Snapshot {
-        pub const kAllowUnknownExternalReferencesForTesting: SerializerFlags = SerializerFlags(1 << 0);
-        pub const kAllowActiveIsolateForTesting: SerializerFlags = SerializerFlags(1 << 1);
-        pub const kReconstructReadOnlyAndSharedObjectCachesForTesting: SerializerFlags = SerializerFlags(1 << 2);
-    }
-
-    #[derive(Debug, Clone, Copy)]
-    pub struct SerializerFlags(u32);
-
-    impl std::ops::BitOr for SerializerFlags {
-        type Output = Self;
-
-        fn bitor(self, other: Self) -> Self {
-            SerializerFlags(self.0 | other.0)
-        }
-    }
-
-    pub struct Serializer {
-        isolate_: *mut Isolate,
-        cage_base_: PtrComprCageBase,
-        hot_objects_: HotObjectsList,
-        reference_map_: SerializerReferenceMap,
-        external_reference_encoder_: ExternalReferenceEncoder,
-        root_index_map_: RootIndexMap,
-        code_address_map_: Option<Box<CodeAddressMap>>,
-        code_buffer_: Vec<u8>,
-        deferred_objects_: GlobalHandleVector<HeapObject>,
-        num_back_refs_: i32,
-        dispatch_handle_map_: HashMap<JSDispatchHandle, u32>,
-        next_forward_ref_id_: i32,
-        unresolved_forward_refs_: i32,
-        forward_refs_per_pending_object_: IdentityMap<*mut Vec<i32>>,
-        seen_backing_stores_index_: u32,
-        recursion_depth_: i32,
-        flags_: Snapshot::SerializerFlags,
-        serializer_tracks_serialization_statistics_: bool,
-        allocation_size_: [usize; kNumberOfSnapshotSpaces],
-        sink_: SnapshotByteSink,
-    }
-
-    impl Serializer {
-        pub fn new(isolate: *mut Isolate, flags: Snapshot::SerializerFlags) -> Self {
-            Serializer {
-                isolate_: isolate,
-                cage_base_: PtrComprCageBase {},
-                hot_objects_: HotObjectsList::new(unsafe { (*isolate).as_mut().unwrap() }),
-                reference_map_: SerializerReferenceMap::new(),
-                external_reference_encoder_: ExternalReferenceEncoder::new(),
-                root_index_map_: RootIndexMap::new(),
-                code_address_map_: None,
-                code_buffer_: Vec::new(),
-                deferred_objects_: GlobalHandleVector::new(),
-                num_back_refs_: 0,
-                dispatch_handle_map_: HashMap::new(),
-                next_forward_ref_id_: 0,
-                unresolved_forward_refs_: 0,
-                forward_refs_per_pending_object_: IdentityMap::new(unsafe { (*isolate).as_mut().unwrap() }),
-                seen_backing_stores_index_: 1,
-                recursion_depth_: 0,
-                flags_: flags,
-                serializer_tracks_serialization_statistics_: true,
-                allocation_size_: [0; kNumberOfSnapshotSpaces],
-                sink_: SnapshotByteSink::new(),
+            if let Some(&index) = self.reference_map_.get(&object) {
+                self.sink_.put(Bytecode::kBackref as u8, "Backref");
+                self.sink_.put_uint30(index, "BackrefIndex");
+                self.hot_objects_.add(object);
+                return;
             }
-        }
-
-        pub fn payload(&self) -> &Vec<u8> {
-            self.sink_.data()
-        }
-
-        pub fn reference_map_contains(&self, o: DirectHandle<HeapObject>) -> bool {
-            self.reference_map_.lookup_reference(o).is_some()
-        }
-
-        pub fn isolate(&self) -> *mut Isolate {
-            self.isolate_
-        }
-
-        pub fn cage_base(&self) -> PtrComprCageBase {
-            self.cage_base_
-        }
-
-        pub fn total_allocation_size(&self) -> i32 {
-            self.allocation_size_.iter().sum::<usize>() as i32
-        }
-
-        pub fn is_not_mapped_symbol(&self, _obj: Tagged<HeapObject>) -> bool {
-            // Placeholder implementation, replace with actual logic
-            false
-        }
-
-        pub fn pad(&mut self, padding_offset: i32) {
-            // Placeholder implementation, replace with actual logic
-        }
-
-        pub fn initialize_code_address_map(&mut self) {
-            if self.code_address_map_.is_none() {
-                self.code_address_map_ = Some(Box::new(CodeAddressMap::new(self.isolate_)));
+            if let Some(reference) = encoder.encode_external(object) {
+                match reference {
+                    ExternalReference::ReadOnlyHeap(index) => {
+                        self.sink_.put(Bytecode::kReadOnlyHeapRef as u8, "ReadOnlyHeapRef");
+                        self.sink_.put_uint30(index, "ReadOnlyHeapIndex");
+                    }
+                    ExternalReference::Root(index) => {
+                        if RootArrayConstant::is_encodable(index) {
+                            self.sink_.put(RootArrayConstant::encode(index), "RootConstant");
+                        } else {
+                            self.sink_.put(Bytecode::kRootArray as u8, "RootSerialization");
+                            self.sink_.put_uint30(index, "root_index");
+                        }
+                    }
+                    ExternalReference::StartupObjectCache(index) => {
+                        self.sink_.put(Bytecode::kStartupObjectCache as u8, "StartupObjectCache");
+                        self.sink_.put_uint30(index, "startup_object_cache_index");
+                    }
+                    ExternalReference::SharedHeapObjectCache(index) => {
+                        self.sink_.put(Bytecode::kSharedHeapObjectCache as u8, "SharedHeapObjectCache");
+                        self.sink_.put_uint30(index, "shared_heap_object_cache_index");
+                    }
+                    ExternalReference::Attached(index) => {
+                        self.sink_.put(Bytecode::kAttachedReference as u8, "AttachedReference");
+                        self.sink_.put_uint30(index, "AttachedReferenceIndex");
+                    }
+                }
+                return;
             }
-        }
-
-        pub fn copy_code(&self, _istream: Tagged<InstructionStream>) -> Tagged<InstructionStream> {
-            // Placeholder implementation, replace with actual logic
-            null_mut()
-        }
-
-        pub fn queue_deferred_object(&mut self, obj: Tagged<HeapObject>) {
-            DCHECK_NULL!(self.reference_map_.lookup_reference(obj));
-            self.deferred_objects_.push(obj);
-        }
-
-        pub fn register_object_is_pending(&mut self, _obj: Tagged<HeapObject>) {
-            // Placeholder implementation, replace with actual logic
-        }
-
-        pub fn resolve_pending_object(&mut self, _obj: Tagged<HeapObject>) {
-            // Placeholder implementation, replace with actual logic
-        }
-
-        pub fn output_statistics(&self, _name: &str) {
-            // Placeholder implementation, replace with actual logic
-        }
-
-        pub fn count_allocation(&mut self, _map: Tagged<Map>, _size: i32, _space: SnapshotSpace) {
-            // Placeholder implementation, replace with actual logic
-        }
-
-        pub fn deferred_objects_empty(&self) -> bool {
-            self.deferred_objects_.size() == 0
-        }
-
-        pub fn serializer_tracks_serialization_statistics(&self) -> bool {
-            self.serializer_tracks_serialization_statistics_
-        }
-        pub fn set_serializer_tracks_serialization_statistics(&mut self, v: bool) {
-            self.serializer_tracks_serialization_statistics_ = v;
-        }
-
-        pub fn allow_unknown_external_references_for_testing(&self) -> bool {
-            (self.flags_.0 & Snapshot::kAllowUnknownExternalReferencesForTesting.0) != 0
-        }
-        pub fn allow_active_isolate_for_testing(&self) -> bool {
-            (self.flags_.0 & Snapshot::kAllowActiveIsolateForTesting.0) != 0
-        }
-        pub fn reconstruct_read_only_and_shared_object_caches_for_testing(&self) -> bool {
-            (self.flags_.0 & Snapshot::kReconstructReadOnlyAndSharedObjectCachesForTesting.0) != 0
-        }
-    }
-
-    impl Drop for Serializer {
-        fn drop(&mut self) {
-            DCHECK_EQ!(self.unresolved_forward_refs_, 0);
-        }
-    }
-
-    pub struct RecursionScope<'a> {
-        serializer_: &'a mut Serializer,
-    }
-
-    impl<'a> RecursionScope<'a> {
-        const K_MAX_RECURSION_DEPTH: i32 = 32;
-
-        pub fn new(serializer: &'a mut Serializer) -> Self {
-            serializer.recursion_depth_ += 1;
-            RecursionScope { serializer_: serializer }
-        }
-
-        pub fn exceeds_maximum(&self) -> bool {
-            self.serializer_.recursion_depth_ > Self::K_MAX_RECURSION_DEPTH
-        }
-
-        pub fn exceeds_maximum_by(&self) -> i32 {
-            self.serializer_.recursion_depth_ - Self::K_MAX_RECURSION_DEPTH
-        }
-    }
-
-    impl<'a> Drop for RecursionScope<'a> {
-        fn drop(&mut self) {
-            self.serializer_.recursion_depth_ -= 1;
-        }
-    }
-
-    pub struct ExternalReferenceEncoder {
-        // Add fields as needed, mirroring the C++ implementation
-    }
-
-    impl ExternalReferenceEncoder {
-        pub fn new() -> Self {
-            ExternalReferenceEncoder {}
-        }
-
-        pub fn try_encode(&self, addr: Address) -> Result<Address, String> {
-            // Placeholder implementation
-            Ok(addr)
-        }
-
-        pub fn encode(&self, addr: Address) -> Address {
-            // Placeholder implementation
-            addr
-        }
-    }
-
-    impl Default for ExternalReferenceEncoder {
-        fn default() -> Self {
-            Self::new()
-        }
-    }
-
-    pub struct RootIndexMap {
-        // Add fields as needed, mirroring the C++ implementation
-    }
-
-    impl RootIndexMap {
-        pub fn new() -> Self {
-            RootIndexMap {}
-        }
-    }
-
-    pub struct GlobalHandleVector<T> {
-        handles: Vec<T>,
-    }
-
-    impl<T> GlobalHandleVector<T> {
-        pub fn new() -> Self {
-            GlobalHandleVector { handles: Vec::new() }
-        }
-
-        pub fn push(&mut self, handle: T) {
-            self.handles.push(handle);
-        }
-
-        pub fn size(&self) -> usize {
-            self.handles.len()
-        }
-    }
-
-    pub struct SnapshotByteSink {
-        data_: Vec<u8>,
-    }
-
-    impl SnapshotByteSink {
-        pub fn new() -> Self {
-            SnapshotByteSink { data_: Vec::new() }
-        }
-
-        pub fn data(&self) -> &Vec<u8> {
-            &self.data_
-        }
-    }
-
-    //TODO(leszeks, v8:10815): Remove this type
-    #[allow(dead_code)]
-    pub struct AllowGarbageCollection {
-    }
-
-    #[allow(dead_code)]
-    impl AllowGarbageCollection {
-        pub fn is_allowed() -> bool {
-            true
-        }
-    }
-
-    pub struct HotObjectsList {
-        heap_: *mut Isolate,
-        circular_queue_: [Address; kHotObjectCount],
-        index_: usize,
-    }
-
-    impl HotObjectsList {
-        pub fn new(heap: *mut Isolate) -> Self {
-            HotObjectsList {
-                heap_: heap,
-                circular_queue_: [kNullAddress; kHotObjectCount],
-                index_: 0,
+            if self.forward_refs_per_pending_object_.contains_key(&object)
+                || self.recursion_depth_ >= Self::K_MAX_RECURSION_DEPTH
+            {
+                // Defer the object and patch the slot once it is serialized.
+                self.register_pending_forward_ref(object);
+                return;
             }
-        }
-
-        pub fn add(&mut self, object: Tagged<HeapObject>) {
-            self.circular_queue_[self.index_] = object as Address;
-            self.index_ = (self.index_ + 1) & (kHotObjectCount - 1);
-        }
-
-        pub fn find(&self, object: Tagged<HeapObject>) -> i32 {
-            for i in 0..kHotObjectCount {
-                if self.circular_queue_[i] == object as Address {
-                    return i as i32;
+            self.serialize_new_object(object, encoder);
+        }
+
+        fn register_pending_forward_ref(&mut self, object: HeapObject) {
+            let forward_ref_id = self.next_forward_ref_id_;
+            self.next_forward_ref_id_ += 1;
+            let refs = self.forward_refs_per_pending_object_.entry(object).or_default();
+            if refs.is_empty() {
+                self.deferred_objects_.push_back(object);
+            }
+            refs.push(forward_ref_id);
+            self.sink_.put(Bytecode::kRegisterPendingForwardRef as u8, "RegisterPendingForwardRef");
+        }
+
+        fn serialize_new_object(&mut self, object: HeapObject, encoder: &mut dyn ReferenceEncoder) {
+            let map = object.map();
+            let length = object.length_for(map);
+            let space = encoder.space(object);
+            self.sink_.put(NewObject::encode::<{ Bytecode::kNewObject as u8 }>(space), "NewObject");
+            self.sink_.put_uint30(map.instance_type() as u32, "InstanceType");
+            self.sink_.put_uint30(length as u32, "Length");
+            let index = self.reference_map_.len() as u32;
+            self.reference_map_.insert(object, index);
+            self.hot_objects_.add(object);
+            if let Some(refs) = self.forward_refs_per_pending_object_.remove(&object) {
+                for forward_ref_id in refs {
+                    self.sink_.put(Bytecode::kResolvePendingForwardRef as u8, "ResolvePendingForwardRef");
+                    self.sink_.put_uint30(forward_ref_id, "PendingForwardRefId");
                 }
             }
-            -1
-        }
-    }
-
-    #[derive(Debug)]
-    pub struct SerializerReference {
-        // Add fields as needed, mirroring the C++ implementation
-    }
 
-    pub struct SerializerReferenceMap {
-        map: Mutex<HashMap<Address, SerializerReference>>,
-    }
+            self.statistics_.allocation_size[space as usize] += object.size();
+            self.statistics_.object_count[space as usize] += 1;
+            *self.statistics_.instance_type_count.entry(map.instance_type()).or_insert(0) += 1;
 
-    impl SerializerReferenceMap {
-        pub fn new() -> Self {
-            SerializerReferenceMap {
-                map: Mutex::new(HashMap::new()),
+            if map.body_kind() == BodyKind::Data {
+                self.sink_.put_raw(object.data(), length, "RawData");
+                return;
             }
-        }
-
-        pub fn lookup_reference(&self, obj: DirectHandle<HeapObject>) -> Option<&SerializerReference> {
-            let map = self.map.lock().unwrap();
-            map.get(&(obj as Address))
-        }
-    }
-
-    pub trait ObjectVisitor {
-        fn visit_pointers(&mut self, host: Tagged<HeapObject>, start: ObjectSlot, end: ObjectSlot);
-        fn visit_pointers_maybe_object(&mut self, host: Tagged<HeapObject>, start: MaybeObjectSlot, end: MaybeObjectSlot);
-        fn visit_instruction_stream_pointer(&mut self, host: Tagged<Code>, slot: InstructionStreamSlot);
-        fn visit_embedded_pointer(&mut self, host: Tagged<InstructionStream>, target: *mut RelocInfo);
-        fn visit_external_reference(&mut self, host: Tagged<InstructionStream>, rinfo: *mut RelocInfo);
-        fn visit_internal_reference(&mut self, host: Tagged<InstructionStream>, rinfo: *mut RelocInfo);
-        fn visit_code_target(&mut self, host: Tagged<InstructionStream>, target: *mut RelocInfo);
-        fn visit_off_heap_target(&mut self, host: Tagged<InstructionStream>, target: *mut RelocInfo);
-        fn visit_external_pointer(&mut self, host: Tagged<HeapObject>, slot: ExternalPointerSlot);
-        fn visit_indirect_pointer(&mut self, host: Tagged<HeapObject>, slot: IndirectPointerSlot, mode: IndirectPointerMode);
-        fn visit_trusted_pointer_table_entry(&mut self, host: Tagged<HeapObject>, slot: IndirectPointerSlot);
-        fn visit_protected_pointer(&mut self, host: Tagged<TrustedObject>, slot: ProtectedPointerSlot);
-        fn visit_protected_pointer_maybe_object(&mut self, host: Tagged<TrustedObject>, slot: ProtectedMaybeObjectSlot);
-        fn visit_cpp_heap_pointer(&mut self, host: Tagged<HeapObject>, slot: CppHeapPointerSlot);
-        fn visit_js_dispatch_table_entry(&mut self, host: Tagged<HeapObject>, handle: JSDispatchHandle);
-    }
-
-    pub struct ObjectSerializer<'a> {
-        isolate_: *mut Isolate,
-        serializer_: &'a mut Serializer,
-        object_: Tagged<HeapObject>,
-        sink_: &'a mut SnapshotByteSink,
-        bytes_processed_so_far_: i32,
-    }
-
-    impl<'a> ObjectSerializer<'a> {
-        pub fn new(serializer: &'a mut Serializer, obj: Tagged<HeapObject>, sink: &'a mut SnapshotByteSink) -> Self {
-            ObjectSerializer {
-                isolate_: serializer.isolate(),
-                serializer_: serializer,
-                object_: obj,
-                sink_: sink,
-                bytes_processed_so_far_: 0,
+            self.recursion_depth_ += 1;
+            for index in 0..length {
+                self.serialize_slot(object.get(index), encoder);
             }
+            self.recursion_depth_ -= 1;
         }
 
-        pub fn serialize(&mut self, _slot_type: SlotType) {
-            // Placeholder implementation, replace with actual logic
-        }
-        pub fn serialize_object(&mut self) {
-            // Placeholder implementation, replace with actual logic
-        }
-        pub fn serialize_deferred(&mut self) {
-            // Placeholder implementation, replace with actual logic
+        /// Serializes all deferred objects, followed by kSynchronize.
+        pub fn serialize_deferred_objects(&mut self, encoder: &mut dyn ReferenceEncoder) {
+            while let Some(object) = self.deferred_objects_.pop_front() {
+                debug_assert!(self.forward_refs_per_pending_object_.contains_key(&object));
+                self.serialize_new_object(object, encoder);
+            }
+            self.put_synchronize();
         }
     }
-
-    #[allow(dead_code)]
-    pub struct RelocInfo {
-        // Placeholder
-    }
-}
\ No newline at end of file
+}
@@ -3,93 +3,41 @@
 // found in the LICENSE file.
 
 pub mod shared_heap_deserializer {
-    use crate::snapshot::deserializer::Deserializer;
-    use crate::snapshot::snapshot_data::SnapshotData;
+    use crate::heap::heap::Heap;
+    use crate::heap::heap_layout::HeapObject;
+    use crate::snapshot::deserializer::deserializer::{Deserializer, ExternalObjects};
+    use crate::snapshot::snapshot_data::internal::SnapshotData;
 
+    /// Deserializes the shared heap object cache written by
+    /// SharedHeapSerializer.
     pub struct SharedHeapDeserializer<'a> {
-        deserializer: Deserializer<'a>,
+        deserializer_: Deserializer<'a>,
     }
 
     impl<'a> SharedHeapDeserializer<'a> {
-        /// Initializes objects in the shared isolate that are not already included in
-        /// the startup snapshot.
-        pub fn new(isolate: &'a mut Isolate, shared_heap_data: &'a SnapshotData, can_rehash: bool) -> Self {
-            let payload = shared_heap_data.payload();
-            let magic_number = shared_heap_data.get_magic_number();
-
-            SharedHeapDeserializer {
-                deserializer: Deserializer::new(isolate, payload, magic_number, false, can_rehash),
+        pub fn new(shared_heap_data: &'a SnapshotData) -> Self {
+            SharedHeapDeserializer { deserializer_: Deserializer::new(shared_heap_data.payload()) }
+        }
+
+        /// Allocates the shared objects in |heap| and returns the shared heap
+        /// object cache.
+        pub fn deserialize_into_isolate(
+            mut self,
+            heap: &Heap,
+            read_only_objects: &[HeapObject],
+            read_only_roots: &[HeapObject],
+        ) -> Vec<HeapObject> {
+            let external = ExternalObjects { read_only_objects, roots: read_only_roots, ..Default::default() };
+            let mut cache = Vec::new();
+            while !self.deserializer_.at_synchronize() {
+                self.deserializer_.read_root(heap, &external);
+                self.deserializer_.read_deferred_objects(heap, &external);
+                let entry = self.deserializer_.take_roots()[0];
+                cache.push(entry.get_heap_object().expect("Shared cache entry is not an object"));
             }
-        }
-
-        /// Depending on runtime flags, deserialize shared heap objects into the
-        /// Isolate.
-        pub fn deserialize_into_isolate(&mut self) {
-            // TODO: Implement logic based on runtime flags.  Placeholder for the actual deserialization.
-            self.deserialize_string_table();
-        }
-
-        /// Deserialize the string table.
-        fn deserialize_string_table(&mut self) {
-            // TODO: Implement string table deserialization logic.
-        }
-    }
-
-    // Placeholder Isolate struct for demonstration purposes.  Needs to be replaced with the real Isolate struct from the V8 codebase.
-    pub struct Isolate {}
-
-    impl Isolate {
-        pub fn new() -> Self {
-            Isolate {}
+            self.deserializer_.read_synchronize();
+            assert!(!self.deserializer_.source().has_more(), "Trailing data in shared heap snapshot");
+            cache
         }
     }
 }
-
-pub mod snapshot {
-    pub mod deserializer {
-        pub struct Deserializer<'a> {
-            isolate: &'a mut super::shared_heap_deserializer::Isolate,
-            payload: &'a [u8],
-            magic_number: u32,
-            // Add necessary fields here
-            can_rehash: bool,
-            is_shared: bool
-        }
-
-        impl<'a> Deserializer<'a> {
-            pub fn new(isolate: &'a mut super::shared_heap_deserializer::Isolate, payload: &'a [u8], magic_number: u32, is_shared: bool, can_rehash: bool) -> Self {
-                Deserializer {
-                    isolate,
-                    payload,
-                    magic_number,
-                    can_rehash,
-                    is_shared
-                }
-            }
-        }
-    }
-
-    pub mod snapshot_data {
-        pub struct SnapshotData {
-            payload: Vec<u8>,
-            magic_number: u32,
-        }
-
-        impl SnapshotData {
-            pub fn new(payload: Vec<u8>, magic_number: u32) -> Self {
-                SnapshotData {
-                    payload,
-                    magic_number,
-                }
-            }
-
-            pub fn payload(&self) -> &[u8] {
-                &self.payload
-            }
-
-            pub fn get_magic_number(&self) -> u32 {
-                self.magic_number
-            }
-        }
-    }
-}
\ No newline at end of file
@@ -1,217 +1,141 @@
 // src/snapshot/snapshot_compression.rs
 
 use std::mem::size_of;
+use std::time::Instant;
 
-use crate::base::platform::elapsed_timer::ElapsedTimer;
-use crate::utils::memcopy::memcopy;
-use crate::utils::utils::PrintF;
-use crate::v8_flags;
-use flate2::{Compression, Decompress, FlushDecompress, Status};
-use flate2::Compress;
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::snapshot::snapshot_data::internal::{SerializedData, SnapshotData};
 
 pub mod zlib_internal {
     pub const ZRAW: i32 = -15;
 }
 
-/// Gets the uncompressed size from the compressed data.
-pub fn get_uncompressed_size(compressed_data: &[u8]) -> u32 {
-    let mut size: u32 = 0;
-    memcopy(&mut size, compressed_data, size_of::<u32>());
-    size
-}
+const K_UINT32_SIZE: usize = size_of::<u32>();
 
-/// Represents snapshot data.
-#[derive(Debug, PartialEq)]
-pub struct SnapshotData {
-    data: Vec<u8>,
+/// Gets the uncompressed size from the compressed data, or None if
+/// |compressed_data| is too short to hold it.
+pub fn get_uncompressed_size(compressed_data: &[u8]) -> Option<u32> {
+    compressed_data.get(..K_UINT32_SIZE).map(LittleEndian::read_u32)
 }
 
-impl SnapshotData {
-    /// Allocates memory for the snapshot data.
-    pub fn allocate_data(&mut self, size: u32) {
-        self.data.resize(size as usize, 0);
-    }
-
-    /// Gets a reference to the raw data.
-    pub fn raw_data(&self) -> &[u8] {
-        &self.data
-    }
-
-    /// Gets a mutable reference to the raw data.
-    pub fn raw_data_mut(&mut self) -> &mut [u8] {
-        &mut self.data
-    }
-
-    /// Resizes the snapshot data.
-    pub fn resize(&mut self, size: u32) {
-        self.data.resize(size as usize, 0);
-    }
-
-    /// Creates a new `SnapshotData` with an empty vector
-    pub fn new() -> Self {
-        SnapshotData { data: Vec::new() }
-    }
+/// Returns true if |data| holds a compressed snapshot section. Compressed
+/// sections start with their uncompressed size, uncompressed ones with the
+/// SerializedData magic number. Data too short for either is neither.
+pub fn is_compressed(data: &[u8]) -> bool {
+    get_uncompressed_size(data).is_some_and(|size| size != SerializedData::K_MAGIC_NUMBER)
 }
 
-/// Provides methods for compressing and decompressing snapshot data.
+/// Provides methods for compressing and decompressing snapshot data. The
+/// compressed form is the uncompressed size followed by a raw deflate
+/// stream (zlib_internal::ZRAW).
 pub struct SnapshotCompression {}
 
 impl SnapshotCompression {
     /// Compresses the given snapshot data.
     pub fn compress(uncompressed_data: &SnapshotData) -> SnapshotData {
-        let mut snapshot_data = SnapshotData::new();
-        let mut timer = ElapsedTimer::new();
-        if v8_flags::profile_deserialization() {
-            timer.start();
-        }
-
-        let input_size = uncompressed_data.raw_data().len();
-        let payload_length = input_size as u32;
-
-        let mut compressor = Compress::new(Compression::default(), false);
-        let max_compressed_size = compressor.total_out() + input_size as u64 + 6; // Add header size from compressBound
+        let timer = v8_flags::profile_deserialization().then(Instant::now);
 
-        snapshot_data.allocate_data((size_of::<u32>() as u32) + max_compressed_size as u32);
-        
-        let compressed_data_ptr = snapshot_data.raw_data_mut();
+        let input = uncompressed_data.raw_data();
+        let payload_length = input.len() as u32;
 
-        // Manually store the uncompressed size.
-        memcopy(compressed_data_ptr, &payload_length, size_of::<u32>());
+        // Raw deflate never grows the input by more than 5 bytes per 16K
+        // block plus a small constant, like compressBound().
+        let max_compressed_size = input.len() + (input.len() >> 12) + (input.len() >> 14) + 64;
+        let mut snapshot_data = SnapshotData::default();
+        snapshot_data.allocate_data((K_UINT32_SIZE + max_compressed_size) as u32);
+        LittleEndian::write_u32(&mut snapshot_data.raw_data_mut()[..K_UINT32_SIZE], payload_length);
 
+        let mut compressor = Compress::new(Compression::best(), false);
         let result = compressor.compress(
-            uncompressed_data.raw_data(), 
-            &mut compressed_data_ptr[size_of::<u32>()..],
-            FlushDecompress::Finish);
-
-        let compressed_data_size = match result {
-            Ok(Status::Ok) | Ok(Status::StreamEnd) => compressor.total_out() as usize,
-            Ok(Status::BufError) => panic!("Buffer error during compression"),
-            Ok(Status::NeedMoreInput) => panic!("Need more input during compression"),
-            Err(e) => panic!("Compression failed: {:?}", e),
-        };
-        
-        snapshot_data.resize(compressed_data_size as u32 + size_of::<u32>() as u32);
-        
-        assert_eq!(
-            payload_length,
-            get_uncompressed_size(snapshot_data.raw_data())
+            input,
+            &mut snapshot_data.raw_data_mut()[K_UINT32_SIZE..],
+            FlushCompress::Finish,
         );
+        match result {
+            Ok(Status::StreamEnd) => {}
+            Ok(status) => panic!("Snapshot compression did not finish: {:?}", status),
+            Err(e) => panic!("Snapshot compression failed: {:?}", e),
+        }
+        snapshot_data.resize((K_UINT32_SIZE as u64 + compressor.total_out()) as u32);
+        assert_eq!(Some(payload_length), get_uncompressed_size(snapshot_data.raw_data()));
 
-        if v8_flags::profile_deserialization() {
-            let ms = timer.elapsed().in_milliseconds_f();
-            PrintF(format!("[Compressing {} bytes took {:.3} ms]\n", payload_length, ms));
+        if let Some(timer) = timer {
+            let ms = timer.elapsed().as_secs_f64() * 1000.0;
+            println!("[Compressing {} bytes took {:.3} ms]", payload_length, ms);
         }
         snapshot_data
     }
 
-    /// Decompresses the given compressed data.
-    pub fn decompress(compressed_data: &[u8]) -> SnapshotData {
-        let mut snapshot_data = SnapshotData::new();
-        let mut timer = ElapsedTimer::new();
-        if v8_flags::profile_deserialization() {
-            timer.start();
-        }
-
-        let uncompressed_payload_length = get_uncompressed_size(compressed_data);
-        let mut input_bytef = &compressed_data[size_of::<u32>()..];
+    /// Decompresses the given compressed data. Fails if |compressed_data| is
+    /// truncated or does not hold a raw deflate stream of the recorded size.
+    pub fn decompress(compressed_data: &[u8]) -> Result<SnapshotData, String> {
+        let timer = v8_flags::profile_deserialization().then(Instant::now);
 
+        let uncompressed_payload_length = get_uncompressed_size(compressed_data)
+            .ok_or_else(|| format!("Compressed snapshot data is too short: {} bytes", compressed_data.len()))?;
+        let mut snapshot_data = SnapshotData::default();
         snapshot_data.allocate_data(uncompressed_payload_length);
 
         let mut decompressor = Decompress::new(false);
-
         let result = decompressor.decompress(
-            input_bytef,
+            &compressed_data[K_UINT32_SIZE..],
             snapshot_data.raw_data_mut(),
             FlushDecompress::Finish,
         );
-
         match result {
-            Ok(Status::Ok) | Ok(Status::StreamEnd) => (),
-            Ok(Status::BufError) => panic!("Buffer error during decompression"),
-            Ok(Status::NeedMoreInput) => panic!("Need more input during decompression"),
-            Err(e) => panic!("Decompression failed: {:?}", e),
-        };
-
-        if v8_flags::profile_deserialization() {
-            let ms = timer.elapsed().in_milliseconds_f();
-            PrintF(format!(
-                "[Decompressing {} bytes took {:.3} ms]\n",
-                uncompressed_payload_length, ms
+            Ok(Status::StreamEnd) => {}
+            Ok(status) => return Err(format!("Snapshot decompression did not finish: {:?}", status)),
+            Err(e) => return Err(format!("Snapshot decompression failed: {}", e)),
+        }
+        if decompressor.total_out() != uncompressed_payload_length as u64 {
+            return Err(format!(
+                "Snapshot is truncated: decompressed {} of {} bytes",
+                decompressor.total_out(),
+                uncompressed_payload_length
             ));
         }
-        snapshot_data
-    }
-}
 
-// Mock implementations needed for compilation
-pub mod base {
-    pub mod platform {
-        pub mod elapsed_timer {
-            use std::time::{Instant, Duration};
-
-            #[derive(Debug)]
-            pub struct ElapsedTimer {
-                start_time: Option<Instant>,
-            }
-
-            impl ElapsedTimer {
-                pub fn new() -> Self {
-                    ElapsedTimer { start_time: None }
-                }
-
-                pub fn start(&mut self) {
-                    self.start_time = Some(Instant::now());
-                }
-
-                pub fn elapsed(&self) -> ElapsedTime {
-                    match self.start_time {
-                        Some(start) => {
-                            let duration = Instant::now().duration_since(start);
-                            ElapsedTime { duration }
-                        },
-                        None => ElapsedTime { duration: Duration::from_secs(0) },
-                    }
-                }
-            }
-
-            #[derive(Debug)]
-            pub struct ElapsedTime {
-                duration: Duration,
-            }
-
-            impl ElapsedTime {
-                pub fn in_milliseconds_f(&self) -> f64 {
-                    self.duration.as_secs_f64() * 1000.0
-                }
-            }
+        if let Some(timer) = timer {
+            let ms = timer.elapsed().as_secs_f64() * 1000.0;
+            println!("[Decompressing {} bytes took {:.3} ms]", uncompressed_payload_length, ms);
         }
+        Ok(snapshot_data)
     }
 }
 
-pub mod utils {
-    pub mod memcopy {
-        use std::mem::size_of;
-        use std::slice;
+mod v8_flags {
+    pub fn profile_deserialization() -> bool {
+        false
+    }
+}
 
-        pub fn memcopy<T: Sized + Copy>(dest: &mut T, src: &[u8], size: usize) {
-            let dest_slice = unsafe {
-                slice::from_raw_parts_mut(dest as *mut T as *mut u8, size_of::<T>())
-            };
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            let copy_size = size.min(dest_slice.len().min(src.len()));
-            dest_slice[..copy_size].copy_from_slice(&src[..copy_size]);
+    #[test]
+    fn test_short_input_is_not_compressed() {
+        for data in [&[][..], &[1][..], &[1, 2, 3][..]] {
+            assert_eq!(get_uncompressed_size(data), None);
+            assert!(!is_compressed(data));
+            assert!(SnapshotCompression::decompress(data).is_err());
         }
     }
 
-    pub mod utils {
-        pub fn PrintF(message: String) {
-            print!("{}", message);
-        }
+    #[test]
+    fn test_round_trip_and_corrupt_input() {
+        let data = SnapshotData::new(&[7; 1000]);
+        let compressed = SnapshotCompression::compress(&data);
+        assert!(is_compressed(compressed.raw_data()));
+        assert!(!is_compressed(data.raw_data()));
+        assert_eq!(SnapshotCompression::decompress(compressed.raw_data()).unwrap(), data);
+
+        let truncated = &compressed.raw_data()[..compressed.raw_data().len() - 1];
+        assert!(SnapshotCompression::decompress(truncated).is_err());
+        let mut oversized = compressed.raw_data().to_vec();
+        oversized[0] += 1;
+        assert!(SnapshotCompression::decompress(&oversized).is_err());
     }
 }
-
-mod v8_flags {
-    pub fn profile_deserialization() -> bool {
-        false
-    }
-}
\ No newline at end of file
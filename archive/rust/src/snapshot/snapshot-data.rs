@@ -3,161 +3,132 @@
 // found in the LICENSE file.
 
 use std::mem::size_of;
-use std::ptr::null_mut;
 
 pub mod internal {
     use super::*;
     use byteorder::{ByteOrder, LittleEndian};
 
+    /// Serialized data with a header of uint32 fields. The first field is
+    /// always the magic number.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
     pub struct SerializedData {
-        data_: *mut u8,
-        size_: u32,
-        owns_data_: bool,
+        data_: Vec<u8>,
     }
 
     impl SerializedData {
-        pub fn new(data: *mut u8, size: i32) -> Self {
-            SerializedData {
-                data_: data,
-                size_: size as u32,
-                owns_data_: false,
-            }
-        }
+        pub const K_MAGIC_NUMBER_OFFSET: u32 = 0;
+        pub const K_MAGIC_NUMBER: u32 = 0xC0DE0000 ^ ExternalReferenceTable::K_SIZE;
 
-        pub fn default() -> Self {
-            SerializedData {
-                data_: null_mut(),
-                size_: 0,
-                owns_data_: false,
-            }
+        pub fn new(data: &[u8]) -> Self {
+            SerializedData { data_: data.to_vec() }
         }
 
-        pub fn move_from(other: &mut SerializedData) -> Self {
-            let data_ = other.data_;
-            let size_ = other.size_;
-            let owns_data_ = other.owns_data_;
+        pub fn get_magic_number(&self) -> u32 {
+            self.get_header_value(Self::K_MAGIC_NUMBER_OFFSET)
+        }
 
-            other.owns_data_ = false;
+        pub fn set_header_value(&mut self, offset: u32, value: u32) {
+            let offset = offset as usize;
+            LittleEndian::write_u32(&mut self.data_[offset..offset + 4], value);
+        }
 
-            SerializedData {
-                data_: data_,
-                size_: size_,
-                owns_data_: owns_data_,
-            }
+        pub fn get_header_value(&self, offset: u32) -> u32 {
+            let offset = offset as usize;
+            LittleEndian::read_u32(&self.data_[offset..offset + 4])
         }
 
-        pub fn get_magic_number(&self) -> u32 {
-            self.get_header_value(Self::K_MAGIC_NUMBER_OFFSET)
+        pub fn allocate_data(&mut self, size: u32) {
+            self.data_ = vec![0; size as usize];
         }
 
-        const K_MAGIC_NUMBER_OFFSET: u32 = 0;
-        const K_MAGIC_NUMBER: u32 = 0xC0DE0000 ^ crate::internal::ExternalReferenceTable::K_SIZE;
-
-        fn set_header_value(&self, offset: u32, value: u32) {
-            let data_ptr = self.data_ as *mut u8;
-            let offset_ptr = unsafe { data_ptr.add(offset as usize) };
-            let value_bytes = value.to_le_bytes();
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    value_bytes.as_ptr(),
-                    offset_ptr,
-                    value_bytes.len(),
-                );
-            }
-        }
-
-        fn get_header_value(&self, offset: u32) const -> u32 {
-            let data_ptr = self.data_ as *const u8;
-            let offset_ptr = unsafe { data_ptr.add(offset as usize) };
-            let mut buffer = [0u8; 4];
-            unsafe {
-                std::ptr::copy_nonoverlapping(
-                    offset_ptr,
-                    buffer.as_mut_ptr(),
-                    buffer.len(),
-                );
-            }
-            LittleEndian::read_u32(&buffer)
-        }
-
-        fn allocate_data(&mut self, size: u32) {
-            self.data_ = unsafe {
-                let layout = std::alloc::Layout::array::<u8>(size as usize).unwrap();
-                std::alloc::alloc(layout)
-            };
-            self.size_ = size;
-            self.owns_data_ = true;
-        }
-
-        fn set_magic_number(&self) {
+        pub fn set_magic_number(&mut self) {
             self.set_header_value(Self::K_MAGIC_NUMBER_OFFSET, Self::K_MAGIC_NUMBER);
         }
-    }
 
-    impl Drop for SerializedData {
-        fn drop(&mut self) {
-            if self.owns_data_ {
-                unsafe {
-                    let layout = std::alloc::Layout::array::<u8>(self.size_ as usize).unwrap();
-                    std::alloc::dealloc(self.data_, layout);
-                }
-            }
+        pub fn size(&self) -> usize {
+            self.data_.len()
+        }
+
+        pub fn data(&self) -> &[u8] {
+            &self.data_
+        }
+
+        pub fn data_mut(&mut self) -> &mut [u8] {
+            &mut self.data_
         }
     }
 
+    /// Wrapper around a serializer's payload that is stored in one of the
+    /// snapshot blob's sections.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
     pub struct SnapshotData {
         serialized_data: SerializedData,
     }
 
     impl SnapshotData {
-        //Used when producing
-        pub fn new(serializer: *const ()) -> Self {
-            // Serializer is unused in the current implementation,
-            // but the signature is kept to match the C++ code.
-            SnapshotData {
-                serialized_data: SerializedData::default(),
-            }
+        const K_UINT32_SIZE: u32 = size_of::<u32>() as u32;
+        const K_PAYLOAD_LENGTH_OFFSET: u32 = SerializedData::K_MAGIC_NUMBER_OFFSET + Self::K_UINT32_SIZE;
+        pub const K_HEADER_SIZE: u32 = Self::K_PAYLOAD_LENGTH_OFFSET + Self::K_UINT32_SIZE;
+
+        /// Used when producing.
+        pub fn new(payload: &[u8]) -> Self {
+            let size = Self::K_HEADER_SIZE + payload.len() as u32;
+            let mut serialized_data = SerializedData::default();
+            serialized_data.allocate_data(size);
+            serialized_data.set_magic_number();
+            serialized_data.set_header_value(Self::K_PAYLOAD_LENGTH_OFFSET, payload.len() as u32);
+            serialized_data.data_mut()[Self::K_HEADER_SIZE as usize..].copy_from_slice(payload);
+            SnapshotData { serialized_data }
         }
 
-        // Used when consuming.
+        /// Used when consuming. Panics if the data fails the sanity check;
+        /// the blob checksum has already been verified at this point, so a
+        /// mismatch means the snapshot was built for a different binary.
         pub fn from_snapshot(snapshot: &[u8]) -> Self {
-            SnapshotData {
-                serialized_data: SerializedData::new(snapshot.as_ptr() as *mut u8, snapshot.len() as i32),
-            }
+            Self::checked_from_snapshot(snapshot).expect("Snapshot data failed the sanity check")
+        }
+
+        /// Like from_snapshot, but returns None instead of panicking. Used by
+        /// tools that inspect untrusted blobs.
+        pub fn checked_from_snapshot(snapshot: &[u8]) -> Option<Self> {
+            let data = SnapshotData { serialized_data: SerializedData::new(snapshot) };
+            data.is_sane().then_some(data)
+        }
+
+        pub fn is_sane(&self) -> bool {
+            let size = self.serialized_data.size();
+            size >= Self::K_HEADER_SIZE as usize
+                && self.serialized_data.get_magic_number() == SerializedData::K_MAGIC_NUMBER
+                && self.serialized_data.get_header_value(Self::K_PAYLOAD_LENGTH_OFFSET) as usize
+                    == size - Self::K_HEADER_SIZE as usize
         }
 
         pub fn payload(&self) -> &[u8] {
-            let data_ptr = self.serialized_data.data_ as *const u8;
-            let payload_start = unsafe { data_ptr.add(Self::K_HEADER_SIZE as usize) };
-            unsafe { std::slice::from_raw_parts(payload_start, (self.serialized_data.size_ - Self::K_HEADER_SIZE) as usize) }
+            &self.serialized_data.data()[Self::K_HEADER_SIZE as usize..]
         }
 
         pub fn raw_data(&self) -> &[u8] {
-            unsafe { std::slice::from_raw_parts(self.serialized_data.data_ as *const u8, self.serialized_data.size_ as usize) }
+            self.serialized_data.data()
         }
-    }
 
-    impl SnapshotData {
-        const K_PAYLOAD_LENGTH_OFFSET: u32 = SerializedData::K_MAGIC_NUMBER_OFFSET + Self::K_UINT32_SIZE;
-        const K_HEADER_SIZE: u32 = Self::K_PAYLOAD_LENGTH_OFFSET + Self::K_UINT32_SIZE;
-        const K_UINT32_SIZE: u32 = size_of::<u32>() as u32;
-    }
+        /// Used by SnapshotCompression, which fills in the raw data itself.
+        pub fn allocate_data(&mut self, size: u32) {
+            self.serialized_data.allocate_data(size);
+        }
 
-    impl SnapshotData {
-        // Resize used by SnapshotCompression so it can shrink the compressed
-        // SnapshotData.
-        fn resize(&mut self, size: u32) {
-            self.serialized_data.size_ = size;
+        pub fn raw_data_mut(&mut self) -> &mut [u8] {
+            self.serialized_data.data_mut()
         }
 
-        fn default() -> Self {
-            SnapshotData {
-                serialized_data: SerializedData::default(),
-            }
+        /// Resize used by SnapshotCompression so it can shrink the compressed
+        /// SnapshotData.
+        pub fn resize(&mut self, size: u32) {
+            self.serialized_data.data_.truncate(size as usize);
         }
     }
 
+    #[allow(non_snake_case)]
     pub mod ExternalReferenceTable {
         pub const K_SIZE: u32 = 1234;
     }
-}
\ No newline at end of file
+}
@@ -127,9 +127,9 @@ impl<'a> SnapshotByteSource<'a> {
         let mut integer = self.data_[self.position_] as u32;
         self.position_ += 1;
 
+        // The low two bits of the first byte hold the number of extra bytes;
+        // the value is stored shifted past them.
         let bytes = (integer & 3) + 1;
-        integer >>= 2;
-
         if bytes > 1 {
             integer |= (self.data_[self.position_] as u32) << 8;
             self.position_ += 1;
@@ -142,7 +142,7 @@ impl<'a> SnapshotByteSource<'a> {
             integer |= (self.data_[self.position_] as u32) << 24;
             self.position_ += 1;
         }
-        integer
+        integer >> 2
     }
 
     /// Gets a blob (a sequence of bytes) from the source.
@@ -166,4 +166,60 @@ impl<'a> SnapshotByteSource<'a> {
     fn advance(&mut self, size: usize) {
         self.position_ += size;
     }
-}
\ No newline at end of file
+
+    /// Returns true if there is data left to read.
+    pub fn has_more(&self) -> bool {
+        self.position_ < self.length_
+    }
+
+    /// Gets a single byte from the source.
+    pub fn get(&mut self) -> u8 {
+        assert!(self.position_ < self.length_);
+        let value = self.data_[self.position_];
+        self.position_ += 1;
+        value
+    }
+
+    /// Returns the next byte without consuming it.
+    pub fn peek(&self) -> u8 {
+        assert!(self.position_ < self.length_);
+        self.data_[self.position_]
+    }
+
+    /// Gets |size| raw bytes from the source.
+    pub fn get_raw(&mut self, size: usize) -> &'a [u8] {
+        assert!(self.position_ + size <= self.length_);
+        let data = &self.data_[self.position_..self.position_ + size];
+        self.advance(size);
+        data
+    }
+
+    /// Returns the current read position.
+    pub fn position(&self) -> usize {
+        self.position_
+    }
+
+    /// Returns the total length of the source.
+    pub fn length(&self) -> usize {
+        self.length_
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uint30_round_trip() {
+        let values = [0, 1, 63, 64, 101, 0x3fff, 0x4000, 0x3f_ffff, 0x40_0000, (1 << 30) - 1];
+        let mut sink = SnapshotByteSink::new();
+        for &value in &values {
+            sink.put_uint30(value, "value");
+        }
+        let mut source = SnapshotByteSource::new(sink.data());
+        for &value in &values {
+            assert_eq!(source.get_uint30(), value);
+        }
+        assert!(!source.has_more());
+    }
+}
@@ -0,0 +1,551 @@
+// Copyright 2024 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+// Compresses, decompresses, verifies and inspects snapshot blobs.
+
+pub mod snapshot_tool {
+    use std::collections::{BTreeMap, HashMap};
+    use std::fmt::Write as _;
+
+    use crate::heap::heap_layout::{BodyKind, InstanceType, Map};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{
+        Bytecode, FixedRawDataWithSize, HotObject, NewObject, RootArrayConstant, SerializerDeserializer, SnapshotSpace,
+        K_TAGGED_SIZE,
+    };
+    use crate::snapshot::snapshot::snapshot::{Snapshot, StartupData};
+    use crate::snapshot::snapshot_source_sink::SnapshotByteSource;
+
+    /// How the sections of a snapshot blob are laid out; see the section
+    /// serializers.
+    #[allow(non_camel_case_types)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SectionKind {
+        kStartup,
+        kReadOnly,
+        kSharedHeap,
+        kContext,
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct InstanceTypeStatistics {
+        pub count: usize,
+        pub size: usize,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    pub struct SectionStatistics {
+        pub name: String,
+        /// Size of the section in the blob, i.e. possibly compressed.
+        pub stored_size: usize,
+        pub payload_size: usize,
+        pub object_count: usize,
+        /// Bytes the deserializer allocates for the section's objects.
+        pub allocation_size: usize,
+        pub bytecode_counts: BTreeMap<&'static str, usize>,
+        pub instance_types: HashMap<InstanceType, InstanceTypeStatistics>,
+    }
+
+    /// Decodes the bytecode stream of a snapshot section without
+    /// materializing any objects. The grammar mirrors DeserializerCore.
+    pub struct SnapshotDisassembler<'a> {
+        source_: SnapshotByteSource<'a>,
+        listing_: Option<String>,
+        statistics_: SectionStatistics,
+    }
+
+    impl<'a> SnapshotDisassembler<'a> {
+        pub fn new(name: &str, payload: &'a [u8], with_listing: bool) -> Self {
+            SnapshotDisassembler {
+                source_: SnapshotByteSource::new(payload),
+                listing_: with_listing.then(String::new),
+                statistics_: SectionStatistics {
+                    name: name.to_string(),
+                    payload_size: payload.len(),
+                    ..Default::default()
+                },
+            }
+        }
+
+        /// Returns the statistics of the section and, if requested, one line
+        /// per bytecode. Fails on the first bytecode that does not fit the
+        /// section grammar instead of panicking, since the input may be any
+        /// file handed to the tool.
+        pub fn disassemble(mut self, kind: SectionKind) -> Result<(SectionStatistics, Option<String>), String> {
+            match kind {
+                SectionKind::kReadOnly => {
+                    // Objects, deferred objects, then the roots table.
+                    self.read_until_synchronize()?;
+                    self.read_until_synchronize()?;
+                    let offset = self.source_.position();
+                    let root_count = self.get_uint30()?;
+                    let roots = (0..root_count)
+                        .map(|_| self.get_uint30().map(|root| root.to_string()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.emit(offset, 0, format!("ReadOnlyRoots [{}]", roots.join(", ")));
+                    self.read_synchronize()?;
+                }
+                SectionKind::kSharedHeap => {
+                    // One object cache entry and its deferred objects per
+                    // synchronization point, terminated by an empty one.
+                    while !self.at_synchronize() {
+                        self.read_until_synchronize()?;
+                    }
+                    self.read_synchronize()?;
+                }
+                SectionKind::kStartup => {
+                    // Strong roots, startup object cache, deferred objects.
+                    for _ in 0..3 {
+                        self.read_until_synchronize()?;
+                    }
+                }
+                SectionKind::kContext => self.read_until_synchronize()?,
+            }
+            if self.source_.has_more() {
+                return Err(self.error(format!("trailing data at offset {}", self.source_.position())));
+            }
+            Ok((self.statistics_, self.listing_))
+        }
+
+        fn error(&self, message: String) -> String {
+            format!("Section {}: {}", self.statistics_.name, message)
+        }
+
+        fn get(&mut self) -> Result<u8, String> {
+            if !self.source_.has_more() {
+                return Err(self.error(format!("unexpected end of data at offset {}", self.source_.position())));
+            }
+            Ok(self.source_.get())
+        }
+
+        fn get_uint30(&mut self) -> Result<u32, String> {
+            // The low two bits of the first byte hold the number of extra
+            // bytes; see SnapshotByteSource::get_uint30.
+            let remaining = self.source_.length() - self.source_.position();
+            if !self.source_.has_more() || remaining < (self.source_.peek() & 3) as usize + 1 {
+                return Err(self.error(format!("truncated integer at offset {}", self.source_.position())));
+            }
+            Ok(self.source_.get_uint30())
+        }
+
+        fn get_raw(&mut self, size: usize) -> Result<&'a [u8], String> {
+            if self.source_.length() - self.source_.position() < size {
+                return Err(self.error(format!(
+                    "{} raw bytes at offset {} run past the end of the data",
+                    size,
+                    self.source_.position()
+                )));
+            }
+            Ok(self.source_.get_raw(size))
+        }
+
+        fn at_synchronize(&self) -> bool {
+            self.source_.has_more() && self.source_.peek() == Bytecode::kSynchronize as u8
+        }
+
+        fn read_synchronize(&mut self) -> Result<(), String> {
+            let offset = self.source_.position();
+            if self.get()? != Bytecode::kSynchronize as u8 {
+                return Err(self.error(format!("snapshot out of sync at offset {}", offset)));
+            }
+            self.count("Synchronize");
+            self.emit(offset, 0, "Synchronize".to_string());
+            Ok(())
+        }
+
+        /// Reads root slots and deferred objects up to and including the next
+        /// kSynchronize. At the top level both start with a bytecode that a
+        /// slot may start with, so they are decoded alike.
+        fn read_until_synchronize(&mut self) -> Result<(), String> {
+            while !self.at_synchronize() {
+                self.read_slot(0)?;
+            }
+            self.read_synchronize()
+        }
+
+        fn read_slot(&mut self, depth: usize) -> Result<(), String> {
+            let mut offset = self.source_.position();
+            let mut bytecode = self.get()?;
+            if bytecode == Bytecode::kWeakPrefix as u8 {
+                self.count("WeakPrefix");
+                self.emit(offset, depth, "WeakPrefix".to_string());
+                offset = self.source_.position();
+                bytecode = self.get()?;
+            }
+            let (name, text): (&'static str, String) = match bytecode {
+                b if b == Bytecode::kClearedWeakReference as u8 => {
+                    ("ClearedWeakReference", "ClearedWeakReference".to_string())
+                }
+                b if b == FixedRawDataWithSize::encode(1) => {
+                    let raw = self.get_raw(K_TAGGED_SIZE)?;
+                    let value = i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]);
+                    ("Smi", format!("Smi {}", value))
+                }
+                b if b == Bytecode::kRegisterPendingForwardRef as u8 => {
+                    ("RegisterPendingForwardRef", "RegisterPendingForwardRef".to_string())
+                }
+                b if b < Bytecode::kBackref as u8 => {
+                    let space = NewObject::decode::<{ Bytecode::kNewObject as u8 }>(b);
+                    return self.read_object(offset, space, depth);
+                }
+                b if b == Bytecode::kBackref as u8 => ("Backref", format!("Backref {}", self.get_uint30()?)),
+                b if b == Bytecode::kReadOnlyHeapRef as u8 => {
+                    ("ReadOnlyHeapRef", format!("ReadOnlyHeapRef {}", self.get_uint30()?))
+                }
+                b if b == Bytecode::kRootArray as u8 => ("RootArray", format!("RootArray {}", self.get_uint30()?)),
+                b if b == Bytecode::kStartupObjectCache as u8 => {
+                    ("StartupObjectCache", format!("StartupObjectCache {}", self.get_uint30()?))
+                }
+                b if b == Bytecode::kSharedHeapObjectCache as u8 => {
+                    ("SharedHeapObjectCache", format!("SharedHeapObjectCache {}", self.get_uint30()?))
+                }
+                b if b == Bytecode::kAttachedReference as u8 => {
+                    ("AttachedReference", format!("AttachedReference {}", self.get_uint30()?))
+                }
+                b if (Bytecode::kRootArrayConstants as u8..Bytecode::kFixedRawData as u8).contains(&b) => {
+                    ("RootArrayConstant", format!("RootArrayConstant {}", RootArrayConstant::decode(b)))
+                }
+                b if (Bytecode::kHotObject as u8
+                    ..Bytecode::kHotObject as u8 + SerializerDeserializer::K_HOT_OBJECT_COUNT as u8)
+                    .contains(&b) =>
+                {
+                    ("HotObject", format!("HotObject {}", HotObject::decode(b)))
+                }
+                b => return Err(self.error(format!("unexpected bytecode {:#x} at offset {}", b, offset))),
+            };
+            self.count(name);
+            self.emit(offset, depth, text);
+            Ok(())
+        }
+
+        fn read_object(&mut self, offset: usize, space: SnapshotSpace, depth: usize) -> Result<(), String> {
+            let raw_instance_type = self.get_uint30()?;
+            let Some(instance_type) = u16::try_from(raw_instance_type).ok().and_then(InstanceType::from_raw) else {
+                return Err(self.error(format!("unknown instance type {} at offset {}", raw_instance_type, offset)));
+            };
+            let map = Map::for_instance_type(instance_type);
+            let length = self.get_uint30()? as usize;
+            let mut forward_refs = Vec::new();
+            while self.source_.has_more() && self.source_.peek() == Bytecode::kResolvePendingForwardRef as u8 {
+                self.source_.get();
+                forward_refs.push(self.get_uint30()?);
+                self.count("ResolvePendingForwardRef");
+            }
+
+            let size = map.object_size(length);
+            self.count("NewObject");
+            self.statistics_.object_count += 1;
+            self.statistics_.allocation_size += size;
+            let entry = self.statistics_.instance_types.entry(instance_type).or_default();
+            entry.count += 1;
+            entry.size += size;
+
+            let mut text = format!("NewObject {} {:?} size={} length={}", space.name(), instance_type, size, length);
+            for forward_ref in forward_refs {
+                let _ = write!(text, " resolves={}", forward_ref);
+            }
+            self.emit(offset, depth, text);
+            if map.body_kind() == BodyKind::Data {
+                self.get_raw(length)?;
+                return Ok(());
+            }
+            for _ in 0..length {
+                self.read_slot(depth + 1)?;
+            }
+            Ok(())
+        }
+
+        fn count(&mut self, name: &'static str) {
+            *self.statistics_.bytecode_counts.entry(name).or_insert(0) += 1;
+        }
+
+        fn emit(&mut self, offset: usize, depth: usize, text: String) {
+            if let Some(listing) = self.listing_.as_mut() {
+                let _ = writeln!(listing, "  {:08x}  {:indent$}{}", offset, "", text, indent = 2 * depth);
+            }
+        }
+    }
+
+    /// Decodes every section of |blob|, which may be compressed.
+    pub fn disassemble_blob(
+        blob: &StartupData,
+        with_listing: bool,
+    ) -> Result<Vec<(SectionStatistics, Option<String>)>, String> {
+        let sections = Snapshot::extract_sections(blob);
+        let mut named_sections = vec![
+            ("startup".to_string(), SectionKind::kStartup, sections.startup),
+            ("read-only".to_string(), SectionKind::kReadOnly, sections.read_only),
+            ("shared heap".to_string(), SectionKind::kSharedHeap, sections.shared_heap),
+        ];
+        for (index, context) in sections.contexts.iter().enumerate() {
+            named_sections.push((format!("context {}", index), SectionKind::kContext, context));
+        }
+        named_sections
+            .into_iter()
+            .map(|(name, kind, raw_data)| {
+                let data = Snapshot::extract_section_data(raw_data).map_err(|error| format!("Section {}: {}", name, error))?;
+                let (mut statistics, listing) =
+                    SnapshotDisassembler::new(&name, data.payload(), with_listing).disassemble(kind)?;
+                statistics.stored_size = raw_data.len();
+                Ok((statistics, listing))
+            })
+            .collect()
+    }
+
+    /// Human-readable summary of |blob|: header fields, per-section sizes,
+    /// the object types taking up the most space and, optionally, the
+    /// bytecode listing of every section.
+    pub fn dump(blob: &StartupData, top_types: usize, with_bytecodes: bool) -> Result<String, String> {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Snapshot blob: {} bytes, {} context(s), {}, {}",
+            blob.raw_size(),
+            Snapshot::extract_num_contexts(blob),
+            if Snapshot::extract_rehashability(blob) { "rehashable" } else { "not rehashable" },
+            if Snapshot::is_compressed(blob) { "compressed" } else { "uncompressed" },
+        );
+        let _ = writeln!(
+            out,
+            "Version: {}, checksum: {}",
+            if Snapshot::version_is_valid(blob) { "matches" } else { "MISMATCH" },
+            if Snapshot::verify_checksum(blob) { "ok" } else { "MISMATCH" },
+        );
+
+        let sections = disassemble_blob(blob, with_bytecodes)?;
+        let _ = writeln!(out, "\nSections:");
+        let _ = writeln!(
+            out,
+            "  {:<12} {:>10} {:>10} {:>8} {:>10}",
+            "section", "stored", "payload", "objects", "allocated"
+        );
+        let mut instance_types: HashMap<InstanceType, InstanceTypeStatistics> = HashMap::new();
+        let mut bytecode_counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+        for (statistics, _) in &sections {
+            let _ = writeln!(
+                out,
+                "  {:<12} {:>10} {:>10} {:>8} {:>10}",
+                statistics.name,
+                statistics.stored_size,
+                statistics.payload_size,
+                statistics.object_count,
+                statistics.allocation_size
+            );
+            for (&instance_type, type_statistics) in &statistics.instance_types {
+                let entry = instance_types.entry(instance_type).or_default();
+                entry.count += type_statistics.count;
+                entry.size += type_statistics.size;
+            }
+            for (&name, &count) in &statistics.bytecode_counts {
+                *bytecode_counts.entry(name).or_insert(0) += count;
+            }
+        }
+
+        let mut by_size: Vec<_> = instance_types.into_iter().collect();
+        by_size.sort_by(|(a_type, a), (b_type, b)| b.size.cmp(&a.size).then(a_type.cmp(b_type)));
+        let _ = writeln!(out, "\nTop object types by size:");
+        for (instance_type, statistics) in by_size.into_iter().take(top_types) {
+            let _ = writeln!(
+                out,
+                "  {:<28} {:>8} objects {:>10} bytes",
+                format!("{:?}", instance_type),
+                statistics.count,
+                statistics.size
+            );
+        }
+
+        let _ = writeln!(out, "\nBytecodes:");
+        for (name, count) in bytecode_counts {
+            let _ = writeln!(out, "  {:<28} {:>8}", name, count);
+        }
+
+        if with_bytecodes {
+            for (statistics, listing) in &sections {
+                let _ = writeln!(out, "\n[{}]", statistics.name);
+                out.push_str(listing.as_deref().unwrap_or(""));
+            }
+        }
+        Ok(out)
+    }
+
+    const USAGE: &str = "Usage: snapshot-tool compress <in> <out>\n       snapshot-tool decompress <in> <out>\n       snapshot-tool verify <in>\n       snapshot-tool dump [--bytecodes] [--top=N] <in>\n";
+
+    fn read_blob(path: &str) -> Result<StartupData, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Unable to read '{}': {}", path, e))?;
+        let blob = StartupData::new(data);
+        if !Snapshot::verify_checksum(&blob) {
+            return Err(format!("'{}' is not a valid snapshot blob: checksum mismatch", path));
+        }
+        if !Snapshot::version_is_valid(&blob) {
+            return Err(format!("'{}' was created by a different V8 version", path));
+        }
+        Ok(blob)
+    }
+
+    fn write_blob(path: &str, blob: &StartupData) -> Result<(), String> {
+        std::fs::write(path, &blob.data).map_err(|e| format!("Unable to write '{}': {}", path, e))
+    }
+
+    /// Runs the tool on |args|, excluding the program name, and returns what
+    /// it prints on success.
+    pub fn run(args: &[String]) -> Result<String, String> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["compress", input, output] => {
+                let blob = read_blob(input)?;
+                let compressed = Snapshot::compress(&blob);
+                write_blob(output, &compressed)?;
+                Ok(format!("Compressed {} bytes to {} bytes\n", blob.raw_size(), compressed.raw_size()))
+            }
+            ["decompress", input, output] => {
+                let blob = read_blob(input)?;
+                let decompressed = Snapshot::decompress(&blob);
+                write_blob(output, &decompressed)?;
+                Ok(format!("Decompressed {} bytes to {} bytes\n", blob.raw_size(), decompressed.raw_size()))
+            }
+            ["verify", input] => {
+                let blob = read_blob(input)?;
+                // Decoding every section also checks the section headers.
+                disassemble_blob(&blob, false)?;
+                Ok(format!("{}: ok\n", input))
+            }
+            ["dump", options @ .., input] => {
+                let mut top_types = 10;
+                let mut with_bytecodes = false;
+                for &option in options {
+                    if option == "--bytecodes" {
+                        with_bytecodes = true;
+                    } else if let Some(value) = option.strip_prefix("--top=") {
+                        top_types = value.parse().map_err(|_| format!("Invalid value for --top: {}", value))?;
+                    } else {
+                        return Err(format!("Unknown option {}\n{}", option, USAGE));
+                    }
+                }
+                let blob = read_blob(input)?;
+                dump(&blob, top_types, with_bytecodes)
+            }
+            _ => Err(USAGE.to_string()),
+        }
+    }
+
+    pub fn main() {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        match run(&args) {
+            Ok(output) => print!("{}", output),
+            Err(error) => {
+                eprint!("{}", error);
+                if !error.ends_with('\n') {
+                    eprintln!();
+                }
+                std::process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::heap::heap_layout::Tagged;
+        use crate::snapshot::snapshot::snapshot::{Isolate, SerializerFlags};
+
+        fn create_blob() -> StartupData {
+            let mut isolate = Isolate::new();
+            isolate.init_without_snapshot();
+            let context = isolate.contexts()[0];
+            isolate.add_script(context, "function framework() { return 42; }");
+            let list = isolate.allocate_fixed_array(&(0..100).map(Tagged::from_smi).collect::<Vec<_>>());
+            isolate.set_global_property(context, "list", Tagged::strong(list));
+            Snapshot::create(&isolate, &[context], SerializerFlags::kNoFlags)
+        }
+
+        #[test]
+        fn test_compress_decompress_round_trip() {
+            let blob = create_blob();
+            assert!(!Snapshot::is_compressed(&blob));
+
+            let compressed = Snapshot::compress(&blob);
+            assert!(Snapshot::is_compressed(&compressed));
+            assert!(Snapshot::verify_checksum(&compressed));
+            assert!(compressed.raw_size() < blob.raw_size());
+            assert_eq!(Snapshot::compress(&compressed), compressed);
+            assert_eq!(Snapshot::decompress(&compressed), blob);
+
+            // Isolates can be set up from compressed blobs directly.
+            let mut isolate = Isolate::new();
+            isolate.set_snapshot_blob(compressed);
+            assert!(Snapshot::initialize(&mut isolate));
+            assert!(Snapshot::new_context_from_snapshot(&mut isolate, 0).is_some());
+        }
+
+        #[test]
+        fn test_disassembly_matches_deserialized_heap() {
+            let blob = create_blob();
+            let sections = disassemble_blob(&blob, true).unwrap();
+            assert_eq!(
+                sections.iter().map(|(statistics, _)| statistics.name.as_str()).collect::<Vec<_>>(),
+                ["startup", "read-only", "shared heap", "context 0"]
+            );
+
+            let mut isolate = Isolate::new();
+            isolate.set_snapshot_blob(blob.clone());
+            assert!(Snapshot::initialize(&mut isolate));
+            Snapshot::new_context_from_snapshot(&mut isolate, 0).unwrap();
+            let object_count: usize = sections.iter().map(|(statistics, _)| statistics.object_count).sum();
+            assert_eq!(object_count, isolate.reachable_objects().len());
+
+            // The same statistics are reported for the compressed blob.
+            let compressed = disassemble_blob(&Snapshot::compress(&blob), false).unwrap();
+            for ((expected, _), (actual, _)) in sections.iter().zip(&compressed) {
+                assert_eq!(expected.object_count, actual.object_count);
+                assert_eq!(expected.allocation_size, actual.allocation_size);
+                assert_eq!(expected.bytecode_counts, actual.bytecode_counts);
+            }
+
+            let output = dump(&blob, 3, true).unwrap();
+            assert!(output.contains("1 context(s), rehashable, uncompressed"));
+            assert!(output.contains("Version: matches, checksum: ok"));
+            let largest_type = output.lines().skip_while(|line| !line.starts_with("Top object types")).nth(1).unwrap();
+            assert!(largest_type.trim_start().starts_with("FIXED_ARRAY_TYPE"), "{}", largest_type);
+            assert!(output.contains("[read-only]"));
+            assert!(output.contains("NewObject read_only_heap INTERNALIZED_STRING_TYPE"));
+            assert!(output.contains("ReadOnlyRoots [1, 3, 5, 7, 8, 9]"));
+        }
+
+        #[test]
+        fn test_malformed_sections_are_reported() {
+            let disassemble = |payload: &[u8]| SnapshotDisassembler::new("test", payload, true).disassemble(SectionKind::kContext);
+            let synchronize = Bytecode::kSynchronize as u8;
+            assert!(disassemble(&[synchronize]).is_ok());
+            assert!(disassemble(&[0xff, synchronize]).unwrap_err().contains("unexpected bytecode 0xff at offset 0"));
+            assert!(disassemble(&[synchronize, synchronize]).unwrap_err().contains("trailing data"));
+            assert!(disassemble(&[]).unwrap_err().contains("unexpected end of data"));
+            assert!(disassemble(&[Bytecode::kBackref as u8, 0x03]).unwrap_err().contains("truncated integer"));
+            assert!(disassemble(&[FixedRawDataWithSize::encode(1), 0]).unwrap_err().contains("run past the end"));
+        }
+
+        #[test]
+        fn test_cli() {
+            let directory = std::env::temp_dir().join(format!("snapshot-tool-test-{}", std::process::id()));
+            std::fs::create_dir_all(&directory).unwrap();
+            let path = |name: &str| directory.join(name).to_string_lossy().into_owned();
+            std::fs::write(path("blob.bin"), create_blob().data).unwrap();
+
+            let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+            run(&args(&["compress", &path("blob.bin"), &path("compressed.bin")])).unwrap();
+            run(&args(&["decompress", &path("compressed.bin"), &path("decompressed.bin")])).unwrap();
+            assert_eq!(std::fs::read(path("blob.bin")).unwrap(), std::fs::read(path("decompressed.bin")).unwrap());
+            assert!(run(&args(&["verify", &path("compressed.bin")])).unwrap().ends_with(": ok\n"));
+            assert!(run(&args(&["dump", "--top=2", &path("compressed.bin")])).unwrap().contains("compressed"));
+
+            let mut corrupted = std::fs::read(path("blob.bin")).unwrap();
+            let last = corrupted.len() - 1;
+            corrupted[last] ^= 0xff;
+            std::fs::write(path("corrupted.bin"), corrupted).unwrap();
+            assert!(run(&args(&["verify", &path("corrupted.bin")])).unwrap_err().contains("checksum mismatch"));
+            assert!(run(&args(&["dump", "--frobnicate", &path("blob.bin")])).is_err());
+            assert!(run(&args(&["compress"])).unwrap_err().starts_with("Usage"));
+
+            std::fs::remove_dir_all(&directory).unwrap();
+        }
+    }
+}
@@ -4,85 +4,206 @@
 
 // The common functionality when building with or without snapshots.
 
-// TODO: Add necessary crate dependencies in Cargo.toml
-
-// mod api; // Assuming api-inl.h is related to the V8 API
-// mod baseline; // Assuming baseline-batch-compiler.h defines baseline compilation functionality
-// mod common; // Assuming common/assert-scope.h defines assertion utilities
-// mod execution; // Assuming execution/local-isolate-inl.h defines local isolate functionality
-// mod handles; // Assuming handles/global-handles-inl.h defines global handles
-// mod heap; // Assuming heap/local-heap-inl.h and other heap-related headers define heap management
-// mod init; // Assuming init/bootstrapper.h defines initialization functionality
-// mod logging; // Assuming logging/counters-scopes.h and runtime-call-stats-scope.h define logging utilities
-// mod objects; // Assuming objects/js-regexp-inl.h defines JS RegExp objects
-// mod snapshot; // Assuming snapshot-related headers define snapshot serialization/deserialization
-// mod utils; // Assuming utils/memcopy.h and version.h define memory utilities and version information
-
-use std::borrow::Cow;
-use std::mem;
-use std::ptr;
-use std::slice;
-
-// Placeholder for SnapshotData, replace with actual implementation
-#[derive(Debug)]
-pub struct SnapshotData<'a> {
-    data: Cow<'a, [u8]>,
-}
+pub mod v8 {
+    use crate::heap::heap::HeapOptions;
 
-impl<'a> SnapshotData<'a> {
-    pub fn new(data: Cow<'a, [u8]>) -> Self {
-        SnapshotData { data }
+    /// A snapshot blob, as produced by mksnapshot or
+    /// SnapshotCreator::CreateBlob.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct StartupData {
+        pub data: Vec<u8>,
     }
 
-    pub fn raw_data(&self) -> &Cow<'a, [u8]> {
-        &self.data
+    impl StartupData {
+        pub fn new(data: Vec<u8>) -> Self {
+            StartupData { data }
+        }
+
+        pub fn raw_size(&self) -> usize {
+            self.data.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.data.is_empty()
+        }
     }
-}
 
-mod snapshot {
-    use super::*;
-    use std::convert::TryInto;
+    /// The limits of the heap of an isolate.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ResourceConstraints {
+        code_range_size_: usize,
+        max_old_generation_size_: usize,
+        initial_old_generation_size_: usize,
+        max_young_generation_size_: usize,
+    }
 
-    // Placeholder for V8 types and functions, replace with actual implementations
-    pub type Isolate = usize; // Replace with actual Isolate struct
-    pub type Context = usize; // Replace with actual Context struct
-    pub type JSGlobalProxy = usize; // Replace with actual JSGlobalProxy struct
-    pub type StartupData = v8::StartupData;
+    impl Default for ResourceConstraints {
+        fn default() -> Self {
+            let options = HeapOptions::default();
+            ResourceConstraints {
+                code_range_size_: 0,
+                max_old_generation_size_: options.max_old_generation_size,
+                initial_old_generation_size_: options.initial_old_generation_size,
+                // Two semi-spaces.
+                max_young_generation_size_: 2 * options.max_semi_space_size,
+            }
+        }
+    }
 
-    pub trait DeserializeEmbedderFieldsCallbackTrait {}
-    pub struct DeserializeEmbedderFieldsCallback {}
-    impl DeserializeEmbedderFieldsCallbackTrait for DeserializeEmbedderFieldsCallback {}
+    impl ResourceConstraints {
+        pub fn code_range_size_in_bytes(&self) -> usize {
+            self.code_range_size_
+        }
+        pub fn set_code_range_size_in_bytes(&mut self, limit: usize) {
+            self.code_range_size_ = limit;
+        }
 
-    pub trait SerializeEmbedderFieldsCallbackTrait {}
-    pub struct SerializeEmbedderFieldsCallback {}
-    impl SerializeEmbedderFieldsCallbackTrait for SerializeEmbedderFieldsCallback {}
+        pub fn max_old_generation_size_in_bytes(&self) -> usize {
+            self.max_old_generation_size_
+        }
+        pub fn set_max_old_generation_size_in_bytes(&mut self, limit: usize) {
+            self.max_old_generation_size_ = limit;
+        }
 
-    pub struct SafepointScope {}
-    pub struct DisallowGarbageCollection {}
+        pub fn initial_old_generation_size_in_bytes(&self) -> usize {
+            self.initial_old_generation_size_
+        }
+        pub fn set_initial_old_generation_size_in_bytes(&mut self, initial_size: usize) {
+            self.initial_old_generation_size_ = initial_size;
+        }
 
-    impl SafepointScope {
-        pub fn new(_isolate: &Isolate, _kind: SafepointKind) -> Self {
-            SafepointScope {}
+        pub fn max_young_generation_size_in_bytes(&self) -> usize {
+            self.max_young_generation_size_
+        }
+        pub fn set_max_young_generation_size_in_bytes(&mut self, limit: usize) {
+            self.max_young_generation_size_ = limit;
         }
     }
 
-    impl DisallowGarbageCollection {
-        pub fn new() -> Self {
-            DisallowGarbageCollection {}
-        }
+    /// Initial configuration parameters for a new Isolate.
+    #[derive(Debug, Clone, Default)]
+    pub struct CreateParams {
+        pub constraints: ResourceConstraints,
+        /// The snapshot blob to set the isolate up from, if any.
+        pub snapshot_blob: Option<StartupData>,
     }
+}
 
-    #[derive(Copy, Clone)]
-    pub enum SafepointKind {
-        kGlobal,
-        kIsolate,
+pub mod snapshot {
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::sync::Arc;
+
+    use byteorder::{ByteOrder, LittleEndian};
+
+    pub use super::v8::{CreateParams, ResourceConstraints, StartupData};
+    use crate::heap::heap::{AllocationType, Heap, HeapOptions, RootHandle};
+    use crate::heap::heap_layout::{
+        BodyKind, HeapObject, InstanceType, Map, Tagged, FIXED_ARRAY_MAP, JS_GLOBAL_OBJECT_MAP,
+        JS_GLOBAL_PROXY_MAP, ODDBALL_MAP, SCRIPT_MAP, SHARED_FUNCTION_INFO_MAP, BYTECODE_ARRAY_MAP, SCOPE_INFO_MAP,
+        FEEDBACK_METADATA_MAP, WEAK_FIXED_ARRAY_MAP,
+    };
+    use crate::logging::runtime_call_stats::{RuntimeCallCounterId, RuntimeCallStatsTables};
+    use crate::logging::runtime_call_stats_scope::HasRuntimeCallStats;
+    use crate::snapshot::context_deserializer::context_deserializer::ContextDeserializer;
+    use crate::snapshot::context_serializer::context_serializer::ContextSerializer;
+    use crate::snapshot::deserializer::deserializer::ExternalObjects;
+    use crate::snapshot::read_only_deserializer::read_only_deserializer::ReadOnlyDeserializer;
+    use crate::snapshot::read_only_serializer::read_only_serializer::ReadOnlySerializer;
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::RootIndex;
+    use crate::snapshot::shared_heap_deserializer::shared_heap_deserializer::SharedHeapDeserializer;
+    use crate::snapshot::shared_heap_serializer::shared_heap_serializer::SharedHeapSerializer;
+    use crate::snapshot::snapshot_compression::{is_compressed, SnapshotCompression};
+    use crate::snapshot::snapshot_data::internal::SnapshotData;
+    use crate::snapshot::snapshot_utils::snapshot_utils::checksum;
+    use crate::snapshot::startup_deserializer::startup_deserializer::StartupDeserializer;
+    use crate::snapshot::startup_serializer::startup_serializer::StartupSerializer;
+    use crate::utils::version::version::get_version_static;
+
+    /// Indices into the read-only roots table.
+    pub mod read_only_root {
+        use super::RootIndex;
+
+        pub const K_UNDEFINED_VALUE: RootIndex = 0;
+        pub const K_NULL_VALUE: RootIndex = 1;
+        pub const K_TRUE_VALUE: RootIndex = 2;
+        pub const K_FALSE_VALUE: RootIndex = 3;
+        pub const K_EMPTY_STRING: RootIndex = 4;
+        pub const K_EMPTY_FIXED_ARRAY: RootIndex = 5;
+    }
+
+    /// Indices into the strong roots.
+    pub mod strong_root {
+        /// ArrayList of the internalized strings that are not read-only.
+        pub const K_STRING_TABLE: usize = 0;
+        /// ArrayList of all scripts of the isolate.
+        pub const K_SCRIPT_LIST: usize = 1;
     }
 
+    /// Body layout of the objects the bootstrapper and the compiler create.
+    /// Integer fields hold Smis.
+    pub mod layout {
+        pub const K_ODDBALL_TO_STRING_SLOT: usize = 0;
+        pub const K_ODDBALL_KIND_SLOT: usize = 1;
+        pub const K_ODDBALL_LENGTH: usize = 2;
+
+        pub const K_ODDBALL_KIND_FALSE: isize = 0;
+        pub const K_ODDBALL_KIND_TRUE: isize = 1;
+        pub const K_ODDBALL_KIND_NULL: isize = 3;
+        pub const K_ODDBALL_KIND_UNDEFINED: isize = 5;
+
+        pub const K_SCRIPT_SOURCE_SLOT: usize = 0;
+        /// Weak ArrayList of the script's SharedFunctionInfos.
+        pub const K_SCRIPT_SHARED_FUNCTION_INFOS_SLOT: usize = 1;
+        pub const K_SCRIPT_LENGTH: usize = 2;
+
+        pub const K_NATIVE_CONTEXT_GLOBAL_PROXY_SLOT: usize = 0;
+        pub const K_NATIVE_CONTEXT_GLOBAL_OBJECT_SLOT: usize = 1;
+        pub const K_NATIVE_CONTEXT_EXTENSION_SLOT: usize = 2;
+        /// Weak ArrayList of the scripts compiled in the context, undefined
+        /// until the first one is.
+        pub const K_NATIVE_CONTEXT_SCRIPT_LIST_SLOT: usize = 3;
+        pub const K_NATIVE_CONTEXT_LENGTH: usize = 4;
+
+        /// ArrayList of name/value pairs.
+        pub const K_GLOBAL_OBJECT_PROPERTIES_SLOT: usize = 0;
+        pub const K_GLOBAL_OBJECT_NATIVE_CONTEXT_SLOT: usize = 1;
+        pub const K_GLOBAL_OBJECT_LENGTH: usize = 2;
+
+        pub const K_GLOBAL_PROXY_TARGET_SLOT: usize = 0;
+        pub const K_GLOBAL_PROXY_LENGTH: usize = 1;
+
+        pub const K_SHARED_FUNCTION_INFO_NAME_SLOT: usize = 0;
+        pub const K_SHARED_FUNCTION_INFO_SCRIPT_SLOT: usize = 1;
+        pub const K_SHARED_FUNCTION_INFO_SCOPE_INFO_SLOT: usize = 2;
+        pub const K_SHARED_FUNCTION_INFO_FEEDBACK_METADATA_SLOT: usize = 3;
+        /// The BytecodeArray of compiled functions, undefined otherwise.
+        pub const K_SHARED_FUNCTION_INFO_FUNCTION_DATA_SLOT: usize = 4;
+        pub const K_SHARED_FUNCTION_INFO_START_POSITION_SLOT: usize = 5;
+        pub const K_SHARED_FUNCTION_INFO_END_POSITION_SLOT: usize = 6;
+        pub const K_SHARED_FUNCTION_INFO_FUNCTION_LITERAL_ID_SLOT: usize = 7;
+        pub const K_SHARED_FUNCTION_INFO_LENGTH: usize = 8;
+
+        pub const K_BYTECODE_ARRAY_CONSTANT_POOL_SLOT: usize = 0;
+        pub const K_BYTECODE_ARRAY_FRAME_SIZE_SLOT: usize = 1;
+        pub const K_BYTECODE_ARRAY_PARAMETER_COUNT_SLOT: usize = 2;
+        /// ByteArray holding the bytecodes.
+        pub const K_BYTECODE_ARRAY_BYTECODES_SLOT: usize = 3;
+        pub const K_BYTECODE_ARRAY_LENGTH: usize = 4;
+
+        /// ArrayLists are FixedArrays, or WeakFixedArrays for lists of weak
+        /// references, whose first element is the number of used elements.
+        pub const K_ARRAY_LIST_LENGTH_SLOT: usize = 0;
+        pub const K_ARRAY_LIST_FIRST_ELEMENT_SLOT: usize = 1;
+        pub const K_ARRAY_LIST_INITIAL_CAPACITY: usize = 4;
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct SerializerFlags {
         bits: u32,
     }
 
     impl SerializerFlags {
+        pub const kNoFlags: SerializerFlags = SerializerFlags { bits: 0 };
         pub const kAllowUnknownExternalReferencesForTesting: SerializerFlags =
             SerializerFlags { bits: 1 << 0 };
         pub const kAllowActiveIsolateForTesting: SerializerFlags =
@@ -91,7 +212,11 @@ mod snapshot {
             SerializerFlags { bits: 1 << 2 };
 
         pub fn from_bits_truncate(bits: u32) -> Self {
-            SerializerFlags { bits }
+            SerializerFlags { bits: bits & 0b111 }
+        }
+
+        pub fn contains(&self, other: SerializerFlags) -> bool {
+            self.bits & other.bits == other.bits
         }
     }
 
@@ -105,253 +230,1343 @@ mod snapshot {
         }
     }
 
-    #[allow(dead_code)]
-    pub fn should_verify_checksum(_data: &StartupData) -> bool {
-        true // Replace with actual logic
-    }
+    /// Compiles and runs |source| in |context|. Installed by the embedder
+    /// through Isolate::set_run_script_callback.
+    pub type RunScriptCallback = fn(&mut Isolate, HeapObject, &str) -> Result<(), String>;
 
-    #[allow(dead_code)]
-    pub fn default_snapshot_blob() -> *const v8::StartupData {
-        ptr::null() // Replace with actual logic
+    /// Compiles |script| and returns its top-level SharedFunctionInfo.
+    /// Installed by the embedder through Isolate::set_compile_script_callback.
+    pub type CompileScriptCallback = fn(&mut Isolate, HeapObject) -> Result<HeapObject, String>;
+
+    /// The isolate state the snapshot system reads and writes. Objects live
+    /// in the isolate's managed heap and are reachable from root handles:
+    /// the read-only objects and roots, the strong roots, the native
+    /// contexts and the object caches of the snapshot it was set up from.
+    ///
+    /// The isolate allocates everything in old space inside an
+    /// always-allocate scope and never collects garbage, so objects do not
+    /// move and may be held as HeapObjects.
+    pub struct Isolate {
+        heap_: Heap,
+        read_only_objects_: RootHandle,
+        read_only_roots_: RootHandle,
+        strong_roots_: RootHandle,
+        contexts_: RootHandle,
+        startup_object_cache_: RootHandle,
+        shared_heap_object_cache_: RootHandle,
+        read_only_set_: HashSet<HeapObject>,
+        run_script_callback_: Option<RunScriptCallback>,
+        compile_script_callback_: Option<CompileScriptCallback>,
+        snapshot_blob_: Option<StartupData>,
+        initialized_from_snapshot_: bool,
+        runtime_call_stats_: Arc<RuntimeCallStatsTables>,
     }
 
-    pub fn create(
-        isolate: &mut Isolate,
-        contexts: &mut Vec<Context>,
-        embedder_fields_serializers: &Vec<SerializeEmbedderFieldsCallback>,
-        safepoint_scope: &SafepointScope,
-        no_gc: &DisallowGarbageCollection,
-        flags: SerializerFlags,
-    ) -> StartupData {
-        SnapshotImpl::create_snapshot_blob(StartupData::default(), StartupData::default(), StartupData::default(), vec![], true)
+    impl Default for Isolate {
+        fn default() -> Self {
+            Self::new()
+        }
     }
 
-    // Placeholder for checksum calculation, replace with actual implementation
-    #[allow(dead_code)]
-    pub fn checksum(_data: &[u8]) -> u32 {
-        0 // Replace with actual checksum algorithm
+    impl HasRuntimeCallStats for Isolate {
+        fn runtime_call_stats(&self) -> Option<&RuntimeCallStatsTables> {
+            Some(&self.runtime_call_stats_)
+        }
     }
 
-    pub fn verify_checksum(data: &StartupData) -> bool {
-        SnapshotImpl::verify_checksum(data)
+    impl Drop for Isolate {
+        fn drop(&mut self) {
+            self.heap_.leave_always_allocate();
+        }
     }
 
-    pub fn initialize(isolate: &mut Isolate) -> bool {
-        if !has_snapshot_available(isolate) {
-            return false;
+    impl Isolate {
+        pub fn new() -> Self {
+            Self::with_params(CreateParams::default())
         }
-    
-        let blob = isolate_snapshot_blob(isolate);
-        if blob.is_null() {
-            return false;
+
+        /// Creates an isolate whose heap is sized by |params.constraints|.
+        /// The snapshot blob, if any, is only recorded; Snapshot::initialize
+        /// sets the isolate up from it.
+        pub fn with_params(params: CreateParams) -> Self {
+            let constraints = params.constraints;
+            let heap = Heap::new(HeapOptions {
+                max_semi_space_size: constraints.max_young_generation_size_in_bytes() / 2,
+                max_old_generation_size: constraints.max_old_generation_size_in_bytes(),
+                initial_old_generation_size: constraints.initial_old_generation_size_in_bytes(),
+                incremental_marking: false,
+                concurrent_marking: false,
+                concurrent_sweeping: false,
+                compaction: false,
+                parallel_compaction: false,
+                ..HeapOptions::default()
+            });
+            heap.enter_always_allocate();
+            let runtime_call_stats = Arc::new(RuntimeCallStatsTables::new());
+            heap.set_runtime_call_stats(runtime_call_stats.clone());
+            Isolate {
+                read_only_objects_: heap.create_root(Tagged::ZERO),
+                read_only_roots_: heap.create_root(Tagged::ZERO),
+                strong_roots_: heap.create_root(Tagged::ZERO),
+                contexts_: heap.create_root(Tagged::ZERO),
+                startup_object_cache_: heap.create_root(Tagged::ZERO),
+                shared_heap_object_cache_: heap.create_root(Tagged::ZERO),
+                heap_: heap,
+                read_only_set_: HashSet::new(),
+                run_script_callback_: None,
+                compile_script_callback_: None,
+                snapshot_blob_: params.snapshot_blob,
+                initialized_from_snapshot_: false,
+                runtime_call_stats_: runtime_call_stats,
+            }
         }
-    
-        SnapshotImpl::check_version(&*blob);
-        if should_verify_checksum(&*blob) {
-            if !verify_checksum(&*blob) {
-                return false;
+
+        pub fn heap(&self) -> &Heap {
+            &self.heap_
+        }
+
+        pub fn set_snapshot_blob(&mut self, blob: StartupData) {
+            self.snapshot_blob_ = Some(blob);
+        }
+
+        pub fn snapshot_blob(&self) -> Option<&StartupData> {
+            self.snapshot_blob_.as_ref()
+        }
+
+        pub fn initialized_from_snapshot(&self) -> bool {
+            self.initialized_from_snapshot_
+        }
+
+        /// Returns true once the read-only heap is set up, either by the
+        /// bootstrapper or from a snapshot.
+        pub fn is_initialized(&self) -> bool {
+            !self.read_only_set_.is_empty()
+        }
+
+        // Root lists.
+
+        fn root_list(&self, handle: RootHandle) -> Vec<Tagged> {
+            match self.heap_.root(handle).get_heap_object() {
+                Some(list) => (0..list.length()).map(|index| list.get(index)).collect(),
+                None => Vec::new(),
             }
         }
-    
-        let startup_data = SnapshotImpl::extract_startup_data(&*blob);
-        let read_only_data = SnapshotImpl::extract_read_only_data(&*blob);
-        let shared_heap_data = SnapshotImpl::extract_shared_heap_data(&*blob);
-    
-        true
-    }
-    
-    fn has_snapshot_available(isolate: &Isolate) -> bool {
-        true
-    }
 
-    unsafe fn isolate_snapshot_blob(isolate: &Isolate) -> *const StartupData {
-        ptr::null()
-    }
+        fn root_objects(&self, handle: RootHandle) -> Vec<HeapObject> {
+            self.root_list(handle)
+                .into_iter()
+                .map(|value| value.get_heap_object().expect("Root list entry is not an object"))
+                .collect()
+        }
 
-    pub fn extract_rehashability(data: &StartupData) -> bool {
-        SnapshotImpl::extract_rehashability(data)
-    }
-}
+        fn set_root_list(&mut self, handle: RootHandle, values: &[Tagged]) {
+            let list = self.allocate_fixed_array(values);
+            self.heap_.set_root(handle, Tagged::strong(list));
+        }
 
-mod v8 {
-    #[derive(Clone, Default)]
-    pub struct StartupData {
-        pub data: *const i8,
-        pub raw_size: i32,
-    }
-}
+        /// The read-only objects in the order kReadOnlyHeapRef indices refer
+        /// to them.
+        pub fn read_only_objects(&self) -> Vec<HeapObject> {
+            self.root_objects(self.read_only_objects_)
+        }
 
-// Placeholder for base functions, replace with actual implementations
-mod base {
-    use std::{mem, ptr};
+        pub fn read_only_roots(&self) -> Vec<HeapObject> {
+            self.root_objects(self.read_only_roots_)
+        }
 
-    pub type Vector<T> = Vec<T>;
+        pub fn strong_roots(&self) -> Vec<Tagged> {
+            self.root_list(self.strong_roots_)
+        }
 
-    pub fn read_little_endian_value<T: Copy>(address: usize) -> T {
-        unsafe { ptr::read_unaligned(address as *const T) }
-    }
+        pub fn startup_object_cache(&self) -> Vec<HeapObject> {
+            self.root_objects(self.startup_object_cache_)
+        }
 
-    pub fn write_little_endian_value<T: Copy>(address: usize, value: T) {
-        unsafe { ptr::write_unaligned(address as *mut T, value) }
-    }
+        pub fn shared_heap_object_cache(&self) -> Vec<HeapObject> {
+            self.root_objects(self.shared_heap_object_cache_)
+        }
+
+        fn strong_root(&self, index: usize) -> HeapObject {
+            self.heap_
+                .root_object(self.strong_roots_)
+                .get(index)
+                .get_heap_object()
+                .expect("Strong root is not an object")
+        }
 
-    pub fn vector_of<T>(data: *const T, length: usize) -> Vector<&'static T> {
-        if data.is_null() {
-            return Vec::new();
+        fn set_strong_root(&self, index: usize, value: HeapObject) {
+            self.heap_.set(self.heap_.root_object(self.strong_roots_), index, Tagged::strong(value));
         }
-    
-        unsafe {
-            let slice = std::slice::from_raw_parts(data, length);
-            slice.iter().collect()
+
+        fn set_read_only_heap(&mut self, objects: &[HeapObject], roots: &[HeapObject]) {
+            self.read_only_set_ = objects.iter().copied().collect();
+            let objects: Vec<Tagged> = objects.iter().map(|&object| Tagged::strong(object)).collect();
+            let roots: Vec<Tagged> = roots.iter().map(|&object| Tagged::strong(object)).collect();
+            self.set_root_list(self.read_only_objects_, &objects);
+            self.set_root_list(self.read_only_roots_, &roots);
+        }
+
+        pub fn is_read_only(&self, object: HeapObject) -> bool {
+            self.read_only_set_.contains(&object)
+        }
+
+        /// Shared objects live in the shared heap: the internalized strings
+        /// of the string table.
+        pub fn is_shared(&self, object: HeapObject) -> bool {
+            !self.is_read_only(object) && object.map().instance_type() == InstanceType::INTERNALIZED_STRING_TYPE
+        }
+
+        pub fn root(&self, index: RootIndex) -> HeapObject {
+            self.read_only_roots()[index as usize]
+        }
+
+        pub fn undefined_value(&self) -> Tagged {
+            Tagged::strong(self.root(read_only_root::K_UNDEFINED_VALUE))
+        }
+
+        pub fn null_value(&self) -> Tagged {
+            Tagged::strong(self.root(read_only_root::K_NULL_VALUE))
+        }
+
+        pub fn true_value(&self) -> Tagged {
+            Tagged::strong(self.root(read_only_root::K_TRUE_VALUE))
+        }
+
+        pub fn false_value(&self) -> Tagged {
+            Tagged::strong(self.root(read_only_root::K_FALSE_VALUE))
+        }
+
+        /// Sets up the heap from scratch: the read-only oddballs and empty
+        /// objects, the strong roots and one native context. This is what
+        /// the bootstrapper does when no snapshot is available.
+        pub fn init_without_snapshot(&mut self) {
+            assert!(!self.is_initialized(), "Isolate is already initialized");
+
+            let mut objects = Vec::new();
+            let mut roots = Vec::new();
+            for (name, kind) in [
+                ("undefined", layout::K_ODDBALL_KIND_UNDEFINED),
+                ("null", layout::K_ODDBALL_KIND_NULL),
+                ("true", layout::K_ODDBALL_KIND_TRUE),
+                ("false", layout::K_ODDBALL_KIND_FALSE),
+            ] {
+                let to_string = self.heap_.allocate_string(name.as_bytes(), true, AllocationType::kOld);
+                let oddball = self.allocate(&ODDBALL_MAP, layout::K_ODDBALL_LENGTH);
+                self.heap_.set(oddball, layout::K_ODDBALL_TO_STRING_SLOT, Tagged::strong(to_string));
+                self.heap_.set(oddball, layout::K_ODDBALL_KIND_SLOT, Tagged::from_smi(kind));
+                objects.extend([to_string, oddball]);
+                roots.push(oddball);
+            }
+            let empty_string = self.heap_.allocate_string(b"", true, AllocationType::kOld);
+            let empty_fixed_array = self.allocate(&FIXED_ARRAY_MAP, 0);
+            objects.extend([empty_string, empty_fixed_array]);
+            roots.extend([empty_string, empty_fixed_array]);
+            self.set_read_only_heap(&objects, &roots);
+
+            let string_table = self.allocate_array_list(false);
+            let script_list = self.allocate_array_list(false);
+            self.set_root_list(self.strong_roots_, &[Tagged::strong(string_table), Tagged::strong(script_list)]);
+
+            let context = self.create_native_context();
+            self.add_context(context);
+        }
+
+        /// Creates a native context with its own global object and proxy.
+        pub fn create_native_context(&mut self) -> HeapObject {
+            let undefined = self.undefined_value();
+            let context = self.heap_.allocate_native_context(layout::K_NATIVE_CONTEXT_LENGTH);
+            let properties = self.allocate_array_list(false);
+            let global_object = self.allocate(&JS_GLOBAL_OBJECT_MAP, layout::K_GLOBAL_OBJECT_LENGTH);
+            self.heap_.set(global_object, layout::K_GLOBAL_OBJECT_PROPERTIES_SLOT, Tagged::strong(properties));
+            self.heap_.set(global_object, layout::K_GLOBAL_OBJECT_NATIVE_CONTEXT_SLOT, Tagged::strong(context));
+            let global_proxy = self.allocate(&JS_GLOBAL_PROXY_MAP, layout::K_GLOBAL_PROXY_LENGTH);
+            self.heap_.set(global_proxy, layout::K_GLOBAL_PROXY_TARGET_SLOT, Tagged::strong(global_object));
+
+            self.heap_.set(context, layout::K_NATIVE_CONTEXT_GLOBAL_PROXY_SLOT, Tagged::strong(global_proxy));
+            self.heap_.set(context, layout::K_NATIVE_CONTEXT_GLOBAL_OBJECT_SLOT, Tagged::strong(global_object));
+            self.heap_.set(context, layout::K_NATIVE_CONTEXT_EXTENSION_SLOT, undefined);
+            self.heap_.set(context, layout::K_NATIVE_CONTEXT_SCRIPT_LIST_SLOT, undefined);
+            context
+        }
+
+        /// The native contexts of the isolate, in creation order.
+        pub fn contexts(&self) -> Vec<HeapObject> {
+            match self.heap_.root(self.contexts_).get_heap_object() {
+                Some(list) => Self::array_list_elements(list)
+                    .into_iter()
+                    .map(|value| value.get_heap_object().expect("Context list entry is not an object"))
+                    .collect(),
+                None => Vec::new(),
+            }
+        }
+
+        pub fn add_context(&mut self, context: HeapObject) {
+            let list = match self.heap_.root(self.contexts_).get_heap_object() {
+                Some(list) => list,
+                None => self.allocate_array_list(false),
+            };
+            let list = self.array_list_append(list, Tagged::strong(context));
+            self.heap_.set_root(self.contexts_, Tagged::strong(list));
+        }
+
+        // Allocation.
+
+        /// Allocates an object of |map| whose body is initialized as by
+        /// Heap::allocate_with_map.
+        pub fn allocate(&self, map: &'static Map, length: usize) -> HeapObject {
+            self.heap_.allocate_with_map(map, length, AllocationType::kOld)
+        }
+
+        pub fn allocate_fixed_array(&self, elements: &[Tagged]) -> HeapObject {
+            let array = self.allocate(&FIXED_ARRAY_MAP, elements.len());
+            for (index, &element) in elements.iter().enumerate() {
+                self.heap_.set(array, index, element);
+            }
+            array
+        }
+
+        pub fn allocate_string(&self, value: &str) -> HeapObject {
+            self.heap_.allocate_string(value.as_bytes(), false, AllocationType::kOld)
+        }
+
+        pub fn string_value(&self, string: HeapObject) -> String {
+            String::from_utf8_lossy(string.data()).into_owned()
+        }
+
+        /// Returns the internalized string for |value|, adding it to the
+        /// string table if needed.
+        pub fn intern(&mut self, value: &str) -> HeapObject {
+            let empty_string = self.root(read_only_root::K_EMPTY_STRING);
+            if value.is_empty() {
+                return empty_string;
+            }
+            let string_table = self.strong_root(strong_root::K_STRING_TABLE);
+            for entry in Self::array_list_elements(string_table) {
+                let entry = entry.get_heap_object().expect("String table entry is not an object");
+                if entry.data() == value.as_bytes() {
+                    return entry;
+                }
+            }
+            let string = self.heap_.allocate_string(value.as_bytes(), true, AllocationType::kOld);
+            let string_table = self.array_list_append(string_table, Tagged::strong(string));
+            self.set_strong_root(strong_root::K_STRING_TABLE, string_table);
+            string
+        }
+
+        /// Returns the string table entry equal to the internalized string
+        /// |string|, adding |string| to the table if there is none. Used for
+        /// strings that were deserialized outside of the shared heap.
+        pub fn internalize(&mut self, string: HeapObject) -> HeapObject {
+            assert_eq!(string.map().instance_type(), InstanceType::INTERNALIZED_STRING_TYPE);
+            let empty_string = self.root(read_only_root::K_EMPTY_STRING);
+            if string.data().is_empty() {
+                return empty_string;
+            }
+            let string_table = self.strong_root(strong_root::K_STRING_TABLE);
+            for entry in Self::array_list_elements(string_table) {
+                let entry = entry.get_heap_object().expect("String table entry is not an object");
+                if entry.data() == string.data() {
+                    return entry;
+                }
+            }
+            let string_table = self.array_list_append(string_table, Tagged::strong(string));
+            self.set_strong_root(strong_root::K_STRING_TABLE, string_table);
+            string
+        }
+
+        /// Allocates an empty ArrayList; |weak| lists hold weak references.
+        pub fn allocate_array_list(&self, weak: bool) -> HeapObject {
+            let map = if weak { &WEAK_FIXED_ARRAY_MAP } else { &FIXED_ARRAY_MAP };
+            self.allocate(map, layout::K_ARRAY_LIST_FIRST_ELEMENT_SLOT + layout::K_ARRAY_LIST_INITIAL_CAPACITY)
+        }
+
+        /// Appends |value| to |list| and returns the list, which is a new
+        /// object if |list| had to grow.
+        pub fn array_list_append(&self, list: HeapObject, value: Tagged) -> HeapObject {
+            let count = Self::array_list_count(list);
+            let index = layout::K_ARRAY_LIST_FIRST_ELEMENT_SLOT + count;
+            let list = if index < list.length() {
+                list
+            } else {
+                let grown = self.allocate(list.map(), 2 * list.length());
+                for slot in 0..index {
+                    self.heap_.set(grown, slot, list.get(slot));
+                }
+                grown
+            };
+            self.heap_.set(list, index, value);
+            self.heap_.set(list, layout::K_ARRAY_LIST_LENGTH_SLOT, Tagged::from_smi(count as isize + 1));
+            list
+        }
+
+        pub fn array_list_count(list: HeapObject) -> usize {
+            list.get(layout::K_ARRAY_LIST_LENGTH_SLOT).smi_value().expect("ArrayList has no length") as usize
+        }
+
+        pub fn array_list_elements(list: HeapObject) -> Vec<Tagged> {
+            let first = layout::K_ARRAY_LIST_FIRST_ELEMENT_SLOT;
+            (first..first + Self::array_list_count(list)).map(|index| list.get(index)).collect()
+        }
+
+        // Scripts.
+
+        pub fn set_run_script_callback(&mut self, callback: RunScriptCallback) {
+            self.run_script_callback_ = Some(callback);
+        }
+
+        /// Runs |source| in |context| through the embedder's callback. The
+        /// isolate has no compiler of its own.
+        pub fn run_script(&mut self, context: HeapObject, source: &str) -> Result<(), String> {
+            crate::rcs_scope!(self, RuntimeCallCounterId::kJS_Execution);
+            match self.run_script_callback_ {
+                Some(callback) => callback(self, context, source),
+                None => Err("No script runner installed".to_string()),
+            }
+        }
+
+        /// Allocates a Script for the source string |source| and adds it to
+        /// the isolate's script list.
+        pub fn allocate_script(&mut self, source: HeapObject) -> HeapObject {
+            let shared_function_infos = self.allocate_array_list(true);
+            let script = self.allocate(&SCRIPT_MAP, layout::K_SCRIPT_LENGTH);
+            self.heap_.set(script, layout::K_SCRIPT_SOURCE_SLOT, Tagged::strong(source));
+            self.heap_.set(script, layout::K_SCRIPT_SHARED_FUNCTION_INFOS_SLOT, Tagged::strong(shared_function_infos));
+            self.register_script(script);
+            script
+        }
+
+        /// Adds |script| to the isolate's script list.
+        pub fn register_script(&mut self, script: HeapObject) {
+            let script_list = self.strong_root(strong_root::K_SCRIPT_LIST);
+            let script_list = self.array_list_append(script_list, Tagged::strong(script));
+            self.set_strong_root(strong_root::K_SCRIPT_LIST, script_list);
+        }
+
+        /// The scripts of the isolate, in compilation order.
+        pub fn scripts(&self) -> Vec<HeapObject> {
+            Self::array_list_elements(self.strong_root(strong_root::K_SCRIPT_LIST))
+                .into_iter()
+                .map(|value| value.get_heap_object().expect("Script list entry is not an object"))
+                .collect()
+        }
+
+        /// Records a compiled script: the script is added to the isolate's
+        /// script list and, weakly, to the script list of |context|.
+        pub fn add_script(&mut self, context: HeapObject, source: &str) -> HeapObject {
+            let source = self.allocate_string(source);
+            let script = self.allocate_script(source);
+
+            let context_scripts = match context.get(layout::K_NATIVE_CONTEXT_SCRIPT_LIST_SLOT).get_heap_object() {
+                Some(list) if !self.is_read_only(list) => list,
+                _ => self.allocate_array_list(true),
+            };
+            let context_scripts = self.array_list_append(context_scripts, Tagged::weak(script));
+            self.heap_.set(context, layout::K_NATIVE_CONTEXT_SCRIPT_LIST_SLOT, Tagged::strong(context_scripts));
+            script
+        }
+
+        pub fn set_compile_script_callback(&mut self, callback: CompileScriptCallback) {
+            self.compile_script_callback_ = Some(callback);
+        }
+
+        /// Compiles |script| through the embedder's callback. Without a
+        /// callback only a lazy top-level SharedFunctionInfo is created, to be
+        /// compiled on first call.
+        pub fn compile_script(&mut self, script: HeapObject) -> Result<HeapObject, String> {
+            crate::rcs_scope!(self, RuntimeCallCounterId::kCompileScript);
+            match self.compile_script_callback_ {
+                Some(callback) => callback(self, script),
+                None => {
+                    let source_length = self.script_source(script).length() as u32;
+                    let undefined = self.undefined_value();
+                    Ok(self.allocate_shared_function_info(script, "", 0, source_length, 0, undefined, undefined, None))
+                }
+            }
+        }
+
+        pub fn script_source(&self, script: HeapObject) -> HeapObject {
+            script.get(layout::K_SCRIPT_SOURCE_SLOT).get_heap_object().expect("Script has no source")
+        }
+
+        // Compiled code.
+
+        pub fn allocate_scope_info(&self, data: &[u8]) -> HeapObject {
+            let scope_info = self.allocate(&SCOPE_INFO_MAP, data.len());
+            scope_info.data().copy_from_slice(data);
+            scope_info
+        }
+
+        /// |slot_kinds| holds one FeedbackSlotKind per feedback slot.
+        pub fn allocate_feedback_metadata(&self, slot_kinds: &[u8]) -> HeapObject {
+            let feedback_metadata = self.allocate(&FEEDBACK_METADATA_MAP, slot_kinds.len());
+            feedback_metadata.data().copy_from_slice(slot_kinds);
+            feedback_metadata
+        }
+
+        pub fn allocate_bytecode_array(
+            &self,
+            bytecodes: &[u8],
+            frame_size: u32,
+            parameter_count: u32,
+            constant_pool: &[Tagged],
+        ) -> HeapObject {
+            let constant_pool = if constant_pool.is_empty() {
+                self.root(read_only_root::K_EMPTY_FIXED_ARRAY)
+            } else {
+                self.allocate_fixed_array(constant_pool)
+            };
+            let bytes = self.heap_.allocate_byte_array(bytecodes.len(), AllocationType::kOld);
+            bytes.data().copy_from_slice(bytecodes);
+            let bytecode_array = self.allocate(&BYTECODE_ARRAY_MAP, layout::K_BYTECODE_ARRAY_LENGTH);
+            let fields = [
+                (layout::K_BYTECODE_ARRAY_CONSTANT_POOL_SLOT, Tagged::strong(constant_pool)),
+                (layout::K_BYTECODE_ARRAY_FRAME_SIZE_SLOT, Tagged::from_smi(frame_size as isize)),
+                (layout::K_BYTECODE_ARRAY_PARAMETER_COUNT_SLOT, Tagged::from_smi(parameter_count as isize)),
+                (layout::K_BYTECODE_ARRAY_BYTECODES_SLOT, Tagged::strong(bytes)),
+            ];
+            for (slot, value) in fields {
+                self.heap_.set(bytecode_array, slot, value);
+            }
+            bytecode_array
+        }
+
+        /// Allocates a SharedFunctionInfo for the function literal
+        /// |function_literal_id| of |script| and registers it, weakly, with
+        /// the script.
+        #[allow(clippy::too_many_arguments)]
+        pub fn allocate_shared_function_info(
+            &mut self,
+            script: HeapObject,
+            name: &str,
+            start_position: u32,
+            end_position: u32,
+            function_literal_id: u32,
+            scope_info: Tagged,
+            feedback_metadata: Tagged,
+            bytecode_array: Option<HeapObject>,
+        ) -> HeapObject {
+            let name = self.intern(name);
+            let function_data = bytecode_array.map_or(self.undefined_value(), Tagged::strong);
+            let shared = self.allocate(&SHARED_FUNCTION_INFO_MAP, layout::K_SHARED_FUNCTION_INFO_LENGTH);
+            let fields = [
+                (layout::K_SHARED_FUNCTION_INFO_NAME_SLOT, Tagged::strong(name)),
+                (layout::K_SHARED_FUNCTION_INFO_SCRIPT_SLOT, Tagged::strong(script)),
+                (layout::K_SHARED_FUNCTION_INFO_SCOPE_INFO_SLOT, scope_info),
+                (layout::K_SHARED_FUNCTION_INFO_FEEDBACK_METADATA_SLOT, feedback_metadata),
+                (layout::K_SHARED_FUNCTION_INFO_FUNCTION_DATA_SLOT, function_data),
+                (layout::K_SHARED_FUNCTION_INFO_START_POSITION_SLOT, Tagged::from_smi(start_position as isize)),
+                (layout::K_SHARED_FUNCTION_INFO_END_POSITION_SLOT, Tagged::from_smi(end_position as isize)),
+                (layout::K_SHARED_FUNCTION_INFO_FUNCTION_LITERAL_ID_SLOT, Tagged::from_smi(function_literal_id as isize)),
+            ];
+            for (slot, value) in fields {
+                self.heap_.set(shared, slot, value);
+            }
+            let infos = script
+                .get(layout::K_SCRIPT_SHARED_FUNCTION_INFOS_SLOT)
+                .get_heap_object()
+                .expect("Script has no SharedFunctionInfo list");
+            let infos = self.array_list_append(infos, Tagged::weak(shared));
+            self.heap_.set(script, layout::K_SCRIPT_SHARED_FUNCTION_INFOS_SLOT, Tagged::strong(infos));
+            shared
+        }
+
+        /// The SharedFunctionInfos registered with |script|, in creation
+        /// order.
+        pub fn shared_function_infos(&self, script: HeapObject) -> Vec<HeapObject> {
+            let infos = script
+                .get(layout::K_SCRIPT_SHARED_FUNCTION_INFOS_SLOT)
+                .get_heap_object()
+                .expect("Script has no SharedFunctionInfo list");
+            Self::array_list_elements(infos).into_iter().filter_map(Tagged::get_heap_object).collect()
+        }
+
+        // Global properties.
+
+        fn global_object(context: HeapObject) -> HeapObject {
+            context
+                .get(layout::K_NATIVE_CONTEXT_GLOBAL_OBJECT_SLOT)
+                .get_heap_object()
+                .expect("Context has no global object")
+        }
+
+        fn global_properties(context: HeapObject) -> HeapObject {
+            Self::global_object(context)
+                .get(layout::K_GLOBAL_OBJECT_PROPERTIES_SLOT)
+                .get_heap_object()
+                .expect("Global object has no properties")
+        }
+
+        /// Defines or updates |name| on the global object of |context|.
+        /// Properties are stored as name/value pairs in the global object's
+        /// backing store.
+        pub fn set_global_property(&mut self, context: HeapObject, name: &str, value: Tagged) {
+            let properties = Self::global_properties(context);
+            let entries = Self::array_list_elements(properties);
+            for (pair, entry) in entries.chunks(2).enumerate() {
+                let key = entry[0].get_heap_object().expect("Property name is not an object");
+                if key.data() == name.as_bytes() {
+                    let value_index = layout::K_ARRAY_LIST_FIRST_ELEMENT_SLOT + 2 * pair + 1;
+                    self.heap_.set(properties, value_index, value);
+                    return;
+                }
+            }
+            let name = self.intern(name);
+            let properties = self.array_list_append(properties, Tagged::strong(name));
+            let properties = self.array_list_append(properties, value);
+            self.heap_.set(Self::global_object(context), layout::K_GLOBAL_OBJECT_PROPERTIES_SLOT, Tagged::strong(properties));
+        }
+
+        /// Looks up |name| on the global object of |context|.
+        pub fn get_global_property(&self, context: HeapObject, name: &str) -> Option<Tagged> {
+            let entries = Self::array_list_elements(Self::global_properties(context));
+            entries.chunks(2).find_map(|entry| {
+                let key = entry[0].get_heap_object()?;
+                (key.data() == name.as_bytes()).then_some(entry[1])
+            })
+        }
+
+        // Verification.
+
+        /// The objects a snapshot of the isolate contains: everything
+        /// reachable from the read-only objects, the strong roots and the
+        /// native contexts, following weak references too.
+        pub fn reachable_objects(&self) -> HashSet<HeapObject> {
+            let mut worklist: Vec<Tagged> = self.read_only_objects().into_iter().map(Tagged::strong).collect();
+            worklist.extend(self.strong_roots());
+            worklist.extend(self.contexts().into_iter().map(Tagged::strong));
+            let mut visited = HashSet::new();
+            while let Some(value) = worklist.pop() {
+                let Some(object) = value.get_heap_object() else { continue };
+                if !visited.insert(object) || object.map().body_kind() == BodyKind::Data {
+                    continue;
+                }
+                worklist.extend((0..object.length()).map(|index| object.get(index)));
+            }
+            visited
+        }
+
+        /// Checks that |other| holds the same object graph as this isolate,
+        /// starting from the read-only roots, the strong roots and the
+        /// contexts.
+        pub fn verify_equivalent(&self, other: &Isolate) -> Result<(), String> {
+            let roots = |isolate: &Isolate| -> Vec<Tagged> {
+                let mut roots: Vec<Tagged> = isolate.read_only_roots().into_iter().map(Tagged::strong).collect();
+                roots.extend(isolate.strong_roots());
+                roots.extend(isolate.contexts().into_iter().map(Tagged::strong));
+                roots
+            };
+            let (expected_roots, actual_roots) = (roots(self), roots(other));
+            if expected_roots.len() != actual_roots.len() {
+                return Err(format!("{} roots, expected {}", actual_roots.len(), expected_roots.len()));
+            }
+
+            let mut mapping: HashMap<HeapObject, HeapObject> = HashMap::new();
+            let mut worklist: VecDeque<(Tagged, Tagged)> = expected_roots.into_iter().zip(actual_roots).collect();
+            while let Some((expected, actual)) = worklist.pop_front() {
+                let (expected_object, actual_object) = match (expected.get_heap_object(), actual.get_heap_object()) {
+                    (Some(e), Some(a)) if expected.is_weak() == actual.is_weak() => (e, a),
+                    (None, None) if expected.smi_value() == actual.smi_value() && expected.is_cleared() == actual.is_cleared() => {
+                        continue;
+                    }
+                    _ => return Err(format!("slot mismatch: {:?} vs {:?}", expected, actual)),
+                };
+                if let Some(&mapped) = mapping.get(&expected_object) {
+                    if mapped != actual_object {
+                        return Err(format!("{:?} maps to both {:?} and {:?}", expected_object, mapped, actual_object));
+                    }
+                    continue;
+                }
+                mapping.insert(expected_object, actual_object);
+
+                let (expected_map, actual_map) = (expected_object.map(), actual_object.map());
+                if expected_map.instance_type() != actual_map.instance_type()
+                    || expected_object.length() != actual_object.length()
+                    || self.is_read_only(expected_object) != other.is_read_only(actual_object)
+                {
+                    return Err(format!(
+                        "{} of length {} became {} of length {}",
+                        expected_map.name(),
+                        expected_object.length(),
+                        actual_map.name(),
+                        actual_object.length()
+                    ));
+                }
+                if expected_map.body_kind() == BodyKind::Data {
+                    if expected_object.data() != actual_object.data() {
+                        return Err(format!("contents of {} differ", expected_map.name()));
+                    }
+                    continue;
+                }
+                for index in 0..expected_object.length() {
+                    worklist.push_back((expected_object.get(index), actual_object.get(index)));
+                }
+            }
+            Ok(())
         }
     }
-}
 
-#[allow(dead_code)]
-mod internal {
-    use super::*;
-    use std::slice;
+    /// The snapshot blob starts with a header, followed by the startup,
+    /// read-only, shared heap and context sections:
+    ///
+    ///   [0] number of contexts N
+    ///   [1] rehashability
+    ///   [2] checksum
+    ///   [3] read-only snapshot checksum
+    ///   [4] (64 bytes) version string
+    ///   [5] offset to read-only
+    ///   [6] offset to shared heap
+    ///   [7] offset to context 0
+    ///   [8] offset to context 1
+    ///   ...
+    ///   ... offset to context N - 1
+    ///   ... startup snapshot data
+    ///   ... read-only snapshot data
+    ///   ... shared heap snapshot data
+    ///   ... context 0 snapshot data
+    ///   ... context 1 snapshot data
+    struct SnapshotImpl {}
 
     const K_UINT32_SIZE: u32 = 4;
-    const K_INT32_SIZE: u32 = 4;
-    const POINTER_SIZE: u32 = 8; // Assuming 64-bit architecture, adjust if needed
+    const K_POINTER_SIZE: u32 = 8;
 
     fn pointer_size_align(size: u32) -> u32 {
-        (size + POINTER_SIZE - 1) & !(POINTER_SIZE - 1)
+        (size + K_POINTER_SIZE - 1) & !(K_POINTER_SIZE - 1)
     }
 
-    struct SnapshotImpl {}
-
     impl SnapshotImpl {
-        #[allow(dead_code)]
+        const K_NUMBER_OF_CONTEXTS_OFFSET: u32 = 0;
+        const K_REHASHABILITY_OFFSET: u32 = Self::K_NUMBER_OF_CONTEXTS_OFFSET + K_UINT32_SIZE;
+        const K_CHECKSUM_OFFSET: u32 = Self::K_REHASHABILITY_OFFSET + K_UINT32_SIZE;
+        const K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET: u32 = Self::K_CHECKSUM_OFFSET + K_UINT32_SIZE;
+        const K_VERSION_STRING_OFFSET: u32 = Self::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET + K_UINT32_SIZE;
+        const K_VERSION_STRING_LENGTH: u32 = 64;
+        const K_READ_ONLY_OFFSET_OFFSET: u32 = Self::K_VERSION_STRING_OFFSET + Self::K_VERSION_STRING_LENGTH;
+        const K_SHARED_HEAP_OFFSET_OFFSET: u32 = Self::K_READ_ONLY_OFFSET_OFFSET + K_UINT32_SIZE;
+        const K_FIRST_CONTEXT_OFFSET_OFFSET: u32 = Self::K_SHARED_HEAP_OFFSET_OFFSET + K_UINT32_SIZE;
+        // Everything after the checksums is covered by the checksum.
+        const K_CHECKSUMMED_CONTENT_OFFSET: u32 = Self::K_VERSION_STRING_OFFSET;
+
         fn create_snapshot_blob(
-            startup_snapshot_in: v8::StartupData,
-            read_only_snapshot_in: v8::StartupData,
-            shared_heap_snapshot_in: v8::StartupData,
-            context_snapshots_in: Vec<v8::StartupData>,
+            startup_snapshot: &SnapshotData,
+            read_only_snapshot: &SnapshotData,
+            shared_heap_snapshot: &SnapshotData,
+            context_snapshots: &[SnapshotData],
             can_be_rehashed: bool,
-        ) -> v8::StartupData {
-            let num_contexts = context_snapshots_in.len() as u32;
-            let startup_snapshot_offset = SnapshotImpl::startup_snapshot_offset(num_contexts as i32);
-            let mut total_length: u32 = startup_snapshot_offset;
-    
-            total_length += read_only_snapshot_in.raw_size as u32;
-    
-            let mut data: Vec<u8> = vec![0; total_length as usize];
-    
-            SnapshotImpl::set_header_value(
-                data.as_mut_ptr() as *mut i8,
-                SnapshotImpl::k_number_of_contexts_offset,
-                num_contexts,
-            );
-            SnapshotImpl::set_header_value(
-                data.as_mut_ptr() as *mut i8,
-                SnapshotImpl::k_rehashability_offset,
-                if can_be_rehashed { 1 } else { 0 },
+        ) -> StartupData {
+            let num_contexts = context_snapshots.len() as u32;
+            let startup_snapshot_offset = Self::startup_snapshot_offset(num_contexts);
+            let mut total_length = startup_snapshot_offset as usize;
+            total_length += startup_snapshot.raw_data().len();
+            total_length += read_only_snapshot.raw_data().len();
+            total_length += shared_heap_snapshot.raw_data().len();
+            total_length += context_snapshots.iter().map(|context| context.raw_data().len()).sum::<usize>();
+
+            let mut data = vec![0u8; startup_snapshot_offset as usize];
+            data.reserve(total_length - data.len());
+            Self::set_header_value(&mut data, Self::K_NUMBER_OF_CONTEXTS_OFFSET, num_contexts);
+            Self::set_header_value(&mut data, Self::K_REHASHABILITY_OFFSET, can_be_rehashed as u32);
+            Self::set_header_value(
+                &mut data,
+                Self::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET,
+                checksum(read_only_snapshot.raw_data()),
             );
-    
-            let result = v8::StartupData {
-                data: data.as_ptr() as *const i8,
-                raw_size: total_length as i32,
+
+            // The version string is null-terminated and zero-padded.
+            let version = Self::version_string();
+            let version_offset = Self::K_VERSION_STRING_OFFSET as usize;
+            data[version_offset..version_offset + version.len()].copy_from_slice(&version);
+
+            let append_section = |data: &mut Vec<u8>, offset_offset: Option<u32>, section: &SnapshotData| {
+                if let Some(offset_offset) = offset_offset {
+                    let offset = data.len() as u32;
+                    Self::set_header_value(data, offset_offset, offset);
+                }
+                data.extend_from_slice(section.raw_data());
             };
-            v8::StartupData{data: ptr::null(), raw_size: 0}
+            append_section(&mut data, None, startup_snapshot);
+            append_section(&mut data, Some(Self::K_READ_ONLY_OFFSET_OFFSET), read_only_snapshot);
+            append_section(&mut data, Some(Self::K_SHARED_HEAP_OFFSET_OFFSET), shared_heap_snapshot);
+            for (i, context) in context_snapshots.iter().enumerate() {
+                append_section(&mut data, Some(Self::context_snapshot_offset_offset(i as u32)), context);
+            }
+            debug_assert_eq!(data.len(), total_length);
+
+            let content_checksum = checksum(&data[Self::K_CHECKSUMMED_CONTENT_OFFSET as usize..]);
+            Self::set_header_value(&mut data, Self::K_CHECKSUM_OFFSET, content_checksum);
+            StartupData::new(data)
         }
 
-        #[allow(dead_code)]
-        fn extract_num_contexts(data: &v8::StartupData) -> u32 {
-            SnapshotImpl::get_header_value(data, SnapshotImpl::k_number_of_contexts_offset)
+        fn version_string() -> [u8; Self::K_VERSION_STRING_LENGTH as usize] {
+            let mut result = [0u8; Self::K_VERSION_STRING_LENGTH as usize];
+            let version = get_version_static().get_string();
+            let length = version.len().min(result.len() - 1);
+            result[..length].copy_from_slice(&version.as_bytes()[..length]);
+            result
         }
 
-        #[allow(dead_code)]
-        fn get_header_value(data: &v8::StartupData, offset: u32) -> u32 {
-            assert!(!data.data.is_null());
-            assert!(offset < data.raw_size as u32);
-            unsafe {
-                base::read_little_endian_value::<u32>(data.data as usize + offset as usize)
-            }
+        fn get_header_value(data: &StartupData, offset: u32) -> u32 {
+            let offset = offset as usize;
+            assert!(offset + K_UINT32_SIZE as usize <= data.raw_size(), "Snapshot blob is truncated");
+            LittleEndian::read_u32(&data.data[offset..])
+        }
+
+        fn set_header_value(data: &mut [u8], offset: u32, value: u32) {
+            LittleEndian::write_u32(&mut data[offset as usize..], value);
         }
-    
-        #[allow(dead_code)]
-        fn set_header_value(data: *mut i8, offset: u32, value: u32) {
-            unsafe {
-                base::write_little_endian_value::<u32>(data as usize + offset as usize, value);
+
+        fn startup_snapshot_offset(num_contexts: u32) -> u32 {
+            pointer_size_align(Self::K_FIRST_CONTEXT_OFFSET_OFFSET + num_contexts * K_UINT32_SIZE)
+        }
+
+        fn context_snapshot_offset_offset(index: u32) -> u32 {
+            Self::K_FIRST_CONTEXT_OFFSET_OFFSET + index * K_UINT32_SIZE
+        }
+
+        fn extract_num_contexts(data: &StartupData) -> u32 {
+            Self::get_header_value(data, Self::K_NUMBER_OF_CONTEXTS_OFFSET)
+        }
+
+        fn checksummed_content(data: &StartupData) -> &[u8] {
+            &data.data[Self::K_CHECKSUMMED_CONTENT_OFFSET as usize..]
+        }
+
+        fn extract_raw_data(snapshot: &StartupData, start_offset: u32, end_offset: u32) -> &[u8] {
+            assert!(start_offset < end_offset, "Snapshot section is empty");
+            assert!(end_offset as usize <= snapshot.raw_size(), "Snapshot blob is truncated");
+            &snapshot.data[start_offset as usize..end_offset as usize]
+        }
+
+        fn extract_data(snapshot: &StartupData, start_offset: u32, end_offset: u32) -> SnapshotData {
+            Self::section_data(Self::extract_raw_data(snapshot, start_offset, end_offset))
+        }
+
+        /// Sections are stored either as is or compressed by
+        /// SnapshotCompression. The blob checksum has been verified before
+        /// sections are read, so a malformed section is a fatal error.
+        fn section_data(raw_data: &[u8]) -> SnapshotData {
+            Self::checked_section_data(raw_data).unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        fn checked_section_data(raw_data: &[u8]) -> Result<SnapshotData, String> {
+            let data = if is_compressed(raw_data) {
+                SnapshotData::checked_from_snapshot(SnapshotCompression::decompress(raw_data)?.raw_data())
+            } else {
+                SnapshotData::checked_from_snapshot(raw_data)
+            };
+            data.ok_or_else(|| "Snapshot data failed the sanity check".to_string())
+        }
+
+        fn extract_sections(data: &StartupData) -> SnapshotSections<'_> {
+            let num_contexts = Self::extract_num_contexts(data);
+            let read_only_offset = Self::get_header_value(data, Self::K_READ_ONLY_OFFSET_OFFSET);
+            let shared_heap_offset = Self::get_header_value(data, Self::K_SHARED_HEAP_OFFSET_OFFSET);
+            let mut context_offsets: Vec<u32> = (0..num_contexts)
+                .map(|index| Self::get_header_value(data, Self::context_snapshot_offset_offset(index)))
+                .collect();
+            context_offsets.push(data.raw_size() as u32);
+            SnapshotSections {
+                startup: Self::extract_raw_data(data, Self::startup_snapshot_offset(num_contexts), read_only_offset),
+                read_only: Self::extract_raw_data(data, read_only_offset, shared_heap_offset),
+                shared_heap: Self::extract_raw_data(data, shared_heap_offset, context_offsets[0]),
+                contexts: context_offsets
+                    .windows(2)
+                    .map(|bounds| Self::extract_raw_data(data, bounds[0], bounds[1]))
+                    .collect(),
             }
         }
-    
-        #[allow(dead_code)]
-        fn check_version(data: &v8::StartupData) {
-            // Placeholder for version check logic
+
+        /// Rebuilds |data| with every section passed through |transform|.
+        fn transform_sections(data: &StartupData, transform: impl Fn(&[u8]) -> SnapshotData) -> StartupData {
+            let sections = Self::extract_sections(data);
+            let contexts: Vec<SnapshotData> = sections.contexts.iter().map(|context| transform(context)).collect();
+            Self::create_snapshot_blob(
+                &transform(sections.startup),
+                &transform(sections.read_only),
+                &transform(sections.shared_heap),
+                &contexts,
+                Snapshot::extract_rehashability(data),
+            )
         }
 
-        const K_NUMBER_OF_CONTEXTS_OFFSET: u32 = 0;
-        const K_REHASHABILITY_OFFSET: u32 = SnapshotImpl::K_NUMBER_OF_CONTEXTS_OFFSET + K_UINT32_SIZE;
-        const K_CHECKSUM_OFFSET: u32 = SnapshotImpl::K_REHASHABILITY_OFFSET + K_UINT32_SIZE;
-        const K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET: u32 =
-            SnapshotImpl::K_CHECKSUM_OFFSET + K_UINT32_SIZE;
-        const K_VERSION_STRING_OFFSET: u32 =
-            SnapshotImpl::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET + K_UINT32_SIZE;
-        const K_VERSION_STRING_LENGTH: u32 = 64;
-        const K_READ_ONLY_OFFSET_OFFSET: u32 =
-            SnapshotImpl::K_VERSION_STRING_OFFSET + SnapshotImpl::K_VERSION_STRING_LENGTH;
-        const K_SHARED_HEAP_OFFSET_OFFSET: u32 =
-            SnapshotImpl::K_READ_ONLY_OFFSET_OFFSET + K_UINT32_SIZE;
-        const K_FIRST_CONTEXT_OFFSET_OFFSET: u32 =
-            SnapshotImpl::K_SHARED_HEAP_OFFSET_OFFSET + K_UINT32_SIZE;
+        fn extract_startup_data(data: &StartupData) -> SnapshotData {
+            let num_contexts = Self::extract_num_contexts(data);
+            Self::extract_data(
+                data,
+                Self::startup_snapshot_offset(num_contexts),
+                Self::get_header_value(data, Self::K_READ_ONLY_OFFSET_OFFSET),
+            )
+        }
 
-        #[allow(dead_code)]
-        fn checksummed_content(data: &v8::StartupData) -> base::Vector<u8> {
-            // Placeholder for checksum calculation
-            base::Vector::new()
+        fn extract_read_only_data(data: &StartupData) -> SnapshotData {
+            Self::extract_data(
+                data,
+                Self::get_header_value(data, Self::K_READ_ONLY_OFFSET_OFFSET),
+                Self::get_header_value(data, Self::K_SHARED_HEAP_OFFSET_OFFSET),
+            )
         }
 
-        #[allow(dead_code)]
-        fn startup_snapshot_offset(num_contexts: i32) -> u32 {
-            pointer_size_align(SnapshotImpl::K_FIRST_CONTEXT_OFFSET_OFFSET + num_contexts as u32 * K_INT32_SIZE)
+        fn extract_shared_heap_data(data: &StartupData) -> SnapshotData {
+            Self::extract_data(
+                data,
+                Self::get_header_value(data, Self::K_SHARED_HEAP_OFFSET_OFFSET),
+                Self::get_header_value(data, Self::context_snapshot_offset_offset(0)),
+            )
         }
 
-        #[allow(dead_code)]
-        fn context_snapshot_offset_offset(index: i32) -> u32 {
-            SnapshotImpl::K_FIRST_CONTEXT_OFFSET_OFFSET + index as u32 * K_INT32_SIZE
+        fn extract_context_data(data: &StartupData, index: u32) -> SnapshotData {
+            let num_contexts = Self::extract_num_contexts(data);
+            assert!(index < num_contexts, "Context index {} out of range", index);
+            let start = Self::get_header_value(data, Self::context_snapshot_offset_offset(index));
+            let end = if index + 1 < num_contexts {
+                Self::get_header_value(data, Self::context_snapshot_offset_offset(index + 1))
+            } else {
+                data.raw_size() as u32
+            };
+            Self::extract_data(data, start, end)
         }
 
-        fn verify_checksum(data: &v8::StartupData) -> bool {
-            // Placeholder for checksum verification logic
-            true
+        fn check_version(data: &StartupData) {
+            let offset = Self::K_VERSION_STRING_OFFSET as usize;
+            let length = Self::K_VERSION_STRING_LENGTH as usize;
+            assert!(data.raw_size() >= offset + length, "Snapshot blob is truncated");
+            let version = Self::version_string();
+            let snapshot_version = &data.data[offset..offset + length];
+            if snapshot_version != version {
+                let printable = |bytes: &[u8]| {
+                    String::from_utf8_lossy(bytes.split(|&b| b == 0).next().unwrap_or(&[])).into_owned()
+                };
+                panic!(
+                    "Version mismatch between V8 binary and snapshot.\n#   V8 binary version: {}\n#    Snapshot version: {}\n# The snapshot consists of {} bytes and contains {} context(s).",
+                    printable(&version),
+                    printable(snapshot_version),
+                    data.raw_size(),
+                    Self::extract_num_contexts(data)
+                );
+            }
+        }
+    }
+
+    pub struct Snapshot {}
+
+    /// The sections of a snapshot blob as stored in it, i.e. possibly
+    /// compressed.
+    #[derive(Debug, Clone)]
+    pub struct SnapshotSections<'a> {
+        pub startup: &'a [u8],
+        pub read_only: &'a [u8],
+        pub shared_heap: &'a [u8],
+        pub contexts: Vec<&'a [u8]>,
+    }
+
+
+    impl Snapshot {
+        /// Serializes the heap of |isolate| with the given contexts into a
+        /// snapshot blob. The first context becomes the default context.
+        pub fn create(isolate: &Isolate, contexts: &[HeapObject], _flags: SerializerFlags) -> StartupData {
+            assert!(!contexts.is_empty(), "A snapshot needs at least one context");
+
+            let mut read_only_serializer = ReadOnlySerializer::new();
+            read_only_serializer.serialize(isolate);
+
+            let mut shared_heap_serializer = SharedHeapSerializer::new(isolate, &read_only_serializer);
+            let mut startup_serializer = StartupSerializer::new(isolate, &read_only_serializer);
+            startup_serializer.serialize_strong_references(&mut shared_heap_serializer);
+
+            let mut can_be_rehashed = true;
+            let mut context_snapshots = Vec::with_capacity(contexts.len());
+            for (&context, context_objects) in contexts.iter().zip(Self::compute_context_objects(isolate, contexts)) {
+                let mut context_serializer = ContextSerializer::new(context_objects);
+                context_serializer.serialize(
+                    isolate,
+                    context,
+                    &read_only_serializer,
+                    &mut startup_serializer,
+                    &mut shared_heap_serializer,
+                );
+                can_be_rehashed &= context_serializer.can_be_rehashed();
+                context_snapshots.push(SnapshotData::new(&context_serializer.into_payload()));
+            }
+
+            startup_serializer.serialize_weak_references_and_deferred(&mut shared_heap_serializer);
+            let startup_snapshot = SnapshotData::new(&startup_serializer.into_payload());
+            let shared_heap_snapshot = SnapshotData::new(&shared_heap_serializer.finalize_serialization());
+            let read_only_snapshot = SnapshotData::new(read_only_serializer.payload());
+
+            SnapshotImpl::create_snapshot_blob(
+                &startup_snapshot,
+                &read_only_snapshot,
+                &shared_heap_snapshot,
+                &context_snapshots,
+                can_be_rehashed,
+            )
+        }
+
+        /// For each context, the objects that only that context reaches.
+        /// Everything reachable from the strong roots or from more than one
+        /// context belongs to the startup snapshot.
+        fn compute_context_objects(isolate: &Isolate, contexts: &[HeapObject]) -> Vec<HashSet<HeapObject>> {
+            let reachable = |roots: Vec<Tagged>| -> HashSet<HeapObject> {
+                let mut visited = HashSet::new();
+                let mut worklist: VecDeque<HeapObject> = roots.into_iter().filter_map(Tagged::get_heap_object).collect();
+                while let Some(object) = worklist.pop_front() {
+                    if isolate.is_read_only(object) || isolate.is_shared(object) || !visited.insert(object) {
+                        continue;
+                    }
+                    if object.map().body_kind() != BodyKind::Data {
+                        worklist.extend((0..object.length()).filter_map(|index| object.get(index).get_heap_object()));
+                    }
+                }
+                visited
+            };
+
+            let from_roots = reachable(isolate.strong_roots());
+            let per_context: Vec<HashSet<HeapObject>> =
+                contexts.iter().map(|&context| reachable(vec![Tagged::strong(context)])).collect();
+            (0..per_context.len())
+                .map(|i| {
+                    per_context[i]
+                        .iter()
+                        .copied()
+                        .filter(|object| {
+                            !from_roots.contains(object)
+                                && per_context.iter().enumerate().all(|(j, other)| i == j || !other.contains(object))
+                        })
+                        .collect()
+                })
+                .collect()
         }
 
-        fn extract_startup_data(data: &v8::StartupData) -> base::Vector<u8> {
-            let num_contexts = SnapshotImpl::extract_num_contexts(data);
-            SnapshotImpl::extract_data(data, SnapshotImpl::startup_snapshot_offset(num_contexts as i32), SnapshotImpl::get_header_value(data, SnapshotImpl::K_READ_ONLY_OFFSET_OFFSET))
+        /// Sets up |isolate| from its snapshot blob. Returns false if the
+        /// isolate has no snapshot.
+        pub fn initialize(isolate: &mut Isolate) -> bool {
+            let blob = match isolate.snapshot_blob_.take() {
+                Some(blob) if !blob.is_empty() => blob,
+                _ => return false,
+            };
+            assert!(!isolate.is_initialized(), "Isolate is already initialized");
+
+            SnapshotImpl::check_version(&blob);
+            if Self::should_verify_checksum(&blob) {
+                assert!(Self::verify_checksum(&blob), "Snapshot checksum mismatch");
+            }
+
+            let read_only_data = SnapshotImpl::extract_read_only_data(&blob);
+            let shared_heap_data = SnapshotImpl::extract_shared_heap_data(&blob);
+            let startup_data = SnapshotImpl::extract_startup_data(&blob);
+
+            let (read_only_objects, read_only_roots) =
+                ReadOnlyDeserializer::new(&read_only_data).deserialize_into_isolate(isolate.heap());
+            isolate.set_read_only_heap(&read_only_objects, &read_only_roots);
+
+            let shared_heap_object_cache = SharedHeapDeserializer::new(&shared_heap_data).deserialize_into_isolate(
+                isolate.heap(),
+                &read_only_objects,
+                &read_only_roots,
+            );
+            let (strong_roots, startup_object_cache) = StartupDeserializer::new(&startup_data).deserialize_into_isolate(
+                isolate.heap(),
+                &read_only_objects,
+                &read_only_roots,
+                &shared_heap_object_cache,
+            );
+            let as_values = |objects: &[HeapObject]| -> Vec<Tagged> { objects.iter().map(|&o| Tagged::strong(o)).collect() };
+            isolate.set_root_list(isolate.shared_heap_object_cache_, &as_values(&shared_heap_object_cache));
+            isolate.set_root_list(isolate.startup_object_cache_, &as_values(&startup_object_cache));
+            isolate.set_root_list(isolate.strong_roots_, &strong_roots);
+
+            isolate.snapshot_blob_ = Some(blob);
+            isolate.initialized_from_snapshot_ = true;
+            true
         }
 
-        fn extract_read_only_data(data: &v8::StartupData) -> base::Vector<u8> {
-            SnapshotImpl::extract_data(data, SnapshotImpl::get_header_value(data, SnapshotImpl::K_READ_ONLY_OFFSET_OFFSET), SnapshotImpl::get_header_value(data, SnapshotImpl::K_SHARED_HEAP_OFFSET_OFFSET))
+        /// Deserializes a new copy of context |context_index| from the
+        /// isolate's snapshot and adds it to the isolate's contexts.
+        pub fn new_context_from_snapshot(isolate: &mut Isolate, context_index: usize) -> Option<HeapObject> {
+            if !isolate.initialized_from_snapshot_ {
+                return None;
+            }
+            let blob = isolate.snapshot_blob_.as_ref()?;
+            if context_index >= SnapshotImpl::extract_num_contexts(blob) as usize {
+                return None;
+            }
+            let context_data = SnapshotImpl::extract_context_data(blob, context_index as u32);
+            let read_only_objects = isolate.read_only_objects();
+            let roots = isolate.read_only_roots();
+            let startup_object_cache = isolate.startup_object_cache();
+            let shared_heap_object_cache = isolate.shared_heap_object_cache();
+            let external = ExternalObjects {
+                read_only_objects: &read_only_objects,
+                roots: &roots,
+                startup_object_cache: &startup_object_cache,
+                shared_heap_object_cache: &shared_heap_object_cache,
+                attached_objects: &[],
+            };
+            let context = ContextDeserializer::new(&context_data).deserialize_context(isolate.heap(), &external);
+            isolate.add_context(context);
+            Some(context)
         }
 
-        fn extract_shared_heap_data(data: &v8::StartupData) -> base::Vector<u8> {
-            SnapshotImpl::extract_data(data, SnapshotImpl::get_header_value(data, SnapshotImpl::K_SHARED_HEAP_OFFSET_OFFSET), SnapshotImpl::get_header_value(data, SnapshotImpl::context_snapshot_offset_offset(0)))
+        pub fn should_verify_checksum(_data: &StartupData) -> bool {
+            cfg!(debug_assertions)
         }
-    
-        fn extract_data(snapshot: &v8::StartupData, start_offset: u32, end_offset: u32) -> base::Vector<u8> {
-            assert!(start_offset < end_offset);
-            assert!(end_offset <= snapshot.raw_size as u32);
-    
-            let length = (end_offset - start_offset) as usize;
-            if snapshot.data.is_null() {
-                return Vec::new();
+
+        pub fn verify_checksum(data: &StartupData) -> bool {
+            if data.raw_size() < SnapshotImpl::K_FIRST_CONTEXT_OFFSET_OFFSET as usize {
+                return false;
+            }
+            let expected = SnapshotImpl::get_header_value(data, SnapshotImpl::K_CHECKSUM_OFFSET);
+            if checksum(SnapshotImpl::checksummed_content(data)) != expected {
+                return false;
             }
-    
-            unsafe {
-                let data = snapshot.data.add(start_offset as usize) as *const u8;
-                slice::from_raw_parts(data, length).to_vec()
+            let read_only_offset = SnapshotImpl::get_header_value(data, SnapshotImpl::K_READ_ONLY_OFFSET_OFFSET) as usize;
+            let shared_heap_offset = SnapshotImpl::get_header_value(data, SnapshotImpl::K_SHARED_HEAP_OFFSET_OFFSET) as usize;
+            if read_only_offset > shared_heap_offset || shared_heap_offset > data.raw_size() {
+                return false;
             }
+            let expected_read_only =
+                SnapshotImpl::get_header_value(data, SnapshotImpl::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET);
+            checksum(&data.data[read_only_offset..shared_heap_offset]) == expected_read_only
         }
 
-        pub fn extract_rehashability(data: &v8::StartupData) -> bool {
-            let rehashability =
-                SnapshotImpl::get_header_value(data, SnapshotImpl::k_rehashability_offset);
+        pub fn extract_num_contexts(data: &StartupData) -> u32 {
+            SnapshotImpl::extract_num_contexts(data)
+        }
+
+        pub fn extract_rehashability(data: &StartupData) -> bool {
+            let rehashability = SnapshotImpl::get_header_value(data, SnapshotImpl::K_REHASHABILITY_OFFSET);
             assert!(rehashability == 0 || rehashability == 1);
             rehashability != 0
         }
+
+        /// Checksum of the read-only snapshot |isolate| was set up from, or
+        /// of the one it would produce if it was bootstrapped. Code caches
+        /// are only accepted by isolates with the same read-only heap.
+        pub fn read_only_snapshot_checksum(isolate: &Isolate) -> u32 {
+            match isolate.snapshot_blob() {
+                Some(blob) if isolate.initialized_from_snapshot() => {
+                    SnapshotImpl::get_header_value(blob, SnapshotImpl::K_READ_ONLY_SNAPSHOT_CHECKSUM_OFFSET)
+                }
+                _ => {
+                    let mut read_only_serializer = ReadOnlySerializer::new();
+                    read_only_serializer.serialize(isolate);
+                    checksum(SnapshotData::new(read_only_serializer.payload()).raw_data())
+                }
+            }
+        }
+
+        pub fn extract_sections(data: &StartupData) -> SnapshotSections<'_> {
+            SnapshotImpl::extract_sections(data)
+        }
+
+        /// Decompresses a section of a blob if needed and checks its header.
+        pub fn extract_section_data(raw_data: &[u8]) -> Result<SnapshotData, String> {
+            SnapshotImpl::checked_section_data(raw_data)
+        }
+
+        pub fn is_compressed(data: &StartupData) -> bool {
+            is_compressed(SnapshotImpl::extract_sections(data).startup)
+        }
+
+        /// Returns a copy of |data| with all sections compressed. The
+        /// checksums are recomputed over the compressed sections.
+        pub fn compress(data: &StartupData) -> StartupData {
+            SnapshotImpl::transform_sections(data, |raw_data| {
+                SnapshotCompression::compress(&SnapshotImpl::section_data(raw_data))
+            })
+        }
+
+        /// Returns a copy of |data| with all sections uncompressed.
+        pub fn decompress(data: &StartupData) -> StartupData {
+            SnapshotImpl::transform_sections(data, SnapshotImpl::section_data)
+        }
+
+        /// Returns true if |data| was created by this version of V8.
+        pub fn version_is_valid(data: &StartupData) -> bool {
+            let offset = SnapshotImpl::K_VERSION_STRING_OFFSET as usize;
+            let length = SnapshotImpl::K_VERSION_STRING_LENGTH as usize;
+            data.raw_size() >= offset + length && data.data[offset..offset + length] == SnapshotImpl::version_string()
+        }
+
+        /// Serializes |isolate| with all of its contexts, deserializes the
+        /// result into a fresh isolate and checks that both heaps hold the
+        /// same object graph.
+        pub fn serialize_deserialize_and_verify_for_testing(isolate: &Isolate) -> Result<StartupData, String> {
+            let contexts = isolate.contexts();
+            let blob = Self::create(isolate, &contexts, SerializerFlags::kNoFlags);
+            if !Self::verify_checksum(&blob) {
+                return Err("checksum mismatch".to_string());
+            }
+            let mut new_isolate = Isolate::new();
+            new_isolate.set_snapshot_blob(blob.clone());
+            if !Self::initialize(&mut new_isolate) {
+                return Err("failed to initialize isolate from snapshot".to_string());
+            }
+            for index in 0..contexts.len() {
+                Self::new_context_from_snapshot(&mut new_isolate, index)
+                    .ok_or_else(|| format!("failed to deserialize context {}", index))?;
+            }
+            isolate.verify_equivalent(&new_isolate)?;
+            Ok(blob)
+        }
+    }
+
+    /// Builds snapshot blobs from an isolate that is set up either from
+    /// scratch or from an existing blob.
+    pub struct SnapshotCreator {
+        isolate_: Isolate,
+        default_context_: Option<HeapObject>,
+        contexts_: Vec<HeapObject>,
+    }
+
+    impl Default for SnapshotCreator {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl SnapshotCreator {
+        pub fn new() -> Self {
+            Self::with_params(CreateParams::default())
+        }
+
+        /// Creates the isolate from |existing_blob| instead of bootstrapping.
+        /// No context is deserialized yet; use Snapshot::new_context_from_snapshot.
+        pub fn from_blob(existing_blob: StartupData) -> Self {
+            Self::with_params(CreateParams { snapshot_blob: Some(existing_blob), ..CreateParams::default() })
+        }
+
+        /// Creates the isolate with |params|: from |params.snapshot_blob| if
+        /// given, bootstrapped otherwise.
+        pub fn with_params(params: CreateParams) -> Self {
+            let from_blob = params.snapshot_blob.is_some();
+            let mut isolate = Isolate::with_params(params);
+            if from_blob {
+                assert!(Snapshot::initialize(&mut isolate), "Could not initialize isolate from snapshot");
+            } else {
+                isolate.init_without_snapshot();
+            }
+            SnapshotCreator {
+                isolate_: isolate,
+                default_context_: None,
+                contexts_: Vec::new(),
+            }
+        }
+
+        pub fn get_isolate(&mut self) -> &mut Isolate {
+            &mut self.isolate_
+        }
+
+        /// Sets the context new isolates get by default. Must be called
+        /// exactly once before create_blob.
+        pub fn set_default_context(&mut self, context: HeapObject) {
+            assert!(self.default_context_.is_none(), "Default context already set");
+            self.default_context_ = Some(context);
+        }
+
+        /// Adds an additional context. Returns the index to pass to
+        /// Snapshot::new_context_from_snapshot.
+        pub fn add_context(&mut self, context: HeapObject) -> usize {
+            self.contexts_.push(context);
+            self.contexts_.len()
+        }
+
+        pub fn create_blob(&mut self) -> StartupData {
+            let default_context = self.default_context_.expect("No default context set");
+            let mut contexts = vec![default_context];
+            contexts.extend_from_slice(&self.contexts_);
+            Snapshot::create(&self.isolate_, &contexts, SerializerFlags::kNoFlags)
+        }
     }
-}
\ No newline at end of file
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn bootstrapped_isolate() -> Isolate {
+            let mut isolate = Isolate::new();
+            isolate.init_without_snapshot();
+            isolate
+        }
+
+        #[test]
+        fn test_round_trip_bootstrapped_isolate() {
+            let isolate = bootstrapped_isolate();
+            let blob = Snapshot::serialize_deserialize_and_verify_for_testing(&isolate).unwrap();
+            assert_eq!(Snapshot::extract_num_contexts(&blob), 1);
+            assert!(Snapshot::extract_rehashability(&blob));
+            assert!(Snapshot::version_is_valid(&blob));
+        }
+
+        #[test]
+        fn test_round_trip_with_scripts_and_shared_objects() {
+            let mut isolate = bootstrapped_isolate();
+            let context = isolate.contexts()[0];
+            isolate.add_script(context, "function framework() { return 42; }");
+            let second = isolate.create_native_context();
+            isolate.add_context(second);
+
+            // An object both contexts point to goes through the startup
+            // object cache; a shared object through the shared heap cache.
+            let common = isolate.allocate_fixed_array(&[Tagged::from_smi(7)]);
+            isolate.set_global_property(context, "common", Tagged::strong(common));
+            isolate.set_global_property(second, "common", Tagged::strong(common));
+            let shared = isolate.intern("shared");
+            assert!(isolate.is_shared(shared));
+            isolate.set_global_property(second, "shared", Tagged::strong(shared));
+
+            // A long chain exceeds the recursion limit and is deferred.
+            let mut chain = Tagged::from_smi(0);
+            for i in 0..100 {
+                let link = isolate.allocate_fixed_array(&[chain, Tagged::from_smi(i)]);
+                chain = Tagged::strong(link);
+            }
+            isolate.set_global_property(context, "chain", chain);
+
+            let blob = Snapshot::serialize_deserialize_and_verify_for_testing(&isolate).unwrap();
+            let mut restored = Isolate::new();
+            restored.set_snapshot_blob(blob);
+            assert!(Snapshot::initialize(&mut restored));
+            let first = Snapshot::new_context_from_snapshot(&mut restored, 0).unwrap();
+            let second = Snapshot::new_context_from_snapshot(&mut restored, 1).unwrap();
+            assert_eq!(
+                restored.get_global_property(first, "common"),
+                restored.get_global_property(second, "common")
+            );
+            let shared = restored.get_global_property(second, "shared").unwrap().get_heap_object().unwrap();
+            assert_eq!(restored.intern("shared"), shared);
+            assert!(Snapshot::new_context_from_snapshot(&mut restored, 2).is_none());
+        }
+
+        #[test]
+        fn test_run_script_goes_through_the_embedder_callback() {
+            fn compile_and_run(isolate: &mut Isolate, context: HeapObject, source: &str) -> Result<(), String> {
+                let script = isolate.add_script(context, source);
+                isolate.compile_script(script).map(|_| ())
+            }
+
+            crate::logging::tracing_flags::RUNTIME_STATS.store(1, std::sync::atomic::Ordering::Relaxed);
+            let mut isolate = bootstrapped_isolate();
+            let context = isolate.contexts()[0];
+            assert!(isolate.run_script(context, "f()").is_err());
+            isolate.set_run_script_callback(compile_and_run);
+            isolate.run_script(context, "f()").unwrap();
+            assert_eq!(isolate.scripts().len(), 1);
+
+            let stats = isolate.runtime_call_stats().unwrap().current_thread_table();
+            let stats = stats.lock().unwrap();
+            assert_eq!(stats.get_counter(RuntimeCallCounterId::kJS_Execution).count(), 2);
+            assert_eq!(stats.get_counter(RuntimeCallCounterId::kCompileScript).count(), 1);
+        }
+
+        #[test]
+        fn test_checksum_detects_corruption() {
+            let isolate = bootstrapped_isolate();
+            let contexts = isolate.contexts();
+            let mut blob = Snapshot::create(&isolate, &contexts, SerializerFlags::kNoFlags);
+            assert!(Snapshot::verify_checksum(&blob));
+            let last = blob.raw_size() - 1;
+            blob.data[last] ^= 0xFF;
+            assert!(!Snapshot::verify_checksum(&blob));
+        }
+    }
+}
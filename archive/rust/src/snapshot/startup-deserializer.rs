@@ -2,586 +2,59 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-//use v8::api; // Placeholder for v8::api crate
-//use v8::codegen; // Placeholder for v8::codegen crate
-//use v8::execution; // Placeholder for v8::execution crate
-//use v8::handles; // Placeholder for v8::handles crate
-//use v8::heap; // Placeholder for v8::heap crate
-//use v8::logging; // Placeholder for v8::logging crate
-//use v8::objects; // Placeholder for v8::objects crate
-//use v8::roots; // Placeholder for v8::roots crate
-
-// Placeholder for base::ElapsedTimer, base::EnumSet, base::ElapsedTimer
-use std::time::{Duration, Instant};
-use std::collections::HashSet;
-
-// Placeholder for v8_flags
-const PROFILE_DESERIALIZATION: bool = false; //Example
-
-macro_rules! V8_UNLIKELY {
-    ($condition:expr) => {
-        $condition
-    };
-}
-
-macro_rules! DCHECK_NULL {
-    ($value:expr) => {
-        debug_assert!($value.is_null());
-    };
-}
-
-macro_rules! DCHECK {
-    ($condition:expr) => {
-        debug_assert!($condition);
-    };
-}
-
-macro_rules! CHECK_EQ {
-    ($left:expr, $right:expr) => {
-        assert_eq!($left, $right);
-    };
-}
-
-macro_rules! TRACE_EVENT0 {
-    ($category:expr, $name:expr) => {
-        // Placeholder: Implement trace event logging
-        println!("TRACE_EVENT0: category={}, name={}", $category, $name);
-    };
-}
-
-macro_rules! RCS_SCOPE {
-    ($isolate:expr, $counter_id:expr) => {
-        // Placeholder: Implement runtime call counter scope
-        println!("RCS_SCOPE: isolate={}, counter_id={:?}", $isolate, $counter_id);
-    };
-}
-
-macro_rules! LOG {
-    ($isolate:expr, $log_event:expr) => {
-        // Placeholder: Implement logging
-        println!("LOG: isolate={}, log_event={:?}", $isolate, $log_event);
-    };
-}
-
-macro_rules! PrintF {
-    ($($arg:tt)*) => {
-        println!($($arg)*);
-    };
-}
-
-#[derive(Debug)]
-enum RuntimeCallCounterId {
-    kDeserializeIsolate,
-}
-
-struct NestedTimedHistogramScope<'a> {
-    isolate_counters: &'a Counters,
-    start_time: Instant,
-}
-
-impl<'a> NestedTimedHistogramScope<'a> {
-    fn new(isolate_counters: &'a Counters, ) -> Self {
-        NestedTimedHistogramScope {
-            isolate_counters,
-            start_time: Instant::now(),
-        }
-    }
-}
-
-impl<'a> Drop for NestedTimedHistogramScope<'a> {
-    fn drop(&mut self) {
-        let elapsed = self.start_time.elapsed();
-        //Placeholder: Update the counters with the elapsed time
-        println!("Elapsed time for histogram scope: {:?}", elapsed);
-    }
-}
-
-#[derive(Debug, Default)]
-struct Counters {
-    // Add counter fields here as needed
-}
-
-#[derive(Debug)]
-struct Isolate {
-    thread_manager: ThreadManager,
-    handle_scope_implementer: HandleScopeImplementer,
-    startup_object_cache: StartupObjectCache,
-    builtins: Builtins,
-    heap: Heap,
-    external_reference_table: ExternalReferenceTable,
-    counters: Counters,
-}
-
-impl Isolate {
-    fn new() -> Self {
-        Isolate {
-            thread_manager: ThreadManager::new(),
-            handle_scope_implementer: HandleScopeImplementer::new(),
-            startup_object_cache: StartupObjectCache::new(),
-            builtins: Builtins::new(),
-            heap: Heap::new(),
-            external_reference_table: ExternalReferenceTable::new(),
-            counters: Counters::default(),
-        }
-    }
-    fn thread_manager(&self) -> &ThreadManager {
-        &self.thread_manager
-    }
-    fn handle_scope_implementer(&self) -> &HandleScopeImplementer {
-        &self.handle_scope_implementer
-    }
-    fn startup_object_cache(&self) -> &StartupObjectCache {
-        &self.startup_object_cache
-    }
-    fn builtins(&self) -> &Builtins {
-        &self.builtins
-    }
-    fn heap(&self) -> &Heap {
-        &self.heap
-    }
-    fn external_reference_table(&self) -> &ExternalReferenceTable {
-        &self.external_reference_table
-    }
-    fn counters(&self) -> &Counters {
-        &self.counters
-    }
-}
-
-#[derive(Debug)]
-struct ThreadManager {}
-impl ThreadManager {
-    fn new() -> Self {ThreadManager{}}
-    fn FirstThreadStateInUse(&self) -> Option<usize> { // usize is a placeholder
-        None // or Some(thread_state) if there is a thread state in use
-    }
-}
-
-#[derive(Debug)]
-struct HandleScopeImplementer {
-    blocks: Vec<u32>, // Placeholder for actual block type
-}
-impl HandleScopeImplementer {
-    fn new() -> Self { HandleScopeImplementer { blocks: Vec::new() } }
-
-    fn blocks(&self) -> &Vec<u32> { // Placeholder for actual block type
-        &self.blocks
-    }
-}
-
-#[derive(Debug)]
-struct StartupObjectCache {}
-
-impl StartupObjectCache {
-    fn new() -> Self {StartupObjectCache{}}
-    fn empty(&self) -> bool {
-        true //Placeholder
-    }
-}
-
-#[derive(Debug)]
-struct Builtins {
-    initialized: bool,
-}
-
-impl Builtins {
-    fn new() -> Self {
-        Builtins { initialized: false }
-    }
-    fn is_initialized(&self) -> bool {
-        self.initialized
-    }
-    fn MarkInitialized(&mut self) {
-        self.initialized = true;
-    }
-}
-
-#[derive(Debug)]
-struct Heap {
-    native_contexts_list: u32, // Placeholder: type needs to be defined
-    allocation_sites_list: u32, // Placeholder: type needs to be defined
-    dirty_js_finalization_registries_list: u32,
-    dirty_js_finalization_registries_list_tail: u32,
-    code_space: CodeSpace,
-}
-
-impl Heap {
-    fn new() -> Self {
-        Heap {
-            native_contexts_list: 0, // Placeholder: initialization
-            allocation_sites_list: 0, // Placeholder: initialization
-            dirty_js_finalization_registries_list: 0,
-            dirty_js_finalization_registries_list_tail: 0,
-            code_space: CodeSpace::new(),
-        }
-    }
-    fn IterateSmiRoots(&self, _deserializer: &StartupDeserializer) {
-        // Placeholder implementation
-    }
-    fn IterateRoots(&self, _deserializer: &StartupDeserializer, _skip_root: EnumSet<SkipRoot>) {
-        // Placeholder implementation
-    }
-    fn IterateWeakRoots(&self, _deserializer: &StartupDeserializer, _skip_root: EnumSet<SkipRoot>) {
-        // Placeholder implementation
-    }
-    fn set_native_contexts_list(&mut self, value: u32) { // Placeholder type u32
-        self.native_contexts_list = value;
-    }
-    fn set_allocation_sites_list(&mut self, value: u32) { // Placeholder type u32
-        self.allocation_sites_list = value;
-    }
-    fn set_dirty_js_finalization_registries_list(&mut self, value: u32) {
-        self.dirty_js_finalization_registries_list = value;
-    }
-    fn set_dirty_js_finalization_registries_list_tail(&mut self, value: u32) {
-        self.dirty_js_finalization_registries_list_tail = value;
-    }
-
-    fn allocation_sites_list(&self) -> u32 {
-        self.allocation_sites_list
-    }
-    fn code_space(&self) -> &CodeSpace {
-        &self.code_space
-    }
-}
-
-#[derive(Debug)]
-struct CodeSpace {
-    pages: Vec<PageMetadata>,
-}
-
-impl CodeSpace {
-    fn new() -> Self {
-        CodeSpace {
-            pages: Vec::new(),
-        }
-    }
-}
-
-impl<'a> IntoIterator for &'a CodeSpace {
-    type Item = &'a PageMetadata;
-    type IntoIter = std::slice::Iter<'a, PageMetadata>;
-
-    fn into_iter(self) -> Self::IntoIter {
-        self.pages.iter()
-    }
-}
-
-#[derive(Debug)]
-struct PageMetadata {
-    area_start: usize,
-    area_end: usize,
-}
-
-impl PageMetadata {
-    fn new(start: usize, end: usize) -> Self {
-        PageMetadata {
-            area_start: start,
-            area_end: end,
-        }
-    }
-
-    fn area_start(&self) -> usize {
-        self.area_start
-    }
-
-    fn area_end(&self) -> usize {
-        self.area_end
-    }
-}
-
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
-enum SkipRoot {
-    kUnserializable,
-    kWeak,
-    kTracedHandles,
-}
-
-#[derive(Debug)]
-struct EnumSet<T> {
-    set: HashSet<T>,
-}
-
-impl<T> EnumSet<T> {
-    fn new(set: HashSet<T>) -> Self {
-        EnumSet { set }
-    }
-}
-
-impl<T: Eq + std::hash::Hash> From<Vec<T>> for EnumSet<T> {
-    fn from(vec: Vec<T>) -> Self {
-        let mut set = HashSet::new();
-        for item in vec {
-            set.insert(item);
-        }
-        EnumSet { set }
-    }
-}
-
-#[derive(Debug)]
-struct ExternalReferenceTable {}
-
-impl ExternalReferenceTable {
-    const kSizeIsolateIndependent: u32 = 0xFFFFFFFF;
-
-    fn new() -> Self {
-        ExternalReferenceTable {}
-    }
-
-    fn address(&self, _index: u32) -> u32 {
-        0 // Placeholder: Implement address lookup
-    }
-}
-
-#[derive(Debug)]
-struct ReadOnlyRoots {
-    undefined_value: u32, //Placeholder
-}
-
-impl ReadOnlyRoots {
-    fn new(_isolate: &Isolate) -> Self {
-        ReadOnlyRoots {
-            undefined_value: 0 // Placeholder: initialization
-        }
-    }
-
-    fn undefined_value(&self) -> u32 {
-        self.undefined_value
-    }
-}
-
-#[derive(Debug)]
-struct Smi {}
-
-impl Smi {
-    fn zero() -> u32 { // Placeholder for Smi type
-        0 // Placeholder
-    }
-}
-
-#[derive(Debug)]
-struct DirectHandle<T> {
-    info: T, //Placeholder
-}
-
-#[derive(Debug)]
-struct AccessorInfo {}
-
-#[derive(Debug)]
-struct FunctionTemplateInfo {}
-
-struct Source {
-    data: Vec<u8>,
-    index: usize,
-}
-
-impl Source {
-    fn new(data: Vec<u8>) -> Self {
-        Source { data, index: 0 }
-    }
-
-    fn GetUint30(&mut self) -> u32 {
-        //Simplified implementation, assumes enough data is available
-        let mut result: u32 = 0;
-        result |= (self.data[self.index] as u32) << 0;
-        self.index += 1;
-        result |= (self.data[self.index] as u32) << 8;
-        self.index += 1;
-        result |= (self.data[self.index] as u32) << 16;
-        self.index += 1;
-        result |= (self.data[self.index] as u32) << 24;
-        self.index += 1;
-
-        result
-    }
-
-    fn length(&self) -> usize {
-        self.data.len()
-    }
-}
-
-struct HandleScope<'a> {
-    isolate: &'a Isolate,
-}
-
-impl<'a> HandleScope<'a> {
-    fn new(isolate: &'a Isolate) -> Self {
-        HandleScope { isolate }
-    }
-}
-
-impl<'a> Drop for HandleScope<'a> {
-    fn drop(&mut self) {
-        // Placeholder: Handle scope cleanup
-        println!("Dropping HandleScope");
-    }
-}
-
-pub struct StartupDeserializer {
-    isolate_: Isolate, //Mutable to simulate isolate() calls
-    source_: Source,
-    accessor_infos_: Vec<DirectHandle<AccessorInfo>>,
-    function_template_infos_: Vec<DirectHandle<FunctionTemplateInfo>>,
-    deserializing_user_code_: bool,
-    should_rehash_: bool,
-}
-
-impl StartupDeserializer {
-    pub fn new(isolate: Isolate, source: Source) -> Self {
-        StartupDeserializer {
-            isolate_: isolate,
-            source_: source,
-            accessor_infos_: Vec::new(),
-            function_template_infos_: Vec::new(),
-            deserializing_user_code_: false,
-            should_rehash_: false,
-        }
-    }
-
-    fn isolate(&mut self) -> &mut Isolate {
-        &mut self.isolate_
-    }
-    fn source(&mut self) -> &mut Source {
-        &mut self.source_
-    }
-    fn accessor_infos(&mut self) -> &mut Vec<DirectHandle<AccessorInfo>> {
-        &mut self.accessor_infos_
-    }
-    fn function_template_infos(&mut self) -> &mut Vec<DirectHandle<FunctionTemplateInfo>> {
-        &mut self.function_template_infos_
-    }
-    fn deserializing_user_code(&self) -> bool {
-        self.deserializing_user_code_
-    }
-    fn should_rehash(&self) -> bool {
-        self.should_rehash_
-    }
-    fn set_should_rehash(&mut self, should_rehash: bool) {
-        self.should_rehash_ = should_rehash;
-    }
-
-    pub fn DeserializeIntoIsolate(&mut self) {
-        TRACE_EVENT0!("v8", "V8.DeserializeIsolate");
-        RCS_SCOPE!(self.isolate(), RuntimeCallCounterId::kDeserializeIsolate);
-
-        let timer = if V8_UNLIKELY!(PROFILE_DESERIALIZATION) {
-             Some(Instant::now())
-        } else {
-            None
-        };
-
-        let histogram_timer = NestedTimedHistogramScope::new(self.isolate().counters());
-        let scope = HandleScope::new(self.isolate());
-
-        // No active threads.
-        DCHECK_NULL!(self.isolate().thread_manager().FirstThreadStateInUse());
-        // No active handles.
-        DCHECK!(self.isolate().handle_scope_implementer().blocks().is_empty());
-        // Startup object cache is not yet populated.
-        DCHECK!(self.isolate().startup_object_cache().empty());
-        // Builtins are not yet created.
-        DCHECK!(!self.isolate().builtins().is_initialized());
-
-        {
-            self.DeserializeAndCheckExternalReferenceTable();
-
-            self.isolate().heap().IterateSmiRoots(self);
-            self.isolate().heap().IterateRoots(
-                self,
-                EnumSet::from(vec![SkipRoot::kUnserializable, SkipRoot::kWeak, SkipRoot::kTracedHandles]),
-            );
-            self.IterateStartupObjectCache(self.isolate(), self);
-
-            self.isolate().heap().IterateWeakRoots(
-                self,
-                EnumSet::from(vec![SkipRoot::kUnserializable]),
-            );
-            self.DeserializeDeferredObjects();
-            for info in self.accessor_infos() {
-                self.RestoreExternalReferenceRedirector(self.isolate(), &info.info);
-            }
-            for info in self.function_template_infos() {
-                self.RestoreExternalReferenceRedirector(self.isolate(), &info.info);
+pub mod startup_deserializer {
+    use crate::heap::heap::Heap;
+    use crate::heap::heap_layout::{HeapObject, Tagged};
+    use crate::snapshot::deserializer::deserializer::{Deserializer, ExternalObjects};
+    use crate::snapshot::snapshot_data::internal::SnapshotData;
+
+    /// Deserializes the startup snapshot written by StartupSerializer.
+    pub struct StartupDeserializer<'a> {
+        deserializer_: Deserializer<'a>,
+    }
+
+    impl<'a> StartupDeserializer<'a> {
+        pub fn new(startup_data: &'a SnapshotData) -> Self {
+            StartupDeserializer { deserializer_: Deserializer::new(startup_data.payload()) }
+        }
+
+        /// Deserializes the strong roots into |heap|. Returns their values
+        /// and the startup object cache that context snapshots refer to.
+        pub fn deserialize_into_isolate(
+            mut self,
+            heap: &Heap,
+            read_only_objects: &[HeapObject],
+            read_only_roots: &[HeapObject],
+            shared_heap_object_cache: &[HeapObject],
+        ) -> (Vec<Tagged>, Vec<HeapObject>) {
+            let external = ExternalObjects {
+                read_only_objects,
+                roots: read_only_roots,
+                startup_object_cache: &[],
+                shared_heap_object_cache,
+                attached_objects: &[],
+            };
+
+            let mut strong_root_count = 0;
+            while !self.deserializer_.at_synchronize() {
+                self.deserializer_.read_root(heap, &external);
+                strong_root_count += 1;
             }
-
-            // Flush the instruction cache for the entire code-space. Must happen after
-            // builtins deserialization.
-            self.FlushICache();
-        }
-
-        self.isolate().heap().set_native_contexts_list(
-            ReadOnlyRoots(self.isolate()).undefined_value(),
-        );
-        // The allocation site list is build during root iteration, but if no sites
-        // were encountered then it needs to be initialized to undefined.
-        if self.isolate().heap().allocation_sites_list() == Smi::zero() {
-            self.isolate().heap().set_allocation_sites_list(
-                ReadOnlyRoots(self.isolate()).undefined_value(),
-            );
-        }
-
-        self.isolate().heap().set_dirty_js_finalization_registries_list(
-            ReadOnlyRoots(self.isolate()).undefined_value(),
-        );
-
-        self.isolate().heap().set_dirty_js_finalization_registries_list_tail(
-            ReadOnlyRoots(self.isolate()).undefined_value(),
-        );
-
-        self.isolate().builtins().MarkInitialized();
-
-        self.LogNewMapEvents();
-        self.WeakenDescriptorArrays();
-
-        if self.should_rehash() {
-            // Hash seed was initialized in ReadOnlyDeserializer.
-            self.Rehash();
-        }
-
-        if let Some(start_time) = timer {
-            let elapsed = start_time.elapsed();
-            // ATTENTION: The Memory.json benchmark greps for this exact output. Do not
-            // change it without also updating Memory.json.
-            let bytes = self.source().length();
-            let ms = elapsed.as_secs_f64() * 1000.0;
-            PrintF!("[Deserializing isolate ({} bytes) took {:.3} ms]\n", bytes, ms);
-        }
-    }
-
-    fn DeserializeAndCheckExternalReferenceTable(&mut self) {
-        // Verify that any external reference entries that were deduplicated in the
-        // serializer are also deduplicated in this isolate.
-        let table = self.isolate().external_reference_table();
-        loop {
-            let index = self.source().GetUint30();
-            if index == ExternalReferenceTable::kSizeIsolateIndependent {
-                break;
+            self.deserializer_.read_synchronize();
+            while !self.deserializer_.at_synchronize() {
+                self.deserializer_.read_root(heap, &external);
             }
-            let encoded_index = self.source().GetUint30();
-            CHECK_EQ!(table.address(index), table.address(encoded_index));
-        }
-    }
+            self.deserializer_.read_synchronize();
+            self.deserializer_.read_deferred_objects(heap, &external);
+            assert!(!self.deserializer_.source().has_more(), "Trailing data in startup snapshot");
 
-    fn LogNewMapEvents(&self) {
-        if true { //Placeholder v8_flags.log_maps
-            self.LogAllMaps();
+            let mut values = self.deserializer_.take_roots();
+            let object_cache = values
+                .split_off(strong_root_count)
+                .into_iter()
+                .map(|value| value.get_heap_object().expect("Startup object cache entry is not an object"))
+                .collect();
+            (values, object_cache)
         }
     }
-
-    fn FlushICache(&self) {
-        DCHECK!(!self.deserializing_user_code());
-        // The entire isolate is newly deserialized. Simply flush all code pages.
-        for p in self.isolate().heap().code_space() {
-            self.FlushInstructionCache(p.area_start(), p.area_end() - p.area_start());
-        }
-    }
-
-    //Placeholder methods
-    fn IterateStartupObjectCache(&self, _isolate: &mut Isolate, _deserializer: &StartupDeserializer) {}
-    fn DeserializeDeferredObjects(&mut self) {}
-    fn RestoreExternalReferenceRedirector(&mut self, _isolate: &mut Isolate, _info: &AccessorInfo) {}
-    fn FlushInstructionCache(&self, _start: usize, _size: usize) {}
-    fn LogAllMaps(&self) {}
-    fn WeakenDescriptorArrays(&self) {}
-    fn Rehash(&mut self) {}
-
-}
\ No newline at end of file
+}
@@ -3,176 +3,113 @@
 // found in the LICENSE file.
 
 pub mod startup_serializer {
-    use std::collections::HashSet;
-    use std::hash::{Hash, Hasher};
-    //use std::rc::Rc;
-    //use std::cell::RefCell;
-
-    //use crate::handles::global_handles::GlobalHandleVector; // Assuming this is in handles module
-    //use crate::snapshot::roots_serializer::RootsSerializer; // Assuming this is in snapshot module
-    //use crate::snapshot::snapshot_byte_sink::SnapshotByteSink; // Assuming this is in snapshot module
-
-    // Placeholder types and enums - Replace with actual implementations.
-    pub struct Isolate {}
-    pub struct SnapshotByteSink {}
-    pub struct HeapObject {}
-    pub struct SharedHeapSerializer {}
-    pub struct Handle<T> {
-        _phantom: std::marker::PhantomData<T>,
-    }
-    pub struct DisallowGarbageCollection {}
-    pub struct AccessorInfo {}
-    pub struct FunctionTemplateInfo {}
-    pub enum SlotType {}
-    pub enum Root {
-        Global,
-        Eternal
-    }
-    pub type FullObjectSlot = *mut HeapObject;
-    pub type Tagged<T> = *mut T;
-    pub type Context = HeapObject;
-    pub type FixedArray = HeapObject;
-    pub type Object = HeapObject;
-    pub struct Snapshot {
-        pub SerializerFlags: i32,
+    use std::collections::HashMap;
+
+    use crate::heap::heap_layout::{HeapObject, Tagged};
+    use crate::snapshot::read_only_serializer::read_only_serializer::ReadOnlySerializer;
+    use crate::snapshot::serializer::serializer::{ReferenceEncoder, Serializer};
+    use crate::snapshot::serializer_deserializer::serializer_deserializer::{ExternalReference, SerializationStatistics};
+    use crate::snapshot::shared_heap_serializer::shared_heap_serializer::SharedHeapSerializer;
+    use crate::snapshot::snapshot::snapshot::Isolate;
+
+    struct StartupEncoder<'a, 'b> {
+        isolate: &'a Isolate,
+        read_only_serializer: &'a ReadOnlySerializer,
+        shared_heap_serializer: &'b mut SharedHeapSerializer<'a>,
     }
 
-    pub trait RootsSerializer {
-        fn SerializeObjectImpl(&mut self, o: Handle<HeapObject>, slot_type: SlotType);
+    impl ReferenceEncoder for StartupEncoder<'_, '_> {
+        fn encode_external(&mut self, object: HeapObject) -> Option<ExternalReference> {
+            if self.isolate.is_read_only(object) {
+                return Some(self.read_only_serializer.encode_reference(object));
+            }
+            self.shared_heap_serializer
+                .serialize_using_shared_heap_object_cache(object)
+                .map(ExternalReference::SharedHeapObjectCache)
+        }
     }
 
+    /// Serializes the isolate's strong roots. Objects that context snapshots
+    /// share with the isolate or with each other are added to the startup
+    /// object cache, which is appended to this section.
+    ///
+    /// Section layout: strong roots, kSynchronize, startup object cache
+    /// entries, kSynchronize, deferred objects, kSynchronize.
     pub struct StartupSerializer<'a> {
-        shared_heap_serializer_: &'a SharedHeapSerializer,
-        accessor_infos_: Vec<Handle<AccessorInfo>>, //GlobalHandleVector<AccessorInfo>,
-        function_template_infos_: Vec<Handle<FunctionTemplateInfo>>, //GlobalHandleVector<FunctionTemplateInfo>,
+        serializer_: Serializer,
+        isolate_: &'a Isolate,
+        read_only_serializer_: &'a ReadOnlySerializer,
+        object_cache_index_: HashMap<HeapObject, u32>,
+        finished_strong_references_: bool,
     }
 
     impl<'a> StartupSerializer<'a> {
-        pub fn new(
-            _isolate: &mut Isolate,
-            _flags: i32, //Snapshot::SerializerFlags,
-            shared_heap_serializer: &'a SharedHeapSerializer,
-        ) -> Self {
+        pub fn new(isolate: &'a Isolate, read_only_serializer: &'a ReadOnlySerializer) -> Self {
             StartupSerializer {
-                shared_heap_serializer_: shared_heap_serializer,
-                accessor_infos_: Vec::new(),
-                function_template_infos_: Vec::new(),
+                serializer_: Serializer::new(),
+                isolate_: isolate,
+                read_only_serializer_: read_only_serializer,
+                object_cache_index_: HashMap::new(),
+                finished_strong_references_: false,
             }
         }
 
-        pub fn serialize_strong_references(&mut self, _no_gc: &DisallowGarbageCollection) {
-            // Implementation goes here
-        }
-
-        pub fn serialize_weak_references_and_deferred(&mut self) {
-            // Implementation goes here
+        fn encoder<'b>(&self, shared: &'b mut SharedHeapSerializer<'a>) -> StartupEncoder<'a, 'b> {
+            StartupEncoder {
+                isolate: self.isolate_,
+                read_only_serializer: self.read_only_serializer_,
+                shared_heap_serializer: shared,
+            }
         }
 
-        pub fn serialize_using_shared_heap_object_cache(
-            &mut self,
-            _sink: &mut SnapshotByteSink,
-            _obj: Handle<HeapObject>,
-        ) -> bool {
-            // Implementation goes here
-            false
+        pub fn serialize_strong_references(&mut self, shared: &mut SharedHeapSerializer<'a>) {
+            assert!(!self.finished_strong_references_);
+            let mut encoder = self.encoder(shared);
+            for root in self.isolate_.strong_roots() {
+                self.serializer_.serialize_root(root, &mut encoder);
+            }
+            self.serializer_.put_synchronize();
+            self.finished_strong_references_ = true;
         }
 
+        /// Returns the startup object cache index of |object|, serializing
+        /// the object into the cache if it is not there yet.
         pub fn serialize_using_startup_object_cache(
             &mut self,
-            _sink: &mut SnapshotByteSink,
-            _obj: Handle<HeapObject>,
-        ) {
-            // Implementation goes here
-        }
-
-        pub fn check_no_dirty_finalization_registries(&self) {
-            // Implementation goes here
-        }
-    }
-
-    impl<'a> Drop for StartupSerializer<'a> {
-        fn drop(&mut self) {
-            // Destructor implementation goes here
-        }
-    }
-
-    //Implement RootsSerializer trait
-    impl<'a> RootsSerializer for StartupSerializer<'a> {
-        fn SerializeObjectImpl(&mut self, _o: Handle<HeapObject>, _slot_type: SlotType) {
-            // Implementation goes here
-        }
-    }
-
-    pub struct SerializedHandleChecker {
-        isolate_: *mut Isolate,
-        serialized_: HashSet<Tagged<Object>, ObjectHasher>,
-        ok_: bool,
-    }
-
-    impl SerializedHandleChecker {
-        pub fn new(
-            isolate: *mut Isolate,
-            _contexts: &mut Vec<Tagged<Context>>,
-        ) -> Self {
-            SerializedHandleChecker {
-                isolate_: isolate,
-                serialized_: HashSet::default(),
-                ok_: true,
+            object: HeapObject,
+            shared: &mut SharedHeapSerializer<'a>,
+        ) -> u32 {
+            assert!(self.finished_strong_references_);
+            if let Some(&index) = self.object_cache_index_.get(&object) {
+                return index;
             }
+            let index = self.object_cache_index_.len() as u32;
+            self.object_cache_index_.insert(object, index);
+            let mut encoder = self.encoder(shared);
+            self.serializer_.serialize_root(Tagged::strong(object), &mut encoder);
+            index
         }
 
-        pub fn visit_root_pointers(
-            &mut self,
-            _root: Root,
-            _description: &str,
-            _start: FullObjectSlot,
-            _end: FullObjectSlot,
-        ) {
-            // Implementation goes here
+        /// Terminates the startup object cache and serializes the objects
+        /// that were deferred while serializing the roots. Must be called
+        /// after all context snapshots have been serialized.
+        pub fn serialize_weak_references_and_deferred(&mut self, shared: &mut SharedHeapSerializer<'a>) {
+            self.serializer_.put_synchronize();
+            let mut encoder = self.encoder(shared);
+            self.serializer_.serialize_deferred_objects(&mut encoder);
         }
 
-        pub fn check_global_and_eternal_handles(&mut self) -> bool {
-            // Implementation goes here
-            self.ok_
+        /// Returns true if |object| is part of the startup snapshot.
+        pub fn contains(&self, object: HeapObject) -> bool {
+            self.serializer_.contains(object)
         }
 
-        fn add_to_set(&mut self, _serialized: Tagged<FixedArray>) {
-            // Implementation goes here
+        pub fn statistics(&self) -> &SerializationStatistics {
+            self.serializer_.statistics()
         }
-    }
-
-    impl RootVisitor for SerializedHandleChecker {
-        fn VisitRootPointers(&mut self, root: Root, description: &str, start: FullObjectSlot, end: FullObjectSlot){
-            self.visit_root_pointers(root, description, start, end);
-        }
-    }
-
-    pub trait RootVisitor {
-        fn VisitRootPointers(&mut self, root: Root, description: &str, start: FullObjectSlot, end: FullObjectSlot);
-    }
-
-    #[derive(Default)]
-    struct ObjectHasher;
-
-    impl Hasher for ObjectHasher {
-        fn finish(&self) -> u64 {
-            0
-        }
-
-        fn write(&mut self, _bytes: &[u8]) {}
-    }
-
-    impl Hash for Tagged<Object> {
-        fn hash<H: Hasher>(&self, state: &mut H) {
-            (self as *const _ as usize).hash(state);
-        }
-    }
-
-    impl Eq for Tagged<Object> {}
 
-    impl PartialEq for Tagged<Object> {
-        fn eq(&self, other: &Self) -> bool {
-            (self as *const _ as usize) == (other as *const _ as usize)
+        pub fn into_payload(self) -> Vec<u8> {
+            self.serializer_.into_payload()
         }
     }
-}
\ No newline at end of file
+}
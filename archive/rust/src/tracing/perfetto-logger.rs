@@ -1,842 +1,290 @@
-// src/tracing/perfetto-logger.rs
+// Copyright 2024 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-// use equivalent crates for absl::flat_hash_map, protobuf, base/logging, base/platform, etc.
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-
-// Placeholder for protobuf definitions
-mod protos {
-    pub mod perfetto {
-        pub mod common {
-            pub mod builtin_clock_pbzero {
-                pub struct BuiltinClock; // Placeholder
-                impl BuiltinClock {
-                    pub const BUILTIN_CLOCK_MONOTONIC: i32 = 1; // Placeholder value
-                }
-            }
-        }
-        pub mod trace {
-            pub mod chrome {
-                pub mod v8_pbzero {
-                    pub struct V8InternalCode {
-                        name: String,
-                        v8_isolate_iid: u64,
-                        instruction_start: u64,
-                        instruction_size_bytes: u64,
-                        machine_code: Vec<u8>,
-                        type_: i32,
-                        builtin_id: i32,
-                    }
-                    impl V8InternalCode {
-                        pub const TYPE_UNKNOWN: i32 = 0; // Placeholder
-                        pub const TYPE_BYTECODE_HANDLER: i32 = 1; // Placeholder
-                        pub const TYPE_FOR_TESTING: i32 = 2; // Placeholder
-                        pub const TYPE_BUILTIN: i32 = 3; // Placeholder
-                        pub const TYPE_WASM_FUNCTION: i32 = 4; // Placeholder
-                        pub const TYPE_WASM_TO_CAPI_FUNCTION: i32 = 5; // Placeholder
-                        pub const TYPE_WASM_TO_JS_FUNCTION: i32 = 6; // Placeholder
-                        pub const TYPE_JS_TO_WASM_FUNCTION: i32 = 7; // Placeholder
-                        pub const TYPE_C_WASM_ENTRY: i32 = 8; // Placeholder
-
-                        pub fn new() -> Self {
-                            V8InternalCode {
-                                name: String::new(),
-                                v8_isolate_iid: 0,
-                                instruction_start: 0,
-                                instruction_size_bytes: 0,
-                                machine_code: Vec::new(),
-                                type_: 0,
-                                builtin_id: 0,
-                            }
-                        }
-                        pub fn set_name(&mut self, name: &str) {
-                            self.name = name.to_string();
-                        }
-                        pub fn set_v8_isolate_iid(&mut self, id: u64) {
-                            self.v8_isolate_iid = id;
-                        }
-                        pub fn set_instruction_start(&mut self, start: u64) {
-                            self.instruction_start = start;
-                        }
-                        pub fn set_instruction_size_bytes(&mut self, size: u64) {
-                            self.instruction_size_bytes = size;
-                        }
-                        pub fn set_machine_code(&mut self, code: &[u8]) {
-                            self.machine_code = code.to_vec();
-                        }
-                        pub fn set_type(&mut self, type_: i32) {
-                            self.type_ = type_;
-                        }
-                        pub fn set_builtin_id(&mut self, id: i32) {
-                            self.builtin_id = id;
-                        }
-                    }
-                    pub struct V8JsCode {
-                        tier: i32,
-                        instruction_start: u64,
-                        instruction_size_bytes: u64,
-                        bytecode: Vec<u8>,
-                        machine_code: Vec<u8>,
-                        v8_js_function_iid: u64,
-                    }
-
-                    impl V8JsCode {
-                        pub const TIER_UNKNOWN: i32 = 0; // Placeholder
-                        pub const TIER_IGNITION: i32 = 1; // Placeholder
-                        pub const TIER_SPARKPLUG: i32 = 2; // Placeholder
-                        pub const TIER_MAGLEV: i32 = 3; // Placeholder
-                        pub const TIER_TURBOFAN: i32 = 4; // Placeholder
-                        
-                        pub fn new() -> Self {
-                            V8JsCode {
-                                tier: 0,
-                                instruction_start: 0,
-                                instruction_size_bytes: 0,
-                                bytecode: Vec::new(),
-                                machine_code: Vec::new(),
-                                v8_js_function_iid: 0,
-                            }
-                        }
-                        pub fn set_tier(&mut self, tier: i32) {
-                            self.tier = tier;
-                        }
-                        pub fn set_instruction_start(&mut self, start: u64) {
-                            self.instruction_start = start;
-                        }
-                        pub fn set_instruction_size_bytes(&mut self, size: u64) {
-                            self.instruction_size_bytes = size;
-                        }
-                        pub fn set_bytecode(&mut self, bytecode: &[u8]) {
-                            self.bytecode = bytecode.to_vec();
-                        }
-                        pub fn set_machine_code(&mut self, machine_code: &[u8]) {
-                            self.machine_code = machine_code.to_vec();
-                        }
-                        pub fn set_v8_js_function_iid(&mut self, id: u64) {
-                            self.v8_js_function_iid = id;
-                        }
-                    }
-                    pub struct V8WasmCode {
-                        v8_isolate_iid: u64,
-                        v8_wasm_script_iid: u64,
-                        function_name: String,
-                        instruction_start: u64,
-                        instruction_size_bytes: u64,
-                        machine_code: Vec<u8>,
-                    }
-
-                    impl V8WasmCode {
-                        pub fn new() -> Self {
-                            V8WasmCode {
-                                v8_isolate_iid: 0,
-                                v8_wasm_script_iid: 0,
-                                function_name: String::new(),
-                                instruction_start: 0,
-                                instruction_size_bytes: 0,
-                                machine_code: Vec::new(),
-                            }
-                        }
-                        pub fn set_v8_isolate_iid(&mut self, id: u64) {
-                            self.v8_isolate_iid = id;
-                        }
-                        pub fn set_v8_wasm_script_iid(&mut self, id: u64) {
-                            self.v8_wasm_script_iid = id;
-                        }
-                        pub fn set_function_name(&mut self, name: &str) {
-                            self.function_name = name.to_string();
-                        }
-                        pub fn set_instruction_start(&mut self, start: u64) {
-                            self.instruction_start = start;
-                        }
-                        pub fn set_instruction_size_bytes(&mut self, size: u64) {
-                            self.instruction_size_bytes = size;
-                        }
-                        pub fn set_machine_code(&mut self, machine_code: &[u8]) {
-                            self.machine_code = machine_code.to_vec();
-                        }
-                    }
-                    pub struct V8RegExpCode {
-                        v8_isolate_iid: u64,
-                        instruction_start: u64,
-                        instruction_size_bytes: u64,
-                        machine_code: Vec<u8>,
-                        pattern: V8String,
-                    }
-
-                    impl V8RegExpCode {
-                        pub fn new() -> Self {
-                            V8RegExpCode {
-                                v8_isolate_iid: 0,
-                                instruction_start: 0,
-                                instruction_size_bytes: 0,
-                                machine_code: Vec::new(),
-                                pattern: V8String::new(),
-                            }
-                        }
-                        pub fn set_v8_isolate_iid(&mut self, id: u64) {
-                            self.v8_isolate_iid = id;
-                        }
-                        pub fn set_instruction_start(&mut self, start: u64) {
-                            self.instruction_start = start;
-                        }
-                        pub fn set_instruction_size_bytes(&mut self, size: u64) {
-                            self.instruction_size_bytes = size;
-                        }
-                        pub fn set_machine_code(&mut self, machine_code: &[u8]) {
-                            self.machine_code = machine_code.to_vec();
-                        }
-                        pub fn set_pattern(&mut self) -> &mut V8String {
-                            &mut self.pattern
-                        }
-                    }
-                    pub struct V8String {
-                        value: String,
-                    }
-
-                    impl V8String {
-                        pub fn new() -> Self {
-                            V8String {
-                                value: String::new(),
-                            }
-                        }
-                        pub fn set_value(&mut self, value: &str) {
-                            self.value = value.to_string();
-                        }
-                    }
-
-                }
-            }
-            pub mod trace_packet_pbzero {
-                pub struct TracePacket {
-                    timestamp: u64,
-                    sequence_flags: u32,
-                    trace_packet_defaults: Option<TracePacketDefaults>,
-                    v8_internal_code: Option<super::chrome::v8_pbzero::V8InternalCode>,
-                    v8_js_code: Option<super::chrome::v8_pbzero::V8JsCode>,
-                    v8_wasm_code: Option<super::chrome::v8_pbzero::V8WasmCode>,
-                    v8_reg_exp_code: Option<super::chrome::v8_pbzero::V8RegExpCode>,
-                    code_move: Option<CodeMove>
-                }
-                impl TracePacket {
-                    pub const SEQ_NEEDS_INCREMENTAL_STATE: u32 = 1; // Placeholder value
-                    pub const SEQ_INCREMENTAL_STATE_CLEARED: u32 = 2; // Placeholder value
-
-                    pub fn new() -> Self {
-                        TracePacket {
-                            timestamp: 0,
-                            sequence_flags: 0,
-                            trace_packet_defaults: None,
-                            v8_internal_code: None,
-                            v8_js_code: None,
-                            v8_wasm_code: None,
-                            v8_reg_exp_code: None,
-                            code_move: None,
-                        }
-                    }
-
-                    pub fn set_timestamp(&mut self, timestamp: u64) {
-                        self.timestamp = timestamp;
-                    }
-                    pub fn set_sequence_flags(&mut self, flags: u32) {
-                        self.sequence_flags = flags;
-                    }
-                    pub fn set_trace_packet_defaults(&mut self) -> &mut TracePacketDefaults {
-                        self.trace_packet_defaults.insert(TracePacketDefaults::new());
-                        self.trace_packet_defaults.as_mut().unwrap()
-                    }
-                    pub fn set_v8_internal_code(&mut self) -> &mut super::chrome::v8_pbzero::V8InternalCode {
-                        self.v8_internal_code.insert(super::chrome::v8_pbzero::V8InternalCode::new());
-                        self.v8_internal_code.as_mut().unwrap()
-                    }
-                    pub fn set_v8_js_code(&mut self) -> &mut super::chrome::v8_pbzero::V8JsCode {
-                        self.v8_js_code.insert(super::chrome::v8_pbzero::V8JsCode::new());
-                        self.v8_js_code.as_mut().unwrap()
-                    }
-                    pub fn set_v8_wasm_code(&mut self) -> &mut super::chrome::v8_pbzero::V8WasmCode {
-                        self.v8_wasm_code.insert(super::chrome::v8_pbzero::V8WasmCode::new());
-                        self.v8_wasm_code.as_mut().unwrap()
-                    }
-                    pub fn set_v8_reg_exp_code(&mut self) -> &mut super::chrome::v8_pbzero::V8RegExpCode {
-                         self.v8_reg_exp_code.insert(super::chrome::v8_pbzero::V8RegExpCode::new());
-                         self.v8_reg_exp_code.as_mut().unwrap()
-                    }
-                    pub fn set_code_move(&mut self) -> &mut CodeMove {
-                        self.code_move.insert(CodeMove::new());
-                        self.code_move.as_mut().unwrap()
-                   }
-                }
-
-                pub struct CodeMove {
-                    isolate_iid: u64,
-                    from_instruction_start_address: u64,
-                    to_instruction_start_address: u64,
-                    instruction_size_bytes: u64,
-                    to_machine_code: Vec<u8>,
-                    to_bytecode: Vec<u8>,
-                }
-                impl CodeMove {
-                    pub fn new() -> Self {
-                        CodeMove {
-                            isolate_iid: 0,
-                            from_instruction_start_address: 0,
-                            to_instruction_start_address: 0,
-                            instruction_size_bytes: 0,
-                            to_machine_code: Vec::new(),
-                            to_bytecode: Vec::new(),
-                        }
-                    }
-                    pub fn set_isolate_iid(&mut self, id: u64) {
-                        self.isolate_iid = id;
-                    }
-                    pub fn set_from_instruction_start_address(&mut self, address: u64) {
-                        self.from_instruction_start_address = address;
-                    }
-                    pub fn set_to_instruction_start_address(&mut self, address: u64) {
-                        self.to_instruction_start_address = address;
-                    }
-                    pub fn set_instruction_size_bytes(&mut self, size: u64) {
-                        self.instruction_size_bytes = size;
-                    }
-                    pub fn set_to_machine_code(&mut self, code: &[u8]) {
-                        self.to_machine_code = code.to_vec();
-                    }
-                    pub fn set_to_bytecode(&mut self, bytecode: &[u8]) {
-                        self.to_bytecode = bytecode.to_vec();
-                    }
-                }
-            }
-            pub mod trace_packet_defaults_pbzero {
-                pub struct TracePacketDefaults {
-                    timestamp_clock_id: i32,
-                    v8_code_defaults: Option<V8CodeDefaults>
-                }
-
-                impl TracePacketDefaults {
-                    pub fn new() -> Self {
-                        TracePacketDefaults {
-                            timestamp_clock_id: 0,
-                            v8_code_defaults: None
-                        }
-                    }
-
-                    pub fn set_timestamp_clock_id(&mut self, id: i32) {
-                        self.timestamp_clock_id = id;
-                    }
-                    pub fn set_v8_code_defaults(&mut self) -> &mut V8CodeDefaults {
-                        self.v8_code_defaults.insert(V8CodeDefaults::new());
-                        self.v8_code_defaults.as_mut().unwrap()
-                    }
-                }
-                pub struct V8CodeDefaults {
-                    tid: i32,
-                }
-
-                impl V8CodeDefaults {
-                    pub fn new() -> Self {
-                        V8CodeDefaults {
-                            tid: 0,
-                        }
-                    }
-
-                    pub fn set_tid(&mut self, tid: i32) {
-                        self.tid = tid;
-                    }
-                }
-            }
-        }
-    }
+
+use crate::libplatform::tracing::tracing_controller::TraceStateObserver;
+use crate::logging::code_events::{
+    AbstractCode, Address, CodeTag, LogEventListener, Logger, Script, SharedFunctionInfo,
+};
+use crate::objects::code_kind::CodeKind;
+use crate::tracing::code_data_source::{
+    CodeDataSource, InternalCodeType, IsolateInfo, JsCodeTier, JsFunctionInfo, ScriptInfo,
+};
+
+/// Forwards the code events of one isolate to the code data source. It is
+/// registered on the isolate's code event logger while the data source is
+/// recording, see `PerfettoLogger::register_isolate()`.
+pub struct PerfettoLogger {
+    isolate: IsolateInfo,
+    data_source: Arc<CodeDataSource>,
+    /// Instruction sizes of the logged code objects by instruction start, for
+    /// code move events.
+    code_sizes: Mutex<HashMap<Address, usize>>,
 }
 
-// Placeholder modules for platform specific code
-mod base {
-    pub mod logging {
-        #[macro_export]
-        macro_rules! CHECK {
-            ($cond:expr) => {
-                if !$cond {
-                    panic!("Check failed: {}", stringify!($cond));
-                }
-            };
-        }
+impl PerfettoLogger {
+    pub fn new(isolate: IsolateInfo, data_source: Arc<CodeDataSource>) -> Self {
+        PerfettoLogger { isolate, data_source, code_sizes: Mutex::new(HashMap::new()) }
     }
-    pub mod platform {
-        pub mod mutex {
-            use std::sync::{Mutex, MutexGuard, TryLockError};
-
-            pub struct MutexHolder(Mutex<()>);
 
-            impl MutexHolder {
-                pub const fn new() -> Self {
-                    MutexHolder(Mutex::new(()))
-                }
+    /// Registers a `PerfettoLogger` for `isolate` on `logger` whenever
+    /// `data_source` starts recording, and removes it when recording stops.
+    pub fn register_isolate(
+        data_source: &Arc<CodeDataSource>,
+        isolate: IsolateInfo,
+        logger: Arc<Logger>,
+    ) -> Arc<PerfettoIsolateRegistration> {
+        let registration = Arc::new(PerfettoIsolateRegistration {
+            data_source: data_source.clone(),
+            isolate,
+            logger,
+            listener: Mutex::new(None),
+        });
+        data_source.controller().add_trace_state_observer(registration.clone());
+        registration
+    }
 
-                pub fn lock(&self) -> MutexGuard<()> {
-                    self.0.lock().unwrap()
-                }
-            }
-        }
-        pub mod os {
-            pub fn get_current_thread_id() -> i32 {
-                // Implement this based on the target OS
-                0 // Placeholder
-            }
-        }
-        pub mod time {
-            pub struct TimeTicks;
-
-            impl TimeTicks {
-                pub fn now() -> Self {
-                    TimeTicks
-                }
-                pub fn since_origin(&self) -> Duration {
-                    SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
-                }
-            }
+    /// Returns the machine code of `code` if the data source logs it.
+    fn instructions<'a>(&self, code: &'a AbstractCode) -> &'a [u8] {
+        if !self.data_source.config().log_instructions || code.instruction_size == 0 {
+            return &[];
         }
+        // SAFETY: code objects stay alive while their creation is logged.
+        unsafe { std::slice::from_raw_parts(code.instruction_start as *const u8, code.instruction_size) }
+    }
+
+    fn record_code(&self, code: &AbstractCode) {
+        self.code_sizes.lock().unwrap().insert(code.instruction_start, code.instruction_size);
+    }
+
+    fn log_internal_code(&self, code: &AbstractCode, name: &str) {
+        self.record_code(code);
+        self.data_source.log_internal_code(
+            &self.isolate,
+            name,
+            internal_code_type(code.kind),
+            None,
+            code.instruction_start,
+            code.instruction_size,
+            self.instructions(code),
+        );
     }
 }
 
-mod src {
-    pub mod tracing {
-        pub mod code_data_source {
-            use std::cell::RefCell;
-            use std::rc::Rc;
-
-            use crate::protos::perfetto::trace::trace_packet_pbzero::TracePacket;
-            use crate::protos::perfetto::common::builtin_clock_pbzero::BuiltinClock;
-            use crate::base::platform::os::get_current_thread_id;
-            use crate::base::platform::time::TimeTicks;
-
-            pub struct CodeDataSource;
-
-            impl CodeDataSource {
-                pub fn trace<F>(callback: F)
-                where
-                    F: FnOnce(TraceContext),
-                {
-                    let context = TraceContext::new();
-                    callback(context);
-                }
-
-                pub fn call_if_enabled<F>(callback: F)
-                where
-                    F: FnOnce(u32), // Placeholder for Isolate*
-                {
-                    // Placeholder implementation
-                    callback(0);
-                }
-
-                pub struct TraceContext {
-                    incremental_state: Rc<RefCell<CodeDataSourceIncrementalState>>,
-                }
-
-                impl TraceContext {
-                    fn new() -> Self {
-                        TraceContext {
-                            incremental_state: Rc::new(RefCell::new(CodeDataSourceIncrementalState::new())),
-                        }
-                    }
-
-                    pub fn new_trace_packet(&self) -> TracePacketHandle {
-                        let mut inc_state = self.incremental_state.borrow_mut();
-                        let mut packet = TracePacket::new();
-                        packet.set_timestamp(TimeTicks::now().since_origin().as_nanos() as u64);
-
-                        if inc_state.is_initialized() {
-                            packet.set_sequence_flags(TracePacket::SEQ_NEEDS_INCREMENTAL_STATE);
-                            return TracePacketHandle::new(packet);
-                        }
-
-                        inc_state.init(self);
-
-                        packet.set_sequence_flags(TracePacket::SEQ_INCREMENTAL_STATE_CLEARED);
-
-                        let defaults = packet.set_trace_packet_defaults();
-                        defaults.set_timestamp_clock_id(BuiltinClock::BUILTIN_CLOCK_MONOTONIC);
-
-                        let v8_defaults = defaults.set_v8_code_defaults();
-                        v8_defaults.set_tid(get_current_thread_id());
-
-                        TracePacketHandle::new(packet)
-                    }
-
-                    pub fn get_incremental_state(&self) -> Rc<RefCell<CodeDataSourceIncrementalState>> {
-                        self.incremental_state.clone()
-                    }
-
-                    pub fn intern_isolate(&self, isolate: *const Isolate) -> u64 {
-                        //Placeholder
-                        isolate as u64
-                    }
-                    pub fn intern_js_function(&self, isolate: *const Isolate, info: *const SharedFunctionInfo, script: *const Script, line: i32, column: i32) -> u64 {
-                        //Placeholder
-                        info as u64
-                    }
-                    pub fn intern_js_script(&self, isolate: *const Isolate, script: *const Script) -> u64 {
-                        //Placeholder
-                        script as u64
-                    }
-                    pub fn intern_wasm_script(&self, isolate: *const Isolate, script_id: i32, source_url: &str) -> u64 {
-                        //Placeholder
-                        script_id as u64
-                    }
-                }
-
-                pub struct TracePacketHandle {
-                    packet: TracePacket,
-                }
-                impl TracePacketHandle {
-                    fn new(packet: TracePacket) -> Self {
-                        TracePacketHandle {
-                            packet,
-                        }
-                    }
-
-                    pub fn set_timestamp(&mut self, timestamp: u64) {
-                        self.packet.set_timestamp(timestamp);
-                    }
-
-                    pub fn set_sequence_flags(&mut self, flags: u32) {
-                        self.packet.set_sequence_flags(flags);
-                    }
-
-                    pub fn set_trace_packet_defaults(&mut self) -> &mut crate::protos::perfetto::trace::trace_packet_defaults_pbzero::TracePacketDefaults {
-                        self.packet.set_trace_packet_defaults()
-                    }
-                    pub fn set_v8_internal_code(&mut self) -> &mut crate::protos::perfetto::trace::chrome::v8_pbzero::V8InternalCode {
-                        self.packet.set_v8_internal_code()
-                    }
-                    pub fn set_v8_js_code(&mut self) -> &mut crate::protos::perfetto::trace::chrome::v8_pbzero::V8JsCode {
-                        self.packet.set_v8_js_code()
-                    }
-                    pub fn set_v8_wasm_code(&mut self) -> &mut crate::protos::perfetto::trace::chrome::v8_pbzero::V8WasmCode {
-                        self.packet.set_v8_wasm_code()
-                    }
-                    pub fn set_v8_reg_exp_code(&mut self) -> &mut crate::protos::perfetto::trace::chrome::v8_pbzero::V8RegExpCode {
-                        self.packet.set_v8_reg_exp_code()
-                    }
-                    pub fn set_code_move(&mut self) -> &mut crate::protos::perfetto::trace::trace_packet_pbzero::CodeMove {
-                        self.packet.set_code_move()
-                    }
-                }
-
-                pub struct CodeDataSourceIncrementalState {
-                    initialized: bool,
-                }
-
-                impl CodeDataSourceIncrementalState {
-                    fn new() -> Self {
-                        CodeDataSourceIncrementalState { initialized: false }
-                    }
-
-                    fn is_initialized(&self) -> bool {
-                        self.initialized
-                    }
-
-                    fn init(&mut self, _context: &TraceContext) {
-                        self.initialized = true;
-                    }
-                }
-
-            }
+fn js_code_tier(kind: CodeKind) -> Option<JsCodeTier> {
+    match kind {
+        CodeKind::INTERPRETED_FUNCTION => Some(JsCodeTier::kIgnition),
+        CodeKind::BASELINE => Some(JsCodeTier::kSparkplug),
+        CodeKind::MAGLEV => Some(JsCodeTier::kMaglev),
+        CodeKind::TURBOFAN_JS => Some(JsCodeTier::kTurbofan),
+        _ => None,
+    }
+}
 
-        }
-        pub mod code_trace_context {
-            use std::rc::Rc;
-            use std::cell::RefCell;
-
-            use crate::protos::perfetto::trace::chrome::v8_pbzero::V8InternalCode;
-            use crate::protos::perfetto::trace::chrome::v8_pbzero::V8JsCode;
-            use crate::protos::perfetto::trace::chrome::v8_pbzero::V8WasmCode;
-            use crate::protos::perfetto::trace::chrome::v8_pbzero::V8RegExpCode;
-            use crate::protos::perfetto::trace::trace_packet_pbzero::CodeMove;
-            use crate::src::tracing::code_data_source::{CodeDataSource, CodeDataSource::TraceContext, CodeDataSource::TracePacketHandle, CodeDataSource::CodeDataSourceIncrementalState};
-            
-            pub struct CodeTraceContext {
-                packet: CodeDataSource::TracePacketHandle,
-                incremental_state: Rc<RefCell<CodeDataSourceIncrementalState>>,
-                log_instructions: bool,
-                trace_context: TraceContext, // Store the TraceContext
-            }
-
-            impl CodeTraceContext {
-                pub fn new(packet: CodeDataSource::TracePacketHandle, incremental_state: Rc<RefCell<CodeDataSourceIncrementalState>>) -> Self {
-                    CodeTraceContext {
-                        packet: packet,
-                        incremental_state: incremental_state,
-                        log_instructions: true,
-                        trace_context: TraceContext::new(), // Initialize the TraceContext
-                    }
-                }
-
-                pub fn set_v8_internal_code(&mut self) -> &mut V8InternalCode {
-                    self.packet.set_v8_internal_code()
-                }
-                pub fn set_v8_js_code(&mut self) -> &mut V8JsCode {
-                    self.packet.set_v8_js_code()
-                }
-                pub fn set_v8_wasm_code(&mut self) -> &mut V8WasmCode {
-                    self.packet.set_v8_wasm_code()
-                }
-                pub fn set_v8_reg_exp_code(&mut self) -> &mut V8RegExpCode {
-                    self.packet.set_v8_reg_exp_code()
-                }
-                pub fn set_code_move(&mut self) -> &mut CodeMove {
-                    self.packet.set_code_move()
-                }
-
-                pub fn log_instructions(&self) -> bool {
-                    self.log_instructions
-                }
-                 pub fn intern_isolate(&self, isolate: *const Isolate) -> u64 {
-                    self.trace_context.intern_isolate(isolate)
-                 }
-                 pub fn intern_js_function(&self, isolate: *const Isolate, info: *const SharedFunctionInfo, script: *const Script, line: i32, column: i32) -> u64 {
-                    self.trace_context.intern_js_function(isolate, info, script, line, column)
-                 }
-                 pub fn intern_js_script(&self, isolate: *const Isolate, script: *const Script) -> u64 {
-                    self.trace_context.intern_js_script(isolate, script)
-                 }
-                 pub fn intern_wasm_script(&self, isolate: *const Isolate, script_id: i32, source_url: &str) -> u64 {
-                    self.trace_context.intern_wasm_script(isolate, script_id, source_url)
-                 }
-
-            }
-        }
-        pub mod perfetto_utils {
-            pub struct PerfettoV8String<'a> {
-                string: &'a str,
-            }
-
-            impl<'a> PerfettoV8String<'a> {
-                pub fn new(string: &'a str) -> Self {
-                    PerfettoV8String { string }
-                }
-                pub fn write_to_proto(&self, proto: &mut crate::protos::perfetto::trace::chrome::v8_pbzero::V8String) {
-                    proto.set_value(self.string);
-                }
-            }
-            impl<'a> From<&'a str> for PerfettoV8String<'a> {
-                fn from(s: &'a str) -> Self {
-                    PerfettoV8String::new(s)
-                }
-            }
-        }
+fn internal_code_type(kind: CodeKind) -> InternalCodeType {
+    match kind {
+        CodeKind::BYTECODE_HANDLER => InternalCodeType::kBytecodeHandler,
+        CodeKind::FOR_TESTING => InternalCodeType::kForTesting,
+        CodeKind::BUILTIN => InternalCodeType::kBuiltin,
+        CodeKind::WASM_FUNCTION => InternalCodeType::kWasmFunction,
+        CodeKind::WASM_TO_CAPI_FUNCTION => InternalCodeType::kWasmToCapiFunction,
+        CodeKind::WASM_TO_JS_FUNCTION => InternalCodeType::kWasmToJsFunction,
+        CodeKind::JS_TO_WASM_FUNCTION => InternalCodeType::kJsToWasmFunction,
+        CodeKind::C_WASM_ENTRY => InternalCodeType::kCWasmEntry,
+        _ => InternalCodeType::kUnknown,
+    }
+}
 
-        use crate::base::{
-            logging::CHECK,
-            platform::mutex::MutexHolder,
-        };
-        use crate::src::tracing::code_data_source::CodeDataSource;
-        use std::sync::Mutex;
-        use crate::src::tracing::code_trace_context::CodeTraceContext;
-
-        // Placeholder types for V8 internal classes
-        pub struct Isolate;
-        pub struct AbstractCode;
-        pub struct Code;
-        pub struct String;
-        pub struct Name;
-        pub struct SharedFunctionInfo;
-        pub struct Script;
-        pub struct BytecodeArray;
-        pub struct InstructionStream;
-
-        // Placeholder enums for V8 internal enums
-        pub enum CodeKind {
-            REGEXP,
-            BYTECODE_HANDLER,
-            FOR_TESTING,
-            BUILTIN,
-            WASM_FUNCTION,
-            WASM_TO_CAPI_FUNCTION,
-            WASM_TO_JS_FUNCTION,
-            JS_TO_WASM_FUNCTION,
-            C_WASM_ENTRY,
-            INTERPRETED_FUNCTION,
-            BASELINE,
-            MAGLEV,
-            TURBOFAN_JS,
-        }
+/// Converts the one-based line and column of code events to a script offset.
+fn start_position(script: Option<&Script>, line: i32, column: i32) -> i32 {
+    let line_start = match script {
+        Some(script) if line > 1 => script.line_ends.get(line as usize - 2).map_or(0, |end| end + 1),
+        _ => 0,
+    };
+    line_start + (column - 1).max(0)
+}
 
-        pub enum Builtin {
-            kInterpreterEntryTrampoline,
-            kEmptyFunction,
-        }
+impl LogEventListener for PerfettoLogger {
+    fn code_create_event(&self, _tag: CodeTag, code: &AbstractCode, name: &str) {
+        self.log_internal_code(code, name);
+    }
 
-        pub enum RegExpFlags {}
+    fn code_create_event_shared(
+        &self,
+        tag: CodeTag,
+        code: &AbstractCode,
+        shared: &SharedFunctionInfo,
+        script_name: &str,
+        line: i32,
+        column: i32,
+    ) {
+        let Some(tier) = js_code_tier(code.kind) else {
+            self.log_internal_code(code, &shared.debug_name);
+            return;
+        };
+        self.record_code(code);
+        let script = shared.script.as_deref();
+        let script_id = script.map_or(-1, |script| script.id);
+        let script_info = ScriptInfo { script_id, url: script_name.to_string(), source: None };
+        let function = JsFunctionInfo {
+            script_id,
+            name: shared.debug_name.clone(),
+            start_position: start_position(script, line, column),
+            is_toplevel: matches!(tag, CodeTag::kScript | CodeTag::kEval | CodeTag::kNativeScript),
+        };
+        self.data_source.log_js_code(
+            &self.isolate,
+            &script_info,
+            &function,
+            tier,
+            code.instruction_start,
+            code.instruction_size,
+            self.instructions(code),
+        );
+    }
 
-        pub enum DeoptimizeKind {}
+    fn code_create_event_wasm(&self, code: &AbstractCode, name: &str, source_url: &str, _code_offset: i32, script_id: i32) {
+        self.record_code(code);
+        let script = ScriptInfo { script_id, url: source_url.to_string(), source: None };
+        self.data_source.log_wasm_code(
+            &self.isolate,
+            &script,
+            name,
+            code.instruction_start,
+            code.instruction_size,
+            self.instructions(code),
+        );
+    }
 
-        // Placeholder functions that use those types
-        pub fn is_code(_abstract_code: &AbstractCode) -> bool {
-            true // Placeholder
-        }
+    fn regexp_code_create_event(&self, code: &AbstractCode, source: &str, flags: &str) {
+        self.record_code(code);
+        self.data_source.log_regexp_code(
+            &self.isolate,
+            &format!("/{source}/{flags}"),
+            code.instruction_start,
+            code.instruction_size,
+            self.instructions(code),
+        );
+    }
 
-        pub fn is_string(_name: &Name) -> bool {
-            true // Placeholder
-        }
+    fn code_move_event(&self, from: Address, to: Address) {
+        let size = {
+            let mut code_sizes = self.code_sizes.lock().unwrap();
+            let size = code_sizes.remove(&from).unwrap_or(0);
+            code_sizes.insert(to, size);
+            size
+        };
+        self.data_source.log_code_move(&self.isolate, from, to, size);
+    }
 
-        pub fn is_script(_obj: &SharedFunctionInfo) -> bool {
-            true // Placeholder
-        }
+    fn code_delete_event(&self, instruction_start: Address) {
+        self.code_sizes.lock().unwrap().remove(&instruction_start);
+    }
 
-        pub fn is_bytecode_array(_abstract_code: &AbstractCode) -> bool {
-            false // Placeholder
-        }
-        
-        impl AbstractCode {
-            pub fn get_bytecode_array(&self) -> &BytecodeArray {
-                // Placeholder
-                static BYTECODE_ARRAY: BytecodeArray = BytecodeArray{};
-                &BYTECODE_ARRAY
-            }
-            pub fn get_code(&self) -> &Code {
-                // Placeholder
-                static CODE: Code = Code{};
-                &CODE
-            }
+    fn is_listening_to_code_events(&self) -> bool {
+        self.data_source.is_enabled()
+    }
 
-        }
+    /// Code moves are logged, so compaction does not break the trace.
+    fn allows_code_compaction(&self) -> bool {
+        true
+    }
+}
 
-        impl BytecodeArray {
-            pub fn get_first_bytecode_address(&self) -> *const u8 {
-                // Placeholder
-                std::ptr::null()
-            }
-            pub fn length(&self) -> u64 {
-                0 // Placeholder
-            }
-        }
+/// Registration of an isolate with the code data source, see
+/// `PerfettoLogger::register_isolate()`.
+pub struct PerfettoIsolateRegistration {
+    data_source: Arc<CodeDataSource>,
+    isolate: IsolateInfo,
+    logger: Arc<Logger>,
+    listener: Mutex<Option<Arc<dyn LogEventListener>>>,
+}
 
-        impl Code {
-            pub fn kind(&self) -> CodeKind {
-                CodeKind::BUILTIN // Placeholder
-            }
-            pub fn builtin_id(&self) -> Builtin {
-                Builtin::kEmptyFunction // Placeholder
-            }
-            pub fn has_instruction_stream(&self) -> bool {
-                false // Placeholder
-            }
-            pub fn instruction_start(&self) -> u64 {
-                0 // Placeholder
-            }
-            pub fn instruction_size(&self) -> u64 {
-                0 // Placeholder
-            }
-        }
+impl PerfettoIsolateRegistration {
+    /// Stops registering a `PerfettoLogger` for the isolate, and removes the
+    /// current one, if any.
+    pub fn unregister(self: &Arc<Self>) {
+        let observer: Arc<dyn TraceStateObserver> = self.clone();
+        self.data_source.controller().remove_trace_state_observer(&observer);
+        self.on_trace_disabled();
+    }
 
-        impl String {
-            pub fn to_c_string(&self) -> std::ffi::CString {
-                 std::ffi::CString::new("").unwrap() // Placeholder
-            }
-        }
+    pub fn is_logging(&self) -> bool {
+        self.listener.lock().unwrap().is_some()
+    }
+}
 
-        impl SharedFunctionInfo {
-            pub fn script(&self) -> &Script {
-                // Placeholder
-                static SCRIPT: Script = Script{};
-                &SCRIPT
-            }
+impl TraceStateObserver for PerfettoIsolateRegistration {
+    fn on_trace_enabled(&self) {
+        if !self.data_source.is_enabled() {
+            return;
         }
-
-        impl InstructionStream {
-            pub fn instruction_start(&self) -> u64 {
-                0 // Placeholder
-            }
-            pub fn code(&self, _tag: u8) -> &Code {
-                // Placeholder
-                static CODE: Code = Code{};
-                &CODE
-            }
+        let mut listener = self.listener.lock().unwrap();
+        if listener.is_none() {
+            let logger: Arc<dyn LogEventListener> =
+                Arc::new(PerfettoLogger::new(self.isolate.clone(), self.data_source.clone()));
+            self.logger.add_listener(logger.clone());
+            *listener = Some(logger);
         }
+    }
 
-        //End Placeholder
-
-        // Implementations of functions from the original C++ code
-        fn new_trace_packet(context: &mut CodeDataSource::TraceContext) -> CodeDataSource::TracePacketHandle {
-            let mut inc_state = context.get_incremental_state().borrow_mut();
-            let mut packet = crate::protos::perfetto::trace::trace_packet_pbzero::TracePacket::new();
-            packet.set_timestamp(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos() as u64);
-
-            if inc_state.is_initialized() {
-                packet.set_sequence_flags(crate::protos::perfetto::trace::trace_packet_pbzero::TracePacket::SEQ_NEEDS_INCREMENTAL_STATE);
-                return CodeDataSource::TracePacketHandle::new(packet);
-            }
-
-            inc_state.init(context);
-
-            packet.set_sequence_flags(crate::protos::perfetto::trace::trace_packet_pbzero::TracePacket::SEQ_INCREMENTAL_STATE_CLEARED);
-
-            let defaults = packet.set_trace_packet_defaults();
-            defaults.set_timestamp_clock_id(crate::protos::perfetto::common::builtin_clock_pbzero::BuiltinClock::BUILTIN_CLOCK_MONOTONIC);
-
-            let v8_defaults = defaults.set_v8_code_defaults();
-            v8_defaults.set_tid(crate::base::platform::os::get_current_thread_id());
-
-            CodeDataSource::TracePacketHandle::new(packet)
+    fn on_trace_disabled(&self) {
+        if let Some(listener) = self.listener.lock().unwrap().take() {
+            self.logger.remove_listener(&listener);
         }
+    }
+}
 
-        fn new_code_trace_context(ctx: &mut CodeDataSource::TraceContext) -> CodeTraceContext {
-             CodeTraceContext::new(new_trace_packet(ctx), ctx.get_incremental_state())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libplatform::tracing::trace_buffer::TraceBufferRingBuffer;
+    use crate::libplatform::tracing::trace_config::TraceConfig;
+    use crate::libplatform::tracing::trace_object::TraceObject;
+    use crate::libplatform::tracing::trace_writer::TraceWriter;
+    use crate::libplatform::tracing::tracing_controller::TracingController;
+    use crate::tracing::code_data_source::{CodeDataSourceConfig, K_CODE_CATEGORY};
+
+    struct RecordingWriter(Arc<Mutex<Vec<String>>>);
+
+    impl TraceWriter for RecordingWriter {
+        fn append_trace_event(&mut self, trace_event: &TraceObject) {
+            self.0.lock().unwrap().push(trace_event.name().to_string());
         }
 
-        struct IsolateRegistry {
-            mutex: MutexHolder,
-            num_active_data_sources: i32,
-            isolates: Mutex<HashMap<*const Isolate, Arc<PerfettoLogger>>>,
-        }
+        fn flush(&mut self) {}
+    }
 
-        impl IsolateRegistry {
-            fn get_instance() -> &'static IsolateRegistry {
-                static mut INSTANCE: *mut IsolateRegistry = std::ptr::null_mut();
-                static ONCE: std::sync::Once = std::sync::Once::new();
-
-                unsafe {
-                    ONCE.call_once(|| {
-                        let instance = IsolateRegistry {
-                            mutex: MutexHolder::new(),
-                            num_active_data_sources: 0,
-                            isolates: Mutex::new(HashMap::new()),
-                        };
-                        INSTANCE = Box::into_raw(Box::new(instance));
-                    });
-                    &*INSTANCE
-                }
-            }
-
-            fn register(&self, isolate: *const Isolate) {
-                let logger = Arc::new(PerfettoLogger::new(isolate));
-                let mut lock = self.mutex.lock();
-                let mut isolates = self.isolates.lock().unwrap();
-
-                if self.num_active_data_sources != 0 {
-                    //Placeholder: isolate.logger().add_listener(logger.get());
-                }
-                CHECK!(isolates.insert(isolate, logger.clone()).is_none());
-            }
-
-            fn unregister(&self, isolate: *const Isolate) {
-                let mut lock = self.mutex.lock();
-                let mut isolates = self.isolates.lock().unwrap();
-                let it = isolates.get(&isolate);
-                CHECK!(it.is_some());
-
-                if self.num_active_data_sources != 0 {
-                     //Placeholder: isolate.logger().remove_listener(it.second.get());
-                }
-                isolates.remove(&isolate);
-            }
-
-            fn on_code_data_source_start(&self) {
-                let mut lock = self.mutex.lock();
-                self.num_active_data_sources += 1;
-                if self.num_active_data_sources == 1 {
-                    self.start_logging(&lock);
-                }
-                self.log_existing_code_for_all_isolates(&lock);
-            }
-
-            fn on_code_data_
\ No newline at end of file
+    #[test]
+    fn logger_is_registered_while_the_data_source_records() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let controller: &'static TracingController = Box::leak(Box::new(TracingController::new()));
+        controller.initialize(Box::new(TraceBufferRingBuffer::new(4, Box::new(RecordingWriter(events.clone())))));
+        let data_source = CodeDataSource::register(controller, CodeDataSourceConfig::default());
+        let logger = Arc::new(Logger::new());
+        let registration =
+            PerfettoLogger::register_isolate(&data_source, IsolateInfo { isolate_id: 1, ..Default::default() }, logger.clone());
+        let code = AbstractCode::new(CodeKind::BUILTIN, 0x1000, 0x40);
+
+        logger.code_create_event(CodeTag::kBuiltin, &code, "Abort");
+        assert!(!registration.is_logging());
+
+        let mut trace_config = TraceConfig::new();
+        trace_config.add_included_category(K_CODE_CATEGORY);
+        controller.start_tracing(trace_config);
+        assert!(registration.is_logging());
+        assert!(logger.is_listening_to_code_events());
+        logger.code_create_event(CodeTag::kBuiltin, &code, "Abort");
+        logger.code_move_event(0x1000, 0x2000);
+        controller.stop_tracing();
+        assert!(!registration.is_logging());
+
+        logger.code_create_event(CodeTag::kBuiltin, &code, "Abort");
+        assert_eq!(*events.lock().unwrap(), ["V8Isolate", "V8InternalCode", "V8CodeMove"]);
+        registration.unregister();
+        data_source.unregister();
+    }
+}
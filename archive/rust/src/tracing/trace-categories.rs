@@ -2,86 +2,45 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// src/tracing/trace-categories.h
-mod trace_categories {
-    // This module would contain definitions from trace-categories.h.
-    // Since we're converting the implementation, we'll define a stub here.
-    // In a full conversion, you'd need to analyze the header.
-    pub fn get_track_event_category_registry() -> &'static perfetto::internal::TrackEventCategoryRegistry {
-        // Implementation goes here if needed, otherwise return a static empty registry
-        &PERFETTO_CATEGORY_REGISTRY
-    }
-
-    static PERFETTO_CATEGORY_REGISTRY: perfetto::internal::TrackEventCategoryRegistry =
-        perfetto::internal::TrackEventCategoryRegistry::new(); // Initialize with a default value
-
-}
-
-// include/v8-trace-categories.h
-mod v8_trace_categories {
-    // This module would contain definitions from v8-trace-categories.h.
-    // Since we're converting the implementation, we'll define a stub here.
-    // In a full conversion, you'd need to analyze the header.
-}
-
-#[cfg(feature = "v8_use_perfetto")]
-mod perfetto_integration {
-    use perfetto::internal::TrackEventCategoryRegistry;
-    use std::sync::Once;
-
-    // This is a simplified representation.  In a real conversion,
-    // `PERFETTO_TRACK_EVENT_STATIC_STORAGE_IN_NAMESPACE_WITH_ATTRS` might be
-    // replaced by a more complex structure, possibly with attributes.
-    // For this example, we assume it expands to a static variable.
-
-    // In C++, V8_EXPORT_PRIVATE controls visibility.  In Rust, we'll use a
-    // module structure to simulate namespace and limit direct access.
-
-    pub mod v8 {
-        use perfetto::internal::TrackEventCategoryRegistry;
-        use std::sync::Once;
-
-        pub mod perfetto_track_event {
-            pub mod internal {
-                use perfetto::internal::TrackEventCategoryRegistry;
-                use std::sync::Once;
-
-                pub static mut K_CATEGORY_REGISTRY: TrackEventCategoryRegistry = TrackEventCategoryRegistry::new();
-                static INIT: Once = Once::new();
-
-                pub fn k_category_registry() -> &'static TrackEventCategoryRegistry {
-                    INIT.call_once(|| {
-                        unsafe {
-                            K_CATEGORY_REGISTRY = TrackEventCategoryRegistry::new(); // Replace with actual initialization if needed
-                        }
-                    });
-
-                    unsafe { &K_CATEGORY_REGISTRY }
-                }
-            }
-        }
-    }
-
-    pub mod v8_wrapper {
-        use super::v8::perfetto_track_event::internal;
-        use perfetto::internal::TrackEventCategoryRegistry;
-
-        pub fn get_track_event_category_registry() -> &'static TrackEventCategoryRegistry {
-            internal::k_category_registry()
-        }
-    }
+/// Prefixes a category name so that it is only recorded when named
+/// explicitly, not by wildcards: `trace_disabled_by_default!("v8.gc")`.
+#[macro_export]
+macro_rules! trace_disabled_by_default {
+    ($name:literal) => {
+        concat!("disabled-by-default-", $name)
+    };
 }
 
-#[cfg(feature = "v8_use_perfetto")]
-pub use perfetto_integration::v8_wrapper::get_track_event_category_registry;
-
-#[cfg(not(feature = "v8_use_perfetto"))]
-pub mod v8_wrapper{
-    use perfetto::internal::TrackEventCategoryRegistry;
-    pub fn get_track_event_category_registry() -> &'static TrackEventCategoryRegistry {
-      panic!("perfetto not enabled")
-    }
-
+/// The categories V8 records trace events in.
+pub const K_V8_CATEGORIES: &[(&str, &str)] = &[
+    ("V8", "V8 (legacy name)"),
+    ("devtools.timeline", "Events for the DevTools timeline"),
+    ("v8", "General V8 events"),
+    ("v8.compile", "Compilation events"),
+    ("v8.execute", "Execution events"),
+    ("v8.gc", "Garbage collection events"),
+    ("v8.runtime", "Runtime function events"),
+    ("v8.wasm", "WebAssembly events"),
+    ("v8.code", "JIT code events"),
+    (trace_disabled_by_default!("devtools.timeline"), ""),
+    (trace_disabled_by_default!("devtools.timeline.frame"), ""),
+    (trace_disabled_by_default!("v8.compile"), "Detailed compilation events"),
+    (trace_disabled_by_default!("v8.cpu_profiler"), "CPU profiler samples"),
+    (trace_disabled_by_default!("v8.gc"), "Detailed garbage collection events"),
+    (trace_disabled_by_default!("v8.gc_stats"), "Garbage collection statistics"),
+    (trace_disabled_by_default!("v8.ic_stats"), "Inline cache statistics"),
+    (trace_disabled_by_default!("v8.runtime"), "Detailed runtime function events"),
+    (trace_disabled_by_default!("v8.runtime_stats"), "Runtime call statistics"),
+    (trace_disabled_by_default!("v8.runtime_stats_sampling"), "Sampled runtime call statistics"),
+    (trace_disabled_by_default!("v8.stack_trace"), "Stack traces"),
+    (trace_disabled_by_default!("v8.turbofan"), "Turbofan compilation details"),
+    (trace_disabled_by_default!("v8.wasm.detailed"), "Detailed WebAssembly events"),
+    (trace_disabled_by_default!("v8.wasm.turbofan"), "WebAssembly Turbofan compilation"),
+    (trace_disabled_by_default!("v8.inspector"), "Inspector events"),
+];
+
+/// Returns the description of a V8 category, or `None` if V8 does not
+/// record in it.
+pub fn get_category_description(category: &str) -> Option<&'static str> {
+    K_V8_CATEGORIES.iter().find(|(name, _)| *name == category).map(|(_, description)| *description)
 }
-
-extern crate perfetto; // Add perfetto as a dependency in Cargo.toml
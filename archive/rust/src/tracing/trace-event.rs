@@ -1,251 +1,332 @@
-// src/tracing/trace_event.rs
-
 // Copyright 2015 the V8 project authors. All rights reserved.
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// use std::ffi::CString;
-// use std::os::raw::c_char;
-// use std::ptr;
-
-// Assuming these constants are defined elsewhere in the V8 codebase,
-// we'll define them here as placeholders.  In a real port, these
-// would likely come from a generated binding or be hardcoded.
-// const TRACE_EVENT_PHASE_BEGIN: u8 = 'B' as u8;
-// const TRACE_EVENT_PHASE_END: u8 = 'E' as u8;
-// const TRACE_EVENT_FLAG_NONE: u8 = 0;
-
-// Define constants for global scope and no ID
-// const kGlobalScope: u64 = 0;
-// const kNoId: u64 = 0;
-
-// Placeholder for V8 platform and tracing controller.  A proper
-// implementation would involve interacting with the V8 platform
-// to get the tracing controller.
-// struct V8Platform;
-// impl V8Platform {
-//     fn get_tracing_controller(&self) -> *mut TracingController {
-//         // Placeholder: In a real implementation, this would
-//         // retrieve the tracing controller from the V8 platform.
-//         ptr::null_mut()
-//     }
-// }
-
-// extern "C" {
-//     fn V8_GetCurrentPlatform() -> *mut V8Platform;
-// }
-
-// pub struct TracingController;
-
-// pub mod trace_event {
-//     //use super::*;
-//     //use std::ffi::CString;
-//     //use std::os::raw::c_char;
-//     //use std::ptr;
-//     // Placeholder for the actual tracing event function.  A proper
-//     // implementation would involve calling into the tracing system.
-//     // pub fn add_trace_event(
-//     //     phase: u8,
-//     //     category_group_enabled: *const u8,
-//     //     name: *const c_char,
-//     //     scope: u64,
-//     //     id: u64,
-//     //     bind_id: u64,
-//     //     flags: u8,
-//     //     ... // Variadic arguments need to be handled carefully.
-//     // ) {
-//     //     // Placeholder: In a real implementation, this would
-//     //     // call into the tracing system with the provided information.
-//     //     unsafe {
-//     //         println!(
-//     //             "add_trace_event(phase: {}, name: {:?})",
-//     //             phase as char,
-//     //             CString::from_raw(name as *mut c_char)
-//     //         );
-//     //     }
-//     // }
-// }
-
-// pub struct TraceEventHelper;
-
-// impl TraceEventHelper {
-//     pub fn get_tracing_controller() -> *mut TracingController {
-//         // Placeholder:  This needs to be updated when v8 platform is available
-//         // unsafe {
-//         //     let platform = V8_GetCurrentPlatform();
-//         //     (*platform).get_tracing_controller()
-//         // }
-//         std::ptr::null_mut()
-//     }
-// }
-
-// #[cfg(V8_RUNTIME_CALL_STATS)]
-// pub struct CallStatsScopedTracer<'a> {
-//     data_: CallStatsScopedTracerData<'a>,
-//     p_data_: *mut CallStatsScopedTracerData<'a>,
-//     has_parent_scope_: bool,
-// }
-
-// #[cfg(V8_RUNTIME_CALL_STATS)]
-// #[derive(Clone, Copy)]
-// struct CallStatsScopedTracerData<'a> {
-//     isolate: *mut Isolate,
-//     category_group_enabled: *const u8,
-//     name: *const i8,
-// }
-
-// #[cfg(V8_RUNTIME_CALL_STATS)]
-// impl<'a> CallStatsScopedTracer<'a> {
-//     pub fn add_end_trace_event(&self) {
-//         unsafe {
-//             if !self.has_parent_scope_ && (*self.p_data_).isolate != std::ptr::null_mut() {
-//                 // TODO: Implement TracedValue and runtime call stats dumping.
-//                 // This is complex and requires further definition.
-//                 // let value = v8::tracing::TracedValue::Create();
-//                 // (*self.p_data_.isolate).counters().runtime_call_stats().Dump(value.get());
-//                 // trace_event::add_trace_event(
-//                 //     TRACE_EVENT_PHASE_END,
-//                 //     (*self.p_data_).category_group_enabled,
-//                 //     (*self.p_data_).name,
-//                 //     kGlobalScope,
-//                 //     kNoId,
-//                 //     kNoId,
-//                 //     TRACE_EVENT_FLAG_NONE,
-//                 //     // TODO: Add runtime-call-stats
-//                 // );
-//             } else {
-//                 // trace_event::add_trace_event(
-//                 //     TRACE_EVENT_PHASE_END,
-//                 //     (*self.p_data_).category_group_enabled,
-//                 //     (*self.p_data_).name,
-//                 //     kGlobalScope,
-//                 //     kNoId,
-//                 //     kNoId,
-//                 //     TRACE_EVENT_FLAG_NONE,
-//                 // );
-//             }
-//         }
-//     }
-
-//     pub fn initialize(
-//         isolate: *mut Isolate,
-//         category_group_enabled: *const u8,
-//         name: *const i8,
-//     ) -> CallStatsScopedTracer<'a> {
-//         let mut data_ = CallStatsScopedTracerData {
-//             isolate,
-//             category_group_enabled,
-//             name,
-//         };
-
-//         let p_data_: *mut CallStatsScopedTracerData = &mut data_;
-
-//         let runtime_call_stats: *mut RuntimeCallStats = unsafe {
-//             (*isolate).counters().runtime_call_stats()
-//         };
-
-//         let has_parent_scope_ = unsafe { (*runtime_call_stats).InUse() };
-//         if !has_parent_scope_ {
-//             unsafe {
-//                 (*runtime_call_stats).Reset();
-//             }
-//         }
-
-//         // trace_event::add_trace_event(
-//         //     TRACE_EVENT_PHASE_BEGIN,
-//         //     category_group_enabled,
-//         //     name,
-//         //     kGlobalScope,
-//         //     kNoId,
-//         //     TRACE_EVENT_FLAG_NONE,
-//         //     kNoId,
-//         // );
-
-//         CallStatsScopedTracer {
-//             data_: data_,
-//             p_data_: p_data_,
-//             has_parent_scope_: has_parent_scope_,
-//         }
-//     }
-// }
-
-// #[cfg(V8_RUNTIME_CALL_STATS)]
-// impl<'a> Drop for CallStatsScopedTracer<'a> {
-//     fn drop(&mut self) {
-//         self.add_end_trace_event();
-//     }
-// }
-
-// Placeholder structs for Isolate, Counters, and RuntimeCallStats.
-// The actual implementation needs to interact with the V8 API.
-// These are just dummies for compilation.
-#[allow(dead_code)]
-struct Isolate {
-    counters_: Counters,
-}
-
-impl Isolate {
-    fn counters(&mut self) -> &mut Counters {
-        &mut self.counters_
+use std::borrow::Cow;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+
+use crate::libplatform::tracing::trace_object::TraceArg;
+use crate::libplatform::tracing::tracing_controller::{CategoryGroupEnabledFlags, TracingController};
+
+// Phase indicates the nature of an event entry. E.g. part of a begin/end pair.
+pub const TRACE_EVENT_PHASE_BEGIN: u8 = b'B';
+pub const TRACE_EVENT_PHASE_END: u8 = b'E';
+pub const TRACE_EVENT_PHASE_COMPLETE: u8 = b'X';
+pub const TRACE_EVENT_PHASE_INSTANT: u8 = b'I';
+pub const TRACE_EVENT_PHASE_ASYNC_BEGIN: u8 = b'S';
+pub const TRACE_EVENT_PHASE_ASYNC_STEP_INTO: u8 = b'T';
+pub const TRACE_EVENT_PHASE_ASYNC_STEP_PAST: u8 = b'p';
+pub const TRACE_EVENT_PHASE_ASYNC_END: u8 = b'F';
+pub const TRACE_EVENT_PHASE_NESTABLE_ASYNC_BEGIN: u8 = b'b';
+pub const TRACE_EVENT_PHASE_NESTABLE_ASYNC_END: u8 = b'e';
+pub const TRACE_EVENT_PHASE_NESTABLE_ASYNC_INSTANT: u8 = b'n';
+pub const TRACE_EVENT_PHASE_FLOW_BEGIN: u8 = b's';
+pub const TRACE_EVENT_PHASE_FLOW_STEP: u8 = b't';
+pub const TRACE_EVENT_PHASE_FLOW_END: u8 = b'f';
+pub const TRACE_EVENT_PHASE_METADATA: u8 = b'M';
+pub const TRACE_EVENT_PHASE_COUNTER: u8 = b'C';
+pub const TRACE_EVENT_PHASE_SAMPLE: u8 = b'P';
+pub const TRACE_EVENT_PHASE_CREATE_OBJECT: u8 = b'N';
+pub const TRACE_EVENT_PHASE_SNAPSHOT_OBJECT: u8 = b'O';
+pub const TRACE_EVENT_PHASE_DELETE_OBJECT: u8 = b'D';
+
+// Flags for changing the behavior of TRACE_EVENT_API_ADD_TRACE_EVENT.
+pub const TRACE_EVENT_FLAG_NONE: u32 = 0;
+pub const TRACE_EVENT_FLAG_COPY: u32 = 1 << 0;
+pub const TRACE_EVENT_FLAG_HAS_ID: u32 = 1 << 1;
+pub const TRACE_EVENT_FLAG_SCOPE_OFFSET: u32 = 1 << 2;
+pub const TRACE_EVENT_FLAG_SCOPE_EXTRA: u32 = 1 << 3;
+pub const TRACE_EVENT_FLAG_EXPLICIT_TIMESTAMP: u32 = 1 << 4;
+pub const TRACE_EVENT_FLAG_ASYNC_TTS: u32 = 1 << 5;
+pub const TRACE_EVENT_FLAG_BIND_TO_ENCLOSING: u32 = 1 << 6;
+pub const TRACE_EVENT_FLAG_FLOW_IN: u32 = 1 << 7;
+pub const TRACE_EVENT_FLAG_FLOW_OUT: u32 = 1 << 8;
+pub const TRACE_EVENT_FLAG_HAS_CONTEXT_ID: u32 = 1 << 9;
+pub const TRACE_EVENT_FLAG_HAS_PROCESS_ID: u32 = 1 << 10;
+pub const TRACE_EVENT_FLAG_HAS_LOCAL_ID: u32 = 1 << 11;
+pub const TRACE_EVENT_FLAG_HAS_GLOBAL_ID: u32 = 1 << 12;
+
+/// Events without a scope.
+pub const K_GLOBAL_SCOPE: Option<&str> = None;
+/// Events without an id.
+pub const K_NO_ID: u64 = 0;
+
+static G_TRACING_CONTROLLER: AtomicPtr<TracingController> = AtomicPtr::new(ptr::null_mut());
+
+pub struct TraceEventHelper;
+
+impl TraceEventHelper {
+    /// Returns the tracing controller of the current platform, if any.
+    pub fn get_tracing_controller() -> Option<&'static TracingController> {
+        unsafe { G_TRACING_CONTROLLER.load(Ordering::Acquire).as_ref() }
+    }
+
+    /// Installs the tracing controller that the trace event macros record
+    /// to. Category flags cached by trace points keep referring to the
+    /// previous controller, so this should happen once, before tracing.
+    pub fn set_tracing_controller(controller: &'static TracingController) {
+        G_TRACING_CONTROLLER.store(controller as *const TracingController as *mut TracingController, Ordering::Release);
     }
 }
 
-#[allow(dead_code)]
-struct Counters {
-    runtime_call_stats_: RuntimeCallStats,
+/// Returns the enabled flags for `category_group`, caching them in `cache`.
+/// Trace points pass a static per call site so that the lookup happens once.
+pub fn get_category_group_enabled_cached(
+    cache: &AtomicPtr<AtomicU8>,
+    category_group: &str,
+) -> Option<&'static AtomicU8> {
+    if let Some(flags) = unsafe { cache.load(Ordering::Relaxed).as_ref() } {
+        return Some(flags);
+    }
+    let flags = TraceEventHelper::get_tracing_controller()?.get_category_group_enabled(category_group);
+    cache.store(flags as *const AtomicU8 as *mut AtomicU8, Ordering::Relaxed);
+    Some(flags)
 }
 
-impl Counters {
-    fn runtime_call_stats(&mut self) -> &mut RuntimeCallStats {
-        &mut self.runtime_call_stats_
+pub fn is_category_group_enabled(category_group_enabled: &AtomicU8) -> bool {
+    category_group_enabled.load(Ordering::Relaxed) & CategoryGroupEnabledFlags::kEnabledForRecording as u8 != 0
+}
+
+/// Records an event with the global tracing controller. Returns the handle
+/// of the event, or 0 if it was not recorded.
+#[allow(clippy::too_many_arguments)]
+pub fn add_trace_event(
+    phase: u8,
+    category_group_enabled: &AtomicU8,
+    name: impl Into<Cow<'static, str>>,
+    scope: Option<&'static str>,
+    id: u64,
+    bind_id: u64,
+    flags: u32,
+    args: Vec<TraceArg>,
+) -> u64 {
+    match TraceEventHelper::get_tracing_controller() {
+        Some(controller) => {
+            controller.add_trace_event(phase, category_group_enabled, name.into(), scope, id, bind_id, args, flags)
+        }
+        None => 0,
     }
 }
 
-#[allow(dead_code)]
-struct RuntimeCallStats {
-    in_use: bool,
+/// Records a complete event that ends when the tracer goes out of scope.
+pub struct ScopedTracer {
+    category_group_enabled: Option<&'static AtomicU8>,
+    name: &'static str,
+    event_handle: u64,
 }
 
-impl RuntimeCallStats {
-    fn InUse(&self) -> bool {
-        self.in_use
+impl ScopedTracer {
+    pub fn new() -> Self {
+        ScopedTracer { category_group_enabled: None, name: "", event_handle: 0 }
     }
-    fn Reset(&mut self) {
-        self.in_use = false;
+
+    pub fn initialize(&mut self, category_group_enabled: &'static AtomicU8, name: &'static str, event_handle: u64) {
+        self.category_group_enabled = Some(category_group_enabled);
+        self.name = name;
+        self.event_handle = event_handle;
     }
-    // fn Dump(&self, value: *mut TracedValue) {
-    //     // Placeholder implementation
-    // }
-}
-
-// struct TracedValue;
-// impl TracedValue {
-//     fn Create() -> Box<TracedValue> {
-//         Box::new(TracedValue{})
-//     }
-
-//     fn get(&self) -> *mut TracedValue{
-//         self as *const TracedValue as *mut TracedValue
-//     }
-// }
-
-// The V8_RUNTIME_CALL_STATS guard prevents the code from compiling without the feature enabled.
-// The `dummy` module allows the code to compile regardless of the `V8_RUNTIME_CALL_STATS` feature by providing empty definitions.
-#[cfg(not(V8_RUNTIME_CALL_STATS))]
-mod dummy {
-    pub struct Isolate {}
-    pub struct CallStatsScopedTracer {}
-    impl CallStatsScopedTracer {
-        pub fn add_end_trace_event(&self) {}
-        pub fn initialize(
-            _isolate: *mut Isolate,
-            _category_group_enabled: *const u8,
-            _name: *const i8,
-        ) -> CallStatsScopedTracer {
-            CallStatsScopedTracer {}
+}
+
+impl Default for ScopedTracer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ScopedTracer {
+    fn drop(&mut self) {
+        let Some(category_group_enabled) = self.category_group_enabled else {
+            return;
+        };
+        if !is_category_group_enabled(category_group_enabled) {
+            return;
+        }
+        if let Some(controller) = TraceEventHelper::get_tracing_controller() {
+            controller.update_trace_event_duration(category_group_enabled, self.name, self.event_handle);
         }
     }
 }
 
-#[cfg(not(V8_RUNTIME_CALL_STATS))]
-use dummy::CallStatsScopedTracer;
\ No newline at end of file
+/// Looks up the enabled flags of a category group, once per call site.
+#[macro_export]
+macro_rules! internal_trace_event_get_category_info {
+    ($category_group:expr) => {{
+        static CATEGORY_GROUP_ENABLED: ::std::sync::atomic::AtomicPtr<::std::sync::atomic::AtomicU8> =
+            ::std::sync::atomic::AtomicPtr::new(::std::ptr::null_mut());
+        $crate::tracing::trace_event::get_category_group_enabled_cached(&CATEGORY_GROUP_ENABLED, $category_group)
+            .filter(|enabled| $crate::tracing::trace_event::is_category_group_enabled(enabled))
+    }};
+}
+
+/// Adds an event if the category is enabled. Arguments are only evaluated
+/// when the event is recorded.
+#[macro_export]
+macro_rules! internal_trace_event_add {
+    ($phase:expr, $category_group:expr, $name:expr, $flags:expr $(, $arg_name:expr => $arg_value:expr)*) => {
+        if let Some(enabled) = $crate::internal_trace_event_get_category_info!($category_group) {
+            $crate::tracing::trace_event::add_trace_event(
+                $phase,
+                enabled,
+                $name,
+                $crate::tracing::trace_event::K_GLOBAL_SCOPE,
+                $crate::tracing::trace_event::K_NO_ID,
+                $crate::tracing::trace_event::K_NO_ID,
+                $flags,
+                vec![$($crate::libplatform::tracing::trace_object::TraceArg::new($arg_name, $arg_value)),*],
+            );
+        }
+    };
+}
+
+/// Records a complete event for the rest of the enclosing scope, e.g.
+/// `trace_event0!("v8", "V8.Execute");`.
+#[macro_export]
+macro_rules! internal_trace_event_add_scoped {
+    ($category_group:expr, $name:expr $(, $arg_name:expr => $arg_value:expr)*) => {
+        let mut _trace_event_tracer = $crate::tracing::trace_event::ScopedTracer::new();
+        if let Some(enabled) = $crate::internal_trace_event_get_category_info!($category_group) {
+            let handle = $crate::tracing::trace_event::add_trace_event(
+                $crate::tracing::trace_event::TRACE_EVENT_PHASE_COMPLETE,
+                enabled,
+                $name,
+                $crate::tracing::trace_event::K_GLOBAL_SCOPE,
+                $crate::tracing::trace_event::K_NO_ID,
+                $crate::tracing::trace_event::K_NO_ID,
+                $crate::tracing::trace_event::TRACE_EVENT_FLAG_NONE,
+                vec![$($crate::libplatform::tracing::trace_object::TraceArg::new($arg_name, $arg_value)),*],
+            );
+            _trace_event_tracer.initialize(enabled, $name, handle);
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event0 {
+    ($category_group:expr, $name:expr) => {
+        $crate::internal_trace_event_add_scoped!($category_group, $name)
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event1 {
+    ($category_group:expr, $name:expr, $arg1_name:expr, $arg1_val:expr) => {
+        $crate::internal_trace_event_add_scoped!($category_group, $name, $arg1_name => $arg1_val)
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event2 {
+    ($category_group:expr, $name:expr, $arg1_name:expr, $arg1_val:expr, $arg2_name:expr, $arg2_val:expr) => {
+        $crate::internal_trace_event_add_scoped!($category_group, $name, $arg1_name => $arg1_val, $arg2_name => $arg2_val)
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event_instant0 {
+    ($category_group:expr, $name:expr) => {
+        $crate::internal_trace_event_add!(
+            $crate::tracing::trace_event::TRACE_EVENT_PHASE_INSTANT,
+            $category_group,
+            $name,
+            $crate::tracing::trace_event::TRACE_EVENT_FLAG_NONE
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event_instant1 {
+    ($category_group:expr, $name:expr, $arg1_name:expr, $arg1_val:expr) => {
+        $crate::internal_trace_event_add!(
+            $crate::tracing::trace_event::TRACE_EVENT_PHASE_INSTANT,
+            $category_group,
+            $name,
+            $crate::tracing::trace_event::TRACE_EVENT_FLAG_NONE,
+            $arg1_name => $arg1_val
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event_instant2 {
+    ($category_group:expr, $name:expr, $arg1_name:expr, $arg1_val:expr, $arg2_name:expr, $arg2_val:expr) => {
+        $crate::internal_trace_event_add!(
+            $crate::tracing::trace_event::TRACE_EVENT_PHASE_INSTANT,
+            $category_group,
+            $name,
+            $crate::tracing::trace_event::TRACE_EVENT_FLAG_NONE,
+            $arg1_name => $arg1_val,
+            $arg2_name => $arg2_val
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event_begin0 {
+    ($category_group:expr, $name:expr) => {
+        $crate::internal_trace_event_add!(
+            $crate::tracing::trace_event::TRACE_EVENT_PHASE_BEGIN,
+            $category_group,
+            $name,
+            $crate::tracing::trace_event::TRACE_EVENT_FLAG_NONE
+        )
+    };
+}
+
+#[macro_export]
+macro_rules! trace_event_end0 {
+    ($category_group:expr, $name:expr) => {
+        $crate::internal_trace_event_add!(
+            $crate::tracing::trace_event::TRACE_EVENT_PHASE_END,
+            $category_group,
+            $name,
+            $crate::tracing::trace_event::TRACE_EVENT_FLAG_NONE
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::libplatform::tracing::trace_buffer::TraceBufferRingBuffer;
+    use crate::libplatform::tracing::trace_config::TraceConfig;
+    use crate::libplatform::tracing::trace_object::TraceObject;
+    use crate::libplatform::tracing::trace_writer::TraceWriter;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingWriter(Arc<Mutex<Vec<(u8, String, i64)>>>);
+
+    impl TraceWriter for RecordingWriter {
+        fn append_trace_event(&mut self, trace_event: &TraceObject) {
+            self.0.lock().unwrap().push((trace_event.phase(), trace_event.name().to_string(), trace_event.duration()));
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    fn traced_function(iterations: u64) {
+        trace_event1!("v8", "V8.Traced", "iterations", iterations);
+        trace_event_instant0!("v8", "V8.Instant");
+        trace_event_instant0!("v8.runtime", "V8.Filtered");
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+
+    #[test]
+    fn macros_record_to_the_global_controller() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let controller: &'static TracingController = Box::leak(Box::new(TracingController::new()));
+        controller.initialize(Box::new(TraceBufferRingBuffer::new(4, Box::new(RecordingWriter(events.clone())))));
+        TraceEventHelper::set_tracing_controller(controller);
+
+        traced_function(1);
+        controller.start_tracing(TraceConfig::create_default_trace_config());
+        traced_function(2);
+        controller.stop_tracing();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].0, events[0].1.as_str()), (TRACE_EVENT_PHASE_COMPLETE, "V8.Traced"));
+        assert!(events[0].2 >= 1000, "duration {}", events[0].2);
+        assert_eq!((events[1].0, events[1].1.as_str()), (TRACE_EVENT_PHASE_INSTANT, "V8.Instant"));
+    }
+}
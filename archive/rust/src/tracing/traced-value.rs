@@ -1,74 +1,22 @@
-// src/tracing/traced_value.rs
-
-use std::fmt::Write;
-
-// Mock for base::platform::platform.h
-mod platform {
-    pub mod os {
-        pub fn snprintf(buffer: &mut [u8], format: &str, value: u32) -> usize {
-            let formatted = format!("{}", value);
-            let len = std::cmp::min(buffer.len(), formatted.len());
-            buffer[..len].copy_from_slice(formatted.as_bytes()[..len].as_ref());
-            len
-        }
-    }
-}
-
-// Mock for src/numbers/conversions.h
-mod internal {
-    pub fn double_to_string_view(value: f64, buffer: &mut String) -> &str {
-        write!(buffer, "{}", value).unwrap();
-        buffer.as_str()
-    }
-}
-
-// Mock for base::vector.h
-mod base {
-    pub struct EmbeddedVector<T, const N: usize> {
-        data: [Option<T>; N],
-        size: usize,
-    }
-
-    impl<T: Copy + Default, const N: usize> EmbeddedVector<T, N> {
-        pub fn new() -> Self {
-            EmbeddedVector {
-                data: [None; N],
-                size: 0,
-            }
-        }
-    }
-}
+// Copyright 2016 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-// Mock for perfetto protos
-#[cfg(feature = "perfetto")]
-mod perfetto {
-    pub mod protos {
-        pub mod pbzero {
-            pub struct DebugAnnotation {
-                legacy_json_value: String,
-            }
+use std::fmt::Write as _;
 
-            impl DebugAnnotation {
-                pub fn new() -> Self {
-                    DebugAnnotation {
-                        legacy_json_value: String::new(),
-                    }
-                }
-                pub fn set_legacy_json_value(&mut self, value: String) {
-                    self.legacy_json_value = value;
-                }
-            }
-        }
-    }
-}
+use crate::libplatform::tracing::trace_object::ConvertableToTraceFormat;
 
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 enum StackType {
     Dict,
     Array,
 }
 
-struct TracedValue {
+/// A JSON value built up incrementally for use as a trace event argument,
+/// e.g. the details of a GC or a code object. The top level is always a
+/// dictionary; nested dictionaries and arrays are opened and closed with
+/// the begin_/end_ methods.
+pub struct TracedValue {
     data: String,
     first_item: bool,
     nesting_stack: Vec<StackType>,
@@ -76,49 +24,29 @@ struct TracedValue {
 
 impl TracedValue {
     pub fn create() -> Box<TracedValue> {
-        let mut tv = TracedValue {
-            data: String::new(),
-            first_item: true,
-            nesting_stack: Vec::new(),
-        };
-        tv.nesting_stack.push(StackType::Dict);
-        Box::new(tv)
+        Box::new(TracedValue { data: String::new(), first_item: true, nesting_stack: vec![StackType::Dict] })
     }
 
     fn dcheck_current_container_is(&self, x: StackType) {
         debug_assert_eq!(Some(&x), self.nesting_stack.last());
     }
 
-    fn dcheck_container_stack_depth_eq(&self, x: usize) {
-        debug_assert_eq!(x, self.nesting_stack.len());
-    }
-
-    fn debug_push_container(&mut self, x: StackType) {
-        self.nesting_stack.push(x);
-    }
-
-    fn debug_pop_container(&mut self) {
-        self.nesting_stack.pop();
-    }
-
     pub fn set_integer(&mut self, name: &str, value: i32) {
         self.dcheck_current_container_is(StackType::Dict);
         self.write_name(name);
-        self.data.push_str(&value.to_string());
+        let _ = write!(self.data, "{value}");
     }
 
     pub fn set_unsigned_integer(&mut self, name: &str, value: u64) {
         self.dcheck_current_container_is(StackType::Dict);
         self.write_name(name);
-        self.data.push_str(&value.to_string());
+        let _ = write!(self.data, "{value}");
     }
 
     pub fn set_double(&mut self, name: &str, value: f64) {
         self.dcheck_current_container_is(StackType::Dict);
         self.write_name(name);
-        let mut buffer = String::new();
-        internal::double_to_string_view(value, &mut buffer);
-        self.data.push_str(&buffer);
+        self.append_double_value(value);
     }
 
     pub fn set_boolean(&mut self, name: &str, value: bool) {
@@ -136,14 +64,12 @@ impl TracedValue {
     pub fn set_value(&mut self, name: &str, value: &TracedValue) {
         self.dcheck_current_container_is(StackType::Dict);
         self.write_name(name);
-        let mut tmp = String::new();
-        value.append_as_trace_format(&mut tmp);
-        self.data.push_str(&tmp);
+        value.append_as_trace_format(&mut self.data);
     }
 
     pub fn begin_dictionary(&mut self, name: &str) {
         self.dcheck_current_container_is(StackType::Dict);
-        self.debug_push_container(StackType::Dict);
+        self.nesting_stack.push(StackType::Dict);
         self.write_name(name);
         self.data.push('{');
         self.first_item = true;
@@ -151,7 +77,7 @@ impl TracedValue {
 
     pub fn begin_array(&mut self, name: &str) {
         self.dcheck_current_container_is(StackType::Dict);
-        self.debug_push_container(StackType::Array);
+        self.nesting_stack.push(StackType::Array);
         self.write_name(name);
         self.data.push('[');
         self.first_item = true;
@@ -160,15 +86,13 @@ impl TracedValue {
     pub fn append_integer(&mut self, value: i32) {
         self.dcheck_current_container_is(StackType::Array);
         self.write_comma();
-        self.data.push_str(&value.to_string());
+        let _ = write!(self.data, "{value}");
     }
 
     pub fn append_double(&mut self, value: f64) {
         self.dcheck_current_container_is(StackType::Array);
         self.write_comma();
-        let mut buffer = String::new();
-        internal::double_to_string_view(value, &mut buffer);
-        self.data.push_str(&buffer);
+        self.append_double_value(value);
     }
 
     pub fn append_boolean(&mut self, value: bool) {
@@ -185,7 +109,7 @@ impl TracedValue {
 
     pub fn begin_dictionary_no_name(&mut self) {
         self.dcheck_current_container_is(StackType::Array);
-        self.debug_push_container(StackType::Dict);
+        self.nesting_stack.push(StackType::Dict);
         self.write_comma();
         self.data.push('{');
         self.first_item = true;
@@ -193,7 +117,7 @@ impl TracedValue {
 
     pub fn begin_array_no_name(&mut self) {
         self.dcheck_current_container_is(StackType::Array);
-        self.debug_push_container(StackType::Array);
+        self.nesting_stack.push(StackType::Array);
         self.write_comma();
         self.data.push('[');
         self.first_item = true;
@@ -201,14 +125,14 @@ impl TracedValue {
 
     pub fn end_dictionary(&mut self) {
         self.dcheck_current_container_is(StackType::Dict);
-        self.debug_pop_container();
+        self.nesting_stack.pop();
         self.data.push('}');
         self.first_item = false;
     }
 
     pub fn end_array(&mut self) {
         self.dcheck_current_container_is(StackType::Array);
-        self.debug_pop_container();
+        self.nesting_stack.pop();
         self.data.push(']');
         self.first_item = false;
     }
@@ -228,6 +152,18 @@ impl TracedValue {
         self.data.push_str("\":");
     }
 
+    /// JSON has no NaN or infinities, so they are written as strings like
+    /// the trace writers do.
+    fn append_double_value(&mut self, value: f64) {
+        if value.is_nan() {
+            self.data.push_str("\"NaN\"");
+        } else if value.is_infinite() {
+            self.data.push_str(if value > 0.0 { "\"Infinity\"" } else { "\"-Infinity\"" });
+        } else {
+            let _ = write!(self.data, "{value}");
+        }
+    }
+
     fn escape_and_append_string(&mut self, value: &str) {
         self.data.push('"');
         for c in value.chars() {
@@ -239,45 +175,63 @@ impl TracedValue {
                 '\t' => self.data.push_str("\\t"),
                 '"' => self.data.push_str("\\\""),
                 '\\' => self.data.push_str("\\\\"),
-                _ => {
-                    if c < '\x20' || c == '\x7F' {
-                        let mut number_buffer = [0u8; 8];
-                        let len = platform::os::snprintf(&mut number_buffer, "\\u%04X", c as u32);
-                        self.data.push_str(std::str::from_utf8(&number_buffer[..len]).unwrap());
-                    } else {
-                        self.data.push(c);
-                    }
+                c if c < '\x20' || c == '\x7F' => {
+                    let _ = write!(self.data, "\\u{:04X}", c as u32);
                 }
+                c => self.data.push(c),
             }
         }
         self.data.push('"');
     }
+}
 
+impl ConvertableToTraceFormat for TracedValue {
     fn append_as_trace_format(&self, out: &mut String) {
         out.push('{');
         out.push_str(&self.data);
         out.push('}');
     }
-
-    #[cfg(feature = "perfetto")]
-    pub fn add(&self, annotation: &mut perfetto::protos::pbzero::DebugAnnotation) {
-        let mut json = String::new();
-        json.push('{');
-        json.push_str(&self.data);
-        json.push('}');
-        annotation.set_legacy_json_value(json);
-    }
-
-    #[cfg(not(feature = "perfetto"))]
-    pub fn add(&self, _annotation: &mut ()) {
-        // Dummy implementation for when perfetto feature is disabled
-    }
 }
 
 impl Drop for TracedValue {
     fn drop(&mut self) {
-        self.dcheck_current_container_is(StackType::Dict);
-        self.debug_pop_container();
-        self.dcheck_container_stack_depth_eq(0);
+        if !std::thread::panicking() {
+            self.dcheck_current_container_is(StackType::Dict);
+            debug_assert_eq!(self.nesting_stack.len(), 1);
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_nested_dictionaries_and_arrays() {
+        let mut value = TracedValue::create();
+        value.set_string("type", "Scavenge");
+        value.begin_dictionary("heap");
+        value.set_unsigned_integer("used", 1 << 20);
+        value.begin_array("spaces");
+        value.begin_dictionary_no_name();
+        value.set_string("name", "new \"space\"\n");
+        value.set_boolean("shrunk", false);
+        value.end_dictionary();
+        value.append_double(0.5);
+        value.append_double(f64::NAN);
+        value.end_array();
+        value.end_dictionary();
+        value.set_integer("reason", -1);
+
+        let mut inner = TracedValue::create();
+        inner.set_double("ms", 2.0);
+        value.set_value("pause", &inner);
+
+        let mut out = String::new();
+        value.append_as_trace_format(&mut out);
+        assert_eq!(
+            out,
+            r#"{"type":"Scavenge","heap":{"used":1048576,"spaces":[{"name":"new \"space\"\n","shrunk":false},0.5,"NaN"]},"reason":-1,"pause":{"ms":2}}"#
+        );
+    }
+}
@@ -13,111 +13,63 @@
 //    should be as self-contained as possible to make it easy to audit the code.
 //
 // 2. Any changes must be reviewed by someone from the crash reporting
-//    or security team. Se OWNERS for suggested reviewers.
+//    or security team. See OWNERS for suggested reviewers.
 //
 // For more information, see https://goo.gl/yMeyUY.
 //
 // For the code that runs in the signal handler itself, see handler-inside.rs.
 
-use std::mem::MaybeUninit;
-
-#[cfg(unix)]
-use libc::{
-    sigaction, sigemptyset, SA_ONSTACK, SA_SIGINFO, SIG_DFL, SIG_ERR,
-};
-
-#[cfg(target_os = "linux")]
-const SIGSEGV: i32 = libc::SIGSEGV;
-#[cfg(target_os = "macos")]
-const SIGSEGV: i32 = libc::SIGSEGV;
-
-// The TH_CHECK macro is assumed to do nothing in this Rust translation,
-// since Rust has stronger compile-time guarantees.
-
-// V8_TRAP_HANDLER_SUPPORTED is assumed to be always true.
-// In a real-world scenario, you'd conditionally compile the code
-// using #[cfg(feature = "trap_handler")] or similar.
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
 
-pub mod trap_handler {
-    use super::*;
-
-    #[cfg(unix)]
-    static mut G_OLD_HANDLER: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
-
-    // When using the default signal handler, we save the old one to restore in case
-    // V8 chooses not to handle the signal.
-    static mut G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED: bool = false;
-
-    // Placeholder for HandleSignal function, which would be defined in
-    // handler-inside-posix.rs or a similar module.
-    #[cfg(unix)]
-    extern "C" {
-        fn HandleSignal(
-            signum: libc::c_int,
-            siginfo: *mut libc::siginfo_t,
-            context: *mut libc::c_void,
-        );
-    }
-
-    #[cfg(unix)]
-    pub fn register_default_trap_handler() -> bool {
-        unsafe {
-            if G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED {
-                return false;
-            }
-
-            let mut action: libc::sigaction = std::mem::zeroed();
-            action.sa_sigaction = HandleSignal;
-            action.sa_flags = SA_SIGINFO | SA_ONSTACK as i32;
-            sigemptyset(&mut action.sa_mask as *mut libc::sigset_t);
-
-            if sigaction(SIGSEGV, &action, G_OLD_HANDLER.as_mut_ptr()) != 0 {
-                return false;
-            }
-
-            // Sanitizers often prevent us from installing our own signal handler. Attempt
-            // to detect this and if so, refuse to enable trap handling.
-            //
-            // TODO(chromium:830894): Remove this once all bots support custom signal
-            // handlers.
-            #[cfg(any(
-                feature = "address_sanitizer",
-                feature = "memory_sanitizer",
-                feature = "thread_sanitizer",
-                feature = "leak_sanitizer",
-                feature = "undefined_sanitizer"
-            ))]
-            {
-                let mut installed_handler: libc::sigaction = std::mem::zeroed();
-                assert_eq!(sigaction(SIGSEGV, std::ptr::null(), &mut installed_handler), 0);
-
-                // This comparison is not directly possible in Rust because function pointers
-                // are not comparable. A more robust solution would involve registering
-                // a token and comparing against that.
-                //
-                // For now, just always enable.
-                // if installed_handler.sa_sigaction != HandleSignal {
-                //     eprintln!(
-                //         "WARNING: sanitizers are preventing signal handler installation. \
-                //          Trap handlers are disabled."
-                //     );
-                //     return false;
-                // }
-            }
-
-            G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED = true;
-            true
-        }
+use std::cell::UnsafeCell;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::trap_handler::handler_inside_posix::{handle_signal, K_OOB_SIGNAL};
+
+/// The handler that was installed before ours. The signal handler restores it
+/// when a fault is not ours to handle, so it must be readable without locks.
+struct OldHandler(UnsafeCell<MaybeUninit<libc::sigaction>>);
+
+// Only written while no trap handler is registered.
+unsafe impl Sync for OldHandler {}
+
+static G_OLD_HANDLER: OldHandler = OldHandler(UnsafeCell::new(MaybeUninit::uninit()));
+
+// When using the default signal handler, we save the old one to restore in case
+// V8 chooses not to handle the signal.
+static G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+pub fn register_default_trap_handler() -> bool {
+    assert!(!G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED.load(Ordering::Relaxed));
+
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    action.sa_sigaction = handle_signal as *const () as usize;
+    // Use SA_ONSTACK so that iff an alternate signal stack was registered via
+    // sigaltstack, that one is used for handling the signal instead of the
+    // default stack. This can be useful if for example the stack pointer is
+    // corrupted or a stack overflow is triggered as that may cause the trap
+    // handler to crash, which would be hard to debug.
+    action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+    // {sigaction} installs a new custom segfault handler. On success, it returns
+    // 0. If we get a nonzero value, we report an error to the caller by returning
+    // false.
+    if unsafe { libc::sigaction(K_OOB_SIGNAL, &action, (*G_OLD_HANDLER.0.get()).as_mut_ptr()) } != 0 {
+        return false;
     }
 
-    #[cfg(unix)]
-    pub fn remove_trap_handler() {
-        unsafe {
-            if G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED {
-                if sigaction(SIGSEGV, G_OLD_HANDLER.as_ptr(), std::ptr::null_mut()) == 0 {
-                    G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED = false;
-                }
-            }
+    G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED.store(true, Ordering::Release);
+    true
+}
+
+/// Restores the signal handler that was installed before ours. Safe to call
+/// from the signal handler.
+pub fn remove_trap_handler() {
+    if G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED.load(Ordering::Acquire) {
+        let old_handler = unsafe { (*G_OLD_HANDLER.0.get()).as_ptr() };
+        if unsafe { libc::sigaction(K_OOB_SIGNAL, old_handler, std::ptr::null_mut()) } == 0 {
+            G_IS_DEFAULT_SIGNAL_HANDLER_REGISTERED.store(false, Ordering::Release);
         }
     }
-}
\ No newline at end of file
+}
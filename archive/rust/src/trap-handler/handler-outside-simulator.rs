@@ -1,79 +1,27 @@
-#![cfg(all(target_arch = "x86_64", feature = "trap-handler"))]
+// Copyright 2021 the V8 project authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
 
-#[cfg(target_os = "macos")]
-macro_rules! symbol {
-    ($name:ident) => {
-        concat!("_", stringify!($name))
-    };
-}
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
 
-#[cfg(not(target_os = "macos"))]
-macro_rules! symbol {
-    ($name:ident) => {
-        stringify!($name)
-    };
-}
-
-// This Rust code provides the equivalent assembly code using inline assembly.
-// It's functionally equivalent to the original C++'s asm block.
-
-#[cfg(target_os = "windows")]
-#[rustfmt::skip]
-#[naked]
-pub unsafe extern "C" fn v8_internal_simulator_ProbeMemory() -> i32 {
-    // First parameter (address) passed in %rdi on Linux/Mac, and %rcx on Windows.
-    // The second parameter (pc) is unused here. It is read by the trap handler
-    // instead.
-    //
-    // movb (%rcx), %al
-    // xorl %eax, %eax
-    // ret
-    core::arch::asm!(
-        ".att_syntax",
-        ".globl {sym}",
-        "{sym}:",
-        "movb (%rcx), %al",
-        "xorl %eax, %eax",
-        "ret",
-        sym = sym v8_internal_simulator_ProbeMemory,
-        options(noreturn)
-    )
-}
-
-#[cfg(not(target_os = "windows"))]
-#[rustfmt::skip]
-#[naked]
-pub unsafe extern "C" fn v8_internal_simulator_ProbeMemory() -> i32 {
-    // First parameter (address) passed in %rdi on Linux/Mac, and %rcx on Windows.
+// Define the ProbeMemory function declared in trap-handler-simulator.rs.
+std::arch::global_asm!(
+    ".pushsection .text",
+    ".globl v8_internal_simulator_ProbeMemory",
+    ".type v8_internal_simulator_ProbeMemory, @function",
+    "v8_internal_simulator_ProbeMemory:",
+    // First parameter (address) passed in %rdi.
     // The second parameter (pc) is unused here. It is read by the trap handler
     // instead.
-    //
-    // movb (%rdi), %al
-    // xorl %eax, %eax
-    // ret
-    core::arch::asm!(
-        ".att_syntax",
-        ".globl {sym}",
-        "{sym}:",
-        "movb (%rdi), %al",
-        "xorl %eax, %eax",
-        "ret",
-        sym = sym v8_internal_simulator_ProbeMemory,
-        options(noreturn)
-    )
-}
-
-#[rustfmt::skip]
-#[naked]
-pub unsafe extern "C" fn v8_simulator_probe_memory_continuation() {
+    "    mov al, byte ptr [rdi]",
+    // Return 0 on success.
+    "    xor eax, eax",
+    // Place an additional label that can be used by the trap handler to
+    // continue execution.
+    ".globl v8_simulator_probe_memory_continuation",
+    "v8_simulator_probe_memory_continuation:",
     // If the trap handler continues here, it wrote the landing pad in %rax.
-    // ret
-    core::arch::asm!(
-        ".att_syntax",
-        ".globl {sym}",
-        "{sym}:",
-        "ret",
-        sym = sym v8_simulator_probe_memory_continuation,
-        options(noreturn)
-    )
-}
\ No newline at end of file
+    "    ret",
+    ".size v8_internal_simulator_ProbeMemory, . - v8_internal_simulator_ProbeMemory",
+    ".popsection",
+);
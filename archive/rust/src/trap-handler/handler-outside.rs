@@ -20,408 +20,312 @@
 // For the code that runs in the trap handler itself, see handler-inside.rs.
 
 use std::mem;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Mutex;
-
-mod trap_handler_internal;
-pub mod trap_handler;
-
-// Assume that `MetadataLock` and `SandboxRecordsLock` are OS-specific mutexes that we cannot
-// accurately reproduce without target platform details.
-#[derive(Default)]
-struct MetadataLock {
-    mutex: Mutex<()>,
-}
-
-impl MetadataLock {
-    fn new() -> Self {
-        MetadataLock {
-            mutex: Mutex::new(()),
-        }
-    }
-}
-
-#[derive(Default)]
-struct SandboxRecordsLock {
-    mutex: Mutex<()>,
-}
-
-impl SandboxRecordsLock {
-    fn new() -> Self {
-        SandboxRecordsLock {
-            mutex: Mutex::new(()),
-        }
-    }
-}
-
-#[derive(Copy, Clone, Debug)]
-pub struct ProtectedInstructionData {
-    pub instr_offset: usize,
-}
-
-#[derive(Debug)]
-#[repr(C)]
-pub struct CodeProtectionInfo {
-    pub base: usize,
-    pub size: usize,
-    pub num_protected_instructions: usize,
-    pub instructions: [ProtectedInstructionData; 0], // Flexible array member, needs custom allocation.
-}
-
-#[derive(Debug)]
-struct CodeProtectionInfoListEntry {
-    code_info: *mut CodeProtectionInfo,
-    next_free: usize,
-}
-
-static mut G_NEXT_CODE_OBJECT: usize = 0;
-
-#[cfg(feature = "slow_dchecks")]
-const K_ENABLE_SLOW_CHECKS: bool = true;
-
-#[cfg(not(feature = "slow_dchecks"))]
-const K_ENABLE_SLOW_CHECKS: bool = false;
-
+use std::sync::atomic::Ordering;
+
+use crate::trap_handler::handler_shared::{
+    G_CODE_OBJECTS, G_LANDING_PAD, G_RECOVERED_TRAP_COUNT, G_SANDBOX_RECORDS, G_THREAD_IN_WASM_CODE,
+};
+use crate::trap_handler::trap_handler::{
+    ProtectedInstructionData, G_CAN_ENABLE_TRAP_HANDLER, G_IS_TRAP_HANDLER_ENABLED, K_INVALID_INDEX,
+    V8_TRAP_HANDLER_SUPPORTED,
+};
+use crate::trap_handler::trap_handler_internal::{
+    CodeObjects, CodeProtectionInfo, CodeProtectionInfoListEntry, SandboxRecord,
+};
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub use crate::trap_handler::handler_outside_posix::{register_default_trap_handler, remove_trap_handler};
+
+const K_ENABLE_SLOW_CHECKS: bool = cfg!(debug_assertions);
+
+/// The table of code objects starts with this many entries and grows by
+/// `K_CODE_OBJECT_GROWTH_FACTOR` whenever it fills up.
 const K_INITIAL_CODE_OBJECT_SIZE: usize = 1024;
 const K_CODE_OBJECT_GROWTH_FACTOR: usize = 2;
-const K_INVALID_INDEX: i32 = -1;
 
 fn handler_data_size(num_protected_instructions: usize) -> usize {
-    mem::size_of::<CodeProtectionInfo>() +
-        num_protected_instructions * mem::size_of::<ProtectedInstructionData>()
+    mem::size_of::<CodeProtectionInfo>() + num_protected_instructions * mem::size_of::<ProtectedInstructionData>()
 }
 
-#[cfg(debug_assertions)]
-fn is_disjoint(a: *const CodeProtectionInfo, b: *const CodeProtectionInfo) -> bool {
-    if a.is_null() || b.is_null() {
-        return true;
-    }
-    unsafe {
-        (*a).base >= (*b).base + (*b).size || (*b).base >= (*a).base + (*a).size
-    }
+fn is_disjoint(a: &CodeProtectionInfo, b: &CodeProtectionInfo) -> bool {
+    a.base >= b.base + b.size || b.base >= a.base + a.size
 }
 
-#[cfg(debug_assertions)]
-fn verify_code_range_is_disjoint(code_info: *const CodeProtectionInfo) {
-    unsafe {
-        for i in 0..G_NUM_CODE_OBJECTS {
-            trap_handler_internal::th_dcheck(is_disjoint(code_info, G_CODE_OBJECTS[i].code_info));
+/// Verify that the code range does not overlap any that have already been
+/// registered.
+fn verify_code_range_is_disjoint(code_objects: &CodeObjects, code_info: &CodeProtectionInfo) {
+    for entry in code_objects.as_slice() {
+        if !entry.code_info.is_null() {
+            assert!(is_disjoint(code_info, unsafe { &*entry.code_info }));
         }
     }
 }
 
-#[cfg(debug_assertions)]
-fn validate_code_objects() {
-    unsafe {
-        for i in 0..G_NUM_CODE_OBJECTS {
-            let data = G_CODE_OBJECTS[i].code_info;
-
-            if data.is_null() {
-                continue;
-            }
-
-            for j in 0..(*data).num_protected_instructions {
-                trap_handler_internal::th_dcheck((*data).instructions[j].instr_offset >= 0);
-                trap_handler_internal::th_dcheck((*data).instructions[j].instr_offset < (*data).size);
-            }
-        }
-
-        let mut free_count: usize = 0;
-        let mut i: usize = G_NEXT_CODE_OBJECT;
-        while i != G_NUM_CODE_OBJECTS {
-            trap_handler_internal::th_dcheck(i < G_NUM_CODE_OBJECTS);
-            free_count += 1;
-            trap_handler_internal::th_dcheck(free_count <= G_NUM_CODE_OBJECTS);
-
-            i = G_CODE_OBJECTS[i].next_free;
+fn validate_code_objects(code_objects: &CodeObjects) {
+    // Sanity-check the code objects
+    let mut free_count2 = 0;
+    for entry in code_objects.as_slice() {
+        if entry.code_info.is_null() {
+            free_count2 += 1;
+            continue;
         }
-
-        let mut free_count2: usize = 0;
-        for i in 0..G_NUM_CODE_OBJECTS {
-            if G_CODE_OBJECTS[i].code_info.is_null() {
-                free_count2 += 1;
-            }
+        let data = unsafe { &*entry.code_info };
+        for instruction in data.instructions() {
+            assert!((instruction.instr_offset as usize) < data.size);
         }
+    }
 
-        trap_handler_internal::th_dcheck(free_count == free_count2);
+    // Check the validity of the free list.
+    let mut free_count = 0;
+    let mut i = code_objects.next_free;
+    while i != code_objects.num_entries {
+        assert!(i < code_objects.num_entries);
+        free_count += 1;
+        // This check will fail if we encounter a cycle.
+        assert!(free_count <= code_objects.num_entries);
+        i = code_objects.as_slice()[i].next_free;
     }
-}
 
-pub fn create_handler_data(
-    base: usize,
-    size: usize,
-    num_protected_instructions: usize,
-    protected_instructions: &[ProtectedInstructionData],
-) -> *mut CodeProtectionInfo {
-    let alloc_size = handler_data_size(num_protected_instructions);
+    // Check that all free entries are reachable via the free list.
+    assert_eq!(free_count, free_count2);
+}
 
+fn create_handler_data(base: usize, size: usize, protected_instructions: &[ProtectedInstructionData]) -> *mut CodeProtectionInfo {
+    // The trap handler reads this without going through the Rust allocator,
+    // so allocate it, including the inline instruction list, with malloc.
+    let data = unsafe { libc::malloc(handler_data_size(protected_instructions.len())) } as *mut CodeProtectionInfo;
+    if data.is_null() {
+        return data;
+    }
     unsafe {
-        let data = libc::malloc(alloc_size) as *mut CodeProtectionInfo;
-
-        if data.is_null() {
-            return std::ptr::null_mut();
-        }
-
         (*data).base = base;
         (*data).size = size;
-        (*data).num_protected_instructions = num_protected_instructions;
-
-        if num_protected_instructions > 0 {
-            let dest = (*data).instructions.as_mut_ptr() as *mut u8;
-            let src = protected_instructions.as_ptr() as *const u8;
-            libc::memcpy(
-                dest,
-                src,
-                num_protected_instructions * mem::size_of::<ProtectedInstructionData>(),
-            );
-        }
-
-        data
+        (*data).num_protected_instructions = protected_instructions.len();
+        std::ptr::copy_nonoverlapping(
+            protected_instructions.as_ptr(),
+            (*data).instructions.as_mut_ptr(),
+            protected_instructions.len(),
+        );
     }
+    data
 }
 
-pub fn register_handler_data(
-    base: usize,
-    size: usize,
-    num_protected_instructions: usize,
-    protected_instructions: &[ProtectedInstructionData],
-) -> i32 {
-    let data = create_handler_data(
-        base,
-        size,
-        num_protected_instructions,
-        protected_instructions,
-    );
-
+/// Registers `protected_instructions` of the code object at
+/// `[base, base + size)` with the trap handler. Returns an index to pass to
+/// `release_handler_data`, or `K_INVALID_INDEX` if the table is full.
+pub fn register_handler_data(base: usize, size: usize, protected_instructions: &[ProtectedInstructionData]) -> i32 {
+    let data = create_handler_data(base, size, protected_instructions);
     if data.is_null() {
-        std::process::abort();
+        panic!("Fatal process out of memory: trap handler data");
     }
 
-    let lock = MetadataLock::new();
-    let _guard = lock.mutex.lock().unwrap();
+    let mut code_objects = G_CODE_OBJECTS.lock();
 
     if K_ENABLE_SLOW_CHECKS {
-        #[cfg(debug_assertions)]
-        verify_code_range_is_disjoint(data);
+        verify_code_range_is_disjoint(&code_objects, unsafe { &*data });
     }
 
-    unsafe {
-        let mut i = G_NEXT_CODE_OBJECT;
-
-        let int_max: usize = i32::MAX as usize;
+    let i = code_objects.next_free;
 
-        if i == G_NUM_CODE_OBJECTS {
-            let mut new_size: usize = if G_NUM_CODE_OBJECTS > 0 {
-                G_NUM_CODE_OBJECTS * K_CODE_OBJECT_GROWTH_FACTOR
-            } else {
-                K_INITIAL_CODE_OBJECT_SIZE
-            };
-
-            if new_size > int_max {
-                new_size = int_max;
-            }
-            if new_size == G_NUM_CODE_OBJECTS {
-                libc::free(data as *mut libc::c_void);
-                return K_INVALID_INDEX;
-            }
+    // Grow the table if needed. Indices have to fit in an i32.
+    if i == code_objects.num_entries {
+        let int_max = i32::MAX as usize;
+        let new_size = if code_objects.num_entries > 0 {
+            (code_objects.num_entries * K_CODE_OBJECT_GROWTH_FACTOR).min(int_max)
+        } else {
+            K_INITIAL_CODE_OBJECT_SIZE
+        };
+        if new_size == code_objects.num_entries {
+            // We're out of space and there's no room to grow. Give up.
+            unsafe { libc::free(data as *mut libc::c_void) };
+            return K_INVALID_INDEX;
+        }
 
-            G_CODE_OBJECTS = libc::realloc(
-                G_CODE_OBJECTS as *mut libc::c_void,
+        // The signal handler cannot run concurrently, since we hold the lock,
+        // so it is fine to move the table.
+        let entries = unsafe {
+            libc::realloc(
+                code_objects.entries as *mut libc::c_void,
                 mem::size_of::<CodeProtectionInfoListEntry>() * new_size,
-            ) as *mut CodeProtectionInfoListEntry;
-
-            if G_CODE_OBJECTS.is_null() {
-                std::process::abort();
-            }
-
-            let start = G_CODE_OBJECTS.add(G_NUM_CODE_OBJECTS) as *mut u8;
-            let size = mem::size_of::<CodeProtectionInfoListEntry>() * (new_size - G_NUM_CODE_OBJECTS);
-            libc::memset(start as *mut libc::c_void, 0, size);
-
-            for j in G_NUM_CODE_OBJECTS..new_size {
-                G_CODE_OBJECTS.add(j).write(CodeProtectionInfoListEntry {
-                    code_info: std::ptr::null_mut(),
-                    next_free: j + 1,
-                });
+            )
+        } as *mut CodeProtectionInfoListEntry;
+        if entries.is_null() {
+            panic!("Fatal process out of memory: trap handler code object table");
+        }
+        for j in code_objects.num_entries..new_size {
+            unsafe {
+                entries.add(j).write(CodeProtectionInfoListEntry { code_info: std::ptr::null_mut(), next_free: j + 1 });
             }
-            G_NUM_CODE_OBJECTS = new_size;
         }
+        code_objects.entries = entries;
+        code_objects.num_entries = new_size;
+    }
 
-        trap_handler_internal::th_dcheck(G_CODE_OBJECTS[i].code_info.is_null());
-
-        G_NEXT_CODE_OBJECT = G_CODE_OBJECTS[i].next_free;
-
-        if i <= int_max {
-            G_CODE_OBJECTS[i].code_info = data;
-
-            if K_ENABLE_SLOW_CHECKS {
-                #[cfg(debug_assertions)]
-                validate_code_objects();
-            }
+    let entry = &mut code_objects.as_mut_slice()[i];
+    debug_assert!(entry.code_info.is_null());
+    entry.code_info = data;
+    code_objects.next_free = entry.next_free;
 
-            return i as i32;
-        } else {
-            libc::free(data as *mut libc::c_void);
-            return K_INVALID_INDEX;
-        }
+    if K_ENABLE_SLOW_CHECKS {
+        validate_code_objects(&code_objects);
     }
+
+    i as i32
 }
 
 pub fn release_handler_data(index: i32) {
     if index == K_INVALID_INDEX {
         return;
     }
-    trap_handler_internal::th_dcheck(index >= 0);
-
-    let mut data: *mut CodeProtectionInfo = std::ptr::null_mut();
-    unsafe {
-        let lock = MetadataLock::new();
-        let _guard = lock.mutex.lock().unwrap();
+    debug_assert!(index >= 0);
 
-        data = G_CODE_OBJECTS[index as usize].code_info;
-        G_CODE_OBJECTS[index as usize].code_info = std::ptr::null_mut();
-
-        G_CODE_OBJECTS[index as usize].next_free = G_NEXT_CODE_OBJECT;
-        G_NEXT_CODE_OBJECT = index as usize;
+    let data = {
+        let mut code_objects = G_CODE_OBJECTS.lock();
+        let next_free = code_objects.next_free;
+        let entry = &mut code_objects.as_mut_slice()[index as usize];
+        let data = entry.code_info;
+        entry.code_info = std::ptr::null_mut();
+        entry.next_free = next_free;
+        code_objects.next_free = index as usize;
 
         if K_ENABLE_SLOW_CHECKS {
-            #[cfg(debug_assertions)]
-            validate_code_objects();
+            validate_code_objects(&code_objects);
         }
-    }
-
-    trap_handler_internal::th_dcheck(!data.is_null());
-
+        data
+    };
+    // Although the lock is no longer held, the data is unreachable for the
+    // trap handler and can be freed.
+    debug_assert!(!data.is_null());
     unsafe { libc::free(data as *mut libc::c_void) };
 }
 
-#[derive(Debug)]
-struct SandboxRecord {
-    base: usize,
-    size: usize,
-    next: *mut SandboxRecord,
-}
-
-static mut G_SANDBOX_RECORDS_HEAD: *mut SandboxRecord = std::ptr::null_mut();
-
+/// Restricts the trap handler to faulting accesses inside
+/// `[base, base + size)` and any other registered sandbox.
 pub fn register_v8_sandbox(base: usize, size: usize) -> bool {
-    let lock = SandboxRecordsLock::new();
-    let _guard = lock.mutex.lock().unwrap();
-
-    unsafe {
-        #[cfg(debug_assertions)]
-        {
-            let mut current = G_SANDBOX_RECORDS_HEAD;
-            while !current.is_null() {
-                trap_handler_internal::th_dcheck((*current).base != base);
-                current = (*current).next;
-            }
-        }
-
-        let new_record = libc::malloc(mem::size_of::<SandboxRecord>()) as *mut SandboxRecord;
-        if new_record.is_null() {
-            return false;
-        }
-
-        (*new_record).base = base;
-        (*new_record).size = size;
-        (*new_record).next = G_SANDBOX_RECORDS_HEAD;
-        G_SANDBOX_RECORDS_HEAD = new_record;
-        return true;
+    let mut head = G_SANDBOX_RECORDS.lock();
+
+    // Sandboxes must not overlap.
+    let mut current = *head;
+    while !current.is_null() {
+        let record = unsafe { &*current };
+        debug_assert!(base >= record.base + record.size || record.base >= base + size);
+        current = record.next;
     }
+
+    let record = Box::into_raw(Box::new(SandboxRecord { base, size, next: *head }));
+    *head = record;
+    true
 }
 
 pub fn unregister_v8_sandbox(base: usize, size: usize) {
-    let lock = SandboxRecordsLock::new();
-    let _guard = lock.mutex.lock().unwrap();
-
-    unsafe {
-        let mut current = G_SANDBOX_RECORDS_HEAD;
-        let mut previous: *mut SandboxRecord = std::ptr::null_mut();
-        while !current.is_null() {
-            if (*current).base == base {
-                break;
-            }
-            previous = current;
-            current = (*current).next;
+    let mut head = G_SANDBOX_RECORDS.lock();
+
+    let mut previous: *mut SandboxRecord = std::ptr::null_mut();
+    let mut current = *head;
+    while !current.is_null() {
+        let record = unsafe { &*current };
+        if record.base == base {
+            break;
         }
-
-        trap_handler_internal::th_check(!current.is_null());
-        trap_handler_internal::th_check((*current).size == size);
-
-        if !previous.is_null() {
-            (*previous).next = (*current).next;
-        } else {
-            G_SANDBOX_RECORDS_HEAD = (*current).next;
-        }
-        libc::free(current as *mut libc::c_void);
+        previous = current;
+        current = record.next;
+    }
+    // The sandbox must have been registered.
+    assert!(!current.is_null());
+    let record = unsafe { Box::from_raw(current) };
+    assert_eq!(record.size, size);
+
+    if previous.is_null() {
+        *head = record.next;
+    } else {
+        unsafe { (*previous).next = record.next };
     }
 }
 
-static mut G_THREAD_IN_WASM_CODE: i32 = 0;
-
+/// Returns the address of the current thread's thread-in-wasm flag, for
+/// generated code that sets and clears the flag itself.
 pub fn get_thread_in_wasm_thread_local_address() -> *mut i32 {
-    unsafe { &mut G_THREAD_IN_WASM_CODE }
+    G_THREAD_IN_WASM_CODE.with(|flag| flag.as_ptr())
 }
 
-static G_RECOVERED_TRAP_COUNT: AtomicUsize = AtomicUsize::new(0);
-
 pub fn get_recovered_trap_count() -> usize {
     G_RECOVERED_TRAP_COUNT.load(Ordering::Relaxed)
 }
 
-// This version is provided for systems that do not support trap handlers.
-// Otherwise, the correct one should be implemented in the appropriate
-// platform-specific handler-outside.rs.
-#[cfg(not(feature = "v8_trap_handler_supported"))]
-pub fn register_default_trap_handler() -> bool {
-    false
-}
-
-#[cfg(not(feature = "v8_trap_handler_supported"))]
-pub fn remove_trap_handler() {}
-
-static mut G_IS_TRAP_HANDLER_ENABLED: bool = false;
-static G_CAN_ENABLE_TRAP_HANDLER: AtomicBool = AtomicBool::new(true);
-static G_LANDING_PAD: AtomicUsize = AtomicUsize::new(0);
-
+/// Enables trap handling for Wasm out-of-bounds accesses. If `use_v8_handler`
+/// is false, the embedder installs its own signal handler and forwards
+/// signals to `try_handle_signal`. Returns whether trap handling is enabled.
 pub fn enable_trap_handler(use_v8_handler: bool) -> bool {
-    let can_enable = G_CAN_ENABLE_TRAP_HANDLER.exchange(false, Ordering::Relaxed);
-    trap_handler_internal::th_check(can_enable);
-
-    #[cfg(not(feature = "v8_trap_handler_supported"))]
-    {
+    // We should only enable the trap handler once, and before any call to
+    // `is_trap_handler_enabled`. Enabling the trap handler late can lead to
+    // problems because code or objects might have been generated under the
+    // assumption that trap handlers are disabled.
+    let can_enable = G_CAN_ENABLE_TRAP_HANDLER.swap(false, Ordering::Relaxed);
+    // EnableTrapHandler called twice, or after IsTrapHandlerEnabled.
+    assert!(can_enable);
+
+    if !V8_TRAP_HANDLER_SUPPORTED {
         return false;
     }
-
-    unsafe {
-        if use_v8_handler {
-            G_IS_TRAP_HANDLER_ENABLED = trap_handler_internal::register_default_trap_handler();
-            return G_IS_TRAP_HANDLER_ENABLED;
-        }
-        G_IS_TRAP_HANDLER_ENABLED = true;
-        return true;
-    }
+    let enabled = !use_v8_handler || register_default_trap_handler();
+    G_IS_TRAP_HANDLER_ENABLED.store(enabled, Ordering::Relaxed);
+    enabled
 }
 
+/// Sets the code that faulting protected instructions continue at.
 pub fn set_landing_pad(landing_pad: usize) {
     G_LANDING_PAD.store(landing_pad, Ordering::Relaxed);
 }
 
-#[cfg(any(feature = "building_v8_shared_private", feature = "using_v8_shared_private"))]
 pub fn assert_thread_not_in_wasm() {
-    unsafe {
-        trap_handler_internal::th_dcheck(!G_IS_TRAP_HANDLER_ENABLED || G_THREAD_IN_WASM_CODE == 0);
-    }
+    // The flag can be set while the trap handler is disabled, but then it has
+    // no effect.
+    assert!(!G_IS_TRAP_HANDLER_ENABLED.load(Ordering::Relaxed) || G_THREAD_IN_WASM_CODE.with(|flag| flag.get()) == 0);
 }
 
-// Global mutable state. Access to these variables should be protected by a mutex.
-// The original C++ uses raw pointers and malloc/free.  This Rust code uses
-// raw pointers as well, but does not provide a safe abstraction.  The
-// reason for this is that memory allocations/deallocations are called from
-// outside the Rust environment and must not trigger Rust's borrow checker.
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn register_default_trap_handler() -> bool {
+    false
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+pub fn remove_trap_handler() {}
 
-// NOTE: All static mut variables must be initialized here because Rust only
-// allows assignment to static mut variables inside a const context.
-static mut G_CODE_OBJECTS: *mut CodeProtectionInfoListEntry = std::ptr::null_mut();
-static mut G_NUM_CODE_OBJECTS: usize = 0;
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trap_handler::handler_inside::{is_accessed_memory_covered, is_fault_address_covered};
+
+    #[test]
+    fn registered_protected_instructions_are_covered() {
+        let base = 0x7f00_dead_0000;
+        let instructions = [ProtectedInstructionData { instr_offset: 0x10 }, ProtectedInstructionData { instr_offset: 0x24 }];
+        let index = register_handler_data(base, 0x100, &instructions);
+        assert_ne!(index, K_INVALID_INDEX);
+
+        let recovered = get_recovered_trap_count();
+        assert!(is_fault_address_covered(base + 0x10));
+        assert!(is_fault_address_covered(base + 0x24));
+        assert!(!is_fault_address_covered(base + 0x14));
+        assert!(!is_fault_address_covered(base + 0x110));
+        assert!(get_recovered_trap_count() >= recovered + 2);
+
+        // Released entries are reused.
+        release_handler_data(index);
+        assert!(!is_fault_address_covered(base + 0x10));
+        let other = register_handler_data(base, 0x100, &instructions[..1]);
+        assert_ne!(other, K_INVALID_INDEX);
+        assert!(is_fault_address_covered(base + 0x10));
+        assert!(!is_fault_address_covered(base + 0x24));
+        release_handler_data(other);
+    }
+
+    #[test]
+    fn accessed_memory_must_be_inside_a_registered_sandbox() {
+        let base = 0x6000_0000_0000;
+        assert!(register_v8_sandbox(base, 0x1000));
+        assert!(is_accessed_memory_covered(base + 0x800));
+        assert!(!is_accessed_memory_covered(base + 0x1000));
+        unregister_v8_sandbox(base, 0x1000);
+    }
+}
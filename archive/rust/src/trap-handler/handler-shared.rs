@@ -17,83 +17,27 @@
 //
 // For more information, see https://goo.gl/yMeyUY.
 
-// src/trap-handler/trap-handler-internal.h is not provided, so stubbing it.
-mod trap_handler_internal {
-    pub struct CodeProtectionInfoListEntry {}
-    pub struct SandboxRecord {}
-}
+use std::cell::Cell;
+use std::sync::atomic::AtomicUsize;
 
-use std::sync::atomic::{AtomicBool, AtomicUsize, AtomicPtr, Ordering};
-use std::thread_local;
+use crate::trap_handler::trap_handler_internal::{MetadataLock, SandboxRecordsLock};
 
 thread_local! {
     /// We declare this as int rather than bool as a workaround for a glibc bug, in
     /// which the dynamic loader cannot handle executables whose TLS area is only
     /// 1 byte in size; see https://sourceware.org/bugzilla/show_bug.cgi?id=14898.
-    static G_THREAD_IN_WASM_CODE: i32 = 0;
-}
-
-pub static mut G_NUM_CODE_OBJECTS: usize = 0;
-pub static mut G_CODE_OBJECTS: *mut trap_handler_internal::CodeProtectionInfoListEntry = std::ptr::null_mut();
-pub static mut G_SANDBOX_RECORDS_HEAD: *mut trap_handler_internal::SandboxRecord = std::ptr::null_mut();
-pub static G_RECOVERED_TRAP_COUNT: AtomicUsize = AtomicUsize::new(0);
-pub static G_LANDING_PAD: AtomicUsize = AtomicUsize::new(0);
-
-pub struct MetadataLock {
-    spinlock_: AtomicBool,
+    /// The initializer is const so that reading the flag from the signal handler
+    /// never runs lazy TLS initialization.
+    pub static G_THREAD_IN_WASM_CODE: Cell<i32> = const { Cell::new(0) };
 }
 
-impl MetadataLock {
-    pub const fn new() -> Self {
-        MetadataLock {
-            spinlock_: AtomicBool::new(false),
-        }
-    }
-    pub fn lock(&self) {
-      G_THREAD_IN_WASM_CODE.with(|in_wasm| {
-          if *in_wasm != 0 {
-              std::process::abort();
-          }
-      });
-      while self.spinlock_.swap(true, Ordering::Acquire) {
-          std::hint::spin_loop();
-      }
-    }
-
-    pub fn unlock(&self) {
-        G_THREAD_IN_WASM_CODE.with(|in_wasm| {
-            if *in_wasm != 0 {
-                std::process::abort();
-            }
-        });
-        self.spinlock_.store(false, Ordering::Release);
-    }
-}
+/// The registered code objects, see `register_handler_data`.
+pub static G_CODE_OBJECTS: MetadataLock = MetadataLock::new();
 
-unsafe impl Sync for MetadataLock {}
-unsafe impl Send for MetadataLock {}
+/// The registered sandboxes, see `register_v8_sandbox`.
+pub static G_SANDBOX_RECORDS: SandboxRecordsLock = SandboxRecordsLock::new();
 
-pub struct SandboxRecordsLock {
-    spinlock_: AtomicBool,
-}
-
-impl SandboxRecordsLock {
-    pub const fn new() -> Self {
-        SandboxRecordsLock {
-            spinlock_: AtomicBool::new(false),
-        }
-    }
-
-    pub fn lock(&self) {
-        while self.spinlock_.swap(true, Ordering::Acquire) {
-          std::hint::spin_loop();
-        }
-    }
-
-    pub fn unlock(&self) {
-        self.spinlock_.store(false, Ordering::Release);
-    }
-}
+pub static G_RECOVERED_TRAP_COUNT: AtomicUsize = AtomicUsize::new(0);
 
-unsafe impl Sync for SandboxRecordsLock {}
-unsafe impl Send for SandboxRecordsLock {}
\ No newline at end of file
+/// Where the trap handler sends execution after a fault in protected code.
+pub static G_LANDING_PAD: AtomicUsize = AtomicUsize::new(0);
@@ -2,151 +2,182 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-pub mod trap_handler_internal {
-    use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
-    use std::sync::Mutex;
-
-    use super::trap_handler::ProtectedInstructionData;
-
-    /// This describes a chunk of code that the trap handler will be able to handle
-    /// faults in. {base} points to the beginning of the chunk, and {size} is the
-    /// number of bytes in the code chunk. The remainder of the struct is a list of
-    /// protected memory access instructions and an offset to a landing pad to handle
-    /// faults on that instruction.
-    #[repr(C)]
-    pub struct CodeProtectionInfo {
-        pub base: usize,
-        pub size: usize,
-        pub num_protected_instructions: usize,
-        pub instructions: [ProtectedInstructionData; 1], // Flexible array member, needs special handling
-    }
-
-    /// A spinlock-based mutex for protecting metadata.
-    pub struct MetadataLock {
-        _private: (),
-    }
-
-    impl MetadataLock {
-        // Initialize the spinlock
-        pub fn new() -> Self {
-            static SPINLOCK: AtomicBool = AtomicBool::new(false);
-            MetadataLock { _private: () }
-        }
+// This file should not be included (even transitively) by files outside of
+// src/trap-handler.
+
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::trap_handler::handler_shared::G_THREAD_IN_WASM_CODE;
+use crate::trap_handler::trap_handler::ProtectedInstructionData;
+
+/// This describes a chunk of code that the trap handler will be able to handle
+/// faults in. {base} points to the beginning of the chunk, and {size} is the
+/// number of bytes in the code chunk. The remainder of the struct is a list of
+/// protected memory access instructions, given as offsets from {base}.
+#[repr(C)]
+pub struct CodeProtectionInfo {
+    pub base: usize,
+    pub size: usize,
+    pub num_protected_instructions: usize,
+    pub instructions: [ProtectedInstructionData; 0],
+}
+
+impl CodeProtectionInfo {
+    pub fn instructions(&self) -> &[ProtectedInstructionData] {
+        // The instructions are allocated inline behind the header, see
+        // `create_handler_data`.
+        unsafe { std::slice::from_raw_parts(self.instructions.as_ptr(), self.num_protected_instructions) }
+    }
+}
+
+/// To enable constant time registration of handler data, we keep a free list of
+/// entries in the code object table. Each free entry contains the index of the
+/// next free entry in {next_free}; the list ends with the table size.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CodeProtectionInfoListEntry {
+    pub code_info: *mut CodeProtectionInfo,
+    pub next_free: usize,
+}
+
+/// The table of registered code objects. It is allocated with malloc so that
+/// the signal handler can walk it without touching the Rust allocator.
+pub struct CodeObjects {
+    pub entries: *mut CodeProtectionInfoListEntry,
+    pub num_entries: usize,
+    /// Index of the first free entry, or `num_entries` if the table is full.
+    pub next_free: usize,
+}
+
+// The table is only touched under the metadata lock.
+unsafe impl Send for CodeObjects {}
+
+impl CodeObjects {
+    pub const fn new() -> Self {
+        CodeObjects { entries: std::ptr::null_mut(), num_entries: 0, next_free: 0 }
+    }
 
-        // Acquire the lock
-        pub fn lock(&self) {
-            static SPINLOCK: AtomicBool = AtomicBool::new(false);
-            while SPINLOCK.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-                std::hint::spin_loop();
-            }
+    pub fn as_slice(&self) -> &[CodeProtectionInfoListEntry] {
+        if self.entries.is_null() {
+            return &[];
         }
+        unsafe { std::slice::from_raw_parts(self.entries, self.num_entries) }
+    }
 
-        // Release the lock
-        pub fn unlock(&self) {
-            static SPINLOCK: AtomicBool = AtomicBool::new(true);
-            SPINLOCK.store(false, Ordering::Release);
+    pub fn as_mut_slice(&mut self) -> &mut [CodeProtectionInfoListEntry] {
+        if self.entries.is_null() {
+            return &mut [];
         }
+        unsafe { std::slice::from_raw_parts_mut(self.entries, self.num_entries) }
     }
+}
 
-    /// To enable constant time registration of handler data, we keep a free list of
-    /// entries in the gCodeObjects table. Each entry contains a {next_free} field,
-    /// which can be used to figure out where the next entry should be inserted.
-    /// In order to avoid having to initialize all the links to start with, we use
-    /// 0 to indicate that this is a fresh, never-used list entry and that therefore
-    /// the next entry is known to be free. If {next_entry} is greater than zero,
-    /// then {next_entry - 1} is the index that we should insert into next.
-    #[repr(C)]
-    pub struct CodeProtectionInfoListEntry {
-        pub code_info: *mut CodeProtectionInfo,
-        pub next_free: usize,
+impl Default for CodeObjects {
+    fn default() -> Self {
+        Self::new()
     }
+}
+
+/// This list describes sandboxes as bases and sizes.
+pub struct SandboxRecord {
+    pub base: usize,
+    pub size: usize,
+    pub next: *mut SandboxRecord,
+}
 
-    lazy_static::lazy_static! {
-        pub static ref G_NUM_CODE_OBJECTS: AtomicUsize = AtomicUsize::new(0);
-        pub static ref G_CODE_OBJECTS: Mutex<Vec<CodeProtectionInfoListEntry>> = Mutex::new(Vec::new());
+/// A spinlock around data shared between the trap handler and the code that
+/// registers it. Neither std's Mutex nor any other lock that may allocate or
+/// park the thread is safe to take inside a signal handler.
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+unsafe impl<T: Send> Send for SpinLock<T> {}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        SpinLock { locked: AtomicBool::new(false), data: UnsafeCell::new(data) }
     }
 
-    /// This list describes sandboxes as bases and sizes.
-    #[repr(C)]
-    pub struct SandboxRecord {
-        pub base: usize,
-        pub size: usize,
-        pub next: *mut SandboxRecord,
+    fn acquire(&self) -> SpinLockGuard<'_, T> {
+        while self.locked.swap(true, Ordering::Acquire) {
+            std::hint::spin_loop();
+        }
+        SpinLockGuard { lock: self }
     }
+}
 
-    /// A spinlock-based mutex for protecting sandbox records.
-    pub struct SandboxRecordsLock {
-        _private: (),
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
     }
+}
 
-    impl SandboxRecordsLock {
-         // Initialize the spinlock
-         pub fn new() -> Self {
-            static SPINLOCK: AtomicBool = AtomicBool::new(false);
-            SandboxRecordsLock { _private: () }
-        }
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
 
-        // Acquire the lock
-        pub fn lock(&self) {
-             static SPINLOCK: AtomicBool = AtomicBool::new(false);
-            while SPINLOCK.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
-                std::hint::spin_loop();
-            }
-        }
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+/// Guards the code object table.
+pub struct MetadataLock(SpinLock<CodeObjects>);
 
-        // Release the lock
-        pub fn unlock(&self) {
-            static SPINLOCK: AtomicBool = AtomicBool::new(true);
-            SPINLOCK.store(false, Ordering::Release);
+impl MetadataLock {
+    pub const fn new() -> Self {
+        MetadataLock(SpinLock::new(CodeObjects::new()))
+    }
+
+    pub fn lock(&self) -> SpinLockGuard<'_, CodeObjects> {
+        // A thread running Wasm code must never take the lock: if it faulted
+        // while holding it, the trap handler would deadlock. The trap handler
+        // clears the flag before it takes the lock.
+        if G_THREAD_IN_WASM_CODE.with(|flag| flag.get()) != 0 {
+            std::process::abort();
         }
+        self.0.acquire()
     }
+}
 
-    lazy_static::lazy_static! {
-        pub static ref G_SANDBOX_RECORDS_HEAD: AtomicPtr<SandboxRecord> = AtomicPtr::new(std::ptr::null_mut());
-        pub static ref G_RECOVERED_TRAP_COUNT: AtomicUsize = AtomicUsize::new(0);
-        pub static ref G_LANDING_PAD: AtomicUsize = AtomicUsize::new(0);
+impl Default for MetadataLock {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    /// Searches the fault location table for an entry matching fault_addr. If found,
-    /// returns true, otherwise, returns false.
-    pub fn is_fault_address_covered(fault_addr: usize) -> bool {
-         let g_code_objects = G_CODE_OBJECTS.lock().unwrap();
-        for entry in g_code_objects.iter() {
-            if !entry.code_info.is_null() {
-                let code_info = unsafe { &*entry.code_info };
-                if fault_addr >= code_info.base && fault_addr < (code_info.base + code_info.size) {
-                    return true;
-                }
-            }
-        }
-        false
-    }
-
-    /// Checks whether the accessed memory is covered by the trap handler. In
-    /// particular, when the V8 sandbox is enabled, only faulting accesses to memory
-    /// inside the sandbox are handled by the trap handler since all Wasm memory
-    /// objects are inside the sandbox.
-    pub fn is_accessed_memory_covered(accessed_addr: usize) -> bool {
-        let sandbox_records_head = G_SANDBOX_RECORDS_HEAD.load(Ordering::Relaxed);
-        let mut current = sandbox_records_head;
-
-        while !current.is_null() {
-            let record = unsafe { &*current };
-            if accessed_addr >= record.base && accessed_addr < (record.base + record.size) {
-                return true;
-            }
-            current = record.next;
-        }
+/// Guards the list of sandbox records.
+pub struct SandboxRecordsLock(SpinLock<*mut SandboxRecord>);
+
+unsafe impl Sync for SandboxRecordsLock {}
 
-        false
+impl SandboxRecordsLock {
+    pub const fn new() -> Self {
+        SandboxRecordsLock(SpinLock::new(std::ptr::null_mut()))
+    }
+
+    /// Returns a guard over the head of the list.
+    pub fn lock(&self) -> SpinLockGuard<'_, *mut SandboxRecord> {
+        self.0.acquire()
     }
 }
 
-pub mod trap_handler {
-    #[repr(C)]
-    #[derive(Debug, Copy, Clone)]
-    pub struct ProtectedInstructionData {
-        pub instruction_offset: u32,
-        pub landing_pad_offset: u32,
+impl Default for SandboxRecordsLock {
+    fn default() -> Self {
+        Self::new()
     }
-}
\ No newline at end of file
+}
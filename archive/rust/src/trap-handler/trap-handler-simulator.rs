@@ -2,13 +2,14 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// This header defines the ProbeMemory function to be used by simulators to
+// This file defines the ProbeMemory function to be used by simulators to
 // trigger a signal at a defined location, before doing an actual memory access.
 
-#[cfg(feature = "trap_handler_via_simulator")]
-pub mod trap_handler_simulator {
-    use std::ffi::c_uint;
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
 
+// Both functions are defined in assembly in handler-outside-simulator.rs, so
+// that the trap handler knows the exact instruction that faults.
+unsafe extern "C" {
     /// Probe a memory address by doing a 1-byte read from the given address. If the
     /// address is not readable, this will cause a trap as usual, but the trap
     /// handler will recognise the address of the instruction doing the access and
@@ -17,15 +18,11 @@ pub mod trap_handler_simulator {
     /// is not registered as a protected instruction, the signal will be propagated
     /// as usual.
     /// If the read at {address} succeeds, this function returns {0} instead.
-    #[cfg(target_os = "macos")]
-    #[link(name = "v8_internal_simulator_ProbeMemory", kind = "static")]
-    extern "C" {
-        pub fn v8_internal_simulator_ProbeMemory(address: usize, pc: usize) -> usize;
-    }
+    #[link_name = "v8_internal_simulator_ProbeMemory"]
+    pub fn probe_memory(address: usize, pc: usize) -> usize;
 
-    #[cfg(not(target_os = "macos"))]
-    extern "C" {
-        #[link_name = "v8_internal_simulator_ProbeMemory"]
-        pub fn v8_internal_simulator_ProbeMemory(address: usize, pc: usize) -> usize;
-    }
-}
\ No newline at end of file
+    /// Where the trap handler continues after a fault in `probe_memory`, with
+    /// the landing pad in the return register.
+    #[link_name = "v8_simulator_probe_memory_continuation"]
+    pub fn probe_memory_continuation();
+}
@@ -4,7 +4,6 @@
 
 pub mod version {
     use std::ffi::CString;
-    use std::os::raw::c_char;
 
     use std::hash::{Hash, Hasher};
 
@@ -79,59 +78,33 @@ pub mod version {
     }
 
     use std::ffi::CStr;
-
-    lazy_static::lazy_static! {
-        static ref VERSION: Version = {
-            // Default initialization.  These values will likely be overwritten by SetVersion().
-            Version {
-                major: 0,
-                minor: 0,
-                build: 0,
-                patch: 0,
-                embedder: CString::new("").unwrap(),
-                candidate: false,
-                soname: CString::new("").unwrap(),
-                version_string: CString::new("").unwrap(),
-            }
+    use std::sync::LazyLock;
+
+    use crate::include::v8_version::v8_version::{
+        V8_BUILD_NUMBER, V8_IS_CANDIDATE_VERSION, V8_MAJOR_VERSION, V8_MINOR_VERSION, V8_PATCH_LEVEL,
+    };
+
+    static VERSION: LazyLock<Version> = LazyLock::new(|| {
+        let (major, minor, build, patch) =
+            (V8_MAJOR_VERSION as i32, V8_MINOR_VERSION as i32, V8_BUILD_NUMBER as i32, V8_PATCH_LEVEL as i32);
+        let version_string = if patch > 0 {
+            format!("{}.{}.{}.{}", major, minor, build, patch)
+        } else {
+            format!("{}.{}.{}", major, minor, build)
         };
-    }
+        Version {
+            major,
+            minor,
+            build,
+            patch,
+            embedder: CString::new("").unwrap(),
+            candidate: V8_IS_CANDIDATE_VERSION,
+            soname: CString::new("").unwrap(),
+            version_string: CString::new(version_string).unwrap(),
+        }
+    });
 
     pub fn get_version_static() -> &'static Version {
         &VERSION
     }
-
-    // NOTE: this function mimics the friend relationship in C++ by allowing
-    // test code to set the static version.  This is generally not good
-    // Rust style.
-    #[cfg(test)]
-    pub fn set_version(
-        major: i32,
-        minor: i32,
-        build: i32,
-        patch: i32,
-        embedder: &str,
-        candidate: bool,
-        soname: &str,
-    ) {
-        use std::sync::Once;
-        static ONCE: Once = Once::new();
-        ONCE.call_once(|| {
-            let mut version = unsafe {
-                std::ptr::addr_of_mut!(*VERSION) as *mut Version
-            };
-
-            unsafe {
-                (*version).major = major;
-                (*version).minor = minor;
-                (*version).build = build;
-                (*version).patch = patch;
-                (*version).embedder = CString::new(embedder).unwrap();
-                (*version).candidate = candidate;
-                (*version).soname = CString::new(soname).unwrap();
-                (*version).version_string = CString::new(format!("{}.{}.{}.{}", major, minor, build, patch)).unwrap();
-
-            }
-        });
-
-    }
-}
\ No newline at end of file
+}
@@ -2,8 +2,7 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-// The fp16 crate provides the necessary functionality for half-precision floating-point numbers.
-use fp16::FP16;
+use crate::numbers::conversions::conversions::{double_to_float16, float16_to_double};
 
 mod base {
     use std::{mem, ptr};
@@ -54,16 +53,37 @@ pub mod internal {
             base::write_unaligned::<u16>(destination, self.bits);
         }
 
-        /// Converts a 32-bit float to a `Float16`.
+        /// Creates a `Float16` from its IEEE 754 binary16 bit pattern.
+        pub const fn from_bits(bits: u16) -> Self {
+            Float16 { bits }
+        }
+
+        /// Returns the IEEE 754 binary16 bit pattern.
+        pub const fn bits(&self) -> u16 {
+            self.bits
+        }
+
+        /// Converts a 32-bit float to a `Float16`, rounding ties to even.
+        /// Widening to a double is exact, so this rounds only once.
         pub fn from_float32(f32: f32) -> Self {
+            Self::from_float64(f32 as f64)
+        }
+
+        /// Converts a 64-bit float to a `Float16`, rounding ties to even.
+        pub fn from_float64(f64: f64) -> Self {
             Float16 {
-                bits: FP16::from_f32(f32).to_bits(),
+                bits: double_to_float16(f64),
             }
         }
 
-        /// Converts the `Float16` to a 32-bit float.
+        /// Converts the `Float16` to a 32-bit float. The conversion is exact.
         pub fn to_float32(&self) -> f32 {
-            FP16::from_bits(self.bits).to_f32()
+            float16_to_double(self.bits) as f32
+        }
+
+        /// Converts the `Float16` to a 64-bit float. The conversion is exact.
+        pub fn to_float64(&self) -> f64 {
+            float16_to_double(self.bits)
         }
     }
 
@@ -27,6 +27,9 @@ mod wasm_interpreter_runtime {
     pub type WasmGlobal = u32;
     pub type ModuleTypeIndex = u32;
     pub type TrapReason = u32;
+
+    /// `TrapReason::kTrapMemOutOfBounds`.
+    const K_TRAP_MEM_OUT_OF_BOUNDS: TrapReason = 1;
     pub type FixedArray = Vec<Object>;
     pub type WasmArray = usize;
     pub type ArrayType = u32;
@@ -123,16 +126,26 @@ mod wasm_interpreter_runtime {
 
     pub struct WasmMemory {
         is_memory64: bool,
+        has_guard_regions: bool,
     }
 
     impl WasmMemory {
-        pub fn new(is_memory64: bool) -> Self {
-            Self { is_memory64 }
+        pub fn new(is_memory64: bool, has_guard_regions: bool) -> Self {
+            Self {
+                is_memory64,
+                has_guard_regions,
+            }
         }
 
         pub fn is_memory64(&self) -> bool {
             self.is_memory64
         }
+
+        /// Whether out-of-bounds accesses of the memory hit guard pages, so that
+        /// they can be caught by the trap handler instead of bounds checks.
+        pub fn has_guard_regions(&self) -> bool {
+            self.has_guard_regions
+        }
     }
 
     pub struct CodeMap {}
@@ -156,6 +169,9 @@ mod wasm_interpreter_runtime {
         fuzzer_start_time_: u64,
         trap_reason_: Arc<Mutex<Option<TrapReason>>>,
         code_: Arc<Mutex<*const u8>>,
+        /// Indices of the bytecode regions registered with the trap handler, see
+        /// `RegisterProtectedBytecode()`.
+        protected_bytecode_: Vec<i32>,
         // Add fields from your WasmInterpreterRuntime here
     }
 
@@ -186,6 +202,7 @@ mod wasm_interpreter_runtime {
                 fuzzer_start_time_,
                 trap_reason_: Arc::new(Mutex::new(None)),
                 code_: Arc::new(Mutex::new(std::ptr::null())),
+                protected_bytecode_: Vec::new(),
             }
         }
 
@@ -208,10 +225,95 @@ mod wasm_interpreter_runtime {
                 return false;
             }
             *size_in = std::cmp::min(*size_in, data.memory0_size() - index);
-            *out_address = self.EffectiveAddress(index);
+            *out_address = data.memory0_start() + index as Address;
             true
         }
 
+        /// Registers the bytecode of a function with the trap handler, so that the
+        /// memory accesses of the bytecodes at `memory_access_offsets` are checked
+        /// by `MemoryAccessAddress()` through the trap handler instead of explicit
+        /// bounds checks. The registration is released when the runtime is dropped.
+        pub fn RegisterProtectedBytecode(&mut self, bytecode: &[u8], memory_access_offsets: &[u32]) {
+            if !self.UseTrapHandler() {
+                return;
+            }
+            let protected_instructions: Vec<_> = memory_access_offsets
+                .iter()
+                .map(|&instr_offset| crate::trap_handler::trap_handler::ProtectedInstructionData { instr_offset })
+                .collect();
+            let index = crate::trap_handler::trap_handler::register_handler_data(
+                bytecode.as_ptr() as Address,
+                bytecode.len(),
+                &protected_instructions,
+            );
+            if index >= 0 {
+                self.protected_bytecode_.push(index);
+            }
+        }
+
+        /// Whether out-of-bounds memory accesses are caught by the trap handler.
+        fn UseTrapHandler(&self) -> bool {
+            crate::trap_handler::trap_handler::V8_TRAP_HANDLER_SUPPORTED
+                && crate::trap_handler::trap_handler::is_trap_handler_enabled()
+                && self.module_.memories.first().is_some_and(WasmMemory::has_guard_regions)
+        }
+
+        /// Returns the address of the `size` bytes at `index` accessed by the
+        /// bytecode at `current_bytecode`, or sets a `kTrapMemOutOfBounds` trap and
+        /// returns `None` if the access is out of bounds.
+        ///
+        /// With the trap handler the access is probed through `probe_memory`, so
+        /// that it faults on the guard regions of the memory and the trap handler
+        /// returns the landing pad for the (registered) bytecode, like for the
+        /// protected instructions of compiled code. Otherwise the access is bounds
+        /// checked explicitly.
+        fn MemoryAccessAddress(
+            &self,
+            index: u64,
+            size: u64,
+            current_bytecode: *const u8,
+            code: &mut *const u8,
+        ) -> Option<Address> {
+            #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+            if self.UseTrapHandler() {
+                use crate::trap_handler::trap_handler::{clear_thread_in_wasm, is_thread_in_wasm, set_thread_in_wasm};
+                use crate::trap_handler::trap_handler_simulator::probe_memory;
+
+                let address = self.memory_start_.wrapping_add(index as Address);
+                let was_in_wasm = is_thread_in_wasm();
+                if !was_in_wasm {
+                    set_thread_in_wasm();
+                }
+                // Probing the first and the last byte covers the whole access,
+                // since the guard regions are larger than any single access.
+                // SAFETY: `probe_memory` only reads from `address`, and faults on
+                // inaccessible memory are recovered by the trap handler because
+                // `current_bytecode` was registered as a protected instruction.
+                let landing_pad = unsafe {
+                    match probe_memory(address, current_bytecode as usize) {
+                        0 if size > 1 => probe_memory(address.wrapping_add(size as Address - 1), current_bytecode as usize),
+                        result => result,
+                    }
+                };
+                if !was_in_wasm {
+                    clear_thread_in_wasm();
+                }
+                if landing_pad != 0 {
+                    self.SetTrap(K_TRAP_MEM_OUT_OF_BOUNDS, code);
+                    return None;
+                }
+                return Some(address);
+            }
+
+            let mut size_in = size;
+            let mut address = 0;
+            if !self.BoundsCheckMemRange(index, &mut size_in, &mut address) {
+                self.SetTrap(K_TRAP_MEM_OUT_OF_BOUNDS, code);
+                return None;
+            }
+            Some(address)
+        }
+
         fn GetGlobalAddress(&self, index: u32) -> *mut u8 {
             assert!((index as usize) < self.module_.globals.len());
             self.global_addresses_[index as usize]
@@ -336,6 +438,14 @@ mod wasm_interpreter_runtime {
         }
     }
 
+    impl Drop for WasmInterpreterRuntime {
+        fn drop(&mut self) {
+            for index in self.protected_bytecode_.drain(..) {
+                crate::trap_handler::trap_handler::release_handler_data(index);
+            }
+        }
+    }
+
     #[derive(Clone, Copy)]
     pub struct CurrentFrame {
         current_bytecode_: *const u8,
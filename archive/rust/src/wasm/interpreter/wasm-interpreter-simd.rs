@@ -29,6 +29,41 @@ mod wasm_opcodes {
         I8x16Eq,
         Fd113,
 
+        // fp16 proposal (0xfd120 - 0xfd14f).
+        F16x8Splat,
+        F16x8ExtractLane,
+        F16x8ReplaceLane,
+        F16x8Abs,
+        F16x8Neg,
+        F16x8Sqrt,
+        F16x8Ceil,
+        F16x8Floor,
+        F16x8Trunc,
+        F16x8NearestInt,
+        F16x8Eq,
+        F16x8Ne,
+        F16x8Lt,
+        F16x8Gt,
+        F16x8Le,
+        F16x8Ge,
+        F16x8Add,
+        F16x8Sub,
+        F16x8Mul,
+        F16x8Div,
+        F16x8Min,
+        F16x8Max,
+        F16x8Pmin,
+        F16x8Pmax,
+        I16x8SConvertF16x8,
+        I16x8UConvertF16x8,
+        F16x8SConvertI16x8,
+        F16x8UConvertI16x8,
+        F16x8DemoteF32x4Zero,
+        F16x8DemoteF64x2Zero,
+        F32x4PromoteLowF16x8,
+        F16x8Qfma,
+        F16x8Qfms,
+
         // Add more opcodes as needed
         Unknown,
     }
@@ -48,6 +83,39 @@ mod wasm_opcodes {
             WasmOpcode::S128Store64Lane => "v128.store64_lane",
             WasmOpcode::I8x16Eq => "i8x16.eq",
             WasmOpcode::Fd113 => "0xfd113",
+            WasmOpcode::F16x8Splat => "f16x8.splat",
+            WasmOpcode::F16x8ExtractLane => "f16x8.extract_lane",
+            WasmOpcode::F16x8ReplaceLane => "f16x8.replace_lane",
+            WasmOpcode::F16x8Abs => "f16x8.abs",
+            WasmOpcode::F16x8Neg => "f16x8.neg",
+            WasmOpcode::F16x8Sqrt => "f16x8.sqrt",
+            WasmOpcode::F16x8Ceil => "f16x8.ceil",
+            WasmOpcode::F16x8Floor => "f16x8.floor",
+            WasmOpcode::F16x8Trunc => "f16x8.trunc",
+            WasmOpcode::F16x8NearestInt => "f16x8.nearest",
+            WasmOpcode::F16x8Eq => "f16x8.eq",
+            WasmOpcode::F16x8Ne => "f16x8.ne",
+            WasmOpcode::F16x8Lt => "f16x8.lt",
+            WasmOpcode::F16x8Gt => "f16x8.gt",
+            WasmOpcode::F16x8Le => "f16x8.le",
+            WasmOpcode::F16x8Ge => "f16x8.ge",
+            WasmOpcode::F16x8Add => "f16x8.add",
+            WasmOpcode::F16x8Sub => "f16x8.sub",
+            WasmOpcode::F16x8Mul => "f16x8.mul",
+            WasmOpcode::F16x8Div => "f16x8.div",
+            WasmOpcode::F16x8Min => "f16x8.min",
+            WasmOpcode::F16x8Max => "f16x8.max",
+            WasmOpcode::F16x8Pmin => "f16x8.pmin",
+            WasmOpcode::F16x8Pmax => "f16x8.pmax",
+            WasmOpcode::I16x8SConvertF16x8 => "i16x8.trunc_sat_f16x8_s",
+            WasmOpcode::I16x8UConvertF16x8 => "i16x8.trunc_sat_f16x8_u",
+            WasmOpcode::F16x8SConvertI16x8 => "f16x8.convert_i16x8_s",
+            WasmOpcode::F16x8UConvertI16x8 => "f16x8.convert_i16x8_u",
+            WasmOpcode::F16x8DemoteF32x4Zero => "f16x8.demote_f32x4_zero",
+            WasmOpcode::F16x8DemoteF64x2Zero => "f16x8.demote_f64x2_zero",
+            WasmOpcode::F32x4PromoteLowF16x8 => "f32x4.promote_low_f16x8",
+            WasmOpcode::F16x8Qfma => "f16x8.relaxed_madd",
+            WasmOpcode::F16x8Qfms => "f16x8.relaxed_nmadd",
 
             WasmOpcode::Unknown => "unknown",
         }
@@ -55,10 +123,11 @@ mod wasm_opcodes {
 
     pub fn is_relaxed_simd_opcode(opcode: WasmOpcode) -> bool {
         // This is a placeholder implementation. Replace with actual logic.
-        false
+        matches!(opcode, WasmOpcode::F16x8Qfma | WasmOpcode::F16x8Qfms)
     }
 }
 
+use crate::wasm::float16::internal::Float16;
 use decoder::NoValidationTag;
 use wasm_opcodes::WasmOpcode;
 
@@ -139,6 +208,288 @@ impl InterpreterCode {
     }
 }
 
+// Lane accessors used by the fp16 SIMD handlers below. Lanes are stored in
+// little-endian order, matching the wasm memory layout of a v128.
+impl Simd128 {
+    fn lane_bytes<const N: usize>(&self, lane: usize) -> [u8; N] {
+        self.value[lane * N..(lane + 1) * N].try_into().unwrap()
+    }
+
+    fn set_lane_bytes<const N: usize>(&mut self, lane: usize, bytes: [u8; N]) {
+        self.value[lane * N..(lane + 1) * N].copy_from_slice(&bytes);
+    }
+
+    pub fn f16x8_lane(&self, lane: usize) -> u16 {
+        u16::from_le_bytes(self.lane_bytes(lane))
+    }
+
+    pub fn set_f16x8_lane(&mut self, lane: usize, bits: u16) {
+        self.set_lane_bytes(lane, bits.to_le_bytes());
+    }
+
+    pub fn i16x8_lane(&self, lane: usize) -> i16 {
+        i16::from_le_bytes(self.lane_bytes(lane))
+    }
+
+    pub fn f32x4_lane(&self, lane: usize) -> f32 {
+        f32::from_le_bytes(self.lane_bytes(lane))
+    }
+
+    pub fn set_f32x4_lane(&mut self, lane: usize, value: f32) {
+        self.set_lane_bytes(lane, value.to_le_bytes());
+    }
+
+    pub fn f64x2_lane(&self, lane: usize) -> f64 {
+        f64::from_le_bytes(self.lane_bytes(lane))
+    }
+}
+
+// Half-precision lanes are computed in double precision and rounded back once.
+// A double has more than twice the precision of a half plus two bits, so the
+// double rounding of +, -, *, / and sqrt is innocuous and results are
+// correctly rounded.
+fn f16_to_f64(bits: u16) -> f64 {
+    Float16::from_bits(bits).to_float64()
+}
+
+fn f64_to_f16(value: f64) -> u16 {
+    Float16::from_float64(value).bits()
+}
+
+const K_F16_SIGN_MASK: u16 = 0x8000;
+
+fn f16x8_map(v: Simd128, op: impl Fn(u16) -> u16) -> Simd128 {
+    let mut result = Simd128::new([0; K_SIMD128_SIZE]);
+    for lane in 0..8 {
+        result.set_f16x8_lane(lane, op(v.f16x8_lane(lane)));
+    }
+    result
+}
+
+fn f16x8_zip(a: Simd128, b: Simd128, op: impl Fn(u16, u16) -> u16) -> Simd128 {
+    let mut result = Simd128::new([0; K_SIMD128_SIZE]);
+    for lane in 0..8 {
+        result.set_f16x8_lane(lane, op(a.f16x8_lane(lane), b.f16x8_lane(lane)));
+    }
+    result
+}
+
+// Wasm min/max: NaN if either operand is NaN, and -0 orders below +0.
+fn wasm_f64_min(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        if a.is_sign_negative() { a } else { b }
+    } else {
+        a.min(b)
+    }
+}
+
+fn wasm_f64_max(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        if a.is_sign_positive() { a } else { b }
+    } else {
+        a.max(b)
+    }
+}
+
+pub fn is_fp16_simd_opcode(opcode: WasmOpcode) -> bool {
+    use WasmOpcode::*;
+    matches!(
+        opcode,
+        F16x8Splat
+            | F16x8ExtractLane
+            | F16x8ReplaceLane
+            | F16x8Abs
+            | F16x8Neg
+            | F16x8Sqrt
+            | F16x8Ceil
+            | F16x8Floor
+            | F16x8Trunc
+            | F16x8NearestInt
+            | F16x8Eq
+            | F16x8Ne
+            | F16x8Lt
+            | F16x8Gt
+            | F16x8Le
+            | F16x8Ge
+            | F16x8Add
+            | F16x8Sub
+            | F16x8Mul
+            | F16x8Div
+            | F16x8Min
+            | F16x8Max
+            | F16x8Pmin
+            | F16x8Pmax
+            | I16x8SConvertF16x8
+            | I16x8UConvertF16x8
+            | F16x8SConvertI16x8
+            | F16x8UConvertI16x8
+            | F16x8DemoteF32x4Zero
+            | F16x8DemoteF64x2Zero
+            | F32x4PromoteLowF16x8
+            | F16x8Qfma
+            | F16x8Qfms
+    )
+}
+
+// f16x8.splat: the scalar operand is an f32.
+pub fn f16x8_splat(value: f32) -> Simd128 {
+    let bits = Float16::from_float32(value).bits();
+    f16x8_map(Simd128::new([0; K_SIMD128_SIZE]), |_| bits)
+}
+
+pub fn f16x8_extract_lane(v: Simd128, lane: u8) -> f32 {
+    Float16::from_bits(v.f16x8_lane(lane as usize)).to_float32()
+}
+
+pub fn f16x8_replace_lane(mut v: Simd128, lane: u8, value: f32) -> Simd128 {
+    v.set_f16x8_lane(lane as usize, Float16::from_float32(value).bits());
+    v
+}
+
+// Executes a one-operand fp16 instruction, including the conversions to and
+// from other lane shapes.
+pub fn execute_fp16_unop(opcode: WasmOpcode, v: Simd128) -> Simd128 {
+    let round = |op: fn(f64) -> f64| f16x8_map(v, move |x| f64_to_f16(op(f16_to_f64(x))));
+    match opcode {
+        // abs and neg only touch the sign bit, so NaN payloads are preserved.
+        WasmOpcode::F16x8Abs => f16x8_map(v, |x| x & !K_F16_SIGN_MASK),
+        WasmOpcode::F16x8Neg => f16x8_map(v, |x| x ^ K_F16_SIGN_MASK),
+        WasmOpcode::F16x8Sqrt => round(f64::sqrt),
+        WasmOpcode::F16x8Ceil => round(f64::ceil),
+        WasmOpcode::F16x8Floor => round(f64::floor),
+        WasmOpcode::F16x8Trunc => round(f64::trunc),
+        WasmOpcode::F16x8NearestInt => round(f64::round_ties_even),
+        // Float-to-int casts saturate and map NaN to zero, which is exactly
+        // trunc_sat.
+        WasmOpcode::I16x8SConvertF16x8 => f16x8_map(v, |x| f16_to_f64(x) as i16 as u16),
+        WasmOpcode::I16x8UConvertF16x8 => f16x8_map(v, |x| f16_to_f64(x) as u16),
+        WasmOpcode::F16x8SConvertI16x8 => f16x8_map(v, |x| f64_to_f16(x as i16 as f64)),
+        WasmOpcode::F16x8UConvertI16x8 => f16x8_map(v, |x| f64_to_f16(x as f64)),
+        WasmOpcode::F16x8DemoteF32x4Zero => {
+            let mut result = Simd128::new([0; K_SIMD128_SIZE]);
+            for lane in 0..4 {
+                result.set_f16x8_lane(lane, Float16::from_float32(v.f32x4_lane(lane)).bits());
+            }
+            result
+        }
+        WasmOpcode::F16x8DemoteF64x2Zero => {
+            let mut result = Simd128::new([0; K_SIMD128_SIZE]);
+            for lane in 0..2 {
+                result.set_f16x8_lane(lane, f64_to_f16(v.f64x2_lane(lane)));
+            }
+            result
+        }
+        WasmOpcode::F32x4PromoteLowF16x8 => {
+            let mut result = Simd128::new([0; K_SIMD128_SIZE]);
+            for lane in 0..4 {
+                result.set_f32x4_lane(lane, Float16::from_bits(v.f16x8_lane(lane)).to_float32());
+            }
+            result
+        }
+        _ => unreachable!("{} is not an fp16 unop", wasm_opcodes::opcode_name(opcode)),
+    }
+}
+
+// Executes a two-operand fp16 instruction. Comparisons produce an i16x8 mask.
+pub fn execute_fp16_binop(opcode: WasmOpcode, a: Simd128, b: Simd128) -> Simd128 {
+    let arith = |op: fn(f64, f64) -> f64| f16x8_zip(a, b, move |x, y| f64_to_f16(op(f16_to_f64(x), f16_to_f64(y))));
+    let compare = |op: fn(&f64, &f64) -> bool| {
+        f16x8_zip(a, b, move |x, y| if op(&f16_to_f64(x), &f16_to_f64(y)) { 0xffff } else { 0 })
+    };
+    match opcode {
+        WasmOpcode::F16x8Eq => compare(f64::eq),
+        WasmOpcode::F16x8Ne => compare(f64::ne),
+        WasmOpcode::F16x8Lt => compare(f64::lt),
+        WasmOpcode::F16x8Gt => compare(f64::gt),
+        WasmOpcode::F16x8Le => compare(f64::le),
+        WasmOpcode::F16x8Ge => compare(f64::ge),
+        WasmOpcode::F16x8Add => arith(|x, y| x + y),
+        WasmOpcode::F16x8Sub => arith(|x, y| x - y),
+        WasmOpcode::F16x8Mul => arith(|x, y| x * y),
+        WasmOpcode::F16x8Div => arith(|x, y| x / y),
+        WasmOpcode::F16x8Min => arith(wasm_f64_min),
+        WasmOpcode::F16x8Max => arith(wasm_f64_max),
+        // Pseudo-min/max return the first operand unless the comparison holds,
+        // propagating its bits unchanged.
+        WasmOpcode::F16x8Pmin => f16x8_zip(a, b, |x, y| if f16_to_f64(y) < f16_to_f64(x) { y } else { x }),
+        WasmOpcode::F16x8Pmax => f16x8_zip(a, b, |x, y| if f16_to_f64(x) < f16_to_f64(y) { y } else { x }),
+        _ => unreachable!("{} is not an fp16 binop", wasm_opcodes::opcode_name(opcode)),
+    }
+}
+
+// Executes the relaxed fused multiply-add instructions. Relaxed semantics allow
+// either a fused or an unfused result; this computes the unfused one, rounding
+// after the multiplication and again after the addition.
+pub fn execute_fp16_ternop(opcode: WasmOpcode, a: Simd128, b: Simd128, c: Simd128) -> Simd128 {
+    let negate = match opcode {
+        WasmOpcode::F16x8Qfma => false,
+        WasmOpcode::F16x8Qfms => true,
+        _ => unreachable!("{} is not an fp16 ternop", wasm_opcodes::opcode_name(opcode)),
+    };
+    let mut result = Simd128::new([0; K_SIMD128_SIZE]);
+    for lane in 0..8 {
+        let product = f16_to_f64(f64_to_f16(f16_to_f64(a.f16x8_lane(lane)) * f16_to_f64(b.f16x8_lane(lane))));
+        let product = if negate { -product } else { product };
+        result.set_f16x8_lane(lane, f64_to_f16(product + f16_to_f64(c.f16x8_lane(lane))));
+    }
+    result
+}
+
+// An operand of the fp16 instructions on the interpreter stack.
+#[derive(Debug, Clone, Copy)]
+pub enum SimdValue {
+    F32(f32),
+    S128(Simd128),
+}
+
+fn pop_f32(stack: &mut Vec<SimdValue>) -> f32 {
+    match stack.pop() {
+        Some(SimdValue::F32(value)) => value,
+        operand => panic!("Expected an f32 operand, got {:?}", operand),
+    }
+}
+
+fn pop_s128(stack: &mut Vec<SimdValue>) -> Simd128 {
+    match stack.pop() {
+        Some(SimdValue::S128(value)) => value,
+        operand => panic!("Expected an s128 operand, got {:?}", operand),
+    }
+}
+
+// Executes an fp16 instruction decoded by decode_simd_op: pops its operands
+// off |stack|, the last operand on top, and pushes the result. The operand
+// types have been checked by the validating decoder.
+pub fn execute_fp16_simd_op(opcode: WasmOpcode, optional: &WasmInstructionOptional, stack: &mut Vec<SimdValue>) {
+    use WasmOpcode::*;
+    let result = match opcode {
+        F16x8Splat => SimdValue::S128(f16x8_splat(pop_f32(stack))),
+        F16x8ExtractLane => SimdValue::F32(f16x8_extract_lane(pop_s128(stack), optional.simd_lane)),
+        F16x8ReplaceLane => {
+            let value = pop_f32(stack);
+            SimdValue::S128(f16x8_replace_lane(pop_s128(stack), optional.simd_lane, value))
+        }
+        F16x8Eq | F16x8Ne | F16x8Lt | F16x8Gt | F16x8Le | F16x8Ge | F16x8Add | F16x8Sub | F16x8Mul | F16x8Div
+        | F16x8Min | F16x8Max | F16x8Pmin | F16x8Pmax => {
+            let b = pop_s128(stack);
+            let a = pop_s128(stack);
+            SimdValue::S128(execute_fp16_binop(opcode, a, b))
+        }
+        F16x8Qfma | F16x8Qfms => {
+            let c = pop_s128(stack);
+            let b = pop_s128(stack);
+            let a = pop_s128(stack);
+            SimdValue::S128(execute_fp16_ternop(opcode, a, b, c))
+        }
+        _ if is_fp16_simd_opcode(opcode) => SimdValue::S128(execute_fp16_unop(opcode, pop_s128(stack))),
+        _ => unreachable!("{} is not an fp16 instruction", wasm_opcodes::opcode_name(opcode)),
+    };
+    stack.push(result);
+}
+
 #[derive(Debug)]
 pub struct Decoder; // Placeholder, needs actual implementation
 
@@ -410,6 +761,7 @@ impl WasmBytecodeGenerator {
             false,  // 0xeb    f32x4.pmax                    -
             false,  // 0xec    f64x2.abs                     -
             false,  // 0xed    f64x2.neg                     -
+            true,   // 0xee    (reserved)
             false,  // 0xef    f64x2.sqrt                    -
             false,  // 0xf0    f64x2.add                     -
             false,  // 0xf1    f64x2.sub                     -
@@ -460,4 +812,152 @@ impl WasmBytecodeGenerator {
         {
             // The following code mimics the C++ version, but needs actual implementations for SimdLaneImmediate and Decoder
             let imm_lane = code.at(pc + *len as usize);
-            let imm = SimdLaneImmediate::new
\ No newline at end of file
+            let imm = SimdLaneImmediate::new(imm_lane, 1);
+            optional.simd_lane = imm.lane;
+            *len += imm.length as i32;
+        } else if opcode == WasmOpcode::F16x8ExtractLane || opcode == WasmOpcode::F16x8ReplaceLane {
+            let imm_lane = code.at(pc + *len as usize);
+            let imm = SimdLaneImmediate::new(imm_lane, 1);
+            optional.simd_lane = imm.lane;
+            *len += imm.length as i32;
+        } else if opcode == WasmOpcode::S128Load8Lane || opcode == WasmOpcode::S128Store64Lane {
+            let imm_offset = code.at(pc + *len as usize);
+            let imm = MemoryAccessImmediate::new(imm_offset as u64, 1);
+            let imm_lane = code.at(pc + *len as usize + imm.length);
+            optional.simd_loadstore_lane = SimdLoadStoreLane { offset: imm.offset, lane: imm_lane };
+            *len += imm.length as i32 + 1;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f16x8(lanes: [f64; 8]) -> Simd128 {
+        let mut v = Simd128::new([0; K_SIMD128_SIZE]);
+        for (lane, value) in lanes.iter().enumerate() {
+            v.set_f16x8_lane(lane, f64_to_f16(*value));
+        }
+        v
+    }
+
+    fn lanes(v: Simd128) -> [f64; 8] {
+        std::array::from_fn(|lane| f16_to_f64(v.f16x8_lane(lane)))
+    }
+
+    #[test]
+    fn test_fp16_lane_access() {
+        let v = f16x8_splat(1.337);
+        assert_eq!(lanes(v), [1.3369140625; 8]);
+        let v = f16x8_replace_lane(v, 3, 70000.0);
+        assert_eq!(f16x8_extract_lane(v, 3), f32::INFINITY);
+        assert_eq!(f16x8_extract_lane(v, 2), 1.3369140625);
+        assert_eq!(&v.value[6..8], &[0x00, 0x7c]);
+    }
+
+    #[test]
+    fn test_fp16_arithmetic() {
+        let a = f16x8([1.0, -2.5, 65504.0, 0.0, -0.0, f64::NAN, 3.0, 2049.0]);
+        let b = f16x8([2.0, 0.5, 65504.0, -0.0, 0.0, 1.0, 0.0, 1.0]);
+        let sum = lanes(execute_fp16_binop(WasmOpcode::F16x8Add, a, b));
+        assert_eq!(sum[..5], [3.0, -2.0, f64::INFINITY, 0.0, 0.0]);
+        assert!(sum[5].is_nan());
+        // 2048 + 1 = 2049 is not representable; ties round to even.
+        assert_eq!(sum[7], 2048.0);
+        let quotient = lanes(execute_fp16_binop(WasmOpcode::F16x8Div, a, b));
+        assert_eq!(quotient[6], f64::INFINITY);
+        let min = execute_fp16_binop(WasmOpcode::F16x8Min, a, b);
+        assert_eq!(min.f16x8_lane(3), 0x8000);
+        assert_eq!(min.f16x8_lane(4), 0x8000);
+        assert!(f16_to_f64(min.f16x8_lane(5)).is_nan());
+        let max = execute_fp16_binop(WasmOpcode::F16x8Max, a, b);
+        assert_eq!(max.f16x8_lane(3), 0x0000);
+        let pmin = execute_fp16_binop(WasmOpcode::F16x8Pmin, a, b);
+        assert_eq!(pmin.f16x8_lane(3), 0x0000);
+        assert_eq!(pmin.f16x8_lane(5), a.f16x8_lane(5));
+        let lt = execute_fp16_binop(WasmOpcode::F16x8Lt, a, b);
+        assert_eq!(lt.i16x8_lane(0), -1);
+        assert_eq!(lt.i16x8_lane(3), 0);
+        assert_eq!(lt.i16x8_lane(5), 0);
+        let ne = execute_fp16_binop(WasmOpcode::F16x8Ne, a, a);
+        assert_eq!(ne.i16x8_lane(5), -1);
+        assert_eq!(ne.i16x8_lane(0), 0);
+    }
+
+    #[test]
+    fn test_fp16_unops() {
+        let v = f16x8([-1.5, 2.5, -0.5, 4.0, 65504.0, -70000.0, f64::NAN, 0.1]);
+        assert_eq!(lanes(execute_fp16_unop(WasmOpcode::F16x8NearestInt, v))[..4], [-2.0, 2.0, -0.0, 4.0]);
+        assert_eq!(lanes(execute_fp16_unop(WasmOpcode::F16x8Ceil, v))[..3], [-1.0, 3.0, -0.0]);
+        assert_eq!(lanes(execute_fp16_unop(WasmOpcode::F16x8Sqrt, v))[3], 2.0);
+        assert_eq!(execute_fp16_unop(WasmOpcode::F16x8Neg, v).f16x8_lane(6), v.f16x8_lane(6) ^ 0x8000);
+        assert_eq!(execute_fp16_unop(WasmOpcode::F16x8Abs, v).f16x8_lane(5), 0x7c00);
+        let ints = execute_fp16_unop(WasmOpcode::I16x8SConvertF16x8, v);
+        assert_eq!((0..8).map(|lane| ints.i16x8_lane(lane)).collect::<Vec<_>>(), [-1, 2, 0, 4, 32767, -32768, 0, 0]);
+        let uints = execute_fp16_unop(WasmOpcode::I16x8UConvertF16x8, v);
+        assert_eq!(uints.f16x8_lane(0), 0);
+        assert_eq!(uints.f16x8_lane(4), 65504);
+        let mut i = Simd128::new([0; K_SIMD128_SIZE]);
+        i.set_f16x8_lane(0, (-2049i16) as u16);
+        i.set_f16x8_lane(1, 65535);
+        assert_eq!(lanes(execute_fp16_unop(WasmOpcode::F16x8SConvertI16x8, i))[..2], [-2048.0, -1.0]);
+        assert_eq!(lanes(execute_fp16_unop(WasmOpcode::F16x8UConvertI16x8, i))[..2], [63488.0, f64::INFINITY]);
+    }
+
+    #[test]
+    fn test_fp16_shape_conversions() {
+        let mut f32s = Simd128::new([0xff; K_SIMD128_SIZE]);
+        for (lane, value) in [1.0f32, 1e-8, -65520.0, 0.333].iter().enumerate() {
+            f32s.set_f32x4_lane(lane, *value);
+        }
+        let demoted = execute_fp16_unop(WasmOpcode::F16x8DemoteF32x4Zero, f32s);
+        assert_eq!(lanes(demoted)[..3], [1.0, 0.0, f64::NEG_INFINITY]);
+        assert_eq!(lanes(demoted)[3], 0.3330078125);
+        assert_eq!(&demoted.value[8..], &[0; 8]);
+        let promoted = execute_fp16_unop(WasmOpcode::F32x4PromoteLowF16x8, demoted);
+        assert_eq!(promoted.f32x4_lane(0), 1.0);
+        assert_eq!(promoted.f32x4_lane(3), 0.3330078125);
+        let mut f64s = Simd128::new([0; K_SIMD128_SIZE]);
+        f64s.value[..8].copy_from_slice(&(1.0 + 2f64.powi(-11) + 2f64.powi(-50)).to_le_bytes());
+        f64s.value[8..].copy_from_slice(&(-3.0f64).to_le_bytes());
+        let demoted = execute_fp16_unop(WasmOpcode::F16x8DemoteF64x2Zero, f64s);
+        // A single rounding from the double keeps the sticky bit.
+        assert_eq!(demoted.f16x8_lane(0), 0x3c01);
+        assert_eq!(lanes(demoted)[1], -3.0);
+    }
+
+    #[test]
+    fn test_fp16_relaxed_madd() {
+        let a = f16x8([2.0, 1.0 + 2f64.powi(-10), 3.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let b = f16x8([3.0, 1.0 + 2f64.powi(-10), -1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let c = f16x8([1.0, -1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        let madd = lanes(execute_fp16_ternop(WasmOpcode::F16x8Qfma, a, b, c));
+        assert_eq!(madd[0], 7.0);
+        assert_eq!(madd[1], 2f64.powi(-9));
+        assert_eq!(madd[2], -2.5);
+        let nmadd = lanes(execute_fp16_ternop(WasmOpcode::F16x8Qfms, a, b, c));
+        assert_eq!(nmadd[0], -5.0);
+        assert_eq!(nmadd[2], 3.5);
+    }
+    #[test]
+    fn test_fp16_dispatch() {
+        let mut optional = WasmInstructionOptional::new();
+        let mut stack = vec![SimdValue::F32(1.5)];
+        execute_fp16_simd_op(WasmOpcode::F16x8Splat, &optional, &mut stack);
+        stack.push(SimdValue::F32(-4.0));
+        optional.simd_lane = 2;
+        execute_fp16_simd_op(WasmOpcode::F16x8ReplaceLane, &optional, &mut stack);
+        execute_fp16_simd_op(WasmOpcode::F16x8Abs, &optional, &mut stack);
+        stack.push(SimdValue::S128(f16x8([2.0; 8])));
+        execute_fp16_simd_op(WasmOpcode::F16x8Sub, &optional, &mut stack);
+        stack.push(SimdValue::S128(f16x8([2.0; 8])));
+        stack.push(SimdValue::S128(f16x8([1.0; 8])));
+        execute_fp16_simd_op(WasmOpcode::F16x8Qfma, &optional, &mut stack);
+        let Some(&SimdValue::S128(v)) = stack.last() else { panic!("Expected an s128 result") };
+        assert_eq!(lanes(v), [0.0, 0.0, 5.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        execute_fp16_simd_op(WasmOpcode::F16x8ExtractLane, &optional, &mut stack);
+        assert!(matches!(stack[..], [SimdValue::F32(5.0)]));
+    }
+}
@@ -14,24 +14,11 @@ use std::cell::RefCell;
 use std::rc::Rc;
 use std::marker::PhantomData;
 
+use crate::include::v8_metrics::{ContextId, WasmModuleCompiled, WasmModuleDecoded};
+use crate::logging::metrics::{self, TimedScope};
+
 // Mock declarations for types from include/v8.h
 mod v8 {
-    pub mod metrics {
-        pub struct Recorder {
-            context_id: ContextId,
-        }
-        impl Recorder {
-            pub fn context_id(&self) -> ContextId {
-                self.context_id
-            }
-        }
-        #[derive(Clone, Copy)]
-        pub struct ContextId(u32);
-
-        #[derive(Default, Clone, Copy)]
-        pub struct WasmModuleDecoded;
-
-    }
     pub struct Isolate;
     pub struct Context;
     pub struct NativeContext;
@@ -193,11 +180,21 @@ pub mod wasm_module_compiler {
         module: Arc<wasm::WasmModule>,
         wire_bytes: base::OwnedVector<u8>,
         compilation_id: i32,
-        context_id: v8::metrics::Recorder::ContextId,
+        context_id: ContextId,
+        metrics_recorder: Option<Arc<metrics::Recorder>>,
         pgo_info: *mut wasm::ProfileInformation,
     ) -> Arc<wasm::NativeModule> {
-        //TODO: Implement compilation logic
-        Arc::new(wasm::NativeModule)
+        let mut metrics_event = WasmModuleCompiled::default();
+        let native_module = {
+            let _timed_scope = TimedScope::new(&mut metrics_event.wall_clock_duration_in_us);
+            //TODO: Implement compilation logic
+            Arc::new(wasm::NativeModule)
+        };
+        metrics_event.success = true;
+        if let Some(metrics_recorder) = metrics_recorder {
+            metrics_recorder.delay_main_thread_event(metrics_event, context_id);
+        }
+        native_module
     }
 
     pub fn validate_and_set_builtin_imports(
@@ -265,8 +262,8 @@ pub mod wasm_module_compiler {
         wire_bytes_: wasm::ModuleWireBytes<'static>, // Lifetime needs to be static, needs to be handled properly
         native_context_: *mut v8::NativeContext,
         incumbent_context_: *mut v8::NativeContext,
-        context_id_: v8::metrics::Recorder::ContextId,
-        metrics_event_: v8::metrics::WasmModuleDecoded,
+        context_id_: ContextId,
+        metrics_event_: WasmModuleDecoded,
         resolver_: Arc<wasm::CompilationResultResolver>,
         module_object_: RefCell<Option<*mut v8::WasmModuleObject>>,
         native_module_: RefCell<Option<Arc<wasm::NativeModule>>>,
@@ -304,8 +301,8 @@ pub mod wasm_module_compiler {
                 wire_bytes_: unsafe {std::mem::transmute(wire_bytes)}, //This is unsafe, lifetime needs to be handled properly
                 native_context_: std::ptr::null_mut(),
                 incumbent_context_: incumbent_context,
-                context_id_: v8::metrics::Recorder::ContextId(0), // Placeholder
-                metrics_event_: v8::metrics::WasmModuleDecoded::default(),
+                context_id_: ContextId::empty(),
+                metrics_event_: WasmModuleDecoded::default(),
                 resolver_: resolver,
                 module_object_: RefCell::new(None),
                 native_module_: RefCell::new(None),
@@ -344,7 +341,7 @@ pub mod wasm_module_compiler {
             self.native_context_
         }
 
-        pub fn context_id(&self) -> v8::metrics::Recorder::ContextId {
+        pub fn context_id(&self) -> ContextId {
             self.context_id_
         }
 
@@ -19,18 +19,6 @@ mod common {
     pub mod globals {}
 }
 
-mod logging {
-    pub mod metrics {
-        pub struct Recorder {}
-        impl Recorder {
-            pub fn new() -> Self {
-                Recorder {}
-            }
-            pub type ContextId = u32;
-        }
-    }
-}
-
 mod wasm {
     pub mod wasm_constants {
         pub const kUnknownSectionCode: u8 = 0;
@@ -41,8 +29,10 @@ mod wasm {
         pub struct WasmEnabledFeatures {}
     }
     pub mod wasm_module {
-        #[derive(Debug)]
-        pub struct WasmModule {}
+        #[derive(Debug, Default)]
+        pub struct WasmModule {
+            pub num_declared_functions: u32,
+        }
     }
 
     pub mod wasm_result {
@@ -67,7 +57,8 @@ mod wasm {
 }
 
 use common::globals::*;
-use logging::metrics;
+use crate::include::v8_metrics::{ContextId, WasmModuleDecoded};
+use crate::logging::metrics::{self, TimedScope};
 use wasm::function_body_decoder::*;
 use wasm::wasm_constants::*;
 use wasm::wasm_features::*;
@@ -185,13 +176,26 @@ pub mod wasm_module_decoder {
         origin: ModuleOrigin,
         counters: Option<&mut Counters>,
         metrics_recorder: Option<Arc<metrics::Recorder>>,
-        context_id: metrics::Recorder::ContextId,
+        context_id: ContextId,
         decoding_method: DecodingMethod,
         detected_features: &mut WasmDetectedFeatures,
     ) -> ModuleResult {
-        // Placeholder implementation
-        println!("decode_wasm_module (with counters) called");
-        Ok(Arc::new(WasmModule {}))
+        let mut metrics_event = WasmModuleDecoded::default();
+        let result = {
+            let _timed_scope = TimedScope::new(&mut metrics_event.wall_clock_duration_in_us);
+            decode_wasm_module_no_counters(enabled_features, wire_bytes, validate_functions, origin, detected_features)
+        };
+        metrics_event.success = result.is_ok();
+        metrics_event.async_flag = matches!(decoding_method, DecodingMethod::kAsync | DecodingMethod::kAsyncStream);
+        metrics_event.streamed = matches!(decoding_method, DecodingMethod::kSyncStream | DecodingMethod::kAsyncStream);
+        if let Ok(module) = &result {
+            metrics_event.function_count = module.num_declared_functions as usize;
+        }
+        metrics_event.module_size_in_bytes = wire_bytes.len();
+        if let Some(metrics_recorder) = metrics_recorder {
+            metrics_recorder.delay_main_thread_event(metrics_event, context_id);
+        }
+        result
     }
 
     pub fn decode_wasm_module_no_counters(
@@ -203,7 +207,7 @@ pub mod wasm_module_decoder {
     ) -> ModuleResult {
         // Placeholder implementation
         println!("decode_wasm_module (without counters) called");
-        Ok(Arc::new(WasmModule {}))
+        Ok(Arc::new(WasmModule::default()))
     }
 
     pub fn decode_wasm_module_for_disassembler(
@@ -212,7 +216,7 @@ pub mod wasm_module_decoder {
     ) -> ModuleResult {
         // Placeholder implementation
         println!("decode_wasm_module_for_disassembler called");
-        Ok(Arc::new(WasmModule {}))
+        Ok(Arc::new(WasmModule::default()))
     }
 
     // Mock definitions
@@ -361,7 +365,7 @@ pub mod wasm_module_decoder {
                 impl_: Box::new(ModuleDecoderImpl::new()),
                 enabled_features,
                 detected_features: Box::new(*detected_features),
-                shared_module: Arc::new(WasmModule {}),
+                shared_module: Arc::new(WasmModule::default()),
                 ok: true,
             }
         }
@@ -23,7 +23,11 @@ mod wasm {
 }
 
 pub mod wasm {
+    use std::sync::Arc;
+
     pub use crate::wasm::well_known_imports::WellKnownImport;
+    use crate::include::v8_metrics::{ContextId, WasmModuleInstantiated};
+    use crate::logging::metrics::{self, TimedScope};
     use crate::common::message_template::MessageTemplate;
     use crate::objects::code_kind::CodeKind;
 
@@ -228,9 +232,20 @@ pub mod wasm {
         _module_object: DirectHandle<WasmModuleObject>,
         _imports: Option<DirectHandle<JSReceiver>>,
         _memory: Option<DirectHandle<JSArrayBuffer>>,
+        context_id: ContextId,
+        metrics_recorder: Option<Arc<metrics::Recorder>>,
     ) -> Result<DirectHandle<WasmInstanceObject>, ()> {
-        // Placeholder implementation
-        Ok(DirectHandle::empty())
+        let mut metrics_event = WasmModuleInstantiated::default();
+        let instance = {
+            let _timed_scope = TimedScope::new(&mut metrics_event.wall_clock_duration_in_us);
+            // Placeholder implementation
+            Ok(DirectHandle::empty())
+        };
+        metrics_event.success = instance.is_ok();
+        if let Some(metrics_recorder) = metrics_recorder {
+            metrics_recorder.delay_main_thread_event(metrics_event, context_id);
+        }
+        instance
     }
 
     pub fn InitializeElementSegment(
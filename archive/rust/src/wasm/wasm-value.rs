@@ -108,7 +108,7 @@ mod wasm {
     use crate::common::simd128::Simd128;
     use crate::handles::DirectHandle;
     use crate::utils::{Float32, Float64};
-    use fp16::fp16_ieee::half::f16;
+    use crate::wasm::float16::internal::Float16;
     use std::any::Any;
     use std::fmt;
     use std::mem::size_of;
@@ -572,7 +572,7 @@ mod wasm {
                 ValueTypeKind::I32 => self.to_i32().to_string(),
                 ValueTypeKind::I64 => self.to_i64().to_string(),
                 ValueTypeKind::F16 => {
-                    fp16::fp16_ieee::to_f32(f16::from_bits(self.to_f16())).to_string()
+                    Float16::from_bits(self.to_f16()).to_float32().to_string()
                 }
                 ValueTypeKind::F32 => self.to_f32().to_string(),
                 ValueTypeKind::F64 => self.to_f64().to_string(),